@@ -6,10 +6,12 @@ use std::{
     fmt,
     marker::PhantomData,
     net::{AddrParseError, IpAddr, SocketAddr},
+    num::ParseIntError,
     str::{FromStr, Utf8Error},
 };
 
 use base64::{Engine, engine::general_purpose};
+use ipnet::{AddrParseError as NetParseError, IpNet};
 #[cfg(feature = "k8s-openapi")]
 use k8s_openapi::api::core::v1::EnvVar;
 use thiserror::Error;
@@ -206,6 +208,72 @@ impl EnvValue for Vec<IpAddr> {
     }
 }
 
+impl EnvValue for Vec<IpNet> {
+    type IntoReprError = Infallible;
+    type FromReprError = ParseEnvError<NetParseError>;
+
+    fn as_repr(&self) -> Result<String, Self::IntoReprError> {
+        Ok(self
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(","))
+    }
+
+    fn from_repr(repr: &[u8]) -> Result<Self, Self::FromReprError> {
+        let as_str = std::str::from_utf8(repr)?;
+
+        as_str
+            .split(',')
+            .map(|item| item.parse::<IpNet>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ParseEnvError::ParseError)
+    }
+}
+
+impl EnvValue for Vec<u16> {
+    type IntoReprError = Infallible;
+    type FromReprError = ParseEnvError<ParseIntError>;
+
+    fn as_repr(&self) -> Result<String, Self::IntoReprError> {
+        Ok(self
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(","))
+    }
+
+    fn from_repr(repr: &[u8]) -> Result<Self, Self::FromReprError> {
+        let as_str = std::str::from_utf8(repr)?;
+
+        as_str
+            .split(',')
+            .map(|item| item.parse::<u16>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ParseEnvError::ParseError)
+    }
+}
+
+/// Stored as a comma-separated list, so the items must not contain commas.
+impl EnvValue for Vec<String> {
+    type IntoReprError = Infallible;
+    type FromReprError = Utf8Error;
+
+    fn as_repr(&self) -> Result<String, Self::IntoReprError> {
+        Ok(self.join(","))
+    }
+
+    fn from_repr(repr: &[u8]) -> Result<Self, Self::FromReprError> {
+        let as_str = std::str::from_utf8(repr)?;
+
+        Ok(as_str
+            .split(',')
+            .filter(|item| !item.is_empty())
+            .map(ToString::to_string)
+            .collect())
+    }
+}
+
 /// Errors that can occur when parsing [`STEAL_TLS_CONFIG`](crate::envs::STEAL_TLS_CONFIG) value.
 #[derive(Error, Debug)]
 pub enum ParseStealTlsConfigError {
@@ -2,9 +2,14 @@
 //!
 //! If you want to add some more, please do it here.
 
-use std::net::{IpAddr, SocketAddr};
+use std::net::IpAddr;
 
-use crate::{checked_env::CheckedEnv, steal_tls::StealPortTlsConfig};
+use ipnet::IpNet;
+
+use crate::{
+    checked_env::CheckedEnv, metrics::MetricsAddress, redirector::RedirectorType,
+    steal_tls::StealPortTlsConfig,
+};
 
 /// Used to pass operator's x509 certificate to the agent.
 ///
@@ -12,8 +17,14 @@ use crate::{checked_env::CheckedEnv, steal_tls::StealPortTlsConfig};
 /// operator that spawned it.
 pub const OPERATOR_CERT: CheckedEnv<String> = CheckedEnv::new("AGENT_OPERATOR_CERT_ENV");
 
-/// Enables Prometheus metrics export point and sets its address.
-pub const METRICS: CheckedEnv<SocketAddr> = CheckedEnv::new("MIRRORD_AGENT_METRICS");
+/// Enables Prometheus metrics export point and sets its address, see [`MetricsAddress`].
+pub const METRICS: CheckedEnv<MetricsAddress> = CheckedEnv::new("MIRRORD_AGENT_METRICS");
+
+/// Bearer token required in the `Authorization` header of requests to the Prometheus metrics
+/// export point.
+///
+/// When not set, the metrics are available without authorization.
+pub const METRICS_TOKEN: CheckedEnv<String> = CheckedEnv::new("MIRRORD_AGENT_METRICS_TOKEN");
 
 /// Used to inform the agent that the target pod is in a mesh.
 pub const IN_SERVICE_MESH: CheckedEnv<bool> = CheckedEnv::new("MIRRORD_AGENT_IN_SERVICE_MESH");
@@ -41,6 +52,9 @@ pub const DNS_TIMEOUT: CheckedEnv<u32> = CheckedEnv::new("MIRRORD_AGENT_DNS_TIME
 /// Sets a hard limit on DNS query attempts.
 pub const DNS_ATTEMPTS: CheckedEnv<u32> = CheckedEnv::new("MIRRORD_AGENT_DNS_ATTEMPTS");
 
+/// Overrides the DNS search list from the target's `resolv.conf`.
+pub const DNS_SEARCH: CheckedEnv<Vec<String>> = CheckedEnv::new("MIRRORD_AGENT_DNS_SEARCH");
+
 /// Used in incoming traffic redirection to produce correct iptables rules.
 pub const POD_IPS: CheckedEnv<Vec<IpAddr>> = CheckedEnv::new("MIRRORD_AGENT_POD_IPS");
 
@@ -91,9 +105,54 @@ pub const MAX_BODY_BUFFER_SIZE: CheckedEnv<u32> = CheckedEnv::new("MIRRORD_MAX_B
 pub const MAX_BODY_BUFFER_TIMEOUT: CheckedEnv<u32> =
     CheckedEnv::new("MIRRORD_MAX_BODY_BUFFER_TIMEOUT");
 
+/// Sets the max number of requests from a single stolen HTTP connection that can wait for their
+/// responses at the same time.
+pub const MAX_PENDING_REQUESTS: CheckedEnv<u32> =
+    CheckedEnv::new("MIRRORD_AGENT_MAX_PENDING_REQUESTS");
+
+/// Sets the max number of connections mirrored to a single client at the same time.
+pub const MAX_MIRRORED_CONNECTIONS: CheckedEnv<u32> =
+    CheckedEnv::new("MIRRORD_AGENT_MAX_MIRRORED_CONNECTIONS");
+
+/// Sets the max number of bytes mirrored to a client from a single connection.
+pub const MAX_MIRRORED_CONNECTION_BYTES: CheckedEnv<u64> =
+    CheckedEnv::new("MIRRORD_AGENT_MAX_MIRRORED_CONNECTION_BYTES");
+
 /// When set, the agent will clean any existing iptables rules.
 pub const CLEAN_IPTABLES_ON_START: CheckedEnv<bool> =
     CheckedEnv::new("MIRRORD_AGENT_CLEAN_IPTABLES_ON_START");
 
+/// Prefix of the iptables chains created by the agent, e.g. `MIRRORD` results in
+/// `MIRRORD_INPUT`, `MIRRORD_OUTPUT` and so on.
+///
+/// When not set, the agent uses `MIRRORD`.
+pub const IPTABLES_CHAIN_PREFIX: CheckedEnv<String> =
+    CheckedEnv::new("MIRRORD_AGENT_IPTABLES_CHAIN_PREFIX");
+
+/// Traffic to these CIDRs is never redirected by the iptables and nftables redirectors.
+pub const IPTABLES_EXCLUDE_DESTINATIONS: CheckedEnv<Vec<IpNet>> =
+    CheckedEnv::new("MIRRORD_AGENT_IPTABLES_EXCLUDE_DESTINATIONS");
+
+/// Traffic from these CIDRs is never redirected by the iptables and nftables redirectors.
+pub const IPTABLES_EXCLUDE_SOURCES: CheckedEnv<Vec<IpNet>> =
+    CheckedEnv::new("MIRRORD_AGENT_IPTABLES_EXCLUDE_SOURCES");
+
+/// Traffic to these TCP ports is never redirected by the iptables and nftables redirectors, e.g.
+/// ports of the other containers in the target pod.
+pub const IPTABLES_EXCLUDE_PORTS: CheckedEnv<Vec<u16>> =
+    CheckedEnv::new("MIRRORD_AGENT_IPTABLES_EXCLUDE_PORTS");
+
+/// How often the iptables and nftables redirectors restore redirection rules that were removed by
+/// other processes.
+///
+/// Specified in seconds, `0` (the default) disables the reconciliation.
+pub const IPTABLES_RECONCILE_INTERVAL: CheckedEnv<u64> =
+    CheckedEnv::new("MIRRORD_AGENT_IPTABLES_RECONCILE_INTERVAL");
+
+/// Selects how the agent redirects incoming traffic, see [`RedirectorType`].
+///
+/// When not set, the agent uses iptables.
+pub const REDIRECTOR: CheckedEnv<RedirectorType> = CheckedEnv::new("MIRRORD_AGENT_REDIRECTOR");
+
 /// Jaq process time limit (ms)
 pub const JAQ_TIME_LIMIT: CheckedEnv<u64> = CheckedEnv::new("MIRRORD_JAQ_TIME_LIMIT");
@@ -17,4 +17,6 @@
 pub mod checked_env;
 pub mod envs;
 pub mod mesh;
+pub mod metrics;
+pub mod redirector;
 pub mod steal_tls;
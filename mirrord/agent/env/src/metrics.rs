@@ -0,0 +1,60 @@
+//! This module contains definition of the address the agent's metrics server listens on.
+//!
+//! As with all definitions in this crate, keep this backwards compatible.
+
+use std::{
+    fmt,
+    net::{AddrParseError, SocketAddr},
+    path::PathBuf,
+    str::FromStr,
+};
+
+use thiserror::Error;
+
+use crate::checked_env::StoredAsString;
+
+/// Where the agent's prometheus metrics server listens.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetricsAddress {
+    /// TCP socket address, e.g. `0.0.0.0:9000`.
+    Tcp(SocketAddr),
+    /// Unix socket path, given as `unix:///path/metrics.sock`.
+    Unix(PathBuf),
+}
+
+impl MetricsAddress {
+    /// Prefix of [`MetricsAddress::Unix`] in the string representation.
+    pub const UNIX_PREFIX: &'static str = "unix://";
+}
+
+impl fmt::Display for MetricsAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(address) => address.fmt(f),
+            Self::Unix(path) => write!(f, "{}{}", Self::UNIX_PREFIX, path.display()),
+        }
+    }
+}
+
+/// Returned when parsing [`MetricsAddress`] fails.
+#[derive(Error, Debug)]
+pub enum ParseMetricsAddressError {
+    #[error("unix socket path `{0}` is not absolute")]
+    RelativeUnixPath(String),
+    #[error("invalid socket address: {0}")]
+    InvalidSocketAddr(#[from] AddrParseError),
+}
+
+impl FromStr for MetricsAddress {
+    type Err = ParseMetricsAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix(Self::UNIX_PREFIX) {
+            Some(path) if path.starts_with('/') => Ok(Self::Unix(path.into())),
+            Some(path) => Err(ParseMetricsAddressError::RelativeUnixPath(path.to_string())),
+            None => Ok(Self::Tcp(s.parse()?)),
+        }
+    }
+}
+
+impl StoredAsString for MetricsAddress {}
@@ -0,0 +1,56 @@
+//! This module contains definition of the incoming traffic redirector selection for the agent.
+//!
+//! As with all definitions in this crate, keep this backwards compatible.
+
+use std::{fmt, str::FromStr};
+
+use thiserror::Error;
+
+use crate::checked_env::StoredAsString;
+
+/// Which implementation the agent should use to redirect incoming traffic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RedirectorType {
+    /// Use iptables/ip6tables rules. Requires `CAP_NET_ADMIN`.
+    #[default]
+    IpTables,
+    /// Use rules in a dedicated nftables table, managed with the `nft` command. Requires
+    /// `CAP_NET_ADMIN`.
+    Nftables,
+    /// Use an eBPF `sk_lookup` program. Requires `CAP_BPF` and Linux 5.9 or newer.
+    Ebpf,
+    /// Use iptables if the agent has `CAP_NET_ADMIN`, otherwise use eBPF.
+    Auto,
+}
+
+impl fmt::Display for RedirectorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IpTables => f.write_str("iptables"),
+            Self::Nftables => f.write_str("nftables"),
+            Self::Ebpf => f.write_str("ebpf"),
+            Self::Auto => f.write_str("auto"),
+        }
+    }
+}
+
+/// Returned when parsing [`RedirectorType`] fails.
+#[derive(Error, Debug)]
+#[error("unknown redirector type `{0}`, expected one of: iptables, nftables, ebpf, auto")]
+pub struct UnknownRedirectorType(String);
+
+impl FromStr for RedirectorType {
+    type Err = UnknownRedirectorType;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "iptables" => Ok(Self::IpTables),
+            "nftables" => Ok(Self::Nftables),
+            "ebpf" => Ok(Self::Ebpf),
+            "auto" => Ok(Self::Auto),
+            other => Err(UnknownRedirectorType(other.to_string())),
+        }
+    }
+}
+
+impl StoredAsString for RedirectorType {}
@@ -1,6 +1,9 @@
-use std::sync::{
-    Arc,
-    atomic::{AtomicI32, Ordering},
+use std::{
+    ops::Not,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicI32, Ordering},
+    },
 };
 
 use crate::{
@@ -13,6 +16,10 @@ pub struct IPTableChain<IPT: IPTables> {
     inner: Arc<IPT>,
     chain_name: String,
     chain_size: AtomicI32,
+    /// Rules that precede the redirects, e.g. exclusions, in order.
+    ///
+    /// Restored with [`IPTableChain::restore`].
+    base_rules: Mutex<Vec<String>>,
 }
 
 impl<IPT> IPTableChain<IPT>
@@ -29,6 +36,7 @@ where
             inner,
             chain_name,
             chain_size,
+            base_rules: Default::default(),
         })
     }
 
@@ -48,6 +56,7 @@ where
             inner,
             chain_name,
             chain_size,
+            base_rules: Default::default(),
         })
     }
 
@@ -75,6 +84,94 @@ where
             })
     }
 
+    /// Adds a rule that must precede the redirects, and restores it in [`IPTableChain::restore`].
+    ///
+    /// Base rules must be added before any redirect.
+    pub fn add_base_rule<R>(&self, rule: R) -> IPTablesResult<i32>
+    where
+        R: Into<String>,
+    {
+        let rule = rule.into();
+        let size = self.add_rule(&rule)?;
+        self.base_rules
+            .lock()
+            .expect("base rules lock poisoned")
+            .push(rule);
+
+        Ok(size)
+    }
+
+    /// Removes a rule added with [`IPTableChain::add_base_rule`].
+    pub fn remove_base_rule<R>(&self, rule: R) -> IPTablesResult<()>
+    where
+        R: AsRef<str>,
+    {
+        self.base_rules
+            .lock()
+            .expect("base rules lock poisoned")
+            .retain(|base_rule| base_rule != rule.as_ref());
+
+        self.remove_rule(rule)
+    }
+
+    /// Recreates the chain, its base rules and the final `-j RETURN`, if they were removed by
+    /// another process, e.g. a CNI plugin that flushed or deleted our chain.
+    ///
+    /// Base rules are restored at their original positions at the start of the chain, so they
+    /// still precede the redirects, which are restored with [`IPTableChain::add_rule_if_absent`].
+    pub fn restore(&self) -> IPTablesResult<()> {
+        if self.inner.list_rules(&self.chain_name).is_err() {
+            self.inner.create_chain(&self.chain_name)?;
+        } else if self.inner.rule_exists(&self.chain_name, "-j RETURN")?.not() {
+            self.inner.add_rule(&self.chain_name, "-j RETURN")?;
+        }
+
+        let base_rules = self
+            .base_rules
+            .lock()
+            .expect("base rules lock poisoned")
+            .clone();
+        for (index, rule) in base_rules.iter().enumerate() {
+            if self.inner.rule_exists(&self.chain_name, rule)?.not() {
+                self.inner
+                    .insert_rule(&self.chain_name, rule, index as i32 + 1)?;
+            }
+        }
+
+        // Skip `-N <chain name>`, the next rule goes before the final `-j RETURN`.
+        let existing_rules = self.inner.list_rules(&self.chain_name)?.len();
+        self.chain_size.store(
+            existing_rules.saturating_sub(1).max(1) as i32,
+            Ordering::Relaxed,
+        );
+
+        Ok(())
+    }
+
+    /// Adds the rule, unless the chain already contains it.
+    ///
+    /// The rule could have been removed by another process, so the size of the chain is refreshed
+    /// before adding it.
+    pub fn add_rule_if_absent<R>(&self, rule: R) -> IPTablesResult<()>
+    where
+        R: AsRef<str>,
+    {
+        if self.inner.rule_exists(&self.chain_name, rule.as_ref())? {
+            return Ok(());
+        }
+
+        // Skip `-N <chain name>`, and insert before the final `-j RETURN` (if it's still there).
+        let existing_rules = self.inner.list_rules(&self.chain_name)?.len();
+        self.chain_size.store(
+            existing_rules.saturating_sub(1).max(1) as i32,
+            Ordering::Relaxed,
+        );
+
+        self.add_rule(rule)?;
+
+        Ok(())
+    }
+
     pub fn remove_rule<R>(&self, rule: R) -> IPTablesResult<()>
     where
         R: AsRef<str>,
@@ -87,6 +184,16 @@ where
     }
 }
 
+/// Output of [`IPTables::list_rules`] for our chain with `rules` rules before the final
+/// `-j RETURN`.
+#[cfg(test)]
+pub(crate) fn listed_chain(chain: &str, rules: usize) -> Vec<String> {
+    std::iter::once(format!("-N {chain}"))
+        .chain((0..rules).map(|index| format!("-A {chain} rule-{index}")))
+        .chain([format!("-A {chain} -j RETURN")])
+        .collect()
+}
+
 impl<IPT> Drop for IPTableChain<IPT>
 where
     IPT: IPTables,
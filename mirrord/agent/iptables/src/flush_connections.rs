@@ -52,6 +52,19 @@ where
         self.inner.unmount_entrypoint().await
     }
 
+    /// Does not flush the connections, as they might be already redirected.
+    #[tracing::instrument(level = Level::TRACE, skip(self), ret, err)]
+    async fn restore_chains(&self) -> IPTablesResult<()> {
+        self.inner.restore_chains().await
+    }
+
+    #[tracing::instrument(level = Level::TRACE, skip(self), ret, err)]
+    async fn restore_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
+        self.inner
+            .restore_redirect(redirected_port, target_port)
+            .await
+    }
+
     #[tracing::instrument(level = Level::TRACE, skip(self), ret, err)]
     async fn add_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
         self.inner
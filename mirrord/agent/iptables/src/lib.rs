@@ -8,7 +8,8 @@ use std::{
 
 use caps::{CapSet, Capability};
 use enum_dispatch::enum_dispatch;
-use mirrord_agent_env::mesh::MeshVendor;
+use ipnet::IpNet;
+use mirrord_agent_env::{envs, mesh::MeshVendor};
 use tracing::{Level, warn};
 
 use crate::{
@@ -28,11 +29,15 @@ mod chain;
 pub mod error;
 mod flush_connections;
 mod mesh;
+pub mod nftables;
 mod output;
 mod prerouting;
 mod redirect;
 mod standard;
 
+/// Default prefix of the chains created by the agent, see [`ChainNames`].
+pub const IPTABLE_CHAIN_PREFIX: &str = "MIRRORD";
+
 pub const IPTABLE_PREROUTING: &str = "MIRRORD_INPUT";
 
 pub const IPTABLE_MESH: &str = "MIRRORD_OUTPUT";
@@ -41,6 +46,125 @@ pub const IPTABLE_STANDARD: &str = "MIRRORD_STANDARD";
 
 pub const IPTABLE_EXCLUDE_FROM_MESH: &str = "MIRRORD_EXCLUDE_FROM_MESH";
 
+/// Names of the chains created by the agent, all sharing a common prefix.
+///
+/// Agents configured with different prefixes (see [`envs::IPTABLES_CHAIN_PREFIX`]) never touch
+/// each other's chains, neither when checking for leftover rules nor when cleaning up.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainNames {
+    /// Jumped to from the `PREROUTING` chain, defaults to [`IPTABLE_PREROUTING`].
+    pub prerouting: String,
+    /// Jumped to from the `OUTPUT` chain when there's a service mesh, defaults to
+    /// [`IPTABLE_MESH`].
+    pub mesh: String,
+    /// Jumped to from the `OUTPUT` chain, defaults to [`IPTABLE_STANDARD`].
+    pub standard: String,
+    /// Excludes the agent's port from the service mesh, defaults to
+    /// [`IPTABLE_EXCLUDE_FROM_MESH`].
+    pub exclude_from_mesh: String,
+}
+
+impl ChainNames {
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self {
+            prerouting: format!("{prefix}_INPUT"),
+            mesh: format!("{prefix}_OUTPUT"),
+            standard: format!("{prefix}_STANDARD"),
+            exclude_from_mesh: format!("{prefix}_EXCLUDE_FROM_MESH"),
+        }
+    }
+
+    /// Uses the prefix from [`envs::IPTABLES_CHAIN_PREFIX`], falling back to
+    /// [`IPTABLE_CHAIN_PREFIX`].
+    pub fn from_env() -> Self {
+        match envs::IPTABLES_CHAIN_PREFIX.try_from_env() {
+            Ok(Some(prefix)) => Self::with_prefix(&prefix),
+            Ok(None) => Self::default(),
+            Err(error) => {
+                tracing::error!(
+                    ?error,
+                    "Failed to read the iptables chain prefix from the env, using the default"
+                );
+                Self::default()
+            }
+        }
+    }
+
+    fn all(&self) -> [&str; 4] {
+        [
+            &self.prerouting,
+            &self.mesh,
+            &self.standard,
+            &self.exclude_from_mesh,
+        ]
+    }
+}
+
+impl Default for ChainNames {
+    fn default() -> Self {
+        Self::with_prefix(IPTABLE_CHAIN_PREFIX)
+    }
+}
+
+/// Traffic that is never redirected, regardless of its port, e.g. to a cloud metadata service.
+///
+/// Excluded with `RETURN` rules at the start of our chains, so they are removed together with the
+/// chains.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RedirectExclusions {
+    /// Traffic to these CIDRs is not redirected.
+    pub destinations: Vec<IpNet>,
+    /// Traffic from these CIDRs is not redirected.
+    pub sources: Vec<IpNet>,
+    /// Traffic to these TCP ports is not redirected.
+    pub ports: Vec<u16>,
+}
+
+impl RedirectExclusions {
+    /// Reads the CIDRs from [`envs::IPTABLES_EXCLUDE_DESTINATIONS`] and
+    /// [`envs::IPTABLES_EXCLUDE_SOURCES`], and the ports from [`envs::IPTABLES_EXCLUDE_PORTS`].
+    pub fn from_env() -> Self {
+        Self {
+            destinations: envs::IPTABLES_EXCLUDE_DESTINATIONS.from_env_or_default(),
+            sources: envs::IPTABLES_EXCLUDE_SOURCES.from_env_or_default(),
+            ports: envs::IPTABLES_EXCLUDE_PORTS.from_env_or_default(),
+        }
+    }
+
+    /// Returns the CIDRs of the given address family, as `(destinations, sources)`.
+    fn of_family(&self, ipv6: bool) -> (Vec<IpNet>, Vec<IpNet>) {
+        let of_family = |cidrs: &[IpNet]| {
+            cidrs
+                .iter()
+                .filter(|cidr| matches!(cidr, IpNet::V6(..)) == ipv6)
+                .copied()
+                .collect()
+        };
+
+        (of_family(&self.destinations), of_family(&self.sources))
+    }
+
+    /// Builds the `RETURN` rules for the given address family.
+    fn rules(&self, ipv6: bool) -> Vec<String> {
+        let (destinations, sources) = self.of_family(ipv6);
+
+        destinations
+            .into_iter()
+            .map(|cidr| format!("-d {cidr} -j RETURN"))
+            .chain(
+                sources
+                    .into_iter()
+                    .map(|cidr| format!("-s {cidr} -j RETURN")),
+            )
+            .chain(
+                self.ports
+                    .iter()
+                    .map(|port| format!("-p tcp --dport {port} -j RETURN")),
+            )
+            .collect()
+    }
+}
+
 pub static IPTABLE_IPV4_ROUTE_LOCALNET_ORIGINAL: LazyLock<String> = LazyLock::new(|| {
     std::fs::read_to_string("/proc/sys/net/ipv4/conf/all/route_localnet")
         .unwrap_or_else(|_| "0".to_string())
@@ -61,6 +185,7 @@ pub trait IPTables {
     fn add_rule(&self, chain: &str, rule: &str) -> IPTablesResult<()>;
     fn insert_rule(&self, chain: &str, rule: &str, index: i32) -> IPTablesResult<()>;
     fn list_rules(&self, chain: &str) -> IPTablesResult<Vec<String>>;
+    fn rule_exists(&self, chain: &str, rule: &str) -> IPTablesResult<bool>;
 
     fn list_table(&self) -> IPTablesResult<Vec<String>>;
     fn remove_rule(&self, chain: &str, rule: &str) -> IPTablesResult<()>;
@@ -135,6 +260,13 @@ impl IPTables for IPTablesWrapper {
         self.tables.list(self.table_name, chain).map_err(From::from)
     }
 
+    #[tracing::instrument(level = Level::TRACE, ret, err)]
+    fn rule_exists(&self, chain: &str, rule: &str) -> IPTablesResult<bool> {
+        self.tables
+            .exists(self.table_name, chain, rule)
+            .map_err(From::from)
+    }
+
     #[tracing::instrument(level = Level::TRACE, ret, err)]
     fn list_table(&self) -> IPTablesResult<Vec<String>> {
         self.tables.list_table(self.table_name).map_err(From::from)
@@ -174,27 +306,43 @@ where
 {
     pub async fn create(
         ipt: IPT,
+        chains: &ChainNames,
         flush_connections: bool,
         pod_ips: Option<&str>,
         ipv6: bool,
         with_mesh_exclusion: bool,
+        exclusions: &RedirectExclusions,
     ) -> IPTablesResult<Self> {
         let ipt = Arc::new(ipt);
+        let exclusions = exclusions.rules(ipv6);
 
         let mut redirect = match MeshVendor::detect(ipt.as_ref())? {
             Some(vendor) => match &vendor {
-                MeshVendor::IstioAmbient => {
-                    Redirects::Ambient(AmbientRedirect::create(ipt.clone(), pod_ips)?)
-                }
-                _ => Redirects::Mesh(MeshRedirect::create(ipt.clone(), vendor, pod_ips)?),
+                MeshVendor::IstioAmbient => Redirects::Ambient(AmbientRedirect::create(
+                    ipt.clone(),
+                    chains,
+                    pod_ips,
+                    &exclusions,
+                )?),
+                _ => Redirects::Mesh(MeshRedirect::create(
+                    ipt.clone(),
+                    chains,
+                    vendor,
+                    pod_ips,
+                    &exclusions,
+                )?),
             },
             _ => {
                 tracing::trace!(ipv6 = ipv6, "creating standard redirect");
-                match StandardRedirect::create(ipt.clone(), pod_ips) {
+                match StandardRedirect::create(ipt.clone(), chains, pod_ips, &exclusions) {
                     Err(err) => {
                         warn!("Unable to create StandardRedirect chain: {err}");
 
-                        Redirects::PrerouteFallback(PreroutingRedirect::create(ipt.clone())?)
+                        Redirects::PrerouteFallback(PreroutingRedirect::create(
+                            ipt.clone(),
+                            &chains.prerouting,
+                            &exclusions,
+                        )?)
                     }
                     Ok(standard) => Redirects::Standard(standard),
                 }
@@ -207,8 +355,11 @@ where
 
         // Should be always the last composed redirect because it handles the order internally.
         if with_mesh_exclusion {
-            redirect =
-                Redirects::WithMeshExclusion(WithMeshExclusion::create(ipt, Box::new(redirect))?)
+            redirect = Redirects::WithMeshExclusion(WithMeshExclusion::create(
+                ipt,
+                chains,
+                Box::new(redirect),
+            )?)
         }
 
         redirect.mount_entrypoint().await?;
@@ -218,26 +369,18 @@ where
 
     /// List rules from other/ previous mirrord agents that exist on the IP table
     #[tracing::instrument(level = Level::TRACE, skip(ipt) ret, err)]
-    pub async fn list_mirrord_rules(ipt: &IPT) -> IPTablesResult<Vec<String>> {
+    pub async fn list_mirrord_rules(ipt: &IPT, chains: &ChainNames) -> IPTablesResult<Vec<String>> {
         let rules = ipt.list_table()?;
 
         Ok(rules
             .into_iter()
-            .filter(|rule| {
-                [
-                    IPTABLE_PREROUTING,
-                    IPTABLE_MESH,
-                    IPTABLE_STANDARD,
-                    IPTABLE_EXCLUDE_FROM_MESH,
-                ]
-                .iter()
-                .any(|chain| rule.contains(*chain))
-            })
+            .filter(|rule| chains.all().iter().any(|chain| rule.contains(chain)))
             .collect())
     }
 
     pub async fn load(
         ipt: IPT,
+        chains: &ChainNames,
         flush_connections: bool,
         with_mesh_exclusion: bool,
     ) -> IPTablesResult<Self> {
@@ -245,14 +388,19 @@ where
 
         let mut redirect = match MeshVendor::detect(ipt.as_ref())? {
             Some(vendor) => match &vendor {
-                MeshVendor::IstioAmbient => Redirects::Ambient(AmbientRedirect::load(ipt.clone())?),
-                _ => Redirects::Mesh(MeshRedirect::load(ipt.clone(), vendor)?),
+                MeshVendor::IstioAmbient => {
+                    Redirects::Ambient(AmbientRedirect::load(ipt.clone(), chains)?)
+                }
+                _ => Redirects::Mesh(MeshRedirect::load(ipt.clone(), chains, vendor)?),
             },
-            _ => match StandardRedirect::load(ipt.clone()) {
+            _ => match StandardRedirect::load(ipt.clone(), chains) {
                 Err(err) => {
                     warn!("Unable to load StandardRedirect chain: {err}");
 
-                    Redirects::PrerouteFallback(PreroutingRedirect::load(ipt.clone())?)
+                    Redirects::PrerouteFallback(PreroutingRedirect::load(
+                        ipt.clone(),
+                        &chains.prerouting,
+                    )?)
                 }
                 Ok(standard) => Redirects::Standard(standard),
             },
@@ -264,14 +412,17 @@ where
 
         // Should be always the last composed redirect because it handles the order internally.
         if with_mesh_exclusion {
-            redirect =
-                Redirects::WithMeshExclusion(WithMeshExclusion::load(ipt, Box::new(redirect))?)
+            redirect = Redirects::WithMeshExclusion(WithMeshExclusion::load(
+                ipt,
+                chains,
+                Box::new(redirect),
+            )?)
         }
 
         Ok(Self { redirect })
     }
 
-    /// Adds the redirect rule to iptables.
+    /// Adds the redirect rule to iptables, does nothing if the rule already exists.
     ///
     /// Used to redirect packets when mirrord incoming feature is set to `steal`.
     #[tracing::instrument(level = Level::DEBUG, skip(self), err)]
@@ -281,6 +432,31 @@ where
             .await
     }
 
+    /// Adds the redirect rule to iptables if it was removed, e.g. by another process that flushed
+    /// our chains.
+    ///
+    /// Unlike [`SafeIpTables::add_redirect`], never flushes existing connections.
+    #[tracing::instrument(level = Level::TRACE, skip(self), err)]
+    pub async fn restore_redirect(
+        &self,
+        redirected_port: u16,
+        target_port: u16,
+    ) -> IPTablesResult<()> {
+        self.redirect
+            .restore_redirect(redirected_port, target_port)
+            .await
+    }
+
+    /// Recreates our chains, their exclusions and the jumps to them if they were removed, e.g. by
+    /// another process that flushed them.
+    ///
+    /// Call before [`SafeIpTables::restore_redirect`], so that the restored redirects follow the
+    /// exclusions.
+    #[tracing::instrument(level = Level::TRACE, skip(self), err)]
+    pub async fn restore_chains(&self) -> IPTablesResult<()> {
+        self.redirect.restore_chains().await
+    }
+
     /// Removes the redirect rule from iptables.
     ///
     /// Stops redirecting packets when mirrord incoming feature is set to `steal`, and there are no
@@ -459,16 +635,27 @@ fn try_drop_cap_sys_module() {
 #[cfg(test)]
 mod tests {
     use mockall::predicate::{eq, str};
+    use rstest::rstest;
 
     use crate::{
-        IPTABLE_EXCLUDE_FROM_MESH, IPTABLE_MESH, IPTABLE_PREROUTING, IPTABLE_STANDARD,
-        MockIPTables, SafeIpTables,
+        ChainNames, IPTABLE_EXCLUDE_FROM_MESH, IPTABLE_MESH, IPTABLE_PREROUTING, IPTABLE_STANDARD,
+        MockIPTables, RedirectExclusions, SafeIpTables, chain::listed_chain,
     };
 
     #[tokio::test]
     async fn default() {
         let mut mock = MockIPTables::new();
 
+        mock.expect_rule_exists().returning(|_, _| Ok(false));
+
+        mock.expect_list_rules()
+            .with(eq(IPTABLE_PREROUTING))
+            .returning(|chain| Ok(listed_chain(chain, 0)));
+
+        mock.expect_list_rules()
+            .with(eq(IPTABLE_STANDARD))
+            .returning(|chain| Ok(listed_chain(chain, 1)));
+
         mock.expect_list_rules()
             .with(eq("OUTPUT"))
             .returning(|_| Ok(vec![]));
@@ -556,9 +743,17 @@ mod tests {
             .times(1)
             .returning(|_| Ok(()));
 
-        let ipt = SafeIpTables::create(mock, false, None, false, false)
-            .await
-            .expect("Create Failed");
+        let ipt = SafeIpTables::create(
+            mock,
+            &ChainNames::default(),
+            false,
+            None,
+            false,
+            false,
+            &Default::default(),
+        )
+        .await
+        .expect("Create Failed");
 
         assert!(ipt.add_redirect(69, 420).await.is_ok());
 
@@ -567,10 +762,191 @@ mod tests {
         assert!(ipt.cleanup().await.is_ok());
     }
 
+    #[test]
+    fn redirect_exclusion_rules() {
+        let exclusions = RedirectExclusions {
+            destinations: vec![
+                "169.254.169.254/32".parse().unwrap(),
+                "fd00:ec2::254/128".parse().unwrap(),
+            ],
+            sources: vec![],
+            ports: vec![9901],
+        };
+
+        assert_eq!(
+            exclusions.rules(false),
+            [
+                "-d 169.254.169.254/32 -j RETURN",
+                "-p tcp --dport 9901 -j RETURN"
+            ]
+        );
+        assert_eq!(
+            exclusions.rules(true),
+            [
+                "-d fd00:ec2::254/128 -j RETURN",
+                "-p tcp --dport 9901 -j RETURN"
+            ]
+        );
+    }
+
+    /// Exclusions are added before the redirect rules, only for the matching address family.
+    #[tokio::test]
+    async fn with_redirect_exclusions() {
+        let mut mock = MockIPTables::new();
+
+        mock.expect_rule_exists().returning(|_, _| Ok(false));
+
+        mock.expect_list_rules()
+            .with(eq(IPTABLE_PREROUTING))
+            .returning(|chain| Ok(listed_chain(chain, 2)));
+
+        mock.expect_list_rules()
+            .with(eq(IPTABLE_STANDARD))
+            .returning(|chain| Ok(listed_chain(chain, 3)));
+
+        mock.expect_list_rules()
+            .with(eq("OUTPUT"))
+            .returning(|_| Ok(vec![]));
+
+        mock.expect_create_chain()
+            .with(eq(IPTABLE_PREROUTING))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        mock.expect_insert_rule()
+            .with(
+                eq(IPTABLE_PREROUTING),
+                eq("-d 169.254.169.254/32 -j RETURN"),
+                eq(1),
+            )
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        mock.expect_insert_rule()
+            .with(
+                eq(IPTABLE_PREROUTING),
+                eq("-s 10.1.0.0/16 -j RETURN"),
+                eq(2),
+            )
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        mock.expect_insert_rule()
+            .with(
+                eq(IPTABLE_PREROUTING),
+                eq("-m tcp -p tcp --dport 69 -j REDIRECT --to-ports 420"),
+                eq(3),
+            )
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        mock.expect_create_chain()
+            .with(eq(IPTABLE_STANDARD))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        mock.expect_insert_rule()
+            .with(
+                eq(IPTABLE_STANDARD),
+                str::starts_with("-m owner --gid-owner"),
+                eq(1),
+            )
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        mock.expect_insert_rule()
+            .with(
+                eq(IPTABLE_STANDARD),
+                eq("-d 169.254.169.254/32 -j RETURN"),
+                eq(2),
+            )
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        mock.expect_insert_rule()
+            .with(eq(IPTABLE_STANDARD), eq("-s 10.1.0.0/16 -j RETURN"), eq(3))
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        mock.expect_insert_rule()
+            .with(
+                eq(IPTABLE_STANDARD),
+                eq("-o lo -m tcp -p tcp --dport 69 -j REDIRECT --to-ports 420"),
+                eq(4),
+            )
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        mock.expect_add_rule()
+            .with(eq("PREROUTING"), eq(format!("-j {}", IPTABLE_PREROUTING)))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        mock.expect_add_rule()
+            .with(eq("OUTPUT"), eq(format!("-j {}", IPTABLE_STANDARD)))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        mock.expect_remove_rule()
+            .with(eq("PREROUTING"), eq(format!("-j {}", IPTABLE_PREROUTING)))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        mock.expect_remove_rule()
+            .with(eq("OUTPUT"), eq(format!("-j {}", IPTABLE_STANDARD)))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        mock.expect_remove_chain()
+            .with(eq(IPTABLE_PREROUTING))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        mock.expect_remove_chain()
+            .with(eq(IPTABLE_STANDARD))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let exclusions = RedirectExclusions {
+            destinations: vec![
+                "169.254.169.254/32".parse().unwrap(),
+                "fd00:ec2::254/128".parse().unwrap(),
+            ],
+            sources: vec!["10.1.0.0/16".parse().unwrap()],
+            ports: vec![],
+        };
+
+        let ipt = SafeIpTables::create(
+            mock,
+            &ChainNames::default(),
+            false,
+            None,
+            false,
+            false,
+            &exclusions,
+        )
+        .await
+        .expect("Create Failed");
+
+        assert!(ipt.add_redirect(69, 420).await.is_ok());
+
+        assert!(ipt.cleanup().await.is_ok());
+    }
+
     #[tokio::test]
     async fn linkerd() {
         let mut mock = MockIPTables::new();
 
+        mock.expect_rule_exists().returning(|_, _| Ok(false));
+
+        mock.expect_list_rules()
+            .with(eq(IPTABLE_PREROUTING))
+            .returning(|chain| Ok(listed_chain(chain, 1)));
+
+        mock.expect_list_rules()
+            .with(eq(IPTABLE_MESH))
+            .returning(|chain| Ok(listed_chain(chain, 1)));
+
         mock.expect_list_rules()
             .with(eq("OUTPUT"))
             .returning(|_| Ok(vec!["-j PROXY_INIT_OUTPUT".to_owned()]));
@@ -689,9 +1065,17 @@ mod tests {
             .times(1)
             .returning(|_| Ok(()));
 
-        let ipt = SafeIpTables::create(mock, false, None, false, false)
-            .await
-            .expect("Create Failed");
+        let ipt = SafeIpTables::create(
+            mock,
+            &ChainNames::default(),
+            false,
+            None,
+            false,
+            false,
+            &Default::default(),
+        )
+        .await
+        .expect("Create Failed");
 
         assert!(ipt.add_redirect(69, 420).await.is_ok());
 
@@ -700,10 +1084,25 @@ mod tests {
         assert!(ipt.cleanup().await.is_ok());
     }
 
+    /// Verifies that the agent port is excluded from the service mesh, the same way with iptables
+    /// and ip6tables.
+    #[rstest]
+    #[case::ipv4(false)]
+    #[case::ipv6(true)]
     #[tokio::test]
-    async fn with_mesh_exclusion() {
+    async fn with_mesh_exclusion(#[case] ipv6: bool) {
         let mut mock = MockIPTables::new();
 
+        mock.expect_rule_exists().returning(|_, _| Ok(false));
+
+        mock.expect_list_rules()
+            .with(eq(IPTABLE_PREROUTING))
+            .returning(|chain| Ok(listed_chain(chain, 0)));
+
+        mock.expect_list_rules()
+            .with(eq(IPTABLE_STANDARD))
+            .returning(|chain| Ok(listed_chain(chain, 1)));
+
         mock.expect_list_rules()
             .with(eq("OUTPUT"))
             .returning(|_| Ok(vec![]));
@@ -769,6 +1168,23 @@ mod tests {
             .times(1)
             .returning(|_, _| Ok(()));
 
+        mock.expect_insert_rule()
+            .with(
+                eq(IPTABLE_EXCLUDE_FROM_MESH),
+                eq("-p tcp --dport 1337 -j ACCEPT"),
+                eq(1),
+            )
+            .times(1)
+            .returning(|_, _, _| Ok(()));
+
+        mock.expect_remove_rule()
+            .with(
+                eq(IPTABLE_EXCLUDE_FROM_MESH),
+                eq("-p tcp --dport 1337 -j ACCEPT"),
+            )
+            .times(1)
+            .returning(|_, _| Ok(()));
+
         mock.expect_remove_rule()
             .with(
                 eq(IPTABLE_PREROUTING),
@@ -818,14 +1234,27 @@ mod tests {
             .times(1)
             .returning(|_| Ok(()));
 
-        let ipt = SafeIpTables::create(mock, false, None, false, true)
-            .await
-            .expect("Create Failed");
+        let ipt = SafeIpTables::create(
+            mock,
+            &ChainNames::default(),
+            false,
+            None,
+            ipv6,
+            true,
+            &Default::default(),
+        )
+        .await
+        .expect("Create Failed");
+
+        let exclusion = ipt.exclusion().expect("mesh exclusion should be set up");
+        assert!(exclusion.add_exclusion(1337).is_ok());
 
         assert!(ipt.add_redirect(69, 420).await.is_ok());
 
         assert!(ipt.remove_redirect(69, 420).await.is_ok());
 
+        assert!(exclusion.remove_exclusion(1337).is_ok());
+
         assert!(ipt.cleanup().await.is_ok());
     }
 
@@ -845,7 +1274,8 @@ mod tests {
             ])
         });
 
-        let leftover_rules_res = SafeIpTables::list_mirrord_rules(&mock).await;
+        let leftover_rules_res =
+            SafeIpTables::list_mirrord_rules(&mock, &ChainNames::default()).await;
         assert_eq!(
             leftover_rules_res.unwrap().len(),
             0,
@@ -870,11 +1300,92 @@ mod tests {
             ])
         });
 
-        let leftover_rules_res = SafeIpTables::list_mirrord_rules(&mock).await;
+        let leftover_rules_res =
+            SafeIpTables::list_mirrord_rules(&mock, &ChainNames::default()).await;
         assert_eq!(
             leftover_rules_res.unwrap().len(),
             1,
             "Fresh IP table should successfully list table rules and list one existing mirrord rule"
         );
     }
+
+    #[test]
+    fn default_chain_names() {
+        assert_eq!(
+            ChainNames::default(),
+            ChainNames {
+                prerouting: IPTABLE_PREROUTING.into(),
+                mesh: IPTABLE_MESH.into(),
+                standard: IPTABLE_STANDARD.into(),
+                exclude_from_mesh: IPTABLE_EXCLUDE_FROM_MESH.into(),
+            }
+        );
+    }
+
+    /// Agents with different chain prefixes don't see each other's rules.
+    #[tokio::test]
+    async fn list_rules_with_prefix() {
+        let mut mock = MockIPTables::new();
+
+        mock.expect_list_table().with().times(1).returning(|| {
+            Ok(vec![
+                "-P PREROUTING ACCEPT".to_owned(),
+                format!("-N {IPTABLE_PREROUTING}"),
+                "-N TEAM_A_INPUT".to_owned(),
+                "-A PREROUTING -j TEAM_A_INPUT".to_owned(),
+            ])
+        });
+
+        let rules = SafeIpTables::list_mirrord_rules(&mock, &ChainNames::with_prefix("TEAM_A"))
+            .await
+            .unwrap();
+        assert_eq!(rules, ["-N TEAM_A_INPUT", "-A PREROUTING -j TEAM_A_INPUT"]);
+    }
+
+    /// Cleanup of a loaded [`SafeIpTables`] removes only the chains with the configured prefix.
+    #[tokio::test]
+    async fn cleanup_with_prefix() {
+        let chains = ChainNames::with_prefix("TEAM_A");
+        let mut mock = MockIPTables::new();
+
+        mock.expect_list_rules()
+            .with(eq("OUTPUT"))
+            .returning(|_| Ok(vec![]));
+
+        mock.expect_list_rules()
+            .with(eq("TEAM_A_INPUT"))
+            .times(1)
+            .returning(|_| Ok(vec!["-N TEAM_A_INPUT".to_owned()]));
+
+        mock.expect_list_rules()
+            .with(eq("TEAM_A_STANDARD"))
+            .times(1)
+            .returning(|_| Ok(vec!["-N TEAM_A_STANDARD".to_owned()]));
+
+        mock.expect_remove_rule()
+            .with(eq("PREROUTING"), eq("-j TEAM_A_INPUT"))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        mock.expect_remove_rule()
+            .with(eq("OUTPUT"), eq("-j TEAM_A_STANDARD"))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        mock.expect_remove_chain()
+            .with(eq("TEAM_A_INPUT"))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        mock.expect_remove_chain()
+            .with(eq("TEAM_A_STANDARD"))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let ipt = SafeIpTables::load(mock, &chains, false, false)
+            .await
+            .expect("Load Failed");
+
+        assert!(ipt.cleanup().await.is_ok());
+    }
 }
@@ -5,7 +5,7 @@ use fancy_regex::Regex;
 use mirrord_agent_env::{envs, mesh::MeshVendor};
 
 use crate::{
-    IPTABLE_MESH, IPTables, error::IPTablesResult, output::OutputRedirect,
+    ChainNames, IPTables, error::IPTablesResult, output::OutputRedirect,
     prerouting::PreroutingRedirect, redirect::Redirect,
 };
 
@@ -30,16 +30,18 @@ where
 {
     pub fn create(
         ipt: Arc<IPT>,
+        chains: &ChainNames,
         vendor: MeshVendor,
         pod_ips: Option<&str>,
+        exclusions: &[String],
     ) -> IPTablesResult<Self> {
-        let prerouting = PreroutingRedirect::create(ipt.clone())?;
+        let prerouting = PreroutingRedirect::create(ipt.clone(), &chains.prerouting, exclusions)?;
 
         for port in Self::get_skip_ports(&ipt, &vendor)? {
-            prerouting.add_rule(format!("-m multiport -p tcp ! --dports {port} -j RETURN"))?;
+            prerouting.add_base_rule(format!("-m multiport -p tcp ! --dports {port} -j RETURN"))?;
         }
 
-        let output = OutputRedirect::create(ipt, IPTABLE_MESH.to_string(), pod_ips)?;
+        let output = OutputRedirect::create(ipt, chains.mesh.clone(), pod_ips, exclusions)?;
 
         Ok(MeshRedirect {
             prerouting,
@@ -48,9 +50,9 @@ where
         })
     }
 
-    pub fn load(ipt: Arc<IPT>, vendor: MeshVendor) -> IPTablesResult<Self> {
-        let prerouting = PreroutingRedirect::load(ipt.clone())?;
-        let output = OutputRedirect::load(ipt, IPTABLE_MESH.to_string())?;
+    pub fn load(ipt: Arc<IPT>, chains: &ChainNames, vendor: MeshVendor) -> IPTablesResult<Self> {
+        let prerouting = PreroutingRedirect::load(ipt.clone(), &chains.prerouting)?;
+        let output = OutputRedirect::load(ipt, chains.mesh.clone())?;
 
         Ok(MeshRedirect {
             prerouting,
@@ -104,6 +106,13 @@ where
         prerouting_res.and(output_res)
     }
 
+    async fn restore_chains(&self) -> IPTablesResult<()> {
+        self.prerouting.restore_chains().await?;
+        self.output.restore_chains().await?;
+
+        Ok(())
+    }
+
     async fn add_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
         if self.vendor != MeshVendor::IstioCni {
             self.prerouting
@@ -203,7 +212,8 @@ mod tests {
     use nix::unistd::getgid;
 
     use crate::{
-        IPTABLE_MESH, IPTABLE_PREROUTING, MockIPTables, mesh::MeshRedirect, redirect::Redirect,
+        ChainNames, IPTABLE_MESH, IPTABLE_PREROUTING, MockIPTables, chain::listed_chain,
+        mesh::MeshRedirect, redirect::Redirect,
     };
 
     fn create_mesh_list_values(mock: &mut MockIPTables) {
@@ -238,6 +248,16 @@ mod tests {
         let gid = getgid();
         let mut mock = MockIPTables::new();
 
+        mock.expect_rule_exists().returning(|_, _| Ok(false));
+
+        mock.expect_list_rules()
+            .with(eq(IPTABLE_PREROUTING))
+            .returning(|chain| Ok(listed_chain(chain, 0)));
+
+        mock.expect_list_rules()
+            .with(eq(IPTABLE_MESH))
+            .returning(|chain| Ok(listed_chain(chain, 1)));
+
         create_mesh_list_values(&mut mock);
 
         mock.expect_create_chain()
@@ -287,8 +307,14 @@ mod tests {
             .times(1)
             .returning(|_| Ok(()));
 
-        let prerouting = MeshRedirect::create(Arc::new(mock), MeshVendor::Linkerd, None)
-            .expect("Unable to create");
+        let prerouting = MeshRedirect::create(
+            Arc::new(mock),
+            &ChainNames::default(),
+            MeshVendor::Linkerd,
+            None,
+            &[],
+        )
+        .expect("Unable to create");
 
         assert!(prerouting.add_redirect(69, 420).await.is_ok());
     }
@@ -1,12 +1,9 @@
-use std::sync::Arc;
+use std::{ops::Not, sync::Arc};
 
 use async_trait::async_trait;
 use tracing::Level;
 
-use crate::{
-    IPTABLE_EXCLUDE_FROM_MESH, IPTables, chain::IPTableChain, error::IPTablesResult,
-    redirect::Redirect,
-};
+use crate::{ChainNames, IPTables, chain::IPTableChain, error::IPTablesResult, redirect::Redirect};
 
 /// Type used for excluding certain ports from the service mesh proxy.
 #[derive(Debug)]
@@ -51,13 +48,33 @@ where
         )
     }
 
+    /// Recreates the chain, its exclusions and the jump to it if they were removed by someone
+    /// else.
+    pub fn restore(&self) -> IPTablesResult<()> {
+        self.managed.restore()?;
+
+        let entrypoint = format!("-j {}", self.managed.chain_name());
+        if self
+            .managed
+            .inner()
+            .rule_exists(Self::ENTRYPOINT, &entrypoint)?
+            .not()
+        {
+            self.managed
+                .inner()
+                .insert_rule(Self::ENTRYPOINT, &entrypoint, 1)?;
+        }
+
+        Ok(())
+    }
+
     pub fn add_exclusion(&self, port: u16) -> IPTablesResult<()> {
-        self.managed.add_rule(Self::accept_port_rule(port))?;
+        self.managed.add_base_rule(Self::accept_port_rule(port))?;
         Ok(())
     }
 
     pub fn remove_exclusion(&self, port: u16) -> IPTablesResult<()> {
-        self.managed.remove_rule(Self::accept_port_rule(port))
+        self.managed.remove_base_rule(Self::accept_port_rule(port))
     }
 
     fn accept_port_rule(port: u16) -> String {
@@ -77,15 +94,15 @@ where
     T: Redirect,
 {
     #[tracing::instrument(level = Level::TRACE, skip_all)]
-    pub fn create(ipt: Arc<IPT>, inner: Box<T>) -> IPTablesResult<Self> {
-        let exclusion = MeshExclusion::create(ipt, IPTABLE_EXCLUDE_FROM_MESH)?;
+    pub fn create(ipt: Arc<IPT>, chains: &ChainNames, inner: Box<T>) -> IPTablesResult<Self> {
+        let exclusion = MeshExclusion::create(ipt, &chains.exclude_from_mesh)?;
 
         Ok(WithMeshExclusion { exclusion, inner })
     }
 
     #[tracing::instrument(level = Level::TRACE, skip_all)]
-    pub fn load(ipt: Arc<IPT>, inner: Box<T>) -> IPTablesResult<Self> {
-        let exclusion = MeshExclusion::load(ipt, IPTABLE_EXCLUDE_FROM_MESH)?;
+    pub fn load(ipt: Arc<IPT>, chains: &ChainNames, inner: Box<T>) -> IPTablesResult<Self> {
+        let exclusion = MeshExclusion::load(ipt, &chains.exclude_from_mesh)?;
 
         Ok(WithMeshExclusion { exclusion, inner })
     }
@@ -125,6 +142,20 @@ where
         self.inner.add_redirect(redirected_port, target_port).await
     }
 
+    #[tracing::instrument(level = Level::TRACE, skip(self), ret, err)]
+    async fn restore_chains(&self) -> IPTablesResult<()> {
+        self.inner.restore_chains().await?;
+
+        self.exclusion.restore()
+    }
+
+    #[tracing::instrument(level = Level::TRACE, skip(self), ret, err)]
+    async fn restore_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
+        self.inner
+            .restore_redirect(redirected_port, target_port)
+            .await
+    }
+
     #[tracing::instrument(level = Level::TRACE, skip(self), ret, err)]
     async fn remove_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
         self.inner
@@ -138,7 +169,7 @@ mod tests {
     use mockall::predicate::eq;
 
     use super::*;
-    use crate::MockIPTables;
+    use crate::{IPTABLE_EXCLUDE_FROM_MESH, MockIPTables};
 
     #[test]
     fn default() {
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use async_trait::async_trait;
 
 use crate::{
-    IPTABLE_IPV4_ROUTE_LOCALNET_ORIGINAL, IPTABLE_MESH, IPTables, error::IPTablesResult,
+    ChainNames, IPTABLE_IPV4_ROUTE_LOCALNET_ORIGINAL, IPTables, error::IPTablesResult,
     output::OutputRedirect, prerouting::PreroutingRedirect, redirect::Redirect,
 };
 
@@ -16,16 +16,21 @@ impl<IPT> AmbientRedirect<IPT>
 where
     IPT: IPTables,
 {
-    pub fn create(ipt: Arc<IPT>, pod_ips: Option<&str>) -> IPTablesResult<Self> {
-        let prerouting = PreroutingRedirect::create(ipt.clone())?;
-        let output = OutputRedirect::create(ipt, IPTABLE_MESH.to_string(), pod_ips)?;
+    pub fn create(
+        ipt: Arc<IPT>,
+        chains: &ChainNames,
+        pod_ips: Option<&str>,
+        exclusions: &[String],
+    ) -> IPTablesResult<Self> {
+        let prerouting = PreroutingRedirect::create(ipt.clone(), &chains.prerouting, exclusions)?;
+        let output = OutputRedirect::create(ipt, chains.mesh.clone(), pod_ips, exclusions)?;
 
         Ok(AmbientRedirect { prerouting, output })
     }
 
-    pub fn load(ipt: Arc<IPT>) -> IPTablesResult<Self> {
-        let prerouting = PreroutingRedirect::load(ipt.clone())?;
-        let output = OutputRedirect::load(ipt, IPTABLE_MESH.to_string())?;
+    pub fn load(ipt: Arc<IPT>, chains: &ChainNames) -> IPTablesResult<Self> {
+        let prerouting = PreroutingRedirect::load(ipt.clone(), &chains.prerouting)?;
+        let output = OutputRedirect::load(ipt, chains.mesh.clone())?;
 
         Ok(AmbientRedirect { prerouting, output })
     }
@@ -61,6 +66,13 @@ where
         prerouting_res.and(output_res).and(route_localnet_res)
     }
 
+    async fn restore_chains(&self) -> IPTablesResult<()> {
+        self.prerouting.restore_chains().await?;
+        self.output.restore_chains().await?;
+
+        Ok(())
+    }
+
     async fn add_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
         self.prerouting
             .add_redirect(redirected_port, target_port)
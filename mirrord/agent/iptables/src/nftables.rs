@@ -0,0 +1,383 @@
+//! Native nftables backend for traffic redirection.
+//!
+//! Unlike the `ip[6]tables-nft` wrappers, which put our chains into the `nat` table shared with
+//! the node and the service mesh, this backend keeps all of its state in a dedicated nftables
+//! table ([`NFT_TABLE`]), so it never touches rules that it does not own:
+//!
+//! ```text
+//! table ip mirrord {
+//!     map redirects {
+//!         type inet_service : inet_service
+//!     }
+//!
+//!     chain prerouting {
+//!         type nat hook prerouting priority -101; policy accept;
+//!         ip daddr { 169.254.169.254 } return
+//!         meta l4proto tcp redirect to :tcp dport map @redirects
+//!     }
+//!
+//!     chain output {
+//!         type nat hook output priority -101; policy accept;
+//!         meta skgid 0 meta l4proto tcp ip saddr != { 10.0.0.1 } return
+//!         ip daddr { 169.254.169.254 } return
+//!         oifname "lo" meta l4proto tcp redirect to :tcp dport map @redirects
+//!     }
+//! }
+//! ```
+//!
+//! Our chains run with a priority lower than the standard `dstnat` priority, so the redirects take
+//! precedence over the `nat` table. Redirected ports are elements of the `redirects` map, so adding
+//! and removing a redirection never modifies the chains. Traffic excluded with
+//! [`RedirectExclusions`] returns from both chains before the redirect rules.
+//!
+//! Cleanup deletes the whole table. The table is also recreated from scratch on
+//! [`NftRedirect::create`], so a table left behind by a crashed agent never affects a new one.
+//!
+//! Service mesh rules are not detected with this backend.
+
+use std::{fmt, ops::Not};
+
+use nix::unistd::getgid;
+use tokio::process::Command;
+use tracing::Level;
+
+use crate::{
+    RedirectExclusions,
+    error::{IPTablesError, IPTablesResult},
+};
+
+/// Name of the nftables table that holds all of our state, in the `ip` and `ip6` families.
+pub const NFT_TABLE: &str = "mirrord";
+
+/// Priority of our chains, just before `dstnat` (-100).
+const NFT_CHAIN_PRIORITY: i32 = -101;
+
+/// Manages the [`NFT_TABLE`] of one address family.
+#[derive(Debug)]
+pub struct NftRedirect {
+    /// `ip` or `ip6`.
+    family: &'static str,
+}
+
+impl NftRedirect {
+    /// Creates the [`NFT_TABLE`], replacing any stale table left behind by a previous agent.
+    ///
+    /// # Params
+    ///
+    /// * `pod_ips` - comma-separated list of pod IPs, local traffic from other addresses is not
+    ///   redirected if it comes from the agent itself.
+    /// * `ipv6` - whether to redirect IPv4 or IPv6 traffic.
+    /// * `exclusions` - traffic that is never redirected, only the CIDRs of this address family are
+    ///   used.
+    #[tracing::instrument(level = Level::DEBUG, err)]
+    pub async fn create(
+        pod_ips: Option<&str>,
+        ipv6: bool,
+        exclusions: &RedirectExclusions,
+    ) -> IPTablesResult<Self> {
+        let redirect = Self::new(ipv6);
+        redirect
+            .run(
+                &redirect
+                    .create_commands(pod_ips, getgid().as_raw(), exclusions)
+                    .join("; "),
+            )
+            .await?;
+
+        Ok(redirect)
+    }
+
+    /// Returns a handle to an existing [`NFT_TABLE`], used for cleanup.
+    pub fn load(ipv6: bool) -> Self {
+        Self::new(ipv6)
+    }
+
+    fn new(ipv6: bool) -> Self {
+        Self {
+            family: if ipv6 { "ip6" } else { "ip" },
+        }
+    }
+
+    /// Builds the commands that replace the [`NFT_TABLE`], executed atomically in one `nft` call.
+    ///
+    /// The table is added first, so that deleting it does not fail when there's nothing to
+    /// replace.
+    fn create_commands(
+        &self,
+        pod_ips: Option<&str>,
+        gid: u32,
+        exclusions: &RedirectExclusions,
+    ) -> Vec<String> {
+        let family = self.family;
+        let exclude_source_ips = pod_ips
+            .map(|pod_ips| format!(" {family} saddr != {{ {pod_ips} }}"))
+            .unwrap_or_default();
+
+        let (destinations, sources) = exclusions.of_family(family == "ip6");
+        let exclusion_rules = |chain: &str| {
+            [("daddr", &destinations), ("saddr", &sources)]
+                .into_iter()
+                .filter(|(_, cidrs)| cidrs.is_empty().not())
+                .map(|(selector, cidrs)| {
+                    let cidrs = cidrs
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    format!(
+                        "add rule {family} {NFT_TABLE} {chain} \
+                        {family} {selector} {{ {cidrs} }} return"
+                    )
+                })
+                .chain(exclusions.ports.is_empty().not().then(|| {
+                    let ports = exclusions
+                        .ports
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    format!("add rule {family} {NFT_TABLE} {chain} tcp dport {{ {ports} }} return")
+                }))
+                .collect::<Vec<_>>()
+        };
+
+        let mut commands = vec![
+            format!("add table {family} {NFT_TABLE}"),
+            format!("delete table {family} {NFT_TABLE}"),
+            format!("add table {family} {NFT_TABLE}"),
+            format!(
+                "add map {family} {NFT_TABLE} redirects {{ type inet_service : inet_service; }}"
+            ),
+            format!(
+                "add chain {family} {NFT_TABLE} prerouting \
+                {{ type nat hook prerouting priority {NFT_CHAIN_PRIORITY}; policy accept; }}"
+            ),
+        ];
+        commands.extend(exclusion_rules("prerouting"));
+        commands.extend([
+            format!(
+                "add rule {family} {NFT_TABLE} prerouting \
+                meta l4proto tcp redirect to :tcp dport map @redirects"
+            ),
+            format!(
+                "add chain {family} {NFT_TABLE} output \
+                {{ type nat hook output priority {NFT_CHAIN_PRIORITY}; policy accept; }}"
+            ),
+            format!(
+                "add rule {family} {NFT_TABLE} output \
+                meta skgid {gid} meta l4proto tcp{exclude_source_ips} return"
+            ),
+        ]);
+        commands.extend(exclusion_rules("output"));
+        commands.push(format!(
+            "add rule {family} {NFT_TABLE} output \
+            oifname \"lo\" meta l4proto tcp redirect to :tcp dport map @redirects"
+        ));
+
+        commands
+    }
+
+    /// Redirects TCP traffic on `redirected_port` to `target_port`.
+    #[tracing::instrument(level = Level::DEBUG, skip(self), err)]
+    pub async fn add_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
+        self.run(&format!(
+            "add element {} {NFT_TABLE} redirects {{ {redirected_port} : {target_port} }}",
+            self.family
+        ))
+        .await
+    }
+
+    /// Stops redirecting TCP traffic on `redirected_port`.
+    #[tracing::instrument(level = Level::TRACE, skip(self), err)]
+    pub async fn remove_redirect(
+        &self,
+        redirected_port: u16,
+        _target_port: u16,
+    ) -> IPTablesResult<()> {
+        self.run(&format!(
+            "delete element {} {NFT_TABLE} redirects {{ {redirected_port} }}",
+            self.family
+        ))
+        .await
+    }
+
+    /// Deletes the [`NFT_TABLE`], if it exists.
+    #[tracing::instrument(level = Level::TRACE, skip(self), err)]
+    pub async fn cleanup(&self) -> IPTablesResult<()> {
+        self.run(&format!(
+            "add table {family} {NFT_TABLE}; delete table {family} {NFT_TABLE}",
+            family = self.family
+        ))
+        .await
+    }
+
+    /// Returns whether the [`NFT_TABLE`] exists, e.g. left behind by another agent.
+    #[tracing::instrument(level = Level::TRACE, skip(self), ret, err)]
+    pub async fn exists(&self) -> IPTablesResult<bool> {
+        let output = Command::new("nft")
+            .args(["list", "tables", self.family])
+            .output()
+            .await?;
+
+        if output.status.success().not() {
+            return Err(NftError::from_output(&output).into());
+        }
+
+        let table = self.to_string();
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .any(|line| line.trim() == table))
+    }
+
+    async fn run(&self, script: &str) -> IPTablesResult<()> {
+        let output = Command::new("nft").arg(script).output().await?;
+
+        if output.status.success().not() {
+            return Err(NftError::from_output(&output).into());
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for NftRedirect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "table {} {NFT_TABLE}", self.family)
+    }
+}
+
+/// `nft` command failed.
+#[derive(Debug)]
+struct NftError(String);
+
+impl NftError {
+    fn from_output(output: &std::process::Output) -> Self {
+        Self(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+impl fmt::Display for NftError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "nft command failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for NftError {}
+
+impl From<NftError> for IPTablesError {
+    fn from(value: NftError) -> Self {
+        Self(Box::new(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Not;
+
+    use super::NftRedirect;
+    use crate::RedirectExclusions;
+
+    #[test]
+    fn create_commands_ipv4() {
+        let commands = NftRedirect::new(false).create_commands(
+            Some("10.0.0.1,10.0.0.2"),
+            7,
+            &Default::default(),
+        );
+
+        assert_eq!(
+            commands,
+            [
+                "add table ip mirrord",
+                "delete table ip mirrord",
+                "add table ip mirrord",
+                "add map ip mirrord redirects { type inet_service : inet_service; }",
+                "add chain ip mirrord prerouting \
+                { type nat hook prerouting priority -101; policy accept; }",
+                "add rule ip mirrord prerouting \
+                meta l4proto tcp redirect to :tcp dport map @redirects",
+                "add chain ip mirrord output \
+                { type nat hook output priority -101; policy accept; }",
+                "add rule ip mirrord output \
+                meta skgid 7 meta l4proto tcp ip saddr != { 10.0.0.1,10.0.0.2 } return",
+                "add rule ip mirrord output \
+                oifname \"lo\" meta l4proto tcp redirect to :tcp dport map @redirects",
+            ]
+        );
+    }
+
+    #[test]
+    fn create_commands_ipv6_without_pod_ips() {
+        let commands = NftRedirect::new(true).create_commands(None, 7, &Default::default());
+
+        assert_eq!(commands[1], "delete table ip6 mirrord");
+        assert!(commands.contains(
+            &"add rule ip6 mirrord output meta skgid 7 meta l4proto tcp return".to_string()
+        ));
+        assert!(
+            commands
+                .iter()
+                .any(|command| command.contains("saddr"))
+                .not()
+        );
+    }
+
+    #[test]
+    fn create_commands_with_exclusions() {
+        let exclusions = RedirectExclusions {
+            destinations: vec![
+                "169.254.169.254/32".parse().unwrap(),
+                "10.2.0.0/16".parse().unwrap(),
+                "fd00:ec2::254/128".parse().unwrap(),
+            ],
+            sources: vec!["fd00::/8".parse().unwrap()],
+            ports: vec![],
+        };
+
+        let commands = NftRedirect::new(false).create_commands(None, 7, &exclusions);
+        assert_eq!(
+            commands[5..],
+            [
+                "add rule ip mirrord prerouting \
+                ip daddr { 169.254.169.254/32, 10.2.0.0/16 } return",
+                "add rule ip mirrord prerouting \
+                meta l4proto tcp redirect to :tcp dport map @redirects",
+                "add chain ip mirrord output \
+                { type nat hook output priority -101; policy accept; }",
+                "add rule ip mirrord output meta skgid 7 meta l4proto tcp return",
+                "add rule ip mirrord output \
+                ip daddr { 169.254.169.254/32, 10.2.0.0/16 } return",
+                "add rule ip mirrord output \
+                oifname \"lo\" meta l4proto tcp redirect to :tcp dport map @redirects",
+            ]
+        );
+
+        let commands = NftRedirect::new(true).create_commands(None, 7, &exclusions);
+        assert!(commands.contains(
+            &"add rule ip6 mirrord prerouting ip6 daddr { fd00:ec2::254/128 } return".to_string()
+        ));
+        assert!(
+            commands
+                .contains(&"add rule ip6 mirrord output ip6 saddr { fd00::/8 } return".to_string())
+        );
+    }
+
+    /// Excluded ports (e.g. of the other containers in the target pod) are returned from both
+    /// chains, for both address families.
+    #[test]
+    fn create_commands_with_port_exclusions() {
+        let exclusions = RedirectExclusions {
+            ports: vec![9901, 15000],
+            ..Default::default()
+        };
+
+        for (ipv6, family) in [(false, "ip"), (true, "ip6")] {
+            let commands = NftRedirect::new(ipv6).create_commands(None, 7, &exclusions);
+            for chain in ["prerouting", "output"] {
+                assert!(commands.contains(&format!(
+                    "add rule {family} mirrord {chain} tcp dport {{ 9901, 15000 }} return"
+                )));
+            }
+        }
+    }
+}
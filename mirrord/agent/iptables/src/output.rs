@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{ops::Not, sync::Arc};
 
 use async_trait::async_trait;
 use nix::unistd::getgid;
@@ -21,6 +21,7 @@ where
         ipt: Arc<IPT>,
         chain_name: String,
         pod_ips: Option<&str>,
+        exclusions: &[String],
     ) -> IPTablesResult<Self> {
         let managed = IPTableChain::create(ipt, chain_name.clone()).inspect_err(
             |e| tracing::error!(%e, "Could not create iptables chain \"{chain_name}\"."),
@@ -32,13 +33,17 @@ where
 
         let gid = getgid();
         managed
-            .add_rule(format!(
+            .add_base_rule(format!(
                 "-m owner --gid-owner {gid} -p tcp {exclude_source_ips} -j RETURN"
             ))
             .inspect_err(|_| {
                 warn!("Unable to create iptable rule with \"--gid-owner {gid}\" filter")
             })?;
 
+        for rule in exclusions {
+            managed.add_base_rule(rule)?;
+        }
+
         Ok(OutputRedirect { managed })
     }
 
@@ -80,12 +85,36 @@ where
         )
     }
 
+    async fn restore_chains(&self) -> IPTablesResult<()> {
+        self.managed.restore()?;
+
+        let entrypoint = format!("-j {}", self.managed.chain_name());
+        if self
+            .managed
+            .inner()
+            .rule_exists(Self::ENTRYPOINT, &entrypoint)?
+            .not()
+        {
+            if USE_INSERT {
+                self.managed
+                    .inner()
+                    .insert_rule(Self::ENTRYPOINT, &entrypoint, 1)?;
+            } else {
+                self.managed
+                    .inner()
+                    .add_rule(Self::ENTRYPOINT, &entrypoint)?;
+            }
+        }
+
+        Ok(())
+    }
+
     async fn add_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
         let redirect_rule = format!(
             "-o lo -m tcp -p tcp --dport {redirected_port} -j REDIRECT --to-ports {target_port}"
         );
 
-        self.managed.add_rule(&redirect_rule)?;
+        self.managed.add_rule_if_absent(&redirect_rule)?;
 
         Ok(())
     }
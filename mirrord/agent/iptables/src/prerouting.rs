@@ -1,8 +1,11 @@
-use std::{ops::Deref, sync::Arc};
+use std::{
+    ops::{Deref, Not},
+    sync::Arc,
+};
 
 use async_trait::async_trait;
 
-use crate::{IPTABLE_PREROUTING, IPTables, Redirect, chain::IPTableChain, error::IPTablesResult};
+use crate::{IPTables, Redirect, chain::IPTableChain, error::IPTablesResult};
 
 pub struct PreroutingRedirect<IPT: IPTables> {
     managed: IPTableChain<IPT>,
@@ -14,14 +17,22 @@ where
 {
     const ENTRYPOINT: &'static str = "PREROUTING";
 
-    pub fn create(ipt: Arc<IPT>) -> IPTablesResult<Self> {
-        let managed = IPTableChain::create(ipt, IPTABLE_PREROUTING.to_string())?;
+    /// Create a new `chain` that will be jumped to from the `PREROUTING` chain.
+    ///
+    /// `exclusions` are added to the chain before any redirect rule.
+    pub fn create(ipt: Arc<IPT>, chain: &str, exclusions: &[String]) -> IPTablesResult<Self> {
+        let managed = IPTableChain::create(ipt, chain.to_string())?;
+
+        for rule in exclusions {
+            managed.add_base_rule(rule)?;
+        }
 
         Ok(PreroutingRedirect { managed })
     }
 
-    pub fn load(ipt: Arc<IPT>) -> IPTablesResult<Self> {
-        let managed = IPTableChain::load(ipt, IPTABLE_PREROUTING.to_string())?;
+    /// Load an existing `chain`.
+    pub fn load(ipt: Arc<IPT>, chain: &str) -> IPTablesResult<Self> {
+        let managed = IPTableChain::load(ipt, chain.to_string())?;
 
         Ok(PreroutingRedirect { managed })
     }
@@ -48,11 +59,29 @@ where
         )
     }
 
+    async fn restore_chains(&self) -> IPTablesResult<()> {
+        self.managed.restore()?;
+
+        let entrypoint = format!("-j {}", self.managed.chain_name());
+        if self
+            .managed
+            .inner()
+            .rule_exists(Self::ENTRYPOINT, &entrypoint)?
+            .not()
+        {
+            self.managed
+                .inner()
+                .add_rule(Self::ENTRYPOINT, &entrypoint)?;
+        }
+
+        Ok(())
+    }
+
     async fn add_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
         let redirect_rule =
             format!("-m tcp -p tcp --dport {redirected_port} -j REDIRECT --to-ports {target_port}");
 
-        self.managed.add_rule(&redirect_rule)?;
+        self.managed.add_rule_if_absent(&redirect_rule)?;
 
         Ok(())
     }
@@ -85,13 +114,20 @@ mod tests {
     use mockall::predicate::eq;
 
     use crate::{
-        IPTABLE_PREROUTING, MockIPTables, prerouting::PreroutingRedirect, redirect::Redirect,
+        IPTABLE_PREROUTING, MockIPTables, chain::listed_chain, error::IPTablesError,
+        prerouting::PreroutingRedirect, redirect::Redirect,
     };
 
     #[tokio::test]
     async fn add_redirect() {
         let mut mock = MockIPTables::new();
 
+        mock.expect_rule_exists().returning(|_, _| Ok(false));
+
+        mock.expect_list_rules()
+            .with(eq(IPTABLE_PREROUTING))
+            .returning(|chain| Ok(listed_chain(chain, 0)));
+
         mock.expect_create_chain()
             .with(eq(IPTABLE_PREROUTING))
             .times(1)
@@ -111,7 +147,8 @@ mod tests {
             .times(1)
             .returning(|_| Ok(()));
 
-        let prerouting = PreroutingRedirect::create(Arc::new(mock)).expect("Unable to create");
+        let prerouting = PreroutingRedirect::create(Arc::new(mock), IPTABLE_PREROUTING, &[])
+            .expect("Unable to create");
 
         assert!(prerouting.add_redirect(69, 420).await.is_ok());
     }
@@ -120,6 +157,17 @@ mod tests {
     async fn add_redirect_twice() {
         let mut mock = MockIPTables::new();
 
+        mock.expect_rule_exists().returning(|_, _| Ok(false));
+
+        let mut added = 0;
+        mock.expect_list_rules()
+            .with(eq(IPTABLE_PREROUTING))
+            .times(2)
+            .returning(move |chain| {
+                added += 1;
+                Ok(listed_chain(chain, added - 1))
+            });
+
         mock.expect_create_chain()
             .with(eq(IPTABLE_PREROUTING))
             .times(1)
@@ -148,12 +196,174 @@ mod tests {
             .times(1)
             .returning(|_| Ok(()));
 
-        let prerouting = PreroutingRedirect::create(Arc::new(mock)).expect("Unable to create");
+        let prerouting = PreroutingRedirect::create(Arc::new(mock), IPTABLE_PREROUTING, &[])
+            .expect("Unable to create");
 
         assert!(prerouting.add_redirect(69, 420).await.is_ok());
         assert!(prerouting.add_redirect(169, 1420).await.is_ok());
     }
 
+    /// Adding a redirect that already exists does nothing.
+    #[tokio::test]
+    async fn add_existing_redirect() {
+        let mut mock = MockIPTables::new();
+
+        mock.expect_create_chain()
+            .with(eq(IPTABLE_PREROUTING))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        mock.expect_rule_exists()
+            .with(
+                eq(IPTABLE_PREROUTING),
+                eq("-m tcp -p tcp --dport 69 -j REDIRECT --to-ports 420"),
+            )
+            .times(2)
+            .returning(|_, _| Ok(true));
+
+        mock.expect_insert_rule().never();
+
+        mock.expect_remove_chain()
+            .with(eq(IPTABLE_PREROUTING))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let prerouting = PreroutingRedirect::create(Arc::new(mock), IPTABLE_PREROUTING, &[])
+            .expect("Unable to create");
+
+        assert!(prerouting.add_redirect(69, 420).await.is_ok());
+        assert!(prerouting.restore_redirect(69, 420).await.is_ok());
+    }
+
+    /// After another process flushes our chain and removes the jump to it, the whole rule set is
+    /// restored: the exclusions first, then the final `-j RETURN`, the jump from `PREROUTING`, and
+    /// the redirect between the exclusions and the final `-j RETURN`.
+    #[tokio::test]
+    async fn restore_removed_redirect() {
+        let mut mock = MockIPTables::new();
+
+        mock.expect_create_chain()
+            .with(eq(IPTABLE_PREROUTING))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        // Nothing survives the flush.
+        mock.expect_rule_exists().returning(|_, _| Ok(false));
+
+        let mut listings = [
+            listed_chain(IPTABLE_PREROUTING, 2),
+            vec![format!("-N {IPTABLE_PREROUTING}")],
+            listed_chain(IPTABLE_PREROUTING, 2),
+            listed_chain(IPTABLE_PREROUTING, 2),
+        ]
+        .into_iter();
+        mock.expect_list_rules()
+            .with(eq(IPTABLE_PREROUTING))
+            .times(4)
+            .returning(move |_| Ok(listings.next().unwrap()));
+
+        for (rule, index) in [
+            ("-d 10.0.0.0/8 -j RETURN", 1),
+            ("-p tcp --dport 9999 -j RETURN", 2),
+        ] {
+            mock.expect_insert_rule()
+                .with(eq(IPTABLE_PREROUTING), eq(rule), eq(index))
+                .times(2)
+                .returning(|_, _, _| Ok(()));
+        }
+
+        mock.expect_insert_rule()
+            .with(
+                eq(IPTABLE_PREROUTING),
+                eq("-m tcp -p tcp --dport 69 -j REDIRECT --to-ports 420"),
+                eq(3),
+            )
+            .times(2)
+            .returning(|_, _, _| Ok(()));
+
+        mock.expect_add_rule()
+            .with(eq(IPTABLE_PREROUTING), eq("-j RETURN"))
+            .times(1)
+            .returning(|_, _| Ok(()));
+
+        mock.expect_add_rule()
+            .with(eq("PREROUTING"), eq(format!("-j {IPTABLE_PREROUTING}")))
+            .times(2)
+            .returning(|_, _| Ok(()));
+
+        mock.expect_remove_chain()
+            .with(eq(IPTABLE_PREROUTING))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let prerouting = PreroutingRedirect::create(
+            Arc::new(mock),
+            IPTABLE_PREROUTING,
+            &[
+                "-d 10.0.0.0/8 -j RETURN".into(),
+                "-p tcp --dport 9999 -j RETURN".into(),
+            ],
+        )
+        .expect("Unable to create");
+
+        assert!(prerouting.mount_entrypoint().await.is_ok());
+        assert!(prerouting.add_redirect(69, 420).await.is_ok());
+
+        // Another process flushes our chain and removes the jump to it.
+        assert!(prerouting.restore_chains().await.is_ok());
+        assert!(prerouting.restore_redirect(69, 420).await.is_ok());
+    }
+
+    /// A chain deleted by another process is created again, with its exclusions.
+    #[tokio::test]
+    async fn restore_deleted_chain() {
+        let mut mock = MockIPTables::new();
+
+        mock.expect_create_chain()
+            .with(eq(IPTABLE_PREROUTING))
+            .times(2)
+            .returning(|_| Ok(()));
+
+        mock.expect_insert_rule()
+            .with(eq(IPTABLE_PREROUTING), eq("-d 10.0.0.0/8 -j RETURN"), eq(1))
+            .times(2)
+            .returning(|_, _, _| Ok(()));
+
+        let mut listings = [
+            Err(IPTablesError("No chain/target/match by that name.".into())),
+            Ok(listed_chain(IPTABLE_PREROUTING, 1)),
+        ]
+        .into_iter();
+        mock.expect_list_rules()
+            .with(eq(IPTABLE_PREROUTING))
+            .times(2)
+            .returning(move |_| listings.next().unwrap());
+
+        mock.expect_rule_exists()
+            .with(eq(IPTABLE_PREROUTING), eq("-d 10.0.0.0/8 -j RETURN"))
+            .times(1)
+            .returning(|_, _| Ok(false));
+
+        mock.expect_rule_exists()
+            .with(eq("PREROUTING"), eq(format!("-j {IPTABLE_PREROUTING}")))
+            .times(1)
+            .returning(|_, _| Ok(true));
+
+        mock.expect_remove_chain()
+            .with(eq(IPTABLE_PREROUTING))
+            .times(1)
+            .returning(|_| Ok(()));
+
+        let prerouting = PreroutingRedirect::create(
+            Arc::new(mock),
+            IPTABLE_PREROUTING,
+            &["-d 10.0.0.0/8 -j RETURN".into()],
+        )
+        .expect("Unable to create");
+
+        assert!(prerouting.restore_chains().await.is_ok());
+    }
+
     #[tokio::test]
     async fn remove_redirect() {
         let mut mock = MockIPTables::new();
@@ -176,7 +386,8 @@ mod tests {
             .times(1)
             .returning(|_| Ok(()));
 
-        let prerouting = PreroutingRedirect::create(Arc::new(mock)).expect("Unable to create");
+        let prerouting = PreroutingRedirect::create(Arc::new(mock), IPTABLE_PREROUTING, &[])
+            .expect("Unable to create");
 
         assert!(prerouting.remove_redirect(69, 420).await.is_ok());
     }
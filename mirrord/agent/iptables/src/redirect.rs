@@ -13,8 +13,21 @@ pub trait Redirect {
     /// that.
     async fn unmount_entrypoint(&self) -> IPTablesResult<()>;
 
-    /// Create port redirection
+    /// Recreate our chains, their base rules (e.g. exclusions) and the jumps to them if they were
+    /// removed by someone else, e.g. flushed by a restarted CNI plugin.
+    ///
+    /// Port redirections are restored separately, with [`Redirect::restore_redirect`].
+    async fn restore_chains(&self) -> IPTablesResult<()>;
+
+    /// Create port redirection, does nothing if it already exists
     async fn add_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()>;
+
+    /// Recreate port redirection if it was removed by someone else, without side effects on
+    /// existing connections
+    async fn restore_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
+        self.add_redirect(redirected_port, target_port).await
+    }
+
     /// Remove port redirection
     async fn remove_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()>;
 }
@@ -3,7 +3,7 @@ use std::sync::Arc;
 use async_trait::async_trait;
 
 use crate::{
-    IPTABLE_STANDARD, IPTables, Redirect, error::IPTablesResult, output::OutputRedirect,
+    ChainNames, IPTables, Redirect, error::IPTablesResult, output::OutputRedirect,
     prerouting::PreroutingRedirect,
 };
 
@@ -16,16 +16,21 @@ impl<IPT> StandardRedirect<IPT>
 where
     IPT: IPTables,
 {
-    pub fn create(ipt: Arc<IPT>, pod_ips: Option<&str>) -> IPTablesResult<Self> {
-        let prerouting = PreroutingRedirect::create(ipt.clone())?;
-        let output = OutputRedirect::create(ipt, IPTABLE_STANDARD.to_string(), pod_ips)?;
+    pub fn create(
+        ipt: Arc<IPT>,
+        chains: &ChainNames,
+        pod_ips: Option<&str>,
+        exclusions: &[String],
+    ) -> IPTablesResult<Self> {
+        let prerouting = PreroutingRedirect::create(ipt.clone(), &chains.prerouting, exclusions)?;
+        let output = OutputRedirect::create(ipt, chains.standard.clone(), pod_ips, exclusions)?;
 
         Ok(StandardRedirect { prerouting, output })
     }
 
-    pub fn load(ipt: Arc<IPT>) -> IPTablesResult<Self> {
-        let prerouting = PreroutingRedirect::load(ipt.clone())?;
-        let output = OutputRedirect::load(ipt, IPTABLE_STANDARD.to_string())?;
+    pub fn load(ipt: Arc<IPT>, chains: &ChainNames) -> IPTablesResult<Self> {
+        let prerouting = PreroutingRedirect::load(ipt.clone(), &chains.prerouting)?;
+        let output = OutputRedirect::load(ipt, chains.standard.clone())?;
 
         Ok(StandardRedirect { prerouting, output })
     }
@@ -53,6 +58,13 @@ where
         prerouting_res.and(output_res)
     }
 
+    async fn restore_chains(&self) -> IPTablesResult<()> {
+        self.prerouting.restore_chains().await?;
+        self.output.restore_chains().await?;
+
+        Ok(())
+    }
+
     async fn add_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
         self.prerouting
             .add_redirect(redirected_port, target_port)
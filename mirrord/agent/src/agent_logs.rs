@@ -0,0 +1,182 @@
+//! In-memory buffer of the agent's most recent log lines, served to the clients with
+//! [`ClientMessage::GetAgentLogs`](mirrord_protocol::ClientMessage::GetAgentLogs).
+//!
+//! The buffer is filled by a [`tracing_subscriber::fmt::Layer`] that uses [`AGENT_LOGS`] as its
+//! writer.
+
+use std::{
+    collections::VecDeque,
+    io,
+    sync::{Arc, LazyLock, Mutex},
+};
+
+use mirrord_protocol::agent_logs::{AGENT_LOGS_MAX_BYTES, AgentLogs};
+use tracing_subscriber::fmt::MakeWriter;
+
+/// How many of the most recent log lines the agent keeps.
+const MAX_LINES: usize = 2000;
+
+/// Log lines of this agent process.
+pub static AGENT_LOGS: LazyLock<AgentLogBuffer> = LazyLock::new(Default::default);
+
+#[derive(Default, Debug)]
+struct Lines {
+    lines: VecDeque<String>,
+    /// Whether any lines were dropped to respect the capacity.
+    dropped: bool,
+}
+
+/// Keeps at most `capacity` of the most recent log lines.
+#[derive(Clone, Debug)]
+pub struct AgentLogBuffer {
+    inner: Arc<Mutex<Lines>>,
+    capacity: usize,
+}
+
+impl Default for AgentLogBuffer {
+    fn default() -> Self {
+        Self::with_capacity(MAX_LINES)
+    }
+}
+
+impl AgentLogBuffer {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inner: Default::default(),
+            capacity,
+        }
+    }
+
+    fn push(&self, line: &str) {
+        let mut inner = self.inner.lock().expect("AgentLogBuffer mutex is poisoned");
+
+        while inner.lines.len() >= self.capacity {
+            inner.lines.pop_front();
+            inner.dropped = true;
+        }
+
+        inner.lines.push_back(line.to_string());
+    }
+
+    /// Returns at most `tail` of the most recent lines, limited to [`AGENT_LOGS_MAX_BYTES`] in
+    /// total.
+    pub fn tail(&self, tail: u64) -> AgentLogs {
+        let tail = usize::try_from(tail).unwrap_or(usize::MAX);
+        let inner = self.inner.lock().expect("AgentLogBuffer mutex is poisoned");
+
+        let mut lines = Vec::new();
+        let mut size = 0;
+        let mut truncated = false;
+
+        for line in inner.lines.iter().rev().take(tail) {
+            size += line.len();
+            if size > AGENT_LOGS_MAX_BYTES {
+                truncated = true;
+                break;
+            }
+
+            lines.push(line.clone());
+        }
+
+        if lines.len() < tail && lines.len() == inner.lines.len() && inner.dropped {
+            truncated = true;
+        }
+
+        lines.reverse();
+
+        AgentLogs { lines, truncated }
+    }
+}
+
+impl<'a> MakeWriter<'a> for AgentLogBuffer {
+    type Writer = LogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        LogWriter {
+            buffer: self.clone(),
+            bytes: Vec::new(),
+        }
+    }
+}
+
+/// Writes a single log event into the [`AgentLogBuffer`] when dropped.
+pub struct LogWriter {
+    buffer: AgentLogBuffer,
+    bytes: Vec<u8>,
+}
+
+impl io::Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.bytes.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for LogWriter {
+    fn drop(&mut self) {
+        String::from_utf8_lossy(&self.bytes)
+            .lines()
+            .filter(|line| !line.is_empty())
+            .for_each(|line| self.buffer.push(line));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use mirrord_protocol::agent_logs::{AGENT_LOGS_MAX_BYTES, AgentLogs};
+    use tracing_subscriber::fmt::MakeWriter;
+
+    use super::AgentLogBuffer;
+
+    fn log(buffer: &AgentLogBuffer, message: &str) {
+        let mut writer = buffer.make_writer();
+        writer.write_all(message.as_bytes()).unwrap();
+        writer.write_all(b"\n").unwrap();
+    }
+
+    #[test]
+    fn returns_bounded_tail() {
+        let buffer = AgentLogBuffer::with_capacity(3);
+        for i in 0..5 {
+            log(&buffer, &format!("line {i}"));
+        }
+
+        assert_eq!(
+            buffer.tail(2),
+            AgentLogs {
+                lines: vec!["line 3".into(), "line 4".into()],
+                truncated: false,
+            }
+        );
+        assert_eq!(
+            buffer.tail(10),
+            AgentLogs {
+                lines: vec!["line 2".into(), "line 3".into(), "line 4".into()],
+                truncated: true,
+            }
+        );
+        assert_eq!(buffer.tail(0), AgentLogs::default());
+    }
+
+    #[test]
+    fn respects_size_cap() {
+        let buffer = AgentLogBuffer::with_capacity(10);
+        let long_line = "a".repeat(AGENT_LOGS_MAX_BYTES / 2);
+        for _ in 0..3 {
+            log(&buffer, &long_line);
+        }
+        log(&buffer, "last");
+
+        let logs = buffer.tail(10);
+        assert!(logs.truncated);
+        assert_eq!(logs.lines.len(), 2);
+        assert_eq!(logs.lines.last().unwrap(), "last");
+        assert!(logs.lines.iter().map(String::len).sum::<usize>() <= AGENT_LOGS_MAX_BYTES);
+    }
+}
@@ -1,9 +1,7 @@
 #![deny(missing_docs)]
 
-use std::net::SocketAddr;
-
 use clap::{Parser, Subcommand};
-use mirrord_agent_env::envs;
+use mirrord_agent_env::{envs, metrics::MetricsAddress};
 
 const DEFAULT_RUNTIME: &str = "containerd";
 
@@ -25,8 +23,14 @@ pub struct Args {
     pub communication_timeout: u16,
 
     /// Controls whether metrics are enabled, and the address to set up the metrics server.
+    ///
+    /// Either a socket address, or an absolute unix socket path prefixed with `unix://`.
     #[arg(long, env = envs::METRICS.name)]
-    pub metrics: Option<SocketAddr>,
+    pub metrics: Option<MetricsAddress>,
+
+    /// Bearer token required in the `Authorization` header of metrics requests.
+    #[arg(long, env = envs::METRICS_TOKEN.name, hide_env_values = true)]
+    pub metrics_token: Option<String>,
 
     /// Return an error after accepting the first client connection, in order to test agent error
     /// cleanup.
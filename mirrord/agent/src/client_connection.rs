@@ -2,6 +2,7 @@ use std::{
     fmt::{self, Debug},
     io,
     sync::Arc,
+    time::Duration,
 };
 
 use actix_codec::Framed;
@@ -9,7 +10,7 @@ use futures::{SinkExt, TryStreamExt};
 use mirrord_protocol::{ClientMessage, DaemonCodec, DaemonMessage};
 use mirrord_tls_util::{GetSanError, HasSubjectAlternateNames};
 use thiserror::Error;
-use tokio::net::TcpStream;
+use tokio::{net::TcpStream, time::Instant};
 use tokio_rustls::{
     TlsConnector,
     client::TlsStream,
@@ -81,10 +82,15 @@ pub(crate) enum TlsSetupError {
     AddToRootStoreError(#[from] tokio_rustls::rustls::Error),
 }
 
+/// Messages buffered with [`ClientConnection::feed`] are flushed at the latest after this long.
+const MAX_FLUSH_DELAY: Duration = Duration::from_millis(1);
+
 /// Wrapper over client's network connection with the agent.
 pub struct ClientConnection {
     framed: ConnectionFramed,
     client_id: ClientId,
+    /// When the oldest message that was buffered but not yet flushed was fed.
+    unflushed_since: Option<Instant>,
 }
 
 impl ClientConnection {
@@ -109,10 +115,14 @@ impl ClientConnection {
             None => ConnectionFramed::Tcp(Framed::new(stream, DaemonCodec::default())),
         };
 
-        Ok(Self { framed, client_id })
+        Ok(Self {
+            framed,
+            client_id,
+            unflushed_since: None,
+        })
     }
 
-    /// Sends a [`DaemonMessage`] to the client.
+    /// Sends a [`DaemonMessage`] to the client, flushing it together with the buffered ones.
     #[tracing::instrument(level = "trace", err)]
     pub async fn send(&mut self, message: DaemonMessage) -> io::Result<()> {
         match &mut self.framed {
@@ -120,9 +130,53 @@ impl ClientConnection {
             ConnectionFramed::Tls(framed) => framed.send(message).await?,
         }
 
+        self.unflushed_since = None;
+
         Ok(())
     }
 
+    /// Buffers a [`DaemonMessage`] to be sent to the client with the next
+    /// [`ClientConnection::flush`], so that a burst of small messages costs a single write.
+    ///
+    /// Flushes right away if the message is [`DaemonMessage::is_latency_sensitive`], or the
+    /// buffered messages waited for [`MAX_FLUSH_DELAY`] already. The underlying [`Framed`] also
+    /// flushes on its own when the buffer gets big.
+    #[tracing::instrument(level = "trace", err)]
+    pub async fn feed(&mut self, message: DaemonMessage) -> io::Result<()> {
+        let urgent = message.is_latency_sensitive();
+
+        match &mut self.framed {
+            ConnectionFramed::Tcp(framed) => framed.feed(message).await?,
+            ConnectionFramed::Tls(framed) => framed.feed(message).await?,
+        }
+
+        let unflushed_since = *self.unflushed_since.get_or_insert_with(Instant::now);
+        if urgent || unflushed_since.elapsed() >= MAX_FLUSH_DELAY {
+            self.flush().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Flushes the messages buffered with [`ClientConnection::feed`].
+    #[tracing::instrument(level = "trace", err)]
+    pub async fn flush(&mut self) -> io::Result<()> {
+        match &mut self.framed {
+            ConnectionFramed::Tcp(framed) => framed.flush().await?,
+            ConnectionFramed::Tls(framed) => framed.flush().await?,
+        }
+
+        self.unflushed_since = None;
+
+        Ok(())
+    }
+
+    /// Whether there are messages buffered with [`ClientConnection::feed`] that were not flushed
+    /// yet.
+    pub fn has_unflushed(&self) -> bool {
+        self.unflushed_since.is_some()
+    }
+
     /// Receives a [`ClientMessage`] from the client.
     #[tracing::instrument(level = "trace", err)]
     pub async fn receive(&mut self) -> io::Result<Option<ClientMessage>> {
@@ -260,4 +314,30 @@ mod test {
             },
         );
     }
+
+    /// Verifies that [`ClientConnection::feed`] buffers messages until a latency sensitive one
+    /// comes, and that the client gets them in order.
+    #[tokio::test]
+    async fn feed_flushes_latency_sensitive() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let version: semver::Version = "1.0.0".parse().unwrap();
+        let buffered = DaemonMessage::SwitchProtocolVersionResponse(version);
+
+        let (stream, accepted) = tokio::join!(TcpStream::connect(addr), listener.accept());
+        let mut connection = ClientConnection::new(stream.unwrap(), 0, None)
+            .await
+            .unwrap();
+        let mut framed = Framed::new(accepted.unwrap().0, ClientCodec::default());
+
+        connection.feed(buffered.clone()).await.unwrap();
+        assert!(connection.has_unflushed());
+
+        connection.feed(DaemonMessage::Pong).await.unwrap();
+        assert!(!connection.has_unflushed());
+
+        assert_eq!(framed.next().await.unwrap().unwrap(), buffered);
+        assert_eq!(framed.next().await.unwrap().unwrap(), DaemonMessage::Pong);
+    }
 }
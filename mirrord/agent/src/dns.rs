@@ -1,5 +1,6 @@
 use std::{
-    collections::HashMap, future, io, path::PathBuf, sync::atomic::Ordering, time::Duration,
+    collections::HashMap, future, io, path::PathBuf, str::FromStr, sync::atomic::Ordering,
+    time::Duration,
 };
 
 use futures::{StreamExt, stream::FuturesOrdered};
@@ -7,8 +8,13 @@ use hickory_resolver::{
     Hosts, TokioAsyncResolver,
     config::{LookupIpStrategy, ServerOrderingStrategy},
     error::{ResolveError, ResolveErrorKind},
+    lookup::Lookup,
     lookup_ip::LookupIp,
-    proto::error::ProtoErrorKind,
+    proto::{
+        error::ProtoErrorKind,
+        op::Query,
+        rr::{Name, RecordType},
+    },
     system_conf::parse_resolv_conf,
 };
 use mirrord_agent_env::envs;
@@ -31,8 +37,11 @@ use tokio::{
 use tokio_util::sync::CancellationToken;
 use tracing::{Level, warn};
 
+use self::search::SearchPolicy;
 use crate::{error::AgentResult, metrics::DNS_REQUEST_COUNT, task::status::BgTaskStatus};
 
+mod search;
+
 #[derive(Debug)]
 pub(crate) enum ClientGetAddrInfoRequest {
     V1(GetAddrInfoRequest),
@@ -73,6 +82,10 @@ pub(crate) struct DnsWorker {
     ///
     /// Configured via [`envs::DNS_TIMEOUT`].
     timeout: Option<Duration>,
+    /// Overrides the search list from the target's `resolv.conf`.
+    ///
+    /// Configured via [`envs::DNS_SEARCH`].
+    search: Option<Vec<String>>,
     /// Whether we want support querying for IPv6 addresses.
     support_ipv6: bool,
     /// Background tasks that handle the DNS requests.
@@ -114,12 +127,14 @@ impl DnsWorker {
             .ok()
             .flatten()
             .map(|attempts| usize::try_from(attempts).unwrap_or(usize::MAX));
+        let search = envs::DNS_SEARCH.try_from_env().ok().flatten();
 
         Self {
             etc_path,
             request_rx,
             timeout,
             attempts,
+            search,
             support_ipv6,
             tasks: Default::default(),
             response_txs: Default::default(),
@@ -129,6 +144,9 @@ impl DnsWorker {
     /// Reads `/etc/resolv.conf` and `/etc/hosts` files, then uses [`TokioAsyncResolver`] to
     /// resolve address of the given `host`.
     ///
+    /// Relative names are expanded with the search list and `ndots` option from `resolv.conf`
+    /// (or the `search` override) by [`SearchPolicy`].
+    ///
     /// # TODO
     ///
     /// We could probably cache results here.
@@ -140,6 +158,7 @@ impl DnsWorker {
         request: GetAddrInfoRequestV2,
         attempts: Option<usize>,
         timeout: Option<Duration>,
+        search: Option<Vec<String>>,
         support_ipv6: bool,
     ) -> Result<DnsLookup, InternalLookupError> {
         // Prepares the `Resolver` after reading some `/etc` DNS files.
@@ -173,26 +192,52 @@ impl DnsWorker {
             };
 
             tracing::debug!(?config, ?options, "Updated resolv configuration");
+            let ip_strategy = options.ip_strategy;
 
-            let mut resolver = TokioAsyncResolver::tokio(config, options);
+            // Like in `glibc`, the local domain is used only when there's no search list.
+            let search_policy = match search {
+                Some(search) => SearchPolicy::new(search, options.ndots),
+                None if config.search().is_empty() => {
+                    SearchPolicy::new(config.domain().map(ToString::to_string), options.ndots)
+                }
+                None => SearchPolicy::new(
+                    config.search().iter().map(ToString::to_string),
+                    options.ndots,
+                ),
+            };
+
+            // The resolver doesn't get the hosts file, which is checked only for the name as it
+            // is, before the DNS queries, see `lookup_hosts`.
+            let resolver = TokioAsyncResolver::tokio(config, options);
             tracing::debug!(?resolver, "Build a DNS resolver");
 
             let hosts = Hosts::default()
                 .read_hosts_conf(hosts_conf.as_slice())
                 .map_err(From::from)?;
 
-            resolver.set_hosts(Some(hosts));
-
-            resolver
+            (resolver, search_policy, hosts, ip_strategy)
         };
 
-        let lookup = resolver
-            .inspect_err(|fail| tracing::error!(?fail, "Failed to build a DNS resolver"))?
-            .lookup_ip(request.node)
+        let (resolver, search_policy, hosts, ip_strategy) = resolver
+            .inspect_err(|fail| tracing::error!(?fail, "Failed to build a DNS resolver"))?;
+
+        if let Some(lookup) = lookup_hosts(&hosts, &request.node, ip_strategy) {
+            tracing::trace!(?lookup, "Found in the hosts file");
+            return Ok(lookup);
+        }
+
+        let resolver = &resolver;
+
+        let lookup = search_policy
+            .lookup(&request.node, move |name| async move {
+                resolver
+                    .lookup_ip(name)
+                    .await
+                    .inspect(|lookup| tracing::trace!(?lookup, "DNS lookup finished"))
+                    .map(|lookup| lookup.convert())
+            })
             .await
-            .inspect(|lookup| tracing::trace!(?lookup, "DNS lookup finished"))
-            .inspect_err(|e| tracing::debug!(%e, "DNS lookup failed"))?
-            .convert();
+            .inspect_err(|e| tracing::debug!(%e, "DNS lookup failed"))?;
 
         Ok(lookup)
     }
@@ -203,6 +248,7 @@ impl DnsWorker {
         let etc_path = self.etc_path.clone();
         let timeout = self.timeout;
         let attempts = self.attempts;
+        let search = self.search.clone();
         let support_ipv6 = self.support_ipv6;
 
         let handle = self.tasks.spawn(Self::do_lookup(
@@ -210,6 +256,7 @@ impl DnsWorker {
             message.request.into_v2(),
             attempts,
             timeout,
+            search,
             support_ipv6,
         ));
         self.response_txs.insert(handle.id(), message.response_tx);
@@ -390,10 +437,47 @@ impl ProtocolConversion<ResolveErrorKindInternal> for &ResolveErrorKind {
     }
 }
 
+/// Looks `name` up in the hosts file, like the `files` backend of `glibc`, which gets the name
+/// as it is, before the DNS search list expansion.
+///
+/// Returns [`None`] if there are no addresses for the name.
+fn lookup_hosts(hosts: &Hosts, name: &str, ip_strategy: LookupIpStrategy) -> Option<DnsLookup> {
+    let name = Name::from_str(name).ok()?;
+
+    let record_types: &[RecordType] = match ip_strategy {
+        LookupIpStrategy::Ipv4Only => &[RecordType::A],
+        LookupIpStrategy::Ipv6Only => &[RecordType::AAAA],
+        LookupIpStrategy::Ipv4AndIpv6 | LookupIpStrategy::Ipv4thenIpv6 => {
+            &[RecordType::A, RecordType::AAAA]
+        }
+        LookupIpStrategy::Ipv6thenIpv4 => &[RecordType::AAAA, RecordType::A],
+    };
+    // Otherwise, we stop at the first record type that has addresses.
+    let both = matches!(ip_strategy, LookupIpStrategy::Ipv4AndIpv6);
+
+    let mut records = Vec::new();
+    for &record_type in record_types {
+        if let Some(lookup) = hosts.lookup_static_host(&Query::query(name.clone(), record_type)) {
+            records.extend(lookup.convert().0);
+        }
+
+        if !records.is_empty() && !both {
+            break;
+        }
+    }
+
+    (!records.is_empty()).then_some(DnsLookup(records))
+}
+
 impl ProtocolConversion<DnsLookup> for LookupIp {
+    fn convert(self) -> DnsLookup {
+        self.as_lookup().convert()
+    }
+}
+
+impl ProtocolConversion<DnsLookup> for &Lookup {
     fn convert(self) -> DnsLookup {
         let lookup_records = self
-            .as_lookup()
             .records()
             .iter()
             .filter_map(|record| {
@@ -427,3 +511,46 @@ impl ProtocolConversion<LookupIpStrategy> for AddressFamily {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use rstest::rstest;
+
+    use super::*;
+
+    const HOSTS: &str = "10.0.0.1 redis\n::1 redis\n10.0.0.2 postgres.default.svc.cluster.local\n";
+
+    /// Only the name as it is goes to the hosts file, and it's found there regardless of the
+    /// search list, which is applied only to the DNS queries.
+    #[rstest]
+    #[case::ipv4("redis", LookupIpStrategy::Ipv4Only, &["10.0.0.1"])]
+    #[case::ipv6("redis", LookupIpStrategy::Ipv6Only, &["::1"])]
+    #[case::both("redis", LookupIpStrategy::Ipv4AndIpv6, &["10.0.0.1", "::1"])]
+    #[case::ipv6_first("redis", LookupIpStrategy::Ipv6thenIpv4, &["::1"])]
+    #[case::absolute("redis.", LookupIpStrategy::Ipv4Only, &["10.0.0.1"])]
+    #[case::not_expanded("postgres", LookupIpStrategy::Ipv4Only, &[])]
+    #[case::not_found("mysql", LookupIpStrategy::Ipv4AndIpv6, &[])]
+    fn lookup_hosts_literal(
+        #[case] name: &str,
+        #[case] ip_strategy: LookupIpStrategy,
+        #[case] expected: &[&str],
+    ) {
+        let hosts = Hosts::default().read_hosts_conf(HOSTS.as_bytes()).unwrap();
+
+        let ips = lookup_hosts(&hosts, name, ip_strategy)
+            .map(|lookup| {
+                lookup
+                    .0
+                    .into_iter()
+                    .map(|record| record.ip)
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        let expected = expected
+            .iter()
+            .map(|ip| ip.parse().unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(ips, expected);
+    }
+}
@@ -0,0 +1,269 @@
+//! Expansion of the names passed to `getaddrinfo` with the DNS search list, see
+//! [`SearchPolicy`].
+
+use std::{future::Future, iter, net::IpAddr};
+
+use hickory_resolver::error::{ResolveError, ResolveErrorKind};
+use mirrord_protocol::dns::DnsLookup;
+
+/// Decides which absolute names we query for a name passed to `getaddrinfo`, following the rules
+/// of `resolv.conf(5)`.
+///
+/// We expand the names ourselves and pass only absolute names to the resolver, so that the
+/// lookups behave the same as they would in the target pod.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SearchPolicy {
+    /// Domains appended to the relative names, without the leading and trailing dots.
+    search: Vec<String>,
+    /// Names with fewer dots than this are tried with the [`Self::search`] domains first.
+    ndots: usize,
+}
+
+impl SearchPolicy {
+    pub(crate) fn new<I, S>(search: I, ndots: usize) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let search = search
+            .into_iter()
+            .map(|domain| domain.as_ref().trim_matches('.').to_string())
+            .filter(|domain| !domain.is_empty())
+            .collect();
+
+        Self { search, ndots }
+    }
+
+    /// Returns the absolute names to query for `name`, in order.
+    ///
+    /// 1. Absolute names (ending with a dot) and IP addresses are queried as they are.
+    /// 2. Names with at least [`Self::ndots`] dots are queried as they are first, then with the
+    ///    [`Self::search`] domains.
+    /// 3. Other names are queried with the [`Self::search`] domains first, then as they are.
+    pub(crate) fn candidates(&self, name: &str) -> Vec<String> {
+        if name.ends_with('.') || name.parse::<IpAddr>().is_ok() {
+            return vec![name.to_string()];
+        }
+
+        let literal = format!("{name}.");
+        let with_search = self.search.iter().map(|domain| format!("{name}.{domain}."));
+
+        if name.matches('.').count() >= self.ndots {
+            iter::once(literal).chain(with_search).collect()
+        } else {
+            with_search.chain(iter::once(literal)).collect()
+        }
+    }
+
+    /// Queries the [`Self::candidates`] for `name` with `lookup`, until one of them is found.
+    ///
+    /// We move on to the next candidate only when the current one has no records. Other errors,
+    /// e.g. timeouts, are returned right away, like `glibc` does.
+    pub(crate) async fn lookup<F, Fut>(
+        &self,
+        name: &str,
+        mut lookup: F,
+    ) -> Result<DnsLookup, ResolveError>
+    where
+        F: FnMut(String) -> Fut,
+        Fut: Future<Output = Result<DnsLookup, ResolveError>>,
+    {
+        let candidates = self.candidates(name);
+        tracing::debug!(
+            name,
+            ndots = self.ndots,
+            search = ?self.search,
+            ?candidates,
+            "Expanded the name with the DNS search list",
+        );
+
+        let mut result = Ok(DnsLookup(Vec::new()));
+        for candidate in candidates {
+            result = lookup(candidate).await;
+
+            match &result {
+                Ok(found) if found.0.is_empty() => {}
+                Err(error) if matches!(error.kind(), ResolveErrorKind::NoRecordsFound { .. }) => {}
+                _ => break,
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashMap, str::FromStr};
+
+    use hickory_resolver::proto::{
+        op::{Query, ResponseCode},
+        rr::{Name, RecordType},
+    };
+    use mirrord_protocol::dns::LookupRecord;
+    use rstest::rstest;
+
+    use super::*;
+
+    const KUBE_SEARCH: [&str; 3] = [
+        "default.svc.cluster.local",
+        "svc.cluster.local",
+        "cluster.local",
+    ];
+
+    fn not_found(name: &str) -> ResolveError {
+        ResolveErrorKind::NoRecordsFound {
+            query: Box::new(Query::query(Name::from_str(name).unwrap(), RecordType::A)),
+            soa: None,
+            negative_ttl: None,
+            response_code: ResponseCode::NXDomain,
+            trusted: true,
+        }
+        .into()
+    }
+
+    /// Resolves `name` with a mock resolver that knows only the given `records`.
+    ///
+    /// Returns the result, and the names that were queried.
+    async fn mock_lookup(
+        policy: &SearchPolicy,
+        name: &str,
+        records: &[(&str, &str)],
+    ) -> (Result<DnsLookup, ResolveError>, Vec<String>) {
+        let records = records.iter().copied().collect::<HashMap<_, _>>();
+        let mut queried = Vec::new();
+
+        let result = policy
+            .lookup(name, |candidate| {
+                let result = match records.get(candidate.as_str()) {
+                    Some(ip) => Ok(DnsLookup(vec![LookupRecord {
+                        name: candidate.clone(),
+                        ip: ip.parse().unwrap(),
+                    }])),
+                    None => Err(not_found(&candidate)),
+                };
+                queried.push(candidate);
+                std::future::ready(result)
+            })
+            .await;
+
+        (result, queried)
+    }
+
+    #[rstest]
+    #[case::single_label("redis", 5, &[
+        "redis.default.svc.cluster.local.",
+        "redis.svc.cluster.local.",
+        "redis.cluster.local.",
+        "redis.",
+    ])]
+    #[case::multi_label_below_ndots("redis.other", 5, &[
+        "redis.other.default.svc.cluster.local.",
+        "redis.other.svc.cluster.local.",
+        "redis.other.cluster.local.",
+        "redis.other.",
+    ])]
+    #[case::multi_label_at_ndots("api.example.com", 2, &[
+        "api.example.com.",
+        "api.example.com.default.svc.cluster.local.",
+        "api.example.com.svc.cluster.local.",
+        "api.example.com.cluster.local.",
+    ])]
+    #[case::absolute("redis.svc.cluster.local.", 5, &["redis.svc.cluster.local."])]
+    #[case::ip_address("10.0.0.1", 5, &["10.0.0.1"])]
+    fn candidates(#[case] name: &str, #[case] ndots: usize, #[case] expected: &[&str]) {
+        let policy = SearchPolicy::new(KUBE_SEARCH, ndots);
+        assert_eq!(policy.candidates(name), expected);
+    }
+
+    #[test]
+    fn normalizes_search_domains() {
+        assert_eq!(
+            SearchPolicy::new([".svc.cluster.local.", "", "cluster.local"], 1),
+            SearchPolicy::new(["svc.cluster.local", "cluster.local"], 1),
+        );
+    }
+
+    /// A single label name is found with the first search domain that has it, the literal name
+    /// is not queried.
+    #[tokio::test]
+    async fn single_label() {
+        let policy = SearchPolicy::new(KUBE_SEARCH, 5);
+
+        let (result, queried) = mock_lookup(
+            &policy,
+            "redis",
+            &[
+                ("redis.svc.cluster.local.", "10.0.0.1"),
+                ("redis.", "10.0.0.2"),
+            ],
+        )
+        .await;
+
+        assert_eq!(
+            result.unwrap().0[0].ip,
+            "10.0.0.1".parse::<IpAddr>().unwrap()
+        );
+        assert_eq!(
+            queried,
+            [
+                "redis.default.svc.cluster.local.",
+                "redis.svc.cluster.local."
+            ]
+        );
+    }
+
+    /// A multi label name below `ndots` falls back to the literal name when none of the search
+    /// domains has it.
+    #[tokio::test]
+    async fn multi_label_below_ndots() {
+        let policy = SearchPolicy::new(KUBE_SEARCH, 5);
+
+        let (result, queried) = mock_lookup(
+            &policy,
+            "db.example.com",
+            &[("db.example.com.", "10.0.0.3")],
+        )
+        .await;
+
+        assert_eq!(result.unwrap().0[0].name, "db.example.com.");
+        assert_eq!(queried.len(), KUBE_SEARCH.len() + 1);
+        assert_eq!(queried.last().unwrap(), "db.example.com.");
+    }
+
+    /// An absolute name is queried only as it is, even when it's not found.
+    #[tokio::test]
+    async fn absolute() {
+        let policy = SearchPolicy::new(KUBE_SEARCH, 5);
+
+        let (result, queried) = mock_lookup(
+            &policy,
+            "redis.",
+            &[("redis.default.svc.cluster.local.", "10.0.0.1")],
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(queried, ["redis."]);
+    }
+
+    /// Errors other than missing records stop the lookup.
+    #[tokio::test]
+    async fn stops_on_timeout() {
+        let policy = SearchPolicy::new(KUBE_SEARCH, 5);
+        let mut queried = 0;
+
+        let result = policy
+            .lookup("redis", |_| {
+                queried += 1;
+                std::future::ready(Err(ResolveErrorKind::Timeout.into()))
+            })
+            .await;
+
+        assert!(matches!(
+            result.unwrap_err().kind(),
+            ResolveErrorKind::Timeout
+        ));
+        assert_eq!(queried, 1);
+    }
+}
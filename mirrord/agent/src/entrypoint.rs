@@ -13,14 +13,20 @@ use std::{
 use async_pidfd::AsyncPidFd;
 use client_connection::AgentTlsConnector;
 use dns::{ClientGetAddrInfoRequest, DnsCommand};
-use futures::{TryFutureExt, future::OptionFuture};
+use futures::TryFutureExt;
+use idle_ttl::{IdleTtl, TtlExtensions};
 use metrics::{CLIENT_COUNT, start_metrics};
 use mirrord_agent_env::envs;
 use mirrord_agent_iptables::{
-    IPTablesWrapper, SafeIpTables,
+    ChainNames, IPTablesWrapper, SafeIpTables,
     error::{IPTablesError, IPTablesResult},
+    nftables::NftRedirect,
+};
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, ERROR_KIND_IN_PROGRESS_VERSION, GetEnvVarsRequest, ResponseError,
+    time::RemoteTime,
+    udp::{DaemonUdp, LayerUdp},
 };
-use mirrord_protocol::{ClientMessage, DaemonMessage, GetEnvVarsRequest};
 use tokio::{
     net::{TcpListener, TcpSocket, TcpStream},
     process::Command,
@@ -31,10 +37,11 @@ use tokio::{
     time::{Duration, timeout},
 };
 use tokio_util::sync::CancellationToken;
-use tracing::{Level, debug, error, trace, warn};
+use tracing::{Level, debug, error, info, trace, warn};
 use tracing_subscriber::{fmt::format::FmtSpan, prelude::*};
 
 use crate::{
+    agent_logs::AGENT_LOGS,
     cli::{self, Args},
     client_connection::{self, ClientConnection},
     container_handle::ContainerHandle,
@@ -42,9 +49,9 @@ use crate::{
     env,
     error::{AgentError, AgentResult},
     file::FileManager,
-    incoming::MirrorHandle,
+    incoming::{self, MirrorHandle, SelectedRedirector},
     metrics,
-    mirror::TcpMirrorApi,
+    mirror::{MirrorLimits, TcpMirrorApi, UdpMirrorApi},
     namespace::NamespaceType,
     outgoing::{TcpOutgoingApi, UdpOutgoingApi},
     reverse_dns::ReverseDnsApi,
@@ -54,6 +61,7 @@ use crate::{
     util::{ClientId, protocol_version::ClientProtocolVersion},
 };
 
+mod idle_ttl;
 mod setup;
 
 /// [`ExitCode`](std::process::ExitCode) returned from the child agent process
@@ -94,6 +102,8 @@ struct State {
     tls_connector: Option<AgentTlsConnector>,
     /// [`tokio::runtime`] that should be used for network operations ([`BackgroundTasks`]).
     network_runtime: Arc<BgTaskRuntime>,
+    /// Extensions of the agent's idle TTL requested by the clients.
+    ttl_extensions: TtlExtensions,
 }
 
 impl State {
@@ -170,6 +180,7 @@ impl State {
             ephemeral,
             tls_connector,
             network_runtime: Arc::new(network_runtime),
+            ttl_extensions: Default::default(),
         })
     }
 
@@ -214,6 +225,24 @@ impl State {
             && envs::EXCLUDE_FROM_MESH.from_env_or_default()
             && envs::IN_SERVICE_MESH.from_env_or_default()
     }
+
+    /// Picks the incoming traffic redirector, based on [`envs::REDIRECTOR`] and the agent's
+    /// capabilities.
+    fn selected_redirector(&self) -> AgentResult<SelectedRedirector> {
+        let requested = envs::REDIRECTOR.from_env_or_default();
+        let selected = incoming::select_redirector(
+            requested,
+            incoming::has_net_admin(),
+            self.is_with_mesh_exclusion(),
+        )?;
+        debug!(
+            ?requested,
+            ?selected,
+            "Selected the incoming traffic redirector."
+        );
+
+        Ok(selected)
+    }
 }
 
 enum BackgroundTask<Command> {
@@ -268,6 +297,8 @@ struct ClientConnectionHandler {
     /// [`None`] when targetless.
     tcp_mirror_api: Option<TcpMirrorApi>,
     /// [`None`] when targetless.
+    udp_mirror_api: Option<UdpMirrorApi>,
+    /// [`None`] when targetless.
     tcp_stealer_api: Option<TcpStealerApi>,
     tcp_outgoing_api: TcpOutgoingApi,
     udp_outgoing_api: UdpOutgoingApi,
@@ -297,17 +328,28 @@ impl ClientConnectionHandler {
     ) -> AgentResult<Self> {
         let protocol_version = ClientProtocolVersion::default();
 
-        let pid = state.container_pid();
+        let pid = state
+            .container_pid()
+            .or_else(|| state.ephemeral.then_some(1));
 
-        let file_manager = FileManager::new(pid.or_else(|| state.ephemeral.then_some(1)));
+        let file_manager = FileManager::new(pid);
 
-        let tcp_mirror_api = bg_tasks
+        let udp_mirror_api = bg_tasks
             .mirror_handle
-            .map(|mirror_handle| TcpMirrorApi::new(mirror_handle, protocol_version.clone()));
+            .is_some()
+            .then(|| UdpMirrorApi::new(&state.network_runtime, protocol_version.clone()));
+        let tcp_mirror_api = bg_tasks.mirror_handle.map(|mirror_handle| {
+            TcpMirrorApi::new(
+                mirror_handle,
+                protocol_version.clone(),
+                MirrorLimits::from_env(),
+            )
+        });
         let tcp_stealer_api = Self::create_stealer_api(
             id,
             protocol_version.clone(),
             bg_tasks.stealer,
+            pid,
             &mut connection,
         )
         .await?;
@@ -321,6 +363,7 @@ impl ClientConnectionHandler {
             file_manager,
             connection,
             tcp_mirror_api,
+            udp_mirror_api,
             tcp_stealer_api,
             tcp_outgoing_api,
             udp_outgoing_api,
@@ -340,11 +383,19 @@ impl ClientConnectionHandler {
         id: ClientId,
         protocol_version: ClientProtocolVersion,
         task: BackgroundTask<StealerCommand>,
+        target_pid: Option<u64>,
         connection: &mut ClientConnection,
     ) -> AgentResult<Option<TcpStealerApi>> {
         match task {
             BackgroundTask::Running(stealer_status, stealer_sender) => {
-                match TcpStealerApi::new(id, protocol_version, stealer_sender, stealer_status).await
+                match TcpStealerApi::new(
+                    id,
+                    protocol_version,
+                    stealer_sender,
+                    stealer_status,
+                    target_pid,
+                )
+                .await
                 {
                     Ok(api) => Ok(Some(api)),
                     Err(e) => {
@@ -408,6 +459,16 @@ impl ClientConnectionHandler {
                     }
                     Err(e) => break e,
                 },
+                message = async {
+                    match self.udp_mirror_api { Some(ref mut mirror_api) => {
+                        mirror_api.recv().await
+                    } _ => {
+                        unreachable!()
+                    }}
+                }, if self.udp_mirror_api.is_some() => match message {
+                    Ok(message) => self.respond(message).await?,
+                    Err(e) => break e,
+                },
                 message = async {
                     match self.tcp_stealer_api { Some(ref mut stealer_api) => {
                         stealer_api.recv().await
@@ -423,7 +484,8 @@ impl ClientConnectionHandler {
                         // Being explicit here.
                         // Throttle permits should be dropped only when the message has been sent and flushed.
                         let _throttle = message.throttle;
-                        self.respond(message.message).await?
+                        self.respond(message.message).await?;
+                        self.connection.flush().await?
                     },
                     Err(e) => break e,
                 },
@@ -432,7 +494,8 @@ impl ClientConnectionHandler {
                         // Being explicit here.
                         // Throttle permits should be dropped only when the message has been sent and flushed.
                         let _throttle = message.throttle;
-                        self.respond(DaemonMessage::UdpOutgoing(message.message)).await?
+                        self.respond(DaemonMessage::UdpOutgoing(message.message)).await?;
+                        self.connection.flush().await?
                     },
                     Err(e) => break e,
                 },
@@ -444,7 +507,15 @@ impl ClientConnectionHandler {
                     Ok(message) => self.respond(DaemonMessage::ReverseDnsLookup(Ok(message))).await?,
                     Err(e) => break e,
                 },
-                _ = cancellation_token.cancelled() => return Ok(()),
+                // `yield_now` completes only when polled again, so we flush the buffered responses
+                // when no other branch is ready, see `ClientConnection::feed`.
+                _ = tokio::task::yield_now(), if self.connection.has_unflushed() => {
+                    self.connection.flush().await?
+                },
+                _ = cancellation_token.cancelled() => {
+                    let _ = self.connection.flush().await;
+                    return Ok(());
+                },
             }
         };
 
@@ -456,13 +527,25 @@ impl ClientConnectionHandler {
     }
 
     /// Sends a [`DaemonMessage`] response to the connected client (`mirrord-layer`).
+    ///
+    /// The response may be buffered and flushed together with the next ones, see
+    /// [`ClientConnection::feed`].
     #[tracing::instrument(level = "trace", skip(self))]
-    async fn respond(&mut self, response: DaemonMessage) -> AgentResult<()> {
+    async fn respond(&mut self, mut response: DaemonMessage) -> AgentResult<()> {
         if matches!(&response, DaemonMessage::LogMessage(..)) && self.ready_for_logs.not() {
             return Ok(());
         }
 
-        self.connection.send(response).await.map_err(Into::into)
+        if let Some(error) = response.response_error_mut()
+            && self
+                .protocol_version
+                .matches(&ERROR_KIND_IN_PROGRESS_VERSION)
+                .not()
+        {
+            error.downgrade_error_kind();
+        }
+
+        self.connection.feed(response).await.map_err(Into::into)
     }
 
     /// Handles incoming messages from the connected client (`mirrord-layer`).
@@ -519,6 +602,17 @@ impl ClientConnectionHandler {
                     .request_reverse_lookup(request.ip_address);
             }
             ClientMessage::Ping => self.respond(DaemonMessage::Pong).await?,
+            ClientMessage::GetRemoteTime => {
+                self.respond(DaemonMessage::RemoteTime(RemoteTime::now()))
+                    .await?
+            }
+            ClientMessage::ExtendTtl(secs) => {
+                self.state.ttl_extensions.extend(Duration::from_secs(secs))
+            }
+            ClientMessage::GetAgentLogs { tail } => {
+                self.respond(DaemonMessage::AgentLogs(AGENT_LOGS.tail(tail)))
+                    .await?
+            }
             // Message handled exclusively by the operator, see its docs for details.
             ClientMessage::OperatorPong(_) => (),
             ClientMessage::Tcp(message) => match &mut self.tcp_mirror_api {
@@ -531,6 +625,17 @@ impl ClientConnectionHandler {
                     )).await?;
                 }
             },
+            ClientMessage::Udp(message) => match &mut self.udp_mirror_api {
+                Some(mirror_api) => mirror_api.handle_client_message(message).await?,
+                None => {
+                    if let LayerUdp::PortSubscribe(..) = message {
+                        self.respond(DaemonMessage::Udp(DaemonUdp::SubscribeResult(Err(
+                            ResponseError::NotImplemented,
+                        ))))
+                        .await?;
+                    }
+                }
+            },
             ClientMessage::TcpSteal(message) => {
                 let error = match self.tcp_stealer_api.as_mut() {
                     Some(tcp_stealer_api) => tcp_stealer_api
@@ -680,10 +785,11 @@ fn monitor_main_container(cancel: CancellationToken, pid: libc::pid_t) {
 async fn get_rules(
     iptables: &IPTablesWrapper,
     ip6tables: Option<&IPTablesWrapper>,
+    chains: &ChainNames,
 ) -> IPTablesResult<Vec<String>> {
-    let rules_v4 = SafeIpTables::list_mirrord_rules(iptables).await?;
+    let rules_v4 = SafeIpTables::list_mirrord_rules(iptables, chains).await?;
     if let Some(ip6tables) = ip6tables {
-        let rules_v6 = SafeIpTables::list_mirrord_rules(ip6tables).await?;
+        let rules_v6 = SafeIpTables::list_mirrord_rules(ip6tables, chains).await?;
         Ok([rules_v4, rules_v6].concat())
     } else {
         Ok(rules_v4)
@@ -703,7 +809,8 @@ async fn check_existing_rules(
     let nftables = envs::NFTABLES.try_from_env().unwrap_or_default();
     let iptables = mirrord_agent_iptables::get_iptables(nftables, false);
     let ip6tables = support_ipv6.then(|| mirrord_agent_iptables::get_iptables(nftables, true));
-    let rules = get_rules(&iptables, ip6tables.as_ref()).await?;
+    let chains = ChainNames::from_env();
+    let rules = get_rules(&iptables, ip6tables.as_ref(), &chains).await?;
     if clean_existing_rules
         && rules.is_empty().not()
         && let Err(err) = clear_iptable_chain(support_ipv6, with_mesh_exclusion).await
@@ -711,7 +818,7 @@ async fn check_existing_rules(
         // the error could be because we tried to remove two rules and only one of them was
         // present to begin with, so removing the other, non-existent one failed.
         // So we check the rules after cleaning and only fail if there are still rules.
-        let rules = get_rules(&iptables, ip6tables.as_ref()).await?;
+        let rules = get_rules(&iptables, ip6tables.as_ref(), &chains).await?;
         if rules.is_empty().not() {
             // There are still rules after the cleanup, the cleanup was not successful.
             return Err(err);
@@ -721,6 +828,35 @@ async fn check_existing_rules(
     Ok(rules)
 }
 
+/// Get existing native nftables tables created by another (potentially still running) agent.
+///
+/// If `clean_existing_tables` is set, the tables will be deleted after fetching them. The tables
+/// from before the cleanup will be returned for logging.
+#[tracing::instrument(level = Level::TRACE, ret, err)]
+async fn check_existing_nft_tables(
+    support_ipv6: bool,
+    clean_existing_tables: bool,
+) -> IPTablesResult<Vec<String>> {
+    let mut tables = Vec::new();
+
+    for ipv6 in [false, true] {
+        if ipv6 && support_ipv6.not() {
+            continue;
+        }
+
+        let nftables = NftRedirect::load(ipv6);
+        if nftables.exists().await? {
+            tables.push(nftables.to_string());
+
+            if clean_existing_tables {
+                nftables.cleanup().await?;
+            }
+        }
+    }
+
+    Ok(tables)
+}
+
 /// Real mirrord-agent routine.
 ///
 /// Obtains the PID of the target container (if there is any),
@@ -758,22 +894,41 @@ async fn start_agent(args: Args) -> AgentResult<()> {
 
     let cancellation_token = CancellationToken::new();
 
+    // The redirector is only relevant if we have a target.
+    let redirector = state
+        .container_pid()
+        .map(|_| state.selected_redirector())
+        .transpose()?;
+
     // Check that chain names won't conflict with another agent or failed cleanup.
-    // This check is only relevant if we have a target.
+    // This check is only relevant if we have a target and use the iptables redirector.
     // If we don't have any target, the agent should be running in a fresh network namespace,
     // and you should **not** expect that it can access iptables.
     if let Some(target_pid) = state.container_pid() {
-        let leftover_rules = state
-            .network_runtime
-            .handle()
-            .spawn(check_existing_rules(
-                args.ipv6,
-                args.clean_iptables_on_start,
-                state.is_with_mesh_exclusion(),
-            ))
-            .await
-            .map_err(|error| AgentError::IPTablesSetupError(error.into()))?
-            .map_err(|error| AgentError::IPTablesSetupError(error.into()))?;
+        let leftover_rules = match redirector {
+            Some(SelectedRedirector::Ebpf) => Vec::new(),
+            Some(SelectedRedirector::Nftables) => state
+                .network_runtime
+                .handle()
+                .spawn(check_existing_nft_tables(
+                    args.ipv6,
+                    args.clean_iptables_on_start,
+                ))
+                .await
+                .map_err(|error| AgentError::IPTablesSetupError(error.into()))?
+                .map_err(|error| AgentError::IPTablesSetupError(error.into()))?,
+            _ => state
+                .network_runtime
+                .handle()
+                .spawn(check_existing_rules(
+                    args.ipv6,
+                    args.clean_iptables_on_start,
+                    state.is_with_mesh_exclusion(),
+                ))
+                .await
+                .map_err(|error| AgentError::IPTablesSetupError(error.into()))?
+                .map_err(|error| AgentError::IPTablesSetupError(error.into()))?,
+        };
 
         if leftover_rules.is_empty().not() {
             if args.clean_iptables_on_start {
@@ -806,10 +961,11 @@ async fn start_agent(args: Args) -> AgentResult<()> {
     // To make sure that background tasks are cancelled when we exit early from this function.
     let cancel_guard = cancellation_token.clone().drop_guard();
 
-    if let Some(metrics_address) = args.metrics {
+    if let Some(metrics_address) = args.metrics.clone() {
         let cancellation_token = cancellation_token.clone();
+        let metrics_token = args.metrics_token.clone();
         tokio::spawn(async move {
-            start_metrics(metrics_address, cancellation_token.clone())
+            start_metrics(metrics_address, metrics_token, cancellation_token.clone())
                 .await
                 .inspect_err(|fail| {
                     tracing::error!(?fail, "Failed starting metrics server!");
@@ -818,12 +974,13 @@ async fn start_agent(args: Args) -> AgentResult<()> {
         });
     }
 
-    let (stealer, mirror_handle) = match state.container_pid() {
+    let (stealer, mirror_handle) = match state.container_pid().zip(redirector) {
         None => (BackgroundTask::Disabled, None),
-        Some(pid) => {
+        Some((pid, redirector)) => {
             let (steal_handle, mirror_handle) = setup::start_traffic_redirector(
                 &state.network_runtime,
                 pid,
+                redirector,
                 state
                     .is_with_mesh_exclusion()
                     .then(|| client_listener_address.port()),
@@ -854,6 +1011,10 @@ async fn start_agent(args: Args) -> AgentResult<()> {
     println!("agent ready - version {}", env!("CARGO_PKG_VERSION"));
 
     let mut clients: JoinSet<ClientId> = JoinSet::new();
+    let mut idle_ttl = IdleTtl::new(
+        Duration::from_secs(envs::IDDLE_TTL.from_env_or_default()),
+        state.ttl_extensions.clone(),
+    );
 
     // We wait for the first client until `communication_timeout` elapses.
     let first_connection = timeout(
@@ -870,6 +1031,7 @@ async fn start_agent(args: Args) -> AgentResult<()> {
                 bg_tasks.clone(),
                 cancellation_token.clone(),
             ));
+            idle_ttl.client_connected();
         }
 
         Ok(Err(error)) => {
@@ -887,11 +1049,7 @@ async fn start_agent(args: Args) -> AgentResult<()> {
         Err(AgentError::TestError)?
     }
 
-    let idle_ttl = Duration::from_secs(envs::IDDLE_TTL.from_env_or_default());
     loop {
-        let exit_idle =
-            OptionFuture::from(clients.is_empty().then_some(tokio::time::sleep(idle_ttl)));
-
         select! {
             Ok((stream, addr)) = listener.accept() => {
                 trace!(peer = %addr, "start_agent -> Connection accepted");
@@ -903,6 +1061,7 @@ async fn start_agent(args: Args) -> AgentResult<()> {
                         cancellation_token.clone()
                     )
                 );
+                idle_ttl.client_connected();
             },
 
             Some(client) = clients.join_next() => {
@@ -914,11 +1073,11 @@ async fn start_agent(args: Args) -> AgentResult<()> {
                         error!(%error, "start_agent -> Failed to join client handler task");
                     }
                 }
+                idle_ttl.client_disconnected();
             }
 
-            Some(..) = exit_idle => {
-                trace!(
-                    ?idle_ttl,
+            _ = idle_ttl.expired() => {
+                info!(
                     "start_agent -> All clients finished and idle ttl expired, exiting main agent loop"
                 );
                 break;
@@ -949,13 +1108,17 @@ async fn clear_iptable_chain(
     with_mesh_exclusion: bool,
 ) -> Result<(), IPTablesError> {
     let nftables = envs::NFTABLES.try_from_env().unwrap_or_default();
+    let chains = ChainNames::from_env();
 
     let v4_result: Result<(), IPTablesError> = try {
         let ipt = mirrord_agent_iptables::get_iptables(nftables, false);
-        if SafeIpTables::list_mirrord_rules(&ipt).await?.is_empty() {
+        if SafeIpTables::list_mirrord_rules(&ipt, &chains)
+            .await?
+            .is_empty()
+        {
             trace!("No iptables mirrord rules found, skipping iptables cleanup.");
         } else {
-            let tables = SafeIpTables::load(ipt, false, with_mesh_exclusion).await?;
+            let tables = SafeIpTables::load(ipt, &chains, false, with_mesh_exclusion).await?;
             tables.cleanup().await?
         }
     };
@@ -963,10 +1126,13 @@ async fn clear_iptable_chain(
     let v6_result: Result<(), IPTablesError> = if ipv6_enabled {
         try {
             let ipt = mirrord_agent_iptables::get_iptables(nftables, true);
-            if SafeIpTables::list_mirrord_rules(&ipt).await?.is_empty() {
+            if SafeIpTables::list_mirrord_rules(&ipt, &chains)
+                .await?
+                .is_empty()
+            {
                 trace!("No ip6tables mirrord rules found, skipping ip6tables cleanup.");
             } else {
-                let tables = SafeIpTables::load(ipt, true, with_mesh_exclusion).await?;
+                let tables = SafeIpTables::load(ipt, &chains, true, with_mesh_exclusion).await?;
                 tables.cleanup().await?
             }
         }
@@ -977,6 +1143,18 @@ async fn clear_iptable_chain(
     v4_result.and(v6_result)
 }
 
+/// Deletes the native nftables tables, if they exist.
+async fn clear_nft_tables(ipv6_enabled: bool) -> Result<(), IPTablesError> {
+    let v4_result = NftRedirect::load(false).cleanup().await;
+    let v6_result = if ipv6_enabled {
+        NftRedirect::load(true).cleanup().await
+    } else {
+        Ok(())
+    };
+
+    v4_result.and(v6_result)
+}
+
 /// Runs the current binary as a child process,
 /// using the exact same command line.
 ///
@@ -1013,6 +1191,7 @@ async fn start_iptable_guard(args: Args) -> AgentResult<()> {
 
     let state = State::new(&args).await?;
     let with_mesh_exclusion = state.is_with_mesh_exclusion();
+    let redirector = state.selected_redirector()?;
 
     let mut sigterm = tokio::signal::unix::signal(SignalKind::terminate())?;
 
@@ -1032,10 +1211,25 @@ async fn start_iptable_guard(args: Args) -> AgentResult<()> {
         },
     };
 
-    state
-        .network_runtime
-        .handle()
-        .spawn(clear_iptable_chain(args.ipv6, with_mesh_exclusion))
+    // The eBPF redirector does not leave any state behind, the kernel cleans it up when the child
+    // process exits.
+    if redirector == SelectedRedirector::Ebpf {
+        return result;
+    }
+
+    let cleanup = if redirector == SelectedRedirector::Nftables {
+        state
+            .network_runtime
+            .handle()
+            .spawn(clear_nft_tables(args.ipv6))
+    } else {
+        state
+            .network_runtime
+            .handle()
+            .spawn(clear_iptable_chain(args.ipv6, with_mesh_exclusion))
+    };
+
+    cleanup
         .await
         .map_err(|error| AgentError::BackgroundTaskFailed {
             task: "IPTablesCleaner",
@@ -1088,6 +1282,11 @@ pub async fn main() -> AgentResult<()> {
                     .with_span_events(FmtSpan::NEW | FmtSpan::CLOSE)
                     .json(),
             )
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(AGENT_LOGS.clone()),
+            )
             .with(tracing_subscriber::EnvFilter::from_default_env())
             .init();
     } else {
@@ -1099,6 +1298,11 @@ pub async fn main() -> AgentResult<()> {
                     .pretty()
                     .with_line_number(true),
             )
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(AGENT_LOGS.clone()),
+            )
             .with(tracing_subscriber::EnvFilter::from_default_env())
             .init();
     }
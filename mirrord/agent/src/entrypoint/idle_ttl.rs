@@ -0,0 +1,185 @@
+//! Idle TTL of the agent, configured with [`envs::IDDLE_TTL`](mirrord_agent_env::envs::IDDLE_TTL).
+//!
+//! The countdown runs only when no clients are connected, and is reset when a client connects.
+//! Clients can additionally keep the agent alive for some time after they disconnect with
+//! [`ClientMessage::ExtendTtl`](mirrord_protocol::ClientMessage::ExtendTtl), so that the agent
+//! waits for them to reconnect.
+
+use std::sync::{Arc, Mutex};
+
+use tokio::time::{Duration, Instant};
+use tracing::info;
+
+/// Extensions of the [`IdleTtl`] requested by the clients.
+///
+/// Can be cheaply cloned and shared with the client connection handlers.
+#[derive(Clone, Default, Debug)]
+pub(super) struct TtlExtensions(Arc<Mutex<Option<Instant>>>);
+
+impl TtlExtensions {
+    /// Keeps the agent alive for at least `duration` from now.
+    ///
+    /// Never shortens an earlier extension. Rejects durations that overflow the [`Instant`].
+    pub(super) fn extend(&self, duration: Duration) {
+        let Some(until) = Instant::now().checked_add(duration) else {
+            tracing::warn!(?duration, "Rejected an idle TTL extension that is too long");
+            return;
+        };
+        let mut guard = self.0.lock().expect("TtlExtensions mutex is poisoned");
+        if guard.is_none_or(|current| current < until) {
+            tracing::debug!(?duration, "Idle TTL extended by a client");
+            *guard = Some(until);
+        }
+    }
+
+    /// Returns the time until which the agent should stay alive, if any client extended the TTL.
+    fn until(&self) -> Option<Instant> {
+        *self.0.lock().expect("TtlExtensions mutex is poisoned")
+    }
+}
+
+/// Tracks the connected clients, and decides when the agent should exit.
+#[derive(Debug)]
+pub(super) struct IdleTtl {
+    /// How long the agent waits for a new client after the last one disconnects.
+    ttl: Duration,
+    extensions: TtlExtensions,
+    /// Number of currently connected clients.
+    connected: usize,
+    /// When the agent should exit, set only when there are no connected clients.
+    deadline: Option<Instant>,
+}
+
+impl IdleTtl {
+    /// Creates a new instance with no connected clients.
+    ///
+    /// The countdown starts only when the last client disconnects, waiting for the first client
+    /// is handled separately.
+    pub(super) fn new(ttl: Duration, extensions: TtlExtensions) -> Self {
+        Self {
+            ttl,
+            extensions,
+            connected: 0,
+            deadline: None,
+        }
+    }
+
+    /// Pauses the countdown.
+    pub(super) fn client_connected(&mut self) {
+        self.connected += 1;
+
+        if self.deadline.take().is_some() {
+            info!(
+                connected = self.connected,
+                "Client connected, idle TTL countdown paused"
+            );
+        }
+    }
+
+    /// Starts the countdown if this was the last connected client.
+    pub(super) fn client_disconnected(&mut self) {
+        self.connected = self.connected.saturating_sub(1);
+
+        if self.connected == 0 {
+            self.start_countdown();
+        }
+    }
+
+    /// Resolves when the countdown expires, never resolves while any client is connected.
+    pub(super) async fn expired(&self) {
+        match self.deadline {
+            Some(deadline) => tokio::time::sleep_until(deadline).await,
+            None => std::future::pending().await,
+        }
+    }
+
+    fn start_countdown(&mut self) {
+        let now = Instant::now();
+        let deadline = self
+            .extensions
+            .until()
+            .map_or(now + self.ttl, |until| until.max(now + self.ttl));
+
+        info!(
+            ttl = ?self.ttl,
+            remaining = ?deadline.duration_since(now),
+            "No clients connected, idle TTL countdown started"
+        );
+        self.deadline = Some(deadline);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::time::{Duration, timeout};
+
+    use super::{IdleTtl, TtlExtensions};
+
+    const TTL: Duration = Duration::from_millis(200);
+
+    /// Simulates clients that disconnect and reconnect just before the TTL expires, and that stay
+    /// connected for longer than the TTL.
+    #[tokio::test]
+    async fn survives_reconnect_cycles() {
+        let mut idle_ttl = IdleTtl::new(TTL, Default::default());
+        idle_ttl.client_connected();
+
+        for _ in 0..3 {
+            timeout(TTL * 2, idle_ttl.expired()).await.unwrap_err();
+
+            idle_ttl.client_disconnected();
+            timeout(TTL / 2, idle_ttl.expired()).await.unwrap_err();
+            idle_ttl.client_connected();
+        }
+
+        idle_ttl.client_disconnected();
+        timeout(TTL * 2, idle_ttl.expired()).await.unwrap();
+    }
+
+    /// The countdown starts only when the last client disconnects.
+    #[tokio::test]
+    async fn counts_connected_clients() {
+        let mut idle_ttl = IdleTtl::new(TTL, Default::default());
+        idle_ttl.client_connected();
+        idle_ttl.client_connected();
+
+        idle_ttl.client_disconnected();
+        timeout(TTL * 2, idle_ttl.expired()).await.unwrap_err();
+
+        idle_ttl.client_disconnected();
+        timeout(TTL * 2, idle_ttl.expired()).await.unwrap();
+    }
+
+    /// An extension keeps the agent alive after the client disconnects, even with zero TTL.
+    #[tokio::test]
+    async fn extension_outlives_ttl() {
+        let extensions = TtlExtensions::default();
+        let mut idle_ttl = IdleTtl::new(Duration::ZERO, extensions.clone());
+        idle_ttl.client_connected();
+
+        extensions.extend(TTL);
+        extensions.extend(Duration::ZERO);
+        idle_ttl.client_disconnected();
+        timeout(TTL / 2, idle_ttl.expired()).await.unwrap_err();
+
+        idle_ttl.client_connected();
+        idle_ttl.client_disconnected();
+        timeout(TTL, idle_ttl.expired()).await.unwrap();
+    }
+
+    /// An extension that overflows the [`Instant`](tokio::time::Instant) is rejected, and doesn't
+    /// replace an earlier one.
+    #[tokio::test]
+    async fn overflowing_extension_rejected() {
+        let extensions = TtlExtensions::default();
+        extensions.extend(Duration::MAX);
+        assert!(extensions.until().is_none());
+
+        extensions.extend(TTL);
+        extensions.extend(Duration::MAX);
+        let mut idle_ttl = IdleTtl::new(Duration::ZERO, extensions);
+        idle_ttl.client_connected();
+        idle_ttl.client_disconnected();
+        timeout(TTL * 2, idle_ttl.expired()).await.unwrap();
+    }
+}
@@ -1,4 +1,7 @@
+use std::{sync::Arc, time::Duration};
+
 use mirrord_agent_env::envs;
+use mirrord_agent_iptables::RedirectExclusions;
 use tokio::sync::mpsc;
 use tokio_util::sync::CancellationToken;
 
@@ -7,20 +10,21 @@ use crate::{
     dns::{DnsCommand, DnsWorker},
     error::{AgentError, AgentResult},
     incoming::{
-        self, MirrorHandle, RedirectorTask, RedirectorTaskConfig, StealHandle,
-        tls::StealTlsHandlerStore,
+        self, EbpfRedirector, MirrorHandle, RedirectorTask, RedirectorTaskConfig,
+        SelectedRedirector, StealHandle, tls::StealTlsHandlerStore,
     },
     steal::{StealerCommand, TcpStealerTask},
     task::{BgTaskRuntime, status::IntoStatus},
     util::path_resolver::InTargetPathResolver,
 };
 
-/// Starts a [`RedirectorTask`] on the given `runtime`.
+/// Starts a [`RedirectorTask`] on the given `runtime`, using the given redirector implementation.
 ///
 /// Returns the [`StealHandle`] that can be used to steal incoming traffic.
 pub(super) async fn start_traffic_redirector(
     runtime: &BgTaskRuntime,
     target_pid: u64,
+    redirector: SelectedRedirector,
     with_mesh_exclusion: Option<u16>,
 ) -> AgentResult<(StealHandle, MirrorHandle)> {
     // IMPORTANT: this makes tokio tasks spawn on `runtime`.
@@ -29,29 +33,63 @@ pub(super) async fn start_traffic_redirector(
 
     let flush_connections = envs::STEALER_FLUSH_CONNECTIONS.from_env_or_default();
     let pod_ips = envs::POD_IPS.from_env_or_default();
+    let exclusions = RedirectExclusions::from_env();
+    let reconcile_interval = match envs::IPTABLES_RECONCILE_INTERVAL.from_env_or_default() {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    };
     let support_ipv6 = envs::IPV6_SUPPORT.from_env_or_default();
     let tls_steal_config = envs::STEAL_TLS_CONFIG.from_env_or_default();
     let tls_handler_store =
         StealTlsHandlerStore::new(tls_steal_config, InTargetPathResolver::new(target_pid));
 
     let redirector_task_config = RedirectorTaskConfig::from_env();
-    let (task, steal_handle, mirror_handle) = tokio::spawn(async move {
-        incoming::create_iptables_redirector(
-            flush_connections,
-            &pod_ips,
-            support_ipv6,
-            with_mesh_exclusion,
-        )
-        .await
-        .map(|redirector| {
-            RedirectorTask::new(redirector, tls_handler_store, redirector_task_config)
-        })
-    })
-    .await
-    .map_err(|error| AgentError::IPTablesSetupError(error.into()))?
-    .map_err(|error| AgentError::IPTablesSetupError(error.into()))?;
-
-    tokio::spawn(task.run());
+
+    let (steal_handle, mirror_handle) = match redirector {
+        SelectedRedirector::IpTables | SelectedRedirector::Nftables => {
+            let (task, steal_handle, mirror_handle) = tokio::spawn(async move {
+                incoming::create_iptables_redirector(
+                    flush_connections,
+                    &pod_ips,
+                    support_ipv6,
+                    with_mesh_exclusion,
+                    redirector == SelectedRedirector::Nftables,
+                    &exclusions,
+                    reconcile_interval,
+                )
+                .await
+                .map(|redirector| {
+                    RedirectorTask::new(redirector, tls_handler_store, redirector_task_config)
+                })
+            })
+            .await
+            .map_err(|error| AgentError::IPTablesSetupError(error.into()))?
+            .map_err(|error| AgentError::IPTablesSetupError(error.into()))?;
+
+            tokio::spawn(task.run());
+
+            (steal_handle, mirror_handle)
+        }
+
+        SelectedRedirector::Ebpf => {
+            // The program is attached to the network namespace of the current thread,
+            // so it must be created on `runtime`.
+            let (task, steal_handle, mirror_handle) = tokio::spawn(async move {
+                EbpfRedirector::create(support_ipv6).map(|redirector| {
+                    RedirectorTask::new(redirector, tls_handler_store, redirector_task_config)
+                })
+            })
+            .await
+            .map_err(|error| AgentError::BackgroundTaskFailed {
+                task: "EbpfRedirectorSetup",
+                error: Arc::new(error),
+            })??;
+
+            tokio::spawn(task.run());
+
+            (steal_handle, mirror_handle)
+        }
+    };
 
     Ok((steal_handle, mirror_handle))
 }
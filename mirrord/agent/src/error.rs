@@ -3,8 +3,11 @@ use std::{process::ExitStatus, sync::Arc};
 use thiserror::Error;
 
 use crate::{
-    client_connection::TlsSetupError, http::filter::FilterCreationError,
-    incoming::RedirectorTaskError, namespace::NamespaceError, runtime,
+    client_connection::TlsSetupError,
+    http::filter::FilterCreationError,
+    incoming::{EbpfRedirectorError, RedirectorTaskError},
+    namespace::NamespaceError,
+    runtime,
     util::error::AgentRuntimeError,
 };
 
@@ -60,6 +63,9 @@ pub(crate) enum AgentError {
     #[error("IP tables setup failed: {0}")]
     IPTablesSetupError(#[source] Box<dyn std::error::Error + Send + Sync + 'static>),
 
+    #[error("eBPF redirector setup failed: {0}")]
+    EbpfSetupError(#[from] EbpfRedirectorError),
+
     #[error("IP tables dirty")]
     IPTablesDirty,
 
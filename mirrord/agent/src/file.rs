@@ -1,14 +1,19 @@
 use std::{
     self,
     borrow::Cow,
-    collections::{HashMap, VecDeque, hash_map::Entry},
-    fs::{File, OpenOptions, ReadDir, read_link},
-    io::{self, SeekFrom, prelude::*},
-    iter::{Enumerate, Peekable},
+    collections::{HashMap, HashSet, VecDeque, hash_map::Entry},
+    ffi::CString,
+    fs::{DirEntry, File, OpenOptions, ReadDir, read_link},
+    io::{self, IoSlice, IoSliceMut, SeekFrom, prelude::*},
+    iter::Peekable,
     ops::RangeInclusive,
     os::{
         fd::{AsRawFd, RawFd},
-        unix::{ffi::OsStrExt, fs::MetadataExt, prelude::FileExt},
+        unix::{
+            ffi::{OsStrExt, OsStringExt},
+            fs::{FileTypeExt, MetadataExt, OpenOptionsExt},
+            prelude::FileExt,
+        },
     },
     path::{Path, PathBuf, StripPrefixError},
     ptr,
@@ -16,7 +21,7 @@ use std::{
 
 use faccess::{AccessMode, PathExt as _};
 use libc::DT_DIR;
-use mirrord_protocol::{FileRequest, FileResponse, RemoteResult, ResponseError, file::*};
+use mirrord_protocol::{FileRequest, FileResponse, Payload, RemoteResult, ResponseError, file::*};
 use nix::unistd::UnlinkatFlags;
 use tracing::{Level, error, trace};
 
@@ -47,10 +52,88 @@ impl PathExt for Path {
     }
 }
 
+/// A file or directory opened by the layer, identified by the fd we returned in
+/// [`OpenFileResponse`].
+///
+/// Operations on an open fd always go through the handle we got when opening it, and never
+/// re-resolve the path it was opened with. This keeps POSIX semantics when the path is unlinked
+/// or renamed while the fd is open: the data remains accessible until [`CloseFileRequest`].
 #[derive(Debug)]
 pub enum RemoteFile {
     File(File),
-    Directory(PathBuf),
+    Directory {
+        /// Used to resolve paths relative to this directory (`openat`, `fstatat`, ...), and to
+        /// list its entries.
+        path: PathBuf,
+        file: File,
+    },
+}
+
+/// Converts the [`OpenOptionsInternal`] into [`OpenOptions`], adding the [`OpenFlagsInternal`] and
+/// `O_NONBLOCK`.
+fn with_open_flags(
+    open_options: OpenOptionsInternal,
+    flags: OpenFlagsInternal,
+    nonblock: bool,
+) -> OpenOptions {
+    let mut custom_flags = 0;
+    if flags.directory {
+        custom_flags |= libc::O_DIRECTORY;
+    }
+    if flags.nofollow {
+        custom_flags |= libc::O_NOFOLLOW;
+    }
+    if nonblock {
+        custom_flags |= libc::O_NONBLOCK;
+    }
+
+    if flags.path {
+        // `O_PATH` ignores the other options, but `OpenOptions` requires an access mode.
+        let mut options = OpenOptions::new();
+        options.read(true).custom_flags(custom_flags | libc::O_PATH);
+        return options;
+    }
+
+    let mut options = OpenOptions::from(open_options);
+    options.custom_flags(custom_flags);
+    options
+}
+
+/// Opens the file at `path`, with `O_NONBLOCK` only when `nonblock` is set.
+///
+/// A blocking open of a FIFO waits for the other end of the pipe, which would stall every other
+/// request of the client, so FIFOs are always opened with `O_NONBLOCK`, and it's cleared right
+/// after when the client did not ask for it. Opening a FIFO for writing with no reader then fails
+/// with `ENXIO`.
+///
+/// Returns whether the file is a FIFO without `O_NONBLOCK`, see [`FileManager::poll_fifo`].
+fn open_file(
+    path: &Path,
+    open_options: OpenOptionsInternal,
+    flags: OpenFlagsInternal,
+    nonblock: bool,
+) -> io::Result<(File, bool)> {
+    let metadata = if flags.nofollow {
+        std::fs::symlink_metadata(path)
+    } else {
+        std::fs::metadata(path)
+    };
+    let is_fifo = metadata.is_ok_and(|metadata| metadata.file_type().is_fifo());
+
+    let file = with_open_flags(open_options, flags, nonblock || is_fifo).open(path)?;
+
+    let blocking_fifo = is_fifo && !nonblock;
+    if blocking_fifo {
+        let fd = file.as_raw_fd();
+        let status_flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if status_flags == -1
+            || unsafe { libc::fcntl(fd, libc::F_SETFL, status_flags & !libc::O_NONBLOCK) } == -1
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok((file, blocking_fifo))
 }
 
 fn log_err(entry_res: io::Result<DirEntryInternal>) -> io::Result<DirEntryInternal> {
@@ -94,14 +177,126 @@ impl Iterator for GetDEnts64Stream {
     }
 }
 
+/// Stream of entries of a directory opened with [`FileManager::fdopen_dir`].
+///
+/// Tracks its position, so that it can be moved to a [`DirCursor`].
+#[derive(Debug)]
+pub(crate) struct DirStream {
+    /// Used to restart the stream when moving backwards.
+    path: PathBuf,
+    inner: ReadDir,
+    /// Index of the next entry.
+    position: usize,
+}
+
+impl DirStream {
+    fn new(path: PathBuf) -> io::Result<Self> {
+        let inner = path.read_dir()?;
+        Ok(Self {
+            path,
+            inner,
+            position: 0,
+        })
+    }
+
+    /// Returns the [`DirCursor`] of the next entry.
+    fn cursor(&self) -> DirCursor {
+        DirCursor(self.position as u64)
+    }
+
+    /// Moves this stream to the given [`DirCursor`].
+    ///
+    /// The directory is read from the start again when moving backwards, so the entries before
+    /// the cursor are the same only as long as the directory does not change.
+    fn seek(&mut self, cursor: DirCursor) -> io::Result<()> {
+        let target = usize::try_from(cursor.0).unwrap_or(usize::MAX);
+
+        if target < self.position {
+            self.inner = self.path.read_dir()?;
+            self.position = 0;
+        }
+
+        while self.position < target && self.next().is_some() {}
+
+        Ok(())
+    }
+}
+
+impl Iterator for DirStream {
+    type Item = (usize, io::Result<DirEntry>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.inner.next()?;
+        let position = self.position;
+        self.position += 1;
+        Some((position, entry))
+    }
+}
+
+/// [`XattrTarget`] resolved by [`FileManager::resolve_xattr_target`].
+enum ResolvedXattrTarget {
+    Path {
+        path: CString,
+        follow_symlinks: bool,
+    },
+    Fd(RawFd),
+}
+
+/// Converts an extended attribute name into a [`CString`].
+fn xattr_name(name: Vec<u8>) -> io::Result<CString> {
+    CString::new(name).map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))
+}
+
+/// Maps errors from the `*xattr` syscalls, so that the layer can translate `ENODATA` and
+/// `ENOTSUP` into its platform's errno.
+fn xattr_error(error: io::Error) -> ResponseError {
+    match error.raw_os_error() {
+        Some(libc::ENODATA) => ResponseError::XattrNotFound,
+        Some(libc::ENOTSUP) => ResponseError::XattrNotSupported,
+        _ => error.into(),
+    }
+}
+
+/// Reads a value of unknown size with one of the `getxattr`/`listxattr` syscalls.
+///
+/// First queries the size with an empty buffer. If the value grows between the two calls
+/// (`ERANGE`), the whole operation is retried.
+fn read_xattr_buffer<F>(mut read: F) -> io::Result<Vec<u8>>
+where
+    F: FnMut(*mut u8, usize) -> isize,
+{
+    loop {
+        let size = read(ptr::null_mut(), 0);
+        if size < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mut buffer = vec![0; size as usize];
+        let read_size = read(buffer.as_mut_ptr(), buffer.len());
+        if read_size < 0 {
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() == Some(libc::ERANGE) {
+                continue;
+            }
+
+            return Err(error);
+        }
+
+        buffer.truncate(read_size as usize);
+        break Ok(buffer);
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct FileManager {
     /// [`None`] when targetless.
     path_resolver: Option<InTargetPathResolver>,
     open_files: HashMap<u64, RemoteFile>,
-    dir_streams: HashMap<u64, Enumerate<ReadDir>>,
+    dir_streams: HashMap<u64, DirStream>,
     getdents_streams: HashMap<u64, Peekable<GetDEnts64Stream>>,
     fds_iter: RangeInclusive<u64>,
+    /// Open FIFOs that the client did not open with `O_NONBLOCK`, see [`open_file`].
+    blocking_fifos: HashSet<u64>,
 }
 
 impl Drop for FileManager {
@@ -126,7 +321,32 @@ impl FileManager {
                     .strip_prefix_root()
                     .inspect_err(|fail| error!("file_worker -> {:#?}", fail))?;
 
-                let open_result = self.open(path_stripped, open_options);
+                let open_result = self.open(path_stripped, open_options, Default::default(), false);
+                Some(FileResponse::Open(open_result))
+            }
+            FileRequest::OpenV2(OpenFileRequestV2 {
+                path,
+                open_options,
+                flags,
+            }) => {
+                let path_stripped = path
+                    .strip_prefix_root()
+                    .inspect_err(|fail| error!("file_worker -> {:#?}", fail))?;
+
+                let open_result = self.open(path_stripped, open_options, flags, false);
+                Some(FileResponse::Open(open_result))
+            }
+            FileRequest::OpenV3(OpenFileRequestV3 {
+                path,
+                open_options,
+                flags,
+                nonblock,
+            }) => {
+                let path_stripped = path
+                    .strip_prefix_root()
+                    .inspect_err(|fail| error!("file_worker -> {:#?}", fail))?;
+
+                let open_result = self.open(path_stripped, open_options, flags, nonblock);
                 Some(FileResponse::Open(open_result))
             }
             FileRequest::OpenRelative(OpenRelativeFileRequest {
@@ -134,7 +354,28 @@ impl FileManager {
                 path,
                 open_options,
             }) => {
-                let open_result = self.open_relative(relative_fd, path, open_options);
+                let open_result =
+                    self.open_relative(relative_fd, path, open_options, Default::default(), false);
+                Some(FileResponse::Open(open_result))
+            }
+            FileRequest::OpenRelativeV2(OpenRelativeFileRequestV2 {
+                relative_fd,
+                path,
+                open_options,
+                flags,
+            }) => {
+                let open_result = self.open_relative(relative_fd, path, open_options, flags, false);
+                Some(FileResponse::Open(open_result))
+            }
+            FileRequest::OpenRelativeV3(OpenRelativeFileRequestV3 {
+                relative_fd,
+                path,
+                open_options,
+                flags,
+                nonblock,
+            }) => {
+                let open_result =
+                    self.open_relative(relative_fd, path, open_options, flags, nonblock);
                 Some(FileResponse::Open(open_result))
             }
             FileRequest::Read(ReadFileRequest {
@@ -182,6 +423,18 @@ impl FileManager {
                 let access_result = self.access(pathname, mode);
                 Some(FileResponse::Access(access_result))
             }
+            FileRequest::AccessV2(AccessFileRequestV2 {
+                pathname,
+                mode,
+                flags,
+            }) => {
+                let pathname = pathname
+                    .strip_prefix_root()
+                    .inspect_err(|fail| error!("file_worker -> {:#?}", fail))?;
+
+                let access_result = self.access_v2(pathname, mode, flags);
+                Some(FileResponse::Access(access_result))
+            }
             FileRequest::Xstat(XstatRequest {
                 path,
                 fd,
@@ -226,6 +479,14 @@ impl FileManager {
                 let read_dir_result = self.read_dir_batch(remote_fd, amount);
                 Some(FileResponse::ReadDirBatch(read_dir_result))
             }
+            FileRequest::ReadDirBatchFrom(ReadDirBatchFromRequest {
+                remote_fd,
+                amount,
+                cursor,
+            }) => {
+                let read_dir_result = self.read_dir_batch_from(remote_fd, amount, cursor);
+                Some(FileResponse::ReadDirBatchFrom(read_dir_result))
+            }
             FileRequest::CloseDir(CloseDirRequest { remote_fd }) => self.close_dir(remote_fd),
             FileRequest::GetDEnts64(GetDEnts64Request {
                 remote_fd,
@@ -261,9 +522,61 @@ impl FileManager {
             FileRequest::Fchown(FchownRequest { fd, owner, group }) => {
                 Some(FileResponse::Fchown(self.fchown(fd, owner, group)))
             }
+            FileRequest::Utimens(UtimensRequest {
+                path,
+                times,
+                follow_symlink,
+            }) => Some(FileResponse::Futimens(self.utimens(
+                path,
+                times,
+                follow_symlink,
+            ))),
+            FileRequest::Chown(ChownRequest {
+                path,
+                owner,
+                group,
+                follow_symlink,
+            }) => Some(FileResponse::Fchown(self.chown(
+                path,
+                owner,
+                group,
+                follow_symlink,
+            ))),
             FileRequest::Fchmod(FchmodRequest { fd, mode }) => {
                 Some(FileResponse::Fchmod(self.fchmod(fd, mode)))
             }
+            FileRequest::GetXattr(GetXattrRequest { target, name }) => {
+                Some(FileResponse::GetXattr(self.getxattr(target, name)))
+            }
+            FileRequest::SetXattr(SetXattrRequest {
+                target,
+                name,
+                value,
+                flags,
+            }) => Some(FileResponse::SetXattr(
+                self.setxattr(target, name, &value, flags),
+            )),
+            FileRequest::ListXattr(ListXattrRequest { target }) => {
+                Some(FileResponse::ListXattr(self.listxattr(target)))
+            }
+            FileRequest::Flock(FlockRequest { fd, operation }) => {
+                Some(FileResponse::Flock(self.flock(fd, operation)))
+            }
+            FileRequest::Syncfs(SyncfsRequest { fd }) => {
+                Some(FileResponse::Syncfs(self.syncfs(fd)))
+            }
+            FileRequest::Readv(ReadvFileRequest { fd, iov_lengths }) => {
+                Some(FileResponse::Readv(self.readv(fd, iov_lengths)))
+            }
+            FileRequest::Writev(WritevFileRequest { fd, buffers }) => {
+                Some(FileResponse::Writev(self.writev(fd, buffers)))
+            }
+            FileRequest::CopyFileRange(request) => {
+                Some(FileResponse::CopyFileRange(self.copy_file_range(request)))
+            }
+            FileRequest::FcntlLock(request) => {
+                Some(FileResponse::FcntlLock(self.fcntl_lock(request)))
+            }
         })
     }
 
@@ -277,6 +590,7 @@ impl FileManager {
             dir_streams: Default::default(),
             getdents_streams: Default::default(),
             fds_iter: (0..=u64::MAX),
+            blocking_fifos: Default::default(),
         }
     }
 
@@ -288,14 +602,34 @@ impl FileManager {
         }
     }
 
+    /// Resolves the `path` to open, without following a symlink in its last component when
+    /// `nofollow` is set, so that the open itself can fail with `ELOOP`.
+    fn resolve_open_path<'a>(&self, path: &'a Path, nofollow: bool) -> io::Result<Cow<'a, Path>> {
+        match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) if nofollow => {
+                Ok(Cow::Owned(self.resolve_path(parent)?.join(name)))
+            }
+            _ => self.resolve_path(path),
+        }
+    }
+
+    /// Same as [`Self::resolve_open_path`], but returns a [`CString`] for the `*at` syscalls.
+    fn resolve_path_cstring(&self, path: &Path, nofollow: bool) -> io::Result<CString> {
+        let path = self.resolve_open_path(path, nofollow)?;
+        CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))
+    }
+
     #[tracing::instrument(level = Level::TRACE, skip(self), ret, err(level = Level::DEBUG))]
     fn open(
         &mut self,
         path: PathBuf,
         open_options: OpenOptionsInternal,
+        flags: OpenFlagsInternal,
+        nonblock: bool,
     ) -> RemoteResult<OpenFileResponse> {
-        let path = self.resolve_path(&path)?;
-        let file = OpenOptions::from(open_options).open(&path)?;
+        let path = self.resolve_open_path(&path, flags.nofollow)?;
+        let (file, blocking_fifo) = open_file(&path, open_options, flags, nonblock)?;
 
         let fd = self
             .fds_iter
@@ -305,7 +639,10 @@ impl FileManager {
         let metadata = file.metadata()?;
 
         let remote_file = if metadata.is_dir() {
-            RemoteFile::Directory(path.into_owned())
+            RemoteFile::Directory {
+                path: path.into_owned(),
+                file,
+            }
         } else {
             RemoteFile::File(file)
         };
@@ -313,6 +650,9 @@ impl FileManager {
         if self.open_files.insert(fd, remote_file).is_none() {
             OPEN_FD_COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
         }
+        if blocking_fifo {
+            self.blocking_fifos.insert(fd);
+        }
 
         Ok(OpenFileResponse { fd })
     }
@@ -323,16 +663,21 @@ impl FileManager {
         relative_fd: u64,
         path: PathBuf,
         open_options: OpenOptionsInternal,
+        flags: OpenFlagsInternal,
+        nonblock: bool,
     ) -> RemoteResult<OpenFileResponse> {
         let relative_dir = self
             .open_files
             .get(&relative_fd)
             .ok_or(ResponseError::NotFound(relative_fd))?;
 
-        if let RemoteFile::Directory(relative_dir) = relative_dir {
+        if let RemoteFile::Directory {
+            path: relative_dir, ..
+        } = relative_dir
+        {
             let path = relative_dir.join(&path);
 
-            let file = OpenOptions::from(open_options).open(&path)?;
+            let (file, blocking_fifo) = open_file(&path, open_options, flags, nonblock)?;
 
             let fd = self.fds_iter.next().ok_or_else(|| {
                 ResponseError::IdsExhausted("FileManager::open_relative".to_string())
@@ -341,7 +686,7 @@ impl FileManager {
             let metadata = file.metadata()?;
 
             let remote_file = if metadata.is_dir() {
-                RemoteFile::Directory(path)
+                RemoteFile::Directory { path, file }
             } else {
                 RemoteFile::File(file)
             };
@@ -349,6 +694,9 @@ impl FileManager {
             if self.open_files.insert(fd, remote_file).is_none() {
                 OPEN_FD_COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
             }
+            if blocking_fifo {
+                self.blocking_fifos.insert(fd);
+            }
 
             Ok(OpenFileResponse { fd })
         } else {
@@ -356,14 +704,45 @@ impl FileManager {
         }
     }
 
+    /// Checks whether the blocking FIFO `fd` is ready for the given `poll` events, failing with
+    /// `EAGAIN` when it's not.
+    ///
+    /// Never waits, as the [`FileManager`] runs on the client's task. The layer sends the request
+    /// again after a while when it fails with `EAGAIN`.
+    ///
+    /// Returns the most bytes that can then be written without waiting for the reader, which is
+    /// not limited for other files.
+    fn poll_fifo(&self, fd: u64, events: libc::c_short) -> RemoteResult<usize> {
+        let Some(RemoteFile::File(file)) = self
+            .open_files
+            .get(&fd)
+            .filter(|_| self.blocking_fifos.contains(&fd))
+        else {
+            return Ok(usize::MAX);
+        };
+
+        let mut pollfd = libc::pollfd {
+            fd: file.as_raw_fd(),
+            events,
+            revents: 0,
+        };
+        match unsafe { libc::poll(&mut pollfd, 1, 0) } {
+            -1 => Err(io::Error::last_os_error().into()),
+            0 => Err(io::Error::from_raw_os_error(libc::EAGAIN).into()),
+            _ => Ok(libc::PIPE_BUF),
+        }
+    }
+
     #[tracing::instrument(level = "trace", skip(self))]
     pub(crate) fn read(&mut self, fd: u64, buffer_size: u64) -> RemoteResult<ReadFileResponse> {
+        self.poll_fifo(fd, libc::POLLIN)?;
+
         self.open_files
             .get_mut(&fd)
             .ok_or(ResponseError::NotFound(fd))
             .and_then(|remote_file| {
                 if let RemoteFile::File(file) = remote_file {
-                    let mut buffer = vec![0; buffer_size as usize];
+                    let mut buffer = vec![0; buffer_size.min(MAX_READ_SIZE) as usize];
                     let read_amount = file.read(&mut buffer)?;
 
                     // Truncate the buffer based on the actual number of bytes read
@@ -394,7 +773,7 @@ impl FileManager {
             .ok_or(ResponseError::NotFound(fd))
             .and_then(|remote_file| {
                 if let RemoteFile::File(file) = remote_file {
-                    let mut buffer = vec![0; buffer_size as usize];
+                    let mut buffer = vec![0; buffer_size.min(MAX_READ_SIZE) as usize];
 
                     let read_amount = file.read_at(&mut buffer, start_from)?;
 
@@ -416,6 +795,137 @@ impl FileManager {
             })
     }
 
+    /// Reads into buffers of the given lengths with a single `readv`.
+    ///
+    /// The buffers are cut short once they add up to [`MAX_READ_SIZE`].
+    #[tracing::instrument(level = Level::TRACE, skip(self))]
+    pub(crate) fn readv(
+        &mut self,
+        fd: u64,
+        iov_lengths: Vec<u64>,
+    ) -> RemoteResult<ReadvFileResponse> {
+        self.poll_fifo(fd, libc::POLLIN)?;
+
+        let RemoteFile::File(file) = self
+            .open_files
+            .get_mut(&fd)
+            .ok_or(ResponseError::NotFound(fd))?
+        else {
+            return Err(ResponseError::NotFile(fd));
+        };
+
+        let mut capacity = MAX_READ_SIZE;
+        let iov_lengths = iov_lengths
+            .into_iter()
+            .map(|length| {
+                let length = length.min(capacity);
+                capacity -= length;
+                length
+            })
+            .collect::<Vec<_>>();
+
+        let mut buffer = vec![0; iov_lengths.iter().sum::<u64>() as usize];
+        let read_amount = {
+            let mut rest = buffer.as_mut_slice();
+            let mut iovs = iov_lengths
+                .iter()
+                .map(|length| {
+                    let (iov, tail) = std::mem::take(&mut rest).split_at_mut(*length as usize);
+                    rest = tail;
+                    IoSliceMut::new(iov)
+                })
+                .collect::<Vec<_>>();
+
+            file.read_vectored(&mut iovs)?
+        };
+        buffer.truncate(read_amount);
+
+        Ok(ReadvFileResponse::new(buffer.into(), &iov_lengths))
+    }
+
+    /// Writes the buffers with a single `writev`.
+    #[tracing::instrument(level = Level::TRACE, skip(self, buffers))]
+    pub(crate) fn writev(
+        &mut self,
+        fd: u64,
+        buffers: Vec<Payload>,
+    ) -> RemoteResult<WriteFileResponse> {
+        let mut capacity = self.poll_fifo(fd, libc::POLLOUT)?;
+
+        let RemoteFile::File(file) = self
+            .open_files
+            .get_mut(&fd)
+            .ok_or(ResponseError::NotFound(fd))?
+        else {
+            return Err(ResponseError::NotFile(fd));
+        };
+
+        let iovs = buffers
+            .iter()
+            .map(|buffer| {
+                let length = buffer.len().min(capacity);
+                capacity -= length;
+                IoSlice::new(&buffer[..length])
+            })
+            .collect::<Vec<_>>();
+        let written_amount = file.write_vectored(&iovs)?;
+
+        Ok(WriteFileResponse {
+            written_amount: written_amount as u64,
+        })
+    }
+
+    /// Copies between two open files with [`libc::copy_file_range`], so that the data doesn't
+    /// have to go through the layer.
+    ///
+    /// Like reads, a single copy is cut short at [`MAX_READ_SIZE`].
+    #[tracing::instrument(level = Level::TRACE, skip(self), ret, err(level = Level::DEBUG))]
+    pub(crate) fn copy_file_range(
+        &mut self,
+        CopyFileRangeRequest {
+            fd_in,
+            off_in,
+            fd_out,
+            off_out,
+            len,
+        }: CopyFileRangeRequest,
+    ) -> RemoteResult<CopyFileRangeResponse> {
+        let raw_fd = |fd| match self.open_files.get(&fd) {
+            Some(RemoteFile::File(file)) => Ok(file.as_raw_fd()),
+            Some(RemoteFile::Directory { .. }) => Err(ResponseError::NotFile(fd)),
+            None => Err(ResponseError::NotFound(fd)),
+        };
+        let raw_in = raw_fd(fd_in)?;
+        let raw_out = raw_fd(fd_out)?;
+
+        let offset_ptr = |offset: &mut Option<libc::loff_t>| {
+            offset
+                .as_mut()
+                .map_or(ptr::null_mut(), |offset| offset as *mut libc::loff_t)
+        };
+        let mut off_in = off_in.map(|offset| offset as libc::loff_t);
+        let mut off_out = off_out.map(|offset| offset as libc::loff_t);
+        let len = len.min(MAX_READ_SIZE) as usize;
+
+        let copied = unsafe {
+            libc::copy_file_range(
+                raw_in,
+                offset_ptr(&mut off_in),
+                raw_out,
+                offset_ptr(&mut off_out),
+                len,
+                0,
+            )
+        };
+
+        match copied {
+            -1 => Err(ResponseError::from(io::Error::last_os_error())),
+            copied => Ok(CopyFileRangeResponse {
+                copied: copied as u64,
+            }),
+        }
+    }
+
     /// Handles our `readlink_detour` with [`std::fs::read_link`].
     #[tracing::instrument(level = Level::TRACE, skip_all)]
     pub(crate) fn read_link(&mut self, path: PathBuf) -> RemoteResult<ReadLinkFileResponse> {
@@ -487,7 +997,10 @@ impl FileManager {
             .get(&dirfd)
             .ok_or(ResponseError::NotFound(dirfd))?;
 
-        if let RemoteFile::Directory(relative_dir) = relative_dir {
+        if let RemoteFile::Directory {
+            path: relative_dir, ..
+        } = relative_dir
+        {
             let path = relative_dir.join(path);
 
             match nix::unistd::mkdir(&path, nix::sys::stat::Mode::from_bits_truncate(mode)) {
@@ -530,7 +1043,10 @@ impl FileManager {
                     .get(&dirfd)
                     .ok_or(ResponseError::NotFound(dirfd))?;
 
-                if let RemoteFile::Directory(relative_dir) = relative_dir {
+                if let RemoteFile::Directory {
+                    path: relative_dir, ..
+                } = relative_dir
+                {
                     Cow::Owned(relative_dir.join(path))
                 } else {
                     return Err(ResponseError::NotDirectory(dirfd));
@@ -626,6 +1142,67 @@ impl FileManager {
         }
     }
 
+    /// Handles `utimensat` and `lutimes`, with `AT_SYMLINK_NOFOLLOW` unless `follow_symlink`.
+    #[tracing::instrument(level = Level::TRACE, skip(self), err(level = Level::DEBUG))]
+    pub(crate) fn utimens(
+        &mut self,
+        path: PathBuf,
+        times: Option<[Timespec; 2]>,
+        follow_symlink: bool,
+    ) -> RemoteResult<()> {
+        let path = self.resolve_path_cstring(&path, !follow_symlink)?;
+        let times = times.map(|times| {
+            times.map(|time| libc::timespec {
+                tv_sec: time.tv_sec,
+                tv_nsec: time.tv_nsec,
+            })
+        });
+        let flags = if follow_symlink {
+            0
+        } else {
+            libc::AT_SYMLINK_NOFOLLOW
+        };
+
+        let result = unsafe {
+            libc::utimensat(
+                libc::AT_FDCWD,
+                path.as_ptr(),
+                times
+                    .as_ref()
+                    .map(|times| times.as_ptr())
+                    .unwrap_or(ptr::null()),
+                flags,
+            )
+        };
+        match result {
+            -1 => Err(ResponseError::from(io::Error::last_os_error())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Handles `fchownat` and `lchown`, with `AT_SYMLINK_NOFOLLOW` unless `follow_symlink`.
+    #[tracing::instrument(level = Level::TRACE, skip(self), err(level = Level::DEBUG))]
+    pub(crate) fn chown(
+        &mut self,
+        path: PathBuf,
+        owner: u32,
+        group: u32,
+        follow_symlink: bool,
+    ) -> RemoteResult<()> {
+        let path = self.resolve_path_cstring(&path, !follow_symlink)?;
+        let flags = if follow_symlink {
+            0
+        } else {
+            libc::AT_SYMLINK_NOFOLLOW
+        };
+
+        let result = unsafe { libc::fchownat(libc::AT_FDCWD, path.as_ptr(), owner, group, flags) };
+        match result {
+            -1 => Err(ResponseError::from(io::Error::last_os_error())),
+            _ => Ok(()),
+        }
+    }
+
     pub(crate) fn fchmod(&mut self, fd: u64, mode: u32) -> RemoteResult<()> {
         let file = self
             .open_files
@@ -644,6 +1221,237 @@ impl FileManager {
         }
     }
 
+    /// Applies the `flock` operation on the open file, so that the lock conflicts with the locks
+    /// of the processes running in the target, and of the other clients (which have their own
+    /// open files).
+    ///
+    /// Never blocks, returns [`ResponseError::LockWouldBlock`] if the lock is held by someone
+    /// else. The kernel releases the lock when we close the file, which also happens when the
+    /// client disconnects.
+    #[tracing::instrument(level = Level::TRACE, skip(self), ret)]
+    pub(crate) fn flock(&mut self, fd: u64, operation: FlockOperation) -> RemoteResult<()> {
+        let file = match self.open_files.get(&fd) {
+            Some(RemoteFile::File(file)) | Some(RemoteFile::Directory { file, .. }) => file,
+            None => return Err(ResponseError::NotFound(fd)),
+        };
+
+        let operation = match operation {
+            FlockOperation::Shared => libc::LOCK_SH,
+            FlockOperation::Exclusive => libc::LOCK_EX,
+            FlockOperation::Unlock => libc::LOCK_UN,
+        };
+
+        if unsafe { libc::flock(file.as_raw_fd(), operation | libc::LOCK_NB) } == -1 {
+            let error = io::Error::last_os_error();
+            return match error.raw_os_error() {
+                Some(libc::EWOULDBLOCK) => Err(ResponseError::LockWouldBlock),
+                _ => Err(error.into()),
+            };
+        }
+
+        Ok(())
+    }
+
+    /// Applies the `fcntl` record lock command on the open file, as an open file description lock
+    /// (`F_OFD_SETLK`, `F_OFD_GETLK`).
+    ///
+    /// Like with [`FileManager::flock`], the kernel releases the lock when we close the file,
+    /// which also happens when the client disconnects.
+    #[tracing::instrument(level = Level::TRACE, skip(self), ret)]
+    pub(crate) fn fcntl_lock(
+        &mut self,
+        FcntlLockRequest { fd, command, lock }: FcntlLockRequest,
+    ) -> RemoteResult<FcntlLockResponse> {
+        let file = match self.open_files.get(&fd) {
+            Some(RemoteFile::File(file)) | Some(RemoteFile::Directory { file, .. }) => file,
+            None => return Err(ResponseError::NotFound(fd)),
+        };
+
+        // `l_pid` must be 0 for open file description locks.
+        let mut flock: libc::flock = unsafe { std::mem::zeroed() };
+        flock.l_type = match lock.lock_type {
+            FcntlLockType::Read => libc::F_RDLCK,
+            FcntlLockType::Write => libc::F_WRLCK,
+            FcntlLockType::Unlock => libc::F_UNLCK,
+        } as libc::c_short;
+        flock.l_whence = match lock.whence {
+            FcntlLockWhence::Start => libc::SEEK_SET,
+            FcntlLockWhence::Current => libc::SEEK_CUR,
+            FcntlLockWhence::End => libc::SEEK_END,
+        } as libc::c_short;
+        flock.l_start = lock.start;
+        flock.l_len = lock.len;
+
+        let cmd = match command {
+            FcntlLockCommand::Set => libc::F_OFD_SETLK,
+            FcntlLockCommand::Get => libc::F_OFD_GETLK,
+        };
+
+        if unsafe { libc::fcntl(file.as_raw_fd(), cmd, &mut flock) } == -1 {
+            let error = io::Error::last_os_error();
+            return match error.raw_os_error() {
+                Some(libc::EAGAIN | libc::EACCES) => Err(ResponseError::LockWouldBlock),
+                _ => Err(error.into()),
+            };
+        }
+
+        let conflicting = match (command, flock.l_type as libc::c_int) {
+            (FcntlLockCommand::Set, _) | (FcntlLockCommand::Get, libc::F_UNLCK) => None,
+            (FcntlLockCommand::Get, lock_type) => Some(FcntlLock {
+                lock_type: if lock_type == libc::F_RDLCK {
+                    FcntlLockType::Read
+                } else {
+                    FcntlLockType::Write
+                },
+                whence: FcntlLockWhence::Start,
+                start: flock.l_start,
+                len: flock.l_len,
+                pid: flock.l_pid,
+            }),
+        };
+
+        Ok(FcntlLockResponse { conflicting })
+    }
+
+    /// Flushes the filesystem that contains the open file with `syncfs`, or all filesystems with
+    /// `sync` when `fd` is [`None`].
+    #[tracing::instrument(level = Level::TRACE, skip(self), ret)]
+    pub(crate) fn syncfs(&mut self, fd: Option<u64>) -> RemoteResult<()> {
+        let Some(fd) = fd else {
+            unsafe { libc::sync() };
+            return Ok(());
+        };
+
+        let file = match self.open_files.get(&fd) {
+            Some(RemoteFile::File(file)) | Some(RemoteFile::Directory { file, .. }) => file,
+            None => return Err(ResponseError::NotFound(fd)),
+        };
+
+        match unsafe { libc::syncfs(file.as_raw_fd()) } {
+            -1 => Err(ResponseError::from(io::Error::last_os_error())),
+            _ => Ok(()),
+        }
+    }
+
+    /// Resolves the [`XattrTarget`] into a file that the `*xattr` syscalls can operate on.
+    fn resolve_xattr_target(&self, target: XattrTarget) -> RemoteResult<ResolvedXattrTarget> {
+        let (path, follow_symlinks) = match target {
+            XattrTarget::Path(path) => (self.resolve_path(&path)?.into_owned(), true),
+            XattrTarget::LinkPath(path) => {
+                (self.resolve_open_path(&path, true)?.into_owned(), false)
+            }
+            XattrTarget::Fd(fd) => match self
+                .open_files
+                .get(&fd)
+                .ok_or(ResponseError::NotFound(fd))?
+            {
+                RemoteFile::File(file) | RemoteFile::Directory { file, .. } => {
+                    return Ok(ResolvedXattrTarget::Fd(file.as_raw_fd()));
+                }
+            },
+        };
+
+        Ok(ResolvedXattrTarget::Path {
+            path: CString::new(path.into_os_string().into_vec())
+                .map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?,
+            follow_symlinks,
+        })
+    }
+
+    #[tracing::instrument(level = Level::TRACE, skip(self), err(level = Level::DEBUG))]
+    pub(crate) fn getxattr(
+        &mut self,
+        target: XattrTarget,
+        name: Vec<u8>,
+    ) -> RemoteResult<GetXattrResponse> {
+        let target = self.resolve_xattr_target(target)?;
+        let name = xattr_name(name)?;
+
+        let value = read_xattr_buffer(|buffer, size| unsafe {
+            match &target {
+                ResolvedXattrTarget::Path {
+                    path,
+                    follow_symlinks: true,
+                } => libc::getxattr(path.as_ptr(), name.as_ptr(), buffer.cast(), size),
+                ResolvedXattrTarget::Path {
+                    path,
+                    follow_symlinks: false,
+                } => libc::lgetxattr(path.as_ptr(), name.as_ptr(), buffer.cast(), size),
+                ResolvedXattrTarget::Fd(fd) => {
+                    libc::fgetxattr(*fd, name.as_ptr(), buffer.cast(), size)
+                }
+            }
+        })
+        .map_err(xattr_error)?;
+
+        Ok(GetXattrResponse {
+            value: value.into(),
+        })
+    }
+
+    #[tracing::instrument(level = Level::TRACE, skip(self, value), err(level = Level::DEBUG))]
+    pub(crate) fn setxattr(
+        &mut self,
+        target: XattrTarget,
+        name: Vec<u8>,
+        value: &[u8],
+        flags: i32,
+    ) -> RemoteResult<()> {
+        let target = self.resolve_xattr_target(target)?;
+        let name = xattr_name(name)?;
+        let value_ptr = value.as_ptr().cast();
+
+        let result = unsafe {
+            match &target {
+                ResolvedXattrTarget::Path {
+                    path,
+                    follow_symlinks: true,
+                } => libc::setxattr(path.as_ptr(), name.as_ptr(), value_ptr, value.len(), flags),
+                ResolvedXattrTarget::Path {
+                    path,
+                    follow_symlinks: false,
+                } => libc::lsetxattr(path.as_ptr(), name.as_ptr(), value_ptr, value.len(), flags),
+                ResolvedXattrTarget::Fd(fd) => {
+                    libc::fsetxattr(*fd, name.as_ptr(), value_ptr, value.len(), flags)
+                }
+            }
+        };
+
+        match result {
+            -1 => Err(xattr_error(io::Error::last_os_error())),
+            _ => Ok(()),
+        }
+    }
+
+    #[tracing::instrument(level = Level::TRACE, skip(self), err(level = Level::DEBUG))]
+    pub(crate) fn listxattr(&mut self, target: XattrTarget) -> RemoteResult<ListXattrResponse> {
+        let target = self.resolve_xattr_target(target)?;
+
+        let list = read_xattr_buffer(|buffer, size| unsafe {
+            match &target {
+                ResolvedXattrTarget::Path {
+                    path,
+                    follow_symlinks: true,
+                } => libc::listxattr(path.as_ptr(), buffer.cast(), size),
+                ResolvedXattrTarget::Path {
+                    path,
+                    follow_symlinks: false,
+                } => libc::llistxattr(path.as_ptr(), buffer.cast(), size),
+                ResolvedXattrTarget::Fd(fd) => libc::flistxattr(*fd, buffer.cast(), size),
+            }
+        })
+        .map_err(xattr_error)?;
+
+        // The kernel returns a list of null-terminated names.
+        let names = list
+            .split(|byte| *byte == 0)
+            .filter(|name| !name.is_empty())
+            .map(<[u8]>::to_vec)
+            .collect();
+
+        Ok(ListXattrResponse { names })
+    }
+
     pub(crate) fn seek(&mut self, fd: u64, seek_from: SeekFrom) -> RemoteResult<SeekFileResponse> {
         trace!(
             "FileManager::seek -> fd {:#?} | seek_from {:#?}",
@@ -677,13 +1485,16 @@ impl FileManager {
             write_bytes.len()
         );
 
+        let capacity = self.poll_fifo(fd, libc::POLLOUT)?;
+        let write_bytes = &write_bytes[..write_bytes.len().min(capacity)];
+
         self.open_files
             .get_mut(&fd)
             .ok_or(ResponseError::NotFound(fd))
             .and_then(|remote_file| {
                 if let RemoteFile::File(file) = remote_file {
                     let write_result =
-                        file.write(&write_bytes)
+                        file.write(write_bytes)
                             .map(|write_amount| WriteFileResponse {
                                 written_amount: write_amount as u64,
                             })?;
@@ -703,6 +1514,7 @@ impl FileManager {
             error!(fd, "fd not found!");
         } else {
             OPEN_FD_COUNT.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+            self.blocking_fifos.remove(&fd);
         }
 
         None
@@ -749,6 +1561,39 @@ impl FileManager {
             .map_err(ResponseError::from)
     }
 
+    /// Like [`Self::access`], but checks with `faccessat`, so that [`AccessFlagsInternal`] are
+    /// respected, and the result matches what an `open` of the file from the agent would do.
+    #[tracing::instrument(level = Level::TRACE, skip(self), ret, err(level = Level::DEBUG))]
+    pub(crate) fn access_v2(
+        &mut self,
+        pathname: PathBuf,
+        mode: u8,
+        flags: AccessFlagsInternal,
+    ) -> RemoteResult<AccessFileResponse> {
+        let pathname = self.resolve_path_cstring(&pathname, flags.nofollow)?;
+
+        let mut at_flags = 0;
+        if flags.effective_ids {
+            at_flags |= libc::AT_EACCESS;
+        }
+        if flags.nofollow {
+            at_flags |= libc::AT_SYMLINK_NOFOLLOW;
+        }
+
+        let result = unsafe {
+            libc::faccessat(
+                libc::AT_FDCWD,
+                pathname.as_ptr(),
+                libc::c_int::from(mode),
+                at_flags,
+            )
+        };
+        match result {
+            -1 => Err(ResponseError::from(io::Error::last_os_error())),
+            _ => Ok(AccessFileResponse),
+        }
+    }
+
     #[tracing::instrument(level = "trace", skip(self))]
     pub(crate) fn xstat(
         &mut self,
@@ -766,7 +1611,9 @@ impl FileManager {
                     .get(&fd)
                     .ok_or(ResponseError::NotFound(fd))?
                 {
-                    RemoteFile::Directory(parent_path) => parent_path.join(path),
+                    RemoteFile::Directory {
+                        path: parent_path, ..
+                    } => parent_path.join(path),
                     _ => {
                         return Err(ResponseError::NotDirectory(fd));
                     }
@@ -779,16 +1626,11 @@ impl FileManager {
                     .get(&fd)
                     .ok_or(ResponseError::NotFound(fd))?
                 {
-                    RemoteFile::File(file) => {
+                    RemoteFile::File(file) | RemoteFile::Directory { file, .. } => {
                         return Ok(XstatResponse {
                             metadata: file.metadata()?.into(),
                         });
                     }
-                    RemoteFile::Directory(path) => {
-                        return Ok(XstatResponse {
-                            metadata: path.metadata()?.into(),
-                        });
-                    }
                 }
             }
             // invalid
@@ -818,12 +1660,9 @@ impl FileManager {
             .get(&fd)
             .ok_or(ResponseError::NotFound(fd))?;
 
-        let statfs = match target {
-            RemoteFile::File(file) => nix::sys::statfs::fstatfs(file)
-                .map_err(|err| std::io::Error::from_raw_os_error(err as i32))?,
-            RemoteFile::Directory(path) => nix::sys::statfs::statfs(path)
-                .map_err(|err| std::io::Error::from_raw_os_error(err as i32))?,
-        };
+        let (RemoteFile::File(file) | RemoteFile::Directory { file, .. }) = target;
+        let statfs = nix::sys::statfs::fstatfs(file)
+            .map_err(|err| std::io::Error::from_raw_os_error(err as i32))?;
 
         Ok(XstatFsResponseV2 {
             metadata: statfs.into(),
@@ -857,7 +1696,7 @@ impl FileManager {
             .get(&fd)
             .ok_or(ResponseError::NotFound(fd))?
         {
-            RemoteFile::Directory(path) => Ok(path),
+            RemoteFile::Directory { path, .. } => Ok(path),
             _ => Err(ResponseError::NotDirectory(fd)),
         }?;
 
@@ -866,7 +1705,7 @@ impl FileManager {
             .next()
             .ok_or_else(|| ResponseError::IdsExhausted("fdopen_dir".to_string()))?;
 
-        let dir_stream = path.read_dir()?.enumerate();
+        let dir_stream = DirStream::new(path.clone())?;
 
         if self.dir_streams.insert(fd, dir_stream).is_none() {
             OPEN_FD_COUNT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
@@ -876,7 +1715,7 @@ impl FileManager {
     }
 
     #[tracing::instrument(level = "trace", skip(self))]
-    pub(crate) fn get_dir_stream(&mut self, fd: u64) -> RemoteResult<&mut Enumerate<ReadDir>> {
+    pub(crate) fn get_dir_stream(&mut self, fd: u64) -> RemoteResult<&mut DirStream> {
         self.dir_streams
             .get_mut(&fd)
             .ok_or(ResponseError::NotFound(fd))
@@ -928,7 +1767,7 @@ impl FileManager {
             Entry::Vacant(e) => match self.open_files.get(&fd) {
                 None => Err(ResponseError::NotFound(fd)),
                 Some(RemoteFile::File(_file)) => Err(ResponseError::NotDirectory(fd)),
-                Some(RemoteFile::Directory(dir)) => {
+                Some(RemoteFile::Directory { path: dir, .. }) => {
                     let current_and_parent = Self::get_current_and_parent_entries(dir);
                     let stream =
                         GetDEnts64Stream::new(dir.read_dir()?, current_and_parent).peekable();
@@ -972,6 +1811,29 @@ impl FileManager {
         Ok(result)
     }
 
+    /// Same as [`Self::read_dir_batch`], but starts reading from the given [`DirCursor`].
+    #[tracing::instrument(level = Level::TRACE, skip(self), ret)]
+    pub(crate) fn read_dir_batch_from(
+        &mut self,
+        fd: u64,
+        amount: usize,
+        cursor: DirCursor,
+    ) -> RemoteResult<ReadDirBatchFromResponse> {
+        let dir_stream = self.get_dir_stream(fd)?;
+        dir_stream.seek(cursor)?;
+
+        let dir_entries = dir_stream
+            .take(amount)
+            .map(DirEntryInternal::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(ReadDirBatchFromResponse {
+            fd,
+            dir_entries,
+            cursor: dir_stream.cursor(),
+        })
+    }
+
     /// The getdents64 syscall writes dir entries to a buffer, as long as they fit.
     /// If a call did not process all the entries in a dir, the result of the next call continues
     /// where the last one stopped.
@@ -1029,3 +1891,796 @@ impl FileManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use mirrord_protocol::RemoteIOError;
+
+    use super::*;
+
+    /// A file unlinked while open remains accessible through its fd until it's closed, like on
+    /// POSIX, and `fstat` on the fd reports that it has no links left.
+    #[test]
+    fn unlink_while_open() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("unlinked");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let mut file_manager = FileManager::new(None);
+
+        let OpenFileResponse { fd } = file_manager
+            .open(
+                path.clone(),
+                OpenOptionsInternal {
+                    read: true,
+                    write: true,
+                    ..Default::default()
+                },
+                Default::default(),
+                false,
+            )
+            .unwrap();
+
+        file_manager.unlink(&path).unwrap();
+        assert!(!path.exists());
+
+        let read = file_manager.read(fd, 64).unwrap();
+        assert_eq!(read.bytes.into_vec(), b"hello");
+
+        let written = file_manager.write(fd, b" world".to_vec()).unwrap();
+        assert_eq!(written.written_amount, 6);
+
+        let read = file_manager.read_limited(fd, 64, 0).unwrap();
+        assert_eq!(read.bytes.into_vec(), b"hello world");
+
+        let XstatResponse { metadata } = file_manager.xstat(None, Some(fd), true).unwrap();
+        assert_eq!(metadata.hard_links, 0);
+        assert_eq!(metadata.size, 11);
+
+        assert!(file_manager.close(fd).is_none());
+        assert!(matches!(
+            file_manager.read(fd, 64),
+            Err(ResponseError::NotFound(closed)) if closed == fd
+        ));
+    }
+
+    /// Reads with a huge `buffer_size` don't allocate it, but are cut short at [`MAX_READ_SIZE`].
+    #[test]
+    fn read_buffer_size_capped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("small");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let mut file_manager = FileManager::new(None);
+
+        let OpenFileResponse { fd } = file_manager
+            .open(
+                path,
+                OpenOptionsInternal {
+                    read: true,
+                    ..Default::default()
+                },
+                Default::default(),
+                false,
+            )
+            .unwrap();
+
+        let read = file_manager.read(fd, u64::MAX).unwrap();
+        assert_eq!(read.bytes.into_vec(), b"hello");
+
+        let read = file_manager.read_limited(fd, u64::MAX, 1).unwrap();
+        assert_eq!(read.bytes.into_vec(), b"ello");
+    }
+
+    /// `writev` writes all of the buffers, `readv` reports how many bytes went to each buffer.
+    #[test]
+    fn readv_writev() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("iov");
+
+        let mut file_manager = FileManager::new(None);
+
+        let OpenFileResponse { fd } = file_manager
+            .open(
+                path.clone(),
+                OpenOptionsInternal {
+                    read: true,
+                    write: true,
+                    create: true,
+                    ..Default::default()
+                },
+                Default::default(),
+                false,
+            )
+            .unwrap();
+
+        let written = file_manager
+            .writev(
+                fd,
+                vec![
+                    Payload::from(b"hello".to_vec()),
+                    Payload::default(),
+                    Payload::from(b" world".to_vec()),
+                ],
+            )
+            .unwrap();
+        assert_eq!(written.written_amount, 11);
+        assert_eq!(std::fs::read(&path).unwrap(), b"hello world");
+
+        file_manager.seek(fd, SeekFrom::Start(0)).unwrap();
+        let read = file_manager.readv(fd, vec![4, 0, 4, 16, 8]).unwrap();
+        assert_eq!(read.bytes.into_vec(), b"hello world");
+        assert_eq!(read.iov_lengths, [4, 0, 4, 3]);
+    }
+
+    /// `copy_file_range` with an offset leaves the file position alone, and without one it copies
+    /// from (and moves) the file position.
+    #[test]
+    fn copy_file_range_offsets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path_in = dir.path().join("in");
+        let path_out = dir.path().join("out");
+        std::fs::write(&path_in, b"hello world").unwrap();
+
+        let mut file_manager = FileManager::new(None);
+        let OpenFileResponse { fd: fd_in } = file_manager
+            .open(
+                path_in,
+                OpenOptionsInternal {
+                    read: true,
+                    ..Default::default()
+                },
+                Default::default(),
+                false,
+            )
+            .unwrap();
+        let OpenFileResponse { fd: fd_out } = file_manager
+            .open(
+                path_out.clone(),
+                OpenOptionsInternal {
+                    write: true,
+                    create: true,
+                    ..Default::default()
+                },
+                Default::default(),
+                false,
+            )
+            .unwrap();
+
+        let copied = file_manager
+            .copy_file_range(CopyFileRangeRequest {
+                fd_in,
+                off_in: Some(6),
+                fd_out,
+                off_out: None,
+                len: 64,
+            })
+            .unwrap();
+        assert_eq!(copied.copied, 5);
+
+        let copied = file_manager
+            .copy_file_range(CopyFileRangeRequest {
+                fd_in,
+                off_in: None,
+                fd_out,
+                off_out: Some(5),
+                len: 6,
+            })
+            .unwrap();
+        assert_eq!(copied.copied, 6);
+        assert_eq!(std::fs::read(&path_out).unwrap(), b"worldhello ");
+
+        let read = file_manager.read(fd_in, 64).unwrap();
+        assert_eq!(read.bytes.into_vec(), b"world");
+
+        // A huge `len` is cut short instead of overflowing the offsets.
+        let copied = file_manager
+            .copy_file_range(CopyFileRangeRequest {
+                fd_in,
+                off_in: Some(0),
+                fd_out,
+                off_out: Some(11),
+                len: u64::MAX,
+            })
+            .unwrap();
+        assert_eq!(copied.copied, 11);
+        assert_eq!(std::fs::read(&path_out).unwrap(), b"worldhello hello world");
+
+        assert!(matches!(
+            file_manager.copy_file_range(CopyFileRangeRequest {
+                fd_in,
+                off_in: None,
+                fd_out: fd_out + 1,
+                off_out: None,
+                len: 1,
+            }),
+            Err(ResponseError::NotFound(..))
+        ));
+    }
+
+    /// `flock` locks coordinate clients of the same agent and processes in the target, and are
+    /// released when the holding file is closed or the client disconnects.
+    #[test]
+    fn flock_across_clients() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("locked");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let mut first = FileManager::new(None);
+        let mut second = FileManager::new(None);
+
+        let options = OpenOptionsInternal {
+            read: true,
+            ..Default::default()
+        };
+        let OpenFileResponse { fd: first_fd } = first
+            .open(path.clone(), options, Default::default(), false)
+            .unwrap();
+        let OpenFileResponse { fd: second_fd } = second
+            .open(path.clone(), options, Default::default(), false)
+            .unwrap();
+
+        // Shared locks don't conflict.
+        first.flock(first_fd, FlockOperation::Shared).unwrap();
+        second.flock(second_fd, FlockOperation::Shared).unwrap();
+        assert_eq!(
+            first.flock(first_fd, FlockOperation::Exclusive),
+            Err(ResponseError::LockWouldBlock)
+        );
+
+        // After the second client unlocks, the first one can upgrade.
+        second.flock(second_fd, FlockOperation::Unlock).unwrap();
+        first.flock(first_fd, FlockOperation::Exclusive).unwrap();
+        assert_eq!(
+            second.flock(second_fd, FlockOperation::Shared),
+            Err(ResponseError::LockWouldBlock)
+        );
+
+        // Closing the file releases the lock.
+        first.close(first_fd);
+        second.flock(second_fd, FlockOperation::Exclusive).unwrap();
+
+        // And so does disconnecting.
+        let OpenFileResponse { fd: first_fd } = first
+            .open(path.clone(), options, Default::default(), false)
+            .unwrap();
+        assert_eq!(
+            first.flock(first_fd, FlockOperation::Shared),
+            Err(ResponseError::LockWouldBlock)
+        );
+        drop(second);
+        first.flock(first_fd, FlockOperation::Exclusive).unwrap();
+
+        // The lock is taken on the file itself, so processes in the target see it too.
+        let target_file = std::fs::File::open(&path).unwrap();
+        let result = unsafe { libc::flock(target_file.as_raw_fd(), libc::LOCK_SH | libc::LOCK_NB) };
+        assert_eq!(result, -1);
+        assert_eq!(
+            io::Error::last_os_error().raw_os_error(),
+            Some(libc::EWOULDBLOCK)
+        );
+    }
+
+    /// `fcntl` record locks of different clients conflict only on overlapping ranges, and are
+    /// released when the holding file is closed or the client disconnects.
+    #[test]
+    fn fcntl_lock_across_clients() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("locked");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let mut first = FileManager::new(None);
+        let mut second = FileManager::new(None);
+
+        let options = OpenOptionsInternal {
+            read: true,
+            write: true,
+            ..Default::default()
+        };
+        let OpenFileResponse { fd: first_fd } = first
+            .open(path.clone(), options, Default::default(), false)
+            .unwrap();
+        let OpenFileResponse { fd: second_fd } = second
+            .open(path.clone(), options, Default::default(), false)
+            .unwrap();
+
+        let lock = |fd, command, lock_type, start| FcntlLockRequest {
+            fd,
+            command,
+            lock: FcntlLock {
+                lock_type,
+                whence: FcntlLockWhence::Start,
+                start,
+                len: 10,
+                pid: 0,
+            },
+        };
+
+        first
+            .fcntl_lock(lock(
+                first_fd,
+                FcntlLockCommand::Set,
+                FcntlLockType::Write,
+                0,
+            ))
+            .unwrap();
+        assert_eq!(
+            second.fcntl_lock(lock(
+                second_fd,
+                FcntlLockCommand::Set,
+                FcntlLockType::Read,
+                5
+            )),
+            Err(ResponseError::LockWouldBlock)
+        );
+        assert_eq!(
+            second
+                .fcntl_lock(lock(
+                    second_fd,
+                    FcntlLockCommand::Get,
+                    FcntlLockType::Read,
+                    5
+                ))
+                .unwrap()
+                .conflicting,
+            Some(FcntlLock {
+                lock_type: FcntlLockType::Write,
+                whence: FcntlLockWhence::Start,
+                start: 0,
+                len: 10,
+                pid: -1,
+            })
+        );
+
+        // Ranges that don't overlap don't conflict.
+        second
+            .fcntl_lock(lock(
+                second_fd,
+                FcntlLockCommand::Set,
+                FcntlLockType::Write,
+                10,
+            ))
+            .unwrap();
+
+        // Closing the file releases the lock.
+        first.close(first_fd);
+        second
+            .fcntl_lock(lock(
+                second_fd,
+                FcntlLockCommand::Set,
+                FcntlLockType::Write,
+                0,
+            ))
+            .unwrap();
+
+        // And so does disconnecting.
+        let OpenFileResponse { fd: first_fd } = first
+            .open(path, options, Default::default(), false)
+            .unwrap();
+        assert_eq!(
+            first
+                .fcntl_lock(lock(
+                    first_fd,
+                    FcntlLockCommand::Get,
+                    FcntlLockType::Read,
+                    0
+                ))
+                .unwrap()
+                .conflicting
+                .map(|lock| lock.lock_type),
+            Some(FcntlLockType::Write)
+        );
+        drop(second);
+        assert_eq!(
+            first
+                .fcntl_lock(lock(
+                    first_fd,
+                    FcntlLockCommand::Get,
+                    FcntlLockType::Write,
+                    0
+                ))
+                .unwrap()
+                .conflicting,
+            None
+        );
+    }
+
+    /// `syncfs` works on open files and directories, and fails on fds that are not open.
+    #[test]
+    fn syncfs_open_fd() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("synced");
+        std::fs::write(&path, b"hello").unwrap();
+
+        let mut file_manager = FileManager::new(None);
+
+        let OpenFileResponse { fd } = file_manager
+            .open(
+                path,
+                OpenOptionsInternal {
+                    read: true,
+                    write: true,
+                    ..Default::default()
+                },
+                Default::default(),
+                false,
+            )
+            .unwrap();
+        file_manager.write(fd, b" world".to_vec()).unwrap();
+        file_manager.syncfs(Some(fd)).unwrap();
+
+        let OpenFileResponse { fd: dir_fd } = file_manager
+            .open(
+                dir.path().to_path_buf(),
+                OpenOptionsInternal {
+                    read: true,
+                    ..Default::default()
+                },
+                Default::default(),
+                false,
+            )
+            .unwrap();
+        file_manager.syncfs(Some(dir_fd)).unwrap();
+
+        file_manager.close(fd);
+        assert!(matches!(
+            file_manager.syncfs(Some(fd)),
+            Err(ResponseError::NotFound(closed)) if closed == fd
+        ));
+    }
+
+    /// Opens the directory at `path` with a new [`FileManager`], like the intproxy does when it
+    /// re-opens a directory after a reconnect.
+    fn open_dir_stream(path: &Path) -> (FileManager, u64) {
+        let mut file_manager = FileManager::new(None);
+        let OpenFileResponse { fd } = file_manager
+            .open(
+                path.to_path_buf(),
+                OpenOptionsInternal {
+                    read: true,
+                    ..Default::default()
+                },
+                Default::default(),
+                false,
+            )
+            .unwrap();
+        let OpenDirResponse { fd } = file_manager.fdopen_dir(fd).unwrap();
+        (file_manager, fd)
+    }
+
+    /// Directory iteration can be resumed from a [`DirCursor`] with a re-opened directory, and
+    /// moved back to the start with the same directory.
+    #[test]
+    fn read_dir_batch_from_cursor() {
+        let dir = tempfile::tempdir().unwrap();
+        for i in 0..5 {
+            std::fs::write(dir.path().join(format!("file-{i}")), b"").unwrap();
+        }
+
+        let (mut file_manager, fd) = open_dir_stream(dir.path());
+        let first = file_manager
+            .read_dir_batch_from(fd, 2, DirCursor::default())
+            .unwrap();
+        assert_eq!(first.dir_entries.len(), 2);
+        assert_eq!(first.cursor, DirCursor(2));
+
+        let (mut reopened, reopened_fd) = open_dir_stream(dir.path());
+        let rest = reopened
+            .read_dir_batch_from(reopened_fd, 10, first.cursor)
+            .unwrap();
+        assert_eq!(rest.cursor, DirCursor(5));
+        assert_eq!(
+            rest.dir_entries
+                .iter()
+                .map(|entry| entry.position)
+                .collect::<Vec<_>>(),
+            [2, 3, 4]
+        );
+
+        let mut names = first
+            .dir_entries
+            .into_iter()
+            .chain(rest.dir_entries)
+            .map(|entry| entry.name)
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            (0..5).map(|i| format!("file-{i}")).collect::<Vec<_>>()
+        );
+
+        let from_start = file_manager
+            .read_dir_batch_from(fd, 10, DirCursor::default())
+            .unwrap();
+        assert_eq!(from_start.dir_entries.len(), 5);
+        assert_eq!(from_start.cursor, DirCursor(5));
+    }
+
+    fn open_flags(directory: bool, nofollow: bool, path: bool) -> OpenFlagsInternal {
+        OpenFlagsInternal {
+            directory,
+            nofollow,
+            path,
+        }
+    }
+
+    /// Opens `name` in a directory with a file, a directory, and symlinks to both, using the
+    /// given `flags`, and checks the error the open fails with, like a local `open` would.
+    #[rstest::rstest]
+    #[case::directory_file("file", open_flags(true, false, false), Some(libc::ENOTDIR))]
+    #[case::directory_dir("dir", open_flags(true, false, false), None)]
+    #[case::directory_file_link("file_link", open_flags(true, false, false), Some(libc::ENOTDIR))]
+    #[case::directory_dir_link("dir_link", open_flags(true, false, false), None)]
+    #[case::nofollow_file("file", open_flags(false, true, false), None)]
+    #[case::nofollow_dir("dir", open_flags(false, true, false), None)]
+    #[case::nofollow_file_link("file_link", open_flags(false, true, false), Some(libc::ELOOP))]
+    #[case::nofollow_dir_link("dir_link", open_flags(false, true, false), Some(libc::ELOOP))]
+    #[case::path_file("file", open_flags(false, false, true), None)]
+    #[case::path_dir("dir", open_flags(false, false, true), None)]
+    #[case::path_file_link("file_link", open_flags(false, false, true), None)]
+    #[case::path_nofollow_link("file_link", open_flags(false, true, true), None)]
+    #[case::path_directory_file("file", open_flags(true, false, true), Some(libc::ENOTDIR))]
+    fn open_with_flags(
+        #[case] name: &str,
+        #[case] flags: OpenFlagsInternal,
+        #[case] expected_errno: Option<i32>,
+    ) {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file"), b"hello").unwrap();
+        std::fs::create_dir(dir.path().join("dir")).unwrap();
+        std::os::unix::fs::symlink("file", dir.path().join("file_link")).unwrap();
+        std::os::unix::fs::symlink("dir", dir.path().join("dir_link")).unwrap();
+
+        let mut file_manager = FileManager::new(None);
+        let options = OpenOptionsInternal {
+            read: true,
+            ..Default::default()
+        };
+
+        let result = file_manager.open(dir.path().join(name), options, flags, false);
+        match expected_errno {
+            None => {
+                result.unwrap();
+            }
+            Some(errno) => assert!(
+                matches!(
+                    &result,
+                    Err(ResponseError::RemoteIO(RemoteIOError {
+                        raw_os_error: Some(raw),
+                        ..
+                    })) if *raw == errno
+                ),
+                "expected errno {errno}, got {result:?}"
+            ),
+        }
+
+        // Relative opens honor the flags the same way.
+        let OpenFileResponse { fd: dir_fd } = file_manager
+            .open(dir.path().to_path_buf(), options, Default::default(), false)
+            .unwrap();
+        let result = file_manager.open_relative(dir_fd, name.into(), options, flags, false);
+        assert_eq!(
+            result.err().and_then(|error| match error {
+                ResponseError::RemoteIO(RemoteIOError { raw_os_error, .. }) => raw_os_error,
+                _ => None,
+            }),
+            expected_errno
+        );
+    }
+
+    /// [`FileManager::access_v2`] agrees with an `open` of the same file from the agent, whatever
+    /// user the test runs as, and checks a dangling symlink itself when `nofollow` is set.
+    #[rstest::rstest]
+    #[case::writable(0o644)]
+    #[case::readonly(0o444)]
+    fn access_v2_matches_open(#[case] permissions: u32) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        std::fs::write(&path, b"hello").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(permissions)).unwrap();
+
+        let mut file_manager = FileManager::new(None);
+        let flags = AccessFlagsInternal {
+            effective_ids: true,
+            nofollow: false,
+        };
+
+        let access = file_manager.access_v2(path.clone(), libc::W_OK as u8, flags);
+        let open = OpenOptions::new().write(true).open(&path);
+        assert_eq!(access.is_ok(), open.is_ok(), "{access:?} / {open:?}");
+
+        let dangling = dir.path().join("dangling");
+        std::os::unix::fs::symlink("missing", &dangling).unwrap();
+        assert!(
+            file_manager
+                .access_v2(dangling.clone(), libc::F_OK as u8, flags)
+                .is_err()
+        );
+        file_manager
+            .access_v2(
+                dangling,
+                libc::F_OK as u8,
+                AccessFlagsInternal {
+                    nofollow: true,
+                    ..flags
+                },
+            )
+            .unwrap();
+    }
+
+    /// [`FileManager::utimens`] and [`FileManager::chown`] change a (dangling) symlink itself
+    /// when not following it, and fail on its target otherwise.
+    #[test]
+    fn path_metadata_nofollow() {
+        let dir = tempfile::tempdir().unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink("missing", &link).unwrap();
+
+        let mut file_manager = FileManager::new(None);
+        let times = [
+            Timespec {
+                tv_sec: 1_000_000,
+                tv_nsec: 0,
+            },
+            Timespec {
+                tv_sec: 2_000_000,
+                tv_nsec: 0,
+            },
+        ];
+
+        assert!(
+            file_manager
+                .utimens(link.clone(), Some(times), true)
+                .is_err()
+        );
+        file_manager
+            .utimens(link.clone(), Some(times), false)
+            .unwrap();
+        let metadata = std::fs::symlink_metadata(&link).unwrap();
+        assert_eq!(metadata.mtime(), 2_000_000);
+
+        let (owner, group) = (metadata.uid(), metadata.gid());
+        assert!(
+            file_manager
+                .chown(link.clone(), owner, group, true)
+                .is_err()
+        );
+        file_manager.chown(link, owner, group, false).unwrap();
+    }
+
+    /// An `O_PATH` fd can be used for `fstat` and `openat`, but not for reading.
+    #[test]
+    fn open_path_only() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file"), b"hello").unwrap();
+
+        let mut file_manager = FileManager::new(None);
+        let options = OpenOptionsInternal {
+            read: true,
+            ..Default::default()
+        };
+
+        let OpenFileResponse { fd: file_fd } = file_manager
+            .open(
+                dir.path().join("file"),
+                options,
+                open_flags(false, false, true),
+                false,
+            )
+            .unwrap();
+        let XstatResponse { metadata } = file_manager.xstat(None, Some(file_fd), true).unwrap();
+        assert_eq!(metadata.size, 5);
+        assert!(matches!(
+            file_manager.read(file_fd, 64),
+            Err(ResponseError::RemoteIO(RemoteIOError {
+                raw_os_error: Some(libc::EBADF),
+                ..
+            }))
+        ));
+
+        let OpenFileResponse { fd: dir_fd } = file_manager
+            .open(
+                dir.path().to_path_buf(),
+                options,
+                open_flags(true, false, true),
+                false,
+            )
+            .unwrap();
+        let OpenFileResponse { fd } = file_manager
+            .open_relative(dir_fd, "file".into(), options, Default::default(), false)
+            .unwrap();
+        let read = file_manager.read(fd, 64).unwrap();
+        assert_eq!(read.bytes.into_vec(), b"hello");
+    }
+
+    /// Opening a FIFO does not wait for the other end, and reads fail with `EAGAIN` when there is
+    /// no data instead of stalling the [`FileManager`], with or without `O_NONBLOCK`.
+    #[rstest::rstest]
+    #[case::nonblock(true)]
+    #[case::blocking(false)]
+    fn read_fifo(#[case] nonblock: bool) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("fifo");
+        let c_path = CString::new(path.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+
+        let mut file_manager = FileManager::new(None);
+
+        let OpenFileResponse { fd: read_fd } = file_manager
+            .open(
+                path.clone(),
+                OpenOptionsInternal {
+                    read: true,
+                    ..Default::default()
+                },
+                Default::default(),
+                nonblock,
+            )
+            .unwrap();
+        let OpenFileResponse { fd: write_fd } = file_manager
+            .open(
+                path,
+                OpenOptionsInternal {
+                    write: true,
+                    ..Default::default()
+                },
+                Default::default(),
+                nonblock,
+            )
+            .unwrap();
+
+        // `O_NONBLOCK` is only kept when it was asked for.
+        for fd in [read_fd, write_fd] {
+            let Some(RemoteFile::File(file)) = file_manager.open_files.get(&fd) else {
+                panic!("fd {fd} is not an open file");
+            };
+            let status_flags = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFL) };
+            assert_eq!(status_flags & libc::O_NONBLOCK != 0, nonblock);
+        }
+
+        let would_block = |result: RemoteResult<ReadFileResponse>| {
+            matches!(
+                result,
+                Err(ResponseError::RemoteIO(RemoteIOError {
+                    raw_os_error: Some(libc::EAGAIN),
+                    ..
+                }))
+            )
+        };
+        assert!(would_block(file_manager.read(read_fd, 64)));
+
+        let written = file_manager.write(write_fd, b"hello".to_vec()).unwrap();
+        assert_eq!(written.written_amount, 5);
+
+        // FIFOs can't be read at an offset, the intproxy stops buffering the file on `ESPIPE`.
+        assert!(matches!(
+            file_manager.read_limited(read_fd, 64, 0),
+            Err(ResponseError::RemoteIO(RemoteIOError {
+                raw_os_error: Some(libc::ESPIPE),
+                ..
+            }))
+        ));
+
+        let read = file_manager.read(read_fd, 64).unwrap();
+        assert_eq!(read.bytes.into_vec(), b"hello");
+        assert!(would_block(file_manager.read(read_fd, 64)));
+
+        // Without `O_NONBLOCK`, a write is cut to `PIPE_BUF`, so that it never waits for the
+        // reader.
+        let written = file_manager
+            .write(write_fd, vec![0; 2 * libc::PIPE_BUF])
+            .unwrap()
+            .written_amount;
+        let expected = if nonblock { 2 } else { 1 } * libc::PIPE_BUF as u64;
+        assert_eq!(written, expected);
+        let read = file_manager.read(read_fd, written).unwrap();
+        assert_eq!(read.read_amount, written);
+
+        // No writers left, so the reader gets EOF.
+        assert!(file_manager.close(write_fd).is_none());
+        let read = file_manager.read(read_fd, 64).unwrap();
+        assert_eq!(read.read_amount, 0);
+    }
+}
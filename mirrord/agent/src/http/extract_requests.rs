@@ -2,8 +2,10 @@ use std::{
     error::Report,
     fmt,
     future::Future,
+    num::NonZeroU32,
     ops::Not,
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
 };
 
@@ -20,7 +22,7 @@ use hyper::{
 };
 use hyper_util::rt::TokioExecutor;
 use mirrord_protocol::batched_body::{BatchedBody, Frames};
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{Semaphore, mpsc, oneshot};
 
 use super::{BoxResponse, HttpVersion, error::MirrordErrorResponse};
 use crate::metrics::{MetricGuard, REDIRECTED_REQUESTS};
@@ -66,6 +68,13 @@ impl fmt::Debug for ExtractedRequest {
 ///
 /// The metric is incremented when a new request is extracted, and decremented when hyper finishes
 /// processing the response.
+///
+/// # Pending requests limit
+///
+/// When created with `max_pending_requests`, at most this many requests can wait for their
+/// responses at the same time. Further requests are not extracted until one of the pending
+/// requests gets a response. With HTTP/2, the limit is also advertised to the peer as the max
+/// number of concurrent streams, so that it stops sending new requests.
 pub struct ExtractedRequests<IO> {
     request_rx: mpsc::Receiver<(Request<Incoming>, oneshot::Sender<BoxResponse>)>,
     connection: Option<Either<ConnV1<IO>, ConnV2<IO>>>,
@@ -75,9 +84,13 @@ impl<IO> ExtractedRequests<IO>
 where
     IO: 'static + hyper::rt::Read + hyper::rt::Write + Unpin + Send,
 {
-    pub fn new(conn: IO, version: HttpVersion) -> Self {
+    pub fn new(conn: IO, version: HttpVersion, max_pending_requests: Option<NonZeroU32>) -> Self {
         let (request_tx, request_rx) = mpsc::channel(4);
-        let service = InnerService { request_tx };
+        let service = InnerService {
+            request_tx,
+            pending_permits: max_pending_requests
+                .map(|limit| Arc::new(Semaphore::new(limit.get() as usize))),
+        };
 
         let connection = match version {
             HttpVersion::V1 => {
@@ -89,8 +102,11 @@ where
             }
 
             HttpVersion::V2 => {
-                let conn =
-                    http2::Builder::new(TokioExecutor::default()).serve_connection(conn, service);
+                let mut builder = http2::Builder::new(TokioExecutor::default());
+                if let Some(limit) = max_pending_requests {
+                    builder.max_concurrent_streams(limit.get());
+                }
+                let conn = builder.serve_connection(conn, service);
                 Either::Right(conn)
             }
         };
@@ -165,6 +181,11 @@ type ConnV2<IO> = http2::Connection<IO, InnerService, TokioExecutor>;
 #[derive(Clone)]
 struct InnerService {
     request_tx: mpsc::Sender<(Request<Incoming>, oneshot::Sender<BoxResponse>)>,
+    /// Limits the number of requests waiting for their responses.
+    ///
+    /// A permit is acquired before the request is sent through
+    /// [`request_tx`](InnerService::request_tx), and released when the response is received.
+    pending_permits: Option<Arc<Semaphore>>,
 }
 
 impl Service<Request<Incoming>> for InnerService {
@@ -181,6 +202,16 @@ impl Service<Request<Incoming>> for InnerService {
             let (response_tx, response_rx) = oneshot::channel();
             let version = request.version();
 
+            let _permit = match this.pending_permits {
+                Some(permits) => Some(
+                    permits
+                        .acquire_owned()
+                        .await
+                        .expect("pending requests semaphore is never closed"),
+                ),
+                None => None,
+            };
+
             if this.request_tx.send((request, response_tx)).await.is_err() {
                 let response = BoxResponse::from(MirrordErrorResponse::new(
                     version,
@@ -241,13 +272,13 @@ impl Body for MetricGuardedBody {
 
 #[cfg(test)]
 mod test {
-    use std::sync::Arc;
+    use std::{num::NonZeroU32, sync::Arc, time::Duration};
 
     use bytes::Bytes;
     use futures::StreamExt;
     use http_body_util::{BodyExt, Empty};
-    use hyper::{Request, Response, http::StatusCode};
-    use hyper_util::rt::TokioIo;
+    use hyper::{Request, Response, client::conn::http2, http::StatusCode};
+    use hyper_util::rt::{TokioExecutor, TokioIo};
     use rstest::rstest;
     use tokio::{
         net::{TcpListener, TcpStream},
@@ -291,7 +322,7 @@ mod test {
         });
 
         let (stream, _) = listener.accept().await.unwrap();
-        let mut requests = ExtractedRequests::new(TokioIo::new(stream), version);
+        let mut requests = ExtractedRequests::new(TokioIo::new(stream), version, None);
 
         let request = requests.next().await.unwrap().unwrap();
         let _ = request.response_tx.send(Response::new(
@@ -335,7 +366,7 @@ mod test {
         });
 
         let (stream, _) = listener.accept().await.unwrap();
-        let mut requests = ExtractedRequests::new(TokioIo::new(stream), version);
+        let mut requests = ExtractedRequests::new(TokioIo::new(stream), version, None);
 
         let request = requests.next().await.unwrap().unwrap();
         std::mem::drop(request);
@@ -345,4 +376,70 @@ mod test {
 
         client.await.unwrap();
     }
+
+    /// Verifies that [`ExtractedRequests`] does not extract more requests than the configured
+    /// limit of pending requests, until one of them gets a response.
+    #[tokio::test]
+    async fn extract_requests_max_pending() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+
+        let addr = listener.local_addr().unwrap();
+        let client = tokio::spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let (mut sender, conn) = http2::handshake(TokioExecutor::new(), TokioIo::new(stream))
+                .await
+                .unwrap();
+            tokio::spawn(conn);
+
+            // Makes sure that the client knows the server settings before sending concurrent
+            // requests.
+            let response = sender
+                .send_request(Request::new(Empty::<Bytes>::new()))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+
+            let requests = (0..2)
+                .map(|_| {
+                    let mut sender = sender.clone();
+                    tokio::spawn(async move {
+                        sender.ready().await.unwrap();
+                        sender
+                            .send_request(Request::new(Empty::<Bytes>::new()))
+                            .await
+                            .unwrap()
+                    })
+                })
+                .collect::<Vec<_>>();
+            for request in requests {
+                assert_eq!(request.await.unwrap().status(), StatusCode::OK);
+            }
+        });
+
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut requests =
+            ExtractedRequests::new(TokioIo::new(stream), HttpVersion::V2, NonZeroU32::new(1));
+
+        for _ in 0..2 {
+            let request = requests.next().await.unwrap().unwrap();
+
+            tokio::time::timeout(Duration::from_millis(200), requests.next())
+                .await
+                .expect_err("the limit of pending requests was exceeded");
+
+            let _ = request.response_tx.send(Response::new(
+                Empty::<Bytes>::new().map_err(|_| unreachable!()).boxed(),
+            ));
+        }
+
+        let request = requests.next().await.unwrap().unwrap();
+        let _ = request.response_tx.send(Response::new(
+            Empty::<Bytes>::new().map_err(|_| unreachable!()).boxed(),
+        ));
+
+        requests.graceful_shutdown();
+        assert!(requests.next().await.is_none());
+
+        client.await.unwrap();
+    }
 }
@@ -1,7 +1,7 @@
 use std::{fmt::Debug, io::Read, ops::Not, sync::LazyLock, time::Duration};
 
 use fancy_regex::Regex;
-use http::HeaderMap;
+use http::{HeaderMap, HeaderName, header::InvalidHeaderName};
 use hyper::http::request::Parts;
 use jaq_core::{
     Ctx, RcIter,
@@ -38,6 +38,15 @@ pub enum HttpFilter {
 
     /// Header based on header using jq
     HeaderJq(JqQuery),
+
+    /// Filter based on a single named header.
+    NamedHeader {
+        name: HeaderName,
+        /// Used against each value of the header. When [`None`], any value matches.
+        value: Option<Regex>,
+        /// If true, matches when no value of the header matches.
+        negate: bool,
+    },
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -50,6 +59,9 @@ pub enum FilterCreationError {
 
     #[error("error compiling jq expression: {0}")]
     Jq(String),
+
+    #[error("invalid header name: {0}")]
+    HeaderName(#[from] InvalidHeaderName),
 }
 
 impl TryFrom<&mirrord_protocol::tcp::HttpFilter> for HttpFilter {
@@ -81,6 +93,15 @@ impl TryFrom<&mirrord_protocol::tcp::HttpFilter> for HttpFilter {
                     .map(HttpFilter::HeaderJq)
                     .map_err(FilterCreationError::Jq)
             }
+            mirrord_protocol::tcp::HttpFilter::NamedHeader(condition) => Ok(Self::NamedHeader {
+                name: HeaderName::try_from(condition.name.as_str())?,
+                value: condition
+                    .value
+                    .as_ref()
+                    .map(|value| Regex::new(&format!("(?i){value}")))
+                    .transpose()?,
+                negate: condition.negate,
+            }),
         }
     }
 }
@@ -208,6 +229,35 @@ impl HttpFilter {
 
                 false
             }
+
+            Self::NamedHeader {
+                name,
+                value,
+                negate,
+            } => {
+                let matched = parts
+                    .headers
+                    .get_all(name)
+                    .iter()
+                    .any(|header_value| match value {
+                        None => true,
+                        Some(regex) => header_value.to_str().is_ok_and(|header_value| {
+                            regex
+                                .is_match(header_value)
+                                .inspect_err(|error| {
+                                    tracing::error!(
+                                        %name,
+                                        header_value,
+                                        ?error,
+                                        "Error while matching header"
+                                    );
+                                })
+                                .unwrap_or_default()
+                        }),
+                    });
+
+                matched != *negate
+            }
         }
     }
 
@@ -328,7 +378,7 @@ mod test {
     use std::{ops::Not, str::FromStr};
 
     use hyper::Request;
-    use mirrord_protocol::tcp::{self, Filter, HttpMethodFilter};
+    use mirrord_protocol::tcp::{self, Filter, HeaderCondition, HttpMethodFilter};
 
     use super::HttpFilter;
 
@@ -404,4 +454,100 @@ mod test {
         let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
         assert!(!filter.matches::<&[u8]>(&mut input, None).await);
     }
+
+    fn named_header(name: &str, value: Option<&str>, negate: bool) -> tcp::HttpFilter {
+        tcp::HttpFilter::NamedHeader(HeaderCondition {
+            name: name.to_string(),
+            value: value.map(|value| Filter::new(value.to_string()).unwrap()),
+            negate,
+        })
+    }
+
+    #[tokio::test]
+    async fn matching_absent_header() {
+        let filter: HttpFilter = TryFrom::try_from(&named_header("x-tenant", None, true)).unwrap();
+
+        // should match
+        let mut input = Request::builder()
+            .uri("https://www.balconia.gov/api")
+            .header("brass-key", "a-bazillion")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None).await);
+
+        // should fail, any value counts as present
+        let mut input = Request::builder()
+            .uri("https://www.balconia.gov/api")
+            .header("X-Tenant", "")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None).await.not());
+    }
+
+    #[tokio::test]
+    async fn matching_negated_header_value() {
+        let filter: HttpFilter =
+            TryFrom::try_from(&named_header("x-tenant", Some("^team-a$"), true)).unwrap();
+
+        for (tenants, should_match) in [
+            (&[][..], true),
+            (&["team-b"][..], true),
+            (&["TEAM-A"][..], false),
+            (&["team-b", "team-a"][..], false),
+        ] {
+            let mut input = Request::builder().uri("https://www.balconia.gov/api");
+            for tenant in tenants {
+                input = input.header("x-tenant", *tenant);
+            }
+            let mut input = input.body(()).unwrap().into_parts().0;
+
+            assert_eq!(
+                filter.matches::<&[u8]>(&mut input, None).await,
+                should_match,
+                "{tenants:?}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn matching_any_named_header() {
+        let tcp_filter = tcp::HttpFilter::Composite {
+            all: false,
+            filters: vec![
+                named_header("brass-key", Some("bazillion"), false),
+                named_header("dungeon-key", None, false),
+            ],
+        };
+        let filter: HttpFilter = TryFrom::try_from(&tcp_filter).unwrap();
+
+        // should match
+        let mut input = Request::builder()
+            .uri("https://www.balconia.gov/api")
+            .header("dungeon-key", "heavy")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None).await);
+
+        // should fail, the value is matched only against the named header
+        let mut input = Request::builder()
+            .uri("https://www.balconia.gov/api")
+            .header("brass-key", "nothin")
+            .header("other-key", "a-bazillion")
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0;
+        assert!(filter.matches::<&[u8]>(&mut input, None).await.not());
+    }
+
+    #[test]
+    fn invalid_header_name() {
+        assert!(HttpFilter::try_from(&named_header("bad header", None, false)).is_err());
+    }
 }
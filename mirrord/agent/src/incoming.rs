@@ -2,6 +2,7 @@
 
 mod composed;
 mod connection;
+mod ebpf;
 mod error;
 mod iptables;
 mod mirror_handle;
@@ -14,6 +15,7 @@ use std::{
     future::Future,
     io,
     net::{IpAddr, SocketAddr},
+    time::Duration,
 };
 
 use composed::ComposedRedirector;
@@ -21,10 +23,15 @@ pub use connection::{
     IncomingStream, IncomingStreamItem,
     http::{MirroredHttp, RedirectedHttp, ResponseBodyProvider, ResponseProvider, StolenHttp},
     tcp::{RedirectedTcp, StolenTcp},
+    unix::StolenUnix,
+};
+pub use ebpf::{
+    EbpfRedirector, EbpfRedirectorError, SelectedRedirector, has_net_admin, select_redirector,
 };
 pub use error::{ConnError, RedirectorTaskError};
 use iptables::IpTablesRedirector;
 pub use mirror_handle::{MirrorHandle, MirroredTraffic};
+use mirrord_agent_iptables::RedirectExclusions;
 pub use steal_handle::{StealHandle, StolenTraffic};
 pub use task::{RedirectorTask, RedirectorTaskConfig};
 use tokio::net::TcpStream;
@@ -98,31 +105,53 @@ impl fmt::Debug for Redirected {
 /// * `pod_ips` - passed to inner redirectors.
 /// * `support_ipv6` - if set, this function will attempt to create both an IPv4 and an IPv6
 ///   redirector. Otherwise, it will only attempt to create an IPv4 redirector.
+/// * `native_nftables` - passed to inner redirectors.
+/// * `exclusions` - passed to inner redirectors.
+/// * `reconcile_interval` - passed to inner redirectors.
 pub async fn create_iptables_redirector(
     flush_connections: bool,
     pod_ips: &[IpAddr],
     support_ipv6: bool,
     with_mesh_exclusion: Option<u16>,
+    native_nftables: bool,
+    exclusions: &RedirectExclusions,
+    reconcile_interval: Option<Duration>,
 ) -> io::Result<ComposedRedirector<IpTablesRedirector>> {
-    let ipv4 = IpTablesRedirector::create(flush_connections, pod_ips, false, with_mesh_exclusion)
+    let ipv4 = IpTablesRedirector::create(
+        flush_connections,
+        pod_ips,
+        false,
+        with_mesh_exclusion,
+        native_nftables,
+        exclusions,
+        reconcile_interval,
+    )
+    .await
+    .inspect_err(|error| {
+        tracing::error!(
+            %error,
+            "Failed to create an IPv4 traffic redirector",
+        )
+    });
+
+    let ipv6 = if support_ipv6 {
+        IpTablesRedirector::create(
+            flush_connections,
+            pod_ips,
+            true,
+            with_mesh_exclusion,
+            native_nftables,
+            exclusions,
+            reconcile_interval,
+        )
         .await
         .inspect_err(|error| {
             tracing::error!(
                 %error,
-                "Failed to create an IPv4 traffic redirector",
+                "Failed to create an IPv6 traffic redirector",
             )
-        });
-
-    let ipv6 = if support_ipv6 {
-        IpTablesRedirector::create(flush_connections, pod_ips, true, with_mesh_exclusion)
-            .await
-            .inspect_err(|error| {
-                tracing::error!(
-                    %error,
-                    "Failed to create an IPv6 traffic redirector",
-                )
-            })
-            .into()
+        })
+        .into()
     } else {
         None
     };
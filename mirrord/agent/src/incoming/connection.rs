@@ -32,6 +32,9 @@ pub mod http;
 mod http_task;
 mod optional_broadcast;
 pub mod tcp;
+pub mod unix;
+
+use http_task::SharedPassthroughH2;
 
 /// Redirected connection info.
 #[derive(Clone, Debug)]
@@ -52,6 +55,9 @@ pub struct ConnectionInfo {
     /// TLS connector that should be used when passing this connection
     /// through to its original destination.
     pub tls_connector: Option<PassThroughTlsConnector>,
+    /// HTTP/2 connection used to pass requests from this connection through to their original
+    /// destination.
+    pub passthrough_h2: SharedPassthroughH2,
 }
 
 impl ConnectionInfo {
@@ -192,6 +198,7 @@ impl MaybeHttp {
                     local_addr,
                     peer_addr,
                     tls_connector: None,
+                    passthrough_h2: Default::default(),
                 },
             });
         };
@@ -248,6 +255,7 @@ impl MaybeHttp {
                 local_addr,
                 peer_addr,
                 tls_connector: Some(tls_connector),
+                passthrough_h2: Default::default(),
             },
         })
     }
@@ -1,21 +1,25 @@
-use std::{error::Report, future::Future, sync::Arc};
+use std::{error::Report, fmt, future::Future, ops::Not, sync::Arc};
 
 use bytes::{Bytes, BytesMut};
 use futures::StreamExt;
-use http_body_util::{BodyExt, StreamBody, combinators::BoxBody};
+use http_body_util::{
+    BodyExt, StreamBody,
+    combinators::{BoxBody, UnsyncBoxBody},
+};
 use hyper::{
     Request, Response,
-    body::{Body, Frame, Incoming},
-    http::{StatusCode, Version},
+    body::{Frame, Incoming},
+    client::conn::http2,
+    http::{StatusCode, Uri, Version},
     upgrade::{OnUpgrade, Upgraded},
 };
-use hyper_util::rt::TokioIo;
+use hyper_util::rt::{TokioExecutor, TokioIo};
 use mirrord_protocol::{Payload, tcp::InternalHttpBodyFrame};
 use mirrord_tls_util::MaybeTls;
 use tokio::{
     io::{AsyncReadExt, AsyncWriteExt},
     net::TcpStream,
-    sync::{mpsc, oneshot},
+    sync::{Mutex, mpsc, oneshot},
     task::JoinHandle,
 };
 use tokio_stream::wrappers::ReceiverStream;
@@ -40,6 +44,50 @@ use crate::{
 
 pub type UpgradeDataRx = mpsc::Receiver<Bytes>;
 
+/// Body of the requests we pass through to their original destination.
+type PassthroughBody = UnsyncBoxBody<Bytes, hyper::Error>;
+
+/// HTTP/2 connection to the original destination, shared by all requests passed through from one
+/// redirected connection.
+///
+/// With HTTP/2, the redirected connection multiplexes many requests (e.g. gRPC calls), and only
+/// some of them are stolen. The rest are sent as streams on a single upstream connection, so the
+/// original destination sees one client connection, like it would without mirrord.
+#[derive(Clone, Default)]
+pub struct SharedPassthroughH2(Arc<Mutex<Option<http2::SendRequest<PassthroughBody>>>>);
+
+impl SharedPassthroughH2 {
+    /// Returns a sender for the upstream connection, making the connection if there is none yet,
+    /// or if the previous one was closed.
+    async fn get_or_connect(
+        &self,
+        info: &ConnectionInfo,
+        uri: &Uri,
+    ) -> Result<HttpSender<PassthroughBody>, ConnError> {
+        let mut sender = self.0.lock().await;
+
+        if let Some(sender) = sender.as_ref().filter(|sender| sender.is_closed().not()) {
+            return Ok(HttpSender::V2(sender.clone()));
+        }
+
+        let stream = HttpTask::<PassthroughConnection>::connect(info, uri).await?;
+        let (new_sender, conn) = http2::handshake(TokioExecutor::default(), TokioIo::new(stream))
+            .await
+            .map_err(From::from)
+            .map_err(ConnError::PassthroughHttpError)?;
+        tokio::spawn(conn);
+
+        Ok(HttpSender::V2(sender.insert(new_sender).clone()))
+    }
+}
+
+impl fmt::Debug for SharedPassthroughH2 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedPassthroughH2")
+            .finish_non_exhaustive()
+    }
+}
+
 /// Background task responsible for handling IO on a redirected HTTP request.
 pub struct HttpTask<D> {
     /// Frames that we need to send to the request destination.
@@ -137,7 +185,7 @@ impl HttpTask<PassthroughConnection> {
                 tail: body_tail,
             };
 
-            let hyper_request = Request::from_parts(request.parts, body);
+            let hyper_request = Request::from_parts(request.parts, body.boxed_unsync());
 
             let mut response = match Self::send_request(&info, hyper_request).await {
                 Ok(response) => response,
@@ -182,13 +230,38 @@ impl HttpTask<PassthroughConnection> {
         }
     }
 
-    async fn send_request<B>(
+    /// Sends the request to its original destination.
+    ///
+    /// HTTP/2 requests go through the [`ConnectionInfo::passthrough_h2`] connection, other
+    /// requests get a new connection.
+    async fn send_request(
         info: &ConnectionInfo,
-        request: Request<B>,
-    ) -> Result<Response<Incoming>, ConnError>
-    where
-        B: 'static + Body<Data = Bytes, Error = hyper::Error> + Send + Unpin,
-    {
+        request: Request<PassthroughBody>,
+    ) -> Result<Response<Incoming>, ConnError> {
+        let mut sender = match request.version() {
+            Version::HTTP_2 => {
+                info.passthrough_h2
+                    .get_or_connect(info, request.uri())
+                    .await?
+            }
+            _ => {
+                let stream = Self::connect(info, request.uri()).await?;
+                HttpSender::new(TokioIo::new(stream), HttpVersion::V1)
+                    .await
+                    .map_err(From::from)
+                    .map_err(ConnError::PassthroughHttpError)?
+            }
+        };
+
+        sender
+            .send(request)
+            .await
+            .map_err(From::from)
+            .map_err(ConnError::PassthroughHttpError)
+    }
+
+    /// Makes a new connection to the original destination, with TLS if required.
+    async fn connect(info: &ConnectionInfo, uri: &Uri) -> Result<MaybeTls, ConnError> {
         let stream = TcpStream::connect(info.pass_through_address())
             .await
             .map_err(From::from)
@@ -197,7 +270,7 @@ impl HttpTask<PassthroughConnection> {
         let stream = match &info.tls_connector {
             Some(connector) => {
                 let stream = connector
-                    .connect(info.original_destination.ip(), Some(request.uri()), stream)
+                    .connect(info.original_destination.ip(), Some(uri), stream)
                     .await
                     .map_err(From::from)
                     .map_err(ConnError::TcpConnectError)?;
@@ -206,19 +279,7 @@ impl HttpTask<PassthroughConnection> {
             None => MaybeTls::NoTls(stream),
         };
 
-        let version = match request.version() {
-            Version::HTTP_2 => HttpVersion::V2,
-            _ => HttpVersion::V1,
-        };
-        let mut sender = HttpSender::new(TokioIo::new(stream), version)
-            .await
-            .map_err(From::from)
-            .map_err(ConnError::PassthroughHttpError)?;
-        sender
-            .send(request)
-            .await
-            .map_err(From::from)
-            .map_err(ConnError::PassthroughHttpError)
+        Ok(stream)
     }
 
     /// Used for applying transformations on responses to
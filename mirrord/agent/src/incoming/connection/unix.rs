@@ -0,0 +1,50 @@
+use bytes::Bytes;
+use tokio::{net::UnixStream, sync::mpsc};
+
+use super::IncomingStream;
+use crate::incoming::{
+    IncomingStreamItem,
+    connection::copy_bidirectional::{self, StealingClient},
+};
+
+/// A connection accepted on a unix socket stolen from the target.
+///
+/// Unlike the [`RedirectedTcp`](super::tcp::RedirectedTcp), it can be neither mirrored nor passed
+/// through, as we replace the original socket.
+pub struct StolenUnix {
+    /// Dropping this stream will be interpreted as dropping the connection.
+    pub stream: IncomingStream,
+    /// Can be used to send data to the peer.
+    ///
+    /// Dropping this sender will be interpreted as a write shutdown.
+    pub data_tx: mpsc::Sender<Bytes>,
+}
+
+impl StolenUnix {
+    /// Starts the connection task in the background.
+    ///
+    /// All data will be directed to the returned handle.
+    pub fn new(mut io: UnixStream) -> Self {
+        let (incoming_tx, incoming_rx) = mpsc::channel(32);
+        let (outgoing_tx, outgoing_rx) = mpsc::channel(32);
+
+        tokio::spawn(async move {
+            let mut outgoing = StealingClient {
+                data_tx: incoming_tx,
+                data_rx: outgoing_rx,
+                mirror_data_tx: None.into(),
+            };
+
+            let result = copy_bidirectional::copy_bidirectional(&mut io, &mut outgoing).await;
+            let _ = outgoing
+                .data_tx
+                .send(IncomingStreamItem::Finished(result))
+                .await;
+        });
+
+        Self {
+            stream: IncomingStream::Steal(incoming_rx),
+            data_tx: outgoing_tx,
+        }
+    }
+}
@@ -0,0 +1,872 @@
+//! A [`PortRedirector`] implementation that does not require `CAP_NET_ADMIN`.
+//!
+//! Instead of iptables rules, it uses an eBPF program of type `BPF_PROG_TYPE_SK_LOOKUP`,
+//! attached to the target's network namespace. When the kernel looks up a socket for an incoming
+//! TCP connection, the program checks the destination port against a map of redirected ports.
+//! If the port is redirected, the program assigns the agent's listener (kept in a `SOCKMAP`) to
+//! the connection. Since there is no NAT involved, the local address of the accepted stream is the
+//! original destination of the connection.
+//!
+//! Connections to loopback addresses are never redirected. This covers the connections the agent
+//! makes when passing traffic through to its original destination (see
+//! [`ConnectionInfo::pass_through_address`](super::ConnectionInfo::pass_through_address)), which
+//! would otherwise loop back into the agent.
+//!
+//! Excluding the agent from the service mesh requires iptables, so this redirector cannot be used
+//! together with `agent.exclude_from_mesh`, see [`select_redirector`].
+//!
+//! The program is small enough to be assembled here by hand, so we don't need a BPF toolchain.
+//!
+//! Nothing is pinned to the BPF filesystem. The program and the maps live only as long as their
+//! file descriptors, so the kernel cleans them up when the agent exits, even when it's killed.
+//!
+//! Requirements:
+//! 1. Linux 5.9 or newer (`BPF_PROG_TYPE_SK_LOOKUP`),
+//! 2. `CAP_BPF` (or `CAP_SYS_ADMIN` on Linux older than 5.8).
+
+use std::{
+    fmt,
+    fs::File,
+    io, mem,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+};
+
+use mirrord_agent_env::redirector::RedirectorType;
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tracing::Level;
+
+use super::{PortRedirector, Redirected};
+
+/// `CAP_NET_ADMIN` capability number, from `linux/capability.h`.
+const CAP_NET_ADMIN: u32 = 12;
+
+/// `bpf(2)` commands, from `linux/bpf.h`.
+const BPF_MAP_CREATE: u32 = 0;
+const BPF_MAP_UPDATE_ELEM: u32 = 2;
+const BPF_MAP_DELETE_ELEM: u32 = 3;
+const BPF_PROG_LOAD: u32 = 5;
+const BPF_LINK_CREATE: u32 = 28;
+
+/// Map types, program types and attach types, from `linux/bpf.h`.
+const BPF_MAP_TYPE_HASH: u32 = 1;
+const BPF_MAP_TYPE_SOCKMAP: u32 = 15;
+const BPF_PROG_TYPE_SK_LOOKUP: u32 = 30;
+const BPF_SK_LOOKUP: u32 = 36;
+
+/// Flag for [`BPF_MAP_UPDATE_ELEM`], creates a new element or updates an existing one.
+const BPF_ANY: u64 = 0;
+
+/// Key of the IPv4 listener in the sockets map.
+const IPV4_LISTENER_KEY: u32 = 0;
+/// Key of the IPv6 listener in the sockets map.
+const IPV6_LISTENER_KEY: u32 = 1;
+
+/// Max number of redirected ports.
+const MAX_REDIRECTED_PORTS: u32 = 1024;
+
+/// Size of the buffer for the kernel verifier log.
+const VERIFIER_LOG_SIZE: usize = 64 * 1024;
+
+/// [`PortRedirector`] implementation picked by [`select_redirector`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelectedRedirector {
+    /// [`IpTablesRedirector`](super::iptables::IpTablesRedirector).
+    IpTables,
+    /// [`IpTablesRedirector`](super::iptables::IpTablesRedirector) with the native nftables
+    /// backend.
+    Nftables,
+    /// [`EbpfRedirector`].
+    Ebpf,
+}
+
+/// Resolves the requested [`RedirectorType`] into the [`PortRedirector`] implementation that the
+/// agent should use.
+///
+/// # Params
+///
+/// * `has_net_admin` - whether the agent has `CAP_NET_ADMIN`, see [`has_net_admin`].
+/// * `with_mesh_exclusion` - whether the agent's port has to be excluded from the service mesh,
+///   which can only be done with iptables (not with the native nftables backend).
+pub fn select_redirector(
+    requested: RedirectorType,
+    has_net_admin: bool,
+    with_mesh_exclusion: bool,
+) -> Result<SelectedRedirector, EbpfRedirectorError> {
+    match requested {
+        RedirectorType::IpTables => Ok(SelectedRedirector::IpTables),
+        RedirectorType::Auto if has_net_admin => Ok(SelectedRedirector::IpTables),
+        RedirectorType::Nftables | RedirectorType::Ebpf | RedirectorType::Auto
+            if with_mesh_exclusion =>
+        {
+            Err(EbpfRedirectorError::MeshExclusion)
+        }
+        RedirectorType::Nftables => Ok(SelectedRedirector::Nftables),
+        RedirectorType::Ebpf | RedirectorType::Auto => Ok(SelectedRedirector::Ebpf),
+    }
+}
+
+/// Checks whether this process has `CAP_NET_ADMIN` in its effective set.
+///
+/// If the capabilities cannot be read, assumes that it does.
+pub fn has_net_admin() -> bool {
+    procfs::process::Process::myself()
+        .and_then(|process| process.status())
+        .map(|status| status.capeff & (1 << CAP_NET_ADMIN) != 0)
+        .inspect_err(|error| {
+            tracing::warn!(%error, "Failed to read agent capabilities, assuming CAP_NET_ADMIN");
+        })
+        .unwrap_or(true)
+}
+
+/// Errors that can occur in the [`EbpfRedirector`].
+#[derive(Error, Debug)]
+pub enum EbpfRedirectorError {
+    #[error(
+        "failed to {operation}: operation not permitted, \
+        the eBPF redirector requires the `BPF` capability \
+        (`SYS_ADMIN` on Linux older than 5.8)"
+    )]
+    MissingCapability { operation: &'static str },
+
+    #[error(
+        "failed to {operation}: {error}, \
+        the eBPF redirector requires `BPF_PROG_TYPE_SK_LOOKUP` support (Linux 5.9 or newer)"
+    )]
+    Unsupported {
+        operation: &'static str,
+        #[source]
+        error: io::Error,
+    },
+
+    #[error("failed to {operation}: {error}")]
+    Bpf {
+        operation: &'static str,
+        #[source]
+        error: io::Error,
+    },
+
+    #[error("the eBPF program was rejected by the kernel verifier: {0}")]
+    Verifier(String),
+
+    #[error(
+        "excluding the agent from the service mesh requires iptables and the `NET_ADMIN` \
+        capability, it cannot be used with the eBPF or the native nftables redirector"
+    )]
+    MeshExclusion,
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl EbpfRedirectorError {
+    /// Maps a failed `bpf(2)` call into a capability or kernel support oriented error.
+    fn from_bpf(operation: &'static str, error: io::Error) -> Self {
+        match error.raw_os_error() {
+            Some(libc::EPERM) => Self::MissingCapability { operation },
+            Some(libc::EINVAL | libc::ENOSYS | libc::EOPNOTSUPP | libc::E2BIG) => {
+                Self::Unsupported { operation, error }
+            }
+            _ => Self::Bpf { operation, error },
+        }
+    }
+}
+
+/// `bpf_attr` for [`BPF_MAP_CREATE`].
+#[repr(C)]
+#[derive(Default)]
+struct MapCreateAttr {
+    map_type: u32,
+    key_size: u32,
+    value_size: u32,
+    max_entries: u32,
+    map_flags: u32,
+}
+
+/// `bpf_attr` for [`BPF_MAP_UPDATE_ELEM`] and [`BPF_MAP_DELETE_ELEM`].
+#[repr(C)]
+#[derive(Default)]
+struct MapElemAttr {
+    map_fd: u32,
+    _pad: u32,
+    key: u64,
+    value: u64,
+    flags: u64,
+}
+
+/// `bpf_attr` for [`BPF_PROG_LOAD`].
+#[repr(C)]
+#[derive(Default)]
+struct ProgLoadAttr {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    kern_version: u32,
+    prog_flags: u32,
+    prog_name: [u8; 16],
+    prog_ifindex: u32,
+    expected_attach_type: u32,
+}
+
+/// `bpf_attr` for [`BPF_LINK_CREATE`].
+#[repr(C)]
+#[derive(Default)]
+struct LinkCreateAttr {
+    prog_fd: u32,
+    target_fd: u32,
+    attach_type: u32,
+    flags: u32,
+}
+
+/// Calls `bpf(2)` with the given command and attributes.
+///
+/// Returns the file descriptor (or `0`) returned by the kernel.
+fn bpf<A>(cmd: u32, attr: &mut A) -> io::Result<RawFd> {
+    // SAFETY: `attr` is a `#[repr(C)]` prefix of `union bpf_attr` for `cmd`, with no implicit
+    // padding, and we pass its exact size.
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            cmd,
+            attr as *mut A,
+            mem::size_of::<A>() as libc::c_uint,
+        )
+    };
+
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result as RawFd)
+    }
+}
+
+/// Wraps a file descriptor returned from `bpf(2)`.
+fn owned_fd(fd: RawFd) -> OwnedFd {
+    // SAFETY: the fd was just returned from a successful `bpf(2)` call, and nobody else owns it.
+    unsafe { OwnedFd::from_raw_fd(fd) }
+}
+
+fn create_map(
+    map_type: u32,
+    key_size: u32,
+    value_size: u32,
+    max_entries: u32,
+) -> Result<OwnedFd, EbpfRedirectorError> {
+    let mut attr = MapCreateAttr {
+        map_type,
+        key_size,
+        value_size,
+        max_entries,
+        ..Default::default()
+    };
+
+    bpf(BPF_MAP_CREATE, &mut attr)
+        .map(owned_fd)
+        .map_err(|error| EbpfRedirectorError::from_bpf("create an eBPF map", error))
+}
+
+fn update_elem<K, V>(map: &OwnedFd, key: &K, value: &V) -> io::Result<()> {
+    let mut attr = MapElemAttr {
+        map_fd: map.as_raw_fd() as u32,
+        key: key as *const K as u64,
+        value: value as *const V as u64,
+        flags: BPF_ANY,
+        ..Default::default()
+    };
+
+    bpf(BPF_MAP_UPDATE_ELEM, &mut attr).map(|_| ())
+}
+
+fn delete_elem<K>(map: &OwnedFd, key: &K) -> io::Result<()> {
+    let mut attr = MapElemAttr {
+        map_fd: map.as_raw_fd() as u32,
+        key: key as *const K as u64,
+        ..Default::default()
+    };
+
+    bpf(BPF_MAP_DELETE_ELEM, &mut attr).map(|_| ())
+}
+
+/// A single eBPF instruction (`struct bpf_insn`).
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BpfInsn {
+    code: u8,
+    /// `dst_reg` in the lower 4 bits, `src_reg` in the upper 4 bits.
+    regs: u8,
+    off: i16,
+    imm: i32,
+}
+
+impl BpfInsn {
+    const fn new(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> Self {
+        Self {
+            code,
+            regs: (src << 4) | (dst & 0x0f),
+            off,
+            imm,
+        }
+    }
+
+    /// `dst = src`
+    const fn mov64_reg(dst: u8, src: u8) -> Self {
+        Self::new(0xbf, dst, src, 0, 0)
+    }
+
+    /// `dst = imm`
+    const fn mov64_imm(dst: u8, imm: i32) -> Self {
+        Self::new(0xb7, dst, 0, 0, imm)
+    }
+
+    /// `dst += imm`
+    const fn add64_imm(dst: u8, imm: i32) -> Self {
+        Self::new(0x07, dst, 0, 0, imm)
+    }
+
+    /// `dst &= imm`
+    const fn and64_imm(dst: u8, imm: i32) -> Self {
+        Self::new(0x57, dst, 0, 0, imm)
+    }
+
+    /// `dst = *(u32 *)(src + off)`
+    const fn ldx_w(dst: u8, src: u8, off: i16) -> Self {
+        Self::new(0x61, dst, src, off, 0)
+    }
+
+    /// `*(u32 *)(dst + off) = src`
+    const fn stx_w(dst: u8, off: i16, src: u8) -> Self {
+        Self::new(0x63, dst, src, off, 0)
+    }
+
+    /// `*(u32 *)(dst + off) = imm`
+    const fn st_w(dst: u8, off: i16, imm: i32) -> Self {
+        Self::new(0x62, dst, 0, off, imm)
+    }
+
+    /// `goto pc + off`
+    const fn ja(off: i16) -> Self {
+        Self::new(0x05, 0, 0, off, 0)
+    }
+
+    /// `if dst == imm goto pc + off`
+    const fn jeq_imm(dst: u8, imm: i32, off: i16) -> Self {
+        Self::new(0x15, dst, 0, off, imm)
+    }
+
+    /// `if dst != imm goto pc + off`
+    const fn jne_imm(dst: u8, imm: i32, off: i16) -> Self {
+        Self::new(0x55, dst, 0, off, imm)
+    }
+
+    /// Calls the given BPF helper.
+    const fn call(helper: i32) -> Self {
+        Self::new(0x85, 0, 0, 0, helper)
+    }
+
+    const fn exit() -> Self {
+        Self::new(0x95, 0, 0, 0, 0)
+    }
+
+    /// `dst = map`, takes 2 instructions.
+    const fn ld_map_fd(dst: u8, map_fd: RawFd) -> [Self; 2] {
+        /// `BPF_PSEUDO_MAP_FD`, from `linux/bpf.h`.
+        const PSEUDO_MAP_FD: u8 = 1;
+
+        [
+            Self::new(0x18, dst, PSEUDO_MAP_FD, 0, map_fd),
+            Self::new(0, 0, 0, 0, 0),
+        ]
+    }
+}
+
+/// Builds the `sk_lookup` program.
+///
+/// ```c
+/// SEC("sk_lookup")
+/// int redirect(struct bpf_sk_lookup *ctx) {
+///     if (ctx->protocol != IPPROTO_TCP)
+///         return SK_PASS;
+///
+///     if (ctx->family == AF_INET6) {
+///         if (!ctx->local_ip6[0] && !ctx->local_ip6[1] && !ctx->local_ip6[2]
+///             && ctx->local_ip6[3] == bpf_htonl(1))
+///             return SK_PASS;
+///     } else if ((ctx->local_ip4 & bpf_htonl(0xff000000)) == bpf_htonl(0x7f000000)) {
+///         return SK_PASS;
+///     }
+///
+///     __u32 port = ctx->local_port;
+///     if (!bpf_map_lookup_elem(&ports, &port))
+///         return SK_PASS;
+///
+///     __u32 key = ctx->family == AF_INET6 ? IPV6_LISTENER_KEY : IPV4_LISTENER_KEY;
+///     struct bpf_sock *sk = bpf_map_lookup_elem(&sockets, &key);
+///     if (!sk)
+///         return SK_PASS;
+///
+///     bpf_sk_assign(ctx, sk, 0);
+///     bpf_sk_release(sk);
+///     return SK_PASS;
+/// }
+/// ```
+fn sk_lookup_program(ports_map: RawFd, sockets_map: RawFd) -> Vec<BpfInsn> {
+    // Offsets in `struct bpf_sk_lookup`.
+    const FAMILY_OFFSET: i16 = 8;
+    const PROTOCOL_OFFSET: i16 = 12;
+    const LOCAL_IP4_OFFSET: i16 = 40;
+    const LOCAL_IP6_OFFSET: i16 = 44;
+    const LOCAL_PORT_OFFSET: i16 = 60;
+
+    // Addresses in the context are in network byte order.
+    const IPV4_FIRST_OCTET_MASK: i32 = u32::from_ne_bytes([0xff, 0, 0, 0]) as i32;
+    const IPV4_LOOPBACK_FIRST_OCTET: i32 = u32::from_ne_bytes([127, 0, 0, 0]) as i32;
+    const IPV6_LOOPBACK_LAST_WORD: i32 = u32::from_ne_bytes([0, 0, 0, 1]) as i32;
+
+    // Helper ids.
+    const MAP_LOOKUP_ELEM: i32 = 1;
+    const SK_RELEASE: i32 = 86;
+    const SK_ASSIGN: i32 = 124;
+
+    const SK_PASS: i32 = 1;
+    const IPPROTO_TCP: i32 = libc::IPPROTO_TCP;
+    const AF_INET6: i32 = libc::AF_INET6;
+
+    let [ports_lo, ports_hi] = BpfInsn::ld_map_fd(1, ports_map);
+    let [sockets_lo, sockets_hi] = BpfInsn::ld_map_fd(1, sockets_map);
+
+    vec![
+        /* 0 */ BpfInsn::mov64_reg(6, 1),
+        /* 1 */ BpfInsn::ldx_w(2, 6, PROTOCOL_OFFSET),
+        /* 2 */ BpfInsn::jne_imm(2, IPPROTO_TCP, 39),
+        /* 3 */ BpfInsn::ldx_w(2, 6, FAMILY_OFFSET),
+        /* 4 */ BpfInsn::jne_imm(2, AF_INET6, 9),
+        /* 5 */ BpfInsn::ldx_w(3, 6, LOCAL_IP6_OFFSET),
+        /* 6 */ BpfInsn::jne_imm(3, 0, 10),
+        /* 7 */ BpfInsn::ldx_w(3, 6, LOCAL_IP6_OFFSET + 4),
+        /* 8 */ BpfInsn::jne_imm(3, 0, 8),
+        /* 9 */ BpfInsn::ldx_w(3, 6, LOCAL_IP6_OFFSET + 8),
+        /* 10 */ BpfInsn::jne_imm(3, 0, 6),
+        /* 11 */ BpfInsn::ldx_w(3, 6, LOCAL_IP6_OFFSET + 12),
+        /* 12 */ BpfInsn::jeq_imm(3, IPV6_LOOPBACK_LAST_WORD, 29),
+        /* 13 */ BpfInsn::ja(3),
+        /* 14 */ BpfInsn::ldx_w(3, 6, LOCAL_IP4_OFFSET),
+        /* 15 */ BpfInsn::and64_imm(3, IPV4_FIRST_OCTET_MASK),
+        /* 16 */ BpfInsn::jeq_imm(3, IPV4_LOOPBACK_FIRST_OCTET, 25),
+        /* 17 */ BpfInsn::ldx_w(2, 6, LOCAL_PORT_OFFSET),
+        /* 18 */ BpfInsn::stx_w(10, -4, 2),
+        /* 19 */ ports_lo,
+        /* 20 */ ports_hi,
+        /* 21 */ BpfInsn::mov64_reg(2, 10),
+        /* 22 */ BpfInsn::add64_imm(2, -4),
+        /* 23 */ BpfInsn::call(MAP_LOOKUP_ELEM),
+        /* 24 */ BpfInsn::jeq_imm(0, 0, 17),
+        /* 25 */ BpfInsn::ldx_w(2, 6, FAMILY_OFFSET),
+        /* 26 */ BpfInsn::st_w(10, -8, IPV4_LISTENER_KEY as i32),
+        /* 27 */ BpfInsn::jne_imm(2, AF_INET6, 1),
+        /* 28 */ BpfInsn::st_w(10, -8, IPV6_LISTENER_KEY as i32),
+        /* 29 */ sockets_lo,
+        /* 30 */ sockets_hi,
+        /* 31 */ BpfInsn::mov64_reg(2, 10),
+        /* 32 */ BpfInsn::add64_imm(2, -8),
+        /* 33 */ BpfInsn::call(MAP_LOOKUP_ELEM),
+        /* 34 */ BpfInsn::jeq_imm(0, 0, 7),
+        /* 35 */ BpfInsn::mov64_reg(7, 0),
+        /* 36 */ BpfInsn::mov64_reg(1, 6),
+        /* 37 */ BpfInsn::mov64_reg(2, 7),
+        /* 38 */ BpfInsn::mov64_imm(3, 0),
+        /* 39 */ BpfInsn::call(SK_ASSIGN),
+        /* 40 */ BpfInsn::mov64_reg(1, 7),
+        /* 41 */ BpfInsn::call(SK_RELEASE),
+        /* 42 */ BpfInsn::mov64_imm(0, SK_PASS),
+        /* 43 */ BpfInsn::exit(),
+    ]
+}
+
+fn load_program(insns: &[BpfInsn]) -> Result<OwnedFd, EbpfRedirectorError> {
+    const LICENSE: &[u8] = b"Dual MIT/GPL\0";
+
+    let mut log = vec![0_u8; VERIFIER_LOG_SIZE];
+    let mut prog_name = [0_u8; 16];
+    prog_name[..15].copy_from_slice(b"mirrord_sk_look");
+
+    let mut attr = ProgLoadAttr {
+        prog_type: BPF_PROG_TYPE_SK_LOOKUP,
+        insn_cnt: insns.len() as u32,
+        insns: insns.as_ptr() as u64,
+        license: LICENSE.as_ptr() as u64,
+        log_level: 1,
+        log_size: log.len() as u32,
+        log_buf: log.as_mut_ptr() as u64,
+        prog_name,
+        expected_attach_type: BPF_SK_LOOKUP,
+        ..Default::default()
+    };
+
+    bpf(BPF_PROG_LOAD, &mut attr)
+        .map(owned_fd)
+        .map_err(|error| {
+            let log_len = log.iter().position(|byte| *byte == 0).unwrap_or(log.len());
+            let log = String::from_utf8_lossy(&log[..log_len]);
+
+            match error.raw_os_error() {
+                Some(libc::EPERM) => EbpfRedirectorError::from_bpf("load the eBPF program", error),
+                _ if !log.trim().is_empty() => {
+                    EbpfRedirectorError::Verifier(log.trim().to_string())
+                }
+                _ => EbpfRedirectorError::from_bpf("load the eBPF program", error),
+            }
+        })
+}
+
+/// Attaches the program to the network namespace of the current thread.
+fn attach_program(program: &OwnedFd) -> Result<OwnedFd, EbpfRedirectorError> {
+    let netns = File::open("/proc/thread-self/ns/net")?;
+
+    let mut attr = LinkCreateAttr {
+        prog_fd: program.as_raw_fd() as u32,
+        target_fd: netns.as_raw_fd() as u32,
+        attach_type: BPF_SK_LOOKUP,
+        ..Default::default()
+    };
+
+    bpf(BPF_LINK_CREATE, &mut attr)
+        .map(owned_fd)
+        .map_err(|error| {
+            EbpfRedirectorError::from_bpf("attach the eBPF program to the network namespace", error)
+        })
+}
+
+/// Objects that keep the `sk_lookup` program attached.
+struct Attached {
+    /// The BPF link, closing it detaches the program.
+    _link: OwnedFd,
+    /// The program itself.
+    _program: OwnedFd,
+    /// Map of redirected ports (`u32` port -> `u8` placeholder).
+    ports: OwnedFd,
+    /// `SOCKMAP` with the agent's listeners, see [`IPV4_LISTENER_KEY`] and
+    /// [`IPV6_LISTENER_KEY`].
+    _sockets: OwnedFd,
+}
+
+/// A [`PortRedirector`] implementation that uses an eBPF `sk_lookup` program to steer
+/// connections into its [`TcpListener`]s.
+///
+/// See the [module docs](self) for more info.
+pub struct EbpfRedirector {
+    /// Listener for redirected IPv4 connections.
+    ipv4_listener: TcpListener,
+    /// Listener for redirected IPv6 connections.
+    ipv6_listener: Option<TcpListener>,
+    /// [`None`] after [`PortRedirector::cleanup`].
+    attached: Option<Attached>,
+}
+
+impl EbpfRedirector {
+    /// Creates a new redirector and attaches the program to the network namespace of the current
+    /// thread.
+    ///
+    /// # Params
+    ///
+    /// * `support_ipv6` - whether IPv6 connections should be redirected as well.
+    #[tracing::instrument(level = Level::DEBUG, ret, err)]
+    pub fn create(support_ipv6: bool) -> Result<Self, EbpfRedirectorError> {
+        let ipv4_listener =
+            std::net::TcpListener::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0))?;
+        ipv4_listener.set_nonblocking(true)?;
+
+        let ipv6_listener = if support_ipv6 {
+            let listener =
+                std::net::TcpListener::bind(SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0))?;
+            listener.set_nonblocking(true)?;
+            Some(listener)
+        } else {
+            None
+        };
+
+        let ports = create_map(
+            BPF_MAP_TYPE_HASH,
+            mem::size_of::<u32>() as u32,
+            mem::size_of::<u8>() as u32,
+            MAX_REDIRECTED_PORTS,
+        )?;
+        let sockets = create_map(
+            BPF_MAP_TYPE_SOCKMAP,
+            mem::size_of::<u32>() as u32,
+            mem::size_of::<u64>() as u32,
+            2,
+        )?;
+
+        let listeners = [(IPV4_LISTENER_KEY, Some(&ipv4_listener))]
+            .into_iter()
+            .chain([(IPV6_LISTENER_KEY, ipv6_listener.as_ref())]);
+        for (key, listener) in listeners {
+            let Some(listener) = listener else {
+                continue;
+            };
+
+            let fd = listener.as_raw_fd() as u64;
+            update_elem(&sockets, &key, &fd).map_err(|error| {
+                EbpfRedirectorError::from_bpf("add the agent listener to the eBPF sockmap", error)
+            })?;
+        }
+
+        let program = load_program(&sk_lookup_program(ports.as_raw_fd(), sockets.as_raw_fd()))?;
+        let link = attach_program(&program)?;
+
+        Ok(Self {
+            ipv4_listener: TcpListener::from_std(ipv4_listener)?,
+            ipv6_listener: ipv6_listener.map(TcpListener::from_std).transpose()?,
+            attached: Some(Attached {
+                _link: link,
+                _program: program,
+                ports,
+                _sockets: sockets,
+            }),
+        })
+    }
+
+    fn attached(&self) -> io::Result<&Attached> {
+        self.attached
+            .as_ref()
+            .ok_or_else(|| io::Error::other("the eBPF program was already detached"))
+    }
+}
+
+impl PortRedirector for EbpfRedirector {
+    type Error = EbpfRedirectorError;
+
+    #[tracing::instrument(level = Level::DEBUG, err, ret)]
+    async fn add_redirection(&mut self, from_port: u16) -> Result<(), Self::Error> {
+        let attached = self.attached()?;
+        update_elem(&attached.ports, &u32::from(from_port), &1_u8)
+            .map_err(|error| EbpfRedirectorError::from_bpf("add a redirected port", error))
+    }
+
+    #[tracing::instrument(level = Level::DEBUG, err, ret)]
+    async fn remove_redirection(&mut self, from_port: u16) -> Result<(), Self::Error> {
+        let attached = self.attached()?;
+
+        match delete_elem(&attached.ports, &u32::from(from_port)) {
+            Err(error) if error.raw_os_error() != Some(libc::ENOENT) => Err(
+                EbpfRedirectorError::from_bpf("remove a redirected port", error),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Detaches the program and drops the maps.
+    #[tracing::instrument(level = Level::DEBUG, err, ret)]
+    async fn cleanup(&mut self) -> Result<(), Self::Error> {
+        self.attached = None;
+        Ok(())
+    }
+
+    async fn next_connection(&mut self) -> Result<Redirected, Self::Error> {
+        let (stream, source) = match &self.ipv6_listener {
+            Some(ipv6_listener) => tokio::select! {
+                result = self.ipv4_listener.accept() => result?,
+                result = ipv6_listener.accept() => result?,
+            },
+            None => self.ipv4_listener.accept().await?,
+        };
+
+        // Connections are assigned to our listener without NAT,
+        // so the local address is the original destination.
+        let destination = stream.local_addr()?;
+
+        Ok(Redirected {
+            stream,
+            source,
+            destination,
+        })
+    }
+}
+
+impl fmt::Debug for EbpfRedirector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EbpfRedirector")
+            .field("ipv4_listener", &self.ipv4_listener.local_addr().ok())
+            .field(
+                "ipv6_listener",
+                &self
+                    .ipv6_listener
+                    .as_ref()
+                    .and_then(|listener| listener.local_addr().ok()),
+            )
+            .field("attached", &self.attached.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+        ops::Not,
+    };
+
+    use mirrord_agent_env::redirector::RedirectorType;
+    use rstest::rstest;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::{
+        BpfInsn, EbpfRedirector, EbpfRedirectorError, SelectedRedirector, select_redirector,
+        sk_lookup_program,
+    };
+    use crate::incoming::PortRedirector;
+
+    /// Returns a non-loopback IPv4 address of this host, the one used for the default route.
+    ///
+    /// Connecting a UDP socket does not send anything.
+    fn non_loopback_ipv4() -> IpAddr {
+        let socket = UdpSocket::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0)).unwrap();
+        socket.connect("10.255.255.255:1").unwrap();
+        let ip = socket.local_addr().unwrap().ip();
+        assert!(ip.is_loopback().not(), "no non-loopback address available");
+        ip
+    }
+
+    /// Verifies that all jumps in the hand-assembled program land on an instruction inside of it.
+    #[test]
+    fn program_jumps_in_bounds() {
+        let program = sk_lookup_program(0, 0);
+
+        for (pc, insn) in program.iter().enumerate() {
+            if matches!(insn.code, 0x05 | 0x15 | 0x55) {
+                let target = pc as isize + 1 + insn.off as isize;
+                assert!(
+                    (0..program.len() as isize).contains(&target),
+                    "jump at {pc} out of bounds"
+                );
+                // The second half of a `ld_map_fd` is not a valid jump target.
+                assert_ne!(
+                    program[target as usize],
+                    BpfInsn::new(0, 0, 0, 0, 0),
+                    "jump at {pc} into the middle of an instruction"
+                );
+            }
+        }
+
+        assert_eq!(program.last(), Some(&BpfInsn::exit()));
+    }
+
+    #[rstest]
+    #[case::iptables(
+        RedirectorType::IpTables,
+        false,
+        false,
+        Some(SelectedRedirector::IpTables)
+    )]
+    #[case::iptables_mesh(
+        RedirectorType::IpTables,
+        false,
+        true,
+        Some(SelectedRedirector::IpTables)
+    )]
+    #[case::nftables(
+        RedirectorType::Nftables,
+        true,
+        false,
+        Some(SelectedRedirector::Nftables)
+    )]
+    #[case::nftables_mesh(RedirectorType::Nftables, true, true, None)]
+    #[case::ebpf(RedirectorType::Ebpf, true, false, Some(SelectedRedirector::Ebpf))]
+    #[case::ebpf_no_net_admin(RedirectorType::Ebpf, false, false, Some(SelectedRedirector::Ebpf))]
+    #[case::ebpf_mesh(RedirectorType::Ebpf, true, true, None)]
+    #[case::auto_net_admin(RedirectorType::Auto, true, false, Some(SelectedRedirector::IpTables))]
+    #[case::auto_net_admin_mesh(
+        RedirectorType::Auto,
+        true,
+        true,
+        Some(SelectedRedirector::IpTables)
+    )]
+    #[case::auto_no_net_admin(RedirectorType::Auto, false, false, Some(SelectedRedirector::Ebpf))]
+    #[case::auto_no_net_admin_mesh(RedirectorType::Auto, false, true, None)]
+    fn redirector_selection(
+        #[case] requested: RedirectorType,
+        #[case] has_net_admin: bool,
+        #[case] with_mesh_exclusion: bool,
+        #[case] expected: Option<SelectedRedirector>,
+    ) {
+        let result = select_redirector(requested, has_net_admin, with_mesh_exclusion);
+
+        match expected {
+            Some(expected) => assert_eq!(result.unwrap(), expected),
+            None => assert!(matches!(result, Err(EbpfRedirectorError::MeshExclusion))),
+        }
+    }
+
+    /// Verifies that a connection made to a redirected port lands in the [`EbpfRedirector`].
+    ///
+    /// Requires Linux 5.9+ and `CAP_BPF`, run with `cargo test -- --ignored` as root.
+    #[tokio::test]
+    #[ignore = "requires Linux 5.9+ and CAP_BPF"]
+    async fn redirects_connection() {
+        let original = TcpListener::bind(SocketAddr::new(non_loopback_ipv4(), 0))
+            .await
+            .unwrap();
+        let original_addr = original.local_addr().unwrap();
+
+        let mut redirector = EbpfRedirector::create(false).unwrap();
+        redirector
+            .add_redirection(original_addr.port())
+            .await
+            .unwrap();
+
+        let mut client = TcpStream::connect(original_addr).await.unwrap();
+        let mut redirected = redirector.next_connection().await.unwrap();
+        assert_eq!(redirected.destination, original_addr);
+        assert_eq!(redirected.source, client.local_addr().unwrap());
+
+        client.write_all(b"hello").await.unwrap();
+        let mut buf = [0_u8; 5];
+        redirected.stream.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+
+        redirector
+            .remove_redirection(original_addr.port())
+            .await
+            .unwrap();
+        redirector.cleanup().await.unwrap();
+
+        let _client = TcpStream::connect(original_addr).await.unwrap();
+        original.accept().await.unwrap();
+    }
+
+    /// Verifies that connections to loopback addresses are not redirected, e.g. the connections
+    /// the agent makes when passing traffic through.
+    ///
+    /// Requires Linux 5.9+ and `CAP_BPF`, run with `cargo test -- --ignored` as root.
+    #[tokio::test]
+    #[ignore = "requires Linux 5.9+ and CAP_BPF"]
+    async fn passes_loopback_through() {
+        let original = TcpListener::bind(SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), 0))
+            .await
+            .unwrap();
+        let port = original.local_addr().unwrap().port();
+
+        let mut redirector = EbpfRedirector::create(false).unwrap();
+        redirector.add_redirection(port).await.unwrap();
+
+        let _client = TcpStream::connect(SocketAddr::new(Ipv4Addr::LOCALHOST.into(), port))
+            .await
+            .unwrap();
+        original.accept().await.unwrap();
+
+        let _client = TcpStream::connect(SocketAddr::new(non_loopback_ipv4(), port))
+            .await
+            .unwrap();
+        redirector.next_connection().await.unwrap();
+
+        redirector.cleanup().await.unwrap();
+    }
+}
@@ -1,25 +1,100 @@
 use std::{
+    collections::HashSet,
     fmt, io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     ops::Not,
+    sync::Arc,
+    time::Duration,
 };
 
 use mirrord_agent_env::envs;
-use mirrord_agent_iptables::{IPTablesWrapper, SafeIpTables, error::IPTablesError};
+use mirrord_agent_iptables::{
+    ChainNames, IPTablesWrapper, RedirectExclusions, SafeIpTables,
+    error::{IPTablesError, IPTablesResult},
+    nftables::NftRedirect,
+};
 use nix::sys::socket::{
     self, SockaddrIn, SockaddrIn6,
     sockopt::{Ip6tOriginalDst, OriginalDst},
 };
-use tokio::net::TcpListener;
+use tokio::{
+    net::TcpListener,
+    sync::Mutex,
+    time::{self, MissedTickBehavior},
+};
+use tokio_util::sync::{CancellationToken, DropGuard};
 use tracing::Level;
 
 use super::{PortRedirector, Redirected};
 
+/// Backend used by the [`IpTablesRedirector`] to alter the rules.
+enum RedirectRules {
+    /// iptables/ip6tables rules in the shared `nat` table.
+    IpTables(SafeIpTables<IPTablesWrapper>),
+    /// Native nftables rules in a dedicated table.
+    Nftables(NftRedirect),
+}
+
+impl RedirectRules {
+    async fn add_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
+        match self {
+            Self::IpTables(iptables) => iptables.add_redirect(redirected_port, target_port).await,
+            Self::Nftables(nftables) => nftables.add_redirect(redirected_port, target_port).await,
+        }
+    }
+
+    async fn restore_chains(&self) -> IPTablesResult<()> {
+        match self {
+            Self::IpTables(iptables) => iptables.restore_chains().await,
+            // Our table is not shared, so nobody else flushes it.
+            Self::Nftables(..) => Ok(()),
+        }
+    }
+
+    async fn restore_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
+        match self {
+            Self::IpTables(iptables) => {
+                iptables
+                    .restore_redirect(redirected_port, target_port)
+                    .await
+            }
+            // Adding an existing element to the map does nothing.
+            Self::Nftables(nftables) => nftables.add_redirect(redirected_port, target_port).await,
+        }
+    }
+
+    async fn remove_redirect(&self, redirected_port: u16, target_port: u16) -> IPTablesResult<()> {
+        match self {
+            Self::IpTables(iptables) => {
+                iptables.remove_redirect(redirected_port, target_port).await
+            }
+            Self::Nftables(nftables) => {
+                nftables.remove_redirect(redirected_port, target_port).await
+            }
+        }
+    }
+
+    async fn cleanup(&self) -> IPTablesResult<()> {
+        match self {
+            Self::IpTables(iptables) => iptables.cleanup().await,
+            Self::Nftables(nftables) => nftables.cleanup().await,
+        }
+    }
+}
+
 /// A [`PortRedirector`] implementation that uses a [`TcpListener`]
-/// and an iptables/ip6tables wrapper to set rules that send traffic to that listener.
+/// and an iptables/ip6tables wrapper (or native nftables) to set rules that send traffic to that
+/// listener.
 pub struct IpTablesRedirector {
-    /// For altering iptables/ip6tables rules.
-    iptables: Option<SafeIpTables<IPTablesWrapper>>,
+    /// For altering iptables/ip6tables or nftables rules.
+    ///
+    /// Shared with the reconciliation task.
+    iptables: Option<Arc<RedirectRules>>,
+    /// Ports that are currently redirected.
+    ///
+    /// Locked for the whole time the rules are being altered, so that the reconciliation task
+    /// never restores a redirection that is being removed.
+    redirections: Arc<Mutex<HashSet<u16>>>,
     /// Port of [`Self::listener`](Self::listener).
     ///
     /// Kept as a field, so that we don't have to call [`TcpListener::local_addr`]
@@ -37,6 +112,14 @@ pub struct IpTablesRedirector {
     ipv6: bool,
     /// Should exclude agent port in iptables
     with_mesh_exclusion: Option<u16>,
+    /// Traffic that is never redirected.
+    exclusions: RedirectExclusions,
+    /// Whether to use native nftables rules instead of iptables/ip6tables.
+    native_nftables: bool,
+    /// How often to restore the redirection rules removed by other processes, if at all.
+    reconcile_interval: Option<Duration>,
+    /// Stops the reconciliation task when dropped.
+    reconcile: Option<DropGuard>,
 }
 
 impl IpTablesRedirector {
@@ -48,12 +131,19 @@ impl IpTablesRedirector {
     ///   on their destination port).
     /// * `pod_ips` - list of pod IPs, will be used in iptables/ip6tables rules.
     /// * `ipv6` - whether to redirect IPv4 or IPv6 traffic.
+    /// * `native_nftables` - whether to use native nftables rules instead of iptables/ip6tables.
+    /// * `exclusions` - traffic that is never redirected, only the CIDRs matching `ipv6` are used.
+    /// * `reconcile_interval` - if set, the redirection rules are periodically checked, and the
+    ///   ones removed by other processes are restored.
     #[tracing::instrument(level = Level::DEBUG, ret, err)]
     pub async fn create(
         flush_connections: bool,
         pod_ips: &[IpAddr],
         ipv6: bool,
         with_mesh_exclusion: Option<u16>,
+        native_nftables: bool,
+        exclusions: &RedirectExclusions,
+        reconcile_interval: Option<Duration>,
     ) -> io::Result<Self> {
         let listener_addr = if ipv6 {
             SocketAddr::new(Ipv6Addr::UNSPECIFIED.into(), 0)
@@ -72,24 +162,59 @@ impl IpTablesRedirector {
 
         Ok(Self {
             iptables: None,
+            redirections: Default::default(),
             redirect_to: listener_addr,
             listener,
             pod_ips: pod_ips.is_empty().not().then_some(pod_ips),
             flush_connections,
             ipv6,
             with_mesh_exclusion,
+            native_nftables,
+            exclusions: exclusions.clone(),
+            reconcile_interval,
+            reconcile: None,
         })
     }
 
     pub async fn init_iptables(&mut self) -> Result<(), IPTablesError> {
+        let rules = if self.native_nftables {
+            RedirectRules::Nftables(
+                NftRedirect::create(self.pod_ips.as_deref(), self.ipv6, &self.exclusions).await?,
+            )
+        } else {
+            RedirectRules::IpTables(self.init_safe_iptables().await?)
+        };
+        let rules = Arc::new(rules);
+
+        if let Some(interval) = self.reconcile_interval {
+            let cancellation_token = CancellationToken::new();
+            let reconcile = Self::reconcile(
+                rules.clone(),
+                self.redirections.clone(),
+                self.redirect_to,
+                interval,
+            );
+            let task_token = cancellation_token.clone();
+            tokio::spawn(async move { task_token.run_until_cancelled(reconcile).await });
+            self.reconcile = Some(cancellation_token.drop_guard());
+        }
+
+        self.iptables = Some(rules);
+
+        Ok(())
+    }
+
+    async fn init_safe_iptables(&self) -> Result<SafeIpTables<IPTablesWrapper>, IPTablesError> {
         let ntfables = envs::NFTABLES.try_from_env().unwrap_or_default();
         let iptables = mirrord_agent_iptables::get_iptables(ntfables, self.ipv6);
         let iptables = SafeIpTables::create(
             iptables,
+            &ChainNames::from_env(),
             self.flush_connections,
             self.pod_ips.as_deref(),
             self.ipv6,
             self.with_mesh_exclusion.is_some(),
+            &self.exclusions,
         )
         .await?;
 
@@ -98,13 +223,44 @@ impl IpTablesRedirector {
         {
             tracing::error!(
                 %error,
-                "Failed to add exclusion to redirector",
+                port,
+                ipv6 = self.ipv6,
+                "Failed to exclude the agent port from the service mesh",
             )
         };
 
-        self.iptables = Some(iptables);
+        Ok(iptables)
+    }
 
-        Ok(())
+    /// Every `interval`, restores our chains with their exclusions, and the rules of all
+    /// `redirections`, if they were removed by other processes, e.g. by a restarted CNI plugin
+    /// that flushed our chains.
+    async fn reconcile(
+        rules: Arc<RedirectRules>,
+        redirections: Arc<Mutex<HashSet<u16>>>,
+        redirect_to: u16,
+        interval: Duration,
+    ) {
+        let mut interval = time::interval(interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes immediately.
+        interval.tick().await;
+
+        loop {
+            interval.tick().await;
+
+            let redirections = redirections.lock().await;
+            if let Err(error) = rules.restore_chains().await {
+                tracing::warn!(%error, "Failed to restore the redirection chains");
+                continue;
+            }
+
+            for &port in redirections.iter() {
+                if let Err(error) = rules.restore_redirect(port, redirect_to).await {
+                    tracing::warn!(%error, port, "Failed to restore a port redirection");
+                }
+            }
+        }
     }
 }
 
@@ -112,7 +268,7 @@ impl PortRedirector for IpTablesRedirector {
     type Error = IPTablesError;
 
     async fn initialize(&mut self) -> Result<(), Self::Error> {
-        // create itpabels guard only if used for exclusion.
+        // Create the rules early only if used for the mesh exclusion, for both IPv4 and IPv6.
         if self.iptables.is_none() && self.with_mesh_exclusion.is_some() {
             self.init_iptables().await?;
         }
@@ -127,7 +283,9 @@ impl PortRedirector for IpTablesRedirector {
         }
 
         if let Some(iptables) = self.iptables.as_ref() {
+            let mut redirections = self.redirections.lock().await;
             iptables.add_redirect(from_port, self.redirect_to).await?;
+            redirections.insert(from_port);
         }
 
         Ok(())
@@ -136,6 +294,8 @@ impl PortRedirector for IpTablesRedirector {
     #[tracing::instrument(level = Level::DEBUG, err, ret)]
     async fn remove_redirection(&mut self, from_port: u16) -> Result<(), Self::Error> {
         if let Some(iptables) = self.iptables.as_ref() {
+            let mut redirections = self.redirections.lock().await;
+            redirections.remove(&from_port);
             iptables
                 .remove_redirect(from_port, self.redirect_to)
                 .await?;
@@ -146,13 +306,21 @@ impl PortRedirector for IpTablesRedirector {
 
     #[tracing::instrument(level = Level::DEBUG, err, ret)]
     async fn cleanup(&mut self) -> Result<(), Self::Error> {
+        // Stop the reconciliation before removing the rules, and wait for its pass to finish.
+        self.reconcile.take();
+        let mut redirections = self.redirections.lock().await;
+        redirections.clear();
+
         if let Some(iptables) = self.iptables.take() {
-            if let Some((exclusion, port)) = iptables.exclusion().zip(self.with_mesh_exclusion)
+            if let RedirectRules::IpTables(iptables) = iptables.as_ref()
+                && let Some((exclusion, port)) = iptables.exclusion().zip(self.with_mesh_exclusion)
                 && let Err(error) = exclusion.remove_exclusion(port)
             {
                 tracing::error!(
                     %error,
-                    "Failed to add exclusion to redirector",
+                    port,
+                    ipv6 = self.ipv6,
+                    "Failed to remove the agent port exclusion from the service mesh",
                 )
             };
 
@@ -208,6 +376,9 @@ impl fmt::Debug for IpTablesRedirector {
             .field("flush_connections", &self.flush_connections)
             .field("ipv6", &self.ipv6)
             .field("with_mesh_exclusion", &self.with_mesh_exclusion)
+            .field("native_nftables", &self.native_nftables)
+            .field("exclusions", &self.exclusions)
+            .field("reconcile_interval", &self.reconcile_interval)
             .finish()
     }
 }
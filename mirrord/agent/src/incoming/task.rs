@@ -2,6 +2,7 @@ use std::{
     collections::{HashMap, hash_map::Entry},
     error::{Error, Report},
     fmt,
+    num::NonZeroU32,
     ops::Not,
     sync::Arc,
 };
@@ -218,6 +219,7 @@ where
                 local_addr,
                 peer_addr: source,
                 tls_connector: None,
+                passthrough_h2: Default::default(),
             };
 
             let shutdown = state.shutdown.child_token();
@@ -294,7 +296,11 @@ where
 
         let tx = self.internal_tx.clone();
         let token = port_state.shutdown.clone();
-        let mut requests = ExtractedRequests::new(TokioIo::new(conn.stream), http_version);
+        let mut requests = ExtractedRequests::new(
+            TokioIo::new(conn.stream),
+            http_version,
+            self.config.max_pending_requests,
+        );
 
         Self::spawn_tracked_connection(self.internal_tx.clone(), port, port_state, async move {
             let mut shutting_down = false;
@@ -558,12 +564,27 @@ impl<R> fmt::Debug for RedirectorTask<R> {
 pub struct RedirectorTaskConfig {
     /// Inject `Mirrord-Agent` headers into responses to stolen requests
     pub inject_headers: bool,
+    /// Max number of requests from a single HTTP connection waiting for their responses.
+    pub max_pending_requests: Option<NonZeroU32>,
 }
 
 impl RedirectorTaskConfig {
     pub fn from_env() -> Self {
+        let max_pending_requests = match envs::MAX_PENDING_REQUESTS.try_from_env() {
+            Ok(limit) => limit.and_then(NonZeroU32::new),
+            Err(error) => {
+                tracing::warn!(
+                    ?error,
+                    "failed to parse {}, not limiting pending requests",
+                    envs::MAX_PENDING_REQUESTS.name
+                );
+                None
+            }
+        };
+
         Self {
             inject_headers: envs::INJECT_HEADERS.from_env_or_default(),
+            max_pending_requests,
         }
     }
 }
@@ -10,6 +10,8 @@ use std::process::ExitCode;
 #[cfg(target_os = "linux")]
 use crate::{entrypoint::IPTABLES_DIRTY_EXIT_CODE, error::AgentError};
 
+#[cfg(target_os = "linux")]
+mod agent_logs;
 #[cfg(target_os = "linux")]
 mod cli;
 #[cfg(target_os = "linux")]
@@ -1,16 +1,27 @@
 use std::{
-    net::SocketAddr,
+    io,
     sync::{
         Arc, LazyLock,
         atomic::{AtomicUsize, Ordering},
     },
 };
 
-use axum::{Router, extract::State, routing::get};
-use http::StatusCode;
+use axum::{
+    Router,
+    extract::{Request, State},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::get,
+};
+use http::{StatusCode, header::AUTHORIZATION};
+use hyper::body::Incoming;
+use hyper_util::rt::TokioIo;
+use mirrord_agent_env::metrics::MetricsAddress;
 use prometheus::{GaugeVec, IntGauge, Registry, proto::MetricFamily};
-use tokio::net::TcpListener;
+use subtle::ConstantTimeEq;
+use tokio::net::{TcpListener, UnixListener};
 use tokio_util::sync::CancellationToken;
+use tower::Service;
 use tracing::Level;
 
 use crate::error::AgentError;
@@ -277,6 +288,10 @@ impl Metrics {
     }
 }
 
+/// The gauges are registered in the global prometheus [`Registry`], so [`Metrics`] can be created
+/// only once.
+static METRICS: LazyLock<Arc<Metrics>> = LazyLock::new(|| Arc::new(Metrics::new()));
+
 /// `GET /metrics`
 ///
 /// Prepares all the metrics with [`Metrics::gather_metrics`], and responds to the prometheus
@@ -295,41 +310,123 @@ async fn get_metrics(State(state): State<Arc<Metrics>>) -> (StatusCode, String)
     }
 }
 
+/// Middleware that rejects requests without the expected bearer token in the `Authorization`
+/// header with [`StatusCode::UNAUTHORIZED`].
+async fn require_bearer_token(
+    State(token): State<Arc<str>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let authorized = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        // Compare in constant time, so that the token can't be guessed from response times.
+        .is_some_and(|provided| provided.as_bytes().ct_eq(token.as_bytes()).into());
+
+    if authorized {
+        next.run(request).await
+    } else {
+        StatusCode::UNAUTHORIZED.into_response()
+    }
+}
+
+/// Serves the metrics [`Router`] on a unix socket, until the `cancellation_token` is cancelled.
+///
+/// [`axum::serve`] supports only [`TcpListener`]s, so we drive the connections with [`hyper`].
+async fn serve_unix(
+    listener: UnixListener,
+    app: Router,
+    cancellation_token: CancellationToken,
+) -> io::Result<()> {
+    loop {
+        let (stream, _) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = cancellation_token.cancelled() => break Ok(()),
+        };
+
+        let app = app.clone();
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |request: http::Request<Incoming>| {
+                app.clone().call(request)
+            });
+
+            if let Err(error) = hyper::server::conn::http1::Builder::new()
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                tracing::debug!(%error, "Metrics connection failed");
+            }
+        });
+    }
+}
+
 /// Starts the mirrord-agent prometheus metrics service.
 ///
 /// You can get the metrics from `GET address/metrics`.
 ///
-/// - `address`: comes from a mirrord-agent config.
+/// - `address`: comes from a mirrord-agent config, either a TCP address or a unix socket path;
+/// - `token`: when set, requests must carry it in the `Authorization: Bearer` header.
 #[tracing::instrument(level = Level::TRACE, skip_all, ret ,err)]
 pub(crate) async fn start_metrics(
-    address: SocketAddr,
+    address: MetricsAddress,
+    token: Option<String>,
     cancellation_token: CancellationToken,
 ) -> Result<(), axum::BoxError> {
-    let metrics_state = Arc::new(Metrics::new());
-
-    let app = Router::new()
-        .route("/metrics", get(get_metrics))
-        .with_state(metrics_state);
+    let app = metrics_router(token);
+    let cancel_on_error = cancellation_token.clone();
 
-    let listener = TcpListener::bind(address)
-        .await
-        .map_err(AgentError::from)
-        .inspect_err(|fail| {
-            tracing::error!(?fail, "Failed to bind TCP socket for metrics server")
-        })?;
+    match address {
+        MetricsAddress::Tcp(address) => {
+            let listener = TcpListener::bind(address)
+                .await
+                .map_err(AgentError::from)
+                .inspect_err(|fail| {
+                    tracing::error!(?fail, "Failed to bind TCP socket for metrics server")
+                })?;
 
-    let cancel_on_error = cancellation_token.clone();
-    axum::serve(listener, app)
-        .with_graceful_shutdown(async move { cancellation_token.cancelled().await })
-        .await
-        .inspect_err(|fail| {
-            tracing::error!(%fail, "Could not start agent metrics server!");
-            cancel_on_error.cancel();
-        })?;
+            axum::serve(listener, app)
+                .with_graceful_shutdown(async move { cancellation_token.cancelled().await })
+                .await
+        }
+        MetricsAddress::Unix(path) => {
+            let listener = UnixListener::bind(&path)
+                .map_err(AgentError::from)
+                .inspect_err(|fail| {
+                    tracing::error!(
+                        ?fail,
+                        ?path,
+                        "Failed to bind unix socket for metrics server"
+                    )
+                })?;
+
+            serve_unix(listener, app, cancellation_token).await
+        }
+    }
+    .inspect_err(|fail| {
+        tracing::error!(%fail, "Could not start agent metrics server!");
+        cancel_on_error.cancel();
+    })?;
 
     Ok(())
 }
 
+/// Builds the metrics [`Router`], that requires the bearer `token` if it's given.
+fn metrics_router(token: Option<String>) -> Router {
+    let app = Router::new()
+        .route("/metrics", get(get_metrics))
+        .with_state(METRICS.clone());
+
+    match token {
+        Some(token) => app.layer(middleware::from_fn_with_state(
+            Arc::<str>::from(token),
+            require_bearer_token,
+        )),
+        None => app,
+    }
+}
+
 /// A guard for a [`GaugeVec`] that decrements the metric value on `Drop`.
 pub(crate) struct GaugeVecMetricGuard {
     /// The metric itself.
@@ -384,10 +481,14 @@ impl Drop for MetricGuard {
 mod tests {
     use std::{sync::atomic::Ordering, time::Duration};
 
+    use http::StatusCode;
+    use http_body_util::{BodyExt, Empty};
+    use hyper_util::rt::TokioIo;
+    use tokio::net::{TcpListener, UnixStream};
     use tokio_util::sync::CancellationToken;
 
     use super::OPEN_FD_COUNT;
-    use crate::metrics::start_metrics;
+    use crate::metrics::{metrics_router, start_metrics};
 
     #[tokio::test]
     async fn test_metrics() {
@@ -396,7 +497,7 @@ mod tests {
 
         let metrics_cancellation = cancellation_token.child_token();
         tokio::spawn(async move {
-            start_metrics(metrics_address, metrics_cancellation)
+            start_metrics(metrics_address, None, metrics_cancellation)
                 .await
                 .unwrap()
         });
@@ -419,4 +520,100 @@ mod tests {
 
         cancellation_token.drop_guard();
     }
+
+    /// Requests without the expected bearer token are rejected with
+    /// [`StatusCode::UNAUTHORIZED`].
+    #[tokio::test]
+    async fn metrics_require_token() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let metrics_url = format!("http://{}/metrics", listener.local_addr().unwrap());
+        let cancellation_token = CancellationToken::new();
+
+        let metrics_cancellation = cancellation_token.child_token();
+        tokio::spawn(async move {
+            axum::serve(listener, metrics_router(Some("secret-token".to_string())))
+                .with_graceful_shutdown(async move { metrics_cancellation.cancelled().await })
+                .await
+                .unwrap()
+        });
+
+        let client = reqwest::Client::new();
+
+        let status = client.get(&metrics_url).send().await.unwrap().status();
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        for token in ["wrong-token", "secret-toke", "secret-token-"] {
+            let status = client
+                .get(&metrics_url)
+                .bearer_auth(token)
+                .send()
+                .await
+                .unwrap()
+                .status();
+            assert_eq!(
+                status,
+                StatusCode::UNAUTHORIZED,
+                "token `{token}` was accepted"
+            );
+        }
+
+        let get_all_metrics = client
+            .get(&metrics_url)
+            .bearer_auth("secret-token")
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(get_all_metrics.contains("mirrord_agent_open_fd_count"));
+
+        cancellation_token.drop_guard();
+    }
+
+    /// Metrics can be served on a unix socket.
+    #[tokio::test]
+    async fn metrics_unix_socket() {
+        let directory = tempfile::tempdir().unwrap();
+        let socket_path = directory.path().join("metrics.sock");
+        let metrics_address = format!("unix://{}", socket_path.display()).parse().unwrap();
+        let cancellation_token = CancellationToken::new();
+
+        let metrics_cancellation = cancellation_token.child_token();
+        tokio::spawn(async move {
+            start_metrics(metrics_address, None, metrics_cancellation)
+                .await
+                .unwrap()
+        });
+
+        // Give the server some time to start.
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        let stream = UnixStream::connect(&socket_path).await.unwrap();
+        let (mut sender, connection) = hyper::client::conn::http1::handshake(TokioIo::new(stream))
+            .await
+            .unwrap();
+        tokio::spawn(connection);
+
+        let response = sender
+            .send_request(
+                http::Request::get("/metrics")
+                    .header(http::header::HOST, "localhost")
+                    .body(Empty::<bytes::Bytes>::new())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert!(
+            String::from_utf8_lossy(&body).contains("mirrord_agent_client_count"),
+            "unexpected metrics: {body:?}"
+        );
+
+        cancellation_token.drop_guard();
+    }
 }
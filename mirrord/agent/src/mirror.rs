@@ -1,17 +1,19 @@
 use std::{
     collections::{HashMap, VecDeque},
     error::Report,
+    num::{NonZeroU32, NonZeroU64},
     ops::{Not, RangeInclusive},
 };
 
 use futures::StreamExt;
+use mirrord_agent_env::envs;
 use mirrord_protocol::{
     ConnectionId, DaemonMessage, LogMessage, Port, RequestId,
     tcp::{
         ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, DaemonTcp,
-        HttpRequestMetadata, IncomingTrafficTransportType, InternalHttpBodyNew,
-        InternalHttpRequest, LayerTcp, MODE_AGNOSTIC_HTTP_REQUESTS, NewTcpConnectionV1,
-        NewTcpConnectionV2, TcpClose, TcpData,
+        HttpRequestMetadata, IncomingTrafficTransportType, InternalHttpBodyFrame,
+        InternalHttpBodyNew, InternalHttpRequest, LayerTcp, MODE_AGNOSTIC_HTTP_REQUESTS,
+        NewTcpConnectionV1, NewTcpConnectionV2, TcpClose, TcpData,
     },
 };
 use tokio::task::JoinSet;
@@ -29,12 +31,66 @@ use crate::{
     util::protocol_version::ClientProtocolVersion,
 };
 
+mod udp;
+
+pub(crate) use udp::UdpMirrorApi;
+
+/// Limits that protect the agent and the target's node from mirror-induced overload.
+///
+/// Traffic over the limits is not mirrored, but it still reaches the original destination.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MirrorLimits {
+    /// Max number of connections (and HTTP requests) mirrored to the client at the same time.
+    pub max_connections: Option<NonZeroU32>,
+    /// Max number of bytes mirrored to the client from a single connection (or HTTP request).
+    pub max_connection_bytes: Option<NonZeroU64>,
+}
+
+impl MirrorLimits {
+    pub fn from_env() -> Self {
+        let max_connections = match envs::MAX_MIRRORED_CONNECTIONS.try_from_env() {
+            Ok(limit) => limit.and_then(NonZeroU32::new),
+            Err(error) => {
+                tracing::warn!(
+                    ?error,
+                    "failed to parse {}, not limiting mirrored connections",
+                    envs::MAX_MIRRORED_CONNECTIONS.name
+                );
+                None
+            }
+        };
+
+        let max_connection_bytes = match envs::MAX_MIRRORED_CONNECTION_BYTES.try_from_env() {
+            Ok(limit) => limit.and_then(NonZeroU64::new),
+            Err(error) => {
+                tracing::warn!(
+                    ?error,
+                    "failed to parse {}, not limiting mirrored bytes",
+                    envs::MAX_MIRRORED_CONNECTION_BYTES.name
+                );
+                None
+            }
+        };
+
+        Self {
+            max_connections,
+            max_connection_bytes,
+        }
+    }
+}
+
 /// Agent client's API for using the TCP mirror feature.
 ///
 /// Wrapper over a [`MirrorHandle`].
 pub struct TcpMirrorApi {
     mirror_handle: MirrorHandle,
     incoming_streams: StreamMap<ConnectionId, IncomingStream>,
+    /// Bytes mirrored so far from each of the [`Self::incoming_streams`], tracked only when
+    /// [`MirrorLimits::max_connection_bytes`] is set.
+    mirrored_bytes: HashMap<ConnectionId, u64>,
+    limits: MirrorLimits,
+    /// Whether we've already warned the client about hitting one of the [`MirrorLimits`].
+    limits_warned: bool,
     protocol_version: ClientProtocolVersion,
     connection_ids_iter: RangeInclusive<ConnectionId>,
     queued_messages: VecDeque<DaemonTcp>,
@@ -51,10 +107,17 @@ impl TcpMirrorApi {
     /// Since `mirrord-intproxy` processes requests independently, this is fine.
     const REQUEST_ID: RequestId = 0;
 
-    pub fn new(mirror_handle: MirrorHandle, protocol_version: ClientProtocolVersion) -> Self {
+    pub fn new(
+        mirror_handle: MirrorHandle,
+        protocol_version: ClientProtocolVersion,
+        limits: MirrorLimits,
+    ) -> Self {
         Self {
             mirror_handle,
             incoming_streams: Default::default(),
+            mirrored_bytes: Default::default(),
+            limits,
+            limits_warned: false,
             protocol_version,
             connection_ids_iter: 0..=ConnectionId::MAX,
             queued_messages: Default::default(),
@@ -67,6 +130,7 @@ impl TcpMirrorApi {
         match message {
             LayerTcp::ConnectionUnsubscribe(id) => {
                 self.incoming_streams.remove(&id);
+                self.mirrored_bytes.remove(&id);
             }
             LayerTcp::PortSubscribe(port) => {
                 self.mirror_handle.mirror(port).await?;
@@ -176,147 +240,391 @@ impl TcpMirrorApi {
         }
     }
 
-    pub async fn recv(&mut self) -> AgentResult<DaemonMessage> {
-        if let Some(message) = self.queued_messages.pop_front() {
-            return Ok(DaemonMessage::Tcp(message));
+    /// Returns whether a new mirrored connection would exceed
+    /// [`MirrorLimits::max_connections`].
+    fn at_connection_limit(&self) -> bool {
+        self.limits
+            .max_connections
+            .is_some_and(|max| self.incoming_streams.len() >= max.get() as usize)
+    }
+
+    /// Adds the bytes carried by the `item` to the connection's count, and returns whether
+    /// [`MirrorLimits::max_connection_bytes`] is exceeded.
+    fn exceeds_byte_limit(&mut self, id: ConnectionId, item: &IncomingStreamItem) -> bool {
+        let Some(max) = self.limits.max_connection_bytes else {
+            return false;
+        };
+
+        let bytes = match item {
+            IncomingStreamItem::Data(data) => data.len(),
+            IncomingStreamItem::Frame(InternalHttpBodyFrame::Data(data)) => data.len(),
+            _ => return false,
+        };
+
+        let mirrored = self.mirrored_bytes.entry(id).or_default();
+        *mirrored = mirrored.saturating_add(bytes as u64);
+        *mirrored > max.get()
+    }
+
+    /// Returns a warning for the client, but only when the first of the [`MirrorLimits`] is hit,
+    /// so that we don't flood the client with logs.
+    fn limit_warning(&mut self, message: String) -> Option<DaemonMessage> {
+        if self.limits_warned {
+            return None;
         }
 
-        let message = tokio::select! {
-            Some((id, item)) = self.incoming_streams.next() => match item {
-                IncomingStreamItem::Data(data) => DaemonTcp::Data(TcpData {
-                    connection_id: id,
-                    bytes: data.into(),
-                }),
-                IncomingStreamItem::NoMoreData => DaemonTcp::Data(TcpData {
-                    connection_id: id,
-                    bytes: Default::default(),
-                }),
-                IncomingStreamItem::Frame(frame) => {
-                    DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(ChunkedRequestBodyV1 {
-                        frames: vec![frame],
-                        is_last: false,
-                        connection_id: id,
-                        request_id: Self::REQUEST_ID,
-                    }))
-                }
-                IncomingStreamItem::NoMoreFrames => {
-                    DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(ChunkedRequestBodyV1 {
-                        frames: Default::default(),
-                        is_last: true,
-                        connection_id: id,
-                        request_id: Self::REQUEST_ID,
-                    }))
-                }
-                IncomingStreamItem::Finished(Ok(())) => {
-                    DaemonTcp::Close(TcpClose { connection_id: id })
-                }
-                IncomingStreamItem::Finished(Err(error)) => {
-                    self.queued_messages.push_back(DaemonTcp::Close(TcpClose { connection_id: id }));
-                    return Ok(DaemonMessage::LogMessage(LogMessage::warn(format!(
-                        "Mirrored connection {id} failed: {}",
-                        Report::new(error)
-                    ))));
-                }
-            },
-
-            traffic = Self::next(&mut self.mirror_handle, &mut self.ongoing_requests, &self.protocol_version, &self.port_filters) => match traffic? {
-                MirroredTraffic::Tcp(tcp) if self.protocol_version.matches(&MODE_AGNOSTIC_HTTP_REQUESTS) => {
-                    let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
-                    let connection = NewTcpConnectionV1 {
-                        connection_id: id,
-                        remote_address: tcp.info.peer_addr.ip(),
-                        destination_port: tcp.info.original_destination.port(),
-                        source_port: tcp.info.peer_addr.port(),
-                        local_address: tcp.info.local_addr.ip(),
-                    };
-                    let message = NewTcpConnectionV2 {
-                        connection,
-                        transport: tcp
-                            .info
-                            .tls_connector
-                            .map(|tls| IncomingTrafficTransportType::Tls {
-                                alpn_protocol: tls.alpn_protocol().map(From::from),
-                                server_name: tls.server_name().map(|s| s.to_str().into_owned()),
-                            })
-                            .unwrap_or(IncomingTrafficTransportType::Tcp),
-                    };
-                    self.incoming_streams.insert(id, tcp.stream);
-                    DaemonTcp::NewConnectionV2(message)
-                }
+        self.limits_warned = true;
+        Some(DaemonMessage::LogMessage(LogMessage::warn(message)))
+    }
+
+    pub async fn recv(&mut self) -> AgentResult<DaemonMessage> {
+        loop {
+            if let Some(message) = self.queued_messages.pop_front() {
+                return Ok(DaemonMessage::Tcp(message));
+            }
+
+            let message = tokio::select! {
+                Some((id, item)) = self.incoming_streams.next() => {
+                    if self.exceeds_byte_limit(id, &item) {
+                        self.incoming_streams.remove(&id);
+                        self.mirrored_bytes.remove(&id);
+                        self.queued_messages.push_back(DaemonTcp::Close(TcpClose { connection_id: id }));
+                        match self.limit_warning(format!(
+                            "Mirrored connection {id} exceeded `agent.max_mirrored_connection_bytes` \
+                            and is no longer mirrored, it still reaches the remote application"
+                        )) {
+                            Some(warning) => return Ok(warning),
+                            None => continue,
+                        }
+                    }
+
+                    match item {
+                        IncomingStreamItem::Data(data) => DaemonTcp::Data(TcpData {
+                            connection_id: id,
+                            bytes: data.into(),
+                        }),
+                        IncomingStreamItem::NoMoreData => DaemonTcp::Data(TcpData {
+                            connection_id: id,
+                            bytes: Default::default(),
+                        }),
+                        IncomingStreamItem::Frame(frame) => {
+                            DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(ChunkedRequestBodyV1 {
+                                frames: vec![frame],
+                                is_last: false,
+                                connection_id: id,
+                                request_id: Self::REQUEST_ID,
+                            }))
+                        }
+                        IncomingStreamItem::NoMoreFrames => {
+                            DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(ChunkedRequestBodyV1 {
+                                frames: Default::default(),
+                                is_last: true,
+                                connection_id: id,
+                                request_id: Self::REQUEST_ID,
+                            }))
+                        }
+                        IncomingStreamItem::Finished(Ok(())) => {
+                            self.mirrored_bytes.remove(&id);
+                            DaemonTcp::Close(TcpClose { connection_id: id })
+                        }
+                        IncomingStreamItem::Finished(Err(error)) => {
+                            self.mirrored_bytes.remove(&id);
+                            self.queued_messages.push_back(DaemonTcp::Close(TcpClose { connection_id: id }));
+                            return Ok(DaemonMessage::LogMessage(LogMessage::warn(format!(
+                                "Mirrored connection {id} failed: {}",
+                                Report::new(error)
+                            ))));
+                        }
+                    }
+                },
+
+                traffic = Self::next(&mut self.mirror_handle, &mut self.ongoing_requests, &self.protocol_version, &self.port_filters) => match traffic? {
+                    MirroredTraffic::Tcp(..) | MirroredTraffic::Http(..) if self.at_connection_limit() => {
+                        match self.limit_warning(
+                            "Reached `agent.max_mirrored_connections`, new connections are not \
+                            mirrored, they still reach the remote application".to_string()
+                        ) {
+                            Some(warning) => return Ok(warning),
+                            None => continue,
+                        }
+                    }
+
+                    MirroredTraffic::Tcp(tcp) if self.protocol_version.matches(&MODE_AGNOSTIC_HTTP_REQUESTS) => {
+                        let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
+                        let connection = NewTcpConnectionV1 {
+                            connection_id: id,
+                            remote_address: tcp.info.peer_addr.ip(),
+                            destination_port: tcp.info.original_destination.port(),
+                            source_port: tcp.info.peer_addr.port(),
+                            local_address: tcp.info.local_addr.ip(),
+                        };
+                        let message = NewTcpConnectionV2 {
+                            connection,
+                            transport: tcp
+                                .info
+                                .tls_connector
+                                .map(|tls| IncomingTrafficTransportType::Tls {
+                                    alpn_protocol: tls.alpn_protocol().map(From::from),
+                                    server_name: tls.server_name().map(|s| s.to_str().into_owned()),
+                                })
+                                .unwrap_or(IncomingTrafficTransportType::Tcp),
+                        };
+                        self.incoming_streams.insert(id, tcp.stream);
+                        DaemonTcp::NewConnectionV2(message)
+                    }
+
+                    MirroredTraffic::Tcp(tcp) => {
+                        if tcp.info.tls_connector.is_some() {
+                            return Ok(DaemonMessage::LogMessage(LogMessage::error(format!(
+                                "A TLS connection was not mirrored due to mirrord-protocol version requirement: {}",
+                                &*MODE_AGNOSTIC_HTTP_REQUESTS,
+                            ))));
+                        }
 
-                MirroredTraffic::Tcp(tcp) => {
-                    if tcp.info.tls_connector.is_some() {
+                        if self.port_filters.contains_key(&tcp.info.original_destination.port()) {
+                            return Ok(DaemonMessage::LogMessage(LogMessage::warn(
+                                "TCP traffic skipped due to HTTP filter on this port".to_string()
+                            )));
+                        }
+
+                        let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
+                        self.incoming_streams.insert(id, tcp.stream);
+
+                        let message = NewTcpConnectionV1 {
+                            connection_id: id,
+                            remote_address: tcp.info.peer_addr.ip(),
+                            destination_port: tcp.info.original_destination.port(),
+                            source_port: tcp.info.peer_addr.port(),
+                            local_address: tcp.info.local_addr.ip(),
+                        };
+                        DaemonTcp::NewConnectionV1(message)
+                    }
+
+                    MirroredTraffic::Http(http) if self.protocol_version.matches(&MODE_AGNOSTIC_HTTP_REQUESTS) => {
+                        let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
+
+                        self.incoming_streams.insert(id, http.stream);
+
+                        let message = ChunkedRequestStartV2 {
+                            connection_id: id,
+                            request_id: Self::REQUEST_ID,
+                            metadata: HttpRequestMetadata::V1 {
+                                source: http.info.peer_addr,
+                                destination: http.info.original_destination,
+                            },
+                            transport: http
+                                .info
+                                .tls_connector
+                                .as_ref()
+                                .map(|tls| IncomingTrafficTransportType::Tls {
+                                    alpn_protocol: tls.alpn_protocol().map(From::from),
+                                    server_name: tls.server_name().map(|s| s.to_str().into_owned()),
+                                })
+                                .unwrap_or(IncomingTrafficTransportType::Tcp),
+                            request: InternalHttpRequest {
+                                method: http.request_head.parts.method,
+                                uri: http.request_head.parts.uri,
+                                headers: http.request_head.parts.headers,
+                                version: http.request_head.parts.version,
+                                body: InternalHttpBodyNew {
+                                    frames: http.request_head.body_head,
+                                    is_last: http.request_head.body_finished,
+                                },
+                            },
+                        };
+                        DaemonTcp::HttpRequestChunked(ChunkedRequest::StartV2(message))
+                    }
+
+                    MirroredTraffic::Http(..) => {
                         return Ok(DaemonMessage::LogMessage(LogMessage::error(format!(
-                            "A TLS connection was not mirrored due to mirrord-protocol version requirement: {}",
+                            "An HTTP request was not mirrored due to mirrord-protocol version requirement: {}",
                             &*MODE_AGNOSTIC_HTTP_REQUESTS,
                         ))));
                     }
+                },
 
-                    if self.port_filters.contains_key(&tcp.info.original_destination.port()) {
-                        return Ok(DaemonMessage::LogMessage(LogMessage::warn(
-                            "TCP traffic skipped due to HTTP filter on this port".to_string()
-                        )));
-                    }
+                else => std::future::pending().await,
+            };
 
-                    let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
-                    self.incoming_streams.insert(id, tcp.stream);
-
-                    let message = NewTcpConnectionV1 {
-                        connection_id: id,
-                        remote_address: tcp.info.peer_addr.ip(),
-                        destination_port: tcp.info.original_destination.port(),
-                        source_port: tcp.info.peer_addr.port(),
-                        local_address: tcp.info.local_addr.ip(),
-                    };
-                    DaemonTcp::NewConnectionV1(message)
-                }
+            return Ok(DaemonMessage::Tcp(message));
+        }
+    }
+}
 
-                MirroredTraffic::Http(http) if self.protocol_version.matches(&MODE_AGNOSTIC_HTTP_REQUESTS) => {
-                    let id = self.connection_ids_iter.next().ok_or(AgentError::ExhaustedConnectionId)?;
+#[cfg(test)]
+mod test {
+    use std::{
+        num::{NonZeroU32, NonZeroU64},
+        time::Duration,
+    };
 
-                    self.incoming_streams.insert(id, http.stream);
+    use mirrord_protocol::{
+        DaemonMessage,
+        tcp::{ChunkedRequest, DaemonTcp, Filter, HttpFilter, LayerTcp, TcpClose},
+    };
+    use rstest::rstest;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
 
-                    let message = ChunkedRequestStartV2 {
-                        connection_id: id,
-                        request_id: Self::REQUEST_ID,
-                        metadata: HttpRequestMetadata::V1 {
-                            source: http.info.peer_addr,
-                            destination: http.info.original_destination,
-                        },
-                        transport: http
-                            .info
-                            .tls_connector
-                            .as_ref()
-                            .map(|tls| IncomingTrafficTransportType::Tls {
-                                alpn_protocol: tls.alpn_protocol().map(From::from),
-                                server_name: tls.server_name().map(|s| s.to_str().into_owned()),
-                            })
-                            .unwrap_or(IncomingTrafficTransportType::Tcp),
-                        request: InternalHttpRequest {
-                            method: http.request_head.parts.method,
-                            uri: http.request_head.parts.uri,
-                            headers: http.request_head.parts.headers,
-                            version: http.request_head.parts.version,
-                            body: InternalHttpBodyNew {
-                                frames: http.request_head.body_head,
-                                is_last: http.request_head.body_finished,
-                            },
-                        },
-                    };
-                    DaemonTcp::HttpRequestChunked(ChunkedRequest::StartV2(message))
-                }
+    use super::{MirrorLimits, TcpMirrorApi};
+    use crate::incoming::{RedirectorTask, RedirectorTaskConfig, test::DummyRedirector};
 
-                MirroredTraffic::Http(..) => {
-                    return Ok(DaemonMessage::LogMessage(LogMessage::error(format!(
-                        "An HTTP request was not mirrored due to mirrord-protocol version requirement: {}",
-                        &*MODE_AGNOSTIC_HTTP_REQUESTS,
-                    ))));
-                }
-            },
+    /// Subscribes a [`TcpMirrorApi`] with the given `limits` to the port of `listener`, and makes
+    /// `count` non-HTTP connections to it.
+    ///
+    /// Returns the messages received from the api until the first [`DaemonMessage::LogMessage`].
+    async fn mirror_until_warning(
+        limits: MirrorLimits,
+        count: usize,
+    ) -> (TcpMirrorApi, Vec<DaemonMessage>) {
+        let (redirector, state, mut tx) = DummyRedirector::new();
+        let (task, _, mirror_handle) = RedirectorTask::new(
+            redirector,
+            Default::default(),
+            RedirectorTaskConfig::from_env(),
+        );
+        tokio::spawn(task.run());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let destination = listener.local_addr().unwrap();
 
-            else => std::future::pending().await,
+        let mut api = TcpMirrorApi::new(mirror_handle, "1.30.0".parse().unwrap(), limits);
+        api.handle_client_message(LayerTcp::PortSubscribe(destination.port()))
+            .await
+            .unwrap();
+        assert!(matches!(
+            api.recv().await.unwrap(),
+            DaemonMessage::Tcp(DaemonTcp::SubscribeResult(Ok(..)))
+        ));
+
+        let mut connections = Vec::new();
+        for _ in 0..count {
+            let mut conn = tx.make_connection(destination).await;
+            conn.write_all(b"definitely not http\r\n\r\n")
+                .await
+                .unwrap();
+            let (original, _) = listener.accept().await.unwrap();
+            connections.push((conn, original));
+        }
+
+        let mut messages = Vec::new();
+        loop {
+            let message = api.recv().await.unwrap();
+            let is_warning = matches!(message, DaemonMessage::LogMessage(..));
+            messages.push(message);
+            if is_warning {
+                break;
+            }
+        }
+
+        // Keep the redirector and the connections alive until we're done.
+        drop((state, tx, connections));
+
+        (api, messages)
+    }
+
+    /// Verifies that connections over [`MirrorLimits::max_connections`] are not mirrored.
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test]
+    async fn mirrored_connections_limit() {
+        let limits = MirrorLimits {
+            max_connections: NonZeroU32::new(1),
+            ..Default::default()
         };
+        let (_, messages) = mirror_until_warning(limits, 2).await;
 
-        Ok(DaemonMessage::Tcp(message))
+        let new_connections = messages
+            .iter()
+            .filter(|message| matches!(message, DaemonMessage::Tcp(DaemonTcp::NewConnectionV2(..))))
+            .count();
+        assert_eq!(new_connections, 1, "{messages:?}");
+    }
+
+    /// Verifies that a connection is closed on the client side when it exceeds
+    /// [`MirrorLimits::max_connection_bytes`].
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test]
+    async fn mirrored_connection_bytes_limit() {
+        let limits = MirrorLimits {
+            max_connection_bytes: NonZeroU64::new(4),
+            ..Default::default()
+        };
+        let (mut api, messages) = mirror_until_warning(limits, 1).await;
+
+        let Some(DaemonMessage::Tcp(DaemonTcp::NewConnectionV2(connection))) = messages.first()
+        else {
+            panic!("expected a new mirrored connection: {messages:?}");
+        };
+        let mirrored_bytes = messages
+            .iter()
+            .map(|message| match message {
+                DaemonMessage::Tcp(DaemonTcp::Data(data)) => data.bytes.len(),
+                _ => 0,
+            })
+            .sum::<usize>();
+        assert!(mirrored_bytes <= 4, "{messages:?}");
+
+        assert_eq!(
+            api.recv().await.unwrap(),
+            DaemonMessage::Tcp(DaemonTcp::Close(TcpClose {
+                connection_id: connection.connection.connection_id,
+            }))
+        );
+    }
+
+    /// Verifies that only the requests matching the filter are mirrored, while the original
+    /// destination still receives all of them.
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    #[tokio::test]
+    async fn filtered_http_mirroring() {
+        let (redirector, _state, mut tx) = DummyRedirector::new();
+        let (task, _, mirror_handle) = RedirectorTask::new(
+            redirector,
+            Default::default(),
+            RedirectorTaskConfig::from_env(),
+        );
+        tokio::spawn(task.run());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let destination = listener.local_addr().unwrap();
+
+        let mut api =
+            TcpMirrorApi::new(mirror_handle, "1.30.0".parse().unwrap(), Default::default());
+        let filter = HttpFilter::Path(Filter::new("^/api".into()).unwrap());
+        api.handle_client_message(LayerTcp::PortSubscribeFilteredHttp(
+            destination.port(),
+            filter,
+        ))
+        .await
+        .unwrap();
+        assert!(matches!(
+            api.recv().await.unwrap(),
+            DaemonMessage::Tcp(DaemonTcp::SubscribeResult(Ok(..)))
+        ));
+
+        for path in ["/health", "/api/v1"] {
+            let mut conn = tx.make_connection(destination).await;
+            conn.write_all(format!("GET {path} HTTP/1.1\r\nHost: test\r\n\r\n").as_bytes())
+                .await
+                .unwrap();
+
+            let (mut original, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0; 64];
+            let read = original.read(&mut buf).await.unwrap();
+            let received = String::from_utf8_lossy(buf.get(..read).unwrap_or_default());
+            assert!(received.starts_with(&format!("GET {path} ")), "{received}");
+        }
+
+        let DaemonMessage::Tcp(DaemonTcp::HttpRequestChunked(ChunkedRequest::StartV2(request))) =
+            api.recv().await.unwrap()
+        else {
+            panic!("expected a mirrored HTTP request");
+        };
+        assert_eq!(request.request.uri.path(), "/api/v1");
     }
 }
@@ -0,0 +1,321 @@
+//! UDP mirror feature.
+//!
+//! Unlike TCP connections, UDP datagrams can't be redirected to the agent without taking them away
+//! from the target. Instead, [`UdpMirrorTask`] opens a raw IPv4 socket in the target's network
+//! namespace. The kernel delivers a copy of every incoming UDP datagram to raw sockets, before
+//! passing the datagram on to the socket of the target.
+//!
+//! Requires `CAP_NET_RAW`. IPv6 datagrams are not mirrored.
+
+use std::{
+    collections::HashSet,
+    fmt,
+    io::{self, Read},
+    net::{Ipv4Addr, SocketAddr},
+    ops::Not,
+};
+
+use mirrord_protocol::{
+    DaemonMessage, Payload, Port,
+    udp::{DaemonUdp, LayerUdp, UDP_MIRROR_VERSION, UdpDatagram},
+};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::{
+    io::unix::AsyncFd,
+    select,
+    sync::mpsc::{self, Receiver, Sender, error::SendError},
+};
+use tracing::Level;
+
+use crate::{
+    error::AgentResult,
+    task::{
+        BgTaskRuntime,
+        status::{BgTaskStatus, IntoStatus},
+    },
+    util::protocol_version::ClientProtocolVersion,
+};
+
+/// Task that handles [`LayerUdp`] and [`DaemonUdp`] messages.
+///
+/// We start these tasks from the [`UdpMirrorApi`] on a [`BgTaskRuntime`].
+struct UdpMirrorTask {
+    /// Ports subscribed by the client.
+    ports: HashSet<Port>,
+    /// Raw socket that receives copies of incoming UDP datagrams.
+    ///
+    /// Opened with the first subscription and closed when the last one is removed, so that
+    /// clients that don't mirror UDP don't pay for it.
+    socket: Option<AsyncFd<Socket>>,
+    /// Buffer for the packets read from [`Self::socket`].
+    buffer: Vec<u8>,
+    layer_rx: Receiver<LayerUdp>,
+    daemon_tx: Sender<DaemonUdp>,
+}
+
+impl fmt::Debug for UdpMirrorTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UdpMirrorTask")
+            .field("ports", &self.ports)
+            .field("socket_open", &self.socket.is_some())
+            .finish()
+    }
+}
+
+impl UdpMirrorTask {
+    /// Maximal size of an IPv4 packet.
+    const MAX_PACKET_SIZE: usize = 64 * 1024;
+
+    fn new(layer_rx: Receiver<LayerUdp>, daemon_tx: Sender<DaemonUdp>) -> Self {
+        Self {
+            ports: Default::default(),
+            socket: None,
+            buffer: vec![0; Self::MAX_PACKET_SIZE],
+            layer_rx,
+            daemon_tx,
+        }
+    }
+
+    /// Runs this task as long as the channels connecting it with the [`UdpMirrorApi`] are open.
+    #[tracing::instrument(level = Level::TRACE, skip(self))]
+    async fn run(mut self) -> io::Result<()> {
+        loop {
+            let channel_closed = select! {
+                message = self.layer_rx.recv() => match message {
+                    Some(message) => self.handle_layer_msg(message).await.is_err(),
+                    None => true,
+                },
+
+                read = Self::read_packet(self.socket.as_ref(), &mut self.buffer) => {
+                    let length = read?;
+                    match self.buffer.get(..length).and_then(parse_datagram) {
+                        Some(datagram) if self.ports.contains(&datagram.destination.port()) => {
+                            self.daemon_tx.send(DaemonUdp::Datagram(datagram)).await.is_err()
+                        }
+                        _ => false,
+                    }
+                },
+            };
+
+            if channel_closed {
+                tracing::trace!("Client channel closed, exiting");
+                break Ok(());
+            }
+        }
+    }
+
+    /// Reads the next packet from the raw socket into the given buffer.
+    ///
+    /// Never resolves if the socket is not open.
+    async fn read_packet(socket: Option<&AsyncFd<Socket>>, buffer: &mut [u8]) -> io::Result<usize> {
+        let Some(socket) = socket else {
+            return std::future::pending().await;
+        };
+
+        loop {
+            let mut guard = socket.readable().await?;
+            match guard.try_io(|inner| inner.get_ref().read(buffer)) {
+                Ok(result) => break result,
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn open_socket() -> io::Result<AsyncFd<Socket>> {
+        let socket = Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::UDP))?;
+        socket.set_nonblocking(true)?;
+        AsyncFd::new(socket)
+    }
+
+    /// Returns [`Err`] only when the client has disconnected.
+    #[tracing::instrument(level = Level::TRACE, err(level = Level::TRACE))]
+    async fn handle_layer_msg(&mut self, message: LayerUdp) -> Result<(), SendError<DaemonUdp>> {
+        match message {
+            LayerUdp::PortSubscribe(port) => {
+                let result = match self.socket {
+                    Some(..) => Ok(()),
+                    None => Self::open_socket()
+                        .map(|socket| {
+                            self.socket.replace(socket);
+                        })
+                        .inspect_err(|error| {
+                            tracing::error!(%error, "Failed to open a raw socket for UDP mirroring")
+                        }),
+                };
+
+                let result = result.map(|()| {
+                    self.ports.insert(port);
+                    port
+                });
+
+                self.daemon_tx
+                    .send(DaemonUdp::SubscribeResult(result.map_err(From::from)))
+                    .await
+            }
+            LayerUdp::PortUnsubscribe(port) => {
+                self.ports.remove(&port);
+                if self.ports.is_empty() {
+                    self.socket = None;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Parses an IPv4 packet read from the raw socket.
+///
+/// Returns [`None`] if the packet does not contain a complete UDP datagram.
+fn parse_datagram(packet: &[u8]) -> Option<UdpDatagram> {
+    let version_and_length = *packet.first()?;
+    if version_and_length >> 4 != 4 || *packet.get(9)? != libc::IPPROTO_UDP as u8 {
+        return None;
+    }
+
+    let header_length = usize::from(version_and_length & 0x0f) * 4;
+    let source_ip = Ipv4Addr::from(<[u8; 4]>::try_from(packet.get(12..16)?).ok()?);
+    let destination_ip = Ipv4Addr::from(<[u8; 4]>::try_from(packet.get(16..20)?).ok()?);
+
+    let udp = packet.get(header_length..)?;
+    let read_u16 = |offset: usize| {
+        udp.get(offset..offset + 2)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(u16::from_be_bytes)
+    };
+    let source_port = read_u16(0)?;
+    let destination_port = read_u16(2)?;
+    let length = usize::from(read_u16(4)?);
+    let payload = udp.get(8..length)?;
+
+    Some(UdpDatagram {
+        source: SocketAddr::new(source_ip.into(), source_port),
+        destination: SocketAddr::new(destination_ip.into(), destination_port),
+        bytes: Payload::from(payload.to_vec()),
+    })
+}
+
+/// Agent client's API for using the UDP mirror feature.
+///
+/// Each agent client has their own independent instance (neither this wrapper nor the background
+/// task are shared).
+pub(crate) struct UdpMirrorApi {
+    task_status: BgTaskStatus,
+    layer_tx: Sender<LayerUdp>,
+    daemon_rx: Receiver<DaemonUdp>,
+    protocol_version: ClientProtocolVersion,
+}
+
+impl UdpMirrorApi {
+    /// Spawns a new [`UdpMirrorTask`] on the given `runtime`, which must be in the target's
+    /// network namespace.
+    pub(crate) fn new(runtime: &BgTaskRuntime, protocol_version: ClientProtocolVersion) -> Self {
+        // IMPORTANT: this makes tokio tasks spawn on `runtime`.
+        // Do not remove this.
+        let _rt = runtime.handle().enter();
+
+        let (layer_tx, layer_rx) = mpsc::channel(1000);
+        let (daemon_tx, daemon_rx) = mpsc::channel(1000);
+
+        let task_status = tokio::spawn(UdpMirrorTask::new(layer_rx, daemon_tx).run())
+            .into_status("UdpMirrorTask");
+
+        Self {
+            task_status,
+            layer_tx,
+            daemon_rx,
+            protocol_version,
+        }
+    }
+
+    /// Passes the [`LayerUdp`] message to the background task.
+    ///
+    /// Messages from clients that don't match [`UDP_MIRROR_VERSION`] are ignored.
+    #[tracing::instrument(level = Level::TRACE, skip(self), err)]
+    pub(crate) async fn handle_client_message(&mut self, message: LayerUdp) -> AgentResult<()> {
+        if self.protocol_version.matches(&UDP_MIRROR_VERSION).not() {
+            tracing::warn!(
+                ?message,
+                "Client sent a UDP mirror message, but its protocol version does not support it"
+            );
+            return Ok(());
+        }
+
+        if self.layer_tx.send(message).await.is_ok() {
+            Ok(())
+        } else {
+            Err(self.task_status.wait_assert_running().await)
+        }
+    }
+
+    /// Receives the next [`DaemonUdp`] message from the background task.
+    pub(crate) async fn recv(&mut self) -> AgentResult<DaemonMessage> {
+        match self.daemon_rx.recv().await {
+            Some(message) => Ok(DaemonMessage::Udp(message)),
+            None => Err(self.task_status.wait_assert_running().await),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_datagram;
+
+    /// Builds an IPv4 packet with a UDP datagram, checksums are left empty.
+    fn packet(options: &[u8], udp_length: u16, payload: &[u8]) -> Vec<u8> {
+        let header_length = 20 + options.len();
+        let mut packet = vec![
+            0x40 | (header_length / 4) as u8,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            64,
+            17,
+        ];
+        packet.extend([0, 0]);
+        packet.extend([10, 0, 0, 7]);
+        packet.extend([10, 0, 0, 2]);
+        packet.extend(options);
+        packet.extend(41000_u16.to_be_bytes());
+        packet.extend(8125_u16.to_be_bytes());
+        packet.extend(udp_length.to_be_bytes());
+        packet.extend([0, 0]);
+        packet.extend(payload);
+        packet
+    }
+
+    #[test]
+    fn parse_valid_datagram() {
+        let datagram = parse_datagram(&packet(&[], 20, b"requests:1|c")).unwrap();
+        assert_eq!(datagram.source, "10.0.0.7:41000".parse().unwrap());
+        assert_eq!(datagram.destination, "10.0.0.2:8125".parse().unwrap());
+        assert_eq!(datagram.bytes.0, b"requests:1|c".as_slice());
+    }
+
+    #[test]
+    fn parse_datagram_with_ip_options() {
+        let datagram = parse_datagram(&packet(&[1, 1, 1, 1], 12, b"meow")).unwrap();
+        assert_eq!(datagram.destination.port(), 8125);
+        assert_eq!(datagram.bytes.0, b"meow".as_slice());
+    }
+
+    #[test]
+    fn parse_truncated_datagram() {
+        assert!(parse_datagram(&packet(&[], 100, b"meow")).is_none());
+        assert!(parse_datagram(&packet(&[], 4, b"meow")).is_none());
+        assert!(parse_datagram(&[0x45, 0, 0]).is_none());
+    }
+
+    #[test]
+    fn parse_not_udp() {
+        let mut packet = packet(&[], 12, b"meow");
+        if let Some(protocol) = packet.get_mut(9) {
+            *protocol = libc::IPPROTO_TCP as u8;
+        }
+        assert!(parse_datagram(&packet).is_none());
+    }
+}
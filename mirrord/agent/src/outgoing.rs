@@ -14,7 +14,7 @@ use mirrord_protocol::{
     outgoing::{tcp::*, *},
     uid::Uid,
 };
-use socket_stream::SocketStream;
+use socket_stream::{SeqPacketReader, SeqPacketStream, SocketStream};
 use streammap_ext::StreamMap;
 use tokio::{
     io::{self, AsyncWriteExt, ReadHalf, WriteHalf},
@@ -158,7 +158,8 @@ impl TcpOutgoingTask {
     const READ_BUFFER_SIZE: usize = 64 * 1024;
     /// How much incoming data we can accumulate in memory, before it's flushed to the client.
     ///
-    /// This **must** be larger than [`Self::READ_BUFFER_SIZE`].
+    /// This **must** be larger than [`Self::READ_BUFFER_SIZE`] and
+    /// [`SeqPacketStream::MAX_PACKET_SIZE`].
     const THROTTLE_PERMITS: usize = 512 * 1024;
 
     /// Timeout for connect attempts.
@@ -322,19 +323,25 @@ impl TcpOutgoingTask {
         Ok(())
     }
 
+    /// Connects to the given address, with a `SOCK_SEQPACKET` unix socket if `seqpacket` is set.
     async fn connect(
         remote_address: SocketAddress,
         target_pid: Option<u64>,
+        seqpacket: bool,
     ) -> RemoteResult<Connected> {
         let started_at = Instant::now();
-        let socket_stream = tokio::time::timeout(
-            Self::CONNECT_TIMEOUT,
-            SocketStream::connect(remote_address.clone(), target_pid),
-        )
-        .await
-        .map_err(|_| {
-            ResponseError::Remote(RemoteError::ConnectTimedOut(remote_address.clone()))
-        })??;
+        let connect = async {
+            if seqpacket {
+                SocketStream::connect_seqpacket(remote_address.clone(), target_pid).await
+            } else {
+                SocketStream::connect(remote_address.clone(), target_pid).await
+            }
+        };
+        let socket_stream = tokio::time::timeout(Self::CONNECT_TIMEOUT, connect)
+            .await
+            .map_err(|_| {
+                ResponseError::Remote(RemoteError::ConnectTimedOut(remote_address.clone()))
+            })??;
         tracing::debug!(
             %remote_address,
             elapsed = ?started_at.elapsed(),
@@ -357,14 +364,26 @@ impl TcpOutgoingTask {
             let connection_id = self.next_connection_id;
             self.next_connection_id += 1;
 
-            let (read_half, write_half) = io::split(connected.stream);
-            self.writers.insert(connection_id, write_half);
+            let reader = match connected.stream {
+                // Packets must be read one by one, `ReaderStream` could merge or split them.
+                SocketStream::SeqPacket(stream) => {
+                    let reader = PeerReader::SeqPacket(stream.packets());
+                    let (_, write_half) = io::split(SocketStream::SeqPacket(stream));
+                    self.writers.insert(connection_id, write_half);
+                    reader
+                }
+                stream => {
+                    let (read_half, write_half) = io::split(stream);
+                    self.writers.insert(connection_id, write_half);
+                    PeerReader::Stream(ReaderStream::with_capacity(
+                        read_half,
+                        Self::READ_BUFFER_SIZE,
+                    ))
+                }
+            };
             self.readers.insert(
                 connection_id,
-                ThrottledStream::new(
-                    ReaderStream::with_capacity(read_half, Self::READ_BUFFER_SIZE),
-                    self.throttler.clone(),
-                ),
+                ThrottledStream::new(reader, self.throttler.clone()),
             );
             TCP_OUTGOING_CONNECTION.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
@@ -398,7 +417,7 @@ impl TcpOutgoingTask {
             // We make connection to the requested address, split the stream into halves with
             // `io::split`, and put them into respective maps.
             LayerTcpOutgoing::Connect(LayerConnect { remote_address }) => {
-                let fut = Self::connect(remote_address, self.pid).boxed();
+                let fut = Self::connect(remote_address, self.pid, false).boxed();
                 self.connects_v1.push(fut);
                 Ok(())
             }
@@ -407,7 +426,20 @@ impl TcpOutgoingTask {
                 uid,
                 remote_address,
             }) => {
-                let fut = Self::connect(remote_address, self.pid)
+                let fut = Self::connect(remote_address, self.pid, false)
+                    .map(move |result| (result, uid))
+                    .boxed();
+                self.connects_v2.push(fut);
+                Ok(())
+            }
+
+            // Same as above, but the connection uses a `SOCK_SEQPACKET` unix socket. Each write
+            // from the layer is sent as one packet, and each read carries one packet.
+            LayerTcpOutgoing::ConnectSeqPacket(LayerConnectV2 {
+                uid,
+                remote_address,
+            }) => {
+                let fut = Self::connect(remote_address, self.pid, true)
                     .map(move |result| (result, uid))
                     .boxed();
                 self.connects_v2.push(fut);
@@ -512,7 +544,25 @@ impl TcpOutgoingTask {
     }
 }
 
-type TcpReadStream = ThrottledStream<ReaderStream<ReadHalf<SocketStream>>>;
+type TcpReadStream = ThrottledStream<PeerReader>;
+
+/// Reading half of a peer connection.
+enum PeerReader {
+    Stream(ReaderStream<ReadHalf<SocketStream>>),
+    /// Yields whole packets, see [`SeqPacketStream`].
+    SeqPacket(SeqPacketReader),
+}
+
+impl Stream for PeerReader {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.get_mut() {
+            Self::Stream(stream) => Pin::new(stream).poll_next(cx),
+            Self::SeqPacket(stream) => Pin::new(stream).poll_next(cx),
+        }
+    }
+}
 
 /// Established outgoing connection.
 struct Connected {
@@ -1,30 +1,36 @@
 use std::{
     ffi::OsStr,
     io::{self, Error},
+    net::Shutdown,
     os::{
         linux::net::SocketAddrExt,
         unix::{ffi::OsStrExt, net::SocketAddr},
     },
     pin::Pin,
+    sync::Arc,
     task::{Context, Poll},
 };
 
+use bytes::{Bytes, BytesMut};
+use futures::Stream;
 use mirrord_protocol::{
     RemoteError, RemoteResult, ResponseError,
     outgoing::{SocketAddress, UnixAddr},
 };
+use socket2::{Domain, SockAddr, Socket, Type};
 use tokio::{
-    io::{AsyncRead, AsyncWrite, ReadBuf},
+    io::{AsyncRead, AsyncWrite, ReadBuf, unix::AsyncFd},
     net::{TcpStream, UnixStream},
 };
 
 use crate::util::path_resolver::InTargetPathResolver;
 
 /// An enum that can mostly be used like tokio's [`TcpStream`] and [`UnixStream`], but can hold
-/// either of them.
+/// either of them, or a [`SeqPacketStream`].
 pub enum SocketStream {
     Ip(TcpStream),
     Unix(UnixStream),
+    SeqPacket(SeqPacketStream),
 }
 
 impl From<TcpStream> for SocketStream {
@@ -59,6 +65,7 @@ impl SocketStream {
 
                 SocketAddress::Unix(addr)
             }
+            SocketStream::SeqPacket(stream) => stream.local_addr()?,
         })
     }
 
@@ -89,6 +96,31 @@ impl SocketStream {
             }
         }
     }
+
+    /// Connect to a given unix [`SocketAddress`] with a `SOCK_SEQPACKET` socket.
+    pub async fn connect_seqpacket(addr: SocketAddress, pid: Option<u64>) -> RemoteResult<Self> {
+        let address = match addr {
+            SocketAddress::Unix(UnixAddr::Pathname(path)) => {
+                // Same as in `Self::connect`.
+                let path = if let Some(pid) = pid {
+                    InTargetPathResolver::new(pid).resolve(&path)?
+                } else {
+                    path
+                };
+
+                SockAddr::unix(path)?
+            }
+            SocketAddress::Unix(UnixAddr::Abstract(mut name)) => {
+                name.insert(0, 0);
+                SockAddr::unix(OsStr::from_bytes(&name))?
+            }
+            SocketAddress::Ip(..) | SocketAddress::Unix(UnixAddr::Unnamed) => {
+                return Err(ResponseError::Remote(RemoteError::InvalidAddress(addr)));
+            }
+        };
+
+        Ok(Self::SeqPacket(SeqPacketStream::connect(address).await?))
+    }
 }
 
 impl AsyncRead for SocketStream {
@@ -100,6 +132,17 @@ impl AsyncRead for SocketStream {
         match self.get_mut() {
             SocketStream::Ip(tcp_stream) => Pin::new(tcp_stream).poll_read(cx, buf),
             SocketStream::Unix(unix_stream) => Pin::new(unix_stream).poll_read(cx, buf),
+            SocketStream::SeqPacket(stream) => {
+                let packet = std::task::ready!(stream.poll_recv_packet(cx))?;
+                if packet.len() > buf.remaining() {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "received packet does not fit in the read buffer",
+                    )));
+                }
+                buf.put_slice(&packet);
+                Poll::Ready(Ok(()))
+            }
         }
     }
 }
@@ -113,6 +156,7 @@ impl AsyncWrite for SocketStream {
         match self.get_mut() {
             SocketStream::Ip(tcp_stream) => Pin::new(tcp_stream).poll_write(cx, buf),
             SocketStream::Unix(unix_stream) => Pin::new(unix_stream).poll_write(cx, buf),
+            SocketStream::SeqPacket(stream) => stream.poll_send_packet(cx, buf),
         }
     }
 
@@ -120,6 +164,7 @@ impl AsyncWrite for SocketStream {
         match self.get_mut() {
             SocketStream::Ip(tcp_stream) => Pin::new(tcp_stream).poll_flush(cx),
             SocketStream::Unix(unix_stream) => Pin::new(unix_stream).poll_flush(cx),
+            SocketStream::SeqPacket(..) => Poll::Ready(Ok(())),
         }
     }
 
@@ -127,6 +172,195 @@ impl AsyncWrite for SocketStream {
         match self.get_mut() {
             SocketStream::Ip(tcp_stream) => Pin::new(tcp_stream).poll_shutdown(cx),
             SocketStream::Unix(unix_stream) => Pin::new(unix_stream).poll_shutdown(cx),
+            SocketStream::SeqPacket(stream) => {
+                Poll::Ready(stream.0.get_ref().shutdown(Shutdown::Write))
+            }
         }
     }
 }
+
+/// Connected unix `SOCK_SEQPACKET` socket.
+///
+/// Tokio has no support for this socket type, so we use [`AsyncFd`] directly. Every write sends
+/// exactly one packet, and [`SeqPacketStream::packets`] yields whole packets.
+///
+/// Cheap to clone, all clones refer to the same socket.
+#[derive(Clone)]
+pub struct SeqPacketStream(Arc<AsyncFd<Socket>>);
+
+impl SeqPacketStream {
+    /// Max size of a packet received from the peer. Larger packets fail the connection.
+    ///
+    /// This **must** be smaller than the amount of throttle permits in the
+    /// [`TcpOutgoingTask`](super::TcpOutgoingTask).
+    pub const MAX_PACKET_SIZE: usize = 256 * 1024;
+
+    async fn connect(address: SockAddr) -> io::Result<Self> {
+        let socket = Socket::new(Domain::UNIX, Type::SEQPACKET, None)?;
+        socket.set_nonblocking(true)?;
+        let socket = AsyncFd::new(socket)?;
+
+        match socket.get_ref().connect(&address) {
+            Ok(()) => {}
+            Err(error) if error.raw_os_error() == Some(libc::EINPROGRESS) => {
+                let _ = socket.writable().await?;
+                if let Some(error) = socket.get_ref().take_error()? {
+                    return Err(error);
+                }
+            }
+            Err(error) => return Err(error),
+        }
+
+        Ok(Self(Arc::new(socket)))
+    }
+
+    fn local_addr(&self) -> io::Result<SocketAddress> {
+        let local_address = self.0.get_ref().local_addr()?;
+
+        let addr = if let Some(path) = local_address.as_pathname() {
+            UnixAddr::Pathname(path.to_path_buf())
+        } else if let Some(name) = local_address.as_abstract_namespace() {
+            UnixAddr::Abstract(name.to_vec())
+        } else {
+            UnixAddr::Unnamed
+        };
+
+        Ok(SocketAddress::Unix(addr))
+    }
+
+    /// Returns a [`Stream`] of packets received from the peer.
+    ///
+    /// The stream ends when the peer shuts down the connection. Mind that 0-length packets are
+    /// indistinguishable from the shutdown.
+    pub fn packets(&self) -> SeqPacketReader {
+        SeqPacketReader(self.clone())
+    }
+
+    fn poll_send_packet(&self, cx: &mut Context<'_>, packet: &[u8]) -> Poll<io::Result<usize>> {
+        loop {
+            let mut guard = std::task::ready!(self.0.poll_write_ready(cx))?;
+            match guard.try_io(|socket| socket.get_ref().send(packet)) {
+                Ok(result) => return Poll::Ready(result),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    fn poll_recv_packet(&self, cx: &mut Context<'_>) -> Poll<io::Result<Bytes>> {
+        loop {
+            let mut guard = std::task::ready!(self.0.poll_read_ready(cx))?;
+            match guard.try_io(|socket| Self::recv_packet(socket.get_ref())) {
+                Ok(result) => return Poll::Ready(result),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+
+    /// Receives exactly one packet, without truncating it.
+    fn recv_packet(socket: &Socket) -> io::Result<Bytes> {
+        // With `MSG_TRUNC`, we get the real size of the packet, even though our buffer is empty.
+        let size = socket.recv_with_flags(&mut [], libc::MSG_PEEK | libc::MSG_TRUNC)?;
+        if size > Self::MAX_PACKET_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "received a packet of {size} bytes, max supported size is {}",
+                    Self::MAX_PACKET_SIZE
+                ),
+            ));
+        }
+
+        let mut buffer = BytesMut::with_capacity(size);
+        let received = socket.recv(buffer.spare_capacity_mut())?;
+        // SAFETY: `recv` initialized `received` bytes of the spare capacity.
+        unsafe { buffer.set_len(received) };
+
+        Ok(buffer.freeze())
+    }
+}
+
+/// [`Stream`] of packets received on a [`SeqPacketStream`], see [`SeqPacketStream::packets`].
+pub struct SeqPacketReader(SeqPacketStream);
+
+impl Stream for SeqPacketReader {
+    type Item = io::Result<Bytes>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match std::task::ready!(self.0.poll_recv_packet(cx)) {
+            Ok(packet) if packet.is_empty() => Poll::Ready(None),
+            result => Poll::Ready(Some(result)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::StreamExt;
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    /// Verifies that [`SeqPacketStream`] preserves boundaries between the packets, in both
+    /// directions.
+    #[tokio::test]
+    async fn seqpacket_boundaries_preserved() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peer.sock");
+
+        let listener = Socket::new(Domain::UNIX, Type::SEQPACKET, None).unwrap();
+        listener.bind(&SockAddr::unix(&path).unwrap()).unwrap();
+        listener.listen(1).unwrap();
+
+        let SocketStream::SeqPacket(stream) = SocketStream::connect_seqpacket(
+            SocketAddress::Unix(UnixAddr::Pathname(path.clone())),
+            None,
+        )
+        .await
+        .unwrap() else {
+            panic!("expected a SEQPACKET stream");
+        };
+        let (peer, _) = listener.accept().unwrap();
+
+        let mut writer = SocketStream::SeqPacket(stream.clone());
+        writer.write_all(b"first").await.unwrap();
+        writer.write_all(b"second").await.unwrap();
+
+        let received = tokio::task::spawn_blocking(move || {
+            let mut buffer = [0_u8; 64];
+            let first = io::Read::read(&mut &peer, &mut buffer).unwrap();
+            let second = io::Read::read(&mut &peer, &mut buffer).unwrap();
+            peer.send(b"third").unwrap();
+            peer.send(b"fourth").unwrap();
+            peer.shutdown(Shutdown::Write).unwrap();
+            (first, second)
+        })
+        .await
+        .unwrap();
+        assert_eq!(received, (b"first".len(), b"second".len()));
+
+        let packets = stream
+            .packets()
+            .map(Result::unwrap)
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(
+            packets,
+            [Bytes::from_static(b"third"), Bytes::from_static(b"fourth")]
+        );
+    }
+
+    /// Verifies that we don't try to connect to a SEQPACKET socket that is not unix.
+    #[tokio::test]
+    async fn seqpacket_requires_unix_address() {
+        let result = SocketStream::connect_seqpacket(
+            SocketAddress::Ip("127.0.0.1:80".parse().unwrap()),
+            None,
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(ResponseError::Remote(RemoteError::InvalidAddress(..)))
+        ));
+    }
+}
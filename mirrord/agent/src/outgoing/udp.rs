@@ -11,7 +11,7 @@ use actix_codec::ReadBuf;
 use bytes::{Bytes, BytesMut};
 use futures::{SinkExt, Stream, StreamExt};
 use mirrord_protocol::{
-    ConnectionId, RemoteResult, ResponseError,
+    ConnectionId, Payload, RemoteResult, ResponseError,
     outgoing::{udp::*, *},
 };
 use streammap_ext::StreamMap;
@@ -233,6 +233,60 @@ impl UdpOutgoingTask {
         })
     }
 
+    /// Sends the given payloads to the peer as separate datagrams, in order.
+    ///
+    /// If any of the sends fails, the connection is closed and the remaining payloads are
+    /// dropped.
+    ///
+    /// Returns [`Err`] only when the client has disconnected.
+    #[tracing::instrument(level = Level::TRACE, skip(payloads), err(level = Level::TRACE))]
+    async fn handle_layer_write<I: IntoIterator<Item = Payload>>(
+        &mut self,
+        connection_id: ConnectionId,
+        payloads: I,
+    ) -> Result<(), SendError<Throttled<DaemonUdpOutgoing>>> {
+        let write_result = match self
+            .writers
+            .get_mut(&connection_id)
+            .ok_or(ResponseError::NotFound(connection_id))
+        {
+            Ok((mirror, remote_address)) => {
+                let mut result = Ok(());
+                for bytes in payloads {
+                    result = mirror
+                        .send((bytes.0, *remote_address))
+                        .await
+                        .map_err(ResponseError::from);
+                    if result.is_err() {
+                        break;
+                    }
+                }
+                result
+            }
+            Err(fail) => Err(fail),
+        };
+
+        match write_result {
+            Ok(()) => Ok(()),
+            Err(error) => {
+                self.writers.remove(&connection_id);
+                self.readers.remove(&connection_id);
+                UDP_OUTGOING_CONNECTION.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
+
+                tracing::trace!(
+                    connection_id,
+                    ?error,
+                    "Failed to handle layer write, sending close message to the client.",
+                );
+
+                let daemon_message = DaemonUdpOutgoing::Close(connection_id);
+                self.daemon_tx.send(daemon_message.into()).await?;
+
+                Ok(())
+            }
+        }
+    }
+
     /// Returns [`Err`] only when the client has disconnected.
     #[tracing::instrument(level = Level::TRACE, ret)]
     async fn handle_layer_msg(
@@ -275,39 +329,13 @@ impl UdpOutgoingTask {
             LayerUdpOutgoing::Write(LayerWrite {
                 connection_id,
                 bytes,
-            }) => {
-                let write_result = match self
-                    .writers
-                    .get_mut(&connection_id)
-                    .ok_or(ResponseError::NotFound(connection_id))
-                {
-                    Ok((mirror, remote_address)) => mirror
-                        .send((bytes.0, *remote_address))
-                        .await
-                        .map_err(ResponseError::from),
-                    Err(fail) => Err(fail),
-                };
-
-                match write_result {
-                    Ok(()) => Ok(()),
-                    Err(error) => {
-                        self.writers.remove(&connection_id);
-                        self.readers.remove(&connection_id);
-                        UDP_OUTGOING_CONNECTION.fetch_sub(1, std::sync::atomic::Ordering::Relaxed);
-
-                        tracing::trace!(
-                            connection_id,
-                            ?error,
-                            "Failed to handle layer write, sending close message to the client.",
-                        );
-
-                        let daemon_message = DaemonUdpOutgoing::Close(connection_id);
-                        self.daemon_tx.send(daemon_message.into()).await?;
-
-                        Ok(())
-                    }
-                }
-            }
+            }) => self.handle_layer_write(connection_id, [bytes]).await,
+            // [user] -> [layer] -> [agent] -> [remote]
+            // `user` wrote multiple messages to the remote host, coalesced by the client.
+            LayerUdpOutgoing::WriteBatch(LayerWriteBatch {
+                connection_id,
+                payloads,
+            }) => self.handle_layer_write(connection_id, payloads).await,
             // [layer] -> [agent]
             // `layer` closed their interceptor stream.
             LayerUdpOutgoing::Close(LayerClose { ref connection_id }) => {
@@ -1,4 +1,4 @@
-use mirrord_protocol::{LogMessage, Port};
+use mirrord_protocol::{LogMessage, Port, tcp::SourceCidr};
 use tokio::sync::mpsc::Sender;
 
 use crate::{
@@ -12,6 +12,7 @@ mod subscriptions;
 mod task;
 #[cfg(test)]
 mod test;
+mod unix;
 
 pub use api::TcpStealerApi;
 pub use task::TcpStealerTask;
@@ -28,8 +29,9 @@ enum Command {
 
     /// The layer wants to subscribe to this [`Port`].
     ///
-    /// The agent starts stealing traffic from this [`Port`].
-    PortSubscribe(Port, Option<HttpFilter>),
+    /// The agent starts stealing traffic from this [`Port`]. If the [`SourceCidr`]s are not
+    /// empty, only connections coming from these ranges are stolen.
+    PortSubscribe(Port, Option<HttpFilter>, Vec<SourceCidr>),
 
     /// The layer wants to unsubscribe from this [`Port`].
     ///
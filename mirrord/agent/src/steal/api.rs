@@ -1,8 +1,9 @@
 use std::{
     collections::{HashMap, VecDeque, hash_map::Entry},
     error::Report,
-    fmt,
+    fmt, io,
     ops::{Not, RangeInclusive},
+    path::PathBuf,
     vec,
 };
 
@@ -11,17 +12,20 @@ use futures::{StreamExt, stream::FuturesUnordered};
 use http_body_util::{BodyExt, combinators::BoxBody};
 use hyper::{Response, body::Frame};
 use mirrord_protocol::{
-    ConnectionId, DaemonMessage, LogMessage, Payload, RequestId,
+    ConnectionId, DaemonMessage, LogMessage, Payload, RequestId, ResponseError,
     tcp::{
         ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, ChunkedResponse, DaemonTcp,
         HTTP_CHUNKED_REQUEST_V2_VERSION, HTTP_CHUNKED_REQUEST_VERSION, HTTP_FRAMED_VERSION,
         HttpRequest, HttpRequestMetadata, HttpResponse, IncomingTrafficTransportType,
         InternalHttpBody, InternalHttpBodyFrame, InternalHttpBodyNew, InternalHttpRequest,
         LayerTcpSteal, MODE_AGNOSTIC_HTTP_REQUESTS, NewTcpConnectionV1, NewTcpConnectionV2,
-        StealType, TcpClose, TcpData,
+        NewUnixConnection, SourceCidr, StealType, TcpClose, TcpData,
     },
 };
-use tokio::sync::mpsc::{self, Receiver, Sender, error::SendError};
+use tokio::{
+    net::UnixStream,
+    sync::mpsc::{self, Receiver, Sender, error::SendError},
+};
 use tokio_stream::StreamMap;
 use tracing::Level;
 
@@ -32,11 +36,13 @@ use crate::{
     http::{MIRRORD_AGENT_HTTP_HEADER_NAME, filter::HttpFilter},
     incoming::{
         ConnError, IncomingStream, IncomingStreamItem, RedirectorTaskConfig, ResponseBodyProvider,
-        ResponseProvider, StolenHttp, StolenTcp,
+        ResponseProvider, StolenHttp, StolenTcp, StolenUnix,
     },
-    steal::api::wait_body::WaitForFullBody,
+    steal::{api::wait_body::WaitForFullBody, unix::UnixSocketSubscriptions},
     task::status::BgTaskStatus,
-    util::{ClientId, protocol_version::ClientProtocolVersion},
+    util::{
+        ClientId, path_resolver::InTargetPathResolver, protocol_version::ClientProtocolVersion,
+    },
 };
 
 mod wait_body;
@@ -77,6 +83,11 @@ pub struct TcpStealerApi {
     ///
     /// We use this queue to store them and return from [`Self::recv`] one by one.
     queued_messages: VecDeque<DaemonMessage>,
+    /// Unix sockets stolen by this client.
+    ///
+    /// These are handled here and not in the [`TcpStealerTask`](super::TcpStealerTask), as there
+    /// is no traffic redirection involved.
+    unix_sockets: UnixSocketSubscriptions,
 }
 
 impl TcpStealerApi {
@@ -96,12 +107,15 @@ impl TcpStealerApi {
     ///
     /// Given `command_tx` will be used to communicate with the
     /// [`TcpStealerTask`](super::TcpStealerTask).
+    ///
+    /// Given `target_pid` is used to resolve paths of the stolen unix sockets.
     #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
     pub(crate) async fn new(
         client_id: ClientId,
         protocol_version: ClientProtocolVersion,
         command_tx: Sender<StealerCommand>,
         task_status: BgTaskStatus,
+        target_pid: Option<u64>,
     ) -> AgentResult<Self> {
         let (message_tx, message_rx) = mpsc::channel(Self::CHANNEL_SIZE);
 
@@ -126,6 +140,7 @@ impl TcpStealerApi {
             requests_in_progress: Default::default(),
             connection_ids_iter: 0..=ConnectionId::MAX,
             queued_messages: Default::default(),
+            unix_sockets: UnixSocketSubscriptions::new(target_pid.map(InTargetPathResolver::new)),
         })
     }
 
@@ -143,6 +158,38 @@ impl TcpStealerApi {
         }
     }
 
+    /// Subscribes to the port of the given [`StealType`].
+    ///
+    /// If `sources` is not empty, only connections coming from these ranges will be stolen.
+    async fn port_subscribe(
+        &mut self,
+        steal_type: StealType,
+        sources: Vec<SourceCidr>,
+    ) -> AgentResult<()> {
+        let (port, filter) = match steal_type {
+            StealType::All(port) => (port, None),
+            StealType::FilteredHttp(port, filter) => (
+                port,
+                Some(
+                    HttpFilter::try_from(&mirrord_protocol::tcp::HttpFilter::Header(filter))
+                        .map_err(Box::new)
+                        .map_err(AgentError::InvalidHttpFilter)?,
+                ),
+            ),
+            StealType::FilteredHttpEx(port, filter) => (
+                port,
+                Some(
+                    HttpFilter::try_from(&filter)
+                        .map_err(Box::new)
+                        .map_err(AgentError::InvalidHttpFilter)?,
+                ),
+            ),
+        };
+
+        self.send_command(Command::PortSubscribe(port, filter, sources))
+            .await
+    }
+
     /// Returns a [`DaemonMessage`] to be sent to the client.
     #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
     pub(crate) async fn recv(&mut self) -> AgentResult<DaemonMessage> {
@@ -178,6 +225,10 @@ impl TcpStealerApi {
                 Some((connection_id, item)) = self.incoming_streams.next() => {
                     self.handle_incoming_item(connection_id, item);
                 }
+
+                (path, result) = self.unix_sockets.next() => {
+                    self.handle_unix_connection(path, result)?;
+                }
             }
         }
     }
@@ -356,6 +407,48 @@ impl TcpStealerApi {
         Ok(())
     }
 
+    /// Handles a connection accepted on one of the stolen unix sockets.
+    #[tracing::instrument(level = Level::TRACE, ret, err(level = Level::TRACE))]
+    fn handle_unix_connection(
+        &mut self,
+        path: PathBuf,
+        result: io::Result<UnixStream>,
+    ) -> AgentResult<()> {
+        let io = match result {
+            Ok(io) => io,
+            Err(error) => {
+                self.unix_sockets.unsubscribe(&path);
+                self.queued_messages
+                    .push_back(DaemonMessage::LogMessage(LogMessage::warn(format!(
+                        "Failed to accept a connection on the stolen unix socket {}, \
+                        no longer stealing from it: {error}",
+                        path.display(),
+                    ))));
+                return Ok(());
+            }
+        };
+
+        let connection_id = self
+            .connection_ids_iter
+            .next()
+            .ok_or(AgentError::ExhaustedConnectionId)?;
+        let StolenUnix { stream, data_tx } = StolenUnix::new(io);
+
+        self.connections
+            .insert(connection_id, ClientConnectionState::Tcp { data_tx });
+        self.incoming_streams.insert(connection_id, stream);
+
+        self.queued_messages
+            .push_back(DaemonMessage::TcpSteal(DaemonTcp::NewUnixConnection(
+                NewUnixConnection {
+                    connection_id,
+                    path,
+                },
+            )));
+
+        Ok(())
+    }
+
     /// Handles an incoming item from one of connections' streams.
     #[tracing::instrument(level = Level::TRACE, ret)]
     fn handle_incoming_item(&mut self, connection_id: ConnectionId, item: IncomingStreamItem) {
@@ -490,36 +583,32 @@ impl TcpStealerApi {
     ) -> AgentResult<()> {
         match message {
             LayerTcpSteal::PortSubscribe(steal_type) => {
-                let (port, filter) = match steal_type {
-                    StealType::All(port) => (port, None),
-                    StealType::FilteredHttp(port, filter) => (
-                        port,
-                        Some(
-                            HttpFilter::try_from(&mirrord_protocol::tcp::HttpFilter::Header(
-                                filter,
-                            ))
-                            .map_err(Box::new)
-                            .map_err(AgentError::InvalidHttpFilter)?,
-                        ),
-                    ),
-                    StealType::FilteredHttpEx(port, filter) => (
-                        port,
-                        Some(
-                            HttpFilter::try_from(&filter)
-                                .map_err(Box::new)
-                                .map_err(AgentError::InvalidHttpFilter)?,
-                        ),
-                    ),
-                };
+                self.port_subscribe(steal_type, Vec::new()).await?;
+            }
 
-                self.send_command(Command::PortSubscribe(port, filter))
-                    .await?;
+            LayerTcpSteal::PortSubscribeFromSources(steal_type, sources) => {
+                self.port_subscribe(steal_type, sources).await?;
             }
 
             LayerTcpSteal::PortUnsubscribe(port) => {
                 self.send_command(Command::PortUnsubscribe(port)).await?;
             }
 
+            LayerTcpSteal::UnixSubscribe(path) => {
+                let result = self
+                    .unix_sockets
+                    .subscribe(path.clone())
+                    .map(|()| path)
+                    .map_err(ResponseError::from);
+                self.queued_messages.push_back(DaemonMessage::TcpSteal(
+                    DaemonTcp::UnixSubscribeResult(result),
+                ));
+            }
+
+            LayerTcpSteal::UnixUnsubscribe(path) => {
+                self.unix_sockets.unsubscribe(&path);
+            }
+
             LayerTcpSteal::ConnectionUnsubscribe(connection_id) => {
                 self.connections.remove(&connection_id);
                 self.incoming_streams.remove(&connection_id);
@@ -2,15 +2,17 @@ use std::{
     borrow::Cow,
     collections::{HashMap, hash_map::Entry},
     fmt,
+    net::IpAddr,
     ops::Not,
 };
 
 use futures::{StreamExt, stream::FuturesUnordered};
-use http::header::UPGRADE;
+use http::{Version, header::UPGRADE};
 use mirrord_protocol::{
-    LogMessage,
+    LogMessage, Port,
     tcp::{
-        HTTP_CHUNKED_REQUEST_V2_VERSION, HTTP_FILTERED_UPGRADE_VERSION, MODE_AGNOSTIC_HTTP_REQUESTS,
+        HTTP_CHUNKED_REQUEST_V2_VERSION, HTTP_FILTERED_UPGRADE_VERSION, HTTP_FRAMED_VERSION,
+        MODE_AGNOSTIC_HTTP_REQUESTS, SourceCidr,
     },
 };
 use tokio::{sync::mpsc, task::JoinSet};
@@ -137,6 +139,10 @@ impl TcpStealerTask {
                     .contains_key(UPGRADE)
                     .then_some(&*HTTP_FILTERED_UPGRADE_VERSION)
             })
+            .or_else(|| {
+                // HTTP/2 responses (e.g. gRPC) can carry trailers, which older clients can't send.
+                (http.parts().version == Version::HTTP_2).then_some(&*HTTP_FRAMED_VERSION)
+            })
             .map(Cow::Borrowed)
             .unwrap_or(Cow::Owned(semver::VersionReq::STAR))
     }
@@ -191,6 +197,17 @@ impl TcpStealerTask {
                     return;
                 };
 
+                let info = conn.info();
+                if client
+                    .accepts_source(info.original_destination.port(), info.peer_addr.ip())
+                    .not()
+                {
+                    join_handle_tx
+                        .send(conn.pass_through(shutdown))
+                        .expect("RedirectorTask dropped oneshot rx for receiving JoinHandle to IO task for TCP connection");
+                    return;
+                }
+
                 let message = if client.protocol_version.matches(&protocol_version_req) {
                     let (steal_handle, join_handle) = conn.steal(shutdown);
                     join_handle_tx
@@ -225,6 +242,15 @@ impl TcpStealerTask {
                     return;
                 };
 
+                let info = http.info();
+                if client
+                    .accepts_source(info.original_destination.port(), info.peer_addr.ip())
+                    .not()
+                {
+                    http.pass_through();
+                    return;
+                }
+
                 let message = if client.protocol_version.matches(&protocol_version_req) {
                     StealerMessage::StolenHttp(http.steal())
                 } else {
@@ -262,6 +288,8 @@ impl TcpStealerTask {
         let mut preempted = vec![]; // other clients that could receive the request as well
         let mut blocked_on_protocol = vec![]; // clients that cannot receive the request due to their protocol version
 
+        let port = http.info().original_destination.port();
+        let source = http.info().peer_addr.ip();
         let (parts, body_reader) = http.parts_and_body();
 
         for (client_id, filter) in filters {
@@ -278,6 +306,10 @@ impl TcpStealerTask {
                 continue;
             };
 
+            if client.accepts_source(port, source).not() {
+                continue;
+            }
+
             if client.protocol_version.matches(&protocol_version_req).not() {
                 blocked_on_protocol.push(client);
             } else if send_to.is_none() {
@@ -339,11 +371,12 @@ impl TcpStealerTask {
                 e.insert(Client {
                     message_tx,
                     protocol_version,
+                    sources: Default::default(),
                 });
             }
 
-            Command::PortSubscribe(port, filter) => {
-                let Some(client) = self.clients.get(&command.client_id) else {
+            Command::PortSubscribe(port, filter, sources) => {
+                let Some(client) = self.clients.get_mut(&command.client_id) else {
                     // The client disconnected after sending the message.
                     return Ok(());
                 };
@@ -352,6 +385,12 @@ impl TcpStealerTask {
                     .add(command.client_id, port, filter)
                     .await?;
 
+                if sources.is_empty() {
+                    client.sources.remove(&port);
+                } else {
+                    client.sources.insert(port, sources);
+                }
+
                 let _ = client
                     .message_tx
                     .send(StealerMessage::PortSubscribed(port))
@@ -360,6 +399,9 @@ impl TcpStealerTask {
 
             Command::PortUnsubscribe(port) => {
                 self.subscriptions.remove(command.client_id, port);
+                if let Some(client) = self.clients.get_mut(&command.client_id) {
+                    client.sources.remove(&port);
+                }
             }
         }
 
@@ -413,4 +455,20 @@ impl fmt::Debug for TcpStealerTask {
 struct Client {
     message_tx: mpsc::Sender<StealerMessage>,
     protocol_version: ClientProtocolVersion,
+    /// Source address ranges that this client steals from, per subscribed port.
+    ///
+    /// Ports without an entry here accept traffic from all sources.
+    sources: HashMap<Port, Vec<SourceCidr>>,
+}
+
+impl Client {
+    /// Returns whether this client should steal traffic coming from the `source` address to the
+    /// given `port`.
+    ///
+    /// Traffic that is not accepted by any client is passed through to its original destination.
+    fn accepts_source(&self, port: Port, source: IpAddr) -> bool {
+        self.sources
+            .get(&port)
+            .is_none_or(|sources| sources.iter().any(|cidr| cidr.contains(source)))
+    }
 }
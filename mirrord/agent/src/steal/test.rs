@@ -4,17 +4,25 @@
 //! to the [`RedirectorTask`](crate::incoming::RedirectorTask).
 #![allow(clippy::indexing_slicing)]
 
-use std::time::Duration;
+use std::{sync::Mutex, time::Duration};
 
 use bytes::{Buf, Bytes};
 use futures::StreamExt;
-use http_body_util::{BodyExt, Full, StreamBody};
-use hyper::body::SizeHint;
+use http::{
+    HeaderMap, HeaderName, HeaderValue, Request, Response, Uri, header::CONTENT_TYPE,
+    uri::PathAndQuery,
+};
+use http_body_util::{BodyExt, Full, StreamBody, combinators::BoxBody};
+use hyper::{
+    body::{Frame, Incoming, SizeHint},
+    service::service_fn,
+};
+use hyper_util::rt::{TokioExecutor, TokioIo};
 use mirrord_protocol::{
     DaemonMessage, LogLevel,
     tcp::{
         DaemonTcp, Filter, HttpBodyFilter, HttpFilter, IncomingTrafficTransportType, JsonPathQuery,
-        StealType,
+        SourceCidr, StealType,
     },
 };
 use mirrord_tls_util::MaybeTls;
@@ -26,6 +34,7 @@ use tokio::{net::TcpListener, sync::mpsc};
 use tokio_rustls::TlsStream;
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_util::sync::CancellationToken;
+use tonic::{client::Grpc, codec::ProstCodec, transport::Endpoint};
 use utils::{StealingClient, TestBody, TestHttpKind, TestRequest, TestTcpProtocol, WithSizeHint};
 
 use super::{StealerCommand, TcpStealerTask};
@@ -253,6 +262,50 @@ async fn tcp_stealing(#[values(false, true)] with_tls: bool, #[values(false, tru
     );
 }
 
+/// Verifies that only connections coming from the subscription's source ranges are stolen,
+/// and that the other connections are passed through to the original destination port.
+#[rstest]
+#[case::matching_ipv4("127.0.0.0/8", true)]
+#[case::matching_single_ipv4("127.0.0.1/32", true)]
+#[case::not_matching_ipv4("10.12.0.0/16", false)]
+#[case::not_matching_ipv6("::1/128", false)]
+#[timeout(Duration::from_secs(5))]
+#[tokio::test]
+async fn tcp_stealing_source_filter(#[case] source: &str, #[case] stolen: bool) {
+    let mut setup = TestSetup::new_tcp(false, RedirectorTaskConfig::from_env()).await;
+
+    let (address, prefix_len) = source.split_once('/').unwrap();
+    let source = SourceCidr {
+        address: address.parse().unwrap(),
+        prefix_len: prefix_len.parse().unwrap(),
+    };
+    let mut client = StealingClient::with_sources(
+        0,
+        setup.stealer_tx.clone(),
+        "1.37.0",
+        StealType::All(setup.original_server.local_addr().unwrap().port()),
+        vec![source],
+        setup.stealer_status.clone(),
+    )
+    .await;
+
+    let conn = setup
+        .conn_tx
+        .make_connection(setup.original_server.local_addr().unwrap())
+        .await;
+    tokio::join!(TestTcpProtocol::Echo.run(conn, false), async {
+        if stolen {
+            let conn = client.expect_connection().await;
+            client
+                .expect_tcp(conn.connection.connection_id, TestTcpProtocol::Echo)
+                .await;
+        } else {
+            let (conn, _) = setup.original_server.accept().await.unwrap();
+            TestTcpProtocol::Echo.run(conn, true).await;
+        }
+    });
+}
+
 /// Verifies scenario where the client cannot steal a TLS connection,
 /// because their mirrord-protocol version is too low.
 #[rstest]
@@ -464,6 +517,7 @@ async fn header_injection(
         http_kind,
         RedirectorTaskConfig {
             inject_headers: true,
+            max_pending_requests: None,
         },
     )
     .await;
@@ -804,6 +858,112 @@ async fn body_filters_pass(
     );
 }
 
+/// Verifies that gRPC calls over cleartext HTTP/2 (h2c) are stolen by their method, and that the
+/// calls that don't match are passed through on a single connection to the original destination.
+#[rstest]
+#[timeout(Duration::from_secs(5))]
+#[tokio::test]
+async fn grpc_h2c_filtered() {
+    let mut setup =
+        TestSetup::new_http(TestHttpKind::Http2, RedirectorTaskConfig::from_env()).await;
+    let destination = setup.original_server.local_addr().unwrap();
+
+    let mut client = StealingClient::new(
+        0,
+        setup.stealer_tx.clone(),
+        "1.22.1",
+        StealType::FilteredHttpEx(
+            destination.port(),
+            HttpFilter::Path(Filter::new("^/test.Echo/Stolen$".into()).unwrap()),
+        ),
+        setup.stealer_status.clone(),
+    )
+    .await;
+
+    let original_server = setup.original_server;
+    let original_server = tokio::spawn(async move {
+        let (stream, _) = original_server.accept().await.unwrap();
+        tokio::spawn(
+            hyper::server::conn::http2::Builder::new(TokioExecutor::new())
+                .serve_connection(TokioIo::new(stream), service_fn(grpc_echo)),
+        );
+        original_server
+    });
+
+    let conn = Mutex::new(Some(setup.conn_tx.make_connection(destination).await));
+    let channel = Endpoint::from_static("http://server")
+        .connect_with_connector(tower::service_fn(move |_: Uri| {
+            let conn = conn
+                .lock()
+                .unwrap()
+                .take()
+                .expect("gRPC client reconnected");
+            std::future::ready(Ok::<_, std::io::Error>(TokioIo::new(conn)))
+        }))
+        .await
+        .unwrap();
+    let mut grpc = Grpc::new(channel);
+
+    for (path, handled_by) in [
+        ("/test.Echo/Passed", "original"),
+        ("/test.Echo/Stolen", "0"),
+        ("/test.Echo/Passed", "original"),
+        ("/test.Echo/Other", "original"),
+    ] {
+        let message = format!("hello from {path}");
+        grpc.ready().await.unwrap();
+
+        let (response, ()) = tokio::join!(
+            grpc.unary(
+                tonic::Request::new(message.clone()),
+                PathAndQuery::from_static(path),
+                ProstCodec::<String, String>::default(),
+            ),
+            async {
+                if handled_by == "0" {
+                    client.expect_grpc_echo(path).await;
+                }
+            },
+        );
+
+        let response = response.unwrap();
+        assert_eq!(
+            response
+                .metadata()
+                .get(TestRequest::HANDLED_BY_HEADER.as_str())
+                .unwrap(),
+            handled_by,
+        );
+        assert_eq!(response.into_inner(), message);
+    }
+
+    let original_server = original_server.await.unwrap();
+    assert!(
+        tokio::time::timeout(Duration::from_millis(100), original_server.accept())
+            .await
+            .is_err(),
+        "passed through gRPC calls should share one connection"
+    );
+}
+
+/// Unary gRPC service that responds with the request message.
+async fn grpc_echo(
+    request: Request<Incoming>,
+) -> hyper::Result<Response<BoxBody<Bytes, hyper::Error>>> {
+    let message = request.into_body().collect().await?.to_bytes();
+    let trailers = HeaderMap::from_iter([(
+        HeaderName::from_static("grpc-status"),
+        HeaderValue::from_static("0"),
+    )]);
+    let frames = [Frame::data(message), Frame::trailers(trailers)];
+
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, "application/grpc")
+        .header(TestRequest::HANDLED_BY_HEADER, "original")
+        .body(StreamBody::new(futures::stream::iter(frames.map(Ok::<_, hyper::Error>))).boxed())
+        .unwrap())
+}
+
 struct TestSetup {
     /// Simulates the app that would be running on the cluster.
     original_server: TcpListener,
@@ -8,14 +8,14 @@ use std::{
     time::Duration,
 };
 
-use bytes::Bytes;
+use bytes::{Bytes, BytesMut};
 use futures::StreamExt;
 use http_body_util::{BodyExt, Empty, StreamBody, combinators::BoxBody};
 use hyper::{
     Request, Response,
     body::{Body, Frame, Incoming, SizeHint},
     header,
-    http::{HeaderName, Method, StatusCode, request},
+    http::{HeaderMap, HeaderName, Method, StatusCode, Version, request},
 };
 use hyper_util::rt::TokioIo;
 use mirrord_protocol::{
@@ -25,7 +25,7 @@ use mirrord_protocol::{
         HTTP_CHUNKED_REQUEST_V2_VERSION, HTTP_CHUNKED_RESPONSE_VERSION, HttpRequestMetadata,
         HttpResponse, IncomingTrafficTransportType, InternalHttpBodyNew, InternalHttpRequest,
         InternalHttpResponse, LayerTcpSteal, MODE_AGNOSTIC_HTTP_REQUESTS, NewTcpConnectionV2,
-        StealType, TcpClose, TcpData,
+        SourceCidr, StealType, TcpClose, TcpData,
     },
 };
 use mirrord_tls_util::MaybeTls;
@@ -236,7 +236,7 @@ impl TestRequest {
     const FRAME: &[u8] = b"hello\n";
     const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("request-id");
     pub const USER_ID_HEADER: HeaderName = HeaderName::from_static("user-id");
-    const HANDLED_BY_HEADER: HeaderName = HeaderName::from_static("handled-by");
+    pub const HANDLED_BY_HEADER: HeaderName = HeaderName::from_static("handled-by");
     pub const MIRRORD_AGENT_HEADER: HeaderName = HeaderName::from_static("mirrord-agent");
 
     fn as_hyper_request(&self) -> Request<BoxBody<Bytes, hyper::Error>> {
@@ -472,7 +472,7 @@ impl TestRequest {
             }
             None => MaybeTls::NoTls(conn),
         };
-        let mut requests = ExtractedRequests::new(TokioIo::new(conn), self.kind.version());
+        let mut requests = ExtractedRequests::new(TokioIo::new(conn), self.kind.version(), None);
         let request = requests.next().await.unwrap().unwrap();
         let conn_task = tokio::spawn(async move { requests.next().await });
 
@@ -618,16 +618,47 @@ impl StealingClient {
         protocol_version: &str,
         steal_type: StealType,
         stealer_status: BgTaskStatus,
+    ) -> Self {
+        Self::with_sources(
+            id,
+            command_tx,
+            protocol_version,
+            steal_type,
+            Vec::new(),
+            stealer_status,
+        )
+        .await
+    }
+
+    /// Same as [`StealingClient::new`], but the client steals only from the given `sources`.
+    ///
+    /// Uses [`LayerTcpSteal::PortSubscribeFromSources`] if `sources` are not empty.
+    pub async fn with_sources(
+        id: ClientId,
+        command_tx: Sender<StealerCommand>,
+        protocol_version: &str,
+        steal_type: StealType,
+        sources: Vec<SourceCidr>,
+        stealer_status: BgTaskStatus,
     ) -> Self {
         let protocol_version = protocol_version.parse::<ClientProtocolVersion>().unwrap();
         assert!(protocol_version.matches(&HTTP_CHUNKED_RESPONSE_VERSION));
 
-        let mut api = TcpStealerApi::new(id, protocol_version.clone(), command_tx, stealer_status)
-            .await
-            .unwrap();
-        api.handle_client_message(LayerTcpSteal::PortSubscribe(steal_type.clone()))
-            .await
-            .unwrap();
+        let mut api = TcpStealerApi::new(
+            id,
+            protocol_version.clone(),
+            command_tx,
+            stealer_status,
+            None,
+        )
+        .await
+        .unwrap();
+        let message = if sources.is_empty() {
+            LayerTcpSteal::PortSubscribe(steal_type.clone())
+        } else {
+            LayerTcpSteal::PortSubscribeFromSources(steal_type.clone(), sources)
+        };
+        api.handle_client_message(message).await.unwrap();
         assert_eq!(
             api.recv().await.unwrap(),
             DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Ok(steal_type.get_port()))),
@@ -844,6 +875,83 @@ impl StealingClient {
         }
     }
 
+    /// Expects a unary gRPC call to `path`, and responds with the request message.
+    ///
+    /// The response has this client's id in the [`TestRequest::HANDLED_BY_HEADER`].
+    pub async fn expect_grpc_echo(&mut self, path: &str) {
+        let mut request = match self.api.recv().await.unwrap() {
+            DaemonMessage::TcpSteal(DaemonTcp::HttpRequestChunked(ChunkedRequest::StartV2(
+                request,
+            ))) => request,
+            other => panic!(
+                "client {} received an unexpected message: {other:?}",
+                self.id
+            ),
+        };
+        assert_eq!(request.request.version, Version::HTTP_2);
+        assert_eq!(request.request.uri.path(), path);
+
+        while request.request.body.is_last.not() {
+            match self.api.recv().await.unwrap() {
+                DaemonMessage::TcpSteal(DaemonTcp::HttpRequestChunked(ChunkedRequest::Body(
+                    ChunkedRequestBodyV1 {
+                        frames, is_last, ..
+                    },
+                ))) => {
+                    request.request.body.frames.extend(frames);
+                    request.request.body.is_last = is_last;
+                }
+                other => panic!("unexpected message: {other:?}"),
+            }
+        }
+
+        let mut message = BytesMut::new();
+        for frame in request.request.body.frames {
+            if let Ok(data) = Frame::<Bytes>::from(frame).into_data() {
+                message.extend_from_slice(&data);
+            }
+        }
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::CONTENT_TYPE, "application/grpc".parse().unwrap());
+        headers.insert(
+            TestRequest::HANDLED_BY_HEADER,
+            self.id.to_string().parse().unwrap(),
+        );
+        let mut trailers = HeaderMap::new();
+        trailers.insert("grpc-status", "0".parse().unwrap());
+
+        let response = ChunkedResponse::Start(HttpResponse {
+            port: self.steal_type.get_port(),
+            connection_id: request.connection_id,
+            request_id: request.request_id,
+            internal_response: InternalHttpResponse {
+                status: StatusCode::OK,
+                version: Version::HTTP_2,
+                headers,
+                body: Default::default(),
+            },
+        });
+        self.api
+            .handle_client_message(LayerTcpSteal::HttpResponseChunked(response))
+            .await
+            .unwrap();
+        self.api
+            .handle_client_message(LayerTcpSteal::HttpResponseChunked(ChunkedResponse::Body(
+                ChunkedRequestBodyV1 {
+                    frames: vec![
+                        Frame::data(message.freeze()).into(),
+                        Frame::trailers(trailers).into(),
+                    ],
+                    is_last: true,
+                    request_id: request.request_id,
+                    connection_id: request.connection_id,
+                },
+            )))
+            .await
+            .unwrap();
+    }
+
     pub async fn expect_tcp(
         &mut self,
         expect_connection_id: ConnectionId,
@@ -0,0 +1,402 @@
+//! Stealing connections from unix domain sockets in the target.
+//!
+//! We can't redirect unix socket connections with iptables, so instead we take the socket's place
+//! in the target's filesystem: the original socket file is moved aside, and our own listener is
+//! bound at its path. The original socket file is moved back when we're done.
+
+use std::{
+    collections::HashSet,
+    fs, io,
+    ops::Not,
+    os::unix::fs::{FileTypeExt, MetadataExt, lchown},
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::{LazyLock, Mutex},
+    task::{Context, Poll},
+};
+
+use futures::Stream;
+use tokio::net::{UnixListener, UnixStream};
+use tokio_stream::{StreamExt, StreamMap};
+
+use crate::util::path_resolver::InTargetPathResolver;
+
+/// Resolved paths of all sockets that are currently stolen by any client.
+static STOLEN_PATHS: LazyLock<Mutex<HashSet<PathBuf>>> = LazyLock::new(Default::default);
+
+/// Identifies a file, so that we can tell whether the file at a path is still the one we created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileId {
+    dev: u64,
+    ino: u64,
+}
+
+impl From<&fs::Metadata> for FileId {
+    fn from(metadata: &fs::Metadata) -> Self {
+        Self {
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+        }
+    }
+}
+
+/// A socket path taken over by the agent.
+///
+/// Restores the original socket file when dropped.
+#[derive(Debug)]
+struct TakenPath {
+    path: PathBuf,
+    /// Where the original socket file was moved, [`None`] if there was none.
+    backup: Option<PathBuf>,
+    /// Our socket file bound at the [`Self::path`], [`None`] until it's bound.
+    bound: Option<FileId>,
+}
+
+impl TakenPath {
+    /// Suffix appended to the socket path to get the path of the moved original socket file.
+    const BACKUP_SUFFIX: &str = ".mirrord-original";
+
+    /// Moves the original socket file at the `path` (if any) aside.
+    fn take(path: PathBuf) -> io::Result<Self> {
+        let mut stolen = STOLEN_PATHS
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+        if stolen.contains(&path) {
+            return Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                format!("socket {} is already stolen", path.display()),
+            ));
+        }
+
+        let mut backup = path.clone().into_os_string();
+        backup.push(Self::BACKUP_SUFFIX);
+        let backup = PathBuf::from(backup);
+
+        let socket_exists = match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_socket() => true,
+            Ok(..) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is not a unix socket", path.display()),
+                ));
+            }
+            Err(error) if error.kind() == io::ErrorKind::NotFound => false,
+            Err(error) => return Err(error),
+        };
+
+        // The backup can be a leftover from an agent that did not exit cleanly.
+        let backup = match (backup.exists(), socket_exists) {
+            (true, true) if is_stale_socket(&path) => {
+                tracing::warn!(
+                    ?path,
+                    ?backup,
+                    "Found a leftover original socket file, removing the stale stealing socket",
+                );
+                fs::remove_file(&path)?;
+                Some(backup)
+            }
+            // The target application bound its socket again after the leftover backup was made,
+            // so the backup is outdated.
+            (true, true) => {
+                tracing::warn!(
+                    ?path,
+                    ?backup,
+                    "Found an outdated original socket file, replacing it with the live socket",
+                );
+                fs::rename(&path, &backup)?;
+                Some(backup)
+            }
+            (true, false) => {
+                tracing::warn!(?path, ?backup, "Found a leftover original socket file");
+                Some(backup)
+            }
+            (false, true) => {
+                fs::rename(&path, &backup)?;
+                Some(backup)
+            }
+            (false, false) => None,
+        };
+
+        stolen.insert(path.clone());
+
+        Ok(Self {
+            path,
+            backup,
+            bound: None,
+        })
+    }
+}
+
+/// Whether no one listens on the socket file at the `path` anymore.
+///
+/// A live socket gets a connection from us, that's closed right away.
+fn is_stale_socket(path: &Path) -> bool {
+    matches!(
+        std::os::unix::net::UnixStream::connect(path),
+        Err(error) if error.kind() == io::ErrorKind::ConnectionRefused
+    )
+}
+
+impl Drop for TakenPath {
+    /// Removes our socket file and moves the original one back.
+    ///
+    /// If the file at the path is not ours anymore (e.g. the target application bound a new
+    /// socket there), it's left alone, and the outdated original socket file is removed instead.
+    fn drop(&mut self) {
+        let replaced = match fs::symlink_metadata(&self.path) {
+            Ok(metadata) if self.bound == Some(FileId::from(&metadata)) => {
+                if let Err(error) = fs::remove_file(&self.path) {
+                    tracing::error!(
+                        %error,
+                        path = ?self.path,
+                        "Failed to remove the stealing socket",
+                    );
+                }
+                false
+            }
+            Ok(..) => true,
+            Err(..) => false,
+        };
+
+        match &self.backup {
+            Some(backup) if replaced => {
+                tracing::warn!(
+                    path = ?self.path,
+                    "The stealing socket was replaced, not restoring the original socket",
+                );
+                if let Err(error) = fs::remove_file(backup) {
+                    tracing::error!(%error, ?backup, "Failed to remove the original socket");
+                }
+            }
+            Some(backup) => {
+                if let Err(error) = fs::rename(backup, &self.path) {
+                    tracing::error!(
+                        %error,
+                        path = ?self.path,
+                        "Failed to restore the original socket",
+                    );
+                }
+            }
+            None => {}
+        }
+
+        STOLEN_PATHS
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .remove(&self.path);
+    }
+}
+
+/// Our listener bound in place of the original socket.
+#[derive(Debug)]
+struct StolenSocket {
+    listener: UnixListener,
+    /// Dropped after the [`Self::listener`], so that the socket file is restored last.
+    _taken: TakenPath,
+}
+
+impl StolenSocket {
+    fn bind(path: PathBuf) -> io::Result<Self> {
+        let mut taken = TakenPath::take(path)?;
+        let listener = UnixListener::bind(&taken.path)?;
+        taken.bound = Some(FileId::from(&fs::symlink_metadata(&taken.path)?));
+
+        // So that the target's clients are still allowed to connect.
+        if let Some(backup) = &taken.backup {
+            let metadata = fs::symlink_metadata(backup)?;
+            fs::set_permissions(&taken.path, metadata.permissions())?;
+            lchown(&taken.path, Some(metadata.uid()), Some(metadata.gid()))?;
+        }
+
+        Ok(Self {
+            listener,
+            _taken: taken,
+        })
+    }
+}
+
+impl Stream for StolenSocket {
+    type Item = io::Result<UnixStream>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.listener
+            .poll_accept(cx)
+            .map(|result| Some(result.map(|(stream, _)| stream)))
+    }
+}
+
+/// Unix sockets stolen by a single client.
+///
+/// The paths are given as seen in the target's filesystem.
+#[derive(Debug)]
+pub(super) struct UnixSocketSubscriptions {
+    /// [`None`] when the agent runs in the target's filesystem.
+    path_resolver: Option<InTargetPathResolver>,
+    sockets: StreamMap<PathBuf, StolenSocket>,
+}
+
+impl UnixSocketSubscriptions {
+    pub(super) fn new(path_resolver: Option<InTargetPathResolver>) -> Self {
+        Self {
+            path_resolver,
+            sockets: Default::default(),
+        }
+    }
+
+    /// Starts stealing connections from the socket at the given `path`.
+    pub(super) fn subscribe(&mut self, path: PathBuf) -> io::Result<()> {
+        if path.is_absolute().not() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("socket path {} is not absolute", path.display()),
+            ));
+        }
+
+        if self.sockets.contains_key(&path) {
+            return Ok(());
+        }
+
+        let resolved = match &self.path_resolver {
+            Some(resolver) => resolver.resolve(&path)?,
+            None => path.clone(),
+        };
+        let socket = StolenSocket::bind(resolved)?;
+        self.sockets.insert(path, socket);
+
+        Ok(())
+    }
+
+    /// Stops stealing connections from the socket at the given `path`, restoring the original
+    /// socket file.
+    pub(super) fn unsubscribe(&mut self, path: &Path) {
+        self.sockets.remove(path);
+    }
+
+    /// Returns the next accepted connection, along with the path of its socket.
+    ///
+    /// Never resolves if there are no subscriptions.
+    pub(super) async fn next(&mut self) -> (PathBuf, io::Result<UnixStream>) {
+        match self.sockets.next().await {
+            Some(item) => item,
+            None => std::future::pending().await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    /// Connections are stolen from the socket while subscribed, and the original socket is
+    /// restored after unsubscribing.
+    #[tokio::test]
+    async fn steal_and_restore() {
+        let root = tempfile::tempdir().unwrap();
+        let original = UnixListener::bind(root.path().join("app.sock")).unwrap();
+
+        let mut subscriptions = UnixSocketSubscriptions::new(Some(
+            InTargetPathResolver::with_root_path(root.path().to_path_buf()),
+        ));
+        subscriptions.subscribe("/app.sock".into()).unwrap();
+
+        let mut client = UnixStream::connect(root.path().join("app.sock"))
+            .await
+            .unwrap();
+        let (path, stolen) = subscriptions.next().await;
+        assert_eq!(path, Path::new("/app.sock"));
+        let mut stolen = stolen.unwrap();
+        client.write_all(b"hello").await.unwrap();
+        let mut buf = [0; 5];
+        stolen.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"hello");
+
+        subscriptions.unsubscribe(Path::new("/app.sock"));
+        assert!(root.path().join("app.sock.mirrord-original").exists().not());
+
+        let _client = UnixStream::connect(root.path().join("app.sock"))
+            .await
+            .unwrap();
+        original.accept().await.unwrap();
+    }
+
+    /// A socket bound at the path by someone else while we were stealing is not removed.
+    #[tokio::test]
+    async fn replaced_socket_kept() {
+        let root = tempfile::tempdir().unwrap();
+        let _original = UnixListener::bind(root.path().join("app.sock")).unwrap();
+
+        let mut subscriptions = UnixSocketSubscriptions::new(Some(
+            InTargetPathResolver::with_root_path(root.path().to_path_buf()),
+        ));
+        subscriptions.subscribe("/app.sock".into()).unwrap();
+
+        fs::remove_file(root.path().join("app.sock")).unwrap();
+        let replacement = UnixListener::bind(root.path().join("app.sock")).unwrap();
+
+        subscriptions.unsubscribe(Path::new("/app.sock"));
+        assert!(root.path().join("app.sock.mirrord-original").exists().not());
+
+        let _client = UnixStream::connect(root.path().join("app.sock"))
+            .await
+            .unwrap();
+        replacement.accept().await.unwrap();
+    }
+
+    /// A leftover original socket file is restored, and the stale socket of the agent that left
+    /// it is removed.
+    #[tokio::test]
+    async fn leftover_backup_restored() {
+        let root = tempfile::tempdir().unwrap();
+        let original = UnixListener::bind(root.path().join("app.sock")).unwrap();
+        fs::rename(
+            root.path().join("app.sock"),
+            root.path().join("app.sock.mirrord-original"),
+        )
+        .unwrap();
+        drop(std::os::unix::net::UnixListener::bind(root.path().join("app.sock")).unwrap());
+
+        let mut subscriptions = UnixSocketSubscriptions::new(Some(
+            InTargetPathResolver::with_root_path(root.path().to_path_buf()),
+        ));
+        subscriptions.subscribe("/app.sock".into()).unwrap();
+        subscriptions.unsubscribe(Path::new("/app.sock"));
+        assert!(root.path().join("app.sock.mirrord-original").exists().not());
+
+        let _client = UnixStream::connect(root.path().join("app.sock"))
+            .await
+            .unwrap();
+        original.accept().await.unwrap();
+    }
+
+    /// A live socket at the path is kept over an outdated leftover original socket file.
+    #[tokio::test]
+    async fn outdated_backup_replaced() {
+        let root = tempfile::tempdir().unwrap();
+        drop(
+            std::os::unix::net::UnixListener::bind(root.path().join("app.sock.mirrord-original"))
+                .unwrap(),
+        );
+        let live = UnixListener::bind(root.path().join("app.sock")).unwrap();
+
+        let mut subscriptions = UnixSocketSubscriptions::new(Some(
+            InTargetPathResolver::with_root_path(root.path().to_path_buf()),
+        ));
+        subscriptions.subscribe("/app.sock".into()).unwrap();
+        subscriptions.unsubscribe(Path::new("/app.sock"));
+        assert!(root.path().join("app.sock.mirrord-original").exists().not());
+
+        let _client = UnixStream::connect(root.path().join("app.sock"))
+            .await
+            .unwrap();
+        live.accept().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn relative_path_rejected() {
+        let mut subscriptions = UnixSocketSubscriptions::new(None);
+        let error = subscriptions.subscribe("app.sock".into()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -56,6 +56,28 @@ Join our Slack at https://metalbear.com/slack , create a GitHub issue at https:/
 pub(super) struct Cli {
     #[command(subcommand)]
     pub(super) commands: Commands,
+
+    /// Format of the error printed when mirrord fails.
+    ///
+    /// With `json`, a single JSON object with the error `code`, `category`, `message` and `hint`
+    /// is printed to stderr. The process exit code is the same with both formats.
+    #[arg(
+        env = "MIRRORD_ERROR_FORMAT",
+        long,
+        global = true,
+        value_enum,
+        default_value_t = ErrorFormat::Human
+    )]
+    pub(super) error_format: ErrorFormat,
+}
+
+/// Format of the error printed when mirrord fails, see [`Cli::error_format`].
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub(super) enum ErrorFormat {
+    /// Human readable error report.
+    Human,
+    /// JSON object, for scripts.
+    Json,
 }
 
 #[derive(Debug, Subcommand)]
@@ -209,6 +231,15 @@ pub(super) enum Commands {
 
     /// Fix issues related to mirrord.
     Fix(FixArgs),
+
+    /// Inspect the mirrord config.
+    Config(ConfigArgs),
+
+    /// Create a mirrord config file by answering a few questions.
+    Init(InitArgs),
+
+    /// Inspect a running mirrord session.
+    Session(Box<SessionArgs>),
 }
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum, Debug)]
@@ -296,9 +327,16 @@ pub(super) struct ExecParams {
 
     /// Load config from config file
     /// When using -f flag without a value, defaults to "./.mirrord/mirrord.json"
+    /// Use `-` to read the config from stdin.
     #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
     pub config_file: Option<PathBuf>,
 
+    /// Format of the config read from stdin (`-f -`), defaults to JSON.
+    ///
+    /// Config files use their extension instead.
+    #[arg(long, value_enum)]
+    pub config_format: Option<ConfigFormat>,
+
     /// Kube context to use from Kubeconfig
     #[arg(long)]
     pub context: Option<String>,
@@ -308,6 +346,8 @@ pub(super) struct ExecParams {
     /// Allows for passing environment variables from an env file.
     ///
     /// These variables will override environment fetched from the remote target.
+    ///
+    /// Use `-` to read the env file from stdin.
     #[arg(long, value_hint = ValueHint::FilePath)]
     pub env_file: Option<PathBuf>,
 
@@ -317,6 +357,11 @@ pub(super) struct ExecParams {
     /// If not provided here or in the config file, a unique key is generated automatically.
     #[arg(long)]
     pub key: Option<String>,
+
+    /// Pause the application before it starts running, until something connects to this local
+    /// TCP port (e.g. your IDE, once its debugger is ready to attach).
+    #[arg(long, value_name = "PORT")]
+    pub wait_for_debugger: Option<u16>,
 }
 
 impl ExecParams {
@@ -413,6 +458,12 @@ impl ExecParams {
                 Cow::Borrowed(config_file.as_ref()),
             );
         }
+        if let Some(config_format) = self.config_format {
+            envs.insert(
+                LayerConfig::FILE_FORMAT_ENV.as_ref(),
+                Cow::Borrowed(config_format.extension().as_ref()),
+            );
+        }
         if let Some(env_file) = &self.env_file {
             envs.insert(
                 MIRRORD_OVERRIDE_ENV_FILE_ENV.as_ref(),
@@ -425,6 +476,12 @@ impl ExecParams {
                 Cow::Borrowed(key.as_ref()),
             );
         }
+        if let Some(port) = self.wait_for_debugger {
+            envs.insert(
+                "MIRRORD_WAIT_FOR_DEBUGGER_PORT".as_ref(),
+                Cow::Owned(port.to_string().into()),
+            );
+        }
 
         envs
     }
@@ -436,8 +493,16 @@ pub(super) struct ExecArgs {
     #[clap(flatten)]
     pub params: Box<ExecParams>,
 
+    /// Only check that mirrord can access the cluster, connect to the agent and fetch the remote
+    /// environment, then exit without running the binary.
+    ///
+    /// Exits with a non-zero code when any of these steps fails.
+    #[arg(long)]
+    pub only_check: bool,
+
     /// Binary to execute and connect with the remote pod.
-    pub binary: String,
+    #[arg(required_unless_present = "only_check")]
+    pub binary: Option<String>,
 
     /// Arguments to pass to the binary.
     pub(super) binary_args: Vec<String>,
@@ -857,7 +922,11 @@ fn hex_id(raw: &str) -> Result<u64, String> {
 
 #[derive(ValueEnum, Clone, Debug)]
 pub enum Format {
+    /// JSON array of target paths, or a JSON object with target paths and namespaces when
+    /// [`ListTargetArgs::RICH_OUTPUT_ENV`] is set.
     Json,
+    /// Versioned JSON object with the details of each target, see `list::DetailedTargets`.
+    DetailedJson,
 }
 
 #[derive(Args, Debug)]
@@ -876,13 +945,17 @@ pub(super) struct ListTargetArgs {
     #[arg(short = 'n', long = "namespace")]
     pub namespace: Option<String>,
 
+    /// List targets in all namespaces.
+    #[arg(short = 'A', long, conflicts_with = "namespace")]
+    pub all_namespaces: bool,
+
     /// Specify config file to use.
     #[arg(short = 'f', long, value_hint = ValueHint::FilePath)]
     pub config_file: Option<PathBuf>,
 
     /// Specify the type of target to be retrieved. If `None`, all types are retrieved.
     /// Can be used multiple times to specify multiple target types.
-    #[arg(short = 't', long)]
+    #[arg(short = 't', long, visible_alias = "kind")]
     pub target_type: Option<Vec<TargetType>>,
 }
 
@@ -938,6 +1011,31 @@ pub(super) enum DiagnoseCommand {
         #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
         config_file: Option<PathBuf>,
     },
+    /// Print the most recent logs of the agent of a running session, without access to the
+    /// agent's pod logs.
+    ///
+    /// Works only for sessions without the mirrord operator.
+    AgentLogs {
+        /// Specify config file to use
+        #[arg(short = 'f', long, value_hint = ValueHint::FilePath, default_missing_value = "./.mirrord/mirrord.json", num_args = 0..=1)]
+        config_file: Option<PathBuf>,
+
+        /// How many of the most recent log lines to print.
+        #[arg(long, default_value_t = 200)]
+        tail: u64,
+
+        /// Name of the pod in which the agent of the session runs.
+        #[arg(long)]
+        agent_pod: String,
+
+        /// Namespace of the agent pod, defaults to the namespace of the kube context.
+        #[arg(long)]
+        agent_namespace: Option<String>,
+
+        /// Port on which the agent accepts connections, read from the agent pod by default.
+        #[arg(long)]
+        agent_port: Option<u16>,
+    },
 }
 
 // `mirrord container` command
@@ -1172,6 +1270,125 @@ pub struct FixKubeconfig {
     pub dry_run: bool,
 }
 
+/// `mirrord config` args.
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    /// Command to use with `mirrord config`.
+    #[command(subcommand)]
+    pub command: ConfigCommand,
+}
+
+/// `mirrord config` commands.
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Print the documentation of a config field, e.g.
+    /// `mirrord config docs feature.network.incoming.on_concurrent_steal`.
+    Docs(ConfigDocsArgs),
+}
+
+/// `mirrord config docs` args
+#[derive(Args, Debug)]
+pub struct ConfigDocsArgs {
+    /// Dotted path of the config field.
+    pub path: String,
+}
+
+/// `mirrord init` args
+#[derive(Args, Debug)]
+pub struct InitArgs {
+    /// Don't ask any questions, write a config with the default values.
+    #[arg(long)]
+    pub defaults: bool,
+
+    /// Overwrite the config file if it already exists.
+    #[arg(long)]
+    pub force: bool,
+
+    /// Format of the config file, written to `.mirrord/mirrord.<format>`.
+    #[arg(long, value_enum, default_value_t = ConfigFormat::Json)]
+    pub format: ConfigFormat,
+
+    /// Kube context to use from Kubeconfig when looking for targets.
+    #[arg(long)]
+    pub context: Option<String>,
+}
+
+/// `mirrord session` args
+#[derive(Args, Debug)]
+pub struct SessionArgs {
+    #[command(subcommand)]
+    pub command: SessionInspectCommand,
+}
+
+/// Commands for inspecting a running mirrord session.
+#[derive(Subcommand, Debug)]
+pub enum SessionInspectCommand {
+    /// Show the CPU and memory usage of the remote target pod, the local application and the
+    /// mirrord internal proxy in one view.
+    ///
+    /// The target pod usage comes from the Kubernetes metrics API, and is shown as N/A when the
+    /// metrics-server is not installed in the cluster.
+    Top(Box<SessionTopArgs>),
+
+    /// Resume stealing incoming traffic after it was downgraded by
+    /// `feature.network.incoming.auto_downgrade`.
+    ResumeSteal(SessionResumeStealArgs),
+}
+
+/// `mirrord session resume-steal` args
+#[derive(Args, Debug)]
+pub struct SessionResumeStealArgs {
+    /// Pid of the local application running with mirrord.
+    ///
+    /// Can be omitted when there's only one mirrord session running.
+    #[arg(long)]
+    pub pid: Option<u32>,
+}
+
+/// `mirrord session top` args
+#[derive(Args, Debug)]
+pub struct SessionTopArgs {
+    /// Pid of the local application running with mirrord.
+    ///
+    /// Can be omitted when there's only one mirrord session running.
+    #[arg(long)]
+    pub pid: Option<u32>,
+
+    /// Print the usage once and exit, instead of refreshing it.
+    #[arg(long)]
+    pub once: bool,
+
+    /// Print the usage as JSON.
+    #[arg(long, requires = "once")]
+    pub json: bool,
+
+    /// How often the usage is refreshed, in seconds.
+    #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u64).range(1..))]
+    pub interval: u64,
+}
+
+/// Format of the config file created with `mirrord init`.
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum, Debug)]
+pub enum ConfigFormat {
+    /// JSON, comments are written as Tera comments.
+    Json,
+    /// TOML.
+    Toml,
+    /// YAML.
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// File extension of this format.
+    pub fn extension(self) -> &'static str {
+        match self {
+            ConfigFormat::Json => "json",
+            ConfigFormat::Toml => "toml",
+            ConfigFormat::Yaml => "yaml",
+        }
+    }
+}
+
 /// Arguments for `mirrord preview` command.
 #[derive(Args, Debug)]
 pub(super) struct PreviewArgs {
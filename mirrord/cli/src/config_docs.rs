@@ -0,0 +1,20 @@
+//! `mirrord config docs <path>` prints the documentation of a config field, so that users don't
+//! have to look it up on the website.
+
+use mirrord_config::docs::ConfigDocs;
+
+use crate::{
+    CliResult,
+    config::{ConfigArgs, ConfigCommand},
+};
+
+pub fn config_command(args: ConfigArgs) -> CliResult<()> {
+    match args.command {
+        ConfigCommand::Docs(args) => {
+            let docs = ConfigDocs::default().field(&args.path)?;
+            print!("{docs}");
+        }
+    }
+
+    Ok(())
+}
@@ -3,11 +3,15 @@ use std::{collections::HashSet, time::Duration};
 use mirrord_analytics::Reporter;
 use mirrord_config::{
     LayerConfig,
+    retry::StartupRetryConfig,
     target::{Target, TargetDisplay},
 };
 use mirrord_intproxy::agent_conn::AgentConnectInfo;
 use mirrord_kube::{
-    api::{container::ContainerConfig, kubernetes::KubernetesAPI},
+    api::{
+        container::{ContainerConfig, random_agent_name},
+        kubernetes::KubernetesAPI,
+    },
     error::KubeApiError,
     resolved::ResolvedTarget,
 };
@@ -188,34 +192,149 @@ pub(crate) async fn create_and_connect<P: Progress, R: Reporter>(
 
     let agent_container_config = ContainerConfig {
         support_ipv6: config.feature.network.ipv6,
+        max_pending_requests: config
+            .feature
+            .network
+            .incoming
+            .http_filter
+            .max_pending_requests,
+        limit_to_target_container: config.feature.network.incoming.limit_to_target_container,
         ..Default::default()
     };
-    let agent_connect_info = tokio::time::timeout(
-        Duration::from_secs(config.agent.startup_timeout),
-        k8s_api.create_agent(
-            progress,
-            &config.target,
-            Some(&mut config.feature.network),
-            agent_container_config,
-        ),
-    )
-    .await
-    .unwrap_or(Err(KubeApiError::AgentReadyTimeout))
-    .map_err(|error| CliError::friendlier_error_or_else(error, CliError::CreateAgentFailed))?;
+    let mut retry = AgentConnectRetry::new(&config.startup_retry);
+
+    let agent_connect_info = loop {
+        // Named upfront, so that we can clean up after a failed attempt.
+        let agent_name = random_agent_name();
+        let result = tokio::time::timeout(
+            Duration::from_secs(config.agent.startup_timeout),
+            k8s_api.create_agent(
+                progress,
+                &config.target,
+                Some(&mut config.feature.network),
+                ContainerConfig {
+                    name: Some(agent_name.clone()),
+                    ..agent_container_config.clone()
+                },
+            ),
+        )
+        .await
+        .unwrap_or(Err(KubeApiError::AgentReadyTimeout));
+
+        let error = match result {
+            Ok(agent_connect_info) => break agent_connect_info,
+            Err(error) => error,
+        };
+
+        if retry.next_backoff(&error).is_none() {
+            return Err(CliError::friendlier_error_or_else(
+                error,
+                CliError::CreateAgentFailed,
+            ));
+        }
+
+        // Retrying with the agent of the failed attempt still around could leave it running.
+        if let Err(cleanup_error) = k8s_api.delete_agent_job(&agent_name).await {
+            tracing::warn!(
+                %cleanup_error,
+                agent_name,
+                "Failed to clean up after a failed attempt to create the agent",
+            );
+            return Err(CliError::friendlier_error_or_else(
+                error,
+                CliError::CreateAgentFailed,
+            ));
+        }
+
+        retry.wait(progress, "create the agent", &error).await;
+    };
 
-    let conn = Connection::<Client>::from_stream(
-        k8s_api
+    // The agent is already running, so we only retry the connection.
+    let stream = loop {
+        match k8s_api
             .create_connection_portforward(agent_connect_info.clone())
             .await
-            .map_err(|error| {
-                CliError::friendlier_error_or_else(error, CliError::AgentConnectionFailed)
-            })?,
-    )
-    .await?;
+        {
+            Ok(stream) => break stream,
+            Err(error) if retry.next_backoff(&error).is_some() => {
+                retry.wait(progress, "connect to the agent", &error).await;
+            }
+            Err(error) => {
+                return Err(CliError::friendlier_error_or_else(
+                    error,
+                    CliError::AgentConnectionFailed,
+                ));
+            }
+        }
+    };
+
+    let conn = Connection::<Client>::from_stream(stream).await?;
 
     Ok((AgentConnectInfo::DirectKubernetes(agent_connect_info), conn))
 }
 
+/// Retries of creating and connecting to the agent in [`create_and_connect`], configured with
+/// [`StartupRetryConfig::agent_retries`].
+///
+/// Only transient failures are retried (see [`KubeApiError::is_transient`]), with an exponential
+/// backoff between [`StartupRetryConfig::min_ms`] and [`StartupRetryConfig::max_ms`]. The retries
+/// are shared between creating the agent and connecting to it.
+struct AgentConnectRetry {
+    /// How many retries were made so far.
+    retries: u32,
+
+    /// Limits the amount of retries.
+    max_retries: u32,
+
+    /// How long to wait before the next retry.
+    backoff: Duration,
+
+    /// Limits the [`AgentConnectRetry::backoff`].
+    max_backoff: Duration,
+
+    /// The backoff returned by the last [`AgentConnectRetry::next_backoff`].
+    current_backoff: Duration,
+}
+
+impl AgentConnectRetry {
+    fn new(config: &StartupRetryConfig) -> Self {
+        Self {
+            retries: 0,
+            max_retries: config.agent_retries,
+            backoff: Duration::from_millis(config.min_ms),
+            max_backoff: Duration::from_millis(config.max_ms),
+            current_backoff: Duration::ZERO,
+        }
+    }
+
+    /// Returns how long to wait before retrying after the given `error`, or [`None`] if we should
+    /// give up.
+    fn next_backoff(&mut self, error: &KubeApiError) -> Option<Duration> {
+        if !error.is_transient() || self.retries >= self.max_retries {
+            return None;
+        }
+
+        self.retries += 1;
+        self.current_backoff = self.backoff;
+        self.backoff = self.backoff.saturating_mul(2).min(self.max_backoff);
+
+        Some(self.current_backoff)
+    }
+
+    /// Reports the retry of the failed `action` with the `progress`, and waits for the backoff
+    /// returned by the last [`AgentConnectRetry::next_backoff`].
+    async fn wait<P: Progress>(&self, progress: &P, action: &str, error: &KubeApiError) {
+        tracing::warn!(%error, retries = self.retries, action, "Retrying agent startup");
+        progress.warning(&format!(
+            "failed to {action} ({error}), retrying in {}ms ({}/{})",
+            self.current_backoff.as_millis(),
+            self.retries,
+            self.max_retries,
+        ));
+        tokio::time::sleep(self.current_backoff).await;
+    }
+}
+
 /// Verifies and adjusts the [`LayerConfig`] after we've determined that this run does not use the
 /// operator.
 fn process_config_oss<P: Progress>(config: &mut LayerConfig, progress: &mut P) -> CliResult<()> {
@@ -334,15 +453,65 @@ where
 
 #[cfg(test)]
 mod tests {
+    use std::time::Duration;
+
+    use kube::core::ErrorResponse;
     use mirrord_config::{
         LayerFileConfig,
         config::{ConfigContext, MirrordConfig},
+        retry::StartupRetryFileConfig,
         target::{Target, TargetFileConfig, pod::PodTarget, service::ServiceTarget},
     };
+    use mirrord_kube::error::KubeApiError;
     use mirrord_progress::NullProgress;
     use rstest::rstest;
 
-    use crate::connection::process_config_oss;
+    use crate::connection::{AgentConnectRetry, process_config_oss};
+
+    fn api_error(code: u16) -> KubeApiError {
+        KubeApiError::KubeError(kube::Error::Api(ErrorResponse {
+            status: "Failure".to_string(),
+            message: "mocked failure".to_string(),
+            reason: "mocked".to_string(),
+            code,
+        }))
+    }
+
+    /// Verifies that only transient errors are retried, with a doubling backoff capped at
+    /// `startup_retry.max_ms`, up to `startup_retry.agent_retries` times.
+    #[rstest]
+    #[case::unavailable(api_error(503), 5000, &[500, 1000, 2000])]
+    #[case::pod_deleted(KubeApiError::AgentPodDeleted, 5000, &[500, 1000, 2000])]
+    #[case::port_forward(KubeApiError::PortForwardFailed, 5000, &[500, 1000, 2000])]
+    #[case::timeout(KubeApiError::AgentReadyTimeout, 5000, &[500, 1000, 2000])]
+    #[case::capped(api_error(503), 800, &[500, 800, 800])]
+    #[case::forbidden(api_error(403), 5000, &[])]
+    #[case::unauthorized(api_error(401), 5000, &[])]
+    fn agent_connect_retry(
+        #[case] error: KubeApiError,
+        #[case] max_ms: u64,
+        #[case] expected_backoffs_ms: &[u64],
+    ) {
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let startup_retry = StartupRetryFileConfig {
+            max_ms: Some(max_ms),
+            ..Default::default()
+        }
+        .generate_config(&mut cfg_context)
+        .unwrap();
+        let mut retry = AgentConnectRetry::new(&startup_retry);
+
+        let backoffs = std::iter::from_fn(|| retry.next_backoff(&error)).collect::<Vec<_>>();
+
+        assert_eq!(
+            backoffs,
+            expected_backoffs_ms
+                .iter()
+                .copied()
+                .map(Duration::from_millis)
+                .collect::<Vec<_>>()
+        );
+    }
 
     /// Ensure that when `process_config_oss` is called, operator-only target types are disallowed.
     /// This occurs when `create_and_connect` fails to establish a connection with the operator.
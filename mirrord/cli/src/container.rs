@@ -1,6 +1,6 @@
 #[cfg(not(target_os = "windows"))]
 use std::os::unix::process::ExitStatusExt;
-use std::{net::SocketAddr, ops::Not, path::PathBuf, process::Stdio};
+use std::{convert::Infallible, net::SocketAddr, ops::Not, path::PathBuf, process::Stdio};
 
 use clap::ValueEnum;
 pub use command_display::CommandDisplay;
@@ -21,7 +21,7 @@ use crate::{
     container::{command_builder::RuntimeCommandBuilder, sidecar::IntproxySidecar},
     ensure_not_nested,
     error::{CliResult, ContainerError},
-    execution::{LINUX_INJECTION_ENV_VAR, MirrordExecution},
+    execution::{LINUX_INJECTION_ENV_VAR, MirrordExecution, injection_env_var},
     logging::pipe_intproxy_sidecar_logs,
     user_data::UserData,
     util::MIRRORD_CONSOLE_ADDR_ENV,
@@ -175,8 +175,11 @@ async fn prepare_proxies<P: Progress>(
     runtime_command.add_network(format!("container:{}", sidecar.container_id()));
     // Add the layer file to the user application container.
     runtime_command.add_volumes_from(sidecar.container_id());
-    // Inject the layer into the user application.
-    runtime_command.add_env(LINUX_INJECTION_ENV_VAR, &resolve_library_path(config)?);
+    // Inject the layer into the user application, the container always runs Linux.
+    runtime_command.add_env(
+        injection_env_var(&config.experimental, LINUX_INJECTION_ENV_VAR),
+        &resolve_library_path(config)?,
+    );
     runtime_command.add_env(LayerConfig::RESOLVED_CONFIG_ENV, &config.encode()?);
 
     // Add platform specification if configured
@@ -202,7 +205,7 @@ async fn prepare_proxies<P: Progress>(
 ///
 /// 1. Spawns the agent (cluster), mirrord-extproxy (natively), and mirrord-intproxy (sidecar).
 /// 2. Adds additional env, volume, and network to the user container command.
-/// 3. Executes the user container command.
+/// 3. Executes the user container command, starting mirrord-extproxy again if it dies unexpectedly.
 #[tracing::instrument(level = Level::DEBUG, skip(watch), ret, err(level = Level::DEBUG, Debug))]
 pub async fn container_command(
     runtime_args: RuntimeArgs,
@@ -230,7 +233,7 @@ pub async fn container_command(
 
     adjust_container_config_for_wsl(runtime_args.runtime, &mut config);
 
-    let (runtime_command, _execution_info, _tls_setup) =
+    let (runtime_command, execution_info, _tls_setup) =
         prepare_proxies(&mut analytics, &progress, &mut config, runtime_args.runtime).await?;
 
     progress.success(None);
@@ -246,7 +249,20 @@ pub async fn container_command(
         .stdout(Stdio::inherit())
         .stderr(Stdio::inherit());
 
-    let status = runtime_command.status().await.map_err(|error| {
+    // Keeps the external proxy up while the container runs.
+    let supervise_extproxy = async {
+        if let Err(error) = execution_info.supervise_external(&progress).await {
+            progress.warning(&format!("mirrord session can't continue: {error}"));
+        }
+
+        std::future::pending::<Infallible>().await
+    };
+
+    let status = tokio::select! {
+        status = runtime_command.status() => status,
+        never = supervise_extproxy => match never {},
+    };
+    let status = status.map_err(|error| {
         analytics.set_error(AnalyticsError::BinaryExecuteFailed);
 
         ContainerError::CommandExec {
@@ -270,7 +286,7 @@ pub async fn container_command(
 /// 2. Adds additional env, volume, and network to the user container command.
 /// 3. Outputs the [`ExtensionRuntimeCommand`](command_builder::ExtensionRuntimeCommand) for the
 ///    extension.
-/// 4. Waits for mirrord-extproxy exit.
+/// 4. Waits for mirrord-extproxy exit, starting it again if it dies unexpectedly.
 #[tracing::instrument(level = Level::DEBUG, skip(watch), ret, err(level = Level::DEBUG, Debug))]
 pub async fn container_ext_command(
     config_file: Option<PathBuf>,
@@ -299,7 +315,7 @@ pub async fn container_ext_command(
 
     let output = serde_json::to_string(&runtime_command.into_command_extension_params())?;
     progress.success(Some(&output));
-    execution_info.wait().await?;
+    execution_info.supervise_external(&progress).await?;
 
     Ok(())
 }
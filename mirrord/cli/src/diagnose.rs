@@ -1,9 +1,12 @@
 use std::{path::Path, time::Duration};
 
+use k8s_openapi::api::core::v1::Pod;
+use kube::Api;
 use mirrord_analytics::NullReporter;
 use mirrord_config::{LayerConfig, config::ConfigContext};
+use mirrord_kube::api::kubernetes::{AgentKubernetesConnectInfo, KubernetesAPI};
 use mirrord_progress::{Progress, ProgressTracker};
-use mirrord_protocol::{ClientMessage, DaemonMessage};
+use mirrord_protocol::{ClientMessage, DaemonMessage, agent_logs::AGENT_LOGS_VERSION};
 use mirrord_protocol_io::{Client, Connection};
 use tokio::time::Instant;
 use tracing::Level;
@@ -89,9 +92,223 @@ async fn diagnose_latency(config: Option<&Path>) -> CliResult<()> {
     Ok(())
 }
 
+/// Returns the port on which the mirrord-agent running in the `pod` accepts connections, taken
+/// from the command line of its container.
+fn agent_port(pod: &Pod) -> Option<u16> {
+    let spec = pod.spec.as_ref()?;
+
+    spec.containers
+        .iter()
+        .filter_map(|container| container.command.as_ref())
+        .chain(
+            spec.ephemeral_containers
+                .iter()
+                .flatten()
+                .filter_map(|container| container.command.as_ref()),
+        )
+        .filter(|command| {
+            command
+                .first()
+                .is_some_and(|program| program.ends_with("mirrord-agent"))
+        })
+        .find_map(|command| {
+            command
+                .windows(2)
+                .find(|args| args[0] == "-l")
+                .and_then(|args| args[1].parse().ok())
+        })
+}
+
+/// Connects to the already running agent in the `pod_name` pod.
+///
+/// Agents created by the mirrord-operator accept only connections from the operator, so this
+/// works only for sessions without the operator.
+async fn connect_to_agent<P: Progress>(
+    config: &LayerConfig,
+    progress: &P,
+    pod_name: String,
+    namespace: Option<String>,
+    port: Option<u16>,
+) -> CliResult<Connection<Client>> {
+    let k8s_api = KubernetesAPI::create(config, progress)
+        .await
+        .map_err(|error| {
+            CliError::friendlier_error_or_else(error, CliError::AgentConnectionFailed)
+        })?;
+
+    let pod_namespace = namespace.unwrap_or_else(|| k8s_api.client().default_namespace().into());
+    let agent_port = match port {
+        Some(port) => port,
+        None => {
+            let pod = Api::<Pod>::namespaced(k8s_api.client().clone(), &pod_namespace)
+                .get(&pod_name)
+                .await
+                .map_err(|error| {
+                    CliError::AgentLogsFailed(format!(
+                        "failed to get the agent pod {pod_namespace}/{pod_name}: {error}"
+                    ))
+                })?;
+
+            agent_port(&pod).ok_or_else(|| {
+                CliError::AgentLogsFailed(format!(
+                    "pod {pod_namespace}/{pod_name} does not run the mirrord-agent, \
+                    or its port is unknown"
+                ))
+            })?
+        }
+    };
+
+    let stream = k8s_api
+        .create_connection_portforward(AgentKubernetesConnectInfo {
+            pod_name,
+            pod_namespace,
+            agent_port,
+        })
+        .await
+        .map_err(|error| {
+            CliError::friendlier_error_or_else(error, CliError::AgentConnectionFailed)
+        })?;
+
+    Ok(Connection::<Client>::from_stream(stream).await?)
+}
+
+/// Print the most recent log lines of the agent of a running session.
+#[tracing::instrument(level = Level::TRACE, ret)]
+async fn diagnose_agent_logs(
+    config: Option<&Path>,
+    tail: u64,
+    agent_pod: String,
+    agent_namespace: Option<String>,
+    agent_port: Option<u16>,
+) -> CliResult<()> {
+    let mut progress = ProgressTracker::from_env("mirrord agent logs");
+
+    let mut context = ConfigContext::default().override_env_opt(LayerConfig::FILE_PATH_ENV, config);
+    let config = LayerConfig::resolve(&mut context)?;
+
+    if !config.use_proxy {
+        remove_proxy_env();
+    }
+
+    let mut connection =
+        connect_to_agent(&config, &progress, agent_pod, agent_namespace, agent_port).await?;
+
+    connection
+        .send(ClientMessage::SwitchProtocolVersion(
+            mirrord_protocol::VERSION.clone(),
+        ))
+        .await;
+    let version = match recv(&mut connection).await? {
+        DaemonMessage::SwitchProtocolVersionResponse(version) => version,
+        message => {
+            return Err(CliError::AgentLogsFailed(format!(
+                "agent sent an unexpected message: {message:?}"
+            )));
+        }
+    };
+
+    if !AGENT_LOGS_VERSION.matches(&version) {
+        return Err(CliError::AgentLogsFailed(format!(
+            "agent uses mirrord-protocol {version}, which does not support fetching logs"
+        )));
+    }
+
+    connection.send(ClientMessage::GetAgentLogs { tail }).await;
+    let logs = match recv(&mut connection).await? {
+        DaemonMessage::AgentLogs(logs) => logs,
+        message => {
+            return Err(CliError::AgentLogsFailed(format!(
+                "agent sent an unexpected message: {message:?}"
+            )));
+        }
+    };
+
+    if logs.truncated {
+        progress.warning("Older agent logs are not available.");
+    }
+    progress.success(None);
+
+    for line in logs.lines {
+        println!("{line}");
+    }
+
+    Ok(())
+}
+
+/// Receives the next message from the agent, answering operator pings and skipping log messages.
+async fn recv(connection: &mut Connection<Client>) -> CliResult<DaemonMessage> {
+    loop {
+        match connection.recv().await {
+            Some(DaemonMessage::OperatorPing(id)) => {
+                connection.send(ClientMessage::OperatorPong(id)).await;
+            }
+            Some(DaemonMessage::LogMessage(..)) => {}
+            Some(DaemonMessage::Close(message)) => {
+                return Err(CliError::AgentLogsFailed(format!(
+                    "agent closed connection with message: {message}"
+                )));
+            }
+            Some(message) => return Ok(message),
+            None => {
+                return Err(CliError::AgentLogsFailed(
+                    "agent unexpectedly closed connection".to_string(),
+                ));
+            }
+        }
+    }
+}
+
 /// Handle commands related to the operator `mirrord diagnose ...`
 pub(crate) async fn diagnose_command(args: DiagnoseArgs) -> CliResult<()> {
     match args.command {
         DiagnoseCommand::Latency { config_file } => diagnose_latency(config_file.as_deref()).await,
+        DiagnoseCommand::AgentLogs {
+            config_file,
+            tail,
+            agent_pod,
+            agent_namespace,
+            agent_port,
+        } => {
+            diagnose_agent_logs(
+                config_file.as_deref(),
+                tail,
+                agent_pod,
+                agent_namespace,
+                agent_port,
+            )
+            .await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::api::core::v1::Pod;
+
+    use super::agent_port;
+
+    #[test]
+    fn agent_port_from_command_line() {
+        let pod: Pod = serde_json::from_value(serde_json::json!({
+            "spec": {
+                "containers": [
+                    { "name": "app", "command": ["./app", "-l", "80"] },
+                ],
+                "ephemeralContainers": [
+                    {
+                        "name": "mirrord-agent-abc",
+                        "command": ["./mirrord-agent", "-l", "43210", "ephemeral"],
+                    },
+                ],
+            }
+        }))
+        .unwrap();
+        assert_eq!(agent_port(&pod), Some(43210));
+
+        let pod: Pod = serde_json::from_value(serde_json::json!({
+            "spec": { "containers": [{ "name": "app", "command": ["./app", "-l", "80"] }] }
+        }))
+        .unwrap();
+        assert_eq!(agent_port(&pod), None);
     }
 }
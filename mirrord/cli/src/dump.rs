@@ -2,6 +2,7 @@ use std::{
     collections::{HashMap, HashSet},
     convert::Infallible,
     fmt,
+    sync::Arc,
     time::Duration,
 };
 
@@ -31,8 +32,12 @@ use tracing::{debug, info};
 
 use super::config::DumpArgs;
 use crate::{
-    CliError, connection::create_and_connect, error::CliResult,
-    kube::kube_client_from_layer_config, user_data::UserData,
+    CliError,
+    connection::create_and_connect,
+    error::CliResult,
+    kube::kube_client_from_layer_config,
+    masking::{JsonMasker, Masker},
+    user_data::UserData,
 };
 
 /// Implements the `mirrord dump` command.
@@ -40,7 +45,8 @@ use crate::{
 /// This command:
 /// 1. Starts a mirrord session using the given config file and target arguments
 /// 2. Subscribes to mirror traffic from the specified ports
-/// 3. Prints all incoming traffic to stdout in a human friendly format
+/// 3. Prints all incoming traffic to stdout in a human friendly format, masking it according to
+///    `feature.network.incoming.masking`
 pub async fn dump_command(
     args: &DumpArgs,
     watch: drain::Watch,
@@ -50,6 +56,7 @@ pub async fn dump_command(
     let mut cfg_context = ConfigContext::default().override_envs(args.params.as_env_vars());
 
     let mut config = LayerConfig::resolve(&mut cfg_context)?;
+    let masker = Masker::new(&config.feature.network.incoming.masking)?;
 
     let mut progress = ProgressTracker::from_env("mirrord dump");
     let mut analytics = AnalyticsReporter::new(
@@ -119,7 +126,7 @@ pub async fn dump_command(
     };
 
     // Start the dump session
-    let session = DumpSession::new(connection, ports);
+    let session = DumpSession::new(connection, ports, masker);
     session.run(&mut progress).await?;
 
     Ok(())
@@ -166,10 +173,14 @@ struct DumpSession {
     ///
     /// Used when handling [`DaemonTcp::Close`].
     conn_id_to_req_id: HashMap<ConnectionId, HashSet<RequestId>>,
+    /// Masks the traffic before we print it.
+    masker: Arc<Masker>,
+    /// Masks JSON bodies of the requests that are still in progress.
+    json_maskers: HashMap<(ConnectionId, RequestId), JsonMasker>,
 }
 
 impl DumpSession {
-    fn new(connection: Connection<Client>, ports: Vec<u16>) -> Self {
+    fn new(connection: Connection<Client>, ports: Vec<u16>, masker: Masker) -> Self {
         let mut ping_interval = tokio::time::interval(Duration::from_secs(30));
         ping_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
@@ -180,6 +191,84 @@ impl DumpSession {
             ping_interval,
             queued_messages: Default::default(),
             conn_id_to_req_id: Default::default(),
+            masker: Arc::new(masker),
+            json_maskers: Default::default(),
+        }
+    }
+
+    /// Masks the head of a new request and prepares masking of its body frames.
+    fn start_request<B>(
+        &mut self,
+        connection_id: ConnectionId,
+        request_id: RequestId,
+        request: &mut InternalHttpRequest<B>,
+    ) {
+        if let Some(json_masker) = self.masker.json_masker(&request.headers) {
+            self.json_maskers
+                .insert((connection_id, request_id), json_masker);
+        }
+        self.masker.mask_headers(&mut request.headers);
+    }
+
+    /// Masks a body frame of a request started with [`Self::start_request`].
+    fn mask_frame(
+        &mut self,
+        connection_id: ConnectionId,
+        request_id: RequestId,
+        frame: InternalHttpBodyFrame,
+    ) -> InternalHttpBodyFrame {
+        match frame {
+            InternalHttpBodyFrame::Data(data) => {
+                match self.json_maskers.get_mut(&(connection_id, request_id)) {
+                    Some(json_masker) => {
+                        InternalHttpBodyFrame::Data(json_masker.feed(&data).into())
+                    }
+                    None => InternalHttpBodyFrame::Data(data),
+                }
+            }
+            InternalHttpBodyFrame::Trailers(mut trailers) => {
+                self.masker.mask_headers(&mut trailers);
+                InternalHttpBodyFrame::Trailers(trailers)
+            }
+        }
+    }
+
+    /// Prints body frames of a request started with [`Self::start_request`].
+    fn print_frames(
+        &mut self,
+        connection_id: ConnectionId,
+        request_id: RequestId,
+        frames: impl IntoIterator<Item = InternalHttpBodyFrame>,
+        is_last: bool,
+    ) {
+        for frame in frames {
+            println!(
+                "{}",
+                RequestFrame {
+                    connection_id,
+                    request_id,
+                    frame: self.mask_frame(connection_id, request_id, frame),
+                }
+            );
+        }
+
+        if is_last {
+            // Replacement of a masked value that was cut off by the end of the body.
+            let tail = self
+                .json_maskers
+                .remove(&(connection_id, request_id))
+                .map(JsonMasker::finish)
+                .unwrap_or_default();
+            if !tail.is_empty() {
+                println!(
+                    "{}",
+                    RequestFrame {
+                        connection_id,
+                        request_id,
+                        frame: InternalHttpBodyFrame::Data(tail.into()),
+                    }
+                );
+            }
         }
     }
 
@@ -257,6 +346,7 @@ impl DumpSession {
             DaemonTcp::Close(close) => match self.conn_id_to_req_id.remove(&close.connection_id) {
                 Some(request_ids) => {
                     for request_id in request_ids {
+                        self.json_maskers.remove(&(close.connection_id, request_id));
                         println!(
                             "## Request ID [{}:{}] finished",
                             close.connection_id, request_id
@@ -280,7 +370,14 @@ impl DumpSession {
                     }
                 }
             }
-            DaemonTcp::HttpRequest(req) => {
+            DaemonTcp::HttpRequest(mut req) => {
+                if let Some(body) = self
+                    .masker
+                    .mask_body(&req.internal_request.headers, &req.internal_request.body)
+                {
+                    req.internal_request.body = body.into();
+                }
+                self.masker.mask_headers(&mut req.internal_request.headers);
                 self.conn_id_to_req_id
                     .entry(req.connection_id)
                     .or_default()
@@ -303,7 +400,8 @@ impl DumpSession {
                     }
                 }
             }
-            DaemonTcp::HttpRequestFramed(req) => {
+            DaemonTcp::HttpRequestFramed(mut req) => {
+                self.start_request(req.connection_id, req.request_id, &mut req.internal_request);
                 self.conn_id_to_req_id
                     .entry(req.connection_id)
                     .or_default()
@@ -313,19 +411,20 @@ impl DumpSession {
                     req.connection_id, req.request_id, req.port,
                 );
                 println!("{}", RequestHead(&req.internal_request));
-                for frame in req.internal_request.body.0 {
-                    println!(
-                        "{}",
-                        RequestFrame {
-                            connection_id: req.connection_id,
-                            request_id: req.request_id,
-                            frame
-                        }
-                    );
-                }
+                self.print_frames(
+                    req.connection_id,
+                    req.request_id,
+                    req.internal_request.body.0,
+                    true,
+                );
             }
             DaemonTcp::HttpRequestChunked(chunked) => match chunked {
-                ChunkedRequest::StartV1(req) => {
+                ChunkedRequest::StartV1(mut req) => {
+                    self.start_request(
+                        req.connection_id,
+                        req.request_id,
+                        &mut req.internal_request,
+                    );
                     self.conn_id_to_req_id
                         .entry(req.connection_id)
                         .or_default()
@@ -335,18 +434,15 @@ impl DumpSession {
                         req.connection_id, req.request_id, req.port,
                     );
                     println!("{}", RequestHead(&req.internal_request));
-                    for frame in req.internal_request.body {
-                        println!(
-                            "{}",
-                            RequestFrame {
-                                connection_id: req.connection_id,
-                                request_id: req.request_id,
-                                frame
-                            }
-                        );
-                    }
+                    self.print_frames(
+                        req.connection_id,
+                        req.request_id,
+                        req.internal_request.body,
+                        false,
+                    );
                 }
-                ChunkedRequest::StartV2(req) => {
+                ChunkedRequest::StartV2(mut req) => {
+                    self.start_request(req.connection_id, req.request_id, &mut req.request);
                     self.conn_id_to_req_id
                         .entry(req.connection_id)
                         .or_default()
@@ -371,36 +467,32 @@ impl DumpSession {
                         req.request_id,
                     );
                     println!("{}", RequestHead(&req.request));
-                    for frame in req.request.body.frames {
-                        println!(
-                            "{}",
-                            RequestFrame {
-                                connection_id: req.connection_id,
-                                request_id: req.request_id,
-                                frame
-                            }
-                        );
-                    }
+                    self.print_frames(
+                        req.connection_id,
+                        req.request_id,
+                        req.request.body.frames,
+                        req.request.body.is_last,
+                    );
                 }
                 ChunkedRequest::Body(body) => {
-                    for frame in body.frames {
-                        println!(
-                            "{}",
-                            RequestFrame {
-                                connection_id: body.connection_id,
-                                request_id: body.request_id,
-                                frame
-                            }
-                        );
-                    }
+                    self.print_frames(
+                        body.connection_id,
+                        body.request_id,
+                        body.frames,
+                        body.is_last,
+                    );
                 }
                 ChunkedRequest::ErrorV1(error) => {
+                    self.json_maskers
+                        .remove(&(error.connection_id, error.request_id));
                     println!(
                         "## Request ID [{}:{}] failed",
                         error.connection_id, error.request_id
                     );
                 }
                 ChunkedRequest::ErrorV2(error) => {
+                    self.json_maskers
+                        .remove(&(error.connection_id, error.request_id));
                     println!(
                         "## Request ID [{}:{}] failed: {}",
                         error.connection_id, error.request_id, error.error_message
@@ -445,7 +537,9 @@ impl DumpSession {
                     }
                 );
             }
-            message @ DaemonTcp::SubscribeResult(..) => {
+            message @ (DaemonTcp::SubscribeResult(..)
+            | DaemonTcp::UnixSubscribeResult(..)
+            | DaemonTcp::NewUnixConnection(..)) => {
                 return Err(DumpSessionError::UnexpectedAgentMessage(Box::new(
                     DaemonMessage::Tcp(message),
                 )));
@@ -497,7 +591,10 @@ impl DumpSession {
                 | DaemonMessage::UdpOutgoing(..)
                 | DaemonMessage::Vpn(..)
                 | DaemonMessage::TcpSteal(..)
-                | DaemonMessage::ReverseDnsLookup(..)) => {
+                | DaemonMessage::ReverseDnsLookup(..)
+                | DaemonMessage::Udp(..)
+                | DaemonMessage::RemoteTime(..)
+                | DaemonMessage::AgentLogs(..)) => {
                     return Err(DumpSessionError::UnexpectedAgentMessage(Box::new(message)));
                 }
             }
@@ -1,11 +1,11 @@
-use std::{ffi::NulError, io, num::ParseIntError, path::PathBuf};
+use std::{ffi::NulError, io, num::ParseIntError, path::PathBuf, process::ExitCode};
 
 #[cfg(feature = "wizard")]
 use axum::response::{IntoResponse, Response};
 use kube::{self, core::ErrorResponse};
 use miette::Diagnostic;
 use mirrord_auth::error::ApiKeyError;
-use mirrord_config::config::ConfigError;
+use mirrord_config::{config::ConfigError, docs::ConfigDocsError};
 use mirrord_console::error::ConsoleError;
 use mirrord_intproxy::{
     agent_conn::{AgentConnectionError, ConnectionTlsError},
@@ -17,15 +17,19 @@ use mirrord_protocol_io::ProtocolError;
 use mirrord_tls_util::SecureChannelError;
 use mirrord_vpn::error::VpnError;
 use reqwest::StatusCode;
+use serde::Serialize;
 use thiserror::Error;
 
 use crate::{
     ci::error::CiError,
+    config::ErrorFormat,
     container::{CommandDisplay, IntproxySidecarError},
     dump::DumpSessionError,
     fix::FixKubeconfigError,
+    init::InitError,
     port_forward::PortForwardError,
     profile::ProfileError,
+    session::SessionError,
 };
 
 pub(crate) type CliResult<T, E = CliError> = core::result::Result<T, E>;
@@ -153,6 +157,10 @@ pub(crate) enum InternalProxyError {
     #[error("Initial ping pong with the agent failed: {0}")]
     #[diagnostic(help("{GENERAL_BUG}"))]
     InitialPingPongFailed(String),
+
+    #[error("Failed to start the metrics server on `{0}`: {1}")]
+    #[diagnostic(help("Check the `internal_proxy.metrics` address in your config."))]
+    MetricsSetup(String, std::io::Error),
 }
 
 /// Errors that can occur when executing the `mirrord operator setup` command.
@@ -214,6 +222,13 @@ pub(crate) enum CliError {
     #[diagnostic(help("Please check agent status and logs.{GENERAL_HELP}"))]
     InitialAgentCommFailed(String),
 
+    #[error("Timed out after {0} seconds while fetching {1} from the agent")]
+    #[diagnostic(help(
+        "Increase `feature.env.fetch_timeout` (or set `MIRRORD_ENV_FETCH_TIMEOUT`), or fetch \
+        fewer variables with `feature.env.include`/`feature.env.exclude`.{GENERAL_HELP}"
+    ))]
+    EnvFetchTimeout(u64, String),
+
     #[error("Failed to execute binary `{0}` with args {1:?}")]
     #[diagnostic(help(
         "Please open an issue on our GitHub repository with binary information:
@@ -255,6 +270,18 @@ pub(crate) enum CliError {
     ))]
     EnvFileAccessError(PathBuf, dotenvy::Error),
 
+    #[error("`{0}` from `feature.env.from_kube_resources` was not found in namespace `{1}`")]
+    #[diagnostic(help(
+        "Please check that the resource exists in the target's namespace.{GENERAL_HELP}"
+    ))]
+    KubeEnvResourceNotFound(String, String),
+
+    #[error("Failed to fetch `{0}` from `feature.env.from_kube_resources`: {1}")]
+    #[diagnostic(help(
+        "Please check that you have permissions to read the resource with `kubectl get`.{GENERAL_HELP}"
+    ))]
+    KubeEnvResourceFetchFailed(String, KubeApiError),
+
     #[cfg(target_os = "macos")]
     #[error("SIP Error: `{0:#?}`")]
     #[diagnostic(help(
@@ -315,6 +342,13 @@ pub(crate) enum CliError {
     #[diagnostic(help("{GENERAL_BUG}"))]
     InternalProxyWaitError(std::io::Error),
 
+    #[error("mirrord external proxy exited unexpectedly ({0})")]
+    #[diagnostic(help(
+        "Set `external_proxy.outage_timeout` to a non-zero value to let mirrord start the \
+        external proxy again.{GENERAL_HELP}"
+    ))]
+    ExternalProxyExited(std::process::ExitStatus),
+
     #[error("Failed to build async runtime: {0}")]
     #[diagnostic(help("{GENERAL_BUG}"))]
     RuntimeError(std::io::Error),
@@ -370,6 +404,10 @@ pub(crate) enum CliError {
     ))]
     PingPongFailed(String),
 
+    #[error("Failed to fetch the agent logs: {0}")]
+    #[diagnostic(help("{GENERAL_HELP}"))]
+    AgentLogsFailed(String),
+
     #[error("Failed to prepare mirrord operator client certificate: {0}")]
     #[diagnostic(help("{GENERAL_BUG}"))]
     OperatorClientCertError(String),
@@ -497,6 +535,18 @@ pub(crate) enum CliError {
     #[error("error while fixing kubeconfig")]
     FixKubeconfig(#[from] FixKubeconfigError),
 
+    #[error(transparent)]
+    #[diagnostic(help("The path should be dotted, e.g. `feature.network.incoming.mode`."))]
+    ConfigDocs(#[from] ConfigDocsError),
+
+    #[error("failed to create the mirrord config: {0}")]
+    #[diagnostic(help("{GENERAL_HELP}"))]
+    Init(#[from] InitError),
+
+    #[error("mirrord session command failed: {0}")]
+    #[diagnostic(help("{GENERAL_HELP}"))]
+    Session(#[from] SessionError),
+
     #[error("No image specified for preview environment")]
     #[diagnostic(help(
         "Specify the image using `-i <image>` or set `feature.preview.image` in your mirrord config file."
@@ -590,7 +640,158 @@ pub(crate) enum CliError {
     PreviewInvalidImage(String),
 }
 
+/// Categories of [`CliError`]s, each one with a stable process exit code, so that scripts can
+/// react to mirrord failures without parsing the error messages.
+///
+/// The codes must not change between releases.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+pub(crate) enum ErrorCategory {
+    /// Any error that does not fall into the other categories.
+    General = 1,
+    /// Invalid or conflicting mirrord configuration, or invalid arguments.
+    Config = 10,
+    /// The Kubernetes cluster could not be reached, e.g. bad kubeconfig or failed authentication.
+    Cluster = 11,
+    /// Failed to create the agent, connect to it, or communicate with it.
+    Agent = 12,
+    /// Failed to spawn a local process: the internal proxy or the user binary.
+    LocalSpawn = 13,
+    /// Failure reported by the mirrord operator, e.g. missing license or permissions.
+    Operator = 14,
+}
+
+impl ErrorCategory {
+    /// Exit code of the mirrord process that failed with an error of this category.
+    pub(crate) fn exit_code(self) -> u8 {
+        self as u8
+    }
+}
+
 impl CliError {
+    /// Returns the [`ErrorCategory`] of this error, which determines the process exit code.
+    pub(crate) fn category(&self) -> ErrorCategory {
+        match self {
+            Self::ConfigError(..)
+            | Self::MissingArg { .. }
+            | Self::EnvFileAccessError(..)
+            | Self::ProfileError(..)
+            | Self::NestedExec => ErrorCategory::Config,
+
+            Self::CreateKubeApiFailed(..)
+            | Self::ListTargetsFailed(..)
+            | Self::InvalidCertificate(..)
+            | Self::KubeAuthExecFailed(..)
+            | Self::KubeEnvResourceNotFound(..)
+            | Self::KubeEnvResourceFetchFailed(..)
+            | Self::WizardTargetError(..)
+            | Self::RuntimeDataResolution(..) => ErrorCategory::Cluster,
+
+            Self::CreateAgentFailed(..)
+            | Self::AgentConnectionFailed(..)
+            | Self::InitialAgentCommFailed(..)
+            | Self::EnvFetchTimeout(..)
+            | Self::PingPongFailed(..)
+            | Self::AgentLogsFailed(..)
+            | Self::AgentConnTlsError(..)
+            | Self::AgentPodDeleted
+            | Self::PortForwardingSetupError(..)
+            | Self::PortForwardingError(..) => ErrorCategory::Agent,
+
+            #[cfg(all(target_os = "macos", target_arch = "aarch64"))]
+            Self::RosettaMissing(..) => ErrorCategory::LocalSpawn,
+            #[cfg(target_os = "macos")]
+            Self::SipError(..) => ErrorCategory::LocalSpawn,
+            #[cfg(not(target_os = "windows"))]
+            Self::ExecveE2Big => ErrorCategory::LocalSpawn,
+            Self::BinaryExecuteFailed(..)
+            | Self::LayerExtractError(..)
+            | Self::InternalProxySpawnError(..)
+            | Self::InternalProxyWaitError(..)
+            | Self::ExternalProxyExited(..)
+            | Self::CliPathError(..)
+            | Self::ExecNulError(..)
+            | Self::BinaryWhichError(..)
+            | Self::ContainerError(..)
+            | Self::LocalRedisError(..) => ErrorCategory::LocalSpawn,
+
+            Self::OperatorSetupError(..)
+            | Self::OperatorStatusNotFound
+            | Self::FeatureRequiresOperatorError(..)
+            | Self::FeatureNotSupportedInOperatorError { .. }
+            | Self::OperatorBranchCreationFailed(..)
+            | Self::OperatorApiFailed(..)
+            | Self::OperatorApiForbidden(..)
+            | Self::OperatorLicenseExpired
+            | Self::ConnectRequestBuildError(..)
+            | Self::OperatorClientCertError(..)
+            | Self::OperatorNotInstalled
+            | Self::OperatorReturnedUnknownTargetType(..)
+            | Self::OperatorTargetResolution(..)
+            | Self::OperatorCopyTargetFailed { .. }
+            | Self::OperatorOperationTimeout { .. }
+            | Self::ApiKey(..)
+            | Self::PreviewImageRequired
+            | Self::PreviewTargetRequired
+            | Self::PreviewTargetResolutionFailed(..)
+            | Self::PreviewSessionRejected(..)
+            | Self::PreviewSessionFailed(..)
+            | Self::PreviewSessionDeleted
+            | Self::PreviewWatchFailed(..)
+            | Self::PreviewTimeout
+            | Self::PreviewListFailed(..)
+            | Self::PreviewDeleteFailed { .. }
+            | Self::PreviewDuplicateSession { .. }
+            | Self::PreviewNotFound(..)
+            | Self::PreviewKeyRequired
+            | Self::PreviewInvalidImage(..) => ErrorCategory::Operator,
+
+            #[cfg(feature = "wizard")]
+            Self::WizardIoError(..) => ErrorCategory::General,
+            Self::JsonSerializeError(..)
+            | Self::ConsoleConnectError(..)
+            | Self::ExternalProxyError(..)
+            | Self::InternalProxyError(..)
+            | Self::VpnError(..)
+            | Self::RuntimeError(..)
+            | Self::ParseInt(..)
+            | Self::DumpError(..)
+            | Self::InvalidBackoff(..)
+            | Self::MirrordForCi(..)
+            | Self::UnsupportedOnWindows(..)
+            | Self::FixKubeconfig(..)
+            | Self::ConfigDocs(..)
+            | Self::Init(..)
+            | Self::Session(..) => ErrorCategory::General,
+        }
+    }
+
+    /// Prints this error to stderr in the given `format`, and returns the exit code of its
+    /// [`ErrorCategory`].
+    pub(crate) fn report(self, format: ErrorFormat) -> ExitCode {
+        let category = self.category();
+
+        match format {
+            ErrorFormat::Human => eprintln!("Error: {:?}", miette::Report::new(self)),
+            ErrorFormat::Json => eprintln!("{}", self.to_json()),
+        }
+
+        ExitCode::from(category.exit_code())
+    }
+
+    /// Structured representation of this error, printed with [`ErrorFormat::Json`].
+    fn to_json(&self) -> serde_json::Value {
+        let category = self.category();
+
+        serde_json::json!({
+            "code": category.exit_code(),
+            "category": category,
+            "message": self.to_string(),
+            "hint": self.help().map(|help| help.to_string()),
+        })
+    }
+
     /// Here we give more meaning to some errors, instead of just letting them pass as
     /// whatever [`KubeApiError`] we're getting.
     ///
@@ -704,6 +905,7 @@ impl IntoResponse for CliError {
 #[cfg(test)]
 mod tests {
     use std::{
+        io,
         net::{Ipv4Addr, SocketAddr},
         sync::Arc,
     };
@@ -719,7 +921,10 @@ mod tests {
         server::conn::auto::Builder,
     };
     use k8s_openapi::api::core::v1::Pod;
-    use kube::{Api, api::ListParams};
+    use kube::{Api, api::ListParams, config::KubeconfigError};
+    use mirrord_config::config::ConfigError;
+    use mirrord_kube::error::KubeApiError;
+    use rstest::rstest;
     use rustls::{
         ServerConfig,
         crypto::aws_lc_rs::default_provider,
@@ -728,6 +933,8 @@ mod tests {
     use tokio::{net::TcpListener, sync::Notify};
     use tokio_rustls::TlsAcceptor;
 
+    use super::{CliError, ErrorCategory};
+
     /// With this test we're trying to `assert` that our [`kube`] crate is (somewhat)
     /// version-synced with [`rustls`]. To give a friendlier error message on kube requests
     /// when there's a certificate problem, we must dig down into the [`kube::Error`].
@@ -834,4 +1041,44 @@ mod tests {
                 .unwrap();
         });
     }
+
+    /// Verifies the stable exit codes of some common failures.
+    #[rstest]
+    #[case::config_conflict(
+        CliError::ConfigError(ConfigError::Conflict(
+            "cannot use `feature.network.incoming.mode` \"off\" with `http_filter`".to_string()
+        )),
+        ErrorCategory::Config,
+        10
+    )]
+    #[case::bad_kubeconfig(
+        CliError::friendlier_error_or_else(
+            KubeApiError::KubeConfigPathError(KubeconfigError::ReadConfig(
+                io::ErrorKind::NotFound.into(),
+                "/nonexistent/kubeconfig".into(),
+            )),
+            CliError::CreateKubeApiFailed,
+        ),
+        ErrorCategory::Cluster,
+        11
+    )]
+    #[case::intproxy_spawn(
+        CliError::InternalProxySpawnError("failed to spawn child process: EAGAIN".to_string()),
+        ErrorCategory::LocalSpawn,
+        13
+    )]
+    fn error_exit_codes(
+        #[case] error: CliError,
+        #[case] category: ErrorCategory,
+        #[case] exit_code: u8,
+    ) {
+        assert_eq!(error.category(), category);
+        assert_eq!(category.exit_code(), exit_code);
+
+        let json = error.to_json();
+        assert_eq!(json["code"], exit_code);
+        assert_eq!(json["category"], serde_json::to_value(category).unwrap());
+        assert_eq!(json["message"], error.to_string());
+        assert!(json["hint"].is_string());
+    }
 }
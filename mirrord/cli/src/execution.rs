@@ -3,34 +3,39 @@ use std::ffi::OsString;
 use std::{
     collections::{HashMap, HashSet},
     net::SocketAddr,
+    path::PathBuf,
     time::Duration,
 };
+#[cfg(unix)]
+use std::{ops::Not, path::Path};
 
 use mirrord_analytics::{AnalyticsError, AnalyticsReporter, Reporter};
 use mirrord_config::{
-    LayerConfig, MIRRORD_LAYER_INTPROXY_ADDR, MIRRORD_TEST_INTPROXY_ADDR, config::ConfigError,
-    external_proxy::MIRRORD_EXTPROXY_TLS_SETUP_PEM, feature::env::mapper::EnvVarsRemapper,
+    LayerConfig, MIRRORD_LAYER_INTPROXY_ADDR, MIRRORD_TEST_INTPROXY_ADDR,
+    external_proxy::MIRRORD_EXTPROXY_TLS_SETUP_PEM,
+    feature::env::{filter::EnvVarsFilter, mapper::EnvVarsRemapper},
+    util::FileSource,
 };
-use mirrord_intproxy::agent_conn::AgentConnectInfo;
+use mirrord_intproxy::{USER_NOTICE_PREFIX, agent_conn::AgentConnectInfo};
 use mirrord_progress::Progress;
-use mirrord_protocol::{ClientMessage, DaemonMessage, EnvVars, GetEnvVarsRequest, LogLevel};
+use mirrord_protocol::{ClientMessage, DaemonMessage, GetEnvVarsRequest, LogLevel};
 use mirrord_protocol_io::{Client, Connection};
 #[cfg(target_os = "macos")]
-use mirrord_sip::{SipError, SipPatchOptions, sip_patch};
+use mirrord_sip::{SipError, SipPatchOptions};
 use mirrord_tls_util::SecureChannelSetup;
 use semver::Version;
 use serde::Serialize;
 use tokio::{
     io::{AsyncBufReadExt, BufReader},
+    net::TcpStream,
     process::{Child, ChildStderr, Command},
     select,
     sync::mpsc::{self, UnboundedReceiver},
+    time::Instant,
 };
 use tokio_util::sync::CancellationToken;
 use tracing::{Level, debug, error, info, trace, warn};
 
-#[cfg(target_os = "macos")]
-use crate::extract::extract_arm64;
 #[cfg(unix)]
 use crate::util::reparent_to_init;
 use crate::{
@@ -40,10 +45,42 @@ use crate::{
     extract::extract_library,
     util::{get_user_git_branch, remove_proxy_env},
 };
+#[cfg(target_os = "macos")]
+use crate::{extract::extract_arm64, sip_cache::sip_patch_cached};
 
 /// Environment variable for saving the execution kind for analytics.
 pub const MIRRORD_EXECUTION_KIND_ENV: &str = "MIRRORD_EXECUTION_KIND";
 
+/// How long we wait for the internal proxy to accept our health check connection.
+const INTPROXY_HEALTH_CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long we wait for the rest of the internal proxy stderr when the health check fails.
+const INTPROXY_STDERR_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How often we try to start the external proxy again after it exited unexpectedly.
+const EXTPROXY_RESTART_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Extracts the layer library from the binary, or uses the existing file if `MIRRORD_LAYER_FILE`
+/// env var is set (for debugging).
+fn layer_library_path<P>(progress: &P) -> CliResult<PathBuf>
+where
+    P: Progress,
+{
+    match std::env::var("MIRRORD_LAYER_FILE") {
+        Ok(existing_path) => {
+            tracing::debug!(
+                "Using existing library file from MIRRORD_LAYER_FILE: {}",
+                existing_path
+            );
+            Ok(PathBuf::from(existing_path))
+        }
+        Err(_) => {
+            tracing::debug!("MIRRORD_LAYER_FILE not set, extracting library from binary");
+            extract_library(None, progress, true)
+        }
+    }
+}
+
 /// Alias to "LD_PRELOAD" enviromnent variable used to mount mirrord-layer on linux targets and as
 /// part of the `mirrord container` command.
 pub(crate) const LINUX_INJECTION_ENV_VAR: &str = "LD_PRELOAD";
@@ -54,6 +91,43 @@ pub(crate) const INJECTION_ENV_VAR: &str = LINUX_INJECTION_ENV_VAR;
 #[cfg(target_os = "macos")]
 pub(crate) const INJECTION_ENV_VAR: &str = "DYLD_INSERT_LIBRARIES";
 
+/// Name of the variable used to inject the layer, either
+/// [`experimental.
+/// injection_env_var`](mirrord_config::experimental::ExperimentalConfig::injection_env_var)
+/// or the `default` of the platform that the application runs on ([`INJECTION_ENV_VAR`] locally,
+/// [`LINUX_INJECTION_ENV_VAR`] in `mirrord container`).
+pub(crate) fn injection_env_var<'a>(
+    experimental: &'a mirrord_config::experimental::ExperimentalConfig,
+    default: &'a str,
+) -> &'a str {
+    experimental.injection_env_var.as_deref().unwrap_or(default)
+}
+
+/// Appends `lib_path` to the `existing` value of the injection variable.
+///
+/// Layer libraries left in the variable by other mirrord runs (possibly from a different mirrord
+/// installation) are removed, as they would be rejected by our internal proxy. Returns the new
+/// value and the removed libraries.
+#[cfg(unix)]
+fn injection_env_value(existing: Option<&str>, lib_path: &str) -> (String, Vec<String>) {
+    let (stale, mut libraries): (Vec<_>, Vec<_>) = existing
+        .into_iter()
+        .flat_map(|value| value.split(':'))
+        .filter(|library| library.is_empty().not() && *library != lib_path)
+        .partition(|library| {
+            Path::new(library)
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().contains("libmirrord_layer"))
+        });
+
+    libraries.push(lib_path);
+
+    (
+        libraries.join(":"),
+        stale.into_iter().map(ToString::to_string).collect(),
+    )
+}
+
 /// A handle to a running mirrord proxy (either internal proxy or external proxy).
 #[derive(Debug, Serialize)]
 pub(crate) struct MirrordExecution {
@@ -74,6 +148,43 @@ pub(crate) struct MirrordExecution {
 
     /// Whether this run uses mirrord operator.
     pub uses_operator: bool,
+
+    /// How to start the external proxy again, see [`MirrordExecution::supervise_external`].
+    #[serde(skip)]
+    external_proxy_restart: Option<ExternalProxyRestart>,
+}
+
+/// Starts the external proxy again after it exited unexpectedly, on the same address.
+#[derive(Debug)]
+struct ExternalProxyRestart {
+    command: Command,
+    /// How long we keep trying to start the external proxy.
+    outage_timeout: Duration,
+}
+
+impl ExternalProxyRestart {
+    /// Starts the external proxy, retrying every [`EXTPROXY_RESTART_INTERVAL`] until
+    /// [`Self::outage_timeout`] passes.
+    async fn spawn<P>(&mut self, progress: &P) -> CliResult<Child>
+    where
+        P: Progress,
+    {
+        let deadline = Instant::now() + self.outage_timeout;
+
+        loop {
+            match MirrordExecution::spawn_external_proxy(&mut self.command, progress).await {
+                Ok((child, address)) => {
+                    info!(%address, "Started the external proxy again");
+                    break Ok(child);
+                }
+                Err(error) if Instant::now() + EXTPROXY_RESTART_INTERVAL < deadline => {
+                    warn!(%error, "Failed to start the external proxy again, retrying");
+                    tokio::time::sleep(EXTPROXY_RESTART_INTERVAL).await;
+                }
+                Err(error) => break Err(error),
+            }
+        }
+    }
 }
 
 /// Struct that when dropped will cancel the token and wait on the join handle
@@ -95,9 +206,37 @@ where
         self.cancellation_token.cancel();
 
         while let Ok(line) = self.stderr_rx.try_recv() {
-            self.progress
-                .warning(format!("internal proxy stderr: {line}").as_str());
+            match line.strip_prefix(USER_NOTICE_PREFIX) {
+                Some(notice) => self.progress.info(notice),
+                None => self.progress.intproxy_stderr(&line),
+            }
+        }
+    }
+}
+
+impl<P> DropProgress<'_, P>
+where
+    P: Progress,
+{
+    /// Takes the stderr lines read so far, waiting up to `timeout` for the stream to end.
+    ///
+    /// Used when the child failed, so that its stderr is reported as a part of the error instead
+    /// of as warnings on drop.
+    async fn take_lines(&mut self, timeout: Duration) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        let _ = tokio::time::timeout(timeout, async {
+            while let Some(line) = self.stderr_rx.recv().await {
+                lines.push(line);
+            }
+        })
+        .await;
+
+        while let Ok(line) = self.stderr_rx.try_recv() {
+            lines.push(line);
         }
+
+        lines
     }
 }
 
@@ -193,31 +332,20 @@ impl MirrordExecution {
     where
         P: Progress,
     {
-        // Extract Layer from exe, or use existing file if MIRRORD_LAYER_FILE env var is set (for
-        // debugging)
-        let lib_path = match std::env::var("MIRRORD_LAYER_FILE") {
-            Ok(existing_path) => {
-                tracing::debug!(
-                    "Using existing library file from MIRRORD_LAYER_FILE: {}",
-                    existing_path
-                );
-                std::path::PathBuf::from(existing_path)
-            }
-            Err(_) => {
-                tracing::debug!("MIRRORD_LAYER_FILE not set, extracting library from binary");
-                extract_library(None, progress, true)?
-            }
-        };
-
         if !config.use_proxy {
             remove_proxy_env();
         }
 
         // Spawn agent and intproxy processes, unless MIRRORD_TEST_INTPROXY_ADDR is set and used
         // instead (test-only: skips agent connection and uses an existing intproxy).
-        let (mut env_vars, proxy_process, uses_operator) =
+        let (mut env_vars, proxy_process, uses_operator, lib_path) =
             match std::env::var(MIRRORD_TEST_INTPROXY_ADDR) {
-                Ok(addr) => (Self::setup_existing_intproxy(addr, config)?, None, false),
+                Ok(addr) => (
+                    Self::setup_existing_intproxy(addr, config)?,
+                    None,
+                    false,
+                    layer_library_path(progress)?,
+                ),
                 _ => {
                     // Box the large future to reduce the stack frame of start_internal.
                     Box::pin(Self::spawn_agent_and_intproxy(
@@ -237,24 +365,30 @@ impl MirrordExecution {
                 extract_arm64(progress, true)?.to_string_lossy().into(),
             );
 
-            // Fixes <https://github.com/metalbear-co/mirrord/issues/1745>
-            // by disabling the fork safety check in the Objective-C runtime.
-            env_vars.insert(
-                "OBJC_DISABLE_INITIALIZE_FORK_SAFETY".to_string(),
-                "YES".to_string(),
-            );
+            env_vars.extend(objc_fork_safety_env(&config.experimental));
         }
 
         let lib_path = lib_path.to_string_lossy().into_owned();
         #[cfg(unix)]
         {
-            // Set LD_PRELOAD/DYLD_INSERT_LIBRARIES
-            // If already exists, we append.
-            if let Ok(v) = std::env::var(INJECTION_ENV_VAR) {
-                env_vars.insert(INJECTION_ENV_VAR.to_string(), format!("{v}:{lib_path}"))
-            } else {
-                env_vars.insert(INJECTION_ENV_VAR.to_string(), lib_path)
-            };
+            // Set LD_PRELOAD/DYLD_INSERT_LIBRARIES (or the configured variable)
+            // If already exists, we append, unless configured to replace.
+            let injection_env_var = injection_env_var(&config.experimental, INJECTION_ENV_VAR);
+            let existing = config
+                .experimental
+                .injection_env_append
+                .then(|| std::env::var(injection_env_var).ok())
+                .flatten();
+            let (value, stale) = injection_env_value(existing.as_deref(), &lib_path);
+            if stale.is_empty().not() {
+                progress.warning(&format!(
+                    "{injection_env_var} contained mirrord layer libraries from other mirrord \
+                    runs, which were removed: {}. Check your environment for stale mirrord \
+                    settings.",
+                    stale.join(", ")
+                ));
+            }
+            env_vars.insert(injection_env_var.to_string(), value);
         }
         #[cfg(windows)]
         {
@@ -280,7 +414,7 @@ impl MirrordExecution {
                         });
                 executable
                     .and_then(|exe| {
-                        sip_patch(
+                        sip_patch_cached(
                             exe,
                             SipPatchOptions {
                                 patch: &config
@@ -305,7 +439,7 @@ impl MirrordExecution {
             }
         };
 
-        Ok(Self {
+        let execution = Self {
             environment: env_vars,
             child: proxy_process,
             patched_path,
@@ -317,14 +451,44 @@ impl MirrordExecution {
                 .map(|unset| unset.to_vec())
                 .unwrap_or_default(),
             uses_operator,
-        })
+            external_proxy_restart: None,
+        };
+        execution.report_summary(progress);
+
+        Ok(execution)
     }
 
+    /// Reports the summary of this execution with [`Progress::summary`].
+    fn report_summary<P: Progress>(&self, progress: &P) {
+        progress.summary(serde_json::json!({
+            "environment_count": self.environment.len(),
+            "patched_path": self.patched_path,
+            "uses_operator": self.uses_operator,
+        }));
+    }
+
+    /// Returns the [`mirrord_protocol`] version of the agent, and settles the version used on
+    /// this connection to ours.
+    ///
+    /// The agent responds to [`ClientMessage::SwitchProtocolVersion`] with the lower of its own
+    /// version and the one we sent, so we first offer a version higher than any real one to learn
+    /// the agent's own version.
     async fn get_agent_version(connection: &mut Connection<Client>) -> CliResult<Version> {
+        let agent_version =
+            Self::switch_protocol_version(connection, Version::new(u64::MAX, 0, 0)).await?;
+        Self::switch_protocol_version(connection, mirrord_protocol::VERSION.clone()).await?;
+
+        Ok(agent_version)
+    }
+
+    /// Sends [`ClientMessage::SwitchProtocolVersion`] with the given `version`, and returns the
+    /// version settled by the agent.
+    async fn switch_protocol_version(
+        connection: &mut Connection<Client>,
+        version: Version,
+    ) -> CliResult<Version> {
         connection
-            .send(ClientMessage::SwitchProtocolVersion(
-                mirrord_protocol::VERSION.clone(),
-            ))
+            .send(ClientMessage::SwitchProtocolVersion(version))
             .await;
 
         match connection.recv().await {
@@ -372,17 +536,67 @@ impl MirrordExecution {
         let env_vars = if config.feature.env.load_from_process.unwrap_or(false) {
             Default::default()
         } else {
-            Self::fetch_env_vars(config, &mut connection)
+            Self::fetch_env_vars(config, &mut connection, progress)
                 .await
                 .inspect_err(|_| analytics.set_error(AnalyticsError::EnvFetch))?
         };
 
         let encoded_config = config.encode()?;
 
+        let mut proxy_command =
+            Self::external_proxy_command(config, &connect_info, &encoded_config, tls, None)?;
+        let (proxy_process, proxy_addr) =
+            Self::spawn_external_proxy(&mut proxy_command, progress).await?;
+
+        // Restarted on the same port, so that the internal proxy sidecar can reconnect.
+        let external_proxy_restart = ExternalProxyRestart {
+            command: Self::external_proxy_command(
+                config,
+                &connect_info,
+                &encoded_config,
+                tls,
+                Some(proxy_addr.port()),
+            )?,
+            outage_timeout: Duration::from_secs(config.external_proxy.outage_timeout),
+        };
+
+        let execution = Self {
+            environment: env_vars,
+            child: Some(proxy_process),
+            patched_path: None,
+            env_to_unset: config
+                .feature
+                .env
+                .unset
+                .clone()
+                .map(|unset| unset.to_vec())
+                .unwrap_or_default(),
+            uses_operator: matches!(connect_info, AgentConnectInfo::Operator(..)),
+            external_proxy_restart: Some(external_proxy_restart),
+        };
+        execution.report_summary(progress);
+
+        Ok((execution, proxy_addr))
+    }
+
+    /// Prepares the command that starts the external proxy, listening on the given `port`, or on
+    /// a random one.
+    fn external_proxy_command(
+        config: &LayerConfig,
+        connect_info: &AgentConnectInfo,
+        encoded_config: &str,
+        tls: Option<&SecureChannelSetup>,
+        port: Option<u16>,
+    ) -> CliResult<Command> {
         let mut proxy_command =
             Command::new(std::env::current_exe().map_err(CliError::CliPathError)?);
+        proxy_command.arg("extproxy");
+
+        if let Some(port) = port {
+            proxy_command.arg("--port").arg(port.to_string());
+        }
+
         proxy_command
-            .arg("extproxy")
             // Start of debug args. Don't add real args after this point,
             // `_debug_args` Clap field will swallow them.
             .arg("--extproxy-log-destination")
@@ -394,14 +608,27 @@ impl MirrordExecution {
             .stdin(std::process::Stdio::null())
             .env(
                 AGENT_CONNECT_INFO_ENV_KEY,
-                serde_json::to_string(&connect_info)?,
+                serde_json::to_string(connect_info)?,
             )
-            .env(LayerConfig::RESOLVED_CONFIG_ENV, &encoded_config);
+            .env(LayerConfig::RESOLVED_CONFIG_ENV, encoded_config)
+            // The config might have been read from stdin, which can't be read again.
+            .env_remove(LayerConfig::FILE_PATH_ENV);
 
         if let Some(tls) = tls {
             proxy_command.env(MIRRORD_EXTPROXY_TLS_SETUP_PEM, tls.server_pem());
         }
 
+        Ok(proxy_command)
+    }
+
+    /// Starts the external proxy child process, and waits until it prints its address.
+    async fn spawn_external_proxy<P>(
+        proxy_command: &mut Command,
+        progress: &P,
+    ) -> CliResult<(Child, SocketAddr)>
+    where
+        P: Progress,
+    {
         let mut proxy_process = proxy_command.spawn().map_err(|e| {
             CliError::InternalProxySpawnError(format!("failed to spawn child process: {e}"))
         })?;
@@ -430,21 +657,7 @@ impl MirrordExecution {
                 ))
             })?;
 
-        let execution = Self {
-            environment: env_vars,
-            child: Some(proxy_process),
-            patched_path: None,
-            env_to_unset: config
-                .feature
-                .env
-                .unset
-                .clone()
-                .map(|unset| unset.to_vec())
-                .unwrap_or_default(),
-            uses_operator: matches!(connect_info, AgentConnectInfo::Operator(..)),
-        };
-
-        Ok((execution, proxy_addr))
+        Ok((proxy_process, proxy_addr))
     }
 
     /// Sets up environment for using an already-running internal proxy.
@@ -478,14 +691,19 @@ impl MirrordExecution {
     /// Establishes the agent connection, validates config against agent capabilities,
     /// fetches remote env vars (unless configured to load from process), and starts the
     /// internal proxy as a child process. Returns the environment map, child
-    /// intproxy process handle, and whether the run uses the operator.
+    /// intproxy process handle, whether the run uses the operator, and the path to the layer
+    /// library.
+    ///
+    /// The remote env is fetched concurrently with extracting the layer library and starting the
+    /// internal proxy. Our agent connection is kept open until both are done, so the agent does
+    /// not go away before the internal proxy connects to it.
     #[tracing::instrument(level = Level::TRACE, skip_all)]
     async fn spawn_agent_and_intproxy<P>(
         config: &mut LayerConfig,
         progress: &mut P,
         analytics: &mut AnalyticsReporter,
         mirrord_for_ci: Option<&MirrordCi>,
-    ) -> CliResult<(HashMap<String, String>, Option<Child>, bool)>
+    ) -> CliResult<(HashMap<String, String>, Option<Child>, bool, PathBuf)>
     where
         P: Progress,
     {
@@ -503,6 +721,13 @@ impl MirrordExecution {
             _ => None,
         };
 
+        if let Some(warning) = agent_protocol_version
+            .as_ref()
+            .and_then(|version| protocol_version_drift_warning(version, &mirrord_protocol::VERSION))
+        {
+            progress.warning(&warning);
+        }
+
         config
             .feature
             .network
@@ -510,16 +735,63 @@ impl MirrordExecution {
             .http_filter
             .ensure_usable_with(agent_protocol_version)?;
 
-        let mut env_vars = if config.feature.env.load_from_process.unwrap_or(false) {
-            Default::default()
-        } else {
-            Self::fetch_env_vars(config, &mut connection)
-                .await
-                .inspect_err(|_| analytics.set_error(AnalyticsError::EnvFetch))?
+        let encoded_config = config.encode()?;
+        let config = &*config;
+        let progress = &*progress;
+
+        // The connection IO is driven by its own task, so the agent prepares the remote env
+        // while we're busy here.
+        let fetch_env = async {
+            if config.feature.env.load_from_process.unwrap_or(false) {
+                Ok(Default::default())
+            } else {
+                Self::fetch_env_vars(config, &mut connection, progress).await
+            }
         };
+        let start_intproxy = async {
+            let lib_path = layer_library_path(progress)?;
+            let (proxy_process, intproxy_address) = Self::spawn_intproxy(
+                config,
+                &connect_info,
+                &encoded_config,
+                mirrord_for_ci,
+                progress,
+            )
+            .await?;
+            Ok::<_, CliError>((lib_path, proxy_process, intproxy_address))
+        };
+        let (env_vars, intproxy) = tokio::join!(fetch_env, start_intproxy);
 
-        let encoded_config = config.encode()?;
+        let mut env_vars =
+            env_vars.inspect_err(|_| analytics.set_error(AnalyticsError::EnvFetch))?;
+        let (lib_path, proxy_process, intproxy_address) = intproxy?;
 
+        env_vars.insert(LayerConfig::RESOLVED_CONFIG_ENV.into(), encoded_config);
+        env_vars.insert(
+            MIRRORD_LAYER_INTPROXY_ADDR.into(),
+            intproxy_address.to_string(),
+        );
+
+        Ok((
+            env_vars,
+            Some(proxy_process),
+            matches!(connect_info, AgentConnectInfo::Operator(..)),
+            lib_path,
+        ))
+    }
+
+    /// Starts the internal proxy child process, and waits until it prints its address and accepts
+    /// a connection on it.
+    async fn spawn_intproxy<P>(
+        config: &LayerConfig,
+        connect_info: &AgentConnectInfo,
+        encoded_config: &str,
+        mirrord_for_ci: Option<&MirrordCi>,
+        progress: &P,
+    ) -> CliResult<(Child, SocketAddr)>
+    where
+        P: Progress,
+    {
         let mut proxy_command =
             Command::new(std::env::current_exe().map_err(CliError::CliPathError)?);
         proxy_command.arg("intproxy");
@@ -537,11 +809,13 @@ impl MirrordExecution {
             .stderr(std::process::Stdio::piped())
             .stdin(std::process::Stdio::null())
             .kill_on_drop(true)
-            .env(LayerConfig::RESOLVED_CONFIG_ENV, &encoded_config);
+            .env(LayerConfig::RESOLVED_CONFIG_ENV, encoded_config)
+            // The config might have been read from stdin, which can't be read again.
+            .env_remove(LayerConfig::FILE_PATH_ENV);
 
         proxy_command.env(
             AGENT_CONNECT_INFO_ENV_KEY,
-            serde_json::to_string(&connect_info)?,
+            serde_json::to_string(connect_info)?,
         );
 
         #[cfg(unix)]
@@ -554,7 +828,7 @@ impl MirrordExecution {
         })?;
 
         let stderr = proxy_process.stderr.take().expect("stderr was piped");
-        let _stderr_guard = watch_stderr(stderr, progress).await;
+        let mut stderr_guard = watch_stderr(stderr, progress).await;
 
         let stdout = proxy_process.stdout.take().expect("stdout was piped");
 
@@ -589,67 +863,114 @@ impl MirrordExecution {
                 ))
             })?;
 
-        env_vars.insert(LayerConfig::RESOLVED_CONFIG_ENV.into(), encoded_config);
-        env_vars.insert(
-            MIRRORD_LAYER_INTPROXY_ADDR.into(),
-            intproxy_address.to_string(),
-        );
+        // The proxy might still die before the user application connects to it, which is much
+        // harder to make sense of from the application side.
+        if let Err(error) = Self::intproxy_health_check(intproxy_address).await {
+            let stderr = stderr_guard.take_lines(INTPROXY_STDERR_TIMEOUT).await;
+            let mut message =
+                format!("proxy is not accepting connections at {intproxy_address}: {error}");
+            if !stderr.is_empty() {
+                message.push_str("\nproxy stderr:\n");
+                message.push_str(&stderr.join("\n"));
+            }
 
-        Ok((
-            env_vars,
-            Some(proxy_process),
-            matches!(connect_info, AgentConnectInfo::Operator(..)),
-        ))
+            return Err(CliError::InternalProxySpawnError(message));
+        }
+
+        Ok((proxy_process, intproxy_address))
+    }
+
+    /// Makes a TCP connection to the internal proxy, and closes it right away.
+    async fn intproxy_health_check(intproxy_address: SocketAddr) -> std::io::Result<()> {
+        tokio::time::timeout(
+            INTPROXY_HEALTH_CHECK_TIMEOUT,
+            TcpStream::connect(intproxy_address),
+        )
+        .await
+        .map_err(|_| std::io::Error::from(std::io::ErrorKind::TimedOut))??;
+
+        Ok(())
+    }
+
+    /// Pre-flight for `mirrord exec --only-check`.
+    ///
+    /// Goes through the same steps as [`MirrordExecution::spawn_agent_and_intproxy`] up to
+    /// fetching the remote environment (only a single variable), but does not spawn the internal
+    /// proxy. The agent connection is closed when this function returns.
+    pub(crate) async fn check_cluster_access<P, R>(
+        config: &mut LayerConfig,
+        progress: &mut P,
+        analytics: &mut R,
+        mirrord_for_ci: Option<&MirrordCi>,
+    ) -> CliResult<()>
+    where
+        P: Progress,
+        R: Reporter,
+    {
+        let branch_name = get_user_git_branch().await;
+        let (connect_info, mut connection) =
+            create_and_connect(config, progress, analytics, branch_name, mirrord_for_ci)
+                .await
+                .inspect_err(|_| analytics.set_error(AnalyticsError::AgentConnection))?;
+
+        if let AgentConnectInfo::DirectKubernetes(_) = &connect_info {
+            let version = Self::get_agent_version(&mut connection).await?;
+            progress.info(&format!("agent protocol version: {version}"));
+            if let Some(warning) =
+                protocol_version_drift_warning(&version, &mirrord_protocol::VERSION)
+            {
+                progress.warning(&warning);
+            }
+        }
+
+        Self::get_remote_env_with_timeout(
+            config,
+            &mut connection,
+            Default::default(),
+            HashSet::from(["PATH".to_owned()]),
+        )
+        .await
+        .inspect_err(|_| analytics.set_error(AnalyticsError::EnvFetch))?;
+
+        Ok(())
     }
 
     /// Construct filter and retrieve remote environment from the connected agent using
     /// `MirrordExecution::get_remote_env`.
-    async fn fetch_env_vars(
+    async fn fetch_env_vars<P>(
         config: &LayerConfig,
         connection: &mut Connection<Client>,
-    ) -> CliResult<HashMap<String, String>> {
-        let (env_vars_exclude, env_vars_include) = match (
-            config
-                .feature
-                .env
-                .exclude
-                .clone()
-                .map(|exclude| exclude.join(";")),
-            config
-                .feature
-                .env
-                .include
-                .clone()
-                .map(|include| include.join(";")),
-        ) {
-            (Some(..), Some(..)) => {
-                return Err(CliError::ConfigError(ConfigError::Conflict(
-                    "cannot use both `include` and `exclude` filters for environment variables"
-                        .to_string(),
-                )));
-            }
-            (Some(exclude), None) => (HashSet::from(EnvVars(exclude)), HashSet::new()),
-            (None, Some(include)) => (HashSet::new(), HashSet::from(EnvVars(include))),
-            (None, None) => (HashSet::new(), HashSet::from(EnvVars("*".to_owned()))),
-        };
-
-        let mut env_vars = if !env_vars_exclude.is_empty() || !env_vars_include.is_empty() {
-            let communication_timeout =
-                Duration::from_secs(config.agent.communication_timeout.unwrap_or(30).into());
-
-            tokio::time::timeout(
-                communication_timeout,
-                Self::get_remote_env(connection, env_vars_exclude, env_vars_include),
+        progress: &P,
+    ) -> CliResult<HashMap<String, String>>
+    where
+        P: Progress,
+    {
+        let env_filter = EnvVarsFilter::new(&config.feature.env)?;
+        let GetEnvVarsRequest {
+            env_vars_filter,
+            env_vars_select,
+        } = env_filter.remote_request();
+
+        let mut env_vars = if !env_vars_filter.is_empty() || !env_vars_select.is_empty() {
+            let remote_env = Self::get_remote_env_with_timeout(
+                config,
+                connection,
+                env_vars_filter,
+                env_vars_select,
             )
-            .await
-            .map_err(|_| CliError::InitialAgentCommFailed("timeout".to_string()))??
+            .await?;
+            env_filter.filtered(remote_env)
         } else {
             Default::default()
         };
 
+        env_vars.extend(crate::kube::fetch_env_from_kube_resources(config, progress).await?);
+
         if let Some(file) = &config.feature.env.env_file {
-            let envs_from_file = dotenvy::from_path_iter(file)
-                .and_then(|iter| iter.collect::<Result<Vec<_>, _>>())
+            let envs_from_file = FileSource::new(file)
+                .read_to_string()
+                .map_err(dotenvy::Error::Io)
+                .and_then(|contents| parse_env_file(contents.as_bytes()))
                 .map_err(|error| CliError::EnvFileAccessError(file.clone(), error))?;
 
             env_vars.extend(envs_from_file);
@@ -668,6 +989,31 @@ impl MirrordExecution {
         Ok(env_vars)
     }
 
+    /// [`MirrordExecution::get_remote_env`] bounded by `feature.env.fetch_timeout`, which
+    /// defaults to `agent.communication_timeout`.
+    ///
+    /// On timeout, the error describes which variables were requested.
+    async fn get_remote_env_with_timeout(
+        config: &LayerConfig,
+        connection: &mut Connection<Client>,
+        env_vars_filter: HashSet<String>,
+        env_vars_select: HashSet<String>,
+    ) -> CliResult<HashMap<String, String>> {
+        let timeout_secs = config
+            .feature
+            .env
+            .fetch_timeout
+            .unwrap_or_else(|| config.agent.communication_timeout.unwrap_or(30).into());
+        let requested = describe_env_request(&env_vars_filter, &env_vars_select);
+
+        tokio::time::timeout(
+            Duration::from_secs(timeout_secs),
+            Self::get_remote_env(connection, env_vars_filter, env_vars_select),
+        )
+        .await
+        .map_err(|_| CliError::EnvFetchTimeout(timeout_secs, requested))?
+    }
+
     /// Retrieve remote environment from the connected agent.
     #[tracing::instrument(level = Level::TRACE, skip_all)]
     async fn get_remote_env(
@@ -733,4 +1079,318 @@ impl MirrordExecution {
 
         Ok(())
     }
+
+    /// Like [`MirrordExecution::wait`], but starts the external proxy again when it exits
+    /// unexpectedly (e.g. it was killed by the OOM killer), trying for up to
+    /// `external_proxy.outage_timeout` seconds.
+    ///
+    /// Returns when the external proxy exits on its own, after its idle timeout.
+    pub(crate) async fn supervise_external<P>(mut self, progress: &P) -> CliResult<()>
+    where
+        P: Progress,
+    {
+        let (Some(mut child), Some(mut restart)) =
+            (self.child.take(), self.external_proxy_restart.take())
+        else {
+            return self.wait().await;
+        };
+
+        loop {
+            let status = child
+                .wait()
+                .await
+                .map_err(CliError::InternalProxyWaitError)?;
+            if status.success() {
+                return Ok(());
+            }
+
+            if restart.outage_timeout.is_zero() {
+                return Err(CliError::ExternalProxyExited(status));
+            }
+
+            progress.warning(&format!(
+                "mirrord external proxy exited unexpectedly ({status}), starting it again"
+            ));
+            child = restart.spawn(progress).await?;
+        }
+    }
+}
+
+/// Fixes <https://github.com/metalbear-co/mirrord/issues/1745> by disabling the fork safety check
+/// in the Objective-C runtime, unless `experimental.keep_objc_fork_safety` is set.
+#[cfg(any(target_os = "macos", test))]
+fn objc_fork_safety_env(
+    experimental: &mirrord_config::experimental::ExperimentalConfig,
+) -> Option<(String, String)> {
+    if experimental.keep_objc_fork_safety {
+        return None;
+    }
+
+    Some((
+        "OBJC_DISABLE_INITIALIZE_FORK_SAFETY".to_string(),
+        "YES".to_string(),
+    ))
+}
+
+/// Describes the remote environment variables requested with the given `filter` (excluded) and
+/// `select` (included) patterns, for error messages.
+fn describe_env_request(filter: &HashSet<String>, select: &HashSet<String>) -> String {
+    let sorted = |patterns: &HashSet<String>| {
+        let mut patterns = patterns.iter().map(String::as_str).collect::<Vec<_>>();
+        patterns.sort_unstable();
+        patterns.join(";")
+    };
+    let count = |patterns: &HashSet<String>| match patterns.len() {
+        1 => "1 pattern".to_string(),
+        len => format!("{len} patterns"),
+    };
+
+    if select.is_empty() || select.contains("*") {
+        if filter.is_empty() {
+            "all remote environment variables".to_string()
+        } else {
+            format!(
+                "all remote environment variables except {} (`{}`)",
+                count(filter),
+                sorted(filter)
+            )
+        }
+    } else {
+        format!(
+            "remote environment variables matching {} (`{}`)",
+            count(select),
+            sorted(select)
+        )
+    }
+}
+
+/// Returns a warning when the protocol version of the agent (or the operator) is more than a minor
+/// version away from the one this mirrord was built with, as their behavior may differ in subtle
+/// ways.
+fn protocol_version_drift_warning(remote: &Version, ours: &Version) -> Option<String> {
+    let far_apart = remote.major != ours.major || remote.minor.abs_diff(ours.minor) > 1;
+    if !far_apart {
+        return None;
+    }
+
+    let outdated = if remote < ours {
+        "the mirrord agent image (or the operator)"
+    } else {
+        "mirrord"
+    };
+
+    Some(format!(
+        "The remote mirrord protocol version {remote} is far from this mirrord's protocol \
+        version {ours}, which may lead to unexpected behavior. Consider upgrading {outdated}."
+    ))
+}
+
+/// Parses the variables from an env file, see
+/// [`feature.env.env_file`](mirrord_config::feature::env::EnvConfig::env_file).
+fn parse_env_file<R: std::io::Read>(reader: R) -> Result<Vec<(String, String)>, dotenvy::Error> {
+    dotenvy::from_read_iter(reader).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use mirrord_analytics::NullReporter;
+    use mirrord_config::{
+        LayerFileConfig,
+        config::{ConfigContext, MirrordConfig},
+        experimental::ExperimentalFileConfig,
+    };
+    use mirrord_progress::NullProgress;
+    use mirrord_protocol::{ClientMessage, DaemonMessage};
+    use mirrord_protocol_io::{Client, Connection};
+    use rstest::rstest;
+    use semver::Version;
+
+    use crate::execution::{
+        MirrordExecution, describe_env_request, parse_env_file, protocol_version_drift_warning,
+    };
+
+    /// Env files can be streamed, e.g. from stdin with `--env-file -`.
+    #[test]
+    fn env_file_from_reader() {
+        let contents = b"# comment\nFOO=bar\nexport QUOTED=\"hello world\"\n";
+
+        let vars = parse_env_file(contents.as_slice()).unwrap();
+
+        assert_eq!(
+            vars,
+            [
+                ("FOO".to_string(), "bar".to_string()),
+                ("QUOTED".to_string(), "hello world".to_string()),
+            ]
+        );
+    }
+
+    /// `OBJC_DISABLE_INITIALIZE_FORK_SAFETY` is injected unless
+    /// `experimental.keep_objc_fork_safety` is set.
+    #[rstest]
+    #[case::default(false)]
+    #[case::keep(true)]
+    fn objc_fork_safety_env_toggle(#[case] keep_objc_fork_safety: bool) {
+        let mut experimental = ExperimentalFileConfig::default()
+            .generate_config(&mut ConfigContext::default())
+            .unwrap();
+        experimental.keep_objc_fork_safety = keep_objc_fork_safety;
+
+        let env = super::objc_fork_safety_env(&experimental);
+
+        if keep_objc_fork_safety {
+            assert_eq!(env, None);
+        } else {
+            assert_eq!(
+                env,
+                Some((
+                    "OBJC_DISABLE_INITIALIZE_FORK_SAFETY".to_string(),
+                    "YES".to_string()
+                ))
+            );
+        }
+    }
+
+    /// Env fetch timeout errors should say what was requested.
+    #[rstest]
+    #[case::all(&[], &["*"], "all remote environment variables")]
+    #[case::exclude(
+        &["B", "A"],
+        &[],
+        "all remote environment variables except 2 patterns (`A;B`)"
+    )]
+    #[case::include(
+        &["X"],
+        &["PATH"],
+        "remote environment variables matching 1 pattern (`PATH`)"
+    )]
+    fn env_request_description(
+        #[case] filter: &[&str],
+        #[case] select: &[&str],
+        #[case] expected: &str,
+    ) {
+        let to_set = |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect();
+        let filter: HashSet<String> = to_set(filter);
+        let select: HashSet<String> = to_set(select);
+
+        assert_eq!(describe_env_request(&filter, &select), expected);
+    }
+
+    #[rstest]
+    #[case::same("1.40.0", false)]
+    #[case::patch("1.40.9", false)]
+    #[case::one_minor_older("1.39.0", false)]
+    #[case::two_minors_older("1.38.5", true)]
+    #[case::two_minors_newer("1.42.0", true)]
+    #[case::major("2.40.0", true)]
+    fn protocol_version_drift(#[case] remote: &str, #[case] warns: bool) {
+        let warning =
+            protocol_version_drift_warning(&remote.parse().unwrap(), &Version::new(1, 40, 0));
+        assert_eq!(warning.is_some(), warns);
+    }
+
+    /// We learn the agent's own protocol version even when it is newer than ours, and the
+    /// connection ends up using our version.
+    #[tokio::test]
+    async fn get_agent_version_newer_agent() {
+        let agent_version = Version::new(mirrord_protocol::VERSION.major, u64::MAX, 0);
+        let (mut connection, agent_tx, agent_rx) = Connection::<Client>::dummy();
+
+        let fake_agent = async {
+            let mut offered = Vec::new();
+            for _ in 0..2 {
+                let Some(ClientMessage::SwitchProtocolVersion(version)) = agent_rx.next().await
+                else {
+                    panic!("expected a protocol version switch");
+                };
+                let settled = version.clone().min(agent_version.clone());
+                agent_tx
+                    .send(DaemonMessage::SwitchProtocolVersionResponse(settled))
+                    .await
+                    .unwrap();
+                offered.push(version);
+            }
+            offered
+        };
+
+        let (version, offered) = tokio::join!(
+            MirrordExecution::get_agent_version(&mut connection),
+            fake_agent
+        );
+        assert_eq!(version.unwrap(), agent_version);
+        assert_eq!(offered.last(), Some(&*mirrord_protocol::VERSION));
+    }
+
+    /// `mirrord exec --only-check` should fail (and make the CLI exit with a non-zero code) when we
+    /// can't connect to the agent.
+    #[tokio::test]
+    async fn check_cluster_access_fails_without_cluster() {
+        let mut cfg_context = ConfigContext::default()
+            .override_env("MIRRORD_KUBECONFIG", "/this/kubeconfig/does/not/exist")
+            .override_env("MIRRORD_OPERATOR_ENABLE", "false")
+            .strict_env(true);
+        let mut config = LayerFileConfig::default()
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = MirrordExecution::check_cluster_access(
+            &mut config,
+            &mut NullProgress,
+            &mut NullReporter::default(),
+            None,
+        )
+        .await;
+
+        assert!(result.is_err());
+    }
+
+    /// The health check passes only when something is listening on the internal proxy address.
+    #[tokio::test]
+    async fn intproxy_health_check() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        MirrordExecution::intproxy_health_check(address)
+            .await
+            .unwrap();
+
+        drop(listener);
+        MirrordExecution::intproxy_health_check(address)
+            .await
+            .unwrap_err();
+    }
+
+    /// Layer libraries from other mirrord runs should be removed from the injection env var.
+    #[cfg(unix)]
+    #[test]
+    fn injection_env_value_removes_stale_layers() {
+        let (value, stale) = super::injection_env_value(
+            Some("/usr/lib/libfoo.so:/tmp/mirrord/libmirrord_layer.so:/tmp/mirrord/new.so"),
+            "/tmp/mirrord/new.so",
+        );
+        assert_eq!(value, "/usr/lib/libfoo.so:/tmp/mirrord/new.so");
+        assert_eq!(stale, vec!["/tmp/mirrord/libmirrord_layer.so".to_string()]);
+
+        let (value, stale) = super::injection_env_value(None, "/tmp/mirrord/new.so");
+        assert_eq!(value, "/tmp/mirrord/new.so");
+        assert!(stale.is_empty());
+    }
+
+    /// `experimental.injection_env_var` overrides the platform default injection variable.
+    #[rstest]
+    #[case::default(None, super::LINUX_INJECTION_ENV_VAR)]
+    #[case::custom(Some("MY_LOADER_PRELOAD"), "MY_LOADER_PRELOAD")]
+    fn injection_env_var_override(#[case] configured: Option<&str>, #[case] expected: &str) {
+        let mut experimental = ExperimentalFileConfig::default()
+            .generate_config(&mut ConfigContext::default())
+            .unwrap();
+        experimental.injection_env_var = configured.map(ToString::to_string);
+
+        assert_eq!(
+            super::injection_env_var(&experimental, super::LINUX_INJECTION_ENV_VAR),
+            expected
+        );
+    }
 }
@@ -221,7 +221,10 @@ pub async fn proxy(
                     | message @ Some(DaemonMessage::PauseTarget(_))
                     | message @ Some(DaemonMessage::SwitchProtocolVersionResponse(_))
                     | message @ Some(DaemonMessage::Vpn(_))
-                    | message @ Some(DaemonMessage::ReverseDnsLookup(_)) => {
+                    | message @ Some(DaemonMessage::ReverseDnsLookup(_))
+                    | message @ Some(DaemonMessage::Udp(_))
+                    | message @ Some(DaemonMessage::RemoteTime(_))
+                    | message @ Some(DaemonMessage::AgentLogs(_)) => {
                         return Err(
                             ExternalProxyError::PingPongFailed(format!(
                                 "agent sent an unexpected message: {message:?}"
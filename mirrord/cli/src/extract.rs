@@ -29,6 +29,8 @@ use std::env::temp_dir;
 use mac::temp_dir;
 
 /// Extract to given directory, or tmp by default.
+/// The file name contains the mirrord version, so that layers extracted by different mirrord
+/// versions don't reuse each other's files.
 /// If prefix is true, add a random prefix to the file name that identifies the specific build
 /// of the layer. This is useful for debug purposes usually.
 pub(crate) fn extract_library<P>(
@@ -46,10 +48,14 @@ where
         .to_str()
         .unwrap();
 
+    let version = env!("CARGO_PKG_VERSION");
     let file_name = if prefix {
-        format!("{}-libmirrord_layer.{extension}", const_random!(u64))
+        format!(
+            "{}-libmirrord_layer-{version}.{extension}",
+            const_random!(u64)
+        )
     } else {
-        format!("libmirrord_layer.{extension}")
+        format!("libmirrord_layer-{version}.{extension}")
     };
 
     let file_path = match dest_dir {
@@ -92,10 +98,14 @@ where
         .to_str()
         .unwrap();
 
+    let version = env!("CARGO_PKG_VERSION");
     let file_name = if prefix {
-        format!("{}-libmirrord_layer_arm64.{extension}", const_random!(u64))
+        format!(
+            "{}-libmirrord_layer_arm64-{version}.{extension}",
+            const_random!(u64)
+        )
     } else {
-        format!("libmirrord_layer_arm64.{extension}")
+        format!("libmirrord_layer_arm64-{version}.{extension}")
     };
 
     let file_path = temp_dir().as_path().join(file_name);
@@ -0,0 +1,151 @@
+//! Session-start audit of the local hosts file ([`LOCAL_HOSTS_FILE`]).
+//!
+//! Entries like `127.0.0.1 my-service.cluster.local` (usually leftovers from old workarounds)
+//! make libraries that consult the hosts file before DNS connect locally, while the rest of the
+//! application uses the addresses resolved in the cluster.
+
+use std::ops::Not;
+
+use mirrord_config::{
+    LayerConfig,
+    feature::network::{
+        dns::{self, DnsFilterConfig, HostsEntry, LOCAL_HOSTS_FILE},
+        filter::{AddressFilter, NamePattern, ProtocolAndAddressFilter},
+        outgoing::OutgoingFilterConfig,
+    },
+};
+use mirrord_progress::Progress;
+
+/// Returns the entries from the hosts file `contents` that collide with remote DNS.
+///
+/// An entry collides when its name looks like a cluster name (see [`dns::is_cluster_name`]), or
+/// is explicitly marked remote by the DNS or the outgoing filter. With
+/// `feature.network.dns.ignore_local_hosts_file`, cluster names are already resolved remotely,
+/// so only the explicitly remote names are reported.
+fn collisions(contents: &str, config: &LayerConfig) -> Vec<HostsEntry> {
+    let network = &config.feature.network;
+    if network.dns.enabled.not() {
+        return Default::default();
+    }
+
+    let dns_remote = match &network.dns.filter {
+        Some(DnsFilterConfig::Remote(filters)) => filters
+            .iter()
+            .filter_map(|filter| match filter.parse::<AddressFilter>() {
+                Ok(AddressFilter::Name(name, _)) => Some(name),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    let outgoing_remote = match &network.outgoing.filter {
+        Some(OutgoingFilterConfig::Remote(filters)) => filters
+            .iter()
+            .filter_map(|filter| match filter.parse::<ProtocolAndAddressFilter>() {
+                Ok(ProtocolAndAddressFilter {
+                    address: AddressFilter::Name(name, _),
+                    ..
+                }) => Some(name),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    };
+    let remote_names = dns_remote
+        .into_iter()
+        .chain(outgoing_remote)
+        .filter_map(|name| name.parse::<NamePattern>().ok())
+        .collect::<Vec<_>>();
+
+    dns::parse_hosts_file(contents)
+        .into_iter()
+        .filter(|entry| {
+            (network.dns.ignore_local_hosts_file.not() && dns::is_cluster_name(&entry.name))
+                || remote_names
+                    .iter()
+                    .any(|pattern| pattern.matches(&entry.name))
+        })
+        .collect()
+}
+
+/// Warns the user about the entries of [`LOCAL_HOSTS_FILE`] that collide with remote DNS.
+///
+/// Does nothing if the file can't be read.
+pub(crate) fn audit_local_hosts_file<P: Progress>(config: &LayerConfig, progress: &P) {
+    let contents = match std::fs::read_to_string(LOCAL_HOSTS_FILE) {
+        Ok(contents) => contents,
+        Err(error) => {
+            tracing::debug!(%error, "Failed to read the local hosts file");
+            return;
+        }
+    };
+
+    let collisions = collisions(&contents, config);
+    if collisions.is_empty() {
+        return;
+    }
+
+    let entries = collisions
+        .iter()
+        .map(|entry| format!("{} ({})", entry.name, entry.address))
+        .collect::<Vec<_>>()
+        .join(", ");
+    progress.warning(&format!(
+        "Your local {LOCAL_HOSTS_FILE} overrides names that mirrord resolves remotely: {entries}. \
+        Libraries that check {LOCAL_HOSTS_FILE} before DNS will connect to the local addresses. \
+        Remove these entries, or set `feature.network.dns.ignore_local_hosts_file` to make mirrord \
+        ignore them for cluster names."
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use mirrord_config::{config::ConfigContext, util::VecOrSingle};
+
+    use super::*;
+
+    const HOSTS_FILE: &str = "\
+127.0.0.1 localhost
+127.0.0.1 my-service.cluster.local
+127.0.0.1 db.default.svc legacy-api.internal.corp
+";
+
+    fn collision_names(config: &LayerConfig) -> Vec<String> {
+        collisions(HOSTS_FILE, config)
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect()
+    }
+
+    fn config() -> LayerConfig {
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        LayerConfig::resolve(&mut cfg_context).unwrap()
+    }
+
+    #[test]
+    fn detects_cluster_names() {
+        assert_eq!(
+            collision_names(&config()),
+            ["my-service.cluster.local", "db.default.svc"]
+        );
+    }
+
+    #[test]
+    fn detects_remote_filter_names() {
+        let mut config = config();
+        config.feature.network.outgoing.filter = Some(OutgoingFilterConfig::Remote(
+            VecOrSingle::Single("tcp://*.internal.corp:443".to_string()),
+        ));
+        config.feature.network.dns.ignore_local_hosts_file = true;
+
+        assert_eq!(collision_names(&config), ["legacy-api.internal.corp"]);
+    }
+
+    #[test]
+    fn no_collisions_with_local_dns() {
+        let mut config = config();
+        config.feature.network.dns.enabled = false;
+
+        assert!(collision_names(&config).is_empty());
+    }
+}
@@ -0,0 +1,758 @@
+//! `mirrord init` creates a mirrord config file, asking the user about the most important
+//! settings: the target, the incoming traffic mode, the file system mode, and the environment.
+//!
+//! Choices offered for the questions come from the config types, through the JSON schema used by
+//! `mirrord config docs`. The created file is resolved and verified just like a config passed to
+//! `mirrord exec` before it's saved.
+
+use std::{
+    fmt::Write as _,
+    io::{self, BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use k8s_openapi::api::core::v1::Namespace;
+use kube::{Api, Client};
+use mirrord_config::{
+    LayerConfig,
+    config::{ConfigContext, ConfigError},
+    docs::{ConfigDocs, ConfigDocsError},
+    feature::{fs::FsModeConfig, network::incoming::IncomingMode},
+    target::{Target, TargetType},
+};
+use mirrord_kube::api::kubernetes::{create_kube_config, seeker::KubeResourceSeeker};
+use mirrord_progress::{Progress, ProgressTracker};
+use serde_json::Value;
+
+use crate::{
+    CliResult,
+    config::{ConfigFormat, InitArgs},
+};
+
+/// Directory where `mirrord init` puts the config file.
+const CONFIG_DIR: &str = ".mirrord";
+
+/// How long we wait for the cluster when looking for namespaces and targets to suggest.
+const CLUSTER_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub async fn init_command(args: InitArgs) -> CliResult<()> {
+    let mut progress = ProgressTracker::from_env("mirrord init");
+
+    let answers = if args.defaults {
+        InitAnswers::default()
+    } else if io::stdin().is_terminal() {
+        let client = cluster_client(args.context).await;
+        let mut prompt = Prompt {
+            input: io::stdin().lock(),
+            output: io::stdout(),
+            docs: ConfigDocs::default(),
+        };
+        InitAnswers::ask(&mut prompt, client.as_ref()).await?
+    } else {
+        return Err(InitError::NotInteractive.into());
+    };
+
+    let (path, warnings) = write_config(&answers, args.format, Path::new(CONFIG_DIR), args.force)?;
+
+    for warning in warnings {
+        progress.warning(&warning);
+    }
+    progress.success(Some(&format!("Created {}", path.display())));
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InitError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    #[error("stdin is not a terminal, use `--defaults` to create the config without questions")]
+    NotInteractive,
+
+    #[error("input ended before all questions were answered")]
+    Aborted,
+
+    #[error("{0:?} already exists, use `--force` to overwrite it")]
+    AlreadyExists(PathBuf),
+
+    #[error("the created config is invalid: {0}")]
+    InvalidConfig(#[from] ConfigError),
+
+    #[error(transparent)]
+    Docs(#[from] ConfigDocsError),
+}
+
+/// Answers to the questions asked by `mirrord init`.
+///
+/// [`Default`] is used with `--defaults`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitAnswers {
+    /// Target path, e.g. `deployment/app`, or `targetless`.
+    ///
+    /// When [`None`], the target is not written to the config, and can be picked when running
+    /// mirrord.
+    target: Option<String>,
+    namespace: Option<String>,
+    incoming_mode: IncomingMode,
+    fs_mode: FsModeConfig,
+    /// Whether the environment variables are imported from the target.
+    remote_env: bool,
+    /// Environment variables that are not imported from the target.
+    env_exclude: Vec<String>,
+}
+
+impl Default for InitAnswers {
+    fn default() -> Self {
+        Self {
+            target: None,
+            namespace: None,
+            incoming_mode: IncomingMode::default(),
+            fs_mode: FsModeConfig::default(),
+            remote_env: true,
+            env_exclude: Vec::new(),
+        }
+    }
+}
+
+impl InitAnswers {
+    /// Asks all the questions, suggesting namespaces and targets from the cluster when `client`
+    /// is available.
+    async fn ask<R: BufRead, W: Write>(
+        prompt: &mut Prompt<R, W>,
+        client: Option<&Client>,
+    ) -> Result<Self, InitError> {
+        let defaults = Self::default();
+
+        let target_types = TargetType::all()
+            .map(|target_type| target_type.to_string())
+            .collect::<Vec<_>>();
+        let target_type = prompt.choose(
+            "Target type (empty to pick the target when running mirrord)",
+            "target.path",
+            &target_types,
+            None,
+        )?;
+
+        let (target, namespace) = match target_type.as_deref() {
+            None => (None, None),
+            Some("targetless") => {
+                let namespace = prompt.namespace(client).await?;
+                (Some("targetless".to_string()), namespace)
+            }
+            Some(target_type) => {
+                let namespace = prompt.namespace(client).await?;
+                let target = prompt
+                    .target(client, target_type, namespace.as_deref())
+                    .await?;
+                (Some(target), namespace)
+            }
+        };
+
+        let incoming_mode = prompt.choose_value(
+            "Incoming traffic mode",
+            "feature.network.incoming.mode",
+            defaults.incoming_mode,
+        )?;
+        let fs_mode =
+            prompt.choose_value("File system mode", "feature.fs.mode", defaults.fs_mode)?;
+
+        let remote_env = prompt.confirm(
+            "Import environment variables from the target?",
+            "feature.env",
+            defaults.remote_env,
+        )?;
+        let env_exclude = if remote_env {
+            prompt
+                .text(
+                    "Environment variables to keep local (comma-separated)",
+                    "feature.env.exclude",
+                )?
+                .map(|exclude| {
+                    exclude
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|name| !name.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+
+        Ok(Self {
+            target,
+            namespace,
+            incoming_mode,
+            fs_mode,
+            remote_env,
+            env_exclude,
+        })
+    }
+
+    /// Fields of the config file, in the order in which they're written.
+    fn fields(&self) -> Vec<ConfigField> {
+        let mut fields = Vec::new();
+
+        if let Some(target) = &self.target {
+            fields.push(ConfigField {
+                path: "target.path",
+                value: Value::String(target.clone()),
+                comment: "Target to impersonate.",
+            });
+        }
+
+        if let Some(namespace) = &self.namespace {
+            fields.push(ConfigField {
+                path: "target.namespace",
+                value: Value::String(namespace.clone()),
+                comment: "Namespace of the target.",
+            });
+        }
+
+        fields.push(ConfigField {
+            path: "feature.network.incoming.mode",
+            value: serde_json::to_value(self.incoming_mode).expect("IncomingMode is serializable"),
+            comment: "Whether incoming traffic is mirrored or stolen from the target.",
+        });
+
+        fields.push(ConfigField {
+            path: "feature.fs.mode",
+            value: serde_json::to_value(self.fs_mode).expect("FsModeConfig is serializable"),
+            comment: "Which files are read or written remotely.",
+        });
+
+        if self.remote_env && !self.env_exclude.is_empty() {
+            fields.push(ConfigField {
+                path: "feature.env.exclude",
+                value: self
+                    .env_exclude
+                    .iter()
+                    .cloned()
+                    .map(Value::String)
+                    .collect(),
+                comment: "Environment variables that are not imported from the target.",
+            });
+        } else {
+            fields.push(ConfigField {
+                path: "feature.env",
+                value: Value::Bool(self.remote_env),
+                comment: "Whether environment variables are imported from the target.",
+            });
+        }
+
+        fields
+    }
+}
+
+/// Renders the config file for the given `answers`, and saves it in `dir` after verifying it.
+///
+/// Returns the path of the file, and warnings produced when verifying the config.
+pub fn write_config(
+    answers: &InitAnswers,
+    format: ConfigFormat,
+    dir: &Path,
+    force: bool,
+) -> Result<(PathBuf, Vec<String>), InitError> {
+    let path = dir.join(format!("mirrord.{}", format.extension()));
+    if path.exists() && !force {
+        return Err(InitError::AlreadyExists(path));
+    }
+
+    let contents = ConfigNode::from_fields(answers.fields()).render(format);
+    let warnings = verify(&contents, format)?;
+
+    std::fs::create_dir_all(dir)?;
+    std::fs::write(&path, contents)?;
+
+    Ok((path, warnings))
+}
+
+/// Resolves and verifies the config file `contents`, without taking the process environment into
+/// account.
+fn verify(contents: &str, format: ConfigFormat) -> Result<Vec<String>, InitError> {
+    let mut file = tempfile::Builder::new()
+        .prefix("mirrord-init-")
+        .suffix(&format!(".{}", format.extension()))
+        .tempfile()?;
+    file.write_all(contents.as_bytes())?;
+
+    let mut context = ConfigContext::default()
+        .strict_env(true)
+        .override_env(LayerConfig::FILE_PATH_ENV, file.path());
+    LayerConfig::resolve(&mut context)?.verify(&mut context)?;
+
+    Ok(context.into_warnings())
+}
+
+/// Creates a client for the cluster, used only for suggestions.
+async fn cluster_client(context: Option<String>) -> Option<Client> {
+    let config = create_kube_config(None, None::<&str>, context)
+        .await
+        .inspect_err(|error| tracing::debug!(%error, "Failed to read kube config"))
+        .ok()?;
+
+    Client::try_from(config)
+        .inspect_err(|error| tracing::debug!(%error, "Failed to create kube client"))
+        .ok()
+}
+
+/// Asks questions on the terminal.
+///
+/// Typing `?` prints the docs of the config field that the question is about.
+struct Prompt<R, W> {
+    input: R,
+    output: W,
+    docs: ConfigDocs,
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    /// Reads an answer, returns [`None`] when the user doesn't type anything.
+    fn read(
+        &mut self,
+        question: &str,
+        field: &str,
+        default: Option<&str>,
+    ) -> Result<Option<String>, InitError> {
+        loop {
+            match default {
+                Some(default) => write!(self.output, "{question} [{default}]: ")?,
+                None => write!(self.output, "{question}: ")?,
+            }
+            self.output.flush()?;
+
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                return Err(InitError::Aborted);
+            }
+
+            match line.trim() {
+                "" => return Ok(None),
+                "?" => write!(self.output, "\n{}\n", self.docs.field(field)?)?,
+                answer => return Ok(Some(answer.to_string())),
+            }
+        }
+    }
+
+    /// Asks to pick one of the `choices`, either by its number or by its value.
+    fn choose(
+        &mut self,
+        question: &str,
+        field: &str,
+        choices: &[String],
+        default: Option<&str>,
+    ) -> Result<Option<String>, InitError> {
+        writeln!(self.output)?;
+        for (index, choice) in choices.iter().enumerate() {
+            writeln!(self.output, "  {}) {choice}", index + 1)?;
+        }
+
+        loop {
+            let Some(answer) = self.read(question, field, default)? else {
+                return Ok(default.map(String::from));
+            };
+
+            let choice = answer
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| choices.get(number.checked_sub(1)?))
+                .or_else(|| choices.iter().find(|choice| **choice == answer));
+
+            match choice {
+                Some(choice) => return Ok(Some(choice.clone())),
+                None => writeln!(self.output, "Please pick one of the listed values.")?,
+            }
+        }
+    }
+
+    /// Asks to pick one of the values accepted by the config `field`, as listed in its schema.
+    fn choose_value<T>(&mut self, question: &str, field: &str, default: T) -> Result<T, InitError>
+    where
+        T: serde::Serialize + serde::de::DeserializeOwned,
+    {
+        let choices = self
+            .docs
+            .field(field)?
+            .accepted_values
+            .iter()
+            .filter_map(|value| serde_json::from_str::<String>(value).ok())
+            .collect::<Vec<_>>();
+        let default = serde_json::to_value(default)
+            .ok()
+            .and_then(|value| value.as_str().map(String::from));
+
+        let answer = self
+            .choose(question, field, &choices, default.as_deref())?
+            .ok_or(InitError::Aborted)?;
+
+        Ok(serde_json::from_value(Value::String(answer)).map_err(ConfigError::ParseJson)?)
+    }
+
+    /// Asks a yes/no question.
+    fn confirm(&mut self, question: &str, field: &str, default: bool) -> Result<bool, InitError> {
+        let hint = if default { "Y/n" } else { "y/N" };
+
+        loop {
+            match self.read(question, field, Some(hint))?.as_deref() {
+                None => return Ok(default),
+                Some("y" | "Y" | "yes") => return Ok(true),
+                Some("n" | "N" | "no") => return Ok(false),
+                Some(_) => writeln!(self.output, "Please answer `y` or `n`.")?,
+            }
+        }
+    }
+
+    /// Asks an open question.
+    fn text(&mut self, question: &str, field: &str) -> Result<Option<String>, InitError> {
+        self.read(question, field, None)
+    }
+
+    /// Asks for the namespace, suggesting the namespaces found in the cluster.
+    async fn namespace(&mut self, client: Option<&Client>) -> Result<Option<String>, InitError> {
+        let Some(client) = client else {
+            return self.text("Namespace (empty for the default one)", "target.namespace");
+        };
+
+        let namespaces = tokio::time::timeout(
+            CLUSTER_TIMEOUT,
+            Api::<Namespace>::all(client.clone()).list(&Default::default()),
+        )
+        .await
+        .ok()
+        .and_then(|result| {
+            result
+                .inspect_err(|error| tracing::debug!(%error, "Failed to list namespaces"))
+                .ok()
+        })
+        .map(|list| {
+            list.items
+                .into_iter()
+                .filter_map(|namespace| namespace.metadata.name)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+        if namespaces.is_empty() {
+            return self.text("Namespace (empty for the default one)", "target.namespace");
+        }
+
+        self.choose(
+            "Namespace",
+            "target.namespace",
+            &namespaces,
+            Some(client.default_namespace()),
+        )
+    }
+
+    /// Asks for the name of the target, suggesting the targets of the given type found in the
+    /// cluster.
+    ///
+    /// Returns the full target path, e.g. `deployment/app`.
+    async fn target(
+        &mut self,
+        client: Option<&Client>,
+        target_type: &str,
+        namespace: Option<&str>,
+    ) -> Result<String, InitError> {
+        let mut targets = Vec::new();
+
+        if let Some(client) = client
+            && let Ok(parsed_type) = target_type.parse::<TargetType>()
+        {
+            let seeker = KubeResourceSeeker {
+                client,
+                namespace: Some(namespace.unwrap_or(client.default_namespace())),
+                copy_target: false,
+            };
+
+            match tokio::time::timeout(CLUSTER_TIMEOUT, seeker.filtered(vec![parsed_type], true))
+                .await
+            {
+                Ok(Ok(found)) => targets = found.into_iter().map(|target| target.path).collect(),
+                Ok(Err(error)) => tracing::debug!(%error, "Failed to list targets"),
+                Err(..) => tracing::debug!("Timed out listing targets"),
+            }
+        }
+
+        if !targets.is_empty() {
+            writeln!(self.output)?;
+            for (index, target) in targets.iter().enumerate() {
+                writeln!(self.output, "  {}) {target}", index + 1)?;
+            }
+        }
+
+        loop {
+            let Some(answer) = self.read(&format!("{target_type} name"), "target.path", None)?
+            else {
+                continue;
+            };
+
+            let target = answer
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| targets.get(number.checked_sub(1)?).cloned())
+                .unwrap_or_else(|| {
+                    if answer.starts_with(&format!("{target_type}/")) {
+                        answer
+                    } else {
+                        format!("{target_type}/{answer}")
+                    }
+                });
+
+            match target.parse::<Target>() {
+                Ok(..) => return Ok(target),
+                Err(error) => writeln!(self.output, "{error}")?,
+            }
+        }
+    }
+}
+
+/// A field of the created config file.
+#[derive(Debug)]
+struct ConfigField {
+    /// Dotted path of the field, e.g. `feature.fs.mode`.
+    path: &'static str,
+    value: Value,
+    /// Written above the field.
+    comment: &'static str,
+}
+
+/// Tree of [`ConfigField`]s, keeps the order in which the fields were added.
+#[derive(Debug, Default)]
+struct ConfigNode {
+    fields: Vec<(&'static str, ConfigField)>,
+    children: Vec<(&'static str, ConfigNode)>,
+}
+
+impl ConfigNode {
+    /// Comment at the top of the file.
+    const HEADER: &str =
+        "Created with `mirrord init`, run `mirrord config docs <field>` to learn about a field.";
+
+    fn from_fields(fields: Vec<ConfigField>) -> Self {
+        let mut root = Self::default();
+
+        for field in fields {
+            let mut node = &mut root;
+            let (parents, key) = field.path.rsplit_once('.').unwrap_or(("", field.path));
+
+            for parent in parents.split('.').filter(|parent| !parent.is_empty()) {
+                let position = match node.children.iter().position(|(key, _)| *key == parent) {
+                    Some(position) => position,
+                    None => {
+                        node.children.push((parent, Self::default()));
+                        node.children.len() - 1
+                    }
+                };
+                node = &mut node.children[position].1;
+            }
+
+            node.fields.push((key, field));
+        }
+
+        root
+    }
+
+    fn render(&self, format: ConfigFormat) -> String {
+        let mut out = String::new();
+
+        match format {
+            ConfigFormat::Json => {
+                // Config files are rendered with Tera, which strips these comments.
+                let _ = writeln!(out, "{{# {} #}}", Self::HEADER);
+                out.push_str("{\n");
+                self.render_json(&mut out, 1);
+                out.push_str("}\n");
+            }
+            ConfigFormat::Toml => {
+                let _ = writeln!(out, "# {}", Self::HEADER);
+                self.render_toml(&mut out, "");
+            }
+            ConfigFormat::Yaml => {
+                let _ = writeln!(out, "# {}", Self::HEADER);
+                self.render_yaml(&mut out, 0);
+            }
+        }
+
+        out
+    }
+
+    fn render_json(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+        let count = self.fields.len() + self.children.len();
+        let separator = |index: usize| if index + 1 < count { "," } else { "" };
+
+        for (index, (key, field)) in self.fields.iter().enumerate() {
+            let _ = writeln!(out, "{indent}{{# {} #}}", field.comment);
+            let _ = writeln!(
+                out,
+                "{indent}\"{key}\": {}{}",
+                field.value,
+                separator(index)
+            );
+        }
+
+        for (index, (key, child)) in self.children.iter().enumerate() {
+            let _ = writeln!(out, "{indent}\"{key}\": {{");
+            child.render_json(out, depth + 1);
+            let _ = writeln!(out, "{indent}}}{}", separator(self.fields.len() + index));
+        }
+    }
+
+    /// JSON strings, booleans and arrays of strings are valid TOML values.
+    fn render_toml(&self, out: &mut String, table: &str) {
+        if !self.fields.is_empty() {
+            if !table.is_empty() {
+                let _ = writeln!(out, "\n[{table}]");
+            }
+
+            for (key, field) in &self.fields {
+                let _ = writeln!(out, "# {}", field.comment);
+                let _ = writeln!(out, "{key} = {}", field.value);
+            }
+        }
+
+        for (key, child) in &self.children {
+            let table = if table.is_empty() {
+                key.to_string()
+            } else {
+                format!("{table}.{key}")
+            };
+            child.render_toml(out, &table);
+        }
+    }
+
+    /// JSON strings, booleans and arrays of strings are valid YAML values.
+    fn render_yaml(&self, out: &mut String, depth: usize) {
+        let indent = "  ".repeat(depth);
+
+        for (key, field) in &self.fields {
+            let _ = writeln!(out, "{indent}# {}", field.comment);
+            let _ = writeln!(out, "{indent}{key}: {}", field.value);
+        }
+
+        for (key, child) in &self.children {
+            let _ = writeln!(out, "{indent}{key}:");
+            child.render_yaml(out, depth + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use mirrord_config::{
+        LayerConfig,
+        config::ConfigContext,
+        env_key::MIRRORD_ENV_KEY,
+        feature::{fs::FsModeConfig, network::incoming::IncomingMode},
+        target::Target,
+    };
+    use rstest::rstest;
+    use tempfile::TempDir;
+
+    use super::{InitAnswers, InitError, write_config};
+    use crate::config::ConfigFormat;
+
+    fn custom_answers() -> InitAnswers {
+        InitAnswers {
+            target: Some("deployment/app/container/main".to_string()),
+            namespace: Some("staging".to_string()),
+            incoming_mode: IncomingMode::Steal,
+            fs_mode: FsModeConfig::LocalWithOverrides,
+            remote_env: true,
+            env_exclude: vec!["AWS_PROFILE".to_string(), "HOME".to_string()],
+        }
+    }
+
+    /// Resolves the config file at `path`, with a fixed key so that configs can be compared.
+    fn resolve(path: &std::path::Path) -> LayerConfig {
+        let mut context = ConfigContext::default()
+            .strict_env(true)
+            .override_env(MIRRORD_ENV_KEY, "init")
+            .override_env(LayerConfig::FILE_PATH_ENV, path);
+        LayerConfig::resolve(&mut context).unwrap()
+    }
+
+    /// Every format parses back to the same config.
+    #[rstest]
+    #[case::defaults(InitAnswers::default())]
+    #[case::custom(custom_answers())]
+    #[case::local_env(InitAnswers { remote_env: false, ..Default::default() })]
+    fn formats_parse_back_identically(#[case] answers: InitAnswers) {
+        let dir = TempDir::new().unwrap();
+
+        let configs = [ConfigFormat::Json, ConfigFormat::Toml, ConfigFormat::Yaml].map(|format| {
+            let (path, _) = write_config(&answers, format, dir.path(), false).unwrap();
+            resolve(&path)
+        });
+
+        assert_eq!(configs[0], configs[1]);
+        assert_eq!(configs[0], configs[2]);
+
+        let config = &configs[0];
+        assert_eq!(
+            config.target.path.as_ref().map(ToString::to_string),
+            answers
+                .target
+                .as_ref()
+                .map(|target| target.parse::<Target>().unwrap().to_string())
+        );
+        assert_eq!(config.target.namespace, answers.namespace);
+        assert_eq!(config.feature.network.incoming.mode, answers.incoming_mode);
+        assert_eq!(config.feature.fs.mode, answers.fs_mode);
+    }
+
+    #[test]
+    fn env_answers() {
+        let dir = TempDir::new().unwrap();
+
+        let (path, _) =
+            write_config(&custom_answers(), ConfigFormat::Json, dir.path(), false).unwrap();
+        let exclude = resolve(&path).feature.env.exclude.map(Vec::from);
+        assert_eq!(
+            exclude,
+            Some(vec!["AWS_PROFILE".to_string(), "HOME".to_string()])
+        );
+
+        let answers = InitAnswers {
+            remote_env: false,
+            ..Default::default()
+        };
+        let (path, _) = write_config(&answers, ConfigFormat::Json, dir.path(), true).unwrap();
+        let exclude = resolve(&path).feature.env.exclude.map(Vec::from);
+        assert_eq!(exclude, Some(vec!["*".to_string()]));
+    }
+
+    #[test]
+    fn does_not_overwrite_without_force() {
+        let dir = TempDir::new().unwrap();
+        let answers = InitAnswers::default();
+
+        let (path, _) = write_config(&answers, ConfigFormat::Yaml, dir.path(), false).unwrap();
+        std::fs::write(&path, "feature: {}").unwrap();
+
+        let error = write_config(&answers, ConfigFormat::Yaml, dir.path(), false).unwrap_err();
+        assert!(matches!(error, InitError::AlreadyExists(..)));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "feature: {}");
+
+        write_config(&answers, ConfigFormat::Yaml, dir.path(), true).unwrap();
+        assert_ne!(std::fs::read_to_string(&path).unwrap(), "feature: {}");
+    }
+
+    /// Invalid answers are rejected before anything is written.
+    #[test]
+    fn rejects_invalid_config() {
+        let dir = TempDir::new().unwrap();
+        let answers = InitAnswers {
+            target: Some("targetless".to_string()),
+            incoming_mode: IncomingMode::Steal,
+            ..Default::default()
+        };
+
+        let error = write_config(&answers, ConfigFormat::Toml, dir.path(), false).unwrap_err();
+        assert!(matches!(error, InitError::InvalidConfig(..)));
+        assert!(!dir.path().join("mirrord.toml").exists());
+    }
+}
@@ -13,6 +13,7 @@
 use std::{
     env, io,
     net::{Ipv4Addr, SocketAddr},
+    path::PathBuf,
     time::Duration,
 };
 #[cfg(not(target_os = "windows"))]
@@ -23,6 +24,7 @@ use mirrord_config::LayerConfig;
 use mirrord_intproxy::{
     IntProxy,
     agent_conn::{AgentConnectInfo, AgentConnection},
+    metrics,
 };
 use mirrord_protocol::{ClientMessage, DaemonMessage, LogLevel, LogMessage};
 #[cfg(not(target_os = "windows"))]
@@ -122,6 +124,10 @@ pub(crate) async fn proxy(
         .map_err(InternalProxyError::ListenerSetup)?;
     print_addr(&listener).map_err(InternalProxyError::ListenerSetup)?;
 
+    if let Some(address) = config.internal_proxy.metrics.as_deref() {
+        start_metrics(address).await?;
+    }
+
     #[cfg(not(target_os = "windows"))]
     if container_mode.not() {
         unsafe { detach_io() }.map_err(InternalProxyError::SetSid)?;
@@ -131,11 +137,26 @@ pub(crate) async fn proxy(
     let consecutive_connection_timeout = Duration::from_secs(config.internal_proxy.idle_timeout);
     let process_logging_interval =
         Duration::from_secs(config.internal_proxy.process_logging_interval);
+    let unix_sockets = config
+        .feature
+        .network
+        .incoming
+        .unix_sockets
+        .iter()
+        .map(|remote| {
+            let local = config
+                .feature
+                .network
+                .incoming
+                .unix_socket_local_path(remote);
+            (PathBuf::from(remote), PathBuf::from(local))
+        })
+        .collect();
 
     IntProxy::new_with_connection(
         agent_conn,
         listener,
-        config.feature.fs.readonly_file_buffer,
+        config.feature.fs.readonly_file_buffers()?,
         config
             .feature
             .network
@@ -143,6 +164,12 @@ pub(crate) async fn proxy(
             .tls_delivery
             .or(config.feature.network.incoming.https_delivery)
             .unwrap_or_default(),
+        config.feature.network.incoming.on_local_unavailable,
+        config.feature.network.incoming.source_ip_delivery,
+        config.feature.network.incoming.delivery_source,
+        config.feature.network.incoming.source_cidrs()?,
+        config.feature.network.incoming.auto_downgrade,
+        unix_sockets,
         process_logging_interval,
         &config.experimental,
     )
@@ -151,6 +178,24 @@ pub(crate) async fn proxy(
     .map_err(From::from)
 }
 
+/// Binds the metrics server address from `internal_proxy.metrics` and serves the metrics in the
+/// background.
+async fn start_metrics(address: &str) -> Result<(), InternalProxyError> {
+    let listener = match address.parse::<SocketAddr>() {
+        Ok(socket_address) => TcpListener::bind(socket_address).await,
+        Err(error) => Err(io::Error::new(io::ErrorKind::InvalidInput, error)),
+    }
+    .map_err(|error| InternalProxyError::MetricsSetup(address.to_string(), error))?;
+
+    tokio::spawn(async move {
+        if let Err(error) = metrics::serve_metrics(listener).await {
+            tracing::error!(%error, "Metrics server failed");
+        }
+    });
+
+    Ok(())
+}
+
 /// Creates a connection with the agent and handles one round of ping pong.
 #[tracing::instrument(level = Level::TRACE, skip(config, analytics))]
 pub(crate) async fn connect_and_ping(
@@ -200,7 +245,10 @@ pub(crate) async fn connect_and_ping(
             | message @ Some(DaemonMessage::PauseTarget(_))
             | message @ Some(DaemonMessage::SwitchProtocolVersionResponse(_))
             | message @ Some(DaemonMessage::Vpn(_))
-            | message @ Some(DaemonMessage::ReverseDnsLookup(_)) => {
+            | message @ Some(DaemonMessage::ReverseDnsLookup(_))
+            | message @ Some(DaemonMessage::Udp(_))
+            | message @ Some(DaemonMessage::RemoteTime(_))
+            | message @ Some(DaemonMessage::AgentLogs(_)) => {
                 break Err(InternalProxyError::InitialPingPongFailed(format!(
                     "agent sent an unexpected message: {message:?}"
                 )));
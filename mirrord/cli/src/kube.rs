@@ -1,13 +1,14 @@
-use std::fmt::Debug;
+use std::{collections::HashMap, fmt::Debug};
 
-use kube::{Resource, api::ListParams, client::ClientBuilder};
-use mirrord_config::LayerConfig;
+use k8s_openapi::api::core::v1::{ConfigMap, Secret};
+use kube::{Api, Resource, api::ListParams, client::ClientBuilder};
+use mirrord_config::{LayerConfig, feature::env::KubeEnvResource};
 use mirrord_kube::{api::kubernetes::create_kube_config, retry::RetryKube};
 use mirrord_progress::Progress;
 use serde::de::DeserializeOwned;
 use tower::{buffer::BufferLayer, retry::RetryLayer};
 
-use crate::error::CliError;
+use crate::{CliResult, error::CliError};
 
 /// Create a kube client according to the layer config, and with a request buffer of 1024 requests
 /// and a retry policy according to the layer config.
@@ -59,3 +60,89 @@ where
         }
     }
 }
+
+/// Fetches the ConfigMaps and Secrets listed in
+/// [`EnvConfig::from_kube_resources`](mirrord_config::feature::env::EnvConfig::from_kube_resources)
+/// from the target's namespace, and returns their data as env vars.
+///
+/// Values that are not valid UTF-8 are skipped with a warning.
+pub(crate) async fn fetch_env_from_kube_resources<P>(
+    layer_config: &LayerConfig,
+    progress: &P,
+) -> CliResult<HashMap<String, String>>
+where
+    P: Progress,
+{
+    let resources = layer_config.feature.env.kube_resources()?;
+    if resources.is_empty() {
+        return Ok(Default::default());
+    }
+
+    let client = kube_client_from_layer_config(layer_config).await?;
+    let namespace = layer_config
+        .target
+        .namespace
+        .clone()
+        .unwrap_or_else(|| client.default_namespace().to_string());
+
+    let mut env_vars = HashMap::new();
+    for resource in resources {
+        let fetch_error = |error: kube::Error| match error {
+            kube::Error::Api(response) if response.code == 404 => {
+                CliError::KubeEnvResourceNotFound(resource.to_string(), namespace.clone())
+            }
+            error => CliError::KubeEnvResourceFetchFailed(
+                resource.to_string(),
+                mirrord_kube::error::KubeApiError::KubeError(error),
+            ),
+        };
+
+        let (data, binary_keys) = match &resource {
+            KubeEnvResource::ConfigMap(name) => {
+                let config_map = Api::<ConfigMap>::namespaced(client.clone(), &namespace)
+                    .get(name)
+                    .await
+                    .map_err(fetch_error)?;
+                let binary_keys = config_map
+                    .binary_data
+                    .unwrap_or_default()
+                    .into_keys()
+                    .collect::<Vec<_>>();
+
+                (config_map.data.unwrap_or_default(), binary_keys)
+            }
+            KubeEnvResource::Secret(name) => {
+                let secret = Api::<Secret>::namespaced(client.clone(), &namespace)
+                    .get(name)
+                    .await
+                    .map_err(fetch_error)?;
+
+                let mut binary_keys = Vec::new();
+                let data = secret
+                    .data
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|(key, value)| match String::from_utf8(value.0) {
+                        Ok(value) => Some((key, value)),
+                        Err(..) => {
+                            binary_keys.push(key);
+                            None
+                        }
+                    })
+                    .collect();
+
+                (data, binary_keys)
+            }
+        };
+
+        for key in binary_keys {
+            progress.warning(&format!(
+                "Skipping binary value of `{key}` from `{resource}` in `feature.env.from_kube_resources`."
+            ));
+        }
+
+        env_vars.extend(data);
+    }
+
+    Ok(env_vars)
+}
@@ -1,13 +1,20 @@
-use std::{sync::LazyLock, time::Instant};
+use std::{ops::Not, sync::LazyLock, time::Instant};
 
 use futures::TryStreamExt;
 use k8s_openapi::api::core::v1::Namespace;
 use mirrord_analytics::NullReporter;
-use mirrord_config::{LayerConfig, config::ConfigContext, target::TargetType};
-use mirrord_kube::{api::kubernetes::seeker::KubeResourceSeeker, error::KubeApiError};
+use mirrord_config::{
+    LayerConfig,
+    config::ConfigContext,
+    target::{Target, TargetDisplay, TargetType},
+};
+use mirrord_kube::{
+    api::kubernetes::seeker::{KubeResourceSeeker, Readiness, SeekedTarget},
+    error::KubeApiError,
+};
 use mirrord_operator::client::OperatorApi;
 use semver::VersionReq;
-use serde::{Serialize, Serializer, ser::SerializeSeq};
+use serde::{Deserialize, Serialize, Serializer, ser::SerializeSeq};
 use tracing::Level;
 
 use crate::{
@@ -34,6 +41,10 @@ struct FoundTarget {
     /// This field is here for forward compatibility, because in the future we might want to return
     /// unavailable targets as well (along with some validation error message) to improve UX.
     available: bool,
+
+    /// Used only in the [`DetailedTargets`] output.
+    #[serde(skip)]
+    details: SeekedTarget,
 }
 
 /// Result of mirrord targets lookup in the cluster.
@@ -53,6 +64,11 @@ struct FoundTargets {
     /// Taken from [`LayerConfig::target`], defaults to [`kube::Client`]'s default namespace.
     current_namespace: String,
 
+    /// Whether the targets were looked up in all namespaces instead of the
+    /// [`Self::current_namespace`].
+    #[serde(skip)]
+    all_namespaces: bool,
+
     /// Available lookup namespaces.
     namespaces: Vec<String>,
 }
@@ -67,11 +83,14 @@ impl FoundTargets {
     /// If `rich_output` is set:
     /// 1. returned [`FoundTargets`] will contain info about namespaces available in the cluster;
     /// 2. only deployment, rollout, and pod targets will be fetched.
+    ///
+    /// If `all_namespaces` is set, targets are looked up in all namespaces.
     #[tracing::instrument(level = Level::DEBUG, skip(layer_config), name = "resolve_targets", err)]
     async fn resolve(
         layer_config: LayerConfig,
         rich_output: bool,
         target_types: Option<Vec<TargetType>>,
+        all_namespaces: bool,
     ) -> CliResult<Self> {
         let client = kube_client_from_layer_config(&layer_config).await?;
 
@@ -96,19 +115,22 @@ impl FoundTargets {
             None
         };
 
+        let current_namespace = layer_config
+            .target
+            .namespace
+            .as_deref()
+            .unwrap_or(client.default_namespace())
+            .to_owned();
+
         let seeker = KubeResourceSeeker {
             client: &client,
-            namespace: layer_config
-                .target
-                .namespace
-                .as_deref()
-                .unwrap_or(client.default_namespace()),
+            namespace: all_namespaces.not().then_some(current_namespace.as_str()),
             copy_target: layer_config.feature.copy_target.enabled,
         };
 
         let (targets, namespaces) = tokio::try_join!(
             async {
-                let paths = match (operator_api, target_types) {
+                let found = match (operator_api, target_types) {
                     (None, _) if layer_config.operator == Some(true) => {
                         Err(CliError::OperatorNotInstalled)
                     }
@@ -144,11 +166,12 @@ impl FoundTargets {
                     }),
                 }?;
 
-                let targets = paths
+                let targets = found
                     .into_iter()
-                    .map(|path| FoundTarget {
-                        path,
+                    .map(|details| FoundTarget {
+                        path: details.path.clone(),
                         available: true,
+                        details,
                     })
                     .collect::<Vec<_>>();
 
@@ -173,16 +196,10 @@ impl FoundTargets {
             }
         )?;
 
-        let current_namespace = layer_config
-            .target
-            .namespace
-            .as_deref()
-            .unwrap_or(client.default_namespace())
-            .to_owned();
-
         Ok(Self {
             targets,
             current_namespace,
+            all_namespaces,
             namespaces,
         })
     }
@@ -211,6 +228,97 @@ impl Serialize for FoundTargetsList<'_> {
     }
 }
 
+/// Output of `mirrord ls --output detailed-json`.
+///
+/// Consumed by the IDE extensions, so the format must stay backward compatible. Breaking changes
+/// require a new [`DetailedTargets::VERSION`].
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct DetailedTargets {
+    /// Always [`DetailedTargets::VERSION`].
+    version: u32,
+
+    /// Lookup namespace, [`None`] when the targets were looked up in all namespaces.
+    current_namespace: Option<String>,
+
+    /// In the same order as [`FoundTargets::targets`].
+    targets: Vec<DetailedTarget>,
+}
+
+impl DetailedTargets {
+    const VERSION: u32 = 1;
+}
+
+/// A single target in the [`DetailedTargets`] output.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct DetailedTarget {
+    /// E.g `pod/my-pod-1234/container/my-container`, can be used as `target.path`.
+    path: String,
+
+    /// E.g. `deployment`.
+    kind: TargetType,
+
+    /// Name of the target resource, e.g. `my-pod-1234`.
+    name: String,
+
+    /// Namespace of the target resource.
+    namespace: String,
+
+    /// Ready replicas of a workload, or ready containers of a pod.
+    ///
+    /// [`None`] for services and cronjobs.
+    readiness: Option<Readiness>,
+
+    /// Containers of the target, excluding service mesh sidecars.
+    containers: Vec<String>,
+
+    /// Whether this target can be used only with `feature.copy_target`, see
+    /// [`Target::requires_copy`].
+    requires_copy_target: bool,
+
+    /// Whether this target can be used only with the mirrord operator, see
+    /// [`Target::requires_operator`].
+    requires_operator: bool,
+}
+
+impl From<&FoundTargets> for DetailedTargets {
+    fn from(found: &FoundTargets) -> Self {
+        let targets = found
+            .targets
+            .iter()
+            .filter(|target| target.available)
+            .filter_map(|found| {
+                let target = found
+                    .path
+                    .parse::<Target>()
+                    .inspect_err(|error| {
+                        tracing::debug!(%error, path = found.path, "Skipping unparsable target")
+                    })
+                    .ok()?;
+
+                Some(DetailedTarget {
+                    path: found.path.clone(),
+                    kind: TargetType::from(&target),
+                    name: target.name().to_owned(),
+                    namespace: found.details.namespace.clone(),
+                    readiness: found.details.readiness,
+                    containers: found.details.containers.clone(),
+                    requires_copy_target: target.requires_copy(),
+                    requires_operator: target.requires_operator(),
+                })
+            })
+            .collect();
+
+        Self {
+            version: Self::VERSION,
+            current_namespace: found
+                .all_namespaces
+                .not()
+                .then(|| found.current_namespace.clone()),
+            targets,
+        }
+    }
+}
+
 /// Controls whether we support listing all targets or just the open source ones.
 static ALL_TARGETS_SUPPORTED_OPERATOR_VERSION: LazyLock<VersionReq> =
     LazyLock::new(|| ">=3.84.0".parse().expect("version should be valid"));
@@ -224,6 +332,9 @@ static ALL_TARGETS_SUPPORTED_OPERATOR_VERSION: LazyLock<VersionReq> =
 /// Otherwise:
 /// 1. targets are printed as a plain JSON array of strings (backward compatibility);
 /// 2. all available target types are fetched.
+///
+/// With [`Format::DetailedJson`], `rich_output` is ignored, and the targets are printed as
+/// [`DetailedTargets`].
 pub(super) async fn print_targets(args: ListTargetArgs, rich_output: bool) -> CliResult<()> {
     let mut cfg_config =
         ConfigContext::default().override_env_opt(LayerConfig::FILE_PATH_ENV, args.config_file);
@@ -252,7 +363,9 @@ pub(super) async fn print_targets(args: ListTargetArgs, rich_output: bool) -> Cl
         }
     };
 
-    let targets = FoundTargets::resolve(layer_config, rich_output, target_types).await?;
+    let rich_output = rich_output && matches!(args.output, Format::Json);
+    let targets =
+        FoundTargets::resolve(layer_config, rich_output, target_types, args.all_namespaces).await?;
 
     match args.output {
         Format::Json => {
@@ -262,9 +375,132 @@ pub(super) async fn print_targets(args: ListTargetArgs, rich_output: bool) -> Cl
                 serde_json::to_string(&FoundTargetsList(&targets)).unwrap()
             };
 
+            println!("{serialized}");
+        }
+        Format::DetailedJson => {
+            let serialized = serde_json::to_string(&DetailedTargets::from(&targets)).unwrap();
+
             println!("{serialized}");
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod test {
+    use mirrord_config::target::TargetType;
+    use mirrord_kube::api::kubernetes::seeker::{Readiness, SeekedTarget};
+
+    use super::{DetailedTarget, DetailedTargets, FoundTarget, FoundTargets};
+
+    fn found(path: &str, readiness: Option<Readiness>, containers: &[&str]) -> FoundTarget {
+        FoundTarget {
+            path: path.into(),
+            available: true,
+            details: SeekedTarget {
+                path: path.into(),
+                namespace: "staging".into(),
+                readiness,
+                containers: containers.iter().map(ToString::to_string).collect(),
+            },
+        }
+    }
+
+    /// The [`DetailedTargets`] format is consumed by the IDE extensions, this test guards against
+    /// accidental changes.
+    #[test]
+    fn detailed_output_is_stable() {
+        let found = FoundTargets {
+            targets: vec![
+                found(
+                    "deployment/api",
+                    Some(Readiness {
+                        ready: 2,
+                        desired: 3,
+                    }),
+                    &["api"],
+                ),
+                found("cronjob/cleanup", None, &["cleanup"]),
+                found(
+                    "pod/api-1234/container/worker",
+                    Some(Readiness {
+                        ready: 2,
+                        desired: 2,
+                    }),
+                    &["api", "worker"],
+                ),
+            ],
+            current_namespace: "staging".into(),
+            all_namespaces: false,
+            namespaces: vec![],
+        };
+
+        let serialized = serde_json::to_value(DetailedTargets::from(&found)).unwrap();
+        let expected = serde_json::json!({
+            "version": 1,
+            "current_namespace": "staging",
+            "targets": [
+                {
+                    "path": "deployment/api",
+                    "kind": "deployment",
+                    "name": "api",
+                    "namespace": "staging",
+                    "readiness": { "ready": 2, "desired": 3 },
+                    "containers": ["api"],
+                    "requires_copy_target": false,
+                    "requires_operator": false
+                },
+                {
+                    "path": "cronjob/cleanup",
+                    "kind": "cronjob",
+                    "name": "cleanup",
+                    "namespace": "staging",
+                    "readiness": null,
+                    "containers": ["cleanup"],
+                    "requires_copy_target": true,
+                    "requires_operator": true
+                },
+                {
+                    "path": "pod/api-1234/container/worker",
+                    "kind": "pod",
+                    "name": "api-1234",
+                    "namespace": "staging",
+                    "readiness": { "ready": 2, "desired": 2 },
+                    "containers": ["api", "worker"],
+                    "requires_copy_target": false,
+                    "requires_operator": false
+                }
+            ]
+        });
+        assert_eq!(serialized, expected);
+
+        let deserialized = serde_json::from_value::<DetailedTargets>(expected).unwrap();
+        assert_eq!(deserialized.version, DetailedTargets::VERSION);
+        assert_eq!(
+            deserialized.targets[1],
+            DetailedTarget {
+                path: "cronjob/cleanup".into(),
+                kind: TargetType::CronJob,
+                name: "cleanup".into(),
+                namespace: "staging".into(),
+                readiness: None,
+                containers: vec!["cleanup".into()],
+                requires_copy_target: true,
+                requires_operator: true,
+            }
+        );
+    }
+
+    #[test]
+    fn detailed_output_all_namespaces() {
+        let found = FoundTargets {
+            targets: vec![],
+            current_namespace: "default".into(),
+            all_namespaces: true,
+            namespaces: vec![],
+        };
+
+        assert_eq!(DetailedTargets::from(&found).current_namespace, None);
+    }
+}
@@ -205,6 +205,9 @@
 //!
 //! The types of target fetched depend on the [`ListTargetArgs::RICH_OUTPUT_ENV`].
 //!
+//! With `--output detailed-json`, prints a versioned JSON object with the kind, namespace,
+//! readiness and containers of each target.
+//!
 //! ### `mirrord completions <SHELL>`
 //!
 //! - [`generate`]
@@ -237,6 +240,15 @@
 //! - [`fix::fix_command`]
 //!
 //! > Contains fixes for commonly occuring issues that prevent mirrord from working optimally.
+//!
+//! ### `mirrord session top [OPTIONS]`
+//!
+//! - [`session::session_command`]
+//!
+//! > Resource usage of a running session.
+//!
+//! Combines the CPU and memory usage of the target pod, taken from the Kubernetes metrics API,
+//! with the usage of the local application and the internal proxy in one refreshing table.
 
 #![feature(try_blocks)]
 #![feature(iterator_try_collect)]
@@ -245,7 +257,7 @@
 #![cfg_attr(all(windows, feature = "windows_build"), feature(windows_change_time))]
 #![cfg_attr(all(windows, feature = "windows_build"), feature(windows_by_handle))]
 
-use std::{collections::HashMap, env::vars, net::SocketAddr, time::Duration};
+use std::{collections::HashMap, env::vars, net::SocketAddr, process::ExitCode, time::Duration};
 #[cfg(not(target_os = "windows"))]
 use std::{ffi::CString, os::unix::ffi::OsStrExt};
 #[cfg(target_os = "macos")]
@@ -276,6 +288,7 @@ use mirrord_config::{
             incoming::IncomingMode,
         },
     },
+    util::FileSource,
 };
 use mirrord_intproxy::agent_conn::{AgentConnection, AgentConnectionError};
 use mirrord_progress::{Progress, ProgressTracker, messages::EXEC_CONTAINER_BINARY};
@@ -291,6 +304,7 @@ use which::which;
 mod browser;
 mod ci;
 mod config;
+mod config_docs;
 mod connection;
 mod container;
 mod db_branches;
@@ -301,6 +315,8 @@ mod execution;
 mod extension;
 mod external_proxy;
 mod extract;
+mod hosts_file;
+mod init;
 mod internal_proxy;
 #[cfg(target_os = "linux")]
 mod is_static;
@@ -308,11 +324,15 @@ mod kube;
 mod list;
 mod local_redis;
 mod logging;
+mod masking;
 mod newsletter;
 mod operator;
 mod port_forward;
 mod preview;
 mod profile;
+mod session;
+#[cfg(target_os = "macos")]
+mod sip_cache;
 mod teams;
 mod user_data;
 mod util;
@@ -338,6 +358,7 @@ use crate::{
 async fn exec_process<P>(
     mut config: LayerConfig,
     config_file_path: Option<&str>,
+    executable: &str,
     args: &ExecArgs,
     progress: &mut P,
     analytics: &mut AnalyticsReporter,
@@ -355,7 +376,7 @@ where
 
         let mut sub_progress =
             sub_progress.subtask("checking if target binary is dynamically linked");
-        if is_static::is_binary_static(Path::new(&args.binary)) {
+        if is_static::is_binary_static(Path::new(executable)) {
             sub_progress.failure(Some(
                 "target binary might not be dynamically linked, mirrord might not work!",
             ));
@@ -380,7 +401,7 @@ where
     let execution_info = MirrordExecution::start_internal(
         &mut config,
         #[cfg(target_os = "macos")]
-        Some(&args.binary),
+        Some(executable),
         #[cfg(target_os = "macos")]
         Some(binary_args.as_slice()),
         &mut sub_progress,
@@ -397,12 +418,12 @@ where
 
     #[cfg(target_os = "macos")]
     let (_did_sip_patch, binary) = match execution_info.patched_path {
-        None => (false, args.binary.clone()),
+        None => (false, executable.to_owned()),
         Some(ref sip_result) => (true, sip_result.to_owned()),
     };
 
     #[cfg(not(target_os = "macos"))]
-    let (_did_sip_patch, binary) = (false, args.binary.clone());
+    let (_did_sip_patch, binary) = (false, executable.to_owned());
 
     let mut env_vars: HashMap<String, String> = vars().collect();
     env_vars.extend(execution_info.environment.clone());
@@ -412,9 +433,9 @@ where
     }
 
     // Put original executable in argv[0] even if actually running patched version.
-    let binary_args = std::iter::once(&args.binary)
-        .chain(args.binary_args.iter())
-        .map(Clone::clone)
+    let binary_args = std::iter::once(executable)
+        .chain(args.binary_args.iter().map(String::as_str))
+        .map(ToOwned::to_owned)
         .collect::<Vec<_>>();
 
     sub_progress.success(Some("ready to launch process"));
@@ -598,6 +619,7 @@ pub(crate) fn print_config<P>(
             None => "mirrord will run without a target".into(),
         },
         match config_file_path {
+            Some(FileSource::STDIN) => "the configuration was read from stdin".into(),
             Some(path) => {
                 format!("the configuration file was loaded from {path}")
             }
@@ -663,22 +685,27 @@ pub(crate) fn print_config<P>(
         DnsConfig {
             enabled: true,
             filter: None,
+            ..
         } => "remotely",
         DnsConfig {
             enabled: true,
             filter: Some(DnsFilterConfig::Remote(filters)),
+            ..
         } if filters.is_empty() => "locally",
         DnsConfig {
             enabled: true,
             filter: Some(DnsFilterConfig::Local(filters)),
+            ..
         } if filters.is_empty() => "remotely",
         DnsConfig {
             enabled: true,
             filter: Some(DnsFilterConfig::Remote(..)),
+            ..
         } => "locally with exceptions",
         DnsConfig {
             enabled: true,
             filter: Some(DnsFilterConfig::Local(..)),
+            ..
         } => "remotely with exceptions",
     };
     progress.info(&format!("dns: DNS will be resolved {}", dns_info));
@@ -697,6 +724,37 @@ into redirected HTTP requests before they're routed to the target.",
     }
 }
 
+/// Handles `mirrord exec --only-check`.
+///
+/// Connects to the agent and fetches the remote environment, reporting the result, without
+/// running the user binary.
+async fn check_cluster_access<P>(
+    mut config: LayerConfig,
+    progress: &mut P,
+    analytics: &mut AnalyticsReporter,
+    mirrord_for_ci: Option<MirrordCi>,
+) -> CliResult<()>
+where
+    P: Progress,
+{
+    let mut sub_progress = progress.subtask("checking cluster access");
+
+    let result = MirrordExecution::check_cluster_access(
+        &mut config,
+        &mut sub_progress,
+        analytics,
+        mirrord_for_ci.as_ref(),
+    )
+    .await;
+
+    match &result {
+        Ok(()) => sub_progress.success(Some("cluster access check passed")),
+        Err(..) => sub_progress.failure(Some("cluster access check failed")),
+    }
+
+    result
+}
+
 async fn exec(
     args: &ExecArgs,
     watch: drain::Watch,
@@ -709,15 +767,17 @@ async fn exec(
     if !args.params.disable_version_check {
         prompt_outdated_version(progress).await;
     }
-    info!(
-        "Launching {:?} with arguments {:?}",
-        args.binary, args.binary_args
-    );
-
-    let container_detection =
-        Regex::new("docker|podman|nerdctl").expect("Failed building container detection regex!");
-    if container_detection.is_match(&args.binary) {
-        progress.warning(EXEC_CONTAINER_BINARY);
+    if let Some(binary) = &args.binary {
+        info!(
+            "Launching {:?} with arguments {:?}",
+            binary, args.binary_args
+        );
+
+        let container_detection = Regex::new("docker|podman|nerdctl")
+            .expect("Failed building container detection regex!");
+        if container_detection.is_match(binary) {
+            progress.warning(EXEC_CONTAINER_BINARY);
+        }
     }
 
     if !(args.params.no_tcp_outgoing || args.params.no_udp_outgoing) && args.params.no_remote_dns {
@@ -781,16 +841,24 @@ async fn exec(
     }
     result?;
 
-    let res = exec_process(
-        config,
-        config_file_path.as_deref(),
-        args,
-        progress,
-        &mut analytics,
-        user_data,
-        mirrord_for_ci,
-    )
-    .await;
+    hosts_file::audit_local_hosts_file(&config, progress);
+
+    let res = match args.binary.as_deref() {
+        Some(binary) if !args.only_check => {
+            exec_process(
+                config,
+                config_file_path.as_deref(),
+                binary,
+                args,
+                progress,
+                &mut analytics,
+                user_data,
+                mirrord_for_ci,
+            )
+            .await
+        }
+        _ => check_cluster_access(config, progress, &mut analytics, mirrord_for_ci).await,
+    };
 
     if res.is_err() && !analytics.has_error() {
         analytics.set_error(AnalyticsError::Unknown);
@@ -943,7 +1011,7 @@ async fn port_forward(
 
 const CURRENT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-fn main() -> miette::Result<()> {
+fn main() -> ExitCode {
     rustls::crypto::CryptoProvider::install_default(rustls::crypto::aws_lc_rs::default_provider())
         .expect("Failed to install crypto provider");
 
@@ -953,7 +1021,17 @@ fn main() -> miette::Result<()> {
     console::ensure_vt_or_dumb_progress();
 
     let cli = Cli::parse();
+    let error_format = cli.error_format;
 
+    // Every failure, including the ones in `mirrord exec` before we `execve` into the user
+    // binary, goes through here, so that the exit code always reflects the error category.
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => error.report(error_format),
+    }
+}
+
+fn run(cli: Cli) -> CliResult<()> {
     let rt = tokio::runtime::Builder::new_current_thread()
         .enable_all()
         .build()
@@ -1061,6 +1139,9 @@ fn main() -> miette::Result<()> {
                 .await?
             }
             Commands::Fix(args) => fix::fix_command(args).await?,
+            Commands::Config(args) => config_docs::config_command(args)?,
+            Commands::Init(args) => init::init_command(args).await?,
+            Commands::Session(args) => session::session_command(*args).await?,
         };
 
         Ok(())
@@ -1075,7 +1156,7 @@ fn main() -> miette::Result<()> {
             });
     });
 
-    res.map_err(Into::into)
+    res
 }
 
 /// Make sure we're not running nested inside another mirrord exec
@@ -1143,10 +1224,13 @@ async fn prompt_outdated_version(progress: &ProgressTracker) {
 
 #[cfg(test)]
 mod tests {
+    use std::ffi::OsStr;
+
     use clap::Parser;
+    use mirrord_config::{LayerConfig, util::FileSource};
     use rstest::rstest;
 
-    use crate::{Cli, Commands};
+    use crate::{Cli, Commands, ErrorFormat};
 
     /// Verifies that
     /// [`ExecParams::accept_invalid_certificates`](crate::config::ExecParams::accept_invalid_certificates)
@@ -1175,4 +1259,37 @@ mod tests {
             other => panic!("unexpected args parsed: {other:?}"),
         }
     }
+
+    /// Verifies that [`Cli::error_format`] can be passed both before and after the subcommand.
+    #[rstest]
+    #[case(&["mirrord", "--error-format", "json", "exec", "--", "echo"], ErrorFormat::Json)]
+    #[case(&["mirrord", "exec", "--error-format", "json", "--", "echo"], ErrorFormat::Json)]
+    #[case(&["mirrord", "exec", "--", "echo", "--error-format", "json"], ErrorFormat::Human)]
+    fn parse_error_format(#[case] args: &[&str], #[case] expected: ErrorFormat) {
+        assert_eq!(Cli::parse_from(args).error_format, expected);
+    }
+
+    /// Verifies that `-f -` reads the config from stdin, in the format passed with
+    /// `--config-format`.
+    #[rstest]
+    #[case(&["mirrord", "exec", "-f", "-", "--", "echo"], None)]
+    #[case(&["mirrord", "exec", "-f", "-", "--config-format", "toml", "--", "echo"], Some("toml"))]
+    #[case(&["mirrord", "exec", "-f", "-", "--config-format", "yaml", "--", "echo"], Some("yaml"))]
+    fn parse_stdin_config(#[case] args: &[&str], #[case] expected_format: Option<&str>) {
+        let Commands::Exec(args) = Cli::parse_from(args).commands else {
+            panic!("expected the exec command");
+        };
+        let envs = args.params.as_env_vars();
+
+        assert_eq!(
+            envs.get(OsStr::new(LayerConfig::FILE_PATH_ENV))
+                .map(|value| &**value),
+            Some(OsStr::new(FileSource::STDIN))
+        );
+        assert_eq!(
+            envs.get(OsStr::new(LayerConfig::FILE_FORMAT_ENV))
+                .map(|value| &**value),
+            expected_format.map(OsStr::new)
+        );
+    }
 }
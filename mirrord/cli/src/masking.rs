@@ -0,0 +1,535 @@
+//! Masking of the incoming traffic that we show locally, configured with
+//! [`MaskingConfig`] (`feature.network.incoming.masking`).
+//!
+//! Only the local copy of the traffic is masked, the traffic delivered to the user application is
+//! never touched.
+
+use std::{collections::HashSet, sync::Arc};
+
+use http::header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue};
+use mirrord_config::{
+    config::ConfigError,
+    feature::network::incoming::masking::{
+        JsonFieldSelector, JsonSelectorSegment, MaskReplacement, MaskingConfig,
+    },
+};
+use sha2::{Digest, Sha256};
+
+/// Maximum length of an object key that we buffer when masking JSON.
+///
+/// Longer keys make us give up on masking the rest of the body.
+const MAX_JSON_KEY_LEN: usize = 1024;
+
+/// Maximum nesting depth that we track when masking JSON.
+///
+/// Deeper bodies make us give up on masking the rest of the body.
+const MAX_JSON_DEPTH: usize = 128;
+
+/// Masks headers and JSON bodies according to the [`MaskingConfig`].
+#[derive(Debug)]
+pub struct Masker {
+    headers: HashSet<HeaderName>,
+    json_fields: Vec<JsonFieldSelector>,
+    replacement: MaskReplacement,
+}
+
+impl Masker {
+    pub fn new(config: &MaskingConfig) -> Result<Self, ConfigError> {
+        let headers = config
+            .headers
+            .iter()
+            .map(|name| {
+                HeaderName::try_from(name.as_str()).map_err(|error| ConfigError::InvalidValue {
+                    name: "feature.network.incoming.masking.headers",
+                    provided: name.clone(),
+                    error: error.into(),
+                })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            headers,
+            json_fields: config.json_selectors()?,
+            replacement: config.replacement.clone(),
+        })
+    }
+
+    /// Replaces values of the masked headers.
+    pub fn mask_headers(&self, headers: &mut HeaderMap) {
+        for (name, value) in headers.iter_mut() {
+            if self.headers.contains(name) {
+                let masked = self.replacement_for(Sha256::new_with_prefix(value.as_bytes()));
+                *value = HeaderValue::try_from(masked)
+                    .unwrap_or_else(|_| HeaderValue::from_static("<masked>"));
+            }
+        }
+    }
+
+    /// Returns a [`JsonMasker`] for a body sent with the given headers.
+    ///
+    /// Returns [`None`] if the body is not JSON or there are no JSON fields to mask.
+    pub fn json_masker(self: &Arc<Self>, headers: &HeaderMap) -> Option<JsonMasker> {
+        let is_json = headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.to_ascii_lowercase().contains("json"));
+
+        (is_json && !self.json_fields.is_empty()).then(|| JsonMasker::new(self.clone()))
+    }
+
+    /// Masks a complete body sent with the given headers.
+    pub fn mask_body(self: &Arc<Self>, headers: &HeaderMap, body: &[u8]) -> Option<Vec<u8>> {
+        let mut masker = self.json_masker(headers)?;
+        let mut masked = masker.feed(body);
+        masked.extend(masker.finish());
+        Some(masked)
+    }
+
+    fn replacement_for(&self, hasher: Sha256) -> String {
+        match &self.replacement {
+            MaskReplacement::Hash => {
+                let digest = hasher.finalize();
+                format!("sha256:{}", hex::encode(&digest[..8]))
+            }
+            MaskReplacement::Token(token) => token.clone(),
+        }
+    }
+
+    /// Whether the value under the given path should be masked.
+    fn is_masked(&self, path: &[JsonContainer]) -> bool {
+        self.json_fields.iter().any(|selector| {
+            selector.0.len() == path.len()
+                && selector.0.iter().zip(path).all(|(segment, container)| {
+                    match (segment, container) {
+                        (JsonSelectorSegment::Any, _) => true,
+                        (
+                            JsonSelectorSegment::Field(name),
+                            JsonContainer::Object { key: Some(key) },
+                        ) => name == key,
+                        (JsonSelectorSegment::Index(expected), JsonContainer::Array { index }) => {
+                            expected == index
+                        }
+                        _ => false,
+                    }
+                })
+        })
+    }
+}
+
+/// A JSON container that we're currently in, along with the position of the current value.
+#[derive(Debug)]
+enum JsonContainer {
+    Object { key: Option<String> },
+    Array { index: usize },
+}
+
+/// What we expect to see next (ignoring whitespace).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Expect {
+    Value,
+    /// Right after `[`.
+    ValueOrEnd,
+    /// Right after `{`.
+    KeyOrEnd,
+    /// Right after `,` in an object.
+    Key,
+    Colon,
+    CommaOrEnd,
+    /// The root value was completed.
+    Done,
+}
+
+/// Lexeme that we're currently in the middle of.
+#[derive(Debug)]
+enum Lexeme {
+    String {
+        escaped: bool,
+        /// Raw bytes of the object key, if this string is a key.
+        key: Option<Vec<u8>>,
+    },
+    /// Number, `true`, `false` or `null`.
+    Literal,
+}
+
+/// Value that is being masked.
+struct MaskedValue {
+    hasher: Sha256,
+    /// Whether the value is a number, `true`, `false` or `null`.
+    literal: bool,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+/// Masks JSON values matching the [`Masker`] selectors in a body that arrives in chunks.
+///
+/// Only the current path in the document is kept in memory, so bodies of any size can be masked.
+/// Masked values are swallowed (and hashed on the fly) and replaced with a JSON string once they
+/// end.
+///
+/// If the body turns out not to be valid JSON, the rest of it is passed through unchanged.
+pub struct JsonMasker {
+    masker: Arc<Masker>,
+    stack: Vec<JsonContainer>,
+    expect: Expect,
+    lexeme: Option<Lexeme>,
+    masked: Option<MaskedValue>,
+    passthrough: bool,
+}
+
+impl JsonMasker {
+    fn new(masker: Arc<Masker>) -> Self {
+        Self {
+            masker,
+            stack: Default::default(),
+            expect: Expect::Value,
+            lexeme: None,
+            masked: None,
+            passthrough: false,
+        }
+    }
+
+    /// Masks the next chunk of the body.
+    pub fn feed(&mut self, chunk: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(chunk.len());
+
+        for &byte in chunk {
+            self.feed_byte(byte, &mut out);
+        }
+
+        out
+    }
+
+    /// Finishes masking, returns the replacement of a value that was cut off by the end of the
+    /// body.
+    pub fn finish(mut self) -> Vec<u8> {
+        let mut out = Vec::new();
+        if let Some(masked) = self.masked.take() {
+            self.push_replacement(masked.hasher, &mut out);
+        }
+        out
+    }
+
+    fn feed_byte(&mut self, byte: u8, out: &mut Vec<u8>) {
+        if self.passthrough {
+            out.push(byte);
+            return;
+        }
+
+        if let Some(masked) = self.masked.as_mut() {
+            if masked.literal && is_literal_end(byte) {
+                let masked = self.masked.take().expect("checked above");
+                self.push_replacement(masked.hasher, out);
+                self.value_done();
+                // The byte belongs to the enclosing container.
+                self.feed_byte(byte, out);
+                return;
+            }
+
+            masked.hasher.update([byte]);
+            let ended = if masked.literal {
+                false
+            } else if masked.in_string {
+                if masked.escaped {
+                    masked.escaped = false;
+                } else if byte == b'\\' {
+                    masked.escaped = true;
+                } else if byte == b'"' {
+                    masked.in_string = false;
+                }
+                !masked.in_string && masked.depth == 0
+            } else {
+                match byte {
+                    b'"' => masked.in_string = true,
+                    b'{' | b'[' => masked.depth += 1,
+                    b'}' | b']' => masked.depth = masked.depth.saturating_sub(1),
+                    _ => {}
+                }
+                masked.depth == 0
+            };
+
+            if ended {
+                let masked = self.masked.take().expect("checked above");
+                self.push_replacement(masked.hasher, out);
+                self.value_done();
+            }
+
+            return;
+        }
+
+        match self.lexeme.as_mut() {
+            Some(Lexeme::String { escaped, key }) => {
+                out.push(byte);
+
+                let closed = if *escaped {
+                    *escaped = false;
+                    false
+                } else if byte == b'\\' {
+                    *escaped = true;
+                    false
+                } else {
+                    byte == b'"'
+                };
+
+                if closed {
+                    match self.lexeme.take() {
+                        Some(Lexeme::String { key: Some(key), .. }) => {
+                            if let Some(JsonContainer::Object { key: current }) =
+                                self.stack.last_mut()
+                            {
+                                *current = Some(String::from_utf8_lossy(&key).into_owned());
+                            }
+                            self.expect = Expect::Colon;
+                        }
+                        _ => self.value_done(),
+                    }
+                } else if let Some(key) = key {
+                    key.push(byte);
+                    if key.len() > MAX_JSON_KEY_LEN {
+                        self.passthrough = true;
+                    }
+                }
+
+                return;
+            }
+            Some(Lexeme::Literal) if !is_literal_end(byte) => {
+                out.push(byte);
+                return;
+            }
+            Some(Lexeme::Literal) => {
+                self.lexeme = None;
+                self.value_done();
+            }
+            None => {}
+        }
+
+        if byte.is_ascii_whitespace() {
+            out.push(byte);
+            return;
+        }
+
+        match (self.expect, byte) {
+            (Expect::ValueOrEnd, b']') | (Expect::KeyOrEnd, b'}') => self.close(),
+            (Expect::CommaOrEnd, b']')
+                if matches!(self.stack.last(), Some(JsonContainer::Array { .. })) =>
+            {
+                self.close()
+            }
+            (Expect::CommaOrEnd, b'}')
+                if matches!(self.stack.last(), Some(JsonContainer::Object { .. })) =>
+            {
+                self.close()
+            }
+            (Expect::CommaOrEnd, b',') => match self.stack.last_mut() {
+                Some(JsonContainer::Array { index }) => {
+                    *index += 1;
+                    self.expect = Expect::Value;
+                }
+                Some(JsonContainer::Object { key }) => {
+                    *key = None;
+                    self.expect = Expect::Key;
+                }
+                None => self.passthrough = true,
+            },
+            (Expect::KeyOrEnd | Expect::Key, b'"') => {
+                self.lexeme = Some(Lexeme::String {
+                    escaped: false,
+                    key: Some(Vec::new()),
+                });
+            }
+            (Expect::Colon, b':') => self.expect = Expect::Value,
+            (Expect::Value | Expect::ValueOrEnd, _) => {
+                if !matches!(
+                    byte,
+                    b'{' | b'[' | b'"' | b'-' | b'0'..=b'9' | b't' | b'f' | b'n'
+                ) {
+                    self.passthrough = true;
+                } else if self.masker.is_masked(&self.stack) {
+                    self.masked = Some(MaskedValue {
+                        hasher: Sha256::new_with_prefix([byte]),
+                        literal: !matches!(byte, b'{' | b'[' | b'"'),
+                        depth: usize::from(matches!(byte, b'{' | b'[')),
+                        in_string: byte == b'"',
+                        escaped: false,
+                    });
+                    return;
+                } else {
+                    match byte {
+                        b'{' => self.open(JsonContainer::Object { key: None }, Expect::KeyOrEnd),
+                        b'[' => self.open(JsonContainer::Array { index: 0 }, Expect::ValueOrEnd),
+                        b'"' => {
+                            self.lexeme = Some(Lexeme::String {
+                                escaped: false,
+                                key: None,
+                            })
+                        }
+                        _ => self.lexeme = Some(Lexeme::Literal),
+                    }
+                }
+            }
+            _ => self.passthrough = true,
+        }
+
+        out.push(byte);
+    }
+
+    fn open(&mut self, container: JsonContainer, expect: Expect) {
+        if self.stack.len() >= MAX_JSON_DEPTH {
+            self.passthrough = true;
+        } else {
+            self.stack.push(container);
+            self.expect = expect;
+        }
+    }
+
+    fn close(&mut self) {
+        self.stack.pop();
+        self.value_done();
+    }
+
+    fn value_done(&mut self) {
+        self.expect = if self.stack.is_empty() {
+            Expect::Done
+        } else {
+            Expect::CommaOrEnd
+        };
+    }
+
+    fn push_replacement(&self, hasher: Sha256, out: &mut Vec<u8>) {
+        let replacement = self.masker.replacement_for(hasher);
+        // Serializing a `String` cannot fail.
+        out.extend(serde_json::to_vec(&replacement).unwrap_or_default());
+    }
+}
+
+fn is_literal_end(byte: u8) -> bool {
+    matches!(byte, b',' | b']' | b'}') || byte.is_ascii_whitespace()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use http::header::{CONTENT_TYPE, HeaderMap, HeaderValue};
+    use mirrord_config::feature::network::incoming::masking::{MaskReplacement, MaskingConfig};
+    use rstest::rstest;
+
+    use super::Masker;
+
+    fn masker(json_fields: &[&str]) -> Arc<Masker> {
+        Masker::new(&MaskingConfig {
+            json_fields: json_fields.iter().map(ToString::to_string).collect(),
+            headers: vec!["authorization".into()],
+            replacement: MaskReplacement::Token("***".into()),
+        })
+        .map(Arc::new)
+        .unwrap()
+    }
+
+    fn json_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers
+    }
+
+    /// Masks the body fed in chunks of the given size.
+    fn mask_in_chunks(masker: &Arc<Masker>, body: &str, chunk_size: usize) -> String {
+        let mut json_masker = masker.json_masker(&json_headers()).unwrap();
+        let mut out = Vec::new();
+        for chunk in body.as_bytes().chunks(chunk_size) {
+            out.extend(json_masker.feed(chunk));
+        }
+        out.extend(json_masker.finish());
+        String::from_utf8(out).unwrap()
+    }
+
+    #[rstest]
+    #[case::nested(
+        &["$.user.email"],
+        r#"{"user": {"email": "a@b.c", "name": "A"}, "email": "x"}"#,
+        r#"{"user": {"email": "***", "name": "A"}, "email": "x"}"#,
+    )]
+    #[case::nested_object(
+        &["$.user"],
+        r#"{"user": {"email": "a@b.c", "tags": [1, "}"]}, "id": 7}"#,
+        r#"{"user": "***", "id": 7}"#,
+    )]
+    #[case::array_wildcard(
+        &["$.cards[*].number"],
+        r#"{"cards": [{"number": 1234, "exp": "01/30"}, {"number": "5678"}]}"#,
+        r#"{"cards": [{"number": "***", "exp": "01/30"}, {"number": "***"}]}"#,
+    )]
+    #[case::array_index(
+        &["$[1]"],
+        r#"[true, {"a": null}, -1.5e3]"#,
+        r#"[true, "***", -1.5e3]"#,
+    )]
+    #[case::any_field(
+        &["$.*.secret"],
+        r#"{"a": {"secret": "x\"y"}, "b": {"secret": false}}"#,
+        r#"{"a": {"secret": "***"}, "b": {"secret": "***"}}"#,
+    )]
+    #[case::root(&["$"], r#"  {"a": 1}"#, r#"  "***""#)]
+    #[case::no_match(&["$.missing"], r#"{"a": [1, 2]}"#, r#"{"a": [1, 2]}"#)]
+    fn mask_json(
+        #[case] json_fields: &[&str],
+        #[case] body: &str,
+        #[case] expected: &str,
+        #[values(1, 3, 1024)] chunk_size: usize,
+    ) {
+        let masker = masker(json_fields);
+        assert_eq!(mask_in_chunks(&masker, body, chunk_size), expected);
+    }
+
+    #[test]
+    fn invalid_json_passthrough() {
+        let masker = masker(&["$.a"]);
+        let body = r#"{"b" 1, "a": 2}"#;
+        assert_eq!(mask_in_chunks(&masker, body, 4), body);
+    }
+
+    #[test]
+    fn non_json_passthrough() {
+        let masker = masker(&["$.a"]);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        assert!(masker.mask_body(&headers, br#"{"a": 1}"#).is_none());
+        assert!(
+            masker
+                .mask_body(&HeaderMap::new(), br#"{"a": 1}"#)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn mask_headers() {
+        let masker = masker(&[]);
+
+        let mut headers = json_headers();
+        headers.insert("Authorization", HeaderValue::from_static("Bearer secret"));
+        masker.mask_headers(&mut headers);
+
+        assert_eq!(headers.get("authorization").unwrap(), "***");
+        assert_eq!(headers.get(CONTENT_TYPE).unwrap(), "application/json");
+    }
+
+    #[test]
+    fn hash_replacement_is_stable() {
+        let masker = Masker::new(&MaskingConfig {
+            json_fields: vec!["$[*]".into()],
+            ..Default::default()
+        })
+        .map(Arc::new)
+        .unwrap();
+
+        let masked = masker
+            .mask_body(&json_headers(), br#"["secret", "secret", "other"]"#)
+            .unwrap();
+        let masked: Vec<String> = serde_json::from_slice(&masked).unwrap();
+
+        assert_eq!(masked[0], masked[1]);
+        assert_ne!(masked[0], masked[2]);
+        assert!(masked[0].starts_with("sha256:"));
+    }
+}
@@ -352,7 +352,10 @@ impl PortForwarder {
             | DaemonMessage::UdpOutgoing(..)
             | DaemonMessage::Vpn(..)
             | DaemonMessage::TcpSteal(..)
-            | DaemonMessage::ReverseDnsLookup(..)) => {
+            | DaemonMessage::ReverseDnsLookup(..)
+            | DaemonMessage::Udp(..)
+            | DaemonMessage::RemoteTime(..)
+            | DaemonMessage::AgentLogs(..)) => {
                 // includes unexpected DaemonMessage::Pong
                 return Err(PortForwardError::AgentError(format!(
                     "unexpected message from agent: {message:?}"
@@ -499,6 +502,13 @@ impl ReversePortForwarder {
                     .clone()
                     .or_else(|| network_config.https_delivery.clone())
                     .unwrap_or_default(),
+                network_config.on_local_unavailable,
+                network_config.source_ip_delivery,
+                network_config.delivery_source,
+                // Already validated in `LayerConfig::verify`.
+                network_config.source_cidrs().unwrap_or_default(),
+                network_config.auto_downgrade,
+                Default::default(),
             ),
             (),
             512,
@@ -618,7 +628,10 @@ impl ReversePortForwarder {
             | message @ DaemonMessage::SwitchProtocolVersionResponse(_)
             | message @ DaemonMessage::Vpn(_)
             | message @ DaemonMessage::Pong
-            | message @ DaemonMessage::ReverseDnsLookup(_) => {
+            | message @ DaemonMessage::ReverseDnsLookup(_)
+            | message @ DaemonMessage::Udp(_)
+            | message @ DaemonMessage::RemoteTime(_)
+            | message @ DaemonMessage::AgentLogs(_) => {
                 return Err(PortForwardError::AgentError(format!(
                     "unexpected message from agent: {message:?}"
                 )));
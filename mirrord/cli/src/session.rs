@@ -0,0 +1,609 @@
+//! `mirrord session top` shows the resource usage of a running mirrord session in one view: the
+//! remote target pod (from the Kubernetes metrics API), the local application and our internal
+//! proxy (sampled locally).
+//!
+//! `mirrord session resume-steal` restores stealing after the internal proxy downgraded it, as
+//! recorded in its [`SessionMetadata`].
+//!
+//! The session is found through the [`LayerConfig::RESOLVED_CONFIG_ENV`] variable, which is set
+//! for both the local application and the internal proxy, and also gives us the target and the
+//! kube config to use.
+
+use std::{ops::Not, time::Duration};
+
+use kube::{
+    Api, Client,
+    api::{ApiResource, DynamicObject},
+};
+use mirrord_config::{LayerConfig, config::ConfigError, target::Target};
+use mirrord_intproxy::session_metadata::SessionMetadata;
+use mirrord_kube::api::runtime::RuntimeDataProvider;
+use prettytable::{Table, row};
+use serde::{Deserialize, Serialize};
+use sysinfo::{Pid, Process, ProcessRefreshKind, ProcessesToUpdate, Signal, System, UpdateKind};
+
+use crate::{
+    CliResult,
+    config::{SessionArgs, SessionInspectCommand, SessionResumeStealArgs, SessionTopArgs},
+    kube::kube_client_from_layer_config,
+};
+
+/// Displayed in place of the values that could not be fetched.
+const NOT_AVAILABLE: &str = "N/A";
+
+pub(crate) async fn session_command(args: SessionArgs) -> CliResult<()> {
+    match args.command {
+        SessionInspectCommand::Top(args) => top(*args).await?,
+        SessionInspectCommand::ResumeSteal(args) => resume_steal(args)?,
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub(crate) enum SessionError {
+    #[error("no running mirrord session was found")]
+    NoSession,
+
+    #[error("found multiple running mirrord sessions, pick one with `--pid`: {0}")]
+    MultipleSessions(String),
+
+    #[error("process {0} is not running with mirrord")]
+    NotInSession(u32),
+
+    #[error("the application with pid {0} exited")]
+    SessionEnded(u32),
+
+    #[error("the internal proxy of the application with pid {0} is not running")]
+    NoIntproxy(u32),
+
+    #[error("failed to read the session metadata: {0}")]
+    Metadata(#[source] std::io::Error),
+
+    #[error("failed to signal the internal proxy with pid {0}, try `kill -USR1 {0}`")]
+    SignalFailed(u32),
+
+    #[error("failed to read the session config: {0}")]
+    Config(#[from] ConfigError),
+
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+/// Prints the resource usage of the session, refreshing it every `interval` seconds until the
+/// local application exits (or just once with `--once`).
+async fn top(args: SessionTopArgs) -> Result<(), SessionError> {
+    let mut system = session_processes();
+
+    let (local, encoded_config) = find_session(&system, args.pid)?;
+    let intproxy = find_intproxy(&system, encoded_config);
+    let config = LayerConfig::decode(encoded_config)?;
+    let target = TargetMetrics::resolve(&config).await;
+
+    let pids = [Some(local), intproxy]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    // CPU usage is computed from the difference between two refreshes.
+    refresh_usage(&mut system, &pids);
+    tokio::time::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL).await;
+
+    loop {
+        refresh_usage(&mut system, &pids);
+        if system.process(local).is_none() {
+            return Err(SessionError::SessionEnded(local.as_u32()));
+        }
+
+        let snapshot = TopSnapshot {
+            target: target.usage().await,
+            local: ProcessUsage::new(&system, Some(local)),
+            intproxy: ProcessUsage::new(&system, intproxy),
+        };
+
+        if args.json {
+            println!("{}", serde_json::to_string_pretty(&snapshot)?);
+        } else {
+            if args.once.not() {
+                // Clears the terminal, so that the table is refreshed in place.
+                print!("\x1b[2J\x1b[H");
+            }
+            snapshot.table().printstd();
+        }
+
+        if args.once {
+            return Ok(());
+        }
+
+        tokio::time::sleep(Duration::from_secs(args.interval)).await;
+    }
+}
+
+/// Asks the internal proxy of the session to resume stealing with `SIGUSR1`, if its
+/// [`SessionMetadata`] says that stealing was downgraded.
+fn resume_steal(args: SessionResumeStealArgs) -> Result<(), SessionError> {
+    let system = session_processes();
+
+    let (local, encoded_config) = find_session(&system, args.pid)?;
+    let intproxy = find_intproxy(&system, encoded_config)
+        .and_then(|pid| system.process(pid))
+        .ok_or(SessionError::NoIntproxy(local.as_u32()))?;
+    let intproxy_pid = intproxy.pid().as_u32();
+
+    let metadata = SessionMetadata::load(&SessionMetadata::path(intproxy_pid))
+        .map_err(SessionError::Metadata)?;
+    if metadata.steal_downgrade.is_none() {
+        println!("Stealing was not downgraded in the session, nothing to resume.");
+        return Ok(());
+    }
+
+    if intproxy.kill_with(Signal::User1) != Some(true) {
+        return Err(SessionError::SignalFailed(intproxy_pid));
+    }
+
+    println!("Asked the internal proxy with pid {intproxy_pid} to resume stealing.");
+
+    Ok(())
+}
+
+/// Loads all the running processes, with what we need to find the mirrord sessions among them.
+fn session_processes() -> System {
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::All,
+        true,
+        ProcessRefreshKind::nothing()
+            .with_cmd(UpdateKind::OnlyIfNotSet)
+            .with_environ(UpdateKind::OnlyIfNotSet),
+    );
+    system
+}
+
+/// Value of [`LayerConfig::RESOLVED_CONFIG_ENV`] in the environment of the `process`, set only
+/// for the processes of a mirrord session.
+fn resolved_config(process: &Process) -> Option<&str> {
+    process.environ().iter().find_map(|var| {
+        var.to_str()?
+            .strip_prefix(LayerConfig::RESOLVED_CONFIG_ENV)?
+            .strip_prefix('=')
+    })
+}
+
+/// Whether the `process` is our internal proxy (`mirrord intproxy`).
+fn is_intproxy(process: &Process) -> bool {
+    process.cmd().get(1).is_some_and(|arg| arg == "intproxy")
+}
+
+/// Finds the local application of the session, either the one with the given `pid`, or the only
+/// one running. Children of the application are part of the same session, so only the topmost
+/// process is considered.
+///
+/// Returns the pid of the application and its encoded config.
+fn find_session(system: &System, pid: Option<u32>) -> Result<(Pid, &str), SessionError> {
+    if let Some(pid) = pid {
+        return system
+            .process(Pid::from_u32(pid))
+            .and_then(|process| Some((process.pid(), resolved_config(process)?)))
+            .ok_or(SessionError::NotInSession(pid));
+    }
+
+    let in_session =
+        |process: &Process| is_intproxy(process).not() && resolved_config(process).is_some();
+
+    let mut sessions = system
+        .processes()
+        .values()
+        .filter(|process| in_session(process))
+        .filter(|process| {
+            process
+                .parent()
+                .and_then(|parent| system.process(parent))
+                .is_none_or(|parent| in_session(parent).not())
+        })
+        .filter_map(|process| Some((process.pid(), resolved_config(process)?)))
+        .collect::<Vec<_>>();
+
+    match sessions.len() {
+        0 => Err(SessionError::NoSession),
+        1 => Ok(sessions.remove(0)),
+        _ => {
+            let mut pids = sessions
+                .iter()
+                .map(|(pid, _)| pid.as_u32())
+                .collect::<Vec<_>>();
+            pids.sort_unstable();
+            Err(SessionError::MultipleSessions(
+                pids.iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            ))
+        }
+    }
+}
+
+/// Finds the internal proxy that was started with the same `encoded_config` as the application.
+fn find_intproxy(system: &System, encoded_config: &str) -> Option<Pid> {
+    system
+        .processes()
+        .values()
+        .find(|process| is_intproxy(process) && resolved_config(process) == Some(encoded_config))
+        .map(Process::pid)
+}
+
+/// Refreshes the CPU and memory usage of the given processes, dropping the ones that exited.
+fn refresh_usage(system: &mut System, pids: &[Pid]) {
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(pids),
+        true,
+        ProcessRefreshKind::nothing().with_cpu().with_memory(),
+    );
+}
+
+/// Where the usage of the target pod comes from, or why it's not available.
+enum TargetMetrics {
+    Available {
+        pod: String,
+        namespace: String,
+        api: Api<DynamicObject>,
+    },
+    Unavailable(String),
+}
+
+impl TargetMetrics {
+    /// Resolves the target pod of the session from its `config`.
+    async fn resolve(config: &LayerConfig) -> Self {
+        let target = match config.target.path.as_ref() {
+            None | Some(Target::Targetless) => {
+                return Self::Unavailable("targetless session".to_string());
+            }
+            Some(target) => target,
+        };
+
+        let client = match kube_client_from_layer_config(config).await {
+            Ok(client) => client,
+            Err(error) => return Self::Unavailable(error.to_string()),
+        };
+
+        match target
+            .runtime_data(&client, config.target.namespace.as_deref())
+            .await
+        {
+            Ok(runtime_data) => Self::Available {
+                api: pod_metrics_api(client, &runtime_data.pod_namespace),
+                pod: runtime_data.pod_name,
+                namespace: runtime_data.pod_namespace,
+            },
+            Err(error) => Self::Unavailable(error.to_string()),
+        }
+    }
+
+    /// Fetches the current usage of the target pod.
+    async fn usage(&self) -> TargetUsage {
+        match self {
+            Self::Available {
+                pod,
+                namespace,
+                api,
+            } => {
+                let result = api.get(pod).await.map_err(|error| match error {
+                    kube::Error::Api(response) if response.code == 404 => {
+                        "no pod metrics, is metrics-server installed?".to_string()
+                    }
+                    error => error.to_string(),
+                });
+
+                TargetUsage::from_metrics(
+                    pod.clone(),
+                    namespace.clone(),
+                    result.map(|object| object.data),
+                )
+            }
+            Self::Unavailable(reason) => TargetUsage {
+                pod: None,
+                namespace: None,
+                usage: Default::default(),
+                unavailable: Some(reason.clone()),
+            },
+        }
+    }
+}
+
+/// [`Api`] for the `PodMetrics` resource of the metrics API, served by metrics-server.
+fn pod_metrics_api(client: Client, namespace: &str) -> Api<DynamicObject> {
+    let resource = ApiResource {
+        group: "metrics.k8s.io".to_string(),
+        version: "v1beta1".to_string(),
+        api_version: "metrics.k8s.io/v1beta1".to_string(),
+        kind: "PodMetrics".to_string(),
+        plural: "pods".to_string(),
+    };
+
+    Api::namespaced_with(client, namespace, &resource)
+}
+
+/// The part of the metrics API `PodMetrics` resource that we use.
+#[derive(Deserialize, Debug)]
+struct PodMetrics {
+    containers: Vec<ContainerMetrics>,
+}
+
+#[derive(Deserialize, Debug)]
+struct ContainerMetrics {
+    usage: ContainerUsage,
+}
+
+#[derive(Deserialize, Debug)]
+struct ContainerUsage {
+    cpu: String,
+    memory: String,
+}
+
+impl PodMetrics {
+    /// Sums the usage of all the containers in the pod, [`None`] if any of the quantities is
+    /// invalid.
+    fn usage(&self) -> Usage {
+        Usage {
+            cpu_millicores: self
+                .containers
+                .iter()
+                .map(|container| cpu_millicores(&container.usage.cpu))
+                .sum(),
+            memory_bytes: self
+                .containers
+                .iter()
+                .map(|container| memory_bytes(&container.usage.memory))
+                .sum(),
+        }
+    }
+}
+
+/// Parses a Kubernetes CPU quantity (e.g. `250m`, `2`, `123456n`) into millicores.
+fn cpu_millicores(quantity: &str) -> Option<f64> {
+    let (number, scale) = [("n", 1e-6), ("u", 1e-3), ("m", 1.0)]
+        .into_iter()
+        .find_map(|(suffix, scale)| Some((quantity.strip_suffix(suffix)?, scale)))
+        .unwrap_or((quantity, 1000.0));
+
+    number.parse::<f64>().ok().map(|number| number * scale)
+}
+
+/// Parses a Kubernetes memory quantity (e.g. `1024Ki`, `64Mi`, `1G`, `1000`) into bytes.
+fn memory_bytes(quantity: &str) -> Option<u64> {
+    let (number, multiplier) = [
+        ("Ki", 1u64 << 10),
+        ("Mi", 1 << 20),
+        ("Gi", 1 << 30),
+        ("Ti", 1 << 40),
+        ("k", 1_000),
+        ("M", 1_000_000),
+        ("G", 1_000_000_000),
+        ("T", 1_000_000_000_000),
+    ]
+    .into_iter()
+    .find_map(|(suffix, multiplier)| Some((quantity.strip_suffix(suffix)?, multiplier)))
+    .unwrap_or((quantity, 1));
+
+    number
+        .parse::<f64>()
+        .ok()
+        .map(|number| (number * multiplier as f64) as u64)
+}
+
+/// CPU and memory usage, [`None`] when not available.
+///
+/// CPU usage is in millicores for both remote and local processes, so `1000` is one fully used
+/// core.
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq)]
+struct Usage {
+    cpu_millicores: Option<f64>,
+    memory_bytes: Option<u64>,
+}
+
+impl Usage {
+    fn cpu(&self) -> String {
+        self.cpu_millicores
+            .map(|cpu| format!("{cpu:.1}m"))
+            .unwrap_or_else(|| NOT_AVAILABLE.to_string())
+    }
+
+    fn memory(&self) -> String {
+        self.memory_bytes
+            .map(|memory| format!("{:.1} MiB", memory as f64 / (1 << 20) as f64))
+            .unwrap_or_else(|| NOT_AVAILABLE.to_string())
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct TargetUsage {
+    pod: Option<String>,
+    namespace: Option<String>,
+    #[serde(flatten)]
+    usage: Usage,
+    /// Why the usage is not available, e.g. when metrics-server is not installed in the cluster.
+    unavailable: Option<String>,
+}
+
+impl TargetUsage {
+    /// Builds the usage of the target `pod` from a `PodMetrics` response of the metrics API.
+    fn from_metrics(
+        pod: String,
+        namespace: String,
+        metrics: Result<serde_json::Value, String>,
+    ) -> Self {
+        let usage = metrics.and_then(|metrics| {
+            serde_json::from_value::<PodMetrics>(metrics)
+                .map(|metrics| metrics.usage())
+                .map_err(|error| format!("invalid pod metrics: {error}"))
+        });
+
+        let (usage, unavailable) = match usage {
+            Ok(usage) => (usage, None),
+            Err(reason) => (Default::default(), Some(reason)),
+        };
+
+        Self {
+            pod: Some(pod),
+            namespace: Some(namespace),
+            usage,
+            unavailable,
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct ProcessUsage {
+    pid: Option<u32>,
+    #[serde(flatten)]
+    usage: Usage,
+}
+
+impl ProcessUsage {
+    fn new(system: &System, pid: Option<Pid>) -> Self {
+        let usage = pid
+            .and_then(|pid| system.process(pid))
+            .map(|process| Usage {
+                // `cpu_usage` is in percent of a single core.
+                cpu_millicores: Some(f64::from(process.cpu_usage()) * 10.0),
+                memory_bytes: Some(process.memory()),
+            })
+            .unwrap_or_default();
+
+        Self {
+            pid: pid.map(Pid::as_u32),
+            usage,
+        }
+    }
+}
+
+/// Resource usage of the whole session, printed as JSON with `--json`.
+#[derive(Serialize, Debug)]
+struct TopSnapshot {
+    target: TargetUsage,
+    local: ProcessUsage,
+    intproxy: ProcessUsage,
+}
+
+impl TopSnapshot {
+    fn table(&self) -> Table {
+        let mut table = Table::new();
+        table.add_row(row!["Process", "CPU", "Memory", "Details"]);
+
+        let target_name = match (&self.target.pod, &self.target.namespace) {
+            (Some(pod), Some(namespace)) => format!("target (pod/{pod} in {namespace})"),
+            _ => "target".to_string(),
+        };
+        table.add_row(row![
+            target_name,
+            self.target.usage.cpu(),
+            self.target.usage.memory(),
+            self.target.unavailable.as_deref().unwrap_or_default()
+        ]);
+
+        for (name, process) in [("local", &self.local), ("intproxy", &self.intproxy)] {
+            table.add_row(row![
+                name,
+                process.usage.cpu(),
+                process.usage.memory(),
+                process
+                    .pid
+                    .map(|pid| format!("pid {pid}"))
+                    .unwrap_or_else(|| "not found".to_string())
+            ]);
+        }
+
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+
+    /// Response of the metrics API for a pod with a sidecar.
+    fn pod_metrics() -> serde_json::Value {
+        json!({
+            "timestamp": "2026-10-15T10:00:00Z",
+            "window": "15s",
+            "containers": [
+                { "name": "app", "usage": { "cpu": "250000000n", "memory": "64Mi" } },
+                { "name": "sidecar", "usage": { "cpu": "5m", "memory": "1024Ki" } }
+            ]
+        })
+    }
+
+    #[rstest]
+    #[case::nano("250000000n", 250.0)]
+    #[case::micro("1500u", 1.5)]
+    #[case::milli("5m", 5.0)]
+    #[case::cores("2", 2000.0)]
+    fn parse_cpu(#[case] quantity: &str, #[case] expected: f64) {
+        assert_eq!(cpu_millicores(quantity), Some(expected));
+    }
+
+    #[rstest]
+    #[case::kibi("1024Ki", 1 << 20)]
+    #[case::mebi("64Mi", 64 << 20)]
+    #[case::giga("1G", 1_000_000_000)]
+    #[case::bytes("1000", 1000)]
+    fn parse_memory(#[case] quantity: &str, #[case] expected: u64) {
+        assert_eq!(memory_bytes(quantity), Some(expected));
+    }
+
+    /// The JSON printed with `--once --json` combines the target pod metrics with the local
+    /// processes.
+    #[test]
+    fn snapshot_json() {
+        let snapshot = TopSnapshot {
+            target: TargetUsage::from_metrics(
+                "app-7d4b9c".to_string(),
+                "default".to_string(),
+                Ok(pod_metrics()),
+            ),
+            local: ProcessUsage {
+                pid: Some(1234),
+                usage: Usage {
+                    cpu_millicores: Some(100.0),
+                    memory_bytes: Some(2048),
+                },
+            },
+            intproxy: ProcessUsage {
+                pid: None,
+                usage: Default::default(),
+            },
+        };
+
+        assert_eq!(
+            serde_json::to_value(&snapshot).unwrap(),
+            json!({
+                "target": {
+                    "pod": "app-7d4b9c",
+                    "namespace": "default",
+                    "cpu_millicores": 255.0,
+                    "memory_bytes": (64 << 20) + (1 << 20),
+                    "unavailable": null
+                },
+                "local": { "pid": 1234, "cpu_millicores": 100.0, "memory_bytes": 2048 },
+                "intproxy": { "pid": null, "cpu_millicores": null, "memory_bytes": null }
+            })
+        );
+    }
+
+    /// Without metrics-server (or with an unexpected response), the target usage is N/A.
+    #[rstest]
+    #[case::missing(Err("no pod metrics, is metrics-server installed?".to_string()))]
+    #[case::invalid(Ok(json!({ "containers": [{ "name": "app" }] })))]
+    fn snapshot_without_metrics(#[case] metrics: Result<serde_json::Value, String>) {
+        let target = TargetUsage::from_metrics("app".to_string(), "default".to_string(), metrics);
+
+        assert_eq!(target.usage, Usage::default());
+        assert!(target.unavailable.is_some());
+
+        let json = serde_json::to_value(&target).unwrap();
+        assert_eq!(json["cpu_millicores"], serde_json::Value::Null);
+        assert_eq!(json["memory_bytes"], serde_json::Value::Null);
+        assert_eq!(target.usage.cpu(), NOT_AVAILABLE);
+    }
+}
@@ -0,0 +1,205 @@
+//! Cache of the [`sip_patch`] results for the executables started with `mirrord exec`, so that we
+//! don't check (and patch) the same large binary again on every run.
+
+use std::{
+    fs,
+    os::unix::ffi::OsStrExt,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use mirrord_sip::{
+    MIRRORD_TEMP_BIN_DIR_PATH_BUF, SipError, SipLogInfo, SipPatchOptions, sip_patch,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, trace};
+
+/// Where the cache entries are stored, relative to [`MIRRORD_TEMP_BIN_DIR_PATH_BUF`], which is
+/// specific to the mirrord version.
+const SIP_CACHE_DIR: &str = ".sip-cache";
+
+/// The result of [`sip_patch`] for an executable, and the state of the executable at the time.
+#[derive(Debug, Serialize, Deserialize)]
+struct SipCacheEntry {
+    modified: SystemTime,
+    size: u64,
+    /// Path to the patched copy, [`None`] if the executable didn't need patching.
+    patched: Option<String>,
+}
+
+impl SipCacheEntry {
+    /// Whether the executable did not change since this entry was made, and the patched copy (if
+    /// any) is still there.
+    fn is_fresh(&self, modified: SystemTime, size: u64) -> bool {
+        self.modified == modified
+            && self.size == size
+            && self
+                .patched
+                .as_ref()
+                .is_none_or(|patched| Path::new(patched).exists())
+    }
+}
+
+/// Like [`sip_patch`], but reuses the result from a previous run if the executable did not change
+/// since.
+///
+/// The results are keyed by the resolved path of the executable and the `opts`, and are
+/// invalidated when the modification time or size of the executable changes. A stale patched copy
+/// is removed before patching again, as [`sip_patch`] would reuse it otherwise.
+pub(crate) fn sip_patch_cached(
+    executable: &str,
+    opts: SipPatchOptions,
+    log_info: Option<SipLogInfo>,
+) -> Result<Option<String>, SipError> {
+    let Some((path, modified, size)) = which::which(executable).ok().and_then(|path| {
+        let metadata = fs::metadata(&path).ok()?;
+        Some((path, metadata.modified().ok()?, metadata.len()))
+    }) else {
+        return sip_patch(executable, opts, log_info);
+    };
+
+    let entry_path = entry_path(&path, opts);
+    match read_entry(&entry_path) {
+        Some(entry) if entry.is_fresh(modified, size) => {
+            trace!(?path, ?entry, "Using the cached SIP patch result");
+            return Ok(entry.patched);
+        }
+        Some(SipCacheEntry {
+            patched: Some(stale),
+            ..
+        }) => {
+            debug!(?path, %stale, "Executable changed, removing the stale SIP-patched copy");
+            let _ = fs::remove_file(stale);
+        }
+        _ => {}
+    }
+
+    let patched = sip_patch(executable, opts, log_info)?;
+
+    let entry = SipCacheEntry {
+        modified,
+        size,
+        patched: patched.clone(),
+    };
+    if let Err(error) = write_entry(&entry_path, &entry) {
+        debug!(%error, ?path, "Failed to store the SIP patch result in the cache");
+    }
+
+    Ok(patched)
+}
+
+/// Path of the cache entry for the executable at `path`, patched with `opts`.
+fn entry_path(path: &Path, opts: SipPatchOptions) -> PathBuf {
+    let mut hasher = Sha256::new_with_prefix(path.as_os_str().as_bytes());
+
+    for (list, values) in [
+        (b"patch".as_slice(), opts.patch),
+        (b"skip".as_slice(), opts.skip),
+    ] {
+        hasher.update(b"\0");
+        hasher.update(list);
+        for value in values {
+            hasher.update(b"\0");
+            hasher.update(value.as_bytes());
+        }
+    }
+
+    MIRRORD_TEMP_BIN_DIR_PATH_BUF
+        .join(SIP_CACHE_DIR)
+        .join(hex::encode(hasher.finalize()))
+}
+
+fn read_entry(entry_path: &Path) -> Option<SipCacheEntry> {
+    let bytes = fs::read(entry_path).ok()?;
+    serde_json::from_slice(&bytes).ok()
+}
+
+fn write_entry(entry_path: &Path, entry: &SipCacheEntry) -> std::io::Result<()> {
+    if let Some(parent) = entry_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    fs::write(entry_path, serde_json::to_vec(entry)?)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{io::Write, os::unix::fs::PermissionsExt};
+
+    use super::*;
+
+    /// Cached results are used while the executable stays the same, and dropped (together with the
+    /// stale patched copy) when it changes.
+    #[test]
+    fn cached_until_executable_changes() {
+        // A copy of a system binary is not SIP protected.
+        let mut executable = tempfile::NamedTempFile::new().unwrap();
+        executable.write_all(&fs::read("/bin/ls").unwrap()).unwrap();
+        executable.flush().unwrap();
+        fs::set_permissions(executable.path(), fs::Permissions::from_mode(0o755)).unwrap();
+        let executable_path = executable.path().to_str().unwrap();
+
+        let patched = tempfile::NamedTempFile::new().unwrap().into_temp_path();
+        let patched_path = patched.to_str().unwrap().to_string();
+
+        // Pretend that a previous run patched the executable.
+        let metadata = fs::metadata(executable.path()).unwrap();
+        let entry_path = entry_path(executable.path(), SipPatchOptions::default());
+        write_entry(
+            &entry_path,
+            &SipCacheEntry {
+                modified: metadata.modified().unwrap(),
+                size: metadata.len(),
+                patched: Some(patched_path.clone()),
+            },
+        )
+        .unwrap();
+
+        let result = sip_patch_cached(executable_path, SipPatchOptions::default(), None).unwrap();
+        assert_eq!(result.as_deref(), Some(patched_path.as_str()));
+
+        executable.write_all(b"changed").unwrap();
+        executable.flush().unwrap();
+
+        let result = sip_patch_cached(executable_path, SipPatchOptions::default(), None).unwrap();
+        assert_ne!(result.as_deref(), Some(patched_path.as_str()));
+        assert!(!patched.exists());
+
+        let _ = fs::remove_file(entry_path);
+    }
+
+    /// The patch and skip lists are a part of the key.
+    #[test]
+    fn options_in_key() {
+        let path = Path::new("/usr/bin/some-binary");
+        let patch = ["a".to_string()];
+
+        assert_ne!(
+            entry_path(path, SipPatchOptions::default()),
+            entry_path(
+                path,
+                SipPatchOptions {
+                    patch: &patch,
+                    skip: &[],
+                }
+            )
+        );
+        assert_ne!(
+            entry_path(
+                path,
+                SipPatchOptions {
+                    patch: &patch,
+                    skip: &[],
+                }
+            ),
+            entry_path(
+                path,
+                SipPatchOptions {
+                    patch: &[],
+                    skip: &patch,
+                }
+            )
+        );
+    }
+}
@@ -119,6 +119,9 @@ pub(crate) fn create_listen_socket(addr: SocketAddr) -> io::Result<TcpListener>
         Some(socket2::Protocol::TCP),
     )?;
 
+    // Allows the external proxy to listen on the same port again after it was restarted.
+    #[cfg(unix)]
+    socket.set_reuse_address(true)?;
     socket.bind(&socket2::SockAddr::from(addr))?;
     socket.listen(1024)?;
     socket.set_nonblocking(true)?;
@@ -258,7 +258,7 @@ async fn cluster_details(State(arc): State<Arc<Mutex<BackendState>>>) -> CliResu
     let client = user_guard.client.clone();
     let seeker = KubeResourceSeeker {
         client: &client,
-        namespace: "default",
+        namespace: Some("default"),
         copy_target: true,
     };
 
@@ -292,7 +292,7 @@ async fn list_targets(
     let client = user_guard.client.clone();
     let seeker = KubeResourceSeeker {
         client: &client,
-        namespace: &namespace,
+        namespace: Some(&namespace),
         copy_target: true,
     };
 
@@ -78,6 +78,7 @@ impl ToTokens for ConfigStruct {
 
         let field_definitions = fields.iter().map(|field| field.definition());
         let field_impl = fields.iter().map(|field| field.implementation(source));
+        let env_overrides = fields.iter().filter_map(|field| field.env_override());
 
         let generator = generator.as_ref().unwrap_or(ident);
 
@@ -90,6 +91,8 @@ impl ToTokens for ConfigStruct {
             impl crate::config::MirrordConfig for #ident {
                 type Generated = #source;
 
+                const ENV_OVERRIDES: &'static [(&'static str, &'static str)] = &[#(#env_overrides),*];
+
                 fn generate_config(self, context: &mut crate::config::ConfigContext) -> crate::config::Result<Self::Generated> {
                     Ok(#source {
                         #(#field_impl),*
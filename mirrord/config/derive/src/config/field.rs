@@ -1,3 +1,4 @@
+use proc_macro2::TokenStream;
 use proc_macro2_diagnostics::Diagnostic;
 use quote::{ToTokens, quote};
 use syn::{Field, GenericArgument, Ident, PathArguments, Type, Visibility};
@@ -187,6 +188,27 @@ impl ConfigField {
             quote! { #ident: #impls #(#layers),* .source_value(context).transpose()?#unwrapper }
         }
     }
+
+    /// Will create the entry of `MirrordConfig::ENV_OVERRIDES` for fields with the `env` flag
+    ///
+    /// ```rust
+    /// #[config(env = "TEST")]
+    /// pub test: String,
+    /// ```
+    /// Will output
+    /// ```rust
+    /// ("test", "TEST")
+    /// ```
+    pub fn env_override(&self) -> Option<TokenStream> {
+        let EnvFlag(env) = self.flags.env.as_ref()?;
+
+        let name = match (&self.flags.rename, &self.ident) {
+            (Some(rename), _) => quote! { #rename },
+            (None, ident) => quote! { stringify!(#ident) },
+        };
+
+        Some(quote! { (#name, #env) })
+    }
 }
 
 impl TryFrom<Field> for ConfigField {
@@ -1,14 +1,23 @@
-use std::{collections::HashMap, fmt, net::SocketAddr, path::Path};
+use std::{
+    collections::HashMap,
+    fmt,
+    net::{IpAddr, SocketAddr},
+    ops::Not,
+    path::Path,
+};
 
-use k8s_openapi::api::core::v1::{ResourceRequirements, Toleration};
+use http::Uri;
+use ipnet::IpNet;
+use k8s_openapi::api::core::v1::{Affinity, ResourceRequirements, Toleration};
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::config::{
-    self, ConfigContext, FromFileError, FromMirrordConfig, MirrordConfig, from_env::FromEnv,
-    source::MirrordConfigSource,
+    self, ConfigContext, ConfigError, FromFileError, FromMirrordConfig, MirrordConfig,
+    from_env::FromEnv, source::MirrordConfigSource,
 };
 
 /// Linux capabilities used by the mirrord-agent container.
@@ -18,10 +27,12 @@ pub enum LinuxCapability {
     SysAdmin,
     SysPtrace,
     NetAdmin,
+    /// Only used with [`AgentRedirector::Ebpf`] and [`AgentRedirector::Auto`].
+    Bpf,
 }
 
 impl LinuxCapability {
-    /// All capabilities that can be used by the agent.
+    /// All capabilities that can be used by the agent by default.
     pub fn all() -> &'static [Self] {
         &[Self::SysAdmin, Self::SysPtrace, Self::NetAdmin]
     }
@@ -31,6 +42,7 @@ impl LinuxCapability {
             Self::SysAdmin => "SYS_ADMIN",
             Self::SysPtrace => "SYS_PTRACE",
             Self::NetAdmin => "NET_ADMIN",
+            Self::Bpf => "BPF",
         }
     }
 }
@@ -41,6 +53,101 @@ impl fmt::Display for LinuxCapability {
     }
 }
 
+/// Implementation used by the agent to redirect incoming traffic, see
+/// [`AgentConfig::redirector`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AgentRedirector {
+    /// Use iptables/ip6tables rules.
+    #[default]
+    Iptables,
+    /// Use rules in a dedicated nftables table.
+    Nftables,
+    /// Use an eBPF `sk_lookup` program.
+    Ebpf,
+    /// Use iptables if the agent has the `NET_ADMIN` capability, eBPF otherwise.
+    Auto,
+}
+
+/// Configuration of the agent's prometheus metrics server, see [`AgentConfig::metrics`].
+#[derive(Clone, Debug, PartialEq, Eq, JsonSchema, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum AgentMetricsConfig {
+    /// Address of the metrics server, e.g. `0.0.0.0:9000` or `unix:///path/metrics.sock`.
+    Address(String),
+    /// Address of the metrics server, and the bearer token required to access it.
+    Detailed {
+        /// Address of the metrics server, e.g. `0.0.0.0:9000` or `unix:///path/metrics.sock`.
+        address: String,
+        /// Name of the environment variable that holds the bearer token.
+        ///
+        /// The variable is read from the environment of the mirrord CLI.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        token_env: Option<String>,
+    },
+}
+
+impl AgentMetricsConfig {
+    /// Prefix of unix socket addresses.
+    const UNIX_PREFIX: &'static str = "unix://";
+
+    pub fn address(&self) -> &str {
+        match self {
+            Self::Address(address) | Self::Detailed { address, .. } => address,
+        }
+    }
+
+    pub fn token_env(&self) -> Option<&str> {
+        match self {
+            Self::Address(..) => None,
+            Self::Detailed { token_env, .. } => token_env.as_deref(),
+        }
+    }
+
+    /// Reads the bearer token from the [`AgentMetricsConfig::token_env`] variable, if set.
+    ///
+    /// Fails when the variable is not present in the environment.
+    pub fn token(&self) -> config::Result<Option<String>> {
+        let Some(token_env) = self.token_env() else {
+            return Ok(None);
+        };
+
+        match std::env::var(token_env) {
+            Ok(token) if token.is_empty().not() => Ok(Some(token)),
+            _ => Err(ConfigError::InvalidValue {
+                name: "agent.metrics.token_env",
+                provided: token_env.to_string(),
+                error: "the environment variable is not set or empty".into(),
+            }),
+        }
+    }
+
+    /// Verifies that the [`AgentMetricsConfig::address`] is either a socket address, or an
+    /// absolute unix socket path prefixed with `unix://`, and that the token can be read.
+    pub fn verify(&self) -> config::Result<()> {
+        let address = self.address();
+        let invalid = |error: String| ConfigError::InvalidValue {
+            name: "agent.metrics",
+            provided: address.to_string(),
+            error: error.into(),
+        };
+
+        match address.strip_prefix(Self::UNIX_PREFIX) {
+            Some(path) if path.starts_with('/') => {}
+            Some(..) => return Err(invalid("the unix socket path must be absolute".to_string())),
+            None => {
+                address
+                    .parse::<SocketAddr>()
+                    .map_err(|error| invalid(error.to_string()))?;
+            }
+        }
+
+        self.token()?;
+
+        Ok(())
+    }
+}
+
 /// Configuration for the mirrord-agent pod that is spawned in the Kubernetes cluster.
 ///
 /// **Note:** this configuration is ignored when using the mirrord Operator.
@@ -222,6 +329,27 @@ pub struct AgentConfig {
     #[config(env = "MIRRORD_AGENT_STARTUP_TIMEOUT", default = 60)]
     pub startup_timeout: u64,
 
+    /// ### agent.connection_proxy {#agent-connection_proxy}
+    ///
+    /// Proxy used only when establishing the connection to the agent (or to the mirrord
+    /// Operator), e.g. `"http://proxy.internal:3128"` or `"socks5://127.0.0.1:1080"`.
+    ///
+    /// This is independent of [`use_proxy`](#root-use_proxy), which only controls whether
+    /// `HTTP[S]_PROXY` env variables are removed, so you can route the mirrord connection
+    /// through a proxy while the application's traffic doesn't use it (or vice versa).
+    ///
+    /// Supports `http`, `https`, `socks5` and `socks5h` URLs.
+    ///
+    /// ```json
+    /// {
+    ///   "agent": {
+    ///     "connection_proxy": "http://proxy.internal:3128"
+    ///   }
+    /// }
+    /// ```
+    #[config(env = "MIRRORD_AGENT_CONNECTION_PROXY")]
+    pub connection_proxy: Option<String>,
+
     /// ### agent.flush_connections {#agent-flush_connections}
     ///
     /// Flushes existing connections when starting to steal, might fix issues where connections
@@ -323,6 +451,37 @@ pub struct AgentConfig {
     /// If not set, the agent will try to detect the correct backend at runtime.
     pub nftables: Option<bool>,
 
+    /// ### agent.redirector {#agent-redirector}
+    ///
+    /// Selects how the agent redirects incoming traffic of the target to itself.
+    ///
+    /// - `"iptables"`: uses iptables/ip6tables rules. Requires the `NET_ADMIN` capability.
+    /// - `"nftables"`: uses native nftables rules, kept in a dedicated `mirrord` table instead of
+    ///   the shared `nat` table, so they never conflict with the node's own rules. Requires the
+    ///   `NET_ADMIN` capability. Service mesh rules are not taken into account, and it is not
+    ///   compatible with [`agent.exclude_from_mesh`](#agent-exclude_from_mesh).
+    /// - `"ebpf"`: uses an eBPF `sk_lookup` program attached to the target's network namespace.
+    ///   Does not require `NET_ADMIN`, but requires the `BPF` capability and Linux 5.9 or newer.
+    ///   Not compatible with [`agent.exclude_from_mesh`](#agent-exclude_from_mesh).
+    /// - `"auto"`: uses iptables when the agent has the `NET_ADMIN` capability, eBPF otherwise.
+    ///
+    /// With `"ebpf"`, the agent container gets the `BPF` capability instead of `NET_ADMIN`.
+    /// With `"auto"`, the agent container gets the `BPF` capability in addition to the default
+    /// ones (use [`agent.disabled_capabilities`](#agent-disabled_capabilities) to drop
+    /// `NET_ADMIN`).
+    ///
+    /// Defaults to `"iptables"`.
+    ///
+    /// ```json
+    /// {
+    ///   "agent": {
+    ///     "redirector": "ebpf"
+    ///   }
+    /// }
+    /// ```
+    #[config(default)]
+    pub redirector: AgentRedirector,
+
     /// ### agent.dns {#agent-dns}
     #[config(nested)]
     pub dns: AgentDnsConfig,
@@ -371,6 +530,49 @@ pub struct AgentConfig {
     /// ```
     pub node_selector: Option<HashMap<String, String>>,
 
+    /// ### agent.node_name {#agent-node_name}
+    ///
+    /// Name of the node to schedule the agent Pod on, e.g. the node where a `hostPath` volume
+    /// that you need lives. Applies only to targetless runs, as targeted agent always runs on the
+    /// same node as its target container.
+    ///
+    /// ```json
+    /// {
+    ///   "agent": {
+    ///     "node_name": "node1"
+    ///   }
+    /// }
+    /// ```
+    pub node_name: Option<String>,
+
+    /// ### agent.affinity {#agent-affinity}
+    ///
+    /// Allows setting up custom [affinity](https://kubernetes.io/docs/concepts/scheduling-eviction/assign-pod-node/#affinity-and-anti-affinity)
+    /// for the agent Pod, e.g. to keep the agent away from nodes that are about to be scaled
+    /// down. The value is passed to the Pod spec as is. Applies only to targetless runs, as
+    /// targeted agent always runs on the same node as its target container.
+    ///
+    /// ```json
+    /// {
+    ///   "agent": {
+    ///     "affinity": {
+    ///       "nodeAffinity": {
+    ///         "requiredDuringSchedulingIgnoredDuringExecution": {
+    ///           "nodeSelectorTerms": [
+    ///             {
+    ///               "matchExpressions": [
+    ///                 { "key": "node-pool", "operator": "In", "values": ["stable"] }
+    ///               ]
+    ///             }
+    ///           ]
+    ///         }
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub affinity: Option<Value>,
+
     /// ### agent.service_account {#agent-service_account}
     ///
     /// Allows setting up custom Service Account for the agent Job and Pod.
@@ -398,7 +600,25 @@ pub struct AgentConfig {
     ///   }
     /// }
     /// ```
-    pub metrics: Option<SocketAddr>,
+    ///
+    /// The metrics server can also listen on a unix socket, e.g. for a sidecar scraper:
+    /// `"metrics": "unix:///var/run/mirrord/metrics.sock"`.
+    ///
+    /// To require a bearer token in the `Authorization` header of the scrape requests, use the
+    /// object form. The token is read from the given environment variable when the agent is
+    /// created:
+    ///
+    /// ```json
+    /// {
+    ///   "agent": {
+    ///     "metrics": {
+    ///       "address": "0.0.0.0:9000",
+    ///       "token_env": "METRICS_TOKEN"
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub metrics: Option<AgentMetricsConfig>,
 
     /// ### agent.exclude_from_mesh {#agent-exclude_from_mesh}
     ///
@@ -455,6 +675,24 @@ pub struct AgentConfig {
     #[config(default = 1000)]
     pub max_body_buffer_timeout: u32,
 
+    /// ### agent.max_mirrored_connections {#agent-max_mirrored_connections}
+    ///
+    /// Maximum number of connections (and HTTP requests) mirrored to a single mirrord session at
+    /// the same time. New connections over this limit are not mirrored, but they still reach the
+    /// remote application.
+    ///
+    /// Not limited by default.
+    pub max_mirrored_connections: Option<u32>,
+
+    /// ### agent.max_mirrored_connection_bytes {#agent-max_mirrored_connection_bytes}
+    ///
+    /// Maximum number of bytes mirrored from a single connection (or HTTP request). When the
+    /// limit is reached, the connection is no longer mirrored, but it still reaches the remote
+    /// application.
+    ///
+    /// Not limited by default.
+    pub max_mirrored_connection_bytes: Option<u64>,
+
     /// ### agent.security_context {#agent-security_context}
     ///
     /// Agent pod security context (not with ephemeral agents).
@@ -468,6 +706,53 @@ pub struct AgentConfig {
     #[config(env = "MIRRORD_AGENT_CLEAN_IPTABLES_ON_START")]
     pub clean_iptables_on_start: Option<bool>,
 
+    /// ### agent.iptables_chain_prefix {#agent-iptables_chain_prefix}
+    ///
+    /// Prefix of the iptables chains created by the agent, e.g. `"TEAM_A"` results in chains
+    /// named `TEAM_A_INPUT`, `TEAM_A_OUTPUT` and so on.
+    ///
+    /// Useful when other tooling in the cluster flushes or collides with mirrord's chains. Agents
+    /// only check for and clean up leftover chains with their own prefix.
+    ///
+    /// Up to 10 characters, letters, digits, `_` and `-`. Defaults to `"MIRRORD"`.
+    #[config(env = "MIRRORD_AGENT_IPTABLES_CHAIN_PREFIX")]
+    pub iptables_chain_prefix: Option<String>,
+
+    /// ### agent.iptables_exclude_destinations {#agent-iptables_exclude_destinations}
+    ///
+    /// Traffic to these IPs or CIDRs is never redirected by the agent, regardless of its port,
+    /// e.g. a cloud metadata service that must always be reached directly.
+    ///
+    /// ```json
+    /// {
+    ///   "agent": {
+    ///     "iptables_exclude_destinations": ["169.254.169.254/32", "fd00:ec2::254"]
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// IPv6 CIDRs only apply when [`feature.network.ipv6`](#feature-network-ipv6) is enabled. Not
+    /// supported with the eBPF redirector.
+    pub iptables_exclude_destinations: Option<Vec<String>>,
+
+    /// ### agent.iptables_exclude_sources {#agent-iptables_exclude_sources}
+    ///
+    /// Traffic from these IPs or CIDRs is never redirected by the agent, regardless of its port.
+    ///
+    /// IPv6 CIDRs only apply when [`feature.network.ipv6`](#feature-network-ipv6) is enabled. Not
+    /// supported with the eBPF redirector.
+    pub iptables_exclude_sources: Option<Vec<String>>,
+
+    /// ### agent.iptables_reconcile_interval {#agent-iptables_reconcile_interval}
+    ///
+    /// How often (in seconds) the agent checks its traffic redirection rules, and restores the
+    /// ones that were removed by other tooling in the cluster, e.g. a CNI plugin that flushes the
+    /// `nat` table.
+    ///
+    /// Disabled by default.
+    #[config(env = "MIRRORD_AGENT_IPTABLES_RECONCILE_INTERVAL")]
+    pub iptables_reconcile_interval: Option<u64>,
+
     /// ### agent.disable_mesh_sidecar_injection {#agent-disable_mesh_sidecar_injection}
     ///
     /// Add relevant labels and annotations to agent pods/jobs to
@@ -597,10 +882,132 @@ impl CollectAnalytics for &AgentConfig {
     }
 }
 
+/// <!--${internal}-->
+/// URL schemes supported in [`AgentConfig::connection_proxy`].
+const CONNECTION_PROXY_SCHEMES: &[&str] = &["http", "https", "socks5", "socks5h"];
+
+/// <!--${internal}-->
+/// Max length of [`AgentConfig::iptables_chain_prefix`], so that the longest chain name
+/// (`<prefix>_EXCLUDE_FROM_MESH`) fits in the 28 characters allowed by iptables.
+const IPTABLES_CHAIN_PREFIX_MAX_LEN: usize = 10;
+
 impl AgentConfig {
     pub fn image(&self) -> &str {
         &self.image.0
     }
+
+    /// Parses [`AgentConfig::connection_proxy`], if set.
+    ///
+    /// Fails when the value is not a valid URL with one of the [`CONNECTION_PROXY_SCHEMES`] and
+    /// a host.
+    pub fn connection_proxy_uri(&self) -> config::Result<Option<Uri>> {
+        let Some(proxy) = self.connection_proxy.as_deref() else {
+            return Ok(None);
+        };
+
+        let invalid = |error: String| ConfigError::InvalidValue {
+            name: "agent.connection_proxy",
+            provided: proxy.to_string(),
+            error: error.into(),
+        };
+
+        let uri = proxy
+            .parse::<Uri>()
+            .map_err(|error| invalid(error.to_string()))?;
+
+        match uri.scheme_str() {
+            Some(scheme) if CONNECTION_PROXY_SCHEMES.contains(&scheme) => {}
+            _ => {
+                return Err(invalid(format!(
+                    "the proxy URL must use one of the schemes: {}",
+                    CONNECTION_PROXY_SCHEMES.join(", ")
+                )));
+            }
+        }
+
+        if uri.host().is_none_or(str::is_empty) {
+            return Err(invalid("the proxy URL must contain a host".to_string()));
+        }
+
+        Ok(Some(uri))
+    }
+
+    /// Parses [`AgentConfig::affinity`], if set.
+    pub fn affinity(&self) -> config::Result<Option<Affinity>> {
+        let Some(affinity) = &self.affinity else {
+            return Ok(None);
+        };
+
+        serde_json::from_value(affinity.clone())
+            .map(Some)
+            .map_err(|error| ConfigError::InvalidValue {
+                name: "agent.affinity",
+                provided: affinity.to_string(),
+                error: error.into(),
+            })
+    }
+
+    /// Returns [`AgentConfig::iptables_chain_prefix`], if set.
+    ///
+    /// Fails when the prefix is empty, longer than [`IPTABLES_CHAIN_PREFIX_MAX_LEN`], or contains
+    /// characters other than ASCII letters, digits, `_` and `-`.
+    pub fn iptables_chain_prefix(&self) -> config::Result<Option<&str>> {
+        let Some(prefix) = self.iptables_chain_prefix.as_deref() else {
+            return Ok(None);
+        };
+
+        let valid = (1..=IPTABLES_CHAIN_PREFIX_MAX_LEN).contains(&prefix.len())
+            && prefix
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if valid.not() {
+            return Err(ConfigError::InvalidValue {
+                name: "agent.iptables_chain_prefix",
+                provided: prefix.to_string(),
+                error: format!(
+                    "the prefix must have between 1 and {IPTABLES_CHAIN_PREFIX_MAX_LEN} characters, \
+                    and contain only letters, digits, `_` and `-`"
+                )
+                .into(),
+            });
+        }
+
+        Ok(Some(prefix))
+    }
+
+    /// Parses [`AgentConfig::iptables_exclude_destinations`] and
+    /// [`AgentConfig::iptables_exclude_sources`], as `(destinations, sources)`.
+    ///
+    /// Plain IPs are turned into single address CIDRs. Fails on the first value that is neither.
+    pub fn iptables_exclusions(&self) -> config::Result<(Vec<IpNet>, Vec<IpNet>)> {
+        let parse = |name: &'static str, values: Option<&Vec<String>>| {
+            values
+                .into_iter()
+                .flatten()
+                .map(|value| {
+                    value
+                        .parse::<IpNet>()
+                        .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+                        .map_err(|error| ConfigError::InvalidValue {
+                            name,
+                            provided: value.clone(),
+                            error: error.into(),
+                        })
+                })
+                .collect::<config::Result<Vec<_>>>()
+        };
+
+        Ok((
+            parse(
+                "agent.iptables_exclude_destinations",
+                self.iptables_exclude_destinations.as_ref(),
+            )?,
+            parse(
+                "agent.iptables_exclude_sources",
+                self.iptables_exclude_sources.as_ref(),
+            )?,
+        ))
+    }
 }
 
 impl AgentFileConfig {
@@ -681,6 +1088,23 @@ pub struct AgentDnsConfig {
     /// Specifies the number of DNS resolution attempts the agent will make before failing.
     /// Setting this too high may cause the internal proxy to time out and exit.
     pub attempts: Option<u32>,
+
+    /// ### agent.dns.search {#agent-dns-search}
+    ///
+    /// Replaces the search list from the target's `/etc/resolv.conf`, which is used to resolve
+    /// relative names like `redis`. The nameservers and the `ndots` option still come from the
+    /// target. An empty list disables the search.
+    ///
+    /// ```json
+    /// {
+    ///   "agent": {
+    ///     "dns": {
+    ///       "search": ["staging.svc.cluster.local", "svc.cluster.local"]
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub search: Option<Vec<String>>,
 }
 
 #[cfg(test)]
@@ -740,4 +1164,42 @@ mod tests {
         assert_eq!(agent.communication_timeout, communication_timeout.1);
         assert_eq!(agent.startup_timeout, startup_timeout.1);
     }
+
+    #[rstest]
+    #[case::http("http://proxy.internal:3128", true)]
+    #[case::https("https://proxy.internal", true)]
+    #[case::socks5("socks5://127.0.0.1:1080", true)]
+    #[case::socks5h("socks5h://proxy.internal:1080", true)]
+    #[case::unsupported_scheme("ftp://proxy.internal:21", false)]
+    #[case::no_scheme("proxy.internal:3128", false)]
+    #[case::no_host("http://:3128", false)]
+    #[case::garbage("not a url", false)]
+    fn connection_proxy(#[case] proxy: &str, #[case] valid: bool) {
+        let mut cfg_context = ConfigContext::default()
+            .override_env("MIRRORD_AGENT_CONNECTION_PROXY", proxy)
+            .strict_env(true);
+        let agent = AgentFileConfig::default()
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        assert_eq!(agent.connection_proxy.as_deref(), Some(proxy));
+        assert_eq!(agent.connection_proxy_uri().is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case::tcp(r#""0.0.0.0:9000""#, true)]
+    #[case::tcp_ipv6(r#""[::]:9000""#, true)]
+    #[case::unix(r#""unix:///var/run/metrics.sock""#, true)]
+    #[case::unix_relative(r#""unix://metrics.sock""#, false)]
+    #[case::hostname(r#""localhost:9000""#, false)]
+    #[case::detailed(r#"{ "address": "0.0.0.0:9000" }"#, true)]
+    #[case::detailed_missing_token(
+        r#"{ "address": "0.0.0.0:9000", "token_env": "MIRRORD_TEST_METRICS_TOKEN_NOT_SET" }"#,
+        false
+    )]
+    fn metrics(#[case] metrics: &str, #[case] valid: bool) {
+        let metrics = serde_json::from_str::<AgentMetricsConfig>(metrics).unwrap();
+
+        assert_eq!(metrics.verify().is_ok(), valid);
+    }
 }
@@ -162,6 +162,10 @@ pub trait MirrordConfig {
     /// The resulting struct you plan on using in the rest of your code
     type Generated;
 
+    /// <!--${internal}-->
+    /// Environment variables that override fields of this config, as `(field, variable)` pairs.
+    const ENV_OVERRIDES: &'static [(&'static str, &'static str)] = &[];
+
     /// <!--${internal}-->
     /// Load configuration from all sources and output as `Self::Generated`
     /// Pass reference to list of warnings which callee can add warnings into.
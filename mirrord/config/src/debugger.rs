@@ -0,0 +1,52 @@
+use mirrord_analytics::CollectAnalytics;
+use mirrord_config_derive::MirrordConfig;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::config::source::MirrordConfigSource;
+
+/// Makes mirrord pause the application until a debugger is ready to attach to it.
+///
+/// Once the layer is loaded into the application and connected to mirrord, it waits for a
+/// connection on the given local [`port`](#wait_for_debugger-port) before letting the
+/// application's `main` run. Have your IDE (or any other tool) connect to this port once the
+/// debugger is ready, e.g. `nc -z 127.0.0.1 7777`.
+///
+/// Only the first process loaded with mirrord waits, its child processes start right away.
+///
+/// ```json
+/// {
+///   "wait_for_debugger": {
+///     "port": 7777,
+///     "timeout": 60
+///   }
+/// }
+/// ```
+#[derive(MirrordConfig, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[config(map_to = "WaitForDebuggerFileConfig", derive = "JsonSchema")]
+#[cfg_attr(test, config(derive = "PartialEq, Eq"))]
+pub struct WaitForDebuggerConfig {
+    /// ### wait_for_debugger.port {#wait_for_debugger-port}
+    ///
+    /// Local TCP port on which mirrord listens, waiting for a connection that signals the
+    /// debugger is ready.
+    ///
+    /// When not set, the application starts right away.
+    #[config(env = "MIRRORD_WAIT_FOR_DEBUGGER_PORT")]
+    pub port: Option<u16>,
+
+    /// ### wait_for_debugger.timeout {#wait_for_debugger-timeout}
+    ///
+    /// How long (in seconds) mirrord waits for the connection before letting the application
+    /// start anyway.
+    ///
+    /// Defaults to `60` seconds.
+    #[config(env = "MIRRORD_WAIT_FOR_DEBUGGER_TIMEOUT", default = 60)]
+    pub timeout: u64,
+}
+
+impl CollectAnalytics for &WaitForDebuggerConfig {
+    fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
+        analytics.add("enabled", self.port.is_some());
+    }
+}
@@ -0,0 +1,535 @@
+//! Documentation lookup for config fields, used by `mirrord config docs <path>`.
+//!
+//! The docs come from the JSON schema of [`LayerFileConfig`], generated from the config structs'
+//! doc comments. Environment variables that override config fields come from
+//! [`MirrordConfig::ENV_OVERRIDES`], and from [`MANUAL_ENV_OVERRIDES`] for configs that are not
+//! generated with `#[derive(MirrordConfig)]`.
+
+use std::fmt;
+
+use schemars::schema::{InstanceType, RootSchema, Schema, SchemaObject, SingleOrVec};
+use thiserror::Error;
+
+use crate::{
+    LayerFileConfig,
+    agent::AgentFileConfig,
+    config::MirrordConfig,
+    debugger::WaitForDebuggerFileConfig,
+    experimental::ExperimentalFileConfig,
+    feature::{
+        env::EnvFileConfig,
+        fs::AdvancedFsUserConfig,
+        network::{
+            NetworkFileConfig, dns::DnsFileConfig, incoming::http_filter::HttpFilterFileConfig,
+            outgoing::OutgoingFileConfig,
+        },
+        preview::PreviewFileConfig,
+    },
+};
+
+/// Name of the schema definition of the root config.
+const ROOT_DEFINITION: &str = "LayerFileConfig";
+
+/// How deep we go into the schema when collecting paths for suggestions.
+const MAX_SUGGESTION_DEPTH: usize = 8;
+
+/// How many suggestions we return for an unknown path.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Environment variables read by hand-written [`MirrordConfig`] implementations, as
+/// `(definition, field, variable)`.
+const MANUAL_ENV_OVERRIDES: &[(&str, &str, &str)] = &[
+    ("TargetFileConfig", "path", "MIRRORD_IMPERSONATED_TARGET"),
+    ("TargetFileConfig", "namespace", "MIRRORD_TARGET_NAMESPACE"),
+    ("AgentFileConfig", "image", "MIRRORD_AGENT_IMAGE"),
+    (
+        "IncomingAdvancedFileConfig",
+        "mode",
+        "MIRRORD_AGENT_TCP_STEAL_TRAFFIC",
+    ),
+    (
+        "IncomingAdvancedFileConfig",
+        "on_concurrent_steal",
+        "MIRRORD_OPERATOR_ON_CONCURRENT_STEAL",
+    ),
+    (
+        "HttpFilterFileConfig",
+        "header_filter",
+        "MIRRORD_HTTP_HEADER_FILTER",
+    ),
+    (
+        "HttpFilterFileConfig",
+        "path_filter",
+        "MIRRORD_HTTP_PATH_FILTER",
+    ),
+    (
+        "HttpFilterFileConfig",
+        "method_filter",
+        "MIRRORD_HTTP_METHOD_FILTER",
+    ),
+    (
+        "HttpFilterFileConfig",
+        "header_filter_jq",
+        "MIRRORD_HTTP_HEADER_FILTER_JQ",
+    ),
+    ("HttpFilterFileConfig", "ports", "MIRRORD_HTTP_FILTER_PORTS"),
+    ("OutgoingFileConfig", "tcp", "MIRRORD_TCP_OUTGOING"),
+    ("OutgoingFileConfig", "udp", "MIRRORD_UDP_OUTGOING"),
+    (
+        "OutgoingFileConfig",
+        "unix_streams",
+        "MIRRORD_OUTGOING_REMOTE_UNIX_STREAMS",
+    ),
+    ("DnsFileConfig", "enabled", "MIRRORD_REMOTE_DNS"),
+    ("AdvancedFsUserConfig", "mode", "MIRRORD_FILE_MODE"),
+    (
+        "AdvancedFsUserConfig",
+        "read_write",
+        "MIRRORD_FILE_READ_WRITE_PATTERN",
+    ),
+    (
+        "AdvancedFsUserConfig",
+        "read_only",
+        "MIRRORD_FILE_READ_ONLY_PATTERN",
+    ),
+    (
+        "AdvancedFsUserConfig",
+        "local",
+        "MIRRORD_FILE_LOCAL_PATTERN",
+    ),
+];
+
+/// Error that can occur when looking up docs of a config field.
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum ConfigDocsError {
+    #[error("`{path}` is not a mirrord config field{}", did_you_mean(.suggestions))]
+    UnknownField {
+        path: String,
+        suggestions: Vec<String>,
+    },
+}
+
+fn did_you_mean(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [suggestion] => format!(", did you mean `{suggestion}`?"),
+        suggestions => format!(
+            ", did you mean one of: {}?",
+            suggestions
+                .iter()
+                .map(|suggestion| format!("`{suggestion}`"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Documentation of a single config field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigFieldDocs {
+    /// Dotted path of the field, e.g. `agent.ttl`.
+    pub path: String,
+    /// Doc comment of the field.
+    pub description: Option<String>,
+    /// Values or types accepted by the field, e.g. `"steal"` or `boolean`.
+    pub accepted_values: Vec<String>,
+    /// Default value, as stated in the field's doc comment.
+    pub default: Option<String>,
+    /// Environment variable that overrides the field.
+    pub env: Option<&'static str>,
+}
+
+impl fmt::Display for ConfigFieldDocs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.path)?;
+
+        if let Some(description) = &self.description {
+            writeln!(f)?;
+            writeln!(f, "{description}")?;
+        }
+
+        if !self.accepted_values.is_empty() || self.default.is_some() || self.env.is_some() {
+            writeln!(f)?;
+        }
+
+        if !self.accepted_values.is_empty() {
+            writeln!(f, "Accepted values: {}", self.accepted_values.join(", "))?;
+        }
+
+        if let Some(default) = &self.default {
+            writeln!(f, "Default: {default}")?;
+        }
+
+        if let Some(env) = self.env {
+            writeln!(f, "Environment variable: {env}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Looks up docs of config fields in the JSON schema of [`LayerFileConfig`].
+pub struct ConfigDocs {
+    schema: RootSchema,
+}
+
+impl Default for ConfigDocs {
+    fn default() -> Self {
+        Self {
+            schema: schemars::schema_for!(LayerFileConfig),
+        }
+    }
+}
+
+impl ConfigDocs {
+    /// Returns docs of the config field under the given dotted path, e.g.
+    /// `feature.network.incoming.on_concurrent_steal`.
+    pub fn field(&self, path: &str) -> Result<ConfigFieldDocs, ConfigDocsError> {
+        let unknown_field = || ConfigDocsError::UnknownField {
+            path: path.to_string(),
+            suggestions: self.suggestions(path),
+        };
+
+        let mut current = &self.schema.schema;
+        let mut definition = ROOT_DEFINITION;
+        let mut field = None;
+
+        for segment in path.split('.') {
+            let (property, parent) = self
+                .objects(current, definition)
+                .into_iter()
+                .find_map(|(object, parent)| {
+                    let property = object.object.as_ref()?.properties.get(segment)?;
+                    Some((property, parent))
+                })
+                .ok_or_else(unknown_field)?;
+
+            let Schema::Object(property) = property else {
+                return Err(unknown_field());
+            };
+
+            field = Some((parent, segment));
+            current = property;
+            definition = parent;
+        }
+
+        let Some((parent, name)) = field else {
+            return Err(unknown_field());
+        };
+
+        let objects = self.objects(current, parent);
+        let description = objects
+            .iter()
+            .find_map(|(object, _)| object.metadata.as_ref()?.description.as_deref())
+            .map(|description| {
+                description
+                    .replace("<!--${internal}-->", "")
+                    .trim()
+                    .to_string()
+            });
+        let default = description.as_deref().and_then(default_from_description);
+
+        Ok(ConfigFieldDocs {
+            path: path.to_string(),
+            description,
+            accepted_values: accepted_values(&objects),
+            default,
+            env: env_override(parent, name),
+        })
+    }
+
+    /// Returns the given schema, and all schemas it refers to (recursively), together with the
+    /// names of definitions they belong to.
+    fn objects<'a>(
+        &'a self,
+        schema: &'a SchemaObject,
+        definition: &'a str,
+    ) -> Vec<(&'a SchemaObject, &'a str)> {
+        let mut objects = Vec::new();
+        self.collect_objects(schema, definition, &mut objects);
+        objects
+    }
+
+    fn collect_objects<'a>(
+        &'a self,
+        schema: &'a SchemaObject,
+        definition: &'a str,
+        out: &mut Vec<(&'a SchemaObject, &'a str)>,
+    ) {
+        // Recursive definitions would make us loop forever.
+        if out.iter().any(|(object, _)| std::ptr::eq(*object, schema)) {
+            return;
+        }
+
+        out.push((schema, definition));
+
+        if let Some(name) = schema
+            .reference
+            .as_deref()
+            .and_then(|reference| reference.strip_prefix("#/definitions/"))
+            && let Some((name, Schema::Object(referenced))) =
+                self.schema.definitions.get_key_value(name)
+        {
+            self.collect_objects(referenced, name, out);
+        }
+
+        if let Some(subschemas) = &schema.subschemas {
+            let nested = [&subschemas.all_of, &subschemas.any_of, &subschemas.one_of];
+
+            for schema in nested.into_iter().flatten().flatten() {
+                if let Schema::Object(schema) = schema {
+                    self.collect_objects(schema, definition, out);
+                }
+            }
+        }
+    }
+
+    /// Collects dotted paths of all config fields, up to [`MAX_SUGGESTION_DEPTH`].
+    fn paths(&self, schema: &SchemaObject, definition: &str, prefix: &str, out: &mut Vec<String>) {
+        if prefix.split('.').count() > MAX_SUGGESTION_DEPTH {
+            return;
+        }
+
+        for (object, parent) in self.objects(schema, definition) {
+            let Some(validation) = &object.object else {
+                continue;
+            };
+
+            for (name, property) in &validation.properties {
+                let path = if prefix.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{prefix}.{name}")
+                };
+
+                if out.contains(&path) {
+                    continue;
+                }
+                out.push(path.clone());
+
+                if let Schema::Object(property) = property {
+                    self.paths(property, parent, &path, out);
+                }
+            }
+        }
+    }
+
+    /// Returns known paths that are similar to the given one.
+    fn suggestions(&self, path: &str) -> Vec<String> {
+        let mut paths = Vec::new();
+        self.paths(&self.schema.schema, ROOT_DEFINITION, "", &mut paths);
+
+        let last_segment = path.rsplit('.').next().unwrap_or(path);
+        let max_distance = std::cmp::max(2, path.len() / 4);
+
+        let mut suggestions = paths
+            .into_iter()
+            .filter_map(|candidate| {
+                let candidate_last = candidate.rsplit('.').next().unwrap_or(&candidate);
+                let distance = if candidate_last == last_segment {
+                    0
+                } else {
+                    levenshtein(path, &candidate)
+                };
+
+                (distance <= max_distance).then_some((distance, candidate))
+            })
+            .collect::<Vec<_>>();
+
+        suggestions.sort();
+        suggestions
+            .into_iter()
+            .take(MAX_SUGGESTIONS)
+            .map(|(_, candidate)| candidate)
+            .collect()
+    }
+}
+
+/// Lists enum values and types accepted by the given schemas.
+fn accepted_values(objects: &[(&SchemaObject, &str)]) -> Vec<String> {
+    let mut values = Vec::new();
+
+    for (object, _) in objects {
+        let found = if let Some(enum_values) = &object.enum_values {
+            enum_values.iter().map(ToString::to_string).collect()
+        } else if let Some(const_value) = &object.const_value {
+            vec![const_value.to_string()]
+        } else if object.object.is_some() {
+            vec!["object".to_string()]
+        } else {
+            match &object.instance_type {
+                Some(SingleOrVec::Single(instance_type)) => {
+                    instance_type_name(instance_type).into_iter().collect()
+                }
+                Some(SingleOrVec::Vec(instance_types)) => instance_types
+                    .iter()
+                    .filter_map(instance_type_name)
+                    .collect(),
+                None => Vec::new(),
+            }
+        };
+
+        for value in found {
+            if !values.contains(&value) {
+                values.push(value);
+            }
+        }
+    }
+
+    values
+}
+
+/// Returns the name of the given [`InstanceType`], skipping `null`, since every config field is
+/// optional.
+fn instance_type_name(instance_type: &InstanceType) -> Option<String> {
+    let name = match instance_type {
+        InstanceType::Null => return None,
+        InstanceType::Boolean => "boolean",
+        InstanceType::Object => "object",
+        InstanceType::Array => "array",
+        InstanceType::Number => "number",
+        InstanceType::String => "string",
+        InstanceType::Integer => "integer",
+    };
+
+    Some(name.to_string())
+}
+
+/// Extracts the default value from the `Defaults to ...` sentence in a field's doc comment.
+fn default_from_description(description: &str) -> Option<String> {
+    let (_, rest) = description.split_once("Defaults to ")?;
+    let line = rest.lines().next()?;
+    let default = line.split(". ").next()?.trim_end_matches('.').trim();
+
+    (!default.is_empty()).then(|| default.to_string())
+}
+
+/// Returns the environment variable that overrides the given field of the config with the given
+/// schema definition name.
+fn env_override(definition: &str, field: &str) -> Option<&'static str> {
+    macro_rules! overrides {
+        ($($config: ty),* $(,)?) => {
+            $(
+                if definition == stringify!($config) {
+                    <$config as MirrordConfig>::ENV_OVERRIDES
+                } else
+            )* {
+                &[]
+            }
+        };
+    }
+
+    let overrides: &[(&str, &str)] = overrides!(
+        LayerFileConfig,
+        AgentFileConfig,
+        NetworkFileConfig,
+        DnsFileConfig,
+        HttpFilterFileConfig,
+        OutgoingFileConfig,
+        PreviewFileConfig,
+        AdvancedFsUserConfig,
+        EnvFileConfig,
+        ExperimentalFileConfig,
+        WaitForDebuggerFileConfig,
+    );
+
+    overrides
+        .iter()
+        .find_map(|(name, env)| (*name == field).then_some(*env))
+        .or_else(|| {
+            MANUAL_ENV_OVERRIDES.iter().find_map(|(parent, name, env)| {
+                (*parent == definition && *name == field).then_some(*env)
+            })
+        })
+}
+
+/// Edit distance between two strings, used to suggest paths for typos.
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{ConfigDocs, ConfigDocsError};
+
+    #[test]
+    fn exact_lookup() {
+        let docs = ConfigDocs::default().field("agent.ttl").unwrap();
+
+        assert_eq!(docs.path, "agent.ttl");
+        assert!(
+            docs.description
+                .as_deref()
+                .unwrap()
+                .starts_with("Controls how long the agent pod persists")
+        );
+        assert_eq!(docs.accepted_values, vec!["integer"]);
+        assert_eq!(docs.default.as_deref(), Some("`1`"));
+        assert_eq!(docs.env, Some("MIRRORD_AGENT_TTL"));
+    }
+
+    #[test]
+    fn enum_values() {
+        let docs = ConfigDocs::default()
+            .field("feature.network.incoming.on_concurrent_steal")
+            .unwrap();
+
+        assert_eq!(
+            docs.accepted_values,
+            vec![r#""override""#, r#""continue""#, r#""abort""#]
+        );
+        assert_eq!(docs.env, Some("MIRRORD_OPERATOR_ON_CONCURRENT_STEAL"));
+    }
+
+    /// Variables read by hand-written config implementations are found as well.
+    #[rstest]
+    #[case::target_path("target.path", "MIRRORD_IMPERSONATED_TARGET")]
+    #[case::target_namespace("target.namespace", "MIRRORD_TARGET_NAMESPACE")]
+    #[case::incoming_mode("feature.network.incoming.mode", "MIRRORD_AGENT_TCP_STEAL_TRAFFIC")]
+    #[case::http_header_filter(
+        "feature.network.incoming.http_filter.header_filter",
+        "MIRRORD_HTTP_HEADER_FILTER"
+    )]
+    #[case::outgoing_tcp("feature.network.outgoing.tcp", "MIRRORD_TCP_OUTGOING")]
+    #[case::dns_enabled("feature.network.dns.enabled", "MIRRORD_REMOTE_DNS")]
+    #[case::fs_mode("feature.fs.mode", "MIRRORD_FILE_MODE")]
+    #[case::fs_local("feature.fs.local", "MIRRORD_FILE_LOCAL_PATTERN")]
+    fn manual_env_overrides(#[case] path: &str, #[case] expected: &str) {
+        let docs = ConfigDocs::default().field(path).unwrap();
+        assert_eq!(docs.env, Some(expected));
+    }
+
+    #[rstest]
+    #[case::typo("agent.tll", "agent.ttl")]
+    #[case::missing_parent("on_concurrent_steal", "feature.network.incoming.on_concurrent_steal")]
+    #[case::wrong_parent("feature.network.ttl", "agent.ttl")]
+    fn suggestions(#[case] path: &str, #[case] expected: &str) {
+        let Err(ConfigDocsError::UnknownField { suggestions, .. }) =
+            ConfigDocs::default().field(path)
+        else {
+            panic!("{path} should not be found");
+        };
+
+        assert!(
+            suggestions.iter().any(|suggestion| suggestion == expected),
+            "{expected} not in {suggestions:?}"
+        );
+    }
+}
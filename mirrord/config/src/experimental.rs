@@ -5,7 +5,7 @@ use mirrord_config_derive::MirrordConfig;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::config::source::MirrordConfigSource;
+use crate::config::{ConfigContext, ConfigError, source::MirrordConfigSource};
 
 /// mirrord Experimental features.
 /// This shouldn't be used unless someone from MetalBear/mirrord tells you to.
@@ -16,13 +16,13 @@ pub struct ExperimentalConfig {
     /// ### _experimental_ tcp_ping4_mock {#experimental-tcp_ping4_mock}
     ///
     /// <https://github.com/metalbear-co/mirrord/issues/2421#issuecomment-2093200904>
-    #[config(default = true)]
+    #[config(env = "MIRRORD_EXPERIMENTAL_TCP_PING4_MOCK", default = true)]
     pub tcp_ping4_mock: bool,
 
     /// ### _experimental_ trust_any_certificate {#experimental-trust_any_certificate}
     ///
     /// Enables trusting any certificate on macOS, useful for <https://github.com/golang/go/issues/51991#issuecomment-2059588252>
-    #[config(default = false)]
+    #[config(env = "MIRRORD_EXPERIMENTAL_TRUST_ANY_CERTIFICATE", default = false)]
     pub trust_any_certificate: bool,
 
     /// ### _experimental_ enable_exec_hooks_linux {#experimental-enable_exec_hooks_linux}
@@ -30,13 +30,13 @@ pub struct ExperimentalConfig {
     /// Enables exec hooks on Linux. Enable Linux hooks can fix issues when the application
     /// shares sockets with child commands (e.g Python web servers with reload),
     /// but the feature is not stable and may cause other issues.
-    #[config(default = true)]
+    #[config(env = "MIRRORD_EXPERIMENTAL_ENABLE_EXEC_HOOKS_LINUX", default = true)]
     pub enable_exec_hooks_linux: bool,
 
     /// ### _experimental_ hide_ipv6_interfaces {#experimental-hide_ipv6_interfaces}
     ///
     /// Enables `getifaddrs` hook that removes IPv6 interfaces from the list returned by libc.
-    #[config(default = false)]
+    #[config(env = "MIRRORD_EXPERIMENTAL_HIDE_IPV6_INTERFACES", default = false)]
     pub hide_ipv6_interfaces: bool,
 
     /// ### _experimental_ disable_reuseaddr {#experimental-disable_reuseaddr}
@@ -46,13 +46,13 @@ pub struct ExperimentalConfig {
     /// correctly. This probably should be on by default but we want to gradually roll it out.
     /// <https://github.com/metalbear-co/mirrord/issues/2819>
     /// This option applies only on macOS.
-    #[config(default = false)]
+    #[config(env = "MIRRORD_EXPERIMENTAL_DISABLE_REUSEADDR", default = false)]
     pub disable_reuseaddr: bool,
 
     /// ### _experimental_ use_dev_null {#experimental-use_dev_null}
     ///
     /// Uses /dev/null for creating local fake files (should be better than using /tmp)
-    #[config(default = true)]
+    #[config(env = "MIRRORD_EXPERIMENTAL_USE_DEV_NULL", default = true)]
     pub use_dev_null: bool,
 
     /// ### _experimental_ idle_local_http_connection_timeout {#experimental-idle_local_http_connection_timeout}
@@ -70,13 +70,19 @@ pub struct ExperimentalConfig {
     /// the request is delivered).
     ///
     /// Defaults to 3000ms.
-    #[config(default = 3000)]
+    #[config(
+        env = "MIRRORD_EXPERIMENTAL_IDLE_LOCAL_HTTP_CONNECTION_TIMEOUT",
+        default = 3000
+    )]
     pub idle_local_http_connection_timeout: u64,
 
     /// ### _experimental_ ignore_system_proxy_config {#experimental-ignore_system_proxy_config}
     ///
     /// Disables any system wide proxy configuration for affecting the running application.
-    #[config(default = false)]
+    #[config(
+        env = "MIRRORD_EXPERIMENTAL_IGNORE_SYSTEM_PROXY_CONFIG",
+        default = false
+    )]
     pub ignore_system_proxy_config: bool,
 
     /// ### _experimental_ browser_extension_config {#experimental-browser_extension_config}
@@ -84,14 +90,14 @@ pub struct ExperimentalConfig {
     /// mirrord will open a URL for initiating mirrord browser extension to
     /// automatically inject HTTP header that matches the HTTP filter configured in
     /// `feature.network.incoming.http_filter.header_filter`.
-    #[config(default = false)]
+    #[config(env = "MIRRORD_EXPERIMENTAL_BROWSER_EXTENSION_CONFIG", default = false)]
     pub browser_extension_config: bool,
 
     /// ### _experimental_ sip_log_destination {#experimental-sip_log_destination}
     ///
     /// Writes basic fork-safe SIP patching logs to a destination file.
     /// Useful for seeing the state of SIP when `stdout` may be affected by another process.
-    #[config(default = None)]
+    #[config(env = "MIRRORD_EXPERIMENTAL_SIP_LOG_DESTINATION")]
     pub sip_log_destination: Option<PathBuf>,
 
     /// ### _experimental_ hook_rename {#experimental-hook_rename}
@@ -144,7 +150,7 @@ pub struct ExperimentalConfig {
     /// Enables better support for outgoing connections using non-blocking TCP sockets.
     ///
     /// Defaults to `false`.
-    #[config(default = false)]
+    #[config(env = "MIRRORD_EXPERIMENTAL_NON_BLOCKING_TCP_CONNECT", default = false)]
     pub non_blocking_tcp_connect: bool,
 
     /// ### _experimental_ dlopen_cgo {#experimental-dlopen_cgo}
@@ -152,7 +158,7 @@ pub struct ExperimentalConfig {
     /// Useful when the user's application loads a c-shared golang library dynamically.
     ///
     /// Defaults to `false`.
-    #[config(default = false)]
+    #[config(env = "MIRRORD_EXPERIMENTAL_DLOPEN_CGO", default = false)]
     pub dlopen_cgo: bool,
 
     /// ### _experimental_ latency {#experimental-latency}
@@ -161,10 +167,140 @@ pub struct ExperimentalConfig {
     #[config(nested)]
     pub latency: LatencyConfig,
 
+    /// ### _experimental_ udp_batch_window {#experimental-udp_batch_window}
+    ///
+    /// Time window (in milliseconds) in which outgoing UDP datagrams sent to the same remote
+    /// address are coalesced into a single message to the mirrord-agent.
+    ///
+    /// The agent still sends every datagram separately and in order, this only reduces the
+    /// number of messages exchanged with the agent when the application emits many small
+    /// datagrams (e.g. metrics sent to a statsd server).
+    ///
+    /// Set to 0 to disable batching (every datagram is forwarded as soon as it is sent).
+    ///
+    /// Defaults to 5ms.
+    #[config(env = "MIRRORD_EXPERIMENTAL_UDP_BATCH_WINDOW", default = 5)]
+    pub udp_batch_window: u64,
+
     /// ### _experimental_ applev {#experimental-applev}
     ///
     /// Configuration for inspecting and modifying apple variables. macOS only.
     pub applev: Option<AppleVariablesConfig>,
+
+    /// ### _experimental_ reconnect {#experimental-reconnect}
+    ///
+    /// Restores the state of remote files after the connection to the mirrord-agent is
+    /// re-established.
+    ///
+    /// Readonly remote files are re-opened after the reconnect, and reads that were interrupted
+    /// by the connection drop are resumed from the last acknowledged offset, instead of failing.
+    /// Directories opened from them are re-opened as well, and their iteration continues from
+    /// the last returned entry (requires an agent that supports it).
+    /// Has no effect when `feature.fs.readonly_file_buffer` is set to 0.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_EXPERIMENTAL_RECONNECT", default = false)]
+    pub reconnect: bool,
+
+    /// ### _experimental_ remote_time_offset {#experimental-remote_time_offset}
+    ///
+    /// Shifts the wall clock of the local application (`CLOCK_REALTIME`), so that it matches the
+    /// clock of the target's node. Useful when the application validates timestamps (e.g. JWT
+    /// expiration) against the cluster time, and the local clock is skewed.
+    ///
+    /// The offset is computed from the agent's clock at startup, and refreshed every minute. It
+    /// is accurate up to half of the round trip time to the agent. Only `clock_gettime` and
+    /// `gettimeofday` calls that go through libc are affected, monotonic clocks are not.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_EXPERIMENTAL_REMOTE_TIME_OFFSET", default = false)]
+    pub remote_time_offset: bool,
+
+    /// ### _experimental_ fault_injection {#experimental-fault_injection}
+    ///
+    /// Injects synthetic latency and failures into remote operations, to test how the
+    /// application behaves when its remote dependencies are slow or failing.
+    ///
+    /// The faults are injected by the internal proxy, the remote operations that fail are not
+    /// sent to the mirrord-agent at all. **For testing only**, never enable it in a regular
+    /// session.
+    ///
+    /// ```json
+    /// {
+    ///   "experimental": {
+    ///     "fault_injection": {
+    ///       "outgoing_connect": { "delay": 200, "failure_percent": 10 },
+    ///       "dns": { "failure_percent": 50 }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    #[config(nested)]
+    pub fault_injection: FaultInjectionConfig,
+
+    /// ### _experimental_ agent_ttl_extension {#experimental-agent_ttl_extension}
+    ///
+    /// Keeps the mirrord-agent alive for this many seconds after the internal proxy disconnects,
+    /// so that the session survives intermittent reconnects.
+    ///
+    /// The agent never exits while clients are connected. With this set, the internal proxy
+    /// also periodically asks the agent to extend its idle TTL, so that the agent waits for
+    /// the internal proxy to reconnect, even if its own idle TTL is shorter. Requires an agent
+    /// that supports it, ignored otherwise.
+    ///
+    /// Set to 0 to disable.
+    ///
+    /// Defaults to 0.
+    #[config(env = "MIRRORD_EXPERIMENTAL_AGENT_TTL_EXTENSION", default = 0)]
+    pub agent_ttl_extension: u64,
+
+    /// ### _experimental_ hostname_truncation {#experimental-hostname_truncation}
+    ///
+    /// Controls what `gethostname` does when the remote hostname does not fit in the buffer
+    /// provided by the application. libc implementations differ here, and so do the
+    /// expectations of the applications.
+    ///
+    /// - `"error"`: fails with `EINVAL`, the buffer contains the start of the hostname.
+    /// - `"truncate"`: succeeds, the buffer contains the start of the hostname, terminated with a
+    ///   null byte.
+    ///
+    /// Defaults to `"error"`.
+    #[config(default)]
+    pub hostname_truncation: HostnameTruncation,
+
+    /// ### _experimental_ keep_objc_fork_safety {#experimental-keep_objc_fork_safety}
+    ///
+    /// macOS only. By default, mirrord sets `OBJC_DISABLE_INITIALIZE_FORK_SAFETY=YES` for the
+    /// application, because the Objective-C runtime otherwise crashes applications that fork
+    /// while another thread is initializing a class (see
+    /// <https://github.com/metalbear-co/mirrord/issues/1745>).
+    ///
+    /// Set this to keep the fork safety check active, for applications that rely on it. Forking
+    /// applications may then crash under mirrord.
+    ///
+    /// Defaults to `false`.
+    #[config(env = "MIRRORD_EXPERIMENTAL_KEEP_OBJC_FORK_SAFETY", default = false)]
+    pub keep_objc_fork_safety: bool,
+
+    /// ### _experimental_ injection_env_var {#experimental-injection_env_var}
+    ///
+    /// Name of the environment variable used to load the mirrord layer into the application.
+    /// Useful with custom loaders that read a different variable than the system one.
+    ///
+    /// Defaults to `LD_PRELOAD` on Linux and `DYLD_INSERT_LIBRARIES` on macOS. With
+    /// `mirrord container`, defaults to `LD_PRELOAD`, as the container runs Linux.
+    #[config(env = "MIRRORD_INJECTION_ENV_VAR")]
+    pub injection_env_var: Option<String>,
+
+    /// ### _experimental_ injection_env_append {#experimental-injection_env_append}
+    ///
+    /// Whether the mirrord layer is appended to the existing value of the injection variable
+    /// (see [`injection_env_var`](#experimental-injection_env_var)), keeping the libraries
+    /// already listed there. When `false`, the existing value is replaced.
+    ///
+    /// Defaults to `true`.
+    #[config(env = "MIRRORD_INJECTION_ENV_APPEND", default = true)]
+    pub injection_env_append: bool,
 }
 
 impl CollectAnalytics for &ExperimentalConfig {
@@ -188,10 +324,34 @@ impl CollectAnalytics for &ExperimentalConfig {
         analytics.add("dlopen_cgo", self.dlopen_cgo);
         analytics.add("latency_transmit_delay", self.latency.transmit_delay);
         analytics.add("latency_receive_delay", self.latency.receive_delay);
+        analytics.add("udp_batch_window", self.udp_batch_window);
         analytics.add("applev", self.applev.is_some());
+        analytics.add("reconnect", self.reconnect);
+        analytics.add("remote_time_offset", self.remote_time_offset);
+        analytics.add("fault_injection", self.fault_injection.is_enabled());
+        analytics.add("agent_ttl_extension", self.agent_ttl_extension);
+        analytics.add(
+            "hostname_truncation",
+            self.hostname_truncation == HostnameTruncation::Truncate,
+        );
+        analytics.add("keep_objc_fork_safety", self.keep_objc_fork_safety);
+        analytics.add("injection_env_var", self.injection_env_var.is_some());
+        analytics.add("injection_env_append", self.injection_env_append);
     }
 }
 
+/// What `gethostname` does when the hostname does not fit in the buffer, see
+/// [`ExperimentalConfig::hostname_truncation`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HostnameTruncation {
+    /// Fail with `EINVAL`.
+    #[default]
+    Error,
+    /// Truncate the hostname and succeed.
+    Truncate,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, JsonSchema, Default)]
 pub struct AppleVariablesConfig {}
 
@@ -217,3 +377,181 @@ pub struct LatencyConfig {
     #[config(default = 0)]
     pub receive_delay: u64,
 }
+
+/// Configuration for injecting synthetic faults into remote operations.
+/// Useful for testing application behavior when remote dependencies are slow or failing.
+#[derive(MirrordConfig, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[config(map_to = "FaultInjectionFileConfig", derive = "JsonSchema")]
+#[cfg_attr(test, config(derive = "PartialEq, Eq"))]
+pub struct FaultInjectionConfig {
+    /// ### _experimental_ fault_injection.outgoing_connect {#experimental-fault_injection-outgoing_connect}
+    ///
+    /// Faults injected into outgoing connections made from the remote target.
+    ///
+    /// Failed connections return `ECONNREFUSED`.
+    #[config(nested)]
+    pub outgoing_connect: FaultConfig,
+
+    /// ### _experimental_ fault_injection.file_read {#experimental-fault_injection-file_read}
+    ///
+    /// Faults injected into reads of remote files.
+    ///
+    /// Failed reads return `EIO`.
+    #[config(nested)]
+    pub file_read: FaultConfig,
+
+    /// ### _experimental_ fault_injection.dns {#experimental-fault_injection-dns}
+    ///
+    /// Faults injected into remote DNS lookups.
+    ///
+    /// Failed lookups return a DNS timeout.
+    #[config(nested)]
+    pub dns: FaultConfig,
+}
+
+impl FaultInjectionConfig {
+    /// Returns the faults configured for each operation, along with the name of the config field.
+    fn faults(&self) -> [(&'static str, &FaultConfig); 3] {
+        [
+            (
+                "experimental.fault_injection.outgoing_connect.failure_percent",
+                &self.outgoing_connect,
+            ),
+            (
+                "experimental.fault_injection.file_read.failure_percent",
+                &self.file_read,
+            ),
+            (
+                "experimental.fault_injection.dns.failure_percent",
+                &self.dns,
+            ),
+        ]
+    }
+
+    /// Returns whether any fault is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.faults().iter().any(|(_, fault)| fault.is_enabled())
+    }
+
+    pub fn verify(&self, context: &mut ConfigContext) -> Result<(), ConfigError> {
+        for (name, fault) in self.faults() {
+            if fault.failure_percent > 100 {
+                return Err(ConfigError::InvalidValue {
+                    name,
+                    provided: fault.failure_percent.to_string(),
+                    error: "the failure percentage must be between 0 and 100".into(),
+                });
+            }
+        }
+
+        if self.is_enabled() {
+            context.add_warning(
+                "experimental.fault_injection is enabled, mirrord will delay or fail some \
+                remote operations on purpose. Use it for testing only."
+                    .to_string(),
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Synthetic faults injected into one type of remote operation.
+#[derive(MirrordConfig, Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[config(map_to = "FaultFileConfig", derive = "JsonSchema")]
+#[cfg_attr(test, config(derive = "PartialEq, Eq"))]
+pub struct FaultConfig {
+    /// #### _experimental_ fault_injection.*.delay {#experimental-fault_injection-delay}
+    ///
+    /// Delay in milliseconds added to every operation.
+    ///
+    /// Defaults to `0` (no delay).
+    #[config(default = 0)]
+    pub delay: u64,
+
+    /// #### _experimental_ fault_injection.*.failure_percent {#experimental-fault_injection-failure_percent}
+    ///
+    /// Percentage (from 0 to 100) of operations that fail. The failure is returned after the
+    /// `delay`.
+    ///
+    /// Defaults to `0` (no failures).
+    #[config(default = 0)]
+    pub failure_percent: u8,
+}
+
+impl FaultConfig {
+    /// Returns whether this config delays or fails any operation.
+    pub fn is_enabled(&self) -> bool {
+        self.delay > 0 || self.failure_percent > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::config::MirrordConfig;
+
+    #[rstest]
+    #[case::bool("MIRRORD_EXPERIMENTAL_HIDE_IPV6_INTERFACES", "true", true)]
+    #[case::number("MIRRORD_EXPERIMENTAL_UDP_BATCH_WINDOW", "0", true)]
+    #[case::path("MIRRORD_EXPERIMENTAL_SIP_LOG_DESTINATION", "/tmp/sip.log", true)]
+    #[case::bad_bool("MIRRORD_EXPERIMENTAL_HIDE_IPV6_INTERFACES", "yes", false)]
+    #[case::bad_number("MIRRORD_EXPERIMENTAL_UDP_BATCH_WINDOW", "-1", false)]
+    fn from_env(#[case] env: &str, #[case] value: &str, #[case] valid: bool) {
+        let mut context = ConfigContext::default()
+            .override_env(env, value)
+            .strict_env(true);
+        let config = ExperimentalFileConfig::default().generate_config(&mut context);
+
+        assert_eq!(config.is_ok(), valid, "{config:?}");
+    }
+
+    /// Env overrides the value from the config file.
+    #[test]
+    fn env_overrides_file() {
+        let mut context = ConfigContext::default()
+            .override_env("MIRRORD_EXPERIMENTAL_HIDE_IPV6_INTERFACES", "true")
+            .override_env("MIRRORD_EXPERIMENTAL_TCP_PING4_MOCK", "false")
+            .strict_env(true);
+        let config = ExperimentalFileConfig {
+            hide_ipv6_interfaces: Some(false),
+            ..Default::default()
+        }
+        .generate_config(&mut context)
+        .unwrap();
+
+        assert!(config.hide_ipv6_interfaces);
+        assert!(!config.tcp_ping4_mock);
+        assert!(config.enable_exec_hooks_linux);
+    }
+
+    #[rstest]
+    #[case::disabled(0, 0, false)]
+    #[case::delay_only(100, 0, false)]
+    #[case::always_fail(0, 100, false)]
+    #[case::invalid_percent(0, 101, true)]
+    fn verify_fault_injection(
+        #[case] delay: u64,
+        #[case] failure_percent: u8,
+        #[case] fails: bool,
+    ) {
+        let fault = FaultConfig {
+            delay,
+            failure_percent,
+        };
+        let config = FaultInjectionConfig {
+            outgoing_connect: fault.clone(),
+            file_read: FaultConfig {
+                delay: 0,
+                failure_percent: 0,
+            },
+            dns: fault,
+        };
+
+        let mut context = ConfigContext::default();
+        assert_eq!(config.verify(&mut context).is_err(), fails);
+        assert_eq!(context.has_warnings(), !fails && config.is_enabled(),);
+    }
+}
@@ -74,6 +74,27 @@ pub struct ExternalProxyConfig {
     #[config(default = 5)]
     pub idle_timeout: u64,
 
+    /// ### external_proxy.outage_timeout {#external_proxy-outage_timeout}
+    ///
+    /// How much time (in seconds) the session can survive the external proxy being down.
+    ///
+    /// When the external proxy exits unexpectedly (e.g. it was killed by the OOM killer), mirrord
+    /// starts it again on the same address, and the internal proxy sidecar keeps reconnecting to
+    /// it (restoring its port subscriptions) until this time passes, instead of giving up after
+    /// [`internal_proxy.reconnect.max_attempts`](#internal_proxy-reconnect-max_attempts).
+    ///
+    /// Set to `0` to fail the session as soon as the external proxy is gone.
+    ///
+    /// ```json
+    /// {
+    ///   "external_proxy": {
+    ///     "outage_timeout": 120
+    ///   }
+    /// }
+    /// ```
+    #[config(default = 60)]
+    pub outage_timeout: u64,
+
     /// ### external_proxy.log_level {#external_proxy-log_level}
     ///
     /// Set the log level for the external proxy.
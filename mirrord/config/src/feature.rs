@@ -4,7 +4,10 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use self::{
-    copy_target::CopyTargetConfig, env::EnvConfig, fs::FsConfig, network::NetworkConfig,
+    copy_target::CopyTargetConfig,
+    env::EnvConfig,
+    fs::{FsConfig, FsModeConfig},
+    network::{NetworkConfig, incoming::IncomingMode},
     preview::PreviewConfig,
 };
 use crate::{
@@ -13,6 +16,7 @@ use crate::{
         database_branches::DatabaseBranchesConfig, magic::MagicConfig,
         split_queues::SplitQueuesConfig,
     },
+    util::VecOrSingle,
 };
 
 pub mod copy_target;
@@ -140,6 +144,46 @@ pub struct FeatureConfig {
     pub preview: PreviewConfig,
 }
 
+impl FeatureConfig {
+    /// Names of the features that can be listed in
+    /// [`LayerConfig::only_features`](crate::LayerConfig::only_features).
+    pub const TOGGLEABLE: [&str; 6] = ["env", "fs", "dns", "incoming", "outgoing", "hostname"];
+
+    /// Turns off every feature from [`Self::TOGGLEABLE`] that is not in `enabled`, overriding
+    /// its detailed configuration.
+    pub fn keep_only<S: AsRef<str>>(&mut self, enabled: &[S]) {
+        let keep = |feature: &str| enabled.iter().any(|name| name.as_ref() == feature);
+
+        if !keep("env") {
+            self.env.include = None;
+            self.env.exclude = Some(VecOrSingle::Single("*".to_owned()));
+            self.env.load_from_process = None;
+            self.env.from_kube_resources = None;
+        }
+
+        if !keep("fs") {
+            self.fs.mode = FsModeConfig::Local;
+        }
+
+        if !keep("dns") {
+            self.network.dns.enabled = false;
+        }
+
+        if !keep("incoming") {
+            self.network.incoming.mode = IncomingMode::Off;
+        }
+
+        if !keep("outgoing") {
+            self.network.outgoing.tcp = false;
+            self.network.outgoing.udp = false;
+        }
+
+        if !keep("hostname") {
+            self.hostname = false;
+        }
+    }
+}
+
 impl CollectAnalytics for &FeatureConfig {
     fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
         analytics.add("env", &self.env);
@@ -9,8 +9,9 @@
 use mirrord_analytics::CollectAnalytics;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::config::{ConfigContext, FromMirrordConfig, MirrordConfig, Result};
+use crate::config::{ConfigContext, ConfigError, FromMirrordConfig, MirrordConfig, Result};
 
 /// ## feature.copy_target {#copy_target}
 ///
@@ -39,7 +40,10 @@ use crate::config::{ConfigContext, FromMirrordConfig, MirrordConfig, Result};
 ///       "enabled": true,
 ///       "scale_down": true,
 ///       "exclude_containers": ["my-container"],
-///       "exclude_init_containers": ["my-init-container"]
+///       "exclude_init_containers": ["my-init-container"],
+///       "patch": {
+///         "containers": [{ "name": "my-sidecar", "$patch": "delete" }]
+///       }
 ///     }
 ///   }
 /// }
@@ -61,6 +65,8 @@ pub enum CopyTargetFileConfig {
         exclude_containers: Option<Vec<String>>,
         /// List of init containers to be ignored by copy_target
         exclude_init_containers: Option<Vec<String>>,
+        /// Strategic merge patch applied to the spec of the copied pod
+        patch: Option<Value>,
     },
 }
 
@@ -80,17 +86,20 @@ impl MirrordConfig for CopyTargetFileConfig {
                 scale_down: false,
                 exclude_containers: vec![],
                 exclude_init_containers: vec![],
+                patch: None,
             },
             Self::Advanced {
                 enabled,
                 scale_down,
                 exclude_containers,
                 exclude_init_containers,
+                patch,
             } => Self::Generated {
                 enabled: enabled.unwrap_or(true),
                 scale_down: scale_down.unwrap_or_default(),
                 exclude_containers: exclude_containers.unwrap_or_default(),
                 exclude_init_containers: exclude_init_containers.unwrap_or_default(),
+                patch,
             },
         };
 
@@ -129,11 +138,101 @@ pub struct CopyTargetConfig {
     ///
     /// Set a list of init containers to be ignored by copy_target
     pub exclude_init_containers: Vec<String>,
+
+    /// #### feature.copy_target.patch {#feature-copy_target-patch}
+    ///
+    /// A [strategic merge patch](https://kubernetes.io/docs/tasks/manage-kubernetes-objects/update-api-object-kubectl-patch/)
+    /// applied to the spec of the copied pod before it is created.
+    ///
+    /// Must be a JSON object, and cannot patch the mirrord agent container.
+    ///
+    /// For example, to remove a sidecar, lower the resource requests of the main container, and
+    /// remove an init container:
+    /// ```json
+    ///     {
+    ///       "patch": {
+    ///         "containers": [
+    ///           { "name": "my-sidecar", "$patch": "delete" },
+    ///           { "name": "my-app", "resources": { "requests": { "cpu": "100m" } } }
+    ///         ],
+    ///         "initContainers": [
+    ///           { "name": "my-migrations", "$patch": "delete" }
+    ///         ]
+    ///       }
+    ///     }
+    /// ```
+    pub patch: Option<Value>,
+}
+
+impl CopyTargetConfig {
+    /// Prefix of the names of the mirrord agent containers.
+    const AGENT_CONTAINER_PREFIX: &'static str = "mirrord-agent";
+
+    /// Verifies that [`Self::patch`] is a JSON object that does not touch the agent container.
+    pub fn verify(&self) -> Result<()> {
+        let Some(patch) = &self.patch else {
+            return Ok(());
+        };
+
+        let invalid = |error: &str| ConfigError::InvalidValue {
+            name: "feature.copy_target.patch",
+            provided: patch.to_string(),
+            error: error.to_string().into(),
+        };
+
+        let patch = patch
+            .as_object()
+            .ok_or_else(|| invalid("the patch has to be a JSON object"))?;
+
+        let patches_agent = ["containers", "initContainers", "ephemeralContainers"]
+            .into_iter()
+            .filter_map(|field| patch.get(field)?.as_array())
+            .flatten()
+            .filter_map(|container| container.get("name")?.as_str())
+            .any(|name| name.starts_with(Self::AGENT_CONTAINER_PREFIX));
+        if patches_agent {
+            return Err(ConfigError::Conflict(format!(
+                "`feature.copy_target.patch` cannot patch the mirrord agent container \
+                (containers with names starting with `{}`)",
+                Self::AGENT_CONTAINER_PREFIX
+            )));
+        }
+
+        Ok(())
+    }
 }
 
 impl CollectAnalytics for &CopyTargetConfig {
     fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
         analytics.add("enabled", self.enabled);
         analytics.add("scale_down", self.scale_down);
+        analytics.add("patch", self.patch.is_some());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+    use serde_json::json;
+
+    use super::*;
+
+    #[rstest]
+    #[case::remove_sidecar(json!({ "containers": [{ "name": "sidecar", "$patch": "delete" }] }), true)]
+    #[case::not_object(json!(["containers"]), false)]
+    #[case::agent_container(json!({ "containers": [{ "name": "mirrord-agent", "image": "x" }] }), false)]
+    #[case::agent_ephemeral(json!({ "ephemeralContainers": [{ "name": "mirrord-agent-abc" }] }), false)]
+    fn verify_patch(#[case] patch: Value, #[case] valid: bool) {
+        let config = CopyTargetFileConfig::Advanced {
+            enabled: None,
+            scale_down: None,
+            exclude_containers: None,
+            exclude_init_containers: None,
+            patch: Some(patch),
+        }
+        .generate_config(&mut ConfigContext::default())
+        .unwrap();
+
+        assert_eq!(config.verify().is_ok(), valid);
     }
 }
@@ -1,4 +1,4 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{collections::HashMap, fmt, path::PathBuf, str::FromStr};
 
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
@@ -6,10 +6,11 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    config::{ConfigContext, Result, from_env::FromEnv, source::MirrordConfigSource},
+    config::{ConfigContext, ConfigError, Result, from_env::FromEnv, source::MirrordConfigSource},
     util::{MirrordToggleableConfig, VecOrSingle},
 };
 
+pub mod filter;
 pub mod mapper;
 
 pub const MIRRORD_OVERRIDE_ENV_VARS_INCLUDE_ENV: &str = "MIRRORD_OVERRIDE_ENV_VARS_INCLUDE";
@@ -26,7 +27,8 @@ pub const MIRRORD_OVERRIDE_ENV_FILE_ENV: &str = "MIRRORD_OVERRIDE_ENV_VARS_FILE"
 /// 3. object - see below (means `true` + additional configuration).
 ///
 /// Which environment variables to load from the remote pod are controlled by setting either
-/// [`include`](#feature-env-include) or [`exclude`](#feature-env-exclude).
+/// [`include`](#feature-env-include) or [`exclude`](#feature-env-exclude), or both with
+/// [`filter_mode`](#feature-env-filter_mode) set to `"ordered"`.
 ///
 /// See the environment variables [reference](https://metalbear.com/mirrord/docs/reference/env/) for more details.
 ///
@@ -56,6 +58,10 @@ pub struct EnvConfig {
     /// Include only these remote environment variables in the local process.
     /// Variable names can be matched using `*` and `?` where `?` matches exactly one occurrence of
     /// any character and `*` matches arbitrary many (including zero) occurrences of any character.
+    /// Character classes like `[AB]`, `[0-9]` and `[!0-9]` are supported too.
+    ///
+    /// A pattern prefixed with `!` excludes the variables it matches instead, and the last
+    /// pattern that matches a variable wins, e.g. `"APP_*;!APP_SECRET_*"`.
     ///
     /// Can be passed as a list or as a semicolon-delimited string (e.g. `"VAR;OTHER_VAR"`).
     ///
@@ -70,6 +76,11 @@ pub struct EnvConfig {
     /// this option.
     /// Variable names can be matched using `*` and `?` where `?` matches exactly one occurrence of
     /// any character and `*` matches arbitrary many (including zero) occurrences of any character.
+    /// Character classes like `[AB]`, `[0-9]` and `[!0-9]` are supported too.
+    ///
+    /// A pattern prefixed with `!` includes the variables it matches again, and the last pattern
+    /// that matches a variable wins, e.g. `"AWS_*;!AWS_REGION"` excludes all `AWS_` variables
+    /// except for `AWS_REGION`.
     ///
     /// Some of the variables that are excluded by default:
     /// `PATH`, `HOME`, `HOMEPATH`, `CLASSPATH`, `JAVA_EXE`, `JAVA_HOME`, `PYTHONPATH`.
@@ -78,6 +89,22 @@ pub struct EnvConfig {
     #[config(env = MIRRORD_OVERRIDE_ENV_VARS_EXCLUDE_ENV)]
     pub exclude: Option<VecOrSingle<String>>,
 
+    /// #### feature.env.filter_mode {#feature-env-filter_mode}
+    ///
+    /// How [`include`](#feature-env-include) and [`exclude`](#feature-env-exclude) are evaluated.
+    ///
+    /// - `"simple"`: only one of `include` and `exclude` can be set.
+    /// - `"ordered"`: both can be set. The patterns are evaluated as one list, `include` first and
+    ///   then `exclude`, and the last pattern that matches a variable decides whether it is
+    ///   loaded. Variables that match no pattern are loaded only when `include` is not set.
+    ///
+    /// For example, with `"include": "AWS_*"` and `"exclude": "AWS_SECRET_*;!AWS_SECRET_ID"`, only
+    /// the `AWS_` variables are loaded, without the `AWS_SECRET_` ones, but with `AWS_SECRET_ID`.
+    ///
+    /// Defaults to `"simple"`.
+    #[config(default)]
+    pub filter_mode: EnvFilterMode,
+
     /// #### feature.env.override {#feature-env-override}
     ///
     /// Allows setting or overriding environment variables (locally) with a custom value.
@@ -115,6 +142,9 @@ pub struct EnvConfig {
     /// Allows for passing environment variables from an env file.
     ///
     /// These variables will override environment fetched from the remote target.
+    ///
+    /// Use `-` to read the env file from stdin (not together with a config read from stdin). A
+    /// `file://` prefix is also accepted.
     #[config(env = MIRRORD_OVERRIDE_ENV_FILE_ENV)]
     pub env_file: Option<PathBuf>,
 
@@ -141,6 +171,106 @@ pub struct EnvConfig {
     ///
     /// * `DATA_1234: common-value` => `DATA_1234: magic-value`
     pub mapping: Option<HashMap<String, String>>,
+
+    /// #### feature.env.from_kube_resources {#feature-env-from_kube_resources}
+    ///
+    /// Loads environment variables from the data of these ConfigMaps and Secrets, in the target's
+    /// namespace. Useful for resources that the target doesn't reference in `envFrom` (e.g. ones
+    /// that are only mounted as files).
+    ///
+    /// Each resource is given as `configmap/{name}` or `secret/{name}`. Later resources take
+    /// precedence over earlier ones, and all of them over the remote environment.
+    /// [`mapping`](#feature-env-mapping) and [`override`](#feature-env-override) are applied
+    /// afterwards.
+    ///
+    /// Secret values that are not valid UTF-8 are skipped.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "env": {
+    ///       "from_kube_resources": ["configmap/my-extra-config", "secret/my-creds"]
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub from_kube_resources: Option<Vec<String>>,
+
+    /// #### feature.env.fetch_timeout {#feature-env-fetch_timeout}
+    ///
+    /// How long (in seconds) mirrord waits for the agent to send the remote environment.
+    ///
+    /// Defaults to [`agent.communication_timeout`](#agent-communication_timeout). Raise it when
+    /// the target has a lot of environment variables and the connection is slow.
+    #[config(env = "MIRRORD_ENV_FETCH_TIMEOUT")]
+    pub fetch_timeout: Option<u64>,
+}
+
+/// How the `include` and `exclude` patterns are evaluated, see [`EnvConfig::filter_mode`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, JsonSchema, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EnvFilterMode {
+    /// Only one of `include` and `exclude` can be set.
+    #[default]
+    Simple,
+    /// Both lists can be set, and the last matching pattern wins.
+    Ordered,
+}
+
+impl EnvConfig {
+    /// Parses [`EnvConfig::from_kube_resources`].
+    pub fn kube_resources(&self) -> Result<Vec<KubeEnvResource>> {
+        self.from_kube_resources
+            .iter()
+            .flatten()
+            .map(|resource| resource.parse())
+            .collect()
+    }
+}
+
+/// A Kubernetes resource listed in [`EnvConfig::from_kube_resources`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KubeEnvResource {
+    /// `configmap/{name}`
+    ConfigMap(String),
+    /// `secret/{name}`
+    Secret(String),
+}
+
+impl fmt::Display for KubeEnvResource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ConfigMap(name) => write!(f, "configmap/{name}"),
+            Self::Secret(name) => write!(f, "secret/{name}"),
+        }
+    }
+}
+
+impl FromStr for KubeEnvResource {
+    type Err = ConfigError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = |error: &str| ConfigError::InvalidValue {
+            name: "feature.env.from_kube_resources",
+            provided: value.to_string(),
+            error: error.into(),
+        };
+
+        let (kind, name) = value
+            .split_once('/')
+            .ok_or_else(|| invalid("expected `configmap/{name}` or `secret/{name}`"))?;
+        if name.is_empty() || name.contains('/') {
+            return Err(invalid("invalid resource name"));
+        }
+
+        match kind.to_lowercase().as_str() {
+            "configmap" => Ok(Self::ConfigMap(name.to_string())),
+            "secret" => Ok(Self::Secret(name.to_string())),
+            _ => Err(invalid(
+                "only `configmap` and `secret` resources are supported",
+            )),
+        }
+    }
 }
 
 impl MirrordToggleableConfig for EnvFileConfig {
@@ -153,6 +283,7 @@ impl MirrordToggleableConfig for EnvFileConfig {
                 .source_value(context)
                 .transpose()?
                 .or_else(|| Some(VecOrSingle::Single("*".to_owned()))),
+            filter_mode: Default::default(),
             load_from_process: None,
             r#override: None,
             unset: None,
@@ -160,6 +291,8 @@ impl MirrordToggleableConfig for EnvFileConfig {
                 .source_value(context)
                 .transpose()?,
             mapping: None,
+            from_kube_resources: None,
+            fetch_timeout: None,
         })
     }
 }
@@ -180,6 +313,10 @@ impl CollectAnalytics for &EnvConfig {
                 .map(|v| v.len() as u32)
                 .unwrap_or_default(),
         );
+        analytics.add(
+            "filter_mode_ordered",
+            self.filter_mode == EnvFilterMode::Ordered,
+        );
         analytics.add(
             "overrides_count",
             self.r#override
@@ -202,6 +339,13 @@ impl CollectAnalytics for &EnvConfig {
                 .map(|v| v.len() as u32)
                 .unwrap_or_default(),
         );
+        analytics.add(
+            "from_kube_resources_count",
+            self.from_kube_resources
+                .as_ref()
+                .map(|v| v.len() as u32)
+                .unwrap_or_default(),
+        );
     }
 }
 
@@ -255,4 +399,28 @@ mod tests {
         assert_eq!(env.include.map(|vec| vec.join(";")).as_deref(), include.1);
         assert_eq!(env.exclude.map(|vec| vec.join(";")).as_deref(), exclude.1);
     }
+
+    #[rstest]
+    #[case(None, None)]
+    #[case(Some("120"), Some(120))]
+    fn fetch_timeout_from_env(#[case] value: Option<&str>, #[case] expected: Option<u64>) {
+        let mut cfg_context = ConfigContext::default()
+            .override_env_opt("MIRRORD_ENV_FETCH_TIMEOUT", value)
+            .strict_env(true);
+        let env = EnvFileConfig::default()
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        assert_eq!(env.fetch_timeout, expected);
+    }
+
+    #[rstest]
+    #[case("configmap/my-extra-config", Some(KubeEnvResource::ConfigMap("my-extra-config".into())))]
+    #[case("Secret/my-creds", Some(KubeEnvResource::Secret("my-creds".into())))]
+    #[case("deployment/my-app", None)]
+    #[case("configmap/", None)]
+    #[case("my-creds", None)]
+    fn parse_kube_resource(#[case] value: &str, #[case] expected: Option<KubeEnvResource>) {
+        assert_eq!(value.parse::<KubeEnvResource>().ok(), expected);
+    }
 }
@@ -0,0 +1,374 @@
+use std::collections::{HashMap, HashSet};
+
+use fancy_regex::Regex;
+use mirrord_protocol::GetEnvVarsRequest;
+use tracing::Level;
+
+use super::{EnvConfig, EnvFilterMode};
+use crate::config::ConfigError;
+
+/// What happens to the variables matched by a [`Rule`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Include,
+    Exclude,
+}
+
+/// A single pattern from [`EnvConfig::include`] or [`EnvConfig::exclude`].
+#[derive(Debug)]
+struct Rule {
+    /// The glob pattern, as written by the user (without the `!` prefix).
+    glob: String,
+    /// [`Rule::glob`] translated to an anchored [`Regex`].
+    regex: Regex,
+    action: Action,
+}
+
+/// Decides which remote environment variables are loaded, based on [`EnvConfig::include`],
+/// [`EnvConfig::exclude`] and [`EnvConfig::filter_mode`].
+///
+/// Patterns are globs (`*`, `?`, `[abc]`, `[a-z]` and `[!abc]`), and a pattern prefixed with `!`
+/// does the opposite of its list. All patterns become one list of rules, the `include` ones
+/// first, and the last rule that matches a variable decides whether it is loaded. Variables that
+/// match no rule are loaded only when `include` is not set.
+///
+/// The agent only understands plain `*` and `?` patterns in a single list, so when the patterns
+/// need more than that, [`Self::remote_request`] asks for a superset of the variables, and
+/// [`Self::filtered`] drops the rest locally.
+#[derive(Debug)]
+pub struct EnvVarsFilter {
+    rules: Vec<Rule>,
+    /// Whether variables that don't match any of the [`Self::rules`] are loaded.
+    include_unmatched: bool,
+}
+
+impl EnvVarsFilter {
+    /// Builds the rules from the `include` and `exclude` lists of the [`EnvConfig`].
+    ///
+    /// Fails when both lists are set, unless [`EnvFilterMode::Ordered`] is used, or when one of
+    /// the patterns is not a valid glob.
+    #[tracing::instrument(level = Level::TRACE, ret, err)]
+    pub fn new(config: &EnvConfig) -> Result<Self, ConfigError> {
+        if config.include.is_some()
+            && config.exclude.is_some()
+            && config.filter_mode != EnvFilterMode::Ordered
+        {
+            return Err(ConfigError::Conflict(
+                "cannot use both `include` and `exclude` filters for environment variables, \
+                unless `feature.env.filter_mode` is `ordered`"
+                    .to_string(),
+            ));
+        }
+
+        let include = Self::patterns(config.include.as_deref(), Action::Include);
+        let exclude = Self::patterns(config.exclude.as_deref(), Action::Exclude);
+
+        let rules = include
+            .chain(exclude)
+            .map(|(pattern, action)| {
+                let glob = pattern.strip_prefix('!').unwrap_or(pattern);
+                let action = match (pattern.starts_with('!'), action) {
+                    (false, action) => action,
+                    (true, Action::Include) => Action::Exclude,
+                    (true, Action::Exclude) => Action::Include,
+                };
+
+                Ok(Rule {
+                    glob: glob.to_string(),
+                    regex: glob_to_regex(glob)?,
+                    action,
+                })
+            })
+            .collect::<Result<Vec<_>, ConfigError>>()?;
+
+        Ok(Self {
+            rules,
+            include_unmatched: config.include.is_none(),
+        })
+    }
+
+    /// Splits the (possibly semicolon-delimited) patterns of a list.
+    fn patterns(list: Option<&[String]>, action: Action) -> impl Iterator<Item = (&str, Action)> {
+        list.into_iter()
+            .flatten()
+            .flat_map(|patterns| patterns.split_terminator(';'))
+            .filter(|pattern| !pattern.is_empty())
+            .map(move |pattern| (pattern, action))
+    }
+
+    /// Whether the remote variable `name` should be loaded.
+    pub fn matches(&self, name: &str) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.regex.is_match(name).unwrap_or(false))
+            .map(|rule| rule.action == Action::Include)
+            .unwrap_or(self.include_unmatched)
+    }
+
+    /// Keeps only the variables that should be loaded, see [`Self::matches`].
+    pub fn filtered(&self, mut env_vars: HashMap<String, String>) -> HashMap<String, String> {
+        env_vars.retain(|name, _| self.matches(name));
+        env_vars
+    }
+
+    /// The [`GetEnvVarsRequest`] for the agent, which returns at least all of the variables
+    /// that [`Self::matches`].
+    ///
+    /// Plain patterns from a single list are passed to the agent as they are. Otherwise, the
+    /// agent is asked for the variables matching any of the rules that include, or for all of the
+    /// variables when unmatched variables are included too.
+    pub fn remote_request(&self) -> GetEnvVarsRequest {
+        let is_plain = |rule: &Rule| !rule.glob.contains('[');
+        let globs = |action: Action| {
+            self.rules
+                .iter()
+                .filter(move |rule| rule.action == action)
+                .map(|rule| rule.glob.clone())
+        };
+
+        let single_list = self
+            .rules
+            .iter()
+            .all(|rule| (rule.action == Action::Include) != self.include_unmatched);
+
+        if single_list && self.rules.iter().all(is_plain) && !self.rules.is_empty() {
+            if self.include_unmatched {
+                return GetEnvVarsRequest {
+                    env_vars_filter: globs(Action::Exclude).collect(),
+                    env_vars_select: Default::default(),
+                };
+            } else {
+                return GetEnvVarsRequest {
+                    env_vars_filter: Default::default(),
+                    env_vars_select: globs(Action::Include).collect(),
+                };
+            }
+        }
+
+        let include_all = self.include_unmatched
+            || self
+                .rules
+                .iter()
+                .any(|rule| rule.action == Action::Include && !is_plain(rule));
+        let env_vars_select = if include_all {
+            HashSet::from(["*".to_string()])
+        } else {
+            globs(Action::Include).collect()
+        };
+
+        GetEnvVarsRequest {
+            env_vars_filter: Default::default(),
+            env_vars_select,
+        }
+    }
+}
+
+/// Translates a glob `pattern` into an anchored [`Regex`].
+fn glob_to_regex(pattern: &str) -> Result<Regex, ConfigError> {
+    let invalid = |error: &str| ConfigError::InvalidValue {
+        name: "feature.env.include/exclude",
+        provided: pattern.to_string(),
+        error: error.into(),
+    };
+
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            '[' => {
+                let mut class = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        closed = true;
+                        break;
+                    }
+                    class.push(c);
+                }
+
+                let (negated, class) = match class.strip_prefix('!') {
+                    Some(class) => (true, class),
+                    None => (false, class.as_str()),
+                };
+                if !closed || class.is_empty() {
+                    return Err(invalid("unterminated or empty `[...]` class"));
+                }
+
+                regex.push('[');
+                if negated {
+                    regex.push('^');
+                }
+                for c in class.chars() {
+                    // Characters that have a special meaning inside of a regex class.
+                    if matches!(c, '\\' | '[' | '^' | '&' | '~') {
+                        regex.push('\\');
+                    }
+                    regex.push(c);
+                }
+                regex.push(']');
+            }
+            c => regex.push_str(&fancy_regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+
+    Regex::new(&regex).map_err(|error| invalid(&error.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::util::VecOrSingle;
+
+    fn config(include: Option<&str>, exclude: Option<&str>, mode: EnvFilterMode) -> EnvConfig {
+        let list = |patterns: Option<&str>| {
+            patterns.map(|patterns| {
+                VecOrSingle::Multiple(patterns.split(';').map(String::from).collect())
+            })
+        };
+
+        EnvConfig {
+            include: list(include),
+            exclude: list(exclude),
+            filter_mode: mode,
+            r#override: None,
+            load_from_process: None,
+            unset: None,
+            env_file: None,
+            mapping: None,
+            from_kube_resources: None,
+            fetch_timeout: None,
+        }
+    }
+
+    #[rstest]
+    // No filters.
+    #[case(None, None, "ANYTHING", true)]
+    // Plain patterns.
+    #[case(Some("FOO;BAR_*"), None, "FOO", true)]
+    #[case(Some("FOO;BAR_*"), None, "FOOBAR", false)]
+    #[case(Some("FOO;BAR_*"), None, "BAR_STOOL", true)]
+    #[case(Some("FO?"), None, "FOX", true)]
+    #[case(Some("FO?"), None, "FOXY", false)]
+    #[case(None, Some("FOO;BAR_*"), "FOO", false)]
+    #[case(None, Some("FOO;BAR_*"), "BAR_STOOL", false)]
+    #[case(None, Some("FOO;BAR_*"), "BAZ", true)]
+    // Character classes.
+    #[case(Some("DB_[AB]"), None, "DB_A", true)]
+    #[case(Some("DB_[AB]"), None, "DB_C", false)]
+    #[case(Some("DB_[0-9]*"), None, "DB_1_HOST", true)]
+    #[case(Some("DB_[!0-9]*"), None, "DB_1_HOST", false)]
+    #[case(Some("DB_[!0-9]*"), None, "DB_HOST", true)]
+    // Negation, the last matching pattern wins.
+    #[case(None, Some("AWS_*;!AWS_REGION"), "AWS_REGION", true)]
+    #[case(None, Some("AWS_*;!AWS_REGION"), "AWS_SECRET_ACCESS_KEY", false)]
+    #[case(None, Some("AWS_*;!AWS_REGION"), "PORT", true)]
+    #[case(None, Some("!AWS_REGION;AWS_*"), "AWS_REGION", false)]
+    #[case(Some("APP_*;!APP_SECRET_*"), None, "APP_PORT", true)]
+    #[case(Some("APP_*;!APP_SECRET_*"), None, "APP_SECRET_KEY", false)]
+    #[case(Some("APP_*;!APP_SECRET_*"), None, "OTHER", false)]
+    fn simple_mode(
+        #[case] include: Option<&str>,
+        #[case] exclude: Option<&str>,
+        #[case] name: &str,
+        #[case] expected: bool,
+    ) {
+        let filter = EnvVarsFilter::new(&config(include, exclude, EnvFilterMode::Simple)).unwrap();
+        assert_eq!(filter.matches(name), expected, "{name}");
+    }
+
+    #[rstest]
+    #[case(Some("AWS_*"), Some("AWS_SECRET_*"), "AWS_REGION", true)]
+    #[case(Some("AWS_*"), Some("AWS_SECRET_*"), "AWS_SECRET_ACCESS_KEY", false)]
+    #[case(Some("AWS_*"), Some("AWS_SECRET_*"), "PORT", false)]
+    #[case(Some("AWS_*"), Some("*;!AWS_REGION"), "AWS_REGION", true)]
+    #[case(Some("AWS_*"), Some("*;!AWS_REGION"), "AWS_PROFILE", false)]
+    #[case(None, Some("AWS_*;!AWS_REGION"), "AWS_REGION", true)]
+    #[case(Some("*"), Some("SECRET_*;!SECRET_PUBLIC"), "SECRET_PUBLIC", true)]
+    #[case(Some("*"), Some("SECRET_*;!SECRET_PUBLIC"), "SECRET_TOKEN", false)]
+    #[case(Some("*"), Some("SECRET_*;!SECRET_PUBLIC"), "PORT", true)]
+    fn ordered_mode(
+        #[case] include: Option<&str>,
+        #[case] exclude: Option<&str>,
+        #[case] name: &str,
+        #[case] expected: bool,
+    ) {
+        let filter = EnvVarsFilter::new(&config(include, exclude, EnvFilterMode::Ordered)).unwrap();
+        assert_eq!(filter.matches(name), expected, "{name}");
+    }
+
+    #[test]
+    fn include_and_exclude_need_ordered_mode() {
+        assert!(matches!(
+            EnvVarsFilter::new(&config(Some("A"), Some("B"), EnvFilterMode::Simple)),
+            Err(ConfigError::Conflict(..))
+        ));
+    }
+
+    #[rstest]
+    #[case("DB_[AB")]
+    #[case("DB_[]")]
+    fn invalid_glob(#[case] pattern: &str) {
+        assert!(matches!(
+            EnvVarsFilter::new(&config(Some(pattern), None, EnvFilterMode::Simple)),
+            Err(ConfigError::InvalidValue { .. })
+        ));
+    }
+
+    /// Plain patterns from a single list go to the agent unchanged, anything else asks the agent
+    /// for a superset.
+    #[rstest]
+    #[case(None, None, EnvFilterMode::Simple, &[], &["*"])]
+    #[case(Some("A;B_*"), None, EnvFilterMode::Simple, &[], &["A", "B_*"])]
+    #[case(None, Some("A;B_*"), EnvFilterMode::Simple, &["A", "B_*"], &[])]
+    #[case(None, Some("AWS_*;!AWS_REGION"), EnvFilterMode::Simple, &[], &["*"])]
+    #[case(Some("APP_*;!APP_SECRET"), None, EnvFilterMode::Simple, &[], &["APP_*"])]
+    #[case(Some("DB_[AB]"), None, EnvFilterMode::Simple, &[], &["*"])]
+    #[case(
+        Some("AWS_*"),
+        Some("AWS_SECRET_*;!AWS_SECRET_ID"),
+        EnvFilterMode::Ordered,
+        &[],
+        &["AWS_*", "AWS_SECRET_ID"]
+    )]
+    fn remote_request(
+        #[case] include: Option<&str>,
+        #[case] exclude: Option<&str>,
+        #[case] mode: EnvFilterMode,
+        #[case] filter: &[&str],
+        #[case] select: &[&str],
+    ) {
+        let request = EnvVarsFilter::new(&config(include, exclude, mode))
+            .unwrap()
+            .remote_request();
+
+        let set = |values: &[&str]| values.iter().map(|value| value.to_string()).collect();
+        assert_eq!(request.env_vars_filter, set(filter));
+        assert_eq!(request.env_vars_select, set(select));
+    }
+
+    #[test]
+    fn filtered() {
+        let filter = EnvVarsFilter::new(&config(
+            None,
+            Some("AWS_*;!AWS_REGION"),
+            EnvFilterMode::Simple,
+        ))
+        .unwrap();
+
+        let env_vars = HashMap::from([
+            ("AWS_REGION".to_string(), "eu-west-1".to_string()),
+            ("AWS_PROFILE".to_string(), "dev".to_string()),
+            ("PORT".to_string(), "80".to_string()),
+        ]);
+
+        let mut names = filter.filtered(env_vars).into_keys().collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["AWS_REGION", "PORT"]);
+    }
+}
@@ -53,6 +53,13 @@ pub mod mode;
 ///   }
 /// }
 /// ```
+///
+/// ### Named pipes (FIFOs) {#fs-fifos}
+///
+/// Remote FIFOs can be read from and written to like regular remote files, but opening one never
+/// waits for the other end, so opening one for writing with no reader fails with `ENXIO`. Unless
+/// the FIFO is opened with `O_NONBLOCK`, reads and writes wait for the other end, and a single
+/// write sends at most `PIPE_BUF` bytes. `O_NONBLOCK` is only honored when passed to `open`.
 #[derive(Deserialize, PartialEq, Eq, Clone, Debug, JsonSchema)]
 #[serde(untagged, deny_unknown_fields, rename_all = "lowercase")]
 pub enum FsUserConfig {
@@ -92,6 +99,10 @@ impl MirrordConfig for FsUserConfig {
                 not_found: None,
                 mapping: None,
                 readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+                readonly_file_buffer_overrides: None,
+                max_write_chunk: MAX_WRITE_CHUNK_DEFAULT,
+                fallback_local: None,
+                fallback_local_on: None,
             },
             FsUserConfig::Advanced(advanced) => advanced.generate_config(context)?,
         };
@@ -121,6 +132,10 @@ impl MirrordToggleableConfig for FsUserConfig {
             not_found: None,
             mapping: None,
             readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+            readonly_file_buffer_overrides: None,
+            max_write_chunk: MAX_WRITE_CHUNK_DEFAULT,
+            fallback_local: None,
+            fallback_local_on: None,
         })
     }
 }
@@ -137,6 +152,8 @@ mod tests {
         let expect = FsConfig {
             mode: FsModeConfig::Read,
             readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+            readonly_file_buffer_overrides: None,
+            max_write_chunk: MAX_WRITE_CHUNK_DEFAULT,
             ..Default::default()
         };
 
@@ -1,7 +1,13 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    path::Path,
+};
 
+use fancy_regex::{Regex, RegexBuilder};
 use mirrord_analytics::{AnalyticValue, CollectAnalytics};
 use mirrord_config_derive::MirrordConfig;
+use mirrord_protocol::ErrorKindInternal;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -19,6 +25,10 @@ pub const READONLY_FILE_BUFFER_WARN_LIMIT: u64 = 1024 * 1024;
 /// Do not allow users to set a value of [`FsConfig::readonly_file_buffer`] larger than 15mb
 pub const READONLY_FILE_BUFFER_HARD_LIMIT: u64 = 15 * 1024 * 1024;
 
+/// The default maximal size in bytes of a single remote write.
+/// See [`FsConfig::max_write_chunk`].
+pub const MAX_WRITE_CHUNK_DEFAULT: u64 = 1024 * 1024;
+
 // TODO(alex): We could turn this derive macro (`MirrordConfig`) into an attribute version, which
 // would allow us to "capture" the `derive` statement, making it possible to implement the same for
 // whatever is generated by `map_to`.
@@ -157,6 +167,63 @@ pub struct FsConfig {
     /// This improves performance when the user application reads data in small portions.
     #[config(default = READONLY_FILE_BUFFER_DEFAULT)]
     pub readonly_file_buffer: u64,
+
+    /// #### feature.fs.readonly_file_buffer_overrides {#feature-fs-readonly_file_buffer_overrides}
+    ///
+    /// Overrides [`readonly_file_buffer`](#feature-fs-readonly_file_buffer) for read-only remote
+    /// files whose path matches a pattern. Setting the value to 0 disables buffering of the
+    /// matching files. Case insensitive.
+    ///
+    /// The patterns are checked in the order they are listed, the first one that matches the path
+    /// of the opened file is used. Files opened with a relative path (e.g. with `openat`) always
+    /// use the value of `readonly_file_buffer`.
+    ///
+    /// Example:
+    /// ```json
+    /// {
+    ///   "readonly_file_buffer_overrides": [
+    ///     { "pattern": "\\.parquet$", "size": 0 },
+    ///     { "pattern": "\\.properties$", "size": 4096 }
+    ///   ]
+    /// }
+    /// ```
+    pub readonly_file_buffer_overrides: Option<Vec<ReadonlyFileBufferOverride>>,
+
+    /// #### feature.fs.max_write_chunk {#feature-fs-max_write_chunk}
+    ///
+    /// Sets the maximal size in bytes of a single write to a remote file. By default, the value
+    /// is 1048576 bytes, or 1 MiB.
+    ///
+    /// Larger positioned writes (e.g. `pwrite`) are split into chunks, written one after
+    /// another. If a chunk fails midway, the application gets a short write with the amount
+    /// written so far.
+    ///
+    /// Setting the value to 0 disables splitting writes.
+    #[config(default = MAX_WRITE_CHUNK_DEFAULT)]
+    pub max_write_chunk: u64,
+
+    /// #### feature.fs.fallback_local {#feature-fs-fallback_local}
+    ///
+    /// Specify file path patterns that are opened locally when opening them remotely fails with
+    /// one of the errors in [`fallback_local_on`](#feature-fs-fallback_local_on).
+    pub fallback_local: Option<VecOrSingle<String>>,
+
+    /// #### feature.fs.fallback_local_on {#feature-fs-fallback_local_on}
+    ///
+    /// Names of the remote errors (as they appear in mirrord's error messages, e.g.
+    /// `"PermissionDenied"` or `"NotFound"`) that make mirrord open a file locally, instead of
+    /// failing, when its path matches [`fallback_local`](#feature-fs-fallback_local).
+    ///
+    /// Writes to a file opened this way stay local.
+    ///
+    /// Example:
+    /// ```json
+    /// {
+    ///   "fallback_local": "^/etc/app/.+",
+    ///   "fallback_local_on": ["PermissionDenied", "NotFound"]
+    /// }
+    /// ```
+    pub fallback_local_on: Option<Vec<String>>,
 }
 
 impl MirrordToggleableConfig for AdvancedFsUserConfig {
@@ -180,6 +247,10 @@ impl MirrordToggleableConfig for AdvancedFsUserConfig {
             not_found: None,
             mapping: None,
             readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+            readonly_file_buffer_overrides: None,
+            max_write_chunk: MAX_WRITE_CHUNK_DEFAULT,
+            fallback_local: None,
+            fallback_local_on: None,
         })
     }
 }
@@ -197,6 +268,100 @@ impl FsConfig {
     pub fn is_active(&self) -> bool {
         !matches!(self.mode, FsModeConfig::Local)
     }
+
+    /// Compiles [`Self::readonly_file_buffer_overrides`] into [`ReadonlyFileBuffers`].
+    ///
+    /// Fails on the first pattern that is not a valid regex.
+    pub fn readonly_file_buffers(&self) -> Result<ReadonlyFileBuffers, ConfigError> {
+        let overrides = self
+            .readonly_file_buffer_overrides
+            .iter()
+            .flatten()
+            .map(|ReadonlyFileBufferOverride { pattern, size }| {
+                RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map(|regex| (regex, *size))
+                    .map_err(|error| ConfigError::InvalidValue {
+                        name: "feature.fs.readonly_file_buffer_overrides",
+                        provided: pattern.clone(),
+                        error: Box::new(error),
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(ReadonlyFileBuffers {
+            default: self.readonly_file_buffer,
+            overrides,
+        })
+    }
+
+    /// Parses [`Self::fallback_local_on`] into [`io::ErrorKind`]s.
+    ///
+    /// Fails on the first name that is not a known error kind.
+    pub fn fallback_local_error_kinds(&self) -> Result<HashSet<io::ErrorKind>, ConfigError> {
+        self.fallback_local_on
+            .iter()
+            .flatten()
+            .map(|name| {
+                name.parse::<ErrorKindInternal>()
+                    .ok()
+                    .and_then(|kind| kind.as_io_error_kind())
+                    .ok_or_else(|| ConfigError::InvalidValue {
+                        name: "feature.fs.fallback_local_on",
+                        provided: name.clone(),
+                        error: "not a known error kind, e.g. `PermissionDenied` or `NotFound`"
+                            .into(),
+                    })
+            })
+            .collect()
+    }
+}
+
+/// A single entry of [`FsConfig::readonly_file_buffer_overrides`].
+#[derive(Clone, Debug, Eq, PartialEq, JsonSchema, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ReadonlyFileBufferOverride {
+    /// Regex matched against the path of the opened file, case insensitive.
+    pub pattern: String,
+
+    /// Buffer size in bytes for the matching files, 0 disables buffering.
+    pub size: u64,
+}
+
+/// Sizes of the buffers for read-only remote files, see [`FsConfig::readonly_file_buffer`] and
+/// [`FsConfig::readonly_file_buffer_overrides`].
+///
+/// Size 0 disables buffering.
+#[derive(Clone, Debug, Default)]
+pub struct ReadonlyFileBuffers {
+    default: u64,
+    overrides: Vec<(Regex, u64)>,
+}
+
+impl ReadonlyFileBuffers {
+    /// Uses the same buffer size for all files.
+    pub fn new(default: u64) -> Self {
+        Self {
+            default,
+            overrides: Default::default(),
+        }
+    }
+
+    /// Buffer size for files that we don't know the path of.
+    pub fn default_size(&self) -> u64 {
+        self.default
+    }
+
+    /// Buffer size for the file at `path`, from the first override that matches it.
+    pub fn size_for(&self, path: &Path) -> u64 {
+        let path = path.to_string_lossy();
+
+        self.overrides
+            .iter()
+            .find_map(|(regex, size)| regex.is_match(&path).unwrap_or_default().then_some(*size))
+            .unwrap_or(self.default)
+    }
 }
 
 impl From<FsModeConfig> for AnalyticValue {
@@ -239,6 +404,21 @@ impl CollectAnalytics for &FsConfig {
                 .unwrap_or_default(),
         );
         analytics.add("readonly_file_buffer", self.readonly_file_buffer);
+        analytics.add(
+            "readonly_file_buffer_overrides",
+            self.readonly_file_buffer_overrides
+                .as_deref()
+                .map(<[_]>::len)
+                .unwrap_or_default(),
+        );
+        analytics.add("max_write_chunk", self.max_write_chunk);
+        analytics.add(
+            "fallback_local_paths",
+            self.fallback_local
+                .as_deref()
+                .map(<[_]>::len)
+                .unwrap_or_default(),
+        );
     }
 }
 
@@ -254,6 +434,8 @@ mod tests {
         let expect = FsConfig {
             mode: FsModeConfig::Read,
             readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+            readonly_file_buffer_overrides: None,
+            max_write_chunk: MAX_WRITE_CHUNK_DEFAULT,
             ..Default::default()
         };
 
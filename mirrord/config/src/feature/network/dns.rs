@@ -1,4 +1,4 @@
-use std::ops::Deref;
+use std::{net::IpAddr, ops::Deref};
 
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
@@ -62,7 +62,20 @@ use crate::{
 /// }
 /// ```
 ///
+/// - Only queries for subdomains of `internal.corp` (but not for `internal.corp` itself) will go
+///   through the local app. Use `**.internal.corp` to match `internal.corp` as well.
+///
+/// ```json
+/// {
+///   "local": ["*.internal.corp"]
+/// }
+/// ```
+///
 /// Valid values follow this pattern: `[name|address|subnet/mask][:port]`.
+///
+/// Names may start with a `*.` or `**.` wildcard, other wildcards and regular expressions are not
+/// supported. Names are matched case insensitively, and internationalized names match their
+/// punycode form (e.g. `bücher.example` matches `xn--bcher-kva.example`).
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug, JsonSchema)]
 #[serde(deny_unknown_fields, rename_all = "lowercase")]
 pub enum DnsFilterConfig {
@@ -99,10 +112,35 @@ pub struct DnsConfig {
     /// Unstable: the precise syntax of this config is subject to change.
     #[config(default, unstable)]
     pub filter: Option<DnsFilterConfig>,
+
+    /// ##### feature.network.dns.ignore_local_hosts_file {#feature-network-dns-ignore_local_hosts_file}
+    ///
+    /// Resolve names that look like Kubernetes service names (ending with `.svc` or
+    /// `.cluster.local`) through the remote pod, even when your local `/etc/hosts` has an entry
+    /// for them, and the DNS filter would resolve them locally.
+    ///
+    /// Useful when the hosts file contains leftovers like `127.0.0.1 my-service.cluster.local`.
+    ///
+    /// Only has an effect together with a
+    /// [`feature.network.dns.filter`](#feature-network-dns-filter) that resolves these names
+    /// locally. Without a filter, all names are already resolved through the remote pod, using its
+    /// hosts file.
+    ///
+    /// Defaults to `false`.
+    #[config(default = false)]
+    pub ignore_local_hosts_file: bool,
 }
 
 impl DnsConfig {
     pub fn verify(&self, context: &mut ConfigContext) -> Result<(), ConfigError> {
+        if self.ignore_local_hosts_file && (!self.enabled || self.filter.is_none()) {
+            context.add_warning(
+                "`feature.network.dns.ignore_local_hosts_file` has no effect without a DNS filter \
+                that resolves names locally"
+                    .to_string(),
+            );
+        }
+
         let filters = match &self.filter {
             Some(..) if !self.enabled => {
                 context.add_warning(
@@ -146,6 +184,49 @@ impl DnsConfig {
     }
 }
 
+/// Path to the local hosts file, checked for entries that collide with remote DNS.
+pub const LOCAL_HOSTS_FILE: &str = "/etc/hosts";
+
+/// Suffixes of the names resolvable with the default search domains of a Kubernetes cluster.
+const CLUSTER_NAME_SUFFIXES: [&str; 2] = [".svc", ".cluster.local"];
+
+/// Returns whether the `name` looks like a name of a Kubernetes service, e.g.
+/// `my-service.my-namespace.svc.cluster.local`.
+pub fn is_cluster_name(name: &str) -> bool {
+    let name = name.strip_suffix('.').unwrap_or(name).to_lowercase();
+    CLUSTER_NAME_SUFFIXES.iter().any(|suffix| {
+        name.strip_suffix(suffix)
+            .is_some_and(|prefix| !prefix.is_empty())
+    })
+}
+
+/// An entry from the local hosts file, see [`parse_hosts_file`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HostsEntry {
+    pub address: IpAddr,
+    pub name: String,
+}
+
+/// Parses the contents of a hosts file, returning one [`HostsEntry`] for each name (including
+/// aliases).
+///
+/// Comments and lines that don't start with an IP address are skipped.
+pub fn parse_hosts_file(contents: &str) -> Vec<HostsEntry> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            let line = line.split_once('#').map_or(line, |(line, _comment)| line);
+            let mut fields = line.split_whitespace();
+            let address = fields.next()?.parse::<IpAddr>().ok()?;
+            Some(fields.map(move |name| HostsEntry {
+                address,
+                name: name.to_string(),
+            }))
+        })
+        .flatten()
+        .collect()
+}
+
 impl MirrordToggleableConfig for DnsFileConfig {
     fn disabled_config(context: &mut ConfigContext) -> Result<Self::Generated, ConfigError> {
         Ok(DnsConfig {
@@ -160,6 +241,7 @@ impl MirrordToggleableConfig for DnsFileConfig {
 impl CollectAnalytics for &DnsConfig {
     fn collect_analytics(&self, analytics: &mut mirrord_analytics::Analytics) {
         analytics.add("enabled", self.enabled);
+        analytics.add("ignore_local_hosts_file", self.ignore_local_hosts_file);
 
         if let Some(filter) = self.filter.as_ref() {
             match filter {
@@ -170,3 +252,77 @@ impl CollectAnalytics for &DnsConfig {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::service("my-service.cluster.local", true)]
+    #[case::namespaced("my-service.default.svc", true)]
+    #[case::fqdn("my-service.default.svc.cluster.local.", true)]
+    #[case::uppercase("My-Service.Cluster.Local", true)]
+    #[case::suffix_only("cluster.local", false)]
+    #[case::localhost("localhost", false)]
+    #[case::lookalike("my-service.svcs", false)]
+    fn cluster_names(#[case] name: &str, #[case] expected: bool) {
+        assert_eq!(is_cluster_name(name), expected);
+    }
+
+    #[rstest]
+    #[case::no_filter(true, false, true)]
+    #[case::disabled(false, true, true)]
+    #[case::local_filter(true, true, false)]
+    fn ignore_local_hosts_file_without_filter(
+        #[case] enabled: bool,
+        #[case] with_filter: bool,
+        #[case] warns: bool,
+    ) {
+        let config = DnsConfig {
+            enabled,
+            filter: with_filter.then(|| {
+                DnsFilterConfig::Local(VecOrSingle::Single("**.cluster.local".to_string()))
+            }),
+            ignore_local_hosts_file: true,
+        };
+
+        let mut context = ConfigContext::default();
+        config.verify(&mut context).unwrap();
+        assert_eq!(
+            context
+                .into_warnings()
+                .iter()
+                .any(|warning| warning.contains("ignore_local_hosts_file")),
+            warns
+        );
+    }
+
+    #[test]
+    fn parse_hosts() {
+        let contents = "\
+# The following lines are desirable for IPv4 capable hosts
+127.0.0.1 localhost localhost.localdomain
+::1       ip6-localhost # loopback
+10.0.0.1\tmy-service.cluster.local
+
+not-an-ip some-name
+";
+
+        let entries = parse_hosts_file(contents)
+            .into_iter()
+            .map(|entry| (entry.address.to_string(), entry.name))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                ("127.0.0.1", "localhost"),
+                ("127.0.0.1", "localhost.localdomain"),
+                ("::1", "ip6-localhost"),
+                ("10.0.0.1", "my-service.cluster.local"),
+            ]
+            .map(|(address, name)| (address.to_string(), name.to_string()))
+        );
+    }
+}
@@ -1,17 +1,13 @@
-use std::{
-    net::{IpAddr, SocketAddr},
-    num::ParseIntError,
-    str::FromStr,
-};
+use std::{fmt, net::IpAddr, num::ParseIntError, str::FromStr};
 
 use nom::{
     IResult,
     branch::alt,
-    bytes::complete::{tag, take_until},
+    bytes::complete::{tag, take_until, take_while1},
     character::complete::{alphanumeric1, digit1},
-    combinator::opt,
+    combinator::{opt, recognize},
     multi::many1,
-    sequence::{delimited, preceded, terminated},
+    sequence::{delimited, pair, preceded, terminated},
 };
 use thiserror::Error;
 
@@ -44,36 +40,175 @@ impl FromStr for ProtocolFilter {
 }
 
 /// <!--${internal}-->
-/// Parsed addresses can be one of these 3 variants.
+/// Port part of an [`AddressFilter`], specified either as a single port `:a`, or as an inclusive
+/// range of ports `:a-b`.
+///
+/// We treat a single `0` port as if it meant **any** port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PortRange {
+    /// First port of the range.
+    pub start: u16,
+    /// Last port of the range (inclusive).
+    pub end: u16,
+}
+
+impl PortRange {
+    /// Matches any port.
+    pub const ANY: Self = Self::single(0);
+
+    pub const fn single(port: u16) -> Self {
+        Self {
+            start: port,
+            end: port,
+        }
+    }
+
+    pub fn is_any(&self) -> bool {
+        *self == Self::ANY
+    }
+
+    /// Checks whether the given port matches this filter.
+    pub fn contains(&self, port: u16) -> bool {
+        self.is_any() || (self.start..=self.end).contains(&port)
+    }
+}
+
+impl From<u16> for PortRange {
+    fn from(port: u16) -> Self {
+        Self::single(port)
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.start == self.end {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = AddressFilterError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let Some((start, end)) = input.split_once('-') else {
+            return input
+                .parse::<u16>()
+                .map(Self::single)
+                .map_err(AddressFilterError::ParsePort);
+        };
+
+        let start = start
+            .parse::<u16>()
+            .map_err(AddressFilterError::ParsePort)?;
+        let end = end.parse::<u16>().map_err(AddressFilterError::ParsePort)?;
+
+        if start > end {
+            return Err(AddressFilterError::InvertedPortRange { start, end });
+        }
+
+        Ok(Self { start, end })
+    }
+}
+
+/// <!--${internal}-->
+/// Parsed addresses can be one of these 4 variants.
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum AddressFilter {
-    /// Only port was specified.
-    Port(u16),
+    /// Only port (or port range) was specified.
+    Port(PortRange),
 
-    /// Just a plain old [`SocketAddr`], specified as `a.b.c.d:e`.
+    /// Just a plain old IP address and a port, specified as `a.b.c.d:e`.
     ///
     /// We treat `0`s here as if it meant **any**, so `0.0.0.0` means we filter any IP, and `:0`
     /// means any port.
-    Socket(SocketAddr),
+    Socket(IpAddr, PortRange),
 
     /// A named address, as we cannot resolve it here, specified as `name:a`.
     ///
     /// We can only resolve such names on the mirrord layer `connect` call, as we have to check if
     /// the user enabled the DNS feature or not (and thus, resolve it through the remote pod, or
     /// the local app).
-    Name(String, u16),
+    Name(String, PortRange),
 
     /// Just a plain old subnet and a port, specified as `a.b.c.d/e:f`.
-    Subnet(ipnet::IpNet, u16),
+    Subnet(ipnet::IpNet, PortRange),
+}
+
+/// <!--${internal}-->
+/// Name from an [`AddressFilter::Name`], possibly starting with a wildcard label.
+///
+/// Names are compared in their ASCII (punycode) form and case insensitively, so
+/// `bücher.example` and `xn--bcher-kva.example` are the same name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NamePattern {
+    /// `internal.corp`, matches only this name.
+    Exact(String),
+    /// `*.internal.corp`, matches any subdomain of the name, but not the name itself.
+    Subdomains(String),
+    /// `**.internal.corp`, matches the name and any of its subdomains.
+    NameAndSubdomains(String),
+}
+
+impl NamePattern {
+    /// Checks whether the given domain name matches this pattern.
+    pub fn matches(&self, name: &str) -> bool {
+        let name = Self::normalize(name);
+
+        let is_subdomain = |parent: &str| {
+            name.strip_suffix(parent)
+                .and_then(|prefix| prefix.strip_suffix('.'))
+                .is_some_and(|prefix| !prefix.is_empty())
+        };
+
+        match self {
+            Self::Exact(exact) => name == *exact,
+            Self::Subdomains(parent) => is_subdomain(parent),
+            Self::NameAndSubdomains(parent) => name == *parent || is_subdomain(parent),
+        }
+    }
+
+    /// Converts the name to its lowercase ASCII form, without the trailing dot.
+    ///
+    /// Names that are not valid IDNs are only lowercased.
+    fn normalize(name: &str) -> String {
+        let name = name.strip_suffix('.').unwrap_or(name);
+        idna::domain_to_ascii(name).unwrap_or_else(|_| name.to_lowercase())
+    }
+}
+
+impl FromStr for NamePattern {
+    type Err = AddressFilterError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let invalid = || AddressFilterError::InvalidWildcard(input.to_string());
+
+        let (pattern, name): (fn(String) -> Self, _) = if let Some(name) = input.strip_prefix("**.")
+        {
+            (Self::NameAndSubdomains, name)
+        } else if let Some(name) = input.strip_prefix("*.") {
+            (Self::Subdomains, name)
+        } else {
+            (Self::Exact, input)
+        };
+
+        if name.is_empty() || name.contains('*') {
+            return Err(invalid());
+        }
+
+        Ok(pattern(Self::normalize(name)))
+    }
 }
 
 impl AddressFilter {
-    pub fn port(&self) -> u16 {
+    pub fn ports(&self) -> PortRange {
         match self {
-            Self::Port(port) => *port,
-            Self::Name(_, port) => *port,
-            Self::Socket(socket) => socket.port(),
-            Self::Subnet(_, port) => *port,
+            Self::Port(ports) => *ports,
+            Self::Name(_, ports) => *ports,
+            Self::Socket(_, ports) => *ports,
+            Self::Subnet(_, ports) => *ports,
         }
     }
 }
@@ -86,6 +221,9 @@ pub enum AddressFilterError {
     #[error("parsing port number failed: {0}")]
     ParsePort(ParseIntError),
 
+    #[error("invalid port range `{start}-{end}`, the first port must not be greater than the last")]
+    InvertedPortRange { start: u16, end: u16 },
+
     #[error("parsing left trailing value: {0}")]
     TrailingValue(String),
 
@@ -100,6 +238,15 @@ pub enum AddressFilterError {
 
     #[error("provided empty string")]
     Empty,
+
+    #[error(
+        "invalid wildcard in `{0}`, only `*.name` (subdomains of the name) and `**.name` (the name \
+        and its subdomains) are supported, regular expressions are not"
+    )]
+    InvalidWildcard(String),
+
+    #[error("wildcard names are supported only in `feature.network.dns.filter`")]
+    WildcardNotSupported,
 }
 
 impl From<nom::Err<nom::error::Error<&str>>> for AddressFilterError {
@@ -112,6 +259,11 @@ impl FromStr for AddressFilter {
     type Err = AddressFilterError;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
+        // Wildcards mixed with regex syntax, e.g. `.*\.internal\.corp`.
+        if input.contains('*') && input.contains(['\\', '^', '$', '+', '?', '(', ')', '|']) {
+            return Err(AddressFilterError::InvalidWildcard(input.to_string()));
+        }
+
         // Perform the basic parsing.
         let (rest, address) = address(input)?;
         let (rest, subnet) = subnet(rest)?;
@@ -123,11 +275,7 @@ impl FromStr for AddressFilter {
 
         match (address, subnet, port) {
             // Only port specified.
-            (None, None, Some(port)) => {
-                let port = port.parse::<u16>().map_err(AddressFilterError::ParsePort)?;
-
-                Ok(Self::Port(port))
-            }
+            (None, None, Some(port)) => Ok(Self::Port(port.parse()?)),
 
             // Subnet specified. Address must be IP.
             (Some(address), Some(subnet), port) => {
@@ -139,29 +287,31 @@ impl FromStr for AddressFilter {
                     .map_err(AddressFilterError::ParseSubnetPrefixLength)?;
                 let ip_net = ipnet::IpNet::new(as_ip, prefix_len)?;
 
-                let port = port
-                    .map(u16::from_str)
-                    .transpose()
-                    .map_err(AddressFilterError::ParsePort)?
-                    .unwrap_or(0);
+                let ports = port
+                    .map(PortRange::from_str)
+                    .transpose()?
+                    .unwrap_or(PortRange::ANY);
 
-                Ok(Self::Subnet(ip_net, port))
+                Ok(Self::Subnet(ip_net, ports))
             }
 
             // Subnet not specified. Address can be a name or an IP.
             (Some(address), None, _) => {
-                let port = port
-                    .map(u16::from_str)
-                    .transpose()
-                    .map_err(AddressFilterError::ParsePort)?
-                    .unwrap_or(0);
+                let ports = port
+                    .map(PortRange::from_str)
+                    .transpose()?
+                    .unwrap_or(PortRange::ANY);
 
-                let result = address
-                    .parse::<IpAddr>()
-                    .map(|ip| Self::Socket(SocketAddr::new(ip, port)))
-                    .unwrap_or(Self::Name(address, port));
+                if let Ok(ip) = address.parse::<IpAddr>() {
+                    return Ok(Self::Socket(ip, ports));
+                }
+
+                if address.contains('*') {
+                    // Only validates the wildcard, the name is kept as it is.
+                    address.parse::<NamePattern>()?;
+                }
 
-                Ok(result)
+                Ok(Self::Name(address, ports))
             }
 
             // Subnet specified but address is missing, error.
@@ -207,7 +357,7 @@ impl FromStr for ProtocolAndAddressFilter {
         let protocol = protocol.parse()?;
 
         let address = rest.parse().or_else(|error| match error {
-            AddressFilterError::Empty => Ok(AddressFilter::Port(0)),
+            AddressFilterError::Empty => Ok(AddressFilter::Port(PortRange::ANY)),
             other => Err(other),
         })?;
 
@@ -229,7 +379,7 @@ fn protocol(input: &str) -> IResult<&str, &str> {
 ///
 /// We try to parse 3 different kinds of values here:
 ///
-/// 1. `name.with.dots`;
+/// 1. `name.with.dots` (possibly with `*` wildcards);
 /// 2. `1.2.3.4.5.6`;
 /// 3. `[dad:1337:fa57::0]`
 ///
@@ -242,7 +392,14 @@ fn address(input: &str) -> IResult<&str, Option<String>> {
     let ipv6 = many1(alt((alphanumeric1, tag(":"))));
     let ipv6_host = delimited(tag("["), ipv6, tag("]"));
 
-    let host_char = alt((alphanumeric1, tag("-"), tag("_"), tag(".")));
+    // Not only ASCII, to allow for IDNs.
+    let host_char = alt((
+        take_while1(char::is_alphanumeric),
+        tag("-"),
+        tag("_"),
+        tag("."),
+        tag("*"),
+    ));
     let dotted_address = many1(host_char);
 
     let (rest, address) = opt(alt((dotted_address, ipv6_host)))(input)?;
@@ -264,11 +421,15 @@ fn subnet(input: &str) -> IResult<&str, Option<&str>> {
 
 /// <!--${internal}-->
 ///
-/// Parses `:1337`, extracting the `1337` part, and discarding the `:`.
+/// Parses `:1337` or `:1337-7331`, extracting the `1337` (or `1337-7331`) part, and discarding
+/// the `:`.
 ///
 /// Returns [`None`] if it doesn't parse anything.
 fn port(input: &str) -> IResult<&str, Option<&str>> {
-    let port_parser = preceded(tag(":"), digit1);
+    let port_parser = preceded(
+        tag(":"),
+        recognize(pair(digit1, opt(pair(tag("-"), digit1)))),
+    );
     let (rest, port) = opt(port_parser)(input)?;
 
     Ok((rest, port))
@@ -291,7 +452,7 @@ mod tests {
     fn full_converted() -> ProtocolAndAddressFilter {
         ProtocolAndAddressFilter {
             protocol: ProtocolFilter::Tcp,
-            address: AddressFilter::Subnet(IpNet::from_str("1.2.3.0/24").unwrap(), 7777),
+            address: AddressFilter::Subnet(IpNet::from_str("1.2.3.0/24").unwrap(), 7777.into()),
         }
     }
 
@@ -305,7 +466,8 @@ mod tests {
         ProtocolAndAddressFilter {
             protocol: ProtocolFilter::Tcp,
             address: AddressFilter::Socket(
-                SocketAddr::from_str("[2800:3f0:4001:81e::2004]:7777").unwrap(),
+                IpAddr::from_str("2800:3f0:4001:81e::2004").unwrap(),
+                7777.into(),
             ),
         }
     }
@@ -319,7 +481,7 @@ mod tests {
     fn protocol_only_converted() -> ProtocolAndAddressFilter {
         ProtocolAndAddressFilter {
             protocol: ProtocolFilter::Tcp,
-            address: AddressFilter::Port(0),
+            address: AddressFilter::Port(PortRange::ANY),
         }
     }
 
@@ -332,7 +494,7 @@ mod tests {
     fn name_converted() -> ProtocolAndAddressFilter {
         ProtocolAndAddressFilter {
             protocol: ProtocolFilter::Tcp,
-            address: AddressFilter::Name("google.com".to_string(), 7777),
+            address: AddressFilter::Name("google.com".to_string(), 7777.into()),
         }
     }
 
@@ -345,7 +507,7 @@ mod tests {
     fn name_only_converted() -> ProtocolAndAddressFilter {
         ProtocolAndAddressFilter {
             protocol: ProtocolFilter::Any,
-            address: AddressFilter::Name("rust-lang.org".to_string(), 0),
+            address: AddressFilter::Name("rust-lang.org".to_string(), PortRange::ANY),
         }
     }
 
@@ -358,7 +520,7 @@ mod tests {
     fn localhost_converted() -> ProtocolAndAddressFilter {
         ProtocolAndAddressFilter {
             protocol: ProtocolFilter::Any,
-            address: AddressFilter::Name("localhost".to_string(), 0),
+            address: AddressFilter::Name("localhost".to_string(), PortRange::ANY),
         }
     }
 
@@ -371,7 +533,7 @@ mod tests {
     fn subnet_port_converted() -> ProtocolAndAddressFilter {
         ProtocolAndAddressFilter {
             protocol: ProtocolFilter::Any,
-            address: AddressFilter::Subnet(IpNet::from_str("1.2.3.0/24").unwrap(), 7777),
+            address: AddressFilter::Subnet(IpNet::from_str("1.2.3.0/24").unwrap(), 7777.into()),
         }
     }
 
@@ -384,7 +546,7 @@ mod tests {
     fn subnet_only_converted() -> ProtocolAndAddressFilter {
         ProtocolAndAddressFilter {
             protocol: ProtocolFilter::Any,
-            address: AddressFilter::Subnet(IpNet::from_str("1.2.3.0/24").unwrap(), 0),
+            address: AddressFilter::Subnet(IpNet::from_str("1.2.3.0/24").unwrap(), PortRange::ANY),
         }
     }
 
@@ -397,7 +559,7 @@ mod tests {
     fn protocol_port_converted() -> ProtocolAndAddressFilter {
         ProtocolAndAddressFilter {
             protocol: ProtocolFilter::Udp,
-            address: AddressFilter::Port(7777),
+            address: AddressFilter::Port(7777.into()),
         }
     }
 
@@ -410,7 +572,87 @@ mod tests {
     fn port_only_converted() -> ProtocolAndAddressFilter {
         ProtocolAndAddressFilter {
             protocol: ProtocolFilter::Any,
-            address: AddressFilter::Port(7777),
+            address: AddressFilter::Port(7777.into()),
+        }
+    }
+
+    #[fixture]
+    fn port_range() -> &'static str {
+        ":8000-8999"
+    }
+
+    #[fixture]
+    fn port_range_converted() -> ProtocolAndAddressFilter {
+        ProtocolAndAddressFilter {
+            protocol: ProtocolFilter::Any,
+            address: AddressFilter::Port(PortRange {
+                start: 8000,
+                end: 8999,
+            }),
+        }
+    }
+
+    #[fixture]
+    fn subnet_port_range() -> &'static str {
+        "udp://1.2.3.0/24:8000-8999"
+    }
+
+    #[fixture]
+    fn subnet_port_range_converted() -> ProtocolAndAddressFilter {
+        ProtocolAndAddressFilter {
+            protocol: ProtocolFilter::Udp,
+            address: AddressFilter::Subnet(
+                IpNet::from_str("1.2.3.0/24").unwrap(),
+                PortRange {
+                    start: 8000,
+                    end: 8999,
+                },
+            ),
+        }
+    }
+
+    #[fixture]
+    fn name_port_range() -> &'static str {
+        "tcp://google.com:80-443"
+    }
+
+    #[fixture]
+    fn name_port_range_converted() -> ProtocolAndAddressFilter {
+        ProtocolAndAddressFilter {
+            protocol: ProtocolFilter::Tcp,
+            address: AddressFilter::Name(
+                "google.com".to_string(),
+                PortRange {
+                    start: 80,
+                    end: 443,
+                },
+            ),
+        }
+    }
+
+    #[fixture]
+    fn wildcard_name() -> &'static str {
+        "*.internal.corp:53"
+    }
+
+    #[fixture]
+    fn wildcard_name_converted() -> ProtocolAndAddressFilter {
+        ProtocolAndAddressFilter {
+            protocol: ProtocolFilter::Any,
+            address: AddressFilter::Name("*.internal.corp".to_string(), 53.into()),
+        }
+    }
+
+    #[fixture]
+    fn idn_name() -> &'static str {
+        "bücher.example"
+    }
+
+    #[fixture]
+    fn idn_name_converted() -> ProtocolAndAddressFilter {
+        ProtocolAndAddressFilter {
+            protocol: ProtocolFilter::Any,
+            address: AddressFilter::Name("bücher.example".to_string(), PortRange::ANY),
         }
     }
 
@@ -430,6 +672,16 @@ mod tests {
         "meow://"
     }
 
+    #[fixture]
+    fn inverted_port_range() -> &'static str {
+        "udp://:9000-100"
+    }
+
+    #[fixture]
+    fn open_port_range() -> &'static str {
+        ":8000-"
+    }
+
     #[rstest]
     #[case(full(), full_converted())]
     #[case(ipv6(), ipv6_converted())]
@@ -441,6 +693,11 @@ mod tests {
     #[case(subnet_only(), subnet_only_converted())]
     #[case(protocol_port(), protocol_port_converted())]
     #[case(port_only(), port_only_converted())]
+    #[case(port_range(), port_range_converted())]
+    #[case(subnet_port_range(), subnet_port_range_converted())]
+    #[case(name_port_range(), name_port_range_converted())]
+    #[case(wildcard_name(), wildcard_name_converted())]
+    #[case(idn_name(), idn_name_converted())]
     fn valid_filters(#[case] input: &'static str, #[case] converted: ProtocolAndAddressFilter) {
         assert_eq!(
             ProtocolAndAddressFilter::from_str(input).unwrap(),
@@ -452,8 +709,45 @@ mod tests {
     #[case(name_with_subnet())]
     #[case(port_protocol())]
     #[case(fake_protocol())]
+    #[case(inverted_port_range())]
+    #[case(open_port_range())]
+    #[case(".*\\.internal\\.corp")]
+    #[case("*internal.corp")]
+    #[case("api.*.corp")]
+    #[case("***.corp")]
+    #[case("*.")]
     #[should_panic]
     fn invalid_filters(#[case] input: &'static str) {
         ProtocolAndAddressFilter::from_str(input).unwrap();
     }
+
+    #[rstest]
+    #[case(PortRange::ANY, 1337, true)]
+    #[case(PortRange::single(1337), 1337, true)]
+    #[case(PortRange::single(1337), 7331, false)]
+    #[case(PortRange { start: 8000, end: 8999 }, 8000, true)]
+    #[case(PortRange { start: 8000, end: 8999 }, 8999, true)]
+    #[case(PortRange { start: 8000, end: 8999 }, 9000, false)]
+    fn port_range_contains(#[case] range: PortRange, #[case] port: u16, #[case] expected: bool) {
+        assert_eq!(range.contains(port), expected);
+    }
+
+    #[rstest]
+    #[case("*.internal.corp", "api.internal.corp", true)]
+    #[case("*.internal.corp", "v1.api.internal.corp", true)]
+    #[case("*.internal.corp", "internal.corp", false)]
+    #[case("*.internal.corp", "notinternal.corp", false)]
+    #[case("**.internal.corp", "internal.corp", true)]
+    #[case("**.internal.corp", "api.internal.corp", true)]
+    #[case("**.internal.corp", "notinternal.corp", false)]
+    #[case("internal.corp", "api.internal.corp", false)]
+    #[case("internal.corp", "Internal.Corp.", true)]
+    #[case("*.bücher.example", "shop.xn--bcher-kva.example", true)]
+    #[case("*.xn--bcher-kva.example", "shop.BÜCHER.example", true)]
+    #[case("**.bücher.example", "bücher.example", true)]
+    #[case("*.bücher.example", "xn--bcher-kva.example", false)]
+    fn name_pattern_matches(#[case] pattern: &str, #[case] name: &str, #[case] expected: bool) {
+        let pattern = pattern.parse::<NamePattern>().unwrap();
+        assert_eq!(pattern.matches(name), expected, "{pattern:?} {name}");
+    }
 }
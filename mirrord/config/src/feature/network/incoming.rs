@@ -1,7 +1,11 @@
-use std::{collections::HashSet, fmt, ops::Not, str::FromStr};
+use std::{collections::HashSet, fmt, net::IpAddr, ops::Not, str::FromStr};
 
+use auto_downgrade::AutoDowngradeConfig;
 use bimap::BiMap;
+use ipnet::IpNet;
+use masking::MaskingConfig;
 use mirrord_analytics::{AnalyticValue, Analytics, CollectAnalytics};
+use mirrord_protocol::tcp::SourceCidr;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize, de, ser, ser::SerializeSeq as _};
 use thiserror::Error;
@@ -15,7 +19,9 @@ use crate::{
     util::{MirrordToggleableConfig, ToggleableConfig},
 };
 
+pub mod auto_downgrade;
 pub mod http_filter;
+pub mod masking;
 pub mod tls_delivery;
 
 use http_filter::*;
@@ -127,6 +133,7 @@ impl MirrordConfig for IncomingFileConfig {
                     .listen_ports
                     .map(|m| m.into_iter().collect())
                     .unwrap_or_default(),
+                bind_any_maps_remote: advanced.bind_any_maps_remote.unwrap_or_default(),
                 on_concurrent_steal: FromEnv::new("MIRRORD_OPERATOR_ON_CONCURRENT_STEAL")
                     .or(advanced.on_concurrent_steal)
                     .layer(|layer| Unstable::new("incoming", "on_concurrent_steal", layer))
@@ -136,6 +143,16 @@ impl MirrordConfig for IncomingFileConfig {
                 ports: advanced.ports.map(|ports| ports.into_iter().collect()),
                 https_delivery: advanced.https_delivery,
                 tls_delivery: advanced.tls_delivery,
+                masking: advanced.masking.unwrap_or_default(),
+                on_local_unavailable: advanced.on_local_unavailable.unwrap_or_default(),
+                source_ip_delivery: advanced.source_ip_delivery.unwrap_or_default(),
+                delivery_source: advanced.delivery_source,
+                source_filter: advanced.source_filter,
+                auto_downgrade: advanced.auto_downgrade,
+                limit_to_target_container: advanced.limit_to_target_container.unwrap_or_default(),
+                strict_ports: advanced.strict_ports.unwrap_or_default(),
+                unix_sockets: advanced.unix_sockets.unwrap_or_default(),
+                unix_socket_mapping: advanced.unix_socket_mapping.unwrap_or_default(),
             },
         };
 
@@ -253,7 +270,7 @@ pub struct IncomingAdvancedFileConfig {
 
     /// ### HTTP Filter
     ///
-    /// Sets up the HTTP traffic filter (currently, only useful when `incoming: steal`).
+    /// Sets up the HTTP traffic filter, used both when stealing and when mirroring traffic.
     ///
     /// See [`filter`](##filter) for details.
     pub http_filter: Option<ToggleableConfig<http_filter::HttpFilterFileConfig>>,
@@ -296,6 +313,15 @@ pub struct IncomingAdvancedFileConfig {
     /// The value of `port_mapping` doesn't affect this.
     pub listen_ports: Option<Vec<(u16, u16)>>,
 
+    /// ### bind_any_maps_remote
+    ///
+    /// Remote ports for listeners that bind port `0` (letting the OS pick the port).
+    ///
+    /// Each listener that binds port `0` gets the next remote port from this list that is not
+    /// yet used by another listener, while it keeps listening locally on the port picked by the
+    /// OS.
+    pub bind_any_maps_remote: Option<Vec<u16>>,
+
     /// ### on_concurrent_steal
     ///
     /// (Operator Only): if value of override will force close any other connections on requested
@@ -319,6 +345,59 @@ pub struct IncomingAdvancedFileConfig {
     /// (Operator Only): configures how mirrord delivers stolen TLS traffic
     /// to the local application.
     pub tls_delivery: Option<LocalTlsDelivery>,
+
+    /// ### masking
+    ///
+    /// Masks headers and JSON body fields of the incoming traffic before it is shown or stored
+    /// locally.
+    pub masking: Option<MaskingConfig>,
+
+    /// ### on_local_unavailable
+    ///
+    /// What mirrord does with stolen traffic when the local application is not listening on the
+    /// port yet.
+    pub on_local_unavailable: Option<OnLocalUnavailable>,
+
+    /// ### source_ip_delivery
+    ///
+    /// How mirrord passes the original client address of stolen traffic to the local
+    /// application.
+    pub source_ip_delivery: Option<SourceIpDelivery>,
+
+    /// ### delivery_source
+    ///
+    /// Local address that stolen traffic is delivered to the local application from.
+    pub delivery_source: Option<IpAddr>,
+
+    /// ### source_filter
+    ///
+    /// Only steal connections coming from these source address ranges (CIDRs).
+    pub source_filter: Option<Vec<String>>,
+
+    /// ### auto_downgrade
+    ///
+    /// Stop stealing when the local application keeps failing to handle the stolen traffic.
+    pub auto_downgrade: Option<AutoDowngradeConfig>,
+
+    /// ### limit_to_target_container
+    ///
+    /// Never redirect traffic to ports declared by the other containers of the target pod.
+    pub limit_to_target_container: Option<bool>,
+
+    /// ### strict_ports
+    ///
+    /// Fail instead of warning when a port is not declared by the target container.
+    pub strict_ports: Option<bool>,
+
+    /// ### unix_sockets
+    ///
+    /// Paths of unix domain sockets in the target to steal connections from.
+    pub unix_sockets: Option<Vec<String>>,
+
+    /// ### unix_socket_mapping
+    ///
+    /// Mapping for local unix socket paths to remote unix socket paths.
+    pub unix_socket_mapping: Option<Vec<(String, String)>>,
 }
 
 fn serialize_bi_map<S>(map: &BiMap<u16, u16>, serializer: S) -> Result<S::Ok, S::Error>
@@ -473,6 +552,33 @@ pub struct IncomingConfig {
     )]
     pub listen_ports: BiMap<u16, u16>,
 
+    /// ##### feature.network.incoming.bind_any_maps_remote {#feature-network-incoming-bind_any_maps_remote}
+    ///
+    /// Remote ports for listeners that bind port `0` (letting the OS pick the port).
+    ///
+    /// Useful when the application binds port `0` and reports the port it got to some service
+    /// discovery, so the remote port can't be configured with
+    /// [`feature.network.incoming.port_mapping`](#feature-network-incoming-port_mapping) upfront.
+    ///
+    /// Each listener that binds port `0` gets the next remote port from this list that is not
+    /// yet used by another listener, while it keeps listening locally on the port picked by the
+    /// OS (this is also the port the application sees in `getsockname`). When all the ports are
+    /// taken, the listener remains local.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "steal",
+    ///         "bind_any_maps_remote": [80, 8080]
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub bind_any_maps_remote: Vec<u16>,
+
     /// ##### feature.network.incoming.on_concurrent_steal {#feature-network-incoming-on_concurrent_steal}
     pub on_concurrent_steal: ConcurrentSteal,
 
@@ -496,6 +602,231 @@ pub struct IncomingConfig {
     /// (Operator Only): configures how mirrord delivers stolen TLS traffic
     /// to the local application.
     pub tls_delivery: Option<LocalTlsDelivery>,
+
+    /// ##### feature.network.incoming.masking {#feature-network-incoming-masking}
+    ///
+    /// Masks configured headers and JSON body fields of the incoming traffic before mirrord
+    /// shows or stores it locally, e.g. in the `mirrord dump` output.
+    ///
+    /// The traffic delivered to the local application is not masked.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "masking": {
+    ///           "json_fields": ["$.user.email", "$.cards[*].number"],
+    ///           "headers": ["authorization"],
+    ///           "replacement": "hash"
+    ///         }
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub masking: MaskingConfig,
+
+    /// ##### feature.network.incoming.on_local_unavailable {#feature-network-incoming-on_local_unavailable}
+    ///
+    /// What mirrord does with a stolen connection or request when the local application is not
+    /// listening on the port yet, e.g. because it is restarting.
+    ///
+    /// Can be set to either `"reset"` (default), `"hold"` or `"reject_http_503"`.
+    ///
+    /// - `"reset"`: The stolen connection is closed, and the stolen HTTP request gets a `502`
+    ///   response.
+    /// - `"hold"`: mirrord keeps the remote connection open and retries until the application
+    ///   starts listening, for at most 30 seconds, buffering at most 1 MiB of data.
+    /// - `"reject_http_503"`: The stolen HTTP request gets a `503` response with a `Retry-After`
+    ///   header. Connections stolen in whole are closed, as with `"reset"`.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "steal",
+    ///         "on_local_unavailable": "hold"
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub on_local_unavailable: OnLocalUnavailable,
+
+    /// ##### feature.network.incoming.source_ip_delivery {#feature-network-incoming-source_ip_delivery}
+    ///
+    /// How mirrord passes the original client address of stolen traffic to the local application,
+    /// which otherwise sees the connections coming from mirrord.
+    ///
+    /// Can be set to either `"none"` (default), `"proxy_protocol"` or `"x_forwarded_for"`.
+    ///
+    /// - `"none"`: The original client address is not passed.
+    /// - `"proxy_protocol"`: Each connection stolen in whole starts with a [PROXY protocol v2](https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt)
+    ///   header. Requests stolen with an HTTP filter are not affected.
+    /// - `"x_forwarded_for"`: The client IP is appended to the `X-Forwarded-For` header of each
+    ///   request stolen with an HTTP filter. Connections stolen in whole are not affected.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "steal",
+    ///         "source_ip_delivery": "proxy_protocol"
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub source_ip_delivery: SourceIpDelivery,
+
+    /// ##### feature.network.incoming.delivery_source {#feature-network-incoming-delivery_source}
+    ///
+    /// Local address that the connections stolen from the remote target are made from, when
+    /// delivered to the local application. Useful when the local application only accepts
+    /// connections from a specific interface.
+    ///
+    /// Must be an address of one of the local interfaces. Ignored for the ports that the local
+    /// application listens on with a different IP family. When not set, mirrord connects from the
+    /// address that the local application listens on, or from localhost.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "steal",
+    ///         "delivery_source": "127.0.0.2"
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub delivery_source: Option<IpAddr>,
+
+    /// ##### feature.network.incoming.source_filter {#feature-network-incoming-source_filter}
+    ///
+    /// Only steal connections coming from these source address ranges, in CIDR notation. Plain IPs
+    /// are treated as single address ranges. Both IPv4 and IPv6 ranges are supported.
+    ///
+    /// Connections coming from other addresses are passed through to their original destination,
+    /// as if mirrord was not running. Applies only to the steal mode, and is ignored by agents
+    /// that do not support it.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "steal",
+    ///         "source_filter": ["10.12.0.0/16", "fd00::/8"]
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub source_filter: Option<Vec<String>>,
+
+    /// ##### feature.network.incoming.auto_downgrade {#feature-network-incoming-auto_downgrade}
+    ///
+    /// Stops stealing when the local application keeps failing to handle the stolen traffic.
+    ///
+    /// If more than `errors` (default `20`) stolen connections or requests fail to be delivered
+    /// to the local application within `window_secs` (default `30`) seconds, mirrord unsubscribes
+    /// from stealing the ports, and prints a warning. With `mirror` (default `true`), the ports
+    /// are mirrored instead.
+    ///
+    /// Run `mirrord session resume-steal`, or send `SIGUSR1` to the mirrord internal proxy process
+    /// (its PID is printed in the warning), to restore stealing.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "steal",
+    ///         "auto_downgrade": { "errors": 20, "window_secs": 30, "mirror": true }
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub auto_downgrade: Option<AutoDowngradeConfig>,
+
+    /// ##### feature.network.incoming.limit_to_target_container {#feature-network-incoming-limit_to_target_container}
+    ///
+    /// Containers of a pod share the network namespace, so by default mirrord can capture traffic
+    /// meant for any of them, e.g. the admin port of a sidecar that happens to be on the list of
+    /// [`ports`](#feature-network-incoming-ports).
+    ///
+    /// When set, the agent never redirects traffic to ports that are declared (`containerPort`)
+    /// by the other containers of the target pod, but not by the target container itself.
+    /// Has no effect when targetless, or when the agent is created by the mirrord operator.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "steal",
+    ///         "limit_to_target_container": true
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub limit_to_target_container: bool,
+
+    /// ##### feature.network.incoming.strict_ports {#feature-network-incoming-strict_ports}
+    ///
+    /// mirrord warns when some of the [`ports`](#feature-network-incoming-ports) (or the
+    /// [`http_filter.ports`](#feature-network-incoming-http_filter-ports)) are not declared
+    /// (`containerPort`) by the target container. When set, mirrord fails to start instead.
+    ///
+    /// The ports are not checked when the target container declares no ports at all, or when
+    /// the agent is created by the mirrord operator.
+    ///
+    /// Defaults to `false`.
+    pub strict_ports: bool,
+
+    /// ##### feature.network.incoming.unix_sockets {#feature-network-incoming-unix_sockets}
+    ///
+    /// Paths of unix domain sockets in the target container to steal connections from, e.g. when
+    /// the target serves its API over `/var/run/app.sock`. The paths have to be absolute.
+    ///
+    /// The agent binds its own socket in place of the original one for the duration of the
+    /// session, and restores the original socket file when the session ends. Stolen connections
+    /// are delivered to the local unix socket listening at the same path, or at the path given
+    /// in [`unix_socket_mapping`](#feature-network-incoming-unix_socket_mapping).
+    ///
+    /// Applies only to the steal mode.
+    ///
+    /// ```json
+    /// {
+    ///   "feature": {
+    ///     "network": {
+    ///       "incoming": {
+    ///         "mode": "steal",
+    ///         "unix_sockets": ["/var/run/app.sock"]
+    ///       }
+    ///     }
+    ///   }
+    /// }
+    /// ```
+    pub unix_sockets: Vec<String>,
+
+    /// ##### feature.network.incoming.unix_socket_mapping {#feature-network-incoming-unix_socket_mapping}
+    ///
+    /// Mapping for local unix socket paths to remote unix socket paths.
+    ///
+    /// This is useful when your local application listens on a different path than the target.
+    /// For example, your local process listens on `/tmp/app.sock` and the target listens on
+    /// `/var/run/app.sock`. You'd use `[["/tmp/app.sock", "/var/run/app.sock"]]`.
+    pub unix_socket_mapping: Vec<(String, String)>,
 }
 
 impl IncomingConfig {
@@ -530,6 +861,42 @@ impl IncomingConfig {
             }
         }
     }
+
+    /// <!--${internal}-->
+    /// Returns the local path to which connections stolen from the remote unix socket at the
+    /// given `remote` path should be delivered, see
+    /// [`IncomingConfig::unix_socket_mapping`].
+    pub fn unix_socket_local_path<'a>(&'a self, remote: &'a str) -> &'a str {
+        self.unix_socket_mapping
+            .iter()
+            .find(|(_, mapped)| mapped == remote)
+            .map(|(local, _)| local.as_str())
+            .unwrap_or(remote)
+    }
+
+    /// Parses [`IncomingConfig::source_filter`] into [`SourceCidr`]s.
+    ///
+    /// Plain IPs are turned into single address CIDRs. Fails on the first value that is neither.
+    pub fn source_cidrs(&self) -> Result<Vec<SourceCidr>> {
+        self.source_filter
+            .iter()
+            .flatten()
+            .map(|value| {
+                value
+                    .parse::<IpNet>()
+                    .or_else(|_| value.parse::<IpAddr>().map(IpNet::from))
+                    .map(|net| SourceCidr {
+                        address: net.network(),
+                        prefix_len: net.prefix_len(),
+                    })
+                    .map_err(|error| ConfigError::InvalidValue {
+                        name: "feature.network.incoming.source_filter",
+                        provided: value.clone(),
+                        error: error.into(),
+                    })
+            })
+            .collect()
+    }
 }
 
 /// Allows selecting between mirroring or stealing traffic.
@@ -634,6 +1001,57 @@ pub enum ConcurrentSteal {
     Abort,
 }
 
+/// What mirrord does with stolen traffic when the local application is not listening on the port.
+///
+/// Can be set to either `"reset"` (default), `"hold"` or `"reject_http_503"`.
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub enum OnLocalUnavailable {
+    /// <!--${internal}-->
+    /// ### reset
+    ///
+    /// Close the stolen connection, respond to the stolen HTTP request with `502`.
+    #[default]
+    Reset,
+    /// <!--${internal}-->
+    /// ### hold
+    ///
+    /// Keep the remote connection open until the application starts listening, bounded by time
+    /// and buffered bytes.
+    Hold,
+    /// <!--${internal}-->
+    /// ### reject_http_503
+    ///
+    /// Respond to the stolen HTTP request with `503` and a `Retry-After` header.
+    #[serde(rename = "reject_http_503")]
+    RejectHttp503,
+}
+
+/// How mirrord passes the original client address of stolen traffic to the local application.
+///
+/// Can be set to either `"none"` (default), `"proxy_protocol"` or `"x_forwarded_for"`.
+#[derive(Default, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(deny_unknown_fields, rename_all = "snake_case")]
+pub enum SourceIpDelivery {
+    /// <!--${internal}-->
+    /// ### none
+    ///
+    /// Do not pass the client address.
+    #[default]
+    None,
+    /// <!--${internal}-->
+    /// ### proxy_protocol
+    ///
+    /// Start each connection stolen in whole with a PROXY protocol v2 header.
+    ProxyProtocol,
+    /// <!--${internal}-->
+    /// ### x_forwarded_for
+    ///
+    /// Append the client IP to the `X-Forwarded-For` header of each request stolen with an HTTP
+    /// filter.
+    XForwardedFor,
+}
+
 #[derive(Error, Debug)]
 #[error("could not parse ConcurrentSteal from string, values continue/override")]
 pub struct ConcurrentStealParseError;
@@ -687,8 +1105,26 @@ impl CollectAnalytics for &IncomingConfig {
         analytics.add("concurrent_steal", &self.on_concurrent_steal);
         analytics.add("port_mapping_count", self.port_mapping.len());
         analytics.add("listen_ports_count", self.listen_ports.len());
+        analytics.add(
+            "bind_any_maps_remote_count",
+            self.bind_any_maps_remote.len(),
+        );
         analytics.add("ignore_localhost", self.ignore_localhost);
         analytics.add("ignore_ports_count", self.ignore_ports.len());
         analytics.add("http", &self.http_filter);
+        analytics.add("masked_json_fields_count", self.masking.json_fields.len());
+        analytics.add("masked_headers_count", self.masking.headers.len());
+        analytics.add(
+            "source_filter_count",
+            self.source_filter
+                .as_ref()
+                .map(Vec::len)
+                .unwrap_or_default(),
+        );
+        analytics.add("delivery_source", self.delivery_source.is_some());
+        analytics.add("auto_downgrade", self.auto_downgrade.is_some());
+        analytics.add("limit_to_target_container", self.limit_to_target_container);
+        analytics.add("strict_ports", self.strict_ports);
+        analytics.add("unix_sockets_count", self.unix_sockets.len());
     }
 }
@@ -0,0 +1,86 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ConfigError;
+
+/// Stops stealing when the local application keeps failing to handle the stolen traffic.
+///
+/// If more than `errors` stolen connections or requests fail to be delivered to the local
+/// application within `window_secs` seconds, mirrord unsubscribes from stealing the ports and
+/// (with `mirror` enabled) mirrors them instead. Stealing can be restored with
+/// `mirrord session resume-steal`, or by sending `SIGUSR1` to the mirrord internal proxy process,
+/// whose PID is printed in the warning.
+///
+/// ```json
+/// {
+///   "errors": 20,
+///   "window_secs": 30,
+///   "mirror": true
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, JsonSchema, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct AutoDowngradeConfig {
+    /// ##### feature.network.incoming.auto_downgrade.errors {#feature-network-incoming-auto_downgrade-errors}
+    ///
+    /// How many failed deliveries within the window are tolerated, defaults to `20`.
+    #[serde(default = "default_errors")]
+    pub errors: u32,
+
+    /// ##### feature.network.incoming.auto_downgrade.window_secs {#feature-network-incoming-auto_downgrade-window_secs}
+    ///
+    /// Length of the window in seconds, defaults to `30`.
+    #[serde(default = "default_window_secs")]
+    pub window_secs: u64,
+
+    /// ##### feature.network.incoming.auto_downgrade.mirror {#feature-network-incoming-auto_downgrade-mirror}
+    ///
+    /// Whether the ports are mirrored after stealing stops, defaults to `true`.
+    #[serde(default = "default_mirror")]
+    pub mirror: bool,
+}
+
+impl AutoDowngradeConfig {
+    /// Checks that the thresholds are not zero.
+    pub fn verify(&self) -> Result<(), ConfigError> {
+        if self.errors == 0 {
+            return Err(ConfigError::InvalidValue {
+                name: "feature.network.incoming.auto_downgrade.errors",
+                provided: self.errors.to_string(),
+                error: "the value has to be greater than 0".into(),
+            });
+        }
+
+        if self.window_secs == 0 {
+            return Err(ConfigError::InvalidValue {
+                name: "feature.network.incoming.auto_downgrade.window_secs",
+                provided: self.window_secs.to_string(),
+                error: "the value has to be greater than 0".into(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for AutoDowngradeConfig {
+    fn default() -> Self {
+        Self {
+            errors: default_errors(),
+            window_secs: default_window_secs(),
+            mirror: default_mirror(),
+        }
+    }
+}
+
+fn default_errors() -> u32 {
+    20
+}
+
+fn default_window_secs() -> u64 {
+    30
+}
+
+fn default_mirror() -> bool {
+    true
+}
@@ -4,8 +4,8 @@ use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
 use mirrord_protocol::tcp::{
     Filter, HTTP_BODY_JSON_FILTER_VERSION, HTTP_COMPOSITE_FILTER_VERSION,
-    HTTP_HEADER_JQ_FILTER_VERSION, HTTP_METHOD_FILTER_VERSION, HttpBodyFilter, HttpFilter,
-    HttpMethodFilter, JqQuery, JsonPathQuery,
+    HTTP_HEADER_JQ_FILTER_VERSION, HTTP_METHOD_FILTER_VERSION, HTTP_NAMED_HEADER_FILTER_VERSION,
+    HeaderCondition, HttpBodyFilter, HttpFilter, HttpMethodFilter, JqQuery, JsonPathQuery,
 };
 use schemars::JsonSchema;
 use semver::{Version, VersionReq};
@@ -23,8 +23,9 @@ use crate::{
 /// feature only captures HTTP requests that match the specified filter, forwarding unmatched
 /// requests to their original destinations.
 ///
-/// Only does something when [`feature.network.incoming.mode`](#feature-network-incoming-mode) is
-/// set as `"steal"`, ignored otherwise.
+/// When [`feature.network.incoming.mode`](#feature-network-incoming-mode) is set as `"mirror"`,
+/// only the matching requests are mirrored, while the remote application still receives all of
+/// them.
 ///
 /// For example, to filter based on header:
 /// ```json
@@ -77,6 +78,17 @@ use crate::{
 /// }
 /// ```
 ///
+/// With `header_name`, you can match a single header by its name, and with `negate`, you can steal
+/// requests that **don't** have the header (or don't have a matching value). For example, this
+/// filter steals only HTTP requests without the `x-tenant` header.
+/// ```json
+/// {
+///   "all_of": [
+///     { "header_name": "x-tenant", "negate": true }
+///   ]
+/// }
+/// ```
+///
 /// If you want to steal HTTP requests that match **any** of the patterns specified, use `any_of`.
 /// For example, this filter steals HTTP requests to endpoint `/api/my-endpoint`
 /// **and** HTTP requests that contain header `x-debug-session` with value `121212`.
@@ -184,6 +196,17 @@ pub struct HttpFilterConfig {
     /// absent, filtering will be done for all ports.
     #[config(env = "MIRRORD_HTTP_FILTER_PORTS")]
     pub ports: Option<VecOrSingle<u16>>,
+
+    /// ##### feature.network.incoming.http_filter.max_pending_requests {#feature-network-incoming-http_filter-max_pending_requests}
+    ///
+    /// Maximum number of requests from a single stolen connection that the agent keeps while
+    /// waiting for their responses.
+    ///
+    /// When the limit is reached, the agent stops reading from the connection until one of the
+    /// pending requests gets a response. When absent, the number is not limited.
+    ///
+    /// Must be greater than 0.
+    pub max_pending_requests: Option<u32>,
 }
 
 impl HttpFilterConfig {
@@ -202,7 +225,7 @@ impl HttpFilterConfig {
         agent_protocol_version: Option<Version>,
     ) -> Result<(), ConfigError> {
         #![allow(clippy::type_complexity)]
-        static REQUIREMENTS: [(fn(&HttpFilterConfig) -> bool, &LazyLock<VersionReq>, &str); 4] = [
+        static REQUIREMENTS: [(fn(&HttpFilterConfig) -> bool, &LazyLock<VersionReq>, &str); 5] = [
             (
                 HttpFilterConfig::is_composite,
                 &HTTP_COMPOSITE_FILTER_VERSION,
@@ -223,6 +246,11 @@ impl HttpFilterConfig {
                 &HTTP_HEADER_JQ_FILTER_VERSION,
                 "JQ header filters",
             ),
+            (
+                HttpFilterConfig::has_named_header_filter,
+                &HTTP_NAMED_HEADER_FILTER_VERSION,
+                "'header_name' HTTP filter type",
+            ),
         ];
 
         for (validator, version, what) in REQUIREMENTS {
@@ -276,6 +304,14 @@ impl HttpFilterConfig {
             })
     }
 
+    fn has_named_header_filter(&self) -> bool {
+        [self.all_of.as_ref(), self.any_of.as_ref()]
+            .into_iter()
+            .flatten()
+            .flatten()
+            .any(|f| matches!(f, InnerFilter::NamedHeader { .. }))
+    }
+
     fn has_json_body_filter(&self) -> bool {
         matches!(self.body_filter, Some(BodyFilter::Json { .. }))
             || self.all_of.as_ref().is_some_and(|composite| {
@@ -318,6 +354,7 @@ impl HttpFilterConfig {
                 all_of: None,
                 any_of: None,
                 ports: _,
+                max_pending_requests: _,
             } => Ok(HttpFilter::Path(Filter::new(path.into())?)),
 
             HttpFilterConfig {
@@ -329,6 +366,7 @@ impl HttpFilterConfig {
                 all_of: None,
                 any_of: None,
                 ports: _,
+                max_pending_requests: _,
             } => Ok(HttpFilter::Header(Filter::new(header.into())?)),
 
             HttpFilterConfig {
@@ -340,6 +378,7 @@ impl HttpFilterConfig {
                 all_of: None,
                 any_of: None,
                 ports: _,
+                max_pending_requests: _,
             } => Ok(HttpFilter::Method(HttpMethodFilter::from_str(method)?)),
 
             HttpFilterConfig {
@@ -351,6 +390,7 @@ impl HttpFilterConfig {
                 all_of: None,
                 any_of: None,
                 ports: _,
+                max_pending_requests: _,
             } => Ok(HttpFilter::Body(filter.as_protocol_http_body_filter()?)),
 
             HttpFilterConfig {
@@ -362,6 +402,7 @@ impl HttpFilterConfig {
                 all_of: None,
                 any_of: None,
                 ports: _,
+                max_pending_requests: _,
             } => Ok(HttpFilter::HeaderJq(
                 JqQuery::new(filter).map_err(HttpFilterParseError::Jq)?,
             )),
@@ -375,6 +416,7 @@ impl HttpFilterConfig {
                 all_of: Some(filters),
                 any_of: None,
                 ports: _,
+                max_pending_requests: _,
             } => Self::make_composite_filter(true, filters),
 
             HttpFilterConfig {
@@ -386,6 +428,7 @@ impl HttpFilterConfig {
                 all_of: None,
                 any_of: Some(filters),
                 ports: _,
+                max_pending_requests: _,
             } => Self::make_composite_filter(false, filters),
 
             _ => panic!("No HTTP filters specified, this should have been caught earlier"),
@@ -412,6 +455,15 @@ impl HttpFilterConfig {
                 InnerFilter::HeaderJq { query } => Ok(HttpFilter::HeaderJq(
                     JqQuery::new(query).map_err(HttpFilterParseError::Jq)?,
                 )),
+                InnerFilter::NamedHeader {
+                    header_name,
+                    value,
+                    negate,
+                } => Ok(HttpFilter::NamedHeader(HeaderCondition {
+                    name: header_name.clone(),
+                    value: value.clone().map(Filter::new).transpose()?,
+                    negate: *negate,
+                })),
             })
             .collect::<Result<Vec<_>, HttpFilterParseError>>()?;
 
@@ -465,6 +517,33 @@ pub enum InnerFilter {
     HeaderJq {
         query: String,
     },
+
+    /// ##### feature.network.incoming.inner_filter.named_header {#feature-network-incoming-inner-named-header}
+    ///
+    /// Matches a single header by its name (case-insensitive).
+    ///
+    /// - `value` is an optional regex, matched against the values of the header. Supports regexes
+    ///   validated by the [`fancy-regex`](https://docs.rs/fancy-regex/latest/fancy_regex/) crate.
+    ///   When absent, the header only has to be present.
+    /// - `negate` inverts the filter, so that it matches requests that don't have the header, or
+    ///   don't have a matching value.
+    ///
+    /// Example:
+    /// ```json
+    /// {
+    ///   "any_of": [
+    ///     { "header_name": "x-tenant", "negate": true },
+    ///     { "header_name": "x-tenant", "value": "^my-team$" }
+    ///   ]
+    /// }
+    /// ```
+    NamedHeader {
+        header_name: String,
+        #[serde(default)]
+        value: Option<String>,
+        #[serde(default)]
+        negate: bool,
+    },
 }
 
 /// Currently only JSON body filtering is supported.
@@ -615,6 +694,7 @@ impl MirrordToggleableConfig for HttpFilterFileConfig {
             all_of,
             any_of,
             ports,
+            max_pending_requests: None,
         })
     }
 }
@@ -624,6 +704,7 @@ impl CollectAnalytics for &HttpFilterConfig {
         analytics.add("header_filter", self.header_filter.is_some());
         analytics.add("path_filter", self.path_filter.is_some());
         analytics.add("ports", self.count_filtered_ports());
+        analytics.add("max_pending_requests", self.max_pending_requests.is_some());
     }
 }
 
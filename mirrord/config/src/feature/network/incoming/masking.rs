@@ -0,0 +1,228 @@
+use std::{fmt, str::FromStr};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::config::ConfigError;
+
+/// Masks sensitive data of the incoming traffic before mirrord shows or stores it locally (e.g. in
+/// the `mirrord dump` output).
+///
+/// The traffic delivered to the local application is **not** masked.
+///
+/// ```json
+/// {
+///   "json_fields": ["$.user.email", "$.cards[*].number"],
+///   "headers": ["authorization", "cookie"],
+///   "replacement": { "token": "<masked>" }
+/// }
+/// ```
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct MaskingConfig {
+    /// ##### feature.network.incoming.masking.json_fields {#feature-network-incoming-masking-json_fields}
+    ///
+    /// Selectors of the JSON body fields to mask, e.g. `$.user.email`.
+    ///
+    /// Selectors start with `$` (the whole body), followed by any number of:
+    ///
+    /// - `.name` or `['name']` - a field of an object;
+    /// - `[3]` - an element of an array;
+    /// - `.*` or `[*]` - any field or element.
+    ///
+    /// Only bodies with a JSON `content-type` are masked, other bodies are left as they are.
+    #[serde(default)]
+    pub json_fields: Vec<String>,
+
+    /// ##### feature.network.incoming.masking.headers {#feature-network-incoming-masking-headers}
+    ///
+    /// Names of the headers to mask (case insensitive).
+    #[serde(default)]
+    pub headers: Vec<String>,
+
+    /// ##### feature.network.incoming.masking.replacement {#feature-network-incoming-masking-replacement}
+    ///
+    /// What masked values are replaced with, either `"hash"` (default), which keeps equal values
+    /// recognizable, or `{ "token": "<masked>" }`.
+    #[serde(default)]
+    pub replacement: MaskReplacement,
+}
+
+impl MaskingConfig {
+    /// Parses [`MaskingConfig::json_fields`].
+    pub fn json_selectors(&self) -> Result<Vec<JsonFieldSelector>, ConfigError> {
+        self.json_fields
+            .iter()
+            .map(|selector| {
+                selector.parse().map_err(|error: JsonFieldSelectorError| {
+                    ConfigError::InvalidValue {
+                        name: "feature.network.incoming.masking.json_fields",
+                        provided: selector.clone(),
+                        error: error.into(),
+                    }
+                })
+            })
+            .collect()
+    }
+}
+
+/// What masked values are replaced with, see [`MaskingConfig::replacement`].
+#[derive(Deserialize, Serialize, Clone, Debug, JsonSchema, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields, rename_all = "lowercase")]
+pub enum MaskReplacement {
+    /// A hash of the original value.
+    #[default]
+    Hash,
+    /// A fixed token.
+    Token(String),
+}
+
+/// One step of a [`JsonFieldSelector`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum JsonSelectorSegment {
+    /// `.name` or `['name']`
+    Field(String),
+    /// `[3]`
+    Index(usize),
+    /// `.*` or `[*]`
+    Any,
+}
+
+/// Parsed selector from [`MaskingConfig::json_fields`], e.g. `$.cards[*].number`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct JsonFieldSelector(pub Vec<JsonSelectorSegment>);
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum JsonFieldSelectorError {
+    #[error("selector must start with `$`")]
+    MissingRoot,
+
+    #[error("unexpected `{0}` at position {1}")]
+    Unexpected(char, usize),
+
+    #[error("selector ended unexpectedly")]
+    UnexpectedEnd,
+}
+
+impl FromStr for JsonFieldSelector {
+    type Err = JsonFieldSelectorError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let rest = value
+            .strip_prefix('$')
+            .ok_or(JsonFieldSelectorError::MissingRoot)?;
+        let mut chars = rest.char_indices().peekable();
+        let mut segments = Vec::new();
+
+        // Positions are reported relative to the whole selector.
+        let position = |index: usize| index + 1;
+
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '.' => {
+                    let mut name = String::new();
+                    while let Some((_, c)) = chars.next_if(|(_, c)| !matches!(c, '.' | '[')) {
+                        name.push(c);
+                    }
+
+                    match name.as_str() {
+                        "" => match chars.peek() {
+                            Some(&(index, c)) => {
+                                return Err(JsonFieldSelectorError::Unexpected(c, position(index)));
+                            }
+                            None => return Err(JsonFieldSelectorError::UnexpectedEnd),
+                        },
+                        "*" => segments.push(JsonSelectorSegment::Any),
+                        _ => segments.push(JsonSelectorSegment::Field(name)),
+                    }
+                }
+                '[' => {
+                    let segment = match chars.next() {
+                        Some((_, quote @ ('\'' | '"'))) => {
+                            let mut name = String::new();
+                            loop {
+                                match chars.next() {
+                                    Some((_, c)) if c == quote => break,
+                                    Some((_, c)) => name.push(c),
+                                    None => return Err(JsonFieldSelectorError::UnexpectedEnd),
+                                }
+                            }
+                            JsonSelectorSegment::Field(name)
+                        }
+                        Some((_, '*')) => JsonSelectorSegment::Any,
+                        Some((index, c)) if c.is_ascii_digit() => {
+                            let mut digits = String::from(c);
+                            while let Some((_, c)) = chars.next_if(|(_, c)| c.is_ascii_digit()) {
+                                digits.push(c);
+                            }
+                            let index = digits.parse().map_err(|_| {
+                                JsonFieldSelectorError::Unexpected(c, position(index))
+                            })?;
+                            JsonSelectorSegment::Index(index)
+                        }
+                        Some((index, c)) => {
+                            return Err(JsonFieldSelectorError::Unexpected(c, position(index)));
+                        }
+                        None => return Err(JsonFieldSelectorError::UnexpectedEnd),
+                    };
+
+                    match chars.next() {
+                        Some((_, ']')) => segments.push(segment),
+                        Some((index, c)) => {
+                            return Err(JsonFieldSelectorError::Unexpected(c, position(index)));
+                        }
+                        None => return Err(JsonFieldSelectorError::UnexpectedEnd),
+                    }
+                }
+                other => return Err(JsonFieldSelectorError::Unexpected(other, position(index))),
+            }
+        }
+
+        Ok(Self(segments))
+    }
+}
+
+impl fmt::Display for JsonFieldSelector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("$")?;
+        for segment in &self.0 {
+            match segment {
+                JsonSelectorSegment::Field(name) => write!(f, "['{name}']")?,
+                JsonSelectorSegment::Index(index) => write!(f, "[{index}]")?,
+                JsonSelectorSegment::Any => f.write_str("[*]")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::{JsonFieldSelector, JsonFieldSelectorError, JsonSelectorSegment::*};
+
+    #[rstest]
+    #[case("$", vec![])]
+    #[case("$.user.email", vec![Field("user".into()), Field("email".into())])]
+    #[case("$.cards[*].number", vec![Field("cards".into()), Any, Field("number".into())])]
+    #[case("$.items[3]", vec![Field("items".into()), Index(3)])]
+    #[case("$['weird.key'].*", vec![Field("weird.key".into()), Any])]
+    fn parse_selector(#[case] value: &str, #[case] expected: Vec<super::JsonSelectorSegment>) {
+        assert_eq!(
+            value.parse::<JsonFieldSelector>(),
+            Ok(JsonFieldSelector(expected))
+        );
+    }
+
+    #[rstest]
+    #[case("user.email", JsonFieldSelectorError::MissingRoot)]
+    #[case("$.", JsonFieldSelectorError::UnexpectedEnd)]
+    #[case("$.items[3", JsonFieldSelectorError::UnexpectedEnd)]
+    #[case("$user", JsonFieldSelectorError::Unexpected('u', 1))]
+    fn parse_invalid_selector(#[case] value: &str, #[case] expected: JsonFieldSelectorError) {
+        assert_eq!(value.parse::<JsonFieldSelector>(), Err(expected));
+    }
+}
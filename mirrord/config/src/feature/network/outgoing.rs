@@ -5,7 +5,7 @@ use mirrord_config_derive::MirrordConfig;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use super::filter::ProtocolAndAddressFilter;
+use super::filter::{AddressFilter, AddressFilterError, ProtocolAndAddressFilter};
 use crate::{
     config::{ConfigContext, ConfigError, from_env::FromEnv, source::MirrordConfigSource},
     util::{MirrordToggleableConfig, VecOrSingle},
@@ -52,7 +52,15 @@ use crate::{
 /// }
 /// ```
 ///
-/// Valid values follow this pattern: `[protocol]://[name|address|subnet/mask]:[port]`.
+/// - Only UDP traffic on ports `8000` to `8999` (inclusive) will go through the local app.
+/// ```json
+/// {
+///   "local": ["udp://:8000-8999"]
+/// }
+/// ```
+///
+/// Valid values follow this pattern: `[protocol]://[name|address|subnet/mask]:[port]`, where
+/// `port` can be either a single port, or an inclusive range of ports `first-last`.
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug, JsonSchema)]
 #[serde(deny_unknown_fields, rename_all = "lowercase")]
 pub enum OutgoingFilterConfig {
@@ -143,6 +151,9 @@ pub struct OutgoingConfig {
     /// of regexes specified here. If there is a match, mirrord will connect your application with
     /// the target unix socket address on the target pod. Otherwise, it will leave the connection
     /// to happen locally on your machine.
+    ///
+    /// This applies to both `SOCK_STREAM` and `SOCK_SEQPACKET` unix sockets. Boundaries between
+    /// `SOCK_SEQPACKET` messages are preserved, but empty messages are not supported.
     #[config(unstable, env = "MIRRORD_OUTGOING_REMOTE_UNIX_STREAMS")]
     pub unix_streams: Option<VecOrSingle<String>>,
 }
@@ -199,8 +210,14 @@ impl OutgoingConfig {
         };
 
         for filter in filters {
-            let Err(error) = filter.parse::<ProtocolAndAddressFilter>() else {
-                continue;
+            let error = match filter.parse::<ProtocolAndAddressFilter>() {
+                // Names in the outgoing filter are resolved, which is not possible with wildcards.
+                Ok(ProtocolAndAddressFilter {
+                    address: AddressFilter::Name(name, ..),
+                    ..
+                }) if name.contains('*') => AddressFilterError::WildcardNotSupported.into(),
+                Ok(..) => continue,
+                Err(error) => error,
             };
 
             return Err(ConfigError::InvalidValue {
@@ -218,10 +235,11 @@ impl OutgoingConfig {
 mod tests {
     use rstest::rstest;
 
+    use super::{OutgoingConfig, OutgoingFilterConfig};
     use crate::{
-        config::{ConfigContext, MirrordConfig},
+        config::{ConfigContext, ConfigError, MirrordConfig},
         feature::network::OutgoingFileConfig,
-        util::ToggleableConfig,
+        util::{ToggleableConfig, VecOrSingle},
     };
 
     #[rstest]
@@ -269,4 +287,34 @@ mod tests {
         assert_eq!(outgoing.tcp, tcp.1);
         assert_eq!(outgoing.udp, udp.1);
     }
+
+    #[rstest]
+    #[case::single_port("udp://:8125", true)]
+    #[case::port_range("udp://:8000-8999", true)]
+    #[case::subnet_port_range("tcp://1.1.1.0/24:8000-8999", true)]
+    #[case::name_port_range("localhost:4317-4318", true)]
+    #[case::inverted_port_range(":9000-100", false)]
+    #[case::wildcard_name("*.internal.corp", false)]
+    fn verify_filters(#[case] filter: &str, #[case] valid: bool) {
+        let outgoing = OutgoingConfig {
+            filter: Some(OutgoingFilterConfig::Local(VecOrSingle::Single(
+                filter.to_string(),
+            ))),
+            ..Default::default()
+        };
+
+        let result = outgoing.verify(&mut ConfigContext::default());
+
+        if valid {
+            result.unwrap();
+        } else {
+            assert!(matches!(
+                result,
+                Err(ConfigError::InvalidValue {
+                    name: "feature.network.outgoing.filter",
+                    ..
+                })
+            ));
+        }
+    }
 }
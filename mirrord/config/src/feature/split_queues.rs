@@ -14,8 +14,9 @@ pub type QueueId = String;
 /// original queue will be made available to the local application, based on message attributes
 /// or headers, and possibly on jq filters (for SQS).
 ///
-/// The queue-ids have to match those defined in the `MirrordWorkloadQueueRegistry` or
-/// `MirrordKafkaTopicsConsumer` for SQS or Kafka respectively.
+/// The queue-ids have to match those defined in the `MirrordWorkloadQueueRegistry`,
+/// `MirrordKafkaTopicsConsumer` or `MirrordRabbitMqQueuesConsumer` for SQS, Kafka or RabbitMQ
+/// respectively.
 ///
 ///
 /// ```json
@@ -46,6 +47,12 @@ pub type QueueId = String;
 ///           "coolz": "^very"
 ///         }
 ///       },
+///       "fifth-queue": {
+///         "queue_type": "RabbitMQ",
+///         "message_filter": {
+///           "x-tenant": "^metalbear$"
+///         }
+///       },
 ///     }
 ///   }
 /// }
@@ -97,6 +104,14 @@ impl SplitQueuesConfig {
         })
     }
 
+    /// Out of the whole queue splitting config, get only the RabbitMQ queues.
+    pub fn rabbitmq(&self) -> impl '_ + Iterator<Item = (&'_ str, &'_ QueueMessageFilter)> {
+        self.0.iter().filter_map(|(name, filter)| match filter {
+            QueueFilter::RabbitMq { message_filter } => Some((name.as_str(), message_filter)),
+            _ => None,
+        })
+    }
+
     fn verify_message_attribute_filter(
         queue_id: &QueueId,
         filter: &QueueMessageFilter,
@@ -142,7 +157,8 @@ impl SplitQueuesConfig {
                         Self::verify_jq_program(queue_name, jq_filter)?;
                     }
                 }
-                QueueFilter::Kafka { message_filter } => {
+                QueueFilter::Kafka { message_filter }
+                | QueueFilter::RabbitMq { message_filter } => {
                     Self::verify_message_attribute_filter(queue_name, message_filter)?;
                 }
                 QueueFilter::Unknown => {
@@ -183,8 +199,8 @@ pub type QueueMessageFilter = BTreeMap<String, String>;
 ///
 /// ### feature.split_queues.{}.queue_type {#feature-split_queues-queue_id-queue_type}
 ///
-/// The type of queue to be split, currently `SQS` and `Kafka` are supported. More queue types might
-/// be added in the future.
+/// The type of queue to be split, currently `SQS`, `Kafka` and `RabbitMQ` are supported. More queue
+/// types might be added in the future.
 #[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, JsonSchema)]
 #[serde(tag = "queue_type")]
 pub enum QueueFilter {
@@ -232,6 +248,15 @@ pub enum QueueFilter {
         message_filter: QueueMessageFilter,
     },
 
+    #[serde(rename = "RabbitMQ")]
+    RabbitMq {
+        /// A filter is a mapping between message header names and regexes they should match.
+        /// The local application will only receive messages that match **all** of the given
+        /// patterns. This means, only messages that have **all** of the headers in the
+        /// filter, with values of those headers matching the respective patterns.
+        message_filter: QueueMessageFilter,
+    },
+
     // When a newer client sends a new filter kind to an older operator, that does not yet know
     // about that filter type, the filter will be deserialized to unknown.
     #[schemars(skip)]
@@ -247,6 +272,7 @@ impl CollectAnalytics for &SplitQueuesConfig {
         // The number of SQS queues filtered with jq filters.
         analytics.add("sqs_jq_filter_count", self.sqs_jq_filters().count());
         analytics.add("kafka_queue_count", self.kafka().count());
+        analytics.add("rabbitmq_queue_count", self.rabbitmq().count());
     }
 }
 
@@ -270,7 +296,8 @@ pub enum QueueSplittingVerificationError {
 
 #[cfg(test)]
 mod test {
-    use super::{QueueFilter, SplitQueuesConfig};
+    use super::{QueueFilter, QueueSplittingVerificationError, SplitQueuesConfig};
+    use crate::config::ConfigContext;
 
     #[test]
     fn deserialize_known_queue_types() {
@@ -304,6 +331,70 @@ mod test {
                 jq_filter: None,
             }
         );
+
+        let value = serde_json::json!({
+            "queue_type": "RabbitMQ",
+            "message_filter": {
+                "key": "value",
+            },
+        });
+
+        let filter = serde_json::from_value::<QueueFilter>(value).unwrap();
+        assert_eq!(
+            filter,
+            QueueFilter::RabbitMq {
+                message_filter: [("key".to_string(), "value".to_string())].into()
+            }
+        );
+    }
+
+    /// A config with all the queue types survives serialization, so the operator gets the same
+    /// config the user wrote.
+    #[test]
+    fn split_queues_round_trip() {
+        let value = serde_json::json!({
+            "sqs-queue": {
+                "queue_type": "SQS",
+                "message_filter": { "who": "me" },
+            },
+            "kafka-topic": {
+                "queue_type": "Kafka",
+                "message_filter": { "who": "you" },
+            },
+            "rabbitmq-queue": {
+                "queue_type": "RabbitMQ",
+                "message_filter": { "x-tenant": "^metalbear$" },
+            },
+        });
+
+        let config = serde_json::from_value::<SplitQueuesConfig>(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&config).unwrap(), value);
+        assert_eq!(
+            config.rabbitmq().collect::<Vec<_>>(),
+            [(
+                "rabbitmq-queue",
+                &[("x-tenant".to_string(), "^metalbear$".to_string())].into()
+            )]
+        );
+
+        config.verify(&mut ConfigContext::default()).unwrap();
+    }
+
+    #[test]
+    fn rabbitmq_verification_fails_on_invalid_regex() {
+        let config = serde_json::from_value::<SplitQueuesConfig>(serde_json::json!({
+            "rabbitmq-queue": {
+                "queue_type": "RabbitMQ",
+                "message_filter": { "x-tenant": "(" },
+            },
+        }))
+        .unwrap();
+
+        assert!(matches!(
+            config.verify(&mut ConfigContext::default()),
+            Err(QueueSplittingVerificationError::InvalidRegex(queue, header, _))
+                if queue == "rabbitmq-queue" && header == "x-tenant"
+        ));
     }
 
     #[test]
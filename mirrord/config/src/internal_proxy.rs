@@ -1,9 +1,12 @@
+use std::net::SocketAddr;
+
 use mirrord_config_derive::MirrordConfig;
+use mirrord_protocol::MAX_DECODE_LIMIT;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    config::source::MirrordConfigSource,
+    config::{ConfigError, source::MirrordConfigSource},
     logfile_path::{Intproxy, LogDestinationConfig},
 };
 
@@ -57,6 +60,9 @@ pub struct InternalProxyConfig {
     /// Common cases would be running a chain of processes that skip using the layer
     /// and don't connect to the proxy.
     ///
+    /// The timer is reset by any traffic that goes through the proxy, e.g. messages from the
+    /// agent.
+    ///
     /// ```json
     /// {
     ///   "internal_proxy": {
@@ -67,6 +73,12 @@ pub struct InternalProxyConfig {
     #[config(default = 5)]
     pub idle_timeout: u64,
 
+    /// ### internal_proxy.reconnect {#internal_proxy-reconnect}
+    ///
+    /// Controls whether and how the proxy re-establishes a lost connection with the agent.
+    #[config(nested)]
+    pub reconnect: ReconnectConfig,
+
     /// <!--${internal}-->
     ///
     /// Sometimes the cpu is too busy with other tasks and the internal proxy sockets end
@@ -134,4 +146,203 @@ pub struct InternalProxyConfig {
     /// ```
     #[config(default = 60)]
     pub process_logging_interval: u64,
+
+    /// ### internal_proxy.metrics {#internal_proxy-metrics}
+    ///
+    /// Enables prometheus metrics for the internal proxy, served on `GET /metrics`.
+    ///
+    /// The metrics include the number of messages processed, file operations, bytes of data
+    /// received from the agent, and the number of messages queued for each of the proxy's tasks.
+    ///
+    /// ```json
+    /// {
+    ///   "internal_proxy": {
+    ///     "metrics": "127.0.0.1:9100"
+    ///   }
+    /// }
+    /// ```
+    pub metrics: Option<String>,
+
+    /// ### internal_proxy.max_message_size {#internal_proxy-max_message_size}
+    ///
+    /// Max size (in bytes) of a single message the proxy accepts from the agent.
+    ///
+    /// Messages bigger than this fail to decode, and the session ends with an error. Increase it
+    /// if you read big remote files (or directories) at once.
+    ///
+    /// Can't be higher than `1073741824` (1 GiB).
+    ///
+    /// Defaults to `268435456` (256 MiB).
+    #[config(default = 268435456)]
+    pub max_message_size: usize,
+}
+
+/// Controls whether and how the internal proxy re-establishes a lost connection with the agent,
+/// e.g. after the network dropped while the application was paused in a debugger.
+///
+/// After a reconnect, the proxy restores the port subscriptions, but the remote files that were
+/// open are lost (unless [`experimental.reconnect`](#experimental-reconnect) is enabled), and
+/// operations on them fail.
+///
+/// ```json
+/// {
+///   "internal_proxy": {
+///     "reconnect": {
+///       "enabled": true,
+///       "max_attempts": 10,
+///       "min_ms": 1000,
+///       "max_ms": 8000
+///     }
+///   }
+/// }
+/// ```
+#[derive(MirrordConfig, Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[config(map_to = "ReconnectFileConfig", derive = "JsonSchema")]
+#[cfg_attr(test, config(derive = "PartialEq, Eq"))]
+pub struct ReconnectConfig {
+    /// ### internal_proxy.reconnect.enabled {#internal_proxy-reconnect-enabled}
+    ///
+    /// Whether the proxy should try to reconnect to the agent when the connection is lost.
+    ///
+    /// When not set, the proxy reconnects only through the mirrord Operator (if the operator
+    /// allows it) and the [external proxy](#external_proxy), but not when it connects to the
+    /// agent directly. Set to `true` to reconnect in all cases, or to `false` to never reconnect.
+    ///
+    /// When connecting through the mirrord Operator, reconnects are also limited by the
+    /// operator.
+    pub enabled: Option<bool>,
+
+    /// ### internal_proxy.reconnect.max_attempts {#internal_proxy-reconnect-max_attempts}
+    ///
+    /// How many times the proxy tries to reconnect, before giving up and failing the session.
+    ///
+    /// Defaults to `10`.
+    #[config(default = 10)]
+    pub max_attempts: u32,
+
+    /// ### internal_proxy.reconnect.min_ms {#internal_proxy-reconnect-min_ms}
+    ///
+    /// Interval (in milliseconds) before the first reconnect attempt. The interval doubles after
+    /// each failed attempt, up to [`max_ms`](#internal_proxy-reconnect-max_ms).
+    ///
+    /// Defaults to `1000` milliseconds.
+    #[config(default = 1000)]
+    pub min_ms: u64,
+
+    /// ### internal_proxy.reconnect.max_ms {#internal_proxy-reconnect-max_ms}
+    ///
+    /// Max interval (in milliseconds) between the reconnect attempts.
+    ///
+    /// Defaults to `8000` milliseconds.
+    #[config(default = 8000)]
+    pub max_ms: u64,
+}
+
+impl InternalProxyConfig {
+    /// Verifies that [`InternalProxyConfig::metrics`] is a valid socket address, that
+    /// [`InternalProxyConfig::max_message_size`] is within bounds, and that the
+    /// [`InternalProxyConfig::reconnect`] intervals are in order.
+    pub fn verify(&self) -> Result<(), ConfigError> {
+        if self.reconnect.min_ms > self.reconnect.max_ms {
+            return Err(ConfigError::InvalidValue {
+                name: "internal_proxy.reconnect.min_ms",
+                provided: self.reconnect.min_ms.to_string(),
+                error: format!(
+                    "the value can't be greater than `internal_proxy.reconnect.max_ms` ({})",
+                    self.reconnect.max_ms
+                )
+                .into(),
+            });
+        }
+
+        if self.max_message_size == 0 || self.max_message_size > MAX_DECODE_LIMIT {
+            return Err(ConfigError::InvalidValue {
+                name: "internal_proxy.max_message_size",
+                provided: self.max_message_size.to_string(),
+                error: format!("the value has to be between 1 and {MAX_DECODE_LIMIT}").into(),
+            });
+        }
+
+        if let Some(metrics) = &self.metrics {
+            metrics
+                .parse::<SocketAddr>()
+                .map_err(|error| ConfigError::InvalidValue {
+                    name: "internal_proxy.metrics",
+                    provided: metrics.clone(),
+                    error: error.into(),
+                })?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::*;
+    use crate::config::{ConfigContext, MirrordConfig};
+
+    #[rstest]
+    #[case::ipv4(r#"{"metrics": "127.0.0.1:9100"}"#, true)]
+    #[case::ipv6(r#"{"metrics": "[::1]:9100"}"#, true)]
+    #[case::missing_port(r#"{"metrics": "127.0.0.1"}"#, false)]
+    #[case::hostname(r#"{"metrics": "localhost:9100"}"#, false)]
+    #[case::disabled("{}", true)]
+    fn metrics(#[case] config: &str, #[case] valid: bool) {
+        let config = serde_json::from_str::<InternalProxyFileConfig>(config)
+            .unwrap()
+            .generate_config(&mut ConfigContext::default())
+            .unwrap();
+
+        assert_eq!(config.verify().is_ok(), valid);
+    }
+
+    #[rstest]
+    #[case::default("{}", true)]
+    #[case::max(r#"{"max_message_size": 1073741824}"#, true)]
+    #[case::zero(r#"{"max_message_size": 0}"#, false)]
+    #[case::above_max(r#"{"max_message_size": 1073741825}"#, false)]
+    fn max_message_size(#[case] config: &str, #[case] valid: bool) {
+        let config = serde_json::from_str::<InternalProxyFileConfig>(config)
+            .unwrap()
+            .generate_config(&mut ConfigContext::default())
+            .unwrap();
+
+        assert_eq!(config.verify().is_ok(), valid);
+    }
+
+    #[test]
+    fn reconnect() {
+        let config = serde_json::from_str::<InternalProxyFileConfig>(
+            r#"{"reconnect": {"enabled": false, "max_ms": 2000}}"#,
+        )
+        .unwrap()
+        .generate_config(&mut ConfigContext::default())
+        .unwrap();
+
+        assert_eq!(
+            config.reconnect,
+            ReconnectConfig {
+                enabled: Some(false),
+                max_attempts: 10,
+                min_ms: 1000,
+                max_ms: 2000,
+            }
+        );
+    }
+
+    #[rstest]
+    #[case::default("{}", true)]
+    #[case::equal(r#"{"reconnect": {"min_ms": 2000, "max_ms": 2000}}"#, true)]
+    #[case::min_above_max(r#"{"reconnect": {"min_ms": 3000, "max_ms": 2000}}"#, false)]
+    fn reconnect_intervals(#[case] config: &str, #[case] valid: bool) {
+        let config = serde_json::from_str::<InternalProxyFileConfig>(config)
+            .unwrap()
+            .generate_config(&mut ConfigContext::default())
+            .unwrap();
+
+        assert_eq!(config.verify().is_ok(), valid);
+    }
 }
@@ -13,6 +13,8 @@ pub mod agent;
 pub mod ci;
 pub mod config;
 pub mod container;
+pub mod debugger;
+pub mod docs;
 pub mod env_key;
 pub mod experimental;
 pub mod external_proxy;
@@ -23,21 +25,25 @@ pub mod retry;
 pub mod target;
 pub mod util;
 
-use std::{collections::HashMap, ffi::OsStr, path::Path};
+use std::{collections::HashMap, ffi::OsStr, io::Read, path::Path};
 
 use base64::prelude::*;
 use config::{ConfigContext, ConfigError, MirrordConfig};
 use experimental::ExperimentalConfig;
+use fancy_regex::Regex;
 use feature::{
-    env::mapper::EnvVarsRemapper,
+    env::{filter::EnvVarsFilter, mapper::EnvVarsRemapper},
     network::{
-        incoming::http_filter::{BodyFilter, InnerFilter},
+        incoming::{
+            SourceIpDelivery,
+            http_filter::{BodyFilter, InnerFilter},
+        },
         outgoing::OutgoingFilterConfig,
     },
 };
 use mirrord_analytics::CollectAnalytics;
 use mirrord_config_derive::MirrordConfig;
-use mirrord_protocol::tcp::JsonPathQuery;
+use mirrord_protocol::tcp::{Filter, JsonPathQuery};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use target::Target;
@@ -45,20 +51,24 @@ use tera::Tera;
 use tracing::warn;
 
 use crate::{
-    agent::AgentConfig,
+    agent::{AgentConfig, AgentRedirector},
     ci::CiConfig,
     config::{FromFileError, source::MirrordConfigSource},
     container::ContainerConfig,
+    debugger::WaitForDebuggerConfig,
     env_key::EnvKey,
     external_proxy::ExternalProxyConfig,
     feature::{
         FeatureConfig,
-        fs::{READONLY_FILE_BUFFER_HARD_LIMIT, READONLY_FILE_BUFFER_WARN_LIMIT},
+        fs::{
+            READONLY_FILE_BUFFER_HARD_LIMIT, READONLY_FILE_BUFFER_WARN_LIMIT,
+            ReadonlyFileBufferOverride,
+        },
     },
     internal_proxy::InternalProxyConfig,
     retry::StartupRetryConfig,
     target::TargetConfig,
-    util::VecOrSingle,
+    util::{FileSource, VecOrSingle},
 };
 
 /// Environment variable we use to pass the internal proxy address to the layer.
@@ -233,6 +243,25 @@ pub struct LayerConfig {
     #[config(env = "MIRRORD_SKIP_PROCESSES")]
     pub skip_processes: Option<VecOrSingle<String>>,
 
+    /// ## skip_processes_regex {#root-skip_processes_regex}
+    ///
+    /// Allows mirrord to skip unwanted processes based on their whole command line.
+    ///
+    /// The regexes are matched against the arguments of the process (starting with the
+    /// executable, as it was invoked) joined with spaces. Unlike
+    /// [`skip_processes`](#root-skip_processes), this can tell apart different invocations of the
+    /// same executable. When both are set, a process that matches either of them is skipped.
+    ///
+    /// Accepts a single value, or an array of values.
+    ///
+    ///```json
+    /// {
+    ///  "skip_processes_regex": ["^node scripts/codegen\\.js"]
+    /// }
+    /// ```
+    #[config(env = "MIRRORD_SKIP_PROCESSES_REGEX")]
+    pub skip_processes_regex: Option<VecOrSingle<String>>,
+
     /// ## skip_build_tools {#root-skip_build_tools}
     ///
     /// Allows mirrord to skip build tools. Useful when running command lines that build and run
@@ -341,6 +370,23 @@ pub struct LayerConfig {
     #[config(nested)]
     pub feature: FeatureConfig,
 
+    /// ## only_features {#root-only_features}
+    ///
+    /// Keeps only the listed features enabled, and turns off all the others, regardless of how
+    /// they are configured under [`feature`](#root-feature). Handy for quickly finding out which
+    /// feature causes an issue.
+    ///
+    /// The features that can be listed are `"env"`, `"fs"`, `"dns"`, `"incoming"`, `"outgoing"`
+    /// and `"hostname"`.
+    ///
+    /// ```json
+    /// {
+    ///   "only_features": ["incoming", "outgoing"]
+    /// }
+    /// ```
+    #[config(env = "MIRRORD_ONLY_FEATURES")]
+    pub only_features: Option<VecOrSingle<String>>,
+
     /// ## telemetry {#root-telemetry}
     /// Controls whether or not mirrord sends telemetry data to MetalBear cloud.
     /// Telemetry sent doesn't contain personal identifiers or any data that
@@ -406,6 +452,10 @@ pub struct LayerConfig {
     #[config(nested)]
     pub ci: CiConfig,
 
+    /// ## wait_for_debugger {#root-wait_for_debugger}
+    #[config(nested)]
+    pub wait_for_debugger: WaitForDebuggerConfig,
+
     /// ## key {#root-key}
     ///
     /// An identifier for a mirrord session.
@@ -457,6 +507,49 @@ pub struct LayerConfig {
     pub baggage: Option<String>,
 }
 
+/// Envelope in which [`LayerConfig::encode`] stores the resolved config.
+///
+/// The [`version`](Self::version) allows the decoding side to tell that the config comes from a
+/// different mirrord version.
+#[derive(Serialize, Deserialize)]
+struct ResolvedConfigEnvelope<C> {
+    version: u32,
+    config: C,
+}
+
+/// Merges the `source` config into the serialized default config (`target`), recording the paths
+/// of `source` fields unknown to the `target` in `ignored`.
+///
+/// Objects are merged field by field only when they share at least one key (which means they're
+/// the same struct). Otherwise (maps, enum variants, [`None`] defaults) the `source` value replaces
+/// the default as a whole.
+fn merge_resolved_config(
+    target: &mut serde_json::Value,
+    source: serde_json::Value,
+    path: &str,
+    ignored: &mut Vec<String>,
+) {
+    match (target, source) {
+        (serde_json::Value::Object(target), serde_json::Value::Object(source))
+            if source.keys().any(|key| target.contains_key(key)) =>
+        {
+            for (key, value) in source {
+                let field_path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+
+                match target.get_mut(&key) {
+                    Some(target) => merge_resolved_config(target, value, &field_path, ignored),
+                    None => ignored.push(field_path),
+                }
+            }
+        }
+        (target, source) => *target = source,
+    }
+}
+
 impl LayerConfig {
     /// Env variable where we set the path to the [`LayerConfig`].
     ///
@@ -465,6 +558,12 @@ impl LayerConfig {
     /// Used in [`LayerConfig::resolve`].
     pub const FILE_PATH_ENV: &str = "MIRRORD_CONFIG_FILE";
 
+    /// Env variable with the format (`json`, `toml` or `yaml`) of the config read from the
+    /// standard input, when [`LayerConfig::FILE_PATH_ENV`] is `-`.
+    ///
+    /// Defaults to `json`. Config files use their extension instead.
+    pub const FILE_FORMAT_ENV: &str = "MIRRORD_CONFIG_FORMAT";
+
     /// Env variable where we store encoded resolved config.
     ///
     /// mirrord CLI children should not [`LayerConfig::resolve`] the configuration again,
@@ -473,25 +572,69 @@ impl LayerConfig {
     /// See [`LayerConfig::encode`] and [`LayerConfig::decode`].
     pub const RESOLVED_CONFIG_ENV: &str = "MIRRORD_RESOLVED_CONFIG";
 
+    /// Version of the [`ResolvedConfigEnvelope`] produced by [`LayerConfig::encode`].
+    ///
+    /// Configs encoded before the envelope was introduced are treated as version `0`.
+    pub const RESOLVED_CONFIG_VERSION: u32 = 1;
+
     /// Decodes an encoded [`LayerConfig`].
     ///
     /// You can encode the config with [`LayerConfig::encode`].
+    ///
+    /// The encoded config might come from a newer (or older) mirrord CLI, e.g. when the parent
+    /// CLI and the `mirrord intproxy` binary are not the same version. Fields that this version
+    /// does not know about are ignored (with a warning), and fields missing from the encoded
+    /// config are filled with their default values.
     pub fn decode(encoded_value: &str) -> Result<Self, ConfigError> {
         let decoded = BASE64_STANDARD
             .decode(encoded_value)
             .map_err(|error| ConfigError::DecodeError(error.to_string()))?;
-        let deserialized = serde_json::from_slice(&decoded)
+        let value: serde_json::Value = serde_json::from_slice(&decoded)
             .map_err(|error| ConfigError::DecodeError(error.to_string()))?;
 
-        Ok(deserialized)
+        let ResolvedConfigEnvelope { version, config } = serde_json::from_value(value.clone())
+            .unwrap_or(ResolvedConfigEnvelope {
+                version: 0,
+                config: value,
+            });
+
+        let default_config = LayerFileConfig::default()
+            .generate_config(&mut ConfigContext::default().strict_env(true))
+            .and_then(|config| {
+                serde_json::to_value(config)
+                    .map_err(|error| ConfigError::DecodeError(error.to_string()))
+            })?;
+        let mut merged = default_config;
+        let mut ignored_fields = Vec::new();
+        merge_resolved_config(&mut merged, config.clone(), "", &mut ignored_fields);
+
+        if !ignored_fields.is_empty() {
+            warn!(
+                version,
+                supported_version = Self::RESOLVED_CONFIG_VERSION,
+                ?ignored_fields,
+                "The resolved mirrord config contains fields that are not supported by this \
+                version of mirrord, these features will be disabled. \
+                Make sure that all mirrord binaries are the same version."
+            );
+        }
+
+        serde_json::from_value(config)
+            .or_else(|_| serde_json::from_value(merged))
+            .map_err(|error| ConfigError::DecodeError(error.to_string()))
     }
 
     /// Encodes this config to a string.
     ///
+    /// The config is wrapped in a versioned [`ResolvedConfigEnvelope`].
+    ///
     /// You can decode the config with [`LayerConfig::decode`].
     pub fn encode(&self) -> Result<String, ConfigError> {
-        let serialized = serde_json::to_string(self)
-            .map_err(|error| ConfigError::EncodeError(error.to_string()))?;
+        let serialized = serde_json::to_string(&ResolvedConfigEnvelope {
+            version: Self::RESOLVED_CONFIG_VERSION,
+            config: self,
+        })
+        .map_err(|error| ConfigError::EncodeError(error.to_string()))?;
         let encoded = BASE64_STANDARD.encode(serialized);
 
         Ok(encoded)
@@ -505,15 +648,42 @@ impl LayerConfig {
     /// This function **does not** use [`LayerConfig::RESOLVED_CONFIG_ENV`] nor
     /// [`LayerConfig::decode`]. It resolves the config from scratch.
     pub fn resolve(context: &mut ConfigContext) -> Result<Self, ConfigError> {
-        let mut config = if let Ok(path) = context.get_env(Self::FILE_PATH_ENV) {
+        let path = context.get_env(Self::FILE_PATH_ENV).ok();
+        let mut config = if let Some(path) = &path {
             LayerFileConfig::from_path(path, context)?.generate_config(context)?
         } else {
             LayerFileConfig::default().generate_config(context)?
         };
+
+        let stdin_config = path
+            .as_deref()
+            .is_some_and(|path| FileSource::new(path.as_ref()) == FileSource::Stdin);
+        let stdin_env_file = config
+            .feature
+            .env
+            .env_file
+            .as_deref()
+            .is_some_and(|path| FileSource::new(path) == FileSource::Stdin);
+        if stdin_config && stdin_env_file {
+            return Err(ConfigError::Conflict(
+                "the config and `feature.env.env_file` cannot both be read from the standard \
+                input (`-`)"
+                    .to_string(),
+            ));
+        }
+
         config.apply_magic();
+        config.apply_only_features();
         Ok(config)
     }
 
+    /// Turns off the features that are not in [`LayerConfig::only_features`], if it's set.
+    fn apply_only_features(&mut self) {
+        if let Some(only_features) = self.only_features.as_deref() {
+            self.feature.keep_only(only_features);
+        }
+    }
+
     /// Applies the presets in `feature.magic` to the config, modifying it in-place.
     fn apply_magic(&mut self) {
         if self.feature.magic.aws {
@@ -540,10 +710,44 @@ impl LayerConfig {
             }
         }
     }
+    /// Compiles the regexes from [`LayerConfig::skip_processes_regex`].
+    pub fn skip_processes_regexes(&self) -> Result<Vec<Regex>, ConfigError> {
+        self.skip_processes_regex
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern).map_err(|error| ConfigError::InvalidValue {
+                    name: "skip_processes_regex",
+                    provided: pattern.clone(),
+                    error: error.into(),
+                })
+            })
+            .collect()
+    }
+
     /// Verifies that there are no conflicting settings in this config.
     ///
     /// Fills the given [`ConfigContext`] with warnings.
     pub fn verify(&self, context: &mut ConfigContext) -> Result<(), ConfigError> {
+        if let Some(unknown) = self
+            .only_features
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .find(|name| !FeatureConfig::TOGGLEABLE.contains(&name.as_str()))
+        {
+            return Err(ConfigError::InvalidValue {
+                name: "only_features",
+                provided: unknown.clone(),
+                error: format!(
+                    "the known features are {}",
+                    FeatureConfig::TOGGLEABLE.join(", ")
+                )
+                .into(),
+            });
+        }
+
         if self.agent.ephemeral && self.agent.namespace.is_some() {
             context.add_warning(
                 "Agent namespace is ignored when using an ephemeral container for the agent."
@@ -551,6 +755,62 @@ impl LayerConfig {
             );
         }
 
+        if self.agent.ephemeral && self.agent.node_selector.is_some() {
+            context.add_warning(
+                "Agent node selector is ignored when using an ephemeral container for the agent."
+                    .to_string(),
+            );
+        }
+
+        if self.agent.ephemeral && self.agent.node_name.is_some() {
+            context.add_warning(
+                "Agent node name is ignored when using an ephemeral container for the agent."
+                    .to_string(),
+            );
+        }
+
+        self.skip_processes_regexes()?;
+        self.agent.connection_proxy_uri()?;
+        self.agent.affinity()?;
+        self.agent.iptables_chain_prefix()?;
+        self.agent.iptables_exclusions()?;
+
+        if let Some(metrics) = &self.agent.metrics {
+            metrics.verify()?;
+        }
+
+        self.internal_proxy.verify()?;
+
+        if self.agent.max_mirrored_connections == Some(0) {
+            return Err(ConfigError::InvalidValue {
+                name: "agent.max_mirrored_connections",
+                provided: "0".to_string(),
+                error: "the value has to be greater than 0".into(),
+            });
+        }
+
+        if self.agent.max_mirrored_connection_bytes == Some(0) {
+            return Err(ConfigError::InvalidValue {
+                name: "agent.max_mirrored_connection_bytes",
+                provided: "0".to_string(),
+                error: "the value has to be greater than 0".into(),
+            });
+        }
+
+        let redirector_without_mesh_exclusion = match self.agent.redirector {
+            AgentRedirector::Ebpf => Some("ebpf"),
+            AgentRedirector::Nftables => Some("nftables"),
+            AgentRedirector::Iptables | AgentRedirector::Auto => None,
+        };
+        if let Some(redirector) = redirector_without_mesh_exclusion
+            && self.agent.exclude_from_mesh
+        {
+            return Err(ConfigError::Conflict(format!(
+                "`agent.redirector: {redirector}` is not compatible with `agent.exclude_from_mesh`, \
+                as excluding the agent from the mesh requires iptables"
+            )));
+        }
+
         if matches!(
             self.feature.network.outgoing.filter,
             Some(OutgoingFilterConfig::Remote(_))
@@ -609,22 +869,60 @@ impl LayerConfig {
             verify_body_filter(body)?;
         }
 
+        let verify_named_header =
+            |header_name: &str, value: Option<&String>| -> Result<(), ConfigError> {
+                http::HeaderName::from_bytes(header_name.as_bytes()).map_err(|e| {
+                    ConfigError::InvalidValue {
+                        name: "feature.network.incoming.http_filter.header_name",
+                        provided: header_name.to_string(),
+                        error: Box::new(e),
+                    }
+                })?;
+
+                match value {
+                    Some(value) => Filter::new(value.clone()).map(|_| ()).map_err(|e| {
+                        ConfigError::InvalidValue {
+                            name: "feature.network.incoming.http_filter.value",
+                            provided: value.clone(),
+                            error: e,
+                        }
+                    }),
+                    None => Ok(()),
+                }
+            };
+
         if let Some(all_of) = &http_filter.all_of {
             for filter in all_of {
-                if let InnerFilter::Body(body) = filter {
-                    verify_body_filter(body)?
+                match filter {
+                    InnerFilter::Body(body) => verify_body_filter(body)?,
+                    InnerFilter::NamedHeader {
+                        header_name, value, ..
+                    } => verify_named_header(header_name, value.as_ref())?,
+                    _ => {}
                 }
             }
         }
 
         if let Some(any_of) = &http_filter.any_of {
             for filter in any_of {
-                if let InnerFilter::Body(body) = filter {
-                    verify_body_filter(body)?
+                match filter {
+                    InnerFilter::Body(body) => verify_body_filter(body)?,
+                    InnerFilter::NamedHeader {
+                        header_name, value, ..
+                    } => verify_named_header(header_name, value.as_ref())?,
+                    _ => {}
                 }
             }
         }
 
+        if http_filter.max_pending_requests == Some(0) {
+            return Err(ConfigError::InvalidValue {
+                name: "feature.network.incoming.http_filter.max_pending_requests",
+                provided: "0".to_string(),
+                error: "the value has to be greater than 0".into(),
+            });
+        }
+
         if !self.feature.network.incoming.ignore_ports.is_empty()
             && self.feature.network.incoming.ports.is_some()
         {
@@ -657,6 +955,106 @@ impl LayerConfig {
             (None, None) => {}
         }
 
+        self.feature.network.incoming.masking.json_selectors()?;
+
+        match self.feature.network.incoming.source_ip_delivery {
+            SourceIpDelivery::None => {}
+            _ if !self.feature.network.incoming.is_steal() => {
+                context.add_warning(
+                    "`feature.network.incoming.source_ip_delivery` only applies to stolen \
+                    traffic, and is ignored when not in the steal mode."
+                        .to_string(),
+                );
+            }
+            SourceIpDelivery::XForwardedFor if !http_filter.is_filter_set() => {
+                return Err(ConfigError::Conflict(
+                    "`feature.network.incoming.source_ip_delivery: x_forwarded_for` requires \
+                    `feature.network.incoming.http_filter`, as connections stolen in whole are \
+                    not delivered as HTTP requests"
+                        .to_string(),
+                ));
+            }
+            SourceIpDelivery::ProxyProtocol if http_filter.is_filter_set() => {
+                context.add_warning(
+                    "`feature.network.incoming.source_ip_delivery: proxy_protocol` only applies \
+                    to connections stolen in whole, requests stolen with the HTTP filter are \
+                    delivered without the PROXY protocol header."
+                        .to_string(),
+                );
+            }
+            SourceIpDelivery::ProxyProtocol | SourceIpDelivery::XForwardedFor => {}
+        }
+
+        if let Some(delivery_source) = self.feature.network.incoming.delivery_source {
+            // Binding fails with `EADDRNOTAVAIL` if the address is not assigned to any local
+            // interface.
+            std::net::TcpListener::bind((delivery_source, 0)).map_err(|error| {
+                ConfigError::InvalidValue {
+                    name: "feature.network.incoming.delivery_source",
+                    provided: delivery_source.to_string(),
+                    error: format!("not a local address: {error}").into(),
+                }
+            })?;
+
+            if !self.feature.network.incoming.is_steal() {
+                context.add_warning(
+                    "`feature.network.incoming.delivery_source` only applies to stolen traffic, \
+                    and is ignored when not in the steal mode."
+                        .to_string(),
+                );
+            }
+        }
+
+        if !self.feature.network.incoming.source_cidrs()?.is_empty()
+            && !self.feature.network.incoming.is_steal()
+        {
+            context.add_warning(
+                "`feature.network.incoming.source_filter` only applies to stolen traffic, \
+                and is ignored when not in the steal mode."
+                    .to_string(),
+            );
+        }
+
+        if let Some(auto_downgrade) = &self.feature.network.incoming.auto_downgrade {
+            auto_downgrade.verify()?;
+
+            if !self.feature.network.incoming.is_steal() {
+                context.add_warning(
+                    "`feature.network.incoming.auto_downgrade` only applies to stolen traffic, \
+                    and is ignored when not in the steal mode."
+                        .to_string(),
+                );
+            }
+        }
+
+        let incoming = &self.feature.network.incoming;
+        if !incoming.unix_sockets.is_empty() && !incoming.is_steal() {
+            return Err(ConfigError::Conflict(
+                "`feature.network.incoming.unix_sockets` requires the steal mode, please set \
+                `feature.network.incoming.mode` to `steal`."
+                    .into(),
+            ));
+        }
+        if let Some(path) = incoming
+            .unix_sockets
+            .iter()
+            .find(|path| !Path::new(path).is_absolute())
+        {
+            return Err(ConfigError::InvalidValue {
+                name: "feature.network.incoming.unix_sockets",
+                provided: path.clone(),
+                error: "the path has to be absolute".into(),
+            });
+        }
+        for (_, remote) in &incoming.unix_socket_mapping {
+            if !incoming.unix_sockets.contains(remote) {
+                context.add_warning(format!(
+                    "`feature.network.incoming.unix_socket_mapping` maps the remote socket \
+                    `{remote}`, which is not in `feature.network.incoming.unix_sockets`."
+                ));
+            }
+        }
+
         if !self.feature.copy_target.enabled
             && self
                 .target
@@ -697,7 +1095,24 @@ impl LayerConfig {
             }
         }
 
+        if self.agent.node_name.is_some()
+            && self
+                .target
+                .path
+                .as_ref()
+                .is_some_and(|target| !matches!(target, Target::Targetless))
+        {
+            return Err(ConfigError::Conflict(
+                "Agent node name can be used only in targetless runs, \
+                as targeted agent always runs on the same node as its target. \
+                Please either disable this option or remove the target."
+                    .into(),
+            ));
+        }
+
         if self.feature.copy_target.enabled {
+            self.feature.copy_target.verify()?;
+
             if self.operator == Some(false) {
                 return Err(ConfigError::Conflict(
                     "The copy target feature requires a mirrord operator, \
@@ -763,20 +1178,18 @@ impl LayerConfig {
         }
 
         // Env vars
-        if self.feature.env.exclude.is_some() && self.feature.env.include.is_some() {
-            return Err(ConfigError::Conflict(
-                "cannot use both `include` and `exclude` filters for environment variables"
-                    .to_string(),
-            ));
-        }
+        EnvVarsFilter::new(&self.feature.env)?;
 
         if let Some(env_vars_mapping) = self.feature.env.mapping.clone() {
             EnvVarsRemapper::new(env_vars_mapping, HashMap::new())?;
         }
 
+        self.feature.env.kube_resources()?;
+
         self.feature.network.dns.verify(context)?;
         self.feature.network.outgoing.verify(context)?;
         self.feature.split_queues.verify(context)?;
+        self.experimental.fault_injection.verify(context)?;
 
         if self.feature.fs.readonly_file_buffer > READONLY_FILE_BUFFER_HARD_LIMIT {
             return Err(ConfigError::InvalidValue {
@@ -796,6 +1209,29 @@ impl LayerConfig {
             ));
         }
 
+        if let Some(ReadonlyFileBufferOverride { pattern, size }) = self
+            .feature
+            .fs
+            .readonly_file_buffer_overrides
+            .iter()
+            .flatten()
+            .find(|entry| entry.size > READONLY_FILE_BUFFER_HARD_LIMIT)
+        {
+            return Err(ConfigError::InvalidValue {
+                name: "feature.fs.readonly_file_buffer_overrides",
+                provided: format!("{pattern}: {size}"),
+                error: format!(
+                    "the buffer size of each pattern must be {} Megabytes or less.",
+                    READONLY_FILE_BUFFER_HARD_LIMIT / 1024 / 1024
+                )
+                .into(),
+            });
+        }
+
+        // Compile the patterns here, so that invalid ones fail at startup.
+        self.feature.fs.readonly_file_buffers()?;
+        self.feature.fs.fallback_local_error_kinds()?;
+
         if let (Some(profile), true) = (&self.profile, context.has_warnings()) {
             // It might be that the user config is fine,
             // but the mirrord profile introduced changes that triggered the warnings.
@@ -852,6 +1288,7 @@ impl CollectAnalytics for &LayerConfig {
         (&self.feature).collect_analytics(analytics);
         (&self.experimental).collect_analytics(analytics);
         (&self.startup_retry).collect_analytics(analytics);
+        analytics.add("wait_for_debugger", &self.wait_for_debugger);
     }
 }
 
@@ -876,60 +1313,87 @@ impl LayerFileConfig {
     ///
     /// The marker prefix on auto-generated keys allows `generate_config` to distinguish them
     /// from user-provided keys (see [`EnvKey::AUTOGENERATED_MARKER`] for details).
+    ///
+    /// # Sources
+    ///
+    /// The `path` is interpreted as a [`FileSource`], so the config can also be read from the
+    /// standard input (`-`), in which case its format is taken from
+    /// [`LayerConfig::FILE_FORMAT_ENV`].
     pub fn from_path<P>(path: P, context: &mut ConfigContext) -> Result<Self, FromFileError>
     where
         P: AsRef<Path>,
     {
+        let source = FileSource::new(path.as_ref());
+        let format = match source {
+            FileSource::Stdin => context.get_env(LayerConfig::FILE_FORMAT_ENV).ok(),
+            FileSource::Path(path) => path.extension().and_then(OsStr::to_str).map(String::from),
+        };
+
+        Self::from_reader(
+            source.read_to_string()?.as_bytes(),
+            format.as_deref(),
+            context,
+        )
+    }
+
+    /// Parses a [`LayerFileConfig`] from the given `reader`, rendering any Tera templates in the
+    /// same way as [`LayerFileConfig::from_path`].
+    ///
+    /// `format` is the file extension of the config (`json`, `toml`, `yaml` or `yml`), defaults to
+    /// `json`.
+    pub fn from_reader<R>(
+        mut reader: R,
+        format: Option<&str>,
+        context: &mut ConfigContext,
+    ) -> Result<Self, FromFileError>
+    where
+        R: Read,
+    {
+        let mut content = String::new();
+        reader.read_to_string(&mut content)?;
+
         let key = context
             .get_env(env_key::MIRRORD_ENV_KEY)
             .ok()
-            .or_else(|| Self::extract_key_from_file(path.as_ref()))
+            .or_else(|| Self::extract_key(&content, format))
             .unwrap_or_else(EnvKey::autogenerated_with_marker);
 
         context.override_env_mut(env_key::MIRRORD_ENV_KEY, &key);
 
-        let mut template_engine = Tera::default();
-        template_engine.add_template_file(path.as_ref(), Some("main"))?;
-
         let mut tera_context = tera::Context::new();
         tera_context.insert("key", &key);
 
-        let rendered = template_engine.render("main", &tera_context)?;
+        let rendered = Tera::one_off(&content, &tera_context, false)?;
 
-        match path.as_ref().extension().and_then(OsStr::to_str) {
+        match format {
             // No Extension? assume json
             Some("json") | None => Ok(serde_json::from_str::<Self>(&rendered)?),
             Some("toml") => Ok(toml::from_str::<Self>(&rendered)?),
             Some("yaml" | "yml") => Ok(serde_yaml::from_str::<Self>(&rendered)?),
-            ext => Err(FromFileError::InvalidExtension(ext.map(String::from))),
+            Some(ext) => Err(FromFileError::InvalidExtension(Some(ext.to_string()))),
         }
     }
 
-    /// Extracts just the `key` field from a config file without template rendering.
+    /// Extracts just the `key` field from the raw config `content` (in the given `format`),
+    /// without template rendering.
     ///
     /// This is used in the first pass of config loading to determine the key value
     /// before rendering templates that might reference `{{ key }}`.
     ///
-    /// Returns `None` if the file doesn't contain a `key` field or if parsing fails.
-    fn extract_key_from_file(path: &Path) -> Option<String> {
-        // Read the raw file content
-        let content = std::fs::read_to_string(path).ok()?;
-
-        // Try to parse based on extension to extract just the key field
-        let extension = path.extension().and_then(OsStr::to_str);
-
-        match extension {
-            Some("json") | None => serde_json::from_str::<serde_json::Value>(&content)
+    /// Returns `None` if the config doesn't contain a `key` field or if parsing fails.
+    fn extract_key(content: &str, format: Option<&str>) -> Option<String> {
+        match format {
+            Some("json") | None => serde_json::from_str::<serde_json::Value>(content)
                 .ok()?
                 .get("key")?
                 .as_str()
                 .map(String::from),
-            Some("toml") => toml::from_str::<toml::Value>(&content)
+            Some("toml") => toml::from_str::<toml::Value>(content)
                 .ok()?
                 .get("key")?
                 .as_str()
                 .map(String::from),
-            Some("yaml" | "yml") => serde_yaml::from_str::<serde_yaml::Value>(&content)
+            Some("yaml" | "yml") => serde_yaml::from_str::<serde_yaml::Value>(content)
                 .ok()?
                 .get("key")?
                 .as_str()
@@ -1190,7 +1654,9 @@ mod tests {
                 namespace: Some("default".to_owned()),
             }),
             skip_processes: None,
+            skip_processes_regex: None,
             skip_extra_build_tools: None,
+            only_features: None,
             skip_build_tools: None,
             agent: Some(AgentFileConfig {
                 privileged: None,
@@ -1227,10 +1693,21 @@ mod tests {
                             ignore_localhost: None,
                             ignore_ports: None,
                             listen_ports: None,
+                            bind_any_maps_remote: None,
                             on_concurrent_steal: None,
                             ports: None,
                             https_delivery: Default::default(),
                             tls_delivery: Default::default(),
+                            masking: None,
+                            on_local_unavailable: None,
+                            source_ip_delivery: None,
+                            delivery_source: None,
+                            source_filter: None,
+                            auto_downgrade: None,
+                            limit_to_target_container: None,
+                            strict_ports: None,
+                            unix_sockets: None,
+                            unix_socket_mapping: None,
                         }),
                     ))),
                     outgoing: Some(ToggleableConfig::Config(OutgoingFileConfig {
@@ -1259,6 +1736,7 @@ mod tests {
             skip_sip: None,
             startup_retry: None,
             ci: None,
+            wait_for_debugger: None,
             traceparent: None,
             baggage: None,
         };
@@ -1424,6 +1902,398 @@ mod tests {
         assert_eq!(decoded, resolved_config);
     }
 
+    /// Encodes the default [`LayerConfig`], and lets `modify` change the encoded envelope before
+    /// it's base64 encoded.
+    fn encode_modified_default_config(
+        modify: impl FnOnce(&mut serde_json::Value),
+    ) -> (LayerConfig, String) {
+        let resolved_config = LayerFileConfig::default()
+            .generate_config(&mut ConfigContext::default().strict_env(true))
+            .unwrap();
+
+        let encoded = resolved_config.encode().unwrap();
+        let mut envelope: serde_json::Value =
+            serde_json::from_slice(&BASE64_STANDARD.decode(encoded).unwrap()).unwrap();
+        modify(&mut envelope);
+
+        let encoded = BASE64_STANDARD.encode(serde_json::to_string(&envelope).unwrap());
+
+        (resolved_config, encoded)
+    }
+
+    /// Verifies that [`LayerConfig::decode`] ignores fields added by a newer mirrord version.
+    #[test]
+    fn decode_config_with_unknown_fields() {
+        let (resolved_config, encoded) = encode_modified_default_config(|envelope| {
+            envelope["version"] = (LayerConfig::RESOLVED_CONFIG_VERSION + 1).into();
+            envelope["config"]["fake_root_field"] = true.into();
+            envelope["config"]["agent"]["fake_agent_field"] = "fake".into();
+        });
+
+        let decoded = LayerConfig::decode(&encoded).unwrap();
+
+        assert_eq!(decoded, resolved_config);
+    }
+
+    /// Verifies that [`LayerConfig::decode`] fills fields missing from a config encoded by an
+    /// older mirrord version with defaults.
+    #[test]
+    fn decode_config_with_missing_fields() {
+        let (resolved_config, encoded) = encode_modified_default_config(|envelope| {
+            envelope["config"]["agent"]
+                .as_object_mut()
+                .unwrap()
+                .remove("ttl")
+                .unwrap();
+            envelope["config"]
+                .as_object_mut()
+                .unwrap()
+                .remove("experimental")
+                .unwrap();
+        });
+
+        let decoded = LayerConfig::decode(&encoded).unwrap();
+
+        assert_eq!(decoded, resolved_config);
+    }
+
+    /// Verifies that `header_name` filters are validated in [`LayerConfig::verify`].
+    #[rstest]
+    #[case::absent(r#"{ "header_name": "x-tenant", "negate": true }"#, true)]
+    #[case::value(r#"{ "header_name": "x-tenant", "value": "^team-a$" }"#, true)]
+    #[case::bad_name(r#"{ "header_name": "x tenant" }"#, false)]
+    #[case::bad_value(r#"{ "header_name": "x-tenant", "value": "(team-a" }"#, false)]
+    fn verify_named_header_filter(#[case] filter: &str, #[case] valid: bool) {
+        let config = format!(
+            r#"{{
+                "feature": {{
+                    "network": {{
+                        "incoming": {{
+                            "mode": "mirror",
+                            "http_filter": {{ "any_of": [{filter}] }}
+                        }}
+                    }}
+                }}
+            }}"#
+        );
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = ConfigType::Json
+            .parse(&config)
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = config.verify(&mut cfg_context);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+        if valid {
+            assert!(matches!(
+                config
+                    .feature
+                    .network
+                    .incoming
+                    .http_filter
+                    .as_protocol_http_filter(),
+                Ok(mirrord_protocol::tcp::HttpFilter::Composite { filters, .. })
+                    if matches!(filters.as_slice(), [mirrord_protocol::tcp::HttpFilter::NamedHeader(..)])
+            ));
+        }
+    }
+
+    /// Verifies that `agent.affinity` is validated and that `agent.node_selector` with an
+    /// ephemeral agent produces a warning in [`LayerConfig::verify`].
+    #[rstest]
+    #[case::node_selector(r#"{ "node_selector": { "pool": "stable" } }"#, true, false)]
+    #[case::node_name(r#"{ "node_name": "node1" }"#, true, false)]
+    #[case::ephemeral_node_name(r#"{ "ephemeral": true, "node_name": "node1" }"#, true, true)]
+    #[case::ephemeral_node_selector(
+        r#"{ "ephemeral": true, "node_selector": { "pool": "stable" } }"#,
+        true,
+        true
+    )]
+    #[case::affinity(
+        r#"{ "affinity": { "podAntiAffinity": { "preferredDuringSchedulingIgnoredDuringExecution": [] } } }"#,
+        true,
+        false
+    )]
+    #[case::bad_affinity(r#"{ "affinity": { "nodeAffinity": [] } }"#, false, false)]
+    fn verify_agent_scheduling(#[case] agent: &str, #[case] valid: bool, #[case] warns: bool) {
+        let config = format!(r#"{{ "agent": {agent} }}"#);
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = ConfigType::Json
+            .parse(&config)
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = config.verify(&mut cfg_context);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+        assert_eq!(cfg_context.has_warnings(), warns);
+    }
+
+    /// Verifies that `agent.node_name` is rejected in [`LayerConfig::verify`] when there's a
+    /// target.
+    #[rstest]
+    #[case::targetless(r#""targetless""#, true)]
+    #[case::pod(r#""pod/foo""#, false)]
+    #[case::deployment(r#""deployment/foo""#, false)]
+    fn verify_agent_node_name(#[case] target: &str, #[case] valid: bool) {
+        let config = format!(r#"{{ "target": {target}, "agent": {{ "node_name": "node1" }} }}"#);
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = ConfigType::Json
+            .parse(&config)
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = config.verify(&mut cfg_context);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+    }
+
+    #[rstest]
+    #[case::valid(
+        r#"[{ "pattern": "\\.parquet$", "size": 0 }, { "pattern": "\\.csv$", "size": 4096 }]"#,
+        true
+    )]
+    #[case::too_big(r#"[{ "pattern": "\\.parquet$", "size": 16777216 }]"#, false)]
+    #[case::bad_regex(r#"[{ "pattern": "(parquet", "size": 0 }]"#, false)]
+    fn verify_readonly_file_buffer_overrides(#[case] overrides: &str, #[case] valid: bool) {
+        let config = format!(
+            r#"{{ "feature": {{ "fs": {{ "readonly_file_buffer_overrides": {overrides} }} }} }}"#
+        );
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = ConfigType::Json
+            .parse(&config)
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = config.verify(&mut cfg_context);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+    }
+
+    #[rstest]
+    #[case::valid(r#"["PermissionDenied", "NotFound"]"#, true)]
+    #[case::unknown(r#"["PermissionDenied", "Forbidden"]"#, false)]
+    #[case::not_io(r#"["Unknown"]"#, false)]
+    fn verify_fallback_local_on(#[case] kinds: &str, #[case] valid: bool) {
+        let config = format!(r#"{{ "feature": {{ "fs": {{ "fallback_local_on": {kinds} }} }} }}"#);
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = ConfigType::Json
+            .parse(&config)
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = config.verify(&mut cfg_context);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+    }
+
+    #[rstest]
+    #[case::default(r#"{}"#, true)]
+    #[case::custom(r#"{ "iptables_chain_prefix": "TEAM-A_1" }"#, true)]
+    #[case::empty(r#"{ "iptables_chain_prefix": "" }"#, false)]
+    #[case::too_long(r#"{ "iptables_chain_prefix": "MIRRORD_TEAM" }"#, false)]
+    #[case::bad_characters(r#"{ "iptables_chain_prefix": "TEAM A" }"#, false)]
+    fn verify_iptables_chain_prefix(#[case] agent: &str, #[case] valid: bool) {
+        let config = format!(r#"{{ "agent": {agent} }}"#);
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = ConfigType::Json
+            .parse(&config)
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = config.verify(&mut cfg_context);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+    }
+
+    #[rstest]
+    #[case::default(r#"{}"#, true)]
+    #[case::cidrs(
+        r#"{ "iptables_exclude_destinations": ["169.254.169.254/32", "fd00:ec2::254"], "iptables_exclude_sources": ["10.1.0.0/16"] }"#,
+        true
+    )]
+    #[case::bad_destination(r#"{ "iptables_exclude_destinations": ["metadata"] }"#, false)]
+    #[case::bad_prefix(r#"{ "iptables_exclude_sources": ["10.1.0.0/33"] }"#, false)]
+    fn verify_iptables_exclusions(#[case] agent: &str, #[case] valid: bool) {
+        let config = format!(r#"{{ "agent": {agent} }}"#);
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = ConfigType::Json
+            .parse(&config)
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = config.verify(&mut cfg_context);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+    }
+
+    #[rstest]
+    #[case::default(r#"{}"#, true, false)]
+    #[case::ipv4(
+        r#"{ "mode": "steal", "source_filter": ["10.12.0.0/16"] }"#,
+        true,
+        false
+    )]
+    #[case::ipv6_and_plain_ip(
+        r#"{ "mode": "steal", "source_filter": ["fd00::/8", "10.12.0.1"] }"#,
+        true,
+        false
+    )]
+    #[case::bad_address(
+        r#"{ "mode": "steal", "source_filter": ["10.12.0.0/a"] }"#,
+        false,
+        false
+    )]
+    #[case::bad_prefix(
+        r#"{ "mode": "steal", "source_filter": ["fd00::/129"] }"#,
+        false,
+        false
+    )]
+    #[case::mirror(
+        r#"{ "mode": "mirror", "source_filter": ["10.12.0.0/16"] }"#,
+        true,
+        true
+    )]
+    fn verify_source_filter(#[case] incoming: &str, #[case] valid: bool, #[case] warns: bool) {
+        let config = format!(
+            r#"{{ "target": "pod/app", "feature": {{ "network": {{ "incoming": {incoming} }} }} }}"#
+        );
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = ConfigType::Json
+            .parse(&config)
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = config.verify(&mut cfg_context);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+        assert_eq!(cfg_context.has_warnings(), warns);
+    }
+
+    #[rstest]
+    #[case::defaults(r#"{ "mode": "steal", "auto_downgrade": {} }"#, true, false)]
+    #[case::custom(
+        r#"{ "mode": "steal", "auto_downgrade": { "errors": 5, "window_secs": 10, "mirror": false } }"#,
+        true,
+        false
+    )]
+    #[case::zero_errors(
+        r#"{ "mode": "steal", "auto_downgrade": { "errors": 0 } }"#,
+        false,
+        false
+    )]
+    #[case::zero_window(
+        r#"{ "mode": "steal", "auto_downgrade": { "window_secs": 0 } }"#,
+        false,
+        false
+    )]
+    #[case::mirror(r#"{ "mode": "mirror", "auto_downgrade": {} }"#, true, true)]
+    fn verify_auto_downgrade(#[case] incoming: &str, #[case] valid: bool, #[case] warns: bool) {
+        let config = format!(
+            r#"{{ "target": "pod/app", "feature": {{ "network": {{ "incoming": {incoming} }} }} }}"#
+        );
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = ConfigType::Json
+            .parse(&config)
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = config.verify(&mut cfg_context);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+        assert_eq!(cfg_context.has_warnings(), warns);
+    }
+
+    #[rstest]
+    #[case::steal(
+        r#"{ "mode": "steal", "unix_sockets": ["/var/run/app.sock"] }"#,
+        true,
+        false
+    )]
+    #[case::mapped(
+        r#"{ "mode": "steal", "unix_sockets": ["/var/run/app.sock"], "unix_socket_mapping": [["/tmp/app.sock", "/var/run/app.sock"]] }"#,
+        true,
+        false
+    )]
+    #[case::mapping_unknown(
+        r#"{ "mode": "steal", "unix_sockets": ["/var/run/app.sock"], "unix_socket_mapping": [["/tmp/app.sock", "/var/run/other.sock"]] }"#,
+        true,
+        true
+    )]
+    #[case::relative(r#"{ "mode": "steal", "unix_sockets": ["app.sock"] }"#, false, false)]
+    #[case::mirror(
+        r#"{ "mode": "mirror", "unix_sockets": ["/var/run/app.sock"] }"#,
+        false,
+        false
+    )]
+    fn verify_unix_sockets(#[case] incoming: &str, #[case] valid: bool, #[case] warns: bool) {
+        let config = format!(
+            r#"{{ "target": "pod/app", "feature": {{ "network": {{ "incoming": {incoming} }} }} }}"#
+        );
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = ConfigType::Json
+            .parse(&config)
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = config.verify(&mut cfg_context);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+        assert_eq!(cfg_context.has_warnings(), warns);
+    }
+
+    #[rstest]
+    #[case::default(r#"{}"#, true, false)]
+    #[case::proxy_protocol(
+        r#"{ "mode": "steal", "source_ip_delivery": "proxy_protocol" }"#,
+        true,
+        false
+    )]
+    #[case::proxy_protocol_with_filter(
+        r#"{ "mode": "steal", "source_ip_delivery": "proxy_protocol", "http_filter": { "path_filter": "/api" } }"#,
+        true,
+        true
+    )]
+    #[case::x_forwarded_for(
+        r#"{ "mode": "steal", "source_ip_delivery": "x_forwarded_for", "http_filter": { "path_filter": "/api" } }"#,
+        true,
+        false
+    )]
+    #[case::x_forwarded_for_without_filter(
+        r#"{ "mode": "steal", "source_ip_delivery": "x_forwarded_for" }"#,
+        false,
+        false
+    )]
+    #[case::mirror(
+        r#"{ "mode": "mirror", "source_ip_delivery": "proxy_protocol" }"#,
+        true,
+        true
+    )]
+    fn verify_source_ip_delivery(#[case] incoming: &str, #[case] valid: bool, #[case] warns: bool) {
+        let config = format!(
+            r#"{{ "target": "pod/app", "feature": {{ "network": {{ "incoming": {incoming} }} }} }}"#
+        );
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = ConfigType::Json
+            .parse(&config)
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = config.verify(&mut cfg_context);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+        assert_eq!(cfg_context.has_warnings(), warns);
+    }
+
+    #[rstest]
+    #[case::localhost(r#"{ "mode": "steal", "delivery_source": "127.0.0.1" }"#, true, false)]
+    #[case::not_local(r#"{ "mode": "steal", "delivery_source": "192.0.2.1" }"#, false, false)]
+    #[case::mirror(r#"{ "mode": "mirror", "delivery_source": "127.0.0.1" }"#, true, true)]
+    fn verify_delivery_source(#[case] incoming: &str, #[case] valid: bool, #[case] warns: bool) {
+        let config = format!(
+            r#"{{ "target": "pod/app", "feature": {{ "network": {{ "incoming": {incoming} }} }} }}"#
+        );
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = ConfigType::Json
+            .parse(&config)
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = config.verify(&mut cfg_context);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+        assert_eq!(cfg_context.has_warnings(), warns);
+    }
+
     #[cfg(not(target_os = "windows"))]
     const USER_ENVVAR: &str = "USER";
 
@@ -1482,6 +2352,52 @@ mod tests {
         assert_eq!(config.key.analytics_len(), "only-cli-key".len());
     }
 
+    /// Verifies that configs read from a stream (e.g. the standard input) are rendered with Tera,
+    /// and parsed according to the given format.
+    #[rstest]
+    #[case::json_default(r#"{"target": "pod/test-{{ key }}"}"#, None)]
+    #[case::json(r#"{"target": "pod/test-{{ key }}"}"#, Some("json"))]
+    #[case::toml(r#"target = "pod/test-{{ key }}""#, Some("toml"))]
+    #[case::yaml("target: pod/test-{{ key }}", Some("yaml"))]
+    fn config_from_reader(#[case] content: &str, #[case] format: Option<&str>) {
+        let mut ctx = ConfigContext::default().override_env(env_key::MIRRORD_ENV_KEY, "my-session");
+        let config = LayerFileConfig::from_reader(content.as_bytes(), format, &mut ctx).unwrap();
+
+        let Some(TargetFileConfig::Simple(Some(Target::Pod(pod_target)))) = config.target else {
+            panic!("Bad target");
+        };
+
+        assert_eq!(pod_target.pod, "test-my-session");
+    }
+
+    #[rstest]
+    #[case::stdin("-", FileSource::Stdin)]
+    #[case::file_url(
+        "file:///tmp/mirrord.json",
+        FileSource::Path(Path::new("/tmp/mirrord.json"))
+    )]
+    #[case::path("/tmp/mirrord.json", FileSource::Path(Path::new("/tmp/mirrord.json")))]
+    #[case::relative("./-", FileSource::Path(Path::new("./-")))]
+    fn file_source(#[case] path: &str, #[case] expected: FileSource<'static>) {
+        assert_eq!(FileSource::new(path.as_ref()), expected);
+    }
+
+    #[test]
+    fn config_from_file_url() {
+        let mut temp_file = NamedTempFile::with_suffix(".toml").unwrap();
+        temp_file.write_all(br#"target = "pod/test""#).unwrap();
+
+        let url = format!("file://{}", temp_file.path().display());
+        let mut ctx = ConfigContext::default().strict_env(true);
+        let config = LayerFileConfig::from_path(url, &mut ctx).unwrap();
+
+        let Some(TargetFileConfig::Simple(Some(Target::Pod(pod_target)))) = config.target else {
+            panic!("Bad target");
+        };
+
+        assert_eq!(pod_target.pod, "test");
+    }
+
     #[test]
     fn test_template_rendering_with_key() {
         let mut temp_file = NamedTempFile::new().unwrap();
@@ -1498,4 +2414,72 @@ mod tests {
 
         assert_eq!(pod_target.pod, "test-my-session");
     }
+
+    /// Features missing from `only_features` are turned off by [`LayerConfig::resolve`], even
+    /// when they are enabled in `feature`.
+    #[test]
+    fn only_features_overrides_feature_config() {
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file
+            .write_all(
+                br#"{
+                    "only_features": ["incoming", "env"],
+                    "feature": {
+                        "fs": "write",
+                        "hostname": true,
+                        "network": {
+                            "incoming": "mirror",
+                            "outgoing": { "tcp": true, "udp": true },
+                            "dns": true
+                        }
+                    }
+                }"#,
+            )
+            .unwrap();
+
+        let mut ctx =
+            ConfigContext::default().override_env(LayerConfig::FILE_PATH_ENV, temp_file.path());
+        let config = LayerConfig::resolve(&mut ctx).unwrap();
+        config.verify(&mut ctx).unwrap();
+
+        assert_eq!(config.feature.network.incoming.mode, IncomingMode::Mirror);
+        assert!(config.feature.env.exclude.is_none());
+
+        assert_eq!(config.feature.fs.mode, FsModeConfig::Local);
+        assert!(!config.feature.hostname);
+        assert!(!config.feature.network.dns.enabled);
+        assert!(!config.feature.network.outgoing.tcp);
+        assert!(!config.feature.network.outgoing.udp);
+    }
+
+    #[rstest]
+    #[case::known(r#"["fs", "dns", "incoming"]"#, true)]
+    #[case::empty("[]", true)]
+    #[case::unknown(r#"["fs", "network"]"#, false)]
+    fn verify_only_features(#[case] only_features: &str, #[case] valid: bool) {
+        let config = format!(r#"{{ "only_features": {only_features} }}"#);
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = ConfigType::Json
+            .parse(&config)
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = config.verify(&mut cfg_context);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+    }
+
+    #[rstest]
+    #[case::valid(r#"["^node scripts/codegen\\.js", "server"]"#, true)]
+    #[case::invalid(r#"["node (scripts"]"#, false)]
+    fn verify_skip_processes_regex(#[case] skip_processes_regex: &str, #[case] valid: bool) {
+        let config = format!(r#"{{ "skip_processes_regex": {skip_processes_regex} }}"#);
+        let mut cfg_context = ConfigContext::default().strict_env(true);
+        let config = ConfigType::Json
+            .parse(&config)
+            .generate_config(&mut cfg_context)
+            .unwrap();
+
+        let result = config.verify(&mut cfg_context);
+        assert_eq!(result.is_ok(), valid, "{result:?}");
+    }
 }
@@ -56,6 +56,40 @@ pub struct StartupRetryConfig {
     /// Defaults to `2`.
     #[config(default = 2)]
     pub max_retries: u32,
+
+    /// ### startup_retry.operator_session_retries {#startup_retry-operator_session_retries}
+    ///
+    /// Sets the max amount of retries for creating the mirrord Operator session, when the
+    /// connection to the operator fails with a transient error.
+    ///
+    /// Before each retry, mirrord cleans up the session that the failed attempt might have left
+    /// behind, so that the retry does not create a second agent. The retries use the same backoff
+    /// as the Kubernetes API requests ([`min_ms`](#startup_retry-min_ms) and
+    /// [`max_ms`](#startup_retry-max_ms)).
+    ///
+    /// If you want to **disable** session retries, set this value to `0`.
+    ///
+    /// Defaults to `2`.
+    #[config(default = 2)]
+    pub operator_session_retries: u32,
+
+    /// ### startup_retry.agent_retries {#startup_retry-agent_retries}
+    ///
+    /// Sets the max amount of retries for creating (and connecting to) the mirrord-agent when
+    /// running without the mirrord Operator, after a transient failure, e.g. when the agent pod is
+    /// evicted, the agent does not become ready in time, or the port-forward fails on a busy
+    /// cluster.
+    ///
+    /// Before each retry, mirrord deletes the agent job that the failed attempt might have left
+    /// behind. The retries use the same backoff as the Kubernetes API requests
+    /// ([`min_ms`](#startup_retry-min_ms) and [`max_ms`](#startup_retry-max_ms)).
+    ///
+    /// Authentication and permission failures are never retried. If you want to **disable** agent
+    /// retries, set this value to `0`.
+    ///
+    /// Defaults to `3`.
+    #[config(default = 3)]
+    pub agent_retries: u32,
 }
 
 impl CollectAnalytics for &StartupRetryConfig {
@@ -63,5 +97,7 @@ impl CollectAnalytics for &StartupRetryConfig {
         analytics.add("min_ms", self.min_ms);
         analytics.add("max_ms", self.max_ms);
         analytics.add("max_attempts", self.max_retries);
+        analytics.add("operator_session_retries", self.operator_session_retries);
+        analytics.add("agent_retries", self.agent_retries);
     }
 }
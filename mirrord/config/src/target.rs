@@ -381,7 +381,7 @@ impl FromStr for Target {
 
 impl Target {
     /// `true` if this [`Target`] is only supported when the copy target feature is enabled.
-    pub(super) fn requires_copy(&self) -> bool {
+    pub fn requires_copy(&self) -> bool {
         matches!(self, Target::Job(_) | Target::CronJob(_))
     }
 
@@ -1,4 +1,14 @@
-use std::{collections::HashSet, fmt, hash::Hash, marker::PhantomData, ops::Deref, str::FromStr};
+use std::{
+    collections::HashSet,
+    fmt,
+    hash::Hash,
+    io::{self, Read},
+    marker::PhantomData,
+    ops::Deref,
+    path::Path,
+    str::FromStr,
+    sync::OnceLock,
+};
 
 use schemars::JsonSchema;
 use serde::{Deserialize, Deserializer, Serialize, de};
@@ -305,3 +315,49 @@ pub fn read_resolved_config() -> Result<LayerConfig, ConfigError> {
 
     LayerConfig::decode(&encoded)
 }
+
+/// Source of a file used by the config, such as the config file itself, or
+/// [`feature.env.env_file`](crate::feature::env::EnvConfig::env_file).
+///
+/// - `-` reads the file from the standard input;
+/// - `file://<path>` reads the file from `<path>`;
+/// - anything else is a plain path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileSource<'a> {
+    Stdin,
+    Path(&'a Path),
+}
+
+impl<'a> FileSource<'a> {
+    /// Value that makes us read the file from the standard input.
+    pub const STDIN: &'static str = "-";
+
+    pub fn new(path: &'a Path) -> Self {
+        match path.to_str() {
+            Some(Self::STDIN) => Self::Stdin,
+            Some(path) => Self::Path(Path::new(path.strip_prefix("file://").unwrap_or(path))),
+            None => Self::Path(path),
+        }
+    }
+
+    /// Reads the whole file.
+    ///
+    /// The standard input is read only once, later calls return the same contents. This way, the
+    /// config can be resolved multiple times in one process.
+    pub fn read_to_string(self) -> io::Result<String> {
+        static STDIN_CONTENTS: OnceLock<String> = OnceLock::new();
+
+        match self {
+            Self::Stdin => {
+                if let Some(contents) = STDIN_CONTENTS.get() {
+                    return Ok(contents.clone());
+                }
+
+                let mut contents = String::new();
+                io::stdin().read_to_string(&mut contents)?;
+                Ok(STDIN_CONTENTS.get_or_init(|| contents).clone())
+            }
+            Self::Path(path) => std::fs::read_to_string(path),
+        }
+    }
+}
@@ -0,0 +1,26 @@
+use std::process::Command;
+
+/// Sets `MIRRORD_GIT_HASH`, used in `BuildVersion::current`.
+///
+/// Can be overridden with the `MIRRORD_GIT_HASH` environment variable, e.g. when building from a
+/// source archive.
+fn main() {
+    println!("cargo::rerun-if-env-changed=MIRRORD_GIT_HASH");
+
+    let git_hash = std::env::var("MIRRORD_GIT_HASH")
+        .ok()
+        .or_else(|| {
+            let output = Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+        .filter(|hash| !hash.is_empty())
+        .unwrap_or_else(|| "unknown".to_string());
+
+    println!("cargo::rustc-env=MIRRORD_GIT_HASH={git_hash}");
+}
@@ -1,6 +1,10 @@
 //! Protocol used in communication between the layer and the internal proxy.
 //! This protocol does not have to be backwards compatible and can be changed freely, as the
 //! internal proxy and the layer are shipped together in a single binary.
+//!
+//! A layer library left from a different mirrord build (e.g. in a stale `LD_PRELOAD`) can still
+//! connect to the internal proxy, so the layer starts the session with its [`BuildVersion`], and
+//! the internal proxy rejects the session if it does not match its own.
 
 use std::{
     collections::HashMap,
@@ -10,11 +14,15 @@ use std::{
 
 use bincode::{Decode, Encode};
 use mirrord_protocol::{
-    FileRequest, FileResponse, GetEnvVarsRequest, Port, RemoteResult,
-    dns::{GetAddrInfoRequestV2, GetAddrInfoResponse},
+    FileRequest, FileResponse, GetEnvVarsRequest, LogMessage, Port, RemoteResult,
+    dns::{
+        GetAddrInfoRequestV2, GetAddrInfoResponse, ReverseDnsLookupRequest,
+        ReverseDnsLookupResponse,
+    },
     file::*,
     outgoing::SocketAddress,
     tcp::{MirrorType, StealType},
+    time::RemoteTime,
 };
 
 #[cfg(feature = "codec")]
@@ -45,12 +53,19 @@ pub enum LayerToProxyMessage {
     File(FileRequest),
     /// A DNS request.
     GetAddrInfo(GetAddrInfoRequestV2),
+    /// A reverse DNS request.
+    ReverseDnsLookup(ReverseDnsLookupRequest),
     /// Requests related to outgoing connections.
     Outgoing(OutgoingRequest),
     /// Requests related to incoming connections.
     Incoming(IncomingRequest),
     /// Fetch environment variables from the target.
     GetEnv(GetEnvVarsRequest),
+    /// Fetch the wall-clock time of the agent.
+    GetRemoteTime(RemoteTimeRequest),
+    /// A message that the internal proxy should show to the user, e.g. the remote port picked
+    /// for a listener bound to port `0`.
+    LogMessage(LogMessage),
 }
 
 /// Layer process information
@@ -99,6 +114,51 @@ pub struct NewSessionRequest {
     /// The layer inherits environment variables from its parent.
     pub parent_layer: Option<LayerId>,
     pub process_info: ProcessInfo,
+    /// Build of the layer, must be [compatible](BuildVersion::is_compatible_with) with the build
+    /// of the internal proxy.
+    pub layer_version: BuildVersion,
+    /// Path to the layer library, if the layer was able to find it.
+    ///
+    /// Used only to tell the user which library to remove when the versions don't match.
+    pub layer_path: Option<String>,
+}
+
+/// Identifies the mirrord build that produced the layer or the internal proxy.
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+pub struct BuildVersion {
+    /// Version of the mirrord crates, e.g. `3.150.0`.
+    pub version: String,
+    /// Git commit the build was made from, [`BuildVersion::UNKNOWN_GIT_HASH`] if it was not
+    /// available at build time.
+    pub git_hash: String,
+}
+
+impl BuildVersion {
+    /// Placeholder for [`BuildVersion::git_hash`] when the build was not made from a git
+    /// repository.
+    pub const UNKNOWN_GIT_HASH: &str = "unknown";
+
+    /// Returns the version of this build.
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: env!("MIRRORD_GIT_HASH").to_string(),
+        }
+    }
+
+    /// Versions must be equal. Git hashes are compared only if both are known.
+    pub fn is_compatible_with(&self, other: &Self) -> bool {
+        self.version == other.version
+            && (self.git_hash == other.git_hash
+                || self.git_hash == Self::UNKNOWN_GIT_HASH
+                || other.git_hash == Self::UNKNOWN_GIT_HASH)
+    }
+}
+
+impl fmt::Display for BuildVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.version, self.git_hash)
+    }
 }
 
 /// Supported network protocols when intercepting outgoing connections.
@@ -115,6 +175,9 @@ pub enum NetProtocol {
     /// which alters this socket's behavior. Currently, we require this call to happen before we
     /// intercept outgoing UDP.
     Datagrams,
+    /// Sequenced packets over UDS (`SOCK_SEQPACKET`). Boundaries between the packets are
+    /// preserved.
+    SeqPacket,
 }
 
 impl fmt::Display for NetProtocol {
@@ -122,12 +185,18 @@ impl fmt::Display for NetProtocol {
         let as_str = match self {
             Self::Stream => "STREAM",
             Self::Datagrams => "DGRAM",
+            Self::SeqPacket => "SEQPACKET",
         };
 
         f.write_str(as_str)
     }
 }
 
+/// A request for the wall-clock time of the agent, used to compute the offset between the local
+/// and the remote clock.
+#[derive(Encode, Decode, Debug, PartialEq, Eq)]
+pub struct RemoteTimeRequest;
+
 /// Requests related to outgoing connections.
 #[derive(Encode, Decode, Debug, PartialEq, Eq)]
 pub enum OutgoingRequest {
@@ -202,6 +271,9 @@ pub struct ConnMetadataResponse {
     /// Due to limitations of the `intproxy <-> agent` protocol, HTTP connections will send the
     /// real address (localhost).
     pub local_address: IpAddr,
+    /// Port that the connection was headed to in the cluster, which is not the port of the
+    /// local listener when `feature.network.incoming.port_mapping` is used.
+    pub destination_port: Port,
 }
 
 /// A request to start proxying incoming connections.
@@ -244,6 +316,8 @@ pub enum ProxyToLayerMessage {
     File(FileResponse),
     /// A response to layer's [`GetAddrInfoRequestV2`].
     GetAddrInfo(GetAddrInfoResponse),
+    /// A response to layer's [`ReverseDnsLookupRequest`].
+    ReverseDnsLookup(RemoteResult<ReverseDnsLookupResponse>),
     /// A response to layer's [`OutgoingRequest`].
     Outgoing(OutgoingResponse),
     /// A response to layer's [`IncomingRequest`].
@@ -252,6 +326,15 @@ pub enum ProxyToLayerMessage {
     GetEnv(RemoteResult<HashMap<String, String>>),
     /// Internal proxy encountered a fatal error.
     ProxyFailed(String),
+    /// A response to [`NewSessionRequest`] from a layer with an incompatible [`BuildVersion`].
+    /// Contains an error message for the user.
+    NewSessionRejected(String),
+    /// A response to layer's [`RemoteTimeRequest`].
+    RemoteTime(RemoteResult<RemoteTime>),
+    /// A message that should be shown to the user, e.g. about a reconnect to the agent.
+    ///
+    /// Not a response to any request, the layer should not match its [`MessageId`].
+    LogMessage(LogMessage),
 }
 
 /// A response to layer's [`IncomingRequest`].
@@ -343,6 +426,34 @@ impl_request!(
     res_path = ProxyToLayerMessage::File => FileResponse::Open,
 );
 
+impl_request!(
+    req = OpenFileRequestV2,
+    res = RemoteResult<OpenFileResponse>,
+    req_path = LayerToProxyMessage::File => FileRequest::OpenV2,
+    res_path = ProxyToLayerMessage::File => FileResponse::Open,
+);
+
+impl_request!(
+    req = OpenRelativeFileRequestV2,
+    res = RemoteResult<OpenFileResponse>,
+    req_path = LayerToProxyMessage::File => FileRequest::OpenRelativeV2,
+    res_path = ProxyToLayerMessage::File => FileResponse::Open,
+);
+
+impl_request!(
+    req = OpenFileRequestV3,
+    res = RemoteResult<OpenFileResponse>,
+    req_path = LayerToProxyMessage::File => FileRequest::OpenV3,
+    res_path = ProxyToLayerMessage::File => FileResponse::Open,
+);
+
+impl_request!(
+    req = OpenRelativeFileRequestV3,
+    res = RemoteResult<OpenFileResponse>,
+    req_path = LayerToProxyMessage::File => FileRequest::OpenRelativeV3,
+    res_path = ProxyToLayerMessage::File => FileResponse::Open,
+);
+
 impl_request!(
     req = ReadFileRequest,
     res = RemoteResult<ReadFileResponse>,
@@ -427,6 +538,13 @@ impl_request!(
     res_path = ProxyToLayerMessage::File => FileResponse::Access,
 );
 
+impl_request!(
+    req = AccessFileRequestV2,
+    res = RemoteResult<AccessFileResponse>,
+    req_path = LayerToProxyMessage::File => FileRequest::AccessV2,
+    res_path = ProxyToLayerMessage::File => FileResponse::Access,
+);
+
 impl_request!(
     req = XstatRequest,
     res = RemoteResult<XstatResponse>,
@@ -509,6 +627,13 @@ impl_request!(
     res_path = ProxyToLayerMessage::GetAddrInfo,
 );
 
+impl_request!(
+    req = ReverseDnsLookupRequest,
+    res = RemoteResult<ReverseDnsLookupResponse>,
+    req_path = LayerToProxyMessage::ReverseDnsLookup,
+    res_path = ProxyToLayerMessage::ReverseDnsLookup,
+);
+
 impl_request!(
     req = OutgoingConnectRequest,
     res = RemoteResult<OutgoingConnectResponse>,
@@ -554,6 +679,15 @@ impl_request!(
     res_path = ProxyToLayerMessage::GetEnv,
 );
 
+impl_request!(
+    req = RemoteTimeRequest,
+    res = RemoteResult<RemoteTime>,
+    req_path = LayerToProxyMessage::GetRemoteTime,
+    res_path = ProxyToLayerMessage::RemoteTime,
+);
+
+impl_request!(req = LogMessage, req_path = LayerToProxyMessage::LogMessage,);
+
 impl_request!(
     req = RenameRequest,
     res = RemoteResult<()>,
@@ -588,3 +722,80 @@ impl_request!(
     req_path = LayerToProxyMessage::File => FileRequest::Fchmod,
     res_path = ProxyToLayerMessage::File => FileResponse::Fchmod,
 );
+
+impl_request!(
+    req = GetXattrRequest,
+    res = RemoteResult<GetXattrResponse>,
+    req_path = LayerToProxyMessage::File => FileRequest::GetXattr,
+    res_path = ProxyToLayerMessage::File => FileResponse::GetXattr,
+);
+
+impl_request!(
+    req = SetXattrRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::SetXattr,
+    res_path = ProxyToLayerMessage::File => FileResponse::SetXattr,
+);
+
+impl_request!(
+    req = ListXattrRequest,
+    res = RemoteResult<ListXattrResponse>,
+    req_path = LayerToProxyMessage::File => FileRequest::ListXattr,
+    res_path = ProxyToLayerMessage::File => FileResponse::ListXattr,
+);
+
+impl_request!(
+    req = FlockRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Flock,
+    res_path = ProxyToLayerMessage::File => FileResponse::Flock,
+);
+
+impl_request!(
+    req = SyncfsRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Syncfs,
+    res_path = ProxyToLayerMessage::File => FileResponse::Syncfs,
+);
+
+impl_request!(
+    req = ReadvFileRequest,
+    res = RemoteResult<ReadvFileResponse>,
+    req_path = LayerToProxyMessage::File => FileRequest::Readv,
+    res_path = ProxyToLayerMessage::File => FileResponse::Readv,
+);
+
+impl_request!(
+    req = WritevFileRequest,
+    res = RemoteResult<WriteFileResponse>,
+    req_path = LayerToProxyMessage::File => FileRequest::Writev,
+    res_path = ProxyToLayerMessage::File => FileResponse::Writev,
+);
+
+impl_request!(
+    req = CopyFileRangeRequest,
+    res = RemoteResult<CopyFileRangeResponse>,
+    req_path = LayerToProxyMessage::File => FileRequest::CopyFileRange,
+    res_path = ProxyToLayerMessage::File => FileResponse::CopyFileRange,
+);
+
+impl_request!(
+    req = UtimensRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Utimens,
+    res_path = ProxyToLayerMessage::File => FileResponse::Futimens,
+);
+
+impl_request!(
+    req = ChownRequest,
+    res = RemoteResult<()>,
+    req_path = LayerToProxyMessage::File => FileRequest::Chown,
+    res_path = ProxyToLayerMessage::File => FileResponse::Fchown,
+);
+
+impl_request!(
+    req = FcntlLockRequest,
+    res = RemoteResult<FcntlLockResponse>,
+    req_path = LayerToProxyMessage::File => FileRequest::FcntlLock,
+    res_path = ProxyToLayerMessage::File => FileResponse::FcntlLock,
+);
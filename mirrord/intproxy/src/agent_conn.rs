@@ -165,6 +165,19 @@ impl AgentConnection {
         analytics: &mut R,
     ) -> Result<Self, AgentConnectionError> {
         let kind = connect_info.discriminant();
+        let reconnect_config = &config.internal_proxy.reconnect;
+        // Unless configured otherwise, we reconnect only when the session is kept on the other
+        // side of the connection.
+        let reconnect_flow = |by_default: bool, connect_info: AgentConnectInfo| {
+            if reconnect_config.enabled.unwrap_or(by_default) && reconnect_config.max_attempts > 0 {
+                ReconnectFlow::ConnectInfo {
+                    config: Box::new(config.clone()),
+                    connect_info,
+                }
+            } else {
+                ReconnectFlow::Break(kind)
+            }
+        };
 
         let (connection, reconnect) = match connect_info {
             AgentConnectInfo::Operator(session) => {
@@ -174,10 +187,7 @@ impl AgentConnection {
                 (
                     connection.conn,
                     if session.allow_reconnect {
-                        ReconnectFlow::ConnectInfo {
-                            config: Box::new(config.clone()),
-                            connect_info: AgentConnectInfo::Operator(session),
-                        }
+                        reconnect_flow(true, AgentConnectInfo::Operator(session))
                     } else {
                         ReconnectFlow::Break(kind)
                     },
@@ -194,17 +204,36 @@ impl AgentConnection {
 
                 let stream = socket.connect(proxy_addr).await?;
 
-                let conn = match tls_pem {
-                    Some(tls_pem) => tls::wrap_raw_connection(stream, tls_pem.as_path()).await?,
-                    None => Connection::from_stream(stream).await?,
+                let max_message_size = config.internal_proxy.max_message_size;
+                let conn = match &tls_pem {
+                    Some(tls_pem) => {
+                        tls::wrap_raw_connection(stream, tls_pem.as_path(), max_message_size)
+                            .await?
+                    }
+                    None => {
+                        Connection::from_stream_with_max_message_size(stream, max_message_size)
+                            .await?
+                    }
                 };
 
-                (conn, ReconnectFlow::Break(kind))
+                (
+                    conn,
+                    reconnect_flow(
+                        true,
+                        AgentConnectInfo::ExternalProxy {
+                            proxy_addr,
+                            tls_pem,
+                        },
+                    ),
+                )
             }
 
             AgentConnectInfo::DirectKubernetes(connect_info) => {
                 let conn = portforward::create_connection(config, connect_info.clone()).await?;
-                (conn, ReconnectFlow::Break(kind))
+                (
+                    conn,
+                    reconnect_flow(false, AgentConnectInfo::DirectKubernetes(connect_info)),
+                )
             }
 
             #[cfg(test)]
@@ -212,12 +241,7 @@ impl AgentConnection {
                 let (conn, tx, rx) = Connection::dummy();
                 sender.send((tx, rx)).await.unwrap();
 
-                let reconnect = ReconnectFlow::ConnectInfo {
-                    config: Box::new(config.clone()),
-                    connect_info: AgentConnectInfo::Dummy(sender),
-                };
-
-                (conn, reconnect)
+                (conn, reconnect_flow(true, AgentConnectInfo::Dummy(sender)))
             }
         };
 
@@ -320,11 +344,26 @@ impl RestartableBackgroundTask for AgentConnection {
                     .send(ProxyMessage::ConnectionRefresh(ConnectionRefresh::Start))
                     .await;
 
-                // 1s, 2s, 4s, 8s, 8s, ...
+                // A dead external proxy is started again by the CLI, so we keep trying for the
+                // whole allowed outage instead of a fixed number of attempts.
+                let reconnect_config = &config.internal_proxy.reconnect;
+                let (max_retries, max_outage) = match connect_info {
+                    AgentConnectInfo::ExternalProxy { .. } => (
+                        usize::MAX,
+                        Some(Duration::from_secs(config.external_proxy.outage_timeout)),
+                    ),
+                    // The first attempt is not delayed.
+                    _ => (
+                        reconnect_config.max_attempts.saturating_sub(1) as usize,
+                        None,
+                    ),
+                };
+
+                // With the defaults: 1s, 2s, 4s, 8s, 8s, ...
                 let retry_strategy = ExponentialBackoff::from_millis(2)
-                    .factor(500)
-                    .max_delay(Duration::from_secs(8))
-                    .take(10);
+                    .factor(reconnect_config.min_ms.div_ceil(2))
+                    .max_delay(Duration::from_millis(reconnect_config.max_ms))
+                    .take(max_retries);
                 // Unless the operator responded with explicit 410 (meaning that the session is
                 // permanently gone), we can still retry.
                 let can_retry = |error: &AgentConnectionError| match error {
@@ -338,7 +377,7 @@ impl RestartableBackgroundTask for AgentConnection {
                     _ => true,
                 };
 
-                let connection = RetryIf::spawn(
+                let reconnect = RetryIf::spawn(
                     retry_strategy,
                     || async {
                         message_bus
@@ -359,8 +398,20 @@ impl RestartableBackgroundTask for AgentConnection {
                             })
                     },
                     can_retry,
-                )
-                .await;
+                );
+                let connection = match max_outage {
+                    Some(max_outage) => tokio::time::timeout(max_outage, reconnect)
+                        .await
+                        .inspect_err(|_| {
+                            tracing::error!(
+                                ?max_outage,
+                                "The {} did not come back in time",
+                                connect_info.discriminant(),
+                            );
+                        })
+                        .unwrap_or(Ok(None)),
+                    None => reconnect.await,
+                };
 
                 match connection {
                     Ok(Some(connection)) => {
@@ -18,7 +18,11 @@ pub async fn create_connection(
         .await
         .map_err(AgentConnectionError::Kube)?;
 
-    Ok(Connection::from_stream(convert(stream)).await?)
+    Ok(Connection::from_stream_with_max_message_size(
+        convert(stream),
+        config.internal_proxy.max_message_size,
+    )
+    .await?)
 }
 
 // If I don't do this stuff rustc complains about some cursed lifetime
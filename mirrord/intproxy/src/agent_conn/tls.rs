@@ -28,9 +28,11 @@ pub enum ConnectionTlsError {
 ///
 /// * `stream` - [`TcpStream`] to use for establishing the TLS connection.
 /// * `tls_pem` - path to a PEM file generated with [`SecureChannelSetup::try_new`].
+/// * `max_message_size` - see [`Connection::from_stream_with_max_message_size`].
 pub async fn wrap_raw_connection(
     stream: TcpStream,
     tls_pem: &Path,
+    max_message_size: usize,
 ) -> Result<Connection<Client>, ConnectionTlsError> {
     let connector = SecureChannelSetup::create_connector(tls_pem).await?;
 
@@ -42,5 +44,5 @@ pub async fn wrap_raw_connection(
         .await
         .map_err(ConnectionTlsError::ConnectionError)?;
 
-    Ok(Connection::from_stream(stream).await?)
+    Ok(Connection::from_stream_with_max_message_size(stream, max_message_size).await?)
 }
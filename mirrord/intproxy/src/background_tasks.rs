@@ -366,4 +366,9 @@ impl<T: BackgroundTask> TaskSender<T> {
     pub async fn send<M: Into<T::MessageIn>>(&self, msg: M) {
         let _ = self.tx.send(msg.into()).await;
     }
+
+    /// Returns the number of messages waiting in the task's queue.
+    pub fn queued(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity()
+    }
 }
@@ -0,0 +1,171 @@
+//! Synthetic faults injected into remote operations, enabled with
+//! `experimental.fault_injection`.
+//!
+//! The faults are injected by the proxies handling the operations (see
+//! [`proxies`](crate::proxies)), before the request is sent to the agent. A failed operation is
+//! never sent to the agent, the layer gets the same [`ResponseError`] it would get from a real
+//! failure.
+//!
+//! Delayed requests wait in [`DelayedRequests`], so the proxy keeps handling other messages in the
+//! meantime.
+
+use std::{fmt, future, ops::Not, time::Duration};
+
+use futures::{StreamExt, future::BoxFuture, stream::FuturesUnordered};
+use mirrord_config::experimental::FaultConfig;
+use mirrord_protocol::{
+    DnsLookupError, ErrorKindInternal, RemoteIOError, ResolveErrorKindInternal, ResponseError,
+};
+
+/// `ECONNREFUSED` on Linux, as sent by the agent.
+const ECONNREFUSED: i32 = 111;
+
+/// `EIO` on Linux, as sent by the agent.
+const EIO: i32 = 5;
+
+/// Injects synthetic faults into one type of remote operation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FaultInjector {
+    /// Added to every operation.
+    delay: Duration,
+    /// Percentage of operations that fail, from 0 to 100.
+    failure_percent: u8,
+}
+
+impl FaultInjector {
+    pub fn new(config: &FaultConfig) -> Self {
+        Self {
+            delay: Duration::from_millis(config.delay),
+            failure_percent: config.failure_percent.min(100),
+        }
+    }
+
+    /// Returns the delay added to every operation, [`None`] if there is no delay.
+    pub fn delay(&self) -> Option<Duration> {
+        self.delay.is_zero().not().then_some(self.delay)
+    }
+
+    /// Decides whether the operation should fail.
+    pub fn fails(&self) -> bool {
+        self.failure_percent > 0 && rand::random_ratio(self.failure_percent.into(), 100)
+    }
+
+    /// Error returned for a failed outgoing connection.
+    pub fn connect_error() -> ResponseError {
+        ResponseError::RemoteIO(RemoteIOError {
+            raw_os_error: Some(ECONNREFUSED),
+            kind: ErrorKindInternal::ConnectionRefused,
+        })
+    }
+
+    /// Error returned for a failed file read.
+    pub fn file_read_error() -> ResponseError {
+        ResponseError::RemoteIO(RemoteIOError {
+            raw_os_error: Some(EIO),
+            kind: ErrorKindInternal::Other,
+        })
+    }
+
+    /// Error returned for a failed DNS lookup.
+    pub fn dns_error() -> ResponseError {
+        ResponseError::DnsLookup(DnsLookupError {
+            kind: ResolveErrorKindInternal::Timeout,
+        })
+    }
+}
+
+/// Requests held back by a [`FaultInjector::delay`].
+///
+/// Each request waits for its own delay, without blocking the proxy that handles it.
+pub struct DelayedRequests<R> {
+    requests: FuturesUnordered<BoxFuture<'static, R>>,
+}
+
+impl<R: Send + 'static> DelayedRequests<R> {
+    /// Holds back the `request` for the given `delay`.
+    pub fn push(&mut self, delay: Duration, request: R) {
+        self.requests.push(Box::pin(async move {
+            tokio::time::sleep(delay).await;
+            request
+        }));
+    }
+
+    /// Returns the next request whose delay has passed.
+    ///
+    /// Never resolves when there are no delayed requests.
+    pub async fn next(&mut self) -> R {
+        match self.requests.next().await {
+            Some(request) => request,
+            None => future::pending().await,
+        }
+    }
+}
+
+impl<R> Default for DelayedRequests<R> {
+    fn default() -> Self {
+        Self {
+            requests: Default::default(),
+        }
+    }
+}
+
+impl<R> fmt::Debug for DelayedRequests<R> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DelayedRequests")
+            .field("len", &self.requests.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+
+    #[test]
+    fn inject_failures() {
+        let never = FaultInjector::new(&FaultConfig {
+            delay: 0,
+            failure_percent: 0,
+        });
+        let always = FaultInjector::new(&FaultConfig {
+            delay: 50,
+            failure_percent: 100,
+        });
+
+        for _ in 0..100 {
+            assert!(never.fails().not());
+            assert!(always.fails());
+        }
+
+        assert_eq!(never.delay(), None);
+        assert_eq!(always.delay(), Some(Duration::from_millis(50)));
+    }
+
+    /// Each request waits only for its own delay, the requests are not delayed one after another.
+    #[tokio::test]
+    async fn delayed_requests_wait_concurrently() {
+        let mut delayed = DelayedRequests::default();
+        let start = Instant::now();
+
+        delayed.push(Duration::from_millis(100), 1);
+        delayed.push(Duration::from_millis(100), 2);
+        delayed.push(Duration::from_millis(50), 3);
+
+        assert_eq!(delayed.next().await, 3);
+        let mut rest = [delayed.next().await, delayed.next().await];
+        rest.sort();
+        assert_eq!(rest, [1, 2]);
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(100));
+        assert!(elapsed < Duration::from_millis(200), "{elapsed:?}");
+
+        assert!(
+            tokio::time::timeout(Duration::from_millis(10), delayed.next())
+                .await
+                .is_err()
+        );
+    }
+}
@@ -1,7 +1,8 @@
-use std::{io, net::SocketAddr};
+use std::{io, net::SocketAddr, ops::Not};
 
 use mirrord_intproxy_protocol::{
-    LayerId, LayerToProxyMessage, LocalMessage, NewSessionRequest, ProxyToLayerMessage,
+    BuildVersion, LayerId, LayerToProxyMessage, LocalMessage, NewSessionRequest,
+    ProxyToLayerMessage,
     codec::{AsyncDecoder, AsyncEncoder, CodecError},
 };
 use thiserror::Error;
@@ -24,6 +25,8 @@ pub enum LayerInitializerError {
     NoMessage,
     #[error("layer sent unexpected message: {0:?}")]
     UnexpectedMessage(LayerToProxyMessage),
+    #[error("{0}")]
+    IncompatibleLayer(String),
 }
 
 /// Handles logic for accepting new layer connections.
@@ -51,25 +54,58 @@ impl LayerInitializer {
     ) -> Result<NewLayer, LayerInitializerError> {
         let mut decoder: AsyncDecoder<LocalMessage<LayerToProxyMessage>, _> =
             AsyncDecoder::new(stream);
-        let msg = decoder
-            .receive()
-            .await?
-            .ok_or(LayerInitializerError::NoMessage)?;
-
-        let id = self.next_layer_id;
-        self.next_layer_id.0 += 1;
+        let msg = match decoder.receive().await {
+            Ok(msg) => msg.ok_or(LayerInitializerError::NoMessage)?,
+            // Layers from other builds may encode the first message differently.
+            Err(CodecError::DecodeError(error)) => {
+                return Err(LayerInitializerError::IncompatibleLayer(format!(
+                    "failed to decode the first message from the layer ({error}), the layer \
+                    library probably comes from a different mirrord build than mirrord {} at {}",
+                    BuildVersion::current(),
+                    proxy_path(),
+                )));
+            }
+            Err(error) => return Err(error.into()),
+        };
 
         let NewSessionRequest {
             parent_layer,
             process_info,
+            layer_version,
+            layer_path,
         } = match msg.inner {
             LayerToProxyMessage::NewSession(request) => request,
             other => return Err(LayerInitializerError::UnexpectedMessage(other)),
         };
-        tracing::info!(?parent_layer, ?process_info, "New layer connected");
 
         let mut encoder: AsyncEncoder<LocalMessage<ProxyToLayerMessage>, _> =
             AsyncEncoder::new(decoder.into_inner());
+
+        let proxy_version = BuildVersion::current();
+        if layer_version.is_compatible_with(&proxy_version).not() {
+            let error = format!(
+                "mirrord layer {layer_version} at {} is incompatible with mirrord \
+                {proxy_version} at {}, remove the stale layer library (e.g. from `LD_PRELOAD`) \
+                and try again",
+                layer_path.as_deref().unwrap_or("<unknown path>"),
+                proxy_path(),
+            );
+            encoder
+                .send(&LocalMessage {
+                    message_id: msg.message_id,
+                    inner: ProxyToLayerMessage::NewSessionRejected(error.clone()),
+                })
+                .await?;
+            encoder.flush().await?;
+
+            return Err(LayerInitializerError::IncompatibleLayer(error));
+        }
+
+        let id = self.next_layer_id;
+        self.next_layer_id.0 += 1;
+
+        tracing::info!(?parent_layer, ?process_info, "New layer connected");
+
         encoder
             .send(&LocalMessage {
                 message_id: msg.message_id,
@@ -89,6 +125,13 @@ impl LayerInitializer {
     }
 }
 
+/// Path to the binary running the internal proxy, for the version mismatch errors.
+fn proxy_path() -> String {
+    std::env::current_exe()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|_| "<unknown path>".to_string())
+}
+
 impl BackgroundTask for LayerInitializer {
     type Error = LayerInitializerError;
     type MessageIn = ();
@@ -105,10 +148,167 @@ impl BackgroundTask for LayerInitializer {
 
                 res = self.listener.accept() => {
                     let (stream, layer_address) = res.map_err(LayerInitializerError::Accept)?;
-                    let new_layer = self.handle_new_stream(stream, layer_address).await?;
+                    let new_layer = match self.handle_new_stream(stream, layer_address).await {
+                        Ok(new_layer) => new_layer,
+                        // Don't let a stale layer break the session of the other layers.
+                        Err(LayerInitializerError::IncompatibleLayer(error)) => {
+                            tracing::error!(%error, "Rejected a layer connection");
+                            crate::notify_user(&error);
+                            continue;
+                        }
+                        // The CLI connects once to check that we're up, before it starts the
+                        // user application.
+                        Err(LayerInitializerError::NoMessage) => {
+                            tracing::debug!(
+                                %layer_address,
+                                "Connection closed before the handshake"
+                            );
+                            continue;
+                        }
+                        Err(error) => break Err(error),
+                    };
                     message_bus.send(new_layer).await;
                 },
             }
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+
+    use mirrord_intproxy_protocol::{
+        BuildVersion, LayerId, LayerToProxyMessage, LocalMessage, NewSessionRequest, ProcessInfo,
+        ProxyToLayerMessage, codec,
+    };
+    use mirrord_protocol_io::Connection;
+    use rstest::rstest;
+    use tokio::net::{TcpListener, TcpStream};
+
+    use super::{LayerInitializer, LayerInitializerError};
+    use crate::{
+        background_tasks::BackgroundTasks,
+        error::ProxyRuntimeError,
+        main_tasks::{MainTaskId, ProxyMessage},
+    };
+
+    /// Connects a fake layer with the given [`BuildVersion`] to the [`LayerInitializer`] at
+    /// `addr`, returning its response to the [`NewSessionRequest`].
+    async fn connect_layer(addr: SocketAddr, layer_version: BuildVersion) -> ProxyToLayerMessage {
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut encoder, mut decoder) = codec::make_async_framed::<
+            LocalMessage<LayerToProxyMessage>,
+            LocalMessage<ProxyToLayerMessage>,
+        >(stream);
+        encoder
+            .send(&LocalMessage {
+                message_id: 0,
+                inner: LayerToProxyMessage::NewSession(NewSessionRequest {
+                    parent_layer: None,
+                    process_info: ProcessInfo {
+                        pid: 1337,
+                        parent_pid: 1336,
+                        name: "hello there".into(),
+                        cmdline: vec!["hello there".into()],
+                        loaded: true,
+                    },
+                    layer_version,
+                    layer_path: Some("/tmp/mirrord/libmirrord_layer.so".into()),
+                }),
+            })
+            .await
+            .unwrap();
+        encoder.flush().await.unwrap();
+
+        decoder.receive().await.unwrap().unwrap().inner
+    }
+
+    /// Connects a fake layer with the given [`BuildVersion`] to the [`LayerInitializer`],
+    /// returning the result of the handshake on both sides.
+    async fn handshake(
+        layer_version: BuildVersion,
+    ) -> (Result<LayerId, LayerInitializerError>, ProxyToLayerMessage) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut initializer = LayerInitializer::new(listener);
+
+        let layer = tokio::spawn(connect_layer(addr, layer_version));
+
+        let (stream, layer_address) = initializer.listener.accept().await.unwrap();
+        let result = initializer
+            .handle_new_stream(stream, layer_address)
+            .await
+            .map(|new_layer| new_layer.id);
+
+        (result, layer.await.unwrap())
+    }
+
+    /// Verifies that a layer from the same build starts a new session.
+    #[rstest]
+    #[case::same_build(BuildVersion::current())]
+    #[case::unknown_git_hash(BuildVersion {
+        git_hash: BuildVersion::UNKNOWN_GIT_HASH.into(),
+        ..BuildVersion::current()
+    })]
+    #[tokio::test]
+    async fn compatible_layer_accepted(#[case] layer_version: BuildVersion) {
+        let (result, response) = handshake(layer_version).await;
+
+        assert_eq!(result.unwrap(), LayerId(0));
+        assert_eq!(response, ProxyToLayerMessage::NewSession(LayerId(0)));
+    }
+
+    /// Verifies that a layer from a different build is rejected with an error that names both
+    /// versions and the path to the layer.
+    #[rstest]
+    #[case::different_version(BuildVersion {
+        version: "0.0.1".into(),
+        ..BuildVersion::current()
+    })]
+    #[case::different_git_hash(BuildVersion {
+        git_hash: "0123456789ab".into(),
+        ..BuildVersion::current()
+    })]
+    #[tokio::test]
+    async fn incompatible_layer_rejected(#[case] layer_version: BuildVersion) {
+        let (result, response) = handshake(layer_version.clone()).await;
+
+        let Err(LayerInitializerError::IncompatibleLayer(error)) = result else {
+            panic!("unexpected handshake result: {result:?}");
+        };
+        assert!(error.contains(&layer_version.to_string()));
+        assert!(error.contains(&BuildVersion::current().to_string()));
+        assert!(error.contains("/tmp/mirrord/libmirrord_layer.so"));
+        assert_eq!(response, ProxyToLayerMessage::NewSessionRejected(error));
+    }
+
+    /// Verifies that a connection closed before the handshake, like the CLI health check, is
+    /// skipped, and the next layer still starts a new session.
+    #[tokio::test]
+    async fn connection_without_handshake_skipped() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let (connection, _, _out) = Connection::dummy();
+        let mut tasks: BackgroundTasks<MainTaskId, ProxyMessage, ProxyRuntimeError> =
+            BackgroundTasks::new(connection.tx_handle());
+        let _initializer = tasks.register(
+            LayerInitializer::new(listener),
+            MainTaskId::LayerInitializer,
+            32,
+        );
+
+        drop(TcpStream::connect(addr).await.unwrap());
+
+        let response = connect_layer(addr, BuildVersion::current()).await;
+        assert_eq!(response, ProxyToLayerMessage::NewSession(LayerId(0)));
+
+        let (task_id, update) = tasks.next().await.unwrap();
+        assert_eq!(task_id, MainTaskId::LayerInitializer);
+        let ProxyMessage::NewLayer(new_layer) = update.unwrap_message() else {
+            panic!("expected a new layer");
+        };
+        assert_eq!(new_layer.id, LayerId(0));
+    }
+}
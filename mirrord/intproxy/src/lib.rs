@@ -4,8 +4,11 @@
 
 use std::{
     collections::{HashMap, HashSet, VecDeque},
+    io::{self, Write},
+    net::IpAddr,
     ops::ControlFlow,
-    time::Duration,
+    path::PathBuf,
+    time::{Duration, Instant},
 };
 
 use background_tasks::{BackgroundTasks, TaskSender, TaskUpdate};
@@ -14,19 +17,28 @@ use layer_conn::LayerConnection;
 use layer_initializer::LayerInitializer;
 use main_tasks::{FromLayer, LayerForked, MainTaskId, ProxyMessage, ToLayer};
 use mirrord_config::{
-    experimental::ExperimentalConfig, feature::network::incoming::tls_delivery::LocalTlsDelivery,
+    experimental::ExperimentalConfig,
+    feature::{
+        fs::ReadonlyFileBuffers,
+        network::incoming::{
+            OnLocalUnavailable, SourceIpDelivery, auto_downgrade::AutoDowngradeConfig,
+            tls_delivery::LocalTlsDelivery,
+        },
+    },
 };
 use mirrord_intproxy_protocol::{
     IncomingRequest, LayerId, LayerToProxyMessage, LocalMessage, MessageId, ProcessInfo,
+    ProxyToLayerMessage,
 };
 use mirrord_protocol::{
-    CLIENT_READY_FOR_LOGS, ClientMessage, DaemonMessage, FileRequest, LogLevel,
+    CLIENT_READY_FOR_LOGS, ClientMessage, DaemonMessage, FileRequest, LogLevel, LogMessage,
+    tcp::SourceCidr,
 };
 use mirrord_protocol_io::{Client, TxHandle};
 use ping_pong::{PingPong, PingPongMessage};
 use proxies::{
     files::{FilesProxy, FilesProxyMessage},
-    incoming::{IncomingProxy, IncomingProxyMessage},
+    incoming::{IncomingProxy, IncomingProxyMessage, ResumeStealSignal},
     outgoing::{OutgoingProxy, OutgoingProxyMessage},
     simple::{SimpleProxy, SimpleProxyMessage},
 };
@@ -42,20 +54,39 @@ use crate::{
     background_tasks::{RestartableBackgroundTaskWrapper, TaskError},
     error::{ProxyRuntimeError, ProxyStartupError},
     failover_strategy::FailoverStrategy,
+    fault_injection::FaultInjector,
     main_tasks::{ConnectionRefresh, LayerClosed},
+    session_metadata::SessionMetadata,
 };
 
 pub mod agent_conn;
 pub mod background_tasks;
 pub mod error;
 mod failover_strategy;
+pub mod fault_injection;
 mod layer_conn;
 mod layer_initializer;
 pub mod main_tasks;
+pub mod metrics;
 mod ping_pong;
 pub mod proxies;
 mod remote_resources;
 mod request_queue;
+pub mod session_metadata;
+
+/// Start of the message that the proxy sends to the layers after it reconnects to the agent.
+const AGENT_RECONNECTED_MESSAGE: &str = "reconnected to agent after";
+
+/// Prefix of the stderr lines that the CLI shows to the user as progress messages, see
+/// [`notify_user`].
+pub const USER_NOTICE_PREFIX: &str = "mirrord notice: ";
+
+/// Shows the `message` to the user, by writing it to stderr with [`USER_NOTICE_PREFIX`].
+///
+/// Errors are ignored, as the CLI that reads our stderr might have already exited.
+fn notify_user(message: &str) {
+    let _ = writeln!(io::stderr(), "{USER_NOTICE_PREFIX}{message}");
+}
 
 /// [`TaskSender`]s for main background tasks. See [`MainTaskId`].
 struct TaskTxs {
@@ -69,6 +100,36 @@ struct TaskTxs {
     files: TaskSender<FilesProxy>,
 }
 
+impl TaskTxs {
+    /// Updates [`metrics::QUEUED_MESSAGES`] with the queue lengths of the main tasks.
+    ///
+    /// Queues of all [`LayerConnection`]s are summed up under one label.
+    fn record_queued_messages(&self) {
+        let layers = self.layers.values().map(TaskSender::queued).sum();
+        let queues = [
+            ("LAYER_CONNECTION".to_string(), layers),
+            (MainTaskId::AgentConnection.to_string(), self.agent.queued()),
+            (MainTaskId::SimpleProxy.to_string(), self.simple.queued()),
+            (MainTaskId::PingPong.to_string(), self.ping_pong.queued()),
+            (
+                MainTaskId::OutgoingProxy.to_string(),
+                self.outgoing.queued(),
+            ),
+            (
+                MainTaskId::IncomingProxy.to_string(),
+                self.incoming.queued(),
+            ),
+            (MainTaskId::FilesProxy.to_string(), self.files.queued()),
+        ];
+
+        for (task, queued) in queues {
+            metrics::QUEUED_MESSAGES
+                .with_label_values(&[task.as_str()])
+                .set(queued.try_into().unwrap_or(i64::MAX));
+        }
+    }
+}
+
 /// This struct contains logic for proxying between multiple layer instances and one agent.
 /// It maintains a singe agent connection.
 ///
@@ -82,12 +143,17 @@ pub struct IntProxy {
     /// this set holds the ids of current layer and msg involved in an exchange with proxy
     pending_layers: HashSet<(LayerId, MessageId)>,
 
+    /// Warnings that came before any layer connected, see [`ProxyMessage::WarnLayers`].
+    queued_warnings: Vec<String>,
+
     /// [`mirrord_protocol`] version negotiated with the agent.
     protocol_version: Option<Version>,
 
     /// Temporary message queue for any [`ProxyMessage`] from layer or to agent that are sent
     /// during reconnection state.
     reconnect_task_queue: Option<VecDeque<ProxyMessage>>,
+    /// When the current reconnection started, for reporting its duration.
+    reconnect_started_at: Option<Instant>,
 
     // Simple ping preset state-machine to debounce ping-pong resets (from agent activity) to at
     // most every 10/th of `PING_INTERVAL`
@@ -102,6 +168,9 @@ pub struct IntProxy {
 
     /// Send handle for the agent connection
     agent_tx: TxHandle<Client>,
+
+    /// Resumes stealing after it was downgraded, see [`AutoDowngradeConfig`].
+    resume_steal: ResumeStealSignal,
 }
 
 impl IntProxy {
@@ -118,11 +187,18 @@ impl IntProxy {
     /// Creates a new [`IntProxy`] using existing [`AgentConnection`].
     /// The returned instance will accept connections from the layers using the given
     /// [`TcpListener`].
+    #[allow(clippy::too_many_arguments)]
     pub fn new_with_connection(
         agent_conn: AgentConnection,
         listener: TcpListener,
-        file_buffer_size: u64,
+        file_buffers: ReadonlyFileBuffers,
         https_delivery: LocalTlsDelivery,
+        on_local_unavailable: OnLocalUnavailable,
+        source_ip_delivery: SourceIpDelivery,
+        delivery_source: Option<IpAddr>,
+        source_filter: Vec<SourceCidr>,
+        auto_downgrade: Option<AutoDowngradeConfig>,
+        unix_sockets: HashMap<PathBuf, PathBuf>,
         process_logging_interval: Duration,
         experimental: &ExperimentalConfig,
     ) -> Self {
@@ -151,12 +227,16 @@ impl IntProxy {
                 } else {
                     0
                 },
+                experimental.agent_ttl_extension,
             ),
             MainTaskId::PingPong,
             Self::CHANNEL_SIZE,
         );
         let simple = background_tasks.register(
-            SimpleProxy::new(experimental.dns_permission_error_fatal),
+            SimpleProxy::new(
+                experimental.dns_permission_error_fatal,
+                FaultInjector::new(&experimental.fault_injection.dns),
+            ),
             MainTaskId::SimpleProxy,
             Self::CHANNEL_SIZE,
         );
@@ -165,6 +245,8 @@ impl IntProxy {
                 experimental.non_blocking_tcp_connect,
                 experimental.latency.receive_delay,
                 experimental.latency.transmit_delay,
+                Duration::from_millis(experimental.udp_batch_window),
+                FaultInjector::new(&experimental.fault_injection.outgoing_connect),
             ),
             MainTaskId::OutgoingProxy,
             Self::CHANNEL_SIZE,
@@ -173,12 +255,23 @@ impl IntProxy {
             IncomingProxy::new(
                 Duration::from_millis(experimental.idle_local_http_connection_timeout),
                 https_delivery,
-            ),
+                on_local_unavailable,
+                source_ip_delivery,
+                delivery_source,
+                source_filter,
+                auto_downgrade,
+                unix_sockets,
+            )
+            .with_session_metadata(SessionMetadata::path(std::process::id())),
             MainTaskId::IncomingProxy,
             Self::CHANNEL_SIZE,
         );
         let files = background_tasks.register(
-            FilesProxy::new(file_buffer_size),
+            FilesProxy::new(
+                file_buffers,
+                experimental.reconnect,
+                FaultInjector::new(&experimental.fault_injection.file_read),
+            ),
             MainTaskId::FilesProxy,
             Self::CHANNEL_SIZE,
         );
@@ -211,13 +304,16 @@ impl IntProxy {
                 files,
             },
             pending_layers: Default::default(),
+            queued_warnings: Default::default(),
             protocol_version: None,
             reconnect_task_queue: Default::default(),
+            reconnect_started_at: None,
             ping_pong_update_debounce,
             ping_pong_update_allowed: false,
             connected_layers: HashMap::new(),
             process_logging_interval,
             agent_tx,
+            resume_steal: ResumeStealSignal::new(auto_downgrade.is_some()),
         }
     }
 
@@ -236,12 +332,19 @@ impl IntProxy {
         first_timeout: Duration,
         idle_timeout: Duration,
     ) -> Result<(), ProxyStartupError> {
-        match self.run_inner(first_timeout, idle_timeout).await {
+        let result = match self.run_inner(first_timeout, idle_timeout).await {
             ControlFlow::Break(result) => result,
             ControlFlow::Continue(failover_strategy) => {
                 failover_strategy.run(idle_timeout, idle_timeout).await
             }
+        };
+
+        let metadata_path = SessionMetadata::path(std::process::id());
+        if let Err(error) = SessionMetadata::remove(&metadata_path) {
+            tracing::warn!(%error, ?metadata_path, "Failed to remove the session metadata");
         }
+
+        result
     }
 
     /// Runs the main event loop of this proxy.
@@ -284,6 +387,7 @@ impl IntProxy {
 
                 _ = proxy.ping_pong_update_debounce.tick(), if proxy.has_layer_connections() => {
                     proxy.ping_pong_update_allowed = true;
+                    proxy.task_txs.record_queued_messages();
                 }
 
                 _ = proxy.process_logging_interval.tick() => {
@@ -296,6 +400,11 @@ impl IntProxy {
                     );
                 }
 
+                _ = proxy.resume_steal.recv() => {
+                    tracing::info!("Received a request to resume stealing");
+                    proxy.task_txs.incoming.send(IncomingProxyMessage::ResumeSteal).await;
+                }
+
                 _ = time::sleep(first_timeout), if !proxy.any_connection_accepted => {
                     return ControlFlow::Break(Err(ProxyStartupError::ConnectionAcceptTimeout));
                 },
@@ -344,11 +453,20 @@ impl IntProxy {
 
                 self.connected_layers
                     .insert(new_layer.id, new_layer.process_info);
+                metrics::CONNECTED_LAYERS
+                    .set(self.connected_layers.len().try_into().unwrap_or(i64::MAX));
                 let tx = self.background_tasks.register(
                     LayerConnection::new(new_layer.stream, new_layer.id),
                     MainTaskId::LayerConnection(new_layer.id),
                     Self::CHANNEL_SIZE,
                 );
+                for message in self.queued_warnings.drain(..) {
+                    tx.send(LocalMessage {
+                        message_id: 0,
+                        inner: ProxyToLayerMessage::LogMessage(LogMessage::warn(message)),
+                    })
+                    .await;
+                }
                 self.task_txs.layers.insert(new_layer.id, tx);
 
                 if let Some(parent) = new_layer.parent_id {
@@ -371,7 +489,11 @@ impl IntProxy {
                         .await;
                 }
             }
-            ProxyMessage::FromAgent(msg) => self.handle_agent_message(msg).await?,
+            ProxyMessage::FromAgent(msg) => {
+                metrics::AGENT_MESSAGES.inc();
+                metrics::AGENT_PAYLOAD_BYTES.inc_by(metrics::payload_len(&msg) as u64);
+                self.handle_agent_message(msg).await?
+            }
             ProxyMessage::FromLayer(msg) => {
                 if !matches!(
                     msg.message,
@@ -398,6 +520,13 @@ impl IntProxy {
                 }
             }
             ProxyMessage::ConnectionRefresh(kind) => self.handle_connection_refresh(kind).await?,
+            ProxyMessage::WarnLayers(message) => {
+                if self.task_txs.layers.is_empty() {
+                    self.queued_warnings.push(message);
+                } else {
+                    self.warn_layers(&message).await;
+                }
+            }
         }
 
         Ok(())
@@ -437,6 +566,8 @@ impl IntProxy {
 
                 self.task_txs.layers.remove(&LayerId(id));
                 self.connected_layers.remove(&LayerId(id));
+                metrics::CONNECTED_LAYERS
+                    .set(self.connected_layers.len().try_into().unwrap_or(i64::MAX));
                 self.pending_layers.retain(|(layer_id, _)| layer_id.0 != id);
             }
 
@@ -558,7 +689,14 @@ impl IntProxy {
 
                 self.task_txs
                     .outgoing
-                    .send(OutgoingProxyMessage::AgentProtocolVersion(protocol_version))
+                    .send(OutgoingProxyMessage::AgentProtocolVersion(
+                        protocol_version.clone(),
+                    ))
+                    .await;
+
+                self.task_txs
+                    .ping_pong
+                    .send(PingPongMessage::AgentProtocolVersion(protocol_version))
                     .await;
             }
             DaemonMessage::LogMessage(log) => match log.level {
@@ -581,9 +719,22 @@ impl IntProxy {
                     .send(SimpleProxyMessage::GetEnvRes(res.map(Into::into)))
                     .await
             }
+            DaemonMessage::ReverseDnsLookup(res) => {
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::ReverseDnsRes(res))
+                    .await
+            }
+            DaemonMessage::RemoteTime(time) => {
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::RemoteTimeRes(time))
+                    .await
+            }
             message @ DaemonMessage::PauseTarget(_)
             | message @ DaemonMessage::Vpn(_)
-            | message @ DaemonMessage::ReverseDnsLookup(_) => {
+            | message @ DaemonMessage::Udp(_)
+            | message @ DaemonMessage::AgentLogs(_) => {
                 Err(ProxyRuntimeError::UnexpectedAgentMessage(
                     UnexpectedAgentMessage(message.into()),
                 ))?;
@@ -601,8 +752,11 @@ impl IntProxy {
             message,
         } = message;
 
+        metrics::LAYER_MESSAGES.inc();
+
         match message {
             LayerToProxyMessage::File(req) => {
+                metrics::FILE_REQUESTS.inc();
                 self.task_txs
                     .files
                     .send(FilesProxyMessage::FileReq(message_id, layer_id, req))
@@ -614,6 +768,12 @@ impl IntProxy {
                     .send(SimpleProxyMessage::AddrInfoReq(message_id, layer_id, req))
                     .await
             }
+            LayerToProxyMessage::ReverseDnsLookup(req) => {
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::ReverseDnsReq(message_id, layer_id, req))
+                    .await
+            }
             LayerToProxyMessage::Outgoing(req) => {
                 self.task_txs
                     .outgoing
@@ -634,12 +794,49 @@ impl IntProxy {
                     .send(SimpleProxyMessage::GetEnvReq(message_id, layer_id, req))
                     .await
             }
+            LayerToProxyMessage::GetRemoteTime(..) => {
+                self.task_txs
+                    .simple
+                    .send(SimpleProxyMessage::RemoteTimeReq(message_id, layer_id))
+                    .await
+            }
+            LayerToProxyMessage::LogMessage(log) => {
+                tracing::info!(
+                    message = log.message,
+                    level = ?log.level,
+                    "Received a log message from the layer"
+                );
+                notify_user(&log.message);
+            }
             other => Err(ProxyRuntimeError::UnexpectedLayerMessage(other))?,
         }
 
         Ok(())
     }
 
+    /// Lets the user know that we reconnected to the agent, in the CLI progress (see
+    /// [`notify_user`]), and with a [`ProxyToLayerMessage::LogMessage`] sent to all connected
+    /// layers.
+    async fn notify_reconnected(&self, elapsed: Duration) {
+        let message = format!("{AGENT_RECONNECTED_MESSAGE} {} seconds", elapsed.as_secs());
+        tracing::warn!(message, "Reconnected to the agent");
+        notify_user(&message);
+
+        self.warn_layers(&message).await;
+    }
+
+    /// Sends the `message` to all connected layers, as a warning
+    /// [`ProxyToLayerMessage::LogMessage`].
+    async fn warn_layers(&self, message: &str) {
+        for tx in self.task_txs.layers.values() {
+            tx.send(LocalMessage {
+                message_id: 0,
+                inner: ProxyToLayerMessage::LogMessage(LogMessage::warn(message.to_string())),
+            })
+            .await;
+        }
+    }
+
     #[tracing::instrument(level = "trace", skip(self))]
     async fn handle_connection_refresh(
         &mut self,
@@ -684,6 +881,7 @@ impl IntProxy {
             ConnectionRefresh::Start => {
                 // Initialise default reconnect message queue
                 self.reconnect_task_queue.get_or_insert_default();
+                self.reconnect_started_at.get_or_insert_with(Instant::now);
             }
             ConnectionRefresh::End(new_agent_tx) => {
                 let task_queue = self.reconnect_task_queue.take().unwrap_or_else(|| {
@@ -702,6 +900,13 @@ impl IntProxy {
                     ))
                     .await;
 
+                let elapsed = self
+                    .reconnect_started_at
+                    .take()
+                    .map(|started_at| started_at.elapsed())
+                    .unwrap_or_default();
+                self.notify_reconnected(elapsed).await;
+
                 Box::pin(async {
                     for msg in task_queue {
                         tracing::debug!(?msg, "dequeueing message for reconnect");
@@ -727,24 +932,28 @@ impl IntProxy {
 
 #[cfg(test)]
 mod test {
-    use std::{net::SocketAddr, path::PathBuf, time::Duration};
+    use std::{net::SocketAddr, ops::Not, path::PathBuf, time::Duration};
 
     use hyper::{HeaderMap, Method, StatusCode, Uri, Version};
     use mirrord_analytics::NullReporter;
     use mirrord_config::{
         LayerFileConfig, config::MirrordConfig, experimental::ExperimentalFileConfig,
+        feature::fs::ReadonlyFileBuffers,
     };
     use mirrord_intproxy_protocol::{
-        IncomingRequest, LayerToProxyMessage, LocalMessage, NetProtocol, NewSessionRequest,
-        OutgoingConnectRequest, OutgoingRequest, OutgoingResponse, PortSubscribe, PortSubscription,
-        ProcessInfo, ProxyToLayerMessage,
-        codec::{AsyncDecoder, AsyncEncoder},
+        BuildVersion, IncomingRequest, LayerToProxyMessage, LocalMessage, NetProtocol,
+        NewSessionRequest, OutgoingConnectRequest, OutgoingRequest, OutgoingResponse,
+        PortSubscribe, PortSubscription, ProcessInfo, ProxyToLayerMessage,
+        codec::{AsyncDecoder, AsyncEncoder, CodecError},
     };
     use mirrord_protocol::{
-        ClientMessage, DaemonMessage, ErrorKindInternal, FileRequest, FileResponse, RemoteIOError,
-        ResponseError, VERSION,
+        ClientMessage, DaemonMessage, ErrorKindInternal, FileRequest, FileResponse, LogLevel,
+        LogMessage, RemoteIOError, ResponseError, VERSION,
         dns::{AddressFamily, GetAddrInfoRequestV2, GetAddrInfoResponse, SockType},
-        file::{OpenFileRequest, StatFsRequestV2},
+        file::{
+            OpenFileRequest, OpenFileResponse, OpenOptionsInternal, ReadFileRequest,
+            ReadFileResponse, ReadLimitedFileRequest, StatFsRequestV2,
+        },
         outgoing::{LayerConnectV2, SocketAddress, tcp::LayerTcpOutgoing},
         tcp::{
             ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, DaemonTcp,
@@ -753,7 +962,7 @@ mod test {
             StealType,
         },
     };
-    use mirrord_protocol_io::{Client, Connection, ConnectionOutput};
+    use mirrord_protocol_io::{Agent, Client, Connection, ConnectionOutput};
     use tokio::{
         io::{AsyncReadExt, AsyncWriteExt},
         net::{
@@ -764,7 +973,7 @@ mod test {
     };
 
     use crate::{
-        IntProxy,
+        AGENT_RECONNECTED_MESSAGE, IntProxy,
         agent_conn::{
             AgentConnectInfo, AgentConnectInfoDiscriminants, AgentConnection, ReconnectFlow,
         },
@@ -793,7 +1002,13 @@ mod test {
         let proxy = IntProxy::new_with_connection(
             agent_conn,
             listener,
-            4096,
+            ReadonlyFileBuffers::new(4096),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
             Default::default(),
             Duration::from_secs(60),
             &ExperimentalFileConfig::default()
@@ -827,6 +1042,8 @@ mod test {
                         loaded: true,
                     },
                     parent_layer: None,
+                    layer_version: BuildVersion::current(),
+                    layer_path: None,
                 }),
             })
             .await
@@ -911,7 +1128,13 @@ mod test {
         let proxy = IntProxy::new_with_connection(
             agent_conn,
             listener,
-            4096,
+            ReadonlyFileBuffers::new(4096),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
             Default::default(),
             Duration::from_secs(60),
             &ExperimentalFileConfig::default()
@@ -938,6 +1161,8 @@ mod test {
                         loaded: true,
                     },
                     parent_layer: None,
+                    layer_version: BuildVersion::current(),
+                    layer_path: None,
                 }),
             })
             .await
@@ -1004,7 +1229,13 @@ mod test {
         let proxy = IntProxy::new_with_connection(
             agent_conn,
             listener,
-            4096,
+            ReadonlyFileBuffers::new(4096),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
             Default::default(),
             Duration::from_secs(60),
             &ExperimentalFileConfig::default()
@@ -1027,10 +1258,10 @@ mod test {
     }
 
     async fn setup_reconnect_test() -> ReconnectTestSetup {
-        let listener = TcpListener::bind("127.0.0.1:0".parse::<SocketAddr>().unwrap())
-            .await
-            .unwrap();
+        setup_reconnect_test_with(ExperimentalFileConfig::default()).await
+    }
 
+    async fn setup_reconnect_test_with(experimental: ExperimentalFileConfig) -> ReconnectTestSetup {
         let (conn_tx, conn_rx) = mpsc::channel(1);
 
         let config = LayerFileConfig::default()
@@ -1045,6 +1276,27 @@ mod test {
         .await
         .unwrap();
 
+        let (from_layer, to_layer) = start_proxy_with_layer(agent_conn, experimental).await;
+
+        ReconnectTestSetup {
+            from_layer,
+            to_layer,
+            conn_rx,
+        }
+    }
+
+    /// Starts an [`IntProxy`] with the given agent connection, and connects a layer to it.
+    async fn start_proxy_with_layer(
+        agent_conn: AgentConnection,
+        experimental: ExperimentalFileConfig,
+    ) -> (
+        AsyncEncoder<LocalMessage<LayerToProxyMessage>, OwnedWriteHalf>,
+        AsyncDecoder<LocalMessage<ProxyToLayerMessage>, OwnedReadHalf>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0".parse::<SocketAddr>().unwrap())
+            .await
+            .unwrap();
+
         let conn = TcpStream::connect(listener.local_addr().unwrap())
             .await
             .unwrap();
@@ -1066,6 +1318,8 @@ mod test {
                         loaded: true,
                     },
                     parent_layer: None,
+                    layer_version: BuildVersion::current(),
+                    layer_path: None,
                 }),
             })
             .await
@@ -1075,10 +1329,16 @@ mod test {
         let proxy = IntProxy::new_with_connection(
             agent_conn,
             listener,
-            4096,
+            ReadonlyFileBuffers::new(4096),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
             Default::default(),
             Duration::from_secs(60),
-            &ExperimentalFileConfig::default()
+            &experimental
                 .generate_config(&mut Default::default())
                 .unwrap(),
         );
@@ -1092,11 +1352,7 @@ mod test {
             other => panic!("unexpected local message from the proxy: {other:?}"),
         }
 
-        ReconnectTestSetup {
-            from_layer,
-            to_layer,
-            conn_rx,
-        }
+        (from_layer, to_layer)
     }
 
     async fn next_proxy_msg(
@@ -1112,6 +1368,22 @@ mod test {
         }
     }
 
+    /// Receives the next message from the proxy, skipping [`ProxyToLayerMessage::LogMessage`]s
+    /// like the layer does.
+    async fn next_layer_msg(
+        to_layer: &mut AsyncDecoder<LocalMessage<ProxyToLayerMessage>, OwnedReadHalf>,
+    ) -> Result<Option<LocalMessage<ProxyToLayerMessage>>, CodecError> {
+        loop {
+            match to_layer.receive().await {
+                Ok(Some(LocalMessage {
+                    inner: ProxyToLayerMessage::LogMessage(..),
+                    ..
+                })) => continue,
+                other => break other,
+            }
+        }
+    }
+
     async fn switch_protocol_version(
         to_proxy: &mpsc::Sender<DaemonMessage>,
         from_proxy: &ConnectionOutput<Client>,
@@ -1196,7 +1468,7 @@ mod test {
                 .unwrap();
 
             assert!(matches!(
-                to_layer.receive().await,
+                next_layer_msg(&mut to_layer).await,
                 Ok(Some(LocalMessage {
                     message_id: 0,
                     inner: ProxyToLayerMessage::Incoming(
@@ -1207,6 +1479,108 @@ mod test {
         }
     }
 
+    /// Verifies that [`IntProxy`] keeps reconnecting to the external proxy while it's down, and
+    /// restores the port subscriptions once it's back on the same address.
+    #[tokio::test]
+    #[rstest::rstest]
+    #[timeout(Duration::from_secs(10))]
+    async fn reconnect_to_restarted_external_proxy() {
+        let mut extproxy = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let extproxy_addr = extproxy.local_addr().unwrap();
+
+        let mut config = LayerFileConfig::default()
+            .generate_config(&mut Default::default())
+            .unwrap();
+        // More attempts than this fit in the downtime below, they should not matter here.
+        config.internal_proxy.reconnect.max_attempts = 2;
+        config.internal_proxy.reconnect.min_ms = 50;
+        config.internal_proxy.reconnect.max_ms = 100;
+
+        let agent_conn = AgentConnection::new(
+            &config,
+            AgentConnectInfo::ExternalProxy {
+                proxy_addr: extproxy_addr,
+                tls_pem: None,
+            },
+            &mut NullReporter::default(),
+        )
+        .await
+        .unwrap();
+        let (mut from_layer, mut to_layer) =
+            start_proxy_with_layer(agent_conn, ExperimentalFileConfig::default()).await;
+
+        from_layer
+            .send(&LocalMessage {
+                message_id: 0,
+                inner: LayerToProxyMessage::Incoming(IncomingRequest::PortSubscribe(
+                    PortSubscribe {
+                        listening_on: "0.0.0.0:42069".parse().unwrap(),
+                        subscription: PortSubscription::Steal(StealType::All(42069)),
+                    },
+                )),
+            })
+            .await
+            .unwrap();
+
+        let mut conn = accept_fake_extproxy(&extproxy).await;
+        assert!(matches!(
+            next_layer_msg(&mut to_layer).await,
+            Ok(Some(LocalMessage {
+                message_id: 0,
+                inner: ProxyToLayerMessage::Incoming(
+                    mirrord_intproxy_protocol::IncomingResponse::PortSubscribe(Ok(()))
+                )
+            }))
+        ));
+
+        for _ in 0..2 {
+            // Kill the external proxy, and start it again on the same address after a while.
+            drop(conn);
+            drop(extproxy);
+            tokio::time::sleep(Duration::from_millis(500)).await;
+
+            extproxy = TcpListener::bind(extproxy_addr).await.unwrap();
+            conn = accept_fake_extproxy(&extproxy).await;
+        }
+    }
+
+    /// Accepts the internal proxy connection on a fake external proxy, and goes through the
+    /// protocol version negotiation and the port subscription.
+    async fn accept_fake_extproxy(listener: &TcpListener) -> Connection<Agent> {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut conn = Connection::<Agent>::from_stream(stream).await.unwrap();
+
+        assert_eq!(
+            next_client_msg(&mut conn).await,
+            ClientMessage::SwitchProtocolVersion(VERSION.clone())
+        );
+        conn.send(DaemonMessage::SwitchProtocolVersionResponse(
+            mirrord_protocol::VERSION.clone(),
+        ))
+        .await;
+
+        assert_eq!(
+            next_client_msg(&mut conn).await,
+            ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(StealType::All(42069)))
+        );
+        conn.send(DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Ok(
+            42069,
+        ))))
+        .await;
+
+        conn
+    }
+
+    async fn next_client_msg(conn: &mut Connection<Agent>) -> ClientMessage {
+        loop {
+            match conn.recv().await.unwrap() {
+                ClientMessage::Ping => conn.send(DaemonMessage::Pong).await,
+                ClientMessage::ReadyForLogs => (),
+                other => return other,
+            }
+        }
+    }
+
     /// Verifies that [`IntProxy`] reconnects correctly when a pong is no received.
     #[tokio::test]
     #[rstest::rstest]
@@ -1241,6 +1615,195 @@ mod test {
         assert_eq!(from_proxy.next().await, Some(ClientMessage::Ping));
     }
 
+    /// Verifies that [`IntProxy`] lets the layers know about a successful reconnect after the
+    /// connection was dropped.
+    #[tokio::test]
+    #[rstest::rstest]
+    #[timeout(Duration::from_secs(5))]
+    async fn reconnect_notifies_layer() {
+        let ReconnectTestSetup {
+            mut conn_rx,
+            from_layer: _from_layer,
+            mut to_layer,
+        } = setup_reconnect_test().await;
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+        switch_protocol_version(&to_proxy, &from_proxy).await;
+
+        drop(to_proxy);
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+        switch_protocol_version(&to_proxy, &from_proxy).await;
+
+        match to_layer.receive().await.unwrap().unwrap().inner {
+            ProxyToLayerMessage::LogMessage(LogMessage {
+                message,
+                level: LogLevel::Warn,
+            }) => assert!(message.starts_with(AGENT_RECONNECTED_MESSAGE), "{message}"),
+            other => panic!("unexpected local message from the proxy: {other:?}"),
+        }
+    }
+
+    /// Verifies that the agent connection is not reconnectable when `internal_proxy.reconnect` is
+    /// disabled.
+    #[tokio::test]
+    async fn reconnect_disabled() {
+        let (conn_tx, _conn_rx) = mpsc::channel(1);
+
+        let mut config = LayerFileConfig::default()
+            .generate_config(&mut Default::default())
+            .unwrap();
+        config.internal_proxy.reconnect.enabled = Some(false);
+
+        let agent_conn = AgentConnection::new(
+            &config,
+            AgentConnectInfo::Dummy(conn_tx),
+            &mut NullReporter::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(agent_conn.reconnectable().not());
+    }
+
+    /// Verifies that the idle timeout (no layer connections) is reset by the traffic from the
+    /// agent.
+    #[tokio::test]
+    #[rstest::rstest]
+    #[timeout(Duration::from_secs(5))]
+    async fn idle_timeout_reset_by_traffic() {
+        let listener = TcpListener::bind("127.0.0.1:0".parse::<SocketAddr>().unwrap())
+            .await
+            .unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+
+        let (connection, proxy_tx, proxy_rx) = Connection::dummy();
+        let agent_conn = AgentConnection {
+            connection,
+            reconnect: ReconnectFlow::Break(AgentConnectInfoDiscriminants::DirectKubernetes),
+        };
+        let proxy = IntProxy::new_with_connection(
+            agent_conn,
+            listener,
+            ReadonlyFileBuffers::new(4096),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Default::default(),
+            Duration::from_secs(60),
+            &ExperimentalFileConfig::default()
+                .generate_config(&mut Default::default())
+                .unwrap(),
+        );
+        let idle_timeout = Duration::from_millis(500);
+        let proxy_handle = tokio::spawn(proxy.run(Duration::from_secs(60), idle_timeout));
+
+        let pong_tx = proxy_tx.clone();
+        tokio::spawn(async move {
+            while let Some(message) = proxy_rx.next().await {
+                if message == ClientMessage::Ping {
+                    let _ = pong_tx.send(DaemonMessage::Pong).await;
+                }
+            }
+        });
+        proxy_tx
+            .send(DaemonMessage::SwitchProtocolVersionResponse(
+                mirrord_protocol::VERSION.clone(),
+            ))
+            .await
+            .unwrap();
+
+        // Connect and disconnect a layer, so that the idle timer starts.
+        let conn = TcpStream::connect(proxy_addr).await.unwrap();
+        let (mut from_layer, mut to_layer) = mirrord_intproxy_protocol::codec::make_async_framed::<
+            LocalMessage<LayerToProxyMessage>,
+            LocalMessage<ProxyToLayerMessage>,
+        >(conn);
+        from_layer
+            .send(&LocalMessage {
+                message_id: 0,
+                inner: LayerToProxyMessage::NewSession(NewSessionRequest {
+                    process_info: ProcessInfo {
+                        pid: 1337,
+                        parent_pid: 1336,
+                        name: "hello there".into(),
+                        cmdline: vec!["hello there".into()],
+                        loaded: true,
+                    },
+                    parent_layer: None,
+                    layer_version: BuildVersion::current(),
+                    layer_path: None,
+                }),
+            })
+            .await
+            .unwrap();
+        from_layer.flush().await.unwrap();
+        assert!(matches!(
+            to_layer.receive().await,
+            Ok(Some(LocalMessage {
+                inner: ProxyToLayerMessage::NewSession(..),
+                ..
+            }))
+        ));
+        drop((from_layer, to_layer));
+
+        // Keep sending messages more often than the idle timeout, for longer than the idle
+        // timeout.
+        for _ in 0..6 {
+            tokio::time::sleep(idle_timeout * 2 / 5).await;
+            proxy_tx
+                .send(DaemonMessage::LogMessage(LogMessage {
+                    message: "still here".into(),
+                    level: LogLevel::Info,
+                }))
+                .await
+                .unwrap();
+            assert!(proxy_handle.is_finished().not());
+        }
+
+        // Without the traffic, the proxy exits.
+        proxy_handle.await.unwrap().unwrap();
+    }
+
+    /// Verifies that [`IntProxy`] keeps extending the agent's TTL with every ping, and right after
+    /// reconnecting.
+    #[tokio::test]
+    #[rstest::rstest]
+    #[timeout(Duration::from_secs(5))]
+    async fn extend_agent_ttl() {
+        let ReconnectTestSetup {
+            mut conn_rx,
+            // Keep the connection so intproxy doesn't exit
+            from_layer: _from_layer,
+            to_layer: _,
+        } = setup_reconnect_test_with(ExperimentalFileConfig {
+            agent_ttl_extension: Some(60),
+            ..Default::default()
+        })
+        .await;
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+
+        switch_protocol_version(&to_proxy, &from_proxy).await;
+
+        assert_eq!(from_proxy.next().await, Some(ClientMessage::ReadyForLogs));
+        assert_eq!(from_proxy.next().await, Some(ClientMessage::ExtendTtl(60)));
+        assert_eq!(from_proxy.next().await, Some(ClientMessage::Ping));
+        assert_eq!(from_proxy.next().await, Some(ClientMessage::ExtendTtl(60)));
+
+        drop(to_proxy);
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+
+        switch_protocol_version(&to_proxy, &from_proxy).await;
+
+        assert_eq!(from_proxy.next().await, Some(ClientMessage::ReadyForLogs));
+        assert_eq!(from_proxy.next().await, Some(ClientMessage::ExtendTtl(60)));
+    }
+
     /// Verifies that [`IntProxy`] reconnects correctly while waiting for a fileops response.
     #[tokio::test]
     #[rstest::rstest]
@@ -1283,7 +1846,7 @@ mod test {
         switch_protocol_version(&to_proxy, &from_proxy).await;
 
         assert!(matches!(
-            to_layer.receive().await,
+            next_layer_msg(&mut to_layer).await,
             Ok(Some(LocalMessage {
                 message_id: 0,
                 inner: ProxyToLayerMessage::File(FileResponse::Open(Err(ResponseError::RemoteIO(
@@ -1296,6 +1859,161 @@ mod test {
         ));
     }
 
+    /// Verifies that [`IntProxy`] re-opens a buffered file after a reconnect and resumes the read
+    /// that was interrupted by the connection drop, when `experimental.reconnect` is enabled.
+    #[tokio::test]
+    #[rstest::rstest]
+    #[timeout(Duration::from_secs(5))]
+    async fn reconnect_resumes_file_read() {
+        let ReconnectTestSetup {
+            mut conn_rx,
+            mut from_layer,
+            mut to_layer,
+        } = setup_reconnect_test_with(ExperimentalFileConfig {
+            reconnect: Some(true),
+            ..Default::default()
+        })
+        .await;
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+        switch_protocol_version(&to_proxy, &from_proxy).await;
+
+        let open_request = FileRequest::Open(OpenFileRequest {
+            path: "/some/file".into(),
+            open_options: OpenOptionsInternal {
+                read: true,
+                ..Default::default()
+            },
+        });
+        from_layer
+            .send(&LocalMessage {
+                message_id: 1,
+                inner: LayerToProxyMessage::File(open_request.clone()),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            next_proxy_msg(&to_proxy, &from_proxy).await,
+            ClientMessage::FileRequest(open_request.clone())
+        );
+        to_proxy
+            .send(DaemonMessage::File(FileResponse::Open(Ok(
+                OpenFileResponse { fd: 1 },
+            ))))
+            .await
+            .unwrap();
+        assert!(matches!(
+            next_layer_msg(&mut to_layer).await,
+            Ok(Some(LocalMessage {
+                message_id: 1,
+                inner: ProxyToLayerMessage::File(FileResponse::Open(Ok(OpenFileResponse {
+                    fd: 1
+                }))),
+            }))
+        ));
+
+        // First read fills the local buffer (4096 bytes) and moves the fd position.
+        from_layer
+            .send(&LocalMessage {
+                message_id: 2,
+                inner: LayerToProxyMessage::File(FileRequest::Read(ReadFileRequest {
+                    remote_fd: 1,
+                    buffer_size: 10,
+                })),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            next_proxy_msg(&to_proxy, &from_proxy).await,
+            ClientMessage::FileRequest(FileRequest::ReadLimited(ReadLimitedFileRequest {
+                remote_fd: 1,
+                buffer_size: 4096,
+                start_from: 0,
+            }))
+        );
+        to_proxy
+            .send(DaemonMessage::File(FileResponse::ReadLimited(Ok(
+                ReadFileResponse {
+                    bytes: vec![0; 4096].into(),
+                    read_amount: 4096,
+                },
+            ))))
+            .await
+            .unwrap();
+        assert!(matches!(
+            next_layer_msg(&mut to_layer).await,
+            Ok(Some(LocalMessage {
+                message_id: 2,
+                inner: ProxyToLayerMessage::File(FileResponse::Read(Ok(ReadFileResponse {
+                    read_amount: 10,
+                    ..
+                }))),
+            }))
+        ));
+
+        // Second read does not fit in the buffer, and is interrupted by the connection drop.
+        let interrupted_read =
+            ClientMessage::FileRequest(FileRequest::ReadLimited(ReadLimitedFileRequest {
+                remote_fd: 1,
+                buffer_size: 8192,
+                start_from: 10,
+            }));
+        from_layer
+            .send(&LocalMessage {
+                message_id: 3,
+                inner: LayerToProxyMessage::File(FileRequest::Read(ReadFileRequest {
+                    remote_fd: 1,
+                    buffer_size: 8192,
+                })),
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            next_proxy_msg(&to_proxy, &from_proxy).await,
+            interrupted_read
+        );
+
+        drop(to_proxy);
+
+        let (to_proxy, from_proxy) = conn_rx.recv().await.unwrap();
+        switch_protocol_version(&to_proxy, &from_proxy).await;
+
+        // The file is re-opened with the new agent, and the read resumes from the same offset.
+        assert_eq!(
+            next_proxy_msg(&to_proxy, &from_proxy).await,
+            ClientMessage::FileRequest(open_request)
+        );
+        to_proxy
+            .send(DaemonMessage::File(FileResponse::Open(Ok(
+                OpenFileResponse { fd: 1 },
+            ))))
+            .await
+            .unwrap();
+        assert_eq!(
+            next_proxy_msg(&to_proxy, &from_proxy).await,
+            interrupted_read
+        );
+        to_proxy
+            .send(DaemonMessage::File(FileResponse::ReadLimited(Ok(
+                ReadFileResponse {
+                    bytes: vec![1; 8192].into(),
+                    read_amount: 8192,
+                },
+            ))))
+            .await
+            .unwrap();
+        assert!(matches!(
+            next_layer_msg(&mut to_layer).await,
+            Ok(Some(LocalMessage {
+                message_id: 3,
+                inner: ProxyToLayerMessage::File(FileResponse::Read(Ok(ReadFileResponse {
+                    read_amount: 8192,
+                    ..
+                }))),
+            }))
+        ));
+    }
+
     /// Verifies that [`IntProxy`] reconnects correctly while waiting for a response to a
     /// [`ClientMessage::TcpOutgoing`].
     #[tokio::test]
@@ -1344,7 +2062,7 @@ mod test {
         switch_protocol_version(&to_proxy, &from_proxy).await;
 
         assert!(matches!(
-            to_layer.receive().await,
+            next_layer_msg(&mut to_layer).await,
             Ok(Some(LocalMessage {
                 message_id: 0,
                 inner: ProxyToLayerMessage::Outgoing(OutgoingResponse::Connect(Err(
@@ -1401,7 +2119,7 @@ mod test {
         switch_protocol_version(&to_proxy, &from_proxy).await;
 
         assert!(matches!(
-            to_layer.receive().await,
+            next_layer_msg(&mut to_layer).await,
             Ok(Some(LocalMessage {
                 message_id: 0,
                 inner: ProxyToLayerMessage::GetAddrInfo(GetAddrInfoResponse(Err(
@@ -1457,7 +2175,7 @@ mod test {
             .unwrap();
 
         assert!(matches!(
-            to_layer.receive().await,
+            next_layer_msg(&mut to_layer).await,
             Ok(Some(LocalMessage {
                 message_id: 0,
                 inner: ProxyToLayerMessage::Incoming(
@@ -1579,7 +2297,7 @@ mod test {
             .unwrap();
 
         assert!(matches!(
-            to_layer.receive().await,
+            next_layer_msg(&mut to_layer).await,
             Ok(Some(LocalMessage {
                 message_id: 0,
                 inner: ProxyToLayerMessage::Incoming(
@@ -23,6 +23,10 @@ pub enum ProxyMessage {
     NewLayer(NewLayer),
     /// Connection to agent was dropped and needs reload.
     ConnectionRefresh(ConnectionRefresh),
+    /// Warning for the user, sent to all connected layer instances.
+    ///
+    /// If no layer instance is connected yet, the warning is sent to the first ones that connect.
+    WarnLayers(String),
 }
 
 #[cfg(test)]
@@ -0,0 +1,165 @@
+//! Prometheus metrics of the internal proxy, enabled with `internal_proxy.metrics`.
+//!
+//! The metrics live in the global prometheus [`Registry`](prometheus::Registry), and are updated
+//! directly by the [`IntProxy`](crate::IntProxy) and its tasks.
+
+use std::{io, sync::LazyLock};
+
+use axum::{Router, http::StatusCode, routing::get};
+use mirrord_protocol::{
+    DaemonMessage, FileResponse,
+    outgoing::{tcp::DaemonTcpOutgoing, udp::DaemonUdpOutgoing},
+    tcp::DaemonTcp,
+};
+use prometheus::{IntCounter, IntGauge, IntGaugeVec, TextEncoder};
+use tokio::net::TcpListener;
+use tracing::Level;
+
+/// Messages received from the layers.
+pub(crate) static LAYER_MESSAGES: LazyLock<IntCounter> = LazyLock::new(|| {
+    prometheus::register_int_counter!(
+        "mirrord_intproxy_layer_messages_total",
+        "amount of messages received by mirrord-intproxy from the layers"
+    )
+    .expect("LAYER_MESSAGES should be valid")
+});
+
+/// Messages received from the agent.
+pub(crate) static AGENT_MESSAGES: LazyLock<IntCounter> = LazyLock::new(|| {
+    prometheus::register_int_counter!(
+        "mirrord_intproxy_agent_messages_total",
+        "amount of messages received by mirrord-intproxy from the agent"
+    )
+    .expect("AGENT_MESSAGES should be valid")
+});
+
+/// File operations requested by the layers.
+pub(crate) static FILE_REQUESTS: LazyLock<IntCounter> = LazyLock::new(|| {
+    prometheus::register_int_counter!(
+        "mirrord_intproxy_file_requests_total",
+        "amount of file operations requested from mirrord-intproxy by the layers"
+    )
+    .expect("FILE_REQUESTS should be valid")
+});
+
+/// Bytes of traffic and file data received from the agent, see [`payload_len`].
+pub(crate) static AGENT_PAYLOAD_BYTES: LazyLock<IntCounter> = LazyLock::new(|| {
+    prometheus::register_int_counter!(
+        "mirrord_intproxy_agent_payload_bytes_total",
+        "amount of traffic and file data bytes received by mirrord-intproxy from the agent"
+    )
+    .expect("AGENT_PAYLOAD_BYTES should be valid")
+});
+
+/// Layers currently connected to the proxy.
+pub(crate) static CONNECTED_LAYERS: LazyLock<IntGauge> = LazyLock::new(|| {
+    prometheus::register_int_gauge!(
+        "mirrord_intproxy_connected_layers",
+        "amount of layers currently connected to mirrord-intproxy"
+    )
+    .expect("CONNECTED_LAYERS should be valid")
+});
+
+/// Messages waiting in the queues of the proxy's main tasks, labeled with the task name.
+pub(crate) static QUEUED_MESSAGES: LazyLock<IntGaugeVec> = LazyLock::new(|| {
+    prometheus::register_int_gauge_vec!(
+        "mirrord_intproxy_queued_messages",
+        "amount of messages waiting in the queue of a mirrord-intproxy task",
+        &["task"]
+    )
+    .expect("QUEUED_MESSAGES should be valid")
+});
+
+/// Whether stealing is currently downgraded after the user application failed to handle the
+/// stolen traffic, see `feature.network.incoming.auto_downgrade`.
+pub(crate) static STEAL_DOWNGRADED: LazyLock<IntGauge> = LazyLock::new(|| {
+    prometheus::register_int_gauge!(
+        "mirrord_intproxy_steal_downgraded",
+        "whether mirrord-intproxy stopped stealing after a storm of local delivery failures"
+    )
+    .expect("STEAL_DOWNGRADED should be valid")
+});
+
+/// Times stealing was downgraded in this session.
+pub(crate) static STEAL_DOWNGRADES: LazyLock<IntCounter> = LazyLock::new(|| {
+    prometheus::register_int_counter!(
+        "mirrord_intproxy_steal_downgrades_total",
+        "amount of times mirrord-intproxy stopped stealing after a storm of local delivery failures"
+    )
+    .expect("STEAL_DOWNGRADES should be valid")
+});
+
+/// Returns the size of the traffic or file data carried by the given message.
+pub(crate) fn payload_len(message: &DaemonMessage) -> usize {
+    match message {
+        DaemonMessage::Tcp(DaemonTcp::Data(data))
+        | DaemonMessage::TcpSteal(DaemonTcp::Data(data)) => data.bytes.len(),
+        DaemonMessage::TcpOutgoing(DaemonTcpOutgoing::Read(Ok(read)))
+        | DaemonMessage::UdpOutgoing(DaemonUdpOutgoing::Read(Ok(read))) => read.bytes.len(),
+        DaemonMessage::File(FileResponse::Read(Ok(read)) | FileResponse::ReadLimited(Ok(read))) => {
+            read.bytes.len()
+        }
+        _ => 0,
+    }
+}
+
+/// `GET /metrics`
+#[tracing::instrument(level = Level::TRACE, ret)]
+async fn get_metrics() -> (StatusCode, String) {
+    match TextEncoder.encode_to_string(&prometheus::gather()) {
+        Ok(response) => (StatusCode::OK, response),
+        Err(fail) => {
+            tracing::error!(?fail, "Failed GET /metrics");
+            (StatusCode::INTERNAL_SERVER_ERROR, fail.to_string())
+        }
+    }
+}
+
+/// Serves the metrics on `GET /metrics`, using the given [`TcpListener`].
+///
+/// Runs until the listener fails.
+pub async fn serve_metrics(listener: TcpListener) -> io::Result<()> {
+    // Register all metrics upfront, so that they are present in the first scrape.
+    LazyLock::force(&LAYER_MESSAGES);
+    LazyLock::force(&AGENT_MESSAGES);
+    LazyLock::force(&FILE_REQUESTS);
+    LazyLock::force(&AGENT_PAYLOAD_BYTES);
+    LazyLock::force(&CONNECTED_LAYERS);
+    LazyLock::force(&QUEUED_MESSAGES);
+
+    let app = Router::new().route("/metrics", get(get_metrics));
+    axum::serve(listener, app).await
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn scrape_metrics() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(serve_metrics(listener));
+
+        LAYER_MESSAGES.inc();
+        QUEUED_MESSAGES.with_label_values(&["scrape_test"]).set(3);
+
+        let mut stream = TcpStream::connect(address).await.unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{response}");
+        assert!(response.contains("mirrord_intproxy_layer_messages_total"));
+        assert!(response.contains("mirrord_intproxy_agent_payload_bytes_total"));
+        assert!(response.contains(r#"mirrord_intproxy_queued_messages{task="scrape_test"} 3"#));
+    }
+}
@@ -4,10 +4,14 @@
 //!
 //! Realized using the [`DaemonMessage::Pong`](mirrord_protocol::codec::DaemonMessage::Pong) and
 //! [`ClientMessage::Ping`] messages.
+//!
+//! When `experimental.agent_ttl_extension` is set, every ping is accompanied by a
+//! [`ClientMessage::ExtendTtl`], which keeps the agent alive while the proxy reconnects.
 
 use std::{ops::ControlFlow, time::Duration};
 
-use mirrord_protocol::ClientMessage;
+use mirrord_protocol::{ClientMessage, codec::EXTEND_TTL_VERSION};
+use semver::Version;
 use thiserror::Error;
 use tokio::time::{self, Instant, Interval, MissedTickBehavior};
 use tracing::Level;
@@ -35,6 +39,8 @@ pub enum PingPongMessage {
     AgentSentPong,
     AgentSentMessage,
     ConnectionRefresh(ConnectionRefresh),
+    /// Protocol version negotiated with the agent.
+    AgentProtocolVersion(Version),
 }
 
 /// Encapsulates logic of the ping pong mechanism on the proxy side.
@@ -50,6 +56,12 @@ pub struct PingPong {
     max_reconnects: usize,
 
     last_agent_message: Option<Instant>,
+
+    /// How long the agent should stay alive after the proxy disconnects, 0 disables
+    /// [`ClientMessage::ExtendTtl`].
+    ttl_extension_secs: u64,
+    /// Whether the agent supports [`ClientMessage::ExtendTtl`].
+    extend_ttl_supported: bool,
 }
 
 impl PingPong {
@@ -58,7 +70,9 @@ impl PingPong {
     /// # Arguments
     ///
     /// * frequency - how often the task should send pings
-    pub fn new(frequency: Duration, max_reconnects: usize) -> Self {
+    /// * ttl_extension_secs - how long the agent should stay alive after the proxy disconnects, see
+    ///   [`ClientMessage::ExtendTtl`]
+    pub fn new(frequency: Duration, max_reconnects: usize, ttl_extension_secs: u64) -> Self {
         let mut ticker = time::interval_at(Instant::now() + frequency, frequency);
         ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
 
@@ -69,6 +83,17 @@ impl PingPong {
             reconnects: 0,
             max_reconnects,
             last_agent_message: None,
+            ttl_extension_secs,
+            extend_ttl_supported: false,
+        }
+    }
+
+    /// Sends [`ClientMessage::ExtendTtl`] to the agent, if enabled and supported.
+    async fn extend_agent_ttl(&self, message_bus: &mut MessageBus<Self>) {
+        if self.ttl_extension_secs > 0 && self.extend_ttl_supported {
+            message_bus
+                .send_agent(ClientMessage::ExtendTtl(self.ttl_extension_secs))
+                .await;
         }
     }
 }
@@ -97,6 +122,7 @@ impl BackgroundTask for PingPong {
                         tracing::debug!("Sending ping to the agent");
                         message_bus.send_agent(ClientMessage::Ping).await;
                         self.awaiting_pongs += 1;
+                        self.extend_agent_ttl(message_bus).await;
                     }
                 },
 
@@ -137,6 +163,10 @@ impl BackgroundTask for PingPong {
                             ConnectionRefresh::Request => {}
                         }
                     }
+                    (Some(PingPongMessage::AgentProtocolVersion(version)), _) => {
+                        self.extend_ttl_supported = EXTEND_TTL_VERSION.matches(&version);
+                        self.extend_agent_ttl(message_bus).await;
+                    }
 
                 },
             }
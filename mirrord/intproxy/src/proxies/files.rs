@@ -3,9 +3,11 @@ use std::{
     borrow::Borrow,
     collections::{HashMap, HashSet, VecDeque},
     ops::Not,
+    path::Path,
     vec,
 };
 
+use mirrord_config::feature::fs::ReadonlyFileBuffers;
 use mirrord_intproxy_protocol::{LayerId, MessageId, ProxyToLayerMessage};
 use mirrord_protocol::{
     ClientMessage, DaemonMessage, ErrorKindInternal, FileRequest, FileResponse, RemoteIOError,
@@ -18,6 +20,7 @@ use tracing::Level;
 use crate::{
     background_tasks::{BackgroundTask, MessageBus},
     error::{UnexpectedAgentMessage, agent_lost_io_error},
+    fault_injection::{DelayedRequests, FaultInjector},
     main_tasks::{ConnectionRefresh, LayerClosed, LayerForked, ProxyMessage, ToLayer},
     remote_resources::RemoteResources,
     request_queue::RequestQueue,
@@ -62,6 +65,16 @@ impl From<AgentLostFileResponse> for ToLayer {
             FileResponse::Futimens(..) => FileResponse::Futimens(Err(error)),
             FileResponse::Fchown(..) => FileResponse::Fchown(Err(error)),
             FileResponse::Fchmod(..) => FileResponse::Fchmod(Err(error)),
+            FileResponse::GetXattr(..) => FileResponse::GetXattr(Err(error)),
+            FileResponse::SetXattr(..) => FileResponse::SetXattr(Err(error)),
+            FileResponse::ListXattr(..) => FileResponse::ListXattr(Err(error)),
+            FileResponse::Flock(..) => FileResponse::Flock(Err(error)),
+            FileResponse::Syncfs(..) => FileResponse::Syncfs(Err(error)),
+            FileResponse::ReadDirBatchFrom(..) => FileResponse::ReadDirBatchFrom(Err(error)),
+            FileResponse::Readv(..) => FileResponse::Readv(Err(error)),
+            FileResponse::Writev(..) => FileResponse::Writev(Err(error)),
+            FileResponse::CopyFileRange(..) => FileResponse::CopyFileRange(Err(error)),
+            FileResponse::FcntlLock(..) => FileResponse::FcntlLock(Err(error)),
         };
 
         debug_assert_eq!(
@@ -97,13 +110,19 @@ impl FileRequestExt for FileRequest {
         let response = match self {
             Self::Close(..) | Self::CloseDir(..) => return None,
             Self::Access(..) => dummy_file_response!(Access),
+            Self::AccessV2(..) => dummy_file_response!(Access),
             Self::FdOpenDir(..) => dummy_file_response!(OpenDir),
             Self::GetDEnts64(..) => dummy_file_response!(GetDEnts64),
             Self::Open(..) => dummy_file_response!(Open),
             Self::OpenRelative(..) => dummy_file_response!(Open),
+            Self::OpenV2(..) => dummy_file_response!(Open),
+            Self::OpenRelativeV2(..) => dummy_file_response!(Open),
+            Self::OpenV3(..) => dummy_file_response!(Open),
+            Self::OpenRelativeV3(..) => dummy_file_response!(Open),
             Self::Read(..) => dummy_file_response!(Read),
             Self::ReadDir(..) => dummy_file_response!(ReadDir),
             Self::ReadDirBatch(..) => dummy_file_response!(ReadDirBatch),
+            Self::ReadDirBatchFrom(..) => dummy_file_response!(ReadDirBatchFrom),
             Self::ReadLimited(..) => dummy_file_response!(ReadLimited),
             Self::Seek(..) => dummy_file_response!(Seek),
             Self::Write(..) => dummy_file_response!(Write),
@@ -124,6 +143,17 @@ impl FileRequestExt for FileRequest {
             Self::Futimens(..) => dummy_file_response!(Futimens),
             Self::Fchown(..) => dummy_file_response!(Fchown),
             Self::Fchmod(..) => dummy_file_response!(Fchmod),
+            Self::GetXattr(..) => dummy_file_response!(GetXattr),
+            Self::SetXattr(..) => dummy_file_response!(SetXattr),
+            Self::ListXattr(..) => dummy_file_response!(ListXattr),
+            Self::Flock(..) => dummy_file_response!(Flock),
+            Self::Syncfs(..) => dummy_file_response!(Syncfs),
+            Self::Readv(..) => dummy_file_response!(Readv),
+            Self::Writev(..) => dummy_file_response!(Writev),
+            Self::CopyFileRange(..) => dummy_file_response!(CopyFileRange),
+            Self::Utimens(..) => dummy_file_response!(Futimens),
+            Self::Chown(..) => dummy_file_response!(Fchown),
+            Self::FcntlLock(..) => dummy_file_response!(FcntlLock),
         };
 
         Some(AgentLostFileResponse(layer_id, message_id, response))
@@ -159,11 +189,17 @@ struct BufferedFileData {
     buffer: Vec<u8>,
     /// Position of [`Self::buffer`] in the file.
     buffer_position: u64,
+    /// How many bytes we fetch at least, when the read does not fit in [`Self::buffer`].
+    buffer_size: u64,
     /// Position of the file descriptor in the file.
     /// This position is normally managed in the agent,
     /// but for buffered files we manage it here.
     /// It's simpler this way.
     fd_position: u64,
+    /// Request that was used to open this file.
+    /// Present only if the file should be re-opened after a reconnect
+    /// (see [`FilesProxy::resume_reads`]).
+    reopen_request: Option<OpenFileRequest>,
 }
 
 impl BufferedFileData {
@@ -182,22 +218,76 @@ impl fmt::Debug for BufferedFileData {
         f.debug_struct("BufferedFileData")
             .field("buffer_position", &self.buffer_position)
             .field("buffer_len", &self.buffer.len())
+            .field("buffer_size", &self.buffer_size)
             .field("fd_position", &self.fd_position)
+            .field("reopen_request", &self.reopen_request)
             .finish()
     }
 }
 
+/// Buffered remote file that was lost together with the connection to the mirrord-agent,
+/// and is waiting to be re-opened (see [`FilesProxy::resume_reads`]).
+#[derive(Debug)]
+struct LostFileData {
+    /// Layer instances that hold this file.
+    layers: Vec<LayerId>,
+    /// Request that was used to open this file.
+    open_request: OpenFileRequest,
+    /// Position of the file descriptor in the file.
+    fd_position: u64,
+    /// Requests that refer to this file and should be handled once it's re-opened.
+    /// Contains reads interrupted by the connection drop, and requests made by the layer before
+    /// the file was re-opened.
+    pending_requests: Vec<(MessageId, LayerId, FileRequest)>,
+}
+
 /// Locally cached data of a remote directory that is buffered.
 #[derive(Default)]
 struct BufferedDirData {
     /// Buffered entries of this directory.
     buffered_entries: vec::IntoIter<DirEntryInternal>,
+    /// Position of the agent's stream of entries, right after [`Self::buffered_entries`].
+    /// Present only if [`mirrord_protocol`] version allows for [`FileRequest::ReadDirBatchFrom`].
+    cursor: Option<DirCursor>,
+    /// Request that was used to open the file this directory was opened from.
+    /// Present only if the directory should be re-opened after a reconnect
+    /// (see [`FilesProxy::resume_reads`]).
+    reopen_request: Option<OpenFileRequest>,
 }
 
 impl fmt::Debug for BufferedDirData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("BufferedDirData")
             .field("remaining_buffered_entries", &self.buffered_entries.len())
+            .field("cursor", &self.cursor)
+            .field("reopen_request", &self.reopen_request)
+            .finish()
+    }
+}
+
+/// Buffered remote directory that was lost together with the connection to the mirrord-agent,
+/// and is waiting to be re-opened (see [`FilesProxy::resume_reads`]).
+struct LostDirData {
+    /// Layer instances that hold this directory.
+    layers: Vec<LayerId>,
+    /// Request that was used to open the file this directory was opened from.
+    open_request: OpenFileRequest,
+    /// Position of the lost agent's stream of entries, the iteration continues from here.
+    cursor: DirCursor,
+    /// Buffered entries of this directory, not yet returned to the layer.
+    buffered_entries: vec::IntoIter<DirEntryInternal>,
+    /// Requests that refer to this directory and should be handled once it's re-opened.
+    pending_requests: Vec<(MessageId, LayerId, FileRequest)>,
+}
+
+impl fmt::Debug for LostDirData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LostDirData")
+            .field("layers", &self.layers)
+            .field("open_request", &self.open_request)
+            .field("cursor", &self.cursor)
+            .field("remaining_buffered_entries", &self.buffered_entries.len())
+            .field("pending_requests", &self.pending_requests)
             .finish()
     }
 }
@@ -207,7 +297,18 @@ impl fmt::Debug for BufferedDirData {
 #[derive(Debug, Default)]
 enum AdditionalRequestData {
     /// Open file that will be buffered.
-    OpenBuffered,
+    OpenBuffered {
+        /// Size of the file buffer, see [`BufferedFileData::buffer_size`].
+        buffer_size: u64,
+        /// Request to be used when re-opening the file after a reconnect.
+        reopen_request: Option<OpenFileRequest>,
+    },
+
+    /// Re-open a buffered file that was lost with the previous mirrord-agent.
+    Reopen {
+        /// User-facing fd of the lost file.
+        user_fd: u64,
+    },
 
     /// Read file that is buffered.
     ReadBuffered {
@@ -219,6 +320,8 @@ enum AdditionalRequestData {
         /// Whether we should update fd position in file
         /// (we store it locally).
         update_fd_position: bool,
+        /// Position in the file from which we read.
+        start_from: u64,
     },
 
     /// Seek file that is buffered.
@@ -227,6 +330,33 @@ enum AdditionalRequestData {
         fd: u64,
     },
 
+    /// Open a directory from a file.
+    OpenDir {
+        /// Request to be used when re-opening the directory after a reconnect.
+        reopen_request: Option<OpenFileRequest>,
+    },
+
+    /// Re-open the file of a buffered directory that was lost with the previous mirrord-agent.
+    ReopenDir {
+        /// User-facing fd of the lost directory.
+        user_fd: u64,
+    },
+
+    /// Open a buffered directory that was lost with the previous mirrord-agent from its
+    /// re-opened file.
+    ReopenDirStream {
+        /// User-facing fd of the lost directory.
+        user_fd: u64,
+        /// Remote fd of the re-opened file, closed once the directory is opened.
+        file_fd: u64,
+    },
+
+    /// Read entries of a directory that is buffered, from its [`BufferedDirData::cursor`].
+    ReadDirBuffered {
+        /// Directory descriptor.
+        fd: u64,
+    },
+
     /// All other file ops.
     #[default]
     Other,
@@ -246,7 +376,13 @@ pub struct RouterFileOps {
     /// Prepared error responses to outstanding [`FileRequest`]s.
     /// We must flush these when connection to the mirrord-agent is lost, otherwise the layer will
     /// hang.
-    queued_error_responses: VecDeque<AgentLostFileResponse>,
+    ///
+    /// [`None`] marks requests that were not sent by the layer, e.g. re-opening a file after a
+    /// reconnect.
+    queued_error_responses: VecDeque<Option<AgentLostFileResponse>>,
+    /// Files that were lost with the previous mirrord-agent and re-opened with the current one.
+    /// Maps user-facing fds to the fds received from the current mirrord-agent.
+    restored_fds: HashMap<u64, u64>,
 }
 
 impl fmt::Debug for RouterFileOps {
@@ -254,11 +390,41 @@ impl fmt::Debug for RouterFileOps {
         f.debug_struct("RouterFileOps")
             .field("highest_user_facing_fd", &self.highest_user_facing_fd)
             .field("current_fd_offset", &self.current_fd_offset)
+            .field("restored_fds", &self.restored_fds)
             .finish()
     }
 }
 
 impl RouterFileOps {
+    /// Returns the fd used by the current mirrord-agent for the given user-facing fd,
+    /// or [`None`] if the fd was lost with a previous mirrord-agent.
+    pub fn remote_fd(&self, user_fd: u64) -> Option<u64> {
+        match self.restored_fds.get(&user_fd) {
+            Some(remote_fd) => Some(*remote_fd),
+            None => user_fd.checked_sub(self.current_fd_offset),
+        }
+    }
+
+    /// Reverse of [`Self::remote_fd`].
+    pub fn user_facing_fd(&self, remote_fd: u64) -> u64 {
+        self.restored_fds
+            .iter()
+            .find_map(|(user_fd, restored)| (*restored == remote_fd).then_some(*user_fd))
+            .unwrap_or(remote_fd + self.current_fd_offset)
+    }
+
+    /// Makes the given user-facing fd (lost with a previous mirrord-agent) refer to a file
+    /// re-opened with the current mirrord-agent.
+    pub fn restore(&mut self, user_fd: u64, remote_fd: u64) {
+        self.restored_fds.insert(user_fd, remote_fd);
+    }
+
+    /// Notify this manager that a [`FileRequest`] was sent to the agent
+    /// on behalf of the proxy itself, and not the layer.
+    pub fn track_internal_request(&mut self) {
+        self.queued_error_responses.push_back(None);
+    }
+
     /// Return a request to be sent to the agent ([`Ok`] variant) or
     /// a response to be sent to the user ([`Err`] variant).
     ///
@@ -274,7 +440,10 @@ impl RouterFileOps {
             // These requests do not refer to any open remote fd.
             // It's safe to pass them as they are.
             FileRequest::Open(..)
+            | FileRequest::OpenV2(..)
+            | FileRequest::OpenV3(..)
             | FileRequest::Access(..)
+            | FileRequest::AccessV2(..)
             | FileRequest::Xstat(XstatRequest { fd: None, .. })
             | FileRequest::ReadLink(..)
             | FileRequest::MakeDir(..)
@@ -283,17 +452,32 @@ impl RouterFileOps {
             | FileRequest::StatFs(..)
             | FileRequest::StatFsV2(..)
             | FileRequest::Rename(..)
-            | FileRequest::UnlinkAt(UnlinkAtRequest { dirfd: None, .. }) => {}
+            | FileRequest::Utimens(..)
+            | FileRequest::Chown(..)
+            | FileRequest::UnlinkAt(UnlinkAtRequest { dirfd: None, .. })
+            | FileRequest::Syncfs(SyncfsRequest { fd: None })
+            | FileRequest::GetXattr(GetXattrRequest {
+                target: XattrTarget::Path(..) | XattrTarget::LinkPath(..),
+                ..
+            })
+            | FileRequest::SetXattr(SetXattrRequest {
+                target: XattrTarget::Path(..) | XattrTarget::LinkPath(..),
+                ..
+            })
+            | FileRequest::ListXattr(ListXattrRequest {
+                target: XattrTarget::Path(..) | XattrTarget::LinkPath(..),
+            }) => {}
 
             // These requests do not require any response from the agent.
             // We need to remap the fd, but if the fd is invalid we simply drop them.
             FileRequest::Close(CloseFileRequest { fd: remote_fd })
             | FileRequest::CloseDir(CloseDirRequest { remote_fd }) => {
-                if *remote_fd < self.current_fd_offset {
+                let Some(mapped) = self.remote_fd(*remote_fd) else {
                     return Ok(None);
-                }
+                };
 
-                *remote_fd -= self.current_fd_offset;
+                self.restored_fds.remove(remote_fd);
+                *remote_fd = mapped;
             }
 
             // These requests refer to an open remote fd and require a response from the agent.
@@ -304,9 +488,18 @@ impl RouterFileOps {
                 relative_fd: remote_fd,
                 ..
             })
+            | FileRequest::OpenRelativeV2(OpenRelativeFileRequestV2 {
+                relative_fd: remote_fd,
+                ..
+            })
+            | FileRequest::OpenRelativeV3(OpenRelativeFileRequestV3 {
+                relative_fd: remote_fd,
+                ..
+            })
             | FileRequest::Read(ReadFileRequest { remote_fd, .. })
             | FileRequest::ReadDir(ReadDirRequest { remote_fd, .. })
             | FileRequest::ReadDirBatch(ReadDirBatchRequest { remote_fd, .. })
+            | FileRequest::ReadDirBatchFrom(ReadDirBatchFromRequest { remote_fd, .. })
             | FileRequest::ReadLimited(ReadLimitedFileRequest { remote_fd, .. })
             | FileRequest::Seek(SeekFileRequest { fd: remote_fd, .. })
             | FileRequest::Write(WriteFileRequest { fd: remote_fd, .. })
@@ -327,21 +520,55 @@ impl RouterFileOps {
             | FileRequest::Ftruncate(FtruncateRequest { fd: remote_fd, .. })
             | FileRequest::Futimens(FutimensRequest { fd: remote_fd, .. })
             | FileRequest::Fchown(FchownRequest { fd: remote_fd, .. })
-            | FileRequest::Fchmod(FchmodRequest { fd: remote_fd, .. }) => {
-                if *remote_fd < self.current_fd_offset {
+            | FileRequest::Fchmod(FchmodRequest { fd: remote_fd, .. })
+            | FileRequest::Flock(FlockRequest { fd: remote_fd, .. })
+            | FileRequest::FcntlLock(FcntlLockRequest { fd: remote_fd, .. })
+            | FileRequest::Syncfs(SyncfsRequest {
+                fd: Some(remote_fd),
+            })
+            | FileRequest::Readv(ReadvFileRequest { fd: remote_fd, .. })
+            | FileRequest::Writev(WritevFileRequest { fd: remote_fd, .. })
+            | FileRequest::GetXattr(GetXattrRequest {
+                target: XattrTarget::Fd(remote_fd),
+                ..
+            })
+            | FileRequest::SetXattr(SetXattrRequest {
+                target: XattrTarget::Fd(remote_fd),
+                ..
+            })
+            | FileRequest::ListXattr(ListXattrRequest {
+                target: XattrTarget::Fd(remote_fd),
+            }) => {
+                let Some(mapped) = self.remote_fd(*remote_fd) else {
                     let error_response = request
                         .agent_lost_response(layer_id, message_id)
                         .expect("these requests require responses")
                         .into();
                     return Err(Box::new(error_response));
-                }
+                };
+
+                *remote_fd = mapped;
+            }
+
+            // This request refers to two open remote fds, and requires a response from the agent.
+            FileRequest::CopyFileRange(CopyFileRangeRequest { fd_in, fd_out, .. }) => {
+                let (Some(mapped_in), Some(mapped_out)) =
+                    (self.remote_fd(*fd_in), self.remote_fd(*fd_out))
+                else {
+                    let error_response = request
+                        .agent_lost_response(layer_id, message_id)
+                        .expect("this request requires a response")
+                        .into();
+                    return Err(Box::new(error_response));
+                };
 
-                *remote_fd -= self.current_fd_offset;
+                *fd_in = mapped_in;
+                *fd_out = mapped_out;
             }
         };
 
         if let Some(response) = request.agent_lost_response(layer_id, message_id) {
-            self.queued_error_responses.push_back(response);
+            self.queued_error_responses.push_back(Some(response));
         }
 
         Ok(Some(request))
@@ -366,6 +593,7 @@ impl RouterFileOps {
             | FileResponse::Open(Err(..))
             | FileResponse::OpenDir(Err(..))
             | FileResponse::ReadDirBatch(Err(..))
+            | FileResponse::ReadDirBatchFrom(Err(..))
             | FileResponse::ReadLink(..)
             | FileResponse::MakeDir(..)
             | FileResponse::Unlink(..)
@@ -374,12 +602,24 @@ impl RouterFileOps {
             | FileResponse::Ftruncate(..)
             | FileResponse::Futimens(..)
             | FileResponse::Fchown(..)
-            | FileResponse::Fchmod(..) => {}
+            | FileResponse::Fchmod(..)
+            | FileResponse::GetXattr(..)
+            | FileResponse::SetXattr(..)
+            | FileResponse::ListXattr(..)
+            | FileResponse::Flock(..)
+            | FileResponse::Syncfs(..)
+            | FileResponse::Readv(..)
+            | FileResponse::Writev(..)
+            | FileResponse::CopyFileRange(..)
+            | FileResponse::FcntlLock(..) => {}
 
             FileResponse::GetDEnts64(Ok(GetDEnts64Response { fd: remote_fd, .. }))
             | FileResponse::Open(Ok(OpenFileResponse { fd: remote_fd }))
             | FileResponse::OpenDir(Ok(OpenDirResponse { fd: remote_fd, .. }))
-            | FileResponse::ReadDirBatch(Ok(ReadDirBatchResponse { fd: remote_fd, .. })) => {
+            | FileResponse::ReadDirBatch(Ok(ReadDirBatchResponse { fd: remote_fd, .. }))
+            | FileResponse::ReadDirBatchFrom(Ok(ReadDirBatchFromResponse {
+                fd: remote_fd, ..
+            })) => {
                 *remote_fd += self.current_fd_offset;
 
                 self.highest_user_facing_fd =
@@ -397,7 +637,11 @@ impl RouterFileOps {
     #[tracing::instrument(level = Level::TRACE)]
     pub fn agent_lost(&mut self) -> VecDeque<AgentLostFileResponse> {
         self.current_fd_offset = self.highest_user_facing_fd.map(|fd| fd + 1).unwrap_or(0);
+        self.restored_fds.clear();
         std::mem::take(&mut self.queued_error_responses)
+            .into_iter()
+            .flatten()
+            .collect()
     }
 }
 
@@ -412,13 +656,19 @@ impl RouterFileOps {
 ///
 /// Excessive entries are cached locally in this proxy and used until depleted.
 ///
+/// When the [`mirrord_protocol`] version allows for it, we use [`FileRequest::ReadDirBatchFrom`]
+/// instead, and track the [`DirCursor`] of every directory. This allows for re-opening a buffered
+/// directory after a reconnect, and continuing the iteration where it stopped (see
+/// [`Self::resume_reads`]).
+///
 /// # File buffering
 ///
 /// To optimize cases where user application makes a lot of small reads on remote files,
 /// we change the way of reading readonly files.
 ///
-/// 1. When created with [`FilesProxy::new`], this proxy is given a desired file buffer size. Buffer
-///    size 0 disables file buffering.
+/// 1. When created with [`FilesProxy::new`], this proxy is given desired file buffer sizes. The
+///    size of a file's buffer is picked when the file is opened, based on its path. Buffer size 0
+///    disables file buffering.
 /// 2. When the user requests a read, we fetch at least `buffer_size` bytes. We return the amount
 ///    requested by the user and store the whole response as a local buffer.
 /// 3. When the user requests a read again, we try to fulfill the request using only the local
@@ -431,9 +681,9 @@ pub struct FilesProxy {
     /// [`FileRequest::ReadLink`].
     protocol_version: Option<Version>,
 
-    /// Size for readonly files buffer.
-    /// If equal to 0, this proxy does not buffer files.
-    file_buffer_size: u64,
+    /// Sizes for readonly files buffers.
+    /// Files with buffer size 0 are not buffered.
+    file_buffers: ReadonlyFileBuffers,
 
     /// Stores metadata of outstanding requests.
     request_queue: RequestQueue<AdditionalRequestData>,
@@ -442,25 +692,44 @@ pub struct FilesProxy {
     remote_files: RemoteResources<u64>,
     /// Locally stored data of buffered files.
     buffered_files: HashMap<u64, BufferedFileData>,
+    /// Whether buffered files should be re-opened after a reconnect,
+    /// with their interrupted reads resumed.
+    resume_reads: bool,
+    /// Buffered files lost with the previous mirrord-agent, waiting to be re-opened.
+    /// Keyed by user-facing fds.
+    lost_files: HashMap<u64, LostFileData>,
 
     /// For tracking remote directory descriptors across layer instances (forks).
     remote_dirs: RemoteResources<u64>,
     /// Locally stored data of buffered directories.
     buffered_dirs: HashMap<u64, BufferedDirData>,
+    /// Buffered directories lost with the previous mirrord-agent, waiting to be re-opened.
+    /// Keyed by user-facing fds.
+    lost_dirs: HashMap<u64, LostDirData>,
 
     reconnect_tracker: RouterFileOps,
+
+    /// Synthetic faults injected into [`FileRequest::Read`]s and [`FileRequest::ReadLimited`]s.
+    read_faults: FaultInjector,
+    /// Reads held back by the [`Self::read_faults`] delay.
+    delayed_reads: DelayedRequests<(FileRequest, LayerId, MessageId)>,
 }
 
 impl fmt::Debug for FilesProxy {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("FilesProxy")
-            .field("file_buffer_size", &self.file_buffer_size)
+            .field("file_buffers", &self.file_buffers)
             .field("buffer_readdir", &self.buffer_dirs())
             .field("buffered_files", &self.buffered_files)
+            .field("resume_reads", &self.resume_reads)
+            .field("lost_files", &self.lost_files)
             .field("buffered_dirs", &self.buffered_dirs)
+            .field("lost_dirs", &self.lost_dirs)
             .field("protocol_version", &self.protocol_version)
             .field("request_queue", &self.request_queue)
             .field("reconnect_tracker", &self.reconnect_tracker)
+            .field("read_faults", &self.read_faults)
+            .field("delayed_reads", &self.delayed_reads)
             .finish()
     }
 }
@@ -473,22 +742,36 @@ impl FilesProxy {
     /// Creates a new files proxy instance.
     /// Proxy can be used as a [`BackgroundTask`].
     ///
-    /// `file_buffer_size` sets size of the readonly files buffer.
+    /// `file_buffers` set sizes of the readonly files buffers.
     /// Size 0 disables buffering.
-    pub fn new(file_buffer_size: u64) -> Self {
+    ///
+    /// `resume_reads` enables re-opening buffered files after a reconnect.
+    ///
+    /// `read_faults` are injected into file reads.
+    pub fn new(
+        file_buffers: ReadonlyFileBuffers,
+        resume_reads: bool,
+        read_faults: FaultInjector,
+    ) -> Self {
         Self {
             protocol_version: Default::default(),
-            file_buffer_size,
+            file_buffers,
 
             request_queue: Default::default(),
 
             remote_files: Default::default(),
             buffered_files: Default::default(),
+            resume_reads,
+            lost_files: Default::default(),
 
             remote_dirs: Default::default(),
             buffered_dirs: Default::default(),
+            lost_dirs: Default::default(),
 
             reconnect_tracker: Default::default(),
+
+            read_faults,
+            delayed_reads: Default::default(),
         }
     }
 
@@ -499,19 +782,79 @@ impl FilesProxy {
             .is_some_and(|version| READDIR_BATCH_VERSION.matches(version))
     }
 
-    /// Returns whether this proxy is configured to buffer readonly files.
-    fn buffer_reads(&self) -> bool {
-        self.file_buffer_size > 0
+    /// Returns whether [`mirrord_protocol`] version allows for reading directories from a
+    /// [`DirCursor`].
+    fn dir_cursors(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| READDIR_CURSOR_VERSION.matches(version))
+    }
+
+    /// Returns whether [`mirrord_protocol`] version allows for sending [`OpenFlagsInternal`].
+    fn open_flags(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| OPEN_FLAGS_VERSION.matches(version))
+    }
+
+    /// Returns whether [`mirrord_protocol`] version allows for sending [`OpenFileRequestV3`].
+    fn open_nonblock(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| OPEN_NONBLOCK_VERSION.matches(version))
+    }
+
+    /// Returns whether [`mirrord_protocol`] version allows for sending [`AccessFlagsInternal`].
+    fn access_flags(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| ACCESS_FLAGS_VERSION.matches(version))
+    }
+
+    /// Returns the buffer size for a readonly file opened at `path`.
+    ///
+    /// Relative paths always get the default size, as we don't know what they're relative to.
+    fn file_buffer_size(&self, path: &Path) -> u64 {
+        if path.is_absolute() {
+            self.file_buffers.size_for(path)
+        } else {
+            self.file_buffers.default_size()
+        }
     }
 
     #[tracing::instrument(level = Level::TRACE)]
     fn layer_forked(&mut self, forked: LayerForked) {
         self.remote_files.clone_all(forked.parent, forked.child);
         self.remote_dirs.clone_all(forked.parent, forked.child);
+
+        for lost in self.lost_files.values_mut() {
+            if lost.layers.contains(&forked.parent) {
+                lost.layers.push(forked.child);
+            }
+        }
+
+        for lost in self.lost_dirs.values_mut() {
+            if lost.layers.contains(&forked.parent) {
+                lost.layers.push(forked.child);
+            }
+        }
     }
 
     #[tracing::instrument(level = Level::TRACE, skip(message_bus))]
     async fn layer_closed(&mut self, closed: LayerClosed, message_bus: &mut MessageBus<Self>) {
+        self.lost_files.retain(|_, lost| {
+            lost.layers.retain(|layer_id| *layer_id != closed.id);
+            lost.pending_requests
+                .retain(|(_, layer_id, _)| *layer_id != closed.id);
+            lost.layers.is_empty().not()
+        });
+        self.lost_dirs.retain(|_, lost| {
+            lost.layers.retain(|layer_id| *layer_id != closed.id);
+            lost.pending_requests
+                .retain(|(_, layer_id, _)| *layer_id != closed.id);
+            lost.layers.is_empty().not()
+        });
+
         for fd in self.remote_files.remove_all(closed.id) {
             self.buffered_files.remove(&fd);
             message_bus
@@ -579,6 +922,77 @@ impl FilesProxy {
             {
                 Err(FileResponse::Rename(Err(ResponseError::NotImplemented)))
             }
+            FileRequest::GetXattr(..)
+                if protocol_version
+                    .is_none_or(|version: &Version| XATTR_VERSION.matches(version).not()) =>
+            {
+                Err(FileResponse::GetXattr(Err(ResponseError::NotImplemented)))
+            }
+            FileRequest::SetXattr(..)
+                if protocol_version
+                    .is_none_or(|version: &Version| XATTR_VERSION.matches(version).not()) =>
+            {
+                Err(FileResponse::SetXattr(Err(ResponseError::NotImplemented)))
+            }
+            FileRequest::ListXattr(..)
+                if protocol_version
+                    .is_none_or(|version: &Version| XATTR_VERSION.matches(version).not()) =>
+            {
+                Err(FileResponse::ListXattr(Err(ResponseError::NotImplemented)))
+            }
+            FileRequest::Flock(..)
+                if protocol_version
+                    .is_none_or(|version: &Version| FLOCK_VERSION.matches(version).not()) =>
+            {
+                Err(FileResponse::Flock(Err(ResponseError::NotImplemented)))
+            }
+            FileRequest::Syncfs(..)
+                if protocol_version
+                    .is_none_or(|version: &Version| SYNCFS_VERSION.matches(version).not()) =>
+            {
+                Err(FileResponse::Syncfs(Err(ResponseError::NotImplemented)))
+            }
+            FileRequest::Readv(..)
+                if protocol_version
+                    .is_none_or(|version: &Version| IOV_VERSION.matches(version).not()) =>
+            {
+                Err(FileResponse::Readv(Err(ResponseError::NotImplemented)))
+            }
+            FileRequest::Writev(..)
+                if protocol_version
+                    .is_none_or(|version: &Version| IOV_VERSION.matches(version).not()) =>
+            {
+                Err(FileResponse::Writev(Err(ResponseError::NotImplemented)))
+            }
+            FileRequest::CopyFileRange(..)
+                if protocol_version.is_none_or(|version: &Version| {
+                    COPY_FILE_RANGE_VERSION.matches(version).not()
+                }) =>
+            {
+                Err(FileResponse::CopyFileRange(Err(
+                    ResponseError::NotImplemented,
+                )))
+            }
+            FileRequest::Utimens(..)
+                if protocol_version.is_none_or(|version: &Version| {
+                    PATH_METADATA_VERSION.matches(version).not()
+                }) =>
+            {
+                Err(FileResponse::Futimens(Err(ResponseError::NotImplemented)))
+            }
+            FileRequest::Chown(..)
+                if protocol_version.is_none_or(|version: &Version| {
+                    PATH_METADATA_VERSION.matches(version).not()
+                }) =>
+            {
+                Err(FileResponse::Fchown(Err(ResponseError::NotImplemented)))
+            }
+            FileRequest::FcntlLock(..)
+                if protocol_version
+                    .is_none_or(|version: &Version| FCNTL_LOCK_VERSION.matches(version).not()) =>
+            {
+                Err(FileResponse::FcntlLock(Err(ResponseError::NotImplemented)))
+            }
             _ => Ok(()),
         }
     }
@@ -610,6 +1024,46 @@ impl FilesProxy {
             return;
         }
 
+        // Older agents don't know the open and access flags, so they handle the path like before
+        // the flags were supported.
+        let request = match request {
+            FileRequest::OpenV3(open) if !self.open_nonblock() => FileRequest::OpenV2(open.into()),
+            FileRequest::OpenRelativeV3(open) if !self.open_nonblock() => {
+                FileRequest::OpenRelativeV2(open.into())
+            }
+            other => other,
+        };
+        let request = match request {
+            FileRequest::OpenV2(open) if !self.open_flags() => FileRequest::Open(open.into()),
+            FileRequest::OpenRelativeV2(open) if !self.open_flags() => {
+                FileRequest::OpenRelative(open.into())
+            }
+            FileRequest::AccessV2(access) if !self.access_flags() => {
+                FileRequest::Access(access.into())
+            }
+            other => other,
+        };
+
+        if matches!(
+            request,
+            FileRequest::Read(..) | FileRequest::ReadLimited(..)
+        ) && self.read_faults.fails()
+        {
+            let error = Err(FaultInjector::file_read_error());
+            let response = match request {
+                FileRequest::Read(..) => FileResponse::Read(error),
+                _ => FileResponse::ReadLimited(error),
+            };
+            message_bus
+                .send(ToLayer {
+                    message_id,
+                    layer_id,
+                    message: ProxyToLayerMessage::File(response),
+                })
+                .await;
+            return;
+        }
+
         match request {
             // Should trigger remote close only when the fd is closed in all layer instances.
             FileRequest::Close(close) => {
@@ -633,8 +1087,12 @@ impl FilesProxy {
 
             // May require storing additional data in the request queue.
             FileRequest::Open(open) => {
-                let additional_data = if self.buffer_reads() && open.open_options.is_read_only() {
-                    AdditionalRequestData::OpenBuffered
+                let buffer_size = self.file_buffer_size(&open.path);
+                let additional_data = if buffer_size > 0 && open.open_options.is_read_only() {
+                    AdditionalRequestData::OpenBuffered {
+                        buffer_size,
+                        reopen_request: self.resume_reads.then(|| open.clone()),
+                    }
                 } else {
                     Default::default()
                 };
@@ -647,8 +1105,12 @@ impl FilesProxy {
 
             // May require storing additional data in the request queue.
             FileRequest::OpenRelative(open) => {
-                let additional_data = if self.buffer_reads() && open.open_options.is_read_only() {
-                    AdditionalRequestData::OpenBuffered
+                let buffer_size = self.file_buffer_size(&open.path);
+                let additional_data = if buffer_size > 0 && open.open_options.is_read_only() {
+                    AdditionalRequestData::OpenBuffered {
+                        buffer_size,
+                        reopen_request: None,
+                    }
                 } else {
                     Default::default()
                 };
@@ -659,6 +1121,93 @@ impl FilesProxy {
                     .await;
             }
 
+            // May require storing additional data in the request queue.
+            //
+            // `O_PATH` fds can't be read from, so they are never buffered.
+            FileRequest::OpenV2(open) => {
+                let buffer_size = self.file_buffer_size(&open.path);
+                let additional_data =
+                    if buffer_size > 0 && open.flags.path.not() && open.open_options.is_read_only()
+                    {
+                        AdditionalRequestData::OpenBuffered {
+                            buffer_size,
+                            reopen_request: None,
+                        }
+                    } else {
+                        Default::default()
+                    };
+                self.request_queue
+                    .push_back_with_data(message_id, layer_id, additional_data);
+                message_bus
+                    .send_agent(ClientMessage::FileRequest(FileRequest::OpenV2(open)))
+                    .await;
+            }
+
+            // May require storing additional data in the request queue.
+            FileRequest::OpenRelativeV2(open) => {
+                let buffer_size = self.file_buffer_size(&open.path);
+                let additional_data =
+                    if buffer_size > 0 && open.flags.path.not() && open.open_options.is_read_only()
+                    {
+                        AdditionalRequestData::OpenBuffered {
+                            buffer_size,
+                            reopen_request: None,
+                        }
+                    } else {
+                        Default::default()
+                    };
+                self.request_queue
+                    .push_back_with_data(message_id, layer_id, additional_data);
+                message_bus
+                    .send_agent(ClientMessage::FileRequest(FileRequest::OpenRelativeV2(
+                        open,
+                    )))
+                    .await;
+            }
+
+            // Same as `OpenV2`, a remote FIFO is no longer buffered once it fails to be read at an
+            // offset.
+            FileRequest::OpenV3(open) => {
+                let buffer_size = self.file_buffer_size(&open.path);
+                let additional_data =
+                    if buffer_size > 0 && open.flags.path.not() && open.open_options.is_read_only()
+                    {
+                        AdditionalRequestData::OpenBuffered {
+                            buffer_size,
+                            reopen_request: None,
+                        }
+                    } else {
+                        Default::default()
+                    };
+                self.request_queue
+                    .push_back_with_data(message_id, layer_id, additional_data);
+                message_bus
+                    .send_agent(ClientMessage::FileRequest(FileRequest::OpenV3(open)))
+                    .await;
+            }
+
+            // Same as `OpenRelativeV2`.
+            FileRequest::OpenRelativeV3(open) => {
+                let buffer_size = self.file_buffer_size(&open.path);
+                let additional_data =
+                    if buffer_size > 0 && open.flags.path.not() && open.open_options.is_read_only()
+                    {
+                        AdditionalRequestData::OpenBuffered {
+                            buffer_size,
+                            reopen_request: None,
+                        }
+                    } else {
+                        Default::default()
+                    };
+                self.request_queue
+                    .push_back_with_data(message_id, layer_id, additional_data);
+                message_bus
+                    .send_agent(ClientMessage::FileRequest(FileRequest::OpenRelativeV3(
+                        open,
+                    )))
+                    .await;
+            }
+
             // Try to use local buffer if possible.
             FileRequest::Read(read) => match self.buffered_files.get_mut(&read.remote_fd) {
                 // File is buffered.
@@ -684,6 +1233,7 @@ impl FilesProxy {
                             fd: read.remote_fd,
                             requested_amount: read.buffer_size,
                             update_fd_position: true,
+                            start_from: data.fd_position,
                         };
                         self.request_queue.push_back_with_data(
                             message_id,
@@ -694,10 +1244,7 @@ impl FilesProxy {
                             .send_agent(ClientMessage::FileRequest(FileRequest::ReadLimited(
                                 ReadLimitedFileRequest {
                                     remote_fd: read.remote_fd,
-                                    buffer_size: std::cmp::max(
-                                        read.buffer_size,
-                                        self.file_buffer_size,
-                                    ),
+                                    buffer_size: std::cmp::max(read.buffer_size, data.buffer_size),
                                     start_from: data.fd_position,
                                 },
                             )))
@@ -738,6 +1285,7 @@ impl FilesProxy {
                             fd: read.remote_fd,
                             requested_amount: read.buffer_size,
                             update_fd_position: false,
+                            start_from: read.start_from,
                         };
                         self.request_queue.push_back_with_data(
                             message_id,
@@ -748,10 +1296,7 @@ impl FilesProxy {
                             .send_agent(ClientMessage::FileRequest(FileRequest::ReadLimited(
                                 ReadLimitedFileRequest {
                                     remote_fd: read.remote_fd,
-                                    buffer_size: std::cmp::max(
-                                        read.buffer_size,
-                                        self.file_buffer_size,
-                                    ),
+                                    buffer_size: std::cmp::max(read.buffer_size, data.buffer_size),
                                     start_from: read.start_from,
                                 },
                             )))
@@ -768,6 +1313,25 @@ impl FilesProxy {
                 }
             },
 
+            // May require storing additional data in the request queue.
+            FileRequest::FdOpenDir(open_dir) => {
+                let reopen_request = if self.resume_reads && self.dir_cursors() {
+                    self.buffered_files
+                        .get(&open_dir.remote_fd)
+                        .and_then(|data| data.reopen_request.clone())
+                } else {
+                    None
+                };
+                self.request_queue.push_back_with_data(
+                    message_id,
+                    layer_id,
+                    AdditionalRequestData::OpenDir { reopen_request },
+                );
+                message_bus
+                    .send_agent(ClientMessage::FileRequest(FileRequest::FdOpenDir(open_dir)))
+                    .await;
+            }
+
             // Try to use local buffer if possible.
             FileRequest::ReadDir(read_dir) => match self.buffered_dirs.get_mut(&read_dir.remote_fd)
             {
@@ -785,6 +1349,23 @@ impl FilesProxy {
                                 ))),
                             })
                             .await;
+                    } else if let Some(cursor) = data.cursor {
+                        self.request_queue.push_back_with_data(
+                            message_id,
+                            layer_id,
+                            AdditionalRequestData::ReadDirBuffered {
+                                fd: read_dir.remote_fd,
+                            },
+                        );
+                        message_bus
+                            .send_agent(ClientMessage::FileRequest(FileRequest::ReadDirBatchFrom(
+                                ReadDirBatchFromRequest {
+                                    remote_fd: read_dir.remote_fd,
+                                    amount: Self::READDIR_BATCH_SIZE,
+                                    cursor,
+                                },
+                            )))
+                            .await;
                     } else {
                         self.request_queue.push_back(message_id, layer_id);
                         message_bus
@@ -811,6 +1392,9 @@ impl FilesProxy {
             FileRequest::ReadDirBatch(..) => {
                 unreachable!("ReadDirBatch request is never sent from the layer");
             }
+            FileRequest::ReadDirBatchFrom(..) => {
+                unreachable!("ReadDirBatchFrom request is never sent from the layer");
+            }
 
             // May require storing additional data in the request queue.
             FileRequest::Seek(mut seek) => {
@@ -876,6 +1460,36 @@ impl FilesProxy {
                     .await;
             }
 
+            // Buffered files are read through the local buffer, the layer falls back to a plain
+            // read.
+            FileRequest::Readv(readv) if self.buffered_files.contains_key(&readv.fd) => {
+                message_bus
+                    .send(ToLayer {
+                        message_id,
+                        layer_id,
+                        message: ProxyToLayerMessage::File(FileResponse::Readv(Err(
+                            ResponseError::NotImplemented,
+                        ))),
+                    })
+                    .await;
+            }
+
+            // The agent doesn't know the position of a buffered file, the layer falls back to
+            // plain reads and writes.
+            FileRequest::CopyFileRange(copy)
+                if copy.off_in.is_none() && self.buffered_files.contains_key(&copy.fd_in) =>
+            {
+                message_bus
+                    .send(ToLayer {
+                        message_id,
+                        layer_id,
+                        message: ProxyToLayerMessage::File(FileResponse::CopyFileRange(Err(
+                            ResponseError::NotImplemented,
+                        ))),
+                    })
+                    .await;
+            }
+
             // Doesn't require any special logic.
             other => {
                 self.request_queue.push_back(message_id, layer_id);
@@ -902,12 +1516,37 @@ impl FilesProxy {
                         )
                     })?;
 
-                self.remote_files.add(layer_id, open.fd);
+                // Our maps are keyed with fds of the current mirrord-agent.
+                let remote_fd = self.reconnect_tracker.remote_fd(open.fd).unwrap_or(open.fd);
 
-                if matches!(additional_data, AdditionalRequestData::OpenBuffered) {
-                    self.buffered_files.insert(open.fd, Default::default());
+                match additional_data {
+                    AdditionalRequestData::Reopen { user_fd } => {
+                        self.file_reopened(user_fd, remote_fd, message_bus).await;
+                        return Ok(());
+                    }
+                    AdditionalRequestData::ReopenDir { user_fd } => {
+                        self.dir_file_reopened(user_fd, remote_fd, message_bus)
+                            .await;
+                        return Ok(());
+                    }
+                    AdditionalRequestData::OpenBuffered {
+                        buffer_size,
+                        reopen_request,
+                    } => {
+                        self.buffered_files.insert(
+                            remote_fd,
+                            BufferedFileData {
+                                buffer_size,
+                                reopen_request,
+                                ..Default::default()
+                            },
+                        );
+                    }
+                    _ => {}
                 }
 
+                self.remote_files.add(layer_id, remote_fd);
+
                 message_bus
                     .send(ToLayer {
                         layer_id,
@@ -917,18 +1556,79 @@ impl FilesProxy {
                     .await;
             }
 
-            // Update dir maps.
-            FileResponse::OpenDir(Ok(open)) => {
-                let (message_id, layer_id) = self.request_queue.pop_front().ok_or_else(|| {
-                    UnexpectedAgentMessage(
-                        DaemonMessage::File(FileResponse::OpenDir(Ok(open.clone()))).into(),
-                    )
-                })?;
-
-                self.remote_dirs.add(layer_id, open.fd);
+            // The file may have been re-opened after a reconnect.
+            FileResponse::Open(Err(error)) => {
+                let (message_id, layer_id, additional_data) =
+                    self.request_queue.pop_front_with_data().ok_or_else(|| {
+                        UnexpectedAgentMessage(
+                            DaemonMessage::File(FileResponse::Open(Err(error.clone()))).into(),
+                        )
+                    })?;
+
+                match additional_data {
+                    AdditionalRequestData::Reopen { user_fd } => {
+                        tracing::warn!(user_fd, %error, "Failed to re-open a lost remote file");
+                        if let Some(lost) = self.lost_files.remove(&user_fd) {
+                            Self::drop_lost_file(lost, message_bus).await;
+                        }
+                        return Ok(());
+                    }
+                    AdditionalRequestData::ReopenDir { user_fd } => {
+                        tracing::warn!(
+                            user_fd,
+                            %error,
+                            "Failed to re-open a lost remote directory"
+                        );
+                        if let Some(lost) = self.lost_dirs.remove(&user_fd) {
+                            Self::drop_lost_dir(lost, message_bus).await;
+                        }
+                        return Ok(());
+                    }
+                    _ => {}
+                }
+
+                message_bus
+                    .send(ToLayer {
+                        layer_id,
+                        message_id,
+                        message: ProxyToLayerMessage::File(FileResponse::Open(Err(error))),
+                    })
+                    .await;
+            }
+
+            // Update dir maps.
+            FileResponse::OpenDir(Ok(open)) => {
+                let (message_id, layer_id, additional_data) =
+                    self.request_queue.pop_front_with_data().ok_or_else(|| {
+                        UnexpectedAgentMessage(
+                            DaemonMessage::File(FileResponse::OpenDir(Ok(open.clone()))).into(),
+                        )
+                    })?;
+
+                // Our maps are keyed with fds of the current mirrord-agent.
+                let remote_fd = self.reconnect_tracker.remote_fd(open.fd).unwrap_or(open.fd);
+
+                let reopen_request = match additional_data {
+                    AdditionalRequestData::ReopenDirStream { user_fd, file_fd } => {
+                        self.dir_reopened(user_fd, file_fd, remote_fd, message_bus)
+                            .await;
+                        return Ok(());
+                    }
+                    AdditionalRequestData::OpenDir { reopen_request } => reopen_request,
+                    _ => None,
+                };
+
+                self.remote_dirs.add(layer_id, remote_fd);
 
                 if self.buffer_dirs() {
-                    self.buffered_dirs.insert(open.fd, Default::default());
+                    self.buffered_dirs.insert(
+                        remote_fd,
+                        BufferedDirData {
+                            cursor: self.dir_cursors().then(DirCursor::default),
+                            reopen_request,
+                            ..Default::default()
+                        },
+                    );
                 }
 
                 message_bus
@@ -940,6 +1640,38 @@ impl FilesProxy {
                     .await;
             }
 
+            // The directory may have been re-opened after a reconnect.
+            FileResponse::OpenDir(Err(error)) => {
+                let (message_id, layer_id, additional_data) =
+                    self.request_queue.pop_front_with_data().ok_or_else(|| {
+                        UnexpectedAgentMessage(
+                            DaemonMessage::File(FileResponse::OpenDir(Err(error.clone()))).into(),
+                        )
+                    })?;
+
+                if let AdditionalRequestData::ReopenDirStream { user_fd, file_fd } = additional_data
+                {
+                    tracing::warn!(user_fd, %error, "Failed to re-open a lost remote directory");
+                    message_bus
+                        .send_agent(ClientMessage::FileRequest(FileRequest::Close(
+                            CloseFileRequest { fd: file_fd },
+                        )))
+                        .await;
+                    if let Some(lost) = self.lost_dirs.remove(&user_fd) {
+                        Self::drop_lost_dir(lost, message_bus).await;
+                    }
+                    return Ok(());
+                }
+
+                message_bus
+                    .send(ToLayer {
+                        layer_id,
+                        message_id,
+                        message: ProxyToLayerMessage::File(FileResponse::OpenDir(Err(error))),
+                    })
+                    .await;
+            }
+
             // If the file is buffered, update `files_data`.
             FileResponse::ReadLimited(Ok(read)) => {
                 let (message_id, layer_id, additional_data) =
@@ -953,6 +1685,7 @@ impl FilesProxy {
                     fd,
                     requested_amount,
                     update_fd_position,
+                    start_from,
                 } = additional_data
                 else {
                     // This file is not buffered.
@@ -992,7 +1725,7 @@ impl FilesProxy {
                 };
 
                 data.buffer = read.bytes.into_vec();
-                data.buffer_position = data.fd_position;
+                data.buffer_position = start_from;
                 let message = if update_fd_position {
                     // User originally sent `FileRequest::Read`.
                     data.fd_position += response.read_amount;
@@ -1023,6 +1756,34 @@ impl FilesProxy {
                     })?;
 
                 let message = match additional_data {
+                    // Not seekable (a FIFO), so it can't be buffered. The user application did a
+                    // plain read, so we stop buffering and pass it to the agent as it was.
+                    AdditionalRequestData::ReadBuffered {
+                        fd,
+                        requested_amount,
+                        update_fd_position: true,
+                        ..
+                    } if matches!(
+                        error,
+                        ResponseError::RemoteIO(RemoteIOError {
+                            kind: ErrorKindInternal::NotSeekable,
+                            ..
+                        })
+                    ) =>
+                    {
+                        tracing::debug!(fd, "Remote file is not seekable, no longer buffering it");
+                        self.buffered_files.remove(&fd);
+                        self.request_queue.push_back(message_id, layer_id);
+                        message_bus
+                            .send_agent(ClientMessage::FileRequest(FileRequest::Read(
+                                ReadFileRequest {
+                                    remote_fd: fd,
+                                    buffer_size: requested_amount,
+                                },
+                            )))
+                            .await;
+                        return Ok(());
+                    }
                     AdditionalRequestData::ReadBuffered {
                         update_fd_position, ..
                     } if update_fd_position => FileResponse::Read(Err(error)),
@@ -1082,7 +1843,11 @@ impl FilesProxy {
                     )
                 })?;
 
-                let Some(data) = self.buffered_dirs.get_mut(&batch.fd) else {
+                let remote_fd = self
+                    .reconnect_tracker
+                    .remote_fd(batch.fd)
+                    .unwrap_or(batch.fd);
+                let Some(data) = self.buffered_dirs.get_mut(&remote_fd) else {
                     // Directory must have been closed from other thread in user application.
                     message_bus
                         .send(ToLayer {
@@ -1110,6 +1875,67 @@ impl FilesProxy {
                     })
                     .await;
             }
+
+            // Store extra entries and the new cursor in `dirs_data`.
+            FileResponse::ReadDirBatchFrom(Ok(batch)) => {
+                let (message_id, layer_id) = self.request_queue.pop_front().ok_or_else(|| {
+                    UnexpectedAgentMessage(
+                        DaemonMessage::File(FileResponse::ReadDirBatchFrom(Ok(batch.clone())))
+                            .into(),
+                    )
+                })?;
+
+                let remote_fd = self
+                    .reconnect_tracker
+                    .remote_fd(batch.fd)
+                    .unwrap_or(batch.fd);
+                let Some(data) = self.buffered_dirs.get_mut(&remote_fd) else {
+                    // Directory must have been closed from other thread in user application.
+                    message_bus
+                        .send(ToLayer {
+                            message_id,
+                            layer_id,
+                            message: ProxyToLayerMessage::File(FileResponse::ReadDir(Err(
+                                ResponseError::NotFound(batch.fd),
+                            ))),
+                        })
+                        .await;
+                    return Ok(());
+                };
+
+                let mut entries = batch.dir_entries.into_iter();
+                let direntry = entries.next();
+                data.buffered_entries = entries;
+                data.cursor = Some(batch.cursor);
+
+                message_bus
+                    .send(ToLayer {
+                        message_id,
+                        layer_id,
+                        message: ProxyToLayerMessage::File(FileResponse::ReadDir(Ok(
+                            ReadDirResponse { direntry },
+                        ))),
+                    })
+                    .await;
+            }
+
+            // The layer sent `ReadDir`, so it expects a `ReadDir` response.
+            FileResponse::ReadDirBatchFrom(Err(error)) => {
+                let (message_id, layer_id) = self.request_queue.pop_front().ok_or_else(|| {
+                    UnexpectedAgentMessage(
+                        DaemonMessage::File(FileResponse::ReadDirBatchFrom(Err(error.clone())))
+                            .into(),
+                    )
+                })?;
+
+                message_bus
+                    .send(ToLayer {
+                        message_id,
+                        layer_id,
+                        message: ProxyToLayerMessage::File(FileResponse::ReadDir(Err(error))),
+                    })
+                    .await;
+            }
             // Convert to XstatFsV2 so that the layer doesn't ever need to deal with the old type.
             FileResponse::XstatFs(res) => {
                 let (message_id, layer_id) = self.request_queue.pop_front().ok_or_else(|| {
@@ -1146,6 +1972,446 @@ impl FilesProxy {
         Ok(())
     }
 
+    /// Moves buffered files that can be re-opened to [`Self::lost_files`], together with their
+    /// interrupted reads.
+    ///
+    /// Must be called when the connection to the mirrord-agent is lost, before the remote files
+    /// are dropped.
+    ///
+    /// Returns ids of the interrupted reads that will be resumed.
+    #[tracing::instrument(level = Level::DEBUG, skip(self, interrupted_requests), ret)]
+    fn collect_lost_files(
+        &mut self,
+        interrupted_requests: Vec<(MessageId, LayerId, AdditionalRequestData)>,
+    ) -> HashSet<(LayerId, MessageId)> {
+        let mut user_fds = HashMap::new();
+
+        for (fd, data) in &self.buffered_files {
+            let Some(open_request) = data.reopen_request.clone() else {
+                continue;
+            };
+
+            let user_fd = self.reconnect_tracker.user_facing_fd(*fd);
+            user_fds.insert(*fd, user_fd);
+            self.lost_files.insert(
+                user_fd,
+                LostFileData {
+                    layers: self.remote_files.holders(fd).collect(),
+                    open_request,
+                    fd_position: data.fd_position,
+                    pending_requests: Default::default(),
+                },
+            );
+        }
+
+        let mut resumed_requests = HashSet::new();
+
+        for (message_id, layer_id, additional_data) in interrupted_requests {
+            let AdditionalRequestData::ReadBuffered {
+                fd,
+                requested_amount,
+                update_fd_position,
+                start_from,
+            } = additional_data
+            else {
+                continue;
+            };
+
+            let Some(user_fd) = user_fds.get(&fd).copied() else {
+                continue;
+            };
+            let Some(lost) = self.lost_files.get_mut(&user_fd) else {
+                continue;
+            };
+
+            // Recreate the request that was originally sent by the layer.
+            let request = if update_fd_position {
+                FileRequest::Read(ReadFileRequest {
+                    remote_fd: user_fd,
+                    buffer_size: requested_amount,
+                })
+            } else {
+                FileRequest::ReadLimited(ReadLimitedFileRequest {
+                    remote_fd: user_fd,
+                    buffer_size: requested_amount,
+                    start_from,
+                })
+            };
+
+            lost.pending_requests.push((message_id, layer_id, request));
+            resumed_requests.insert((layer_id, message_id));
+        }
+
+        resumed_requests
+    }
+
+    /// Moves buffered directories that can be re-opened to [`Self::lost_dirs`], together with
+    /// their interrupted reads.
+    ///
+    /// Must be called when the connection to the mirrord-agent is lost, before the remote
+    /// directories are dropped.
+    ///
+    /// Returns ids of the interrupted reads that will be resumed.
+    #[tracing::instrument(level = Level::DEBUG, skip(self, interrupted_requests), ret)]
+    fn collect_lost_dirs(
+        &mut self,
+        interrupted_requests: &[(MessageId, LayerId, AdditionalRequestData)],
+    ) -> HashSet<(LayerId, MessageId)> {
+        let mut user_fds = HashMap::new();
+
+        for (fd, data) in &mut self.buffered_dirs {
+            let (Some(open_request), Some(cursor)) = (data.reopen_request.clone(), data.cursor)
+            else {
+                continue;
+            };
+
+            let user_fd = self.reconnect_tracker.user_facing_fd(*fd);
+            user_fds.insert(*fd, user_fd);
+            self.lost_dirs.insert(
+                user_fd,
+                LostDirData {
+                    layers: self.remote_dirs.holders(fd).collect(),
+                    open_request,
+                    cursor,
+                    buffered_entries: std::mem::take(&mut data.buffered_entries),
+                    pending_requests: Default::default(),
+                },
+            );
+        }
+
+        let mut resumed_requests = HashSet::new();
+
+        for (message_id, layer_id, additional_data) in interrupted_requests {
+            let AdditionalRequestData::ReadDirBuffered { fd } = additional_data else {
+                continue;
+            };
+
+            let Some(user_fd) = user_fds.get(fd).copied() else {
+                continue;
+            };
+            let Some(lost) = self.lost_dirs.get_mut(&user_fd) else {
+                continue;
+            };
+
+            // Recreate the request that was originally sent by the layer.
+            let request = FileRequest::ReadDir(ReadDirRequest { remote_fd: user_fd });
+            lost.pending_requests
+                .push((*message_id, *layer_id, request));
+            resumed_requests.insert((*layer_id, *message_id));
+        }
+
+        resumed_requests
+    }
+
+    /// Sends requests to re-open all [`Self::lost_files`] and [`Self::lost_dirs`].
+    ///
+    /// Called when the [`mirrord_protocol`] version is negotiated with the new mirrord-agent.
+    #[tracing::instrument(level = Level::DEBUG, skip_all)]
+    async fn reopen_lost_files(&mut self, message_bus: &mut MessageBus<Self>) {
+        // The iteration can't be resumed without a cursor.
+        if self.dir_cursors().not() {
+            for (_, lost) in self.lost_dirs.drain() {
+                Self::drop_lost_dir(lost, message_bus).await;
+            }
+        }
+
+        for (user_fd, lost) in &self.lost_dirs {
+            let Some(layer_id) = lost.layers.first().copied() else {
+                continue;
+            };
+
+            // This request is not made by the layer, the message id is never used.
+            self.request_queue.push_back_with_data(
+                0,
+                layer_id,
+                AdditionalRequestData::ReopenDir { user_fd: *user_fd },
+            );
+            self.reconnect_tracker.track_internal_request();
+            message_bus
+                .send_agent(ClientMessage::FileRequest(FileRequest::Open(
+                    lost.open_request.clone(),
+                )))
+                .await;
+        }
+
+        for (user_fd, lost) in &self.lost_files {
+            let Some(layer_id) = lost.layers.first().copied() else {
+                continue;
+            };
+
+            // This request is not made by the layer, the message id is never used.
+            self.request_queue.push_back_with_data(
+                0,
+                layer_id,
+                AdditionalRequestData::Reopen { user_fd: *user_fd },
+            );
+            self.reconnect_tracker.track_internal_request();
+            message_bus
+                .send_agent(ClientMessage::FileRequest(FileRequest::Open(
+                    lost.open_request.clone(),
+                )))
+                .await;
+        }
+    }
+
+    /// Restores a lost file that was successfully re-opened with the current mirrord-agent,
+    /// and handles its pending requests.
+    #[tracing::instrument(level = Level::DEBUG, skip(self, message_bus))]
+    async fn file_reopened(
+        &mut self,
+        user_fd: u64,
+        remote_fd: u64,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        let Some(lost) = self.lost_files.remove(&user_fd) else {
+            // The file was closed before it was re-opened.
+            message_bus
+                .send_agent(ClientMessage::FileRequest(FileRequest::Close(
+                    CloseFileRequest { fd: remote_fd },
+                )))
+                .await;
+            return;
+        };
+
+        self.reconnect_tracker.restore(user_fd, remote_fd);
+        for layer_id in lost.layers {
+            self.remote_files.add(layer_id, remote_fd);
+        }
+        self.buffered_files.insert(
+            remote_fd,
+            BufferedFileData {
+                fd_position: lost.fd_position,
+                buffer_size: self.file_buffer_size(&lost.open_request.path),
+                reopen_request: Some(lost.open_request),
+                ..Default::default()
+            },
+        );
+
+        for (message_id, layer_id, request) in lost.pending_requests {
+            self.layer_request(request, layer_id, message_id, message_bus)
+                .await;
+        }
+    }
+
+    /// Responds with errors to all requests waiting for the given lost file.
+    async fn drop_lost_file(lost: LostFileData, message_bus: &mut MessageBus<Self>) {
+        for (message_id, layer_id, request) in lost.pending_requests {
+            if let Some(response) = request.agent_lost_response(layer_id, message_id) {
+                message_bus.send(ToLayer::from(response)).await;
+            }
+        }
+    }
+
+    /// Opens a lost directory from its file, that was successfully re-opened with the current
+    /// mirrord-agent.
+    #[tracing::instrument(level = Level::DEBUG, skip(self, message_bus))]
+    async fn dir_file_reopened(
+        &mut self,
+        user_fd: u64,
+        file_fd: u64,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        let Some(layer_id) = self
+            .lost_dirs
+            .get(&user_fd)
+            .and_then(|lost| lost.layers.first().copied())
+        else {
+            // The directory was closed before it was re-opened.
+            message_bus
+                .send_agent(ClientMessage::FileRequest(FileRequest::Close(
+                    CloseFileRequest { fd: file_fd },
+                )))
+                .await;
+            return;
+        };
+
+        // This request is not made by the layer, the message id is never used.
+        self.request_queue.push_back_with_data(
+            0,
+            layer_id,
+            AdditionalRequestData::ReopenDirStream { user_fd, file_fd },
+        );
+        self.reconnect_tracker.track_internal_request();
+        message_bus
+            .send_agent(ClientMessage::FileRequest(FileRequest::FdOpenDir(
+                FdOpenDirRequest { remote_fd: file_fd },
+            )))
+            .await;
+    }
+
+    /// Restores a lost directory that was successfully re-opened with the current
+    /// mirrord-agent, and handles its pending requests.
+    ///
+    /// The iteration continues from the [`LostDirData::cursor`].
+    #[tracing::instrument(level = Level::DEBUG, skip(self, message_bus))]
+    async fn dir_reopened(
+        &mut self,
+        user_fd: u64,
+        file_fd: u64,
+        remote_fd: u64,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        // The file was only needed to open the directory.
+        message_bus
+            .send_agent(ClientMessage::FileRequest(FileRequest::Close(
+                CloseFileRequest { fd: file_fd },
+            )))
+            .await;
+
+        let Some(lost) = self.lost_dirs.remove(&user_fd) else {
+            // The directory was closed before it was re-opened.
+            message_bus
+                .send_agent(ClientMessage::FileRequest(FileRequest::CloseDir(
+                    CloseDirRequest { remote_fd },
+                )))
+                .await;
+            return;
+        };
+
+        self.reconnect_tracker.restore(user_fd, remote_fd);
+        for layer_id in lost.layers {
+            self.remote_dirs.add(layer_id, remote_fd);
+        }
+        self.buffered_dirs.insert(
+            remote_fd,
+            BufferedDirData {
+                buffered_entries: lost.buffered_entries,
+                cursor: Some(lost.cursor),
+                reopen_request: Some(lost.open_request),
+            },
+        );
+
+        for (message_id, layer_id, request) in lost.pending_requests {
+            self.layer_request(request, layer_id, message_id, message_bus)
+                .await;
+        }
+    }
+
+    /// Responds with errors to all requests waiting for the given lost directory.
+    async fn drop_lost_dir(lost: LostDirData, message_bus: &mut MessageBus<Self>) {
+        for (message_id, layer_id, request) in lost.pending_requests {
+            if let Some(response) = request.agent_lost_response(layer_id, message_id) {
+                message_bus.send(ToLayer::from(response)).await;
+            }
+        }
+    }
+
+    /// If the given [`FileRequest`] refers to a file from [`Self::lost_files`] or a directory from
+    /// [`Self::lost_dirs`], stores it until the file is re-opened and returns [`None`].
+    ///
+    /// Only requests that are made while reading a file are stored, other requests fail as usual.
+    async fn park_if_lost(
+        &mut self,
+        request: FileRequest,
+        layer_id: LayerId,
+        message_id: MessageId,
+        message_bus: &mut MessageBus<Self>,
+    ) -> Option<FileRequest> {
+        if let FileRequest::ReadDir(ReadDirRequest { remote_fd })
+        | FileRequest::CloseDir(CloseDirRequest { remote_fd }) = &request
+        {
+            let user_fd = *remote_fd;
+            let Some(lost) = self.lost_dirs.get_mut(&user_fd) else {
+                return Some(request);
+            };
+
+            if matches!(request, FileRequest::CloseDir(..)) {
+                lost.layers.retain(|id| *id != layer_id);
+                if lost.layers.is_empty()
+                    && let Some(lost) = self.lost_dirs.remove(&user_fd)
+                {
+                    Self::drop_lost_dir(lost, message_bus).await;
+                }
+            } else {
+                lost.pending_requests.push((message_id, layer_id, request));
+            }
+
+            return None;
+        }
+
+        let user_fd = match &request {
+            FileRequest::Read(ReadFileRequest { remote_fd, .. })
+            | FileRequest::ReadLimited(ReadLimitedFileRequest { remote_fd, .. })
+            | FileRequest::Seek(SeekFileRequest { fd: remote_fd, .. })
+            | FileRequest::Xstat(XstatRequest {
+                fd: Some(remote_fd),
+                ..
+            })
+            | FileRequest::Close(CloseFileRequest { fd: remote_fd }) => *remote_fd,
+            _ => return Some(request),
+        };
+
+        let Some(lost) = self.lost_files.get_mut(&user_fd) else {
+            return Some(request);
+        };
+
+        if matches!(request, FileRequest::Close(..)) {
+            lost.layers.retain(|id| *id != layer_id);
+            if lost.layers.is_empty()
+                && let Some(lost) = self.lost_files.remove(&user_fd)
+            {
+                Self::drop_lost_file(lost, message_bus).await;
+            }
+        } else {
+            lost.pending_requests.push((message_id, layer_id, request));
+        }
+
+        None
+    }
+
+    /// Handles a [`FileRequest`] coming from the layer.
+    async fn layer_request(
+        &mut self,
+        request: FileRequest,
+        layer_id: LayerId,
+        message_id: MessageId,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        if let Some(delay) = self.read_faults.delay()
+            && matches!(
+                request,
+                FileRequest::Read(..) | FileRequest::ReadLimited(..)
+            )
+        {
+            self.delayed_reads
+                .push(delay, (request, layer_id, message_id));
+            return;
+        }
+
+        self.undelayed_layer_request(request, layer_id, message_id, message_bus)
+            .await
+    }
+
+    /// Handles a [`FileRequest`] coming from the layer, after the [`Self::read_faults`] delay.
+    async fn undelayed_layer_request(
+        &mut self,
+        request: FileRequest,
+        layer_id: LayerId,
+        message_id: MessageId,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        let Some(request) = self
+            .park_if_lost(request, layer_id, message_id, message_bus)
+            .await
+        else {
+            return;
+        };
+
+        match self
+            .reconnect_tracker
+            .map_request(layer_id, message_id, request)
+        {
+            Ok(None) => {}
+            Err(response) => {
+                message_bus.send(*response).await;
+            }
+            Ok(Some(request)) => {
+                self.file_request(request, layer_id, message_id, message_bus)
+                    .await
+            }
+        };
+    }
+
     #[tracing::instrument(level = Level::INFO, skip(message_bus), ret)]
     async fn handle_reconnect(
         &mut self,
@@ -1154,6 +2420,16 @@ impl FilesProxy {
     ) {
         match refresh {
             ConnectionRefresh::Start => {
+                // Responses to these requests will never arrive.
+                let interrupted_requests = self.request_queue.drain().collect::<Vec<_>>();
+                let resumed_requests = if self.resume_reads {
+                    let mut resumed_requests = self.collect_lost_dirs(&interrupted_requests);
+                    resumed_requests.extend(self.collect_lost_files(interrupted_requests));
+                    resumed_requests
+                } else {
+                    Default::default()
+                };
+
                 let files_to_drop = self
                     .remote_files
                     .drain()
@@ -1174,7 +2450,10 @@ impl FilesProxy {
                     self.buffered_dirs.remove(&fd);
                 }
 
-                let responses = self.reconnect_tracker.agent_lost();
+                let mut responses = self.reconnect_tracker.agent_lost();
+                responses.retain(|AgentLostFileResponse(layer_id, message_id, _)| {
+                    resumed_requests.contains(&(*layer_id, *message_id)).not()
+                });
                 tracing::debug!(
                     num_responses = responses.len(),
                     "Flushing error responses to file requests"
@@ -1201,22 +2480,23 @@ impl BackgroundTask for FilesProxy {
 
     #[tracing::instrument(level = Level::INFO, name = "files_proxy_main_loop", skip_all, ret, err)]
     async fn run(&mut self, message_bus: &mut MessageBus<Self>) -> Result<(), Self::Error> {
-        while let Some(message) = message_bus.recv().await {
+        loop {
+            let message = tokio::select! {
+                message = message_bus.recv() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+                (request, layer_id, message_id) = self.delayed_reads.next() => {
+                    self.undelayed_layer_request(request, layer_id, message_id, message_bus)
+                        .await;
+                    continue;
+                }
+            };
+
             match message {
                 FilesProxyMessage::FileReq(message_id, layer_id, request) => {
-                    match self
-                        .reconnect_tracker
-                        .map_request(layer_id, message_id, request)
-                    {
-                        Ok(None) => {}
-                        Err(response) => {
-                            message_bus.send(*response).await;
-                        }
-                        Ok(Some(request)) => {
-                            self.file_request(request, layer_id, message_id, message_bus)
-                                .await
-                        }
-                    };
+                    self.layer_request(request, layer_id, message_id, message_bus)
+                        .await
                 }
                 FilesProxyMessage::FileRes(response) => {
                     let response = self.reconnect_tracker.map_response(response);
@@ -1226,7 +2506,10 @@ impl BackgroundTask for FilesProxy {
                     self.layer_closed(closed, message_bus).await;
                 }
                 FilesProxyMessage::LayerForked(forked) => self.layer_forked(forked),
-                FilesProxyMessage::ProtocolVersion(version) => self.protocol_version(version),
+                FilesProxyMessage::ProtocolVersion(version) => {
+                    self.protocol_version(version);
+                    self.reopen_lost_files(message_bus).await;
+                }
                 FilesProxyMessage::ConnectionRefresh(refresh) => {
                     self.handle_reconnect(message_bus, refresh).await
                 }
@@ -1241,16 +2524,26 @@ impl BackgroundTask for FilesProxy {
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
+    use std::{
+        path::PathBuf,
+        time::{Duration, Instant},
+    };
 
+    use mirrord_config::{
+        experimental::FaultConfig,
+        feature::fs::{FsConfig, ReadonlyFileBufferOverride, ReadonlyFileBuffers},
+    };
     use mirrord_intproxy_protocol::{LayerId, ProxyToLayerMessage};
     use mirrord_protocol::{
         ClientMessage, ErrorKindInternal, FileRequest, FileResponse, RemoteIOError, ResponseError,
         file::{
-            FdOpenDirRequest, OpenDirResponse, OpenFileRequest, OpenFileResponse,
-            OpenOptionsInternal, ReadDirBatchRequest, ReadDirBatchResponse, ReadDirRequest,
+            AccessFileRequestV2, AccessFileResponse, AccessFlagsInternal, CloseFileRequest,
+            CopyFileRangeRequest, DirCursor, DirEntryInternal, FdOpenDirRequest, OpenDirResponse,
+            OpenFileRequest, OpenFileRequestV2, OpenFileRequestV3, OpenFileResponse,
+            OpenFlagsInternal, OpenOptionsInternal, ReadDirBatchFromRequest,
+            ReadDirBatchFromResponse, ReadDirBatchRequest, ReadDirBatchResponse, ReadDirRequest,
             ReadDirResponse, ReadFileRequest, ReadFileResponse, ReadLimitedFileRequest,
-            SeekFileRequest, SeekFileResponse, SeekFromInternal,
+            ReadvFileRequest, SeekFileRequest, SeekFileResponse, SeekFromInternal,
         },
     };
     use mirrord_protocol_io::{Client, Connection, ConnectionOutput};
@@ -1262,7 +2555,8 @@ mod tests {
     use crate::{
         background_tasks::{BackgroundTasks, TaskSender, TaskUpdate},
         error::ProxyRuntimeError,
-        main_tasks::{MainTaskId, ProxyMessage, ToLayer},
+        fault_injection::FaultInjector,
+        main_tasks::{ConnectionRefresh, MainTaskId, ProxyMessage, ToLayer},
     };
 
     #[derive(Debug, PartialEq)]
@@ -1299,6 +2593,25 @@ mod tests {
         TaskSender<FilesProxy>,
         BackgroundTasks<MainTaskId, ProxyMessage, ProxyRuntimeError>,
         ConnectionOutput<Client>,
+    ) {
+        setup_proxy_with_faults(
+            protocol_version,
+            ReadonlyFileBuffers::new(file_buffer_size),
+            Default::default(),
+        )
+        .await
+    }
+
+    /// Like [`setup_proxy`], but the proxy uses the given `file_buffers` and injects the given
+    /// `read_faults` into file reads.
+    async fn setup_proxy_with_faults(
+        protocol_version: Version,
+        file_buffers: ReadonlyFileBuffers,
+        read_faults: FaultInjector,
+    ) -> (
+        TaskSender<FilesProxy>,
+        BackgroundTasks<MainTaskId, ProxyMessage, ProxyRuntimeError>,
+        ConnectionOutput<Client>,
     ) {
         let (connection, _, out) = Connection::dummy();
 
@@ -1306,7 +2619,7 @@ mod tests {
             BackgroundTasks::new(connection.tx_handle());
 
         let proxy = tasks.register(
-            FilesProxy::new(file_buffer_size),
+            FilesProxy::new(file_buffers, false, read_faults),
             MainTaskId::FilesProxy,
             32,
         );
@@ -1419,48 +2732,300 @@ mod tests {
 
         let request = FileRequest::ReadDir(ReadDirRequest { remote_fd: 0xdad });
         proxy
-            .send(FilesProxyMessage::FileReq(0xbad, LayerId(0xa55), request))
+            .send(FilesProxyMessage::FileReq(0xbad, LayerId(0xa55), request))
+            .await;
+        let update = out.next().await;
+
+        assert!(
+            matches!(
+                update,
+                Some(ClientMessage::FileRequest(FileRequest::ReadDirBatch(
+                    ReadDirBatchRequest {
+                        remote_fd: 0xdad,
+                        amount: FilesProxy::READDIR_BATCH_SIZE,
+                    }
+                )))
+            ),
+            "Mismatched message for `ReadDirBatchRequest` {update:?}!"
+        );
+
+        let response = FileResponse::ReadDirBatch(Ok(ReadDirBatchResponse {
+            fd: 0xdad,
+            dir_entries: Vec::new(),
+        }));
+        proxy.send(FilesProxyMessage::FileRes(response)).await;
+        let (_, update) = tasks.next().await.unzip();
+
+        assert!(
+            matches!(
+                update,
+                Some(TaskUpdate::Message(ProxyMessage::ToLayer(ToLayer {
+                    message_id: 0xbad,
+                    layer_id: LayerId(0xa55),
+                    message: ProxyToLayerMessage::File(FileResponse::ReadDir(Ok(
+                        ReadDirResponse { .. }
+                    )))
+                })))
+            ),
+            "Mismatched message for `ReadDirBatchResponse` {update:?}!"
+        );
+
+        drop(proxy);
+        let results = tasks.results().await;
+        for (_, result) in results {
+            assert!(result.is_ok(), "{result:?}");
+        }
+    }
+
+    /// [`FileRequest::OpenV2`] reaches agents that support [`OPEN_FLAGS_VERSION`], and is
+    /// downgraded to [`FileRequest::Open`] for older ones.
+    #[rstest]
+    #[case::old_protocol(Version::new(1, 38, 0), false)]
+    #[case::new_protocol(Version::new(1, 39, 0), true)]
+    #[tokio::test]
+    async fn open_flags_version_gated(#[case] version: Version, #[case] supported: bool) {
+        let (proxy, mut tasks, out) = setup_proxy(version, 0).await;
+
+        let request = OpenFileRequestV2 {
+            path: PathBuf::from("/some/dir"),
+            open_options: OpenOptionsInternal {
+                read: true,
+                ..Default::default()
+            },
+            flags: OpenFlagsInternal {
+                directory: true,
+                path: true,
+                ..Default::default()
+            },
+        };
+        proxy
+            .send(FilesProxyMessage::FileReq(
+                0xbad,
+                LayerId(0xa55),
+                FileRequest::OpenV2(request.clone()),
+            ))
+            .await;
+
+        let expected = if supported {
+            FileRequest::OpenV2(request)
+        } else {
+            FileRequest::Open(request.into())
+        };
+        assert_eq!(
+            out.next().await.unwrap(),
+            ClientMessage::FileRequest(expected)
+        );
+
+        let response = FileResponse::Open(Ok(OpenFileResponse { fd: 0xdad }));
+        proxy
+            .send(FilesProxyMessage::FileRes(response.clone()))
+            .await;
+        let update = tasks.next().await.unwrap().1.unwrap_message();
+        assert_eq!(
+            update,
+            ProxyMessage::ToLayer(ToLayer {
+                message_id: 0xbad,
+                layer_id: LayerId(0xa55),
+                message: ProxyToLayerMessage::File(response),
+            })
+        );
+    }
+
+    /// [`FileRequest::OpenV3`] reaches agents that support
+    /// [`OPEN_NONBLOCK_VERSION`](super::OPEN_NONBLOCK_VERSION), and is downgraded to
+    /// [`FileRequest::OpenV2`] or [`FileRequest::Open`] for older ones.
+    #[rstest]
+    #[case::no_open_flags(Version::new(1, 38, 0))]
+    #[case::no_nonblock(Version::new(1, 46, 0))]
+    #[case::nonblock(Version::new(1, 47, 0))]
+    #[tokio::test]
+    async fn open_nonblock_version_gated(#[case] version: Version) {
+        let (proxy, _tasks, out) = setup_proxy(version.clone(), 0).await;
+
+        let request = OpenFileRequestV3 {
+            path: PathBuf::from("/tmp/fifo"),
+            open_options: OpenOptionsInternal {
+                read: true,
+                ..Default::default()
+            },
+            flags: OpenFlagsInternal {
+                nofollow: true,
+                ..Default::default()
+            },
+            nonblock: true,
+        };
+        proxy
+            .send(FilesProxyMessage::FileReq(
+                0xbad,
+                LayerId(0xa55),
+                FileRequest::OpenV3(request.clone()),
+            ))
+            .await;
+
+        let expected = if super::OPEN_NONBLOCK_VERSION.matches(&version) {
+            FileRequest::OpenV3(request)
+        } else if super::OPEN_FLAGS_VERSION.matches(&version) {
+            FileRequest::OpenV2(request.into())
+        } else {
+            FileRequest::Open(OpenFileRequestV2::from(request).into())
+        };
+        assert_eq!(
+            out.next().await.unwrap(),
+            ClientMessage::FileRequest(expected)
+        );
+    }
+
+    /// [`FileRequest::AccessV2`] reaches agents that support
+    /// [`ACCESS_FLAGS_VERSION`](super::ACCESS_FLAGS_VERSION), and is downgraded to
+    /// [`FileRequest::Access`] for older ones.
+    #[rstest]
+    #[case::old_protocol(Version::new(1, 42, 0), false)]
+    #[case::new_protocol(Version::new(1, 43, 0), true)]
+    #[tokio::test]
+    async fn access_flags_version_gated(#[case] version: Version, #[case] supported: bool) {
+        let (proxy, mut tasks, out) = setup_proxy(version, 0).await;
+
+        let request = AccessFileRequestV2 {
+            pathname: PathBuf::from("/app/test.txt"),
+            mode: 2,
+            flags: AccessFlagsInternal {
+                effective_ids: true,
+                nofollow: false,
+            },
+        };
+        proxy
+            .send(FilesProxyMessage::FileReq(
+                0xbad,
+                LayerId(0xa55),
+                FileRequest::AccessV2(request.clone()),
+            ))
+            .await;
+
+        let expected = if supported {
+            FileRequest::AccessV2(request)
+        } else {
+            FileRequest::Access(request.into())
+        };
+        assert_eq!(
+            out.next().await.unwrap(),
+            ClientMessage::FileRequest(expected)
+        );
+
+        let response = FileResponse::Access(Ok(AccessFileResponse));
+        proxy
+            .send(FilesProxyMessage::FileRes(response.clone()))
+            .await;
+        let update = tasks.next().await.unwrap().1.unwrap_message();
+        assert_eq!(
+            update,
+            ProxyMessage::ToLayer(ToLayer {
+                message_id: 0xbad,
+                layer_id: LayerId(0xa55),
+                message: ProxyToLayerMessage::File(response),
+            })
+        );
+    }
+
+    /// [`FileRequest::Readv`] reaches agents that support [`IOV_VERSION`](super::IOV_VERSION),
+    /// unless the file is buffered. Otherwise the layer gets [`ResponseError::NotImplemented`] and
+    /// falls back to a plain read.
+    #[rstest]
+    #[case::old_protocol(Version::new(1, 40, 0), 0, false)]
+    #[case::new_protocol(Version::new(1, 41, 0), 0, true)]
+    #[case::buffered_file(Version::new(1, 41, 0), 4096, false)]
+    #[tokio::test]
+    async fn readv_gated(
+        #[case] version: Version,
+        #[case] file_buffer_size: u64,
+        #[case] forwarded: bool,
+    ) {
+        let (proxy, mut tasks, out) = setup_proxy(version, file_buffer_size).await;
+        let fd = open_file(&proxy, &mut tasks, &out, true).await;
+
+        let request = FileRequest::Readv(ReadvFileRequest {
+            fd,
+            iov_lengths: vec![4, 8],
+        });
+        proxy
+            .send(FilesProxyMessage::FileReq(
+                0xbad,
+                LayerId(0),
+                request.clone(),
+            ))
+            .await;
+
+        let update = select! {
+            a = out.next() => Either::Left(a.unwrap()),
+            b = tasks.next() => Either::Right(b.unwrap().1.unwrap_message()),
+        };
+        if forwarded {
+            assert_eq!(update.unwrap_left(), ClientMessage::FileRequest(request));
+        } else {
+            assert_eq!(
+                update.unwrap_right(),
+                ProxyMessage::ToLayer(ToLayer {
+                    message_id: 0xbad,
+                    layer_id: LayerId(0),
+                    message: ProxyToLayerMessage::File(FileResponse::Readv(Err(
+                        ResponseError::NotImplemented
+                    ))),
+                })
+            );
+        }
+    }
+
+    /// [`FileRequest::CopyFileRange`] reaches agents that support
+    /// [`COPY_FILE_RANGE_VERSION`](super::COPY_FILE_RANGE_VERSION), unless it would read from the
+    /// position of a buffered file. Otherwise the layer gets [`ResponseError::NotImplemented`] and
+    /// falls back to plain reads and writes.
+    #[rstest]
+    #[case::old_protocol(Version::new(1, 43, 0), 0, None, false)]
+    #[case::new_protocol(Version::new(1, 44, 0), 0, None, true)]
+    #[case::buffered_file(Version::new(1, 44, 0), 4096, None, false)]
+    #[case::buffered_file_offset(Version::new(1, 44, 0), 4096, Some(8), true)]
+    #[tokio::test]
+    async fn copy_file_range_gated(
+        #[case] version: Version,
+        #[case] file_buffer_size: u64,
+        #[case] off_in: Option<u64>,
+        #[case] forwarded: bool,
+    ) {
+        let (proxy, mut tasks, out) = setup_proxy(version, file_buffer_size).await;
+        let fd_in = open_file_at(&proxy, &mut tasks, &out, "/some/input", true).await;
+        let fd_out = open_file_at(&proxy, &mut tasks, &out, "/some/output", false).await;
+
+        let request = FileRequest::CopyFileRange(CopyFileRangeRequest {
+            fd_in,
+            off_in,
+            fd_out,
+            off_out: None,
+            len: 64,
+        });
+        proxy
+            .send(FilesProxyMessage::FileReq(
+                0xbad,
+                LayerId(0),
+                request.clone(),
+            ))
             .await;
-        let update = out.next().await;
-
-        assert!(
-            matches!(
-                update,
-                Some(ClientMessage::FileRequest(FileRequest::ReadDirBatch(
-                    ReadDirBatchRequest {
-                        remote_fd: 0xdad,
-                        amount: FilesProxy::READDIR_BATCH_SIZE,
-                    }
-                )))
-            ),
-            "Mismatched message for `ReadDirBatchRequest` {update:?}!"
-        );
 
-        let response = FileResponse::ReadDirBatch(Ok(ReadDirBatchResponse {
-            fd: 0xdad,
-            dir_entries: Vec::new(),
-        }));
-        proxy.send(FilesProxyMessage::FileRes(response)).await;
-        let (_, update) = tasks.next().await.unzip();
-
-        assert!(
-            matches!(
-                update,
-                Some(TaskUpdate::Message(ProxyMessage::ToLayer(ToLayer {
+        let update = select! {
+            a = out.next() => Either::Left(a.unwrap()),
+            b = tasks.next() => Either::Right(b.unwrap().1.unwrap_message()),
+        };
+        if forwarded {
+            assert_eq!(update.unwrap_left(), ClientMessage::FileRequest(request));
+        } else {
+            assert_eq!(
+                update.unwrap_right(),
+                ProxyMessage::ToLayer(ToLayer {
                     message_id: 0xbad,
-                    layer_id: LayerId(0xa55),
-                    message: ProxyToLayerMessage::File(FileResponse::ReadDir(Ok(
-                        ReadDirResponse { .. }
-                    )))
-                })))
-            ),
-            "Mismatched message for `ReadDirBatchResponse` {update:?}!"
-        );
-
-        drop(proxy);
-        let results = tasks.results().await;
-        for (_, result) in results {
-            assert!(result.is_ok(), "{result:?}");
+                    layer_id: LayerId(0),
+                    message: ProxyToLayerMessage::File(FileResponse::CopyFileRange(Err(
+                        ResponseError::NotImplemented
+                    ))),
+                })
+            );
         }
     }
 
@@ -1470,12 +3035,22 @@ mod tests {
         tasks: &mut BackgroundTasks<MainTaskId, ProxyMessage, ProxyRuntimeError>,
         out: &ConnectionOutput<Client>,
         readonly: bool,
+    ) -> u64 {
+        open_file_at(proxy, tasks, out, "/some/path", readonly).await
+    }
+
+    async fn open_file_at(
+        proxy: &TaskSender<FilesProxy>,
+        tasks: &mut BackgroundTasks<MainTaskId, ProxyMessage, ProxyRuntimeError>,
+        out: &ConnectionOutput<Client>,
+        path: &str,
+        readonly: bool,
     ) -> u64 {
         let message_id = rand::random();
         let fd = rand::random();
 
         let request = FileRequest::Open(OpenFileRequest {
-            path: PathBuf::from("/some/path"),
+            path: PathBuf::from(path),
             open_options: OpenOptionsInternal {
                 read: true,
                 write: !readonly,
@@ -1643,6 +3218,140 @@ mod tests {
         );
     }
 
+    /// Verifies that file reads fail without reaching the agent, when
+    /// `experimental.fault_injection.file_read` is configured to always fail.
+    #[rstest]
+    #[case::read(None)]
+    #[case::read_limited(Some(0))]
+    #[tokio::test]
+    async fn read_fault_injected(#[case] start_from: Option<u64>) {
+        let faults = FaultInjector::new(&FaultConfig {
+            delay: 0,
+            failure_percent: 100,
+        });
+        let (proxy, mut tasks, out) = setup_proxy_with_faults(
+            mirrord_protocol::VERSION.clone(),
+            ReadonlyFileBuffers::new(4096),
+            faults,
+        )
+        .await;
+
+        let fd = open_file(&proxy, &mut tasks, &out, true).await;
+
+        let update = make_read_request(&proxy, &mut tasks, &out, fd, 10, start_from)
+            .await
+            .unwrap_right()
+            .unwrap_proxy_to_layer_message();
+        let error = Err(FaultInjector::file_read_error());
+        let expected = if start_from.is_some() {
+            FileResponse::ReadLimited(error)
+        } else {
+            FileResponse::Read(error)
+        };
+        assert_eq!(update, ProxyToLayerMessage::File(expected));
+    }
+
+    /// Verifies that a read delayed by `experimental.fault_injection.file_read` doesn't hold back
+    /// other requests.
+    #[tokio::test]
+    async fn read_fault_delay_does_not_block_proxy() {
+        let faults = FaultInjector::new(&FaultConfig {
+            delay: 200,
+            failure_percent: 100,
+        });
+        let (proxy, mut tasks, out) = setup_proxy_with_faults(
+            mirrord_protocol::VERSION.clone(),
+            ReadonlyFileBuffers::new(0),
+            faults,
+        )
+        .await;
+
+        let fd = open_file(&proxy, &mut tasks, &out, false).await;
+
+        let start = Instant::now();
+        proxy
+            .send(FilesProxyMessage::FileReq(
+                0,
+                LayerId(0),
+                FileRequest::Read(ReadFileRequest {
+                    remote_fd: fd,
+                    buffer_size: 10,
+                }),
+            ))
+            .await;
+
+        open_file_at(&proxy, &mut tasks, &out, "/other/path", false).await;
+        assert!(start.elapsed() < Duration::from_millis(200));
+
+        let update = tasks.next().await.unwrap().1.unwrap_message();
+        assert!(start.elapsed() >= Duration::from_millis(200));
+        assert_eq!(
+            update,
+            ProxyMessage::ToLayer(ToLayer {
+                message_id: 0,
+                layer_id: LayerId(0),
+                message: ProxyToLayerMessage::File(FileResponse::Read(Err(
+                    FaultInjector::file_read_error()
+                ))),
+            })
+        );
+    }
+
+    /// The buffer size of a file is picked from the first override that matches its path, and
+    /// files matching an override of 0 are not buffered.
+    #[rstest]
+    #[case::default("/data/file.txt", Some(4096))]
+    #[case::bigger("/data/file.csv", Some(8192))]
+    #[case::first_match("/data/archive/file.csv", Some(2048))]
+    #[case::disabled("/data/file.PARQUET", None)]
+    #[tokio::test]
+    async fn per_file_buffer_size(#[case] path: &str, #[case] expected_buffer: Option<u64>) {
+        let file_buffers = FsConfig {
+            readonly_file_buffer: 4096,
+            readonly_file_buffer_overrides: Some(vec![
+                ReadonlyFileBufferOverride {
+                    pattern: r"\.parquet$".to_string(),
+                    size: 0,
+                },
+                ReadonlyFileBufferOverride {
+                    pattern: "^/data/archive/".to_string(),
+                    size: 2048,
+                },
+                ReadonlyFileBufferOverride {
+                    pattern: r"\.csv$".to_string(),
+                    size: 8192,
+                },
+            ]),
+            ..Default::default()
+        }
+        .readonly_file_buffers()
+        .unwrap();
+        let (proxy, mut tasks, out) = setup_proxy_with_faults(
+            mirrord_protocol::VERSION.clone(),
+            file_buffers,
+            Default::default(),
+        )
+        .await;
+
+        let fd = open_file_at(&proxy, &mut tasks, &out, path, true).await;
+
+        let update = make_read_request(&proxy, &mut tasks, &out, fd, 1, None)
+            .await
+            .unwrap_left();
+        let expected = match expected_buffer {
+            Some(buffer_size) => FileRequest::ReadLimited(ReadLimitedFileRequest {
+                remote_fd: fd,
+                buffer_size,
+                start_from: 0,
+            }),
+            None => FileRequest::Read(ReadFileRequest {
+                remote_fd: fd,
+                buffer_size: 1,
+            }),
+        };
+        assert_eq!(update, ClientMessage::FileRequest(expected));
+    }
+
     #[tokio::test]
     async fn reading_from_buffered_file() {
         let (proxy, mut tasks, out) = setup_proxy(mirrord_protocol::VERSION.clone(), 4096).await;
@@ -1772,6 +3481,57 @@ mod tests {
         );
     }
 
+    /// A read-only file that turns out not to be seekable (a FIFO) is no longer buffered, and the
+    /// read is retried as a plain [`FileRequest::Read`].
+    #[tokio::test]
+    async fn reading_from_unseekable_file() {
+        let (proxy, mut tasks, out) = setup_proxy(mirrord_protocol::VERSION.clone(), 4096).await;
+
+        let fd = open_file(&proxy, &mut tasks, &out, true).await;
+
+        let update = make_read_request(&proxy, &mut tasks, &out, fd, 10, None)
+            .await
+            .unwrap_left();
+        assert_eq!(
+            update,
+            ClientMessage::FileRequest(FileRequest::ReadLimited(ReadLimitedFileRequest {
+                remote_fd: fd,
+                buffer_size: 4096,
+                start_from: 0,
+            })),
+        );
+
+        proxy
+            .send(FilesProxyMessage::FileRes(FileResponse::ReadLimited(Err(
+                ResponseError::RemoteIO(RemoteIOError {
+                    raw_os_error: Some(29),
+                    kind: ErrorKindInternal::NotSeekable,
+                }),
+            ))))
+            .await;
+        let read_request = ClientMessage::FileRequest(FileRequest::Read(ReadFileRequest {
+            remote_fd: fd,
+            buffer_size: 10,
+        }));
+        assert_eq!(out.next().await.unwrap(), read_request);
+
+        let update = respond_to_read_request(&proxy, &mut tasks, b"hello".to_vec(), false)
+            .await
+            .unwrap_proxy_to_layer_message();
+        assert_eq!(
+            update,
+            ProxyToLayerMessage::File(FileResponse::Read(Ok(ReadFileResponse {
+                bytes: b"hello".to_vec().into(),
+                read_amount: 5,
+            }))),
+        );
+
+        let update = make_read_request(&proxy, &mut tasks, &out, fd, 10, None)
+            .await
+            .unwrap_left();
+        assert_eq!(update, read_request);
+    }
+
     #[tokio::test]
     async fn seeking_in_buffered_file() {
         let (proxy, mut tasks, out) = setup_proxy(mirrord_protocol::VERSION.clone(), 4096).await;
@@ -1904,4 +3664,199 @@ mod tests {
             ProxyToLayerMessage::File(FileResponse::Read(Err(res_error))),
         );
     }
+
+    fn dir_entry(position: u64) -> DirEntryInternal {
+        DirEntryInternal {
+            inode: position,
+            position,
+            name: format!("entry-{position}"),
+            file_type: Default::default(),
+        }
+    }
+
+    /// With `resume_reads` enabled, a buffered directory lost with the agent is re-opened after a
+    /// reconnect, and the iteration continues from its [`DirCursor`].
+    #[tokio::test]
+    async fn resume_dir_iteration_from_cursor() {
+        let (connection, _, out) = Connection::dummy();
+        let mut tasks: BackgroundTasks<MainTaskId, ProxyMessage, ProxyRuntimeError> =
+            BackgroundTasks::new(connection.tx_handle());
+        let proxy = tasks.register(
+            FilesProxy::new(ReadonlyFileBuffers::new(4096), true, Default::default()),
+            MainTaskId::FilesProxy,
+            32,
+        );
+        proxy
+            .send(FilesProxyMessage::ProtocolVersion(
+                mirrord_protocol::VERSION.clone(),
+            ))
+            .await;
+
+        let open_request = OpenFileRequest {
+            path: PathBuf::from("/some/dir"),
+            open_options: OpenOptionsInternal {
+                read: true,
+                ..Default::default()
+            },
+        };
+        proxy
+            .send(FilesProxyMessage::FileReq(
+                0,
+                LayerId(0),
+                FileRequest::Open(open_request.clone()),
+            ))
+            .await;
+        assert_eq!(
+            out.next().await.unwrap(),
+            ClientMessage::FileRequest(FileRequest::Open(open_request.clone())),
+        );
+        proxy
+            .send(FilesProxyMessage::FileRes(FileResponse::Open(Ok(
+                OpenFileResponse { fd: 1 },
+            ))))
+            .await;
+        tasks.next().await.unwrap().1.unwrap_message();
+
+        let open_dir = FileRequest::FdOpenDir(FdOpenDirRequest { remote_fd: 1 });
+        proxy
+            .send(FilesProxyMessage::FileReq(1, LayerId(0), open_dir.clone()))
+            .await;
+        assert_eq!(
+            out.next().await.unwrap(),
+            ClientMessage::FileRequest(open_dir)
+        );
+        proxy
+            .send(FilesProxyMessage::FileRes(FileResponse::OpenDir(Ok(
+                OpenDirResponse { fd: 2 },
+            ))))
+            .await;
+        tasks.next().await.unwrap().1.unwrap_message();
+
+        // First batch, the proxy buffers the second entry.
+        let read_dir = FileRequest::ReadDir(ReadDirRequest { remote_fd: 2 });
+        proxy
+            .send(FilesProxyMessage::FileReq(2, LayerId(0), read_dir.clone()))
+            .await;
+        assert_eq!(
+            out.next().await.unwrap(),
+            ClientMessage::FileRequest(FileRequest::ReadDirBatchFrom(ReadDirBatchFromRequest {
+                remote_fd: 2,
+                amount: FilesProxy::READDIR_BATCH_SIZE,
+                cursor: DirCursor::default(),
+            })),
+        );
+        proxy
+            .send(FilesProxyMessage::FileRes(FileResponse::ReadDirBatchFrom(
+                Ok(ReadDirBatchFromResponse {
+                    fd: 2,
+                    dir_entries: vec![dir_entry(0), dir_entry(1)],
+                    cursor: DirCursor(2),
+                }),
+            )))
+            .await;
+        assert_eq!(
+            tasks.next().await.unwrap().1.unwrap_message(),
+            ProxyMessage::ToLayer(ToLayer {
+                message_id: 2,
+                layer_id: LayerId(0),
+                message: ProxyToLayerMessage::File(FileResponse::ReadDir(Ok(ReadDirResponse {
+                    direntry: Some(dir_entry(0)),
+                }))),
+            }),
+        );
+
+        proxy
+            .send(FilesProxyMessage::ConnectionRefresh(
+                ConnectionRefresh::Start,
+            ))
+            .await;
+        proxy
+            .send(FilesProxyMessage::ConnectionRefresh(
+                ConnectionRefresh::End(connection.tx_handle()),
+            ))
+            .await;
+        proxy
+            .send(FilesProxyMessage::ProtocolVersion(
+                mirrord_protocol::VERSION.clone(),
+            ))
+            .await;
+
+        // The directory is re-opened from a new file, and the buffered file is re-opened as well.
+        for _ in 0..2 {
+            assert_eq!(
+                out.next().await.unwrap(),
+                ClientMessage::FileRequest(FileRequest::Open(open_request.clone())),
+            );
+        }
+        proxy
+            .send(FilesProxyMessage::FileRes(FileResponse::Open(Ok(
+                OpenFileResponse { fd: 5 },
+            ))))
+            .await;
+        assert_eq!(
+            out.next().await.unwrap(),
+            ClientMessage::FileRequest(FileRequest::FdOpenDir(FdOpenDirRequest { remote_fd: 5 })),
+        );
+        proxy
+            .send(FilesProxyMessage::FileRes(FileResponse::Open(Ok(
+                OpenFileResponse { fd: 6 },
+            ))))
+            .await;
+        proxy
+            .send(FilesProxyMessage::FileRes(FileResponse::OpenDir(Ok(
+                OpenDirResponse { fd: 7 },
+            ))))
+            .await;
+        assert_eq!(
+            out.next().await.unwrap(),
+            ClientMessage::FileRequest(FileRequest::Close(CloseFileRequest { fd: 5 })),
+        );
+
+        // The buffered entry survives the reconnect.
+        proxy
+            .send(FilesProxyMessage::FileReq(3, LayerId(0), read_dir.clone()))
+            .await;
+        assert_eq!(
+            tasks.next().await.unwrap().1.unwrap_message(),
+            ProxyMessage::ToLayer(ToLayer {
+                message_id: 3,
+                layer_id: LayerId(0),
+                message: ProxyToLayerMessage::File(FileResponse::ReadDir(Ok(ReadDirResponse {
+                    direntry: Some(dir_entry(1)),
+                }))),
+            }),
+        );
+
+        // The next batch is read with the re-opened directory, from the cursor.
+        proxy
+            .send(FilesProxyMessage::FileReq(4, LayerId(0), read_dir))
+            .await;
+        assert_eq!(
+            out.next().await.unwrap(),
+            ClientMessage::FileRequest(FileRequest::ReadDirBatchFrom(ReadDirBatchFromRequest {
+                remote_fd: 7,
+                amount: FilesProxy::READDIR_BATCH_SIZE,
+                cursor: DirCursor(2),
+            })),
+        );
+        proxy
+            .send(FilesProxyMessage::FileRes(FileResponse::ReadDirBatchFrom(
+                Ok(ReadDirBatchFromResponse {
+                    fd: 7,
+                    dir_entries: vec![dir_entry(2)],
+                    cursor: DirCursor(3),
+                }),
+            )))
+            .await;
+        assert_eq!(
+            tasks.next().await.unwrap().1.unwrap_message(),
+            ProxyMessage::ToLayer(ToLayer {
+                message_id: 4,
+                layer_id: LayerId(0),
+                message: ProxyToLayerMessage::File(FileResponse::ReadDir(Ok(ReadDirResponse {
+                    direntry: Some(dir_entry(2)),
+                }))),
+            }),
+        );
+    }
 }
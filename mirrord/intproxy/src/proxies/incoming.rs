@@ -11,6 +11,7 @@ use std::{
     io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
     ops::Not,
+    path::PathBuf,
     sync::Arc,
     time::Duration,
 };
@@ -20,18 +21,21 @@ use futures::future::Either;
 use http::{ClientStore, ResponseMode, StreamingBody};
 use http_gateway::HttpGatewayTask;
 use metadata_store::MetadataStore;
-use mirrord_config::feature::network::incoming::tls_delivery::LocalTlsDelivery;
+use mirrord_config::feature::network::incoming::{
+    OnLocalUnavailable, SourceIpDelivery, auto_downgrade::AutoDowngradeConfig,
+    tls_delivery::LocalTlsDelivery,
+};
 use mirrord_intproxy_protocol::{
     ConnMetadataRequest, ConnMetadataResponse, IncomingRequest, IncomingResponse, LayerId,
     MessageId, PortSubscription, ProxyToLayerMessage,
 };
 use mirrord_protocol::{
-    ClientMessage, ConnectionId, RequestId, ResponseError,
+    ClientMessage, ConnectionId, LogMessage, RequestId, ResponseError,
     tcp::{
         ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestErrorV1, ChunkedRequestErrorV2,
         DaemonTcp, HttpRequest, HttpRequestMetadata, IncomingTrafficTransportType,
         InternalHttpBodyFrame, InternalHttpRequest, LayerTcp, LayerTcpSteal, NewTcpConnectionV1,
-        NewTcpConnectionV2,
+        NewTcpConnectionV2, NewUnixConnection, STEAL_UNIX_SOCKET_VERSION, SourceCidr,
     },
 };
 use semver::Version;
@@ -39,29 +43,41 @@ use tasks::{HttpGatewayId, HttpOut, InProxyTask, InProxyTaskError, InProxyTaskMe
 use tcp_proxy::{LocalTcpConnection, TcpProxyTask};
 use thiserror::Error;
 use tls::LocalTlsSetup;
-use tokio::sync::mpsc;
+use tokio::{sync::mpsc, time::Instant};
 use tracing::Level;
+use unavailable::LocalUnavailable;
+use unix_proxy::UnixProxyTask;
 
-use self::subscriptions::SubscriptionsManager;
+pub(crate) use self::auto_downgrade::ResumeStealSignal;
+use self::{
+    auto_downgrade::AutoDowngrade, port_subscription_ext::StealMode,
+    subscriptions::SubscriptionsManager,
+};
 use crate::{
     ProxyMessage,
     background_tasks::{
         BackgroundTask, BackgroundTasks, MessageBus, TaskError, TaskSender, TaskUpdate,
     },
     main_tasks::{ConnectionRefresh, LayerClosed, LayerForked, ToLayer},
+    metrics,
+    session_metadata::{SessionMetadata, StealDowngrade},
 };
 
+mod auto_downgrade;
 mod bound_socket;
 pub mod http;
 mod http_gateway;
 mod metadata_store;
 mod port_subscription_ext;
+mod source_ip;
 mod subscriptions;
 pub mod tasks;
 mod tcp_proxy;
 #[cfg(test)]
 mod tests;
 pub mod tls;
+mod unavailable;
+mod unix_proxy;
 
 /// Maps IDs of remote connections to `T`.
 ///
@@ -104,6 +120,13 @@ pub enum IncomingProxyError {
 
     #[error("HTTP method filter is not supported for this protocol version {0:?}!")]
     HttpMethodFilterNotSupported(Option<Version>),
+
+    #[error(
+        "stealing only from the addresses in `feature.network.incoming.source_filter` is not \
+        supported by the agent's mirrord-protocol version {0}, update the agent or remove the \
+        filter"
+    )]
+    SourceFilterNotSupported(Version),
 }
 
 /// Messages consumed by [`IncomingProxy`] running as a [`BackgroundTask`].
@@ -117,6 +140,8 @@ pub enum IncomingProxyMessage {
     /// Agent responded to [`ClientMessage::SwitchProtocolVersion`].
     AgentProtocolVersion(semver::Version),
     ConnectionRefresh(ConnectionRefresh),
+    /// The user asked to resume stealing after it was downgraded by the [`AutoDowngrade`].
+    ResumeSteal,
 }
 
 /// Handle to a running [`HttpGatewayTask`].
@@ -164,6 +189,8 @@ struct HttpGatewayHandle {
 /// 3. We never send [`LayerTcpSteal::ConnectionUnsubscribe`] (due to requests being handled
 ///    independently). If a request fails locally, we send a
 ///    [`StatusCode::BAD_GATEWAY`](hyper::http::StatusCode::BAD_GATEWAY) response.
+/// 4. If the user application is not listening on the port, the stolen request is handled according
+///    to the configured [`OnLocalUnavailable`] (this applies to the stolen connections as well).
 ///
 /// We are notified about mirrored/stolen requests with the [`HttpRequest`] messages.
 ///
@@ -179,6 +206,20 @@ struct HttpGatewayHandle {
 /// A mirrored/stolen HTTP request can result in an HTTP upgrade.
 /// When this happens, the TCP connection is recovered and passed to a new [`TcpProxyTask`].
 /// The TCP connection is then treated as mirrored/stolen in whole.
+///
+/// # Connections stolen from unix sockets
+///
+/// Each such connection is handled by a single [`UnixProxyTask`], that connects to the user
+/// application's socket. We ask the agent to steal from the configured sockets each time the
+/// [`mirrord_protocol`] version is negotiated, so the subscriptions survive reconnects.
+///
+/// # Automatic downgrade
+///
+/// When configured with [`AutoDowngradeConfig`], too many stolen connections and requests failing
+/// to be delivered to the user application make us switch all `steal` subscriptions to another
+/// [`StealMode`] (mirror or nothing at all). Stealing is restored with
+/// [`IncomingProxyMessage::ResumeSteal`]. The downgrade is recorded in the [`SessionMetadata`],
+/// if [`IncomingProxy::with_session_metadata`] was used.
 pub struct IncomingProxy {
     /// Active port subscriptions for all layers.
     subscriptions: SubscriptionsManager,
@@ -188,8 +229,23 @@ pub struct IncomingProxy {
     response_mode: ResponseMode,
     /// Cache for [`LocalHttpClient`](http::LocalHttpClient)s.
     client_store: ClientStore,
+    /// Cache for [`LocalHttpClient`](http::LocalHttpClient)s delivering stolen requests, which
+    /// connect from [`Self::delivery_source`].
+    steal_client_store: ClientStore,
     /// For connecting to the user application's server with TLS.
     tls_setup: Option<Arc<LocalTlsSetup>>,
+    /// What we do with stolen traffic when the user application is not listening.
+    local_unavailable: LocalUnavailable,
+    /// How we pass the original client address of stolen traffic to the user application.
+    source_ip_delivery: SourceIpDelivery,
+    /// Local address that stolen traffic is delivered to the user application from.
+    delivery_source: Option<IpAddr>,
+    /// Source address ranges that we steal from, empty means all sources.
+    source_filter: Vec<SourceCidr>,
+    /// Downgrades stealing when the user application fails to handle the stolen traffic.
+    auto_downgrade: Option<AutoDowngrade>,
+    /// File where we record the downgrades of stealing.
+    session_metadata: Option<PathBuf>,
     /// Each mirrored/stolen remote connection is mapped to a [`TcpProxyTask`].
     ///
     /// Each entry here maps to a connection that is in progress both locally and remotely.
@@ -198,6 +254,11 @@ pub struct IncomingProxy {
     ///
     /// Each entry here maps to a request that is in progress both locally and remotely.
     http_gateways: ConnectionMap<HashMap<RequestId, HttpGatewayHandle>>,
+    /// Remote unix socket paths to steal from, mapped to the local paths of the user
+    /// application's sockets.
+    unix_sockets: HashMap<PathBuf, PathBuf>,
+    /// Each remote connection stolen from a unix socket is mapped to a [`UnixProxyTask`].
+    unix_proxies: HashMap<ConnectionId, TaskSender<UnixProxyTask>>,
     /// Running [`BackgroundTask`]s utilized by this proxy.
     tasks: Option<BackgroundTasks<InProxyTask, InProxyTaskMessage, InProxyTaskError>>,
 
@@ -214,25 +275,65 @@ impl IncomingProxy {
     pub fn new(
         idle_local_http_connection_timeout: Duration,
         https_delivery: LocalTlsDelivery,
+        on_local_unavailable: OnLocalUnavailable,
+        source_ip_delivery: SourceIpDelivery,
+        delivery_source: Option<IpAddr>,
+        source_filter: Vec<SourceCidr>,
+        auto_downgrade: Option<AutoDowngradeConfig>,
+        unix_sockets: HashMap<PathBuf, PathBuf>,
     ) -> Self {
         let tls_setup = LocalTlsSetup::from_config(https_delivery);
+        let client_store =
+            ClientStore::new_with_timeout(idle_local_http_connection_timeout, tls_setup.clone());
+        let steal_client_store = match delivery_source {
+            Some(..) => {
+                ClientStore::new_with_timeout(idle_local_http_connection_timeout, tls_setup.clone())
+                    .with_delivery_source(delivery_source)
+            }
+            None => client_store.clone(),
+        };
+
         Self {
             subscriptions: Default::default(),
             metadata_store: Default::default(),
             response_mode: Default::default(),
-            client_store: ClientStore::new_with_timeout(
-                idle_local_http_connection_timeout,
-                tls_setup.clone(),
-            ),
+            client_store,
+            steal_client_store,
             tls_setup,
+            local_unavailable: LocalUnavailable::new(on_local_unavailable),
+            source_ip_delivery,
+            delivery_source,
+            source_filter,
+            auto_downgrade: auto_downgrade.map(AutoDowngrade::new),
+            session_metadata: None,
             tcp_proxies: Default::default(),
             http_gateways: Default::default(),
+            unix_sockets,
+            unix_proxies: Default::default(),
             tasks: None,
             protocol_version: None,
             restore_subscriptions_on_protocol_version_switch: false,
         }
     }
 
+    /// Records the downgrades of stealing in the [`SessionMetadata`] stored at the given `path`.
+    pub fn with_session_metadata(mut self, path: PathBuf) -> Self {
+        self.session_metadata = Some(path);
+        self
+    }
+
+    /// Records the current [`StealDowngrade`] in the [`SessionMetadata`], if enabled.
+    fn record_steal_downgrade(&self, steal_downgrade: Option<StealDowngrade>) {
+        let Some(path) = self.session_metadata.as_deref() else {
+            return;
+        };
+
+        let metadata = SessionMetadata { steal_downgrade };
+        if let Err(error) = metadata.store(path) {
+            tracing::warn!(%error, ?path, "Failed to record the steal downgrade");
+        }
+    }
+
     /// Starts a new [`HttpGatewayTask`] to handle the given request.
     ///
     /// If we don't have a [`PortSubscription`] for the port, the task is not started.
@@ -257,10 +358,11 @@ impl IncomingProxy {
             "Received an HTTP request from the agent",
         );
 
+        let steal_mode = self.subscriptions.steal_mode();
         let subscription = self.subscriptions.get(request.port).filter(|subscription| {
             match &subscription.subscription {
                 PortSubscription::Mirror(..) => is_steal.not(),
-                PortSubscription::Steal(..) => is_steal,
+                PortSubscription::Steal(..) => steal_mode.accepts(is_steal),
             }
         });
         let Some(subscription) = subscription else {
@@ -301,10 +403,15 @@ impl IncomingProxy {
         let tx = self.tasks.as_mut().unwrap().register(
             HttpGatewayTask::new(
                 request,
-                self.client_store.clone(),
+                if is_steal {
+                    self.steal_client_store.clone()
+                } else {
+                    self.client_store.clone()
+                },
                 is_steal.then_some(self.response_mode),
                 server_addr,
                 transport,
+                self.local_unavailable.clone(),
             ),
             if is_steal {
                 InProxyTask::StealHttpGateway(id)
@@ -343,12 +450,13 @@ impl IncomingProxy {
             transport,
         } = connection;
 
+        let steal_mode = self.subscriptions.steal_mode();
         let subscription = self
             .subscriptions
             .get(destination_port)
             .filter(|subscription| match &subscription.subscription {
                 PortSubscription::Mirror(..) => is_steal.not(),
-                PortSubscription::Steal(..) => is_steal,
+                PortSubscription::Steal(..) => steal_mode.accepts(is_steal),
             });
         let Some(subscription) = subscription else {
             tracing::debug!(
@@ -368,10 +476,20 @@ impl IncomingProxy {
             return Ok(());
         };
 
-        let socket = BoundTcpSocket::bind_specified_or_localhost(subscription.listening_on.ip())
-            .map_err(IncomingProxyError::SocketSetupFailed)?;
+        let socket = BoundTcpSocket::bind_source_or_specified(
+            self.delivery_source.filter(|_| is_steal),
+            subscription.listening_on.ip(),
+        )
+        .map_err(IncomingProxyError::SocketSetupFailed)?;
 
         let peer_address = normalize_connection_address(subscription.listening_on);
+        let proxy_header = (is_steal && self.source_ip_delivery == SourceIpDelivery::ProxyProtocol)
+            .then(|| {
+                source_ip::proxy_protocol_v2_header(
+                    SocketAddr::new(remote_address, source_port),
+                    SocketAddr::new(local_address, destination_port),
+                )
+            });
 
         self.metadata_store.expect(
             ConnMetadataRequest {
@@ -384,6 +502,7 @@ impl IncomingProxy {
             ConnMetadataResponse {
                 remote_source: SocketAddr::new(remote_address, source_port),
                 local_address,
+                destination_port,
             },
         );
 
@@ -400,6 +519,9 @@ impl IncomingProxy {
                     peer: peer_address,
                     transport,
                     tls_setup: self.tls_setup.clone(),
+                    proxy_header,
+                    hold: (is_steal && self.local_unavailable.mode() == OnLocalUnavailable::Hold)
+                        .then(|| self.local_unavailable.hold()),
                 },
                 is_steal.not(),
             ),
@@ -412,6 +534,44 @@ impl IncomingProxy {
         Ok(())
     }
 
+    /// Handles [`NewUnixConnection`] message from the agent, starting a new [`UnixProxyTask`].
+    ///
+    /// If we don't steal from the socket, the task is not started.
+    /// Instead, we respond immediately to the agent.
+    #[tracing::instrument(level = Level::TRACE, skip(self, message_bus))]
+    async fn handle_new_unix_connection(
+        &mut self,
+        connection: NewUnixConnection,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        let NewUnixConnection {
+            connection_id,
+            path,
+        } = connection;
+
+        let Some(local_path) = self.unix_sockets.get(&path) else {
+            tracing::debug!(
+                ?path,
+                connection_id,
+                "Received a new connection from a unix socket we don't steal from, \
+                sending an unsubscribe request.",
+            );
+            message_bus
+                .send_agent(ClientMessage::TcpSteal(
+                    LayerTcpSteal::ConnectionUnsubscribe(connection_id),
+                ))
+                .await;
+            return;
+        };
+
+        let tx = self.tasks.as_mut().unwrap().register(
+            UnixProxyTask::new(connection_id, local_path.clone()),
+            InProxyTask::StealUnixProxy(connection_id),
+            Self::CHANNEL_SIZE,
+        );
+        self.unix_proxies.insert(connection_id, tx);
+    }
+
     /// Handles [`ChunkedRequest`] message from the agent.
     async fn handle_chunked_request(
         &mut self,
@@ -446,8 +606,11 @@ impl IncomingProxy {
 
                 let transport = request.transport;
 
-                let HttpRequestMetadata::V1 { destination, .. } = request.metadata;
-                let request = HttpRequest {
+                let HttpRequestMetadata::V1 {
+                    source,
+                    destination,
+                } = request.metadata;
+                let mut request = HttpRequest {
                     connection_id: request.connection_id,
                     request_id: request.request_id,
                     internal_request: InternalHttpRequest {
@@ -460,6 +623,13 @@ impl IncomingProxy {
                     port: destination.port(),
                 };
 
+                if is_steal && self.source_ip_delivery == SourceIpDelivery::XForwardedFor {
+                    source_ip::append_x_forwarded_for(
+                        &mut request.internal_request.headers,
+                        source.ip(),
+                    );
+                }
+
                 self.start_http_gateway(request, body_tx, transport, is_steal, message_bus)
                     .await;
             }
@@ -572,13 +742,21 @@ impl IncomingProxy {
                 self.http_gateways
                     .get_mut(is_steal)
                     .remove(&close.connection_id);
+                if is_steal {
+                    self.unix_proxies.remove(&close.connection_id);
+                }
             }
 
             DaemonTcp::Data(data) => {
                 let tx = self.tcp_proxies.get(is_steal).get(&data.connection_id);
+                let unix_tx = is_steal
+                    .then(|| self.unix_proxies.get(&data.connection_id))
+                    .flatten();
 
                 if let Some(tx) = tx {
                     tx.send(data.bytes.into_vec()).await;
+                } else if let Some(tx) = unix_tx {
+                    tx.send(data.bytes.into_vec()).await;
                 } else {
                     tracing::debug!(
                         connection_id = data.connection_id,
@@ -640,11 +818,67 @@ impl IncomingProxy {
                     message_bus.send(msg).await;
                 }
             }
+
+            DaemonTcp::UnixSubscribeResult(Ok(path)) => {
+                tracing::info!(?path, "Stealing connections from the remote unix socket");
+            }
+
+            DaemonTcp::UnixSubscribeResult(Err(error)) => {
+                tracing::warn!(%error, "Failed to steal connections from a remote unix socket");
+                message_bus
+                    .send(ProxyMessage::WarnLayers(format!(
+                        "Failed to steal connections from a remote unix socket \
+                        (`feature.network.incoming.unix_sockets`): {error}"
+                    )))
+                    .await;
+            }
+
+            DaemonTcp::NewUnixConnection(connection) if is_steal => {
+                self.handle_new_unix_connection(connection, message_bus)
+                    .await;
+            }
+
+            DaemonTcp::NewUnixConnection(connection) => {
+                tracing::debug!(
+                    ?connection,
+                    "Received a mirrored unix socket connection, ignoring",
+                );
+            }
         }
 
         Ok(())
     }
 
+    /// Asks the agent to steal connections from [`Self::unix_sockets`], if it supports it.
+    async fn subscribe_unix_sockets(&self, message_bus: &mut MessageBus<Self>) {
+        if self.unix_sockets.is_empty() {
+            return;
+        }
+
+        if self
+            .protocol_version
+            .as_ref()
+            .is_none_or(|version| STEAL_UNIX_SOCKET_VERSION.matches(version).not())
+        {
+            let message = "The agent does not support stealing from unix sockets, \
+                `feature.network.incoming.unix_sockets` is ignored. \
+                Consider upgrading the mirrord agent image (or the operator).";
+            tracing::warn!(protocol_version = ?self.protocol_version, "{message}");
+            message_bus
+                .send(ProxyMessage::WarnLayers(message.to_string()))
+                .await;
+            return;
+        }
+
+        for path in self.unix_sockets.keys() {
+            message_bus
+                .send_agent(ClientMessage::TcpSteal(LayerTcpSteal::UnixSubscribe(
+                    path.clone(),
+                )))
+                .await;
+        }
+    }
+
     /// Handles all messages from this task's [`MessageBus`].
     #[tracing::instrument(level = Level::TRACE, skip(self, message_bus), ret, err)]
     async fn handle_message(
@@ -660,7 +894,9 @@ impl IncomingProxy {
                         message_id,
                         subscribe,
                         self.protocol_version.as_ref(),
-                    );
+                        &self.source_filter,
+                    )?;
+                    self.local_unavailable.listener_added();
                     match msg {
                         Some(Either::Left(m)) => message_bus.send(m).await,
                         Some(Either::Right(m)) => message_bus.send_agent(m).await,
@@ -717,14 +953,18 @@ impl IncomingProxy {
                     for subscription in self.subscriptions.iter_mut() {
                         tracing::info!(?subscription, "Resubscribing after connection refresh");
 
-                        message_bus
-                            .send_agent(
-                                subscription.resubscribe_message(self.protocol_version.as_ref()),
-                            )
-                            .await
+                        let message = subscription.resubscribe_message(
+                            self.protocol_version.as_ref(),
+                            &self.source_filter,
+                        )?;
+                        if let Some(message) = message {
+                            message_bus.send_agent(message).await;
+                        }
                     }
                     self.restore_subscriptions_on_protocol_version_switch = false;
                 }
+
+                self.subscribe_unix_sockets(message_bus).await;
             }
 
             IncomingProxyMessage::ConnectionRefresh(refresh) => {
@@ -734,6 +974,7 @@ impl IncomingProxy {
                         self.tcp_proxies.steal.clear();
                         self.http_gateways.mirror.clear();
                         self.http_gateways.steal.clear();
+                        self.unix_proxies.clear();
                         self.tasks.as_mut().unwrap().clear();
 
                         // Reset protocol version since we'll need another negotiation
@@ -751,25 +992,123 @@ impl IncomingProxy {
                     ConnectionRefresh::Request => {}
                 }
             }
+
+            IncomingProxyMessage::ResumeSteal => self.resume_steal(message_bus).await?,
+        }
+
+        Ok(())
+    }
+
+    /// Records a stolen connection or request that failed to be delivered to the user
+    /// application, and downgrades stealing if there were too many of them.
+    async fn delivery_failed(
+        &mut self,
+        message_bus: &mut MessageBus<Self>,
+    ) -> Result<(), IncomingProxyError> {
+        if self.subscriptions.steal_mode() != StealMode::Steal {
+            return Ok(());
+        }
+
+        let Some(auto_downgrade) = self.auto_downgrade.as_mut() else {
+            return Ok(());
+        };
+
+        if auto_downgrade.record_failure(Instant::now()).not() {
+            return Ok(());
+        }
+
+        auto_downgrade.reset();
+        let steal_mode = auto_downgrade.downgraded_mode();
+        let config = auto_downgrade.config();
+
+        let pid = std::process::id();
+        let message = format!(
+            "Stopped stealing incoming traffic, more than {} stolen connections or requests failed \
+            to reach the local application within {} seconds. {} \
+            Run `mirrord session resume-steal` (or `kill -USR1 {pid}`) to resume stealing.",
+            config.errors,
+            config.window_secs,
+            if steal_mode == StealMode::Mirror {
+                "The traffic is mirrored instead."
+            } else {
+                "The traffic is no longer intercepted."
+            },
+        );
+        tracing::warn!(?steal_mode, "{message}");
+        metrics::STEAL_DOWNGRADED.set(1);
+        metrics::STEAL_DOWNGRADES.inc();
+        self.record_steal_downgrade(Some(StealDowngrade::now(steal_mode == StealMode::Mirror)));
+
+        for layer_id in self.subscriptions.steal_layers() {
+            message_bus
+                .send(ToLayer {
+                    message_id: 0,
+                    layer_id,
+                    message: ProxyToLayerMessage::LogMessage(LogMessage::warn(message.clone())),
+                })
+                .await;
+        }
+
+        let messages = self.subscriptions.set_steal_mode(
+            steal_mode,
+            self.protocol_version.as_ref(),
+            &self.source_filter,
+        )?;
+        for message in messages {
+            message_bus.send_agent(message).await;
+        }
+
+        Ok(())
+    }
+
+    /// Restores stealing after it was downgraded, see [`IncomingProxyMessage::ResumeSteal`].
+    async fn resume_steal(
+        &mut self,
+        message_bus: &mut MessageBus<Self>,
+    ) -> Result<(), IncomingProxyError> {
+        if self.subscriptions.steal_mode() == StealMode::Steal {
+            tracing::info!("Stealing was not downgraded, nothing to resume");
+            return Ok(());
+        }
+
+        tracing::warn!("Resuming stealing incoming traffic");
+        metrics::STEAL_DOWNGRADED.set(0);
+        self.record_steal_downgrade(None);
+
+        if let Some(auto_downgrade) = self.auto_downgrade.as_mut() {
+            auto_downgrade.reset();
+        }
+
+        let messages = self.subscriptions.set_steal_mode(
+            StealMode::Steal,
+            self.protocol_version.as_ref(),
+            &self.source_filter,
+        )?;
+        for message in messages {
+            message_bus.send_agent(message).await;
         }
 
         Ok(())
     }
 
     /// Handles all updates from [`TcpProxyTask`]s.
-    #[tracing::instrument(level = Level::TRACE, skip(self, message_bus), ret)]
+    #[tracing::instrument(level = Level::TRACE, skip(self, message_bus), ret, err)]
     async fn handle_tcp_proxy_update(
         &mut self,
         connection_id: ConnectionId,
         is_steal: bool,
         update: TaskUpdate<InProxyTaskMessage, InProxyTaskError>,
         message_bus: &mut MessageBus<Self>,
-    ) {
+    ) -> Result<(), IncomingProxyError> {
         match update {
             TaskUpdate::Finished(result) => {
                 match result {
                     Err(TaskError::Error(error)) => {
                         tracing::warn!(connection_id, %error, is_steal, "TcpProxyTask failed");
+
+                        if is_steal {
+                            self.delivery_failed(message_bus).await?;
+                        }
                     }
                     Err(TaskError::Panic) => {
                         tracing::error!(connection_id, is_steal, "TcpProxyTask task panicked");
@@ -807,17 +1146,54 @@ impl IncomingProxy {
                 unreachable!("TcpProxyTask does not produce HTTP messages")
             }
         }
+
+        Ok(())
     }
 
-    /// Handles all updates from [`HttpGatewayTask`]s.
+    /// Handles all updates from [`UnixProxyTask`]s.
     #[tracing::instrument(level = Level::TRACE, skip(self, message_bus), ret)]
+    async fn handle_unix_proxy_update(
+        &mut self,
+        connection_id: ConnectionId,
+        update: TaskUpdate<InProxyTaskMessage, InProxyTaskError>,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        match update {
+            TaskUpdate::Finished(result) => {
+                match result {
+                    Err(TaskError::Error(error)) => {
+                        tracing::warn!(connection_id, %error, "UnixProxyTask failed");
+                    }
+                    Err(TaskError::Panic) => {
+                        tracing::error!(connection_id, "UnixProxyTask task panicked");
+                    }
+                    Ok(()) => {}
+                };
+
+                if self.unix_proxies.remove(&connection_id).is_some() {
+                    message_bus
+                        .send_agent(ClientMessage::TcpSteal(
+                            LayerTcpSteal::ConnectionUnsubscribe(connection_id),
+                        ))
+                        .await;
+                }
+            }
+
+            TaskUpdate::Message(..) => {
+                unreachable!("UnixProxyTask does not produce messages")
+            }
+        }
+    }
+
+    /// Handles all updates from [`HttpGatewayTask`]s.
+    #[tracing::instrument(level = Level::TRACE, skip(self, message_bus), ret, err)]
     async fn handle_http_gateway_update(
         &mut self,
         id: HttpGatewayId,
         is_steal: bool,
         update: TaskUpdate<InProxyTaskMessage, InProxyTaskError>,
         message_bus: &mut MessageBus<Self>,
-    ) {
+    ) -> Result<(), IncomingProxyError> {
         match update {
             TaskUpdate::Finished(result) => {
                 let respond_on_panic = self
@@ -866,7 +1242,7 @@ impl IncomingProxy {
                     .and_then(|gateways| gateways.get(&id.request_id))
                     .is_some();
                 if !exists {
-                    return;
+                    return Ok(());
                 }
 
                 match message {
@@ -889,9 +1265,13 @@ impl IncomingProxy {
                             .get_mut(is_steal)
                             .insert(id.connection_id, proxy);
                     }
+
+                    HttpOut::DeliveryFailed => self.delivery_failed(message_bus).await?,
                 }
             }
         }
+
+        Ok(())
     }
 }
 
@@ -919,16 +1299,21 @@ impl BackgroundTask for IncomingProxy {
 
                 Some((id, update)) = self.tasks.as_mut().unwrap().next() => match id {
                     InProxyTask::MirrorTcpProxy(connection_id) => {
-                        self.handle_tcp_proxy_update(connection_id, false, update, message_bus).await;
+                        self.handle_tcp_proxy_update(connection_id, false, update, message_bus)
+                            .await?;
                     }
                     InProxyTask::StealTcpProxy(connection_id) => {
-                        self.handle_tcp_proxy_update(connection_id, true, update, message_bus).await;
+                        self.handle_tcp_proxy_update(connection_id, true, update, message_bus)
+                            .await?;
                     }
                     InProxyTask::MirrorHttpGateway(id) => {
-                        self.handle_http_gateway_update(id, false, update, message_bus).await;
+                        self.handle_http_gateway_update(id, false, update, message_bus).await?;
                     }
                     InProxyTask::StealHttpGateway(id) => {
-                        self.handle_http_gateway_update(id, true, update, message_bus).await;
+                        self.handle_http_gateway_update(id, true, update, message_bus).await?;
+                    }
+                    InProxyTask::StealUnixProxy(connection_id) => {
+                        self.handle_unix_proxy_update(connection_id, update, message_bus).await;
                     }
                 },
             }
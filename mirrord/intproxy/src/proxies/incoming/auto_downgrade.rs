@@ -0,0 +1,146 @@
+//! Tracking of stolen traffic that fails to be delivered to the user application, see
+//! [`AutoDowngradeConfig`].
+
+use std::{collections::VecDeque, time::Duration};
+
+use mirrord_config::feature::network::incoming::auto_downgrade::AutoDowngradeConfig;
+use tokio::time::Instant;
+
+use super::port_subscription_ext::StealMode;
+
+/// Decides when stealing should be downgraded, based on the failed deliveries in a sliding window.
+#[derive(Debug)]
+pub struct AutoDowngrade {
+    config: AutoDowngradeConfig,
+    /// When the failures in the current window happened, oldest first.
+    failures: VecDeque<Instant>,
+}
+
+impl AutoDowngrade {
+    pub fn new(config: AutoDowngradeConfig) -> Self {
+        Self {
+            config,
+            failures: Default::default(),
+        }
+    }
+
+    pub fn config(&self) -> &AutoDowngradeConfig {
+        &self.config
+    }
+
+    /// The [`StealMode`] that stealing is downgraded to.
+    pub fn downgraded_mode(&self) -> StealMode {
+        if self.config.mirror {
+            StealMode::Mirror
+        } else {
+            StealMode::Off
+        }
+    }
+
+    /// Records a failed delivery that happened at `now`.
+    ///
+    /// Returns whether stealing should be downgraded, which is when more than
+    /// [`AutoDowngradeConfig::errors`] failures happened within the window.
+    pub fn record_failure(&mut self, now: Instant) -> bool {
+        let window = Duration::from_secs(self.config.window_secs);
+        while let Some(oldest) = self.failures.front()
+            && now.duration_since(*oldest) >= window
+        {
+            self.failures.pop_front();
+        }
+
+        self.failures.push_back(now);
+
+        self.failures.len() > self.config.errors as usize
+    }
+
+    /// Forgets all recorded failures, called when stealing is downgraded or resumed.
+    pub fn reset(&mut self) {
+        self.failures.clear();
+    }
+}
+
+/// Listens for the `SIGUSR1` signal, with which the user resumes stealing after it was downgraded.
+pub(crate) struct ResumeStealSignal {
+    #[cfg(unix)]
+    signal: Option<tokio::signal::unix::Signal>,
+}
+
+impl ResumeStealSignal {
+    /// Starts listening only when `enabled`, so that the default `SIGUSR1` handling is kept when
+    /// [`AutoDowngradeConfig`] is not used.
+    pub(crate) fn new(enabled: bool) -> Self {
+        #[cfg(unix)]
+        {
+            let signal = enabled
+                .then(|| {
+                    tokio::signal::unix::signal(tokio::signal::unix::SignalKind::user_defined1())
+                })
+                .transpose()
+                .inspect_err(|error| {
+                    tracing::warn!(%error, "Failed to listen for the SIGUSR1 signal");
+                })
+                .ok()
+                .flatten();
+
+            Self { signal }
+        }
+
+        #[cfg(not(unix))]
+        {
+            let _ = enabled;
+            Self {}
+        }
+    }
+
+    /// Resolves when the signal is received, never if we're not listening for it.
+    pub(crate) async fn recv(&mut self) {
+        #[cfg(unix)]
+        if let Some(signal) = self.signal.as_mut()
+            && signal.recv().await.is_some()
+        {
+            return;
+        }
+
+        std::future::pending().await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn config(errors: u32, window_secs: u64) -> AutoDowngradeConfig {
+        AutoDowngradeConfig {
+            errors,
+            window_secs,
+            mirror: true,
+        }
+    }
+
+    #[test]
+    fn downgrades_after_threshold() {
+        let mut auto_downgrade = AutoDowngrade::new(config(3, 10));
+        let start = Instant::now();
+
+        for i in 0..3 {
+            assert!(!auto_downgrade.record_failure(start + Duration::from_secs(i)));
+        }
+        assert!(auto_downgrade.record_failure(start + Duration::from_secs(3)));
+
+        auto_downgrade.reset();
+        assert!(!auto_downgrade.record_failure(start + Duration::from_secs(4)));
+    }
+
+    #[test]
+    fn old_failures_leave_the_window() {
+        let mut auto_downgrade = AutoDowngrade::new(config(2, 10));
+        let start = Instant::now();
+
+        assert!(!auto_downgrade.record_failure(start));
+        assert!(!auto_downgrade.record_failure(start + Duration::from_secs(5)));
+        // The first failure is out of the window now.
+        assert!(!auto_downgrade.record_failure(start + Duration::from_secs(10)));
+        assert!(auto_downgrade.record_failure(start + Duration::from_secs(11)));
+    }
+}
@@ -28,6 +28,37 @@ impl BoundTcpSocket {
         Ok(Self(socket))
     }
 
+    /// Opens a new TCP socket for connecting to the user application listening on `peer`.
+    ///
+    /// Binds the socket to `source` and a random port, if `source` is given and has the same IP
+    /// family as `peer`. Otherwise, behaves like [`Self::bind_specified_or_localhost`] with
+    /// `peer`.
+    pub fn bind_source_or_specified(source: Option<IpAddr>, peer: IpAddr) -> io::Result<Self> {
+        match source {
+            Some(source) if source.is_ipv4() == peer.is_ipv4() => {
+                Self::bind_specified_or_localhost(source)
+            }
+            _ => Self::bind_specified_or_localhost(peer),
+        }
+    }
+
+    /// Opens a new TCP socket and binds it to the given address, allowing the address to be
+    /// reused.
+    ///
+    /// Used to retry a failed connection from the same address.
+    #[tracing::instrument(level = Level::TRACE, ret, err)]
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        let socket = match addr {
+            SocketAddr::V4(..) => TcpSocket::new_v4()?,
+            SocketAddr::V6(..) => TcpSocket::new_v6()?,
+        };
+
+        socket.set_reuseaddr(true)?;
+        socket.bind(addr)?;
+
+        Ok(Self(socket))
+    }
+
     /// Returns the address to which this socket is bound.
     pub fn local_addr(&self) -> io::Result<SocketAddr> {
         self.0.local_addr()
@@ -4,6 +4,7 @@ use hyper::{
     Request, Response, StatusCode, Version,
     body::Incoming,
     client::conn::{http1, http2},
+    header::{self, HeaderValue},
 };
 use hyper_util::rt::{TokioExecutor, TokioIo};
 use mirrord_protocol::{
@@ -121,6 +122,14 @@ impl LocalHttpError {
             .not(),
         }
     }
+
+    /// Returns whether this error means that the user application is not listening on the port.
+    pub fn is_local_unavailable(&self) -> bool {
+        matches!(
+            self,
+            Self::ConnectTcpFailed(error) if error.kind() == io::ErrorKind::ConnectionRefused
+        )
+    }
 }
 
 /// Produces a mirrord-specific [`StatusCode::BAD_GATEWAY`] response.
@@ -130,6 +139,52 @@ pub fn mirrord_error_response<M: fmt::Display>(
     connection_id: ConnectionId,
     request_id: RequestId,
     port: Port,
+) -> HttpResponse<Payload> {
+    mirrord_response(
+        StatusCode::BAD_GATEWAY,
+        message,
+        version,
+        connection_id,
+        request_id,
+        port,
+    )
+}
+
+/// Produces a mirrord-specific [`StatusCode::SERVICE_UNAVAILABLE`] response, sent when the user
+/// application is not listening on the port.
+///
+/// The response has a `Retry-After` header of [`UNAVAILABLE_RETRY_AFTER_SECS`].
+pub fn mirrord_unavailable_response(
+    version: Version,
+    connection_id: ConnectionId,
+    request_id: RequestId,
+    port: Port,
+) -> HttpResponse<Payload> {
+    let mut response = mirrord_response(
+        StatusCode::SERVICE_UNAVAILABLE,
+        format_args!("local application is not listening on port {port}"),
+        version,
+        connection_id,
+        request_id,
+        port,
+    );
+    response.internal_response.headers.insert(
+        header::RETRY_AFTER,
+        HeaderValue::from(UNAVAILABLE_RETRY_AFTER_SECS),
+    );
+    response
+}
+
+/// Value of the `Retry-After` header in [`mirrord_unavailable_response`].
+pub const UNAVAILABLE_RETRY_AFTER_SECS: u64 = 1;
+
+fn mirrord_response<M: fmt::Display>(
+    status: StatusCode,
+    message: M,
+    version: Version,
+    connection_id: ConnectionId,
+    request_id: RequestId,
+    port: Port,
 ) -> HttpResponse<Payload> {
     let body = format!(
         "mirrord-intproxy v{}: {message}\n",
@@ -142,7 +197,7 @@ pub fn mirrord_error_response<M: fmt::Display>(
         port,
         request_id,
         internal_response: InternalHttpResponse {
-            status: StatusCode::BAD_GATEWAY,
+            status,
             version,
             headers: Default::default(),
             body,
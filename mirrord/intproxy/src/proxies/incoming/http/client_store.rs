@@ -1,6 +1,6 @@
 use std::{
     cmp, fmt,
-    net::SocketAddr,
+    net::{IpAddr, SocketAddr},
     sync::{Arc, Mutex},
     time::Duration,
 };
@@ -19,7 +19,7 @@ use tokio_rustls::TlsStream;
 use tracing::Level;
 
 use super::{HttpSender, LocalHttpClient, LocalHttpError};
-use crate::proxies::incoming::tls::LocalTlsSetup;
+use crate::proxies::incoming::{bound_socket::BoundTcpSocket, tls::LocalTlsSetup};
 
 /// Idle [`LocalHttpClient`] caches in [`ClientStore`].
 struct IdleLocalClient {
@@ -60,6 +60,9 @@ impl fmt::Debug for IdleLocalClient {
 pub struct ClientStore {
     clients: Arc<Mutex<Vec<IdleLocalClient>>>,
     tls_setup: Option<Arc<LocalTlsSetup>>,
+    /// Local address that new clients connect from, see
+    /// [`BoundTcpSocket::bind_source_or_specified`].
+    delivery_source: Option<IpAddr>,
     /// Used to notify other tasks when there is a new client in the store.
     ///
     /// Make sure to only call [`Notify::notify_waiters`] and [`Notify::notified`] when holding a
@@ -76,6 +79,7 @@ impl ClientStore {
             clients: Default::default(),
             notify: Default::default(),
             tls_setup,
+            delivery_source: None,
         };
 
         // Only spawn cleanup task if connection pooling is enabled
@@ -86,6 +90,14 @@ impl ClientStore {
         store
     }
 
+    /// Makes the new clients connect from the given local address.
+    pub fn with_delivery_source(self, delivery_source: Option<IpAddr>) -> Self {
+        Self {
+            delivery_source,
+            ..self
+        }
+    }
+
     /// Determines whether connection pooling should be enabled.
     ///
     /// On Windows, connection pooling was previously disabled due to "channel closed" errors
@@ -280,9 +292,16 @@ impl ClientStore {
 
         let uses_tls = connector_and_name.is_some();
 
-        let stream = TcpStream::connect(local_server_address)
-            .await
-            .map_err(LocalHttpError::ConnectTcpFailed)?;
+        let stream = match self.delivery_source {
+            Some(source) => {
+                BoundTcpSocket::bind_source_or_specified(Some(source), local_server_address.ip())
+                    .map_err(LocalHttpError::SocketSetupFailed)?
+                    .connect(local_server_address)
+                    .await
+            }
+            None => TcpStream::connect(local_server_address).await,
+        }
+        .map_err(LocalHttpError::ConnectTcpFailed)?;
         let address = stream
             .local_addr()
             .map_err(LocalHttpError::SocketSetupFailed)?;
@@ -10,6 +10,7 @@ use std::{
 
 use http_body_util::BodyExt;
 use hyper::{StatusCode, body::Incoming, http::response::Parts};
+use mirrord_config::feature::network::incoming::OnLocalUnavailable;
 use mirrord_protocol::{
     ClientMessage, Payload,
     batched_body::BatchedBody,
@@ -24,8 +25,12 @@ use tokio_retry::strategy::ExponentialBackoff;
 use tracing::Level;
 
 use super::{
-    http::{ClientStore, LocalHttpError, ResponseMode, StreamingBody, mirrord_error_response},
+    http::{
+        ClientStore, LocalHttpError, ResponseMode, StreamingBody, mirrord_error_response,
+        mirrord_unavailable_response,
+    },
     tasks::{HttpOut, InProxyTaskMessage},
+    unavailable::{HoldRetry, LocalUnavailable},
 };
 use crate::background_tasks::{BackgroundTask, MessageBus};
 
@@ -47,6 +52,10 @@ pub struct HttpGatewayTask {
     server_addr: SocketAddr,
     /// How to transport the HTTP request to the server.
     transport: IncomingTrafficTransportType,
+    /// What we do when the server is not listening.
+    ///
+    /// Ignored if this is a mirrored request.
+    local_unavailable: LocalUnavailable,
 }
 
 impl fmt::Debug for HttpGatewayTask {
@@ -56,6 +65,7 @@ impl fmt::Debug for HttpGatewayTask {
             .field("response_mode", &self.response_mode)
             .field("server_addr", &self.server_addr)
             .field("transport", &self.transport)
+            .field("on_local_unavailable", &self.local_unavailable.mode())
             .finish()
     }
 }
@@ -68,6 +78,7 @@ impl HttpGatewayTask {
         response_mode: Option<ResponseMode>,
         server_addr: SocketAddr,
         transport: IncomingTrafficTransportType,
+        local_unavailable: LocalUnavailable,
     ) -> Self {
         Self {
             request,
@@ -75,6 +86,7 @@ impl HttpGatewayTask {
             response_mode,
             server_addr,
             transport,
+            local_unavailable,
         }
     }
 
@@ -363,6 +375,10 @@ impl BackgroundTask for HttpGatewayTask {
 
         let closed_token = message_bus.closed_token().clone();
 
+        // Started on the first failed attempt, if we're configured with
+        // [`OnLocalUnavailable::Hold`].
+        let mut hold: Option<HoldRetry> = None;
+
         let mut attempt = 0;
         let error = loop {
             attempt += 1;
@@ -381,6 +397,61 @@ impl BackgroundTask for HttpGatewayTask {
             match send_result {
                 None | Some(Ok(())) => return Ok(()),
                 Some(Err(error)) => {
+                    if self.response_mode.is_some() && error.is_local_unavailable() {
+                        match self.local_unavailable.mode() {
+                            OnLocalUnavailable::Reset => {}
+
+                            OnLocalUnavailable::RejectHttp503 => {
+                                tracing::debug!(
+                                    gateway = ?self,
+                                    failed_attempts = attempt,
+                                    "The user application is not listening, \
+                                    sending a 503 response",
+                                );
+
+                                let response = mirrord_unavailable_response(
+                                    self.request.version(),
+                                    self.request.connection_id,
+                                    self.request.request_id,
+                                    self.request.port,
+                                );
+                                message_bus
+                                    .send_agent(ClientMessage::TcpSteal(
+                                        LayerTcpSteal::HttpResponse(response),
+                                    ))
+                                    .await;
+                                message_bus.send(HttpOut::DeliveryFailed).await;
+
+                                return Ok(());
+                            }
+
+                            OnLocalUnavailable::Hold => {
+                                let hold =
+                                    hold.get_or_insert_with(|| self.local_unavailable.hold());
+                                match closed_token.run_until_cancelled(hold.wait()).await {
+                                    None => return Ok(()),
+                                    Some(true) => {
+                                        tracing::trace!(
+                                            failed_attempts = attempt,
+                                            "The user application is not listening yet, \
+                                            trying again",
+                                        );
+                                        continue;
+                                    }
+                                    Some(false) => {
+                                        tracing::warn!(
+                                            gateway = ?self,
+                                            failed_attempts = attempt,
+                                            "The user application did not start listening in time",
+                                        );
+
+                                        break error;
+                                    }
+                                }
+                            }
+                        }
+                    }
+
                     let backoff = error.can_retry().then(|| backoffs.next()).flatten();
 
                     let Some(backoff) = backoff else {
@@ -436,6 +507,7 @@ impl BackgroundTask for HttpGatewayTask {
                     response,
                 )))
                 .await;
+            message_bus.send(HttpOut::DeliveryFailed).await;
         }
 
         Ok(())
@@ -681,6 +753,7 @@ mod test {
                 } else {
                     IncomingTrafficTransportType::Tcp
                 },
+                Default::default(),
             );
             tasks.register(gateway, 0, 8)
         };
@@ -721,6 +794,7 @@ mod test {
             .unwrap_message();
         let on_upgrade = match message {
             InProxyTaskMessage::Http(HttpOut::Upgraded(on_upgrade)) => on_upgrade,
+            other => panic!("unexpected task message: {other:?}"),
         };
         let update = tasks.next().await.expect("no task result");
         match update.1 {
@@ -843,6 +917,7 @@ mod test {
                 response_mode,
                 addr,
                 IncomingTrafficTransportType::Tcp,
+                Default::default(),
             ),
             (),
             8,
@@ -1004,6 +1079,7 @@ mod test {
                 Some(ResponseMode::Basic),
                 addr,
                 IncomingTrafficTransportType::Tcp,
+                Default::default(),
             ),
             (),
             8,
@@ -1084,6 +1160,7 @@ mod test {
                 Some(ResponseMode::Basic),
                 addr,
                 IncomingTrafficTransportType::Tcp,
+                Default::default(),
             ),
             0,
             8,
@@ -1095,6 +1172,7 @@ mod test {
                 Some(ResponseMode::Basic),
                 addr,
                 IncomingTrafficTransportType::Tcp,
+                Default::default(),
             ),
             1,
             8,
@@ -1122,4 +1200,107 @@ mod test {
             }
         }
     }
+
+    /// Verifies that [`HttpGatewayTask`] handles a request that arrives before the user
+    /// application starts listening according to the configured [`OnLocalUnavailable`].
+    ///
+    /// The server starts listening after the default retries are exhausted.
+    #[rstest]
+    #[case::reset(OnLocalUnavailable::Reset, StatusCode::BAD_GATEWAY)]
+    #[case::hold(OnLocalUnavailable::Hold, StatusCode::OK)]
+    #[case::reject_http_503(OnLocalUnavailable::RejectHttp503, StatusCode::SERVICE_UNAVAILABLE)]
+    #[tokio::test]
+    async fn request_before_local_listener(
+        #[case] on_local_unavailable: OnLocalUnavailable,
+        #[case] expected_status: StatusCode,
+    ) {
+        // Reserve an address, but don't listen on it yet.
+        let addr = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let request = HttpRequest {
+            connection_id: 0,
+            request_id: 0,
+            port: 80,
+            internal_request: InternalHttpRequest {
+                method: Method::GET,
+                uri: "/".parse().unwrap(),
+                headers: Default::default(),
+                version: Version::HTTP_11,
+                body: Default::default(),
+            },
+        };
+
+        let (connection, _, proxy_rx) = Connection::dummy();
+
+        let mut tasks: BackgroundTasks<(), InProxyTaskMessage, Infallible> =
+            BackgroundTasks::new(connection.tx_handle());
+
+        let local_unavailable = LocalUnavailable::new(on_local_unavailable);
+        let _gateway = tasks.register(
+            HttpGatewayTask::new(
+                request,
+                ClientStore::new_with_timeout(Duration::from_secs(1), Default::default()),
+                Some(ResponseMode::Basic),
+                addr,
+                IncomingTrafficTransportType::Tcp,
+                local_unavailable.clone(),
+            ),
+            (),
+            8,
+        );
+
+        let server_task = tokio::spawn(async move {
+            // Longer than the default retries.
+            time::sleep(Duration::from_secs(4)).await;
+            let listener = TcpListener::bind(addr).await.unwrap();
+            local_unavailable.listener_added();
+
+            let (connection, _) = listener.accept().await.unwrap();
+            http1::Builder::new()
+                .serve_connection(
+                    TokioIo::new(connection),
+                    service_fn(|_req: Request<Incoming>| {
+                        std::future::ready(Ok::<_, Infallible>(
+                            Response::new(Empty::<Bytes>::new()),
+                        ))
+                    }),
+                )
+                .await
+                .unwrap()
+        });
+
+        match proxy_rx.next().await.unwrap() {
+            ClientMessage::TcpSteal(LayerTcpSteal::HttpResponse(response)) => {
+                assert_eq!(response.internal_response.status, expected_status);
+                assert_eq!(
+                    response
+                        .internal_response
+                        .headers
+                        .contains_key(header::RETRY_AFTER),
+                    on_local_unavailable == OnLocalUnavailable::RejectHttp503,
+                );
+            }
+            other => panic!("unexpected message: {other:?}"),
+        }
+
+        if on_local_unavailable != OnLocalUnavailable::Hold {
+            match tasks.next().await.unwrap().1 {
+                TaskUpdate::Message(InProxyTaskMessage::Http(HttpOut::DeliveryFailed)) => {}
+                other => panic!("unexpected task update: {other:?}"),
+            }
+        }
+
+        match tasks.next().await.unwrap().1 {
+            TaskUpdate::Finished(Ok(())) => {}
+            other => panic!("unexpected task update: {other:?}"),
+        }
+
+        if on_local_unavailable == OnLocalUnavailable::Hold {
+            server_task.await.unwrap();
+        }
+    }
 }
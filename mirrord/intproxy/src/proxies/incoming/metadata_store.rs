@@ -22,6 +22,7 @@ impl MetadataStore {
             .unwrap_or_else(|| ConnMetadataResponse {
                 remote_source: req.peer_address,
                 local_address: req.listener_address.ip(),
+                destination_port: req.listener_address.port(),
             })
     }
 
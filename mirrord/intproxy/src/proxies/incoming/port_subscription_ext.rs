@@ -1,11 +1,44 @@
 //! Utilities for handling toggleable `steal` feature in [`IncomingProxy`](super::IncomingProxy).
 
+use std::ops::Not;
+
 use mirrord_intproxy_protocol::PortSubscription;
 use mirrord_protocol::{
     ClientMessage, Port,
-    tcp::{LayerTcp, LayerTcpSteal, MIRROR_HTTP_FILTER_VERSION, MirrorType, StealType},
+    tcp::{
+        LayerTcp, LayerTcpSteal, MIRROR_HTTP_FILTER_VERSION, MirrorType,
+        STEAL_SOURCE_FILTER_VERSION, SourceCidr, StealType,
+    },
 };
 
+use super::IncomingProxyError;
+
+/// How the `steal` [`PortSubscription`]s are currently made in the agent.
+///
+/// Changed when stealing is downgraded by the
+/// [`AutoDowngrade`](super::auto_downgrade::AutoDowngrade), and back when it's resumed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StealMode {
+    /// The traffic is stolen, as requested by the layer.
+    #[default]
+    Steal,
+    /// The traffic is mirrored instead, from the whole port.
+    Mirror,
+    /// Nothing is subscribed in the agent.
+    Off,
+}
+
+impl StealMode {
+    /// Whether traffic on a `steal` [`PortSubscription`] is expected to come from the given flow.
+    pub fn accepts(self, is_steal: bool) -> bool {
+        match self {
+            Self::Steal => is_steal,
+            Self::Mirror => is_steal.not(),
+            Self::Off => false,
+        }
+    }
+}
+
 /// Retrieves subscribed port from the given [`StealType`].
 fn get_port(steal_type: &StealType) -> Port {
     match steal_type {
@@ -22,10 +55,21 @@ pub trait PortSubscriptionExt {
     fn port(&self) -> Port;
 
     /// Returns a subscribe request to be sent to the agent.
-    fn agent_subscribe(&self, protocol_version: Option<&semver::Version>) -> ClientMessage;
+    ///
+    /// The `source_filter` only applies to the `steal` flow. Fails if it's not empty, and the
+    /// negotiated mirrord-protocol version does not support it.
+    fn agent_subscribe(
+        &self,
+        protocol_version: Option<&semver::Version>,
+        source_filter: &[SourceCidr],
+    ) -> Result<ClientMessage, IncomingProxyError>;
 
     /// Returns an unsubscribe request to be sent to the agent.
     fn wrap_agent_unsubscribe(&self) -> ClientMessage;
+
+    /// Returns the subscription that should be made in the agent when the `steal` subscriptions
+    /// are in the given [`StealMode`], or [`None`] if nothing should be subscribed.
+    fn in_steal_mode(&self, mode: StealMode) -> Option<PortSubscription>;
 }
 
 impl PortSubscriptionExt for PortSubscription {
@@ -36,10 +80,14 @@ impl PortSubscriptionExt for PortSubscription {
         }
     }
 
-    /// [`LayerTcp::PortSubscribe`], [`LayerTcp::PortSubscribeFilteredHttp`], or
-    /// [`LayerTcpSteal::PortSubscribe`].
-    fn agent_subscribe(&self, protocol_version: Option<&semver::Version>) -> ClientMessage {
-        match self {
+    /// [`LayerTcp::PortSubscribe`], [`LayerTcp::PortSubscribeFilteredHttp`],
+    /// [`LayerTcpSteal::PortSubscribe`], or [`LayerTcpSteal::PortSubscribeFromSources`].
+    fn agent_subscribe(
+        &self,
+        protocol_version: Option<&semver::Version>,
+        source_filter: &[SourceCidr],
+    ) -> Result<ClientMessage, IncomingProxyError> {
+        let message = match self {
             Self::Mirror(mirror_type) => match mirror_type {
                 MirrorType::FilteredHttp(port, filter) => {
                     // Check if the agent supports filtered HTTP mirroring
@@ -65,10 +113,26 @@ impl PortSubscriptionExt for PortSubscription {
                     ClientMessage::Tcp(LayerTcp::PortSubscribe(mirror_type.get_port()))
                 }
             },
-            Self::Steal(steal_type) => {
+            Self::Steal(steal_type) if source_filter.is_empty() => {
                 ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(steal_type.clone()))
             }
-        }
+            Self::Steal(steal_type) => match protocol_version {
+                // Stealing from all sources would intercept traffic the user explicitly excluded.
+                Some(version) if STEAL_SOURCE_FILTER_VERSION.matches(version).not() => {
+                    return Err(IncomingProxyError::SourceFilterNotSupported(
+                        version.clone(),
+                    ));
+                }
+                // When the version is not known yet, we're in the middle of a reconnect, and all
+                // subscriptions are made again once it's negotiated.
+                _ => ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribeFromSources(
+                    steal_type.clone(),
+                    source_filter.to_vec(),
+                )),
+            },
+        };
+
+        Ok(message)
     }
 
     /// [`LayerTcp::PortUnsubscribe`] or [`LayerTcpSteal::PortUnsubscribe`].
@@ -82,4 +146,14 @@ impl PortSubscriptionExt for PortSubscription {
             }
         }
     }
+
+    fn in_steal_mode(&self, mode: StealMode) -> Option<PortSubscription> {
+        match (self, mode) {
+            (Self::Mirror(..), _) | (Self::Steal(..), StealMode::Steal) => Some(self.clone()),
+            (Self::Steal(steal_type), StealMode::Mirror) => {
+                Some(Self::Mirror(MirrorType::All(get_port(steal_type))))
+            }
+            (Self::Steal(..), StealMode::Off) => None,
+        }
+    }
 }
@@ -0,0 +1,139 @@
+//! Passing the original client address of stolen traffic to the user application, configured with
+//! [`feature.network.incoming.
+//! source_ip_delivery`](mirrord_config::feature::network::incoming::IncomingConfig::source_ip_delivery).
+
+use std::net::{IpAddr, Ipv6Addr, SocketAddr};
+
+use hyper::{HeaderMap, header::HeaderValue};
+
+/// Signature that starts every PROXY protocol v2 header.
+const PROXY_V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+
+/// PROXY protocol v2 header byte: version 2, `PROXY` command.
+const PROXY_V2_VERSION_COMMAND: u8 = 0x21;
+
+/// PROXY protocol v2 header byte: TCP over IPv4.
+const PROXY_V2_TCP4: u8 = 0x11;
+
+/// PROXY protocol v2 header byte: TCP over IPv6.
+const PROXY_V2_TCP6: u8 = 0x21;
+
+/// Name of the header that carries the client IP of a stolen HTTP request.
+pub const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Builds the PROXY protocol v2 header for a connection from `source` to `destination`.
+///
+/// If only one of the addresses is IPv4, it is mapped to IPv6, as both addresses must be of the
+/// same family.
+pub fn proxy_protocol_v2_header(source: SocketAddr, destination: SocketAddr) -> Vec<u8> {
+    let mut header = PROXY_V2_SIGNATURE.to_vec();
+    header.push(PROXY_V2_VERSION_COMMAND);
+
+    match (source.ip(), destination.ip()) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => {
+            header.push(PROXY_V2_TCP4);
+            header.extend_from_slice(&12_u16.to_be_bytes());
+            header.extend_from_slice(&source.octets());
+            header.extend_from_slice(&destination.octets());
+        }
+        (source, destination) => {
+            let to_ipv6 = |ip: IpAddr| -> Ipv6Addr {
+                match ip {
+                    IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+                    IpAddr::V6(ip) => ip,
+                }
+            };
+
+            header.push(PROXY_V2_TCP6);
+            header.extend_from_slice(&36_u16.to_be_bytes());
+            header.extend_from_slice(&to_ipv6(source).octets());
+            header.extend_from_slice(&to_ipv6(destination).octets());
+        }
+    }
+
+    header.extend_from_slice(&source.port().to_be_bytes());
+    header.extend_from_slice(&destination.port().to_be_bytes());
+
+    header
+}
+
+/// Appends `client` to the [`X_FORWARDED_FOR`] header, merging all of its existing values into
+/// one.
+pub fn append_x_forwarded_for(headers: &mut HeaderMap, client: IpAddr) {
+    let client = client.to_string();
+    let value = headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .chain([client.as_str()])
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    headers.insert(
+        X_FORWARDED_FOR,
+        HeaderValue::try_from(value).expect("joined header values are a valid header value"),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use hyper::{HeaderMap, header::HeaderValue};
+
+    use super::{X_FORWARDED_FOR, append_x_forwarded_for, proxy_protocol_v2_header};
+
+    #[test]
+    fn proxy_protocol_v2_ipv4() {
+        let header = proxy_protocol_v2_header(
+            "192.168.1.10:51234".parse().unwrap(),
+            "10.0.0.5:80".parse().unwrap(),
+        );
+
+        assert_eq!(
+            header,
+            [
+                b"\r\n\r\n\0\r\nQUIT\n".as_slice(),
+                &[0x21, 0x11, 0, 12],
+                &[192, 168, 1, 10],
+                &[10, 0, 0, 5],
+                &51234_u16.to_be_bytes(),
+                &80_u16.to_be_bytes(),
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn proxy_protocol_v2_mixed_families() {
+        let header = proxy_protocol_v2_header(
+            "10.0.0.1:1000".parse().unwrap(),
+            "[fd00::1]:80".parse().unwrap(),
+        );
+
+        assert_eq!(header.len(), 16 + 36);
+        assert_eq!(header[13], 0x21);
+        assert_eq!(&header[14..16], &36_u16.to_be_bytes());
+        assert_eq!(
+            &header[16..32],
+            &"::ffff:10.0.0.1"
+                .parse::<std::net::Ipv6Addr>()
+                .unwrap()
+                .octets()
+        );
+    }
+
+    #[test]
+    fn x_forwarded_for() {
+        let mut headers = HeaderMap::new();
+        append_x_forwarded_for(&mut headers, "1.2.3.4".parse().unwrap());
+        assert_eq!(headers.get(X_FORWARDED_FOR).unwrap(), "1.2.3.4");
+
+        let mut headers = HeaderMap::new();
+        headers.append(X_FORWARDED_FOR, HeaderValue::from_static("10.0.0.1"));
+        headers.append(X_FORWARDED_FOR, HeaderValue::from_static("10.0.0.2"));
+        append_x_forwarded_for(&mut headers, "fd00::1".parse().unwrap());
+        assert_eq!(
+            headers.get_all(X_FORWARDED_FOR).iter().collect::<Vec<_>>(),
+            ["10.0.0.1, 10.0.0.2, fd00::1"]
+        );
+    }
+}
@@ -1,17 +1,23 @@
 use std::{
-    collections::{HashMap, hash_map::Entry},
+    collections::{HashMap, HashSet, hash_map::Entry},
     net::SocketAddr,
 };
 
 use futures::future::Either;
 use mirrord_intproxy_protocol::{
-    IncomingResponse, LayerId, MessageId, PortSubscribe, PortUnsubscribe, ProxyToLayerMessage,
+    IncomingResponse, LayerId, MessageId, PortSubscribe, PortSubscription, PortUnsubscribe,
+    ProxyToLayerMessage,
+};
+use mirrord_protocol::{
+    BlockedAction, ClientMessage, Port, RemoteResult, ResponseError, tcp::SourceCidr,
 };
-use mirrord_protocol::{BlockedAction, ClientMessage, Port, RemoteResult, ResponseError};
 use semver::Version;
 use tracing::Level;
 
-use super::{IncomingProxyError, port_subscription_ext::PortSubscriptionExt};
+use super::{
+    IncomingProxyError,
+    port_subscription_ext::{PortSubscriptionExt, StealMode},
+};
 use crate::{
     main_tasks::{ProxyMessage, ToLayer},
     remote_resources::RemoteResources,
@@ -35,25 +41,36 @@ pub struct Subscription {
     active_source: Source,
     /// Whether this subscription is confirmed.
     confirmed: bool,
+    /// How this subscription is made in the agent, if it's a `steal` one.
+    steal_mode: StealMode,
 }
 
 impl Subscription {
     /// Creates a new subscription from the given [`Source`].
     /// Additionally returns a message to be sent to the agent.
-    fn new(source: Source, protocol_version: Option<&Version>) -> (Self, ClientMessage) {
+    /// Returns [`None`] if nothing should be subscribed in the agent in the given [`StealMode`].
+    fn new(
+        source: Source,
+        protocol_version: Option<&Version>,
+        source_filter: &[SourceCidr],
+        steal_mode: StealMode,
+    ) -> Result<(Self, Option<ClientMessage>), IncomingProxyError> {
         let message = source
             .request
             .subscription
-            .agent_subscribe(protocol_version);
+            .in_steal_mode(steal_mode)
+            .map(|subscription| subscription.agent_subscribe(protocol_version, source_filter))
+            .transpose()?;
 
-        (
+        Ok((
             Self {
                 queued_sources: Default::default(),
                 active_source: source,
                 confirmed: false,
+                steal_mode,
             },
             message,
-        )
+        ))
     }
 
     /// Overwrites the active subscription [`Source`].
@@ -123,8 +140,12 @@ impl Subscription {
     }
 
     /// Removed a source from this subscription.
-    /// If this source is the last one, returns [`Err`] with a message to be sent to the agent.
-    fn remove_source(mut self, listening_on: SocketAddr) -> Result<Self, Box<ClientMessage>> {
+    /// If this source is the last one, returns [`Err`] with an optional message to be sent to the
+    /// agent.
+    fn remove_source(
+        mut self,
+        listening_on: SocketAddr,
+    ) -> Result<Self, Option<Box<ClientMessage>>> {
         let queue_size = self.queued_sources.len();
         self.queued_sources
             .retain(|source| source.request.listening_on != listening_on);
@@ -141,22 +162,63 @@ impl Subscription {
                 self.active_source = next_in_queue;
                 Ok(self)
             }
-            None => Err(Box::new(
-                self.active_source
-                    .request
-                    .subscription
-                    .wrap_agent_unsubscribe(),
-            )),
+            None => Err(self
+                .active_source
+                .request
+                .subscription
+                .in_steal_mode(self.steal_mode)
+                .map(|subscription| Box::new(subscription.wrap_agent_unsubscribe()))),
         }
     }
 
-    pub fn resubscribe_message(&mut self, protocol_version: Option<&Version>) -> ClientMessage {
-        self.confirmed = false;
-
-        self.active_source
+    /// Returns [`None`] if nothing should be subscribed in the agent in the current
+    /// [`StealMode`].
+    pub fn resubscribe_message(
+        &mut self,
+        protocol_version: Option<&Version>,
+        source_filter: &[SourceCidr],
+    ) -> Result<Option<ClientMessage>, IncomingProxyError> {
+        let Some(subscription) = self
+            .active_source
             .request
             .subscription
-            .agent_subscribe(protocol_version)
+            .in_steal_mode(self.steal_mode)
+        else {
+            return Ok(None);
+        };
+        self.confirmed = false;
+
+        subscription
+            .agent_subscribe(protocol_version, source_filter)
+            .map(Some)
+    }
+
+    /// Switches this subscription to the given [`StealMode`].
+    /// Returns messages to be sent to the agent.
+    ///
+    /// The confirmation state is kept, as the layers already got their responses.
+    fn set_steal_mode(
+        &mut self,
+        steal_mode: StealMode,
+        protocol_version: Option<&Version>,
+        source_filter: &[SourceCidr],
+    ) -> Result<Vec<ClientMessage>, IncomingProxyError> {
+        let previous = std::mem::replace(&mut self.steal_mode, steal_mode);
+        let subscription = &self.active_source.request.subscription;
+        if previous == steal_mode || matches!(subscription, PortSubscription::Mirror(..)) {
+            return Ok(vec![]);
+        }
+
+        subscription
+            .in_steal_mode(previous)
+            .map(|subscription| Ok(subscription.wrap_agent_unsubscribe()))
+            .into_iter()
+            .chain(
+                subscription.in_steal_mode(steal_mode).map(|subscription| {
+                    subscription.agent_subscribe(protocol_version, source_filter)
+                }),
+            )
+            .collect()
     }
 }
 
@@ -172,6 +234,8 @@ impl Subscription {
 pub struct SubscriptionsManager {
     remote_ports: RemoteResources<(Port, SocketAddr)>,
     subscriptions: HashMap<Port, Subscription>,
+    /// How the `steal` subscriptions are made in the agent.
+    steal_mode: StealMode,
 }
 
 impl SubscriptionsManager {
@@ -188,14 +252,15 @@ impl SubscriptionsManager {
     /// Subsequent subscriptions of the same port will take precedence over previous ones, meaning
     /// that new connections will be routed to the listener from the most recent [`PortSubscribe`]
     /// request.
-    #[tracing::instrument(level = Level::INFO, skip(self), ret)]
+    #[tracing::instrument(level = Level::INFO, skip(self), ret, err)]
     pub fn layer_subscribed(
         &mut self,
         layer_id: LayerId,
         message_id: MessageId,
         request: PortSubscribe,
         protocol_version: Option<&Version>,
-    ) -> Option<Either<ProxyMessage, ClientMessage>> {
+        source_filter: &[SourceCidr],
+    ) -> Result<Option<Either<ProxyMessage, ClientMessage>>, IncomingProxyError> {
         self.remote_ports.add(
             layer_id,
             (request.subscription.port(), request.listening_on),
@@ -208,17 +273,28 @@ impl SubscriptionsManager {
             request,
         };
 
-        match self.subscriptions.entry(port) {
+        let response = match self.subscriptions.entry(port) {
             Entry::Occupied(mut e) => e
                 .get_mut()
                 .push_source(source)
                 .map(|m| Either::Left(ProxyMessage::ToLayer(m))),
             Entry::Vacant(e) => {
-                let (subscription, message) = Subscription::new(source, protocol_version);
+                let (mut subscription, message) =
+                    Subscription::new(source, protocol_version, source_filter, self.steal_mode)?;
+                let response = match message {
+                    Some(message) => Some(Either::Right(message)),
+                    // Nothing to wait for in the agent, the layer gets the confirmation right away.
+                    None => subscription
+                        .confirm()
+                        .pop()
+                        .map(|m| Either::Left(ProxyMessage::ToLayer(m))),
+                };
                 e.insert(subscription);
-                Some(Either::Right(message))
+                response
             }
-        }
+        };
+
+        Ok(response)
     }
 
     /// Unregisters a subscription from this struct.
@@ -243,7 +319,7 @@ impl SubscriptionsManager {
                 self.subscriptions.insert(request.port, subscription);
                 None
             }
-            Err(message) => Some(*message),
+            Err(message) => message.map(|message| *message),
         }
     }
 
@@ -327,7 +403,7 @@ impl SubscriptionsManager {
                         self.subscriptions.insert(port, subscription);
                         None
                     }
-                    Err(message) => Some(*message),
+                    Err(message) => message.map(|message| *message),
                 }
             })
             .collect()
@@ -341,12 +417,53 @@ impl SubscriptionsManager {
     pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Subscription> + '_ {
         self.subscriptions.values_mut()
     }
+
+    /// Returns how the `steal` subscriptions are currently made in the agent.
+    pub fn steal_mode(&self) -> StealMode {
+        self.steal_mode
+    }
+
+    /// Switches all `steal` subscriptions, including the future ones, to the given [`StealMode`].
+    /// Returns messages to be sent to the agent.
+    pub fn set_steal_mode(
+        &mut self,
+        steal_mode: StealMode,
+        protocol_version: Option<&Version>,
+        source_filter: &[SourceCidr],
+    ) -> Result<Vec<ClientMessage>, IncomingProxyError> {
+        self.steal_mode = steal_mode;
+
+        let messages = self
+            .subscriptions
+            .values_mut()
+            .map(|subscription| {
+                subscription.set_steal_mode(steal_mode, protocol_version, source_filter)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(messages.into_iter().flatten().collect())
+    }
+
+    /// Returns the layers that have an active `steal` subscription.
+    pub fn steal_layers(&self) -> HashSet<LayerId> {
+        self.subscriptions
+            .values()
+            .filter(|subscription| {
+                matches!(
+                    subscription.active_source.request.subscription,
+                    PortSubscription::Steal(..)
+                )
+            })
+            .map(|subscription| subscription.active_source.layer)
+            .collect()
+    }
 }
 
 #[cfg(test)]
 mod test {
     use mirrord_intproxy_protocol::PortSubscription;
-    use mirrord_protocol::tcp::{LayerTcp, MirrorType};
+    use mirrord_protocol::tcp::{LayerTcp, LayerTcpSteal, MirrorType, StealType};
+    use rstest::rstest;
 
     use super::*;
 
@@ -357,15 +474,18 @@ mod test {
 
         let mut manager = SubscriptionsManager::default();
 
-        let response = manager.layer_subscribed(
-            LayerId(0),
-            0,
-            PortSubscribe {
-                listening_on: listener_1,
-                subscription: PortSubscription::Mirror(MirrorType::All(80)),
-            },
-            None,
-        );
+        let response = manager
+            .layer_subscribed(
+                LayerId(0),
+                0,
+                PortSubscribe {
+                    listening_on: listener_1,
+                    subscription: PortSubscription::Mirror(MirrorType::All(80)),
+                },
+                None,
+                &[],
+            )
+            .unwrap();
         assert!(
             matches!(
                 response,
@@ -376,15 +496,18 @@ mod test {
             "{response:?}"
         );
 
-        let response = manager.layer_subscribed(
-            LayerId(0),
-            1,
-            PortSubscribe {
-                listening_on: listener_2,
-                subscription: PortSubscription::Mirror(MirrorType::All(80)),
-            },
-            None,
-        );
+        let response = manager
+            .layer_subscribed(
+                LayerId(0),
+                1,
+                PortSubscribe {
+                    listening_on: listener_2,
+                    subscription: PortSubscription::Mirror(MirrorType::All(80)),
+                },
+                None,
+                &[],
+            )
+            .unwrap();
         assert!(response.is_none(), "{response:?}");
 
         let mut responses = manager.agent_responded(Ok(80)).unwrap();
@@ -438,15 +561,18 @@ mod test {
 
         let mut manager = SubscriptionsManager::default();
 
-        let response = manager.layer_subscribed(
-            LayerId(0),
-            0,
-            PortSubscribe {
-                listening_on,
-                subscription: PortSubscription::Mirror(MirrorType::All(80)),
-            },
-            None,
-        );
+        let response = manager
+            .layer_subscribed(
+                LayerId(0),
+                0,
+                PortSubscribe {
+                    listening_on,
+                    subscription: PortSubscription::Mirror(MirrorType::All(80)),
+                },
+                None,
+                &[],
+            )
+            .unwrap();
         assert!(
             matches!(
                 response,
@@ -505,15 +631,18 @@ mod test {
 
         let mut manager = SubscriptionsManager::default();
 
-        let response = manager.layer_subscribed(
-            LayerId(0),
-            0,
-            PortSubscribe {
-                listening_on,
-                subscription: PortSubscription::Mirror(MirrorType::All(80)),
-            },
-            None,
-        );
+        let response = manager
+            .layer_subscribed(
+                LayerId(0),
+                0,
+                PortSubscribe {
+                    listening_on,
+                    subscription: PortSubscription::Mirror(MirrorType::All(80)),
+                },
+                None,
+                &[],
+            )
+            .unwrap();
         assert!(
             matches!(
                 response,
@@ -545,4 +674,128 @@ mod test {
             .unwrap();
         assert!(responses.is_empty(), "{responses:?}");
     }
+
+    /// Verifies that the source filter is sent to the agent when the negotiated mirrord-protocol
+    /// version supports it, and that the subscription fails instead of stealing from all sources
+    /// when it doesn't.
+    #[rstest]
+    #[case::supported("1.37.0", true)]
+    #[case::unsupported("1.36.0", false)]
+    fn with_source_filter(#[case] protocol_version: &str, #[case] supported: bool) {
+        let source_filter = [SourceCidr {
+            address: "10.12.0.0".parse().unwrap(),
+            prefix_len: 16,
+        }];
+        let protocol_version = protocol_version.parse::<Version>().unwrap();
+
+        let mut manager = SubscriptionsManager::default();
+
+        let response = manager.layer_subscribed(
+            LayerId(0),
+            0,
+            PortSubscribe {
+                listening_on: "127.0.0.1:1111".parse().unwrap(),
+                subscription: PortSubscription::Steal(StealType::All(80)),
+            },
+            Some(&protocol_version),
+            &source_filter,
+        );
+
+        if supported {
+            let expected = ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribeFromSources(
+                StealType::All(80),
+                source_filter.to_vec(),
+            ));
+            assert!(
+                matches!(&response, Ok(Some(Either::Right(message))) if *message == expected),
+                "{response:?}"
+            );
+        } else {
+            assert!(
+                matches!(
+                    &response,
+                    Err(IncomingProxyError::SourceFilterNotSupported(version))
+                        if *version == protocol_version
+                ),
+                "{response:?}"
+            );
+        }
+    }
+
+    /// Verifies that switching the [`StealMode`] moves only the `steal` subscriptions, and that
+    /// new subscriptions are confirmed right away when nothing is subscribed in the agent.
+    #[test]
+    fn with_steal_mode() {
+        let mut manager = SubscriptionsManager::default();
+
+        for (port, subscription) in [
+            (80, PortSubscription::Steal(StealType::All(80))),
+            (81, PortSubscription::Mirror(MirrorType::All(81))),
+        ] {
+            manager
+                .layer_subscribed(
+                    LayerId(0),
+                    port.into(),
+                    PortSubscribe {
+                        listening_on: format!("127.0.0.1:{port}").parse().unwrap(),
+                        subscription,
+                    },
+                    None,
+                    &[],
+                )
+                .unwrap();
+            manager.agent_responded(Ok(port)).unwrap();
+        }
+
+        let messages = manager
+            .set_steal_mode(StealMode::Mirror, None, &[])
+            .unwrap();
+        assert_eq!(
+            messages,
+            vec![
+                ClientMessage::TcpSteal(LayerTcpSteal::PortUnsubscribe(80)),
+                ClientMessage::Tcp(LayerTcp::PortSubscribe(80)),
+            ]
+        );
+
+        let messages = manager.set_steal_mode(StealMode::Off, None, &[]).unwrap();
+        assert_eq!(
+            messages,
+            vec![ClientMessage::Tcp(LayerTcp::PortUnsubscribe(80))]
+        );
+
+        let response = manager
+            .layer_subscribed(
+                LayerId(0),
+                2,
+                PortSubscribe {
+                    listening_on: "127.0.0.1:82".parse().unwrap(),
+                    subscription: PortSubscription::Steal(StealType::All(82)),
+                },
+                None,
+                &[],
+            )
+            .unwrap();
+        assert!(
+            matches!(
+                response,
+                Some(Either::Left(ProxyMessage::ToLayer(ToLayer {
+                    message_id: 2,
+                    message: ProxyToLayerMessage::Incoming(IncomingResponse::PortSubscribe(Ok(()))),
+                    ..
+                })))
+            ),
+            "{response:?}"
+        );
+
+        let mut messages = manager.set_steal_mode(StealMode::Steal, None, &[]).unwrap();
+        messages.sort_by_key(|message| format!("{message:?}"));
+        assert_eq!(
+            messages,
+            vec![
+                ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(StealType::All(80))),
+                ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(StealType::All(82))),
+            ]
+        );
+    }
 }
@@ -4,7 +4,7 @@ use hyper::{Version, upgrade::OnUpgrade};
 use mirrord_protocol::{ConnectionId, Port, RequestId};
 use thiserror::Error;
 
-use super::tls::LocalTlsSetupError;
+use super::{tls::LocalTlsSetupError, unavailable::HOLD_MAX_BYTES};
 
 /// Messages produced by the [`BackgroundTask`](crate::background_tasks::BackgroundTask)s used in
 /// the [`IncomingProxy`](super::IncomingProxy).
@@ -27,6 +27,9 @@ impl fmt::Debug for InProxyTaskMessage {
 #[derive(Debug)]
 pub enum HttpOut {
     Upgraded(OnUpgrade),
+    /// The stolen request could not be delivered to the user application, and the agent got an
+    /// error response instead.
+    DeliveryFailed,
 }
 
 impl From<HttpOut> for InProxyTaskMessage {
@@ -54,6 +57,11 @@ pub enum InProxyTaskError {
     Upgrade(#[source] hyper::Error),
     #[error("failed to prepare TLS client configuration: {0}")]
     TlsSetup(#[from] LocalTlsSetupError),
+    #[error(
+        "buffered more than {} bytes while waiting for the local application to start listening",
+        HOLD_MAX_BYTES
+    )]
+    HoldBufferFull,
 }
 
 impl From<Infallible> for InProxyTaskError {
@@ -74,6 +82,9 @@ pub enum InProxyTask {
     MirrorHttpGateway(HttpGatewayId),
     /// [`HttpGatewayTask`](super::http_gateway::HttpGatewayTask) handling a stolen HTTP request.
     StealHttpGateway(HttpGatewayId),
+    /// [`UnixProxyTask`](super::unix_proxy::UnixProxyTask) handling a connection stolen from a
+    /// unix socket.
+    StealUnixProxy(ConnectionId),
 }
 
 /// Identifies a [`HttpGatewayTask`](super::http_gateway::HttpGatewayTask).
@@ -10,18 +10,19 @@ use mirrord_protocol::{
 use mirrord_tls_util::MaybeTls;
 use rustls::pki_types::ServerName;
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     time,
 };
 use tokio_rustls::TlsStream;
-use tracing::Level;
+use tracing::{Instrument, Level};
 
 use super::{
     bound_socket::BoundTcpSocket,
     tasks::{InProxyTaskError, InProxyTaskMessage},
     tls::LocalTlsSetup,
+    unavailable::{HOLD_MAX_BYTES, HoldRetry},
 };
-use crate::background_tasks::{BackgroundTask, MessageBus};
+use crate::background_tasks::{BackgroundTask, MessageBus, MessageBusInner};
 
 /// Local TCP connections between the [`TcpProxyTask`] and the user application.
 #[derive(Debug)]
@@ -33,22 +34,57 @@ pub enum LocalTcpConnection {
         peer: SocketAddr,
         transport: IncomingTrafficTransportType,
         tls_setup: Option<Arc<LocalTlsSetup>>,
+        /// PROXY protocol header, sent before any other data (and before the TLS handshake).
+        proxy_header: Option<Vec<u8>>,
+        /// Set when we should wait for the user application to start listening.
+        hold: Option<HoldRetry>,
     },
     /// Upgraded HTTP connection from a previously stolen HTTP request.
     AfterUpgrade(OnUpgrade),
 }
 
 impl LocalTcpConnection {
+    /// Returns whether [`Self::connect`] waits for the user application to start listening.
+    fn holds(&self) -> bool {
+        matches!(self, Self::FromTheStart { hold: Some(..), .. })
+    }
+
     /// Makes the connection, returning the IO stream and data ready to be sent to the agent.
     async fn connect(self) -> Result<(MaybeTls, Vec<u8>), InProxyTaskError> {
         match self {
             LocalTcpConnection::FromTheStart {
-                socket,
+                mut socket,
                 peer,
                 transport,
                 tls_setup,
+                proxy_header,
+                hold,
             } => {
-                let stream = socket.connect(peer).await?;
+                let mut stream = match hold {
+                    Some(mut hold) => loop {
+                        let addr = socket.local_addr()?;
+                        let error = match socket.connect(peer).await {
+                            Err(error) if error.kind() == ErrorKind::ConnectionRefused => error,
+                            result => break result?,
+                        };
+
+                        if hold.wait().await.not() {
+                            return Err(error.into());
+                        }
+
+                        tracing::trace!(
+                            %peer,
+                            "The user application is not listening yet, retrying",
+                        );
+
+                        // Same address, so that the connection metadata stays valid.
+                        socket = BoundTcpSocket::bind(addr)?;
+                    },
+                    None => socket.connect(peer).await?,
+                };
+                if let Some(header) = proxy_header {
+                    stream.write_all(&header).await?;
+                }
                 let stream = match (transport, tls_setup) {
                     (IncomingTrafficTransportType::Tcp, ..) => MaybeTls::NoTls(stream),
                     (.., None) => MaybeTls::NoTls(stream),
@@ -148,7 +184,43 @@ impl BackgroundTask for TcpProxyTask {
             .take()
             .expect("task should have a valid connection before run");
 
-        let (mut stream, read_buf) = connection.connect().await?;
+        // When holding the connection, we buffer the data from the agent until we're connected.
+        let holds = connection.holds();
+        let mut held = Vec::new();
+        let mut held_bytes = 0;
+
+        let connect = connection.connect();
+        tokio::pin!(connect);
+        let (mut stream, read_buf) = loop {
+            tokio::select! {
+                result = &mut connect => break result?,
+
+                msg = message_bus.recv(), if holds => match msg {
+                    None => {
+                        tracing::trace!(
+                            "Message bus closed before the user application started listening, \
+                            exiting",
+                        );
+                        return Ok(());
+                    }
+                    Some(data) => {
+                        held_bytes += data.len();
+                        if held_bytes > HOLD_MAX_BYTES {
+                            return Err(InProxyTaskError::HoldBufferFull);
+                        }
+                        held.push(data);
+                    }
+                },
+            }
+        };
+
+        for data in held {
+            if data.is_empty() {
+                stream.shutdown().await?;
+            } else {
+                stream.write_all(&data).await?;
+            }
+        }
 
         if self.mirror.not() && read_buf.is_empty().not() {
             // We don't send empty data,
@@ -163,96 +235,90 @@ impl BackgroundTask for TcpProxyTask {
         let peer_addr = stream.as_ref().peer_addr()?;
         let self_addr = stream.as_ref().local_addr()?;
 
-        let mut buf = BytesMut::with_capacity(64 * 1024);
-        let mut reading_closed = false;
-        let mut is_lingering = false;
-
-        loop {
-            tokio::select! {
-                res = stream.read_buf(&mut buf), if !reading_closed => match res {
-                    Err(e) if e.kind() == ErrorKind::WouldBlock => {},
-                    Err(e) => break Err(e.into()),
-                    Ok(..) => {
-                        if buf.is_empty() {
-                            reading_closed = true;
-
-                            tracing::trace!(
-                                peer_addr = %peer_addr,
-                                self_addr = %self_addr,
-                                "The user application shut down its side of the connection",
-                            )
-                        } else {
-                            tracing::trace!(
-                                data_len = buf.len(),
-                                peer_addr = %peer_addr,
-                                self_addr = %self_addr,
-                                "Received some data from the user application",
-                            );
-                        }
+        proxy_stream(stream, self.connection_id, self.mirror, message_bus)
+            .instrument(tracing::trace_span!("proxy_stream", %peer_addr, %self_addr))
+            .await
+    }
+}
 
-                        if !self.mirror {
-                            let msg =
-                                ClientMessage::TcpSteal(LayerTcpSteal::Data(TcpData {
-                                    connection_id: self.connection_id,
-                                    bytes: buf.clone().into(),
-                                }));
-                            message_bus.send_agent(msg).await;
-                        }
+/// Proxies data between the agent and the user application over the given local `stream`, until
+/// either side closes the connection.
+///
+/// Used by [`TcpProxyTask`] and [`UnixProxyTask`](super::unix_proxy::UnixProxyTask).
+///
+/// If `mirror` is set, the data coming from the user application is discarded, and we exit only
+/// after [`TcpProxyTask::MIRROR_MODE_LINGER_TIMEOUT`] of silence once the [`MessageBusInner`] is
+/// closed.
+pub(super) async fn proxy_stream<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    connection_id: ConnectionId,
+    mirror: bool,
+    message_bus: &mut MessageBusInner<Vec<u8>, InProxyTaskMessage>,
+) -> Result<(), InProxyTaskError> {
+    let mut buf = BytesMut::with_capacity(64 * 1024);
+    let mut reading_closed = false;
+    let mut is_lingering = false;
 
-                        buf.clear();
-                    }
-                },
+    loop {
+        tokio::select! {
+            res = stream.read_buf(&mut buf), if !reading_closed => match res {
+                Err(e) if e.kind() == ErrorKind::WouldBlock => {},
+                Err(e) => break Err(e.into()),
+                Ok(..) => {
+                    if buf.is_empty() {
+                        reading_closed = true;
 
-                msg = message_bus.recv(), if !is_lingering => match msg {
-                    None if self.mirror => {
+                        tracing::trace!("The user application shut down its side of the connection")
+                    } else {
                         tracing::trace!(
-                            peer_addr = %peer_addr,
-                            self_addr = %self_addr,
-                            "Message bus closed, waiting until the connection is silent",
+                            data_len = buf.len(),
+                            "Received some data from the user application",
                         );
+                    }
 
-                        is_lingering = true;
+                    if !mirror {
+                        let msg = ClientMessage::TcpSteal(LayerTcpSteal::Data(TcpData {
+                            connection_id,
+                            bytes: buf.clone().into(),
+                        }));
+                        message_bus.send_agent(msg).await;
                     }
-                    None => {
+
+                    buf.clear();
+                }
+            },
+
+            msg = message_bus.recv(), if !is_lingering => match msg {
+                None if mirror => {
+                    tracing::trace!("Message bus closed, waiting until the connection is silent");
+
+                    is_lingering = true;
+                }
+                None => {
+                    tracing::trace!("Message bus closed, exiting");
+
+                    break Ok(());
+                }
+                Some(data) => {
+                    if data.is_empty() {
+                        tracing::trace!("The agent shut down its side of the connection");
+
+                        stream.shutdown().await?;
+                    } else {
                         tracing::trace!(
-                            peer_addr = %peer_addr,
-                            self_addr = %self_addr,
-                            "Message bus closed, exiting",
+                            data_len = data.len(),
+                            "Received some data from the agent",
                         );
 
-                        break Ok(());
+                        stream.write_all(&data).await?;
                     }
-                    Some(data) => {
-                        if data.is_empty() {
-                            tracing::trace!(
-                                peer_addr = %peer_addr,
-                                self_addr = %self_addr,
-                                "The agent shut down its side of the connection",
-                            );
-
-                            stream.shutdown().await?;
-                        } else {
-                            tracing::trace!(
-                                data_len = data.len(),
-                                peer_addr = %peer_addr,
-                                self_addr = %self_addr,
-                                "Received some data from the agent",
-                            );
-
-                            stream.write_all(&data).await?;
-                        }
-                    },
                 },
+            },
 
-                _ = time::sleep(Self::MIRROR_MODE_LINGER_TIMEOUT), if is_lingering => {
-                    tracing::trace!(
-                        peer_addr = %peer_addr,
-                        self_addr = %self_addr,
-                        "Message bus is closed and the connection is silent, exiting",
-                    );
+            _ = time::sleep(TcpProxyTask::MIRROR_MODE_LINGER_TIMEOUT), if is_lingering => {
+                tracing::trace!("Message bus is closed and the connection is silent, exiting");
 
-                    break Ok(());
-                }
+                break Ok(());
             }
         }
     }
@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{net::IpAddr, ops::Not, path::PathBuf, time::Duration};
 
 use bytes::Bytes;
 use futures::FutureExt;
@@ -9,27 +9,34 @@ use hyper::{
     service::Service,
 };
 use hyper_util::rt::TokioIo;
+use mirrord_config::feature::network::incoming::{
+    OnLocalUnavailable, SourceIpDelivery, auto_downgrade::AutoDowngradeConfig,
+};
 use mirrord_intproxy_protocol::{
     IncomingRequest, IncomingResponse, LayerId, PortSubscribe, PortSubscription,
     ProxyToLayerMessage,
 };
 use mirrord_protocol::{
-    ClientMessage,
+    ClientMessage, ResponseError,
     tcp::{
         ChunkedRequest, ChunkedRequestBodyV1, ChunkedRequestStartV2, ChunkedResponse, DaemonTcp,
         HttpFilter, HttpMethodFilter, HttpRequestMetadata, IncomingTrafficTransportType,
-        InternalHttpBodyFrame, InternalHttpBodyNew, InternalHttpRequest, LayerTcpSteal, StealType,
-        TcpClose,
+        InternalHttpBodyFrame, InternalHttpBodyNew, InternalHttpRequest, LayerTcp, LayerTcpSteal,
+        NewTcpConnectionV1, NewTcpConnectionV2, NewUnixConnection, StealType, TcpClose, TcpData,
     },
 };
-use mirrord_protocol_io::Connection;
+use mirrord_protocol_io::{Client, Connection, ConnectionOutput};
 use rstest::rstest;
-use tokio::net::TcpListener;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
 
 use crate::{
-    background_tasks::BackgroundTasks,
+    background_tasks::{BackgroundTasks, TaskSender},
     main_tasks::{ProxyMessage, ToLayer},
     proxies::incoming::{IncomingProxy, IncomingProxyError, IncomingProxyMessage},
+    session_metadata::SessionMetadata,
 };
 
 /// Dummy service mocking a server sent events HTTP server.
@@ -62,7 +69,16 @@ async fn http_request_terminates_on_remote_close(#[case] steal_type: StealType)
     let local_addr = local_listener.local_addr().unwrap();
 
     let (conn, _, out) = Connection::dummy();
-    let proxy = IncomingProxy::new(Duration::from_secs(3), Default::default());
+    let proxy = IncomingProxy::new(
+        Duration::from_secs(3),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
     let mut background_tasks: BackgroundTasks<(), ProxyMessage, IncomingProxyError> =
         BackgroundTasks::new(conn.tx_handle());
 
@@ -187,3 +203,765 @@ async fn http_request_terminates_on_remote_close(#[case] steal_type: StealType)
         panic!("{error}");
     }
 }
+
+/// Verifies that [`IncomingProxy`] configured with [`OnLocalUnavailable::Hold`] delivers a stolen
+/// connection that arrives before the user application starts listening, together with the data
+/// received in the meantime.
+#[tokio::test]
+async fn stolen_connection_held_until_local_listener_starts() {
+    // Reserve an address, but don't listen on it yet.
+    let local_addr = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let (conn, _, out) = Connection::dummy();
+    let proxy = IncomingProxy::new(
+        Duration::from_secs(3),
+        Default::default(),
+        OnLocalUnavailable::Hold,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+    let mut background_tasks: BackgroundTasks<(), ProxyMessage, IncomingProxyError> =
+        BackgroundTasks::new(conn.tx_handle());
+
+    let proxy = background_tasks.register(proxy, (), 8);
+
+    proxy
+        .send(IncomingProxyMessage::AgentProtocolVersion(
+            mirrord_protocol::VERSION.clone(),
+        ))
+        .await;
+    proxy
+        .send(IncomingProxyMessage::LayerRequest(
+            0,
+            LayerId(0),
+            IncomingRequest::PortSubscribe(PortSubscribe {
+                listening_on: local_addr,
+                subscription: PortSubscription::Steal(StealType::All(80)),
+            }),
+        ))
+        .await;
+    assert_eq!(
+        out.next().await.unwrap(),
+        ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(StealType::All(80))),
+    );
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(
+            DaemonTcp::SubscribeResult(Ok(80)),
+        ))
+        .await;
+    assert_eq!(
+        background_tasks.next().await.unwrap().1.unwrap_message(),
+        ProxyMessage::ToLayer(ToLayer {
+            message_id: 0,
+            layer_id: LayerId(0),
+            message: ProxyToLayerMessage::Incoming(IncomingResponse::PortSubscribe(Ok(())))
+        }),
+    );
+
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(
+            DaemonTcp::NewConnectionV2(NewTcpConnectionV2 {
+                connection: NewTcpConnectionV1 {
+                    connection_id: 0,
+                    remote_address: "1.1.1.1".parse().unwrap(),
+                    destination_port: 80,
+                    source_port: 55555,
+                    local_address: "2.2.2.2".parse().unwrap(),
+                },
+                transport: IncomingTrafficTransportType::Tcp,
+            }),
+        ))
+        .await;
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(DaemonTcp::Data(TcpData {
+            connection_id: 0,
+            bytes: b"hello".to_vec().into(),
+        })))
+        .await;
+
+    // Longer than the default retries of an HTTP request.
+    tokio::time::sleep(Duration::from_secs(4)).await;
+    let local_listener = TcpListener::bind(local_addr).await.unwrap();
+
+    let (mut stream, _) = tokio::time::timeout(Duration::from_secs(1), local_listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    let mut buf = [0; 5];
+    stream.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"hello");
+
+    stream.write_all(b"hi").await.unwrap();
+    assert_eq!(
+        out.next().await.unwrap(),
+        ClientMessage::TcpSteal(LayerTcpSteal::Data(TcpData {
+            connection_id: 0,
+            bytes: b"hi".to_vec().into(),
+        })),
+    );
+}
+
+/// Verifies that [`IncomingProxy`] configured with [`OnLocalUnavailable::Reset`] closes a stolen
+/// connection that arrives before the user application starts listening.
+#[tokio::test]
+async fn stolen_connection_reset_without_local_listener() {
+    let local_addr = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let (conn, _, out) = Connection::dummy();
+    let proxy = IncomingProxy::new(
+        Duration::from_secs(3),
+        Default::default(),
+        OnLocalUnavailable::Reset,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+    let mut background_tasks: BackgroundTasks<(), ProxyMessage, IncomingProxyError> =
+        BackgroundTasks::new(conn.tx_handle());
+
+    let proxy = background_tasks.register(proxy, (), 8);
+
+    proxy
+        .send(IncomingProxyMessage::AgentProtocolVersion(
+            mirrord_protocol::VERSION.clone(),
+        ))
+        .await;
+    proxy
+        .send(IncomingProxyMessage::LayerRequest(
+            0,
+            LayerId(0),
+            IncomingRequest::PortSubscribe(PortSubscribe {
+                listening_on: local_addr,
+                subscription: PortSubscription::Steal(StealType::All(80)),
+            }),
+        ))
+        .await;
+    assert_eq!(
+        out.next().await.unwrap(),
+        ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(StealType::All(80))),
+    );
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(
+            DaemonTcp::SubscribeResult(Ok(80)),
+        ))
+        .await;
+    assert_eq!(
+        background_tasks.next().await.unwrap().1.unwrap_message(),
+        ProxyMessage::ToLayer(ToLayer {
+            message_id: 0,
+            layer_id: LayerId(0),
+            message: ProxyToLayerMessage::Incoming(IncomingResponse::PortSubscribe(Ok(())))
+        }),
+    );
+
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(
+            DaemonTcp::NewConnectionV2(NewTcpConnectionV2 {
+                connection: NewTcpConnectionV1 {
+                    connection_id: 0,
+                    remote_address: "1.1.1.1".parse().unwrap(),
+                    destination_port: 80,
+                    source_port: 55555,
+                    local_address: "2.2.2.2".parse().unwrap(),
+                },
+                transport: IncomingTrafficTransportType::Tcp,
+            }),
+        ))
+        .await;
+
+    assert_eq!(
+        tokio::time::timeout(Duration::from_secs(1), out.next())
+            .await
+            .unwrap()
+            .unwrap(),
+        ClientMessage::TcpSteal(LayerTcpSteal::ConnectionUnsubscribe(0)),
+    );
+}
+
+/// Starts an [`IncomingProxy`] with the given [`SourceIpDelivery`] and delivery source address,
+/// and steals a port for the user application listening on `local_addr`.
+async fn steal_with_source_ip_delivery(
+    conn: &Connection<Client>,
+    out: &ConnectionOutput<Client>,
+    source_ip_delivery: SourceIpDelivery,
+    delivery_source: Option<IpAddr>,
+    local_addr: std::net::SocketAddr,
+    steal_type: StealType,
+) -> (
+    TaskSender<IncomingProxy>,
+    BackgroundTasks<(), ProxyMessage, IncomingProxyError>,
+) {
+    let proxy = IncomingProxy::new(
+        Duration::from_secs(3),
+        Default::default(),
+        Default::default(),
+        source_ip_delivery,
+        delivery_source,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+    );
+    let mut background_tasks: BackgroundTasks<(), ProxyMessage, IncomingProxyError> =
+        BackgroundTasks::new(conn.tx_handle());
+
+    let proxy = background_tasks.register(proxy, (), 8);
+
+    proxy
+        .send(IncomingProxyMessage::AgentProtocolVersion(
+            mirrord_protocol::VERSION.clone(),
+        ))
+        .await;
+    proxy
+        .send(IncomingProxyMessage::LayerRequest(
+            0,
+            LayerId(0),
+            IncomingRequest::PortSubscribe(PortSubscribe {
+                listening_on: local_addr,
+                subscription: PortSubscription::Steal(steal_type.clone()),
+            }),
+        ))
+        .await;
+    assert_eq!(
+        out.next().await.unwrap(),
+        ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(steal_type)),
+    );
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(
+            DaemonTcp::SubscribeResult(Ok(80)),
+        ))
+        .await;
+    assert_eq!(
+        background_tasks.next().await.unwrap().1.unwrap_message(),
+        ProxyMessage::ToLayer(ToLayer {
+            message_id: 0,
+            layer_id: LayerId(0),
+            message: ProxyToLayerMessage::Incoming(IncomingResponse::PortSubscribe(Ok(())))
+        }),
+    );
+
+    (proxy, background_tasks)
+}
+
+/// Verifies that [`IncomingProxy`] configured with [`SourceIpDelivery::ProxyProtocol`] starts a
+/// stolen connection with a PROXY protocol v2 header that carries the original addresses.
+#[tokio::test]
+async fn stolen_connection_starts_with_proxy_protocol_header() {
+    let local_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let local_addr = local_listener.local_addr().unwrap();
+
+    let (conn, _, out) = Connection::dummy();
+    let (proxy, _background_tasks) = steal_with_source_ip_delivery(
+        &conn,
+        &out,
+        SourceIpDelivery::ProxyProtocol,
+        None,
+        local_addr,
+        StealType::All(80),
+    )
+    .await;
+
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(
+            DaemonTcp::NewConnectionV2(NewTcpConnectionV2 {
+                connection: NewTcpConnectionV1 {
+                    connection_id: 0,
+                    remote_address: "1.1.1.1".parse().unwrap(),
+                    destination_port: 80,
+                    source_port: 55555,
+                    local_address: "2.2.2.2".parse().unwrap(),
+                },
+                transport: IncomingTrafficTransportType::Tcp,
+            }),
+        ))
+        .await;
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(DaemonTcp::Data(TcpData {
+            connection_id: 0,
+            bytes: b"hello".to_vec().into(),
+        })))
+        .await;
+
+    let (mut stream, _) = tokio::time::timeout(Duration::from_secs(1), local_listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+
+    let expected_header = super::source_ip::proxy_protocol_v2_header(
+        "1.1.1.1:55555".parse().unwrap(),
+        "2.2.2.2:80".parse().unwrap(),
+    );
+    let mut buf = vec![0; expected_header.len() + 5];
+    stream.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf[..expected_header.len()], expected_header);
+    assert_eq!(&buf[expected_header.len()..], b"hello");
+}
+
+/// Verifies that [`IncomingProxy`] configured with [`SourceIpDelivery::XForwardedFor`] appends
+/// the original client IP to the `X-Forwarded-For` header of a stolen request.
+#[tokio::test]
+async fn stolen_request_has_x_forwarded_for() {
+    let local_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let local_addr = local_listener.local_addr().unwrap();
+
+    let (conn, _, out) = Connection::dummy();
+    let (proxy, _background_tasks) = steal_with_source_ip_delivery(
+        &conn,
+        &out,
+        SourceIpDelivery::XForwardedFor,
+        None,
+        local_addr,
+        StealType::FilteredHttpEx(80, HttpFilter::Method(HttpMethodFilter::Get)),
+    )
+    .await;
+
+    let mut headers = hyper::HeaderMap::new();
+    headers.insert(
+        super::source_ip::X_FORWARDED_FOR,
+        "10.0.0.1".parse().unwrap(),
+    );
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(
+            DaemonTcp::HttpRequestChunked(ChunkedRequest::StartV2(ChunkedRequestStartV2 {
+                connection_id: 0,
+                request_id: 0,
+                metadata: HttpRequestMetadata::V1 {
+                    source: "1.1.1.1:55555".parse().unwrap(),
+                    destination: "2.2.2.2:80".parse().unwrap(),
+                },
+                transport: IncomingTrafficTransportType::Tcp,
+                request: InternalHttpRequest {
+                    method: Method::GET,
+                    uri: "http://127.0.0.1:80/hello".parse().unwrap(),
+                    version: Version::HTTP_11,
+                    headers,
+                    body: InternalHttpBodyNew {
+                        frames: Default::default(),
+                        is_last: true,
+                    },
+                },
+            })),
+        ))
+        .await;
+
+    let (mut stream, _) = tokio::time::timeout(Duration::from_secs(1), local_listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+
+    let mut request = Vec::new();
+    while request.ends_with(b"\r\n\r\n").not() {
+        let mut buf = [0; 1024];
+        let read = stream.read(&mut buf).await.unwrap();
+        assert_ne!(
+            read, 0,
+            "connection closed before the end of the request head"
+        );
+        request.extend_from_slice(&buf[..read]);
+    }
+
+    let request = String::from_utf8(request).unwrap().to_lowercase();
+    assert!(
+        request.contains("\r\nx-forwarded-for: 10.0.0.1, 1.1.1.1\r\n"),
+        "{request}"
+    );
+}
+
+/// Verifies that [`IncomingProxy`] configured with a delivery source address makes the stolen
+/// connections and requests to the user application from that address.
+///
+/// Linux only, as other systems don't route the whole `127.0.0.0/8` to the loopback interface.
+#[cfg(target_os = "linux")]
+#[rstest]
+#[case::unfiltered(StealType::All(80))]
+#[case::filtered(StealType::FilteredHttpEx(80, HttpFilter::Method(HttpMethodFilter::Get)))]
+#[tokio::test]
+async fn stolen_traffic_from_delivery_source(#[case] steal_type: StealType) {
+    let local_listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let local_addr = local_listener.local_addr().unwrap();
+    let delivery_source: IpAddr = "127.0.0.2".parse().unwrap();
+
+    let (conn, _, out) = Connection::dummy();
+    let (proxy, _background_tasks) = steal_with_source_ip_delivery(
+        &conn,
+        &out,
+        SourceIpDelivery::None,
+        Some(delivery_source),
+        local_addr,
+        steal_type.clone(),
+    )
+    .await;
+
+    let message = match steal_type {
+        StealType::All(..) => new_connection(0),
+        _ => DaemonTcp::HttpRequestChunked(ChunkedRequest::StartV2(ChunkedRequestStartV2 {
+            connection_id: 0,
+            request_id: 0,
+            metadata: HttpRequestMetadata::V1 {
+                source: "1.1.1.1:55555".parse().unwrap(),
+                destination: "2.2.2.2:80".parse().unwrap(),
+            },
+            transport: IncomingTrafficTransportType::Tcp,
+            request: InternalHttpRequest {
+                method: Method::GET,
+                uri: "http://127.0.0.1:80/hello".parse().unwrap(),
+                version: Version::HTTP_11,
+                headers: Default::default(),
+                body: InternalHttpBodyNew {
+                    frames: Default::default(),
+                    is_last: true,
+                },
+            },
+        })),
+    };
+    proxy.send(IncomingProxyMessage::AgentSteal(message)).await;
+
+    let (_stream, peer) = tokio::time::timeout(Duration::from_secs(1), local_listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(peer.ip(), delivery_source);
+}
+
+/// Returns a [`DaemonTcp::NewConnectionV2`] for port 80.
+fn new_connection(connection_id: u64) -> DaemonTcp {
+    DaemonTcp::NewConnectionV2(NewTcpConnectionV2 {
+        connection: NewTcpConnectionV1 {
+            connection_id,
+            remote_address: "1.1.1.1".parse().unwrap(),
+            destination_port: 80,
+            source_port: 55555,
+            local_address: "2.2.2.2".parse().unwrap(),
+        },
+        transport: IncomingTrafficTransportType::Tcp,
+    })
+}
+
+/// Verifies that [`IncomingProxy`] configured with [`AutoDowngradeConfig`] stops stealing after
+/// a storm of stolen connections that fail to reach the user application, and that
+/// [`IncomingProxyMessage::ResumeSteal`] restores stealing. The downgrade is recorded in the
+/// [`SessionMetadata`] while it lasts.
+#[rstest]
+#[case::mirror(true)]
+#[case::off(false)]
+#[tokio::test]
+async fn auto_downgrade_on_delivery_failures(#[case] mirror: bool) {
+    let local_addr = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+
+    let metadata_dir = tempfile::tempdir().unwrap();
+    let metadata_path = metadata_dir.path().join("session.json");
+
+    let (conn, _, out) = Connection::dummy();
+    let proxy = IncomingProxy::new(
+        Duration::from_secs(3),
+        Default::default(),
+        OnLocalUnavailable::Reset,
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Some(AutoDowngradeConfig {
+            errors: 2,
+            window_secs: 60,
+            mirror,
+        }),
+        Default::default(),
+    )
+    .with_session_metadata(metadata_path.clone());
+    let mut background_tasks: BackgroundTasks<(), ProxyMessage, IncomingProxyError> =
+        BackgroundTasks::new(conn.tx_handle());
+
+    let proxy = background_tasks.register(proxy, (), 8);
+
+    proxy
+        .send(IncomingProxyMessage::AgentProtocolVersion(
+            mirrord_protocol::VERSION.clone(),
+        ))
+        .await;
+    proxy
+        .send(IncomingProxyMessage::LayerRequest(
+            0,
+            LayerId(0),
+            IncomingRequest::PortSubscribe(PortSubscribe {
+                listening_on: local_addr,
+                subscription: PortSubscription::Steal(StealType::All(80)),
+            }),
+        ))
+        .await;
+    assert_eq!(
+        out.next().await.unwrap(),
+        ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(StealType::All(80))),
+    );
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(
+            DaemonTcp::SubscribeResult(Ok(80)),
+        ))
+        .await;
+    assert_eq!(
+        background_tasks.next().await.unwrap().1.unwrap_message(),
+        ProxyMessage::ToLayer(ToLayer {
+            message_id: 0,
+            layer_id: LayerId(0),
+            message: ProxyToLayerMessage::Incoming(IncomingResponse::PortSubscribe(Ok(())))
+        }),
+    );
+
+    // The user application is not listening, so the stolen connections fail.
+    for connection_id in 0..2 {
+        proxy
+            .send(IncomingProxyMessage::AgentSteal(new_connection(
+                connection_id,
+            )))
+            .await;
+        assert_eq!(
+            out.next().await.unwrap(),
+            ClientMessage::TcpSteal(LayerTcpSteal::ConnectionUnsubscribe(connection_id)),
+        );
+    }
+
+    // One failure too many.
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(new_connection(2)))
+        .await;
+    assert_eq!(
+        out.next().await.unwrap(),
+        ClientMessage::TcpSteal(LayerTcpSteal::PortUnsubscribe(80)),
+    );
+    if mirror {
+        assert_eq!(
+            out.next().await.unwrap(),
+            ClientMessage::Tcp(LayerTcp::PortSubscribe(80)),
+        );
+    }
+    assert_eq!(
+        out.next().await.unwrap(),
+        ClientMessage::TcpSteal(LayerTcpSteal::ConnectionUnsubscribe(2)),
+    );
+    let message = background_tasks.next().await.unwrap().1.unwrap_message();
+    assert!(
+        matches!(
+            &message,
+            ProxyMessage::ToLayer(ToLayer {
+                layer_id: LayerId(0),
+                message: ProxyToLayerMessage::LogMessage(..),
+                ..
+            })
+        ),
+        "{message:?}"
+    );
+    let steal_downgrade = SessionMetadata::load(&metadata_path)
+        .unwrap()
+        .steal_downgrade
+        .unwrap();
+    assert_eq!(steal_downgrade.mirror, mirror);
+
+    // Stolen traffic is stale now.
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(new_connection(3)))
+        .await;
+    assert_eq!(
+        out.next().await.unwrap(),
+        ClientMessage::TcpSteal(LayerTcpSteal::ConnectionUnsubscribe(3)),
+    );
+
+    if mirror {
+        proxy
+            .send(IncomingProxyMessage::AgentMirror(
+                DaemonTcp::SubscribeResult(Ok(80)),
+            ))
+            .await;
+
+        // Mirrored traffic reaches the user application.
+        let local_listener = TcpListener::bind(local_addr).await.unwrap();
+        proxy
+            .send(IncomingProxyMessage::AgentMirror(new_connection(4)))
+            .await;
+        tokio::time::timeout(Duration::from_secs(1), local_listener.accept())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    proxy.send(IncomingProxyMessage::ResumeSteal).await;
+    if mirror {
+        assert_eq!(
+            out.next().await.unwrap(),
+            ClientMessage::Tcp(LayerTcp::PortUnsubscribe(80)),
+        );
+    }
+    assert_eq!(
+        out.next().await.unwrap(),
+        ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(StealType::All(80))),
+    );
+    assert_eq!(
+        SessionMetadata::load(&metadata_path).unwrap(),
+        SessionMetadata::default()
+    );
+}
+
+/// Starts an [`IncomingProxy`] that steals connections from the remote unix socket at
+/// `/app.sock`, for the user application's socket at `local_path`.
+async fn steal_unix_socket(
+    conn: &Connection<Client>,
+    out: &ConnectionOutput<Client>,
+    local_path: PathBuf,
+) -> (
+    TaskSender<IncomingProxy>,
+    BackgroundTasks<(), ProxyMessage, IncomingProxyError>,
+) {
+    let proxy = IncomingProxy::new(
+        Duration::from_secs(3),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        Default::default(),
+        [(PathBuf::from("/app.sock"), local_path)].into(),
+    );
+    let mut background_tasks: BackgroundTasks<(), ProxyMessage, IncomingProxyError> =
+        BackgroundTasks::new(conn.tx_handle());
+
+    let proxy = background_tasks.register(proxy, (), 8);
+
+    proxy
+        .send(IncomingProxyMessage::AgentProtocolVersion(
+            mirrord_protocol::VERSION.clone(),
+        ))
+        .await;
+    assert_eq!(
+        out.next().await.unwrap(),
+        ClientMessage::TcpSteal(LayerTcpSteal::UnixSubscribe("/app.sock".into())),
+    );
+
+    (proxy, background_tasks)
+}
+
+/// Verifies that [`IncomingProxy`] delivers a connection stolen from a remote unix socket to the
+/// user application's socket, and that the data is passed both ways.
+#[cfg(not(target_os = "windows"))]
+#[tokio::test]
+async fn stolen_unix_connection_proxied() {
+    let dir = tempfile::tempdir().unwrap();
+    let local_path = dir.path().join("local.sock");
+    let local_listener = tokio::net::UnixListener::bind(&local_path).unwrap();
+
+    let (conn, _, out) = Connection::dummy();
+    let (proxy, _background_tasks) = steal_unix_socket(&conn, &out, local_path).await;
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(
+            DaemonTcp::UnixSubscribeResult(Ok("/app.sock".into())),
+        ))
+        .await;
+
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(
+            DaemonTcp::NewUnixConnection(NewUnixConnection {
+                connection_id: 0,
+                path: "/app.sock".into(),
+            }),
+        ))
+        .await;
+    let (mut stream, _) = tokio::time::timeout(Duration::from_secs(1), local_listener.accept())
+        .await
+        .unwrap()
+        .unwrap();
+
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(DaemonTcp::Data(TcpData {
+            connection_id: 0,
+            bytes: b"hello".to_vec().into(),
+        })))
+        .await;
+    let mut buf = [0; 5];
+    stream.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"hello");
+
+    stream.write_all(b"hi").await.unwrap();
+    assert_eq!(
+        out.next().await.unwrap(),
+        ClientMessage::TcpSteal(LayerTcpSteal::Data(TcpData {
+            connection_id: 0,
+            bytes: b"hi".to_vec().into(),
+        })),
+    );
+
+    // The connection is closed when the agent closes it.
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(DaemonTcp::Close(
+            TcpClose { connection_id: 0 },
+        )))
+        .await;
+    let mut rest = Vec::new();
+    let read = tokio::time::timeout(Duration::from_secs(1), stream.read_to_end(&mut rest))
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(read, 0);
+}
+
+/// Verifies that [`IncomingProxy`] unsubscribes from a connection stolen from a remote unix
+/// socket, when the socket is not configured or the user application is not listening on it.
+#[cfg(not(target_os = "windows"))]
+#[rstest]
+#[case::unknown_socket("/other.sock")]
+#[case::not_listening("/app.sock")]
+#[tokio::test]
+async fn stolen_unix_connection_unsubscribed(#[case] remote_path: &str) {
+    let dir = tempfile::tempdir().unwrap();
+
+    let (conn, _, out) = Connection::dummy();
+    let (proxy, _background_tasks) =
+        steal_unix_socket(&conn, &out, dir.path().join("local.sock")).await;
+
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(
+            DaemonTcp::NewUnixConnection(NewUnixConnection {
+                connection_id: 0,
+                path: remote_path.into(),
+            }),
+        ))
+        .await;
+    assert_eq!(
+        tokio::time::timeout(Duration::from_secs(1), out.next())
+            .await
+            .unwrap()
+            .unwrap(),
+        ClientMessage::TcpSteal(LayerTcpSteal::ConnectionUnsubscribe(0)),
+    );
+}
+
+/// Verifies that [`IncomingProxy`] lets the user know when stealing from a remote unix socket
+/// fails.
+#[tokio::test]
+async fn unix_subscribe_failure_reported() {
+    let (conn, _, out) = Connection::dummy();
+    let (proxy, mut background_tasks) =
+        steal_unix_socket(&conn, &out, "/tmp/local.sock".into()).await;
+
+    proxy
+        .send(IncomingProxyMessage::AgentSteal(
+            DaemonTcp::UnixSubscribeResult(Err(ResponseError::NotImplemented)),
+        ))
+        .await;
+    let message = background_tasks.next().await.unwrap().1.unwrap_message();
+    assert!(
+        matches!(&message, ProxyMessage::WarnLayers(..)),
+        "{message:?}"
+    );
+}
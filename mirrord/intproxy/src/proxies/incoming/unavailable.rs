@@ -0,0 +1,95 @@
+//! Handling of stolen traffic that arrives when the user application is not listening on the port,
+//! configured with
+//! [`feature.network.incoming.
+//! on_local_unavailable`](mirrord_config::feature::network::incoming::IncomingConfig::on_local_unavailable).
+
+use std::{
+    ops::Not,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use mirrord_config::feature::network::incoming::OnLocalUnavailable;
+use tokio::{sync::Notify, time};
+use tokio_retry::strategy::ExponentialBackoff;
+
+/// With [`OnLocalUnavailable::Hold`], how long we wait for the user application to start
+/// listening.
+pub const HOLD_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// With [`OnLocalUnavailable::Hold`], how much data received from the agent a
+/// [`TcpProxyTask`](super::tcp_proxy::TcpProxyTask) can buffer while waiting for the user
+/// application to start listening.
+pub const HOLD_MAX_BYTES: usize = 1024 * 1024;
+
+/// Shared by the [`IncomingProxy`](super::IncomingProxy) with its tasks.
+#[derive(Clone, Debug, Default)]
+pub struct LocalUnavailable {
+    mode: OnLocalUnavailable,
+    /// Notified when a layer subscribes a port, which means that the user application just
+    /// started listening.
+    listeners: Arc<Notify>,
+}
+
+impl LocalUnavailable {
+    pub fn new(mode: OnLocalUnavailable) -> Self {
+        Self {
+            mode,
+            listeners: Default::default(),
+        }
+    }
+
+    pub fn mode(&self) -> OnLocalUnavailable {
+        self.mode
+    }
+
+    /// Wakes all [`HoldRetry`]s, so that they make their next connect attempts immediately.
+    pub fn listener_added(&self) {
+        self.listeners.notify_waiters();
+    }
+
+    /// Starts waiting for the user application, with [`HOLD_TIMEOUT`] counted from now.
+    pub fn hold(&self) -> HoldRetry {
+        HoldRetry {
+            // 50ms, 100ms, 200ms, 400ms, 500ms, 500ms, ...
+            backoffs: ExponentialBackoff::from_millis(2)
+                .factor(25)
+                .max_delay(Duration::from_millis(500)),
+            deadline: Instant::now() + HOLD_TIMEOUT,
+            listeners: self.listeners.clone(),
+        }
+    }
+}
+
+/// Paces connect attempts while we wait for the user application to start listening.
+///
+/// The next attempt is made after a backoff, or as soon as a layer subscribes a port.
+#[derive(Debug)]
+pub struct HoldRetry {
+    backoffs: ExponentialBackoff,
+    deadline: Instant,
+    listeners: Arc<Notify>,
+}
+
+impl HoldRetry {
+    /// Waits until the next connect attempt.
+    ///
+    /// Returns `false` when we've already waited for [`HOLD_TIMEOUT`].
+    pub async fn wait(&mut self) -> bool {
+        let Some(remaining) = self
+            .deadline
+            .checked_duration_since(Instant::now())
+            .filter(|remaining| remaining.is_zero().not())
+        else {
+            return false;
+        };
+        let backoff = self.backoffs.next().unwrap_or(remaining).min(remaining);
+
+        tokio::select! {
+            _ = time::sleep(backoff) => {},
+            _ = self.listeners.notified() => {},
+        }
+
+        true
+    }
+}
@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use mirrord_protocol::ConnectionId;
+use tracing::Level;
+
+use super::{
+    tasks::{InProxyTaskError, InProxyTaskMessage},
+    tcp_proxy::proxy_stream,
+};
+use crate::background_tasks::{BackgroundTask, MessageBus};
+
+/// [`BackgroundTask`] of [`IncomingProxy`](super::IncomingProxy) that handles a remote
+/// connection stolen from a unix socket in the target.
+///
+/// Connects to the unix socket at [`Self::path`], and exits immediately when its
+/// [`TaskSender`](crate::background_tasks::TaskSender) is dropped.
+#[derive(Debug)]
+pub struct UnixProxyTask {
+    /// ID of the remote connection this task handles.
+    connection_id: ConnectionId,
+    /// Path of the local unix socket of the user application.
+    path: PathBuf,
+}
+
+impl UnixProxyTask {
+    pub fn new(connection_id: ConnectionId, path: PathBuf) -> Self {
+        Self {
+            connection_id,
+            path,
+        }
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    async fn connect(&self) -> std::io::Result<tokio::net::UnixStream> {
+        tokio::net::UnixStream::connect(&self.path).await
+    }
+
+    #[cfg(target_os = "windows")]
+    async fn connect(&self) -> std::io::Result<tokio::net::TcpStream> {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "unix sockets are not supported on this platform",
+        ))
+    }
+}
+
+impl BackgroundTask for UnixProxyTask {
+    type Error = InProxyTaskError;
+    type MessageIn = Vec<u8>;
+    type MessageOut = InProxyTaskMessage;
+
+    #[tracing::instrument(
+        level = Level::DEBUG, name = "unix_proxy_task_main_loop",
+        skip(message_bus),
+        ret, err(level = Level::WARN),
+    )]
+    async fn run(&mut self, message_bus: &mut MessageBus<Self>) -> Result<(), Self::Error> {
+        let stream = self.connect().await?;
+        proxy_stream(stream, self.connection_id, false, message_bus).await
+    }
+}
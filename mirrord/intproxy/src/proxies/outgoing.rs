@@ -1,6 +1,12 @@
 //! Handles the logic of the `outgoing` feature.
 
-use std::{collections::HashMap, fmt, io, net::SocketAddr, time::Instant};
+use std::{
+    collections::HashMap,
+    fmt, io,
+    net::SocketAddr,
+    ops::Not,
+    time::{Duration, Instant},
+};
 
 use bytes::Bytes;
 use mirrord_intproxy_protocol::{
@@ -8,10 +14,12 @@ use mirrord_intproxy_protocol::{
     OutgoingConnectResponse, OutgoingRequest, OutgoingResponse, ProxyToLayerMessage,
 };
 use mirrord_protocol::{
-    ConnectionId, DaemonMessage, RemoteResult, ResponseError,
+    ClientMessage, ConnectionId, DaemonMessage, Payload, RemoteResult, ResponseError,
     outgoing::{
-        DaemonConnect, DaemonConnectV2, DaemonRead, OUTGOING_CONNECT_V2, SocketAddress,
-        tcp::DaemonTcpOutgoing, udp::DaemonUdpOutgoing,
+        DaemonConnect, DaemonConnectV2, DaemonRead, LayerWriteBatch, OUTGOING_CONNECT_V2,
+        OUTGOING_UNIX_SEQPACKET_VERSION, SocketAddress, UDP_WRITE_BATCH_VERSION,
+        tcp::DaemonTcpOutgoing,
+        udp::{DaemonUdpOutgoing, LayerUdpOutgoing},
     },
     uid::Uid,
 };
@@ -26,6 +34,7 @@ use crate::{
         BackgroundTask, BackgroundTasks, MessageBus, TaskError, TaskSender, TaskUpdate,
     },
     error::{UnexpectedAgentMessage, agent_lost_io_error},
+    fault_injection::{DelayedRequests, FaultInjector},
     main_tasks::{ConnectionRefresh, LayerClosed, LayerForked, ToLayer},
     proxies::outgoing::{
         busy_tcp_listener::{BusyListenerMethod, BusyTcpListener},
@@ -62,7 +71,8 @@ pub enum OutgoingProxyError {
 pub struct InterceptorId {
     /// Id of the intercepted connection.
     pub connection_id: ConnectionId,
-    /// Network protocol used.
+    /// Network protocol used in the messages exchanged with the agent, see
+    /// [`NetProtocolExt::agent_protocol`].
     pub protocol: NetProtocol,
 }
 
@@ -95,6 +105,11 @@ impl From<AgentLostOutgoingResponse> for ToLayer {
 
 #[derive(Debug)]
 struct ConnectInProgress {
+    /// Protocol requested by the layer.
+    ///
+    /// May be different from the protocol used in the messages exchanged with the agent, see
+    /// [`NetProtocolExt::agent_protocol`].
+    protocol: NetProtocol,
     prepared_socket: Option<BusyTcpListener>,
     remote_address: SocketAddress,
     requested_at: Instant,
@@ -103,6 +118,31 @@ struct ConnectInProgress {
     id: u128,
 }
 
+/// Outgoing UDP datagrams waiting to be sent to the agent as a single [`LayerWriteBatch`].
+#[derive(Default)]
+struct UdpBatch {
+    payloads: Vec<Payload>,
+    /// Total length of all [`Self::payloads`].
+    bytes: usize,
+}
+
+impl UdpBatch {
+    /// Produces a message for the agent. Batches with a single datagram are sent as a regular
+    /// [`LayerWrite`](mirrord_protocol::outgoing::LayerWrite).
+    fn into_agent_message(mut self, connection_id: ConnectionId) -> ClientMessage {
+        if self.payloads.len() == 1
+            && let Some(bytes) = self.payloads.pop()
+        {
+            return NetProtocol::Datagrams.wrap_agent_write(connection_id, bytes.0);
+        }
+
+        ClientMessage::UdpOutgoing(LayerUdpOutgoing::WriteBatch(LayerWriteBatch {
+            connection_id,
+            payloads: self.payloads,
+        }))
+    }
+}
+
 /// Handles logic and state of the `outgoing` feature.
 ///
 /// Run as a [`BackgroundTask`].
@@ -156,6 +196,14 @@ struct ConnectInProgress {
 /// 4. If the [`OutgoingProxy`] does not use the non-blocking flow, each outgoing connect attempt
 ///    will effectively *freeze* the NodeJS reactor for some time (observed in real life to be over
 ///    200ms). Latency goes through the roof. Also, it affects all other async tasks/promises.
+///
+/// # UDP batching
+///
+/// Applications that emit many small datagrams (e.g. metrics sent to a statsd server) would
+/// otherwise produce one agent message per datagram. When the agent supports
+/// [`UDP_WRITE_BATCH_VERSION`], datagrams received from the layer are held for up to
+/// `udp_batch_window`, and all datagrams for the same connection are sent in one
+/// [`LayerWriteBatch`]. The agent sends them to the peer one by one, in order.
 pub struct OutgoingProxy {
     /// In progress [`OutgoingConnectRequest`]s originating from
     /// [`LayerConnect`](mirrord_protocol::outgoing::LayerConnect), related to
@@ -193,6 +241,15 @@ pub struct OutgoingProxy {
     /// Delay to apply to transmit operations (Layer → Agent), in milliseconds.
     transmit_delay_ms: u64,
 
+    /// For how long outgoing UDP datagrams can be held before being sent to the agent.
+    ///
+    /// [`Duration::ZERO`] disables batching.
+    udp_batch_window: Duration,
+    /// Outgoing UDP datagrams waiting to be sent to the agent.
+    udp_batches: HashMap<ConnectionId, UdpBatch>,
+    /// When [`Self::udp_batches`] should be flushed.
+    udp_flush_at: Option<tokio::time::Instant>,
+
     /// Outgoing connection local IDs, by layer instance.
     ///
     /// Local IDs are random and generated in this proxy.
@@ -201,12 +258,20 @@ pub struct OutgoingProxy {
     connections_in_layers: RemoteResources<u128>,
     /// Maps outgoing connection local IDs to local addresses of corresponding agent sockets.
     agent_local_addresses: HashMap<u128, SocketAddr>,
+
+    /// Synthetic faults injected into [`OutgoingConnectRequest`]s.
+    connect_faults: FaultInjector,
+    /// [`OutgoingConnectRequest`]s held back by the [`Self::connect_faults`] delay.
+    delayed_connects: DelayedRequests<(OutgoingConnectRequest, LayerId, MessageId)>,
 }
 
 impl OutgoingProxy {
     /// Used when registering new [`Interceptor`] tasks in the [`BackgroundTasks`] struct.
     const CHANNEL_SIZE: usize = 512;
 
+    /// Batches of outgoing UDP datagrams are flushed early when they reach this size.
+    const MAX_UDP_BATCH_BYTES: usize = 64 * 1024;
+
     /// Creates a new instance, ready to run.
     ///
     /// # Params
@@ -214,10 +279,14 @@ impl OutgoingProxy {
     /// * `non_blocking_tcp_connect` - see struct level docs
     /// * `receive_delay_ms` - delay in milliseconds for receive operations (Agent → Layer)
     /// * `transmit_delay_ms` - delay in milliseconds for transmit operations (Layer → Agent)
+    /// * `udp_batch_window` - see struct level docs, [`Duration::ZERO`] disables UDP batching
+    /// * `connect_faults` - synthetic faults injected into connect requests
     pub fn new(
         non_blocking_tcp_connect: bool,
         receive_delay_ms: u64,
         transmit_delay_ms: u64,
+        udp_batch_window: Duration,
+        connect_faults: FaultInjector,
     ) -> Self {
         if non_blocking_tcp_connect {
             // First call to `get_working_method` might take a while.
@@ -235,8 +304,13 @@ impl OutgoingProxy {
             protocol_version: Default::default(),
             receive_delay_ms,
             transmit_delay_ms,
+            udp_batch_window,
+            udp_batches: Default::default(),
+            udp_flush_at: None,
             connections_in_layers: Default::default(),
             agent_local_addresses: Default::default(),
+            connect_faults,
+            delayed_connects: Default::default(),
         }
     }
 
@@ -244,7 +318,67 @@ impl OutgoingProxy {
     fn queue(&mut self, protocol: NetProtocol) -> &mut RequestQueue<ConnectInProgress> {
         match protocol {
             NetProtocol::Datagrams => &mut self.datagrams_reqs,
-            NetProtocol::Stream => &mut self.stream_reqs,
+            NetProtocol::Stream | NetProtocol::SeqPacket => &mut self.stream_reqs,
+        }
+    }
+
+    /// Whether outgoing UDP datagrams should be batched, see struct level docs.
+    fn udp_batching_enabled(&self) -> bool {
+        !self.udp_batch_window.is_zero()
+            && self
+                .protocol_version
+                .as_ref()
+                .is_some_and(|version| UDP_WRITE_BATCH_VERSION.matches(version))
+    }
+
+    /// Sends the data received from an [`Interceptor`] task to the agent.
+    ///
+    /// UDP datagrams may be held in [`Self::udp_batches`], see struct level docs.
+    async fn handle_interceptor_data(
+        &mut self,
+        id: InterceptorId,
+        bytes: Bytes,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        if id.protocol == NetProtocol::Stream || !self.udp_batching_enabled() {
+            let msg = id.protocol.wrap_agent_write(id.connection_id, bytes);
+            message_bus.send_agent(msg).await;
+            return;
+        }
+
+        let batch = self.udp_batches.entry(id.connection_id).or_default();
+        batch.bytes += bytes.len();
+        batch.payloads.push(bytes.into());
+
+        if batch.bytes >= Self::MAX_UDP_BATCH_BYTES {
+            self.flush_udp_batch(id.connection_id, message_bus).await;
+        } else if self.udp_flush_at.is_none() {
+            self.udp_flush_at = Some(tokio::time::Instant::now() + self.udp_batch_window);
+        }
+    }
+
+    /// Sends the pending UDP datagrams of the given connection to the agent.
+    async fn flush_udp_batch(
+        &mut self,
+        connection_id: ConnectionId,
+        message_bus: &mut MessageBus<Self>,
+    ) {
+        if let Some(batch) = self.udp_batches.remove(&connection_id) {
+            message_bus
+                .send_agent(batch.into_agent_message(connection_id))
+                .await;
+        }
+    }
+
+    /// Sends all pending UDP datagrams to the agent.
+    #[tracing::instrument(level = Level::TRACE, skip_all, fields(connections = self.udp_batches.len()))]
+    async fn flush_udp_batches(&mut self, message_bus: &mut MessageBus<Self>) {
+        self.udp_flush_at = None;
+
+        for (connection_id, batch) in std::mem::take(&mut self.udp_batches) {
+            message_bus
+                .send_agent(batch.into_agent_message(connection_id))
+                .await;
         }
     }
 
@@ -313,13 +447,13 @@ impl OutgoingProxy {
                 (None, NetProtocol::Datagrams) => {
                     DaemonMessage::UdpOutgoing(DaemonUdpOutgoing::Connect(connect))
                 }
-                (Some(uid), NetProtocol::Stream) => {
+                (Some(uid), NetProtocol::Stream | NetProtocol::SeqPacket) => {
                     DaemonMessage::TcpOutgoing(DaemonTcpOutgoing::ConnectV2(DaemonConnectV2 {
                         uid,
                         connect,
                     }))
                 }
-                (None, NetProtocol::Stream) => {
+                (None, NetProtocol::Stream | NetProtocol::SeqPacket) => {
                     DaemonMessage::TcpOutgoing(DaemonTcpOutgoing::Connect(connect))
                 }
             };
@@ -370,7 +504,7 @@ impl OutgoingProxy {
         let prepared_socket = match in_progress.prepared_socket {
             Some(socket) => PreparedSocket::BusyTcpListener(socket),
             None => {
-                let prepared_socket = protocol.prepare_socket(remote_address).await?;
+                let prepared_socket = in_progress.protocol.prepare_socket(remote_address).await?;
                 let layer_address = prepared_socket.local_address()?;
 
                 message_bus
@@ -419,6 +553,35 @@ impl OutgoingProxy {
         request: OutgoingConnectRequest,
         message_bus: &mut MessageBus<Self>,
     ) -> Result<(), OutgoingProxyError> {
+        if request.protocol == NetProtocol::SeqPacket
+            && self
+                .protocol_version
+                .as_ref()
+                .is_none_or(|version| OUTGOING_UNIX_SEQPACKET_VERSION.matches(version).not())
+        {
+            let to_layer = ToLayer {
+                message_id,
+                layer_id: session_id,
+                message: ProxyToLayerMessage::Outgoing(OutgoingResponse::Connect(Err(
+                    ResponseError::NotImplemented,
+                ))),
+            };
+            message_bus.send(to_layer).await;
+            return Ok(());
+        }
+
+        if self.connect_faults.fails() {
+            let to_layer = ToLayer {
+                message_id,
+                layer_id: session_id,
+                message: ProxyToLayerMessage::Outgoing(OutgoingResponse::Connect(Err(
+                    FaultInjector::connect_error(),
+                ))),
+            };
+            message_bus.send(to_layer).await;
+            return Ok(());
+        }
+
         let prepared_socket = if self.non_blocking_tcp_connect
             && matches!(&request.remote_address, SocketAddress::Ip(..))
             && request.protocol == NetProtocol::Stream
@@ -464,8 +627,9 @@ impl OutgoingProxy {
         {
             let request_uid = Uid::new_v4();
             self.v2_reqs.insert(
-                (request_uid, request.protocol),
+                (request_uid, request.protocol.agent_protocol()),
                 ConnectInProgress {
+                    protocol: request.protocol,
                     prepared_socket,
                     remote_address: request.remote_address.clone(),
                     requested_at: Instant::now(),
@@ -476,18 +640,20 @@ impl OutgoingProxy {
             );
             Some(request_uid)
         } else {
-            self.queue(request.protocol).push_back_with_data(
-                message_id,
-                session_id,
-                ConnectInProgress {
-                    id: connection_id,
-                    prepared_socket,
-                    remote_address: request.remote_address.clone(),
-                    requested_at: Instant::now(),
-                    layer_id: session_id,
+            self.queue(request.protocol.agent_protocol())
+                .push_back_with_data(
                     message_id,
-                },
-            );
+                    session_id,
+                    ConnectInProgress {
+                        protocol: request.protocol,
+                        id: connection_id,
+                        prepared_socket,
+                        remote_address: request.remote_address.clone(),
+                        requested_at: Instant::now(),
+                        layer_id: session_id,
+                        message_id,
+                    },
+                );
             None
         };
 
@@ -510,6 +676,8 @@ impl OutgoingProxy {
                 tracing::debug!("Closing all local connections");
                 self.txs.clear();
                 self.background_tasks.as_mut().unwrap().clear();
+                self.udp_batches.clear();
+                self.udp_flush_at = None;
                 self.protocol_version = None;
 
                 tracing::debug!(
@@ -569,10 +737,17 @@ impl OutgoingProxy {
         message_bus: &mut MessageBus<Self>,
     ) -> Result<(), OutgoingProxyError> {
         match request {
-            OutgoingRequest::Connect(req) => {
-                self.handle_connect_request(message_id, layer_id, req, message_bus)
-                    .await
-            }
+            OutgoingRequest::Connect(req) => match self.connect_faults.delay() {
+                Some(delay) => {
+                    self.delayed_connects
+                        .push(delay, (req, layer_id, message_id));
+                    Ok(())
+                }
+                None => {
+                    self.handle_connect_request(message_id, layer_id, req, message_bus)
+                        .await
+                }
+            },
             OutgoingRequest::ConnMetadata(req) => {
                 let response =
                     self.agent_local_addresses.get(&req.conn_id).copied().map(
@@ -648,6 +823,7 @@ impl BackgroundTask for OutgoingProxy {
                         DaemonUdpOutgoing::Close(close) => {
                             let id = InterceptorId { connection_id: close, protocol: NetProtocol::Datagrams};
                             self.txs.remove(&id);
+                            self.udp_batches.remove(&close);
                         }
                         DaemonUdpOutgoing::Read(read) => self.handle_agent_read(read, NetProtocol::Datagrams).await?,
                         DaemonUdpOutgoing::Connect(connect) => self.handle_connect_response(connect, NetProtocol::Datagrams, None, message_bus).await?,
@@ -682,8 +858,7 @@ impl BackgroundTask for OutgoingProxy {
                             tokio::time::sleep(std::time::Duration::from_millis(self.transmit_delay_ms)).await;
                         }
 
-                        let msg = id.protocol.wrap_agent_write(id.connection_id, bytes);
-                        message_bus.send_agent(msg).await;
+                        self.handle_interceptor_data(id, bytes, message_bus).await;
                     }
                     (id, TaskUpdate::Finished(res)) => {
                         match res {
@@ -696,6 +871,10 @@ impl BackgroundTask for OutgoingProxy {
                             }
                         }
 
+                        if id.protocol == NetProtocol::Datagrams {
+                            self.flush_udp_batch(id.connection_id, message_bus).await;
+                        }
+
                         if self.txs.remove(&id).is_some() {
                             tracing::trace!(%id, "Local connection closed, notifying the agent");
                             let msg = id.protocol.wrap_agent_close(id.connection_id);
@@ -704,6 +883,14 @@ impl BackgroundTask for OutgoingProxy {
                         }
                     }
                 },
+
+                (request, layer_id, message_id) = self.delayed_connects.next() => {
+                    self.handle_connect_request(message_id, layer_id, request, message_bus).await?;
+                },
+
+                _ = tokio::time::sleep_until(self.udp_flush_at.unwrap_or_else(tokio::time::Instant::now)), if self.udp_flush_at.is_some() => {
+                    self.flush_udp_batches(message_bus).await;
+                },
             }
         }
     }
@@ -711,23 +898,31 @@ impl BackgroundTask for OutgoingProxy {
 
 #[cfg(test)]
 mod test {
-    use std::net::SocketAddr;
+    use std::{
+        net::{Ipv4Addr, SocketAddr},
+        time::Duration,
+    };
 
+    use mirrord_config::experimental::FaultConfig;
     use mirrord_intproxy_protocol::{
-        LayerId, NetProtocol, OutgoingConnectRequest, OutgoingRequest, OutgoingResponse,
-        ProxyToLayerMessage,
+        LayerId, NetProtocol, OutgoingConnectRequest, OutgoingConnectResponse, OutgoingRequest,
+        OutgoingResponse, ProxyToLayerMessage,
     };
     use mirrord_protocol::{
         ClientMessage,
         outgoing::{
-            DaemonConnect, LayerConnect, SocketAddress,
+            DaemonConnect, DaemonConnectV2, LayerConnect, LayerConnectV2, LayerWrite,
+            LayerWriteBatch, SocketAddress,
             tcp::{DaemonTcpOutgoing, LayerTcpOutgoing},
+            udp::{DaemonUdpOutgoing, LayerUdpOutgoing},
         },
     };
     use mirrord_protocol_io::Connection;
+    use tokio::net::UdpSocket;
 
     use crate::{
         background_tasks::{BackgroundTasks, TaskUpdate},
+        fault_injection::FaultInjector,
         main_tasks::{ConnectionRefresh, ProxyMessage, ToLayer},
         proxies::outgoing::{OutgoingProxy, OutgoingProxyError, OutgoingProxyMessage},
     };
@@ -741,7 +936,11 @@ mod test {
 
         let mut background_tasks: BackgroundTasks<(), ProxyMessage, OutgoingProxyError> =
             BackgroundTasks::new(connection.tx_handle());
-        let outgoing = background_tasks.register(OutgoingProxy::new(false, 0, 0), (), 8);
+        let outgoing = background_tasks.register(
+            OutgoingProxy::new(false, 0, 0, Duration::ZERO, Default::default()),
+            (),
+            8,
+        );
 
         for i in 0..=1 {
             // Layer wants to make an outgoing connection.
@@ -805,4 +1004,294 @@ mod test {
             other => panic!("unexpected update from the outgoing proxy: {other:?}"),
         }
     }
+
+    /// Verifies that connect requests fail without reaching the agent, when
+    /// `experimental.fault_injection.outgoing_connect` is configured to always fail.
+    #[tokio::test]
+    async fn connect_fault_injected() {
+        let peer_addr = "1.1.1.1:80".parse::<SocketAddr>().unwrap();
+        let (connection, _, _out) = Connection::dummy();
+
+        let mut background_tasks: BackgroundTasks<(), ProxyMessage, OutgoingProxyError> =
+            BackgroundTasks::new(connection.tx_handle());
+        let faults = FaultInjector::new(&FaultConfig {
+            delay: 0,
+            failure_percent: 100,
+        });
+        let outgoing = background_tasks.register(
+            OutgoingProxy::new(false, 0, 0, Duration::ZERO, faults),
+            (),
+            8,
+        );
+
+        outgoing
+            .send(OutgoingProxyMessage::Layer(
+                OutgoingRequest::Connect(OutgoingConnectRequest {
+                    remote_address: SocketAddress::Ip(peer_addr),
+                    protocol: NetProtocol::Stream,
+                }),
+                0,
+                LayerId(0),
+            ))
+            .await;
+
+        let message = background_tasks.next().await.unwrap().1.unwrap_message();
+        match message {
+            ProxyMessage::ToLayer(ToLayer {
+                message_id: 0,
+                layer_id: LayerId(0),
+                message: ProxyToLayerMessage::Outgoing(OutgoingResponse::Connect(Err(error))),
+            }) => assert_eq!(error, FaultInjector::connect_error()),
+            other => panic!("unexpected message from outgoing proxy: {other:?}"),
+        }
+    }
+
+    /// Verifies that outgoing UDP datagrams sent in a burst are coalesced into fewer agent
+    /// messages, and that the agent still receives every datagram, unchanged and in order.
+    #[tokio::test]
+    async fn udp_datagrams_batched() {
+        const DATAGRAMS: usize = 100;
+
+        let peer_addr = "1.1.1.1:8125".parse::<SocketAddr>().unwrap();
+        let (connection, _, out) = Connection::dummy();
+
+        let mut background_tasks: BackgroundTasks<(), ProxyMessage, OutgoingProxyError> =
+            BackgroundTasks::new(connection.tx_handle());
+        let outgoing = background_tasks.register(
+            OutgoingProxy::new(false, 0, 0, Duration::from_millis(50), Default::default()),
+            (),
+            8,
+        );
+        outgoing
+            .send(OutgoingProxyMessage::AgentProtocolVersion(
+                mirrord_protocol::VERSION.clone(),
+            ))
+            .await;
+
+        outgoing
+            .send(OutgoingProxyMessage::Layer(
+                OutgoingRequest::Connect(OutgoingConnectRequest {
+                    remote_address: SocketAddress::Ip(peer_addr),
+                    protocol: NetProtocol::Datagrams,
+                }),
+                0,
+                LayerId(0),
+            ))
+            .await;
+        let uid = match out.next().await.unwrap() {
+            ClientMessage::UdpOutgoing(LayerUdpOutgoing::ConnectV2(LayerConnectV2 {
+                uid,
+                remote_address,
+            })) => {
+                assert_eq!(remote_address, SocketAddress::Ip(peer_addr));
+                uid
+            }
+            other => panic!("unexpected client message from outgoing proxy: {other:?}"),
+        };
+
+        outgoing
+            .send(OutgoingProxyMessage::AgentDatagrams(
+                DaemonUdpOutgoing::ConnectV2(DaemonConnectV2 {
+                    uid,
+                    connect: Ok(DaemonConnect {
+                        connection_id: 0,
+                        remote_address: SocketAddress::Ip(peer_addr),
+                        local_address: SocketAddress::Ip("127.0.0.1:1337".parse().unwrap()),
+                    }),
+                }),
+            ))
+            .await;
+        let layer_address = match background_tasks.next().await.unwrap().1.unwrap_message() {
+            ProxyMessage::ToLayer(ToLayer {
+                message:
+                    ProxyToLayerMessage::Outgoing(OutgoingResponse::Connect(Ok(
+                        OutgoingConnectResponse {
+                            layer_address: SocketAddress::Ip(addr),
+                            ..
+                        },
+                    ))),
+                ..
+            }) => SocketAddr::new(Ipv4Addr::LOCALHOST.into(), addr.port()),
+            other => panic!("unexpected message from outgoing proxy: {other:?}"),
+        };
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(layer_address).await.unwrap();
+        let sent = (0..DATAGRAMS)
+            .map(|i| format!("metric.{i}:1|c").into_bytes())
+            .collect::<Vec<_>>();
+        for datagram in &sent {
+            socket.send(datagram).await.unwrap();
+        }
+
+        let mut received = Vec::with_capacity(DATAGRAMS);
+        let mut messages = 0;
+        while received.len() < DATAGRAMS {
+            messages += 1;
+            match out.next().await.unwrap() {
+                ClientMessage::UdpOutgoing(LayerUdpOutgoing::WriteBatch(LayerWriteBatch {
+                    connection_id: 0,
+                    payloads,
+                })) => received.extend(payloads.into_iter().map(|payload| payload.0.to_vec())),
+                ClientMessage::UdpOutgoing(LayerUdpOutgoing::Write(LayerWrite {
+                    connection_id: 0,
+                    bytes,
+                })) => received.push(bytes.0.to_vec()),
+                other => panic!("unexpected client message from outgoing proxy: {other:?}"),
+            }
+        }
+
+        assert_eq!(received, sent);
+        assert!(
+            messages < DATAGRAMS / 10,
+            "{DATAGRAMS} datagrams were sent to the agent in {messages} messages"
+        );
+    }
+
+    /// Verifies that boundaries between the packets are preserved on outgoing SEQPACKET
+    /// connections, in both directions.
+    #[cfg(not(target_os = "windows"))]
+    #[tokio::test]
+    async fn seqpacket_boundaries_preserved() {
+        use mirrord_protocol::{
+            ResponseError,
+            outgoing::{DaemonRead, UnixAddr},
+        };
+        use socket2::{Domain, SockAddr, Socket, Type};
+
+        let peer_addr = SocketAddress::Unix(UnixAddr::Pathname("/tmp/peer.sock".into()));
+        let (connection, _, out) = Connection::dummy();
+
+        let mut background_tasks: BackgroundTasks<(), ProxyMessage, OutgoingProxyError> =
+            BackgroundTasks::new(connection.tx_handle());
+        let outgoing = background_tasks.register(
+            OutgoingProxy::new(false, 0, 0, Duration::ZERO, Default::default()),
+            (),
+            8,
+        );
+
+        // The agent does not support SEQPACKET yet.
+        outgoing
+            .send(OutgoingProxyMessage::AgentProtocolVersion(
+                "1.29.0".parse().unwrap(),
+            ))
+            .await;
+        outgoing
+            .send(OutgoingProxyMessage::Layer(
+                OutgoingRequest::Connect(OutgoingConnectRequest {
+                    remote_address: peer_addr.clone(),
+                    protocol: NetProtocol::SeqPacket,
+                }),
+                0,
+                LayerId(0),
+            ))
+            .await;
+        match background_tasks.next().await.unwrap().1.unwrap_message() {
+            ProxyMessage::ToLayer(ToLayer {
+                message:
+                    ProxyToLayerMessage::Outgoing(OutgoingResponse::Connect(Err(
+                        ResponseError::NotImplemented,
+                    ))),
+                ..
+            }) => {}
+            other => panic!("unexpected message from outgoing proxy: {other:?}"),
+        }
+
+        outgoing
+            .send(OutgoingProxyMessage::AgentProtocolVersion(
+                mirrord_protocol::VERSION.clone(),
+            ))
+            .await;
+        outgoing
+            .send(OutgoingProxyMessage::Layer(
+                OutgoingRequest::Connect(OutgoingConnectRequest {
+                    remote_address: peer_addr.clone(),
+                    protocol: NetProtocol::SeqPacket,
+                }),
+                1,
+                LayerId(0),
+            ))
+            .await;
+        let uid = match out.next().await.unwrap() {
+            ClientMessage::TcpOutgoing(LayerTcpOutgoing::ConnectSeqPacket(LayerConnectV2 {
+                uid,
+                remote_address,
+            })) => {
+                assert_eq!(remote_address, peer_addr);
+                uid
+            }
+            other => panic!("unexpected client message from outgoing proxy: {other:?}"),
+        };
+
+        outgoing
+            .send(OutgoingProxyMessage::AgentStream(
+                DaemonTcpOutgoing::ConnectV2(DaemonConnectV2 {
+                    uid,
+                    connect: Ok(DaemonConnect {
+                        connection_id: 0,
+                        remote_address: peer_addr.clone(),
+                        local_address: SocketAddress::Unix(UnixAddr::Unnamed),
+                    }),
+                }),
+            ))
+            .await;
+        let layer_address = match background_tasks.next().await.unwrap().1.unwrap_message() {
+            ProxyMessage::ToLayer(ToLayer {
+                message:
+                    ProxyToLayerMessage::Outgoing(OutgoingResponse::Connect(Ok(
+                        OutgoingConnectResponse {
+                            layer_address: SocketAddress::Unix(UnixAddr::Pathname(path)),
+                            ..
+                        },
+                    ))),
+                ..
+            }) => path,
+            other => panic!("unexpected message from outgoing proxy: {other:?}"),
+        };
+
+        let socket = Socket::new(Domain::UNIX, Type::SEQPACKET, None).unwrap();
+        socket
+            .connect(&SockAddr::unix(&layer_address).unwrap())
+            .unwrap();
+
+        let sent = [b"first".to_vec(), b"second".to_vec(), b"third".to_vec()];
+        for packet in &sent {
+            assert_eq!(socket.send(packet).unwrap(), packet.len());
+        }
+        for packet in &sent {
+            match out.next().await.unwrap() {
+                ClientMessage::TcpOutgoing(LayerTcpOutgoing::Write(LayerWrite {
+                    connection_id: 0,
+                    bytes,
+                })) => assert_eq!(&bytes.0[..], &packet[..]),
+                other => panic!("unexpected client message from outgoing proxy: {other:?}"),
+            }
+        }
+
+        for packet in &sent {
+            outgoing
+                .send(OutgoingProxyMessage::AgentStream(DaemonTcpOutgoing::Read(
+                    Ok(DaemonRead {
+                        connection_id: 0,
+                        bytes: packet.clone().into(),
+                    }),
+                )))
+                .await;
+        }
+        let received = tokio::task::spawn_blocking(move || {
+            sent.iter()
+                .map(|_| {
+                    let mut buffer = [0_u8; 64];
+                    let received = std::io::Read::read(&mut &socket, &mut buffer).unwrap();
+                    buffer[..received].to_vec()
+                })
+                .collect::<Vec<_>>()
+        })
+        .await
+        .unwrap();
+        assert_eq!(
+            received,
+            [b"first".to_vec(), b"second".to_vec(), b"third".to_vec()]
+        );
+    }
 }
@@ -2,7 +2,11 @@
 //! [`OutgoingProxy`](super::OutgoingProxy).
 
 #[cfg(not(target_os = "windows"))]
-use std::{env, path::PathBuf};
+use std::{
+    env,
+    net::Shutdown,
+    path::{Path, PathBuf},
+};
 use std::{
     io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
@@ -29,7 +33,12 @@ use mirrord_protocol::{
 #[cfg(not(target_os = "windows"))]
 use rand::distr::{Alphanumeric, SampleString};
 #[cfg(not(target_os = "windows"))]
-use tokio::net::{UnixListener, UnixStream};
+use socket2::{Domain, SockAddr, Socket, Type};
+#[cfg(not(target_os = "windows"))]
+use tokio::{
+    io::{Interest, unix::AsyncFd},
+    net::{UnixListener, UnixStream},
+};
 
 use crate::proxies::outgoing::busy_tcp_listener::BusyTcpListener;
 
@@ -48,6 +57,12 @@ pub trait NetProtocolExt: Sized {
     /// The enum path used here depends on this protocol.
     fn wrap_agent_connect(self, remote_address: SocketAddress, uid: Option<Uid>) -> ClientMessage;
 
+    /// Returns the protocol used in the messages exchanged with the agent.
+    ///
+    /// The agent handles [`NetProtocol::SeqPacket`] connections together with the
+    /// [`NetProtocol::Stream`] ones, so they share the message types and connection ids.
+    fn agent_protocol(self) -> NetProtocol;
+
     /// Opens a new socket for intercepting a connection to the given remote address.
     async fn prepare_socket(self, for_remote_address: SocketAddress) -> io::Result<PreparedSocket>;
 }
@@ -59,10 +74,12 @@ impl NetProtocolExt for NetProtocol {
                 connection_id,
                 bytes: bytes.into(),
             })),
-            Self::Stream => ClientMessage::TcpOutgoing(LayerTcpOutgoing::Write(LayerWrite {
-                connection_id,
-                bytes: bytes.into(),
-            })),
+            Self::Stream | Self::SeqPacket => {
+                ClientMessage::TcpOutgoing(LayerTcpOutgoing::Write(LayerWrite {
+                    connection_id,
+                    bytes: bytes.into(),
+                }))
+            }
         }
     }
 
@@ -71,7 +88,7 @@ impl NetProtocolExt for NetProtocol {
             Self::Datagrams => {
                 ClientMessage::UdpOutgoing(LayerUdpOutgoing::Close(LayerClose { connection_id }))
             }
-            Self::Stream => {
+            Self::Stream | Self::SeqPacket => {
                 ClientMessage::TcpOutgoing(LayerTcpOutgoing::Close(LayerClose { connection_id }))
             }
         }
@@ -101,6 +118,22 @@ impl NetProtocolExt for NetProtocol {
                     remote_address,
                 }))
             }
+            (Self::SeqPacket, Some(uid)) => {
+                ClientMessage::TcpOutgoing(LayerTcpOutgoing::ConnectSeqPacket(LayerConnectV2 {
+                    uid,
+                    remote_address,
+                }))
+            }
+            (Self::SeqPacket, None) => {
+                unreachable!("outgoing SEQPACKET connections are made only with a Uid")
+            }
+        }
+    }
+
+    fn agent_protocol(self) -> NetProtocol {
+        match self {
+            Self::Stream | Self::SeqPacket => Self::Stream,
+            Self::Datagrams => Self::Datagrams,
         }
     }
 
@@ -116,6 +149,12 @@ impl NetProtocolExt for NetProtocol {
                 match self {
                     Self::Datagrams => PreparedSocket::UdpSocket(UdpSocket::bind(bind_at).await?),
                     Self::Stream => PreparedSocket::TcpListener(TcpListener::bind(bind_at).await?),
+                    Self::SeqPacket => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "SEQPACKET is supported only over unix sockets",
+                        ));
+                    }
                 }
             }
             #[cfg(not(target_os = "windows"))]
@@ -124,6 +163,10 @@ impl NetProtocolExt for NetProtocol {
                     let path = PreparedSocket::generate_uds_path().await?;
                     PreparedSocket::UnixListener(UnixListener::bind(path)?)
                 }
+                Self::SeqPacket => {
+                    let path = PreparedSocket::generate_uds_path().await?;
+                    PreparedSocket::SeqPacketListener(SeqPacketSocket::bind(&path)?)
+                }
                 Self::Datagrams => {
                     tracing::error!(
                         "layer requested intercepting outgoing datagrams over unix socket, this is not supported"
@@ -153,6 +196,8 @@ pub enum PreparedSocket {
     BusyTcpListener(BusyTcpListener),
     #[cfg(not(target_os = "windows"))]
     UnixListener(UnixListener),
+    #[cfg(not(target_os = "windows"))]
+    SeqPacketListener(SeqPacketSocket),
 }
 
 impl PreparedSocket {
@@ -183,6 +228,10 @@ impl PreparedSocket {
                 let pathname = addr.as_pathname().unwrap().to_path_buf();
                 SocketAddress::Unix(UnixAddr::Pathname(pathname))
             }
+            #[cfg(not(target_os = "windows"))]
+            Self::SeqPacketListener(listener) => {
+                SocketAddress::Unix(UnixAddr::Pathname(listener.local_path()?))
+            }
         };
 
         Ok(address)
@@ -206,6 +255,11 @@ impl PreparedSocket {
                 let (stream, _) = listener.accept().await?;
                 (InnerConnectedSocket::UnixStream(stream), true)
             }
+            #[cfg(not(target_os = "windows"))]
+            Self::SeqPacketListener(listener) => (
+                InnerConnectedSocket::SeqPacket(listener.accept().await?),
+                true,
+            ),
         };
 
         Ok(ConnectedSocket {
@@ -221,6 +275,8 @@ enum InnerConnectedSocket {
     TcpStream(TcpStream),
     #[cfg(not(target_os = "windows"))]
     UnixStream(UnixStream),
+    #[cfg(not(target_os = "windows"))]
+    SeqPacket(SeqPacketSocket),
 }
 
 /// A socket for intercepted connection with the layer.
@@ -247,10 +303,14 @@ impl ConnectedSocket {
             InnerConnectedSocket::TcpStream(stream) => stream.write_all(bytes).await,
             #[cfg(not(target_os = "windows"))]
             InnerConnectedSocket::UnixStream(stream) => stream.write_all(bytes).await,
+            #[cfg(not(target_os = "windows"))]
+            InnerConnectedSocket::SeqPacket(socket) => socket.send(bytes).await,
         }
     }
 
     /// Receives some data from the layer.
+    ///
+    /// For [`NetProtocol::SeqPacket`], receives exactly one packet.
     pub async fn receive(&mut self) -> io::Result<Vec<u8>> {
         match &mut self.inner {
             InnerConnectedSocket::UdpSocket(socket) => {
@@ -278,6 +338,13 @@ impl ConnectedSocket {
                 self.buffer.clear();
                 Ok(bytes)
             }
+            #[cfg(not(target_os = "windows"))]
+            InnerConnectedSocket::SeqPacket(socket) => {
+                socket.receive(&mut self.buffer).await?;
+                let bytes = self.buffer.to_vec();
+                self.buffer.clear();
+                Ok(bytes)
+            }
         }
     }
 
@@ -291,7 +358,97 @@ impl ConnectedSocket {
             InnerConnectedSocket::TcpStream(stream) => stream.shutdown().await,
             #[cfg(not(target_os = "windows"))]
             InnerConnectedSocket::UnixStream(stream) => stream.shutdown().await,
+            #[cfg(not(target_os = "windows"))]
+            InnerConnectedSocket::SeqPacket(socket) => socket.shutdown(),
             InnerConnectedSocket::UdpSocket(..) => Ok(()),
         }
     }
 }
+
+/// Unix `SOCK_SEQPACKET` socket, either listening or connected.
+///
+/// Tokio has no support for this socket type, so we use [`AsyncFd`] directly. Unlike with
+/// [`UnixStream`], every send and receive transfers exactly one packet.
+#[cfg(not(target_os = "windows"))]
+#[derive(Debug)]
+pub struct SeqPacketSocket(AsyncFd<Socket>);
+
+#[cfg(not(target_os = "windows"))]
+impl SeqPacketSocket {
+    /// Max size of a packet received from the layer. Larger packets fail the connection.
+    const MAX_PACKET_SIZE: usize = 256 * 1024;
+
+    /// Binds a new listening socket to the given path.
+    fn bind(path: &Path) -> io::Result<Self> {
+        let socket = Socket::new(Domain::UNIX, Type::SEQPACKET, None)?;
+        socket.set_nonblocking(true)?;
+        socket.bind(&SockAddr::unix(path)?)?;
+        socket.listen(1)?;
+
+        Ok(Self(AsyncFd::new(socket)?))
+    }
+
+    fn local_path(&self) -> io::Result<PathBuf> {
+        self.0
+            .get_ref()
+            .local_addr()?
+            .as_pathname()
+            .map(Path::to_path_buf)
+            .ok_or_else(|| io::Error::other("SEQPACKET listener is not bound to a path"))
+    }
+
+    /// Accepts one connection on this listening socket.
+    async fn accept(&self) -> io::Result<Self> {
+        let (socket, _) = self
+            .0
+            .async_io(Interest::READABLE, |socket| socket.accept())
+            .await?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self(AsyncFd::new(socket)?))
+    }
+
+    /// Sends the given bytes as one packet.
+    async fn send(&self, packet: &[u8]) -> io::Result<()> {
+        let sent = self
+            .0
+            .async_io(Interest::WRITABLE, |socket| socket.send(packet))
+            .await?;
+
+        if sent != packet.len() {
+            Err(io::Error::other("failed to send the whole packet"))?;
+        }
+
+        Ok(())
+    }
+
+    /// Receives one packet into the given buffer.
+    async fn receive(&self, buffer: &mut BytesMut) -> io::Result<()> {
+        // One extra byte, so that we can detect truncated packets.
+        buffer.reserve(Self::MAX_PACKET_SIZE + 1);
+        let spare = &mut buffer.spare_capacity_mut()[..Self::MAX_PACKET_SIZE + 1];
+
+        let received = self
+            .0
+            .async_io(Interest::READABLE, |socket| socket.recv(&mut *spare))
+            .await?;
+        if received > Self::MAX_PACKET_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "received a packet larger than {} bytes",
+                    Self::MAX_PACKET_SIZE
+                ),
+            ));
+        }
+
+        // SAFETY: `recv` initialized `received` bytes of the spare capacity.
+        unsafe { buffer.set_len(buffer.len() + received) };
+
+        Ok(())
+    }
+
+    fn shutdown(&self) -> io::Result<()> {
+        self.0.get_ref().shutdown(Shutdown::Write)
+    }
+}
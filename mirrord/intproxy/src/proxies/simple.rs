@@ -1,13 +1,17 @@
 //! The most basic proxying logic. Handles cases when the only job to do in the internal proxy is to
 //! pass requests and responses between the layer and the agent.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, ops::Not};
 
 use mirrord_intproxy_protocol::{LayerId, MessageId, ProxyToLayerMessage};
 use mirrord_protocol::{
     ClientMessage, DaemonMessage, DnsLookupError, GetEnvVarsRequest, RemoteResult,
     ResolveErrorKindInternal, ResponseError,
-    dns::{ADDRINFO_V2_VERSION, AddressFamily, GetAddrInfoRequestV2, GetAddrInfoResponse},
+    dns::{
+        ADDRINFO_V2_VERSION, AddressFamily, GetAddrInfoRequestV2, GetAddrInfoResponse,
+        REVERSE_DNS_LOOKUP_VERSION, ReverseDnsLookupRequest, ReverseDnsLookupResponse,
+    },
+    time::{REMOTE_TIME_VERSION, RemoteTime},
 };
 use semver::Version;
 use thiserror::Error;
@@ -17,6 +21,7 @@ use crate::{
     ProxyMessage,
     background_tasks::{BackgroundTask, MessageBus},
     error::{UnexpectedAgentMessage, agent_lost_io_error},
+    fault_injection::{DelayedRequests, FaultInjector},
     main_tasks::{ConnectionRefresh, ToLayer},
     request_queue::RequestQueue,
 };
@@ -25,8 +30,12 @@ use crate::{
 pub enum SimpleProxyMessage {
     AddrInfoReq(MessageId, LayerId, GetAddrInfoRequestV2),
     AddrInfoRes(GetAddrInfoResponse),
+    ReverseDnsReq(MessageId, LayerId, ReverseDnsLookupRequest),
+    ReverseDnsRes(RemoteResult<ReverseDnsLookupResponse>),
     GetEnvReq(MessageId, LayerId, GetEnvVarsRequest),
     GetEnvRes(RemoteResult<HashMap<String, String>>),
+    RemoteTimeReq(MessageId, LayerId),
+    RemoteTimeRes(RemoteTime),
     /// Protocol version was negotiated with the agent.
     ProtocolVersion(Version),
     ConnectionRefresh(ConnectionRefresh),
@@ -48,7 +57,9 @@ pub enum SimpleProxyError {
 
 pub enum AgentLostSimpleResponseKind {
     AddrInfo,
+    ReverseDns,
     GetEnv,
+    RemoteTime,
 }
 
 /// Lightweight (no allocations) [`ProxyMessage`] to be returned when connection with the
@@ -60,9 +71,25 @@ impl AgentLostSimpleResponse {
         AgentLostSimpleResponse(AgentLostSimpleResponseKind::AddrInfo, layer_id, message_id)
     }
 
+    pub fn reverse_dns(layer_id: LayerId, message_id: MessageId) -> Self {
+        AgentLostSimpleResponse(
+            AgentLostSimpleResponseKind::ReverseDns,
+            layer_id,
+            message_id,
+        )
+    }
+
     pub fn get_env(layer_id: LayerId, message_id: MessageId) -> Self {
         AgentLostSimpleResponse(AgentLostSimpleResponseKind::GetEnv, layer_id, message_id)
     }
+
+    pub fn remote_time(layer_id: LayerId, message_id: MessageId) -> Self {
+        AgentLostSimpleResponse(
+            AgentLostSimpleResponseKind::RemoteTime,
+            layer_id,
+            message_id,
+        )
+    }
 }
 
 impl From<AgentLostSimpleResponse> for ToLayer {
@@ -74,7 +101,11 @@ impl From<AgentLostSimpleResponse> for ToLayer {
             AgentLostSimpleResponseKind::AddrInfo => {
                 ProxyToLayerMessage::GetAddrInfo(GetAddrInfoResponse(Err(error)))
             }
+            AgentLostSimpleResponseKind::ReverseDns => {
+                ProxyToLayerMessage::ReverseDnsLookup(Err(error))
+            }
             AgentLostSimpleResponseKind::GetEnv => ProxyToLayerMessage::GetEnv(Err(error)),
+            AgentLostSimpleResponseKind::RemoteTime => ProxyToLayerMessage::RemoteTime(Err(error)),
         };
 
         ToLayer {
@@ -90,22 +121,34 @@ impl From<AgentLostSimpleResponse> for ToLayer {
 pub struct SimpleProxy {
     /// For [`GetAddrInfoRequestV2`]s.
     addr_info_reqs: RequestQueue,
+    /// For [`ReverseDnsLookupRequest`]s.
+    reverse_dns_reqs: RequestQueue,
     /// For [`GetEnvVarsRequest`]s.
     get_env_reqs: RequestQueue,
+    /// For [`RemoteTimeRequest`](mirrord_intproxy_protocol::RemoteTimeRequest)s.
+    remote_time_reqs: RequestQueue,
     /// [`mirrord_protocol`] version negotiated with the agent.
     /// Determines whether we can use `GetAddrInfoRequestV2`.
     protocol_version: Option<Version>,
     /// Whether to consider permission errors in DNS lookup to be fatal.
     dns_permission_error_fatal: bool,
+    /// Synthetic faults injected into [`GetAddrInfoRequestV2`]s.
+    dns_faults: FaultInjector,
+    /// [`SimpleProxyMessage::AddrInfoReq`]s held back by the [`Self::dns_faults`] delay.
+    delayed_addr_info_reqs: DelayedRequests<SimpleProxyMessage>,
 }
 
 impl SimpleProxy {
-    pub fn new(dns_permission_error_fatal: bool) -> Self {
+    pub fn new(dns_permission_error_fatal: bool, dns_faults: FaultInjector) -> Self {
         Self {
             addr_info_reqs: Default::default(),
+            reverse_dns_reqs: Default::default(),
             get_env_reqs: Default::default(),
+            remote_time_reqs: Default::default(),
             protocol_version: Default::default(),
             dns_permission_error_fatal,
+            dns_faults,
+            delayed_addr_info_reqs: Default::default(),
         }
     }
 
@@ -120,6 +163,21 @@ impl SimpleProxy {
             .is_some_and(|version| ADDRINFO_V2_VERSION.matches(version))
     }
 
+    /// Returns whether [`mirrord_protocol`] version allows for a [`ReverseDnsLookupRequest`].
+    fn reverse_dns(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| REVERSE_DNS_LOOKUP_VERSION.matches(version))
+    }
+
+    /// Returns whether [`mirrord_protocol`] version allows for a
+    /// [`ClientMessage::GetRemoteTime`].
+    fn remote_time(&self) -> bool {
+        self.protocol_version
+            .as_ref()
+            .is_some_and(|version| REMOTE_TIME_VERSION.matches(version))
+    }
+
     #[tracing::instrument(level = Level::INFO, skip_all)]
     async fn handle_connection_refresh(
         &mut self,
@@ -140,6 +198,18 @@ impl SimpleProxy {
                         .await;
                 }
 
+                tracing::debug!(
+                    num_responses = self.reverse_dns_reqs.len(),
+                    "Flushing error responses to ReverseDnsLookupRequests"
+                );
+                while let Some((message_id, layer_id)) = self.reverse_dns_reqs.pop_front() {
+                    message_bus
+                        .send(ToLayer::from(AgentLostSimpleResponse::reverse_dns(
+                            layer_id, message_id,
+                        )))
+                        .await;
+                }
+
                 tracing::debug!(
                     num_responses = self.get_env_reqs.len(),
                     "Flushing error responses to GetEnvVarsRequests"
@@ -152,6 +222,18 @@ impl SimpleProxy {
                         .await;
                 }
 
+                tracing::debug!(
+                    num_responses = self.remote_time_reqs.len(),
+                    "Flushing error responses to RemoteTimeRequests"
+                );
+                while let Some((message_id, layer_id)) = self.remote_time_reqs.pop_front() {
+                    message_bus
+                        .send(ToLayer::from(AgentLostSimpleResponse::remote_time(
+                            layer_id, message_id,
+                        )))
+                        .await;
+                }
+
                 // Reset protocol version since we'll need another negotiation
                 // round for the new connection.
                 self.protocol_version = None;
@@ -169,9 +251,40 @@ impl BackgroundTask for SimpleProxy {
 
     #[tracing::instrument(level = Level::INFO, name = "simple_proxy_main_loop", skip_all, ret, err)]
     async fn run(&mut self, message_bus: &mut MessageBus<Self>) -> Result<(), Self::Error> {
-        while let Some(msg) = message_bus.recv().await {
+        loop {
+            let (msg, delayed) = tokio::select! {
+                msg = message_bus.recv() => match msg {
+                    Some(msg) => (msg, false),
+                    None => break,
+                },
+                msg = self.delayed_addr_info_reqs.next() => (msg, true),
+            };
+
             match msg {
                 SimpleProxyMessage::AddrInfoReq(message_id, session_id, req) => {
+                    if let Some(delay) = self.dns_faults.delay()
+                        && delayed.not()
+                    {
+                        self.delayed_addr_info_reqs.push(
+                            delay,
+                            SimpleProxyMessage::AddrInfoReq(message_id, session_id, req),
+                        );
+                        continue;
+                    }
+
+                    if self.dns_faults.fails() {
+                        message_bus
+                            .send(ToLayer {
+                                message_id,
+                                message: ProxyToLayerMessage::GetAddrInfo(GetAddrInfoResponse(
+                                    Err(FaultInjector::dns_error()),
+                                )),
+                                layer_id: session_id,
+                            })
+                            .await;
+                        continue;
+                    }
+
                     self.addr_info_reqs.push_back(message_id, session_id);
                     if self.addr_info_v2() {
                         message_bus
@@ -213,6 +326,39 @@ impl BackgroundTask for SimpleProxy {
                         })
                         .await;
                 }
+                SimpleProxyMessage::ReverseDnsReq(message_id, layer_id, req) => {
+                    if self.reverse_dns() {
+                        self.reverse_dns_reqs.push_back(message_id, layer_id);
+                        message_bus
+                            .send_agent(ClientMessage::ReverseDnsLookup(req))
+                            .await;
+                    } else {
+                        message_bus
+                            .send(ToLayer {
+                                message_id,
+                                message: ProxyToLayerMessage::ReverseDnsLookup(Err(
+                                    ResponseError::NotImplemented,
+                                )),
+                                layer_id,
+                            })
+                            .await;
+                    }
+                }
+                SimpleProxyMessage::ReverseDnsRes(res) => {
+                    let (message_id, layer_id) =
+                        self.reverse_dns_reqs.pop_front().ok_or_else(|| {
+                            UnexpectedAgentMessage(
+                                DaemonMessage::ReverseDnsLookup(res.clone()).into(),
+                            )
+                        })?;
+                    message_bus
+                        .send(ToLayer {
+                            message_id,
+                            message: ProxyToLayerMessage::ReverseDnsLookup(res),
+                            layer_id,
+                        })
+                        .await;
+                }
                 SimpleProxyMessage::GetEnvReq(message_id, layer_id, req) => {
                     self.get_env_reqs.push_back(message_id, layer_id);
                     message_bus
@@ -235,6 +381,35 @@ impl BackgroundTask for SimpleProxy {
                         })
                         .await
                 }
+                SimpleProxyMessage::RemoteTimeReq(message_id, layer_id) => {
+                    if self.remote_time() {
+                        self.remote_time_reqs.push_back(message_id, layer_id);
+                        message_bus.send_agent(ClientMessage::GetRemoteTime).await;
+                    } else {
+                        message_bus
+                            .send(ToLayer {
+                                message_id,
+                                message: ProxyToLayerMessage::RemoteTime(Err(
+                                    ResponseError::NotImplemented,
+                                )),
+                                layer_id,
+                            })
+                            .await;
+                    }
+                }
+                SimpleProxyMessage::RemoteTimeRes(time) => {
+                    let (message_id, layer_id) =
+                        self.remote_time_reqs.pop_front().ok_or_else(|| {
+                            UnexpectedAgentMessage(DaemonMessage::RemoteTime(time).into())
+                        })?;
+                    message_bus
+                        .send(ToLayer {
+                            message_id,
+                            message: ProxyToLayerMessage::RemoteTime(Ok(time)),
+                            layer_id,
+                        })
+                        .await
+                }
                 SimpleProxyMessage::ProtocolVersion(version) => self.set_protocol_version(version),
                 SimpleProxyMessage::ConnectionRefresh(new_agent_tx) => {
                     self.handle_connection_refresh(message_bus, new_agent_tx)
@@ -124,6 +124,14 @@ where
         }
     }
 
+    /// Returns an [`Iterator`] over layer instances that hold the given resource.
+    pub(crate) fn holders<'a>(&'a self, resource: &'a T) -> impl 'a + Iterator<Item = LayerId> {
+        self.by_layer
+            .iter()
+            .filter(move |(_, resources)| resources.contains(resource))
+            .map(|(layer_id, _)| *layer_id)
+    }
+
     /// Removes all resources held by all layers instances.
     /// Returns an [`Iterator`] of layers and remote files/folders that were removed.
     ///
@@ -74,6 +74,11 @@ impl<T: fmt::Debug> RequestQueue<T> {
         Some((message_id, layer_id, data))
     }
 
+    /// Removes all requests from this queue, returning them in order.
+    pub fn drain(&mut self) -> impl '_ + Iterator<Item = (MessageId, LayerId, T)> {
+        self.inner.drain(..)
+    }
+
     pub fn len(&self) -> usize {
         self.inner.len()
     }
@@ -0,0 +1,130 @@
+//! Metadata of the running mirrord session, kept by the internal proxy in a file in the temp
+//! directory, so that the `mirrord session` commands can inspect it.
+//!
+//! The file is named after the pid of the internal proxy, see [`SessionMetadata::path`].
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SessionMetadata {
+    /// Set when stealing was downgraded after too many stolen connections or requests failed to
+    /// reach the user application, see
+    /// [`AutoDowngradeConfig`](mirrord_config::feature::network::incoming::auto_downgrade::AutoDowngradeConfig).
+    pub steal_downgrade: Option<StealDowngrade>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StealDowngrade {
+    /// Whether the traffic is mirrored instead of stolen, otherwise it's not intercepted at all.
+    pub mirror: bool,
+    /// When stealing was downgraded, in seconds since the UNIX epoch.
+    pub since: u64,
+}
+
+impl StealDowngrade {
+    /// Downgrade that happened just now.
+    pub fn now(mirror: bool) -> Self {
+        let since = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        Self { mirror, since }
+    }
+}
+
+impl SessionMetadata {
+    /// Path of the metadata file of the internal proxy with the given pid.
+    pub fn path(intproxy_pid: u32) -> PathBuf {
+        std::env::temp_dir().join(format!("mirrord-session-{intproxy_pid}.json"))
+    }
+
+    /// Reads the metadata from the given file, which does not exist until there is something to
+    /// record.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read(path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).map_err(io::Error::other),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Default::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Writes the metadata to the given file.
+    ///
+    /// The file is replaced with a rename, so readers never see it half-written. The new file is
+    /// created under a random name, only readable by the current user, so that other users of
+    /// the shared temp directory can't redirect the write with a symlink.
+    pub fn store(&self, path: &Path) -> io::Result<()> {
+        let dir = path.parent().unwrap_or_else(|| Path::new("."));
+        let mut file = NamedTempFile::new_in(dir)?;
+        serde_json::to_writer(&mut file, self).map_err(io::Error::other)?;
+        file.persist(path)?;
+
+        Ok(())
+    }
+
+    /// Removes the given file, when the session ends.
+    pub fn remove(path: &Path) -> io::Result<()> {
+        match fs::remove_file(path) {
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn store_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("session.json");
+
+        assert_eq!(
+            SessionMetadata::load(&path).unwrap(),
+            SessionMetadata::default()
+        );
+
+        let metadata = SessionMetadata {
+            steal_downgrade: Some(StealDowngrade::now(true)),
+        };
+        metadata.store(&path).unwrap();
+        assert_eq!(SessionMetadata::load(&path).unwrap(), metadata);
+
+        SessionMetadata::remove(&path).unwrap();
+        assert_eq!(
+            SessionMetadata::load(&path).unwrap(),
+            SessionMetadata::default()
+        );
+        SessionMetadata::remove(&path).unwrap();
+    }
+
+    /// The stored file replaces a symlink planted at its path, instead of writing through it, and
+    /// is only accessible by the current user.
+    #[cfg(unix)]
+    #[test]
+    fn store_replaces_symlink() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("target");
+        fs::write(&target, "untouched").unwrap();
+        let path = dir.path().join("session.json");
+        std::os::unix::fs::symlink(&target, &path).unwrap();
+
+        SessionMetadata::default().store(&path).unwrap();
+
+        assert_eq!(fs::read_to_string(&target).unwrap(), "untouched");
+        let metadata = fs::symlink_metadata(&path).unwrap();
+        assert!(metadata.is_file());
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+    }
+}
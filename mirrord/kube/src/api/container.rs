@@ -35,6 +35,8 @@ pub static SKIP_NAMES: LazyLock<HashSet<&'static str>> = LazyLock::new(|| {
 /// Configuration of the mirrord-agent container.
 #[derive(Clone, Debug, Default)]
 pub struct ContainerConfig {
+    /// Predefined name of the agent container (and its job), see [`random_agent_name`].
+    pub name: Option<String>,
     /// Predefined port on which the agent will accept client connections.
     pub port: Option<u16>,
     /// Value for [`OPERATOR_CERT`](mirrord_agent_env::envs::OPERATOR_CERT) set in
@@ -47,6 +49,16 @@ pub struct ContainerConfig {
     pub steal_tls_config: Vec<StealPortTlsConfig>,
     /// How long the agent should keep running after all client connections have been closed.
     pub idle_ttl: Duration,
+    /// Max number of requests from a single stolen HTTP connection waiting for responses.
+    pub max_pending_requests: Option<u32>,
+    /// Whether the agent should not redirect ports declared by the other containers in the target
+    /// pod, see
+    /// [`IncomingConfig::limit_to_target_container`](mirrord_config::feature::network::incoming::IncomingConfig::limit_to_target_container).
+    pub limit_to_target_container: bool,
+    /// Ports that the agent never redirects, filled from
+    /// [`RuntimeData`](super::runtime::RuntimeData)
+    /// when [`ContainerConfig::limit_to_target_container`] is set.
+    pub excluded_ports: Vec<u16>,
 }
 
 #[derive(Clone, Debug)]
@@ -68,6 +80,20 @@ pub struct ContainerParams {
     pub steal_tls_config: Vec<StealPortTlsConfig>,
     /// How long the agent should keep running after all client connections have been closed.
     pub idle_ttl: Duration,
+    /// Max number of requests from a single stolen HTTP connection waiting for responses.
+    pub max_pending_requests: Option<u32>,
+    /// Ports that the agent never redirects.
+    pub excluded_ports: Vec<u16>,
+}
+
+/// Generates a unique name for the agent container (and its job).
+pub fn random_agent_name() -> String {
+    format!(
+        "mirrord-agent-{}",
+        Alphanumeric
+            .sample_string(&mut rand::rng(), 10)
+            .to_lowercase()
+    )
 }
 
 impl From<ContainerConfig> for ContainerParams {
@@ -77,15 +103,8 @@ impl From<ContainerConfig> for ContainerParams {
             .unwrap_or_else(|| rand::random_range(30000..=65535));
         let gid: u16 = rand::random_range(3000..u16::MAX);
 
-        let name = format!(
-            "mirrord-agent-{}",
-            Alphanumeric
-                .sample_string(&mut rand::rng(), 10)
-                .to_lowercase()
-        );
-
         Self {
-            name,
+            name: value.name.unwrap_or_else(random_agent_name),
             gid,
             port,
             tls_cert: value.tls_cert,
@@ -93,6 +112,8 @@ impl From<ContainerConfig> for ContainerParams {
             support_ipv6: value.support_ipv6,
             steal_tls_config: value.steal_tls_config,
             idle_ttl: value.idle_ttl,
+            max_pending_requests: value.max_pending_requests,
+            excluded_ports: value.excluded_ports,
         }
     }
 }
@@ -1,9 +1,9 @@
 use futures::StreamExt;
 use k8s_openapi::api::core::v1::{
-    Capabilities, EphemeralContainer as KubeEphemeralContainer, Pod, SecurityContext,
+    Capabilities, EphemeralContainer as KubeEphemeralContainer, Pod, Secret, SecurityContext,
 };
 use kube::{
-    Api, Client,
+    Api, Client, Resource,
     api::PostParams,
     runtime::{WatchStreamExt, watcher},
 };
@@ -18,7 +18,10 @@ use crate::{
     api::{
         container::{
             ContainerParams, ContainerVariant,
-            util::{base_command_line, get_capabilities, wait_for_agent_startup},
+            util::{
+                base_command_line, create_metrics_token_secret, get_capabilities,
+                wait_for_agent_startup,
+            },
         },
         kubernetes::AgentKubernetesConnectInfo,
         runtime::RuntimeData,
@@ -84,6 +87,16 @@ where
         ephemeral_container.env_from = Some(env)
     }
 
+    // The ephemeral container can't be removed from the target pod, so the pod owns the secret.
+    let secret_api: Api<Secret> = Api::namespaced(client.clone(), &runtime_data.pod_namespace);
+    create_metrics_token_secret(
+        &secret_api,
+        variant.agent_config(),
+        params,
+        pod.owner_ref(&()),
+    )
+    .await?;
+
     let mut ephemeral_containers_subresource: Pod = pod_api
         .get_subresource("ephemeralcontainers", &runtime_data.pod_name)
         .await
@@ -7,11 +7,11 @@ use std::{
 use futures::StreamExt;
 use k8s_openapi::api::{
     batch::v1::{Job, JobSpec},
-    core::v1::{Pod, PodStatus, PodTemplateSpec},
+    core::v1::{Pod, PodStatus, PodTemplateSpec, Secret},
 };
 use kube::{
-    Api, Client, ResourceExt,
-    api::{ObjectMeta, PostParams},
+    Api, Client, Resource, ResourceExt,
+    api::{DeleteParams, ObjectMeta, Patch, PatchParams, PostParams},
     runtime::{
         WatchStreamExt,
         watcher::{self, Event, watcher},
@@ -26,7 +26,7 @@ use crate::{
         container::{
             ContainerParams, ContainerVariant,
             pod::{PodTargetedVariant, PodVariant},
-            util::wait_for_agent_startup,
+            util::{create_metrics_token_secret, wait_for_agent_startup},
         },
         kubernetes::{AgentKubernetesConnectInfo, get_k8s_resource_api},
         runtime::RuntimeData,
@@ -53,12 +53,34 @@ where
     let agent = variant.agent_config();
     let agent_job: Job = variant.as_update();
 
-    let job_api = get_k8s_resource_api(client, agent.namespace.as_deref());
+    let job_api: Api<Job> = get_k8s_resource_api(client, agent.namespace.as_deref());
+    let secret_api: Api<Secret> = get_k8s_resource_api(client, agent.namespace.as_deref());
 
-    job_api
-        .create(&PostParams::default(), &agent_job)
-        .await
-        .map_err(KubeApiError::KubeError)?;
+    // The secret is created before the job, so that the agent container does not fail to start
+    // without it. It becomes owned by the job once the job is created.
+    let metrics_secret = create_metrics_token_secret(&secret_api, agent, params, None).await?;
+
+    let agent_job = match job_api.create(&PostParams::default(), &agent_job).await {
+        Ok(agent_job) => agent_job,
+        Err(error) => {
+            if metrics_secret {
+                let _ = secret_api
+                    .delete(&params.name, &DeleteParams::default())
+                    .await;
+            }
+
+            return Err(KubeApiError::KubeError(error));
+        }
+    };
+
+    if metrics_secret && let Some(owner) = agent_job.owner_ref(&()) {
+        let patch = serde_json::json!({ "metadata": { "ownerReferences": [owner] } });
+
+        secret_api
+            .patch(&params.name, &PatchParams::default(), &Patch::Merge(patch))
+            .await
+            .map_err(KubeApiError::KubeError)?;
+    }
 
     let watcher_config = watcher::Config::default()
         .labels(&format!("job-name={}", params.name))
@@ -380,6 +402,8 @@ mod test {
             support_ipv6,
             steal_tls_config: Default::default(),
             idle_ttl: Default::default(),
+            max_pending_requests: None,
+            excluded_ports: vec![],
         };
 
         let update = JobVariant::new(&agent, &params).as_update();
@@ -473,6 +497,8 @@ mod test {
             support_ipv6,
             steal_tls_config: Default::default(),
             idle_ttl: Default::default(),
+            max_pending_requests: None,
+            excluded_ports: vec![],
         };
 
         let update = JobTargetedVariant::new(
@@ -491,6 +517,8 @@ mod test {
                 guessed_container: false,
                 share_process_namespace: false,
                 containers_probe_ports: vec![],
+                container_ports: vec![],
+                other_containers_ports: vec![],
             },
         )
         .as_update();
@@ -603,4 +631,56 @@ mod test {
 
         Ok(())
     }
+
+    #[test]
+    fn targetless_scheduling() -> Result<(), Box<dyn std::error::Error>> {
+        let mut config_context = ConfigContext::default();
+        let mut agent = AgentFileConfig::default().generate_config(&mut config_context)?;
+        agent.priority_class = Some("mirrord-high".to_string());
+        agent.node_selector = Some([("pool".to_string(), "stable".to_string())].into());
+        agent.node_name = Some("node1".to_string());
+        let affinity = serde_json::json!({
+            "nodeAffinity": {
+                "requiredDuringSchedulingIgnoredDuringExecution": {
+                    "nodeSelectorTerms": [{
+                        "matchExpressions": [
+                            { "key": "autoscaler/scale-down", "operator": "DoesNotExist" }
+                        ]
+                    }]
+                }
+            }
+        });
+        agent.affinity = Some(affinity.clone());
+        let params = ContainerParams {
+            name: "foobar".to_string(),
+            port: 3000,
+            gid: 13,
+            tls_cert: None,
+            pod_ips: None,
+            support_ipv6: false,
+            steal_tls_config: Default::default(),
+            idle_ttl: Default::default(),
+            max_pending_requests: None,
+            excluded_ports: vec![],
+        };
+
+        let spec = JobVariant::new(&agent, &params)
+            .as_update()
+            .spec
+            .and_then(|spec| spec.template.spec)
+            .expect("job should include pod spec");
+
+        assert_eq!(spec.priority_class_name.as_deref(), Some("mirrord-high"));
+        assert_eq!(
+            spec.node_selector,
+            Some([("pool".to_string(), "stable".to_string())].into())
+        );
+        assert_eq!(spec.node_name.as_deref(), Some("node1"));
+        assert_eq!(
+            spec.affinity,
+            Some(serde_json::from_value(affinity).unwrap())
+        );
+
+        Ok(())
+    }
 }
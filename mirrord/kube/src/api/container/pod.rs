@@ -122,6 +122,9 @@ impl ContainerVariant for PodVariant<'_> {
                 image_pull_secrets,
                 tolerations: agent.tolerations.clone(),
                 node_selector: Some(node_selector),
+                node_name: agent.node_name.clone(),
+                // Validated when verifying the config.
+                affinity: agent.affinity().ok().flatten(),
                 service_account_name: agent.service_account.clone(),
                 containers: vec![Container {
                     name: "mirrord-agent".to_string(),
@@ -266,6 +269,9 @@ impl ContainerVariant for PodTargetedVariant<'_> {
         pod.merge_from(update);
 
         if let Some(spec) = pod.spec.as_mut() {
+            // The agent has to run on the node of the target.
+            spec.affinity = None;
+
             if let Some(node_hostname) = runtime_data.node_hostname.as_ref() {
                 spec.node_name = None;
                 spec.node_selector
@@ -309,6 +315,8 @@ mod test {
             support_ipv6: false,
             steal_tls_config: Default::default(),
             idle_ttl: Default::default(),
+            max_pending_requests: None,
+            excluded_ports: vec![],
         };
 
         let update = PodVariant::new(&agent, &params).as_update();
@@ -333,6 +341,8 @@ mod test {
                 guessed_container: false,
                 share_process_namespace: false,
                 containers_probe_ports: vec![],
+                container_ports: vec![],
+                other_containers_ports: vec![],
             },
         )
         .as_update();
@@ -357,6 +367,8 @@ mod test {
             support_ipv6: false,
             steal_tls_config: Default::default(),
             idle_ttl: Default::default(),
+            max_pending_requests: None,
+            excluded_ports: vec![],
         };
 
         let update = PodTargetedVariant::new(
@@ -375,6 +387,8 @@ mod test {
                 guessed_container: false,
                 share_process_namespace: false,
                 containers_probe_ports: vec![],
+                container_ports: vec![],
+                other_containers_ports: vec![],
             },
         )
         .as_update();
@@ -1,14 +1,26 @@
-use std::{ops::Not, sync::LazyLock};
+use std::{collections::BTreeMap, ops::Not, sync::LazyLock};
 
 use futures::{AsyncBufReadExt, TryStreamExt};
-use k8s_openapi::api::core::v1::{EnvVar, Pod, Toleration};
-use kube::{Api, api::LogParams};
-use mirrord_agent_env::envs;
-use mirrord_config::agent::{AgentConfig, LinuxCapability};
+use k8s_openapi::{
+    api::core::v1::{EnvVar, EnvVarSource, Pod, Secret, SecretKeySelector, Toleration},
+    apimachinery::pkg::apis::meta::v1::OwnerReference,
+};
+use kube::{
+    Api,
+    api::{LogParams, ObjectMeta, PostParams},
+};
+use mirrord_agent_env::{envs, metrics::MetricsAddress, redirector::RedirectorType};
+use mirrord_config::agent::{AgentConfig, AgentRedirector, LinuxCapability};
 use regex::Regex;
 use tracing::warn;
 
-use crate::{api::container::ContainerParams, error::Result};
+use crate::{
+    api::container::ContainerParams,
+    error::{KubeApiError, Result},
+};
+
+/// Key of the metrics token in the [`Secret`] created with [`create_metrics_token_secret`].
+const METRICS_TOKEN_SECRET_KEY: &str = "metrics-token";
 
 static AGENT_READY_REGEX: LazyLock<Regex> = LazyLock::new(|| {
     Regex::new("agent ready( - version (\\S+))?").expect("failed to create regex")
@@ -22,8 +34,27 @@ pub(super) static DEFAULT_TOLERATIONS: LazyLock<Vec<Toleration>> = LazyLock::new
 });
 
 /// Retrieve a list of Linux capabilities for the agent container.
+///
+/// Depends on the [`AgentConfig::redirector`]:
+/// 1. [`AgentRedirector::Ebpf`] replaces `NET_ADMIN` with `BPF`,
+/// 2. [`AgentRedirector::Auto`] adds `BPF`.
 pub(super) fn get_capabilities(agent: &AgentConfig) -> Vec<LinuxCapability> {
-    LinuxCapability::all()
+    let capabilities: &[LinuxCapability] = match agent.redirector {
+        AgentRedirector::Iptables | AgentRedirector::Nftables => LinuxCapability::all(),
+        AgentRedirector::Ebpf => &[
+            LinuxCapability::SysAdmin,
+            LinuxCapability::SysPtrace,
+            LinuxCapability::Bpf,
+        ],
+        AgentRedirector::Auto => &[
+            LinuxCapability::SysAdmin,
+            LinuxCapability::SysPtrace,
+            LinuxCapability::NetAdmin,
+            LinuxCapability::Bpf,
+        ],
+    };
+
+    capabilities
         .iter()
         .copied()
         .filter(|c| {
@@ -65,12 +96,38 @@ pub(super) fn agent_env(agent: &AgentConfig, params: &ContainerParams) -> Vec<En
         env.push(envs::DNS_TIMEOUT.as_k8s_spec(&timeout));
     };
 
+    if let Some(search) = &agent.dns.search {
+        env.push(envs::DNS_SEARCH.as_k8s_spec(search));
+    }
+
     if let Some(pod_ips) = &params.pod_ips {
         env.push(envs::POD_IPS.as_k8s_spec(pod_ips));
     }
 
-    if let Some(metrics_address) = agent.metrics.as_ref() {
-        env.push(envs::METRICS.as_k8s_spec(metrics_address));
+    if let Some(metrics) = agent.metrics.as_ref() {
+        match (metrics.address().parse::<MetricsAddress>(), metrics.token()) {
+            (Ok(address), Ok(token)) => {
+                env.push(envs::METRICS.as_k8s_spec(&address));
+                if token.is_some() {
+                    env.push(EnvVar {
+                        name: envs::METRICS_TOKEN.name.to_string(),
+                        value_from: Some(EnvVarSource {
+                            secret_key_ref: Some(SecretKeySelector {
+                                name: params.name.clone(),
+                                key: METRICS_TOKEN_SECRET_KEY.to_string(),
+                                optional: None,
+                            }),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    });
+                }
+            }
+            (Err(error), _) => {
+                warn!(%error, "Invalid `agent.metrics` address, metrics are disabled")
+            }
+            (_, Err(error)) => warn!(%error, "Invalid `agent.metrics` token, metrics are disabled"),
+        }
     }
 
     if let Some(cert) = &params.tls_cert {
@@ -85,6 +142,18 @@ pub(super) fn agent_env(agent: &AgentConfig, params: &ContainerParams) -> Vec<En
         env.push(envs::IDDLE_TTL.as_k8s_spec(&params.idle_ttl.as_secs()))
     }
 
+    if let Some(max_pending_requests) = params.max_pending_requests {
+        env.push(envs::MAX_PENDING_REQUESTS.as_k8s_spec(&max_pending_requests));
+    }
+
+    if let Some(max_connections) = agent.max_mirrored_connections {
+        env.push(envs::MAX_MIRRORED_CONNECTIONS.as_k8s_spec(&max_connections));
+    }
+
+    if let Some(max_bytes) = agent.max_mirrored_connection_bytes {
+        env.push(envs::MAX_MIRRORED_CONNECTION_BYTES.as_k8s_spec(&max_bytes));
+    }
+
     if agent.inject_headers {
         env.push(envs::INJECT_HEADERS.as_k8s_spec(&agent.inject_headers));
     }
@@ -93,6 +162,38 @@ pub(super) fn agent_env(agent: &AgentConfig, params: &ContainerParams) -> Vec<En
         env.push(envs::CLEAN_IPTABLES_ON_START.as_k8s_spec(&clean));
     }
 
+    if let Ok(Some(prefix)) = agent.iptables_chain_prefix() {
+        env.push(envs::IPTABLES_CHAIN_PREFIX.as_k8s_spec(&prefix.to_string()));
+    }
+
+    if let Ok((destinations, sources)) = agent.iptables_exclusions() {
+        if destinations.is_empty().not() {
+            env.push(envs::IPTABLES_EXCLUDE_DESTINATIONS.as_k8s_spec(&destinations));
+        }
+
+        if sources.is_empty().not() {
+            env.push(envs::IPTABLES_EXCLUDE_SOURCES.as_k8s_spec(&sources));
+        }
+    }
+
+    if params.excluded_ports.is_empty().not() {
+        env.push(envs::IPTABLES_EXCLUDE_PORTS.as_k8s_spec(&params.excluded_ports));
+    }
+
+    if let Some(interval) = agent.iptables_reconcile_interval.filter(|secs| *secs > 0) {
+        env.push(envs::IPTABLES_RECONCILE_INTERVAL.as_k8s_spec(&interval));
+    }
+
+    match agent.redirector {
+        // Agents that don't know this variable use iptables anyway.
+        AgentRedirector::Iptables => {}
+        AgentRedirector::Nftables => {
+            env.push(envs::REDIRECTOR.as_k8s_spec(&RedirectorType::Nftables))
+        }
+        AgentRedirector::Ebpf => env.push(envs::REDIRECTOR.as_k8s_spec(&RedirectorType::Ebpf)),
+        AgentRedirector::Auto => env.push(envs::REDIRECTOR.as_k8s_spec(&RedirectorType::Auto)),
+    }
+
     env
 }
 
@@ -115,6 +216,45 @@ pub(super) fn base_command_line(agent: &AgentConfig, params: &ContainerParams) -
     command_line
 }
 
+/// Creates a [`Secret`] named after the agent container, that holds the token from
+/// `agent.metrics.token_env`.
+///
+/// The agent reads the token from this [`Secret`] (see [`agent_env`]), so that it is not visible
+/// in the agent's spec to everyone who can `get` pods. The [`Secret`] should be owned by the
+/// agent's job or target pod, so that it is deleted with it.
+///
+/// Returns whether the [`Secret`] was created, which happens only when the token is set.
+pub(super) async fn create_metrics_token_secret(
+    secret_api: &Api<Secret>,
+    agent: &AgentConfig,
+    params: &ContainerParams,
+    owner: Option<OwnerReference>,
+) -> Result<bool> {
+    let Some(Ok(Some(token))) = agent.metrics.as_ref().map(|metrics| metrics.token()) else {
+        return Ok(false);
+    };
+
+    let secret = Secret {
+        metadata: ObjectMeta {
+            name: Some(params.name.clone()),
+            owner_references: owner.map(|owner| vec![owner]),
+            ..Default::default()
+        },
+        string_data: Some(BTreeMap::from([(
+            METRICS_TOKEN_SECRET_KEY.to_string(),
+            token,
+        )])),
+        ..Default::default()
+    };
+
+    secret_api
+        .create(&PostParams::default(), &secret)
+        .await
+        .map_err(KubeApiError::KubeError)?;
+
+    Ok(true)
+}
+
 /**
  * Wait until the agent prints the "agent ready" message.
  * Return agent version extracted from the message (if found).
@@ -149,9 +289,91 @@ pub(super) async fn wait_for_agent_startup(
 
 #[cfg(test)]
 mod test {
+    use mirrord_config::{
+        agent::AgentFileConfig,
+        config::{ConfigContext, MirrordConfig},
+    };
     use rstest::rstest;
 
     use super::*;
+    use crate::api::container::ContainerConfig;
+
+    #[rstest]
+    #[case(AgentRedirector::Iptables, &["SYS_ADMIN", "SYS_PTRACE", "NET_ADMIN"])]
+    #[case(AgentRedirector::Nftables, &["SYS_ADMIN", "SYS_PTRACE", "NET_ADMIN"])]
+    #[case(AgentRedirector::Ebpf, &["SYS_ADMIN", "SYS_PTRACE", "BPF"])]
+    #[case(AgentRedirector::Auto, &["SYS_ADMIN", "SYS_PTRACE", "NET_ADMIN", "BPF"])]
+    fn capabilities_for_redirector(#[case] redirector: AgentRedirector, #[case] expected: &[&str]) {
+        let mut config_context = ConfigContext::default();
+        let mut agent = AgentFileConfig::default()
+            .generate_config(&mut config_context)
+            .unwrap();
+        agent.redirector = redirector;
+
+        let capabilities = get_capabilities(&agent)
+            .into_iter()
+            .map(LinuxCapability::as_spec_str)
+            .collect::<Vec<_>>();
+        assert_eq!(capabilities, expected);
+    }
+
+    #[rstest]
+    #[case(None, None)]
+    #[case(Some("TEAM_A"), Some("TEAM_A"))]
+    #[case(Some("NOT A VALID PREFIX"), None)]
+    fn iptables_chain_prefix_env(#[case] prefix: Option<&str>, #[case] expected: Option<&str>) {
+        let mut config_context = ConfigContext::default();
+        let mut agent = AgentFileConfig::default()
+            .generate_config(&mut config_context)
+            .unwrap();
+        agent.iptables_chain_prefix = prefix.map(ToString::to_string);
+
+        let env = agent_env(&agent, &ContainerParams::from(ContainerConfig::default()));
+        let value = env
+            .iter()
+            .find(|env| env.name == envs::IPTABLES_CHAIN_PREFIX.name)
+            .and_then(|env| env.value.as_deref());
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn iptables_exclusions_env() {
+        let mut config_context = ConfigContext::default();
+        let mut agent = AgentFileConfig::default()
+            .generate_config(&mut config_context)
+            .unwrap();
+        agent.iptables_exclude_destinations =
+            Some(vec!["169.254.169.254".into(), "fd00:ec2::/64".into()]);
+
+        let env = agent_env(&agent, &ContainerParams::from(ContainerConfig::default()));
+        let value = |name| {
+            env.iter()
+                .find(|env| env.name == name)
+                .and_then(|env| env.value.as_deref())
+        };
+        assert_eq!(
+            value(envs::IPTABLES_EXCLUDE_DESTINATIONS.name),
+            Some("169.254.169.254/32,fd00:ec2::/64")
+        );
+        assert_eq!(value(envs::IPTABLES_EXCLUDE_SOURCES.name), None);
+        assert_eq!(value(envs::IPTABLES_RECONCILE_INTERVAL.name), None);
+
+        let params = ContainerParams::from(ContainerConfig {
+            excluded_ports: vec![9901, 15000],
+            ..Default::default()
+        });
+        let env = agent_env(&agent, &params);
+        assert!(env.iter().any(|env| {
+            env.name == envs::IPTABLES_EXCLUDE_PORTS.name
+                && env.value.as_deref() == Some("9901,15000")
+        }));
+
+        agent.iptables_reconcile_interval = Some(30);
+        let env = agent_env(&agent, &ContainerParams::from(ContainerConfig::default()));
+        assert!(env.iter().any(|env| {
+            env.name == envs::IPTABLES_RECONCILE_INTERVAL.name && env.value.as_deref() == Some("30")
+        }));
+    }
 
     #[rstest]
     #[case("agent ready", None)]
@@ -3,9 +3,10 @@ use std::{
     ops::{Deref, Not},
 };
 
-use k8s_openapi::NamespaceResourceScope;
+use k8s_openapi::{NamespaceResourceScope, api::batch::v1::Job};
 use kube::{
     Api, Client, Config, Discovery,
+    api::DeleteParams,
     client::ClientBuilder,
     config::{KubeConfigOptions, Kubeconfig},
 };
@@ -53,12 +54,13 @@ impl KubernetesAPI {
     /// If [`LayerConfig::target`] specifies a targetless run,
     /// replaces [`AgentConfig::namespace`] with the target namespace.
     pub async fn create<P: Progress>(config: &LayerConfig, progress: &P) -> Result<Self> {
-        let client_config = create_kube_config(
+        let mut client_config = create_kube_config(
             config.accept_invalid_certificates,
             config.kubeconfig.clone(),
             config.kube_context.clone(),
         )
         .await?;
+        apply_connection_proxy(&mut client_config, &config.agent)?;
 
         let client = progress
             .suspend(|| ClientBuilder::try_from(client_config.clone()))?
@@ -180,6 +182,25 @@ impl KubernetesAPI {
         Ok(stream)
     }
 
+    /// Deletes the agent job with the given name, e.g. one left behind by a failed
+    /// [`KubernetesAPI::create_agent`].
+    ///
+    /// Does nothing for ephemeral agents, as ephemeral containers can't be removed from the pod
+    /// (they exit on their own once their idle TTL passes), and when the job does not exist.
+    #[tracing::instrument(level = Level::DEBUG, skip(self), err)]
+    pub async fn delete_agent_job(&self, name: &str) -> Result<()> {
+        if self.agent.ephemeral {
+            return Ok(());
+        }
+
+        let job_api: Api<Job> = get_k8s_resource_api(&self.client, self.agent.namespace.as_deref());
+        match job_api.delete(name, &DeleteParams::background()).await {
+            Ok(..) => Ok(()),
+            Err(kube::Error::Api(response)) if response.code == 404 => Ok(()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
     /// Prepares params to create an agent.
     ///
     /// Unless targetless, fetches [`RuntimeData`] for the given target and fills
@@ -209,6 +230,13 @@ impl KubernetesAPI {
 
         config.pod_ips = pod_ips;
 
+        if config.limit_to_target_container {
+            config.excluded_ports = runtime_data
+                .as_ref()
+                .map(|runtime_data| runtime_data.other_containers_ports.clone())
+                .unwrap_or_default();
+        }
+
         Ok((config.into(), runtime_data))
     }
 
@@ -231,12 +259,14 @@ impl KubernetesAPI {
             .create_agent_params(target_config, container_config)
             .await?;
 
-        if let Some(RuntimeData {
-            guessed_container,
-            container_name,
-            containers_probe_ports,
-            ..
-        }) = runtime_data.as_ref()
+        if let Some(
+            runtime_data @ RuntimeData {
+                guessed_container,
+                container_name,
+                containers_probe_ports,
+                ..
+            },
+        ) = runtime_data.as_ref()
         {
             if *guessed_container {
                 progress.warning(format!("Target has multiple containers, mirrord picked \"{container_name}\". To target a different one, include it in the target path.").as_str());
@@ -259,6 +289,30 @@ impl KubernetesAPI {
                     stolen_probes,
                 ));
                 }
+
+                let undeclared_ports = runtime_data.undeclared_ports(&network_config.incoming);
+                if undeclared_ports.is_empty().not() {
+                    let ports = undeclared_ports
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ");
+
+                    if network_config.incoming.strict_ports {
+                        return Err(KubeApiError::UndeclaredPorts {
+                            container: container_name.clone(),
+                            ports,
+                        });
+                    }
+
+                    progress.warning(&format!(
+                        "Ports [{ports}] are not declared by the target container \
+                        \"{container_name}\", so the traffic mirrord gets on them may be meant \
+                        for another container in the pod. Set \
+                        `feature.network.incoming.limit_to_target_container` to leave the ports \
+                        of other containers alone."
+                    ));
+                }
             }
         }
 
@@ -388,6 +442,19 @@ where
     Ok(config)
 }
 
+/// Makes the given [`Config`] use [`AgentConfig::connection_proxy`], if it is set.
+///
+/// Only used for the clients that establish the agent/operator connection, the proxy
+/// env variables of the user application are not affected.
+pub fn apply_connection_proxy(config: &mut Config, agent: &AgentConfig) -> Result<()> {
+    if let Some(proxy_url) = agent.connection_proxy_uri()? {
+        debug!(%proxy_url, "Using the configured agent connection proxy");
+        config.proxy_url = Some(proxy_url);
+    }
+
+    Ok(())
+}
+
 #[tracing::instrument(level = "trace", skip(client))]
 pub fn get_k8s_resource_api<K>(client: &Client, namespace: Option<&str>) -> Api<K>
 where
@@ -400,3 +467,44 @@ where
         Api::default_namespaced(client.clone())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use kube::Config;
+    use mirrord_config::{
+        agent::AgentFileConfig,
+        config::{ConfigContext, MirrordConfig},
+    };
+    use rstest::rstest;
+
+    use super::apply_connection_proxy;
+
+    #[rstest]
+    #[case::http("http://proxy.internal:3128")]
+    #[case::socks5("socks5://127.0.0.1:1080")]
+    fn connection_proxy_is_used(#[case] proxy: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let mut config_context = ConfigContext::default();
+        let mut agent = AgentFileConfig::default().generate_config(&mut config_context)?;
+        agent.connection_proxy = Some(proxy.to_string());
+
+        let mut config = Config::new("https://kubernetes.default.svc".parse()?);
+        apply_connection_proxy(&mut config, &agent)?;
+
+        assert_eq!(config.proxy_url.unwrap().to_string(), format!("{proxy}/"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn connection_proxy_not_set() -> Result<(), Box<dyn std::error::Error>> {
+        let mut config_context = ConfigContext::default();
+        let agent = AgentFileConfig::default().generate_config(&mut config_context)?;
+
+        let mut config = Config::new("https://kubernetes.default.svc".parse()?);
+        apply_connection_proxy(&mut config, &agent)?;
+
+        assert!(config.proxy_url.is_none());
+
+        Ok(())
+    }
+}
@@ -3,16 +3,16 @@ use std::{fmt, ops::Not, time::Instant};
 use futures::{Stream, StreamExt, TryStreamExt, stream};
 use itertools::join;
 use k8s_openapi::{
-    ClusterResourceScope, Metadata, NamespaceResourceScope,
+    ClusterResourceScope, NamespaceResourceScope,
     api::{
         apps::v1::{Deployment, ReplicaSet, StatefulSet},
         batch::v1::{CronJob, Job},
-        core::v1::{Pod, Service},
+        core::v1::{Pod, PodTemplateSpec, Service},
     },
 };
 use kube::{Api, Resource, api::ListParams};
 use mirrord_config::target::TargetType;
-use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 
 use crate::{
     api::{container::SKIP_NAMES, kubernetes::rollout::Rollout},
@@ -21,18 +21,226 @@ use crate::{
 
 pub struct KubeResourceSeeker<'a> {
     pub client: &'a kube::Client,
-    pub namespace: &'a str,
+    /// Namespace to look for targets in, [`None`] means all namespaces.
+    pub namespace: Option<&'a str>,
     pub copy_target: bool,
 }
 
+/// A target found by the [`KubeResourceSeeker`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SeekedTarget {
+    /// E.g `pod/my-pod-1234/container/my-container`.
+    pub path: String,
+    /// Namespace of the target resource.
+    pub namespace: String,
+    /// [`None`] for resources that don't run pods on their own (services and cronjobs).
+    pub readiness: Option<Readiness>,
+    /// Names of the containers in the target's pod template, excluding the mesh sidecars.
+    ///
+    /// Empty for services, and for rollouts that reference their pod template with a
+    /// `workloadRef`.
+    pub containers: Vec<String>,
+}
+
+/// How many replicas of a workload (or containers of a pod) are ready.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Readiness {
+    pub ready: i32,
+    pub desired: i32,
+}
+
+/// A resource that can be listed by the [`KubeResourceSeeker`] as a mirrord target.
+trait SeekableResource:
+    'static
+    + Clone
+    + fmt::Debug
+    + DeserializeOwned
+    + Resource<DynamicType = (), Scope = NamespaceResourceScope>
+    + Send
+{
+    /// First segment of the target path, e.g. `deployment`.
+    const PATH_PREFIX: &'static str;
+
+    fn readiness(&self) -> Option<Readiness>;
+
+    fn pod_template(&self) -> Option<&PodTemplateSpec>;
+
+    /// Builds a [`SeekedTarget`] from this resource, [`None`] if the resource has no name.
+    fn seeked_target(&self) -> Option<SeekedTarget> {
+        let name = self.meta().name.as_deref()?;
+
+        Some(SeekedTarget {
+            path: format!("{}/{name}", Self::PATH_PREFIX),
+            namespace: self.meta().namespace.clone().unwrap_or_default(),
+            readiness: self.readiness(),
+            containers: self
+                .pod_template()
+                .and_then(|template| template.spec.as_ref())
+                .map(|spec| {
+                    spec.containers
+                        .iter()
+                        .filter(|container| SKIP_NAMES.contains(container.name.as_str()).not())
+                        .map(|container| container.name.clone())
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl SeekableResource for Deployment {
+    const PATH_PREFIX: &'static str = "deployment";
+
+    fn readiness(&self) -> Option<Readiness> {
+        Some(Readiness {
+            ready: self.status.as_ref()?.ready_replicas.unwrap_or_default(),
+            desired: self.spec.as_ref()?.replicas.unwrap_or(1),
+        })
+    }
+
+    fn pod_template(&self) -> Option<&PodTemplateSpec> {
+        Some(&self.spec.as_ref()?.template)
+    }
+}
+
+impl SeekableResource for Rollout {
+    const PATH_PREFIX: &'static str = "rollout";
+
+    fn readiness(&self) -> Option<Readiness> {
+        Some(Readiness {
+            ready: self.status.as_ref()?.available_replicas.unwrap_or_default(),
+            desired: self.spec.as_ref()?.replicas.unwrap_or(1),
+        })
+    }
+
+    fn pod_template(&self) -> Option<&PodTemplateSpec> {
+        self.spec.as_ref()?.template.as_ref().map(AsRef::as_ref)
+    }
+}
+
+impl SeekableResource for StatefulSet {
+    const PATH_PREFIX: &'static str = "statefulset";
+
+    fn readiness(&self) -> Option<Readiness> {
+        Some(Readiness {
+            ready: self.status.as_ref()?.ready_replicas.unwrap_or_default(),
+            desired: self.spec.as_ref()?.replicas.unwrap_or(1),
+        })
+    }
+
+    fn pod_template(&self) -> Option<&PodTemplateSpec> {
+        Some(&self.spec.as_ref()?.template)
+    }
+}
+
+impl SeekableResource for ReplicaSet {
+    const PATH_PREFIX: &'static str = "replicaset";
+
+    fn readiness(&self) -> Option<Readiness> {
+        Some(Readiness {
+            ready: self.status.as_ref()?.ready_replicas.unwrap_or_default(),
+            desired: self.spec.as_ref()?.replicas.unwrap_or(1),
+        })
+    }
+
+    fn pod_template(&self) -> Option<&PodTemplateSpec> {
+        self.spec.as_ref()?.template.as_ref()
+    }
+}
+
+impl SeekableResource for Job {
+    const PATH_PREFIX: &'static str = "job";
+
+    fn readiness(&self) -> Option<Readiness> {
+        Some(Readiness {
+            ready: self.status.as_ref()?.ready.unwrap_or_default(),
+            desired: self.spec.as_ref()?.parallelism.unwrap_or(1),
+        })
+    }
+
+    fn pod_template(&self) -> Option<&PodTemplateSpec> {
+        Some(&self.spec.as_ref()?.template)
+    }
+}
+
+impl SeekableResource for CronJob {
+    const PATH_PREFIX: &'static str = "cronjob";
+
+    fn readiness(&self) -> Option<Readiness> {
+        None
+    }
+
+    fn pod_template(&self) -> Option<&PodTemplateSpec> {
+        Some(&self.spec.as_ref()?.job_template.spec.as_ref()?.template)
+    }
+}
+
+impl SeekableResource for Service {
+    const PATH_PREFIX: &'static str = "service";
+
+    fn readiness(&self) -> Option<Readiness> {
+        None
+    }
+
+    fn pod_template(&self) -> Option<&PodTemplateSpec> {
+        None
+    }
+}
+
+/// Builds the [`SeekedTarget`]s of a [`Pod`], one per container if it has more than one.
+fn pod_targets(pod: Pod) -> Option<Vec<SeekedTarget>> {
+    let name = pod.metadata.name?;
+    let namespace = pod.metadata.namespace.unwrap_or_default();
+    let spec = pod.spec?;
+
+    let readiness = Readiness {
+        ready: pod
+            .status
+            .and_then(|status| status.container_statuses)
+            .unwrap_or_default()
+            .iter()
+            .filter(|status| status.ready)
+            .count() as i32,
+        desired: spec.containers.len() as i32,
+    };
+
+    let containers = spec
+        .containers
+        .into_iter()
+        .map(|container| container.name)
+        .filter(|container| SKIP_NAMES.contains(container.as_str()).not())
+        .collect::<Vec<_>>();
+
+    let paths = if containers.len() == 1 {
+        vec![format!("pod/{name}")]
+    } else {
+        containers
+            .iter()
+            .map(|container| format!("pod/{name}/container/{container}"))
+            .collect()
+    };
+
+    Some(
+        paths
+            .into_iter()
+            .map(|path| SeekedTarget {
+                path,
+                namespace: namespace.clone(),
+                readiness: Some(readiness),
+                containers: containers.clone(),
+            })
+            .collect(),
+    )
+}
+
 impl KubeResourceSeeker<'_> {
     /// Returns all resource types that don't require the operator to operate ie. [`Pod`],
     /// [`Deployment`] and [`Rollout`]
-    pub async fn all_open_source(&self) -> Result<Vec<String>> {
+    pub async fn all_open_source(&self) -> Result<Vec<SeekedTarget>> {
         let (pods, deployments, rollouts) = tokio::try_join!(
             self.pods(),
             self.deployments(),
-            self.simple_list_resource::<Rollout>("rollout")
+            self.simple_list_resource::<Rollout>()
         )?;
 
         Ok(pods
@@ -51,16 +259,16 @@ impl KubeResourceSeeker<'_> {
     /// 6. [`Service`]s
     /// 7. [`ReplicaSet`]s
     /// 8. [`Pod`]s
-    pub async fn all(&self) -> Result<Vec<String>> {
+    pub async fn all(&self) -> Result<Vec<SeekedTarget>> {
         let (pods, deployments, rollouts, jobs, cronjobs, statefulsets, services, replicasets) = tokio::try_join!(
             self.pods(),
-            self.simple_list_resource::<Deployment>("deployment"),
-            self.simple_list_resource::<Rollout>("rollout"),
-            self.simple_list_resource::<Job>("job"),
-            self.simple_list_resource::<CronJob>("cronjob"),
-            self.simple_list_resource::<StatefulSet>("statefulset"),
-            self.simple_list_resource::<Service>("service"),
-            self.simple_list_resource::<ReplicaSet>("replicaset"),
+            self.simple_list_resource::<Deployment>(),
+            self.simple_list_resource::<Rollout>(),
+            self.simple_list_resource::<Job>(),
+            self.simple_list_resource::<CronJob>(),
+            self.simple_list_resource::<StatefulSet>(),
+            self.simple_list_resource::<Service>(),
+            self.simple_list_resource::<ReplicaSet>(),
         )?;
 
         Ok(deployments
@@ -81,7 +289,7 @@ impl KubeResourceSeeker<'_> {
         &self,
         resource_types: Vec<TargetType>,
         operator_active: bool,
-    ) -> Result<Vec<String>> {
+    ) -> Result<Vec<SeekedTarget>> {
         Ok(futures::future::try_join_all(
             resource_types
                 .into_iter()
@@ -98,27 +306,22 @@ impl KubeResourceSeeker<'_> {
         &self,
         resource_type: TargetType,
         operator_active: bool,
-    ) -> Result<Vec<String>> {
+    ) -> Result<Vec<SeekedTarget>> {
         match resource_type {
             TargetType::Deployment if operator_active => {
-                self.simple_list_resource::<Deployment>("deployment").await
+                self.simple_list_resource::<Deployment>().await
             }
             TargetType::Deployment => self.deployments().await,
             TargetType::Pod => self.pods().await,
-            TargetType::Rollout => self.simple_list_resource::<Rollout>("rollout").await,
-            TargetType::Job if operator_active => self.simple_list_resource::<Job>("job").await,
-            TargetType::CronJob if operator_active => {
-                self.simple_list_resource::<CronJob>("cronjob").await
-            }
+            TargetType::Rollout => self.simple_list_resource::<Rollout>().await,
+            TargetType::Job if operator_active => self.simple_list_resource::<Job>().await,
+            TargetType::CronJob if operator_active => self.simple_list_resource::<CronJob>().await,
             TargetType::StatefulSet if operator_active => {
-                self.simple_list_resource::<StatefulSet>("statefulset")
-                    .await
-            }
-            TargetType::Service if operator_active => {
-                self.simple_list_resource::<Service>("service").await
+                self.simple_list_resource::<StatefulSet>().await
             }
+            TargetType::Service if operator_active => self.simple_list_resource::<Service>().await,
             TargetType::ReplicaSet if operator_active => {
-                self.simple_list_resource::<ReplicaSet>("replicaset").await
+                self.simple_list_resource::<ReplicaSet>().await
             }
             TargetType::Targetless => Err(KubeApiError::InvalidTargetType(resource_type)),
             resource_type if !operator_active => {
@@ -128,9 +331,9 @@ impl KubeResourceSeeker<'_> {
         }
     }
 
-    /// Returns a list of (pod name, [container names]) pairs, filtering out mesh side cars
+    /// Returns the targets of all pods, one per container, filtering out mesh side cars
     /// as well as any pods which are not ready or have crashed.
-    async fn pods(&self) -> Result<Vec<String>> {
+    async fn pods(&self) -> Result<Vec<SeekedTarget>> {
         fn check_pod_status(pod: &Pod) -> bool {
             pod.status
                 .as_ref()
@@ -144,20 +347,6 @@ impl KubeResourceSeeker<'_> {
                 .unwrap_or(false)
         }
 
-        fn create_pod_container_map(pod: Pod) -> Option<(String, Vec<String>)> {
-            let name = pod.metadata.name.clone()?;
-            let containers = pod
-                .spec
-                .as_ref()?
-                .containers
-                .iter()
-                .filter(|&container| SKIP_NAMES.contains(container.name.as_str()).not())
-                .map(|container| container.name.clone())
-                .collect();
-
-            Some((name, containers))
-        }
-
         // `copy_target` can be used on dead resources.
         if self.copy_target {
             self.list_all_namespaced(None, None)
@@ -165,17 +354,8 @@ impl KubeResourceSeeker<'_> {
             self.list_all_namespaced(Some("status.phase=Running"), None)
         }
         .try_filter(|pod| std::future::ready(self.copy_target || check_pod_status(pod)))
-        .try_filter_map(|pod| std::future::ready(Ok(create_pod_container_map(pod))))
-        .map_ok(|(pod, containers)| {
-            stream::iter(if containers.len() == 1 {
-                vec![Ok(format!("pod/{pod}"))]
-            } else {
-                containers
-                    .iter()
-                    .map(move |container| Ok(format!("pod/{pod}/container/{container}")))
-                    .collect()
-            })
-        })
+        .try_filter_map(|pod| std::future::ready(Ok(pod_targets(pod))))
+        .map_ok(|targets| stream::iter(targets.into_iter().map(Ok)))
         .try_flatten()
         .try_collect()
         .await
@@ -185,7 +365,7 @@ impl KubeResourceSeeker<'_> {
     /// The list of deployments that have at least 1 `Replicas` and a deployment name.
     ///
     /// - When `copy_target` is enabled, we ignore the replicas requirement.
-    async fn deployments(&self) -> Result<Vec<String>> {
+    async fn deployments(&self) -> Result<Vec<SeekedTarget>> {
         fn check_deployment_replicas(deployment: &Deployment) -> bool {
             deployment
                 .status
@@ -199,36 +379,19 @@ impl KubeResourceSeeker<'_> {
             .try_filter(|deployment| {
                 std::future::ready(self.copy_target || check_deployment_replicas(deployment))
             })
-            .try_filter_map(|deployment| {
-                std::future::ready(Ok(deployment
-                    .metadata
-                    .name
-                    .map(|name| format!("deployment/{name}"))))
-            })
+            .try_filter_map(|deployment| std::future::ready(Ok(deployment.seeked_target())))
             .try_collect()
             .await
             .map_err(From::from)
     }
 
-    async fn simple_list_resource<'s, R>(&self, prefix: &'s str) -> Result<Vec<String>>
+    async fn simple_list_resource<R>(&self) -> Result<Vec<SeekedTarget>>
     where
-        R: 'static
-            + Clone
-            + fmt::Debug
-            + for<'de> de::Deserialize<'de>
-            + Resource<DynamicType = (), Scope = NamespaceResourceScope>
-            + Metadata
-            + Send,
+        R: SeekableResource,
     {
         self.list_all_namespaced::<R>(None, None)
             .filter(|response| std::future::ready(response.is_ok()))
-            .try_filter_map(|rollout| {
-                std::future::ready(Ok(rollout
-                    .meta()
-                    .name
-                    .as_ref()
-                    .map(|name| format!("{prefix}/{name}"))))
-            })
+            .try_filter_map(|resource| std::future::ready(Ok(resource.seeked_target())))
             .try_collect()
             .await
             .map_err(From::from)
@@ -254,7 +417,8 @@ impl KubeResourceSeeker<'_> {
         }
     }
 
-    /// Returns a [`Stream`] of all objects in this [`KubeResourceSeeker`]'s namespace.
+    /// Returns a [`Stream`] of all objects in this [`KubeResourceSeeker`]'s namespace, or in all
+    /// namespaces if it's not set.
     ///
     /// 1. `field_selector` can be used for filtering.
     /// 2. Our own resources are excluded.
@@ -271,8 +435,11 @@ impl KubeResourceSeeker<'_> {
             + DeserializeOwned
             + Send,
     {
-        let namespace = self.namespace.to_string();
-        let api = Api::namespaced(self.client.clone(), &namespace);
+        let namespace = self.namespace.map(ToString::to_string);
+        let api = match &namespace {
+            Some(namespace) => Api::namespaced(self.client.clone(), namespace),
+            None => Api::all(self.client.clone()),
+        };
         let mut params = Self::make_list_params(field_selector, label_selector);
 
         async_stream::stream! {
@@ -287,7 +454,7 @@ impl KubeResourceSeeker<'_> {
                     fetched_resources = response.items.len(),
                     continue_token = ?response.metadata.continue_,
                     list_params = ?params,
-                    namespace,
+                    ?namespace,
                     "Made a resource list request",
                 );
 
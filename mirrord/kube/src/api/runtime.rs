@@ -16,7 +16,10 @@ use k8s_openapi::{
 };
 use kube::{Api, Client, Resource, api::ListParams};
 use mirrord_agent_env::mesh::MeshVendor;
-use mirrord_config::target::Target;
+use mirrord_config::{
+    feature::network::incoming::{IncomingConfig, IncomingMode},
+    target::Target,
+};
 use serde::de::DeserializeOwned;
 use thiserror::Error;
 use tracing::Level;
@@ -99,6 +102,13 @@ pub struct RuntimeData {
     /// Ports where HTTP/gRPC probes are configured
     /// in the target pod.
     pub containers_probe_ports: Vec<u16>,
+
+    /// TCP ports declared (`containerPort`) by the target container.
+    pub container_ports: Vec<u16>,
+
+    /// TCP ports declared by the other containers in the target pod, but not by the target
+    /// container.
+    pub other_containers_ports: Vec<u16>,
 }
 
 impl RuntimeData {
@@ -221,6 +231,12 @@ impl RuntimeData {
             }
         };
 
+        let (container_ports, other_containers_ports) = pod
+            .spec
+            .as_ref()
+            .map(|spec| Self::declared_ports(&spec.containers, &container_name))
+            .unwrap_or_default();
+
         let mesh = check_mesh_vendor(pod);
 
         Ok(RuntimeData {
@@ -240,9 +256,78 @@ impl RuntimeData {
                 .and_then(|spec| spec.share_process_namespace)
                 .unwrap_or_default(),
             containers_probe_ports,
+            container_ports,
+            other_containers_ports,
         })
     }
 
+    /// Returns the TCP ports declared by the container named `container_name`, and the ones
+    /// declared only by the other `containers`, both sorted.
+    fn declared_ports(containers: &[Container], container_name: &str) -> (Vec<u16>, Vec<u16>) {
+        let tcp_ports = |container: &Container| {
+            container
+                .ports
+                .iter()
+                .flatten()
+                .filter(|port| {
+                    port.protocol
+                        .as_deref()
+                        .is_none_or(|protocol| protocol == "TCP")
+                })
+                .filter_map(|port| u16::try_from(port.container_port).ok())
+                .collect::<Vec<_>>()
+        };
+
+        let (target, others): (Vec<_>, Vec<_>) = containers
+            .iter()
+            .partition(|container| container.name == container_name);
+
+        let mut container_ports = target.into_iter().flat_map(tcp_ports).collect::<Vec<_>>();
+        container_ports.sort_unstable();
+        container_ports.dedup();
+
+        let mut other_containers_ports = others
+            .into_iter()
+            .flat_map(tcp_ports)
+            .filter(|port| container_ports.binary_search(port).is_err())
+            .collect::<Vec<_>>();
+        other_containers_ports.sort_unstable();
+        other_containers_ports.dedup();
+
+        (container_ports, other_containers_ports)
+    }
+
+    /// Returns the ports that mirrord is configured to subscribe to (in
+    /// [`IncomingConfig::ports`] and the HTTP filter ports), but are not declared by the target
+    /// container.
+    ///
+    /// Returns nothing when the target container declares no ports at all, as then we can't tell
+    /// which ports it uses.
+    pub fn undeclared_ports(&self, incoming: &IncomingConfig) -> Vec<u16> {
+        if self.container_ports.is_empty() || incoming.mode == IncomingMode::Off {
+            return vec![];
+        }
+
+        let mut undeclared = incoming
+            .ports
+            .iter()
+            .flatten()
+            .chain(
+                incoming
+                    .http_filter
+                    .ports
+                    .iter()
+                    .flat_map(|ports| ports.iter()),
+            )
+            .copied()
+            .filter(|port| self.container_ports.contains(port).not())
+            .collect::<Vec<_>>();
+        undeclared.sort_unstable();
+        undeclared.dedup();
+
+        undeclared
+    }
+
     /// Resolves and stores `kubernetes.io/hostname` label from the target node when available.
     /// This is best-effort and intentionally non-fatal.
     #[tracing::instrument(level = Level::TRACE, skip(client))]
@@ -519,13 +604,109 @@ impl RuntimeDataProvider for ResolvedTarget<true> {
 
 #[cfg(test)]
 mod tests {
-    use mirrord_config::target::{
-        deployment::DeploymentTarget, job::JobTarget, pod::PodTarget, service::ServiceTarget,
+    use mirrord_config::{
+        config::{ConfigContext, MirrordConfig},
+        feature::network::incoming::IncomingFileConfig,
+        target::{
+            deployment::DeploymentTarget, job::JobTarget, pod::PodTarget, service::ServiceTarget,
+        },
     };
-    use rstest::rstest;
+    use rstest::{fixture, rstest};
 
     use super::*;
 
+    /// A running pod with an `app` container and an `envoy` sidecar.
+    #[fixture]
+    fn multi_container_pod() -> Pod {
+        serde_json::from_value(serde_json::json!({
+            "metadata": { "name": "foo", "namespace": "default" },
+            "spec": {
+                "nodeName": "node",
+                "containers": [
+                    {
+                        "name": "app",
+                        "ports": [
+                            { "containerPort": 8080 },
+                            { "containerPort": 9000, "protocol": "TCP" },
+                            { "containerPort": 5353, "protocol": "UDP" }
+                        ]
+                    },
+                    {
+                        "name": "envoy",
+                        "ports": [
+                            { "containerPort": 9901 },
+                            { "containerPort": 15000 },
+                            { "containerPort": 8080 }
+                        ]
+                    }
+                ]
+            },
+            "status": {
+                "phase": "Running",
+                "podIPs": [{ "ip": "10.0.0.1" }],
+                "containerStatuses": [
+                    {
+                        "name": "app",
+                        "ready": true,
+                        "containerID": "containerd://app",
+                        "image": "app",
+                        "imageID": "app",
+                        "restartCount": 0
+                    },
+                    {
+                        "name": "envoy",
+                        "ready": true,
+                        "containerID": "containerd://envoy",
+                        "image": "envoy",
+                        "imageID": "envoy",
+                        "restartCount": 0
+                    }
+                ]
+            }
+        }))
+        .unwrap()
+    }
+
+    /// Ports of the other containers are separated from the ones of the target container, so that
+    /// the agent can exclude them from redirection.
+    #[rstest]
+    fn container_ports(multi_container_pod: Pod) {
+        let runtime_data = RuntimeData::from_pod(&multi_container_pod, Some("app")).unwrap();
+        assert_eq!(runtime_data.container_ports, [8080, 9000]);
+        assert_eq!(runtime_data.other_containers_ports, [9901, 15000]);
+
+        let runtime_data = RuntimeData::from_pod(&multi_container_pod, Some("envoy")).unwrap();
+        assert_eq!(runtime_data.container_ports, [8080, 9901, 15000]);
+        assert_eq!(runtime_data.other_containers_ports, [9000]);
+    }
+
+    /// Subscribed ports that the target container does not declare are reported.
+    #[rstest]
+    #[case::declared(r#"{ "mode": "steal", "ports": [8080, 9000] }"#, &[])]
+    #[case::sidecar(r#"{ "mode": "steal", "ports": [8080, 9901] }"#, &[9901])]
+    #[case::http_filter(
+        r#"{ "mode": "steal", "http_filter": { "header_filter": "x: y", "ports": [15000] } }"#,
+        &[15000]
+    )]
+    #[case::off(r#"{ "mode": "off", "ports": [9901] }"#, &[])]
+    fn undeclared_ports(
+        multi_container_pod: Pod,
+        #[case] incoming: &str,
+        #[case] expected: &[u16],
+    ) {
+        let incoming = serde_json::from_str::<IncomingFileConfig>(incoming)
+            .unwrap()
+            .generate_config(&mut ConfigContext::default())
+            .unwrap();
+
+        let runtime_data = RuntimeData::from_pod(&multi_container_pod, Some("app")).unwrap();
+        assert_eq!(runtime_data.undeclared_ports(&incoming), expected);
+
+        let mut runtime_data = runtime_data;
+        runtime_data.container_ports.clear();
+        assert!(runtime_data.undeclared_ports(&incoming).is_empty());
+    }
+
     #[rstest]
     #[case("pod/foobaz", Target::Pod(PodTarget {pod: "foobaz".to_string(), container: None}))]
     #[case("deployment/foobaz", Target::Deployment(DeploymentTarget {deployment: "foobaz".to_string(), container: None}))]
@@ -1,7 +1,7 @@
 use std::{convert::Infallible, fmt};
 
 use kube::Resource;
-use mirrord_config::target::TargetType;
+use mirrord_config::{config::ConfigError, target::TargetType};
 use thiserror::Error;
 use tower::retry::backoff::InvalidBackoff;
 
@@ -84,6 +84,20 @@ pub enum KubeApiError {
     /// Spawned agent pod was deleted during startup.
     #[error("Agent pod was unexpectedly deleted")]
     AgentPodDeleted,
+
+    /// [`AgentConfig::connection_proxy`](mirrord_config::agent::AgentConfig::connection_proxy)
+    /// is not a valid proxy URL.
+    #[error("Invalid agent connection proxy: {0}")]
+    InvalidConnectionProxy(#[from] ConfigError),
+
+    /// Some of the configured incoming ports are not declared by the target container, and
+    /// [`IncomingConfig::strict_ports`](mirrord_config::feature::network::incoming::IncomingConfig::strict_ports)
+    /// is set.
+    #[error(
+        "Ports [{ports}] are not declared by the target container `{container}` \
+        (`feature.network.incoming.strict_ports` is set)"
+    )]
+    UndeclaredPorts { container: String, ports: String },
 }
 
 impl KubeApiError {
@@ -153,6 +167,24 @@ impl KubeApiError {
     pub fn requires_copy<R: Resource<DynamicType = ()>>() -> Self {
         Self::RequiresCopy(R::plural(&()).into_owned())
     }
+
+    /// Returns whether this is a transient failure of creating or connecting to the agent, after
+    /// which the operation can be retried.
+    ///
+    /// Authentication and permission failures are never transient.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::KubeError(kube::Error::Api(response)) => {
+                [429, 500, 502, 503, 504].contains(&response.code)
+            }
+            Self::KubeError(kube::Error::HyperError(..) | kube::Error::Service(..)) => true,
+            Self::KubeConnectionError(..)
+            | Self::PortForwardFailed
+            | Self::AgentPodDeleted
+            | Self::AgentReadyTimeout => true,
+            _ => false,
+        }
+    }
 }
 
 impl From<Infallible> for KubeApiError {
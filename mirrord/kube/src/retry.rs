@@ -61,6 +61,7 @@ impl TryFrom<&StartupRetryConfig> for RetryKube {
             min_ms,
             max_ms,
             max_retries,
+            ..
         }: &StartupRetryConfig,
     ) -> Result<Self, Self::Error> {
         let backoff = ExponentialBackoffMaker::new(
@@ -85,6 +86,8 @@ impl Default for RetryKube {
             min_ms: 500,
             max_ms: 5000,
             max_retries: 2,
+            operator_session_retries: 2,
+            agent_retries: 3,
         };
 
         Self::try_from(&retry_config).expect("Default values should be valid!")
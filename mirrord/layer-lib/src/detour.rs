@@ -228,6 +228,13 @@ pub enum Bypass {
     /// DNS query should be done locally.
     LocalDns,
 
+    /// The app's working directory is local, so `getcwd` should be handled locally.
+    LocalWorkingDirectory,
+
+    /// Called `getnameinfo` without a `host` buffer, or with `NI_NUMERICHOST`, so there is no
+    /// name to resolve remotely.
+    NumericHost,
+
     /// Operation is not implemented, but it should not be a hard error.
     ///
     /// Useful for operations that are version gated, and we want to bypass when the protocol
@@ -239,8 +246,10 @@ pub enum Bypass {
     OpenLocal,
 
     /// Invalid argument value
-    #[cfg(target_os = "macos")]
     InvalidArgValue,
+
+    /// Socket option (`level`, `optname`) that is not handled by the `getsockopt` hook.
+    SocketOption(i32, i32),
 }
 
 #[cfg(unix)]
@@ -146,6 +146,9 @@ pub enum HookError {
     #[error("mirrord-layer: Could not get PROXY_CONNECTION, can't send a hook message!")]
     CannotGetProxyConnection,
 
+    #[error("mirrord-layer: The internal proxy rejected this layer: {0}")]
+    SessionRejected(String),
+
     #[error("mirrord-layer: Converting int failed with `{0}`!")]
     TryFromInt(#[from] std::num::TryFromIntError),
 
@@ -158,6 +161,10 @@ pub enum HookError {
     #[error("mirrord-layer: Failed creating local file for `remote_fd` `{0}`! with errno `{1}`")]
     LocalFileCreation(u64, i32),
 
+    #[cfg(unix)]
+    #[error("mirrord-layer: Failed creating local virtual file with errno `{0}`!")]
+    VirtualFileCreation(i32),
+
     #[cfg(target_os = "macos")]
     #[error("mirrord-layer: SIP patch failed with error `{0}`!")]
     FailedSipPatch(#[from] SipError),
@@ -371,6 +378,7 @@ fn get_platform_errno(fail: HookError) -> i32 {
         HookError::Null(_) => libc::EINVAL,
         HookError::TryFromInt(_) => libc::EINVAL,
         HookError::CannotGetProxyConnection => libc::EINVAL,
+        HookError::SessionRejected(_) => libc::EPROTO,
         HookError::ProxyError(_) => libc::EINVAL,
         HookError::IO(io_fail) => io_fail.raw_os_error().unwrap_or(libc::EIO),
         HookError::LockError => libc::EINVAL,
@@ -395,6 +403,12 @@ fn get_platform_errno(fail: HookError) -> i32 {
             ResponseError::PortAlreadyStolen(_port) => libc::EINVAL,
             ResponseError::NotImplemented => libc::EINVAL,
             ResponseError::StripPrefix(_) => libc::EINVAL,
+            #[cfg(target_os = "macos")]
+            ResponseError::XattrNotFound => libc::ENOATTR,
+            #[cfg(not(target_os = "macos"))]
+            ResponseError::XattrNotFound => libc::ENODATA,
+            ResponseError::XattrNotSupported => libc::ENOTSUP,
+            ResponseError::LockWouldBlock => libc::EWOULDBLOCK,
             err @ (ResponseError::Forbidden { .. } | ResponseError::ForbiddenWithReason { .. }) => {
                 graceful_exit!(
                     "Stopping mirrord run. Please adjust your mirrord configuration.\n{err}"
@@ -405,6 +419,7 @@ fn get_platform_errno(fail: HookError) -> i32 {
         HookError::Utf8(_) => libc::EINVAL,
         HookError::NullPointer => libc::EINVAL,
         HookError::LocalFileCreation(_, err) => err,
+        HookError::VirtualFileCreation(err) => err,
         #[cfg(target_os = "macos")]
         HookError::FailedSipPatch(_) => libc::EACCES,
         HookError::SocketUnsuportedIpv6 => libc::EAFNOSUPPORT,
@@ -434,6 +449,7 @@ fn get_platform_errno(fail: HookError) -> u32 {
         HookError::Null(_) => WSAEINVAL,
         HookError::TryFromInt(_) => WSAEINVAL,
         HookError::CannotGetProxyConnection => WSAEINVAL,
+        HookError::SessionRejected(_) => ERROR_REVISION_MISMATCH,
         HookError::ProxyError(_) => WSAEINVAL,
         HookError::IO(io_fail) => io_fail
             .raw_os_error()
@@ -469,6 +485,9 @@ fn get_platform_errno(fail: HookError) -> u32 {
             ResponseError::PortAlreadyStolen(_port) => WSAEINVAL,
             ResponseError::NotImplemented => WSAEINVAL,
             ResponseError::StripPrefix(_) => WSAEINVAL,
+            ResponseError::XattrNotFound => ERROR_NOT_FOUND,
+            ResponseError::XattrNotSupported => ERROR_NOT_SUPPORTED,
+            ResponseError::LockWouldBlock => ERROR_LOCK_VIOLATION,
             err @ (ResponseError::Forbidden { .. } | ResponseError::ForbiddenWithReason { .. }) => {
                 graceful_exit!(
                     "Stopping mirrord run. Please adjust your mirrord configuration.\n{err}"
@@ -549,7 +568,10 @@ impl From<HookError> for i64 {
                 | ResponseError::NotFile(_)
                 | ResponseError::NotDirectory(_)
                 | ResponseError::Remote(_)
-                | ResponseError::RemoteIO(_),
+                | ResponseError::RemoteIO(_)
+                | ResponseError::XattrNotFound
+                | ResponseError::XattrNotSupported
+                | ResponseError::LockWouldBlock,
             ) => {
                 info!("libc error (doesn't indicate a problem) >> {fail:#?}")
             }
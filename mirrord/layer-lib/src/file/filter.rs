@@ -1,4 +1,4 @@
-use std::path::Path;
+use std::{collections::HashSet, io, path::Path};
 
 /// Controls which files are ignored (opened locally) by mirrord file operations.
 ///
@@ -12,6 +12,7 @@ use mirrord_config::{
     feature::fs::{FsConfig, FsModeConfig},
     util::VecOrSingle,
 };
+use mirrord_protocol::ErrorKindInternal;
 use regex::{RegexSet, RegexSetBuilder};
 
 #[cfg(unix)]
@@ -83,6 +84,11 @@ pub struct FileFilter {
     pub default_local: RegexSet,
     pub default_remote_ro: RegexSet,
     pub default_not_found: RegexSet,
+    /// Paths that are opened locally when the remote open fails with one of
+    /// [`Self::fallback_local_on`].
+    pub fallback_local: RegexSet,
+    /// Remote errors that trigger the local fallback.
+    pub fallback_local_on: HashSet<io::ErrorKind>,
     pub mode: FsModeConfig,
 }
 
@@ -101,12 +107,16 @@ impl FileFilter {
     /// If not, it does the default behavior set by user (default is read only remote).
     #[mirrord_layer_macro::instrument(level = "trace")]
     pub fn new(fs_config: FsConfig) -> Self {
+        let fallback_local_on = fs_config
+            .fallback_local_error_kinds()
+            .expect("parsing fallback-local error kinds failed");
         let FsConfig {
             read_write,
             read_only,
             local,
             mode,
             not_found,
+            fallback_local,
             ..
         } = fs_config;
 
@@ -117,6 +127,8 @@ impl FileFilter {
         let local = Self::make_regex_set(local).expect("building local path regex set failed");
         let not_found =
             Self::make_regex_set(not_found).expect("building not-found regex set failed");
+        let fallback_local =
+            Self::make_regex_set(fallback_local).expect("building fallback-local regex set failed");
 
         let default_local = generate_local_set();
         let default_remote_ro = generate_remote_ro_set();
@@ -130,6 +142,8 @@ impl FileFilter {
             default_local,
             default_remote_ro,
             default_not_found,
+            fallback_local,
+            fallback_local_on,
             mode,
         }
     }
@@ -161,6 +175,16 @@ impl FileFilter {
         }
     }
 
+    /// Whether the remote open of `path` that failed with `kind` should be retried locally,
+    /// according to `feature.fs.fallback_local` and `feature.fs.fallback_local_on`.
+    pub fn should_fallback_local(&self, path: &Path, kind: &ErrorKindInternal) -> bool {
+        kind.as_io_error_kind()
+            .is_some_and(|kind| self.fallback_local_on.contains(&kind))
+            && self
+                .fallback_local
+                .is_match(path.to_str().unwrap_or_default())
+    }
+
     pub fn check_not_found(&self, path: &Path) -> bool {
         matches!(
             self.check(path.to_str().unwrap_or_default()),
@@ -16,6 +16,7 @@ use mirrord_intproxy_protocol::{
     MessageId, NewSessionRequest, ProxyToLayerMessage,
     codec::{self, CodecError, SyncDecoder, SyncEncoder},
 };
+use mirrord_protocol::{LogLevel, LogMessage};
 use thiserror::Error;
 
 use crate::{
@@ -37,6 +38,12 @@ use crate::{
 /// [`make_proxy_request_no_response`] functions instead.
 pub static mut PROXY_CONNECTION: OnceLock<ProxyConnection> = OnceLock::new();
 
+/// Set when the internal proxy rejected the session of this layer (see
+/// [`ProxyError::SessionRejected`]), instead of [`PROXY_CONNECTION`].
+///
+/// Makes the hooks fail with [`HookError::SessionRejected`].
+pub static SESSION_REJECTED: OnceLock<String> = OnceLock::new();
+
 #[derive(Debug, Error)]
 pub enum ProxyError {
     #[error("{0}")]
@@ -54,6 +61,8 @@ pub enum ProxyError {
     LockPoisoned,
     #[error("{0}")]
     IoFailed(#[from] io::Error),
+    #[error("internal proxy rejected the session: {0}")]
+    SessionRejected(String),
 }
 
 impl<T> From<PoisonError<T>> for ProxyError {
@@ -95,15 +104,19 @@ impl ProxyConnection {
 
         let mut responses = ResponseManager::new(receiver);
         let response = responses.receive(0)?;
-        let ProxyToLayerMessage::NewSession(layer_id) = &response else {
-            return Err(ProxyError::UnexpectedResponse(Box::new(response)));
+        let layer_id = match response {
+            ProxyToLayerMessage::NewSession(layer_id) => layer_id,
+            ProxyToLayerMessage::NewSessionRejected(error) => {
+                return Err(ProxyError::SessionRejected(error));
+            }
+            other => return Err(ProxyError::UnexpectedResponse(Box::new(other))),
         };
 
         Ok(Self {
             sender: Mutex::new(sender),
             responses: Mutex::new(responses),
             next_message_id: AtomicU64::new(1),
-            layer_id: *layer_id,
+            layer_id,
             proxy_addr,
         })
     }
@@ -189,6 +202,11 @@ impl ResponseManager {
                 .receive()?
                 .ok_or(ProxyError::ConnectionClosed)?;
 
+            if let ProxyToLayerMessage::LogMessage(log) = &response.inner {
+                show_log_message(log);
+                continue;
+            }
+
             if response.message_id == response_id {
                 break Ok(response.inner);
             }
@@ -199,6 +217,72 @@ impl ResponseManager {
     }
 }
 
+/// Logs a [`ProxyToLayerMessage::LogMessage`] from the internal proxy.
+///
+/// We don't print it to stderr, as that belongs to the user application.
+fn show_log_message(log: &LogMessage) {
+    match log.level {
+        LogLevel::Error => tracing::error!(
+            message = log.message,
+            "Received a log message from the internal proxy"
+        ),
+        LogLevel::Warn => tracing::warn!(
+            message = log.message,
+            "Received a log message from the internal proxy"
+        ),
+        LogLevel::Info => tracing::info!(
+            message = log.message,
+            "Received a log message from the internal proxy"
+        ),
+    }
+}
+
+/// Records the [`ProxyError::SessionRejected`] error in [`SESSION_REJECTED`].
+///
+/// The error is shown to the user by the internal proxy, so here we only log it.
+///
+/// The layer keeps running without [`PROXY_CONNECTION`], and the hooks that need it fail with
+/// [`HookError::SessionRejected`], so the application sees an error instead of mirrord exiting
+/// it.
+pub fn reject_session(error: String) {
+    tracing::error!(%error, "Internal proxy rejected the layer");
+    let _ = SESSION_REJECTED.set(error);
+}
+
+/// Path to the layer library loaded into this process, for [`NewSessionRequest::layer_path`].
+pub fn layer_library_path() -> Option<String> {
+    #[cfg(unix)]
+    {
+        let mut info: libc::Dl_info = unsafe { std::mem::zeroed() };
+        // SAFETY: `info` is valid for writes, and the address belongs to this library.
+        let found = unsafe {
+            libc::dladdr(
+                layer_library_path as fn() -> Option<String> as *const libc::c_void,
+                &mut info,
+            )
+        };
+        if found == 0 || info.dli_fname.is_null() {
+            return None;
+        }
+
+        // SAFETY: `dladdr` succeeded, so `dli_fname` is a valid C string.
+        let path = unsafe { std::ffi::CStr::from_ptr(info.dli_fname) };
+        Some(path.to_string_lossy().into_owned())
+    }
+
+    #[cfg(windows)]
+    {
+        std::env::var("MIRRORD_LAYER_FILE").ok()
+    }
+}
+
+fn missing_proxy_connection() -> HookError {
+    match SESSION_REJECTED.get() {
+        Some(error) => HookError::SessionRejected(error.clone()),
+        None => HookError::CannotGetProxyConnection,
+    }
+}
+
 /// Makes a request to the internal proxy using global [`PROXY_CONNECTION`].
 /// Blocks until the proxy responds.
 pub fn make_proxy_request_with_response<T>(request: T) -> HookResult<T::Response>
@@ -211,7 +295,7 @@ where
     unsafe {
         PROXY_CONNECTION
             .get()
-            .ok_or(HookError::CannotGetProxyConnection)?
+            .ok_or_else(missing_proxy_connection)?
             .make_request_with_response(request)
             .map_err(Into::into)
     }
@@ -227,7 +311,7 @@ pub fn make_proxy_request_no_response<T: IsLayerRequest + Debug>(
     unsafe {
         PROXY_CONNECTION
             .get()
-            .ok_or(HookError::CannotGetProxyConnection)?
+            .ok_or_else(missing_proxy_connection)?
             .make_request_no_response(request)
             .map_err(Into::into)
     }
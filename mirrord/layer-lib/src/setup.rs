@@ -5,7 +5,7 @@ use mirrord_config::{
     experimental::ExperimentalConfig,
     feature::{
         env::EnvConfig,
-        fs::{FsConfig, FsModeConfig, READONLY_FILE_BUFFER_DEFAULT},
+        fs::{FsConfig, FsModeConfig, MAX_WRITE_CHUNK_DEFAULT, READONLY_FILE_BUFFER_DEFAULT},
         network::{
             NetworkConfig,
             incoming::{IncomingConfig, IncomingMode as ConfigIncomingMode},
@@ -50,6 +50,10 @@ pub fn init_layer_setup(mut config: LayerConfig, sip_only: bool) {
             not_found: None,
             mapping: None,
             readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+            readonly_file_buffer_overrides: None,
+            max_write_chunk: MAX_WRITE_CHUNK_DEFAULT,
+            fallback_local: None,
+            fallback_local_on: None,
         };
     } else {
         if config.target.path.is_none() && config.feature.fs.mode.ne(&FsModeConfig::Local) {
@@ -17,7 +17,9 @@ use hickory_resolver::{Resolver, error::ResolveErrorKind};
 use libc::c_int;
 // Cross-platform socket constants
 #[cfg(unix)]
-pub use libc::{AF_INET, AF_INET6, AF_UNIX, SOCK_DGRAM, SOCK_STREAM, sockaddr, socklen_t};
+pub use libc::{
+    AF_INET, AF_INET6, AF_UNIX, SOCK_DGRAM, SOCK_SEQPACKET, SOCK_STREAM, sockaddr, socklen_t,
+};
 use mirrord_config::feature::network::{
     filter::{AddressFilter, ProtocolAndAddressFilter, ProtocolFilter},
     outgoing::{OutgoingConfig, OutgoingFilterConfig},
@@ -87,6 +89,13 @@ pub struct Connected {
     /// port.
     pub local_address: Option<SocketAddress>,
 
+    /// Address that the connection was headed to in the cluster.
+    ///
+    /// Whenever the user calls `getsockopt` with `SO_ORIGINAL_DST`, this is the address we return
+    /// to them. Only for sockets created in our `accept`, where it differs from
+    /// [`Self::local_address`] when the listener's port is mapped with `port_mapping`.
+    pub original_destination: Option<SocketAddress>,
+
     /// The address of the interceptor socket, this is what we're really connected to in the
     /// outgoing feature.
     pub layer_address: Option<SocketAddress>,
@@ -116,6 +125,16 @@ pub struct Bound {
     /// Actual bound address that we use to communicate between the user's listener socket and our
     /// interceptor socket.
     pub address: SocketAddr,
+
+    /// Remote port that this socket subscribed to, set when the socket starts listening.
+    ///
+    /// For sockets bound to port `0` with `feature.network.incoming.bind_any_maps_remote`, it is
+    /// already set on `bind`, to reserve the port.
+    ///
+    /// Differs from the port of [`Bound::requested_address`] when the port is mapped with
+    /// `feature.network.incoming.port_mapping`, or picked from
+    /// `feature.network.incoming.bind_any_maps_remote`.
+    pub remote_port: Option<u16>,
 }
 
 #[derive(Debug, Default, Clone, Encode, Decode)]
@@ -136,6 +155,8 @@ pub enum SocketState {
 pub enum SocketKind {
     Tcp(c_int),
     Udp(c_int),
+    /// Unix `SOCK_SEQPACKET` socket.
+    SeqPacket(c_int),
 }
 
 impl SocketKind {
@@ -153,6 +174,7 @@ impl From<SocketKind> for NetProtocol {
         match kind {
             SocketKind::Tcp(..) => Self::Stream,
             SocketKind::Udp(..) => Self::Datagrams,
+            SocketKind::SeqPacket(..) => Self::SeqPacket,
         }
     }
 }
@@ -161,6 +183,13 @@ impl TryFrom<c_int> for SocketKind {
     type Error = Bypass;
 
     fn try_from(type_: c_int) -> Result<Self, Self::Error> {
+        // `SOCK_SEQPACKET` shares bits with `SOCK_STREAM`, so it must be checked first. The type
+        // can be combined with `SOCK_NONBLOCK` and `SOCK_CLOEXEC`, which use higher bits.
+        #[cfg(unix)]
+        if (type_ & 0xf) == SOCK_SEQPACKET {
+            return Ok(SocketKind::SeqPacket(type_));
+        }
+
         if (type_ & SOCK_STREAM) > 0 {
             Ok(SocketKind::Tcp(type_))
         } else if (type_ & SOCK_DGRAM) > 0 {
@@ -217,7 +246,9 @@ impl UserSocket {
                 ..
             } => {
                 let _ = make_proxy_request_no_response(PortUnsubscribe {
-                    port: bound.requested_address.port(),
+                    port: bound
+                        .remote_port
+                        .unwrap_or_else(|| bound.requested_address.port()),
                     listening_on: bound.address,
                 });
             }
@@ -666,8 +697,7 @@ impl ProtocolAndAddressFilterExt for ProtocolAndAddressFilter {
             return Ok(false);
         };
 
-        let port = self.address.port();
-        if port != 0 && port != address.port() {
+        if !self.address.ports().contains(address.port()) {
             return Ok(false);
         }
 
@@ -680,9 +710,16 @@ impl ProtocolAndAddressFilterExt for ProtocolAndAddressFilter {
         };
 
         match &self.address {
-            AddressFilter::Name(name, port) => {
+            AddressFilter::Name(name, ..) => {
                 let resolved_ips = if setup().remote_dns_enabled() && !force_local_dns {
-                    match remote_getaddrinfo(name.to_string(), *port, 0, family, 0, addr_protocol) {
+                    match remote_getaddrinfo(
+                        name.to_string(),
+                        address.port(),
+                        0,
+                        family,
+                        0,
+                        addr_protocol,
+                    ) {
                         Ok(res) => res.into_iter().map(|(_, ip)| ip).collect(),
                         Err(HookError::ResponseError(ResponseError::DnsLookup(
                             DnsLookupError {
@@ -714,9 +751,7 @@ impl ProtocolAndAddressFilterExt for ProtocolAndAddressFilter {
 
                 Ok(resolved_ips.into_iter().any(|ip| ip == address.ip()))
             }
-            AddressFilter::Socket(addr) => {
-                Ok(addr.ip().is_unspecified() || addr.ip() == address.ip())
-            }
+            AddressFilter::Socket(ip, ..) => Ok(ip.is_unspecified() || *ip == address.ip()),
             AddressFilter::Subnet(net, _) => Ok(net.contains(&address.ip())),
             AddressFilter::Port(..) => Ok(true),
         }
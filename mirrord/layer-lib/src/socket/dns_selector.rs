@@ -1,8 +1,12 @@
-use std::{net::IpAddr, ops::Deref};
+use std::{
+    collections::HashSet,
+    net::IpAddr,
+    ops::{Deref, Not},
+};
 
 use mirrord_config::feature::network::{
-    dns::{DnsConfig, DnsFilterConfig},
-    filter::AddressFilter,
+    dns::{self, DnsConfig, DnsFilterConfig, LOCAL_HOSTS_FILE},
+    filter::{AddressFilter, NamePattern},
 };
 use tracing::Level;
 
@@ -13,9 +17,21 @@ use crate::detour::{Bypass, Detour};
 #[derive(Debug)]
 pub struct DnsSelector {
     /// Filters provided in the config.
-    filters: Vec<AddressFilter>,
+    filters: Vec<DnsFilter>,
     /// Whether a query matching one of [`Self::filters`] should be done locally.
     filter_is_local: bool,
+    /// Cluster names from the local hosts file, always resolved remotely.
+    ///
+    /// Only populated with `feature.network.dns.ignore_local_hosts_file`.
+    hosts_file_overrides: HashSet<String>,
+}
+
+/// Parsed filter from the [`DnsFilterConfig`].
+#[derive(Debug)]
+struct DnsFilter {
+    address: AddressFilter,
+    /// Parsed name of an [`AddressFilter::Name`], which may contain wildcards.
+    name: Option<NamePattern>,
 }
 
 impl DnsSelector {
@@ -25,16 +41,15 @@ impl DnsSelector {
         let matched = self
             .filters
             .iter()
-            .filter(|filter| {
-                let filter_port = filter.port();
-                filter_port == 0 || filter_port == port
-            })
-            .any(|filter| match filter {
+            .filter(|filter| filter.address.ports().contains(port))
+            .any(|filter| match &filter.address {
                 AddressFilter::Port(..) => true,
-                AddressFilter::Name(filter_name, _) => filter_name == node,
-                AddressFilter::Socket(filter_socket) => {
-                    filter_socket.ip().is_unspecified()
-                        || Some(filter_socket.ip()) == node.parse().ok()
+                AddressFilter::Name(..) => filter
+                    .name
+                    .as_ref()
+                    .is_some_and(|pattern| pattern.matches(node)),
+                AddressFilter::Socket(filter_ip, ..) => {
+                    filter_ip.is_unspecified() || Some(*filter_ip) == node.parse().ok()
                 }
                 AddressFilter::Subnet(filter_subnet, _) => {
                     let Ok(ip) = node.parse::<IpAddr>() else {
@@ -45,12 +60,34 @@ impl DnsSelector {
                 }
             });
 
-        if matched == self.filter_is_local {
+        if matched == self.filter_is_local && self.is_hosts_file_override(node).not() {
             Detour::Bypass(Bypass::LocalDns)
         } else {
             Detour::Success(())
         }
     }
+
+    fn is_hosts_file_override(&self, node: &str) -> bool {
+        self.hosts_file_overrides.is_empty().not()
+            && self.hosts_file_overrides.contains(
+                node.strip_suffix('.')
+                    .unwrap_or(node)
+                    .to_lowercase()
+                    .as_str(),
+            )
+    }
+
+    /// Returns the cluster names (see [`dns::is_cluster_name`]) from the hosts file `contents`.
+    fn hosts_file_overrides(contents: &str) -> HashSet<String> {
+        dns::parse_hosts_file(contents)
+            .into_iter()
+            .filter(|entry| dns::is_cluster_name(&entry.name))
+            .map(|entry| {
+                let name = entry.name.strip_suffix('.').unwrap_or(&entry.name);
+                name.to_lowercase()
+            })
+            .collect()
+    }
 }
 
 impl From<&DnsConfig> for DnsSelector {
@@ -59,6 +96,7 @@ impl From<&DnsConfig> for DnsSelector {
             return Self {
                 filters: Default::default(),
                 filter_is_local: false,
+                hosts_file_overrides: Default::default(),
             };
         }
 
@@ -72,15 +110,72 @@ impl From<&DnsConfig> for DnsSelector {
             .into_iter()
             .flatten()
             .map(|filter| {
-                filter
+                let address = filter
                     .parse::<AddressFilter>()
-                    .expect("bad address filter, should be verified in the CLI")
+                    .expect("bad address filter, should be verified in the CLI");
+                let name = match &address {
+                    AddressFilter::Name(name, _) => Some(
+                        name.parse::<NamePattern>()
+                            .expect("bad name pattern, should be verified in the CLI"),
+                    ),
+                    _ => None,
+                };
+
+                DnsFilter { address, name }
             })
             .collect();
 
+        let hosts_file_overrides = if value.ignore_local_hosts_file {
+            std::fs::read_to_string(LOCAL_HOSTS_FILE)
+                .inspect_err(|error| {
+                    tracing::debug!(%error, "Failed to read the local hosts file");
+                })
+                .map(|contents| Self::hosts_file_overrides(&contents))
+                .unwrap_or_default()
+        } else {
+            Default::default()
+        };
+
         Self {
             filters,
             filter_is_local,
+            hosts_file_overrides,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use mirrord_config::util::VecOrSingle;
+
+    use super::*;
+
+    /// Verifies that cluster names from the local hosts file are resolved remotely, even when the
+    /// filter says otherwise.
+    #[test]
+    fn hosts_file_overrides_local_filter() {
+        let mut selector = DnsSelector::from(&DnsConfig {
+            enabled: true,
+            filter: Some(DnsFilterConfig::Local(VecOrSingle::Single(
+                "**.cluster.local".to_string(),
+            ))),
+            ignore_local_hosts_file: false,
+        });
+        assert!(matches!(
+            selector.check_query("my-service.cluster.local", 80),
+            Detour::Bypass(Bypass::LocalDns)
+        ));
+
+        selector.hosts_file_overrides = DnsSelector::hosts_file_overrides(
+            "127.0.0.1 localhost my-service.cluster.local\n127.0.0.1 other.internal.corp",
+        );
+        assert!(matches!(
+            selector.check_query("My-Service.cluster.local.", 80),
+            Detour::Success(())
+        ));
+        assert!(matches!(
+            selector.check_query("other.cluster.local", 80),
+            Detour::Bypass(Bypass::LocalDns)
+        ));
+    }
+}
@@ -12,7 +12,7 @@ use std::{
 
 use libc::c_int;
 use mirrord_intproxy_protocol::{NetProtocol, OutgoingConnectRequest, OutgoingConnectResponse};
-use mirrord_protocol::outgoing::SocketAddress;
+use mirrord_protocol::{ResponseError, outgoing::SocketAddress};
 #[cfg(unix)]
 use nix::sys::socket::{SockaddrStorage, sockopt};
 use socket2::SockAddr;
@@ -156,6 +156,11 @@ where
         Ok(())
     }?;
 
+    // `SOCK_SEQPACKET` is supported only over unix sockets.
+    if matches!(socket_kind, SocketKind::SeqPacket(..)) && domain != AF_UNIX {
+        return Detour::Bypass(Bypass::Type(type_));
+    }
+
     if domain == AF_INET6 && setup().layer_config().feature.network.ipv6.not() {
         return Detour::Error(HookError::SocketUnsuportedIpv6);
     }
@@ -202,6 +207,7 @@ where
                     SocketState::Listening(Bound {
                         requested_address,
                         address,
+                        ..
                     }) => (requested_address.port() == ip_address.port()
                         && socket.protocol == user_socket_info.protocol)
                         .then(|| SockAddr::from(address)),
@@ -358,6 +364,23 @@ where
             _ => Detour::Bypass(Bypass::DisabledOutgoing),
         },
 
+        // Routed like unix streams, matched with the same `unix_streams` config.
+        NetProtocol::SeqPacket => match user_socket_info.state {
+            SocketState::Initialized | SocketState::Bound { .. }
+                if remote_address.is_unix() && !unix_streams.is_empty() =>
+            {
+                connect_outgoing_common(
+                    sockfd,
+                    remote_address,
+                    user_socket_info,
+                    NetProtocol::SeqPacket,
+                    call_connect_fn,
+                )
+            }
+
+            _ => Detour::Bypass(Bypass::DisabledOutgoing),
+        },
+
         _ => Detour::Bypass(Bypass::DisabledOutgoing),
     }
 }
@@ -410,7 +433,13 @@ where
             connection_id,
             mut layer_address,
             in_cluster_address,
-        } = make_proxy_request_with_response(request)??;
+        } = match make_proxy_request_with_response(request)? {
+            // The agent does not support `SOCK_SEQPACKET`, connect locally.
+            Err(ResponseError::NotImplemented) if protocol == NetProtocol::SeqPacket => {
+                return Detour::Bypass(Bypass::NotImplemented);
+            }
+            response => response?,
+        };
 
         if let SocketAddress::Ip(interceptor_addr) = &mut layer_address {
             // Our socket can be bound to any local interface,
@@ -486,6 +515,7 @@ where
             connection_id: Some(connection_id),
             remote_address,
             local_address: in_cluster_address,
+            original_destination: None,
             layer_address: Some(layer_address),
         };
 
@@ -579,6 +609,7 @@ pub fn send_dns_patch(
                     Bound {
                         requested_address,
                         address,
+                        ..
                     },
                 ..
             } => {
@@ -15,11 +15,12 @@ use mirrord_config::{
 };
 use mirrord_intproxy::{IntProxy, agent_conn::AgentConnection};
 use mirrord_protocol::{
-    ClientMessage, ConnectionId, DaemonCodec, DaemonMessage, FileRequest, FileResponse, ToPayload,
+    ClientMessage, ConnectionId, DaemonCodec, DaemonMessage, ErrorKindInternal, FileRequest,
+    FileResponse, RemoteIOError, ResponseError, ToPayload,
     file::{
-        AccessFileRequest, AccessFileResponse, MetadataInternal, OpenFileRequest,
-        OpenOptionsInternal, ReadFileRequest, SeekFromInternal, XstatFsResponseV2, XstatRequest,
-        XstatResponse,
+        AccessFileRequestV2, AccessFileResponse, MetadataInternal, OpenFileRequest,
+        OpenOptionsInternal, ReadFileRequest, ReadvFileRequest, ReadvFileResponse,
+        SeekFromInternal, XstatFsResponseV2, XstatRequest, XstatResponse,
     },
     outgoing::{
         DaemonConnect, DaemonConnectV2, LayerConnectV2, SocketAddress,
@@ -61,7 +62,13 @@ impl TestIntProxy {
             let intproxy = IntProxy::new_with_connection(
                 agent_conn,
                 listener,
-                0,
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                Default::default(),
+                Default::default(),
                 Default::default(),
                 Duration::from_secs(60),
                 &experimental_config,
@@ -624,6 +631,40 @@ impl TestIntProxy {
         Self::expect_message_file_read(self.codec.next().await.unwrap().unwrap(), expected_fd).await
     }
 
+    /// Verify the layer hooks a readv of `expected_fd`, return the buffer lengths.
+    pub async fn expect_file_readv(&mut self, expected_fd: u64) -> Vec<u64> {
+        let message = self.codec.next().await.unwrap().unwrap();
+        if let ClientMessage::FileRequest(FileRequest::Readv(ReadvFileRequest {
+            fd,
+            iov_lengths,
+        })) = message
+        {
+            assert_eq!(expected_fd, fd);
+            return iov_lengths;
+        }
+        panic!("Expected Readv FileRequest. Got {message:?}");
+    }
+
+    /// Send file readv response with given `contents`, split into the `iov_lengths`.
+    pub async fn answer_file_readv(&mut self, contents: Vec<u8>, iov_lengths: &[u64]) {
+        self.codec
+            .send(DaemonMessage::File(FileResponse::Readv(Ok(
+                ReadvFileResponse::new(contents.into(), iov_lengths),
+            ))))
+            .await
+            .unwrap();
+    }
+
+    /// Answer an already verified file readv request as an agent that does not support it.
+    pub async fn answer_file_readv_not_implemented(&mut self) {
+        self.codec
+            .send(DaemonMessage::File(FileResponse::Readv(Err(
+                ResponseError::NotImplemented,
+            ))))
+            .await
+            .unwrap();
+    }
+
     pub async fn answer_file_open(&mut self) {
         self.codec
             .send(DaemonMessage::File(FileResponse::Open(Ok(
@@ -748,18 +789,34 @@ impl TestIntProxy {
         );
     }
 
-    /// Verify the next message from the layer is an access to the given path with the given mode.
-    /// Send back a response.
+    /// Verify the next message from the layer is an access to the given path with the given mode,
+    /// and no flags. Send back a response.
     pub async fn expect_file_access(&mut self, pathname: PathBuf, mode: u8) {
+        self.expect_file_access_v2(
+            AccessFileRequestV2 {
+                pathname,
+                mode,
+                flags: Default::default(),
+            },
+            Ok(AccessFileResponse {}),
+        )
+        .await;
+    }
+
+    /// Verify the next message from the layer is the given access request, and answer it with the
+    /// given `response`.
+    pub async fn expect_file_access_v2(
+        &mut self,
+        request: AccessFileRequestV2,
+        response: RemoteResult<AccessFileResponse>,
+    ) {
         assert_eq!(
             self.recv().await,
-            ClientMessage::FileRequest(FileRequest::Access(AccessFileRequest { pathname, mode }))
+            ClientMessage::FileRequest(FileRequest::AccessV2(request))
         );
 
         self.codec
-            .send(DaemonMessage::File(FileResponse::Access(Ok(
-                AccessFileResponse {},
-            ))))
+            .send(DaemonMessage::File(FileResponse::Access(response)))
             .await
             .unwrap();
     }
@@ -789,6 +846,29 @@ impl TestIntProxy {
             .unwrap();
     }
 
+    /// Assert that the layer sends an xstat request for the given path, answer the request with a
+    /// remote [`ErrorKindInternal::NotFound`] error.
+    pub async fn expect_xstat_not_found(&mut self, path: PathBuf) {
+        assert_eq!(
+            self.recv().await,
+            ClientMessage::FileRequest(FileRequest::Xstat(XstatRequest {
+                path: Some(path),
+                fd: None,
+                follow_symlink: true,
+            }))
+        );
+
+        self.codec
+            .send(DaemonMessage::File(FileResponse::Xstat(Err(
+                ResponseError::RemoteIO(RemoteIOError {
+                    raw_os_error: Some(2),
+                    kind: ErrorKindInternal::NotFound,
+                }),
+            ))))
+            .await
+            .unwrap();
+    }
+
     /// Assert that the layer sends an xstat request with the given fd, answer the request.
     pub async fn expect_xstat(&mut self, path: Option<PathBuf>, fd: Option<u64>) {
         self.expect_xstat_with_metadata(path, fd, Default::default())
@@ -383,6 +383,7 @@ unsafe extern "system" fn bind_detour(s: SOCKET, name: *const SOCKADDR, namelen:
         bound: Bound {
             requested_address: requested_addr,
             address: actual_bound_addr,
+            remote_port: None,
         },
         // Note(Daniel): not yet migrated "will_not_trigger_subscription" from unix layer bind
         is_only_bound: false,
@@ -504,7 +505,10 @@ unsafe extern "system" fn listen_detour(s: SOCKET, backlog: INT) -> INT {
                 bound_state.requested_address.port()
             );
 
-            Arc::get_mut(&mut socket).unwrap().state = SocketState::Listening(bound_state);
+            Arc::get_mut(&mut socket).unwrap().state = SocketState::Listening(Bound {
+                remote_port: Some(mapped_port),
+                ..bound_state
+            });
             SOCKETS
                 .lock()
                 .expect("listen_detour -> failed to lock sockets for state update")
@@ -602,6 +606,7 @@ unsafe extern "system" fn accept_detour(
             SocketState::Listening(Bound {
                 requested_address,
                 address,
+                ..
             }) => (
                 socket.domain,
                 socket.protocol,
@@ -634,6 +639,7 @@ unsafe extern "system" fn accept_detour(
     let ConnMetadataResponse {
         remote_source,
         local_address,
+        destination_port,
     } = match make_proxy_request_with_response(ConnMetadataRequest {
         listener_address,
         peer_address,
@@ -663,6 +669,7 @@ unsafe extern "system" fn accept_detour(
         connection_id: None,
         remote_address: remote_source.into(),
         local_address: Some(SocketAddr::new(local_address, port).into()),
+        original_destination: Some(SocketAddr::new(local_address, destination_port).into()),
         layer_address: None,
     });
     let new_socket = UserSocket::new(domain, type_, protocol, state, socket_kind);
@@ -739,15 +746,18 @@ unsafe extern "system" fn getsockname_detour(
             }
         }
         SocketState::Bound {
-            bound: Bound {
-                requested_address,
-                address,
-            },
+            bound:
+                Bound {
+                    requested_address,
+                    address,
+                    ..
+                },
             ..
         }
         | SocketState::Listening(Bound {
             requested_address,
             address,
+            ..
         }) => Some(if requested_address.port() == 0 {
             SocketAddr::new(requested_address.ip(), address.port())
         } else {
@@ -8,7 +8,7 @@ use std::{net::SocketAddr, time::Duration};
 
 use mirrord_layer_lib::{
     error::{LayerError, LayerResult},
-    proxy_connection::ProxyConnection,
+    proxy_connection::{ProxyConnection, layer_library_path},
 };
 
 /// Environment variable for child process parent PID
@@ -86,6 +86,8 @@ pub fn create_proxy_connection(context: &ProcessContext) -> LayerResult<ProxyCon
             let session = mirrord_intproxy_protocol::NewSessionRequest {
                 parent_layer: Some(mirrord_intproxy_protocol::LayerId(*layer_id)),
                 process_info,
+                layer_version: mirrord_intproxy_protocol::BuildVersion::current(),
+                layer_path: layer_library_path(),
             };
 
             let connection = ProxyConnection::new(*proxy_addr, session, timeout)
@@ -114,6 +116,8 @@ pub fn create_proxy_connection(context: &ProcessContext) -> LayerResult<ProxyCon
             let session = mirrord_intproxy_protocol::NewSessionRequest {
                 parent_layer: None,
                 process_info,
+                layer_version: mirrord_intproxy_protocol::BuildVersion::current(),
+                layer_path: layer_library_path(),
             };
 
             ProxyConnection::new(address, session, timeout)
@@ -0,0 +1,96 @@
+//! Pausing the application until a debugger is ready to attach, see [`WaitForDebuggerConfig`].
+
+use std::{
+    io,
+    net::{Ipv4Addr, TcpListener},
+    thread,
+    time::{Duration, Instant},
+};
+
+use mirrord_config::debugger::WaitForDebuggerConfig;
+use mirrord_progress::{IdeMessage, NotificationLevel, NullProgress, Progress, ProgressTracker};
+
+/// Name of environment variable used to mark that we've already waited for the debugger, so that
+/// child processes start right away.
+const DEBUGGER_WAITED: &str = "MIRRORD_DEBUGGER_WAITED";
+
+/// How often we check for the debugger connection.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Blocks until something connects to [`WaitForDebuggerConfig::port`] on localhost, or until
+/// [`WaitForDebuggerConfig::timeout`] passes.
+///
+/// The wait is reported through the progress stream (if [`mirrord_progress::MIRRORD_PROGRESS_ENV`]
+/// is set), so that IDEs know when to attach.
+///
+/// Must be called with a [`DetourGuard`](mirrord_layer_lib::detour::DetourGuard), as the listener
+/// should not go through our socket hooks.
+pub(crate) fn wait_for_debugger(config: &WaitForDebuggerConfig) {
+    let Some(port) = config.port else {
+        return;
+    };
+
+    if std::env::var(DEBUGGER_WAITED).is_ok() {
+        return;
+    }
+    // TODO: Audit that the environment access only happens in single-threaded code.
+    unsafe { std::env::set_var(DEBUGGER_WAITED, "true") };
+
+    let mut progress =
+        ProgressTracker::try_from_env(&format!("waiting for debugger on port {port}"))
+            .unwrap_or_else(|| NullProgress.into());
+    progress.ide(
+        serde_json::to_value(IdeMessage {
+            id: "wait_for_debugger".to_string(),
+            level: NotificationLevel::Info,
+            text: format!("mirrord is ready, connect to port {port} to start the application"),
+            actions: Default::default(),
+        })
+        .unwrap_or_default(),
+    );
+
+    let timeout = Duration::from_secs(config.timeout);
+    match accept_once(port, timeout) {
+        Ok(true) => {
+            tracing::info!(port, "Debugger connected, resuming the application");
+            progress.success(Some("debugger connected"));
+        }
+        Ok(false) => {
+            tracing::warn!(port, ?timeout, "Timed out waiting for the debugger");
+            progress.warning(&format!(
+                "timed out waiting for debugger on port {port}, starting the application"
+            ));
+            progress.success(None);
+        }
+        Err(error) => {
+            tracing::warn!(port, %error, "Failed to wait for the debugger");
+            progress.warning(&format!(
+                "failed to wait for debugger on port {port}: {error}, starting the application"
+            ));
+            progress.success(None);
+        }
+    }
+}
+
+/// Listens on localhost `port` and accepts a single connection.
+///
+/// Returns `false` if nothing connected before the `timeout`.
+fn accept_once(port: u16, timeout: Duration) -> io::Result<bool> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port))?;
+    listener.set_nonblocking(true)?;
+
+    let deadline = Instant::now() + timeout;
+    loop {
+        match listener.accept() {
+            Ok(..) => return Ok(true),
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => {
+                if Instant::now() >= deadline {
+                    return Ok(false);
+                }
+                thread::sleep(ACCEPT_INTERVAL);
+            }
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+}
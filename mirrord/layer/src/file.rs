@@ -1,15 +1,22 @@
 use std::{
     collections::HashMap,
     os::unix::io::RawFd,
+    path::PathBuf,
     sync::{Arc, LazyLock},
 };
 
-use libc::{O_ACCMODE, O_APPEND, O_CREAT, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY, c_int};
+#[cfg(target_os = "linux")]
+use libc::O_PATH;
+use libc::{
+    O_ACCMODE, O_APPEND, O_CREAT, O_DIRECTORY, O_NOFOLLOW, O_RDONLY, O_RDWR, O_TRUNC, O_WRONLY,
+    c_int,
+};
 use mirrord_layer_lib::mutex::Mutex;
 use mirrord_protocol::file::{
-    AccessFileRequest, CloseFileRequest, FdOpenDirRequest, OpenDirResponse, OpenOptionsInternal,
-    OpenRelativeFileRequest, ReadFileRequest, ReadLimitedFileRequest, SeekFileRequest,
-    WriteFileRequest, WriteLimitedFileRequest, XstatRequest,
+    AccessFileRequestV2, AccessFlagsInternal, CloseFileRequest, FdOpenDirRequest, OpenDirResponse,
+    OpenFlagsInternal, OpenOptionsInternal, OpenRelativeFileRequest, ReadFileRequest,
+    ReadLimitedFileRequest, SeekFileRequest, WriteFileRequest, WriteLimitedFileRequest,
+    XstatRequest,
 };
 /// File operations on remote pod.
 ///
@@ -26,6 +33,7 @@ use mirrord_protocol::file::{GetDEnts64Request, GetDEnts64Response};
 pub(crate) mod hooks;
 pub(crate) mod open_dirs;
 pub(crate) mod ops;
+pub(crate) mod virtual_files;
 
 type RemoteFd = u64;
 type LocalFd = RawFd;
@@ -38,6 +46,13 @@ type DirStreamFd = usize;
 pub(crate) static OPEN_FILES: LazyLock<Mutex<HashMap<LocalFd, Arc<ops::RemoteFile>>>> =
     LazyLock::new(|| Mutex::new(HashMap::new()));
 
+/// Remote working directory of the app, set when it `chdir`s (or `fchdir`s) into a remote
+/// directory.
+///
+/// While set, relative paths are resolved against it instead of the local working directory, and
+/// `getcwd` reports it. Cleared when the app changes back into a local directory.
+pub(crate) static REMOTE_CWD: LazyLock<Mutex<Option<PathBuf>>> = LazyLock::new(|| Mutex::new(None));
+
 /// Extension trait for [`OpenOptionsInternal`], used to convert between `libc`-ish open options and
 /// Rust's [`std::fs::OpenOptions`]
 pub(crate) trait OpenOptionsInternalExt {
@@ -91,3 +106,22 @@ impl OpenOptionsInternalExt for OpenOptionsInternal {
             })
     }
 }
+
+/// Extension trait for [`OpenFlagsInternal`], used to extract the `libc` open flags that are not
+/// covered by [`OpenOptionsInternal`].
+pub(crate) trait OpenFlagsInternalExt {
+    fn from_flags(flags: c_int) -> Self;
+}
+
+impl OpenFlagsInternalExt for OpenFlagsInternal {
+    fn from_flags(flags: c_int) -> Self {
+        OpenFlagsInternal {
+            directory: (flags & O_DIRECTORY != 0),
+            nofollow: (flags & O_NOFOLLOW != 0),
+            #[cfg(target_os = "linux")]
+            path: (flags & O_PATH != 0),
+            #[cfg(not(target_os = "linux"))]
+            path: false,
+        }
+    }
+}
@@ -6,7 +6,6 @@ use core::ffi::{c_size_t, c_ssize_t};
 /// NOTICE: If a file operation fails, it might be because it depends on some `libc` function
 /// that is not being hooked (`strace` the program to check).
 use std::{
-    borrow::Borrow,
     ffi::CString,
     os::unix::{ffi::OsStrExt, io::RawFd},
     ptr, slice,
@@ -14,8 +13,9 @@ use std::{
 };
 
 use libc::{
-    self, AT_EACCESS, AT_FDCWD, DIR, EINVAL, O_DIRECTORY, O_RDONLY, c_char, c_int, c_void, dirent,
-    gid_t, iovec, mode_t, off_t, size_t, ssize_t, stat, statfs, timespec, uid_t,
+    self, AT_EACCESS, AT_FDCWD, AT_SYMLINK_NOFOLLOW, DIR, EINVAL, O_DIRECTORY, O_NONBLOCK,
+    O_RDONLY, c_char, c_int, c_void, dirent, gid_t, iovec, mode_t, off_t, size_t, ssize_t, stat,
+    statfs, timespec, uid_t,
 };
 #[cfg(target_os = "linux")]
 use libc::{dirent64, stat64, statx};
@@ -30,8 +30,8 @@ use mirrord_layer_macro::{hook_fn, hook_guard_fn};
 #[cfg(target_os = "linux")]
 use mirrord_protocol::ResponseError::{NotDirectory, NotFound};
 use mirrord_protocol::file::{
-    FsMetadataInternalV2, MetadataInternal, ReadFileResponse, ReadLinkFileResponse, Timespec,
-    WriteFileResponse,
+    FsMetadataInternalV2, MetadataInternal, ReadFileResponse, ReadLinkFileResponse,
+    ReadvFileResponse, Timespec, WriteFileResponse,
 };
 use nix::errno::Errno;
 use num_traits::Bounded;
@@ -39,7 +39,7 @@ use tracing::trace;
 #[cfg(target_os = "linux")]
 use tracing::{error, info, warn};
 
-use super::{OpenOptionsInternalExt, open_dirs, ops::*};
+use super::{OpenFlagsInternalExt, OpenOptionsInternalExt, open_dirs, ops::*};
 use crate::{
     close_layer_fd,
     common::CheckedInto,
@@ -81,10 +81,15 @@ fn update_ptr_from_bypass(ptr: *const c_char, bypass: &Bypass) -> *const c_char
 unsafe fn open_logic(raw_path: *const c_char, open_flags: c_int, _mode: c_int) -> Detour<RawFd> {
     let path = raw_path.checked_into();
     let open_options = OpenOptionsInternalExt::from_flags(open_flags);
+    let flags = OpenFlagsInternalExt::from_flags(open_flags);
+    let nonblock = open_flags & O_NONBLOCK != 0;
 
-    trace!("path {:#?} | open_options {:#?}", path, open_options);
+    trace!(
+        "path {:#?} | open_options {:#?} | flags {:#?} | nonblock {nonblock}",
+        path, open_options, flags
+    );
 
-    open(path, open_options)
+    open(path, open_options, flags, nonblock)
 }
 
 /// Hook for `libc::open`.
@@ -183,74 +188,84 @@ pub(super) unsafe extern "C" fn opendir_detour(raw_filename: *const c_char) -> u
     }
 }
 
-/// Emulates sendfile using a sequence of read + write operations at the layer level.
-/// This allows copying files when the input/output fds are on different
-/// machines.
+/// Emulates `sendfile` for a remote `in_fd`, writing the data read from the agent to `out_fd`
+/// (which may itself be a remote file).
 ///
-/// Returns (bytes_written, new_offset) on success.
-unsafe fn sendfile_impl(
-    in_fd: RawFd,
+/// Returns the amount transferred and the offset right after it.
+fn sendfile_logic(
     out_fd: RawFd,
-    offset: Option<off_t>,
-    count: size_t,
-) -> Option<(ssize_t, off_t)> {
-    let mut buffer = vec![0u8; count];
-
-    let bytes_read = unsafe {
-        if let Some(offset) = offset {
-            libc::pread(in_fd, buffer.as_mut_ptr() as *mut c_void, count, offset)
-        } else {
-            libc::read(in_fd, buffer.as_mut_ptr() as *mut c_void, count)
-        }
-    };
-
-    if bytes_read < 0 {
-        return None;
-    }
-
-    let written = unsafe {
-        libc::write(
-            out_fd,
-            buffer.as_ptr() as *const c_void,
-            bytes_read as usize,
-        )
-    };
-
-    if written < 0 {
-        return None;
-    }
-
-    Some((written, offset.unwrap_or(0) + bytes_read as off_t))
+    in_fd: RawFd,
+    offset: Option<u64>,
+    count: u64,
+) -> Detour<(u64, u64)> {
+    sendfile(in_fd, offset, count, |chunk| unsafe {
+        write_logic(out_fd, chunk.as_ptr() as *const c_void, chunk.len())
+    })
 }
 
 /// Hook for macos's [`libc::sendfile`].
+///
+/// Headers and trailers in `hdtr` are not sent when `fd` is a remote file.
+///
+/// **Bypassed** by `fd`s that are not managed by us (not found in `OPEN_FILES`).
 #[cfg(target_os = "macos")]
-#[hook_fn]
+#[hook_guard_fn]
 pub(super) unsafe extern "C" fn sendfile_detour(
     fd: c_int,
     s: c_int,
     offset: off_t,
     len: *mut off_t,
-    _hdtr: *const libc::sf_hdtr,
-    _flags: c_int,
+    hdtr: *mut libc::sf_hdtr,
+    flags: c_int,
 ) -> c_int {
     unsafe {
-        let Some(count) = len.as_mut() else {
-            return -1;
+        let Some(requested) = len.as_mut() else {
+            return FN_SENDFILE(fd, s, offset, len, hdtr, flags);
+        };
+
+        // A `len` of 0 means "until the end of the file".
+        let count = match *requested {
+            0 => u64::MAX,
+            requested => requested as u64,
         };
 
-        sendfile_impl(fd, s, Some(offset), *count as usize)
-            .map(|(written, _)| {
-                *count = written as off_t;
+        sendfile_logic(s, fd, Some(offset as u64), count)
+            .map(|(transferred, _)| {
+                *requested = transferred as off_t;
                 0
             })
-            .unwrap_or(-1)
+            .unwrap_or_bypass_with(|_| FN_SENDFILE(fd, s, offset, len, hdtr, flags))
+    }
+}
+
+/// Implementation of [`sendfile_detour`] and [`sendfile64_detour`] for linux.
+///
+/// Advances `offset` when it's not null, otherwise advances the remote file position, just like
+/// the kernel does.
+#[cfg(target_os = "linux")]
+unsafe fn linux_sendfile(
+    out_fd: RawFd,
+    in_fd: RawFd,
+    offset: *mut off_t,
+    count: size_t,
+) -> Detour<ssize_t> {
+    unsafe {
+        let start = offset.as_ref().map(|offset| *offset as u64);
+
+        let (transferred, end) = sendfile_logic(out_fd, in_fd, start, count as u64)?;
+        if let Some(offset) = offset.as_mut() {
+            *offset = end as off_t;
+        }
+
+        Detour::Success(transferred as ssize_t)
     }
 }
 
 /// Hook for linux's [`libc::sendfile`].
+///
+/// **Bypassed** by `in_fd`s that are not managed by us (not found in `OPEN_FILES`).
 #[cfg(target_os = "linux")]
-#[hook_fn]
+#[hook_guard_fn]
 pub(super) unsafe extern "C" fn sendfile_detour(
     out_fd: c_int,
     in_fd: c_int,
@@ -258,20 +273,101 @@ pub(super) unsafe extern "C" fn sendfile_detour(
     count: size_t,
 ) -> ssize_t {
     unsafe {
-        let offset_val = if offset.is_null() {
-            None
-        } else {
-            Some(*offset)
+        linux_sendfile(out_fd, in_fd, offset, count)
+            .unwrap_or_bypass_with(|_| FN_SENDFILE(out_fd, in_fd, offset, count))
+    }
+}
+
+/// Hook for linux's [`libc::sendfile64`].
+///
+/// **Bypassed** by `in_fd`s that are not managed by us (not found in `OPEN_FILES`).
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(super) unsafe extern "C" fn sendfile64_detour(
+    out_fd: c_int,
+    in_fd: c_int,
+    offset: *mut off_t,
+    count: size_t,
+) -> ssize_t {
+    unsafe {
+        linux_sendfile(out_fd, in_fd, offset, count)
+            .unwrap_or_bypass_with(|_| FN_SENDFILE64(out_fd, in_fd, offset, count))
+    }
+}
+
+/// Implementation of [`copy_file_range_detour`].
+///
+/// The copy is done by the agent when both fds are remote. Otherwise (or when the agent can't do
+/// it) the data is read from the remote `fd_in` and written to `fd_out`, like in
+/// [`sendfile_logic`]. Advances `off_in` and `off_out` when they're not null, just like the kernel
+/// does.
+#[cfg(target_os = "linux")]
+unsafe fn copy_file_range_logic(
+    fd_in: RawFd,
+    off_in: *mut libc::loff_t,
+    fd_out: RawFd,
+    off_out: *mut libc::loff_t,
+    len: size_t,
+) -> Detour<ssize_t> {
+    unsafe {
+        let start_in = off_in.as_ref().map(|offset| *offset as u64);
+        let start_out = off_out.as_ref().map(|offset| *offset as u64);
+
+        let copied = match copy_file_range(fd_in, start_in, fd_out, start_out, len as u64)? {
+            Some(copied) => copied,
+            None => {
+                let mut position_out = start_out;
+                let (copied, _) = sendfile(fd_in, start_in, len as u64, |chunk| {
+                    let buffer = chunk.as_ptr() as *const c_void;
+                    let Some(position) = position_out.as_mut() else {
+                        return write_logic(fd_out, buffer, chunk.len());
+                    };
+
+                    let offset = *position as off_t;
+                    let written = pwrite_logic(fd_out, buffer, chunk.len(), offset)
+                        .unwrap_or_bypass_with(|_| FN_PWRITE(fd_out, buffer, chunk.len(), offset));
+                    if written > 0 {
+                        *position += written as u64;
+                    }
+                    written
+                })?;
+                copied
+            }
         };
 
-        sendfile_impl(in_fd, out_fd, offset_val, count)
-            .map(|(written, new_offset)| {
-                if !offset.is_null() {
-                    *offset = new_offset;
-                }
-                written
-            })
-            .unwrap_or(-1)
+        if let Some(offset) = off_in.as_mut() {
+            *offset += copied as libc::loff_t;
+        }
+        if let Some(offset) = off_out.as_mut() {
+            *offset += copied as libc::loff_t;
+        }
+
+        Detour::Success(copied as ssize_t)
+    }
+}
+
+/// Hook for [`libc::copy_file_range`].
+///
+/// **Bypassed** by `fd_in`s that are not managed by us (not found in `OPEN_FILES`), and when
+/// `flags` are given.
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(super) unsafe extern "C" fn copy_file_range_detour(
+    fd_in: c_int,
+    off_in: *mut libc::loff_t,
+    fd_out: c_int,
+    off_out: *mut libc::loff_t,
+    len: size_t,
+    flags: libc::c_uint,
+) -> ssize_t {
+    unsafe {
+        if flags != 0 {
+            return FN_COPY_FILE_RANGE(fd_in, off_in, fd_out, off_out, len, flags);
+        }
+
+        copy_file_range_logic(fd_in, off_in, fd_out, off_out, len).unwrap_or_bypass_with(|_| {
+            FN_COPY_FILE_RANGE(fd_in, off_in, fd_out, off_out, len, flags)
+        })
     }
 }
 
@@ -283,31 +379,105 @@ pub(super) unsafe extern "C" fn ftruncate_detour(fd: c_int, length: off_t) -> c_
         .unwrap_or_bypass_with(|_| unsafe { FN_FTRUNCATE(fd, length) })
 }
 
+/// Converts the access and modification times passed to `futimens` and `utimensat`, [`None`]
+/// (current time) when `raw_times` is null.
+unsafe fn timespecs(raw_times: *const timespec) -> Option<[Timespec; 2]> {
+    if raw_times.is_null() {
+        return None;
+    }
+
+    unsafe {
+        let [first, second] = slice::from_raw_parts(raw_times, 2) else {
+            unreachable!("We create the slice with two elements")
+        };
+
+        Some([
+            Timespec {
+                tv_sec: first.tv_sec,
+                tv_nsec: first.tv_nsec,
+            },
+            Timespec {
+                tv_sec: second.tv_sec,
+                tv_nsec: second.tv_nsec,
+            },
+        ])
+    }
+}
+
+/// Whether an `*at` call with `dirfd`, `raw_path` and `flags` can be handled like a call with a
+/// path, that is `raw_path` is absolute or relative to the cwd, and the only flag is
+/// `AT_SYMLINK_NOFOLLOW`.
+unsafe fn at_path_call(dirfd: c_int, raw_path: *const c_char, flags: c_int) -> bool {
+    unsafe {
+        let absolute = !raw_path.is_null() && *raw_path == b'/' as c_char;
+        (dirfd == AT_FDCWD || absolute) && (flags & !AT_SYMLINK_NOFOLLOW) == 0
+    }
+}
+
 /// Hook for [`libc::futimens`].
 #[hook_guard_fn]
 pub(super) unsafe extern "C" fn futimens_detour(fd: c_int, raw_times: *const timespec) -> c_int {
     unsafe {
-        let times = if !raw_times.is_null() {
+        futimens(fd, timespecs(raw_times))
+            .map(|()| 0)
+            .unwrap_or_bypass_with(|_| FN_FUTIMENS(fd, raw_times))
+    }
+}
+
+/// Hook for [`libc::utimensat`].
+///
+/// **Bypassed** when `raw_path` is relative to a `dirfd` other than `AT_FDCWD`, or `flags` other
+/// than `AT_SYMLINK_NOFOLLOW` are given.
+#[hook_guard_fn]
+pub(super) unsafe extern "C" fn utimensat_detour(
+    dirfd: c_int,
+    raw_path: *const c_char,
+    raw_times: *const timespec,
+    flags: c_int,
+) -> c_int {
+    unsafe {
+        if !at_path_call(dirfd, raw_path, flags) {
+            return FN_UTIMENSAT(dirfd, raw_path, raw_times, flags);
+        }
+
+        let follow_symlink = (flags & AT_SYMLINK_NOFOLLOW) == 0;
+        utimens(
+            raw_path.checked_into(),
+            timespecs(raw_times),
+            follow_symlink,
+        )
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|bypass| {
+            let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+            FN_UTIMENSAT(dirfd, raw_path, raw_times, flags)
+        })
+    }
+}
+
+/// Hook for [`libc::lutimes`], changes the times of a symlink itself.
+#[hook_guard_fn]
+pub(super) unsafe extern "C" fn lutimes_detour(
+    raw_path: *const c_char,
+    raw_times: *const libc::timeval,
+) -> c_int {
+    unsafe {
+        let times = (!raw_times.is_null()).then(|| {
             let [first, second] = slice::from_raw_parts(raw_times, 2) else {
                 unreachable!("We create the slice with two elements")
             };
 
-            Some([
-                Timespec {
-                    tv_sec: first.tv_sec,
-                    tv_nsec: first.tv_nsec,
-                },
-                Timespec {
-                    tv_sec: second.tv_sec,
-                    tv_nsec: second.tv_nsec,
-                },
-            ])
-        } else {
-            None
-        };
-        futimens(fd, times)
+            [first, second].map(|time| Timespec {
+                tv_sec: time.tv_sec,
+                tv_nsec: i64::from(time.tv_usec) * 1000,
+            })
+        });
+
+        utimens(raw_path.checked_into(), times, false)
             .map(|()| 0)
-            .unwrap_or_bypass_with(|_| FN_FUTIMENS(fd, raw_times))
+            .unwrap_or_bypass_with(|bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_LUTIMES(raw_path, raw_times)
+            })
     }
 }
 
@@ -319,6 +489,50 @@ pub(super) unsafe extern "C" fn fchown_detour(fd: c_int, owner: uid_t, group: gi
         .unwrap_or_bypass_with(|_| unsafe { FN_FCHOWN(fd, owner, group) })
 }
 
+/// Hook for [`libc::fchownat`].
+///
+/// **Bypassed** when `raw_path` is relative to a `dirfd` other than `AT_FDCWD`, or `flags` other
+/// than `AT_SYMLINK_NOFOLLOW` are given.
+#[hook_guard_fn]
+pub(super) unsafe extern "C" fn fchownat_detour(
+    dirfd: c_int,
+    raw_path: *const c_char,
+    owner: uid_t,
+    group: gid_t,
+    flags: c_int,
+) -> c_int {
+    unsafe {
+        if !at_path_call(dirfd, raw_path, flags) {
+            return FN_FCHOWNAT(dirfd, raw_path, owner, group, flags);
+        }
+
+        let follow_symlink = (flags & AT_SYMLINK_NOFOLLOW) == 0;
+        chown(raw_path.checked_into(), owner, group, follow_symlink)
+            .map(|()| 0)
+            .unwrap_or_bypass_with(|bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_FCHOWNAT(dirfd, raw_path, owner, group, flags)
+            })
+    }
+}
+
+/// Hook for [`libc::lchown`], changes the owner of a symlink itself.
+#[hook_guard_fn]
+pub(super) unsafe extern "C" fn lchown_detour(
+    raw_path: *const c_char,
+    owner: uid_t,
+    group: gid_t,
+) -> c_int {
+    unsafe {
+        chown(raw_path.checked_into(), owner, group, false)
+            .map(|()| 0)
+            .unwrap_or_bypass_with(|bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_LCHOWN(raw_path, owner, group)
+            })
+    }
+}
+
 /// Hook for [`libc::fchmod`].
 #[hook_guard_fn]
 pub(super) unsafe extern "C" fn fchmod_detour(fd: c_int, mode: mode_t) -> c_int {
@@ -331,6 +545,36 @@ pub(super) unsafe extern "C" fn fchmod_detour(fd: c_int, mode: mode_t) -> c_int
         .unwrap_or_bypass_with(|_| unsafe { FN_FCHMOD(fd, mode) })
 }
 
+/// Hook for [`libc::flock`].
+#[hook_guard_fn]
+pub(super) unsafe extern "C" fn flock_detour(fd: c_int, operation: c_int) -> c_int {
+    flock(fd, operation)
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|_| unsafe { FN_FLOCK(fd, operation) })
+}
+
+/// Hook for [`libc::sync`].
+///
+/// Local filesystems are always flushed, errors of the remote flush are only logged, as `sync`
+/// cannot fail.
+#[hook_guard_fn]
+pub(super) unsafe extern "C" fn sync_detour() {
+    if let Detour::Error(error) = sync() {
+        tracing::warn!(%error, "Failed to sync the remote filesystems");
+    }
+
+    unsafe { FN_SYNC() }
+}
+
+/// Hook for [`libc::syncfs`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(super) unsafe extern "C" fn syncfs_detour(fd: c_int) -> c_int {
+    syncfs(fd)
+        .map(|()| 0)
+        .unwrap_or_bypass_with(|_| unsafe { FN_SYNCFS(fd) })
+}
+
 /// see below, to have nice code we also implement it for other archs.
 #[cfg(not(all(target_os = "macos", target_arch = "aarch64")))]
 unsafe fn opendir_bypass(raw_filename: *const c_char) -> usize {
@@ -497,11 +741,14 @@ pub(crate) unsafe extern "C" fn openat_detour(
             FN_OPENAT(fd, raw_path, open_flags, mode)
         } else {
             let open_options = OpenOptionsInternalExt::from_flags(open_flags);
-
-            openat(fd, raw_path.checked_into(), open_options).unwrap_or_bypass_with(|bypass| {
-                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
-                FN_OPENAT(fd, raw_path, open_flags, mode)
-            })
+            let flags = OpenFlagsInternalExt::from_flags(open_flags);
+            let nonblock = open_flags & O_NONBLOCK != 0;
+
+            openat(fd, raw_path.checked_into(), open_options, flags, nonblock)
+                .unwrap_or_bypass_with(|bypass| {
+                    let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                    FN_OPENAT(fd, raw_path, open_flags, mode)
+                })
         }
     }
 }
@@ -519,11 +766,15 @@ pub(crate) unsafe extern "C" fn openat64_detour(
 ) -> RawFd {
     unsafe {
         let open_options = OpenOptionsInternalExt::from_flags(open_flags);
+        let flags = OpenFlagsInternalExt::from_flags(open_flags);
+        let nonblock = open_flags & O_NONBLOCK != 0;
 
-        openat(fd, raw_path.checked_into(), open_options).unwrap_or_bypass_with(|bypass| {
-            let raw_path = update_ptr_from_bypass(raw_path, &bypass);
-            FN_OPENAT64(fd, raw_path, open_flags)
-        })
+        openat(fd, raw_path.checked_into(), open_options, flags, nonblock).unwrap_or_bypass_with(
+            |bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_OPENAT64(fd, raw_path, open_flags)
+            },
+        )
     }
 }
 
@@ -535,11 +786,15 @@ pub(crate) unsafe extern "C" fn openat_nocancel_detour(
 ) -> RawFd {
     unsafe {
         let open_options = OpenOptionsInternalExt::from_flags(open_flags);
+        let flags = OpenFlagsInternalExt::from_flags(open_flags);
+        let nonblock = open_flags & O_NONBLOCK != 0;
 
-        openat(fd, raw_path.checked_into(), open_options).unwrap_or_bypass_with(|bypass| {
-            let raw_path = update_ptr_from_bypass(raw_path, &bypass);
-            FN_OPENAT_NOCANCEL(fd, raw_path, open_flags)
-        })
+        openat(fd, raw_path.checked_into(), open_options, flags, nonblock).unwrap_or_bypass_with(
+            |bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_OPENAT_NOCANCEL(fd, raw_path, open_flags)
+            },
+        )
     }
 }
 
@@ -843,11 +1098,15 @@ pub(crate) unsafe extern "C" fn write_nocancel_detour(
 }
 
 /// Implementation of access_detour, used in access_detour and faccessat_detour
-unsafe fn access_logic(raw_path: *const c_char, mode: c_int) -> c_int {
+unsafe fn access_logic(raw_path: *const c_char, mode: c_int, flags: c_int) -> c_int {
     unsafe {
-        access(raw_path.checked_into(), mode).unwrap_or_bypass_with(|bypass| {
+        access(raw_path.checked_into(), mode, flags).unwrap_or_bypass_with(|bypass| {
             let raw_path = update_ptr_from_bypass(raw_path, &bypass);
-            FN_ACCESS(raw_path, mode)
+            if flags == 0 {
+                FN_ACCESS(raw_path, mode)
+            } else {
+                FN_FACCESSAT(AT_FDCWD, raw_path, mode, flags)
+            }
         })
     }
 }
@@ -855,7 +1114,7 @@ unsafe fn access_logic(raw_path: *const c_char, mode: c_int) -> c_int {
 /// Hook for `libc::access`.
 #[hook_guard_fn]
 pub(crate) unsafe extern "C" fn access_detour(raw_path: *const c_char, mode: c_int) -> c_int {
-    unsafe { access_logic(raw_path, mode) }
+    unsafe { access_logic(raw_path, mode, 0) }
 }
 
 /// Hook for `libc::faccessat`.
@@ -867,8 +1126,9 @@ pub(crate) unsafe extern "C" fn faccessat_detour(
     flags: c_int,
 ) -> c_int {
     unsafe {
-        if dirfd == AT_FDCWD && (flags == AT_EACCESS || flags == 0) {
-            access_logic(pathname, mode)
+        let absolute = !pathname.is_null() && *pathname == b'/' as c_char;
+        if (dirfd == AT_FDCWD || absolute) && (flags & !(AT_EACCESS | AT_SYMLINK_NOFOLLOW)) == 0 {
+            access_logic(pathname, mode, flags)
         } else {
             FN_FACCESSAT(dirfd, pathname, mode, flags)
         }
@@ -1052,6 +1312,276 @@ unsafe extern "C" fn statx_detour(
     }
 }
 
+/// Reads the name of an extended attribute passed to one of the `*xattr` hooks.
+///
+/// Returns [`None`] if the pointer is null, the original function should handle it then.
+#[cfg(target_os = "linux")]
+unsafe fn xattr_name(raw_name: *const c_char) -> Option<Vec<u8>> {
+    (!raw_name.is_null()).then(|| {
+        unsafe { std::ffi::CStr::from_ptr(raw_name) }
+            .to_bytes()
+            .to_vec()
+    })
+}
+
+/// Copies the result of a `getxattr`/`listxattr` call into the user buffer, following the libc
+/// semantics:
+///
+/// 1. when `size` is 0, only the size of the result is returned,
+/// 2. when the buffer is too small, `ERANGE` is returned.
+#[cfg(target_os = "linux")]
+unsafe fn copy_xattr_buffer(bytes: &[u8], out_buffer: *mut c_void, size: size_t) -> ssize_t {
+    if size == 0 {
+        return ssize_t::try_from(bytes.len()).unwrap();
+    }
+
+    if bytes.len() > size {
+        Errno::set_raw(libc::ERANGE);
+        return -1;
+    }
+
+    unsafe { ptr::copy_nonoverlapping(bytes.as_ptr(), out_buffer.cast(), bytes.len()) };
+
+    ssize_t::try_from(bytes.len()).unwrap()
+}
+
+/// Hook for [`libc::getxattr`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn getxattr_detour(
+    raw_path: *const c_char,
+    raw_name: *const c_char,
+    value: *mut c_void,
+    size: size_t,
+) -> ssize_t {
+    unsafe {
+        let Some(name) = xattr_name(raw_name) else {
+            return FN_GETXATTR(raw_path, raw_name, value, size);
+        };
+
+        let file = XattrFile::Path {
+            path: raw_path.checked_into(),
+            follow_symlinks: true,
+        };
+
+        getxattr(file, name)
+            .map(|bytes| copy_xattr_buffer(&bytes, value, size))
+            .unwrap_or_bypass_with(|bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_GETXATTR(raw_path, raw_name, value, size)
+            })
+    }
+}
+
+/// Hook for [`libc::lgetxattr`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn lgetxattr_detour(
+    raw_path: *const c_char,
+    raw_name: *const c_char,
+    value: *mut c_void,
+    size: size_t,
+) -> ssize_t {
+    unsafe {
+        let Some(name) = xattr_name(raw_name) else {
+            return FN_LGETXATTR(raw_path, raw_name, value, size);
+        };
+
+        let file = XattrFile::Path {
+            path: raw_path.checked_into(),
+            follow_symlinks: false,
+        };
+
+        getxattr(file, name)
+            .map(|bytes| copy_xattr_buffer(&bytes, value, size))
+            .unwrap_or_bypass_with(|bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_LGETXATTR(raw_path, raw_name, value, size)
+            })
+    }
+}
+
+/// Hook for [`libc::fgetxattr`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn fgetxattr_detour(
+    fd: c_int,
+    raw_name: *const c_char,
+    value: *mut c_void,
+    size: size_t,
+) -> ssize_t {
+    unsafe {
+        let Some(name) = xattr_name(raw_name) else {
+            return FN_FGETXATTR(fd, raw_name, value, size);
+        };
+
+        getxattr(XattrFile::Fd(fd), name)
+            .map(|bytes| copy_xattr_buffer(&bytes, value, size))
+            .unwrap_or_bypass_with(|_| FN_FGETXATTR(fd, raw_name, value, size))
+    }
+}
+
+/// Hook for [`libc::setxattr`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn setxattr_detour(
+    raw_path: *const c_char,
+    raw_name: *const c_char,
+    value: *const c_void,
+    size: size_t,
+    flags: c_int,
+) -> c_int {
+    unsafe {
+        let Some(name) = xattr_name(raw_name) else {
+            return FN_SETXATTR(raw_path, raw_name, value, size, flags);
+        };
+        if value.is_null() && size > 0 {
+            return FN_SETXATTR(raw_path, raw_name, value, size, flags);
+        }
+
+        let file = XattrFile::Path {
+            path: raw_path.checked_into(),
+            follow_symlinks: true,
+        };
+        let bytes = xattr_value(value, size);
+
+        setxattr(file, name, bytes, flags)
+            .map(|()| 0)
+            .unwrap_or_bypass_with(|bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_SETXATTR(raw_path, raw_name, value, size, flags)
+            })
+    }
+}
+
+/// Hook for [`libc::lsetxattr`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn lsetxattr_detour(
+    raw_path: *const c_char,
+    raw_name: *const c_char,
+    value: *const c_void,
+    size: size_t,
+    flags: c_int,
+) -> c_int {
+    unsafe {
+        let Some(name) = xattr_name(raw_name) else {
+            return FN_LSETXATTR(raw_path, raw_name, value, size, flags);
+        };
+        if value.is_null() && size > 0 {
+            return FN_LSETXATTR(raw_path, raw_name, value, size, flags);
+        }
+
+        let file = XattrFile::Path {
+            path: raw_path.checked_into(),
+            follow_symlinks: false,
+        };
+        let bytes = xattr_value(value, size);
+
+        setxattr(file, name, bytes, flags)
+            .map(|()| 0)
+            .unwrap_or_bypass_with(|bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_LSETXATTR(raw_path, raw_name, value, size, flags)
+            })
+    }
+}
+
+/// Hook for [`libc::fsetxattr`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn fsetxattr_detour(
+    fd: c_int,
+    raw_name: *const c_char,
+    value: *const c_void,
+    size: size_t,
+    flags: c_int,
+) -> c_int {
+    unsafe {
+        let Some(name) = xattr_name(raw_name) else {
+            return FN_FSETXATTR(fd, raw_name, value, size, flags);
+        };
+        if value.is_null() && size > 0 {
+            return FN_FSETXATTR(fd, raw_name, value, size, flags);
+        }
+
+        let bytes = xattr_value(value, size);
+
+        setxattr(XattrFile::Fd(fd), name, bytes, flags)
+            .map(|()| 0)
+            .unwrap_or_bypass_with(|_| FN_FSETXATTR(fd, raw_name, value, size, flags))
+    }
+}
+
+/// Copies the value passed to one of the `*setxattr` hooks.
+///
+/// The caller must verify that `value` is not null when `size` is not 0.
+#[cfg(target_os = "linux")]
+unsafe fn xattr_value(value: *const c_void, size: size_t) -> Vec<u8> {
+    if size == 0 {
+        Vec::new()
+    } else {
+        unsafe { slice::from_raw_parts(value.cast::<u8>(), size) }.to_vec()
+    }
+}
+
+/// Hook for [`libc::listxattr`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn listxattr_detour(
+    raw_path: *const c_char,
+    list: *mut c_char,
+    size: size_t,
+) -> ssize_t {
+    unsafe {
+        let file = XattrFile::Path {
+            path: raw_path.checked_into(),
+            follow_symlinks: true,
+        };
+
+        listxattr(file)
+            .map(|bytes| copy_xattr_buffer(&bytes, list.cast(), size))
+            .unwrap_or_bypass_with(|bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_LISTXATTR(raw_path, list, size)
+            })
+    }
+}
+
+/// Hook for [`libc::llistxattr`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn llistxattr_detour(
+    raw_path: *const c_char,
+    list: *mut c_char,
+    size: size_t,
+) -> ssize_t {
+    unsafe {
+        let file = XattrFile::Path {
+            path: raw_path.checked_into(),
+            follow_symlinks: false,
+        };
+
+        listxattr(file)
+            .map(|bytes| copy_xattr_buffer(&bytes, list.cast(), size))
+            .unwrap_or_bypass_with(|bypass| {
+                let raw_path = update_ptr_from_bypass(raw_path, &bypass);
+                FN_LLISTXATTR(raw_path, list, size)
+            })
+    }
+}
+
+/// Hook for [`libc::flistxattr`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn flistxattr_detour(fd: c_int, list: *mut c_char, size: size_t) -> ssize_t {
+    unsafe {
+        listxattr(XattrFile::Fd(fd))
+            .map(|bytes| copy_xattr_buffer(&bytes, list.cast(), size))
+            .unwrap_or_bypass_with(|_| FN_FLISTXATTR(fd, list, size))
+    }
+}
+
 /// Hook for libc's stat syscall wrapper.
 #[hook_guard_fn]
 pub(crate) unsafe extern "C" fn __xstat_detour(
@@ -1327,20 +1857,22 @@ pub(crate) unsafe extern "C" fn rename_detour(
         })
 }
 
-fn vec_to_iovec(bytes: &[u8], iovecs: &[iovec]) {
+/// Copies the bytes of a [`ReadvFileResponse`] to the `iovecs`, split as in the response.
+///
+/// Returns the amount of bytes copied.
+fn copy_to_iovecs(response: &ReadvFileResponse, iovecs: &[iovec]) -> ssize_t {
+    let mut bytes: &[u8] = &response.bytes;
     let mut copied = 0;
-    let mut iov_index = 0;
 
-    while copied < bytes.len() {
-        let iov = &iovecs.get(iov_index).expect("ioevec out of bounds");
-        let read_ptr = unsafe { bytes.as_ptr().add(copied) };
-        let copy_amount = std::cmp::min(bytes.len(), iov.iov_len);
-        let out_buffer = iov.iov_base.cast();
-        unsafe { ptr::copy(read_ptr, out_buffer, copy_amount) };
-        copied += copy_amount;
-        // we trust iov_index to be in correct size since we checked it before
-        iov_index += 1;
+    for (iov, length) in iovecs.iter().zip(&response.iov_lengths) {
+        let length = (*length as usize).min(iov.iov_len).min(bytes.len());
+        let (chunk, rest) = bytes.split_at(length);
+        unsafe { ptr::copy_nonoverlapping(chunk.as_ptr(), iov.iov_base.cast(), length) };
+        bytes = rest;
+        copied += length;
     }
+
+    ssize_t::try_from(copied).unwrap()
 }
 
 /// Hook for `libc::readv`.
@@ -1357,16 +1889,10 @@ pub(crate) unsafe extern "C" fn readv_detour(
 
         let iovs = (!iovecs.is_null()).then(|| slice::from_raw_parts(iovecs, iovec_count as usize));
 
-        readv(iovs)
-            .and_then(|(iovs, read_size)| Detour::Success((read(fd, read_size)?, iovs)))
-            .map(|(read_file, iovs)| {
-                let ReadFileResponse { bytes, .. } = read_file;
-
-                vec_to_iovec(bytes.borrow(), iovs);
-                // WARN: Must be careful when it comes to `EOF`, incorrect handling may appear as
-                // the `read` call being repeated.
-                ssize_t::try_from(bytes.len()).unwrap()
-            })
+        // WARN: Must be careful when it comes to `EOF`, incorrect handling may appear as the
+        // `read` call being repeated.
+        readv(fd, iovs)
+            .map(|(response, iovs)| copy_to_iovecs(&response, iovs))
             .unwrap_or_bypass_with(|_| FN_READV(fd, iovecs, iovec_count))
     }
 }
@@ -1385,16 +1911,10 @@ pub(crate) unsafe extern "C" fn readv_nocancel_detour(
 
         let iovs = (!iovecs.is_null()).then(|| slice::from_raw_parts(iovecs, iovec_count as usize));
 
-        readv(iovs)
-            .and_then(|(iovs, read_size)| Detour::Success((read(fd, read_size)?, iovs)))
-            .map(|(read_file, iovs)| {
-                let ReadFileResponse { bytes, .. } = read_file;
-
-                vec_to_iovec(bytes.borrow(), iovs);
-                // WARN: Must be careful when it comes to `EOF`, incorrect handling may appear as
-                // the `read` call being repeated.
-                ssize_t::try_from(bytes.len()).unwrap()
-            })
+        // WARN: Must be careful when it comes to `EOF`, incorrect handling may appear as the
+        // `read` call being repeated.
+        readv(fd, iovs)
+            .map(|(response, iovs)| copy_to_iovecs(&response, iovs))
             .unwrap_or_bypass_with(|_| FN_READV_NOCANCEL(fd, iovecs, iovec_count))
     }
 }
@@ -1414,19 +1934,10 @@ pub(crate) unsafe extern "C" fn preadv_detour(
 
         let iovs = (!iovecs.is_null()).then(|| slice::from_raw_parts(iovecs, iovec_count as usize));
 
-        readv(iovs)
-            .and_then(|(iovs, read_size)| {
-                Detour::Success((pread(fd, read_size, offset as u64)?, iovs))
-            })
-            .map(|(read_file, iovs)| {
-                let ReadFileResponse { bytes, .. } = read_file;
-
-                vec_to_iovec(bytes.borrow(), iovs);
-
-                // WARN: Must be careful when it comes to `EOF`, incorrect handling may appear as
-                // the `read` call being repeated.
-                ssize_t::try_from(bytes.len()).unwrap()
-            })
+        // WARN: Must be careful when it comes to `EOF`, incorrect handling may appear as the
+        // `read` call being repeated.
+        preadv(fd, iovs, offset as u64)
+            .map(|(response, iovs)| copy_to_iovecs(&response, iovs))
             .unwrap_or_bypass_with(|_| FN_PREADV(fd, iovecs, iovec_count, offset))
     }
 }
@@ -1446,20 +1957,68 @@ pub(crate) unsafe extern "C" fn preadv_nocancel_detour(
 
         let iovs = (!iovecs.is_null()).then(|| slice::from_raw_parts(iovecs, iovec_count as usize));
 
-        readv(iovs)
-            .and_then(|(iovs, read_size)| {
-                Detour::Success((pread(fd, read_size, offset as u64)?, iovs))
-            })
-            .map(|(read_file, iovs)| {
-                let ReadFileResponse { bytes, .. } = read_file;
+        // WARN: Must be careful when it comes to `EOF`, incorrect handling may appear as the
+        // `read` call being repeated.
+        preadv(fd, iovs, offset as u64)
+            .map(|(response, iovs)| copy_to_iovecs(&response, iovs))
+            .unwrap_or_bypass_with(|_| FN_PREADV_NOCANCEL(fd, iovecs, iovec_count, offset))
+    }
+}
 
-                vec_to_iovec(bytes.borrow(), iovs);
+/// Copies the contents of the `iovecs` out of the user application.
+///
+/// [`None`] when `iovecs` is null.
+unsafe fn iovecs_to_vecs(iovecs: *const iovec, iovec_count: c_int) -> Option<Vec<Vec<u8>>> {
+    unsafe {
+        (!iovecs.is_null()).then(|| {
+            slice::from_raw_parts(iovecs, iovec_count as usize)
+                .iter()
+                // WARN: Be veeery careful here, you cannot construct the `Vec` directly, as the
+                // buffer allocation is handled on the C side.
+                .map(|iov| match iov.iov_len {
+                    0 => Vec::new(),
+                    len => slice::from_raw_parts(iov.iov_base as *const u8, len).to_vec(),
+                })
+                .collect()
+        })
+    }
+}
 
-                // WARN: Must be careful when it comes to `EOF`, incorrect handling may appear as
-                // the `read` call being repeated.
-                ssize_t::try_from(bytes.len()).unwrap()
-            })
-            .unwrap_or_bypass_with(|_| FN_PREADV_NOCANCEL(fd, iovecs, iovec_count, offset))
+/// Hook for `libc::writev`.
+///
+/// **Bypassed** by `fd`s that are not managed by us (not found in `OPEN_FILES`).
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn writev_detour(
+    fd: RawFd,
+    iovecs: *const iovec,
+    iovec_count: c_int,
+) -> ssize_t {
+    unsafe {
+        if iovec_count < 0 {
+            return FN_WRITEV(fd, iovecs, iovec_count);
+        }
+
+        writev(fd, iovecs_to_vecs(iovecs, iovec_count))
+            .unwrap_or_bypass_with(|_| FN_WRITEV(fd, iovecs, iovec_count))
+    }
+}
+
+/// Hook for `libc::pwritev`.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn pwritev_detour(
+    fd: RawFd,
+    iovecs: *const iovec,
+    iovec_count: c_int,
+    offset: off_t,
+) -> ssize_t {
+    unsafe {
+        if iovec_count < 0 {
+            return FN_PWRITEV(fd, iovecs, iovec_count, offset);
+        }
+
+        pwritev(fd, iovecs_to_vecs(iovecs, iovec_count), offset as u64)
+            .map(|WriteFileResponse { written_amount }| written_amount as ssize_t)
+            .unwrap_or_bypass_with(|_| FN_PWRITEV(fd, iovecs, iovec_count, offset))
     }
 }
 
@@ -1532,6 +2091,88 @@ pub(crate) unsafe extern "C" fn rmdir_detour(pathname: *const c_char) -> c_int {
     }
 }
 
+/// Hook for `libc::chdir`.
+///
+/// Changing into a remote directory only sets [`REMOTE_CWD`](crate::file::REMOTE_CWD), while
+/// changing into a local one clears it.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn chdir_detour(pathname: *const c_char) -> c_int {
+    unsafe {
+        chdir(pathname.checked_into())
+            .map(|()| 0)
+            .unwrap_or_bypass_with(|bypass| {
+                let raw_path = update_ptr_from_bypass(pathname, &bypass);
+                let result = FN_CHDIR(raw_path);
+                if result == 0 {
+                    leave_remote_cwd();
+                }
+                result
+            })
+    }
+}
+
+/// Hook for `libc::fchdir`, see [`chdir_detour`].
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn fchdir_detour(fd: c_int) -> c_int {
+    unsafe {
+        fchdir(fd).map(|()| 0).unwrap_or_bypass_with(|_| {
+            let result = FN_FCHDIR(fd);
+            if result == 0 {
+                leave_remote_cwd();
+            }
+            result
+        })
+    }
+}
+
+/// Clears the [`REMOTE_CWD`](crate::file::REMOTE_CWD) after a successful local `chdir`/`fchdir`.
+fn leave_remote_cwd() {
+    if let Ok(mut remote_cwd) = crate::file::REMOTE_CWD.lock() {
+        remote_cwd.take();
+    }
+}
+
+/// Hook for `libc::getcwd`.
+///
+/// Reports the [`REMOTE_CWD`](crate::file::REMOTE_CWD) when the app is in a remote directory,
+/// including the glibc extension of allocating the buffer when `buffer` is null.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn getcwd_detour(buffer: *mut c_char, size: size_t) -> *mut c_char {
+    unsafe {
+        getcwd()
+            .map(|cwd| {
+                let cwd = cwd.as_bytes_with_nul();
+
+                let output = if buffer.is_null() {
+                    let length = if size == 0 { cwd.len() } else { size };
+                    if length < cwd.len() {
+                        Errno::ERANGE.set();
+                        return ptr::null_mut();
+                    }
+
+                    let output = libc::malloc(length) as *mut c_char;
+                    if output.is_null() {
+                        Errno::ENOMEM.set();
+                        return ptr::null_mut();
+                    }
+                    output
+                } else if size == 0 {
+                    Errno::EINVAL.set();
+                    return ptr::null_mut();
+                } else if size < cwd.len() {
+                    Errno::ERANGE.set();
+                    return ptr::null_mut();
+                } else {
+                    buffer
+                };
+
+                output.copy_from_nonoverlapping(cwd.as_ptr().cast(), cwd.len());
+                output
+            })
+            .unwrap_or_bypass_with(|_| FN_GETCWD(buffer, size))
+    }
+}
+
 /// Hook for `libc::unlink`.
 #[hook_guard_fn]
 pub(crate) unsafe extern "C" fn unlink_detour(pathname: *const c_char) -> c_int {
@@ -1655,6 +2296,10 @@ pub(crate) unsafe fn enable_file_hooks(hook_manager: &mut HookManager, state: &L
 
         replace!(hook_manager, "rmdir", rmdir_detour, FnRmdir, FN_RMDIR);
 
+        replace!(hook_manager, "chdir", chdir_detour, FnChdir, FN_CHDIR);
+        replace!(hook_manager, "fchdir", fchdir_detour, FnFchdir, FN_FCHDIR);
+        replace!(hook_manager, "getcwd", getcwd_detour, FnGetcwd, FN_GETCWD);
+
         replace!(hook_manager, "unlink", unlink_detour, FnUnlink, FN_UNLINK);
         replace!(
             hook_manager,
@@ -1684,6 +2329,15 @@ pub(crate) unsafe fn enable_file_hooks(hook_manager: &mut HookManager, state: &L
             FN_PWRITE_NOCANCEL
         );
 
+        replace!(hook_manager, "writev", writev_detour, FnWritev, FN_WRITEV);
+        replace!(
+            hook_manager,
+            "pwritev",
+            pwritev_detour,
+            FnPwritev,
+            FN_PWRITEV
+        );
+
         replace!(hook_manager, "access", access_detour, FnAccess, FN_ACCESS);
         replace!(
             hook_manager,
@@ -1732,6 +2386,71 @@ pub(crate) unsafe fn enable_file_hooks(hook_manager: &mut HookManager, state: &L
         #[cfg(target_os = "linux")]
         {
             replace!(hook_manager, "statx", statx_detour, FnStatx, FN_STATX);
+
+            replace!(
+                hook_manager,
+                "getxattr",
+                getxattr_detour,
+                FnGetxattr,
+                FN_GETXATTR
+            );
+            replace!(
+                hook_manager,
+                "lgetxattr",
+                lgetxattr_detour,
+                FnLgetxattr,
+                FN_LGETXATTR
+            );
+            replace!(
+                hook_manager,
+                "fgetxattr",
+                fgetxattr_detour,
+                FnFgetxattr,
+                FN_FGETXATTR
+            );
+            replace!(
+                hook_manager,
+                "setxattr",
+                setxattr_detour,
+                FnSetxattr,
+                FN_SETXATTR
+            );
+            replace!(
+                hook_manager,
+                "lsetxattr",
+                lsetxattr_detour,
+                FnLsetxattr,
+                FN_LSETXATTR
+            );
+            replace!(
+                hook_manager,
+                "fsetxattr",
+                fsetxattr_detour,
+                FnFsetxattr,
+                FN_FSETXATTR
+            );
+            replace!(
+                hook_manager,
+                "listxattr",
+                listxattr_detour,
+                FnListxattr,
+                FN_LISTXATTR
+            );
+            replace!(
+                hook_manager,
+                "llistxattr",
+                llistxattr_detour,
+                FnLlistxattr,
+                FN_LLISTXATTR
+            );
+            replace!(
+                hook_manager,
+                "flistxattr",
+                flistxattr_detour,
+                FnFlistxattr,
+                FN_FLISTXATTR
+            );
+
             replace!(
                 hook_manager,
                 "fstatfs64",
@@ -1930,6 +2649,24 @@ pub(crate) unsafe fn enable_file_hooks(hook_manager: &mut HookManager, state: &L
             FN_SENDFILE
         );
 
+        #[cfg(target_os = "linux")]
+        replace!(
+            hook_manager,
+            "sendfile64",
+            sendfile64_detour,
+            FnSendfile64,
+            FN_SENDFILE64
+        );
+
+        #[cfg(target_os = "linux")]
+        replace!(
+            hook_manager,
+            "copy_file_range",
+            copy_file_range_detour,
+            FnCopy_file_range,
+            FN_COPY_FILE_RANGE
+        );
+
         replace!(
             hook_manager,
             "ftruncate",
@@ -1948,6 +2685,42 @@ pub(crate) unsafe fn enable_file_hooks(hook_manager: &mut HookManager, state: &L
 
         replace!(hook_manager, "fchown", fchown_detour, FnFchown, FN_FCHOWN);
 
+        replace!(
+            hook_manager,
+            "fchownat",
+            fchownat_detour,
+            FnFchownat,
+            FN_FCHOWNAT
+        );
+
+        replace!(hook_manager, "lchown", lchown_detour, FnLchown, FN_LCHOWN);
+
+        replace!(
+            hook_manager,
+            "utimensat",
+            utimensat_detour,
+            FnUtimensat,
+            FN_UTIMENSAT
+        );
+
+        replace!(
+            hook_manager,
+            "lutimes",
+            lutimes_detour,
+            FnLutimes,
+            FN_LUTIMES
+        );
+
         replace!(hook_manager, "fchmod", fchmod_detour, FnFchmod, FN_FCHMOD);
+
+        replace!(hook_manager, "flock", flock_detour, FnFlock, FN_FLOCK);
+
+        // Remote files can be modified only in the write mode.
+        if state.fs_config().mode.is_write() {
+            replace!(hook_manager, "sync", sync_detour, FnSync, FN_SYNC);
+        }
+
+        #[cfg(target_os = "linux")]
+        replace!(hook_manager, "syncfs", syncfs_detour, FnSyncfs, FN_SYNCFS);
     }
 }
@@ -3,14 +3,13 @@
 //! When operating on the paths provided from the user application, remember to verify/remap them.
 //! Canonical order of operations can be found in [`common_path_check`].
 
-#[cfg(target_os = "linux")]
-use std::time::Duration;
 use std::{
     env,
     ffi::CString,
     io::SeekFrom,
-    os::unix::io::RawFd,
+    os::unix::{ffi::OsStringExt, io::RawFd},
     path::{Path, PathBuf},
+    time::Duration,
 };
 
 use libc::{AT_FDCWD, c_int, iovec};
@@ -22,14 +21,23 @@ use mirrord_layer_lib::{
     error::{HookError, HookResult as Result},
     file::filter::FileFilter,
 };
+#[cfg(target_os = "linux")]
+use mirrord_protocol::file::{
+    CopyFileRangeRequest, CopyFileRangeResponse, GetXattrRequest, GetXattrResponse,
+    ListXattrRequest, ListXattrResponse, SetXattrRequest, XattrTarget,
+};
 use mirrord_protocol::{
-    Payload, ResponseError,
+    ErrorKindInternal, Payload, RemoteIOError, ResponseError,
     file::{
-        FchmodRequest, FchownRequest, FtruncateRequest, FutimensRequest, MakeDirAtRequest,
-        MakeDirRequest, OpenFileRequest, OpenFileResponse, OpenOptionsInternal, ReadFileResponse,
-        ReadLinkFileRequest, ReadLinkFileResponse, RemoveDirRequest, RenameRequest,
-        SeekFileResponse, StatFsRequestV2, Timespec, UnlinkAtRequest, UnlinkRequest,
-        WriteFileResponse, XstatFsRequestV2, XstatFsResponseV2, XstatResponse,
+        ChownRequest, FchmodRequest, FchownRequest, FcntlLock, FcntlLockCommand, FcntlLockRequest,
+        FcntlLockResponse, FcntlLockType, FcntlLockWhence, FlockOperation, FlockRequest,
+        FtruncateRequest, FutimensRequest, MakeDirAtRequest, MakeDirRequest, OpenFileRequest,
+        OpenFileRequestV2, OpenFileRequestV3, OpenFileResponse, OpenFlagsInternal,
+        OpenOptionsInternal, OpenRelativeFileRequestV2, OpenRelativeFileRequestV3,
+        ReadFileResponse, ReadLinkFileRequest, ReadLinkFileResponse, ReadvFileRequest,
+        ReadvFileResponse, RemoveDirRequest, RenameRequest, SeekFileResponse, StatFsRequestV2,
+        SyncfsRequest, Timespec, UnlinkAtRequest, UnlinkRequest, UtimensRequest, WriteFileResponse,
+        WritevFileRequest, XstatFsRequestV2, XstatFsResponseV2, XstatResponse,
     },
 };
 use nix::errno::Errno;
@@ -99,15 +107,32 @@ pub fn ensure_remote(file_filter: &FileFilter, path: &Path, write: bool) -> Deto
     }
 }
 
+/// Resolves a relative `path` against the [`REMOTE_CWD`], when the app has changed into a remote
+/// directory. Other paths are returned as they are.
+fn resolve_remote_cwd(path: PathBuf) -> Detour<PathBuf> {
+    if path.is_absolute() {
+        return Detour::Success(path);
+    }
+
+    let path = match REMOTE_CWD.lock()?.as_ref() {
+        Some(cwd) => absolute_path(cwd.join(path)),
+        None => path,
+    };
+
+    Detour::Success(path)
+}
+
 /// Performs standard verification of paths accessed by the user application.
 ///
 /// Operations in order:
-/// 1. Bypass if the path is not relative and not present in the `fs.not_found` filters.
-/// 2. Remap the file according to the config.
-/// 3. Bypass if the new path should be accessed locally.
+/// 1. Resolve a relative path against the [`REMOTE_CWD`], if the app is in a remote directory.
+/// 2. Bypass if the path is still relative and not present in the `fs.not_found` filters.
+/// 3. Remap the file according to the config.
+/// 4. Bypass if the new path should be accessed locally.
 ///
 /// Returns the remapped path.
 fn common_path_check(path: PathBuf, write: bool) -> Detour<PathBuf> {
+    let path = resolve_remote_cwd(path)?;
     path.ensure_not_relative_or_not_found()?;
 
     let path = crate::setup().file_remapper().change_path(path);
@@ -119,22 +144,50 @@ fn common_path_check(path: PathBuf, write: bool) -> Detour<PathBuf> {
 pub(crate) struct RemoteFile {
     pub fd: u64,
     pub path: String,
+    /// Opened with `O_PATH`, so it can't be read from or written to.
+    pub path_only: bool,
+    /// Opened with `O_NONBLOCK`, so reads and writes on a remote FIFO fail with `EAGAIN` instead
+    /// of waiting, see [`retry_while_would_block`].
+    pub nonblock: bool,
 }
 
 impl RemoteFile {
-    pub(crate) fn new(fd: u64, path: String) -> Self {
-        Self { fd, path }
+    pub(crate) fn new(fd: u64, path: String, flags: OpenFlagsInternal, nonblock: bool) -> Self {
+        Self {
+            fd,
+            path,
+            path_only: flags.path,
+            nonblock,
+        }
     }
 
     /// Sends a [`OpenFileRequest`] message, opening the file in the agent.
+    ///
+    /// Sends a [`OpenFileRequestV2`] instead when any of the [`OpenFlagsInternal`] is set, and a
+    /// [`OpenFileRequestV3`] when the file is opened with `O_NONBLOCK`.
     #[mirrord_layer_macro::instrument(level = "trace")]
     pub(crate) fn remote_open(
         path: PathBuf,
         open_options: OpenOptionsInternal,
+        flags: OpenFlagsInternal,
+        nonblock: bool,
     ) -> Detour<OpenFileResponse> {
-        let requesting_file = OpenFileRequest { path, open_options };
-
-        let response = common::make_proxy_request_with_response(requesting_file)??;
+        let response = if nonblock {
+            common::make_proxy_request_with_response(OpenFileRequestV3 {
+                path,
+                open_options,
+                flags,
+                nonblock,
+            })??
+        } else if flags.is_empty() {
+            common::make_proxy_request_with_response(OpenFileRequest { path, open_options })??
+        } else {
+            common::make_proxy_request_with_response(OpenFileRequestV2 {
+                path,
+                open_options,
+                flags,
+            })??
+        };
 
         Detour::Success(response)
     }
@@ -195,6 +248,23 @@ fn get_remote_fd(local_fd: RawFd) -> Detour<u64> {
     )
 }
 
+/// Same as [`get_remote_fd`], but for reading and writing, which fails with
+/// [`HookError::BadDescriptor`] on fds opened with `O_PATH`.
+fn get_remote_io_fd(local_fd: RawFd) -> Detour<u64> {
+    // don't add a trace here since it causes deadlocks in some cases.
+    let (remote_fd, path_only) = OPEN_FILES
+        .lock()?
+        .get(&local_fd)
+        .map(|remote_file| (remote_file.fd, remote_file.path_only))
+        .ok_or(Bypass::LocalFdNotFound(local_fd))?;
+
+    if path_only {
+        Detour::Error(HookError::BadDescriptor)
+    } else {
+        Detour::Success(remote_fd)
+    }
+}
+
 /// Create temporary local file to get a valid local fd.
 #[mirrord_layer_macro::instrument(level = "trace", ret)]
 fn create_local_fake_file(remote_fd: u64) -> Detour<RawFd> {
@@ -253,15 +323,22 @@ fn close_remote_file_on_failure(fd: u64) -> Result<()> {
 /// _local_ and _remote_ file association, plus **inserting** it into the storage for
 /// [`OPEN_FILES`].
 #[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
-pub(crate) fn open(path: Detour<PathBuf>, open_options: OpenOptionsInternal) -> Detour<RawFd> {
-    let path = common_path_check(path?, open_options.is_write())?;
+pub(crate) fn open(
+    path: Detour<PathBuf>,
+    open_options: OpenOptionsInternal,
+    flags: OpenFlagsInternal,
+    nonblock: bool,
+) -> Detour<RawFd> {
+    let path = path?;
+    if let Some(local_file_fd) = virtual_files::open(&path, &open_options)? {
+        return Detour::Success(local_file_fd);
+    }
 
-    let OpenFileResponse { fd: remote_fd } = RemoteFile::remote_open(path.clone(), open_options)
-        .or_else(|fail| match fail {
-            // The operator has a policy that matches this `path` as local-only.
-            HookError::ResponseError(ResponseError::OpenLocal) => Detour::Bypass(Bypass::OpenLocal),
-            other => Detour::Error(other),
-        })?;
+    let path = common_path_check(path, open_options.is_write())?;
+
+    let OpenFileResponse { fd: remote_fd } =
+        RemoteFile::remote_open(path.clone(), open_options, flags, nonblock)
+            .or_else(|fail| remote_open_failed(crate::setup().file_filter(), &path, fail))?;
 
     // TODO: Need a way to say "open a directory", right now `is_dir` always returns false.
     // This requires having a fake directory name (`/fake`, for example), instead of just converting
@@ -270,22 +347,49 @@ pub(crate) fn open(path: Detour<PathBuf>, open_options: OpenOptionsInternal) ->
 
     OPEN_FILES.lock()?.insert(
         local_file_fd,
-        Arc::new(RemoteFile::new(remote_fd, path.display().to_string())),
+        Arc::new(RemoteFile::new(
+            remote_fd,
+            path.display().to_string(),
+            flags,
+            nonblock,
+        )),
     );
 
     Detour::Success(local_file_fd)
 }
 
+/// Decides whether a failed remote open of `path` should be retried locally.
+///
+/// Bypasses when the operator has a policy that matches this `path` as local-only, or when the
+/// error and `path` match `feature.fs.fallback_local_on` and `feature.fs.fallback_local`. The
+/// local fd is then a regular local file, so writes to it stay local.
+fn remote_open_failed(
+    file_filter: &FileFilter,
+    path: &Path,
+    fail: HookError,
+) -> Detour<OpenFileResponse> {
+    match fail {
+        HookError::ResponseError(ResponseError::OpenLocal) => Detour::Bypass(Bypass::OpenLocal),
+        HookError::ResponseError(ResponseError::RemoteIO(RemoteIOError { ref kind, .. }))
+            if file_filter.should_fallback_local(path, kind) =>
+        {
+            tracing::debug!(
+                path = %path.display(),
+                ?kind,
+                "Remote open failed, falling back to a local open"
+            );
+            Detour::Bypass(Bypass::ignored_file(path.to_str().unwrap_or_default()))
+        }
+        other => Detour::Error(other),
+    }
+}
+
 /// creates a directory stream for the `remote_fd` in the agent
 #[mirrord_layer_macro::instrument(level = "trace", ret)]
 pub(crate) fn fdopendir(fd: RawFd) -> Detour<usize> {
     // usize == ptr size
     // we don't return a pointer to an address that contains DIR
-    let remote_file_fd = OPEN_FILES
-        .lock()?
-        .get(&fd)
-        .ok_or(Bypass::LocalFdNotFound(fd))?
-        .fd;
+    let remote_file_fd = get_remote_io_fd(fd)?;
 
     let open_dir_request = FdOpenDirRequest {
         remote_fd: remote_file_fd,
@@ -307,59 +411,160 @@ pub(crate) fn openat(
     fd: RawFd,
     path: Detour<PathBuf>,
     open_options: OpenOptionsInternal,
+    flags: OpenFlagsInternal,
+    nonblock: bool,
 ) -> Detour<RawFd> {
     let path = path?;
 
     // `openat` behaves the same as `open` when the path is absolute. When called with AT_FDCWD, the
     // call is propagated to `open`.
     if path.is_absolute() || fd == AT_FDCWD {
-        return open(Detour::Success(path), open_options);
+        return open(Detour::Success(path), open_options, flags, nonblock);
     }
 
     // Relative path requires special handling, we must identify the relative part
     // (relative to what).
     let remote_fd = get_remote_fd(fd)?;
 
-    let requesting_file = OpenRelativeFileRequest {
-        relative_fd: remote_fd,
-        path: path.clone(),
-        open_options,
+    let OpenFileResponse { fd: remote_fd } = if nonblock {
+        common::make_proxy_request_with_response(OpenRelativeFileRequestV3 {
+            relative_fd: remote_fd,
+            path: path.clone(),
+            open_options,
+            flags,
+            nonblock,
+        })??
+    } else if flags.is_empty() {
+        common::make_proxy_request_with_response(OpenRelativeFileRequest {
+            relative_fd: remote_fd,
+            path: path.clone(),
+            open_options,
+        })??
+    } else {
+        common::make_proxy_request_with_response(OpenRelativeFileRequestV2 {
+            relative_fd: remote_fd,
+            path: path.clone(),
+            open_options,
+            flags,
+        })??
     };
 
-    let OpenFileResponse { fd: remote_fd } =
-        common::make_proxy_request_with_response(requesting_file)??;
-
     let local_file_fd = create_local_fake_file(remote_fd)?;
 
     OPEN_FILES.lock()?.insert(
         local_file_fd,
-        Arc::new(RemoteFile::new(remote_fd, path.display().to_string())),
+        Arc::new(RemoteFile::new(
+            remote_fd,
+            path.display().to_string(),
+            flags,
+            nonblock,
+        )),
     );
 
     Detour::Success(local_file_fd)
 }
 
+/// Interval between attempts to read from or write to a blocking remote FIFO that is not ready,
+/// see [`retry_while_would_block`].
+const FIFO_RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Retries a remote read or write on `local_fd` while it fails with `EAGAIN`, unless the file was
+/// opened with `O_NONBLOCK`.
+///
+/// The agent never waits for a remote FIFO to become ready, so that it doesn't stall the other
+/// requests of this session. When the pipe is not ready, we get `EAGAIN` and send the request
+/// again after [`FIFO_RETRY_INTERVAL`].
+///
+/// Opening a remote FIFO never waits: with no writer, reading it gives EOF, and opening one for
+/// writing with no reader fails with `ENXIO`.
+fn retry_while_would_block<T>(
+    local_fd: RawFd,
+    mut operation: impl FnMut() -> Detour<T>,
+) -> Detour<T> {
+    let non_blocking = OPEN_FILES
+        .lock()?
+        .get(&local_fd)
+        .is_some_and(|remote_file| remote_file.nonblock);
+
+    loop {
+        match operation() {
+            Detour::Error(HookError::ResponseError(ResponseError::RemoteIO(RemoteIOError {
+                kind: ErrorKindInternal::WouldBlock,
+                ..
+            }))) if !non_blocking => std::thread::sleep(FIFO_RETRY_INTERVAL),
+            other => break other,
+        }
+    }
+}
+
 /// Blocking wrapper around [`libc::read`] call.
 ///
 /// **Bypassed** when trying to load system files, and files from the current working directory, see
 /// `open`.
 pub(crate) fn read(local_fd: RawFd, read_amount: u64) -> Detour<ReadFileResponse> {
-    get_remote_fd(local_fd).and_then(|remote_fd| RemoteFile::remote_read(remote_fd, read_amount))
+    let remote_fd = get_remote_io_fd(local_fd)?;
+    retry_while_would_block(local_fd, || RemoteFile::remote_read(remote_fd, read_amount))
 }
 
-/// Helper for dealing with a potential null pointer being passed to `*const iovec` from
-/// `readv_detour` and `preadv_detour`.
-pub(crate) fn readv(iovs: Option<&[iovec]>) -> Detour<(&[iovec], u64)> {
+/// Lengths of the `iovs`, for dealing with a potential null pointer being passed to
+/// `*const iovec` from `readv_detour` and `preadv_detour`.
+fn iov_lengths(iovs: Option<&[iovec]>) -> Detour<(&[iovec], Vec<u64>)> {
     let iovs = iovs?;
-    let read_size: u64 = iovs.iter().fold(0, |sum, iov| sum + iov.iov_len as u64);
+    let iov_lengths = iovs.iter().map(|iov| iov.iov_len as u64).collect();
 
-    Detour::Success((iovs, read_size))
+    Detour::Success((iovs, iov_lengths))
+}
+
+/// Reads into the `iovs` with a single [`ReadvFileRequest`].
+///
+/// Falls back to a plain [`read`] when the agent does not support [`ReadvFileRequest`], or when
+/// the file is buffered by the internal proxy.
+#[mirrord_layer_macro::instrument(level = "trace", skip(iovs))]
+pub(crate) fn readv(
+    local_fd: RawFd,
+    iovs: Option<&[iovec]>,
+) -> Detour<(ReadvFileResponse, &[iovec])> {
+    let (iovs, iov_lengths) = iov_lengths(iovs)?;
+    let remote_fd = get_remote_io_fd(local_fd)?;
+
+    let response = retry_while_would_block(local_fd, || {
+        let request = ReadvFileRequest {
+            fd: remote_fd,
+            iov_lengths: iov_lengths.clone(),
+        };
+        match common::make_proxy_request_with_response(request)? {
+            Err(ResponseError::NotImplemented) => Detour::Success(None),
+            response => Detour::Success(Some(response?)),
+        }
+    })?;
+
+    let response = match response {
+        Some(response) => response,
+        None => {
+            let ReadFileResponse { bytes, .. } = read(local_fd, iov_lengths.iter().sum())?;
+            ReadvFileResponse::new(bytes, &iov_lengths)
+        }
+    };
+
+    Detour::Success((response, iovs))
+}
+
+/// Reads into the `iovs` with a single [`pread`], starting from `offset`.
+pub(crate) fn preadv(
+    local_fd: RawFd,
+    iovs: Option<&[iovec]>,
+    offset: u64,
+) -> Detour<(ReadvFileResponse, &[iovec])> {
+    let (iovs, iov_lengths) = iov_lengths(iovs)?;
+    let ReadFileResponse { bytes, .. } = pread(local_fd, iov_lengths.iter().sum(), offset)?;
+
+    Detour::Success((ReadvFileResponse::new(bytes, &iov_lengths), iovs))
 }
 
 #[mirrord_layer_macro::instrument(level = "trace")]
 pub(crate) fn pread(local_fd: RawFd, buffer_size: u64, offset: u64) -> Detour<ReadFileResponse> {
     // We're only interested in files that are paired with mirrord-agent.
-    let remote_fd = get_remote_fd(local_fd)?;
+    let remote_fd = get_remote_io_fd(local_fd)?;
 
     let reading_file = ReadLimitedFileRequest {
         remote_fd,
@@ -463,6 +668,7 @@ pub(crate) fn unlinkat(dirfd: RawFd, path: Detour<PathBuf>, flags: u32) -> Detou
     let mut path = path?;
 
     if dirfd == AT_FDCWD {
+        path = resolve_remote_cwd(path)?;
         path.ensure_not_relative_or_not_found()?;
     }
 
@@ -495,19 +701,153 @@ pub(crate) fn unlinkat(dirfd: RawFd, path: Detour<PathBuf>, flags: u32) -> Detou
     }
 }
 
+/// Writes `buffer` to the remote file at `offset`, with [`WriteLimitedFileRequest`]s of at most
+/// `feature.fs.max_write_chunk` bytes each (see [`write_in_chunks`]).
 pub(crate) fn pwrite(local_fd: RawFd, buffer: &[u8], offset: u64) -> Detour<WriteFileResponse> {
-    let remote_fd = get_remote_fd(local_fd)?;
+    let remote_fd = get_remote_io_fd(local_fd)?;
     trace!("pwrite: local_fd {local_fd}");
-    let write_bytes = Payload::from(buffer.to_vec());
-    let writing_file = WriteLimitedFileRequest {
-        remote_fd,
-        write_bytes,
-        start_from: offset,
+
+    let max_chunk = crate::setup().fs_config().max_write_chunk;
+    write_in_chunks(buffer, offset, max_chunk, |chunk, start_from| {
+        let writing_file = WriteLimitedFileRequest {
+            remote_fd,
+            write_bytes: Payload::from(chunk.to_vec()),
+            start_from,
+        };
+
+        let response = common::make_proxy_request_with_response(writing_file)??;
+
+        Detour::Success(response)
+    })
+}
+
+/// Writes the `buffers` with a single [`pwrite`], starting from `offset`.
+pub(crate) fn pwritev(
+    local_fd: RawFd,
+    buffers: Option<Vec<Vec<u8>>>,
+    offset: u64,
+) -> Detour<WriteFileResponse> {
+    let buffer = buffers.ok_or(Bypass::EmptyBuffer)?.concat();
+    pwrite(local_fd, &buffer, offset)
+}
+
+/// Splits `buffer` into chunks of at most `max_chunk` bytes (0 means no limit), and passes them
+/// to `write_chunk` one after another, along with their offsets (starting from `offset`).
+///
+/// The write stops on the first chunk that is written partially or fails, like a short write.
+/// An error is only returned when nothing was written, otherwise the amount written so far is
+/// returned.
+fn write_in_chunks<W>(
+    buffer: &[u8],
+    offset: u64,
+    max_chunk: u64,
+    mut write_chunk: W,
+) -> Detour<WriteFileResponse>
+where
+    W: FnMut(&[u8], u64) -> Detour<WriteFileResponse>,
+{
+    let chunk_size = match usize::try_from(max_chunk) {
+        Ok(0) | Err(..) => buffer.len(),
+        Ok(size) => size,
     };
+    if buffer.len() <= chunk_size {
+        return write_chunk(buffer, offset);
+    }
 
-    let response = common::make_proxy_request_with_response(writing_file)??;
+    let mut written_amount = 0;
+    for chunk in buffer.chunks(chunk_size) {
+        match write_chunk(chunk, offset + written_amount) {
+            Detour::Success(WriteFileResponse {
+                written_amount: written,
+            }) => {
+                written_amount += written;
+                if written < chunk.len() as u64 {
+                    break;
+                }
+            }
+            Detour::Error(fail) if written_amount > 0 => {
+                trace!(%fail, written_amount, "pwrite: chunk failed, returning a short write");
+                break;
+            }
+            other => return other,
+        }
+    }
 
-    Detour::Success(response)
+    Detour::Success(WriteFileResponse { written_amount })
+}
+
+/// Emulates `sendfile` for a remote `in_fd`, reading it in chunks of at most [`MAX_READ_SIZE`]
+/// with [`ReadLimitedFileRequest`]s and passing each chunk to `write_out`.
+///
+/// `write_out` behaves like `write(2)` on the output fd. The transfer stops at EOF, after
+/// `count` bytes, or on the first short write, like the kernel does. An error is only returned
+/// when nothing was transferred.
+///
+/// When `offset` is `None` the transfer starts at the remote file position, which is then moved
+/// past the transferred bytes. Returns the amount transferred and the offset right after it.
+#[mirrord_layer_macro::instrument(level = "trace", skip(write_out), ret)]
+pub(crate) fn sendfile<W>(
+    in_fd: RawFd,
+    offset: Option<u64>,
+    count: u64,
+    mut write_out: W,
+) -> Detour<(u64, u64)>
+where
+    W: FnMut(&[u8]) -> isize,
+{
+    // Bypass early if `in_fd` is not ours.
+    get_remote_fd(in_fd)?;
+
+    let start = match offset {
+        Some(offset) => offset,
+        None => lseek(in_fd, 0, libc::SEEK_CUR)?,
+    };
+
+    let mut transferred = 0;
+    let mut result = Detour::Success(());
+
+    'transfer: while transferred < count {
+        let buffer_size = std::cmp::min(count - transferred, MAX_READ_SIZE);
+        let bytes = match pread(in_fd, buffer_size, start + transferred) {
+            Detour::Success(ReadFileResponse { bytes, .. }) if bytes.is_empty() => break,
+            Detour::Success(ReadFileResponse { bytes, .. }) => bytes,
+            Detour::Bypass(bypass) => {
+                result = Detour::Bypass(bypass);
+                break;
+            }
+            Detour::Error(fail) => {
+                result = Detour::Error(fail);
+                break;
+            }
+        };
+
+        let mut chunk: &[u8] = &bytes;
+        while !chunk.is_empty() {
+            let written = write_out(chunk);
+            if written < 0 {
+                result = Detour::Error(std::io::Error::last_os_error().into());
+                break 'transfer;
+            }
+
+            let written = written as usize;
+            transferred += written as u64;
+            if written < chunk.len() {
+                break 'transfer;
+            }
+            chunk = &chunk[written..];
+        }
+    }
+
+    let end = start + transferred;
+    if offset.is_none() && transferred > 0 {
+        lseek(in_fd, end as i64, libc::SEEK_SET)?;
+    }
+
+    match result {
+        Detour::Error(fail) if transferred == 0 => Detour::Error(fail),
+        Detour::Bypass(bypass) if transferred == 0 => Detour::Bypass(bypass),
+        _ => Detour::Success((transferred, end)),
+    }
 }
 
 #[mirrord_layer_macro::instrument(level = "trace")]
@@ -540,29 +880,113 @@ pub(crate) fn lseek(local_fd: RawFd, offset: i64, whence: i32) -> Detour<u64> {
 }
 
 pub(crate) fn write(local_fd: RawFd, write_bytes: Option<Vec<u8>>) -> Detour<isize> {
-    let remote_fd = get_remote_fd(local_fd)?;
+    let remote_fd = get_remote_io_fd(local_fd)?;
 
-    let writing_file = WriteFileRequest {
-        fd: remote_fd,
-        write_bytes: write_bytes.ok_or(Bypass::EmptyBuffer)?.into(),
-    };
+    let write_bytes: Payload = write_bytes.ok_or(Bypass::EmptyBuffer)?.into();
 
-    let WriteFileResponse { written_amount } =
-        common::make_proxy_request_with_response(writing_file)??;
+    let WriteFileResponse { written_amount } = retry_while_would_block(local_fd, || {
+        let writing_file = WriteFileRequest {
+            fd: remote_fd,
+            write_bytes: write_bytes.clone(),
+        };
+        Detour::Success(common::make_proxy_request_with_response(writing_file)??)
+    })?;
     Detour::Success(written_amount.try_into()?)
 }
 
+/// Writes the `buffers` with a single [`WritevFileRequest`].
+///
+/// Falls back to a plain [`write`] of the concatenated `buffers` when the agent does not support
+/// [`WritevFileRequest`].
+#[mirrord_layer_macro::instrument(level = "trace", skip(buffers))]
+pub(crate) fn writev(local_fd: RawFd, buffers: Option<Vec<Vec<u8>>>) -> Detour<isize> {
+    let remote_fd = get_remote_io_fd(local_fd)?;
+
+    let buffers: Vec<Payload> = buffers
+        .ok_or(Bypass::EmptyBuffer)?
+        .into_iter()
+        .map(Payload::from)
+        .collect();
+
+    let response = retry_while_would_block(local_fd, || {
+        let request = WritevFileRequest {
+            fd: remote_fd,
+            buffers: buffers.clone(),
+        };
+        match common::make_proxy_request_with_response(request)? {
+            Err(ResponseError::NotImplemented) => Detour::Success(None),
+            response => Detour::Success(Some(response?)),
+        }
+    })?;
+
+    match response {
+        Some(WriteFileResponse { written_amount }) => Detour::Success(written_amount.try_into()?),
+        None => write(
+            local_fd,
+            Some(
+                buffers
+                    .iter()
+                    .flat_map(|buffer| buffer.iter().copied())
+                    .collect(),
+            ),
+        ),
+    }
+}
+
+/// Copies up to `len` bytes from `fd_in` to `fd_out` on the agent, with a single
+/// [`CopyFileRangeRequest`], returning the amount copied.
+///
+/// **Bypassed** when `fd_in` is not a remote file. Returns [`None`] when `fd_out` is not a remote
+/// file, when the agent does not support [`CopyFileRangeRequest`], or when `fd_in` is buffered by
+/// the internal proxy, so that the caller can copy with reads and writes instead.
+#[cfg(target_os = "linux")]
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(crate) fn copy_file_range(
+    fd_in: RawFd,
+    off_in: Option<u64>,
+    fd_out: RawFd,
+    off_out: Option<u64>,
+    len: u64,
+) -> Detour<Option<u64>> {
+    let remote_fd_in = get_remote_io_fd(fd_in)?;
+    let remote_fd_out = match get_remote_io_fd(fd_out) {
+        Detour::Bypass(..) => return Detour::Success(None),
+        remote_fd_out => remote_fd_out?,
+    };
+
+    let request = CopyFileRangeRequest {
+        fd_in: remote_fd_in,
+        off_in,
+        fd_out: remote_fd_out,
+        off_out,
+        len,
+    };
+    match common::make_proxy_request_with_response(request)? {
+        Err(ResponseError::NotImplemented) => Detour::Success(None),
+        response => {
+            let CopyFileRangeResponse { copied } = response?;
+            Detour::Success(Some(copied))
+        }
+    }
+}
+
 #[mirrord_layer_macro::instrument(level = "trace")]
-pub(crate) fn access(path: Detour<PathBuf>, mode: c_int) -> Detour<c_int> {
+pub(crate) fn access(path: Detour<PathBuf>, mode: c_int, flags: c_int) -> Detour<c_int> {
     // Even though `access` is never a write operation (even if mode is write), we take the mode
     // into account when deciding whether to ignore, because when a caller is asking whether they
     // have write access to a file and then write to it, we want the test and the actual write to
     // happen with the same file.
     let path = common_path_check(path?, (mode & libc::W_OK) != 0)?;
 
-    let access = AccessFileRequest {
+    // The agent checks with the same `faccessat` flags, against the remote file, so that the
+    // result agrees with what a following remote `open` does.
+    let access = AccessFileRequestV2 {
         pathname: path,
         mode: mode as u8,
+        flags: AccessFlagsInternal {
+            effective_ids: (flags & libc::AT_EACCESS) != 0,
+            nofollow: (flags & libc::AT_SYMLINK_NOFOLLOW) != 0,
+        },
     };
 
     let _ = common::make_proxy_request_with_response(access)??;
@@ -740,17 +1164,9 @@ pub(crate) fn statx_logic(
 
     // SAFETY: all-zero statx struct is valid
     *statx_buf = unsafe { std::mem::zeroed() };
-    statx_buf.stx_mask = libc::STATX_TYPE
-        & libc::STATX_MODE
-        & libc::STATX_NLINK
-        & libc::STATX_UID
-        & libc::STATX_GID
-        & libc::STATX_ATIME
-        & libc::STATX_MTIME
-        & libc::STATX_CTIME
-        & libc::STATX_INO
-        & libc::STATX_SIZE
-        & libc::STATX_BLOCKS;
+    // `MetadataInternal` carries only the basic stats, so the other fields (e.g. `stx_btime`,
+    // `stx_mnt_id`) stay zeroed, and their bits are not set, even if they were requested.
+    statx_buf.stx_mask = libc::STATX_BASIC_STATS;
     statx_buf.stx_attributes_mask = 0;
 
     statx_buf.stx_blksize = response.block_size.try_into().unwrap_or(u32::MAX);
@@ -805,7 +1221,7 @@ pub(crate) fn statfs(path: Detour<PathBuf>) -> Detour<XstatFsResponseV2> {
 #[mirrord_layer_macro::instrument(level = "trace")]
 pub(crate) fn getdents64(fd: RawFd, buffer_size: u64) -> Detour<GetDEnts64Response> {
     // We're only interested in files that are paired with mirrord-agent.
-    let remote_fd = get_remote_fd(fd)?;
+    let remote_fd = get_remote_io_fd(fd)?;
 
     let getdents64 = GetDEnts64Request {
         remote_fd,
@@ -848,6 +1264,88 @@ pub(crate) fn realpath(path: Detour<PathBuf>) -> Detour<PathBuf> {
     Detour::Success(realpath)
 }
 
+/// Checks that `request` refers to a remote directory.
+fn ensure_remote_directory(request: XstatRequest) -> Detour<()> {
+    let XstatResponse { metadata } = common::make_proxy_request_with_response(request)??;
+
+    if (metadata.mode & libc::S_IFMT as u32) != libc::S_IFDIR as u32 {
+        return Detour::Error(HookError::IO(std::io::Error::from_raw_os_error(
+            libc::ENOTDIR,
+        )));
+    }
+
+    Detour::Success(())
+}
+
+/// Changes the working directory of the app into a remote directory, setting [`REMOTE_CWD`].
+///
+/// The local working directory is left as is. Bypasses when `path` should be accessed locally, or
+/// when it doesn't exist in the remote, in which case the caller should clear [`REMOTE_CWD`] if the
+/// local `chdir` succeeds.
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(crate) fn chdir(path: Detour<PathBuf>) -> Detour<()> {
+    let path = absolute_path(common_path_check(path?, false)?);
+
+    match ensure_remote_directory(XstatRequest {
+        path: Some(path.clone()),
+        fd: None,
+        follow_symlink: true,
+    }) {
+        Detour::Error(HookError::ResponseError(ResponseError::RemoteIO(RemoteIOError {
+            kind: ErrorKindInternal::NotFound,
+            ..
+        }))) => {
+            tracing::debug!(
+                path = %path.display(),
+                "Remote directory not found, falling back to a local chdir"
+            );
+            return Detour::Bypass(Bypass::ignored_file(path.to_str().unwrap_or_default()));
+        }
+        other => other?,
+    }
+
+    REMOTE_CWD.lock()?.replace(path);
+
+    Detour::Success(())
+}
+
+/// Same as [`chdir`], for a remote directory that the app has opened.
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(crate) fn fchdir(fd: RawFd) -> Detour<()> {
+    let remote_file = OPEN_FILES
+        .lock()?
+        .get(&fd)
+        .cloned()
+        .ok_or(Bypass::LocalFdNotFound(fd))?;
+
+    // Directories opened relative to another directory (`openat`) only know their relative path.
+    let path = PathBuf::from(&remote_file.path);
+    if path.is_relative() {
+        return Detour::Bypass(Bypass::relative_path(remote_file.path.as_str()));
+    }
+
+    ensure_remote_directory(XstatRequest {
+        path: None,
+        fd: Some(remote_file.fd),
+        follow_symlink: true,
+    })?;
+
+    REMOTE_CWD.lock()?.replace(absolute_path(path));
+
+    Detour::Success(())
+}
+
+/// Returns the [`REMOTE_CWD`] for `getcwd`, bypassing when the app is in a local directory.
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(crate) fn getcwd() -> Detour<CString> {
+    let cwd = REMOTE_CWD
+        .lock()?
+        .clone()
+        .ok_or(Bypass::LocalWorkingDirectory)?;
+
+    Detour::Success(CString::new(cwd.into_os_string().into_vec())?)
+}
+
 /// Renames a file/dir from `old_path` to `new_path`, replacing the original.
 ///
 /// - When `fs.mapping` config is being used, we need to remap both `old_path` and `new_path`, so we
@@ -899,6 +1397,56 @@ pub(crate) fn futimens(fd: RawFd, times: Option<[Timespec; 2]>) -> Detour<()> {
     )??)
 }
 
+/// Changes the timestamps of the remote file at `path`, or of the symlink itself when not
+/// `follow_symlink`.
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn utimens(
+    path: Detour<PathBuf>,
+    times: Option<[Timespec; 2]>,
+    follow_symlink: bool,
+) -> Detour<()> {
+    let path = common_path_check(path?, true)?;
+
+    let request = UtimensRequest {
+        path,
+        times,
+        follow_symlink,
+    };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(request)? {
+        Ok(()) => Detour::Success(()),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+/// Changes the owner of the remote file at `path`, or of the symlink itself when not
+/// `follow_symlink`.
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn chown(
+    path: Detour<PathBuf>,
+    owner: u32,
+    group: u32,
+    follow_symlink: bool,
+) -> Detour<()> {
+    let path = common_path_check(path?, true)?;
+
+    let request = ChownRequest {
+        path,
+        owner,
+        group,
+        follow_symlink,
+    };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(request)? {
+        Ok(()) => Detour::Success(()),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
 pub(crate) fn fchown(fd: RawFd, owner: u32, group: u32) -> Detour<()> {
     let fd = get_remote_fd(fd)?;
     Detour::Success(common::make_proxy_request_with_response(FchownRequest {
@@ -916,12 +1464,247 @@ pub(crate) fn fchmod(fd: RawFd, mode: u32) -> Detour<()> {
     })??)
 }
 
+/// Interval between attempts to take a remote `flock` or `fcntl` lock that is held by someone
+/// else.
+const FLOCK_RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Applies `flock` on a remote file, see [`FlockRequest`].
+///
+/// The agent never blocks on the lock, so blocking calls (without `LOCK_NB`) retry until the
+/// other client releases the lock.
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn flock(fd: RawFd, operation: c_int) -> Detour<()> {
+    let fd = get_remote_fd(fd)?;
+
+    let non_blocking = operation & libc::LOCK_NB != 0;
+    let operation = match operation & !libc::LOCK_NB {
+        libc::LOCK_SH => FlockOperation::Shared,
+        libc::LOCK_EX => FlockOperation::Exclusive,
+        libc::LOCK_UN => FlockOperation::Unlock,
+        _ => return Detour::Bypass(Bypass::InvalidArgValue),
+    };
+
+    loop {
+        match common::make_proxy_request_with_response(FlockRequest { fd, operation })? {
+            Ok(()) => break Detour::Success(()),
+            Err(ResponseError::LockWouldBlock) if !non_blocking => {
+                std::thread::sleep(FLOCK_RETRY_INTERVAL)
+            }
+            // `NotImplemented` error here means that the protocol doesn't support it.
+            Err(ResponseError::NotImplemented) => break Detour::Bypass(Bypass::NotImplemented),
+            Err(fail) => break Detour::Error(fail.into()),
+        }
+    }
+}
+
+/// Applies a record lock command of `fcntl` (`F_SETLK`, `F_SETLKW` or `F_GETLK`) on a remote file,
+/// see [`FcntlLockRequest`].
+///
+/// Like with [`flock`], the agent never blocks on the lock, so `F_SETLKW` retries until the lock
+/// is released.
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn fcntl_lock(local_fd: RawFd, cmd: c_int, lock: *mut libc::flock) -> Detour<()> {
+    let fd = get_remote_fd(local_fd)?;
+
+    let Some(lock) = (unsafe { lock.as_mut() }) else {
+        return Detour::Error(HookError::BadPointer);
+    };
+
+    let (command, blocking) = match cmd {
+        libc::F_SETLK => (FcntlLockCommand::Set, false),
+        libc::F_SETLKW => (FcntlLockCommand::Set, true),
+        libc::F_GETLK => (FcntlLockCommand::Get, false),
+        _ => return Detour::Bypass(Bypass::InvalidArgValue),
+    };
+    let lock_type = match lock.l_type as c_int {
+        libc::F_RDLCK => FcntlLockType::Read,
+        libc::F_WRLCK => FcntlLockType::Write,
+        libc::F_UNLCK => FcntlLockType::Unlock,
+        _ => return Detour::Bypass(Bypass::InvalidArgValue),
+    };
+    let (whence, start) = match lock.l_whence as c_int {
+        libc::SEEK_SET => (FcntlLockWhence::Start, lock.l_start),
+        // The agent's file offset is not ours when the intproxy buffers the file, so we get the
+        // offset with an `lseek` that goes through the intproxy.
+        libc::SEEK_CUR => {
+            let offset = lseek(local_fd, 0, libc::SEEK_CUR)?;
+            (
+                FcntlLockWhence::Start,
+                (offset as i64).saturating_add(lock.l_start),
+            )
+        }
+        libc::SEEK_END => (FcntlLockWhence::End, lock.l_start),
+        _ => return Detour::Bypass(Bypass::InvalidArgValue),
+    };
+
+    let request = FcntlLockRequest {
+        fd,
+        command,
+        lock: FcntlLock {
+            lock_type,
+            whence,
+            start,
+            len: lock.l_len,
+            pid: 0,
+        },
+    };
+
+    let conflicting = loop {
+        match common::make_proxy_request_with_response(request.clone())? {
+            Ok(FcntlLockResponse { conflicting }) => break conflicting,
+            Err(ResponseError::LockWouldBlock) if blocking => {
+                std::thread::sleep(FLOCK_RETRY_INTERVAL)
+            }
+            // `NotImplemented` error here means that the protocol doesn't support it.
+            Err(ResponseError::NotImplemented) => return Detour::Bypass(Bypass::NotImplemented),
+            Err(fail) => return Detour::Error(fail.into()),
+        }
+    };
+
+    if command == FcntlLockCommand::Get {
+        match conflicting {
+            Some(conflicting) => {
+                lock.l_type = match conflicting.lock_type {
+                    FcntlLockType::Read => libc::F_RDLCK,
+                    _ => libc::F_WRLCK,
+                } as _;
+                lock.l_whence = libc::SEEK_SET as _;
+                lock.l_start = conflicting.start;
+                lock.l_len = conflicting.len;
+                lock.l_pid = conflicting.pid;
+            }
+            None => lock.l_type = libc::F_UNLCK as _,
+        }
+    }
+
+    Detour::Success(())
+}
+
+/// Flushes the remote filesystem that holds the remote file `fd`, see [`SyncfsRequest`].
+#[cfg(target_os = "linux")]
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn syncfs(fd: RawFd) -> Detour<()> {
+    let fd = get_remote_fd(fd)?;
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(SyncfsRequest { fd: Some(fd) })? {
+        Ok(()) => Detour::Success(()),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+/// Flushes all of the remote filesystems, see [`SyncfsRequest`].
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn sync() -> Detour<()> {
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(SyncfsRequest { fd: None })? {
+        Ok(()) => Detour::Success(()),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+/// File that an `*xattr` hook operates on.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub(crate) enum XattrFile {
+    /// `getxattr`/`setxattr`/`listxattr` (`follow_symlinks`), or their `l` variants.
+    Path {
+        path: Detour<PathBuf>,
+        follow_symlinks: bool,
+    },
+    /// `fgetxattr`/`fsetxattr`/`flistxattr`.
+    Fd(RawFd),
+}
+
+#[cfg(target_os = "linux")]
+impl XattrFile {
+    /// Checks whether the file should be accessed remotely, and converts it into an
+    /// [`XattrTarget`].
+    fn into_remote(self, write: bool) -> Detour<XattrTarget> {
+        match self {
+            Self::Path {
+                path,
+                follow_symlinks,
+            } => {
+                let path = common_path_check(path?, write)?;
+
+                Detour::Success(if follow_symlinks {
+                    XattrTarget::Path(path)
+                } else {
+                    XattrTarget::LinkPath(path)
+                })
+            }
+            Self::Fd(fd) => Detour::Success(XattrTarget::Fd(get_remote_fd(fd)?)),
+        }
+    }
+}
+
+/// Returns the whole value of the remote extended attribute.
+#[cfg(target_os = "linux")]
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn getxattr(file: XattrFile, name: Vec<u8>) -> Detour<Vec<u8>> {
+    let target = file.into_remote(false)?;
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(GetXattrRequest { target, name })? {
+        Ok(GetXattrResponse { value }) => Detour::Success(value.into_vec()),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn setxattr(file: XattrFile, name: Vec<u8>, value: Vec<u8>, flags: i32) -> Detour<()> {
+    let target = file.into_remote(true)?;
+
+    let request = SetXattrRequest {
+        target,
+        name,
+        value: value.into(),
+        flags,
+    };
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(request)? {
+        Ok(()) => Detour::Success(()),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
+/// Returns the names of all remote extended attributes, in the null-separated format of
+/// `listxattr`.
+#[cfg(target_os = "linux")]
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret)]
+pub(crate) fn listxattr(file: XattrFile) -> Detour<Vec<u8>> {
+    let target = file.into_remote(false)?;
+
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match common::make_proxy_request_with_response(ListXattrRequest { target })? {
+        Ok(ListXattrResponse { names }) => Detour::Success(
+            names
+                .into_iter()
+                .flat_map(|mut name| {
+                    name.push(0);
+                    name
+                })
+                .collect(),
+        ),
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::path::PathBuf;
 
     use mirrord_config::{feature::fs::FsConfig, util::VecOrSingle};
     use mirrord_layer_lib::detour::Detour;
+    use mirrord_protocol::ErrorKindInternal;
     use rstest::*;
 
     use super::{absolute_path, *};
@@ -941,6 +1724,93 @@ mod test {
         )
     }
 
+    /// Runs [`write_in_chunks`] at offset 100 with a fake remote file, that fails writes starting
+    /// at or past `fail_from`.
+    ///
+    /// Returns the result, and the sizes of the chunks that were attempted.
+    fn chunked_write(
+        buffer_len: usize,
+        max_chunk: u64,
+        fail_from: Option<u64>,
+    ) -> (Detour<WriteFileResponse>, Vec<usize>) {
+        let buffer = vec![7_u8; buffer_len];
+        let mut chunks = Vec::new();
+        let mut expected_offset = 100;
+
+        let result = write_in_chunks(&buffer, 100, max_chunk, |chunk, start_from| {
+            assert_eq!(start_from, expected_offset);
+            chunks.push(chunk.len());
+
+            if fail_from.is_some_and(|fail_from| start_from >= fail_from) {
+                return Detour::Error(HookError::ResponseError(ResponseError::NotFound(0)));
+            }
+
+            expected_offset += chunk.len() as u64;
+            Detour::Success(WriteFileResponse {
+                written_amount: chunk.len() as u64,
+            })
+        });
+
+        (result, chunks)
+    }
+
+    #[rstest]
+    #[case::exact_multiple(12, 4, vec![4, 4, 4])]
+    #[case::remainder(10, 4, vec![4, 4, 2])]
+    #[case::smaller_than_chunk(3, 4, vec![3])]
+    #[case::no_limit(10, 0, vec![10])]
+    fn write_chunks(
+        #[case] buffer_len: usize,
+        #[case] max_chunk: u64,
+        #[case] expected_chunks: Vec<usize>,
+    ) {
+        let (result, chunks) = chunked_write(buffer_len, max_chunk, None);
+
+        assert_eq!(chunks, expected_chunks);
+        assert!(matches!(
+            result,
+            Detour::Success(WriteFileResponse { written_amount }) if written_amount == buffer_len as u64
+        ));
+    }
+
+    #[test]
+    fn write_chunks_error_midway() {
+        let (result, chunks) = chunked_write(10, 4, Some(108));
+
+        assert_eq!(chunks, [4, 4, 2]);
+        assert!(matches!(
+            result,
+            Detour::Success(WriteFileResponse { written_amount: 8 })
+        ));
+    }
+
+    #[test]
+    fn write_chunks_error_first() {
+        let (result, chunks) = chunked_write(10, 4, Some(100));
+
+        assert_eq!(chunks, [4]);
+        assert!(matches!(result, Detour::Error(..)));
+    }
+
+    #[test]
+    fn write_chunks_short_write() {
+        let buffer = vec![7_u8; 10];
+        let mut chunks = Vec::new();
+
+        let result = write_in_chunks(&buffer, 0, 4, |chunk, _| {
+            chunks.push(chunk.len());
+            Detour::Success(WriteFileResponse {
+                written_amount: if chunks.len() == 2 { 1 } else { 4 },
+            })
+        });
+
+        assert_eq!(chunks, [4, 4]);
+        assert!(matches!(
+            result,
+            Detour::Success(WriteFileResponse { written_amount: 5 })
+        ));
+    }
+
     /// Helper type for testing [`FileFilter`] results.
     #[derive(PartialEq, Eq, Debug)]
     enum DetourKind {
@@ -1157,7 +2027,7 @@ mod test {
         #[case] write: bool,
         #[case] expected: DetourKind,
     ) {
-        use mirrord_config::feature::fs::READONLY_FILE_BUFFER_DEFAULT;
+        use mirrord_config::feature::fs::{MAX_WRITE_CHUNK_DEFAULT, READONLY_FILE_BUFFER_DEFAULT};
 
         let read_write = Some(VecOrSingle::Multiple(vec![
             r"/pain/read_write.*\.a".to_string(),
@@ -1175,6 +2045,10 @@ mod test {
             mode,
             mapping: None,
             readonly_file_buffer: READONLY_FILE_BUFFER_DEFAULT,
+            readonly_file_buffer_overrides: None,
+            max_write_chunk: MAX_WRITE_CHUNK_DEFAULT,
+            fallback_local: None,
+            fallback_local_on: None,
         };
 
         let file_filter = FileFilter::new(fs_config);
@@ -1223,6 +2097,51 @@ mod test {
         assert_eq!(DetourKind::from(&res), expected);
     }
 
+    /// Remote open errors listed in `fallback_local_on` reopen matching paths locally, other
+    /// errors and paths still fail.
+    #[rstest]
+    #[case(
+        ErrorKindInternal::PermissionDenied,
+        "/etc/app/secret.conf",
+        DetourKind::Bypass
+    )]
+    #[case(
+        ErrorKindInternal::NotFound,
+        "/etc/app/secret.conf",
+        DetourKind::Bypass
+    )]
+    #[case(
+        ErrorKindInternal::IsADirectory,
+        "/etc/app/secret.conf",
+        DetourKind::Error
+    )]
+    #[case(
+        ErrorKindInternal::PermissionDenied,
+        "/etc/other.conf",
+        DetourKind::Error
+    )]
+    fn fallback_local_on_open_error(
+        #[case] kind: ErrorKindInternal,
+        #[case] path: &str,
+        #[case] expected: DetourKind,
+    ) {
+        let fs_config = FsConfig {
+            mode: FsModeConfig::Read,
+            fallback_local: Some(VecOrSingle::Single("^/etc/app/.+".to_string())),
+            fallback_local_on: Some(vec!["PermissionDenied".to_string(), "NotFound".to_string()]),
+            ..Default::default()
+        };
+        let file_filter = FileFilter::new(fs_config);
+
+        let error = HookError::ResponseError(ResponseError::RemoteIO(RemoteIOError {
+            raw_os_error: Some(libc::EACCES),
+            kind,
+        }));
+        let res = remote_open_failed(&file_filter, Path::new(path), error);
+
+        assert_eq!(DetourKind::from(&res), expected);
+    }
+
     /// Sanity test for empty [`RegexSet`] behaviour.
     #[test]
     fn empty_regex_set() {
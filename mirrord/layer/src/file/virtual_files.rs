@@ -0,0 +1,111 @@
+//! Virtual files, whose contents are generated by the layer instead of being read from the remote
+//! (or local) file system.
+//!
+//! Opening one of these paths for reading gives the app a local file filled with the generated
+//! contents, in every [`FsModeConfig`](mirrord_config::feature::fs::FsModeConfig) other than
+//! `local`, which disables the file hooks. Opening them for writing is always bypassed, so writes
+//! stay local.
+//!
+//! To add another synthetic file, add a [`VirtualFile`] to [`VIRTUAL_FILES`].
+
+use std::{ffi::CString, os::unix::io::RawFd, path::Path};
+
+use libc::{O_CREAT, O_EXCL, O_RDONLY, O_RDWR};
+use mirrord_layer_lib::{
+    detour::{Bypass, Detour},
+    error::HookError,
+    setup::LayerSetup,
+};
+use mirrord_protocol::file::OpenOptionsInternal;
+use nix::errno::Errno;
+use rand::distr::{Alphanumeric, SampleString};
+
+use super::hooks::FN_OPEN;
+use crate::socket::ops::gethostname;
+
+/// A file generated by the layer, see the [module docs](self).
+struct VirtualFile {
+    /// Absolute paths under which the file is served.
+    paths: &'static [&'static str],
+
+    /// Whether the file should be served with the given setup, on top of the fs feature being
+    /// active.
+    enabled: fn(&LayerSetup) -> bool,
+
+    /// Generates the contents of the file.
+    contents: fn() -> Detour<Vec<u8>>,
+}
+
+/// All the [`VirtualFile`]s known to the layer.
+const VIRTUAL_FILES: &[VirtualFile] = &[
+    // The remote hostname, the same value that the `gethostname` hook returns.
+    VirtualFile {
+        paths: &["/etc/hostname", "/proc/sys/kernel/hostname"],
+        enabled: |setup| !setup.local_hostname(),
+        contents: || {
+            let mut hostname = gethostname()?.as_bytes().to_vec();
+            hostname.push(b'\n');
+            Detour::Success(hostname)
+        },
+    },
+];
+
+/// Opens the [`VirtualFile`] at `path`, if there is one.
+///
+/// Returns [`None`] when `path` is not a virtual file, in which case it should be opened as usual.
+/// The returned fd is a plain local file, so it's not tracked in
+/// [`OPEN_FILES`](super::OPEN_FILES).
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(crate) fn open(path: &Path, open_options: &OpenOptionsInternal) -> Detour<Option<RawFd>> {
+    let setup = crate::setup();
+    let Some(file) = VIRTUAL_FILES
+        .iter()
+        .find(|file| {
+            file.paths
+                .iter()
+                .any(|virtual_path| path == Path::new(virtual_path))
+        })
+        .filter(|file| setup.fs_config().is_active() && (file.enabled)(setup))
+    else {
+        return Detour::Success(None);
+    };
+
+    if open_options.is_write() {
+        return Detour::Bypass(Bypass::ignored_file(path.to_str().unwrap_or_default()));
+    }
+
+    let contents = (file.contents)()?;
+    create_local_file(&contents).map(Some)
+}
+
+/// Creates an unlinked local file with the given `contents`, and opens it for reading.
+fn create_local_file(contents: &[u8]) -> Detour<RawFd> {
+    let random_string = Alphanumeric.sample_string(&mut rand::rng(), 16);
+    let file_path = std::env::temp_dir().join(format!("mirrord-virtual-{random_string}"));
+    let file_c_string = CString::new(file_path.to_string_lossy().to_string())?;
+    let file_path_ptr = file_c_string.as_ptr();
+
+    let write_fd: RawFd = unsafe { FN_OPEN(file_path_ptr, O_RDWR | O_CREAT | O_EXCL, 0o600) };
+    if write_fd == -1 {
+        return Detour::Error(HookError::VirtualFileCreation(Errno::last_raw()));
+    }
+
+    let written = unsafe { libc::write(write_fd, contents.as_ptr().cast(), contents.len()) };
+    let result = if written == contents.len() as isize {
+        let read_fd: RawFd = unsafe { FN_OPEN(file_path_ptr, O_RDONLY) };
+        if read_fd == -1 {
+            Detour::Error(HookError::VirtualFileCreation(Errno::last_raw()))
+        } else {
+            Detour::Success(read_fd)
+        }
+    } else {
+        Detour::Error(HookError::VirtualFileCreation(Errno::last_raw()))
+    };
+
+    unsafe {
+        libc::close(write_fd);
+        libc::unlink(file_path_ptr);
+    }
+
+    result
+}
@@ -73,7 +73,7 @@ extern crate core;
 use std::ffi::c_void;
 use std::{
     cmp::Ordering,
-    collections::{HashMap, HashSet},
+    collections::HashMap,
     fs::File,
     io::Read,
     net::SocketAddr,
@@ -96,22 +96,24 @@ use load::ExecuteArgs;
 #[cfg(doc)]
 use mirrord_config::feature::fs::FsConfig;
 use mirrord_config::{
-    LayerConfig, MIRRORD_LAYER_INTPROXY_ADDR, feature::env::mapper::EnvVarsRemapper,
+    LayerConfig, MIRRORD_LAYER_INTPROXY_ADDR,
+    feature::env::{filter::EnvVarsFilter, mapper::EnvVarsRemapper},
 };
-use mirrord_intproxy_protocol::NewSessionRequest;
+use mirrord_intproxy_protocol::{BuildVersion, NewSessionRequest};
 #[cfg(doc)]
 use mirrord_layer_lib::setup::SETUP;
 use mirrord_layer_lib::{
     detour::DetourGuard,
     error::{LayerError, Result},
     logging::init_tracing,
-    proxy_connection::{PROXY_CONNECTION, ProxyConnection},
+    proxy_connection::{
+        PROXY_CONNECTION, ProxyConnection, ProxyError, layer_library_path, reject_session,
+    },
     setup::{LayerSetup, init_layer_setup, setup},
     socket::dns::reverse_dns::REMOTE_DNS_REVERSE_MAPPING,
     trace_only::is_trace_only_mode,
 };
 use mirrord_layer_macro::{hook_fn, hook_guard_fn};
-use mirrord_protocol::{EnvVars, GetEnvVarsRequest};
 use nix::errno::Errno;
 use socket::SOCKETS;
 
@@ -131,7 +133,6 @@ mod integration_tests_deps {
     use mirrord_intproxy as _;
     use mirrord_layer_tests as _;
     use mirrord_test_utils as _;
-    use serde_json as _;
     use tempfile as _;
     use test_cdylib as _;
     use tokio as _;
@@ -139,6 +140,7 @@ mod integration_tests_deps {
 }
 
 mod common;
+mod debugger;
 mod exec_hooks;
 #[cfg(target_os = "macos")]
 mod exec_utils;
@@ -147,6 +149,7 @@ mod hooks;
 mod load;
 mod macros;
 mod socket;
+mod time;
 #[cfg(target_os = "macos")]
 mod tls;
 
@@ -269,7 +272,7 @@ fn load_only_layer_start(config: &LayerConfig) {
         .parse::<SocketAddr>()
         .expect("malformed internal proxy address");
 
-    let new_connection = ProxyConnection::new(
+    let new_connection = match ProxyConnection::new(
         address,
         NewSessionRequest {
             process_info: EXECUTABLE_ARGS
@@ -277,11 +280,19 @@ fn load_only_layer_start(config: &LayerConfig) {
                 .expect("EXECUTABLE_ARGS MUST BE SET")
                 .to_process_info(config),
             parent_layer: None,
+            layer_version: BuildVersion::current(),
+            layer_path: layer_library_path(),
         },
         *PROXY_CONNECTION_TIMEOUT
             .get_or_init(|| Duration::from_secs(config.internal_proxy.socket_timeout)),
-    )
-    .expect("failed to initialize proxy connection");
+    ) {
+        Ok(connection) => connection,
+        Err(ProxyError::SessionRejected(error)) => {
+            reject_session(error);
+            return;
+        }
+        Err(error) => panic!("failed to initialize proxy connection: {error}"),
+    };
 
     unsafe {
         // SAFETY
@@ -334,7 +345,9 @@ fn mirrord_layer_entry_point() {
 /// 4. Replaces the [`libc`] calls with our hooks with [`enable_hooks`];
 ///
 /// 5. Fetches remote environment from the agent (if enabled with
-///    [`EnvFileConfig::load_from_process`](mirrord_config::feature::env::EnvFileConfig::load_from_process)).
+///    [`EnvFileConfig::load_from_process`](mirrord_config::feature::env::EnvFileConfig::load_from_process));
+///
+/// 6. Waits for a debugger (if enabled with [`LayerConfig::wait_for_debugger`]).
 fn layer_start(config: LayerConfig) {
     init_tracing();
 
@@ -376,15 +389,24 @@ fn layer_start(config: LayerConfig) {
     #[allow(static_mut_refs)]
     unsafe {
         let address = setup().proxy_address();
-        let new_connection = ProxyConnection::new(
+        let new_connection = match ProxyConnection::new(
             address,
             NewSessionRequest {
                 process_info,
                 parent_layer: None,
+                layer_version: BuildVersion::current(),
+                layer_path: layer_library_path(),
             },
             proxy_connection_timeout,
-        )
-        .unwrap_or_else(|_| panic!("failed to initialize proxy connection at {address}"));
+        ) {
+            Ok(connection) => connection,
+            Err(ProxyError::SessionRejected(error)) => {
+                // Hooks are already enabled, and will fail with the rejection error.
+                reject_session(error);
+                return;
+            }
+            Err(..) => panic!("failed to initialize proxy connection at {address}"),
+        };
         PROXY_CONNECTION
             .set(new_connection)
             .expect("setting PROXY_CONNECTION singleton")
@@ -416,6 +438,12 @@ fn layer_start(config: LayerConfig) {
         });
     }
 
+    if setup().experimental().remote_time_offset {
+        time::init_offset();
+    }
+
+    debugger::wait_for_debugger(&setup().layer_config().wait_for_debugger);
+
     #[cfg(target_os = "macos")]
     if setup().experimental().applev.as_ref().is_some() {
         unsafe {
@@ -438,35 +466,15 @@ const REMOTE_ENV_FETCHED: &str = "MIRRORD_REMOTE_ENV_FETCHED";
 /// Fetches remote environment from the agent.
 /// Uses [`SETUP`] and [`PROXY_CONNECTION`] globals.
 fn fetch_env_vars() -> HashMap<String, String> {
-    let (env_vars_exclude, env_vars_include) = match (
-        setup()
-            .env_config()
-            .exclude
-            .clone()
-            .map(|exclude| exclude.join(";")),
-        setup()
-            .env_config()
-            .include
-            .clone()
-            .map(|include| include.join(";")),
-    ) {
-        (Some(..), Some(..)) => {
-            panic!("invalid env config");
-        }
-        (Some(exclude), None) => (HashSet::from(EnvVars(exclude)), HashSet::new()),
-        (None, Some(include)) => (HashSet::new(), HashSet::from(EnvVars(include))),
-        (None, None) => (HashSet::new(), HashSet::from(EnvVars("*".to_owned()))),
-    };
+    let env_filter = EnvVarsFilter::new(setup().env_config()).expect("invalid env config");
+    let request = env_filter.remote_request();
 
-    let mut env_vars = if !env_vars_exclude.is_empty() || !env_vars_include.is_empty() {
-        {
-            make_proxy_request_with_response(GetEnvVarsRequest {
-                env_vars_filter: env_vars_exclude,
-                env_vars_select: env_vars_include,
-            })
+    let mut env_vars = if !request.env_vars_filter.is_empty() || !request.env_vars_select.is_empty()
+    {
+        let remote_env = make_proxy_request_with_response(request)
             .expect("failed to make request to proxy")
-            .expect("failed to fetch remote env")
-        }
+            .expect("failed to fetch remote env");
+        env_filter.filtered(remote_env)
     } else {
         Default::default()
     };
@@ -613,6 +621,10 @@ fn enable_hooks(state: &LayerSetup) {
         unsafe { file::hooks::enable_file_hooks(&mut hook_manager, state) };
     }
 
+    if state.experimental().remote_time_offset {
+        unsafe { time::enable_time_hooks(&mut hook_manager) };
+    }
+
     #[cfg(all(
         any(target_arch = "x86_64", target_arch = "aarch64"),
         target_os = "linux"
@@ -742,6 +754,8 @@ pub(crate) unsafe extern "C" fn fork_detour() -> pid_t {
                             .get()
                             .expect("should always be set in layer constructor")
                             .to_process_info(setup().layer_config()),
+                        layer_version: BuildVersion::current(),
+                        layer_path: layer_library_path(),
                     },
                     PROXY_CONNECTION_TIMEOUT
                         .get()
@@ -5,6 +5,7 @@ use std::{
     sync::LazyLock,
 };
 
+use fancy_regex::Regex;
 use mirrord_config::LayerConfig;
 use mirrord_intproxy_protocol::ProcessInfo;
 use mirrord_layer_lib::error::LayerError;
@@ -96,6 +97,16 @@ impl ExecuteArgs {
             || skip_build_tools.contains(self.invoked_as.as_str())
     }
 
+    /// The arguments (starting with the executable, as it was invoked) joined with spaces, as
+    /// matched by [`LayerConfig::skip_processes_regex`].
+    fn command_line(&self) -> String {
+        self.args
+            .iter()
+            .map(|arg| arg.to_string_lossy())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
     /// Checks if mirrord-layer should load with this process.
     ///
     /// ## Details
@@ -105,6 +116,7 @@ impl ExecuteArgs {
     fn should_load<S: AsRef<str>>(
         &self,
         skip_processes: &[S],
+        skip_processes_regex: &[Regex],
         skip_build_tools: bool,
         skip_extra_build_tools: Option<&[String]>,
     ) -> bool {
@@ -127,17 +139,33 @@ impl ExecuteArgs {
             return false;
         }
 
-        !skip_processes
+        if skip_processes
             .iter()
             .any(|name| name.as_ref() == self.exec_name || name.as_ref() == self.invoked_as)
+        {
+            return false;
+        }
+
+        if skip_processes_regex.is_empty() {
+            return true;
+        }
+
+        let command_line = self.command_line();
+        !skip_processes_regex
+            .iter()
+            .any(|regex| regex.is_match(&command_line).unwrap_or(false))
     }
 
     /// Determine the [`LoadType`] for this process.
     pub fn load_type(&self, config: &LayerConfig) -> LoadType {
         let skip_processes = config.skip_processes.as_deref().unwrap_or(&[]);
+        let skip_processes_regex = config.skip_processes_regexes().expect(
+            "Invalid skip_processes_regex, this should've been caught when verifying config!",
+        );
 
         if self.should_load(
             skip_processes,
+            &skip_processes_regex,
             config.skip_build_tools,
             config.skip_extra_build_tools.as_deref(),
         ) {
@@ -239,6 +267,7 @@ mod tests {
 
         assert!(executable_name.should_load(
             skip_processes,
+            &[],
             skip_build_tools,
             skip_extra_build_tools.as_deref()
         ));
@@ -267,8 +296,37 @@ mod tests {
 
         assert!(!executable_name.should_load(
             skip_processes,
+            &[],
             skip_build_tools,
             skip_extra_build_tools.as_deref()
         ));
     }
+
+    /// [`LayerConfig::skip_processes_regex`] tells apart invocations of the same executable, and
+    /// skips the process when either it or `skip_processes` matches.
+    #[rstest]
+    #[case(&["node", "scripts/codegen.js"], &[], false)]
+    #[case(&["node", "scripts/codegen.js", "--out", "gen"], &["bash"], false)]
+    #[case(&["node", "server.js"], &[], true)]
+    #[case(&["node", "server.js"], &["node"], false)]
+    #[case(&["python", "scripts/codegen.js"], &[], true)]
+    #[case(&["python", "server.py"], &["python"], false)]
+    fn should_load_command_line(
+        #[case] args: &[&str],
+        #[case] skip_processes: &[&str],
+        #[case] expected: bool,
+    ) {
+        let executable_name = ExecuteArgs {
+            exec_name: args[0].to_string(),
+            invoked_as: args[0].to_string(),
+            args: args.iter().map(OsString::from).collect(),
+        };
+
+        let skip_processes_regex = [Regex::new(r"^node scripts/codegen\.js").unwrap()];
+
+        assert_eq!(
+            executable_name.should_load(skip_processes, &skip_processes_regex, false, None),
+            expected
+        );
+    }
 }
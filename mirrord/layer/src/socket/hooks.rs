@@ -1,5 +1,4 @@
-use alloc::ffi::CString;
-use core::{cmp, ffi::CStr};
+use core::ffi::CStr;
 use std::{collections::HashSet, os::unix::io::RawFd, sync::LazyLock};
 
 use libc::{c_char, c_int, c_void, hostent, size_t, sockaddr, socklen_t, ssize_t};
@@ -113,6 +112,22 @@ pub(crate) unsafe extern "C" fn getsockname_detour(
     }
 }
 
+/// Hook for `libc::getsockopt`, see [`getsockopt`] for the options we handle.
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(super) unsafe extern "C" fn getsockopt_detour(
+    sockfd: RawFd,
+    level: c_int,
+    optname: c_int,
+    optval: *mut c_void,
+    optlen: *mut socklen_t,
+) -> c_int {
+    unsafe {
+        getsockopt(sockfd, level, optname, optval, optlen)
+            .unwrap_or_bypass_with(|_| FN_GETSOCKOPT(sockfd, level, optname, optval, optlen))
+    }
+}
+
 /// Hook for `libc::gethostname`.
 ///
 /// Reads remote hostname bytes into `raw_name`, will rais EINVAL errno and return -1 if hostname
@@ -125,15 +140,19 @@ pub(crate) unsafe extern "C" fn gethostname_detour(
     unsafe {
         gethostname()
             .map(|host| {
-                let host_len = host.as_bytes_with_nul().len();
-                raw_name.copy_from_nonoverlapping(host.as_ptr(), cmp::min(name_length, host_len));
-
-                if host_len > name_length {
-                    Errno::EINVAL.set();
-
-                    -1
+                let buffer: &mut [u8] = if raw_name.is_null() {
+                    &mut []
                 } else {
-                    0
+                    std::slice::from_raw_parts_mut(raw_name.cast(), name_length)
+                };
+                let truncation = crate::setup().experimental().hostname_truncation;
+
+                match copy_hostname(host, buffer, truncation) {
+                    Ok(()) => 0,
+                    Err(errno) => {
+                        errno.set();
+                        -1
+                    }
                 }
             })
             .unwrap_or_bypass_with(|_| FN_GETHOSTNAME(raw_name, name_length))
@@ -154,6 +173,144 @@ unsafe extern "C" fn gethostbyname_detour(raw_name: *const c_char) -> *mut hoste
     }
 }
 
+/// `netdb.h` `h_errno` value for an authoritative "no such host" answer.
+#[cfg(target_os = "linux")]
+const HOST_NOT_FOUND: c_int = 1;
+
+/// `netdb.h` `h_errno` value for internal errors, see `errno` for the actual error.
+#[cfg(target_os = "linux")]
+const NETDB_INTERNAL: c_int = -1;
+
+/// Common part of [`gethostbyname_r_detour`] and [`gethostbyname2_r_detour`].
+///
+/// Resolves `raw_name` with [`gethostbyname_r`], and copies the result into the caller-provided
+/// `ret` and `buffer`, following the glibc contract:
+///
+/// - success: returns `0`, and `*result` points to `ret`;
+/// - host not found: returns `0`, `*result` is null, and `*h_errnop` is `HOST_NOT_FOUND`;
+/// - `buffer` is too small (or null): returns `ERANGE`, `*result` is null, and `*h_errnop` is
+///   `NETDB_INTERNAL`, so the caller can retry with a bigger buffer.
+#[cfg(target_os = "linux")]
+unsafe fn gethostbyname_r_common(
+    raw_name: *const c_char,
+    family: c_int,
+    ret: *mut hostent,
+    buffer: *mut c_char,
+    buffer_length: size_t,
+    result: *mut *mut hostent,
+    h_errnop: *mut c_int,
+) -> Detour<c_int> {
+    unsafe {
+        if ret.is_null() || result.is_null() {
+            return Detour::Bypass(mirrord_layer_lib::detour::Bypass::EmptyOption);
+        }
+
+        let rawish_name = (!raw_name.is_null()).then(|| CStr::from_ptr(raw_name));
+        let host = gethostbyname_r(rawish_name, family)?;
+
+        result.write(std::ptr::null_mut());
+        let (h_errno, return_value) = match host {
+            Some(host) => match host.copy_into(ret, buffer, buffer_length) {
+                0 => {
+                    result.write(ret);
+                    return Detour::Success(0);
+                }
+                error => {
+                    Errno::set_raw(error);
+                    (NETDB_INTERNAL, error)
+                }
+            },
+            None => (HOST_NOT_FOUND, 0),
+        };
+
+        if let Some(h_errnop) = h_errnop.as_mut() {
+            *h_errnop = h_errno;
+        }
+
+        Detour::Success(return_value)
+    }
+}
+
+/// Hook for glibc's reentrant `gethostbyname_r`, see [`gethostbyname_r_common`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn gethostbyname_r_detour(
+    raw_name: *const c_char,
+    ret: *mut hostent,
+    buffer: *mut c_char,
+    buffer_length: size_t,
+    result: *mut *mut hostent,
+    h_errnop: *mut c_int,
+) -> c_int {
+    unsafe {
+        gethostbyname_r_common(
+            raw_name,
+            libc::AF_INET,
+            ret,
+            buffer,
+            buffer_length,
+            result,
+            h_errnop,
+        )
+        .unwrap_or_bypass_with(|_| {
+            FN_GETHOSTBYNAME_R(raw_name, ret, buffer, buffer_length, result, h_errnop)
+        })
+    }
+}
+
+/// Hook for glibc's reentrant `gethostbyname2_r`, which also takes the address `family`
+/// (`AF_INET` or `AF_INET6`), see [`gethostbyname_r_common`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+unsafe extern "C" fn gethostbyname2_r_detour(
+    raw_name: *const c_char,
+    family: c_int,
+    ret: *mut hostent,
+    buffer: *mut c_char,
+    buffer_length: size_t,
+    result: *mut *mut hostent,
+    h_errnop: *mut c_int,
+) -> c_int {
+    unsafe {
+        gethostbyname_r_common(
+            raw_name,
+            family,
+            ret,
+            buffer,
+            buffer_length,
+            result,
+            h_errnop,
+        )
+        .unwrap_or_bypass_with(|_| {
+            FN_GETHOSTBYNAME2_R(
+                raw_name,
+                family,
+                ret,
+                buffer,
+                buffer_length,
+                result,
+                h_errnop,
+            )
+        })
+    }
+}
+
+/// Hook for `libc::gethostbyaddr`.
+///
+/// Resolves the name of `raw_address` with a reverse DNS lookup on the remote, and returns it in
+/// the same `static` [`libc::hostent`] as [`gethostbyname_detour`].
+#[hook_guard_fn]
+unsafe extern "C" fn gethostbyaddr_detour(
+    raw_address: *const c_void,
+    address_length: socklen_t,
+    family: c_int,
+) -> *mut hostent {
+    unsafe {
+        gethostbyaddr(raw_address, address_length, family)
+            .unwrap_or_bypass_with(|_| FN_GETHOSTBYADDR(raw_address, address_length, family))
+    }
+}
+
 #[hook_guard_fn]
 pub(crate) unsafe extern "C" fn accept_detour(
     sockfd: c_int,
@@ -225,11 +382,34 @@ pub(super) unsafe extern "C" fn accept_nocancel_detour(
     }
 }
 
+/// Handles the record lock commands (`F_SETLK`, `F_SETLKW`, `F_GETLK`) of [`fcntl_detour`] on
+/// remote files, see [`crate::file::ops::fcntl_lock`].
+///
+/// Returns [`None`] when the call should go to the original `fcntl`.
+fn fcntl_lock(fd: c_int, cmd: c_int, arg: usize) -> Option<c_int> {
+    if !matches!(cmd, libc::F_SETLK | libc::F_SETLKW | libc::F_GETLK) {
+        return None;
+    }
+
+    let _guard = DetourGuard::new()?;
+    match crate::file::ops::fcntl_lock(fd, cmd, arg as *mut libc::flock) {
+        Detour::Success(()) => Some(0),
+        Detour::Bypass(..) => None,
+        Detour::Error(error) => Some(error.into()),
+    }
+}
+
 /// <https://github.com/metalbear-co/mirrord/issues/184>
+///
+/// Record locks on remote files are taken on the agent, see [`fcntl_lock`].
 #[hook_fn]
 pub(crate) unsafe extern "C" fn fcntl_detour(fd: c_int, cmd: c_int, mut arg: ...) -> c_int {
     unsafe {
         let arg = arg.arg::<usize>();
+        if let Some(result) = fcntl_lock(fd, cmd, arg) {
+            return result;
+        }
+
         let fcntl_result = FN_FCNTL(fd, cmd, arg);
         let guard = DetourGuard::new();
         if guard.is_none() {
@@ -255,6 +435,10 @@ pub(crate) unsafe extern "C" fn fcntl_nocancel_detour(
 ) -> c_int {
     unsafe {
         let arg = arg.arg::<usize>();
+        if let Some(result) = fcntl_lock(fd, cmd, arg) {
+            return result;
+        }
+
         let fcntl_result = FN_FCNTL_NOCANCEL(fd, cmd, arg);
         let guard = DetourGuard::new();
         if guard.is_none() {
@@ -344,10 +528,12 @@ unsafe extern "C" fn getaddrinfo_detour(
         getaddrinfo(rawish_node, rawish_service, rawish_hints)
             .map(|c_addr_info_ptr| {
                 out_addr_info.copy_from_nonoverlapping(&c_addr_info_ptr, 1);
-                MANAGED_ADDRINFO
-                    .lock()
-                    .expect("MANAGED_ADDRINFO lock failed")
-                    .insert(c_addr_info_ptr as usize);
+                register_addrinfo(
+                    &mut MANAGED_ADDRINFO
+                        .lock()
+                        .expect("MANAGED_ADDRINFO lock failed"),
+                    c_addr_info_ptr,
+                );
                 0
             })
             .unwrap_or_bypass_with(|_| {
@@ -356,50 +542,100 @@ unsafe extern "C" fn getaddrinfo_detour(
     }
 }
 
+/// Resolves the `host` part of `getnameinfo` through the agent, see [`getnameinfo`].
+///
+/// The `serv` part is always resolved by the original function, as service names come from the
+/// local services database, even when the host is resolved remotely.
+///
+/// # Warning:
+/// - `host` and/or `serv` might be null!
+#[hook_guard_fn]
+unsafe extern "C" fn getnameinfo_detour(
+    raw_address: *const sockaddr,
+    address_length: socklen_t,
+    host: *mut c_char,
+    host_length: socklen_t,
+    serv: *mut c_char,
+    serv_length: socklen_t,
+    flags: c_int,
+) -> c_int {
+    unsafe {
+        let rawish_host_length = if host.is_null() { 0 } else { host_length };
+
+        getnameinfo(raw_address, address_length, rawish_host_length, flags)
+            .map(|hostname| match hostname {
+                Some(hostname) => {
+                    if !serv.is_null() && serv_length > 0 {
+                        let serv_result = FN_GETNAMEINFO(
+                            raw_address,
+                            address_length,
+                            std::ptr::null_mut(),
+                            0,
+                            serv,
+                            serv_length,
+                            flags,
+                        );
+                        if serv_result != 0 {
+                            return serv_result;
+                        }
+                    }
+
+                    let hostname = hostname.as_bytes_with_nul();
+                    if hostname.len() > host_length as usize {
+                        return libc::EAI_OVERFLOW;
+                    }
+
+                    host.cast::<u8>()
+                        .copy_from_nonoverlapping(hostname.as_ptr(), hostname.len());
+                    0
+                }
+                None if flags & libc::NI_NAMEREQD != 0 => libc::EAI_NONAME,
+                None => FN_GETNAMEINFO(
+                    raw_address,
+                    address_length,
+                    host,
+                    host_length,
+                    serv,
+                    serv_length,
+                    flags | libc::NI_NUMERICHOST,
+                ),
+            })
+            .unwrap_or_bypass_with(|_| {
+                FN_GETNAMEINFO(
+                    raw_address,
+                    address_length,
+                    host,
+                    host_length,
+                    serv,
+                    serv_length,
+                    flags,
+                )
+            })
+    }
+}
+
 /// Deallocates a `*mut libc::addrinfo` that was previously allocated with `Box::new` in
 /// `getaddrinfo_detour` and converted into a raw pointer by `Box::into_raw`. Same thing must also
 /// be done for `addrinfo.ai_addr`.
 ///
 /// Also follows the `addr_info.ai_next` pointer, deallocating the next pointers in the linked list.
+/// Every node is tracked separately in [`MANAGED_ADDRINFO`], so trimmed lists and lists mixing our
+/// nodes with ones allocated by libc are handled, see [`free_addrinfo`].
 ///
 /// # Protocol
 ///
 /// No need to send any sort of `free` message to `mirrord-agent`, as the `addrinfo` there is not
 /// kept around.
-///
-/// # Warning
-///
-/// The `addrinfo` pointer has to be allocated respecting the `Box`'s
-/// [memory layout](https://doc.rust-lang.org/std/boxed/index.html#memory-layout).
-///
-/// This needs to support trimmed linked lists, but at the moment if someone does that
-/// it will call the original freeaddrinfo which might cause UB or crash.
-/// if crashes occur on getaddrinfo - check this case.
-/// This can be solved probably by adding each pointer in the linked list to our HashSet.
 #[hook_guard_fn]
 unsafe extern "C" fn freeaddrinfo_detour(addrinfo: *mut libc::addrinfo) {
     unsafe {
         let mut managed_addr_info = MANAGED_ADDRINFO
             .lock()
             .expect("MANAGED_ADDRINFO lock failed");
-        if managed_addr_info.remove(&(addrinfo as usize)) {
-            // Iterate over `addrinfo` linked list dropping it.
-            let mut current = addrinfo;
-            while !current.is_null() {
-                let current_box = Box::from_raw(current);
-                let ai_addr = Box::from_raw(current_box.ai_addr);
-                let ai_canonname = CString::from_raw(current_box.ai_canonname);
-
-                current = (*current).ai_next;
-
-                drop(ai_addr);
-                drop(ai_canonname);
-                drop(current_box);
-                managed_addr_info.remove(&(current as usize));
-            }
-        } else {
-            FN_FREEADDRINFO(addrinfo);
-        }
+
+        free_addrinfo(&mut managed_addr_info, addrinfo, |foreign| {
+            FN_FREEADDRINFO(foreign)
+        });
     }
 }
 
@@ -578,6 +814,97 @@ pub(super) unsafe extern "C" fn sendmsg_nocancel_detour(
     }
 }
 
+/// Batched version of [`recvmsg_detour`].
+///
+/// Fills the source address of every received message, similar to how `recv_from` works. The
+/// kernel has already set `msg_len` for each of them.
+///
+/// TODO(alex): We are ignoring the control message header [`libc::cmsghdr`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(super) unsafe extern "C" fn recvmmsg_detour(
+    sockfd: i32,
+    message_headers: *mut libc::mmsghdr,
+    message_count: libc::c_uint,
+    flags: c_int,
+    timeout: *mut libc::timespec,
+) -> c_int {
+    unsafe {
+        let recvmmsg_result = FN_RECVMMSG(sockfd, message_headers, message_count, flags, timeout);
+
+        if recvmmsg_result > 0 {
+            for index in 0..recvmmsg_result as usize {
+                let message = &mut *message_headers.add(index);
+                if message.msg_hdr.msg_name.is_null() {
+                    continue;
+                }
+
+                let _ = recv_from(
+                    sockfd,
+                    message.msg_len as isize,
+                    message.msg_hdr.msg_name as *mut _,
+                    &mut message.msg_hdr.msg_namelen,
+                );
+            }
+
+            // Don't leak an `errno` from our handling into a successful call.
+            Errno::set_raw(0);
+        }
+
+        recvmmsg_result
+    }
+}
+
+/// Batched version of [`sendmsg_detour`].
+///
+/// When none of the messages has a destination, this is the same as calling `send` for each of
+/// them, so we go straight to the original function. Otherwise each message goes through
+/// [`sendmsg`], and its `msg_len` is set to the amount of bytes sent.
+///
+/// Like the kernel, stops at the first message that fails: if it was the first one, returns `-1`
+/// with `errno` set, otherwise returns the amount of messages sent until then (the error is left
+/// for the next call to report).
+///
+/// TODO(alex): We are ignoring the control message header [`libc::cmsghdr`].
+#[cfg(target_os = "linux")]
+#[hook_guard_fn]
+pub(super) unsafe extern "C" fn sendmmsg_detour(
+    sockfd: RawFd,
+    message_headers: *mut libc::mmsghdr,
+    message_count: libc::c_uint,
+    flags: c_int,
+) -> c_int {
+    unsafe {
+        if message_headers.is_null()
+            || (0..message_count as usize)
+                .all(|index| (*message_headers.add(index)).msg_hdr.msg_name.is_null())
+        {
+            return FN_SENDMMSG(sockfd, message_headers, message_count, flags);
+        }
+
+        let mut sent = 0;
+        for index in 0..message_count as usize {
+            let message = &mut *message_headers.add(index);
+
+            let sendmsg_result = if message.msg_hdr.msg_name.is_null() {
+                libc::sendmsg(sockfd, &message.msg_hdr, flags)
+            } else {
+                sendmsg(sockfd, &message.msg_hdr, flags)
+                    .unwrap_or_bypass_with(|_| libc::sendmsg(sockfd, &message.msg_hdr, flags))
+            };
+
+            if sendmsg_result == -1 {
+                return if sent == 0 { -1 } else { sent };
+            }
+
+            message.msg_len = sendmsg_result as libc::c_uint;
+            sent += 1;
+        }
+
+        sent
+    }
+}
+
 /// Not a faithful reproduction of what [`FN_DNS_CONFIGURATION_COPY`] is supposed to do, see
 /// [`remote_dns_configuration_copy`].
 #[cfg(target_os = "macos")]
@@ -628,6 +955,47 @@ pub(crate) unsafe extern "C" fn getifaddrs_detour(ifaddrs: *mut *mut libc::ifadd
     }
 }
 
+/// Hook for [`libc::if_nametoindex`].
+///
+/// Fails with [`libc::ENXIO`] for interfaces hidden by [`getifaddrs_detour`].
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn if_nametoindex_detour(ifname: *const c_char) -> libc::c_uint {
+    unsafe {
+        let hidden =
+            !ifname.is_null() && is_interface_hidden(CStr::from_ptr(ifname)).unwrap_or_default();
+
+        if hidden {
+            Errno::set_raw(libc::ENXIO);
+            0
+        } else {
+            FN_IF_NAMETOINDEX(ifname)
+        }
+    }
+}
+
+/// Hook for [`libc::if_indextoname`].
+///
+/// Fails with [`libc::ENXIO`] for interfaces hidden by [`getifaddrs_detour`].
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn if_indextoname_detour(
+    ifindex: libc::c_uint,
+    ifname: *mut c_char,
+) -> *mut c_char {
+    unsafe {
+        let name = FN_IF_INDEXTONAME(ifindex, ifname);
+        if name.is_null() {
+            return name;
+        }
+
+        if is_interface_hidden(CStr::from_ptr(name)).unwrap_or_default() {
+            Errno::set_raw(libc::ENXIO);
+            std::ptr::null_mut()
+        } else {
+            name
+        }
+    }
+}
+
 #[cfg(target_os = "macos")]
 #[hook_guard_fn]
 pub(crate) unsafe extern "C" fn connectx_detour(
@@ -708,6 +1076,24 @@ pub(crate) unsafe fn enable_socket_hooks(
             FN_SENDMSG_NOCANCEL
         );
 
+        #[cfg(target_os = "linux")]
+        {
+            replace!(
+                hook_manager,
+                "recvmmsg",
+                recvmmsg_detour,
+                FnRecvmmsg,
+                FN_RECVMMSG
+            );
+            replace!(
+                hook_manager,
+                "sendmmsg",
+                sendmmsg_detour,
+                FnSendmmsg,
+                FN_SENDMMSG
+            );
+        }
+
         replace!(hook_manager, "bind", bind_detour, FnBind, FN_BIND);
         replace!(hook_manager, "listen", listen_detour, FnListen, FN_LISTEN);
 
@@ -762,6 +1148,15 @@ pub(crate) unsafe fn enable_socket_hooks(
             FN_GETSOCKNAME
         );
 
+        #[cfg(target_os = "linux")]
+        replace!(
+            hook_manager,
+            "getsockopt",
+            getsockopt_detour,
+            FnGetsockopt,
+            FN_GETSOCKOPT
+        );
+
         replace!(
             hook_manager,
             "gethostname",
@@ -810,6 +1205,33 @@ pub(crate) unsafe fn enable_socket_hooks(
                 FN_GETHOSTBYNAME
             );
 
+            #[cfg(target_os = "linux")]
+            {
+                replace!(
+                    hook_manager,
+                    "gethostbyname_r",
+                    gethostbyname_r_detour,
+                    FnGethostbyname_r,
+                    FN_GETHOSTBYNAME_R
+                );
+
+                replace!(
+                    hook_manager,
+                    "gethostbyname2_r",
+                    gethostbyname2_r_detour,
+                    FnGethostbyname2_r,
+                    FN_GETHOSTBYNAME2_R
+                );
+            }
+
+            replace!(
+                hook_manager,
+                "gethostbyaddr",
+                gethostbyaddr_detour,
+                FnGethostbyaddr,
+                FN_GETHOSTBYADDR
+            );
+
             replace!(
                 hook_manager,
                 "getaddrinfo",
@@ -818,6 +1240,14 @@ pub(crate) unsafe fn enable_socket_hooks(
                 FN_GETADDRINFO
             );
 
+            replace!(
+                hook_manager,
+                "getnameinfo",
+                getnameinfo_detour,
+                FnGetnameinfo,
+                FN_GETNAMEINFO
+            );
+
             replace!(
                 hook_manager,
                 "freeaddrinfo",
@@ -861,6 +1291,20 @@ pub(crate) unsafe fn enable_socket_hooks(
                 FnGetifaddrs,
                 FN_GETIFADDRS
             );
+            replace!(
+                hook_manager,
+                "if_nametoindex",
+                if_nametoindex_detour,
+                FnIf_nametoindex,
+                FN_IF_NAMETOINDEX
+            );
+            replace!(
+                hook_manager,
+                "if_indextoname",
+                if_indextoname_detour,
+                FnIf_indextoname,
+                FN_IF_INDEXTONAME
+            );
         }
 
         #[cfg(target_os = "macos")]
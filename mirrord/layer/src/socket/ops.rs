@@ -3,6 +3,7 @@ use core::{ffi::CStr, mem};
 #[cfg(target_os = "macos")]
 use std::os::fd::BorrowedFd;
 use std::{
+    collections::{HashMap, HashSet},
     io,
     net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, TcpStream},
     ops::Not,
@@ -18,7 +19,10 @@ use std::{
 use libc::{AF_UNIX, c_int, c_void, hostent, sockaddr, socklen_t};
 #[cfg(target_os = "macos")]
 use libc::{SAE_ASSOCID_ANY, c_uint, iovec, sa_endpoints_t, sae_associd_t, sae_connid_t, size_t};
-use mirrord_config::feature::network::incoming::{IncomingConfig, IncomingMode};
+use mirrord_config::{
+    experimental::HostnameTruncation,
+    feature::network::incoming::{IncomingConfig, IncomingMode},
+};
 use mirrord_intproxy_protocol::{
     ConnMetadataRequest, ConnMetadataResponse, NetProtocol, OutgoingConnMetadataRequest,
     PortSubscribe,
@@ -29,7 +33,7 @@ use mirrord_layer_lib::{
     detour::{Detour, OnceLockExt, OptionExt},
     error::{HookError, HookResult},
     graceful_exit,
-    proxy_connection::make_proxy_request_with_response,
+    proxy_connection::{make_proxy_request_no_response, make_proxy_request_with_response},
     socket::{
         Bound, Connected, SocketAddrExt, SocketKind, SocketState,
         dns::{remote_getaddrinfo, unix::getaddrinfo as getaddrinfo_lib},
@@ -37,12 +41,14 @@ use mirrord_layer_lib::{
     },
 };
 use mirrord_protocol::{
+    LogLevel, LogMessage, ResponseError,
+    dns::{ReverseDnsLookupRequest, ReverseDnsLookupResponse},
     file::{OpenFileResponse, OpenOptionsInternal, ReadFileResponse},
     outgoing::SocketAddress,
 };
 use nix::{
     errno::Errno,
-    sys::socket::{SockaddrLike, SockaddrStorage},
+    sys::socket::{SockaddrIn6, SockaddrLike, SockaddrStorage},
 };
 use socket2::SockAddr;
 #[cfg(debug_assertions)]
@@ -55,7 +61,7 @@ use crate::file::{self, OPEN_FILES};
 /// Hostname initialized from the agent with [`gethostname`].
 pub(crate) static HOSTNAME: OnceLock<CString> = OnceLock::new();
 
-/// Globals used by `gethostbyname`.
+/// Globals used by `gethostbyname` and `gethostbyaddr`.
 static mut GETHOSTBYNAME_HOSTNAME: Option<CString> = None;
 static mut GETHOSTBYNAME_ALIASES_STR: Option<Vec<CString>> = None;
 
@@ -64,10 +70,11 @@ static mut GETHOSTBYNAME_ALIASES_STR: Option<Vec<CString>> = None;
 /// have `*const _`. As this is being filled to fulfill the contract of a deprecated function, I
 /// (alex) don't think we're going to hit this issue ever.
 static mut GETHOSTBYNAME_ALIASES_PTR: Option<Vec<*const i8>> = None;
-static mut GETHOSTBYNAME_ADDRESSES_VAL: Option<Vec<[u8; 4]>> = None;
+static mut GETHOSTBYNAME_ADDRESSES_VAL: Option<Vec<Vec<u8>>> = None;
 static mut GETHOSTBYNAME_ADDRESSES_PTR: Option<Vec<*mut u8>> = None;
 
-/// Global static that the user will receive when calling [`gethostbyname`].
+/// Global static that the user will receive when calling [`gethostbyname`] or
+/// [`gethostbyaddr`].
 ///
 /// **Safety**:
 /// Even though we fill it with some `*const _` while it expects `*mut _`, it shouldn't be a problem
@@ -149,6 +156,15 @@ fn bind_similar_address(
 }
 
 /// Checks if given TCP port needs to be ignored based on ports logic
+/// Whether the remote `port` is allowed by the `feature.network.incoming.ports` whitelist (if
+/// any).
+fn is_whitelisted_remote_port(port: u16, config: &IncomingConfig) -> bool {
+    config
+        .ports
+        .as_ref()
+        .is_none_or(|ports| ports.contains(&port))
+}
+
 fn is_ignored_tcp_port(addr: &SocketAddr, config: &IncomingConfig) -> bool {
     let mapped_port = crate::setup()
         .incoming_config()
@@ -157,12 +173,7 @@ fn is_ignored_tcp_port(addr: &SocketAddr, config: &IncomingConfig) -> bool {
         .copied()
         .unwrap_or_else(|| addr.port());
 
-    let have_whitelist_and_port_is_not_whitelisted = config
-        .ports
-        .as_ref()
-        .is_some_and(|ports| ports.contains(&mapped_port).not());
-
-    is_ignored_port(addr) || have_whitelist_and_port_is_not_whitelisted
+    is_ignored_port(addr) || is_whitelisted_remote_port(mapped_port, config).not()
 }
 
 /// If the socket is not found in [`SOCKETS`], bypass.
@@ -225,11 +236,21 @@ pub(super) fn bind(
         .get_by_left(&requested_address.port())
         .copied();
 
+    // Port `0` is normally ignored, unless we have remote ports to map it to, see
+    // `bind_any_remote_port`.
+    let binds_any_mapped_to_remote = requested_port == 0
+        && matches!(socket.kind, SocketKind::Tcp(_))
+        && incoming_config
+            .bind_any_maps_remote
+            .iter()
+            .any(|port| is_whitelisted_remote_port(*port, incoming_config));
+
     // we don't use `is_localhost` here since unspecified means to listen
     // on all IPs.
     let will_not_trigger_subscription = (incoming_config.ignore_localhost
         && requested_address.ip().is_loopback())
         || ((matches!(socket.kind, SocketKind::Tcp(_)))
+            && binds_any_mapped_to_remote.not()
             && is_ignored_tcp_port(&requested_address, incoming_config)
             || crate::setup().is_debugger_port(&requested_address)
             || incoming_config.ignore_ports.contains(&requested_port));
@@ -294,15 +315,52 @@ pub(super) fn bind(
         return Detour::Bypass(Bypass::AddressConversion);
     };
 
+    let mut sockets = SOCKETS.lock()?;
+
+    // The remote port is reserved under the same lock that we insert the socket with, so that
+    // concurrent binds don't pick the same port.
+    let remote_port = if binds_any_mapped_to_remote && will_not_trigger_subscription.not() {
+        let Some(remote_port) = bind_any_remote_port(&sockets) else {
+            warn!(
+                bind_any_maps_remote = ?incoming_config.bind_any_maps_remote,
+                local_port = address.port(),
+                "All whitelisted ports from `feature.network.incoming.bind_any_maps_remote` are \
+                already taken, the listener bound to port 0 will remain local"
+            );
+
+            // We don't put the socket into `SOCKETS`, so it remains local.
+            Errno::set_raw(0);
+            return Detour::Success(0);
+        };
+
+        Some(remote_port)
+    } else {
+        None
+    };
+
     Arc::get_mut(&mut socket).unwrap().state = SocketState::Bound {
         bound: Bound {
             requested_address,
             address,
+            remote_port,
         },
         is_only_bound: will_not_trigger_subscription,
     };
 
-    SOCKETS.lock()?.insert(sockfd, socket);
+    sockets.insert(sockfd, socket);
+    drop(sockets);
+
+    if let Some(remote_port) = remote_port {
+        let message = format!(
+            "listener bound to port 0 (local port {}) is mapped to remote port {remote_port}",
+            address.port()
+        );
+        tracing::info!(message, "Picked a port from `bind_any_maps_remote`");
+        let _ = make_proxy_request_no_response(LogMessage {
+            message,
+            level: LogLevel::Info,
+        });
+    }
 
     // node reads errno to check if bind was successful and doesn't care about the return value
     // (???)
@@ -310,6 +368,31 @@ pub(super) fn bind(
     Detour::Success(0)
 }
 
+/// For a socket that the user bound to port `0`, picks the first remote port from
+/// `feature.network.incoming.bind_any_maps_remote` that is not yet reserved by another bound or
+/// listening socket in `sockets`.
+///
+/// Ports that are not in the `feature.network.incoming.ports` whitelist are skipped.
+///
+/// Returns [`None`] when all ports from the list are already taken.
+fn bind_any_remote_port(sockets: &HashMap<RawFd, Arc<UserSocket>>) -> Option<u16> {
+    let incoming_config = crate::setup().incoming_config();
+
+    incoming_config
+        .bind_any_maps_remote
+        .iter()
+        .copied()
+        .find(|port| {
+            is_whitelisted_remote_port(*port, incoming_config)
+                && sockets.values().all(|socket| match &socket.state {
+                    SocketState::Bound { bound, .. } | SocketState::Listening(bound) => {
+                        bound.remote_port != Some(*port)
+                    }
+                    SocketState::Initialized | SocketState::Connected(_) => true,
+                })
+        })
+}
+
 /// Subscribe to the agent on the real port. Messages received from the agent on the real port will
 /// later be routed to the fake local port.
 #[mirrord_layer_macro::instrument(level = Level::TRACE, fields(pid = std::process::id()), ret)]
@@ -335,10 +418,12 @@ pub(super) fn listen(sockfd: RawFd, backlog: c_int) -> Detour<i32> {
 
     match socket.state {
         SocketState::Bound {
-            bound: Bound {
-                requested_address,
-                address,
-            },
+            bound:
+                Bound {
+                    requested_address,
+                    address,
+                    remote_port,
+                },
             is_only_bound,
         } if is_only_bound.not() => {
             let listen_result = unsafe { FN_LISTEN(sockfd, backlog) };
@@ -350,12 +435,16 @@ pub(super) fn listen(sockfd: RawFd, backlog: c_int) -> Detour<i32> {
                 Err(error)?
             }
 
-            let mapped_port = setup
-                .incoming_config()
-                .port_mapping
-                .get_by_left(&requested_address.port())
-                .copied()
-                .unwrap_or_else(|| requested_address.port());
+            // Port `0` reaches this point only with a port reserved from `bind_any_maps_remote`,
+            // see `bind`.
+            let mapped_port = remote_port.unwrap_or_else(|| {
+                setup
+                    .incoming_config()
+                    .port_mapping
+                    .get_by_left(&requested_address.port())
+                    .copied()
+                    .unwrap_or_else(|| requested_address.port())
+            });
 
             make_proxy_request_with_response(PortSubscribe {
                 listening_on: address,
@@ -368,6 +457,7 @@ pub(super) fn listen(sockfd: RawFd, backlog: c_int) -> Detour<i32> {
             Arc::get_mut(&mut socket).unwrap().state = SocketState::Listening(Bound {
                 requested_address,
                 address,
+                remote_port: Some(mapped_port),
             });
 
             SOCKETS.lock()?.insert(sockfd, socket);
@@ -605,15 +695,18 @@ pub(super) fn getsockname(
             map_ipv64(socket.domain, response.in_cluster_address).into()
         }
         SocketState::Bound {
-            bound: Bound {
-                requested_address,
-                address,
-            },
+            bound:
+                Bound {
+                    requested_address,
+                    address,
+                    ..
+                },
             ..
         }
         | SocketState::Listening(Bound {
             requested_address,
             address,
+            ..
         }) => {
             if requested_address.port() == 0 {
                 SocketAddr::new(requested_address.ip(), address.port()).into()
@@ -632,6 +725,50 @@ pub(super) fn getsockname(
     fill_address(address, address_len, local_address)
 }
 
+/// Returns the original destination of a connection that we delivered through [`accept`], for
+/// the `SO_ORIGINAL_DST` (`SOL_IP`) and `IP6T_SO_ORIGINAL_DST` (`SOL_IPV6`) socket options.
+///
+/// The connection reaches the user application from the internal proxy, so the kernel would give
+/// back the local listener address here, instead of the address the connection was headed to in
+/// the cluster. Any other option is left for libc.
+#[cfg(target_os = "linux")]
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret, skip(optval, optlen))]
+pub(super) fn getsockopt(
+    sockfd: RawFd,
+    level: c_int,
+    optname: c_int,
+    optval: *mut c_void,
+    optlen: *mut socklen_t,
+) -> Detour<i32> {
+    let is_original_dst = (level == libc::SOL_IP && optname == libc::SO_ORIGINAL_DST)
+        || (level == libc::SOL_IPV6 && optname == libc::IP6T_SO_ORIGINAL_DST);
+    if !is_original_dst {
+        return Detour::Bypass(Bypass::SocketOption(level, optname));
+    }
+
+    let original_destination = SOCKETS
+        .lock()?
+        .get(&sockfd)
+        .bypass(Bypass::LocalFdNotFound(sockfd))
+        .and_then(|socket| match &socket.state {
+            SocketState::Connected(Connected {
+                original_destination: Some(original_destination),
+                ..
+            }) => Detour::Success(map_socketaddress_ipv64(
+                socket.domain,
+                original_destination.clone(),
+            )),
+            _ => Detour::Bypass(Bypass::InvalidState(sockfd)),
+        })?;
+
+    trace!(
+        "getsockopt -> original_destination {:#?}",
+        original_destination
+    );
+
+    fill_address(optval.cast(), optlen, original_destination.try_into()?)
+}
+
 /// When the fd is "ours", we accept and use [`ConnMetadataRequest`] to retrieve peer address from
 /// the internal proxy.
 #[mirrord_layer_macro::instrument(level = "trace", ret, skip(address, address_len))]
@@ -650,6 +787,7 @@ pub(super) fn accept(
                 SocketState::Listening(Bound {
                     requested_address,
                     address,
+                    ..
                 }) => Detour::Success((
                     socket.domain,
                     socket.protocol,
@@ -673,6 +811,7 @@ pub(super) fn accept(
     let ConnMetadataResponse {
         remote_source,
         local_address,
+        destination_port,
     } = make_proxy_request_with_response(ConnMetadataRequest {
         listener_address,
         peer_address,
@@ -682,6 +821,7 @@ pub(super) fn accept(
         connection_id: None,
         remote_address: remote_source.into(),
         local_address: Some(SocketAddr::new(local_address, port).into()),
+        original_destination: Some(SocketAddr::new(local_address, destination_port).into()),
         layer_address: None,
     });
 
@@ -757,6 +897,131 @@ pub(super) fn getaddrinfo(
     getaddrinfo_lib(rawish_node, rawish_service, raw_hints)
 }
 
+/// Registers every node of the `addrinfo` list allocated by [`getaddrinfo`] in `managed`, so that
+/// [`free_addrinfo`] recognizes them even when the user trims the list and frees its nodes
+/// separately.
+pub(super) fn register_addrinfo(managed: &mut HashSet<usize>, addrinfo: *mut libc::addrinfo) {
+    let mut current = addrinfo;
+
+    // Safety: the list was just allocated by us, every node is valid.
+    while let Some(node) = unsafe { current.as_ref() } {
+        managed.insert(current as usize);
+        current = node.ai_next;
+    }
+}
+
+/// Frees the `addrinfo` list starting at `addrinfo`, as `freeaddrinfo` would.
+///
+/// Nodes registered in `managed` (with [`register_addrinfo`]) are deallocated by us, every run of
+/// other nodes is cut off from the rest of the list and passed to `free_foreign` (the original
+/// `freeaddrinfo`), so that libc never sees a node that we allocated, even if the user stitched
+/// our list together with one returned by libc.
+///
+/// # Safety
+///
+/// `addrinfo` must be null or point to a valid `addrinfo` list, where the nodes registered in
+/// `managed` were allocated the same way as in
+/// [`getaddrinfo`](mirrord_layer_lib::socket::dns::unix::getaddrinfo).
+pub(super) unsafe fn free_addrinfo<F>(
+    managed: &mut HashSet<usize>,
+    addrinfo: *mut libc::addrinfo,
+    mut free_foreign: F,
+) where
+    F: FnMut(*mut libc::addrinfo),
+{
+    let mut current = addrinfo;
+
+    unsafe {
+        while !current.is_null() {
+            if managed.remove(&(current as usize)) {
+                let node = Box::from_raw(current);
+
+                if !node.ai_addr.is_null() {
+                    drop(Box::from_raw(node.ai_addr));
+                }
+
+                if !node.ai_canonname.is_null() {
+                    drop(CString::from_raw(node.ai_canonname));
+                }
+
+                current = node.ai_next;
+                continue;
+            }
+
+            // Find the end of the foreign run, and cut it off before our next node.
+            let foreign = current;
+            let mut last = current;
+            while !(*last).ai_next.is_null() && !managed.contains(&((*last).ai_next as usize)) {
+                last = (*last).ai_next;
+            }
+
+            current = (*last).ai_next;
+            (*last).ai_next = ptr::null_mut();
+
+            free_foreign(foreign);
+        }
+    }
+}
+
+/// Resolves the hostname of the address passed to `getnameinfo` through the agent (reverse DNS
+/// lookup on the remote).
+///
+/// Returns [`None`] when the agent could not find a name for the address, in which case the
+/// caller falls back to the numeric form of the address (or fails with `EAI_NONAME` if
+/// `NI_NAMEREQD` was requested).
+///
+/// `NI_NOFQDN` is handled by keeping only the first label of the resolved name, as we don't know
+/// the remote's local domain here.
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(super) fn getnameinfo(
+    raw_address: *const sockaddr,
+    address_length: socklen_t,
+    host_length: socklen_t,
+    flags: c_int,
+) -> Detour<Option<CString>> {
+    if host_length == 0 || flags & libc::NI_NUMERICHOST != 0 {
+        return Detour::Bypass(Bypass::NumericHost);
+    }
+
+    let address = SocketAddr::try_from_raw(raw_address, address_length)?;
+
+    crate::setup()
+        .dns_selector()
+        .check_query(&address.ip().to_string(), address.port())?;
+
+    let Some(hostname) = remote_reverse_lookup(address.ip())? else {
+        return Detour::Success(None);
+    };
+
+    let hostname = if flags & libc::NI_NOFQDN != 0 {
+        hostname.split('.').next().unwrap_or_default().to_owned()
+    } else {
+        hostname
+    };
+
+    Detour::Success(Some(CString::new(hostname)?))
+}
+
+/// Resolves the hostname of `ip_address` with a reverse DNS lookup on the remote.
+///
+/// Returns [`None`] when the agent could not find a name for the address.
+fn remote_reverse_lookup(ip_address: IpAddr) -> Detour<Option<String>> {
+    // `NotImplemented` error here means that the protocol doesn't support it.
+    match make_proxy_request_with_response(ReverseDnsLookupRequest { ip_address })? {
+        Ok(ReverseDnsLookupResponse {
+            hostname: Ok(hostname),
+        }) => Detour::Success(Some(hostname)),
+        Ok(ReverseDnsLookupResponse {
+            hostname: Err(fail),
+        }) => {
+            trace!("Remote reverse DNS lookup of {ip_address} failed with {fail}");
+            Detour::Success(None)
+        }
+        Err(ResponseError::NotImplemented) => Detour::Bypass(Bypass::NotImplemented),
+        Err(fail) => Detour::Error(fail.into()),
+    }
+}
+
 /// Retrieves the `hostname` from the agent's `/etc/hostname` to be used by [`gethostname`]
 fn remote_hostname_string() -> Detour<CString> {
     if crate::setup().local_hostname() {
@@ -779,26 +1044,165 @@ fn remote_hostname_string() -> Detour<CString> {
         trace!("Leaking remote file fd (should be harmless) due to {fail:#?}!")
     });
 
-    CString::new(
-        bytes
-            .into_vec()
-            .into_iter()
-            .take(read_amount as usize - 1)
-            .collect::<Vec<_>>(),
-    )
-    .map(Detour::Success)?
+    let mut hostname = bytes.into_vec();
+    hostname.truncate(read_amount as usize);
+    if hostname.last() == Some(&b'\n') {
+        hostname.pop();
+    }
+
+    CString::new(hostname).map(Detour::Success)?
 }
 
-/// Resolves a hostname and set result to static global like the original `gethostbyname` does.
-///
-/// Used by erlang/elixir to resolve DNS.
+/// A resolved host, in the shape of a [`libc::hostent`].
 ///
-/// **Safety**:
-/// See the [`GETHOSTBYNAME_ALIASES_PTR`] docs. If you see this function being called and some weird
-/// issue is going on, assume that you might've triggered the UB.
-#[mirrord_layer_macro::instrument(level = "trace", ret)]
-pub(super) fn gethostbyname(raw_name: Option<&CStr>) -> Detour<*mut hostent> {
-    let name: String = raw_name
+/// Filled by the remote lookups of the `gethostbyname` family and [`gethostbyaddr`], and then
+/// copied either into our static [`GETHOSTBYNAME_HOSTENT`], or into the caller-provided buffer of
+/// the reentrant `gethostbyname_r`/`gethostbyname2_r`.
+#[derive(Debug)]
+pub(super) struct RemoteHostent {
+    /// Official name of the host (`h_name`).
+    name: CString,
+    /// Alternative names of the host (`h_aliases`).
+    aliases: Vec<CString>,
+    /// Either `AF_INET` or `AF_INET6` (`h_addrtype`), matching every entry in `addresses`.
+    family: c_int,
+    /// Addresses of the host in network byte order (`h_addr_list`).
+    addresses: Vec<Vec<u8>>,
+}
+
+impl RemoteHostent {
+    /// Length of each of the addresses (`h_length`).
+    fn address_length(&self) -> usize {
+        if self.family == libc::AF_INET6 { 16 } else { 4 }
+    }
+
+    /// Replaces the contents of [`GETHOSTBYNAME_HOSTENT`] with this host, and returns a pointer
+    /// to it.
+    fn into_static_hostent(self) -> *mut hostent {
+        let h_length = self.address_length() as c_int;
+        let Self {
+            name,
+            aliases,
+            family,
+            mut addresses,
+        } = self;
+
+        let mut aliases_ptrs: Vec<*const i8> = aliases
+            .iter()
+            .map(|alias| alias.as_ptr().cast())
+            .collect::<Vec<_>>();
+        let mut addresses_ptrs = addresses
+            .iter_mut()
+            .map(|address| address.as_mut_ptr())
+            .collect::<Vec<_>>();
+
+        // Put a null ptr to signal end of the list.
+        aliases_ptrs.push(ptr::null());
+        addresses_ptrs.push(ptr::null_mut());
+
+        // Need long-lived values so we can take pointers to them.
+        #[allow(static_mut_refs)]
+        unsafe {
+            GETHOSTBYNAME_HOSTNAME.replace(name);
+            GETHOSTBYNAME_ALIASES_STR.replace(aliases);
+            GETHOSTBYNAME_ALIASES_PTR.replace(aliases_ptrs);
+            GETHOSTBYNAME_ADDRESSES_VAL.replace(addresses);
+            GETHOSTBYNAME_ADDRESSES_PTR.replace(addresses_ptrs);
+
+            // Fill the `*mut hostent` that the user will interact with.
+            GETHOSTBYNAME_HOSTENT.h_name = GETHOSTBYNAME_HOSTNAME.as_ref().unwrap().as_ptr() as _;
+            GETHOSTBYNAME_HOSTENT.h_length = h_length;
+            GETHOSTBYNAME_HOSTENT.h_addrtype = family;
+            GETHOSTBYNAME_HOSTENT.h_aliases =
+                GETHOSTBYNAME_ALIASES_PTR.as_ref().unwrap().as_ptr() as _;
+            GETHOSTBYNAME_HOSTENT.h_addr_list =
+                GETHOSTBYNAME_ADDRESSES_PTR.as_ref().unwrap().as_ptr() as *mut *mut libc::c_char;
+        }
+
+        std::ptr::addr_of!(GETHOSTBYNAME_HOSTENT) as _
+    }
+
+    /// Copies this host into `ret`, placing everything it points to (names, addresses and the
+    /// null-terminated pointer arrays) in the caller-provided `buffer`, like the reentrant
+    /// `gethostbyname_r` does.
+    ///
+    /// Returns `ERANGE` when `buffer` is null or too small to hold this host, otherwise `0`.
+    ///
+    /// # Safety
+    ///
+    /// `ret` must be valid for writes, and `buffer` must be either null or valid for writes of
+    /// `buffer_length` bytes.
+    #[cfg(target_os = "linux")]
+    pub(super) unsafe fn copy_into(
+        &self,
+        ret: *mut hostent,
+        buffer: *mut libc::c_char,
+        buffer_length: usize,
+    ) -> c_int {
+        let address_length = self.address_length();
+        let pointer_size = mem::size_of::<*mut libc::c_char>();
+
+        let padding = buffer.align_offset(mem::align_of::<*mut libc::c_char>());
+        let required_length = padding
+            + (self.aliases.len() + 1 + self.addresses.len() + 1) * pointer_size
+            + self.addresses.len() * address_length
+            + self.name.as_bytes_with_nul().len()
+            + self
+                .aliases
+                .iter()
+                .map(|alias| alias.as_bytes_with_nul().len())
+                .sum::<usize>();
+
+        if buffer.is_null() || required_length > buffer_length {
+            return libc::ERANGE;
+        }
+
+        unsafe {
+            let mut cursor = buffer.add(padding);
+
+            let aliases_ptrs = cursor.cast::<*mut libc::c_char>();
+            cursor = cursor.add((self.aliases.len() + 1) * pointer_size);
+            let addresses_ptrs = cursor.cast::<*mut libc::c_char>();
+            cursor = cursor.add((self.addresses.len() + 1) * pointer_size);
+
+            for (index, address) in self.addresses.iter().enumerate() {
+                copy_nonoverlapping(address.as_ptr(), cursor.cast(), address_length);
+                addresses_ptrs.add(index).write(cursor);
+                cursor = cursor.add(address_length);
+            }
+            addresses_ptrs
+                .add(self.addresses.len())
+                .write(ptr::null_mut());
+
+            let name = cursor;
+            let name_bytes = self.name.as_bytes_with_nul();
+            copy_nonoverlapping(name_bytes.as_ptr(), cursor.cast(), name_bytes.len());
+            cursor = cursor.add(name_bytes.len());
+
+            for (index, alias) in self.aliases.iter().enumerate() {
+                let alias_bytes = alias.as_bytes_with_nul();
+                copy_nonoverlapping(alias_bytes.as_ptr(), cursor.cast(), alias_bytes.len());
+                aliases_ptrs.add(index).write(cursor);
+                cursor = cursor.add(alias_bytes.len());
+            }
+            aliases_ptrs.add(self.aliases.len()).write(ptr::null_mut());
+
+            ret.write(hostent {
+                h_name: name,
+                h_aliases: aliases_ptrs,
+                h_addrtype: self.family,
+                h_length: address_length as c_int,
+                h_addr_list: addresses_ptrs,
+            });
+        }
+
+        0
+    }
+}
+
+/// Converts the `name` passed to the `gethostbyname` family into a [`String`].
+fn gethostbyname_name(raw_name: Option<&CStr>) -> Detour<String> {
+    let name = raw_name
         .bypass(Bypass::NullNode)?
         .to_str()
         .map_err(|fail| {
@@ -808,6 +1212,20 @@ pub(super) fn gethostbyname(raw_name: Option<&CStr>) -> Detour<*mut hostent> {
         })?
         .into();
 
+    Detour::Success(name)
+}
+
+/// Resolves a hostname and set result to static global like the original `gethostbyname` does.
+///
+/// Used by erlang/elixir to resolve DNS.
+///
+/// **Safety**:
+/// See the [`GETHOSTBYNAME_ALIASES_PTR`] docs. If you see this function being called and some weird
+/// issue is going on, assume that you might've triggered the UB.
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(super) fn gethostbyname(raw_name: Option<&CStr>) -> Detour<*mut hostent> {
+    let name = gethostbyname_name(raw_name)?;
+
     crate::setup().dns_selector().check_query(&name, 0)?;
 
     let hosts_and_ips = remote_getaddrinfo(name.clone(), 0, 0, 0, 0, 0)?;
@@ -820,66 +1238,172 @@ pub(super) fn gethostbyname(raw_name: Option<&CStr>) -> Detour<*mut hostent> {
         return Detour::Success(ptr::null_mut());
     }
 
-    // We need `*mut _` at the end, so `ips` has to be `mut`.
-    let (aliases, mut ips) = hosts_and_ips
+    let (aliases, addresses) = hosts_and_ips
         .into_iter()
         .filter_map(|(host, ip)| match ip {
             // Only care about ipv4s and hosts that exist.
             IpAddr::V4(ip) => {
                 let c_host = CString::new(host).ok()?;
-                Some((c_host, ip.octets()))
+                Some((c_host, ip.octets().to_vec()))
             }
             IpAddr::V6(ip) => {
                 trace!("ipv6 received - ignoring - {ip:?}");
                 None
             }
         })
-        .fold(
-            (Vec::default(), Vec::default()),
-            |(mut aliases, mut ips), (host, octets)| {
-                aliases.push(host);
-                ips.push(octets);
-                (aliases, ips)
-            },
-        );
+        .unzip();
+
+    let host = RemoteHostent {
+        name: host_name,
+        aliases,
+        family: libc::AF_INET,
+        addresses,
+    };
+
+    Detour::Success(host.into_static_hostent())
+}
 
-    let mut aliases_ptrs: Vec<*const i8> = aliases
+/// Resolves a hostname for the reentrant `gethostbyname_r` (`AF_INET`) and `gethostbyname2_r`
+/// (`AF_INET` or `AF_INET6`).
+///
+/// Unlike [`gethostbyname`], the result is not stored in our globals, the caller copies it into
+/// its own buffer with [`RemoteHostent::copy_into`]. Returns [`None`] when the remote could not
+/// resolve the host, or has no addresses of the requested `family` for it.
+#[cfg(target_os = "linux")]
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(super) fn gethostbyname_r(
+    raw_name: Option<&CStr>,
+    family: c_int,
+) -> Detour<Option<RemoteHostent>> {
+    if family != libc::AF_INET && family != libc::AF_INET6 {
+        return Detour::Bypass(Bypass::Domain(family));
+    }
+
+    let name = gethostbyname_name(raw_name)?;
+
+    crate::setup().dns_selector().check_query(&name, 0)?;
+
+    let hosts_and_ips = match remote_getaddrinfo(name.clone(), 0, 0, family, 0, 0) {
+        Ok(hosts_and_ips) => hosts_and_ips,
+        Err(HookError::ResponseError(ResponseError::DnsLookup(fail))) => {
+            trace!("Remote lookup of {name} failed with {fail:?}");
+            return Detour::Success(None);
+        }
+        Err(fail) => return Detour::Error(fail),
+    };
+
+    let addresses = hosts_and_ips
         .iter()
-        .map(|alias| alias.as_ptr().cast())
+        .filter_map(|(_, ip)| match ip {
+            IpAddr::V4(ip) if family == libc::AF_INET => Some(ip.octets().to_vec()),
+            IpAddr::V6(ip) if family == libc::AF_INET6 => Some(ip.octets().to_vec()),
+            _ => None,
+        })
         .collect::<Vec<_>>();
-    let mut ips_ptrs = ips.iter_mut().map(|ip| ip.as_mut_ptr()).collect::<Vec<_>>();
 
-    // Put a null ptr to signal end of the list.
-    aliases_ptrs.push(ptr::null());
-    ips_ptrs.push(ptr::null_mut());
+    if addresses.is_empty() {
+        return Detour::Success(None);
+    }
 
-    // Need long-lived values so we can take pointers to them.
-    #[allow(static_mut_refs)]
-    unsafe {
-        GETHOSTBYNAME_HOSTNAME.replace(host_name);
-        GETHOSTBYNAME_ALIASES_STR.replace(aliases);
-        GETHOSTBYNAME_ALIASES_PTR.replace(aliases_ptrs);
-        GETHOSTBYNAME_ADDRESSES_VAL.replace(ips);
-        GETHOSTBYNAME_ADDRESSES_PTR.replace(ips_ptrs);
-
-        // Fill the `*mut hostent` that the user will interact with.
-        GETHOSTBYNAME_HOSTENT.h_name = GETHOSTBYNAME_HOSTNAME.as_ref().unwrap().as_ptr() as _;
-        GETHOSTBYNAME_HOSTENT.h_length = 4;
-        GETHOSTBYNAME_HOSTENT.h_addrtype = libc::AF_INET;
-        GETHOSTBYNAME_HOSTENT.h_aliases = GETHOSTBYNAME_ALIASES_PTR.as_ref().unwrap().as_ptr() as _;
-        GETHOSTBYNAME_HOSTENT.h_addr_list =
-            GETHOSTBYNAME_ADDRESSES_PTR.as_ref().unwrap().as_ptr() as *mut *mut libc::c_char;
+    let mut aliases = Vec::new();
+    for (host, _) in hosts_and_ips {
+        if host != name && !aliases.contains(&host) {
+            aliases.push(host);
+        }
     }
 
-    Detour::Success(std::ptr::addr_of!(GETHOSTBYNAME_HOSTENT) as _)
+    Detour::Success(Some(RemoteHostent {
+        name: CString::new(name)?,
+        aliases: aliases
+            .into_iter()
+            .map(CString::new)
+            .collect::<Result<_, _>>()?,
+        family,
+        addresses,
+    }))
+}
+
+/// Resolves the name of an address with a reverse DNS lookup on the remote, and sets the result
+/// to the same static global as [`gethostbyname`], like the original `gethostbyaddr` does.
+///
+/// Returns a null pointer when the remote could not find a name for the address.
+///
+/// **Safety**:
+/// See the [`GETHOSTBYNAME_ALIASES_PTR`] docs.
+#[mirrord_layer_macro::instrument(level = "trace", ret)]
+pub(super) fn gethostbyaddr(
+    raw_address: *const c_void,
+    address_length: socklen_t,
+    family: c_int,
+) -> Detour<*mut hostent> {
+    if raw_address.is_null() {
+        return Detour::Bypass(Bypass::AddressConversion);
+    }
+
+    // SAFETY: the length of the address was checked against its family.
+    let address: IpAddr = match (family, address_length) {
+        (libc::AF_INET, 4) => Ipv4Addr::from(unsafe { *raw_address.cast::<[u8; 4]>() }).into(),
+        (libc::AF_INET6, 16) => Ipv6Addr::from(unsafe { *raw_address.cast::<[u8; 16]>() }).into(),
+        _ => return Detour::Bypass(Bypass::Domain(family)),
+    };
+
+    crate::setup()
+        .dns_selector()
+        .check_query(&address.to_string(), 0)?;
+
+    let Some(hostname) = remote_reverse_lookup(address)? else {
+        return Detour::Success(ptr::null_mut());
+    };
+
+    let host = RemoteHostent {
+        name: CString::new(hostname)?,
+        aliases: Vec::new(),
+        family,
+        addresses: vec![match address {
+            IpAddr::V4(ip) => ip.octets().to_vec(),
+            IpAddr::V6(ip) => ip.octets().to_vec(),
+        }],
+    };
+
+    Detour::Success(host.into_static_hostent())
 }
 
 /// Resolve hostname from remote host with caching for the result
 #[mirrord_layer_macro::instrument(level = "trace")]
-pub(super) fn gethostname() -> Detour<&'static CString> {
+pub(crate) fn gethostname() -> Detour<&'static CString> {
     HOSTNAME.get_or_detour_init(remote_hostname_string)
 }
 
+/// Copies the `host` returned from [`gethostname`] into the application's `buffer`.
+///
+/// When the `host` (with the null byte) does not fit, the `buffer` is filled with the start of
+/// the `host`, and the result depends on the configured [`HostnameTruncation`]:
+/// - [`HostnameTruncation::Error`] - returns [`Errno::EINVAL`], the `buffer` is not terminated;
+/// - [`HostnameTruncation::Truncate`] - the last byte of the `buffer` is set to null.
+pub(super) fn copy_hostname(
+    host: &CStr,
+    buffer: &mut [u8],
+    truncation: HostnameTruncation,
+) -> Result<(), Errno> {
+    let host = host.to_bytes_with_nul();
+    let copied = host.len().min(buffer.len());
+    buffer[..copied].copy_from_slice(&host[..copied]);
+
+    if host.len() <= buffer.len() {
+        return Ok(());
+    }
+
+    match truncation {
+        HostnameTruncation::Error => Err(Errno::EINVAL),
+        HostnameTruncation::Truncate => {
+            if let Some(last) = buffer.last_mut() {
+                *last = 0;
+            }
+            Ok(())
+        }
+    }
+}
+
 /// Retrieves the contents of remote's `/etc/resolv.conf`
 #[cfg(target_os = "macos")]
 #[mirrord_layer_macro::instrument(level = "trace")]
@@ -1354,6 +1878,17 @@ pub(super) fn remote_dns_configuration_copy() -> Detour<*mut dns_config_t> {
     Detour::Success(config)
 }
 
+/// Returns the address of `ifaddr` if it's an IPv6 address, which means [`getifaddrs`] removes
+/// it from the list.
+///
+/// # Safety
+///
+/// `ifaddr` must come from [`libc::getifaddrs`].
+unsafe fn hidden_ipv6_address(ifaddr: &libc::ifaddrs) -> Option<SockaddrIn6> {
+    let address = unsafe { SockaddrStorage::from_raw(ifaddr.ifa_addr, None) }?;
+    address.as_sockaddr_in6().copied()
+}
+
 /// Calls [`libc::getifaddrs`] and removes IPv6 addresses from the list.
 #[mirrord_layer_macro::instrument(level = Level::TRACE, ret, err)]
 pub(super) fn getifaddrs() -> HookResult<*mut libc::ifaddrs> {
@@ -1390,9 +1925,7 @@ pub(super) fn getifaddrs() -> HookResult<*mut libc::ifaddrs> {
     // initialized memory.
     unsafe {
         while let Some(ifaddr) = inspected.as_mut() {
-            let address = SockaddrStorage::from_raw(ifaddr.ifa_addr, None);
-
-            match address.as_ref().and_then(SockaddrStorage::as_sockaddr_in6) {
+            match hidden_ipv6_address(ifaddr) {
                 // If not ipv6, advance to the next interface address in the original list.
                 // Move both `previous` and `inspected`.
                 None => {
@@ -1435,3 +1968,207 @@ pub(super) fn getifaddrs() -> HookResult<*mut libc::ifaddrs> {
 
     Ok(new_list_start)
 }
+
+/// Checks whether the interface `name` is hidden by [`getifaddrs`], which is the case when all of
+/// its IP addresses are IPv6 addresses (so none of them show up in the list).
+///
+/// Used to keep [`libc::if_nametoindex`] and [`libc::if_indextoname`] consistent with
+/// [`getifaddrs`].
+#[mirrord_layer_macro::instrument(level = Level::TRACE, ret, err)]
+pub(super) fn is_interface_hidden(name: &CStr) -> HookResult<bool> {
+    let mut head = std::ptr::null_mut();
+    let result: i32 = unsafe { FN_GETIFADDRS(&mut head) };
+    if result != 0 {
+        Err(io::Error::last_os_error())?;
+    }
+
+    let mut has_ipv4 = false;
+    let mut has_ipv6 = false;
+    let mut inspected: *mut libc::ifaddrs = head;
+
+    // Safety: we only dereference pointers received from libc. They should be nulls or point to
+    // initialized memory.
+    unsafe {
+        while let Some(ifaddr) = inspected.as_ref() {
+            if !ifaddr.ifa_name.is_null() && CStr::from_ptr(ifaddr.ifa_name) == name {
+                if hidden_ipv6_address(ifaddr).is_some() {
+                    has_ipv6 = true;
+                } else if SockaddrStorage::from_raw(ifaddr.ifa_addr, None)
+                    .is_some_and(|address| address.as_sockaddr_in().is_some())
+                {
+                    has_ipv4 = true;
+                }
+            }
+
+            inspected = ifaddr.ifa_next;
+        }
+
+        libc::freeifaddrs(head);
+    }
+
+    Ok(has_ipv6 && !has_ipv4)
+}
+
+#[cfg(test)]
+mod test {
+    use std::{collections::HashSet, ffi::CString, mem, ops::Not, ptr};
+
+    use mirrord_config::experimental::HostnameTruncation;
+    use nix::errno::Errno;
+    use rstest::rstest;
+
+    use super::{copy_hostname, free_addrinfo, register_addrinfo};
+
+    /// Allocates a list of `len` nodes the same way our `getaddrinfo` does, and registers it.
+    fn managed_addrinfo(managed: &mut HashSet<usize>, len: usize) -> Vec<*mut libc::addrinfo> {
+        let nodes = (0..len)
+            .map(|i| {
+                Box::into_raw(Box::new(libc::addrinfo {
+                    ai_flags: 0,
+                    ai_family: libc::AF_INET,
+                    ai_socktype: libc::SOCK_STREAM,
+                    ai_protocol: 0,
+                    ai_addrlen: mem::size_of::<libc::sockaddr>() as _,
+                    ai_addr: Box::into_raw(Box::new(unsafe { mem::zeroed::<libc::sockaddr>() })),
+                    ai_canonname: CString::new(format!("node-{i}")).unwrap().into_raw(),
+                    ai_next: ptr::null_mut(),
+                }))
+            })
+            .collect::<Vec<_>>();
+
+        for pair in nodes.windows(2) {
+            unsafe { (*pair[0]).ai_next = pair[1] };
+        }
+
+        register_addrinfo(managed, nodes[0]);
+        assert_eq!(managed.len(), len);
+
+        nodes
+    }
+
+    /// Returns a list allocated by libc.
+    fn libc_addrinfo() -> *mut libc::addrinfo {
+        let hints = libc::addrinfo {
+            ai_flags: libc::AI_NUMERICHOST,
+            ..unsafe { mem::zeroed() }
+        };
+        let mut result = ptr::null_mut();
+
+        let status =
+            unsafe { libc::getaddrinfo(c"127.0.0.1".as_ptr(), ptr::null(), &hints, &mut result) };
+        assert_eq!(status, 0);
+        assert!(result.is_null().not());
+
+        result
+    }
+
+    fn last_node(mut addrinfo: *mut libc::addrinfo) -> *mut libc::addrinfo {
+        unsafe {
+            while (*addrinfo).ai_next.is_null().not() {
+                addrinfo = (*addrinfo).ai_next;
+            }
+        }
+
+        addrinfo
+    }
+
+    fn no_foreign(_: *mut libc::addrinfo) {
+        panic!("managed node passed to libc");
+    }
+
+    #[test]
+    fn frees_trimmed_suffix() {
+        let mut managed = HashSet::new();
+        let nodes = managed_addrinfo(&mut managed, 4);
+
+        unsafe {
+            (*nodes[1]).ai_next = ptr::null_mut();
+
+            free_addrinfo(&mut managed, nodes[2], no_foreign);
+            assert_eq!(
+                managed,
+                HashSet::from([nodes[0] as usize, nodes[1] as usize])
+            );
+
+            free_addrinfo(&mut managed, nodes[0], no_foreign);
+            assert!(managed.is_empty());
+        }
+    }
+
+    #[test]
+    fn frees_nodes_individually() {
+        let mut managed = HashSet::new();
+        let nodes = managed_addrinfo(&mut managed, 3);
+
+        unsafe {
+            for node in &nodes {
+                (**node).ai_next = ptr::null_mut();
+            }
+
+            for node in nodes.into_iter().rev() {
+                free_addrinfo(&mut managed, node, no_foreign);
+                assert!(managed.contains(&(node as usize)).not());
+            }
+        }
+
+        assert!(managed.is_empty());
+    }
+
+    /// Our list with a libc list stitched in the middle, and a libc list followed by ours.
+    #[test]
+    fn frees_mixed_lists() {
+        let mut managed = HashSet::new();
+        let nodes = managed_addrinfo(&mut managed, 2);
+        let foreign = libc_addrinfo();
+        let mut freed_foreign = Vec::new();
+
+        unsafe {
+            (*nodes[0]).ai_next = foreign;
+            (*last_node(foreign)).ai_next = nodes[1];
+
+            free_addrinfo(&mut managed, nodes[0], |addrinfo| {
+                freed_foreign.push(addrinfo);
+                libc::freeaddrinfo(addrinfo);
+            });
+        }
+
+        assert!(managed.is_empty());
+        assert_eq!(freed_foreign, [foreign]);
+
+        let nodes = managed_addrinfo(&mut managed, 1);
+        let foreign = libc_addrinfo();
+        freed_foreign.clear();
+
+        unsafe {
+            (*last_node(foreign)).ai_next = nodes[0];
+
+            free_addrinfo(&mut managed, foreign, |addrinfo| {
+                freed_foreign.push(addrinfo);
+                libc::freeaddrinfo(addrinfo);
+            });
+        }
+
+        assert!(managed.is_empty());
+        assert_eq!(freed_foreign, [foreign]);
+    }
+
+    #[rstest]
+    #[case::fits(16, HostnameTruncation::Error, Ok(()), b"mirrord-pod\0")]
+    #[case::exact(12, HostnameTruncation::Error, Ok(()), b"mirrord-pod\0")]
+    #[case::too_small_error(8, HostnameTruncation::Error, Err(Errno::EINVAL), b"mirrord-")]
+    #[case::too_small_truncate(8, HostnameTruncation::Truncate, Ok(()), b"mirrord\0")]
+    #[case::empty_truncate(0, HostnameTruncation::Truncate, Ok(()), b"")]
+    fn copies_hostname(
+        #[case] buffer_len: usize,
+        #[case] truncation: HostnameTruncation,
+        #[case] expected: Result<(), Errno>,
+        #[case] contents: &[u8],
+    ) {
+        let mut buffer = vec![0xff; buffer_len];
+
+        let result = copy_hostname(c"mirrord-pod", &mut buffer, truncation);
+
+        assert_eq!(result, expected);
+        assert_eq!(&buffer[..contents.len()], contents);
+    }
+}
@@ -0,0 +1,196 @@
+//! Hooks that shift the wall clock of the application by the offset to the clock of the agent,
+//! enabled with `experimental.remote_time_offset`.
+//!
+//! # Precision
+//!
+//! The offset is computed from a single [`RemoteTimeRequest`], assuming that the request and the
+//! response took the same time to travel. The error is bounded by half of the round trip time
+//! between the layer and the agent (usually a few milliseconds). To bound the drift between the
+//! clocks, the offset is refreshed on the first clock read after [`REFRESH_INTERVAL_SECS`].
+//!
+//! Only `CLOCK_REALTIME` read through libc is shifted. Monotonic clocks, `time(2)`, direct
+//! syscalls and vDSO calls made without libc (e.g. by Go) are not affected.
+
+use std::{
+    ops::Not,
+    sync::atomic::{AtomicI64, Ordering},
+    time::SystemTime,
+};
+
+use libc::{c_int, c_void, clockid_t, timespec, timeval};
+use mirrord_intproxy_protocol::RemoteTimeRequest;
+use mirrord_layer_macro::hook_guard_fn;
+
+use crate::{common::make_proxy_request_with_response, hooks::HookManager, replace};
+
+/// Offset between the remote and the local wall clock, in nanoseconds.
+static OFFSET_NANOS: AtomicI64 = AtomicI64::new(0);
+
+/// Value of `CLOCK_MONOTONIC` (in seconds) after which [`OFFSET_NANOS`] should be refreshed.
+static REFRESH_AT: AtomicI64 = AtomicI64::new(0);
+
+/// How often [`OFFSET_NANOS`] is refreshed.
+const REFRESH_INTERVAL_SECS: i64 = 60;
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+/// Returns the local wall-clock time in nanoseconds since the epoch.
+///
+/// Must be called with the hooks bypassed, otherwise the offset is applied.
+fn local_nanos() -> i128 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|since_epoch| since_epoch.as_nanos() as i128)
+        .unwrap_or_default()
+}
+
+/// Fetches the remote time from the agent and updates [`OFFSET_NANOS`].
+///
+/// On failure, the previous offset is kept.
+fn refresh_offset() {
+    let before = local_nanos();
+    let remote = match make_proxy_request_with_response(RemoteTimeRequest) {
+        Ok(Ok(remote)) => remote,
+        result => {
+            tracing::warn!(
+                ?result,
+                "Failed to fetch the remote time, the clock offset is not updated"
+            );
+            return;
+        }
+    };
+    let after = local_nanos();
+
+    let offset = remote.nanos_since_epoch as i128 - (before + after) / 2;
+    let offset = i64::try_from(offset).unwrap_or_default();
+    tracing::debug!(offset_nanos = offset, "Updated the remote clock offset");
+    OFFSET_NANOS.store(offset, Ordering::Relaxed);
+}
+
+/// Fetches the initial offset, called when the layer starts.
+pub(crate) fn init_offset() {
+    current_offset();
+}
+
+/// Returns the current offset, refreshing it first if [`REFRESH_INTERVAL_SECS`] has elapsed.
+///
+/// Only one thread refreshes the offset, others use the previous value in the meantime.
+fn current_offset() -> i64 {
+    let mut monotonic = timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    let now = match unsafe { FN_CLOCK_GETTIME(libc::CLOCK_MONOTONIC, &mut monotonic) } {
+        0 => monotonic.tv_sec,
+        _ => return OFFSET_NANOS.load(Ordering::Relaxed),
+    };
+
+    let refresh_at = REFRESH_AT.load(Ordering::Relaxed);
+    if now >= refresh_at
+        && REFRESH_AT
+            .compare_exchange(
+                refresh_at,
+                now + REFRESH_INTERVAL_SECS,
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+            )
+            .is_ok()
+    {
+        refresh_offset();
+    }
+
+    OFFSET_NANOS.load(Ordering::Relaxed)
+}
+
+/// Shifts the given `seconds` and `nanos` by `offset` nanoseconds.
+fn shift(seconds: i64, nanos: i64, offset: i64) -> (i64, i64) {
+    let nanos = nanos + offset % NANOS_PER_SEC;
+    let seconds = seconds + offset / NANOS_PER_SEC + nanos.div_euclid(NANOS_PER_SEC);
+    (seconds, nanos.rem_euclid(NANOS_PER_SEC))
+}
+
+// Field types of `timespec` and `timeval` differ between platforms.
+#[allow(clippy::unnecessary_cast)]
+fn shift_timespec(time: &mut timespec, offset: i64) {
+    let (seconds, nanos) = shift(time.tv_sec as i64, time.tv_nsec as i64, offset);
+    time.tv_sec = seconds as _;
+    time.tv_nsec = nanos as _;
+}
+
+#[allow(clippy::unnecessary_cast)]
+fn shift_timeval(time: &mut timeval, offset: i64) {
+    let (seconds, nanos) = shift(time.tv_sec as i64, time.tv_usec as i64 * 1000, offset);
+    time.tv_sec = seconds as _;
+    time.tv_usec = (nanos / 1000) as _;
+}
+
+/// ## Hook
+///
+/// Replaces [`libc::clock_gettime`], shifting only `CLOCK_REALTIME`.
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn clock_gettime_detour(
+    clock_id: clockid_t,
+    tp: *mut timespec,
+) -> c_int {
+    unsafe {
+        let result = FN_CLOCK_GETTIME(clock_id, tp);
+        if result == 0 && clock_id == libc::CLOCK_REALTIME && tp.is_null().not() {
+            shift_timespec(&mut *tp, current_offset());
+        }
+        result
+    }
+}
+
+/// ## Hook
+///
+/// Replaces [`libc::gettimeofday`].
+#[hook_guard_fn]
+pub(crate) unsafe extern "C" fn gettimeofday_detour(tp: *mut timeval, tz: *mut c_void) -> c_int {
+    unsafe {
+        let result = FN_GETTIMEOFDAY(tp, tz);
+        if result == 0 && tp.is_null().not() {
+            shift_timeval(&mut *tp, current_offset());
+        }
+        result
+    }
+}
+
+pub(crate) unsafe fn enable_time_hooks(hook_manager: &mut HookManager) {
+    unsafe {
+        replace!(
+            hook_manager,
+            "clock_gettime",
+            clock_gettime_detour,
+            FnClock_gettime,
+            FN_CLOCK_GETTIME
+        );
+        replace!(
+            hook_manager,
+            "gettimeofday",
+            gettimeofday_detour,
+            FnGettimeofday,
+            FN_GETTIMEOFDAY
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rstest::rstest;
+
+    use super::shift;
+
+    #[rstest]
+    #[case::forward((10, 500), 3_000_000_000, (13, 500))]
+    #[case::nanos_overflow((10, 999_999_999), 2, (11, 1))]
+    #[case::backward((10, 500), -1_000_000_000, (9, 500))]
+    #[case::nanos_underflow((10, 1), -2, (9, 999_999_999))]
+    #[case::zero((10, 500), 0, (10, 500))]
+    fn shift_by_offset(
+        #[case] time: (i64, i64),
+        #[case] offset: i64,
+        #[case] expected: (i64, i64),
+    ) {
+        assert_eq!(shift(time.0, time.1, offset), expected);
+    }
+}
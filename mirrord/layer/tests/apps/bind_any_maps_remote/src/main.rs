@@ -0,0 +1,23 @@
+#[cfg(target_family = "unix")]
+use std::{fs::File, net::TcpListener};
+
+#[cfg(target_family = "unix")]
+fn main() {
+    let first = TcpListener::bind("0.0.0.0:0").expect("first tcp listener bind");
+    let second = TcpListener::bind("0.0.0.0:0").expect("second tcp listener bind");
+
+    let first_port = first.local_addr().expect("first local addr").port();
+    let second_port = second.local_addr().expect("second local addr").port();
+    assert_ne!(first_port, 0);
+    assert_ne!(second_port, 0);
+    assert_ne!(first_port, second_port);
+
+    // trigger a trivial proxy message
+    File::open("/bind_any_maps_remote").expect("file open failed");
+}
+
+#[cfg(not(target_family = "unix"))]
+fn main() {
+    eprintln!("ERROR: test bind_any_maps_remote is not supported on non-Unix platforms");
+    std::process::exit(1);
+}
@@ -0,0 +1,49 @@
+#include <assert.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdlib.h>
+#include <string.h>
+#include <unistd.h>
+
+/// Test `chdir` into a remote directory:
+/// - changes into a directory that mirrord should handle remotely;
+/// - `getcwd` should report the remote directory, failing with `ERANGE` when the buffer is too
+///   small, and allocating the buffer when none is given;
+/// - opens a relative path, which should be resolved against the remote directory, and opened
+///   remotely;
+/// - changes into a directory that doesn't exist in the remote, which should fall back to a local
+///   `chdir`, after which `getcwd` reports the local directory.
+int main()
+{
+  int chdir_result = chdir("/chdir_test_dir");
+  assert(chdir_result == 0);
+
+  char cwd[256];
+  assert(getcwd(cwd, sizeof(cwd)) != NULL);
+  assert(strcmp(cwd, "/chdir_test_dir") == 0);
+
+  char small[4];
+  errno = 0;
+  assert(getcwd(small, sizeof(small)) == NULL);
+  assert(errno == ERANGE);
+
+  char *allocated = getcwd(NULL, 0);
+  assert(allocated != NULL);
+  assert(strcmp(allocated, "/chdir_test_dir") == 0);
+  free(allocated);
+
+  int fd = open("file", O_RDONLY);
+  assert(fd != -1);
+
+  int close_result = close(fd);
+  assert(close_result == 0);
+
+  int local_chdir_result = chdir("/var");
+  assert(local_chdir_result == 0);
+
+  char local_cwd[256];
+  assert(getcwd(local_cwd, sizeof(local_cwd)) != NULL);
+  assert(strcmp(local_cwd, "/chdir_test_dir") != 0);
+
+  return 0;
+}
@@ -0,0 +1,39 @@
+#define _GNU_SOURCE
+#include <stdio.h>
+
+#ifdef __linux__
+#include <assert.h>
+#include <fcntl.h>
+#include <unistd.h>
+
+/// Test `copy_file_range` between two remote files:
+/// - without offsets, which should be copied on the agent;
+/// - with offsets, which should be advanced by the amount copied (the agent in the test doesn't
+///   support the copy, so the layer reads and writes the data itself).
+int main()
+{
+  int fd_in = open("/app/copy_in.txt", O_RDONLY);
+  assert(fd_in >= 0);
+  int fd_out = open("/app/copy_out.txt", O_WRONLY | O_CREAT, 0644);
+  assert(fd_out >= 0);
+
+  assert(copy_file_range(fd_in, NULL, fd_out, NULL, 5, 0) == 5);
+
+  loff_t off_in = 7;
+  loff_t off_out = 5;
+  assert(copy_file_range(fd_in, &off_in, fd_out, &off_out, 5, 0) == 5);
+  assert(off_in == 12);
+  assert(off_out == 10);
+
+  close(fd_out);
+  close(fd_in);
+
+  return 0;
+}
+#else
+int main()
+{
+  printf("test copy_file_range is only supported on Linux\n");
+  return 1;
+}
+#endif
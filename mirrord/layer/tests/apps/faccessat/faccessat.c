@@ -0,0 +1,33 @@
+#define _GNU_SOURCE
+#include <stdio.h>
+
+#ifdef __linux__
+#include <assert.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <unistd.h>
+
+/// Test that `faccessat` agrees with `open` on remote files, whatever their local state:
+/// - `/app/remote_writable.txt` is writable remotely, so both the check and the open succeed;
+/// - `/app/remote_readonly.txt` is not writable remotely, so both fail with `EACCES`.
+int main()
+{
+  assert(faccessat(AT_FDCWD, "/app/remote_writable.txt", W_OK, AT_EACCESS) == 0);
+  int fd = open("/app/remote_writable.txt", O_WRONLY);
+  assert(fd >= 0);
+  close(fd);
+
+  assert(faccessat(AT_FDCWD, "/app/remote_readonly.txt", W_OK, AT_EACCESS | AT_SYMLINK_NOFOLLOW) == -1);
+  assert(errno == EACCES);
+  assert(open("/app/remote_readonly.txt", O_WRONLY) == -1);
+  assert(errno == EACCES);
+
+  return 0;
+}
+#else
+int main()
+{
+  printf("test faccessat is only supported on Linux\n");
+  return 1;
+}
+#endif
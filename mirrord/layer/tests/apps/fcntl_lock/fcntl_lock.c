@@ -0,0 +1,46 @@
+#include <assert.h>
+#include <errno.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <unistd.h>
+
+/// Test `fcntl` record locks on a remote file:
+/// - `F_SETLKW`, which should wait until the lock is released;
+/// - `F_GETLK`, which should report the conflicting lock;
+/// - `F_SETLK`, which should fail with `EAGAIN` when the lock is held;
+/// - `SEEK_CUR`, which should be counted from the offset of the file.
+int main()
+{
+  int fd = open("/app/locked.txt", O_RDWR);
+  assert(fd >= 0);
+
+  struct flock lock = {.l_type = F_WRLCK, .l_whence = SEEK_SET, .l_start = 0, .l_len = 10};
+  assert(fcntl(fd, F_SETLKW, &lock) == 0);
+
+  lock.l_type = F_RDLCK;
+  lock.l_len = 0;
+  assert(fcntl(fd, F_GETLK, &lock) == 0);
+  assert(lock.l_type == F_WRLCK);
+  assert(lock.l_whence == SEEK_SET);
+  assert(lock.l_start == 5);
+  assert(lock.l_len == 5);
+  assert(lock.l_pid == 42);
+
+  lock.l_type = F_UNLCK;
+  lock.l_start = 0;
+  lock.l_len = 10;
+  assert(fcntl(fd, F_SETLK, &lock) == 0);
+
+  lock.l_type = F_WRLCK;
+  assert(fcntl(fd, F_SETLK, &lock) == -1);
+  assert(errno == EAGAIN);
+
+  lock.l_whence = SEEK_CUR;
+  lock.l_start = 2;
+  lock.l_len = 3;
+  assert(fcntl(fd, F_SETLK, &lock) == 0);
+
+  assert(close(fd) == 0);
+
+  return 0;
+}
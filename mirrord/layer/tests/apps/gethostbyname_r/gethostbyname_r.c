@@ -0,0 +1,66 @@
+#include <stdio.h>
+
+#ifdef __linux__
+#include <arpa/inet.h>
+#include <errno.h>
+#include <netdb.h>
+#include <string.h>
+
+int check_address(const struct hostent *host, int family, const char expected[]) {
+  char address[INET6_ADDRSTRLEN];
+
+  if (host->h_addrtype != family || host->h_addr_list[0] == NULL || host->h_addr_list[1] != NULL) {
+    return 0;
+  }
+
+  inet_ntop(family, host->h_addr_list[0], address, sizeof(address));
+  return strcmp(host->h_name, "remote.host") == 0 && strcmp(address, expected) == 0;
+}
+
+int main(int argc, char *argv[]) {
+  struct hostent ret;
+  struct hostent *result = NULL;
+  int h_errnop = 0;
+  char small_buffer[8];
+  char buffer[1024];
+  int status;
+
+  printf("test gethostbyname_r: START\n");
+
+  // Too small buffer, the caller should retry with a bigger one.
+  status = gethostbyname_r("remote.host", &ret, small_buffer, sizeof(small_buffer), &result,
+                           &h_errnop);
+  if (status != ERANGE || result != NULL || h_errnop != NETDB_INTERNAL) {
+    printf("gethostbyname_r with small buffer: status %d, h_errno %d\n", status, h_errnop);
+    return 1;
+  }
+
+  status = gethostbyname_r("remote.host", &ret, buffer, sizeof(buffer), &result, &h_errnop);
+  if (status != 0 || result != &ret || !check_address(result, AF_INET, "93.184.216.34")) {
+    printf("gethostbyname_r: status %d, h_errno %d\n", status, h_errnop);
+    return 1;
+  }
+
+  status = gethostbyname2_r("remote.host", AF_INET6, &ret, buffer, sizeof(buffer), &result,
+                            &h_errnop);
+  if (status != 0 || result != &ret ||
+      !check_address(result, AF_INET6, "2606:2800:220:1:248:1893:25c8:1946")) {
+    printf("gethostbyname2_r: status %d, h_errno %d\n", status, h_errnop);
+    return 1;
+  }
+
+  status = gethostbyname_r("www.invalid.dev", &ret, buffer, sizeof(buffer), &result, &h_errnop);
+  if (status != 0 || result != NULL || h_errnop != HOST_NOT_FOUND) {
+    printf("gethostbyname_r of invalid host: status %d, h_errno %d\n", status, h_errnop);
+    return 1;
+  }
+
+  printf("test gethostbyname_r: SUCCESS\n");
+  return 0;
+}
+#else
+int main(int argc, char *argv[]) {
+  printf("test gethostbyname_r is only supported on Linux\n");
+  return 1;
+}
+#endif
@@ -0,0 +1,44 @@
+#include <stdio.h>
+
+#ifdef __linux__
+#include <assert.h>
+#include <fcntl.h>
+#include <string.h>
+#include <unistd.h>
+
+/// Reads `path` with `open` and `read`, and checks that it contains the remote hostname,
+/// followed by a single newline.
+void assert_remote_hostname(const char *path)
+{
+  char buf[256] = {0};
+
+  int fd = open(path, O_RDONLY);
+  assert(fd >= 0);
+
+  ssize_t read_amount = read(fd, buf, sizeof(buf) - 1);
+  assert(read_amount == (ssize_t)strlen("foobar\n"));
+  assert(strcmp(buf, "foobar\n") == 0);
+
+  assert(close(fd) == 0);
+}
+
+/// Test that the hostname files are served with the remote hostname (the same value that
+/// `gethostname` returns), when the fs feature is active.
+int main()
+{
+  assert_remote_hostname("/etc/hostname");
+  assert_remote_hostname("/proc/sys/kernel/hostname");
+
+  char hostname[256] = {0};
+  assert(gethostname(hostname, sizeof(hostname)) == 0);
+  assert(strcmp(hostname, "foobar") == 0);
+
+  return 0;
+}
+#else
+int main()
+{
+  printf("test hostname_files is only supported on Linux\n");
+  return 1;
+}
+#endif
@@ -0,0 +1,73 @@
+#include <assert.h>
+#include <errno.h>
+#include <ifaddrs.h>
+#include <net/if.h>
+#include <netinet/in.h>
+#include <stdio.h>
+#include <string.h>
+#include <sys/socket.h>
+
+/// Checks whether `getifaddrs` lists an IPv4 address for the interface `name`.
+int has_ipv4_address(struct ifaddrs *addresses, const char *name)
+{
+  for (struct ifaddrs *address = addresses; address != NULL; address = address->ifa_next)
+  {
+    if (address->ifa_addr != NULL && address->ifa_addr->sa_family == AF_INET &&
+        strcmp(address->ifa_name, name) == 0)
+    {
+      return 1;
+    }
+  }
+
+  return 0;
+}
+
+/// Test that `if_nametoindex` and `if_indextoname` are consistent with `getifaddrs` when
+/// `hide_ipv6_interfaces` is enabled:
+/// - `getifaddrs` doesn't list any IPv6 address;
+/// - interfaces with IPv4 addresses are resolved as usual;
+/// - hidden interfaces fail with `ENXIO` in both functions.
+int main()
+{
+  struct ifaddrs *addresses;
+  assert(getifaddrs(&addresses) == 0);
+
+  for (struct ifaddrs *address = addresses; address != NULL; address = address->ifa_next)
+  {
+    assert(address->ifa_addr == NULL || address->ifa_addr->sa_family != AF_INET6);
+  }
+
+  struct if_nameindex *interfaces = if_nameindex();
+  assert(interfaces != NULL);
+
+  for (struct if_nameindex *interface = interfaces; interface->if_index != 0; interface++)
+  {
+    char name[IF_NAMESIZE];
+
+    errno = 0;
+    unsigned int index = if_nametoindex(interface->if_name);
+
+    if (index == 0)
+    {
+      assert(errno == ENXIO);
+      assert(!has_ipv4_address(addresses, interface->if_name));
+
+      errno = 0;
+      assert(if_indextoname(interface->if_index, name) == NULL);
+      assert(errno == ENXIO);
+
+      printf("hidden interface: %s\n", interface->if_name);
+    }
+    else
+    {
+      assert(index == interface->if_index);
+      assert(if_indextoname(index, name) != NULL);
+      assert(strcmp(name, interface->if_name) == 0);
+    }
+  }
+
+  if_freenameindex(interfaces);
+  freeifaddrs(addresses);
+
+  return 0;
+}
@@ -0,0 +1,148 @@
+//! Sends 2 datagrams with a single `sendmmsg` through a connected UDP socket, and receives the
+//! echoes with a single `recvmmsg`, checking their contents and source addresses.
+//!
+//! Then sends 2 more datagrams with a single `sendmmsg` through an unconnected UDP socket, each
+//! with its own destination.
+
+#[cfg(target_os = "linux")]
+fn main() {
+    use std::{
+        mem,
+        net::{SocketAddr, SocketAddrV4},
+        os::fd::AsRawFd,
+        ptr,
+    };
+
+    use socket2::{Domain, SockAddr, Socket, Type};
+
+    let address: SocketAddr = "1.2.3.4:4367".parse().unwrap();
+
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, None).expect("Failed to create socket");
+    socket
+        .connect(&address.into())
+        .expect("Failed to connect to socket");
+
+    let mut outgoing = [b"first".to_vec(), b"second".to_vec()];
+    let mut outgoing_iovecs = outgoing
+        .iter_mut()
+        .map(|data| libc::iovec {
+            iov_base: data.as_mut_ptr().cast(),
+            iov_len: data.len(),
+        })
+        .collect::<Vec<_>>();
+    let mut outgoing_headers = outgoing_iovecs
+        .iter_mut()
+        .map(|iovec| {
+            let mut header: libc::mmsghdr = unsafe { mem::zeroed() };
+            header.msg_hdr.msg_iov = ptr::from_mut(iovec);
+            header.msg_hdr.msg_iovlen = 1;
+            header
+        })
+        .collect::<Vec<_>>();
+
+    let sent = unsafe {
+        libc::sendmmsg(
+            socket.as_raw_fd(),
+            outgoing_headers.as_mut_ptr(),
+            outgoing_headers.len() as _,
+            0,
+        )
+    };
+    assert_eq!(sent, 2, "sendmmsg failed");
+    for (header, data) in outgoing_headers.iter().zip(&outgoing) {
+        assert_eq!(header.msg_len as usize, data.len());
+    }
+
+    let mut incoming = [[0u8; 64]; 2];
+    let mut incoming_iovecs = incoming
+        .iter_mut()
+        .map(|data| libc::iovec {
+            iov_base: data.as_mut_ptr().cast(),
+            iov_len: data.len(),
+        })
+        .collect::<Vec<_>>();
+    let mut sources: [libc::sockaddr_in; 2] = unsafe { mem::zeroed() };
+    let mut incoming_headers = incoming_iovecs
+        .iter_mut()
+        .zip(sources.iter_mut())
+        .map(|(iovec, source)| {
+            let mut header: libc::mmsghdr = unsafe { mem::zeroed() };
+            header.msg_hdr.msg_iov = ptr::from_mut(iovec);
+            header.msg_hdr.msg_iovlen = 1;
+            header.msg_hdr.msg_name = ptr::from_mut(source).cast();
+            header.msg_hdr.msg_namelen = mem::size_of::<libc::sockaddr_in>() as _;
+            header
+        })
+        .collect::<Vec<_>>();
+
+    let received = unsafe {
+        libc::recvmmsg(
+            socket.as_raw_fd(),
+            incoming_headers.as_mut_ptr(),
+            incoming_headers.len() as _,
+            0,
+            ptr::null_mut(),
+        )
+    };
+    assert_eq!(received, 2, "recvmmsg failed");
+
+    let received = incoming_headers
+        .iter()
+        .zip(&incoming)
+        .map(|(header, data)| data.get(..header.msg_len as usize).unwrap().to_vec())
+        .collect::<Vec<_>>();
+    assert_eq!(received, outgoing);
+
+    for source in sources {
+        let source = SocketAddrV4::new(
+            u32::from_be(source.sin_addr.s_addr).into(),
+            u16::from_be(source.sin_port),
+        );
+        assert_eq!(SocketAddr::V4(source), address);
+    }
+
+    let dns_address: SocketAddr = "1.2.3.4:53".parse().unwrap();
+    let socket = Socket::new(Domain::IPV4, Type::DGRAM, None).expect("Failed to create socket");
+
+    let mut outgoing = [b"third".to_vec(), b"fourth".to_vec()];
+    let mut outgoing_iovecs = outgoing
+        .iter_mut()
+        .map(|data| libc::iovec {
+            iov_base: data.as_mut_ptr().cast(),
+            iov_len: data.len(),
+        })
+        .collect::<Vec<_>>();
+    // Separate address for each message, the layer may overwrite them.
+    let destinations = [SockAddr::from(dns_address), SockAddr::from(dns_address)];
+    let mut outgoing_headers = outgoing_iovecs
+        .iter_mut()
+        .zip(destinations.iter())
+        .map(|(iovec, destination)| {
+            let mut header: libc::mmsghdr = unsafe { mem::zeroed() };
+            header.msg_hdr.msg_iov = ptr::from_mut(iovec);
+            header.msg_hdr.msg_iovlen = 1;
+            header.msg_hdr.msg_name = destination.as_ptr().cast_mut().cast();
+            header.msg_hdr.msg_namelen = destination.len();
+            header
+        })
+        .collect::<Vec<_>>();
+
+    let sent = unsafe {
+        libc::sendmmsg(
+            socket.as_raw_fd(),
+            outgoing_headers.as_mut_ptr(),
+            outgoing_headers.len() as _,
+            0,
+        )
+    };
+    assert_eq!(sent, 2, "sendmmsg with destinations failed");
+    for (header, data) in outgoing_headers.iter().zip(&outgoing) {
+        assert_eq!(header.msg_len as usize, data.len());
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn main() {
+    eprintln!("ERROR: test mmsg is only supported on Linux");
+    std::process::exit(1);
+}
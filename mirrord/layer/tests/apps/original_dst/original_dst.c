@@ -0,0 +1,69 @@
+#include <stdio.h>
+
+#ifdef __linux__
+#include <arpa/inet.h>
+#include <assert.h>
+#include <linux/netfilter_ipv4.h>
+#include <netinet/in.h>
+#include <stdlib.h>
+#include <string.h>
+#include <sys/socket.h>
+#include <unistd.h>
+
+/// Test `getsockopt(SO_ORIGINAL_DST)` on a connection accepted from a port subscription.
+///
+/// The connection comes from the internal proxy, but the option should give back the address the
+/// connection was headed to in the cluster (`1.1.1.1:80` in the fake agent), also when the
+/// listener's port (`LISTEN_PORT`, 80 by default) is mapped to port 80 with `port_mapping`. Other
+/// options should be handled by libc as usual.
+int main()
+{
+  const char *listen_port = getenv("LISTEN_PORT");
+
+  int listener = socket(AF_INET, SOCK_STREAM, 0);
+  assert(listener >= 0);
+
+  struct sockaddr_in address = {0};
+  address.sin_family = AF_INET;
+  address.sin_port = htons(listen_port ? atoi(listen_port) : 80);
+  address.sin_addr.s_addr = htonl(INADDR_ANY);
+
+  assert(bind(listener, (struct sockaddr *)&address, sizeof(address)) == 0);
+  assert(listen(listener, 1) == 0);
+
+  int connection = accept(listener, NULL, NULL);
+  assert(connection >= 0);
+
+  struct sockaddr_in original_dst = {0};
+  socklen_t original_dst_len = sizeof(original_dst);
+  assert(getsockopt(connection, SOL_IP, SO_ORIGINAL_DST, &original_dst, &original_dst_len) == 0);
+  assert(original_dst_len == sizeof(original_dst));
+  assert(original_dst.sin_family == AF_INET);
+  assert(ntohs(original_dst.sin_port) == 80);
+
+  char ip[INET_ADDRSTRLEN];
+  assert(inet_ntop(AF_INET, &original_dst.sin_addr, ip, sizeof(ip)) != NULL);
+  assert(strcmp(ip, "1.1.1.1") == 0);
+
+  int type = 0;
+  socklen_t type_len = sizeof(type);
+  assert(getsockopt(connection, SOL_SOCKET, SO_TYPE, &type, &type_len) == 0);
+  assert(type == SOCK_STREAM);
+
+  char buffer[64];
+  while (read(connection, buffer, sizeof(buffer)) > 0)
+  {
+  }
+
+  close(connection);
+  close(listener);
+
+  return 0;
+}
+#else
+int main()
+{
+  printf("test original_dst is only supported on Linux\n");
+  return 1;
+}
+#endif
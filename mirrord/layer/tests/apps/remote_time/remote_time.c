@@ -0,0 +1,44 @@
+#include <stdio.h>
+
+#ifdef __linux__
+#include <assert.h>
+#include <sys/syscall.h>
+#include <sys/time.h>
+#include <time.h>
+#include <unistd.h>
+
+/// Test the remote time offset, with the remote clock one hour ahead of the local one.
+///
+/// Clocks read through libc are compared with clocks read with direct syscalls, which are not
+/// affected by mirrord:
+/// - `CLOCK_REALTIME` and `gettimeofday` should be shifted by the offset;
+/// - `CLOCK_MONOTONIC` should be left untouched.
+int main()
+{
+  struct timespec raw, shifted;
+
+  assert(syscall(SYS_clock_gettime, CLOCK_REALTIME, &raw) == 0);
+  assert(clock_gettime(CLOCK_REALTIME, &shifted) == 0);
+  long diff = shifted.tv_sec - raw.tv_sec;
+  assert(diff >= 3599 && diff <= 3601);
+
+  struct timeval shifted_tv;
+  assert(syscall(SYS_clock_gettime, CLOCK_REALTIME, &raw) == 0);
+  assert(gettimeofday(&shifted_tv, NULL) == 0);
+  diff = shifted_tv.tv_sec - raw.tv_sec;
+  assert(diff >= 3599 && diff <= 3601);
+
+  assert(syscall(SYS_clock_gettime, CLOCK_MONOTONIC, &raw) == 0);
+  assert(clock_gettime(CLOCK_MONOTONIC, &shifted) == 0);
+  diff = shifted.tv_sec - raw.tv_sec;
+  assert(diff >= 0 && diff <= 1);
+
+  return 0;
+}
+#else
+int main()
+{
+  printf("test remote_time is only supported on Linux\n");
+  return 1;
+}
+#endif
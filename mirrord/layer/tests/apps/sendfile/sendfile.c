@@ -0,0 +1,46 @@
+#include <stdio.h>
+
+#ifdef __linux__
+#include <assert.h>
+#include <fcntl.h>
+#include <string.h>
+#include <sys/sendfile.h>
+#include <unistd.h>
+
+/// Test `sendfile` from a remote file into a local pipe:
+/// - with an offset, which should be advanced by the amount sent;
+/// - without an offset, which should use and advance the remote file position, stopping at EOF
+///   when `count` is larger than what's left in the file.
+int main()
+{
+  int fd = open("/sendfile_test_file", O_RDONLY);
+  assert(fd >= 0);
+
+  int pipe_fds[2];
+  assert(pipe(pipe_fds) == 0);
+
+  char buffer[64];
+
+  off_t offset = 2;
+  assert(sendfile(pipe_fds[1], fd, &offset, 5) == 5);
+  assert(offset == 7);
+  assert(read(pipe_fds[0], buffer, sizeof(buffer)) == 5);
+  assert(memcmp(buffer, "llo, ", 5) == 0);
+
+  assert(sendfile(pipe_fds[1], fd, NULL, 100) == 12);
+  assert(read(pipe_fds[0], buffer, sizeof(buffer)) == 12);
+  assert(memcmp(buffer, "hello, world", 12) == 0);
+
+  close(pipe_fds[0]);
+  close(pipe_fds[1]);
+  close(fd);
+
+  return 0;
+}
+#else
+int main()
+{
+  printf("test sendfile is only supported on Linux\n");
+  return 1;
+}
+#endif
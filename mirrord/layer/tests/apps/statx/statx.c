@@ -0,0 +1,46 @@
+#define _GNU_SOURCE
+#include <stdio.h>
+
+#ifdef __linux__
+#include <assert.h>
+#include <fcntl.h>
+#include <sys/stat.h>
+#include <unistd.h>
+
+/// Test `statx` on a remote-only file:
+/// - with a path, following symlinks;
+/// - with a path and `AT_SYMLINK_NOFOLLOW`;
+/// - with a remote fd and `AT_EMPTY_PATH`.
+///
+/// `stx_btime` is not available remotely, so `STATX_BTIME` should be cleared from `stx_mask`.
+int main()
+{
+  struct statx buf;
+
+  assert(statx(AT_FDCWD, "/statx_test_file", 0, STATX_BASIC_STATS | STATX_BTIME, &buf) == 0);
+  assert((buf.stx_mask & STATX_BASIC_STATS) == STATX_BASIC_STATS);
+  assert((buf.stx_mask & STATX_BTIME) == 0);
+  assert(buf.stx_size == 12);
+  assert(buf.stx_uid == 1000);
+  assert(S_ISREG(buf.stx_mode));
+
+  assert(statx(AT_FDCWD, "/statx_test_file", AT_SYMLINK_NOFOLLOW, STATX_BASIC_STATS, &buf) == 0);
+  assert(S_ISLNK(buf.stx_mode));
+
+  int fd = open("/statx_test_file", O_RDONLY);
+  assert(fd >= 0);
+
+  assert(statx(fd, "", AT_EMPTY_PATH, STATX_SIZE, &buf) == 0);
+  assert(buf.stx_size == 12);
+
+  close(fd);
+
+  return 0;
+}
+#else
+int main()
+{
+  printf("test statx is only supported on Linux\n");
+  return 1;
+}
+#endif
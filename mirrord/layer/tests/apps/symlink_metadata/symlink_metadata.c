@@ -0,0 +1,24 @@
+#define _GNU_SOURCE
+#include <assert.h>
+#include <fcntl.h>
+#include <stdio.h>
+#include <sys/stat.h>
+#include <sys/time.h>
+#include <unistd.h>
+
+/// Test changing the metadata of a remote symlink itself, without following it:
+/// - `lutimes` and `utimensat` with `AT_SYMLINK_NOFOLLOW`;
+/// - `lchown` and `fchownat` with `AT_SYMLINK_NOFOLLOW`.
+int main()
+{
+  struct timeval timevals[2] = {{.tv_sec = 1, .tv_usec = 2}, {.tv_sec = 3, .tv_usec = 4}};
+  assert(lutimes("/app/link", timevals) == 0);
+
+  struct timespec timespecs[2] = {{.tv_sec = 5, .tv_nsec = 6}, {.tv_sec = 7, .tv_nsec = 8}};
+  assert(utimensat(AT_FDCWD, "/app/link", timespecs, AT_SYMLINK_NOFOLLOW) == 0);
+
+  assert(lchown("/app/link", 1000, 1001) == 0);
+  assert(fchownat(AT_FDCWD, "/app/link", 1002, 1003, AT_SYMLINK_NOFOLLOW) == 0);
+
+  return 0;
+}
@@ -0,0 +1,38 @@
+#define _GNU_SOURCE
+#include <stdio.h>
+
+#ifdef __linux__
+#include <assert.h>
+#include <fcntl.h>
+#include <sys/uio.h>
+#include <unistd.h>
+
+/// Test `writev` and `pwritev` to a remote file:
+/// - `writev` should send all the buffers in a single request (the agent in the test doesn't
+///   support it on the second call, so the layer writes the concatenated buffers itself);
+/// - `pwritev` should write the concatenated buffers at the given offset.
+int main()
+{
+  int fd = open("/app/writev.txt", O_WRONLY | O_CREAT, 0644);
+  assert(fd >= 0);
+
+  struct iovec iov[2] = {
+      {.iov_base = "hello ", .iov_len = 6},
+      {.iov_base = "world", .iov_len = 5},
+  };
+
+  assert(writev(fd, iov, 2) == 11);
+  assert(writev(fd, iov, 2) == 11);
+  assert(pwritev(fd, iov, 2, 20) == 11);
+
+  close(fd);
+
+  return 0;
+}
+#else
+int main()
+{
+  printf("test writev is only supported on Linux\n");
+  return 1;
+}
+#endif
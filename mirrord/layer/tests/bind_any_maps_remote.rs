@@ -0,0 +1,126 @@
+#![cfg(target_family = "unix")]
+
+use rstest::rstest;
+
+mod common;
+
+use std::{io::Write, path::Path, time::Duration};
+
+pub use common::*;
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, FileRequest, FileResponse,
+    file::OpenFileResponse,
+    tcp::{DaemonTcp, LayerTcpSteal, StealType},
+};
+
+/// Two listeners bound to port `0` should subscribe to distinct remote ports from
+/// `feature.network.incoming.bind_any_maps_remote`, in the configured order.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(15))]
+async fn bind_any_maps_remote(dylib_path: &Path) {
+    let config = serde_json::json!({
+        "target": "pod/real-pod",
+        "feature": {
+            "network": {
+                "incoming": {
+                    "mode": "steal",
+                    "bind_any_maps_remote": [80, 8080]
+                }
+            }
+        }
+    });
+    let mut config_file = tempfile::NamedTempFile::with_suffix(".json").unwrap();
+    config_file
+        .as_file_mut()
+        .write_all(serde_json::to_string(&config).unwrap().as_bytes())
+        .unwrap();
+
+    let (mut test_process, mut intproxy) = Application::BindAnyMapsRemote
+        .start_process_with_layer(dylib_path, vec![], Some(config_file.path()))
+        .await;
+
+    for expected_port in [80, 8080] {
+        let ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(StealType::All(port))) =
+            intproxy.recv().await
+        else {
+            panic!("no port subscribe request")
+        };
+        assert_eq!(port, expected_port);
+
+        intproxy
+            .send(DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Ok(
+                port,
+            ))))
+            .await;
+    }
+
+    let ClientMessage::FileRequest(FileRequest::Open(file_request)) = intproxy.recv().await else {
+        panic!("unexpected client message")
+    };
+    assert_eq!(file_request.path.to_str().unwrap(), "/bind_any_maps_remote");
+
+    intproxy
+        .send(DaemonMessage::File(FileResponse::Open(Ok(
+            OpenFileResponse { fd: 1 },
+        ))))
+        .await;
+
+    test_process.wait_assert_success().await;
+}
+
+/// Ports from `feature.network.incoming.bind_any_maps_remote` that are not in the
+/// `feature.network.incoming.ports` whitelist are skipped. The second listener remains local, as
+/// the only whitelisted port is taken by the first one.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(15))]
+async fn bind_any_maps_remote_whitelist(dylib_path: &Path) {
+    let config = serde_json::json!({
+        "target": "pod/real-pod",
+        "feature": {
+            "network": {
+                "incoming": {
+                    "mode": "steal",
+                    "ports": [8080],
+                    "bind_any_maps_remote": [80, 8080, 9090]
+                }
+            }
+        }
+    });
+    let mut config_file = tempfile::NamedTempFile::with_suffix(".json").unwrap();
+    config_file
+        .as_file_mut()
+        .write_all(serde_json::to_string(&config).unwrap().as_bytes())
+        .unwrap();
+
+    let (mut test_process, mut intproxy) = Application::BindAnyMapsRemote
+        .start_process_with_layer(dylib_path, vec![], Some(config_file.path()))
+        .await;
+
+    let ClientMessage::TcpSteal(LayerTcpSteal::PortSubscribe(StealType::All(port))) =
+        intproxy.recv().await
+    else {
+        panic!("no port subscribe request")
+    };
+    assert_eq!(port, 8080);
+
+    intproxy
+        .send(DaemonMessage::TcpSteal(DaemonTcp::SubscribeResult(Ok(
+            port,
+        ))))
+        .await;
+
+    let ClientMessage::FileRequest(FileRequest::Open(file_request)) = intproxy.recv().await else {
+        panic!("unexpected client message")
+    };
+    assert_eq!(file_request.path.to_str().unwrap(), "/bind_any_maps_remote");
+
+    intproxy
+        .send(DaemonMessage::File(FileResponse::Open(Ok(
+            OpenFileResponse { fd: 1 },
+        ))))
+        .await;
+
+    test_process.wait_assert_success().await;
+}
@@ -0,0 +1,49 @@
+#![cfg(target_family = "unix")]
+
+use std::{path::Path, time::Duration};
+
+use mirrord_protocol::file::MetadataInternal;
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Test for the [`libc::chdir`] and [`libc::getcwd`] hooks: after changing into a remote
+/// directory, relative paths are resolved against it, and changing into a directory that doesn't
+/// exist in the remote falls back to a local `chdir`.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn chdir(dylib_path: &Path) {
+    let application = Application::CChdir;
+
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(dylib_path, Default::default(), None)
+        .await;
+
+    println!("waiting for XstatRequest.");
+    intproxy
+        .expect_xstat_with_metadata(
+            Some("/chdir_test_dir".into()),
+            None,
+            MetadataInternal {
+                mode: libc::S_IFDIR as u32 | 0o755,
+                ..Default::default()
+            },
+        )
+        .await;
+
+    println!("waiting for OpenFileRequest.");
+    intproxy
+        .expect_file_open_for_reading("/chdir_test_dir/file", 1)
+        .await;
+
+    intproxy.expect_file_close(1).await;
+
+    println!("waiting for XstatRequest of the local directory.");
+    intproxy.expect_xstat_not_found("/var".into()).await;
+
+    test_process.wait_assert_success().await;
+    test_process.assert_no_error_in_stderr().await;
+    test_process.assert_no_error_in_stdout().await;
+}
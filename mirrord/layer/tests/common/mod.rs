@@ -153,13 +153,41 @@ pub enum Application {
     RustIssue2001,
     RustDnsResolve,
     RustRecvFrom,
+    /// Rust app that batches UDP datagrams with `sendmmsg` and `recvmmsg`.
+    RustMmsg,
     RustListenPorts,
     Fork,
     ReadLink,
     StatfsFstatfs,
     MkdirRmdir,
+    /// C app that `chdir`s into a remote directory and opens a relative path.
+    CChdir,
+    /// C app that `sendfile`s a remote file into a pipe.
+    CSendfile,
+    /// C app that calls `statx` on a remote file, by path and by fd.
+    CStatx,
+    /// C app that checks remote files with `faccessat` before opening them for writing.
+    CFaccessat,
+    /// C app that copies between two remote files with `copy_file_range`.
+    CCopyFileRange,
+    /// C app that writes multiple buffers to a remote file with `writev` and `pwritev`.
+    CWritev,
+    /// C app that changes the times and the owner of a remote symlink, without following it.
+    CSymlinkMetadata,
+    /// C app that takes `fcntl` record locks on a remote file.
+    CFcntlLock,
+    /// C app that resolves interfaces with `if_nametoindex` and `if_indextoname`.
+    CIfNameToIndex,
+    /// C app that compares clocks read through libc with clocks read with direct syscalls.
+    CRemoteTime,
+    /// C app that reads the hostname files, which are served with the remote hostname.
+    CHostnameFiles,
+    /// C app that reads `SO_ORIGINAL_DST` of an accepted connection.
+    COriginalDst,
     OpenFile,
     CIssue2055,
+    /// C app that calls glibc's reentrant `gethostbyname_r` and `gethostbyname2_r`.
+    CGethostbynameR,
     CIssue2178,
     RustIssue2058,
     Realpath,
@@ -193,6 +221,8 @@ pub enum Application {
     DupListen,
     /// Rust app that listens on a socket twice
     DoubleListen,
+    /// Rust app that binds two listeners to port `0`.
+    BindAnyMapsRemote,
 }
 
 impl Application {
@@ -227,6 +257,22 @@ impl Application {
             Application::ReadLink => String::from("tests/apps/readlink/out.c_test_app"),
             Application::StatfsFstatfs => String::from("tests/apps/statfs_fstatfs/out.c_test_app"),
             Application::MkdirRmdir => String::from("tests/apps/mkdir_rmdir/out.c_test_app"),
+            Application::CChdir => String::from("tests/apps/chdir/out.c_test_app"),
+            Application::CSendfile => String::from("tests/apps/sendfile/out.c_test_app"),
+            Application::CStatx => String::from("tests/apps/statx/out.c_test_app"),
+            Application::CFaccessat => String::from("tests/apps/faccessat/out.c_test_app"),
+            Application::CCopyFileRange => {
+                String::from("tests/apps/copy_file_range/out.c_test_app")
+            }
+            Application::CWritev => String::from("tests/apps/writev/out.c_test_app"),
+            Application::CSymlinkMetadata => {
+                String::from("tests/apps/symlink_metadata/out.c_test_app")
+            }
+            Application::CFcntlLock => String::from("tests/apps/fcntl_lock/out.c_test_app"),
+            Application::COriginalDst => String::from("tests/apps/original_dst/out.c_test_app"),
+            Application::CIfNameToIndex => String::from("tests/apps/if_nametoindex/out.c_test_app"),
+            Application::CRemoteTime => String::from("tests/apps/remote_time/out.c_test_app"),
+            Application::CHostnameFiles => String::from("tests/apps/hostname_files/out.c_test_app"),
             Application::Realpath => String::from("tests/apps/realpath/out.c_test_app"),
             Application::NodeHTTP
             | Application::NodeIssue2283
@@ -293,6 +339,13 @@ impl Application {
                     "../../target/debug/recv_from"
                 )
             }
+            Application::RustMmsg => {
+                format!(
+                    "{}/{}",
+                    env!("CARGO_MANIFEST_DIR"),
+                    "../../target/debug/mmsg"
+                )
+            }
             Application::RustListenPorts => {
                 format!(
                     "{}/{}",
@@ -359,6 +412,11 @@ impl Application {
                 env!("CARGO_MANIFEST_DIR"),
                 "tests/apps/gethostbyname/out.c_test_app",
             ),
+            Application::CGethostbynameR => format!(
+                "{}/{}",
+                env!("CARGO_MANIFEST_DIR"),
+                "tests/apps/gethostbyname_r/out.c_test_app",
+            ),
             Application::CIssue2178 => format!(
                 "{}/{}",
                 env!("CARGO_MANIFEST_DIR"),
@@ -389,6 +447,13 @@ impl Application {
                     "../../target/debug/double_listen"
                 )
             }
+            Application::BindAnyMapsRemote => {
+                format!(
+                    "{}/{}",
+                    env!("CARGO_MANIFEST_DIR"),
+                    "../../target/debug/bind_any_maps_remote"
+                )
+            }
         }
     }
 
@@ -482,6 +547,17 @@ impl Application {
             | Application::ReadLink
             | Application::StatfsFstatfs
             | Application::MkdirRmdir
+            | Application::CChdir
+            | Application::CSendfile
+            | Application::CStatx
+            | Application::CFaccessat
+            | Application::CCopyFileRange
+            | Application::CWritev
+            | Application::CSymlinkMetadata
+            | Application::CFcntlLock
+            | Application::CIfNameToIndex
+            | Application::CRemoteTime
+            | Application::CHostnameFiles
             | Application::Realpath
             | Application::RustFileOps
             | Application::RustIssue1123
@@ -494,6 +570,7 @@ impl Application {
             | Application::RustIssue2001
             | Application::RustDnsResolve
             | Application::RustRecvFrom
+            | Application::RustMmsg
             | Application::RustListenPorts
             | Application::EnvBashCat
             | Application::BashShebang
@@ -502,6 +579,7 @@ impl Application {
             | Application::RustIssue2058
             | Application::OpenFile
             | Application::CIssue2055
+            | Application::CGethostbynameR
             | Application::CIssue2178
             | Application::RustIssue2204
             | Application::RustRebind0
@@ -511,6 +589,8 @@ impl Application {
             | Application::DlopenCgo
             | Application::Connectx
             | Application::DoubleListen
+            | Application::BindAnyMapsRemote
+            | Application::COriginalDst
             | Application::DupListen => vec![],
             Application::RustOutgoingUdp => ["--udp", RUST_OUTGOING_LOCAL, RUST_OUTGOING_PEERS]
                 .into_iter()
@@ -554,6 +634,7 @@ impl Application {
             | Application::NodeHTTP
             | Application::RustIssue1054
             | Application::PythonFlaskHTTP
+            | Application::COriginalDst
             | Application::DupListen => 80,
             // mapped from 9999 in `configs/port_mapping.json`
             Application::PythonFastApiHTTP | Application::PythonIssue864 => 1234,
@@ -574,6 +655,17 @@ impl Application {
             | Application::ReadLink
             | Application::StatfsFstatfs
             | Application::MkdirRmdir
+            | Application::CChdir
+            | Application::CSendfile
+            | Application::CStatx
+            | Application::CFaccessat
+            | Application::CCopyFileRange
+            | Application::CWritev
+            | Application::CSymlinkMetadata
+            | Application::CFcntlLock
+            | Application::CIfNameToIndex
+            | Application::CRemoteTime
+            | Application::CHostnameFiles
             | Application::Realpath
             | Application::GoIssue834(..)
             | Application::GoRead(..)
@@ -593,8 +685,10 @@ impl Application {
             | Application::RustIssue2001
             | Application::RustListenPorts
             | Application::RustRecvFrom
+            | Application::RustMmsg
             | Application::OpenFile
             | Application::CIssue2055
+            | Application::CGethostbynameR
             | Application::CIssue2178
             | Application::NodeIssue2283
             | Application::RustIssue2204
@@ -607,6 +701,7 @@ impl Application {
             | Application::GoIssue2988(..)
             | Application::NodeMakeConnections
             | Application::DoubleListen
+            | Application::BindAnyMapsRemote
             | Application::Connectx => unimplemented!("shouldn't get here"),
             Application::PythonSelfConnect => 1337,
             Application::RustIssue2058 => 1234,
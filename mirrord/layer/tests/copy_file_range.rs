@@ -0,0 +1,120 @@
+#![cfg(target_os = "linux")]
+
+use std::{path::Path, time::Duration};
+
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, FileRequest, FileResponse, ResponseError,
+    file::{
+        CopyFileRangeRequest, CopyFileRangeResponse, OpenOptionsInternal, ReadFileResponse,
+        ReadLimitedFileRequest, WriteFileResponse, WriteLimitedFileRequest,
+    },
+};
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Test for the [`libc::copy_file_range`] hook: a copy between two remote files is sent to the
+/// agent with a [`CopyFileRangeRequest`], and done with [`ReadLimitedFileRequest`]s and
+/// [`WriteLimitedFileRequest`]s when the agent doesn't support it.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn copy_file_range(dylib_path: &Path) {
+    let application = Application::CCopyFileRange;
+
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(
+            dylib_path,
+            vec![("MIRRORD_FILE_READ_WRITE_PATTERN", "^/app/copy_")],
+            None,
+        )
+        .await;
+
+    intproxy
+        .expect_file_open_for_reading("/app/copy_in.txt", 1)
+        .await;
+    intproxy
+        .expect_file_open_with_options(
+            "/app/copy_out.txt",
+            2,
+            OpenOptionsInternal {
+                write: true,
+                create: true,
+                ..Default::default()
+            },
+        )
+        .await;
+
+    // Copied on the agent.
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::FileRequest(FileRequest::CopyFileRange(CopyFileRangeRequest {
+            fd_in: 1,
+            off_in: None,
+            fd_out: 2,
+            off_out: None,
+            len: 5,
+        }))
+    );
+    intproxy
+        .send(DaemonMessage::File(FileResponse::CopyFileRange(Ok(
+            CopyFileRangeResponse { copied: 5 },
+        ))))
+        .await;
+
+    // Not supported by the agent, copied by the layer.
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::FileRequest(FileRequest::CopyFileRange(CopyFileRangeRequest {
+            fd_in: 1,
+            off_in: Some(7),
+            fd_out: 2,
+            off_out: Some(5),
+            len: 5,
+        }))
+    );
+    intproxy
+        .send(DaemonMessage::File(FileResponse::CopyFileRange(Err(
+            ResponseError::NotImplemented,
+        ))))
+        .await;
+
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::FileRequest(FileRequest::ReadLimited(ReadLimitedFileRequest {
+            remote_fd: 1,
+            buffer_size: 5,
+            start_from: 7,
+        }))
+    );
+    intproxy
+        .send(DaemonMessage::File(FileResponse::ReadLimited(Ok(
+            ReadFileResponse {
+                bytes: b"world".to_vec().into(),
+                read_amount: 5,
+            },
+        ))))
+        .await;
+
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::FileRequest(FileRequest::WriteLimited(WriteLimitedFileRequest {
+            remote_fd: 2,
+            write_bytes: b"world".to_vec().into(),
+            start_from: 5,
+        }))
+    );
+    intproxy
+        .send(DaemonMessage::File(FileResponse::WriteLimited(Ok(
+            WriteFileResponse { written_amount: 5 },
+        ))))
+        .await;
+
+    intproxy.expect_file_close(2).await;
+    intproxy.expect_file_close(1).await;
+
+    test_process.wait_assert_success().await;
+    test_process.assert_no_error_in_stderr().await;
+    test_process.assert_no_error_in_stdout().await;
+}
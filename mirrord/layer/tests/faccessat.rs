@@ -0,0 +1,92 @@
+#![cfg(target_os = "linux")]
+
+use std::{io, path::Path, time::Duration};
+
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, FileRequest, FileResponse, ResponseError,
+    file::{
+        AccessFileRequestV2, AccessFileResponse, AccessFlagsInternal, OpenFileRequest,
+        OpenOptionsInternal,
+    },
+};
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Test for the [`libc::faccessat`] hook, with `AT_EACCESS` and `AT_SYMLINK_NOFOLLOW`.
+///
+/// The access checks are answered like the remote files are, and the app asserts that the opens
+/// that follow them have the same outcome.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn faccessat(dylib_path: &Path) {
+    let application = Application::CFaccessat;
+
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(
+            dylib_path,
+            vec![
+                ("MIRRORD_FILE_MODE", "localwithoverrides"),
+                ("MIRRORD_FILE_READ_WRITE_PATTERN", "^/app/remote_"),
+            ],
+            None,
+        )
+        .await;
+
+    let write_only = OpenOptionsInternal {
+        write: true,
+        ..Default::default()
+    };
+
+    intproxy
+        .expect_file_access_v2(
+            AccessFileRequestV2 {
+                pathname: "/app/remote_writable.txt".into(),
+                mode: libc::W_OK as u8,
+                flags: AccessFlagsInternal {
+                    effective_ids: true,
+                    nofollow: false,
+                },
+            },
+            Ok(AccessFileResponse),
+        )
+        .await;
+    intproxy
+        .expect_file_open_with_options("/app/remote_writable.txt", 1, write_only)
+        .await;
+    intproxy.expect_file_close(1).await;
+
+    intproxy
+        .expect_file_access_v2(
+            AccessFileRequestV2 {
+                pathname: "/app/remote_readonly.txt".into(),
+                mode: libc::W_OK as u8,
+                flags: AccessFlagsInternal {
+                    effective_ids: true,
+                    nofollow: true,
+                },
+            },
+            Err(ResponseError::from(io::Error::from_raw_os_error(
+                libc::EACCES,
+            ))),
+        )
+        .await;
+    assert_eq!(
+        intproxy.consume_xstats().await,
+        ClientMessage::FileRequest(FileRequest::Open(OpenFileRequest {
+            path: "/app/remote_readonly.txt".into(),
+            open_options: write_only,
+        }))
+    );
+    intproxy
+        .send(DaemonMessage::File(FileResponse::Open(Err(
+            ResponseError::from(io::Error::from_raw_os_error(libc::EACCES)),
+        ))))
+        .await;
+
+    test_process.wait_assert_success().await;
+    test_process.assert_no_error_in_stderr().await;
+    test_process.assert_no_error_in_stdout().await;
+}
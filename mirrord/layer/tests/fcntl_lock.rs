@@ -0,0 +1,166 @@
+#![cfg(target_family = "unix")]
+
+use std::{path::Path, time::Duration};
+
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, FileRequest, FileResponse, ResponseError,
+    file::{
+        FcntlLock, FcntlLockCommand, FcntlLockRequest, FcntlLockResponse, FcntlLockType,
+        FcntlLockWhence, OpenOptionsInternal, SeekFileRequest, SeekFileResponse, SeekFromInternal,
+    },
+};
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Answers the next [`FcntlLockRequest`], checking its parameters.
+async fn expect_fcntl_lock(
+    intproxy: &mut TestIntProxy,
+    command: FcntlLockCommand,
+    lock_type: FcntlLockType,
+    len: i64,
+    response: Result<FcntlLockResponse, ResponseError>,
+) {
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::FileRequest(FileRequest::FcntlLock(FcntlLockRequest {
+            fd: 1,
+            command,
+            lock: FcntlLock {
+                lock_type,
+                whence: FcntlLockWhence::Start,
+                start: 0,
+                len,
+                pid: 0,
+            },
+        }))
+    );
+    intproxy
+        .send(DaemonMessage::File(FileResponse::FcntlLock(response)))
+        .await;
+}
+
+/// Test for the record lock commands of the `fcntl` hook on a remote file, which are sent to the
+/// agent with [`FcntlLockRequest`]s.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn fcntl_lock(dylib_path: &Path) {
+    let application = Application::CFcntlLock;
+
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(
+            dylib_path,
+            vec![("MIRRORD_FILE_READ_WRITE_PATTERN", "^/app/locked.txt$")],
+            None,
+        )
+        .await;
+
+    intproxy
+        .expect_file_open_with_options(
+            "/app/locked.txt",
+            1,
+            OpenOptionsInternal {
+                read: true,
+                write: true,
+                ..Default::default()
+            },
+        )
+        .await;
+
+    let unlocked = Ok(FcntlLockResponse { conflicting: None });
+
+    // `F_SETLKW` retries until the lock is free.
+    expect_fcntl_lock(
+        &mut intproxy,
+        FcntlLockCommand::Set,
+        FcntlLockType::Write,
+        10,
+        Err(ResponseError::LockWouldBlock),
+    )
+    .await;
+    expect_fcntl_lock(
+        &mut intproxy,
+        FcntlLockCommand::Set,
+        FcntlLockType::Write,
+        10,
+        unlocked.clone(),
+    )
+    .await;
+
+    expect_fcntl_lock(
+        &mut intproxy,
+        FcntlLockCommand::Get,
+        FcntlLockType::Read,
+        0,
+        Ok(FcntlLockResponse {
+            conflicting: Some(FcntlLock {
+                lock_type: FcntlLockType::Write,
+                whence: FcntlLockWhence::Start,
+                start: 5,
+                len: 5,
+                pid: 42,
+            }),
+        }),
+    )
+    .await;
+
+    expect_fcntl_lock(
+        &mut intproxy,
+        FcntlLockCommand::Set,
+        FcntlLockType::Unlock,
+        10,
+        unlocked,
+    )
+    .await;
+
+    // `F_SETLK` does not retry.
+    expect_fcntl_lock(
+        &mut intproxy,
+        FcntlLockCommand::Set,
+        FcntlLockType::Write,
+        10,
+        Err(ResponseError::LockWouldBlock),
+    )
+    .await;
+
+    // `SEEK_CUR` is resolved with the offset from the intproxy, which may buffer the file.
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::FileRequest(FileRequest::Seek(SeekFileRequest {
+            fd: 1,
+            seek_from: SeekFromInternal::Current(0),
+        }))
+    );
+    intproxy
+        .send(DaemonMessage::File(FileResponse::Seek(Ok(
+            SeekFileResponse { result_offset: 4 },
+        ))))
+        .await;
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::FileRequest(FileRequest::FcntlLock(FcntlLockRequest {
+            fd: 1,
+            command: FcntlLockCommand::Set,
+            lock: FcntlLock {
+                lock_type: FcntlLockType::Write,
+                whence: FcntlLockWhence::Start,
+                start: 6,
+                len: 3,
+                pid: 0,
+            },
+        }))
+    );
+    intproxy
+        .send(DaemonMessage::File(FileResponse::FcntlLock(Ok(
+            FcntlLockResponse { conflicting: None },
+        ))))
+        .await;
+
+    intproxy.expect_file_close(1).await;
+
+    test_process.wait_assert_success().await;
+    test_process.assert_no_error_in_stderr().await;
+    test_process.assert_no_error_in_stdout().await;
+}
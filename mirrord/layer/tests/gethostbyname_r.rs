@@ -0,0 +1,66 @@
+#![cfg(target_os = "linux")]
+
+use std::{net::IpAddr, path::Path, time::Duration};
+
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, DnsLookupError,
+    ResolveErrorKindInternal::NoRecordsFound,
+    ResponseError,
+    dns::{DnsLookup, GetAddrInfoRequestV2, GetAddrInfoResponse, LookupRecord},
+};
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Verify that glibc's reentrant `gethostbyname_r` and `gethostbyname2_r` resolve through the
+/// agent, fill the caller-provided buffer, and report `ERANGE` and `HOST_NOT_FOUND`.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn gethostbyname_r(dylib_path: &Path) {
+    let application = Application::CGethostbynameR;
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(dylib_path, vec![("MIRRORD_REMOTE_DNS", "true")], None)
+        .await;
+
+    // The first call has a buffer that's too small, the second is the retry.
+    for ip in [
+        "93.184.216.34",
+        "93.184.216.34",
+        "2606:2800:220:1:248:1893:25c8:1946",
+    ] {
+        let msg = intproxy.recv().await;
+        let ClientMessage::GetAddrInfoRequestV2(GetAddrInfoRequestV2 { node, .. }) = msg else {
+            panic!("Invalid message received from layer: {msg:?}");
+        };
+        assert_eq!(node, "remote.host");
+
+        intproxy
+            .send(DaemonMessage::GetAddrInfoResponse(GetAddrInfoResponse(Ok(
+                DnsLookup(vec![LookupRecord {
+                    name: node,
+                    ip: ip.parse::<IpAddr>().unwrap(),
+                }]),
+            ))))
+            .await;
+    }
+
+    let msg = intproxy.recv().await;
+    let ClientMessage::GetAddrInfoRequestV2(GetAddrInfoRequestV2 { .. }) = msg else {
+        panic!("Invalid message received from layer: {msg:?}");
+    };
+
+    intproxy
+        .send(DaemonMessage::GetAddrInfoResponse(GetAddrInfoResponse(
+            Err(ResponseError::DnsLookup(DnsLookupError {
+                kind: NoRecordsFound(3),
+            })),
+        )))
+        .await;
+
+    test_process.wait_assert_success().await;
+    test_process
+        .assert_stdout_contains("test gethostbyname_r: SUCCESS")
+        .await;
+}
@@ -50,3 +50,37 @@ async fn check_ipv6s_hidden_with_config(
         .assert_stdout_doesnt_contain("family: 'IPv6'")
         .await;
 }
+
+/// Verifies that `if_nametoindex` and `if_indextoname` don't resolve the interfaces hidden from
+/// `getifaddrs` when `hide_ipv6_interfaces` is used.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn interface_indices_consistent_with_hidden_ipv6s(dylib_path: &Path) {
+    let dir = tempfile::tempdir().unwrap();
+    let file_id = rand::random::<u64>();
+    let config_path = dir.path().join(format!("{file_id:X}.json"));
+
+    let config = serde_json::json!({
+        "experimental": {
+            "hide_ipv6_interfaces": true
+        },
+        "feature": {
+            "network": {
+                "dns": false
+            },
+            "fs": "local",
+        }
+    });
+
+    tokio::fs::write(&config_path, serde_json::to_string_pretty(&config).unwrap())
+        .await
+        .expect("failed to save layer config to tmp file");
+
+    let (mut test_process, _intproxy) = Application::CIfNameToIndex
+        .start_process_with_layer(dylib_path, Default::default(), Some(&config_path))
+        .await;
+
+    test_process.wait_assert_success().await;
+    test_process.assert_no_error_in_stderr().await;
+}
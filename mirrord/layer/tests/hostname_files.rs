@@ -0,0 +1,31 @@
+#![cfg(target_os = "linux")]
+
+use std::{path::Path, time::Duration};
+
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Test that `/etc/hostname` and `/proc/sys/kernel/hostname` are read with the remote hostname,
+/// in the fs modes that enable the file hooks.
+///
+/// The remote hostname is fetched only once, and then shared by the files and `gethostname`.
+#[rstest]
+#[case::read("read")]
+#[case::local_with_overrides("localwithoverrides")]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn hostname_files(dylib_path: &Path, #[case] fs_mode: &str) {
+    let application = Application::CHostnameFiles;
+
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(dylib_path, vec![("MIRRORD_FILE_MODE", fs_mode)], None)
+        .await;
+
+    intproxy.expect_gethostname(1).await;
+
+    test_process.wait_assert_success().await;
+    test_process.assert_no_error_in_stderr().await;
+    test_process.assert_no_error_in_stdout().await;
+}
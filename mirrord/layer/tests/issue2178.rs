@@ -12,6 +12,7 @@ pub use common::*;
 #[timeout(Duration::from_secs(60))]
 async fn test_issue2178(
     #[values(Application::CIssue2178)] application: Application,
+    #[values(true, false)] readv_supported: bool,
     dylib_path: &Path,
 ) {
     let (mut test_process, mut intproxy) = application
@@ -23,9 +24,17 @@ async fn test_issue2178(
     intproxy
         .expect_file_open_with_read_flag("/app/test.txt", 3)
         .await;
-    assert_eq!(intproxy.expect_only_file_read(3).await, 12);
+    let iov_lengths = intproxy.expect_file_readv(3).await;
+    assert_eq!(iov_lengths, [4, 8]);
     let file_data = "abcdefgh".as_bytes().to_vec();
-    intproxy.answer_file_read(file_data).await;
+    if readv_supported {
+        intproxy.answer_file_readv(file_data, &iov_lengths).await;
+    } else {
+        // The layer falls back to a single read.
+        intproxy.answer_file_readv_not_implemented().await;
+        assert_eq!(intproxy.expect_only_file_read(3).await, 12);
+        intproxy.answer_file_read(file_data).await;
+    }
     intproxy.expect_file_close(3).await;
 
     test_process.wait_assert_success().await;
@@ -0,0 +1,109 @@
+#![cfg(target_os = "linux")]
+#![warn(clippy::indexing_slicing)]
+
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage,
+    outgoing::{
+        DaemonRead, LayerConnectV2, LayerWrite, LayerWriteBatch, SocketAddress,
+        udp::{DaemonUdpOutgoing, LayerUdpOutgoing},
+    },
+};
+use rstest::rstest;
+
+mod common;
+
+pub use common::*;
+
+/// Datagrams batched with `sendmmsg` and `recvmmsg` go through the outgoing UDP interceptor, and
+/// each received message gets the remote source address.
+///
+/// Datagrams batched with `sendmmsg` on an unconnected socket are intercepted one by one, the same
+/// way as with repeated `sendmsg` calls.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn mmsg(#[values(Application::RustMmsg)] application: Application, dylib_path: &Path) {
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(dylib_path, vec![], None)
+        .await;
+
+    let (uid, addr) = intproxy.recv_udp_connect().await;
+    intproxy
+        .send_udp_connect_ok(uid, 0, addr, RUST_OUTGOING_LOCAL.parse().unwrap())
+        .await;
+
+    // Both datagrams are sent at once, so the intproxy may coalesce them into one message.
+    let mut received = Vec::new();
+    while received.len() < 2 {
+        match intproxy.recv().await {
+            ClientMessage::UdpOutgoing(LayerUdpOutgoing::Write(LayerWrite {
+                connection_id: 0,
+                bytes,
+            })) => received.push(bytes),
+            ClientMessage::UdpOutgoing(LayerUdpOutgoing::WriteBatch(LayerWriteBatch {
+                connection_id: 0,
+                payloads,
+            })) => received.extend(payloads),
+            other => panic!("Invalid message received from layer: {other:?}"),
+        }
+    }
+    assert_eq!(
+        received.iter().map(|bytes| &**bytes).collect::<Vec<_>>(),
+        [b"first".as_slice(), b"second".as_slice()]
+    );
+
+    // send back the same bytes, the app asserts that they are the same
+    for bytes in received {
+        intproxy
+            .send(DaemonMessage::UdpOutgoing(DaemonUdpOutgoing::Read(Ok(
+                DaemonRead {
+                    connection_id: 0,
+                    bytes,
+                },
+            ))))
+            .await;
+    }
+
+    let mut writes = HashMap::new();
+    let mut next_connection_id = 1;
+    while writes.len() < 2 {
+        match intproxy.recv().await {
+            ClientMessage::UdpOutgoing(LayerUdpOutgoing::ConnectV2(LayerConnectV2 {
+                uid,
+                remote_address: SocketAddress::Ip(addr),
+            })) => {
+                assert_eq!(addr, "1.2.3.4:53".parse().unwrap());
+                intproxy
+                    .send_udp_connect_ok(
+                        uid,
+                        next_connection_id,
+                        addr,
+                        RUST_OUTGOING_LOCAL.parse().unwrap(),
+                    )
+                    .await;
+                next_connection_id += 1;
+            }
+            ClientMessage::UdpOutgoing(LayerUdpOutgoing::Write(LayerWrite {
+                connection_id,
+                bytes,
+            })) => {
+                writes.insert(connection_id, bytes);
+            }
+            other => panic!("Invalid message received from layer: {other:?}"),
+        }
+    }
+    assert_eq!(
+        writes.get(&1).map(|bytes| &**bytes),
+        Some(b"third".as_slice())
+    );
+    assert_eq!(
+        writes.get(&2).map(|bytes| &**bytes),
+        Some(b"fourth".as_slice())
+    );
+
+    test_process.wait_assert_success().await;
+    test_process.assert_no_error_in_stderr().await;
+    test_process.assert_no_error_in_stdout().await;
+}
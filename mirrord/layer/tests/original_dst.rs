@@ -0,0 +1,51 @@
+#![cfg(target_os = "linux")]
+
+use std::{path::Path, time::Duration};
+
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage,
+    tcp::{DaemonTcp, LayerTcp},
+};
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Verifies that `getsockopt(SO_ORIGINAL_DST)` on an accepted connection returns the remote
+/// destination of the connection, and not the address of the local listener, also when the
+/// listener's port is mapped to another remote port. The assertions are done in the test app.
+#[rstest]
+#[case::same_port("80", None)]
+#[case::port_mapping("9999", Some("port_mapping_original_dst.json"))]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn original_dst(
+    #[case] listen_port: &str,
+    #[case] config: Option<&str>,
+    dylib_path: &Path,
+    config_dir: &Path,
+) {
+    let application = Application::COriginalDst;
+    let config = config.map(|config| config_dir.join(config));
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(
+            dylib_path,
+            vec![("LISTEN_PORT", listen_port)],
+            config.as_deref(),
+        )
+        .await;
+
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::Tcp(LayerTcp::PortSubscribe(80)),
+    );
+    intproxy
+        .send(DaemonMessage::Tcp(DaemonTcp::SubscribeResult(Ok(80))))
+        .await;
+
+    intproxy.send_connection_then_data("hello", 80).await;
+
+    test_process.wait_assert_success().await;
+    test_process.assert_no_error_in_stderr().await;
+    test_process.assert_no_error_in_stdout().await;
+}
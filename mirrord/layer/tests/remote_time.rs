@@ -0,0 +1,36 @@
+#![cfg(target_os = "linux")]
+
+use std::{
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+use mirrord_protocol::{ClientMessage, DaemonMessage, time::RemoteTime};
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Test for `experimental.remote_time_offset`: the fake agent reports a clock one hour ahead of
+/// the local one, and the app verifies that only its realtime clocks are shifted.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn remote_time_offset(dylib_path: &Path, config_dir: &Path) {
+    let application = Application::CRemoteTime;
+    let config_path = config_dir.join("remote_time_offset.json");
+
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(dylib_path, Default::default(), Some(&config_path))
+        .await;
+
+    assert_eq!(intproxy.recv().await, ClientMessage::GetRemoteTime);
+    let remote_time = SystemTime::now() + Duration::from_secs(60 * 60);
+    intproxy
+        .send(DaemonMessage::RemoteTime(RemoteTime::from(remote_time)))
+        .await;
+
+    test_process.wait_assert_success().await;
+    test_process.assert_no_error_in_stderr().await;
+    test_process.assert_no_error_in_stdout().await;
+}
@@ -0,0 +1,85 @@
+#![cfg(target_os = "linux")]
+
+use std::{path::Path, time::Duration};
+
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, FileRequest, FileResponse,
+    file::{
+        ReadFileResponse, ReadLimitedFileRequest, SeekFileRequest, SeekFileResponse,
+        SeekFromInternal,
+    },
+};
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Answers the next [`ReadLimitedFileRequest`], checking its parameters.
+async fn expect_read_limited(
+    intproxy: &mut TestIntProxy,
+    buffer_size: u64,
+    start_from: u64,
+    contents: &[u8],
+) {
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::FileRequest(FileRequest::ReadLimited(ReadLimitedFileRequest {
+            remote_fd: 1,
+            buffer_size,
+            start_from,
+        }))
+    );
+    intproxy
+        .send(DaemonMessage::File(FileResponse::ReadLimited(Ok(
+            ReadFileResponse {
+                bytes: contents.to_vec().into(),
+                read_amount: contents.len() as u64,
+            },
+        ))))
+        .await;
+}
+
+/// Answers the next [`SeekFileRequest`], checking its parameters.
+async fn expect_seek(intproxy: &mut TestIntProxy, seek_from: SeekFromInternal, result_offset: u64) {
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::FileRequest(FileRequest::Seek(SeekFileRequest { fd: 1, seek_from }))
+    );
+    intproxy
+        .send(DaemonMessage::File(FileResponse::Seek(Ok(
+            SeekFileResponse { result_offset },
+        ))))
+        .await;
+}
+
+/// Test for the [`libc::sendfile`] hook: a remote file is read with [`ReadLimitedFileRequest`]s
+/// and written to a local pipe, advancing either the given offset or the remote file position.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn sendfile(dylib_path: &Path) {
+    let application = Application::CSendfile;
+
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(dylib_path, Default::default(), None)
+        .await;
+
+    intproxy
+        .expect_file_open_for_reading("/sendfile_test_file", 1)
+        .await;
+
+    // `sendfile` with an offset.
+    expect_read_limited(&mut intproxy, 5, 2, b"llo, ").await;
+
+    // `sendfile` without an offset, with a `count` past the end of the file.
+    expect_seek(&mut intproxy, SeekFromInternal::Current(0), 0).await;
+    expect_read_limited(&mut intproxy, 100, 0, b"hello, world").await;
+    expect_read_limited(&mut intproxy, 88, 12, b"").await;
+    expect_seek(&mut intproxy, SeekFromInternal::Start(12), 12).await;
+
+    intproxy.expect_file_close(1).await;
+
+    test_process.wait_assert_success().await;
+    test_process.assert_no_error_in_stderr().await;
+    test_process.assert_no_error_in_stdout().await;
+}
@@ -0,0 +1,88 @@
+#![cfg(target_os = "linux")]
+
+use std::{path::Path, time::Duration};
+
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, FileRequest, FileResponse,
+    file::{MetadataInternal, XstatRequest, XstatResponse},
+};
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Answers the next [`XstatRequest`], checking its parameters.
+async fn expect_xstat(intproxy: &mut TestIntProxy, request: XstatRequest, mode: u32) {
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::FileRequest(FileRequest::Xstat(request))
+    );
+
+    let metadata = MetadataInternal {
+        mode,
+        size: 12,
+        user_id: 1000,
+        ..Default::default()
+    };
+    intproxy
+        .send(DaemonMessage::File(FileResponse::Xstat(Ok(
+            XstatResponse { metadata },
+        ))))
+        .await;
+}
+
+/// Test for the [`libc::statx`] hook, called with a path (with and without
+/// `AT_SYMLINK_NOFOLLOW`), and with a remote fd and `AT_EMPTY_PATH`.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn statx(dylib_path: &Path) {
+    let application = Application::CStatx;
+
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(dylib_path, Default::default(), None)
+        .await;
+
+    expect_xstat(
+        &mut intproxy,
+        XstatRequest {
+            path: Some("/statx_test_file".into()),
+            fd: None,
+            follow_symlink: true,
+        },
+        libc::S_IFREG | 0o644,
+    )
+    .await;
+
+    expect_xstat(
+        &mut intproxy,
+        XstatRequest {
+            path: Some("/statx_test_file".into()),
+            fd: None,
+            follow_symlink: false,
+        },
+        libc::S_IFLNK | 0o777,
+    )
+    .await;
+
+    intproxy
+        .expect_file_open_for_reading("/statx_test_file", 1)
+        .await;
+
+    expect_xstat(
+        &mut intproxy,
+        XstatRequest {
+            path: None,
+            fd: Some(1),
+            follow_symlink: true,
+        },
+        libc::S_IFREG | 0o644,
+    )
+    .await;
+
+    intproxy.expect_file_close(1).await;
+
+    test_process.wait_assert_success().await;
+    test_process.assert_no_error_in_stderr().await;
+    test_process.assert_no_error_in_stdout().await;
+}
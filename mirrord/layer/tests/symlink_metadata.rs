@@ -0,0 +1,69 @@
+#![cfg(target_family = "unix")]
+
+use std::{path::Path, time::Duration};
+
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, FileRequest, FileResponse,
+    file::{ChownRequest, Timespec, UtimensRequest},
+};
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Answers the next [`UtimensRequest`], checking its parameters.
+async fn expect_utimens(intproxy: &mut TestIntProxy, times: [(i64, i64); 2]) {
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::FileRequest(FileRequest::Utimens(UtimensRequest {
+            path: "/app/link".into(),
+            times: Some(times.map(|(tv_sec, tv_nsec)| Timespec { tv_sec, tv_nsec })),
+            follow_symlink: false,
+        }))
+    );
+    intproxy
+        .send(DaemonMessage::File(FileResponse::Futimens(Ok(()))))
+        .await;
+}
+
+/// Answers the next [`ChownRequest`], checking its parameters.
+async fn expect_chown(intproxy: &mut TestIntProxy, owner: u32, group: u32) {
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::FileRequest(FileRequest::Chown(ChownRequest {
+            path: "/app/link".into(),
+            owner,
+            group,
+            follow_symlink: false,
+        }))
+    );
+    intproxy
+        .send(DaemonMessage::File(FileResponse::Fchown(Ok(()))))
+        .await;
+}
+
+/// Test for the `lutimes`, `utimensat`, `lchown` and `fchownat` hooks on a remote symlink, which
+/// should not be followed.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn symlink_metadata(dylib_path: &Path) {
+    let application = Application::CSymlinkMetadata;
+
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(
+            dylib_path,
+            vec![("MIRRORD_FILE_READ_WRITE_PATTERN", "^/app/link$")],
+            None,
+        )
+        .await;
+
+    expect_utimens(&mut intproxy, [(1, 2000), (3, 4000)]).await;
+    expect_utimens(&mut intproxy, [(5, 6), (7, 8)]).await;
+    expect_chown(&mut intproxy, 1000, 1001).await;
+    expect_chown(&mut intproxy, 1002, 1003).await;
+
+    test_process.wait_assert_success().await;
+    test_process.assert_no_error_in_stderr().await;
+    test_process.assert_no_error_in_stdout().await;
+}
@@ -0,0 +1,74 @@
+#![cfg(target_family = "unix")]
+
+use std::{path::Path, time::Duration};
+
+use mirrord_protocol::file::MetadataInternal;
+use rstest::rstest;
+use tokio::net::{TcpListener, TcpStream};
+
+mod common;
+pub use common::*;
+
+/// Verify that with `wait_for_debugger` the application doesn't start until something connects to
+/// the given port, and that the wait is reported in the progress stream.
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn wait_for_debugger(dylib_path: &Path) {
+    let port = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+        .to_string();
+
+    let application = Application::CChdir;
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(
+            dylib_path,
+            vec![
+                ("MIRRORD_WAIT_FOR_DEBUGGER_PORT", port.as_str()),
+                ("MIRRORD_PROGRESS_MODE", "json"),
+            ],
+            None,
+        )
+        .await;
+
+    // The application is paused, so its `chdir` doesn't reach us.
+    assert!(
+        tokio::time::timeout(Duration::from_secs(2), intproxy.recv())
+            .await
+            .is_err()
+    );
+    test_process
+        .assert_stdout_contains(&format!("waiting for debugger on port {port}"))
+        .await;
+
+    TcpStream::connect(format!("127.0.0.1:{port}"))
+        .await
+        .unwrap();
+
+    intproxy
+        .expect_xstat_with_metadata(
+            Some("/chdir_test_dir".into()),
+            None,
+            MetadataInternal {
+                mode: libc::S_IFDIR as u32 | 0o755,
+                ..Default::default()
+            },
+        )
+        .await;
+
+    intproxy
+        .expect_file_open_for_reading("/chdir_test_dir/file", 1)
+        .await;
+
+    intproxy.expect_file_close(1).await;
+
+    test_process.wait_assert_success().await;
+    test_process
+        .assert_stdout_contains("debugger connected")
+        .await;
+    test_process.assert_no_error_in_stderr().await;
+}
@@ -0,0 +1,100 @@
+#![cfg(target_os = "linux")]
+
+use std::{path::Path, time::Duration};
+
+use mirrord_protocol::{
+    ClientMessage, DaemonMessage, FileRequest, FileResponse, ResponseError,
+    file::{
+        OpenOptionsInternal, WriteFileRequest, WriteFileResponse, WriteLimitedFileRequest,
+        WritevFileRequest,
+    },
+};
+use rstest::rstest;
+
+mod common;
+pub use common::*;
+
+/// Test for the [`libc::writev`] and [`libc::pwritev`] hooks: `writev` sends all of the buffers
+/// with a single [`WritevFileRequest`], and falls back to a [`WriteFileRequest`] of the
+/// concatenated buffers when the agent doesn't support it. `pwritev` sends the concatenated
+/// buffers with a [`WriteLimitedFileRequest`].
+#[rstest]
+#[tokio::test]
+#[timeout(Duration::from_secs(60))]
+async fn writev(dylib_path: &Path) {
+    let application = Application::CWritev;
+
+    let (mut test_process, mut intproxy) = application
+        .start_process_with_layer(
+            dylib_path,
+            vec![("MIRRORD_FILE_READ_WRITE_PATTERN", "^/app/writev")],
+            None,
+        )
+        .await;
+
+    intproxy
+        .expect_file_open_with_options(
+            "/app/writev.txt",
+            1,
+            OpenOptionsInternal {
+                write: true,
+                create: true,
+                ..Default::default()
+            },
+        )
+        .await;
+
+    let expected_writev = ClientMessage::FileRequest(FileRequest::Writev(WritevFileRequest {
+        fd: 1,
+        buffers: vec![b"hello ".to_vec().into(), b"world".to_vec().into()],
+    }));
+
+    // Written on the agent with a single request.
+    assert_eq!(intproxy.recv().await, expected_writev);
+    intproxy
+        .send(DaemonMessage::File(FileResponse::Writev(Ok(
+            WriteFileResponse { written_amount: 11 },
+        ))))
+        .await;
+
+    // Not supported by the agent, written as a single buffer.
+    assert_eq!(intproxy.recv().await, expected_writev);
+    intproxy
+        .send(DaemonMessage::File(FileResponse::Writev(Err(
+            ResponseError::NotImplemented,
+        ))))
+        .await;
+
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::FileRequest(FileRequest::Write(WriteFileRequest {
+            fd: 1,
+            write_bytes: b"hello world".to_vec().into(),
+        }))
+    );
+    intproxy
+        .send(DaemonMessage::File(FileResponse::Write(Ok(
+            WriteFileResponse { written_amount: 11 },
+        ))))
+        .await;
+
+    assert_eq!(
+        intproxy.recv().await,
+        ClientMessage::FileRequest(FileRequest::WriteLimited(WriteLimitedFileRequest {
+            remote_fd: 1,
+            write_bytes: b"hello world".to_vec().into(),
+            start_from: 20,
+        }))
+    );
+    intproxy
+        .send(DaemonMessage::File(FileResponse::WriteLimited(Ok(
+            WriteFileResponse { written_amount: 11 },
+        ))))
+        .await;
+
+    intproxy.expect_file_close(1).await;
+
+    test_process.wait_assert_success().await;
+    test_process.assert_no_error_in_stderr().await;
+    test_process.assert_no_error_in_stdout().await;
+}
@@ -19,12 +19,13 @@ use mirrord_auth::{
     credentials::{CiApiKey, Credentials, LicenseValidity},
 };
 use mirrord_config::{
-    LayerConfig, feature::database_branches::default_creation_timeout_secs, target::Target,
+    LayerConfig, feature::database_branches::default_creation_timeout_secs,
+    retry::StartupRetryConfig, target::Target,
 };
 use mirrord_kube::{
     api::{
         kubernetes::{
-            create_kube_config,
+            apply_connection_proxy, create_kube_config,
             rollout::{Rollout, RolloutSpec, workload_ref::WorkloadRef},
         },
         runtime::RuntimeDataProvider,
@@ -49,7 +50,7 @@ use crate::{
     },
     crd::{
         MirrordClusterOperatorUserCredential, MirrordOperatorCrd, NewOperatorFeature,
-        OPERATOR_STATUS_NAME, TargetCrd,
+        OPERATOR_STATUS_NAME, SessionCrd, TargetCrd,
         copy_target::{CopyTargetCrd, CopyTargetSpec, CopyTargetStatus},
         db_branching::{
             mongodb::MongodbBranchDatabase, mysql::MysqlBranchDatabase, pg::PgBranchDatabase,
@@ -67,6 +68,7 @@ mod credentials;
 pub mod database_branches;
 mod discovery;
 pub mod error;
+mod session_retry;
 mod upgrade;
 
 /// State of client's [`Certificate`] the should be attached to some operator requests.
@@ -543,6 +545,8 @@ where
         )
         .await
         .map_err(OperatorApiError::CreateKubeClient)?;
+        apply_connection_proxy(&mut client_config, &layer_config.agent)
+            .map_err(OperatorApiError::CreateKubeClient)?;
 
         client_config.headers.push((
             HeaderName::from_static(MIRRORD_CLI_VERSION_HEADER),
@@ -605,6 +609,12 @@ where
                 .require_feature(NewOperatorFeature::CopyTargetExcludeContainers)?
         }
 
+        if layer_config.feature.copy_target.patch.is_some() {
+            self.operator
+                .spec
+                .require_feature(NewOperatorFeature::CopyTargetPatch)?
+        }
+
         if layer_config.feature.split_queues.sqs().next().is_some() {
             self.operator
                 .spec
@@ -617,6 +627,18 @@ where
                 .require_feature(NewOperatorFeature::KafkaQueueSplitting)?;
         }
 
+        if layer_config
+            .feature
+            .split_queues
+            .rabbitmq()
+            .next()
+            .is_some()
+        {
+            self.operator
+                .spec
+                .require_feature(NewOperatorFeature::RabbitMqQueueSplitting)?;
+        }
+
         if layer_config
             .feature
             .split_queues
@@ -857,7 +879,14 @@ impl OperatorApi<PreparedClientCert> {
         };
 
         let mut connection_subtask = progress.subtask("connecting to the target");
-        let (conn, session) = match Self::connect_target(&self.client, &session).await {
+        let (conn, session) = match self
+            .connect_target_with_retries(
+                &session,
+                &layer_config.startup_retry,
+                layer_config.internal_proxy.max_message_size,
+            )
+            .await
+        {
             Ok(conn) => {
                 connection_subtask.success(Some("connected to the target"));
                 (conn, session)
@@ -892,7 +921,12 @@ impl OperatorApi<PreparedClientCert> {
                 )?;
 
                 let mut connection_subtask = progress.subtask("connecting to the target");
-                let conn = Self::connect_target(&self.client, &session).await?;
+                let conn = Self::connect_target(
+                    &self.client,
+                    &session,
+                    layer_config.internal_proxy.max_message_size,
+                )
+                .await?;
                 connection_subtask.success(Some("connected to the target"));
                 (conn, session)
             }
@@ -990,7 +1024,13 @@ impl OperatorApi<PreparedClientCert> {
         )?;
 
         let mut connection_subtask = progress.subtask("connecting to the target");
-        let conn = Self::connect_target(&self.client, &session).await?;
+        let conn = self
+            .connect_target_with_retries(
+                &session,
+                &layer_config.startup_retry,
+                layer_config.internal_proxy.max_message_size,
+            )
+            .await?;
         connection_subtask.success(Some("connected to the target"));
 
         Ok(OperatorSessionConnection {
@@ -1037,6 +1077,18 @@ impl OperatorApi<PreparedClientCert> {
             return Ok((true, Some("Kafka splitting")));
         }
 
+        if config.feature.split_queues.rabbitmq().next().is_some()
+            && self
+                .operator()
+                .spec
+                .supported_features()
+                .contains(&NewOperatorFeature::RabbitMqQueueSplittingDirect)
+                .not()
+        {
+            // Operator does not support RabbitMQ splitting without copying the target.
+            return Ok((true, Some("RabbitMQ splitting")));
+        }
+
         let ResolvedTarget::Deployment(ResolvedResource { resource, .. }) = target else {
             // We do replicas checks only for deployments.
             return Ok((false, None));
@@ -1274,10 +1326,11 @@ impl OperatorApi<PreparedClientCert> {
             connect: true,
             on_concurrent_steal: None,
             profile,
-            // Kafka and SQS splits are passed in the request body.
+            // Kafka, SQS and RabbitMQ splits are passed in the request body.
             kafka_splits: Default::default(),
             sqs_splits: Default::default(),
             sqs_jq_filters: Default::default(),
+            rabbitmq_splits: Default::default(),
             branch_name,
             mongodb_branch_names,
             mysql_branch_names,
@@ -1348,6 +1401,7 @@ impl OperatorApi<PreparedClientCert> {
             .copy_target
             .exclude_init_containers
             .clone();
+        let patch = layer_config.feature.copy_target.patch.clone();
 
         let copy_target_api: Api<CopyTargetCrd> = Api::namespaced(self.client.clone(), namespace);
 
@@ -1359,6 +1413,7 @@ impl OperatorApi<PreparedClientCert> {
             split_queues,
             exclude_containers,
             exclude_init_containers,
+            patch,
         };
 
         let copied = copy_target_api
@@ -1407,6 +1462,7 @@ impl OperatorApi<PreparedClientCert> {
             .copy_target
             .exclude_init_containers
             .clone();
+        let patch = layer_config.feature.copy_target.patch.clone();
 
         let user_id = self.get_user_id_str();
 
@@ -1418,6 +1474,7 @@ impl OperatorApi<PreparedClientCert> {
             split_queues,
             exclude_containers,
             exclude_init_containers,
+            patch,
         };
 
         let existing = copy_target_api
@@ -1549,16 +1606,75 @@ impl OperatorApi<PreparedClientCert> {
             )?))
             .build();
 
-        let conn = Self::connect_target(&client, &session).await?;
+        let conn = Self::connect_target(
+            &client,
+            &session,
+            layer_config.internal_proxy.max_message_size,
+        )
+        .await?;
 
         Ok(OperatorSessionConnection { conn, session })
     }
 
+    /// Creates a new session in the operator with [`Self::connect_target`], retrying transient
+    /// failures as configured in [`StartupRetryConfig::operator_session_retries`].
+    ///
+    /// Before each retry, the session that the failed attempt might have created is deleted with
+    /// [`Self::delete_session`].
+    #[tracing::instrument(level = Level::TRACE, skip(self), err)]
+    async fn connect_target_with_retries(
+        &self,
+        session: &OperatorSession,
+        retry_config: &StartupRetryConfig,
+        max_message_size: usize,
+    ) -> OperatorApiResult<Connection<ProtocolClient>> {
+        session_retry::retry_session_creation(
+            retry_config,
+            || Self::connect_target(&self.client, session, max_message_size),
+            || self.delete_session(session.id),
+        )
+        .await
+    }
+
+    /// Deletes the session with the given id from the operator, if it exists.
+    ///
+    /// Fails with [`OperatorApiError::UnsupportedFeature`] if the operator does not support
+    /// [`NewOperatorFeature::SessionManagement`], as we can't tell whether the session is gone,
+    /// so it's not safe to retry its creation.
+    #[tracing::instrument(level = Level::DEBUG, skip(self), err)]
+    async fn delete_session(&self, id: u64) -> OperatorApiResult<()> {
+        if self
+            .operator
+            .spec
+            .supported_features()
+            .contains(&NewOperatorFeature::SessionManagement)
+            .not()
+        {
+            return Err(OperatorApiError::UnsupportedFeature {
+                feature: NewOperatorFeature::SessionManagement,
+                operator_version: self.operator.spec.operator_version.to_string(),
+            });
+        }
+
+        match Api::<SessionCrd>::all(self.client.clone())
+            .delete(&id.to_string(), &Default::default())
+            .await
+        {
+            Ok(..) => Ok(()),
+            Err(kube::Error::Api(response)) if response.code == 404 => Ok(()),
+            Err(error) => Err(OperatorApiError::KubeError {
+                error,
+                operation: OperatorOperation::SessionManagement,
+            }),
+        }
+    }
+
     /// Creates websocket connection to the operator target.
     #[tracing::instrument(level = Level::TRACE, skip(client), err)]
     async fn connect_target(
         client: &Client,
         session: &OperatorSession,
+        max_message_size: usize,
     ) -> OperatorApiResult<Connection<ProtocolClient>> {
         let request_builder = Request::builder()
             .uri(&session.connect_url)
@@ -1626,6 +1742,7 @@ impl OperatorApi<PreparedClientCert> {
                 },
                 other => Either::Left(other),
             }),
+            max_message_size,
         )
         .await?;
 
@@ -1956,6 +2073,7 @@ mod test {
         kafka_splits: HashMap<&'static str, BTreeMap<String, String>>,
         sqs_splits: HashMap<&'static str, BTreeMap<String, String>>,
         sqs_jq_filters: HashMap<&'static str, &'static str>,
+        rabbitmq_splits: HashMap<&'static str, BTreeMap<String, String>>,
         mysql_branch_names: Vec<String>,
         pg_branch_names: Vec<String>,
         mongodb_branch_names: Vec<String>,
@@ -1986,6 +2104,7 @@ mod test {
                 kafka_splits: Default::default(),
                 sqs_splits: Default::default(),
                 sqs_jq_filters: Default::default(),
+                rabbitmq_splits: Default::default(),
                 mysql_branch_names: Default::default(),
                 pg_branch_names: Default::default(),
                 mongodb_branch_names: Default::default(),
@@ -2119,6 +2238,19 @@ mod test {
             ..Default::default()
             }
     )]
+    #[case::deployment_container_proxy_rabbitmq_splits(
+        TargetConnectUrlTestCase{
+            use_proxy: true,
+            target: deployment_with_container(),
+            rabbitmq_splits: HashMap::from([(
+                "queue-id",
+                BTreeMap::from([("x-tenant".to_string(), "^metalbear$".to_string())]),
+            )]),
+            expected: "/apis/operator.metalbear.co/v1/proxy/namespaces/default/targets/deployment.py-serv-deployment.container.py-serv\
+            ?connect=true&on_concurrent_steal=abort&rabbitmq_splits=%7B%22queue-id%22%3A%7B%22x-tenant%22%3A%22%5Emetalbear%24%22%7D%7D",
+            ..Default::default()
+            }
+    )]
     #[case::deployment_container_proxy_mysql_branches(
         TargetConnectUrlTestCase{
             use_proxy: true,
@@ -2190,6 +2322,7 @@ mod test {
             kafka_splits,
             sqs_splits,
             sqs_jq_filters,
+            rabbitmq_splits,
             mysql_branch_names,
             pg_branch_names,
             mongodb_branch_names,
@@ -2208,6 +2341,11 @@ mod test {
             .map(|(topic_id, filters)| (*topic_id, filters))
             .collect();
 
+        let rabbitmq_splits = rabbitmq_splits
+            .iter()
+            .map(|(queue_id, filters)| (*queue_id, filters))
+            .collect();
+
         let params = ConnectParams {
             connect: true,
             on_concurrent_steal: Some(concurrent_steal),
@@ -2215,6 +2353,7 @@ mod test {
             kafka_splits,
             sqs_splits,
             sqs_jq_filters,
+            rabbitmq_splits,
             branch_name: None,
             mongodb_branch_names,
             mysql_branch_names,
@@ -2332,6 +2471,7 @@ mod test {
             kafka_splits: Default::default(),
             sqs_splits: Default::default(),
             sqs_jq_filters: Default::default(),
+            rabbitmq_splits: Default::default(),
             branch_name: None,
             mongodb_branch_names: Default::default(),
             mysql_branch_names: Default::default(),
@@ -35,6 +35,9 @@ pub struct ConnectParams<'a> {
     )]
     pub sqs_jq_filters: HashMap<&'a str, &'a str>,
 
+    #[serde(with = "force_json_ser", skip_serializing_if = "HashMap::is_empty")]
+    pub rabbitmq_splits: HashMap<&'a str, &'a BTreeMap<String, String>>,
+
     /// User's current git branch name - may be an empty string if user is in detached head mode or
     /// another error occurred: this case handled by the operator
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -91,6 +94,7 @@ impl<'a> ConnectParams<'a> {
             kafka_splits: config.feature.split_queues.kafka().collect(),
             sqs_splits: config.feature.split_queues.sqs().collect(),
             sqs_jq_filters: config.feature.split_queues.sqs_jq_filters().collect(),
+            rabbitmq_splits: config.feature.split_queues.rabbitmq().collect(),
             branch_name,
             mongodb_branch_names,
             mysql_branch_names,
@@ -109,4 +109,24 @@ pub enum OperatorApiError {
     SerdeJson(#[from] serde_json::Error),
 }
 
+impl OperatorApiError {
+    /// Returns whether this is a transient failure of the websocket connection with the operator,
+    /// after which creating the session can be retried.
+    pub fn is_transient(&self) -> bool {
+        let Self::KubeError {
+            error,
+            operation: OperatorOperation::WebsocketConnection,
+        } = self
+        else {
+            return false;
+        };
+
+        match error {
+            kube::Error::Api(response) => [429, 500, 502, 503, 504].contains(&response.code),
+            kube::Error::HyperError(..) | kube::Error::Service(..) => true,
+            _ => false,
+        }
+    }
+}
+
 pub type OperatorApiResult<T, E = OperatorApiError> = Result<T, E>;
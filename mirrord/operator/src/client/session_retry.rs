@@ -0,0 +1,207 @@
+//! Retrying the creation of an [`OperatorSession`](super::OperatorSession) without leaking agents.
+//!
+//! When the websocket connection with the operator fails with a transient error, we don't know
+//! whether the operator already created the session (and its agent). To make the retry safe:
+//!
+//! 1. All attempts send the same session id in the
+//!    [`SESSION_ID_HEADER`](crate::types::SESSION_ID_HEADER), which the operator uses as an
+//!    idempotency key;
+//! 2. Before each retry, we delete the session that the failed attempt might have left behind.
+
+use std::time::Duration;
+
+use mirrord_config::retry::StartupRetryConfig;
+
+use super::error::OperatorApiResult;
+
+/// Creates the session with `connect`, retrying transient failures (see
+/// [`OperatorApiError::is_transient`](super::error::OperatorApiError::is_transient)) up to
+/// [`StartupRetryConfig::operator_session_retries`] times.
+///
+/// `cleanup` is called before each retry. If it fails, we give up and return the connection
+/// error, as retrying could duplicate the session.
+pub(super) async fn retry_session_creation<T, C, D>(
+    config: &StartupRetryConfig,
+    mut connect: impl FnMut() -> C,
+    mut cleanup: impl FnMut() -> D,
+) -> OperatorApiResult<T>
+where
+    C: Future<Output = OperatorApiResult<T>>,
+    D: Future<Output = OperatorApiResult<()>>,
+{
+    let max_backoff = Duration::from_millis(config.max_ms);
+    let mut backoff = Duration::from_millis(config.min_ms);
+    let mut retries = 0;
+
+    loop {
+        let error = match connect().await {
+            Err(error) if error.is_transient() && retries < config.operator_session_retries => {
+                error
+            }
+            result => break result,
+        };
+
+        retries += 1;
+        tracing::warn!(
+            %error,
+            retries,
+            "Failed to create the operator session, cleaning up before retrying",
+        );
+
+        if let Err(cleanup_error) = cleanup().await {
+            tracing::warn!(
+                %cleanup_error,
+                "Failed to clean up after a failed attempt to create the operator session",
+            );
+            break Err(error);
+        }
+
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(max_backoff);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{ops::Not, sync::Mutex};
+
+    use kube::core::ErrorResponse;
+    use mirrord_config::retry::StartupRetryConfig;
+
+    use super::retry_session_creation;
+    use crate::{
+        client::error::{OperatorApiError, OperatorApiResult, OperatorOperation},
+        crd::NewOperatorFeature,
+    };
+
+    const SESSION_ID: u64 = 0xC0FFEE;
+
+    fn config(operator_session_retries: u32) -> StartupRetryConfig {
+        StartupRetryConfig {
+            min_ms: 1,
+            max_ms: 2,
+            max_retries: 0,
+            operator_session_retries,
+            agent_retries: 0,
+        }
+    }
+
+    fn websocket_error(code: u16) -> OperatorApiError {
+        OperatorApiError::KubeError {
+            error: kube::Error::Api(ErrorResponse {
+                status: "Failure".to_string(),
+                message: "mocked failure".to_string(),
+                reason: "mocked".to_string(),
+                code,
+            }),
+            operation: OperatorOperation::WebsocketConnection,
+        }
+    }
+
+    /// Mocked operator, creates an agent for the session even when the connection then fails.
+    #[derive(Default)]
+    struct MockOperator {
+        /// Ids of the sessions that own an agent.
+        agents: Mutex<Vec<u64>>,
+        /// Status codes of the next connection failures.
+        failures: Mutex<Vec<u16>>,
+        /// Whether sessions can be deleted.
+        without_session_management: bool,
+    }
+
+    impl MockOperator {
+        fn failing_with(failures: &[u16]) -> Self {
+            Self {
+                failures: Mutex::new(failures.iter().rev().copied().collect()),
+                ..Default::default()
+            }
+        }
+
+        async fn connect(&self, id: u64) -> OperatorApiResult<u64> {
+            self.agents.lock().unwrap().push(id);
+
+            match self.failures.lock().unwrap().pop() {
+                Some(code) => Err(websocket_error(code)),
+                None => Ok(id),
+            }
+        }
+
+        async fn delete_session(&self, id: u64) -> OperatorApiResult<()> {
+            if self.without_session_management {
+                return Err(OperatorApiError::UnsupportedFeature {
+                    feature: NewOperatorFeature::SessionManagement,
+                    operator_version: "3.0.0".to_string(),
+                });
+            }
+
+            self.agents.lock().unwrap().retain(|agent| *agent != id);
+            Ok(())
+        }
+
+        fn agents(&self) -> Vec<u64> {
+            self.agents.lock().unwrap().clone()
+        }
+    }
+
+    /// Verifies that a retried session creation does not leave duplicated agents behind.
+    #[tokio::test]
+    async fn retry_does_not_duplicate_agents() {
+        let operator = MockOperator::failing_with(&[503, 502]);
+
+        let session = retry_session_creation(
+            &config(2),
+            || operator.connect(SESSION_ID),
+            || operator.delete_session(SESSION_ID),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(session, SESSION_ID);
+        assert_eq!(operator.agents(), [SESSION_ID]);
+    }
+
+    /// Verifies that we stop after the configured amount of retries, and that non-transient
+    /// errors are not retried.
+    #[tokio::test]
+    async fn gives_up() {
+        let operator = MockOperator::failing_with(&[503, 503]);
+        let result = retry_session_creation(
+            &config(1),
+            || operator.connect(SESSION_ID),
+            || operator.delete_session(SESSION_ID),
+        )
+        .await;
+        assert!(result.unwrap_err().is_transient());
+        assert_eq!(operator.failures.lock().unwrap().len(), 0);
+
+        let operator = MockOperator::failing_with(&[403, 503]);
+        let result = retry_session_creation(
+            &config(2),
+            || operator.connect(SESSION_ID),
+            || operator.delete_session(SESSION_ID),
+        )
+        .await;
+        assert!(result.unwrap_err().is_transient().not());
+        assert_eq!(operator.failures.lock().unwrap().len(), 1);
+    }
+
+    /// Verifies that we don't retry when the operator can't delete the session left behind by the
+    /// failed attempt, as the retry could create a second agent.
+    #[tokio::test]
+    async fn no_retry_without_session_management() {
+        let operator = MockOperator {
+            without_session_management: true,
+            ..MockOperator::failing_with(&[503])
+        };
+
+        let result = retry_session_creation(
+            &config(2),
+            || operator.connect(SESSION_ID),
+            || operator.delete_session(SESSION_ID),
+        )
+        .await;
+
+        assert!(result.unwrap_err().is_transient());
+        assert_eq!(operator.agents(), [SESSION_ID]);
+    }
+}
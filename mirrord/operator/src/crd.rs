@@ -33,6 +33,7 @@ pub mod patch;
 pub mod policy;
 pub mod preview;
 pub mod profile;
+pub mod rabbitmq;
 pub mod session;
 pub mod steal_tls;
 
@@ -490,6 +491,14 @@ pub enum NewOperatorFeature {
 
     PreviewEnv,
 
+    /// The operator can apply `feature.copy_target.patch` to the copied pod.
+    CopyTargetPatch,
+
+    /// The operator can split RabbitMQ queues, filtering messages by their headers.
+    RabbitMqQueueSplitting,
+    /// The operator can split RabbitMQ queues without copying the target.
+    RabbitMqQueueSplittingDirect,
+
     /// This variant is what a client sees when the operator includes a feature the client is not
     /// yet aware of, because it was introduced in a version newer than the client's.
     #[schemars(skip)]
@@ -517,6 +526,11 @@ impl Display for NewOperatorFeature {
             NewOperatorFeature::PgBranching => "PostgreSQL branching",
             NewOperatorFeature::MongodbBranching => "MongoDB branching",
             NewOperatorFeature::PreviewEnv => "preview environments",
+            NewOperatorFeature::CopyTargetPatch => "copy target pod patch",
+            NewOperatorFeature::RabbitMqQueueSplitting => "RabbitMQ queue splitting",
+            NewOperatorFeature::RabbitMqQueueSplittingDirect => {
+                "RabbitMQ queue splitting without copy target"
+            }
             NewOperatorFeature::ExtendableUserCredentials => "ExtendableUserCredentials",
             NewOperatorFeature::BypassCiCertificateVerification => {
                 "BypassCiCertificateVerification"
@@ -918,6 +932,7 @@ mod tests {
         policy::{MirrordClusterPolicy, MirrordPolicy},
         preview::PreviewSession,
         profile::{MirrordClusterProfile, MirrordProfile},
+        rabbitmq::{MirrordRabbitMqClientConfig, MirrordRabbitMqQueuesConsumer},
         session::MirrordClusterSession,
         steal_tls::{MirrordClusterTlsStealConfig, MirrordTlsStealConfig},
     };
@@ -1056,6 +1071,18 @@ mod tests {
         write_crd_yaml::<MirrordKafkaEphemeralTopic>();
     }
 
+    #[test]
+    #[ignore]
+    fn write_mirrord_rabbitmq_client_config_crd_yaml() {
+        write_crd_yaml::<MirrordRabbitMqClientConfig>();
+    }
+
+    #[test]
+    #[ignore]
+    fn write_mirrord_rabbitmq_queues_consumer_crd_yaml() {
+        write_crd_yaml::<MirrordRabbitMqQueuesConsumer>();
+    }
+
     #[test]
     #[ignore]
     fn write_mirrord_cluster_profile_crd_yaml() {
@@ -1102,6 +1129,8 @@ mod tests {
         write_crd_yaml::<MirrordKafkaClientConfig>();
         write_crd_yaml::<MirrordKafkaTopicsConsumer>();
         write_crd_yaml::<MirrordKafkaEphemeralTopic>();
+        write_crd_yaml::<MirrordRabbitMqClientConfig>();
+        write_crd_yaml::<MirrordRabbitMqQueuesConsumer>();
         write_crd_yaml::<MirrordClusterProfile>();
         write_crd_yaml::<MirrordProfile>();
         write_crd_yaml::<MirrordTlsStealConfig>();
@@ -1,7 +1,12 @@
 use kube::CustomResource;
 use mirrord_config::{feature::split_queues::SplitQueuesConfig, target::Target};
-use schemars::JsonSchema;
+use schemars::{
+    JsonSchema,
+    r#gen::SchemaGenerator,
+    schema::{InstanceType, Schema, SchemaObject},
+};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use crate::crd::Session;
 
@@ -33,6 +38,23 @@ pub struct CopyTargetSpec {
     /// Init containers that are ignored by copy target.
     #[serde(default)]
     pub exclude_init_containers: Vec<String>,
+    /// Strategic merge patch applied to the spec of the copied pod.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[schemars(schema_with = "patch_schema")]
+    pub patch: Option<Value>,
+}
+
+/// Schema of [`CopyTargetSpec::patch`], an arbitrary JSON object.
+fn patch_schema(_: &mut SchemaGenerator) -> Schema {
+    let mut schema = SchemaObject {
+        instance_type: Some(InstanceType::Object.into()),
+        ..Default::default()
+    };
+    schema.extensions.insert("nullable".into(), true.into());
+    schema
+        .extensions
+        .insert("x-kubernetes-preserve-unknown-fields".into(), true.into());
+    schema.into()
 }
 
 /// This is the `status` field for [`CopyTargetCrd`].
@@ -0,0 +1,128 @@
+use kube::CustomResource;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::crd::kafka::EnvVarLocation;
+
+/// Configuration to use when creating operator's RabbitMQ client.
+/// Resources of this kind should live in the operator's namespace.
+#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[kube(
+    group = "queues.mirrord.metalbear.co",
+    version = "v1alpha",
+    kind = "MirrordRabbitMqClientConfig",
+    namespaced,
+    printcolumn = r#"{"name":"SECRET", "type":"string", "description":"Name of Secret to load the URI from.", "jsonPath":".spec.uriFromSecret"}"#,
+    printcolumn = r#"{"name":"VHOST", "type":"string", "description":"Virtual host to connect to.", "jsonPath":".spec.virtualHost"}"#
+)]
+#[serde(rename_all = "camelCase")]
+pub struct MirrordRabbitMqClientConfigSpec {
+    /// AMQP URI of the RabbitMQ broker, e.g. `amqp://rabbitmq.default.svc.cluster.local:5672`.
+    ///
+    /// Exactly one of `uri` and `uriFromSecret` should be set.
+    pub uri: Option<String>,
+
+    /// Namespace, name and key of a `Secret` entry that contains the AMQP URI, to avoid storing
+    /// credentials in this resource.
+    ///
+    /// Example value: `default/my-secret/uri`
+    pub uri_from_secret: Option<String>,
+
+    /// Virtual host to use, overrides the one from the URI.
+    pub virtual_host: Option<String>,
+}
+
+/// Defines splittable RabbitMQ queues consumed by some workload living in the same namespace.
+///
+/// # Example
+///
+/// ```yaml
+/// apiVersion: queues.mirrord.metalbear.co/v1alpha
+/// kind: MirrordRabbitMqQueuesConsumer
+/// metadata:
+///   name: example
+///   namespace: default
+/// spec:
+///   consumerName: example-deployment
+///   consumerApiVersion: apps/v1
+///   consumerKind: Deployment
+///   queues:
+///     - id: example-queue
+///       nameSources:
+///         - directEnvVar:
+///             container: example-container
+///             variable: RABBITMQ_QUEUE_NAME
+///       clientConfig: example-config
+/// ```
+///
+/// 1. Creating the resource above will enable RabbitMQ splitting on a deployment
+///    `example-deployment` living in namespace `default`. Id `example-queue` can be then used in
+///    the mirrord config to split the queue for the duration of the mirrord session.
+///
+/// 2. Queue name will be resolved based on `example-deployment`'s pod template by extracting value
+///    of variable `RABBITMQ_QUEUE_NAME` defined directly in `example-container`.
+///
+/// 3. For the duration of the session, `example-deployment` will be patched - the mirrord operator
+///    will substitute queue name in `RABBITMQ_QUEUE_NAME` variable with a name of an ephemeral
+///    queue, which gets the messages that don't match any session's filter.
+///
+/// 4. Local application will see a different value of the `RABBITMQ_QUEUE_NAME` - it will be a name
+///    of another ephemeral queue, which gets the messages with headers matching the session's
+///    `message_filter`.
+///
+/// 5. `MirrordRabbitMqClientConfig` named `example-config` living in mirrord operator's namespace
+///    will be used to manage the ephemeral queues and consume/publish messages.
+#[derive(CustomResource, Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[kube(
+    group = "queues.mirrord.metalbear.co",
+    version = "v1alpha",
+    kind = "MirrordRabbitMqQueuesConsumer",
+    namespaced,
+    printcolumn = r#"{"name":"CONSUMER-NAME", "type":"string", "description":"Name of the queue consumer workload.", "jsonPath":".spec.consumerName"}"#,
+    printcolumn = r#"{"name":"CONSUMER-KIND", "type":"string", "description":"Kind of the queue consumer workload.", "jsonPath":".spec.consumerKind"}"#,
+    printcolumn = r#"{"name":"CONSUMER-API-VERSION", "type":"string", "description":"Api version of the queue consumer workload.", "jsonPath":".spec.consumerApiVersion"}"#
+)]
+#[serde(rename_all = "camelCase")]
+pub struct MirrordRabbitMqQueuesConsumerSpec {
+    /// Workload name, for example `my-deployment`.
+    pub consumer_name: String,
+
+    /// Workload kind, for example `Deployment`.
+    pub consumer_kind: String,
+
+    /// Workload api version, for example `apps/v1`.
+    pub consumer_api_version: String,
+
+    /// Timeout for waiting until workload patch takes effect, that is at least one pod reads from
+    /// the ephemeral queue.
+    ///
+    /// Specified in seconds. Defaults to 60s.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub consumer_restart_timeout: Option<u32>,
+
+    /// List of consumed splittable queues.
+    pub queues: Vec<RabbitMqQueueDetails>,
+}
+
+/// Splittable RabbitMQ queue consumed by some remote target.
+#[derive(Clone, Debug, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RabbitMqQueueDetails {
+    /// Id of this queue. Can be used in mirrord config to identify this queue.
+    pub id: String,
+
+    /// All occurrences of this queue's name in the workload's pod template.
+    pub name_sources: Vec<QueuePropertySource>,
+
+    /// Links to [`MirrordRabbitMqClientConfig`] in the operator's namespace.
+    /// This config will be used to manage ephemeral queues and consume/publish messages.
+    pub client_config: String,
+}
+
+/// Source of some queue property required for RabbitMQ splitting.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize, JsonSchema, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum QueuePropertySource {
+    /// Environment variable with value defined directly in the pod template.
+    DirectEnvVar(EnvVarLocation),
+}
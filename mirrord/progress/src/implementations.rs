@@ -1,4 +1,8 @@
-use std::{collections::HashSet, time::Duration};
+use std::{
+    collections::HashSet,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
 
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
 use serde::Serialize;
@@ -6,9 +10,17 @@ use serde_json::Value;
 
 use crate::Progress;
 
+/// Source of the [`JsonProgress::id`]s.
+static NEXT_TASK_ID: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Debug)]
 pub struct JsonProgress {
-    parent: Option<String>,
+    /// Identifies this task in the messages, unique within the process.
+    ///
+    /// Unlike the names, the ids allow the IDEs to build the tree of tasks when some of them have
+    /// the same name.
+    id: u64,
+    parent: Option<(u64, String)>,
     name: String,
     done: bool,
     fail_on_drop: bool,
@@ -16,8 +28,13 @@ pub struct JsonProgress {
 
 impl JsonProgress {
     pub fn new(text: &str) -> JsonProgress {
+        Self::with_parent(text, None)
+    }
+
+    fn with_parent(text: &str, parent: Option<(u64, String)>) -> JsonProgress {
         let progress = JsonProgress {
-            parent: None,
+            id: NEXT_TASK_ID.fetch_add(1, Ordering::Relaxed),
+            parent,
             name: text.to_string(),
             done: false,
             fail_on_drop: true,
@@ -26,16 +43,22 @@ impl JsonProgress {
         progress
     }
 
-    fn print_new_task(&self) {
-        let message = ProgressMessage::NewTask(NewTaskMessage {
+    fn new_task_message(&self) -> ProgressMessage {
+        ProgressMessage::NewTask(NewTaskMessage {
+            id: self.id,
             name: self.name.clone(),
-            parent: self.parent.clone(),
-        });
-        message.print();
+            parent_id: self.parent.as_ref().map(|(id, _)| *id),
+            parent: self.parent.as_ref().map(|(_, name)| name.clone()),
+        })
+    }
+
+    fn print_new_task(&self) {
+        self.new_task_message().print();
     }
 
     fn print_finished_task(&self, success: bool, msg: Option<&str>) {
         let message = ProgressMessage::FinishedTask(FinishedTaskMessage {
+            id: self.id,
             name: self.name.clone(),
             message: msg.map(|s| s.to_string()),
             success,
@@ -46,14 +69,7 @@ impl JsonProgress {
 
 impl Progress for JsonProgress {
     fn subtask(&self, text: &str) -> JsonProgress {
-        let task = JsonProgress {
-            parent: Some(self.name.clone()),
-            name: text.to_string(),
-            done: false,
-            fail_on_drop: true,
-        };
-        task.print_new_task();
-        task
+        Self::with_parent(text, Some((self.id, self.name.clone())))
     }
 
     fn success(&mut self, msg: Option<&str>) {
@@ -68,6 +84,7 @@ impl Progress for JsonProgress {
 
     fn warning(&self, msg: &str) {
         let message = ProgressMessage::Warning(WarningMessage {
+            task_id: self.id,
             message: msg.to_string(),
         });
         message.print();
@@ -75,11 +92,28 @@ impl Progress for JsonProgress {
 
     fn info(&self, msg: &str) {
         let message = ProgressMessage::Info {
+            task_id: self.id,
             message: msg.to_string(),
         };
         message.print();
     }
 
+    fn summary(&self, summary: serde_json::Value) {
+        let message = ProgressMessage::Summary {
+            task_id: self.id,
+            summary,
+        };
+        message.print();
+    }
+
+    fn intproxy_stderr(&self, line: &str) {
+        let message = ProgressMessage::IntproxyStderr {
+            task_id: self.id,
+            line: line.to_string(),
+        };
+        message.print();
+    }
+
     fn ide(&self, value: serde_json::Value) {
         if std::env::var("MIRRORD_PROGRESS_SUPPORT_IDE")
             .ok()
@@ -276,8 +310,12 @@ impl Drop for SpinnerProgress {
 /// Message sent when a new task is created using subtask/new
 #[derive(Serialize, Debug, Clone, Default)]
 struct NewTaskMessage {
-    /// Task name (identifier)
+    /// Task id, see [`JsonProgress::id`].
+    id: u64,
+    /// Task name
     name: String,
+    /// Parent task id, if subtask.
+    parent_id: Option<u64>,
     /// Parent task name, if subtask.
     parent: Option<String>,
 }
@@ -285,6 +323,8 @@ struct NewTaskMessage {
 /// Message sent when a task is finished.
 #[derive(Serialize, Debug, Clone, Default)]
 struct FinishedTaskMessage {
+    /// Finished task id
+    id: u64,
     /// Finished task name
     name: String,
     /// Was the task successful?
@@ -293,9 +333,11 @@ struct FinishedTaskMessage {
     message: Option<String>,
 }
 
-/// Message sent when a task is finished.
+/// Message sent when a task issues a warning.
 #[derive(Serialize, Debug, Clone, Default)]
 struct WarningMessage {
+    /// Id of the task that issued the warning.
+    task_id: u64,
     /// Warning message
     message: String,
 }
@@ -349,8 +391,21 @@ enum ProgressMessage {
     Warning(WarningMessage),
     FinishedTask(FinishedTaskMessage),
     Info {
+        task_id: u64,
         message: String,
     },
+    /// Summary of a finished startup, e.g. of `mirrord exec`.
+    Summary {
+        task_id: u64,
+        summary: Value,
+    },
+    /// A line from the stderr of the internal proxy, reported separately so that the IDEs don't
+    /// have to guess it from the warnings.
+    #[serde(rename = "intproxy_stderr")]
+    IntproxyStderr {
+        task_id: u64,
+        line: String,
+    },
     /// Messages that are passed to the IDE and shown to the user in notification boxes.
     IdeMessage {
         /// It's a generic json [`Value`].
@@ -365,3 +420,97 @@ impl ProgressMessage {
         println!("{}", serde_json::to_string(self).unwrap());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// Subtasks carry the id of their parent, so that the IDEs can build the tree of tasks.
+    #[test]
+    fn subtask_parent_id() {
+        let parent = JsonProgress::new("parent");
+        let child = parent.subtask("child");
+        assert_ne!(child.id, parent.id);
+
+        assert_eq!(
+            serde_json::to_value(parent.new_task_message()).unwrap(),
+            json!({
+                "type": "NewTask",
+                "id": parent.id,
+                "name": "parent",
+                "parent_id": null,
+                "parent": null,
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(child.new_task_message()).unwrap(),
+            json!({
+                "type": "NewTask",
+                "id": child.id,
+                "name": "child",
+                "parent_id": parent.id,
+                "parent": "parent",
+            })
+        );
+    }
+
+    #[test]
+    fn message_format() {
+        let messages = [
+            (
+                ProgressMessage::FinishedTask(FinishedTaskMessage {
+                    id: 1,
+                    name: "task".to_string(),
+                    success: true,
+                    message: None,
+                }),
+                json!({
+                    "type": "FinishedTask",
+                    "id": 1,
+                    "name": "task",
+                    "success": true,
+                    "message": null,
+                }),
+            ),
+            (
+                ProgressMessage::Warning(WarningMessage {
+                    task_id: 1,
+                    message: "careful".to_string(),
+                }),
+                json!({ "type": "Warning", "task_id": 1, "message": "careful" }),
+            ),
+            (
+                ProgressMessage::IntproxyStderr {
+                    task_id: 2,
+                    line: "error: oops".to_string(),
+                },
+                json!({ "type": "intproxy_stderr", "task_id": 2, "line": "error: oops" }),
+            ),
+            (
+                ProgressMessage::Summary {
+                    task_id: 3,
+                    summary: json!({
+                        "environment_count": 4,
+                        "patched_path": null,
+                        "uses_operator": false,
+                    }),
+                },
+                json!({
+                    "type": "Summary",
+                    "task_id": 3,
+                    "summary": {
+                        "environment_count": 4,
+                        "patched_path": null,
+                        "uses_operator": false,
+                    },
+                }),
+            ),
+        ];
+
+        for (message, expected) in messages {
+            assert_eq!(serde_json::to_value(message).unwrap(), expected);
+        }
+    }
+}
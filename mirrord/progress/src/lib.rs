@@ -39,6 +39,17 @@ pub trait Progress: Sized + Send + Sync {
     /// You may use this to pass additional context to the IDE through the `value` object.
     fn ide(&self, _: serde_json::Value) {}
 
+    /// When you want to pass the summary of a finished startup (e.g. of `mirrord exec`) to the
+    /// IDE, in the `summary` object.
+    fn summary(&self, _: serde_json::Value) {}
+
+    /// When a line is read from the stderr of the internal proxy.
+    ///
+    /// Shown as a warning, unless the implementation reports it in a structured way.
+    fn intproxy_stderr(&self, line: &str) {
+        self.warning(&format!("internal proxy stderr: {line}"));
+    }
+
     /// When you want to print a message, cli only.
     fn print(&self, _: &str) {}
 
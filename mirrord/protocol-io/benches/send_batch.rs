@@ -0,0 +1,185 @@
+//! Compares how many writes it takes, and how long, to send a burst of small messages through a
+//! [`Connection`], which batches them, against writing every message on its own.
+//!
+//! Run with `cargo bench -p mirrord-protocol-io`.
+
+use std::{
+    pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use mirrord_protocol_io::{Connection, ProtocolEndpoint};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, DuplexStream, ReadBuf};
+
+/// How many messages every run sends.
+const MESSAGES: usize = 100_000;
+
+/// Payload of every message, about the size of a file stat or a small read.
+const PAYLOAD_LEN: usize = 64;
+
+/// How many queues send the messages concurrently.
+const QUEUES: usize = 4;
+
+/// How many times every benchmark runs.
+const RUNS: usize = 5;
+
+#[derive(Clone)]
+struct Bench;
+
+impl ProtocolEndpoint for Bench {
+    type InMsg = Vec<u8>;
+    type OutMsg = Vec<u8>;
+}
+
+/// Counts the writes to the inner stream.
+struct CountWrites {
+    inner: DuplexStream,
+    writes: Arc<AtomicUsize>,
+}
+
+impl AsyncRead for CountWrites {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for CountWrites {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(..)) = result {
+            self.writes.fetch_add(1, Ordering::Relaxed);
+        }
+        result
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+/// Writes and time it took to deliver [`MESSAGES`] messages.
+struct Report {
+    writes: usize,
+    elapsed: Duration,
+}
+
+/// Returns a stream that counts its writes, and a [`Connection`] that receives from it.
+async fn counted_stream() -> (CountWrites, Arc<AtomicUsize>, Connection<Bench>) {
+    let (stream, peer_stream) = tokio::io::duplex(1024 * 1024);
+    let writes = Arc::new(AtomicUsize::new(0));
+    let peer = Connection::<Bench>::from_stream(peer_stream).await.unwrap();
+
+    let stream = CountWrites {
+        inner: stream,
+        writes: writes.clone(),
+    };
+
+    (stream, writes, peer)
+}
+
+/// Sends the messages through the queues of a [`Connection`], which batches them.
+async fn batched() -> Report {
+    let (stream, writes, mut peer) = counted_stream().await;
+    let connection = Connection::<Bench>::from_stream(stream).await.unwrap();
+
+    let start = Instant::now();
+
+    let senders: Vec<_> = (0..QUEUES)
+        .map(|_| {
+            let handle = connection.tx_handle();
+            tokio::spawn(async move {
+                for _ in 0..MESSAGES / QUEUES {
+                    handle.send(vec![0; PAYLOAD_LEN]).await;
+                }
+            })
+        })
+        .collect();
+
+    for _ in 0..MESSAGES {
+        peer.recv().await.unwrap();
+    }
+
+    let elapsed = start.elapsed();
+
+    for sender in senders {
+        sender.await.unwrap();
+    }
+
+    Report {
+        writes: writes.load(Ordering::Relaxed),
+        elapsed,
+    }
+}
+
+/// Writes every message to the stream on its own.
+async fn unbatched() -> Report {
+    let (mut stream, writes, mut peer) = counted_stream().await;
+    let encoded =
+        bincode::encode_to_vec(vec![0_u8; PAYLOAD_LEN], bincode::config::standard()).unwrap();
+
+    let start = Instant::now();
+
+    let sender = tokio::spawn(async move {
+        for _ in 0..MESSAGES {
+            stream.write_all(&encoded).await.unwrap();
+            stream.flush().await.unwrap();
+        }
+        stream
+    });
+
+    for _ in 0..MESSAGES {
+        peer.recv().await.unwrap();
+    }
+
+    let elapsed = start.elapsed();
+
+    sender.await.unwrap();
+
+    Report {
+        writes: writes.load(Ordering::Relaxed),
+        elapsed,
+    }
+}
+
+fn print_report(name: &str, reports: &[Report]) {
+    let writes = reports.iter().map(|report| report.writes).sum::<usize>() / reports.len();
+    let elapsed = reports.iter().map(|report| report.elapsed).min().unwrap();
+    let throughput = MESSAGES as f64 / elapsed.as_secs_f64();
+
+    println!(
+        "{name:>10}: {MESSAGES} messages in {writes} writes, best {elapsed:?} \
+         ({throughput:.0} messages/s)"
+    );
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let mut reports = Vec::with_capacity(RUNS);
+    for _ in 0..RUNS {
+        reports.push(unbatched().await);
+    }
+    print_report("unbatched", &reports);
+
+    reports.clear();
+    for _ in 0..RUNS {
+        reports.push(batched().await);
+    }
+    print_report("batched", &reports);
+}
@@ -4,23 +4,25 @@ use std::{
     collections::{HashMap, VecDeque},
     fmt,
     io::{self},
-    marker::PhantomData,
+    pin::Pin,
     sync::{
         Arc, Mutex,
         atomic::{AtomicUsize, Ordering},
     },
     task::{Context, Poll},
+    time::Duration,
 };
 
 use actix_codec::{AsyncRead, AsyncWrite, Decoder, Encoder, Framed};
 use bincode::error::DecodeError;
 use bytes::{BufMut, BytesMut};
 use futures::{Sink, SinkExt, Stream, StreamExt, future::Either};
-use mirrord_protocol::{ClientMessage, DaemonMessage, ProtocolCodec};
+use mirrord_protocol::{ClientMessage, DEFAULT_DECODE_LIMIT, DaemonMessage, ProtocolCodec};
 use rand::seq::IteratorRandom;
 use tokio::{
     pin, select,
     sync::{Notify, futures::OwnedNotified, mpsc},
+    time::{Instant, sleep_until},
 };
 use tokio_util::sync::CancellationToken;
 use tracing::{Level, instrument};
@@ -45,6 +47,12 @@ impl<T, I, O> Transport<I, O> for T where
 pub trait ProtocolEndpoint: 'static + Sized + Clone {
     type InMsg: bincode::Decode<()> + Send + fmt::Debug;
     type OutMsg: bincode::Encode + Send + fmt::Debug;
+
+    /// Whether the outgoing message should be flushed right away, instead of waiting to be batched
+    /// with the messages that follow it.
+    fn is_urgent(_msg: &Self::OutMsg) -> bool {
+        false
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -64,22 +72,30 @@ pub struct Agent;
 impl ProtocolEndpoint for Client {
     type InMsg = DaemonMessage;
     type OutMsg = ClientMessage;
+
+    fn is_urgent(msg: &ClientMessage) -> bool {
+        msg.is_latency_sensitive()
+    }
 }
 
 impl ProtocolEndpoint for Agent {
     type InMsg = ClientMessage;
     type OutMsg = DaemonMessage;
+
+    fn is_urgent(msg: &DaemonMessage) -> bool {
+        msg.is_latency_sensitive()
+    }
 }
 
 // Same as protocolCodec but outputs raw Vec<u8>s
-struct Codec<I>(PhantomData<I>);
+struct Codec<I>(ProtocolCodec<I, ()>);
 
 impl<I: bincode::Decode<()>> Decoder for Codec<I> {
     type Item = I;
     type Error = io::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<Self::Item>> {
-        ProtocolCodec::<I, ()>::default().decode(src)
+        self.0.decode(src)
     }
 }
 
@@ -106,6 +122,12 @@ impl<I> Encoder<Vec<u8>> for Codec<I> {
 /// nonempty queues and sending it over the wire. Future versions will
 /// use message chunking to fairly split the available bandwidth
 /// between all queues (as messages can vary in size).
+///
+/// Messages that are already waiting in the queues are written out
+/// together, unless [`ProtocolEndpoint::is_urgent`]. This does not
+/// change what goes over the wire, as the messages are
+/// self-delimiting and every version of the peer can decode them from
+/// a single read.
 pub struct Connection<Type: ProtocolEndpoint> {
     rx: mpsc::Receiver<Type::InMsg>,
     shared_state: Arc<SharedState<Type>>,
@@ -118,7 +140,20 @@ impl<Type: ProtocolEndpoint> Connection<Type> {
     where
         IO: AsyncIO,
     {
-        let framed = Framed::new(inner, Codec::<Type::InMsg>(PhantomData));
+        Self::from_stream_with_max_message_size(inner, DEFAULT_DECODE_LIMIT).await
+    }
+
+    /// Same as [`Connection::from_stream`], but incoming messages bigger than `max_message_size`
+    /// bytes fail to decode, see [`ProtocolCodec::with_max_message_size`].
+    pub async fn from_stream_with_max_message_size<IO>(
+        inner: IO,
+        max_message_size: usize,
+    ) -> Result<Self, ProtocolError>
+    where
+        IO: AsyncIO,
+    {
+        let codec = ProtocolCodec::default().with_max_message_size(max_message_size);
+        let framed = Framed::new(inner, Codec::<Type::InMsg>(codec));
 
         let (inbound_tx, inbound_rx) = mpsc::channel(64);
 
@@ -138,18 +173,22 @@ impl<Type: ProtocolEndpoint> Connection<Type> {
 
     /// Create a new connection, running over a `Sink` + `Stream` of `Vec<u8>`s.
     /// Used for connecting to the operator over a websocket connection.
+    ///
+    /// Incoming messages bigger than `max_message_size` bytes fail to decode, see
+    /// [`ProtocolCodec::with_max_message_size`].
     pub async fn from_channel<C, Filter>(
         channel: C,
         filter: Option<Filter>,
+        max_message_size: usize,
     ) -> Result<Self, ProtocolError>
     where
         C: Transport<Vec<u8>, Vec<u8>>,
         Filter: Fn(Type::OutMsg) -> Either<Type::OutMsg, Type::InMsg> + Send + Sync + 'static,
         C::Error: From<DecodeError> + std::error::Error + Send + 'static,
     {
-        let framed = channel.map(|msg| {
+        let framed = channel.map(move |msg| {
             msg.and_then(|e| {
-                bincode::decode_from_slice::<Type::InMsg, _>(&e, bincode::config::standard())
+                mirrord_protocol::decode_message_with_limit::<Type::InMsg>(&e, max_message_size)
                     .map(|(msg, _)| msg)
                     .map_err(<C::Error as From<DecodeError>>::from)
             })
@@ -250,7 +289,7 @@ where
     Type::OutMsg: bincode::Decode<()>,
 {
     pub async fn next(&self) -> Option<Type::OutMsg> {
-        bincode::decode_from_slice(&self.0.next().await, bincode::config::standard())
+        mirrord_protocol::decode_message(&self.0.next().await.bytes)
             .ok()
             .map(|e| e.0)
     }
@@ -267,7 +306,15 @@ async fn io_task<Channel, Type>(
     Channel::Error: std::error::Error + Send,
 {
     pin!(framed);
+
+    // The batch that was fed into `framed`, but not flushed yet, see [`send_batch`].
+    let mut batch: Option<Batch> = None;
+    // Whether the previous batch was big, see [`send_batch`].
+    let mut busy = false;
+
     loop {
+        let deadline = batch.as_ref().map(|batch| batch.deadline);
+
         select! {
             // When >=2 futures complete simultaneously, poll them in
             // the order they are declared here. We want everything to
@@ -276,10 +323,40 @@ async fn io_task<Channel, Type>(
             // drop messages.
             biased;
 
+            // Waiting for the batch to fill up happens here, and not in `send_batch`, so that
+            // we keep receiving messages in the meantime.
+            _ = sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                let Some(flushed) = batch.take() else {
+                    continue;
+                };
+
+                match flush_batch(framed.as_mut(), flushed).await {
+                    Ok(was_busy) => busy = was_busy,
+                    Err(error) => {
+                        tracing::error!(?error, "failed to send message");
+                        break;
+                    }
+                }
+            }
             to_send = queues.next() => {
-                if let Err(error) = framed.send(to_send).await {
-                    tracing::error!(?error, "failed to send message");
-                    break;
+                let pending = batch.get_or_insert_with(Batch::new);
+
+                let flush = match send_batch(framed.as_mut(), &queues, to_send, pending).await {
+                    Ok(complete) => complete || !busy,
+                    Err(error) => {
+                        tracing::error!(?error, "failed to send message");
+                        break;
+                    }
+                };
+
+                if flush && let Some(flushed) = batch.take() {
+                    match flush_batch(framed.as_mut(), flushed).await {
+                        Ok(was_busy) => busy = was_busy,
+                        Err(error) => {
+                            tracing::error!(?error, "failed to send message");
+                            break;
+                        }
+                    }
                 }
             }
             received = framed.next() => {
@@ -310,9 +387,99 @@ async fn io_task<Channel, Type>(
     let _ = framed.close().await;
 }
 
+/// Outgoing batches are flushed once they reach this many bytes.
+const MAX_BATCH_BYTES: usize = 64 * 1024;
+
+/// How long a batch waits for more messages when we're busy, see [`send_batch`].
+const MAX_BATCH_DELAY: Duration = Duration::from_millis(1);
+
+/// A batch of at least this many messages means that we're busy.
+const BUSY_BATCH_LEN: usize = 8;
+
+/// Outgoing messages that were fed into the sink, but not flushed yet.
+#[derive(Debug)]
+struct Batch {
+    /// How many messages were fed.
+    len: usize,
+    /// How many bytes were fed.
+    bytes: usize,
+    /// When the batch is flushed at the latest.
+    deadline: Instant,
+}
+
+impl Batch {
+    fn new() -> Self {
+        Self {
+            len: 0,
+            bytes: 0,
+            deadline: Instant::now() + MAX_BATCH_DELAY,
+        }
+    }
+}
+
+/// Feeds `first` and the messages that are already waiting in the `queues` into the `framed`
+/// sink, so that a burst of small messages is flushed with a single write.
+///
+/// Returns whether the `batch` is complete and should be flushed right away, which is when it
+/// reaches [`MAX_BATCH_BYTES`], or right after an urgent message (see
+/// [`ProtocolEndpoint::is_urgent`]).
+///
+/// Otherwise, [`io_task`] flushes the batch right away only when it's not busy (the previous
+/// batch was small). When busy, we're likely in the middle of a bulk transfer, and the batch
+/// waits up to [`MAX_BATCH_DELAY`] for more messages.
+async fn send_batch<Channel, Type>(
+    mut framed: Pin<&mut Channel>,
+    queues: &SharedState<Type>,
+    first: Outgoing,
+    batch: &mut Batch,
+) -> Result<bool, Channel::Error>
+where
+    Type: ProtocolEndpoint,
+    Channel: Sink<Vec<u8>>,
+{
+    let mut next = Some(first);
+
+    while let Some(Outgoing { bytes, urgent }) = next.take() {
+        batch.len += 1;
+        batch.bytes += bytes.len();
+        framed.as_mut().feed(bytes).await?;
+
+        if urgent || batch.bytes >= MAX_BATCH_BYTES {
+            return Ok(true);
+        }
+
+        next = queues.poll_next();
+    }
+
+    Ok(false)
+}
+
+/// Flushes the messages of the `batch` from the `framed` sink.
+///
+/// Returns whether the batch was big, which means that we're busy.
+async fn flush_batch<Channel>(
+    framed: Pin<&mut Channel>,
+    batch: Batch,
+) -> Result<bool, Channel::Error>
+where
+    Channel: Sink<Vec<u8>>,
+{
+    framed.flush().await?;
+
+    Ok(batch.len >= BUSY_BATCH_LEN)
+}
+
+/// An encoded outgoing message.
+#[derive(Debug)]
+struct Outgoing {
+    bytes: Vec<u8>,
+    /// See [`ProtocolEndpoint::is_urgent`].
+    urgent: bool,
+}
+
 #[derive(Debug, Default)]
 struct OutQueue {
-    messages: VecDeque<Vec<u8>>,
+    messages: VecDeque<Outgoing>,
     used_bytes: usize,
 
     free: Arc<Notify>,
@@ -388,8 +555,8 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
     fn try_push(
         &self,
         queue_id: QueueId,
-        encoded: Vec<u8>,
-    ) -> Result<(), (Vec<u8>, OwnedNotified)> {
+        encoded: Outgoing,
+    ) -> Result<(), (Outgoing, OwnedNotified)> {
         let mut lock = self.queues.lock().unwrap();
 
         // Garbage-collect unused queues
@@ -405,7 +572,7 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
             return Err((encoded, queue.free.clone().notified_owned()));
         }
 
-        queue.used_bytes += encoded.len();
+        queue.used_bytes += encoded.bytes.len();
         queue.messages.push_back(encoded);
 
         if queue.messages.len() == 1 {
@@ -443,7 +610,11 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
             }
         }
 
-        let mut encoded = bincode::encode_to_vec(msg, bincode::config::standard()).unwrap();
+        let urgent = Type::is_urgent(&msg);
+        let mut encoded = Outgoing {
+            bytes: bincode::encode_to_vec(msg, bincode::config::standard()).unwrap(),
+            urgent,
+        };
 
         loop {
             match self.try_push(id, encoded) {
@@ -458,7 +629,7 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
 
     /// Check for enqueued messages and return one from a
     /// randomly-picked nonempty queue.
-    fn poll_next(&self) -> Option<Vec<u8>> {
+    fn poll_next(&self) -> Option<Outgoing> {
         let mut lock = self.queues.lock().unwrap();
 
         // If `ready` is empty then we have nothing to do.
@@ -477,7 +648,7 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
 
         let was_full = queue.used_bytes >= Self::MAX_CAPACITY;
 
-        queue.used_bytes -= next.len();
+        queue.used_bytes -= next.bytes.len();
 
         if was_full && queue.used_bytes < Self::MAX_CAPACITY {
             queue.free.notify_waiters();
@@ -491,7 +662,7 @@ impl<Type: ProtocolEndpoint> SharedState<Type> {
     }
 
     /// Wait for a new message to be enqueued and return it.
-    async fn next(&self) -> Vec<u8> {
+    async fn next(&self) -> Outgoing {
         loop {
             match self.poll_next() {
                 Some(msg) => break msg,
@@ -584,11 +755,72 @@ mod tests {
         }
     }
 
+    /// Messages from this sender are [`ProtocolEndpoint::is_urgent`].
+    const URGENT: u32 = u32::MAX;
+
     #[derive(Clone)]
     struct Test;
     impl ProtocolEndpoint for Test {
         type InMsg = Message;
         type OutMsg = Message;
+
+        fn is_urgent(msg: &Message) -> bool {
+            msg.from == URGENT
+        }
+    }
+
+    /// Records the size of every write to the inner stream.
+    struct RecordWrites<IO> {
+        inner: IO,
+        writes: Arc<Mutex<Vec<usize>>>,
+    }
+
+    impl<IO: AsyncRead + Unpin> AsyncRead for RecordWrites<IO> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut tokio::io::ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl<IO: AsyncWrite + Unpin> AsyncWrite for RecordWrites<IO> {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let result = Pin::new(&mut self.inner).poll_write(cx, buf);
+            if let Poll::Ready(Ok(written)) = result {
+                self.writes.lock().unwrap().push(written);
+            }
+            result
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    /// Returns a [`Connection`] that records its writes, and its peer.
+    async fn recorded_connection() -> (Connection<Test>, Connection<Test>, Arc<Mutex<Vec<usize>>>) {
+        let (stream, peer_stream) = tokio::io::duplex(1024 * 1024);
+        let writes = Arc::new(Mutex::new(Vec::new()));
+
+        let connection = Connection::<Test>::from_stream(RecordWrites {
+            inner: stream,
+            writes: writes.clone(),
+        })
+        .await
+        .unwrap();
+        let peer = Connection::<Test>::from_stream(peer_stream).await.unwrap();
+
+        (connection, peer, writes)
     }
 
     #[tokio::test]
@@ -646,4 +878,74 @@ mod tests {
             assert_eq!(seq.next(), None);
         }
     }
+
+    /// Messages that pile up in the queues are written out in a few big writes instead of one
+    /// write per message, and still arrive in order.
+    #[tokio::test]
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    async fn batches_queued_messages() {
+        let (connection, mut peer, writes) = recorded_connection().await;
+
+        let num_queues = 4;
+        let msg_count_per_queue = 250;
+
+        let handles: Vec<_> = (0..num_queues).map(|_| connection.tx_handle()).collect();
+        let sequences: Vec<Vec<Message>> = (0..num_queues)
+            .map(|n| (0..msg_count_per_queue).map(|_| Message::new(n)).collect())
+            .collect();
+
+        // The IO task does not run before we yield, so all messages are queued at once.
+        for i in 0..msg_count_per_queue {
+            for (handle, sequence) in handles.iter().zip(&sequences) {
+                handle.send(sequence[i].clone()).await;
+            }
+        }
+
+        let mut sequences: Vec<_> = sequences.into_iter().map(|v| v.into_iter()).collect();
+        for _ in 0..num_queues * msg_count_per_queue {
+            let msg = peer.recv().await.unwrap();
+            let sequence = sequences.get_mut(msg.from as usize).unwrap();
+            assert_eq!(sequence.next(), Some(msg));
+        }
+
+        let writes = writes.lock().unwrap().len();
+        assert!(
+            writes < 10,
+            "{} messages took {writes} writes",
+            num_queues * msg_count_per_queue
+        );
+    }
+
+    /// Urgent messages are flushed right away, without the messages queued after them.
+    #[tokio::test]
+    #[rstest]
+    #[timeout(Duration::from_secs(5))]
+    async fn flushes_urgent_messages() {
+        let (connection, mut peer, writes) = recorded_connection().await;
+
+        let urgent = Message::new(URGENT);
+        let messages: Vec<_> = (0..100).map(|_| Message::new(0)).collect();
+
+        connection.send(urgent.clone()).await;
+        for msg in messages.clone() {
+            connection.send(msg).await;
+        }
+
+        assert_eq!(peer.recv().await, Some(urgent.clone()));
+        for msg in messages {
+            assert_eq!(peer.recv().await, Some(msg));
+        }
+
+        let urgent_len = bincode::encode_to_vec(urgent, bincode::config::standard())
+            .unwrap()
+            .len();
+        let writes = writes.lock().unwrap();
+        assert_eq!(writes.first(), Some(&urgent_len));
+        assert!(
+            writes.len() < 10,
+            "101 messages took {} writes",
+            writes.len()
+        );
+    }
 }
@@ -0,0 +1,32 @@
+//! Messages of the agent logs feature.
+//!
+//! The client asks for the most recent log lines of the agent with
+//! [`ClientMessage::GetAgentLogs`], answered with [`DaemonMessage::AgentLogs`]. This allows for
+//! inspecting the agent's logs without access to the cluster.
+//!
+//! [`ClientMessage::GetAgentLogs`]: crate::ClientMessage::GetAgentLogs
+//! [`DaemonMessage::AgentLogs`]: crate::DaemonMessage::AgentLogs
+
+use std::sync::LazyLock;
+
+use bincode::{Decode, Encode};
+use semver::VersionReq;
+
+/// Minimal mirrord-protocol version that allows for
+/// [`ClientMessage::GetAgentLogs`](crate::ClientMessage::GetAgentLogs).
+pub static AGENT_LOGS_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.35.0".parse().expect("Bad Identifier"));
+
+/// Maximum total size of the [`AgentLogs::lines`] sent in one response, in bytes.
+pub const AGENT_LOGS_MAX_BYTES: usize = 512 * 1024;
+
+/// Most recent log lines of the agent, sent in response to
+/// [`ClientMessage::GetAgentLogs`](crate::ClientMessage::GetAgentLogs).
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Default)]
+pub struct AgentLogs {
+    /// Log lines, oldest first.
+    pub lines: Vec<String>,
+    /// Whether older lines were left out, because the agent no longer has them or to respect
+    /// [`AGENT_LOGS_MAX_BYTES`].
+    pub truncated: bool,
+}
@@ -21,16 +21,20 @@ use semver::VersionReq;
 
 use crate::{
     ResponseError,
+    agent_logs::AgentLogs,
     dns::{
         GetAddrInfoRequest, GetAddrInfoRequestV2, GetAddrInfoResponse, ReverseDnsLookupRequest,
         ReverseDnsLookupResponse,
     },
     file::*,
     outgoing::{
+        DaemonConnectV2,
         tcp::{DaemonTcpOutgoing, LayerTcpOutgoing},
         udp::{DaemonUdpOutgoing, LayerUdpOutgoing},
     },
     tcp::{DaemonTcp, LayerTcp, LayerTcpSteal},
+    time::RemoteTime,
+    udp::{DaemonUdp, LayerUdp},
     vpn::{ClientVpn, ServerVpn},
 };
 
@@ -116,12 +120,62 @@ pub enum FileRequest {
     Futimens(FutimensRequest),
     Fchown(FchownRequest),
     Fchmod(FchmodRequest),
+    GetXattr(GetXattrRequest),
+    SetXattr(SetXattrRequest),
+    ListXattr(ListXattrRequest),
+    Flock(FlockRequest),
+    Syncfs(SyncfsRequest),
+
+    /// Same as [`FileRequest::ReadDirBatch`], but can resume the iteration from a
+    /// [`DirCursor`]. Intproxy only, see [`READDIR_CURSOR_VERSION`].
+    ReadDirBatchFrom(ReadDirBatchFromRequest),
+
+    /// Same as [`FileRequest::Open`], but with [`OpenFlagsInternal`]. See
+    /// [`OPEN_FLAGS_VERSION`].
+    OpenV2(OpenFileRequestV2),
+
+    /// Same as [`FileRequest::OpenRelative`], but with [`OpenFlagsInternal`]. See
+    /// [`OPEN_FLAGS_VERSION`].
+    OpenRelativeV2(OpenRelativeFileRequestV2),
+
+    /// See [`IOV_VERSION`].
+    Readv(ReadvFileRequest),
+
+    /// See [`IOV_VERSION`].
+    Writev(WritevFileRequest),
+
+    /// Same as [`FileRequest::Access`], but with [`AccessFlagsInternal`]. See
+    /// [`ACCESS_FLAGS_VERSION`].
+    AccessV2(AccessFileRequestV2),
+
+    /// See [`COPY_FILE_RANGE_VERSION`].
+    CopyFileRange(CopyFileRangeRequest),
+
+    /// Answered with [`FileResponse::Futimens`]. See [`PATH_METADATA_VERSION`].
+    Utimens(UtimensRequest),
+
+    /// Answered with [`FileResponse::Fchown`]. See [`PATH_METADATA_VERSION`].
+    Chown(ChownRequest),
+
+    /// See [`FCNTL_LOCK_VERSION`].
+    FcntlLock(FcntlLockRequest),
+
+    /// Same as [`FileRequest::OpenV2`], but with `O_NONBLOCK`. See [`OPEN_NONBLOCK_VERSION`].
+    OpenV3(OpenFileRequestV3),
+
+    /// Same as [`FileRequest::OpenRelativeV2`], but with `O_NONBLOCK`. See
+    /// [`OPEN_NONBLOCK_VERSION`].
+    OpenRelativeV3(OpenRelativeFileRequestV3),
 }
 
 /// Minimal mirrord-protocol version that allows `ClientMessage::ReadyForLogs` message.
 pub static CLIENT_READY_FOR_LOGS: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.3.1".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows [`ClientMessage::ExtendTtl`] message.
+pub static EXTEND_TTL_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.34.0".parse().expect("Bad Identifier"));
+
 /// `-layer` --> `-agent` messages.
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub enum ClientMessage {
@@ -166,6 +220,29 @@ pub enum ClientMessage {
     ///
     /// Sent by the operator when enforcing hostname-based outgoing network policies.
     ReverseDnsLookup(ReverseDnsLookupRequest),
+    /// UDP mirror message.
+    ///
+    /// These are the messages used by the `mirror` feature (udp), and handled by the
+    /// `UdpMirrorApi` in the agent. Supported from
+    /// [`UDP_MIRROR_VERSION`](crate::udp::UDP_MIRROR_VERSION).
+    Udp(LayerUdp),
+    /// Asks for the wall-clock time of the agent, answered with [`DaemonMessage::RemoteTime`].
+    ///
+    /// Supported from [`REMOTE_TIME_VERSION`](crate::time::REMOTE_TIME_VERSION).
+    GetRemoteTime,
+    /// Asks the agent to stay alive for at least this many seconds, even if all of its clients
+    /// disconnect in the meantime. Not answered.
+    ///
+    /// Sent periodically as a keepalive, so that the agent waits for the client to reconnect.
+    /// Supported from [`EXTEND_TTL_VERSION`].
+    ExtendTtl(u64),
+    /// Asks for at most `tail` of the most recent log lines of the agent, answered with
+    /// [`DaemonMessage::AgentLogs`].
+    ///
+    /// Supported from [`AGENT_LOGS_VERSION`](crate::agent_logs::AGENT_LOGS_VERSION).
+    GetAgentLogs {
+        tail: u64,
+    },
 }
 
 /// Type alias for `Result`s that should be returned from mirrord-agent to mirrord-layer.
@@ -196,6 +273,58 @@ pub enum FileResponse {
     Futimens(RemoteResult<()>),
     Fchown(RemoteResult<()>),
     Fchmod(RemoteResult<()>),
+    GetXattr(RemoteResult<GetXattrResponse>),
+    SetXattr(RemoteResult<()>),
+    ListXattr(RemoteResult<ListXattrResponse>),
+    Flock(RemoteResult<()>),
+    Syncfs(RemoteResult<()>),
+    ReadDirBatchFrom(RemoteResult<ReadDirBatchFromResponse>),
+    Readv(RemoteResult<ReadvFileResponse>),
+    Writev(RemoteResult<WriteFileResponse>),
+    CopyFileRange(RemoteResult<CopyFileRangeResponse>),
+    FcntlLock(RemoteResult<FcntlLockResponse>),
+}
+
+impl FileResponse {
+    /// Returns the error carried by this response, if any.
+    pub fn error_mut(&mut self) -> Option<&mut ResponseError> {
+        match self {
+            Self::Open(Err(error))
+            | Self::Read(Err(error))
+            | Self::ReadLimited(Err(error))
+            | Self::Write(Err(error))
+            | Self::WriteLimited(Err(error))
+            | Self::Seek(Err(error))
+            | Self::Access(Err(error))
+            | Self::Xstat(Err(error))
+            | Self::XstatFs(Err(error))
+            | Self::ReadDir(Err(error))
+            | Self::OpenDir(Err(error))
+            | Self::GetDEnts64(Err(error))
+            | Self::ReadLink(Err(error))
+            | Self::ReadDirBatch(Err(error))
+            | Self::MakeDir(Err(error))
+            | Self::RemoveDir(Err(error))
+            | Self::Unlink(Err(error))
+            | Self::XstatFsV2(Err(error))
+            | Self::Rename(Err(error))
+            | Self::Ftruncate(Err(error))
+            | Self::Futimens(Err(error))
+            | Self::Fchown(Err(error))
+            | Self::Fchmod(Err(error))
+            | Self::GetXattr(Err(error))
+            | Self::SetXattr(Err(error))
+            | Self::ListXattr(Err(error))
+            | Self::Flock(Err(error))
+            | Self::Syncfs(Err(error))
+            | Self::ReadDirBatchFrom(Err(error))
+            | Self::Readv(Err(error))
+            | Self::Writev(Err(error))
+            | Self::CopyFileRange(Err(error))
+            | Self::FcntlLock(Err(error)) => Some(error),
+            _ => None,
+        }
+    }
 }
 
 /// `-agent` --> `-layer` messages.
@@ -230,6 +359,84 @@ pub enum DaemonMessage {
     ///
     /// Sent by the agent in response to [`ClientMessage::ReverseDnsLookup`].
     ReverseDnsLookup(RemoteResult<ReverseDnsLookupResponse>),
+    /// UDP mirror message.
+    ///
+    /// Sent by the agent in response to [`ClientMessage::Udp`].
+    Udp(DaemonUdp),
+    /// Wall-clock time of the agent.
+    ///
+    /// Sent by the agent in response to [`ClientMessage::GetRemoteTime`].
+    RemoteTime(RemoteTime),
+    /// Most recent log lines of the agent.
+    ///
+    /// Sent by the agent in response to [`ClientMessage::GetAgentLogs`].
+    AgentLogs(AgentLogs),
+}
+
+impl ClientMessage {
+    /// Whether this message should be flushed to the agent right away, instead of waiting to be
+    /// batched with the messages that follow it.
+    pub fn is_latency_sensitive(&self) -> bool {
+        matches!(
+            self,
+            Self::Close
+                | Self::Ping
+                | Self::OperatorPong(..)
+                | Self::GetAddrInfoRequest(..)
+                | Self::GetAddrInfoRequestV2(..)
+        )
+    }
+}
+
+impl DaemonMessage {
+    /// Whether this message should be flushed to the client right away, instead of waiting to be
+    /// batched with the messages that follow it.
+    pub fn is_latency_sensitive(&self) -> bool {
+        matches!(
+            self,
+            Self::Close(..) | Self::Pong | Self::OperatorPing(..) | Self::GetAddrInfoResponse(..)
+        )
+    }
+
+    /// Returns the [`ResponseError`] carried by this message, if any.
+    ///
+    /// Used by the agent to adjust errors for clients with older protocol versions, see
+    /// [`ResponseError::downgrade_error_kind`].
+    pub fn response_error_mut(&mut self) -> Option<&mut ResponseError> {
+        match self {
+            Self::File(response) => response.error_mut(),
+            Self::Tcp(DaemonTcp::SubscribeResult(Err(error)))
+            | Self::Tcp(DaemonTcp::UnixSubscribeResult(Err(error)))
+            | Self::TcpSteal(DaemonTcp::SubscribeResult(Err(error)))
+            | Self::TcpSteal(DaemonTcp::UnixSubscribeResult(Err(error)))
+            | Self::TcpOutgoing(
+                DaemonTcpOutgoing::Connect(Err(error))
+                | DaemonTcpOutgoing::Read(Err(error))
+                | DaemonTcpOutgoing::ConnectV2(DaemonConnectV2 {
+                    connect: Err(error),
+                    ..
+                }),
+            )
+            | Self::UdpOutgoing(
+                DaemonUdpOutgoing::Connect(Err(error))
+                | DaemonUdpOutgoing::Read(Err(error))
+                | DaemonUdpOutgoing::ConnectV2(DaemonConnectV2 {
+                    connect: Err(error),
+                    ..
+                }),
+            )
+            | Self::GetEnvVarsResponse(Err(error))
+            | Self::GetAddrInfoResponse(GetAddrInfoResponse(Err(error)))
+            | Self::ReverseDnsLookup(
+                Err(error)
+                | Ok(ReverseDnsLookupResponse {
+                    hostname: Err(error),
+                }),
+            )
+            | Self::Udp(DaemonUdp::SubscribeResult(Err(error))) => Some(error),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Encode, Decode, PartialEq, Eq, Clone, From, Into, Deref)]
@@ -243,15 +450,81 @@ impl core::fmt::Debug for RemoteEnvVars {
     }
 }
 
-pub struct ProtocolCodec<I, O> {
+/// Default cap on the memory (in bytes) that decoding a single message can allocate, see
+/// [`ProtocolCodec`].
+///
+/// Way above the size of any message we send, but low enough that a malformed message can't make
+/// us allocate all of the memory.
+pub const DEFAULT_DECODE_LIMIT: usize = 256 * 1024 * 1024;
+
+/// Hard upper bound for the size (in bytes) of a single message, see
+/// [`ProtocolCodec::with_max_message_size`].
+pub const MAX_DECODE_LIMIT: usize = 1024 * 1024 * 1024;
+
+/// Decodes a single message from `src`, with the [`DEFAULT_DECODE_LIMIT`].
+///
+/// Returns the message and the number of bytes it took.
+pub fn decode_message<I: Decode<()>>(src: &[u8]) -> Result<(I, usize), DecodeError> {
+    decode_message_limited::<I, DEFAULT_DECODE_LIMIT>(src, DEFAULT_DECODE_LIMIT)
+}
+
+/// Decodes a single message from `src`, failing with [`DecodeError::LimitExceeded`] if it's
+/// bigger than `max_message_size` bytes (capped at [`MAX_DECODE_LIMIT`]).
+///
+/// Returns the message and the number of bytes it took.
+pub fn decode_message_with_limit<I: Decode<()>>(
+    src: &[u8],
+    max_message_size: usize,
+) -> Result<(I, usize), DecodeError> {
+    decode_message_limited::<I, DEFAULT_DECODE_LIMIT>(src, max_message_size.min(MAX_DECODE_LIMIT))
+}
+
+/// Decodes a single message from `src`, allocating at most `DECODE_LIMIT` bytes, unless
+/// `max_message_size` is higher.
+///
+/// The bincode limit is a part of its config type, so a higher `max_message_size` is handled by
+/// decoding again with the [`MAX_DECODE_LIMIT`], and checking the size of the message afterwards.
+fn decode_message_limited<I: Decode<()>, const DECODE_LIMIT: usize>(
+    src: &[u8],
+    max_message_size: usize,
+) -> Result<(I, usize), DecodeError> {
+    let config = bincode::config::standard();
+
+    let result = match bincode::decode_from_slice(src, config.with_limit::<DECODE_LIMIT>()) {
+        Err(DecodeError::LimitExceeded) if max_message_size > DECODE_LIMIT => {
+            bincode::decode_from_slice(src, config.with_limit::<MAX_DECODE_LIMIT>())
+        }
+        result => result,
+    };
+
+    match result {
+        Ok((_, read)) if read > max_message_size => Err(DecodeError::LimitExceeded),
+        // We already have more than `max_message_size` bytes, and the message is not complete.
+        Err(DecodeError::UnexpectedEnd { .. }) if src.len() > max_message_size => {
+            Err(DecodeError::LimitExceeded)
+        }
+        result => result,
+    }
+}
+
+/// Encodes outgoing messages and decodes incoming ones.
+///
+/// When decoding, length prefixes of byte buffers, strings and collections are checked against
+/// `DECODE_LIMIT` before anything is allocated for them, so that a malformed message fails to
+/// decode instead of allocating an arbitrary amount of memory.
+///
+/// Messages bigger than [`ProtocolCodec::with_max_message_size`] (`DECODE_LIMIT` by default) fail
+/// to decode as well.
+pub struct ProtocolCodec<I, O, const DECODE_LIMIT: usize = DEFAULT_DECODE_LIMIT> {
     config: bincode::config::Configuration,
+    max_message_size: usize,
     /// Phantom fields to make this struct generic over message types.
     _phantom_incoming_message: PhantomData<I>,
     _phantom_outgoing_message: PhantomData<O>,
 }
 
-impl<I, O> Copy for ProtocolCodec<I, O> {}
-impl<I, O> Clone for ProtocolCodec<I, O> {
+impl<I, O, const DECODE_LIMIT: usize> Copy for ProtocolCodec<I, O, DECODE_LIMIT> {}
+impl<I, O, const DECODE_LIMIT: usize> Clone for ProtocolCodec<I, O, DECODE_LIMIT> {
     fn clone(&self) -> Self {
         *self
     }
@@ -264,22 +537,33 @@ pub type ClientCodec = ProtocolCodec<DaemonMessage, ClientMessage>;
 // `DaemonMessage`s to the client.
 pub type DaemonCodec = ProtocolCodec<ClientMessage, DaemonMessage>;
 
-impl<I, O> Default for ProtocolCodec<I, O> {
+impl<I, O, const DECODE_LIMIT: usize> Default for ProtocolCodec<I, O, DECODE_LIMIT> {
     fn default() -> Self {
         Self {
             config: bincode::config::standard(),
+            max_message_size: DECODE_LIMIT,
             _phantom_incoming_message: Default::default(),
             _phantom_outgoing_message: Default::default(),
         }
     }
 }
 
-impl<I: bincode::Decode<()>, O> Decoder for ProtocolCodec<I, O> {
+impl<I, O, const DECODE_LIMIT: usize> ProtocolCodec<I, O, DECODE_LIMIT> {
+    /// Sets the max size (in bytes) of a decoded message, capped at [`MAX_DECODE_LIMIT`].
+    pub fn with_max_message_size(mut self, max_message_size: usize) -> Self {
+        self.max_message_size = max_message_size.min(MAX_DECODE_LIMIT);
+        self
+    }
+}
+
+impl<I: bincode::Decode<()>, O, const DECODE_LIMIT: usize> Decoder
+    for ProtocolCodec<I, O, DECODE_LIMIT>
+{
     type Item = I;
     type Error = io::Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> io::Result<Option<Self::Item>> {
-        match bincode::decode_from_slice(&src[..], self.config) {
+        match decode_message_limited::<I, DECODE_LIMIT>(&src[..], self.max_message_size) {
             Ok((message, read)) => {
                 src.advance(read);
                 Ok(Some(message))
@@ -290,7 +574,9 @@ impl<I: bincode::Decode<()>, O> Decoder for ProtocolCodec<I, O> {
     }
 }
 
-impl<I, O: bincode::Encode> Encoder<O> for ProtocolCodec<I, O> {
+impl<I, O: bincode::Encode, const DECODE_LIMIT: usize> Encoder<O>
+    for ProtocolCodec<I, O, DECODE_LIMIT>
+{
     type Error = io::Error;
 
     fn encode(&mut self, msg: O, dst: &mut BytesMut) -> Result<(), Self::Error> {
@@ -322,7 +608,11 @@ mod tests {
     use bytes::{BufMut, BytesMut};
 
     use super::*;
-    use crate::{Payload, tcp::TcpData};
+    use crate::{
+        ErrorKindInternal, Payload, RemoteIOError,
+        tcp::{NewUnixConnection, TcpData},
+        udp::UdpDatagram,
+    };
 
     #[test]
     fn sanity_client_encode_decode() {
@@ -359,6 +649,485 @@ mod tests {
         assert!(buf.is_empty());
     }
 
+    #[test]
+    fn xattr_requests_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        let messages = [
+            FileRequest::GetXattr(GetXattrRequest {
+                target: XattrTarget::Path("/etc/hosts".into()),
+                name: b"security.selinux".to_vec(),
+            }),
+            FileRequest::SetXattr(SetXattrRequest {
+                target: XattrTarget::LinkPath("/tmp/link".into()),
+                name: b"user.meow".to_vec(),
+                value: Payload::from(vec![1, 2, 3]),
+                flags: 1,
+            }),
+            FileRequest::ListXattr(ListXattrRequest {
+                target: XattrTarget::Fd(7),
+            }),
+        ];
+
+        for msg in messages.map(ClientMessage::FileRequest) {
+            client_codec.encode(msg.clone(), &mut buf).unwrap();
+
+            let decoded = daemon_codec.decode(&mut buf).unwrap().unwrap();
+
+            assert_eq!(decoded, msg);
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn xattr_responses_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        let messages = [
+            FileResponse::GetXattr(Ok(GetXattrResponse {
+                value: Payload::from(b"system_u:object_r:etc_t:s0".to_vec()),
+            })),
+            FileResponse::GetXattr(Err(ResponseError::XattrNotFound)),
+            FileResponse::SetXattr(Err(ResponseError::XattrNotSupported)),
+            FileResponse::ListXattr(Ok(ListXattrResponse {
+                names: vec![b"security.selinux".to_vec(), b"user.meow".to_vec()],
+            })),
+        ];
+
+        for msg in messages.map(DaemonMessage::File) {
+            daemon_codec.encode(msg.clone(), &mut buf).unwrap();
+
+            let decoded = client_codec.decode(&mut buf).unwrap().unwrap();
+
+            assert_eq!(decoded, msg);
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn flock_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        let request = ClientMessage::FileRequest(FileRequest::Flock(FlockRequest {
+            fd: 3,
+            operation: FlockOperation::Exclusive,
+        }));
+        client_codec.encode(request.clone(), &mut buf).unwrap();
+        assert_eq!(daemon_codec.decode(&mut buf).unwrap().unwrap(), request);
+        assert!(buf.is_empty());
+
+        let response = DaemonMessage::File(FileResponse::Flock(Err(ResponseError::LockWouldBlock)));
+        daemon_codec.encode(response.clone(), &mut buf).unwrap();
+        assert_eq!(client_codec.decode(&mut buf).unwrap().unwrap(), response);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn syncfs_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        let request =
+            ClientMessage::FileRequest(FileRequest::Syncfs(SyncfsRequest { fd: Some(3) }));
+        client_codec.encode(request.clone(), &mut buf).unwrap();
+        assert_eq!(daemon_codec.decode(&mut buf).unwrap().unwrap(), request);
+        assert!(buf.is_empty());
+
+        let response = DaemonMessage::File(FileResponse::Syncfs(Ok(())));
+        daemon_codec.encode(response.clone(), &mut buf).unwrap();
+        assert_eq!(client_codec.decode(&mut buf).unwrap().unwrap(), response);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn iov_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        let requests = [
+            FileRequest::Readv(ReadvFileRequest {
+                fd: 3,
+                iov_lengths: vec![4, 0, 16],
+            }),
+            FileRequest::Writev(WritevFileRequest {
+                fd: 3,
+                buffers: vec![Payload::from(b"hello".to_vec()), Payload::default()],
+            }),
+        ];
+        for request in requests {
+            let request = ClientMessage::FileRequest(request);
+            client_codec.encode(request.clone(), &mut buf).unwrap();
+            assert_eq!(daemon_codec.decode(&mut buf).unwrap().unwrap(), request);
+            assert!(buf.is_empty());
+        }
+
+        let responses = [
+            FileResponse::Readv(Ok(ReadvFileResponse {
+                bytes: Payload::from(b"hello".to_vec()),
+                iov_lengths: vec![4, 0, 1],
+            })),
+            FileResponse::Writev(Ok(WriteFileResponse { written_amount: 5 })),
+        ];
+        for response in responses {
+            let response = DaemonMessage::File(response);
+            daemon_codec.encode(response.clone(), &mut buf).unwrap();
+            assert_eq!(client_codec.decode(&mut buf).unwrap().unwrap(), response);
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn access_v2_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        let request = ClientMessage::FileRequest(FileRequest::AccessV2(AccessFileRequestV2 {
+            pathname: "/app/test.txt".into(),
+            mode: 2,
+            flags: AccessFlagsInternal {
+                effective_ids: true,
+                nofollow: true,
+            },
+        }));
+        client_codec.encode(request.clone(), &mut buf).unwrap();
+        assert_eq!(daemon_codec.decode(&mut buf).unwrap().unwrap(), request);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn copy_file_range_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        let request =
+            ClientMessage::FileRequest(FileRequest::CopyFileRange(CopyFileRangeRequest {
+                fd_in: 3,
+                off_in: Some(16),
+                fd_out: 4,
+                off_out: None,
+                len: 1024,
+            }));
+        client_codec.encode(request.clone(), &mut buf).unwrap();
+        assert_eq!(daemon_codec.decode(&mut buf).unwrap().unwrap(), request);
+        assert!(buf.is_empty());
+
+        let response =
+            DaemonMessage::File(FileResponse::CopyFileRange(Ok(CopyFileRangeResponse {
+                copied: 1024,
+            })));
+        daemon_codec.encode(response.clone(), &mut buf).unwrap();
+        assert_eq!(client_codec.decode(&mut buf).unwrap().unwrap(), response);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn path_metadata_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        let requests = [
+            FileRequest::Utimens(UtimensRequest {
+                path: "/app/link".into(),
+                times: Some([
+                    Timespec {
+                        tv_sec: 1,
+                        tv_nsec: 2,
+                    },
+                    Timespec {
+                        tv_sec: 3,
+                        tv_nsec: 4,
+                    },
+                ]),
+                follow_symlink: false,
+            }),
+            FileRequest::Chown(ChownRequest {
+                path: "/app/link".into(),
+                owner: 1000,
+                group: 1000,
+                follow_symlink: false,
+            }),
+        ];
+        for request in requests {
+            let request = ClientMessage::FileRequest(request);
+            client_codec.encode(request.clone(), &mut buf).unwrap();
+            assert_eq!(daemon_codec.decode(&mut buf).unwrap().unwrap(), request);
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn fcntl_lock_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        let lock = FcntlLock {
+            lock_type: FcntlLockType::Write,
+            whence: FcntlLockWhence::Start,
+            start: 8,
+            len: 16,
+            pid: 0,
+        };
+
+        let request = ClientMessage::FileRequest(FileRequest::FcntlLock(FcntlLockRequest {
+            fd: 3,
+            command: FcntlLockCommand::Get,
+            lock,
+        }));
+        client_codec.encode(request.clone(), &mut buf).unwrap();
+        assert_eq!(daemon_codec.decode(&mut buf).unwrap().unwrap(), request);
+        assert!(buf.is_empty());
+
+        let response = DaemonMessage::File(FileResponse::FcntlLock(Ok(FcntlLockResponse {
+            conflicting: Some(FcntlLock { pid: -1, ..lock }),
+        })));
+        daemon_codec.encode(response.clone(), &mut buf).unwrap();
+        assert_eq!(client_codec.decode(&mut buf).unwrap().unwrap(), response);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn unix_steal_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        let requests = [
+            LayerTcpSteal::UnixSubscribe("/var/run/app.sock".into()),
+            LayerTcpSteal::UnixUnsubscribe("/var/run/app.sock".into()),
+        ];
+        for request in requests {
+            let request = ClientMessage::TcpSteal(request);
+            client_codec.encode(request.clone(), &mut buf).unwrap();
+            assert_eq!(daemon_codec.decode(&mut buf).unwrap().unwrap(), request);
+            assert!(buf.is_empty());
+        }
+
+        let responses = [
+            DaemonTcp::UnixSubscribeResult(Ok("/var/run/app.sock".into())),
+            DaemonTcp::NewUnixConnection(NewUnixConnection {
+                connection_id: 1,
+                path: "/var/run/app.sock".into(),
+            }),
+        ];
+        for response in responses {
+            let response = DaemonMessage::TcpSteal(response);
+            daemon_codec.encode(response.clone(), &mut buf).unwrap();
+            assert_eq!(client_codec.decode(&mut buf).unwrap().unwrap(), response);
+            assert!(buf.is_empty());
+        }
+    }
+
+    #[test]
+    fn open_v2_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        let request = ClientMessage::FileRequest(FileRequest::OpenV2(OpenFileRequestV2 {
+            path: "/etc".into(),
+            open_options: OpenOptionsInternal {
+                read: true,
+                ..Default::default()
+            },
+            flags: OpenFlagsInternal {
+                directory: true,
+                nofollow: true,
+                path: true,
+            },
+        }));
+        client_codec.encode(request.clone(), &mut buf).unwrap();
+        assert_eq!(daemon_codec.decode(&mut buf).unwrap().unwrap(), request);
+        assert!(buf.is_empty());
+
+        let request =
+            ClientMessage::FileRequest(FileRequest::OpenRelativeV2(OpenRelativeFileRequestV2 {
+                relative_fd: 3,
+                path: "hosts".into(),
+                open_options: Default::default(),
+                flags: OpenFlagsInternal {
+                    nofollow: true,
+                    ..Default::default()
+                },
+            }));
+        client_codec.encode(request.clone(), &mut buf).unwrap();
+        assert_eq!(daemon_codec.decode(&mut buf).unwrap().unwrap(), request);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn open_v3_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        let request = ClientMessage::FileRequest(FileRequest::OpenV3(OpenFileRequestV3 {
+            path: "/tmp/fifo".into(),
+            open_options: OpenOptionsInternal {
+                read: true,
+                ..Default::default()
+            },
+            flags: Default::default(),
+            nonblock: true,
+        }));
+        client_codec.encode(request.clone(), &mut buf).unwrap();
+        assert_eq!(daemon_codec.decode(&mut buf).unwrap().unwrap(), request);
+        assert!(buf.is_empty());
+
+        let request =
+            ClientMessage::FileRequest(FileRequest::OpenRelativeV3(OpenRelativeFileRequestV3 {
+                relative_fd: 3,
+                path: "fifo".into(),
+                open_options: Default::default(),
+                flags: OpenFlagsInternal {
+                    nofollow: true,
+                    ..Default::default()
+                },
+                nonblock: true,
+            }));
+        client_codec.encode(request.clone(), &mut buf).unwrap();
+        assert_eq!(daemon_codec.decode(&mut buf).unwrap().unwrap(), request);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn read_dir_batch_from_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        let request =
+            ClientMessage::FileRequest(FileRequest::ReadDirBatchFrom(ReadDirBatchFromRequest {
+                remote_fd: 3,
+                amount: 128,
+                cursor: DirCursor(256),
+            }));
+        client_codec.encode(request.clone(), &mut buf).unwrap();
+        assert_eq!(daemon_codec.decode(&mut buf).unwrap().unwrap(), request);
+        assert!(buf.is_empty());
+
+        let response = DaemonMessage::File(FileResponse::ReadDirBatchFrom(Ok(
+            ReadDirBatchFromResponse {
+                fd: 3,
+                dir_entries: vec![DirEntryInternal {
+                    inode: 1,
+                    position: 256,
+                    name: "entry".to_string(),
+                    file_type: 8,
+                }],
+                cursor: DirCursor(257),
+            },
+        )));
+        daemon_codec.encode(response.clone(), &mut buf).unwrap();
+        assert_eq!(client_codec.decode(&mut buf).unwrap().unwrap(), response);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn udp_mirror_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        let request = ClientMessage::Udp(LayerUdp::PortSubscribe(8125));
+        client_codec.encode(request.clone(), &mut buf).unwrap();
+        assert_eq!(daemon_codec.decode(&mut buf).unwrap().unwrap(), request);
+        assert!(buf.is_empty());
+
+        let response = DaemonMessage::Udp(DaemonUdp::Datagram(UdpDatagram {
+            source: "10.0.0.7:41000".parse().unwrap(),
+            destination: "10.0.0.2:8125".parse().unwrap(),
+            bytes: Payload::from(b"requests:1|c".to_vec()),
+        }));
+        daemon_codec.encode(response.clone(), &mut buf).unwrap();
+        assert_eq!(client_codec.decode(&mut buf).unwrap().unwrap(), response);
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn remote_time_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        client_codec
+            .encode(ClientMessage::GetRemoteTime, &mut buf)
+            .unwrap();
+        assert_eq!(
+            daemon_codec.decode(&mut buf).unwrap().unwrap(),
+            ClientMessage::GetRemoteTime
+        );
+        assert!(buf.is_empty());
+
+        let time = RemoteTime {
+            nanos_since_epoch: 1_760_000_000_123_456_789,
+        };
+        daemon_codec
+            .encode(DaemonMessage::RemoteTime(time), &mut buf)
+            .unwrap();
+        assert_eq!(
+            client_codec.decode(&mut buf).unwrap().unwrap(),
+            DaemonMessage::RemoteTime(time)
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn extend_ttl_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        client_codec
+            .encode(ClientMessage::ExtendTtl(60), &mut buf)
+            .unwrap();
+        assert_eq!(
+            daemon_codec.decode(&mut buf).unwrap().unwrap(),
+            ClientMessage::ExtendTtl(60)
+        );
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn agent_logs_encode_decode() {
+        let mut client_codec = ClientCodec::default();
+        let mut daemon_codec = DaemonCodec::default();
+        let mut buf = BytesMut::new();
+
+        client_codec
+            .encode(ClientMessage::GetAgentLogs { tail: 100 }, &mut buf)
+            .unwrap();
+        assert_eq!(
+            daemon_codec.decode(&mut buf).unwrap().unwrap(),
+            ClientMessage::GetAgentLogs { tail: 100 }
+        );
+        assert!(buf.is_empty());
+
+        let logs = AgentLogs {
+            lines: vec!["first".into(), "second".into()],
+            truncated: true,
+        };
+        daemon_codec
+            .encode(DaemonMessage::AgentLogs(logs.clone()), &mut buf)
+            .unwrap();
+        assert_eq!(
+            client_codec.decode(&mut buf).unwrap().unwrap(),
+            DaemonMessage::AgentLogs(logs)
+        );
+        assert!(buf.is_empty());
+    }
+
     #[test]
     fn decode_client_invalid_data() {
         let mut codec = ClientCodec::default();
@@ -393,4 +1162,254 @@ mod tests {
             Err(err) => assert_eq!(err.kind(), io::ErrorKind::Other),
         }
     }
+
+    /// A length prefix above the decode limit fails the decoding, instead of allocating.
+    #[test]
+    fn decode_inflated_length_prefix() {
+        let mut client_codec = ClientCodec::default();
+        let mut buf = BytesMut::new();
+
+        let msg = DaemonMessage::Tcp(DaemonTcp::Data(TcpData {
+            connection_id: 1,
+            bytes: Payload::from(vec![1, 2, 3]),
+        }));
+        let mut encoded = bincode::encode_to_vec(msg, bincode::config::standard()).unwrap();
+
+        // Replace the length prefix of the payload with `u64::MAX`.
+        assert_eq!(encoded.split_off(encoded.len() - 4), [3, 1, 2, 3]);
+        encoded.push(253);
+        encoded.extend_from_slice(&u64::MAX.to_le_bytes());
+        encoded.extend_from_slice(&[1, 2, 3]);
+
+        assert!(decode_message::<DaemonMessage>(&encoded).is_err());
+
+        buf.put_slice(&encoded);
+        let err = client_codec.decode(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Other);
+    }
+
+    /// The decode limit of [`ProtocolCodec`] is configurable.
+    #[test]
+    fn decode_limit() {
+        let mut daemon_codec = DaemonCodec::default();
+        let mut limited_codec = ProtocolCodec::<DaemonMessage, ClientMessage, 1024>::default();
+        let mut buf = BytesMut::new();
+
+        let msg = DaemonMessage::Tcp(DaemonTcp::Data(TcpData {
+            connection_id: 1,
+            bytes: Payload::from(vec![0; 2048]),
+        }));
+
+        daemon_codec.encode(msg.clone(), &mut buf).unwrap();
+        assert!(limited_codec.decode(&mut buf.clone()).is_err());
+        assert_eq!(
+            ClientCodec::default().decode(&mut buf).unwrap().unwrap(),
+            msg
+        );
+    }
+
+    /// Messages just under [`ProtocolCodec::with_max_message_size`] decode, messages just over
+    /// it don't, also when it's above the `DECODE_LIMIT`.
+    #[test]
+    fn max_message_size() {
+        let msg = |len| {
+            DaemonMessage::Tcp(DaemonTcp::Data(TcpData {
+                connection_id: 1,
+                bytes: Payload::from(vec![0; len]),
+            }))
+        };
+        // Size of the message without the payload. The length prefix of the payload takes 4 more
+        // bytes when the payload is at least 2^16 bytes long.
+        let overhead = bincode::encode_to_vec(msg(0), bincode::config::standard())
+            .unwrap()
+            .len()
+            + 4;
+
+        for max_message_size in [1024 * 1024, DEFAULT_DECODE_LIMIT + 1024 * 1024] {
+            let mut codec = ClientCodec::default().with_max_message_size(max_message_size);
+            let mut buf = BytesMut::new();
+
+            DaemonCodec::default()
+                .encode(msg(max_message_size - overhead), &mut buf)
+                .unwrap();
+            assert_eq!(buf.len(), max_message_size);
+            let decoded = codec.decode(&mut buf).unwrap();
+            assert!(
+                matches!(
+                    decoded,
+                    Some(DaemonMessage::Tcp(DaemonTcp::Data(TcpData { bytes, .. })))
+                        if bytes.len() == max_message_size - overhead
+                ),
+                "max_message_size {max_message_size}"
+            );
+            assert!(buf.is_empty());
+
+            DaemonCodec::default()
+                .encode(msg(max_message_size - overhead + 1024), &mut buf)
+                .unwrap();
+            assert!(codec.decode(&mut buf).is_err());
+
+            // Incomplete messages fail as well, once we have more bytes than the limit.
+            buf.truncate(max_message_size);
+            assert!(codec.decode(&mut buf).unwrap().is_none());
+            buf.put_u8(0);
+            assert!(codec.decode(&mut buf).is_err());
+        }
+    }
+
+    #[test]
+    fn response_error_mut() {
+        let in_progress = || ResponseError::from(io::Error::from(io::ErrorKind::InProgress));
+
+        for mut message in [
+            DaemonMessage::File(FileResponse::FcntlLock(Err(in_progress()))),
+            DaemonMessage::TcpOutgoing(DaemonTcpOutgoing::Connect(Err(in_progress()))),
+            DaemonMessage::ReverseDnsLookup(Ok(ReverseDnsLookupResponse {
+                hostname: Err(in_progress()),
+            })),
+        ] {
+            let error = message
+                .response_error_mut()
+                .expect("message carries an error");
+            assert_eq!(*error, in_progress());
+            error.downgrade_error_kind();
+            assert!(matches!(
+                message.response_error_mut(),
+                Some(ResponseError::RemoteIO(RemoteIOError {
+                    kind: ErrorKindInternal::Unknown(..),
+                    ..
+                }))
+            ));
+        }
+
+        assert!(DaemonMessage::Pong.response_error_mut().is_none());
+        assert!(
+            DaemonMessage::File(FileResponse::Unlink(Ok(())))
+                .response_error_mut()
+                .is_none()
+        );
+    }
+
+    /// Decodes the messages from [`fuzz_corpus`], mutated with bit flips, truncations, inflated
+    /// length prefixes and random bytes. Decoding must never panic.
+    #[test]
+    fn fuzz_decode() {
+        const ITERATIONS: usize = 2000;
+
+        // xorshift, so that failures are reproducible.
+        let mut state = 0x9E37_79B9_7F4A_7C15_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+
+        let (client_messages, daemon_messages) = fuzz_corpus();
+        let corpus = client_messages
+            .iter()
+            .map(|msg| bincode::encode_to_vec(msg, bincode::config::standard()).unwrap())
+            .chain(
+                daemon_messages
+                    .iter()
+                    .map(|msg| bincode::encode_to_vec(msg, bincode::config::standard()).unwrap()),
+            )
+            .collect::<Vec<_>>();
+
+        for encoded in &corpus {
+            for _ in 0..ITERATIONS {
+                let mut mutated = encoded.clone();
+
+                for _ in 0..=next() % 4 {
+                    let position = (next() as usize) % (mutated.len() + 1);
+                    match next() % 4 {
+                        0 if position < mutated.len() => {
+                            mutated[position] ^= 1 << (next() % 8);
+                        }
+                        1 => mutated.truncate(position),
+                        2 => {
+                            let prefix = [251, 252, 253][(next() % 3) as usize];
+                            let len = next().to_le_bytes();
+                            let len = match prefix {
+                                251 => &len[..2],
+                                252 => &len[..4],
+                                _ => &len[..],
+                            };
+                            mutated.splice(position..position, [prefix].iter().chain(len).copied());
+                        }
+                        _ => mutated.insert(position, next() as u8),
+                    }
+                }
+
+                let _ = decode_message::<ClientMessage>(&mutated);
+                let _ = decode_message::<DaemonMessage>(&mutated);
+
+                let mut daemon_codec = DaemonCodec::default();
+                let mut buf = BytesMut::from(mutated.as_slice());
+                while let Ok(Some(..)) = daemon_codec.decode(&mut buf) {}
+
+                let mut client_codec = ClientCodec::default();
+                let mut buf = BytesMut::from(mutated.as_slice());
+                while let Ok(Some(..)) = client_codec.decode(&mut buf) {}
+            }
+        }
+    }
+
+    /// Baseline messages for [`fuzz_decode`].
+    fn fuzz_corpus() -> (Vec<ClientMessage>, Vec<DaemonMessage>) {
+        let client_messages = vec![
+            ClientMessage::Close,
+            ClientMessage::Ping,
+            ClientMessage::Tcp(LayerTcp::PortSubscribe(80)),
+            ClientMessage::FileRequest(FileRequest::Open(OpenFileRequest {
+                path: "/etc/hosts".into(),
+                open_options: OpenOptionsInternal {
+                    read: true,
+                    ..Default::default()
+                },
+            })),
+            ClientMessage::FileRequest(FileRequest::Read(ReadFileRequest {
+                remote_fd: 3,
+                buffer_size: 4096,
+            })),
+            ClientMessage::FileRequest(FileRequest::SetXattr(SetXattrRequest {
+                target: XattrTarget::Path("/tmp/file".into()),
+                name: b"user.meow".to_vec(),
+                value: Payload::from(vec![1, 2, 3]),
+                flags: 0,
+            })),
+            ClientMessage::GetEnvVarsRequest(GetEnvVarsRequest {
+                env_vars_filter: ["SECRET".to_string()].into(),
+                env_vars_select: ["*".to_string()].into(),
+            }),
+            ClientMessage::GetAddrInfoRequest(GetAddrInfoRequest {
+                node: "example.com".to_string(),
+            }),
+            ClientMessage::SwitchProtocolVersion(semver::Version::new(1, 40, 0)),
+        ];
+
+        let daemon_messages = vec![
+            DaemonMessage::Close("bye".to_string()),
+            DaemonMessage::Pong,
+            DaemonMessage::Tcp(DaemonTcp::Data(TcpData {
+                connection_id: 1,
+                bytes: Payload::from(b"GET / HTTP/1.1\r\n\r\n".to_vec()),
+            })),
+            DaemonMessage::File(FileResponse::Read(Ok(ReadFileResponse {
+                bytes: Payload::from(b"127.0.0.1 localhost\n".to_vec()),
+                read_amount: 20,
+            }))),
+            DaemonMessage::File(FileResponse::ListXattr(Ok(ListXattrResponse {
+                names: vec![b"security.selinux".to_vec(), b"user.meow".to_vec()],
+            }))),
+            DaemonMessage::File(FileResponse::GetXattr(Err(ResponseError::XattrNotFound))),
+            DaemonMessage::GetEnvVarsResponse(Ok(RemoteEnvVars(
+                [("HOME".to_string(), "/root".to_string())].into(),
+            ))),
+            DaemonMessage::LogMessage(LogMessage::warn("careful".to_string())),
+            DaemonMessage::SwitchProtocolVersionResponse(semver::Version::new(1, 40, 0)),
+        ];
+
+        (client_messages, daemon_messages)
+    }
 }
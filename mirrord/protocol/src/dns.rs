@@ -11,6 +11,10 @@ use crate::RemoteResult;
 pub static ADDRINFO_V2_VERSION: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.15.0".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows clients to send [`ReverseDnsLookupRequest`].
+pub static REVERSE_DNS_LOOKUP_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.26.0".parse().expect("Bad Identifier"));
+
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub struct LookupRecord {
     pub name: String,
@@ -127,7 +131,8 @@ impl From<GetAddrInfoRequest> for GetAddrInfoRequestV2 {
 
 /// Request for reverse DNS lookup (IP address to hostname).
 ///
-/// Triggered by the operator when enforcing hostname-based outgoing network policies.
+/// Triggered by the operator when enforcing hostname-based outgoing network policies, and by the
+/// `mirrord-layer` hook of `getnameinfo_detour`.
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub struct ReverseDnsLookupRequest {
     pub ip_address: IpAddr,
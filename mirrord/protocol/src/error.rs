@@ -69,6 +69,57 @@ pub enum ResponseError {
         policy_name: Option<String>,
         reason: String,
     },
+
+    /// The remote file has no extended attribute with the requested name (`ENODATA`).
+    #[error("Extended attribute not found on the remote file!")]
+    XattrNotFound,
+
+    /// The remote filesystem does not support extended attributes (`ENOTSUP`).
+    #[error("Extended attributes are not supported by the remote filesystem!")]
+    XattrNotSupported,
+
+    /// The remote file is locked by another mirrord client (`EWOULDBLOCK`), see
+    /// [`FlockRequest`](crate::file::FlockRequest).
+    #[error("Remote file is locked by another mirrord client!")]
+    LockWouldBlock,
+}
+
+impl ResponseError {
+    /// Whether this error is likely transient, so that the failed operation can be retried with
+    /// a chance of success (e.g. the remote resource was busy, or the operation timed out).
+    ///
+    /// This is a classification of the existing errors, and does not change what is sent over the
+    /// wire, so it works with any agent version.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Self::RemoteIO(RemoteIOError { kind, .. }) => matches!(
+                kind,
+                ErrorKindInternal::WouldBlock
+                    | ErrorKindInternal::Interrupted
+                    | ErrorKindInternal::TimedOut
+                    | ErrorKindInternal::ResourceBusy
+            ),
+            Self::DnsLookup(DnsLookupError {
+                kind: ResolveErrorKindInternal::Timeout,
+            }) => true,
+            _ => false,
+        }
+    }
+
+    /// Replaces an [`ErrorKindInternal`] added after [`ErrorKindInternal::Unknown`] with
+    /// [`ErrorKindInternal::Unknown`], so that the error can be sent to a client that does not
+    /// match [`ERROR_KIND_IN_PROGRESS_VERSION`].
+    ///
+    /// The message of the error is preserved.
+    pub fn downgrade_error_kind(&mut self) {
+        if let Self::RemoteIO(RemoteIOError {
+            kind: kind @ ErrorKindInternal::InProgress,
+            ..
+        }) = self
+        {
+            *kind = ErrorKindInternal::Unknown(kind.to_string());
+        }
+    }
 }
 
 impl From<StripPrefixError> for ResponseError {
@@ -95,6 +146,13 @@ pub static MIRROR_BLOCK_VERSION: LazyLock<VersionReq> =
 pub static MIRROR_POLICY_REASON_VERSION: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.17.0".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows [`ErrorKindInternal::InProgress`].
+///
+/// Older clients get [`ErrorKindInternal::Unknown`] instead, see
+/// [`ResponseError::downgrade_error_kind`].
+pub static ERROR_KIND_IN_PROGRESS_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.40.0".parse().expect("Bad Identifier"));
+
 /// All the actions that can be blocked by the operator, to identify the blocked feature in a
 /// [`ResponseError::Forbidden`] or [`ResponseError::ForbiddenWithReason`] message.
 #[derive(Encode, Decode, Debug, PartialEq, Clone, Eq, Error)]
@@ -180,7 +238,7 @@ pub struct RemoteIOError {
 
 impl core::fmt::Display for RemoteIOError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "{:?}", self.kind)?;
+        write!(f, "{}", self.kind)?;
         if let Some(code) = self.raw_os_error {
             write!(f, " (error code {code})")?;
         }
@@ -209,7 +267,9 @@ impl From<io::Error> for ResponseError {
 }
 
 /// Alternative to `std::io::ErrorKind`, used to implement `bincode::Encode` and `bincode::Decode`.
-#[derive(Encode, Decode, Debug, PartialEq, Clone, Eq)]
+///
+/// Parses from the variant names, as they appear in the `Debug` output.
+#[derive(Encode, Decode, Debug, PartialEq, Clone, Eq, strum_macros::EnumString)]
 pub enum ErrorKindInternal {
     NotFound,
     PermissionDenied,
@@ -252,7 +312,76 @@ pub enum ErrorKindInternal {
     OutOfMemory,
     Other,
     // Unknown is for uncovered cases (enum is non-exhaustive)
+    #[strum(disabled)]
     Unknown(String),
+    // New variants go after `Unknown`, so that the encoding of the existing ones doesn't change.
+    InProgress,
+}
+
+impl ErrorKindInternal {
+    /// Returns the matching [`io::ErrorKind`], [`None`] for [`ErrorKindInternal::Unknown`].
+    pub fn as_io_error_kind(&self) -> Option<io::ErrorKind> {
+        let kind = match self {
+            ErrorKindInternal::NotFound => io::ErrorKind::NotFound,
+            ErrorKindInternal::PermissionDenied => io::ErrorKind::PermissionDenied,
+            ErrorKindInternal::ConnectionRefused => io::ErrorKind::ConnectionRefused,
+            ErrorKindInternal::ConnectionReset => io::ErrorKind::ConnectionReset,
+            ErrorKindInternal::HostUnreachable => io::ErrorKind::HostUnreachable,
+            ErrorKindInternal::NetworkUnreachable => io::ErrorKind::NetworkUnreachable,
+            ErrorKindInternal::ConnectionAborted => io::ErrorKind::ConnectionAborted,
+            ErrorKindInternal::NotConnected => io::ErrorKind::NotConnected,
+            ErrorKindInternal::AddrInUse => io::ErrorKind::AddrInUse,
+            ErrorKindInternal::AddrNotAvailable => io::ErrorKind::AddrNotAvailable,
+            ErrorKindInternal::NetworkDown => io::ErrorKind::NetworkDown,
+            ErrorKindInternal::BrokenPipe => io::ErrorKind::BrokenPipe,
+            ErrorKindInternal::AlreadyExists => io::ErrorKind::AlreadyExists,
+            ErrorKindInternal::WouldBlock => io::ErrorKind::WouldBlock,
+            ErrorKindInternal::NotADirectory => io::ErrorKind::NotADirectory,
+            ErrorKindInternal::IsADirectory => io::ErrorKind::IsADirectory,
+            ErrorKindInternal::DirectoryNotEmpty => io::ErrorKind::DirectoryNotEmpty,
+            ErrorKindInternal::ReadOnlyFilesystem => io::ErrorKind::ReadOnlyFilesystem,
+            ErrorKindInternal::FilesystemLoop => io::ErrorKind::FilesystemLoop,
+            ErrorKindInternal::StaleNetworkFileHandle => io::ErrorKind::StaleNetworkFileHandle,
+            ErrorKindInternal::InvalidInput => io::ErrorKind::InvalidInput,
+            ErrorKindInternal::InvalidData => io::ErrorKind::InvalidData,
+            ErrorKindInternal::TimedOut => io::ErrorKind::TimedOut,
+            ErrorKindInternal::WriteZero => io::ErrorKind::WriteZero,
+            ErrorKindInternal::StorageFull => io::ErrorKind::StorageFull,
+            ErrorKindInternal::NotSeekable => io::ErrorKind::NotSeekable,
+            ErrorKindInternal::FilesystemQuotaExceeded => io::ErrorKind::QuotaExceeded,
+            ErrorKindInternal::FileTooLarge => io::ErrorKind::FileTooLarge,
+            ErrorKindInternal::ResourceBusy => io::ErrorKind::ResourceBusy,
+            ErrorKindInternal::ExecutableFileBusy => io::ErrorKind::ExecutableFileBusy,
+            ErrorKindInternal::Deadlock => io::ErrorKind::Deadlock,
+            ErrorKindInternal::CrossesDevices => io::ErrorKind::CrossesDevices,
+            ErrorKindInternal::TooManyLinks => io::ErrorKind::TooManyLinks,
+            ErrorKindInternal::InvalidFilename => io::ErrorKind::InvalidFilename,
+            ErrorKindInternal::ArgumentListTooLong => io::ErrorKind::ArgumentListTooLong,
+            ErrorKindInternal::Interrupted => io::ErrorKind::Interrupted,
+            ErrorKindInternal::Unsupported => io::ErrorKind::Unsupported,
+            ErrorKindInternal::UnexpectedEof => io::ErrorKind::UnexpectedEof,
+            ErrorKindInternal::OutOfMemory => io::ErrorKind::OutOfMemory,
+            ErrorKindInternal::Other => io::ErrorKind::Other,
+            ErrorKindInternal::InProgress => io::ErrorKind::InProgress,
+            ErrorKindInternal::Unknown(..) => return None,
+        };
+
+        Some(kind)
+    }
+}
+
+/// Reads like [`io::ErrorKind`]'s `Display`, e.g. `entity not found`.
+impl core::fmt::Display for ErrorKindInternal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ErrorKindInternal::Unknown(message) => f.write_str(message),
+            kind => write!(
+                f,
+                "{}",
+                kind.as_io_error_kind().unwrap_or(io::ErrorKind::Other)
+            ),
+        }
+    }
 }
 
 /// Alternative to `std::io::ErrorKind`, used to implement `bincode::Encode` and `bincode::Decode`.
@@ -337,7 +466,84 @@ impl From<io::ErrorKind> for ErrorKindInternal {
             io::ErrorKind::UnexpectedEof => ErrorKindInternal::UnexpectedEof,
             io::ErrorKind::OutOfMemory => ErrorKindInternal::OutOfMemory,
             io::ErrorKind::Other => ErrorKindInternal::Other,
+            io::ErrorKind::InProgress => ErrorKindInternal::InProgress,
             _ => ErrorKindInternal::Unknown(error_kind.to_string()),
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Known [`io::ErrorKind`]s are not collapsed into [`ErrorKindInternal::Unknown`], and display
+    /// like the original.
+    #[test]
+    fn error_kind_roundtrip() {
+        for kind in [
+            io::ErrorKind::NotFound,
+            io::ErrorKind::StorageFull,
+            io::ErrorKind::QuotaExceeded,
+            io::ErrorKind::NetworkDown,
+            io::ErrorKind::InProgress,
+            io::ErrorKind::Other,
+        ] {
+            let internal = ErrorKindInternal::from(kind);
+            assert!(!matches!(internal, ErrorKindInternal::Unknown(..)));
+            assert_eq!(internal.as_io_error_kind(), Some(kind));
+            assert_eq!(internal.to_string(), kind.to_string());
+        }
+    }
+
+    #[test]
+    fn remote_io_error_display() {
+        let error = RemoteIOError {
+            raw_os_error: Some(2),
+            kind: ErrorKindInternal::NotFound,
+        };
+        assert_eq!(error.to_string(), "entity not found (error code 2)");
+
+        let error = RemoteIOError {
+            raw_os_error: None,
+            kind: ErrorKindInternal::Unknown("connection lost".to_string()),
+        };
+        assert_eq!(error.to_string(), "connection lost");
+    }
+
+    #[test]
+    fn retryable() {
+        for kind in [io::ErrorKind::ResourceBusy, io::ErrorKind::Interrupted] {
+            assert!(ResponseError::from(io::Error::from(kind)).is_retryable());
+        }
+        assert!(
+            ResponseError::DnsLookup(DnsLookupError {
+                kind: ResolveErrorKindInternal::Timeout,
+            })
+            .is_retryable()
+        );
+
+        for kind in [io::ErrorKind::NotFound, io::ErrorKind::PermissionDenied] {
+            assert!(!ResponseError::from(io::Error::from(kind)).is_retryable());
+        }
+        assert!(!ResponseError::NotFound(3).is_retryable());
+        assert!(!ResponseError::LockWouldBlock.is_retryable());
+    }
+
+    #[test]
+    fn downgrade_error_kind() {
+        let mut error = ResponseError::from(io::Error::from(io::ErrorKind::InProgress));
+        error.downgrade_error_kind();
+        assert_eq!(
+            error,
+            ResponseError::RemoteIO(RemoteIOError {
+                raw_os_error: None,
+                kind: ErrorKindInternal::Unknown(io::ErrorKind::InProgress.to_string()),
+            })
+        );
+
+        let mut error = ResponseError::from(io::Error::from(io::ErrorKind::NotFound));
+        let original = error.clone();
+        error.downgrade_error_kind();
+        assert_eq!(error, original);
+    }
+}
@@ -49,6 +49,53 @@ pub static RENAME_VERSION: LazyLock<VersionReq> =
 pub static COPYFILE_VERSION: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.24.0".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows [`GetXattrRequest`], [`SetXattrRequest`] and
+/// [`ListXattrRequest`].
+pub static XATTR_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.27.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`FlockRequest`].
+pub static FLOCK_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.29.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`SyncfsRequest`].
+pub static SYNCFS_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.36.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`ReadDirBatchFromRequest`].
+pub static READDIR_CURSOR_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.38.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`OpenFileRequestV2`] and
+/// [`OpenRelativeFileRequestV2`].
+pub static OPEN_FLAGS_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.39.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`ReadvFileRequest`] and [`WritevFileRequest`].
+pub static IOV_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.41.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`AccessFileRequestV2`].
+pub static ACCESS_FLAGS_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.43.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`CopyFileRangeRequest`].
+pub static COPY_FILE_RANGE_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.44.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`UtimensRequest`] and [`ChownRequest`].
+pub static PATH_METADATA_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.45.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`FcntlLockRequest`].
+pub static FCNTL_LOCK_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.46.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows [`OpenFileRequestV3`] and
+/// [`OpenRelativeFileRequestV3`].
+pub static OPEN_NONBLOCK_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.47.0".parse().expect("Bad Identifier"));
+
 /// Internal version of Metadata across operating system (macOS, Linux)
 /// Only mutual attributes
 #[derive(Encode, Decode, Debug, PartialEq, Clone, Copy, Eq, Default)]
@@ -369,6 +416,122 @@ pub struct OpenRelativeFileRequest {
     pub open_options: OpenOptionsInternal,
 }
 
+/// `open` flags that are not covered by [`OpenOptionsInternal`], as they change how the path is
+/// resolved, or what the opened fd can be used for.
+#[derive(Encode, Decode, Debug, PartialEq, Clone, Copy, Eq, Default)]
+pub struct OpenFlagsInternal {
+    /// `O_DIRECTORY`, the open fails with `ENOTDIR` if the path is not a directory.
+    pub directory: bool,
+    /// `O_NOFOLLOW`, the open fails with `ELOOP` if the path is a symbolic link.
+    pub nofollow: bool,
+    /// `O_PATH`, the fd can be used with `openat` and `fstat`, but not for reading or writing.
+    pub path: bool,
+}
+
+impl OpenFlagsInternal {
+    /// Whether none of the flags is set, which allows using the older open requests.
+    pub fn is_empty(&self) -> bool {
+        !(self.directory || self.nofollow || self.path)
+    }
+}
+
+/// Same as [`OpenFileRequest`], but also carries the [`OpenFlagsInternal`].
+///
+/// Only sent to agents that support [`OPEN_FLAGS_VERSION`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct OpenFileRequestV2 {
+    pub path: PathBuf,
+    pub open_options: OpenOptionsInternal,
+    pub flags: OpenFlagsInternal,
+}
+
+impl From<OpenFileRequestV2> for OpenFileRequest {
+    /// Drops the [`OpenFlagsInternal`], for agents that don't support them.
+    fn from(request: OpenFileRequestV2) -> Self {
+        Self {
+            path: request.path,
+            open_options: request.open_options,
+        }
+    }
+}
+
+/// Same as [`OpenRelativeFileRequest`], but also carries the [`OpenFlagsInternal`].
+///
+/// Only sent to agents that support [`OPEN_FLAGS_VERSION`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct OpenRelativeFileRequestV2 {
+    pub relative_fd: u64,
+    pub path: PathBuf,
+    pub open_options: OpenOptionsInternal,
+    pub flags: OpenFlagsInternal,
+}
+
+impl From<OpenRelativeFileRequestV2> for OpenRelativeFileRequest {
+    /// Drops the [`OpenFlagsInternal`], for agents that don't support them.
+    fn from(request: OpenRelativeFileRequestV2) -> Self {
+        Self {
+            relative_fd: request.relative_fd,
+            path: request.path,
+            open_options: request.open_options,
+        }
+    }
+}
+
+/// Same as [`OpenFileRequestV2`], but also carries `O_NONBLOCK`.
+///
+/// Only sent to agents that support [`OPEN_NONBLOCK_VERSION`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct OpenFileRequestV3 {
+    pub path: PathBuf,
+    pub open_options: OpenOptionsInternal,
+    pub flags: OpenFlagsInternal,
+    /// `O_NONBLOCK`, reads and writes on a FIFO fail with `EAGAIN` instead of waiting for the
+    /// other end.
+    pub nonblock: bool,
+}
+
+impl From<OpenFileRequestV3> for OpenFileRequestV2 {
+    /// Drops the `nonblock` flag, for agents that don't support it.
+    fn from(request: OpenFileRequestV3) -> Self {
+        Self {
+            path: request.path,
+            open_options: request.open_options,
+            flags: request.flags,
+        }
+    }
+}
+
+/// Same as [`OpenRelativeFileRequestV2`], but also carries `O_NONBLOCK`.
+///
+/// Only sent to agents that support [`OPEN_NONBLOCK_VERSION`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct OpenRelativeFileRequestV3 {
+    pub relative_fd: u64,
+    pub path: PathBuf,
+    pub open_options: OpenOptionsInternal,
+    pub flags: OpenFlagsInternal,
+    /// Same as [`OpenFileRequestV3::nonblock`].
+    pub nonblock: bool,
+}
+
+impl From<OpenRelativeFileRequestV3> for OpenRelativeFileRequestV2 {
+    /// Drops the `nonblock` flag, for agents that don't support it.
+    fn from(request: OpenRelativeFileRequestV3) -> Self {
+        Self {
+            relative_fd: request.relative_fd,
+            path: request.path,
+            open_options: request.open_options,
+            flags: request.flags,
+        }
+    }
+}
+
+/// Most bytes that the agent reads for a single [`ReadFileRequest`] or [`ReadLimitedFileRequest`].
+///
+/// A larger `buffer_size` results in a short read, so that a client can't make the agent allocate
+/// an arbitrary amount of memory.
+pub const MAX_READ_SIZE: u64 = 64 * 1024 * 1024;
+
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub struct ReadFileRequest {
     pub remote_fd: u64,
@@ -516,6 +679,39 @@ pub struct AccessFileRequest {
     pub mode: u8,
 }
 
+/// `faccessat` flags, change how the access is checked.
+#[derive(Encode, Decode, Debug, PartialEq, Clone, Copy, Eq, Default)]
+pub struct AccessFlagsInternal {
+    /// `AT_EACCESS`, the access is checked with the effective user and group IDs, instead of the
+    /// real ones.
+    pub effective_ids: bool,
+    /// `AT_SYMLINK_NOFOLLOW`, a symbolic link in the last component of the path is checked
+    /// itself, and not followed.
+    pub nofollow: bool,
+}
+
+/// Same as [`AccessFileRequest`], but also carries the [`AccessFlagsInternal`], and the access is
+/// checked with the same semantics as `faccessat` against the remote file.
+///
+/// Only sent to agents that support [`ACCESS_FLAGS_VERSION`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct AccessFileRequestV2 {
+    pub pathname: PathBuf,
+    /// `R_OK`, `W_OK` and `X_OK` bits, or `F_OK`.
+    pub mode: u8,
+    pub flags: AccessFlagsInternal,
+}
+
+impl From<AccessFileRequestV2> for AccessFileRequest {
+    /// Drops the [`AccessFlagsInternal`], for agents that don't support them.
+    fn from(request: AccessFileRequestV2) -> Self {
+        Self {
+            pathname: request.pathname,
+            mode: request.mode,
+        }
+    }
+}
+
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub struct AccessFileResponse;
 
@@ -633,6 +829,37 @@ pub struct ReadDirBatchResponse {
     pub dir_entries: Vec<DirEntryInternal>,
 }
 
+/// Opaque position in the agent's stream of entries of a remote directory.
+///
+/// Returned with every [`ReadDirBatchFromResponse`], so that the directory iteration can be
+/// resumed from the same position, e.g. with a directory re-opened after a reconnect.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct DirCursor(pub u64);
+
+/// Same as [`ReadDirBatchRequest`], but first moves the agent's iterator of dirs to the given
+/// [`DirCursor`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct ReadDirBatchFromRequest {
+    /// The fd of the dir in the agent.
+    pub remote_fd: u64,
+    /// Max amount to take from the agent's iterator of dirs.
+    pub amount: usize,
+    /// Where to start reading the entries from, [`DirCursor::default`] for the first entry.
+    pub cursor: DirCursor,
+}
+
+/// Response to [`ReadDirBatchFromRequest`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct ReadDirBatchFromResponse {
+    /// Remote fd of the dir.
+    pub fd: u64,
+    /// The list of [`DirEntryInternal`] where `length` is, at max, the `amount` we took
+    /// from the agent's read dir iterator.
+    pub dir_entries: Vec<DirEntryInternal>,
+    /// Position right after the last of [`Self::dir_entries`], to be used in the next request.
+    pub cursor: DirCursor,
+}
+
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub struct CloseDirRequest {
     pub remote_fd: u64,
@@ -687,3 +914,266 @@ pub struct FchmodRequest {
     pub fd: u64,
     pub mode: u32,
 }
+
+/// Same as [`FutimensRequest`], but for the file at `path` (`utimensat`, `lutimes`).
+///
+/// When `follow_symlink` is `false` and `path` is a symlink, the timestamps of the symlink itself
+/// are changed.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct UtimensRequest {
+    pub path: PathBuf,
+    pub times: Option<[Timespec; 2]>,
+    pub follow_symlink: bool,
+}
+
+/// Same as [`FchownRequest`], but for the file at `path` (`fchownat`, `lchown`).
+///
+/// When `follow_symlink` is `false` and `path` is a symlink, the owner of the symlink itself is
+/// changed.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct ChownRequest {
+    pub path: PathBuf,
+    pub owner: u32,
+    pub group: u32,
+    pub follow_symlink: bool,
+}
+
+/// File that an extended attribute request operates on.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub enum XattrTarget {
+    /// File at the given path, following symlinks (`getxattr`, `setxattr`, `listxattr`).
+    Path(PathBuf),
+    /// File at the given path, without following symlinks (`lgetxattr`, `lsetxattr`,
+    /// `llistxattr`).
+    LinkPath(PathBuf),
+    /// Open remote file (`fgetxattr`, `fsetxattr`, `flistxattr`).
+    Fd(u64),
+}
+
+/// `getxattr` request, the agent responds with the whole value of the attribute.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct GetXattrRequest {
+    pub target: XattrTarget,
+    pub name: Vec<u8>,
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct GetXattrResponse {
+    pub value: Payload,
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct SetXattrRequest {
+    pub target: XattrTarget,
+    pub name: Vec<u8>,
+    pub value: Payload,
+    /// `XATTR_CREATE`/`XATTR_REPLACE` flags, as defined on Linux.
+    pub flags: i32,
+}
+
+/// `listxattr` request, the agent responds with the names of all attributes.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct ListXattrRequest {
+    pub target: XattrTarget,
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct ListXattrResponse {
+    pub names: Vec<Vec<u8>>,
+}
+
+/// Operation of a [`FlockRequest`], see `flock(2)`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FlockOperation {
+    /// `LOCK_SH`
+    Shared,
+    /// `LOCK_EX`
+    Exclusive,
+    /// `LOCK_UN`
+    Unlock,
+}
+
+/// `flock` request on an open remote file.
+///
+/// The agent takes the lock on its open file with `flock(2)`, so it coordinates with the
+/// processes running in the target, and with the other mirrord clients connected to the agent.
+/// The lock is released when the file is closed or the client disconnects.
+///
+/// The agent never blocks on this request. If the lock is held by someone else, it responds
+/// with [`ResponseError::LockWouldBlock`](crate::ResponseError::LockWouldBlock), and it's up to
+/// the client to retry.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct FlockRequest {
+    pub fd: u64,
+    pub operation: FlockOperation,
+}
+
+/// Type of a POSIX record lock, see `fcntl(2)`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FcntlLockType {
+    /// `F_RDLCK`
+    Read,
+    /// `F_WRLCK`
+    Write,
+    /// `F_UNLCK`
+    Unlock,
+}
+
+/// Where the [`FcntlLock::start`] is counted from (`l_whence`).
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FcntlLockWhence {
+    /// `SEEK_SET`
+    Start,
+    /// `SEEK_CUR`, counted from the offset of the agent's open file.
+    ///
+    /// This is not the client's offset when the client buffers the file, so clients should
+    /// prefer to resolve the offset themselves and send [`FcntlLockWhence::Start`].
+    Current,
+    /// `SEEK_END`
+    End,
+}
+
+/// POSIX record lock on a byte range of a file, the protocol version of `struct flock`.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FcntlLock {
+    pub lock_type: FcntlLockType,
+    pub whence: FcntlLockWhence,
+    pub start: i64,
+    /// 0 means up to the end of the file, however big it grows.
+    pub len: i64,
+    /// Process that holds the lock, only set in [`FcntlLockResponse::conflicting`]. -1 when the
+    /// lock belongs to an open file, e.g. when it's held by another mirrord client.
+    pub pid: i32,
+}
+
+/// `fcntl` record lock command on an open remote file.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FcntlLockCommand {
+    /// `F_SETLK` and `F_SETLKW`.
+    Set,
+    /// `F_GETLK`.
+    Get,
+}
+
+/// `fcntl` record lock request (`F_SETLK`, `F_SETLKW`, `F_GETLK`) on an open remote file.
+///
+/// Like with [`FlockRequest`], the agent takes the lock on the file itself, here as an open file
+/// description lock (`F_OFD_SETLK`), so it coordinates with the processes running in the target
+/// too. The lock belongs to the open remote file, and is released when the file is closed, or
+/// when the client disconnects.
+///
+/// The agent never blocks on this request. If the lock is held by someone else, it responds
+/// with [`ResponseError::LockWouldBlock`](crate::ResponseError::LockWouldBlock), and it's up to
+/// the client to retry.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct FcntlLockRequest {
+    pub fd: u64,
+    pub command: FcntlLockCommand,
+    pub lock: FcntlLock,
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct FcntlLockResponse {
+    /// For [`FcntlLockCommand::Get`], the lock that conflicts with the requested one (with
+    /// [`FcntlLockWhence::Start`]), if there is any.
+    pub conflicting: Option<FcntlLock>,
+}
+
+/// Flushes the remote filesystem that contains an open remote file (`syncfs`), or all remote
+/// filesystems (`sync`).
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct SyncfsRequest {
+    /// The open remote file for `syncfs`, [`None`] for `sync`.
+    pub fd: Option<u64>,
+}
+
+/// Reads from an open remote file into multiple buffers at once (`readv`).
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct ReadvFileRequest {
+    pub fd: u64,
+    /// Lengths of the buffers to read into, in order.
+    pub iov_lengths: Vec<u64>,
+}
+
+#[derive(Encode, Decode, PartialEq, Eq, Clone)]
+pub struct ReadvFileResponse {
+    /// Bytes read into all of the buffers, concatenated.
+    pub bytes: Payload,
+    /// How many of the [`ReadvFileResponse::bytes`] were read into each of the buffers, in order.
+    ///
+    /// Buffers after the last one that got any bytes are omitted.
+    pub iov_lengths: Vec<u64>,
+}
+
+impl ReadvFileResponse {
+    /// Splits the `bytes` between buffers of the given `buffer_lengths`, filling them in order.
+    pub fn new(bytes: Payload, buffer_lengths: &[u64]) -> Self {
+        let mut left = bytes.len() as u64;
+        let mut iov_lengths = buffer_lengths
+            .iter()
+            .map(|length| {
+                let read = (*length).min(left);
+                left -= read;
+                read
+            })
+            .collect::<Vec<_>>();
+        while iov_lengths.last() == Some(&0) {
+            iov_lengths.pop();
+        }
+
+        Self { bytes, iov_lengths }
+    }
+}
+
+impl fmt::Debug for ReadvFileResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReadvFileResponse")
+            .field("bytes (length)", &self.bytes.len())
+            .field("iov_lengths", &self.iov_lengths)
+            .finish()
+    }
+}
+
+/// Writes multiple buffers to an open remote file at once (`writev`).
+///
+/// Answered with a [`WriteFileResponse`].
+#[derive(Encode, Decode, PartialEq, Eq, Clone)]
+pub struct WritevFileRequest {
+    pub fd: u64,
+    pub buffers: Vec<Payload>,
+}
+
+impl fmt::Debug for WritevFileRequest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WritevFileRequest")
+            .field("fd", &self.fd)
+            .field(
+                "buffers (lengths)",
+                &self
+                    .buffers
+                    .iter()
+                    .map(|buffer| buffer.len())
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+/// Copies data between two open remote files on the agent (`copy_file_range`).
+///
+/// Like the syscall, an offset of [`None`] means that the copy starts at the file position, which
+/// is then moved past the copied bytes, while a given offset leaves the file position untouched.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct CopyFileRangeRequest {
+    pub fd_in: u64,
+    pub off_in: Option<u64>,
+    pub fd_out: u64,
+    pub off_out: Option<u64>,
+    pub len: u64,
+}
+
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct CopyFileRangeResponse {
+    /// How many bytes were copied, 0 at the end of `fd_in`.
+    pub copied: u64,
+}
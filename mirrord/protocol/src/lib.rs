@@ -55,12 +55,14 @@
 
 #![feature(const_trait_impl)]
 #![feature(io_error_more)]
+#![feature(io_error_inprogress)]
 #![warn(clippy::indexing_slicing)]
 #![deny(unused_crate_dependencies)]
 // windows features for protocol/file.rs in From<Metadata> for MetadataInternal
 #![cfg_attr(target_os = "windows", feature(windows_change_time))]
 #![cfg_attr(target_os = "windows", feature(windows_by_handle))]
 
+pub mod agent_logs;
 pub mod batched_body;
 pub mod codec;
 pub mod dns;
@@ -71,6 +73,8 @@ pub mod outgoing;
 pub mod pause;
 pub mod payload;
 pub mod tcp;
+pub mod time;
+pub mod udp;
 pub mod uid;
 pub mod vpn;
 
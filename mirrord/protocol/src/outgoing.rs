@@ -18,6 +18,14 @@ pub mod udp;
 pub static OUTGOING_CONNECT_V2: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.22.0".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows for [`udp::LayerUdpOutgoing::WriteBatch`].
+pub static UDP_WRITE_BATCH_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.28.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows for [`tcp::LayerTcpOutgoing::ConnectSeqPacket`].
+pub static OUTGOING_UNIX_SEQPACKET_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.30.0".parse().expect("Bad Identifier"));
+
 /// A serializable socket address type that can represent IP addresses or addresses of unix sockets.
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 pub enum SocketAddress {
@@ -166,6 +174,28 @@ impl fmt::Debug for LayerWrite {
     }
 }
 
+/// Multiple writes to the same connection, coalesced into one message.
+///
+/// Each payload is a separate write (datagram) on the agent side, performed in order.
+#[derive(Encode, Decode, PartialEq, Eq, Clone)]
+pub struct LayerWriteBatch {
+    pub connection_id: ConnectionId,
+    pub payloads: Vec<Payload>,
+}
+
+impl fmt::Debug for LayerWriteBatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LayerWriteBatch")
+            .field("connection_id", &self.connection_id)
+            .field("payloads", &self.payloads.len())
+            .field(
+                "bytes (length)",
+                &self.payloads.iter().map(|p| p.len()).sum::<usize>(),
+            )
+            .finish()
+    }
+}
+
 /// `layer` interceptor socket closed or failed.
 #[derive(Debug, Encode, Decode, PartialEq, Eq, Clone)]
 pub struct LayerClose {
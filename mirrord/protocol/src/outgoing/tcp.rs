@@ -26,6 +26,15 @@ pub enum LayerTcpOutgoing {
 
     /// Same as [`LayerTcpOutgoing::Connect`], but contains a [`Uid`].
     ConnectV2(LayerConnectV2),
+
+    /// Same as [`LayerTcpOutgoing::ConnectV2`], but the agent connects to the unix socket with a
+    /// `SOCK_SEQPACKET` socket, and the agent responds with [`DaemonTcpOutgoing::ConnectV2`].
+    ///
+    /// Message boundaries are preserved on this connection: every [`LayerWrite`] is sent to the
+    /// peer as one packet, and every [`DaemonTcpOutgoing::Read`] carries exactly one packet.
+    /// Like on stream connections, an empty [`LayerWrite`] or [`DaemonRead`] means a shutdown,
+    /// so 0-length packets cannot be proxied.
+    ConnectSeqPacket(LayerConnectV2),
 }
 
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
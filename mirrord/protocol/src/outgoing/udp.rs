@@ -30,6 +30,14 @@ pub enum LayerUdpOutgoing {
 
     /// Same as [`LayerUdpOutgoing::Connect`], but contains a [`Uid`].
     ConnectV2(LayerConnectV2),
+
+    /// Multiple [`LayerUdpOutgoing::Write`]s to the same connection, sent as one message.
+    ///
+    /// The agent sends every payload as a separate datagram, preserving their order.
+    ///
+    /// Only sent when the agent's protocol version matches
+    /// [`UDP_WRITE_BATCH_VERSION`](super::UDP_WRITE_BATCH_VERSION).
+    WriteBatch(LayerWriteBatch),
 }
 
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
@@ -4,6 +4,7 @@ use std::{
     convert::Infallible,
     fmt,
     net::{IpAddr, SocketAddr},
+    path::PathBuf,
     pin::Pin,
     sync::LazyLock,
     task::{Context, Poll},
@@ -48,6 +49,16 @@ pub struct NewTcpConnectionV2 {
     pub transport: IncomingTrafficTransportType,
 }
 
+/// Connection accepted on a remote unix socket, see [`LayerTcpSteal::UnixSubscribe`].
+///
+/// The data is then exchanged with [`TcpData`] messages, as with the TCP connections.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct NewUnixConnection {
+    pub connection_id: ConnectionId,
+    /// Path of the unix socket in the target's filesystem.
+    pub path: PathBuf,
+}
+
 #[derive(Encode, Decode, PartialEq, Eq, Clone)]
 pub struct TcpData {
     pub connection_id: ConnectionId,
@@ -110,6 +121,14 @@ pub enum DaemonTcp {
     HttpRequestFramed(HttpRequest<InternalHttpBody>),
     HttpRequestChunked(ChunkedRequest),
     NewConnectionV2(NewTcpConnectionV2),
+
+    /// Response to [`LayerTcpSteal::UnixSubscribe`].
+    ///
+    /// Supported since [`STEAL_UNIX_SOCKET_VERSION`].
+    UnixSubscribeResult(RemoteResult<PathBuf>),
+
+    /// Supported since [`STEAL_UNIX_SOCKET_VERSION`].
+    NewUnixConnection(NewUnixConnection),
 }
 
 /// Contents of a chunked message from server.
@@ -389,6 +408,35 @@ pub enum HttpFilter {
 
     /// Filter by header using JQ
     HeaderJq(JqQuery),
+
+    /// Filter by a single named header, possibly negated ("X-Tenant" is absent)
+    NamedHeader(HeaderCondition),
+}
+
+/// Condition on a single named header, see [`HttpFilter::NamedHeader`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct HeaderCondition {
+    /// Name of the header, case-insensitive.
+    pub name: String,
+    /// Matched against the values of the header. When [`None`], the header only has to be
+    /// present.
+    pub value: Option<Filter>,
+    /// Inverts the condition, so that it matches when the request has no header with this name,
+    /// or none of its values match [`Self::value`].
+    pub negate: bool,
+}
+
+impl Display for HeaderCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negate {
+            write!(f, "not ")?;
+        }
+
+        match &self.value {
+            Some(value) => write!(f, "{}={value}", self.name),
+            None => write!(f, "{} present", self.name),
+        }
+    }
 }
 
 impl Display for HttpFilter {
@@ -427,6 +475,7 @@ impl Display for HttpFilter {
             },
             HttpFilter::Body(filter) => write!(f, "body={filter}"),
             HttpFilter::HeaderJq(filter) => write!(f, "header_jq={filter}"),
+            HttpFilter::NamedHeader(condition) => write!(f, "header({condition})"),
         }
     }
 }
@@ -453,6 +502,46 @@ impl StealType {
     }
 }
 
+/// A range of source addresses in CIDR notation, e.g. `10.12.0.0/16`.
+///
+/// Used in [`LayerTcpSteal::PortSubscribeFromSources`].
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct SourceCidr {
+    /// Network address of the range.
+    pub address: IpAddr,
+    /// Number of leading bits of [`Self::address`] that identify the network.
+    pub prefix_len: u8,
+}
+
+impl SourceCidr {
+    /// Returns whether the given address belongs to this range.
+    ///
+    /// IPv4-mapped IPv6 addresses (e.g. `::ffff:10.12.0.1`) are matched as IPv4 addresses.
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.address, address.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX
+                    .checked_shl(32u32.saturating_sub(self.prefix_len.into()))
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX
+                    .checked_shl(128u32.saturating_sub(self.prefix_len.into()))
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for SourceCidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix_len)
+    }
+}
+
 /// Describes the mirroring subscription to a port
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
 #[protocol_break(2)]
@@ -508,6 +597,27 @@ pub enum LayerTcpSteal {
     HttpResponse(HttpResponse<Payload>),
     HttpResponseFramed(HttpResponse<InternalHttpBody>),
     HttpResponseChunked(ChunkedResponse),
+
+    /// Same as [`LayerTcpSteal::PortSubscribe`], but only connections coming from one of the
+    /// [`SourceCidr`]s are stolen. Connections from other addresses are passed through to their
+    /// original destination.
+    ///
+    /// Supported since [`STEAL_SOURCE_FILTER_VERSION`].
+    PortSubscribeFromSources(StealType, Vec<SourceCidr>),
+
+    /// User is interested in stealing connections from the unix socket at this path in the
+    /// target's filesystem.
+    ///
+    /// The agent replaces the socket with its own, and puts the original socket back on
+    /// [`LayerTcpSteal::UnixUnsubscribe`] or when the client disconnects.
+    ///
+    /// Supported since [`STEAL_UNIX_SOCKET_VERSION`].
+    UnixSubscribe(PathBuf),
+
+    /// Stops stealing from the unix socket at this path, see [`LayerTcpSteal::UnixSubscribe`].
+    ///
+    /// Supported since [`STEAL_UNIX_SOCKET_VERSION`].
+    UnixUnsubscribe(PathBuf),
 }
 
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
@@ -643,6 +753,21 @@ pub static HTTP_BODY_JSON_FILTER_VERSION: LazyLock<VersionReq> =
 pub static HTTP_HEADER_JQ_FILTER_VERSION: LazyLock<VersionReq> =
     LazyLock::new(|| ">=1.26.0".parse().expect("Bad Identifier"));
 
+/// Minimal mirrord-protocol version that allows HTTP filtering by a named header, including its
+/// absence ([`HttpFilter::NamedHeader`]).
+pub static HTTP_NAMED_HEADER_FILTER_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.33.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows stealing only from the given source addresses
+/// ([`LayerTcpSteal::PortSubscribeFromSources`]).
+pub static STEAL_SOURCE_FILTER_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.37.0".parse().expect("Bad Identifier"));
+
+/// Minimal mirrord-protocol version that allows stealing connections from unix sockets
+/// ([`LayerTcpSteal::UnixSubscribe`]).
+pub static STEAL_UNIX_SOCKET_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.42.0".parse().expect("Bad Identifier"));
+
 /// Protocol break - on version 2, please add source port, dest/src IP to the message
 /// so we can avoid losing this information.
 #[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
@@ -0,0 +1,53 @@
+//! Messages of the remote time feature.
+//!
+//! The client asks for the wall-clock time of the agent with [`ClientMessage::GetRemoteTime`],
+//! and computes the offset between the remote and the local clock on its own.
+//!
+//! [`ClientMessage::GetRemoteTime`]: crate::ClientMessage::GetRemoteTime
+
+use std::{
+    sync::LazyLock,
+    time::{Duration, SystemTime},
+};
+
+use bincode::{Decode, Encode};
+use semver::VersionReq;
+
+/// Minimal mirrord-protocol version that allows for
+/// [`ClientMessage::GetRemoteTime`](crate::ClientMessage::GetRemoteTime).
+pub static REMOTE_TIME_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.32.0".parse().expect("Bad Identifier"));
+
+/// Wall-clock time of the agent, sent in response to
+/// [`ClientMessage::GetRemoteTime`](crate::ClientMessage::GetRemoteTime).
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone, Copy)]
+pub struct RemoteTime {
+    /// Nanoseconds since [`SystemTime::UNIX_EPOCH`].
+    pub nanos_since_epoch: u128,
+}
+
+impl RemoteTime {
+    /// Returns the current wall-clock time of this machine.
+    pub fn now() -> Self {
+        Self::from(SystemTime::now())
+    }
+}
+
+impl From<SystemTime> for RemoteTime {
+    fn from(time: SystemTime) -> Self {
+        Self {
+            nanos_since_epoch: time
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_nanos(),
+        }
+    }
+}
+
+impl From<RemoteTime> for SystemTime {
+    fn from(time: RemoteTime) -> Self {
+        let secs = u64::try_from(time.nanos_since_epoch / 1_000_000_000).unwrap_or(u64::MAX);
+        let nanos = (time.nanos_since_epoch % 1_000_000_000) as u32;
+        SystemTime::UNIX_EPOCH + Duration::new(secs, nanos)
+    }
+}
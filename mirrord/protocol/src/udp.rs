@@ -0,0 +1,45 @@
+//! Messages of the UDP mirror feature.
+//!
+//! Unlike TCP, there are no connections here. The agent sends every datagram that arrives at a
+//! subscribed port as a separate [`DaemonUdp::Datagram`].
+
+use std::{net::SocketAddr, sync::LazyLock};
+
+use bincode::{Decode, Encode};
+use semver::VersionReq;
+
+use crate::{Payload, Port, RemoteResult};
+
+/// Minimal mirrord-protocol version that allows for [`LayerUdp`] and [`DaemonUdp`].
+pub static UDP_MIRROR_VERSION: LazyLock<VersionReq> =
+    LazyLock::new(|| ">=1.31.0".parse().expect("Bad Identifier"));
+
+/// Messages related to the UDP mirror feature from the layer.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub enum LayerUdp {
+    /// User is interested in mirroring datagrams sent to this `Port`.
+    PortSubscribe(Port),
+
+    /// User is no longer interested in datagrams sent to this `Port`.
+    PortUnsubscribe(Port),
+}
+
+/// Messages related to the UDP mirror feature from the agent.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub enum DaemonUdp {
+    /// Result of a [`LayerUdp::PortSubscribe`].
+    SubscribeResult(RemoteResult<Port>),
+
+    /// A copy of a datagram that arrived at a subscribed port.
+    Datagram(UdpDatagram),
+}
+
+/// A mirrored UDP datagram.
+#[derive(Encode, Decode, Debug, PartialEq, Eq, Clone)]
+pub struct UdpDatagram {
+    /// Address of the peer that sent the datagram.
+    pub source: SocketAddr,
+    /// Original destination of the datagram in the target.
+    pub destination: SocketAddr,
+    pub bytes: Payload,
+}
@@ -0,0 +1,161 @@
+#![cfg(test)]
+#![cfg(feature = "operator")]
+//! Tests for the `copy_target` feature.
+
+use core::{ops::Not, time::Duration};
+
+use k8s_openapi::api::core::v1::Pod;
+use kube::{api::ListParams, Api, Client};
+use mirrord_test_utils::run_command::run_exec_with_target;
+use rstest::*;
+
+use crate::utils::{
+    application::Application,
+    kube_client,
+    kube_service::KubeService,
+    port_forwarder::PortForwarder,
+    send_requests,
+    services::{basic_service, sidecar_service},
+    CONTAINER_NAME, SIDECAR_CONTAINER_NAME,
+};
+
+/// Starts mirrord with `feature.copy_target.patch` adding an env var to the copied pod, and
+/// verifies that the application sees it in the remote environment.
+#[cfg_attr(target_os = "windows", ignore)]
+#[rstest]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(Duration::from_secs(240))]
+pub async fn copy_target_patch(
+    #[future]
+    #[notrace]
+    basic_service: KubeService,
+) {
+    let service = basic_service.await;
+
+    let mut config_file = tempfile::Builder::new()
+        .prefix("copy_target_patch")
+        .suffix(".json")
+        .tempfile()
+        .unwrap();
+    let config = serde_json::json!({
+        "feature": {
+            "copy_target": {
+                "enabled": true,
+                "patch": {
+                    "containers": [{
+                        "name": CONTAINER_NAME,
+                        "env": [{ "name": "MIRRORD_COPY_TARGET_PATCHED", "value": "yes" }]
+                    }]
+                }
+            }
+        }
+    });
+    serde_json::to_writer(config_file.as_file_mut(), &config).unwrap();
+
+    let command = vec![
+        "bash".to_string(),
+        "-c".to_string(),
+        r#"test "$MIRRORD_COPY_TARGET_PATCHED" = yes"#.to_string(),
+    ];
+    let mut process = run_exec_with_target(
+        command,
+        &service.deployment_target(),
+        None,
+        Some(vec!["--config-file", config_file.path().to_str().unwrap()]),
+        None,
+    )
+    .await;
+    let res = process.wait().await;
+    assert!(res.success());
+}
+
+/// Starts mirrord with `feature.copy_target.patch` deleting the sidecar container from the copied
+/// pod, and verifies that the copy has no sidecar, and that its traffic can still be stolen.
+#[cfg_attr(target_os = "windows", ignore)]
+#[rstest]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+#[timeout(Duration::from_secs(240))]
+pub async fn copy_target_patch_delete_sidecar(
+    #[future]
+    #[notrace]
+    sidecar_service: KubeService,
+    #[future] kube_client: Client,
+) {
+    let service = sidecar_service.await;
+    let kube_client = kube_client.await;
+    let application = Application::PythonFastApiHTTP;
+
+    let mut config_file = tempfile::Builder::new()
+        .prefix("copy_target_patch_delete_sidecar")
+        .suffix(".json")
+        .tempfile()
+        .unwrap();
+    let config = serde_json::json!({
+        "feature": {
+            "copy_target": {
+                "enabled": true,
+                "scale_down": true,
+                "patch": {
+                    "containers": [{
+                        "name": SIDECAR_CONTAINER_NAME,
+                        "$patch": "delete"
+                    }]
+                }
+            }
+        }
+    });
+    serde_json::to_writer(config_file.as_file_mut(), &config).unwrap();
+
+    let process = application
+        .run(
+            &service.deployment_target(),
+            Some(&service.namespace),
+            Some(vec![
+                "--steal",
+                "--config-file",
+                config_file.path().to_str().unwrap(),
+            ]),
+            None,
+        )
+        .await;
+    process
+        .wait_for_line(Duration::from_secs(120), "daemon subscribed")
+        .await;
+
+    let copy_pod = Api::<Pod>::namespaced(kube_client.clone(), &service.namespace)
+        .list(&ListParams::default().labels(&format!("app={}", service.name)))
+        .await
+        .unwrap()
+        .items
+        .into_iter()
+        .find(|pod| pod.metadata.name.as_deref() != Some(service.pod_name.as_str()))
+        .expect("the copied pod should have the labels of the original pod");
+    let containers = copy_pod
+        .spec
+        .as_ref()
+        .unwrap()
+        .containers
+        .iter()
+        .map(|container| container.name.as_str())
+        .collect::<Vec<_>>();
+    assert!(
+        containers.contains(&SIDECAR_CONTAINER_NAME).not(),
+        "the sidecar should be deleted from the copied pod, containers: {containers:?}"
+    );
+
+    let portforwarder = PortForwarder::new(
+        kube_client,
+        copy_pod.metadata.name.as_deref().unwrap(),
+        &service.namespace,
+        80,
+    )
+    .await;
+    send_requests(
+        &format!("http://{}", portforwarder.address()),
+        true,
+        Default::default(),
+    )
+    .await;
+
+    application.assert(&process).await;
+}
@@ -6,6 +6,7 @@ mod argo_rollout;
 mod cleanup;
 #[cfg(feature = "cli")]
 mod cli;
+mod copy_target;
 mod env;
 mod file_ops;
 mod http;
@@ -27,6 +27,9 @@ pub mod watch;
 const TEXT: &str = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua. Ut enim ad minim veniam, quis nostrud exercitation ullamco laboris nisi ut aliquip ex ea commodo consequat. Duis aute irure dolor in reprehenderit in voluptate velit esse cillum dolore eu fugiat nulla pariatur. Excepteur sint occaecat cupidatat non proident, sunt in culpa qui officia deserunt mollit anim id est laborum.";
 pub const CONTAINER_NAME: &str = "test";
 
+/// Name of the extra container in the pods of [`services::sidecar_service`].
+pub const SIDECAR_CONTAINER_NAME: &str = "sidecar";
+
 /// Name of the environment variable used to control cleanup after failed tests.
 /// By default, resources from failed tests are deleted.
 /// However, if this variable is set, resources will always be preserved.
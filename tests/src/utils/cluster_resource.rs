@@ -10,7 +10,7 @@ use kube::runtime::reflector::Lookup;
 use mirrord_kube::api::kubernetes::rollout::Rollout;
 use serde_json::{json, Value};
 
-use crate::utils::{CONTAINER_NAME, TEST_RESOURCE_LABEL};
+use crate::utils::{CONTAINER_NAME, SIDECAR_CONTAINER_NAME, TEST_RESOURCE_LABEL};
 
 pub(crate) mod operator;
 
@@ -99,6 +99,25 @@ pub(super) fn deployment_from_json(
     .expect("Failed creating `deployment` from json spec!")
 }
 
+/// Adds a [`SIDECAR_CONTAINER_NAME`] container, that just sleeps, to the pod template of the
+/// `deployment`.
+pub(super) fn add_sidecar(deployment: &mut Deployment) {
+    deployment
+        .spec
+        .as_mut()
+        .and_then(|spec| spec.template.spec.as_mut())
+        .expect("deployment should have a pod template")
+        .containers
+        .push(
+            serde_json::from_value(json!({
+                "name": SIDECAR_CONTAINER_NAME,
+                "image": "busybox",
+                "command": ["sleep", "infinity"],
+            }))
+            .expect("Failed creating sidecar `container` from json spec!"),
+        );
+}
+
 pub(super) fn service_from_json(name: &str, service_type: &str) -> Service {
     serde_json::from_value(json!({
         "apiVersion": "v1",
@@ -113,6 +113,10 @@ pub async fn service_with_env_and_env_from(
 pub enum TestWorkloadType {
     #[default]
     Deployment,
+    /// [`TestWorkloadType::Deployment`] with an extra [`SIDECAR_CONTAINER_NAME`] container.
+    ///
+    /// [`SIDECAR_CONTAINER_NAME`]: crate::utils::SIDECAR_CONTAINER_NAME
+    DeploymentWithSidecar,
     ArgoRolloutWithWorkloadRef,
     ArgoRolloutWithTemplate,
     StatefulSet,
@@ -288,6 +292,15 @@ pub async fn internal_service(
                     .unwrap();
             guards.push(deployment_guard);
         }
+        TestWorkloadType::DeploymentWithSidecar => {
+            let mut deployment = deployment_from_json(&name, image, env, env_from, 1);
+            add_sidecar(&mut deployment);
+            let (deployment_guard, _deployment) =
+                ResourceGuard::create(deployment_api.clone(), &deployment, delete_after_fail)
+                    .await
+                    .unwrap();
+            guards.push(deployment_guard);
+        }
         TestWorkloadType::ArgoRolloutWithWorkloadRef => {
             let deployment = deployment_from_json(&name, image, env, env_from, 0);
             let (deployment_guard, deployment) =
@@ -534,6 +547,32 @@ pub async fn rollout_service(
     .await
 }
 
+/// Like [`basic_service`], but the pod has an extra container that just sleeps.
+#[fixture]
+pub async fn sidecar_service(
+    #[default("default")] namespace: &str,
+    #[default("NodePort")] service_type: &str,
+    #[default("ghcr.io/metalbear-co/mirrord-pytest:latest")] image: &str,
+    #[default("http-echo")] service_name: &str,
+    #[default(true)] randomize_name: bool,
+    #[future] kube_client: Client,
+) -> KubeService {
+    internal_service(
+        namespace,
+        service_type,
+        image,
+        service_name,
+        randomize_name,
+        kube_client.await,
+        default_env(),
+        None,
+        None,
+        false,
+        TestWorkloadType::DeploymentWithSidecar,
+    )
+    .await
+}
+
 #[fixture]
 pub async fn stateful_set_service(
     #[default("default")] namespace: &str,